@@ -0,0 +1,506 @@
+// Copyright 2021 The MWC Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Audit log of the owner API calls. Every call is appended to the log file as a json
+//! line. An entry includes the hash of the previous entry and its own hash, so a removed,
+//! inserted or modified entry breaks the hash chain.
+
+use crate::rest::{Error, ErrorKind};
+use crate::router::{ClientCert, Handler, HandlerObj, RemoteAddr, ResponseFuture};
+use crate::util::{Mutex, RwLock, ToHex};
+use crate::web::response;
+use chrono::Utc;
+use hyper::header::AUTHORIZATION;
+use hyper::{Body, Request, Response, StatusCode};
+use ring::digest;
+use schemars::JsonSchema;
+use serde_json::Value;
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, Read, Write};
+use std::path::Path;
+use std::sync::Arc;
+
+/// Previous hash of the first entry of the log
+const AUDIT_LOG_GENESIS_HASH: &str =
+	"0000000000000000000000000000000000000000000000000000000000000000";
+
+/// Number of entries returned by a query if no limit is specified
+const DEFAULT_AUDIT_LOG_QUERY_LIMIT: usize = 100;
+
+lazy_static! {
+	/// Audit log of the owner API, set when it is enabled in the config
+	static ref OWNER_API_AUDIT_LOG: RwLock<Option<Arc<AuditLog>>> = RwLock::new(None);
+}
+
+pub fn set_owner_api_audit_log(audit_log: Option<Arc<AuditLog>>) {
+	*OWNER_API_AUDIT_LOG.write() = audit_log;
+}
+
+pub fn get_owner_api_audit_log() -> Option<Arc<AuditLog>> {
+	OWNER_API_AUDIT_LOG.read().clone()
+}
+
+/// Single call recorded in the audit log
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct AuditLogEntry {
+	/// Sequence number of the entry, starting from 0
+	pub index: u64,
+	/// rfc3339 time of the call
+	pub timestamp: String,
+	/// Called method, comma separated methods for a batch call
+	pub method: String,
+	/// Identity of the caller, the fingerprint of the api secret it authenticated with
+	pub identity: String,
+	/// IP address of the caller
+	pub source_ip: Option<String>,
+	/// "ok" or the error returned to the caller
+	pub outcome: String,
+	/// Hash of the previous entry
+	pub prev_hash: String,
+	/// Hash of this entry, sha256 of the previous hash and the fields above
+	pub hash: String,
+}
+
+impl AuditLogEntry {
+	fn compute_hash(&self) -> String {
+		let data = serde_json::to_string(&(
+			&self.prev_hash,
+			self.index,
+			&self.timestamp,
+			&self.method,
+			&self.identity,
+			&self.source_ip,
+			&self.outcome,
+		))
+		.unwrap_or_default();
+		digest::digest(&digest::SHA256, data.as_bytes())
+			.as_ref()
+			.to_hex()
+	}
+}
+
+/// Result of an audit log query
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct AuditLogPage {
+	/// Matching entries, oldest first
+	pub entries: Vec<AuditLogEntry>,
+	/// Total number of entries in the log
+	pub total: u64,
+	/// Position of the first entry that breaks the hash chain, None if the log is intact
+	pub broken_at: Option<u64>,
+}
+
+struct AuditLogState {
+	file: File,
+	// Size of the complete entries in the file, the queries don't read past it
+	len: u64,
+	next_index: u64,
+	last_hash: String,
+}
+
+/// Response extension of the owner API calls that are recorded by the handler already
+#[derive(Debug, Clone, Copy)]
+pub struct AuditRecorded;
+
+/// Mark the response as recorded into the audit log
+pub fn audit_recorded(mut resp: Response<Body>) -> Response<Body> {
+	resp.extensions_mut().insert(AuditRecorded);
+	resp
+}
+
+/// Append only audit log file with hash chained entries
+pub struct AuditLog {
+	path: String,
+	state: Mutex<AuditLogState>,
+}
+
+impl AuditLog {
+	/// Open the audit log at path, created if missing. The existing entries are verified,
+	/// the new entries are chained to the last one even if the log is broken.
+	pub fn open(path: &str) -> Result<AuditLog, Error> {
+		let (entries, broken_at) = read_entries(path, None)?;
+		if let Some(pos) = broken_at {
+			error!(
+				"Audit log {} hash chain is broken at entry {}, the log was modified",
+				path, pos
+			);
+		}
+		let file = OpenOptions::new()
+			.create(true)
+			.append(true)
+			.open(path)
+			.map_err(|e| {
+				ErrorKind::Internal(format!("Unable to open audit log {}, {}", path, e))
+			})?;
+		let len = file
+			.metadata()
+			.map_err(|e| ErrorKind::Internal(format!("Unable to open audit log {}, {}", path, e)))?
+			.len();
+		let (next_index, last_hash) = match entries.last() {
+			Some(e) => (e.index + 1, e.hash.clone()),
+			None => (0, AUDIT_LOG_GENESIS_HASH.to_string()),
+		};
+		Ok(AuditLog {
+			path: path.to_string(),
+			state: Mutex::new(AuditLogState {
+				file,
+				len,
+				next_index,
+				last_hash,
+			}),
+		})
+	}
+
+	/// Append the call to the log, the entry is synced to the disk before returning.
+	pub fn append(
+		&self,
+		method: &str,
+		identity: &str,
+		source_ip: Option<String>,
+		outcome: &str,
+	) -> Result<AuditLogEntry, Error> {
+		let mut state = self.state.lock();
+		let mut entry = AuditLogEntry {
+			index: state.next_index,
+			timestamp: Utc::now().to_rfc3339(),
+			method: method.to_string(),
+			identity: identity.to_string(),
+			source_ip,
+			outcome: outcome.to_string(),
+			prev_hash: state.last_hash.clone(),
+			hash: String::new(),
+		};
+		entry.hash = entry.compute_hash();
+
+		let line = serde_json::to_string(&entry).map_err(|e| {
+			ErrorKind::Internal(format!("Unable to serialize audit log entry, {}", e))
+		})?;
+		writeln!(state.file, "{}", line)
+			.and_then(|_| state.file.sync_data())
+			.map_err(|e| {
+				ErrorKind::Internal(format!("Unable to write audit log {}, {}", self.path, e))
+			})?;
+		state.len += line.len() as u64 + 1;
+		state.next_index += 1;
+		state.last_hash = entry.hash.clone();
+		Ok(entry)
+	}
+
+	/// Same as `append`, the entry is written and synced to the disk on the blocking
+	/// thread pool, so the async executor doesn't wait for the disk.
+	pub async fn append_async(
+		self: Arc<Self>,
+		method: String,
+		identity: String,
+		source_ip: Option<String>,
+		outcome: String,
+	) {
+		let res = tokio::task::spawn_blocking(move || {
+			self.append(&method, &identity, source_ip, &outcome)
+				.map_err(|e| format!("{}, {}", method, e))
+		})
+		.await;
+		match res {
+			Ok(Ok(_)) => (),
+			Ok(Err(e)) => error!("Unable to record the owner API call {}", e),
+			Err(e) => error!("Unable to record the owner API call, {}", e),
+		}
+	}
+
+	/// Query the log, the whole log is verified.
+	/// * `start_index` - return the entries starting from this index.
+	/// * `method` - return the calls of this method only.
+	/// * `limit` - max number of entries to return, 100 by default.
+	pub fn query(
+		&self,
+		start_index: Option<u64>,
+		method: Option<String>,
+		limit: Option<usize>,
+	) -> Result<AuditLogPage, Error> {
+		// Only the complete entries are read, the appends are not blocked by the query
+		let len = self.state.lock().len;
+		let (entries, broken_at) = read_entries(&self.path, Some(len))?;
+		let total = entries.len() as u64;
+		let entries = entries
+			.into_iter()
+			.filter(|e| e.index >= start_index.unwrap_or(0))
+			.filter(|e| method.as_ref().map_or(true, |m| e.method == *m))
+			.take(limit.unwrap_or(DEFAULT_AUDIT_LOG_QUERY_LIMIT))
+			.collect();
+		Ok(AuditLogPage {
+			entries,
+			total,
+			broken_at,
+		})
+	}
+
+	/// Record the owner API call. `request` and `response` are the json-rpc messages.
+	pub fn record_call(
+		&self,
+		identity: &str,
+		source_ip: Option<String>,
+		request: Option<&Value>,
+		response: Result<&Value, String>,
+	) {
+		let (method, outcome) = call_record(request, response);
+		if let Err(e) = self.append(&method, identity, source_ip, &outcome) {
+			error!("Unable to record the owner API call {}, {}", method, e);
+		}
+	}
+
+	/// Same as `record_call`, the entry is written on the blocking thread pool.
+	pub async fn record_call_async(
+		self: Arc<Self>,
+		identity: String,
+		source_ip: Option<String>,
+		request: Option<&Value>,
+		response: Result<&Value, String>,
+	) {
+		let (method, outcome) = call_record(request, response);
+		self.append_async(method, identity, source_ip, outcome)
+			.await
+	}
+}
+
+/// Records the owner API requests the json-rpc handler doesn't record itself: the v1 owner
+/// routes and the requests rejected before reaching the handler (e.g. 401 of the basic auth),
+/// so it must go before the auth middlewares.
+pub struct AuditMiddleware {
+	audit_log: Arc<AuditLog>,
+	ignore_uris: Vec<String>,
+}
+
+impl AuditMiddleware {
+	pub fn new(audit_log: Arc<AuditLog>, ignore_uris: Vec<String>) -> AuditMiddleware {
+		AuditMiddleware {
+			audit_log,
+			ignore_uris,
+		}
+	}
+}
+
+impl Handler for AuditMiddleware {
+	fn call(
+		&self,
+		req: Request<Body>,
+		mut handlers: Box<dyn Iterator<Item = HandlerObj>>,
+	) -> ResponseFuture {
+		let next_handler = match handlers.next() {
+			Some(h) => h,
+			None => return response(StatusCode::INTERNAL_SERVER_ERROR, "no handler found"),
+		};
+		if req.method().as_str() == "OPTIONS"
+			|| self.ignore_uris.iter().any(|u| req.uri().path() == u)
+		{
+			return next_handler.call(req, handlers);
+		}
+		let audit_log = self.audit_log.clone();
+		let method = format!("{} {}", req.method(), req.uri().path());
+		let identity = caller_identity(&req);
+		let source_ip = caller_ip(&req);
+		let resp = next_handler.call(req, handlers);
+		Box::pin(async move {
+			let resp = resp.await?;
+			if resp.extensions().get::<AuditRecorded>().is_none() {
+				let status = resp.status();
+				let outcome = if !status.is_client_error() && !status.is_server_error() {
+					"ok".to_string()
+				} else {
+					format!("error: {}", status)
+				};
+				audit_log
+					.append_async(method, identity, source_ip, outcome)
+					.await;
+			}
+			Ok(resp)
+		})
+	}
+}
+
+/// Read the entries of the log (up to len bytes if provided) and verify the hash chain.
+/// Returns the position of the first entry that breaks the chain, if any.
+fn read_entries(path: &str, len: Option<u64>) -> Result<(Vec<AuditLogEntry>, Option<u64>), Error> {
+	if !Path::new(path).exists() {
+		return Ok((vec![], None));
+	}
+	let file = File::open(path)
+		.map_err(|e| ErrorKind::Internal(format!("Unable to open audit log {}, {}", path, e)))?;
+
+	let mut entries: Vec<AuditLogEntry> = vec![];
+	let mut broken_at = None;
+	let file = file.take(len.unwrap_or(u64::MAX));
+	for line in BufReader::new(file).lines() {
+		let line = line.map_err(|e| {
+			ErrorKind::Internal(format!("Unable to read audit log {}, {}", path, e))
+		})?;
+		let pos = entries.len() as u64;
+		match serde_json::from_str::<AuditLogEntry>(&line) {
+			Ok(entry) => {
+				let prev_hash = entries
+					.last()
+					.map_or(AUDIT_LOG_GENESIS_HASH, |e| e.hash.as_str());
+				if broken_at.is_none()
+					&& (entry.index != pos
+						|| entry.prev_hash != prev_hash
+						|| entry.hash != entry.compute_hash())
+				{
+					broken_at = Some(pos);
+				}
+				entries.push(entry);
+			}
+			Err(_) => {
+				// A line that is not an entry at all, the rest of the log can't be trusted
+				broken_at = broken_at.or(Some(pos));
+			}
+		}
+	}
+	Ok((entries, broken_at))
+}
+
+/// Identity of the owner API caller: the fingerprint of the TLS client certificate or of the
+/// Authorization header, so the calls made with different (e.g. rotated) secrets can be told
+/// apart without logging them.
+pub fn caller_identity(req: &Request<Body>) -> String {
+	if let Some(client_cert) = req.extensions().get::<ClientCert>() {
+		return format!("cert:{}", &client_cert.fingerprint[..8]);
+	}
+	match req.headers().get(AUTHORIZATION) {
+		Some(authorization) => {
+			let fingerprint = digest::digest(&digest::SHA256, authorization.as_bytes());
+			format!("secret:{}", fingerprint.as_ref()[..4].to_hex())
+		}
+		None => "anonymous".to_string(),
+	}
+}
+
+/// IP address of the API caller
+pub fn caller_ip(req: &Request<Body>) -> Option<String> {
+	req.extensions()
+		.get::<RemoteAddr>()
+		.map(|addr| addr.0.ip().to_string())
+}
+
+/// Method and outcome of the json-rpc call
+fn call_record(request: Option<&Value>, response: Result<&Value, String>) -> (String, String) {
+	let method = request.map_or("unknown".to_string(), call_method);
+	let outcome = match response {
+		Ok(response) => call_outcome(response),
+		Err(e) => e,
+	};
+	(method, outcome)
+}
+
+fn call_method(request: &Value) -> String {
+	match request {
+		Value::Array(requests) => requests
+			.iter()
+			.map(call_method)
+			.collect::<Vec<_>>()
+			.join(","),
+		_ => request["method"].as_str().unwrap_or("unknown").to_string(),
+	}
+}
+
+/// "ok" or the error of the json-rpc response. Owner methods return their errors
+/// as an "Err" result.
+fn call_outcome(response: &Value) -> String {
+	match response {
+		Value::Array(responses) => responses
+			.iter()
+			.map(call_outcome)
+			.find(|o| o != "ok")
+			.unwrap_or_else(|| "ok".to_string()),
+		_ => {
+			if !response["error"].is_null() {
+				format!("error: {}", response["error"])
+			} else if !response["result"]["Err"].is_null() {
+				format!("error: {}", response["result"]["Err"])
+			} else {
+				"ok".to_string()
+			}
+		}
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+	use std::fs;
+
+	#[test]
+	fn test_audit_log_hash_chain() {
+		let dir = std::env::temp_dir().join("mwc_test_audit_log");
+		let _ = fs::remove_dir_all(&dir);
+		fs::create_dir_all(&dir).unwrap();
+		let path = dir.join("api_audit.log");
+		let path = path.to_str().unwrap();
+
+		let log = AuditLog::open(path).unwrap();
+		let request = serde_json::json!({"jsonrpc": "2.0", "method": "ban_peer", "id": 1});
+		let response = serde_json::json!({"jsonrpc": "2.0", "result": {"Ok": null}, "id": 1});
+		log.record_call(
+			"secret:01020304",
+			Some("127.0.0.1".to_string()),
+			Some(&request),
+			Ok(&response),
+		);
+		let response =
+			serde_json::json!({"jsonrpc": "2.0", "result": {"Err": "NotFound"}, "id": 1});
+		log.record_call("anonymous", None, Some(&request), Ok(&response));
+		log.append("get_status", "anonymous", None, "ok").unwrap();
+
+		let page = log.query(None, Some("ban_peer".to_string()), None).unwrap();
+		assert_eq!(page.total, 3);
+		assert_eq!(page.broken_at, None);
+		assert_eq!(page.entries.len(), 2);
+		assert_eq!(page.entries[0].outcome, "ok");
+		assert_eq!(page.entries[0].source_ip, Some("127.0.0.1".to_string()));
+		assert_eq!(page.entries[1].outcome, "error: \"NotFound\"");
+		assert_eq!(page.entries[1].prev_hash, page.entries[0].hash);
+
+		// Reopened log continues the chain
+		drop(log);
+		let log = AuditLog::open(path).unwrap();
+		assert_eq!(
+			log.append("get_status", "anonymous", None, "ok")
+				.unwrap()
+				.index,
+			3
+		);
+		let page = log.query(Some(3), None, None).unwrap();
+		assert_eq!(page.entries.len(), 1);
+		assert_eq!(page.broken_at, None);
+
+		// Half written entry is not read by the queries
+		OpenOptions::new()
+			.append(true)
+			.open(path)
+			.unwrap()
+			.write_all(b"{\"index\":4")
+			.unwrap();
+		assert_eq!(log.query(None, None, None).unwrap().broken_at, None);
+
+		// Modified entry breaks the chain
+		let content = fs::read_to_string(path).unwrap();
+		fs::write(path, content.replacen("ban_peer", "get_peers", 1)).unwrap();
+		assert_eq!(log.query(None, None, None).unwrap().broken_at, Some(0));
+
+		// Removed entry breaks the chain
+		let lines: Vec<_> = content.lines().collect();
+		fs::write(path, format!("{}\n{}\n", lines[0], lines[2])).unwrap();
+		assert_eq!(log.query(None, None, None).unwrap().broken_at, Some(1));
+
+		fs::remove_dir_all(&dir).unwrap();
+	}
+}
@@ -12,37 +12,189 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use crate::router::{Handler, HandlerObj, ResponseFuture};
+use crate::rest::{ClientPermission, Error, ErrorKind};
+use crate::router::{ClientCert, Handler, HandlerObj, ResponseFuture};
+use crate::util::{to_base64, RwLock};
 use crate::web::response;
+use chrono::Utc;
 use futures::future::ok;
 use hyper::header::{HeaderValue, AUTHORIZATION, WWW_AUTHENTICATE};
-use hyper::{Body, Request, Response, StatusCode};
+use hyper::{Body, Method, Request, Response, StatusCode};
 use ring::constant_time::verify_slices_are_equal;
+use ring::rand::{SecureRandom, SystemRandom};
+use serde_json::Value;
+use std::fs::{self, File};
+use std::io::Write;
+use std::sync::Arc;
+
+/// Length of the generated API secrets
+const API_SECRET_LEN: usize = 20;
+
+/// Owner API methods that are allowed for the read-only clients, besides the 'get_' ones
+const OWNER_READ_ONLY_METHODS: [&str; 3] = [
+	"check_integrity_kernel",
+	"validate_block",
+	"simulate_message",
+];
 
 lazy_static! {
 	pub static ref MWC_BASIC_REALM: HeaderValue =
 		HeaderValue::from_str("Basic realm=MWC-API").unwrap();
 	pub static ref MWC_FOREIGN_BASIC_REALM: HeaderValue =
 		HeaderValue::from_str("Basic realm=MWCForeignAPI").unwrap();
+
+	/// Credentials of the owner API, set when the API is protected by the secret
+	static ref OWNER_API_CREDENTIALS: RwLock<Option<Arc<BasicAuthCredentials>>> = RwLock::new(None);
+	/// Credentials of the foreign API, set when the API is protected by the secret
+	static ref FOREIGN_API_CREDENTIALS: RwLock<Option<Arc<BasicAuthCredentials>>> =
+		RwLock::new(None);
+}
+
+pub fn set_owner_api_credentials(credentials: Option<Arc<BasicAuthCredentials>>) {
+	*OWNER_API_CREDENTIALS.write() = credentials;
+}
+
+pub fn get_owner_api_credentials() -> Option<Arc<BasicAuthCredentials>> {
+	OWNER_API_CREDENTIALS.read().clone()
+}
+
+pub fn set_foreign_api_credentials(credentials: Option<Arc<BasicAuthCredentials>>) {
+	*FOREIGN_API_CREDENTIALS.write() = credentials;
+}
+
+pub fn get_foreign_api_credentials() -> Option<Arc<BasicAuthCredentials>> {
+	FOREIGN_API_CREDENTIALS.read().clone()
+}
+
+struct CredentialsState {
+	// Current 'Authorization' header value
+	current: String,
+	// Previous 'Authorization' header value and its expiration timestamp
+	previous: Option<(String, i64)>,
+}
+
+/// Basic auth credentials of the API. The secret can be rotated while the node is running,
+/// the previous secret is still accepted during the grace period so the clients have time
+/// to pick up the new one.
+pub struct BasicAuthCredentials {
+	basic_auth_key: String,
+	secret_path: String,
+	state: RwLock<CredentialsState>,
+}
+
+impl BasicAuthCredentials {
+	/// Create credentials for the secret that is stored at secret_path
+	pub fn new(basic_auth_key: &str, api_secret: &str, secret_path: &str) -> BasicAuthCredentials {
+		BasicAuthCredentials {
+			basic_auth_key: basic_auth_key.to_string(),
+			secret_path: secret_path.to_string(),
+			state: RwLock::new(CredentialsState {
+				current: basic_auth_header(basic_auth_key, api_secret),
+				previous: None,
+			}),
+		}
+	}
+
+	/// Path to the secret file
+	pub fn secret_path(&self) -> &str {
+		&self.secret_path
+	}
+
+	/// Check the 'Authorization' header value
+	pub fn verify(&self, authorization: &[u8], now: i64) -> bool {
+		let state = self.state.read();
+		if verify_slices_are_equal(authorization, state.current.as_bytes()).is_ok() {
+			return true;
+		}
+		match &state.previous {
+			Some((previous, valid_until)) => {
+				now < *valid_until
+					&& verify_slices_are_equal(authorization, previous.as_bytes()).is_ok()
+			}
+			None => false,
+		}
+	}
+
+	/// Generate a new secret and write it into the secret file. The current secret
+	/// stays valid for grace_period_secs. Returns the expiration timestamp of the previous secret.
+	pub fn rotate(&self, grace_period_secs: i64) -> Result<i64, Error> {
+		let api_secret = generate_api_secret()?;
+		write_secret_file(&self.secret_path, &api_secret)?;
+
+		let valid_until = Utc::now().timestamp().saturating_add(grace_period_secs);
+		let mut state = self.state.write();
+		let current = basic_auth_header(&self.basic_auth_key, &api_secret);
+		let previous = std::mem::replace(&mut state.current, current);
+		state.previous = Some((previous, valid_until));
+		Ok(valid_until)
+	}
+}
+
+fn basic_auth_header(basic_auth_key: &str, api_secret: &str) -> String {
+	format!(
+		"Basic {}",
+		to_base64(&format!("{}:{}", basic_auth_key, api_secret))
+	)
+}
+
+/// Random alphanumeric secret, same format as the secrets that are generated for the new configs
+fn generate_api_secret() -> Result<String, Error> {
+	const CHARSET: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789";
+	let rng = SystemRandom::new();
+	let mut secret = String::with_capacity(API_SECRET_LEN);
+	while secret.len() < API_SECRET_LEN {
+		let mut buf = [0u8; 32];
+		rng.fill(&mut buf)
+			.map_err(|_| ErrorKind::Internal("Unable to generate random secret".to_string()))?;
+		// Rejecting the bytes above the charset multiple to keep the distribution uniform
+		let limit = (256 / CHARSET.len() * CHARSET.len()) as u8;
+		for b in buf.iter().filter(|b| **b < limit) {
+			if secret.len() < API_SECRET_LEN {
+				secret.push(CHARSET[*b as usize % CHARSET.len()] as char);
+			}
+		}
+	}
+	Ok(secret)
+}
+
+/// Write the secret into the temporary file and move it over the secret file,
+/// so the secret file is never left half written.
+fn write_secret_file(secret_path: &str, api_secret: &str) -> Result<(), Error> {
+	let tmp_path = format!("{}.tmp", secret_path);
+	let write_tmp = || -> std::io::Result<()> {
+		let mut file = File::create(&tmp_path)?;
+		file.write_all(api_secret.as_bytes())?;
+		file.sync_all()
+	};
+	write_tmp()
+		.and_then(|_| fs::rename(&tmp_path, secret_path))
+		.map_err(|e| {
+			let _ = fs::remove_file(&tmp_path);
+			ErrorKind::Internal(format!(
+				"Unable to write secret file {}, {}",
+				secret_path, e
+			))
+			.into()
+		})
 }
 
 // Basic Authentication Middleware
 pub struct BasicAuthMiddleware {
-	api_basic_auth: String,
+	credentials: Arc<BasicAuthCredentials>,
 	basic_realm: &'static HeaderValue,
-	ignore_uri: Option<String>,
+	ignore_uris: Vec<String>,
 }
 
 impl BasicAuthMiddleware {
 	pub fn new(
-		api_basic_auth: String,
+		credentials: Arc<BasicAuthCredentials>,
 		basic_realm: &'static HeaderValue,
-		ignore_uri: Option<String>,
+		ignore_uris: Vec<String>,
 	) -> BasicAuthMiddleware {
 		BasicAuthMiddleware {
-			api_basic_auth,
+			credentials,
 			basic_realm,
-			ignore_uri,
+			ignore_uris,
 		}
 	}
 }
@@ -60,18 +212,14 @@ impl Handler for BasicAuthMiddleware {
 		if req.method().as_str() == "OPTIONS" {
 			return next_handler.call(req, handlers);
 		}
-		if let Some(u) = self.ignore_uri.as_ref() {
-			if req.uri().path() == u {
-				return next_handler.call(req, handlers);
-			}
+		if self.ignore_uris.iter().any(|u| req.uri().path() == u) {
+			return next_handler.call(req, handlers);
 		}
 		if req.headers().contains_key(AUTHORIZATION)
-			&& verify_slices_are_equal(
+			&& self.credentials.verify(
 				req.headers()[AUTHORIZATION].as_bytes(),
-				&self.api_basic_auth.as_bytes(),
-			)
-			.is_ok()
-		{
+				Utc::now().timestamp(),
+			) {
 			next_handler.call(req, handlers)
 		} else {
 			// Unauthorized 401
@@ -82,19 +230,19 @@ impl Handler for BasicAuthMiddleware {
 
 // Basic Authentication Middleware
 pub struct BasicAuthURIMiddleware {
-	api_basic_auth: String,
+	credentials: Arc<BasicAuthCredentials>,
 	basic_realm: &'static HeaderValue,
 	target_uri: String,
 }
 
 impl BasicAuthURIMiddleware {
 	pub fn new(
-		api_basic_auth: String,
+		credentials: Arc<BasicAuthCredentials>,
 		basic_realm: &'static HeaderValue,
 		target_uri: String,
 	) -> BasicAuthURIMiddleware {
 		BasicAuthURIMiddleware {
-			api_basic_auth,
+			credentials,
 			basic_realm,
 			target_uri,
 		}
@@ -116,12 +264,10 @@ impl Handler for BasicAuthURIMiddleware {
 		}
 		if req.uri().path() == self.target_uri {
 			if req.headers().contains_key(AUTHORIZATION)
-				&& verify_slices_are_equal(
+				&& self.credentials.verify(
 					req.headers()[AUTHORIZATION].as_bytes(),
-					&self.api_basic_auth.as_bytes(),
-				)
-				.is_ok()
-			{
+					Utc::now().timestamp(),
+				) {
 				next_handler.call(req, handlers)
 			} else {
 				// Unauthorized 401
@@ -133,6 +279,68 @@ impl Handler for BasicAuthURIMiddleware {
 	}
 }
 
+/// Permission of the client by its TLS client certificate. When the client certificates
+/// are used, the clients without one are read-only, see `ClientCertMiddleware`.
+/// None if the client certificates are not used.
+pub fn client_permission(req: &Request<Body>) -> Option<ClientPermission> {
+	req.extensions()
+		.get::<ClientPermission>()
+		.cloned()
+		.or_else(|| req.extensions().get::<ClientCert>().map(|c| c.permission))
+}
+
+/// true if all json-rpc calls of the request (might be a batch) are read-only owner API methods
+pub fn is_read_only_owner_request(request: &Value) -> bool {
+	match request {
+		Value::Array(requests) => requests.iter().all(is_read_only_owner_request),
+		_ => match request["method"].as_str() {
+			Some(method) => method.starts_with("get_") || OWNER_READ_ONLY_METHODS.contains(&method),
+			None => false,
+		},
+	}
+}
+
+/// Limits the clients with the read-only TLS client certificate to the GET requests. The clients
+/// without a certificate are read-only as well, so a certificate holder can't get around the
+/// restriction by not presenting it. The json-rpc APIs at the ignored URIs check the called
+/// methods themselves, by the permission this middleware puts into the request extensions.
+pub struct ClientCertMiddleware {
+	ignore_uris: Vec<String>,
+}
+
+impl ClientCertMiddleware {
+	pub fn new(ignore_uris: Vec<String>) -> ClientCertMiddleware {
+		ClientCertMiddleware { ignore_uris }
+	}
+}
+
+impl Handler for ClientCertMiddleware {
+	fn call(
+		&self,
+		mut req: Request<Body>,
+		mut handlers: Box<dyn Iterator<Item = HandlerObj>>,
+	) -> ResponseFuture {
+		let next_handler = match handlers.next() {
+			Some(h) => h,
+			None => return response(StatusCode::INTERNAL_SERVER_ERROR, "no handler found"),
+		};
+		let permission = client_permission(&req).unwrap_or(ClientPermission::ReadOnly);
+		req.extensions_mut().insert(permission);
+		if permission == ClientPermission::ReadOnly
+			&& !(req.method() == Method::GET
+				|| req.method() == Method::HEAD
+				|| req.method() == Method::OPTIONS)
+			&& !self.ignore_uris.iter().any(|u| req.uri().path() == u)
+		{
+			return response(
+				StatusCode::FORBIDDEN,
+				"client certificate has the read-only permission",
+			);
+		}
+		next_handler.call(req, handlers)
+	}
+}
+
 fn unauthorized_response(basic_realm: &HeaderValue) -> ResponseFuture {
 	let response = Response::builder()
 		.status(StatusCode::UNAUTHORIZED)
@@ -141,3 +349,64 @@ fn unauthorized_response(basic_realm: &HeaderValue) -> ResponseFuture {
 		.unwrap();
 	Box::pin(ok(response))
 }
+
+#[cfg(test)]
+mod test {
+	use super::*;
+
+	#[test]
+	fn test_read_only_owner_request() {
+		let call = |method: &str| serde_json::json!({"jsonrpc": "2.0", "method": method, "id": 1});
+		assert!(is_read_only_owner_request(&call("get_status")));
+		assert!(is_read_only_owner_request(&call("validate_block")));
+		assert!(!is_read_only_owner_request(&call("ban_peer")));
+		assert!(!is_read_only_owner_request(&serde_json::json!({"id": 1})));
+		assert!(is_read_only_owner_request(&Value::Array(vec![
+			call("get_status"),
+			call("get_connected_peers"),
+		])));
+		assert!(!is_read_only_owner_request(&Value::Array(vec![
+			call("get_status"),
+			call("compact_chain"),
+		])));
+	}
+
+	#[test]
+	fn test_client_permission() {
+		let mut req = Request::builder().body(Body::empty()).unwrap();
+		assert_eq!(client_permission(&req), None);
+		req.extensions_mut().insert(ClientPermission::ReadOnly);
+		assert_eq!(client_permission(&req), Some(ClientPermission::ReadOnly));
+		assert_eq!(
+			crate::rest::normalize_fingerprint("AB:cd:0F"),
+			crate::rest::normalize_fingerprint("abcd0f")
+		);
+	}
+
+	#[test]
+	fn test_rotate_credentials() {
+		let dir = std::env::temp_dir().join("mwc_test_rotate_credentials");
+		fs::create_dir_all(&dir).unwrap();
+		let secret_path = dir.join(".api_secret");
+		let secret_path = secret_path.to_str().unwrap();
+		fs::write(secret_path, "old_secret").unwrap();
+
+		let credentials = BasicAuthCredentials::new("mwc", "old_secret", secret_path);
+		let old_auth = basic_auth_header("mwc", "old_secret");
+		let now = Utc::now().timestamp();
+		assert!(credentials.verify(old_auth.as_bytes(), now));
+		assert!(!credentials.verify(b"Basic bad", now));
+
+		let valid_until = credentials.rotate(60).unwrap();
+		let new_secret = fs::read_to_string(secret_path).unwrap();
+		assert_eq!(new_secret.len(), API_SECRET_LEN);
+		assert!(new_secret.chars().all(|c| c.is_ascii_alphanumeric()));
+		let new_auth = basic_auth_header("mwc", &new_secret);
+		assert!(credentials.verify(new_auth.as_bytes(), now));
+		// previous secret is accepted during the grace period only
+		assert!(credentials.verify(old_auth.as_bytes(), valid_until - 1));
+		assert!(!credentials.verify(old_auth.as_bytes(), valid_until));
+
+		fs::remove_dir_all(&dir).unwrap();
+	}
+}
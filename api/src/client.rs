@@ -232,7 +232,40 @@ where
 	Ok(ser)
 }
 
+/// Transport level reason of the failed request, so the caller can decide if it can be retried
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum RequestFailure {
+	/// Unable to connect, the request is not sent
+	Connect,
+	/// Response is not received in time, the request might be processed anyway
+	Timeout,
+	/// Node responded with a non success HTTP status code
+	Status(u16),
+	/// Request failed for some other reason
+	Other,
+}
+
+fn hyper_failure(e: &hyper::Error) -> RequestFailure {
+	if e.is_connect() {
+		return RequestFailure::Connect;
+	}
+	let mut source = std::error::Error::source(e);
+	while let Some(err) = source {
+		if let Some(io_err) = err.downcast_ref::<std::io::Error>() {
+			if io_err.kind() == std::io::ErrorKind::TimedOut {
+				return RequestFailure::Timeout;
+			}
+		}
+		source = err.source();
+	}
+	RequestFailure::Other
+}
+
 async fn send_request_async(req: Request<Body>) -> Result<String, Error> {
+	send_request_failure_async(req).await.map_err(|(e, _)| e)
+}
+
+async fn send_request_failure_async(req: Request<Body>) -> Result<String, (Error, RequestFailure)> {
 	let https = hyper_rustls::HttpsConnector::new();
 	let mut connector = TimeoutConnector::new(https);
 	connector.set_connect_timeout(Some(Duration::from_secs(20)));
@@ -240,29 +273,55 @@ async fn send_request_async(req: Request<Body>) -> Result<String, Error> {
 	connector.set_write_timeout(Some(Duration::from_secs(20)));
 	let client = Client::builder().build::<_, Body>(connector);
 
-	let resp = client
-		.request(req)
-		.await
-		.map_err(|e| ErrorKind::RequestError(format!("Cannot make request: {}", e)))?;
+	let resp = client.request(req).await.map_err(|e| {
+		(
+			ErrorKind::RequestError(format!("Cannot make request: {}", e)).into(),
+			hyper_failure(&e),
+		)
+	})?;
 
 	let status = resp.status().clone();
 
 	// Read body first because we want to return it in case of error.
-	let raw = body::to_bytes(resp)
-		.await
-		.map_err(|e| ErrorKind::RequestError(format!("Cannot read response body: {}", e)))?;
+	let raw = body::to_bytes(resp).await.map_err(|e| {
+		(
+			ErrorKind::RequestError(format!("Cannot read response body: {}", e)).into(),
+			hyper_failure(&e),
+		)
+	})?;
 	let response_body = String::from_utf8_lossy(&raw).to_string();
 
 	if !status.is_success() {
-		return Err(ErrorKind::RequestError(format!(
-			"Wrong response code: {} with data {}",
-			status, response_body
-		))
-		.into());
+		return Err((
+			ErrorKind::RequestError(format!(
+				"Wrong response code: {} with data {}",
+				status, response_body
+			))
+			.into(),
+			RequestFailure::Status(status.as_u16()),
+		));
 	}
 	Ok(response_body)
 }
 
+/// Send the request, the error comes with its transport level reason.
+pub fn send_request_with_failure(
+	req: Request<Body>,
+) -> Result<String, (Error, Option<RequestFailure>)> {
+	let mut rt = Builder::new()
+		.basic_scheduler()
+		.enable_all()
+		.build()
+		.map_err(|e| {
+			(
+				ErrorKind::Internal(format!("can't create Tokio runtime, {}", e)).into(),
+				None,
+			)
+		})?;
+	rt.block_on(send_request_failure_async(req))
+		.map_err(|(e, failure)| (e, Some(failure)))
+}
+
 pub fn send_request(req: Request<Body>) -> Result<String, Error> {
 	let mut rt = Builder::new()
 		.basic_scheduler()
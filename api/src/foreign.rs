@@ -17,24 +17,30 @@
 use crate::chain::{Chain, SyncState};
 use crate::core::core::hash::Hash;
 use crate::core::core::hash::Hashed;
-use crate::core::core::transaction::Transaction;
+use crate::core::core::transaction::{Transaction, Weighting};
 use crate::core::core::verifier_cache::VerifierCache;
+use crate::core::core::TxKernel;
+use crate::core::ser::{self, ProtocolVersion};
 use crate::handlers::blocks_api::{BlockHandler, HeaderHandler};
 use crate::handlers::chain_api::{ChainHandler, KernelHandler, OutputHandler};
 use crate::handlers::pool_api::PoolHandler;
 use crate::handlers::transactions_api::TxHashSetHandler;
+use crate::handlers::utils::w;
 use crate::handlers::version_api::VersionHandler;
-use crate::pool::{self, BlockChain, PoolAdapter, PoolEntry};
+use crate::pool::{self, BlockChain, FeeEstimate, PoolAdapter, PoolEntry};
 use crate::rest::*;
 use crate::types::{
-	BlockHeaderPrintable, BlockPrintable, LocatedTxKernel, OutputListing, OutputPrintable, Tip,
-	Version,
+	BlockHeaderPrintable, BlockListing, BlockPrintable, ChainStats, CoinbaseMerkleProof,
+	DecodedTransaction, KernelMerkleProof, KernelState, KernelStatus, LocatedTxKernel,
+	OutputCursorListing, OutputListing, OutputPrintable, OutputRangeListing, Tip, Version,
 };
-use crate::util::RwLock;
+use crate::util::secp::pedersen::Commitment;
+use crate::util::{self, RwLock};
 use crate::{Libp2pMessages, Libp2pPeers};
-use chrono::Utc;
-use grin_p2p::libp2p_connection;
-use std::sync::Weak;
+use chrono::{DateTime, Utc};
+use grin_p2p::libp2p_mailbox::{self, MailboxMessage};
+use grin_p2p::{libp2p_connection, Error as P2pError};
+use std::sync::{Arc, Weak};
 
 /// Main interface into all node API functions.
 /// Node APIs are split into two separate blocks of functionality
@@ -151,6 +157,69 @@ where
 		)
 	}
 
+	/// Gets the blocks of the main chain in a range of heights, for traversing the chain
+	/// without a request per block. At most `max` blocks (capped at 1000) are returned,
+	/// the next page starts at `last_retrieved_height + 1`. On a pruned node the blocks below
+	/// the horizon are not available and are skipped.
+	///
+	/// # Arguments
+	/// * `start_height` - height of the first block.
+	/// * `end_height` - optional height of the last block, the chain head if not provided.
+	/// * `max` - max number of blocks to return.
+	/// * `include_proof` - include range proofs for outputs. Default: false
+	///
+	/// # Returns
+	/// * Result Containing:
+	/// * A [`BlockListing`](types/struct.BlockListing.html)
+	/// * or [`Error`](struct.Error.html) if an error is encountered.
+	///
+
+	pub fn get_blocks(
+		&self,
+		start_height: u64,
+		end_height: Option<u64>,
+		max: u64,
+		include_proof: Option<bool>,
+	) -> Result<BlockListing, Error> {
+		let block_handler = BlockHandler {
+			chain: self.chain.clone(),
+		};
+		block_handler.get_blocks(
+			start_height,
+			end_height,
+			max,
+			include_proof.unwrap_or(false),
+		)
+	}
+
+	/// Gets the wall-clock time this node first saw a block, given either a height, a hash
+	/// or an unspent output commitment. Unlike the header timestamp, this is the local time
+	/// the block reached this node, it is kept across restarts.
+	///
+	/// # Arguments
+	/// * `height` - block height.
+	/// * `hash` - block hash.
+	/// * `commit` - output commitment.
+	///
+	/// # Returns
+	/// * Result Containing:
+	/// * The first seen time, `None` if the block was not received by this node (i.e. fast sync).
+	/// * or [`Error`](struct.Error.html) if an error is encountered.
+	///
+
+	pub fn get_block_first_seen(
+		&self,
+		height: Option<u64>,
+		hash: Option<Hash>,
+		commit: Option<String>,
+	) -> Result<Option<DateTime<Utc>>, Error> {
+		let block_handler = BlockHandler {
+			chain: self.chain.clone(),
+		};
+		let hash = block_handler.parse_inputs(height, hash, commit)?;
+		block_handler.get_block_first_seen(&hash)
+	}
+
 	/// Returns the node version and block header version (used by grin-wallet).
 	///
 	/// # Returns
@@ -181,6 +250,26 @@ where
 		chain_handler.get_tip()
 	}
 
+	/// Returns chain statistics as of the block at the provided height: UTXO count,
+	/// kernel count, total supply and txhashset sizes. Heights below the horizon are
+	/// only available on archive nodes.
+	///
+	/// # Arguments
+	/// * `height` - block height on the current chain.
+	///
+	/// # Returns
+	/// * Result Containing:
+	/// * A [`ChainStats`](types/struct.ChainStats.html)
+	/// * or [`Error`](struct.Error.html) if an error is encountered.
+	///
+
+	pub fn get_chain_stats(&self, height: u64) -> Result<ChainStats, Error> {
+		let chain_handler = ChainHandler {
+			chain: self.chain.clone(),
+		};
+		chain_handler.get_chain_stats(height)
+	}
+
 	/// Returns a [`LocatedTxKernel`](types/struct.LocatedTxKernel.html) based on the kernel excess.
 	/// The `min_height` and `max_height` parameters are both optional.
 	/// If not supplied, `min_height` will be set to 0 and `max_height` will be set to the head of the chain.
@@ -210,6 +299,82 @@ where
 		kernel_handler.get_kernel_v2(excess, min_height, max_height)
 	}
 
+	/// Returns the status of a transaction identified by its kernel excess: in the
+	/// transaction pool, in a block on the current chain (with the number of
+	/// confirmations) or unknown to the node. Transactions in the Dandelion stem phase
+	/// are unknown.
+	///
+	/// # Arguments
+	/// * `excess` - kernel excess to look for.
+	///
+	/// # Returns
+	/// * Result Containing:
+	/// * A [`KernelStatus`](types/struct.KernelStatus.html)
+	/// * or [`Error`](struct.Error.html) if an error is encountered.
+	///
+
+	pub fn get_kernel_status(&self, excess: String) -> Result<KernelStatus, Error> {
+		let commit = util::from_hex(&excess)
+			.ok()
+			.filter(|c| c.len() == 33)
+			.map(Commitment::from_vec)
+			.ok_or_else(|| ErrorKind::RequestError(format!("invalid excess {}", excess)))?;
+
+		let pool_handler = PoolHandler {
+			tx_pool: self.tx_pool.clone(),
+		};
+		if let Some(state) = pool_handler.get_kernel_state(&commit)? {
+			return Ok(KernelStatus {
+				excess,
+				state,
+				height: None,
+				confirmations: None,
+			});
+		}
+
+		let chain = w(&self.chain)?;
+		match chain
+			.get_kernel_by_excess(&commit)
+			.map_err(|e| ErrorKind::Internal(format!("failed to get the kernel, {}", e)))?
+		{
+			Some((_, height, _)) => {
+				let tip = self.get_tip()?;
+				Ok(KernelStatus {
+					excess,
+					state: KernelState::Chain,
+					height: Some(height),
+					confirmations: Some(tip.height.saturating_sub(height) + 1),
+				})
+			}
+			None => Ok(KernelStatus {
+				excess,
+				state: KernelState::Unknown,
+				height: None,
+				confirmations: None,
+			}),
+		}
+	}
+
+	/// Gets the wall-clock time this node first saw a transaction, identified by its
+	/// (first) kernel excess. Fee estimation and explorers can use it to age pool entries,
+	/// it is kept across restarts for about a week.
+	///
+	/// # Arguments
+	/// * `excess` - kernel excess of the transaction.
+	///
+	/// # Returns
+	/// * Result Containing:
+	/// * The first seen time, `None` if the transaction is unknown.
+	/// * or [`Error`](struct.Error.html) if an error is encountered.
+	///
+
+	pub fn get_tx_first_seen(&self, excess: String) -> Result<Option<DateTime<Utc>>, Error> {
+		let kernel_handler = KernelHandler {
+			chain: self.chain.clone(),
+		};
+		kernel_handler.get_tx_first_seen(excess)
+	}
+
 	/// Retrieves details about specifics outputs. Supports retrieval of multiple outputs in a single request.
 	/// Support retrieval by both commitment string and block height.
 	///
@@ -273,6 +438,112 @@ where
 		output_handler.get_unspent_outputs(start_index, end_index, max, include_proof)
 	}
 
+	/// UTXO traversal with a cursor. Retrieves up to `max` unspent outputs after the
+	/// cursor returned by the previous call. Unlike the MMR index based traversal the cursor
+	/// stays valid when the chain is compacted, and when the scanned blocks are reorged
+	/// the traversal resumes from the fork point.
+	///
+	/// # Arguments
+	/// * `cursor` - cursor returned by the previous call, none to start from the first output.
+	/// * `max` - max number of outputs to return.
+	/// * `include_proof` - whether or not to include the range proof in the response.
+	///
+	/// # Returns
+	/// * Result Containing:
+	/// * An [`OutputCursorListing`](types/struct.OutputCursorListing.html)
+	/// * or [`Error`](struct.Error.html) if an error is encountered.
+	///
+
+	pub fn get_unspent_outputs_cursor(
+		&self,
+		cursor: Option<String>,
+		max: u64,
+		include_proof: Option<bool>,
+	) -> Result<OutputCursorListing, Error> {
+		let output_handler = OutputHandler {
+			chain: self.chain.clone(),
+		};
+		output_handler.get_unspent_outputs_cursor(cursor, max, include_proof)
+	}
+
+	/// Retrieves the output at the given output MMR index, whether it is spent or not.
+	/// Only available on archive nodes, pruned nodes don't keep spent outputs.
+	///
+	/// # Arguments
+	/// * `mmr_index` - index of the output in the output MMR.
+	/// * `include_proof` - whether or not to include the range proof in the response.
+	///
+	/// # Returns
+	/// * Result Containing:
+	/// * An [`OutputPrintable`](types/struct.OutputPrintable.html)
+	/// * or [`Error`](struct.Error.html) if an error is encountered.
+	///
+
+	pub fn get_historical_output(
+		&self,
+		mmr_index: u64,
+		include_proof: Option<bool>,
+	) -> Result<OutputPrintable, Error> {
+		let output_handler = OutputHandler {
+			chain: self.chain.clone(),
+		};
+		output_handler.get_historical_output(mmr_index, include_proof)
+	}
+
+	/// Retrieves the outputs created by the blocks in the height range, in a single
+	/// request. Wallets can use it for the restore instead of the MMR index paging.
+	/// At most 1000 blocks are processed, a longer range is truncated.
+	///
+	/// # Arguments
+	/// * `start_height` - height of the first block.
+	/// * `end_height` - height of the last block, inclusive.
+	/// * `include_proof` - whether or not to include the range proofs in the response.
+	/// The proof hash is empty when the proofs are not included.
+	/// * `include_spent` - whether or not to include the spent outputs. They are only
+	/// available within the horizon or on archive nodes.
+	///
+	/// At most 1000 blocks are read, and no more blocks once 1000 outputs are collected.
+	/// The rest of the range is requested again from `next_height`. A range starting past
+	/// the chain head is empty.
+	///
+	/// # Returns
+	/// * Result Containing:
+	/// * An [`OutputRangeListing`](types/struct.OutputRangeListing.html)
+	/// * or [`Error`](struct.Error.html) if an error is encountered.
+	///
+
+	pub fn get_outputs_in_range(
+		&self,
+		start_height: u64,
+		end_height: u64,
+		include_proof: Option<bool>,
+		include_spent: Option<bool>,
+	) -> Result<OutputRangeListing, Error> {
+		let output_handler = OutputHandler {
+			chain: self.chain.clone(),
+		};
+		output_handler.get_outputs_in_range(start_height, end_height, include_proof, include_spent)
+	}
+
+	/// Gets the height of the block that includes an unspent output. The lookup is done
+	/// via the output commitment index, wallets can use it instead of scanning the output MMR.
+	///
+	/// # Arguments
+	/// * `commit` - output commitment, hex encoded.
+	///
+	/// # Returns
+	/// * Result Containing:
+	/// * The block height, `None` if the output is unknown or spent.
+	/// * or [`Error`](struct.Error.html) if an error is encountered.
+	///
+
+	pub fn get_output_height(&self, commit: String) -> Result<Option<u64>, Error> {
+		let output_handler = OutputHandler {
+			chain: self.chain.clone(),
+		};
+		output_handler.get_output_height(commit)
+	}
+
 	/// Retrieves the PMMR indices based on the provided block height(s).
 	///
 	/// # Arguments
@@ -342,6 +613,26 @@ where
 		pool_handler.get_unconfirmed_transactions()
 	}
 
+	/// Estimates the fee for a transaction to be mined within the target number of blocks,
+	/// from the transactions waiting in the pool and the fees paid in the recent blocks.
+	///
+	/// # Arguments
+	/// * `blocks_target` - number of blocks the transaction should be mined within (1 to 60).
+	///
+	/// # Returns
+	/// * Result Containing:
+	/// * A [`FeeEstimate`](../grin_pool/fees/struct.FeeEstimate.html), the fee of the
+	/// transaction is its weight multiplied by the estimated `fee_base`.
+	/// * or [`Error`](struct.Error.html) if an error is encountered.
+	///
+
+	pub fn get_fee_estimate(&self, blocks_target: u64) -> Result<FeeEstimate, Error> {
+		let pool_handler = PoolHandler {
+			tx_pool: self.tx_pool.clone(),
+		};
+		pool_handler.get_fee_estimate(blocks_target)
+	}
+
 	/// Push new transaction to our local transaction pool.
 	///
 	/// # Arguments
@@ -370,6 +661,116 @@ where
 		}
 	}
 
+	/// Build the merkle proof for the unspent coinbase output against the output root
+	/// of the block that includes it.
+	///
+	/// # Arguments
+	/// * `commit` - coinbase output commitment.
+	///
+	/// # Returns
+	/// * Result Containing:
+	/// * A [`CoinbaseMerkleProof`](types/struct.CoinbaseMerkleProof.html)
+	/// * or [`Error`](struct.Error.html) if an error is encountered.
+	///
+	pub fn get_coinbase_merkle_proof(&self, commit: String) -> Result<CoinbaseMerkleProof, Error> {
+		let txhashset_handler = TxHashSetHandler {
+			chain: self.chain.clone(),
+		};
+		txhashset_handler.get_coinbase_merkle_proof(commit)
+	}
+
+	/// Verify the coinbase merkle proof. The block must be on the current chain.
+	///
+	/// # Arguments
+	/// * `proof` - merkle proof as it was returned by `get_coinbase_merkle_proof`.
+	///
+	/// # Returns
+	/// * Result Containing:
+	/// * true if the proof is valid
+	/// * or [`Error`](struct.Error.html) if the proof data is malformed.
+	///
+	pub fn verify_coinbase_merkle_proof(&self, proof: CoinbaseMerkleProof) -> Result<bool, Error> {
+		let txhashset_handler = TxHashSetHandler {
+			chain: self.chain.clone(),
+		};
+		txhashset_handler.verify_coinbase_merkle_proof(proof)
+	}
+
+	/// Build the merkle proof for the kernel at the kernel MMR index against the kernel root
+	/// of the block at the given height. The kernel MMR is never pruned, so the proof is
+	/// available for any kernel included at or below that height.
+	///
+	/// # Arguments
+	/// * `mmr_index` - kernel MMR index (1-based), as returned by `get_kernel`.
+	/// * `height` - height of the block to build the proof against.
+	///
+	/// # Returns
+	/// * Result Containing:
+	/// * A [`KernelMerkleProof`](types/struct.KernelMerkleProof.html)
+	/// * or [`Error`](struct.Error.html) if an error is encountered.
+	///
+	pub fn get_kernel_merkle_proof(
+		&self,
+		mmr_index: u64,
+		height: u64,
+	) -> Result<KernelMerkleProof, Error> {
+		let txhashset_handler = TxHashSetHandler {
+			chain: self.chain.clone(),
+		};
+		txhashset_handler.get_kernel_merkle_proof(mmr_index, height)
+	}
+
+	/// Verify the kernel merkle proof. The block must be on the current chain.
+	///
+	/// # Arguments
+	/// * `proof` - merkle proof as it was returned by `get_kernel_merkle_proof`.
+	///
+	/// # Returns
+	/// * Result Containing:
+	/// * true if the proof is valid
+	/// * or [`Error`](struct.Error.html) if the proof data is malformed.
+	///
+	pub fn verify_kernel_merkle_proof(&self, proof: KernelMerkleProof) -> Result<bool, Error> {
+		let txhashset_handler = TxHashSetHandler {
+			chain: self.chain.clone(),
+		};
+		txhashset_handler.verify_kernel_merkle_proof(proof)
+	}
+
+	/// Decode raw transaction without submitting it to the pool.
+	///
+	/// # Arguments
+	/// * `tx_hex` - hex encoded binary transaction. Any supported protocol version is accepted.
+	///
+	/// # Returns
+	/// * Result Containing:
+	/// * A [`DecodedTransaction`](types/struct.DecodedTransaction.html)
+	/// * or [`Error`](struct.Error.html) if the data can't be decoded as a transaction.
+	///
+	pub fn decode_tx(&self, tx_hex: String) -> Result<DecodedTransaction, Error> {
+		let tx_bin = util::from_hex(tx_hex.trim())
+			.map_err(|e| ErrorKind::Argument(format!("Invalid transaction hex, {}", e)))?;
+
+		// Wallets might use any protocol version, trying from our local one down to the first.
+		let mut res = Err(ErrorKind::Argument("Unable to decode transaction".to_string()).into());
+		for version in (1..=ProtocolVersion::local().value()).rev() {
+			let version = ProtocolVersion(version);
+			let mut data = &tx_bin[..];
+			let tx = ser::deserialize::<Transaction, _>(&mut data, version);
+			// All data must be consumed, otherwise the version is wrong
+			if let (Ok(tx), true) = (tx, data.is_empty()) {
+				let verifier_cache = w(&self.tx_pool)?.read().verifier_cache.clone();
+				let validation_error = tx
+					.validate(Weighting::AsTransaction, verifier_cache)
+					.err()
+					.map(|e| format!("{}", e));
+				res = Ok(DecodedTransaction::from_tx(&tx, version, validation_error));
+				break;
+			}
+		}
+		res
+	}
+
 	/// Get TOR address on this node. Return none if TOR is not running.
 	pub fn get_libp2p_peers(&self) -> Result<Libp2pPeers, Error> {
 		//get_server_onion_address()
@@ -405,4 +806,75 @@ where
 				.collect(),
 		})
 	}
+
+	/// Posts the direct message for another wallet into the mailbox. The message is stored
+	/// by this node and published to the libp2p network, the nodes that run the mailbox keep
+	/// it until it expires or it is replaced by the messages that pay more.
+	///
+	/// # Arguments
+	/// * `message` - integrity message, hex. The message data is built with
+	/// [`seal_message`](../grin_p2p/libp2p_mailbox/fn.seal_message.html) for the recipient
+	/// onion address.
+	///
+	/// # Returns
+	/// * Result Containing:
+	/// * A [`MailboxMessage`](../grin_p2p/libp2p_mailbox/struct.MailboxMessage.html) that was stored.
+	/// * or [`Error`](struct.Error.html) if an error is encountered.
+	///
+
+	pub fn post_mailbox_message(&self, message: String) -> Result<MailboxMessage, Error> {
+		let data = util::from_hex(&message)
+			.map_err(|e| ErrorKind::Argument(format!("Invalid message hex, {}", e)))?;
+		let fee_base = libp2p_connection::get_integrity_fee_base().ok_or_else(|| {
+			ErrorKind::P2pError("libp2p node is not running, unable to post message".to_string())
+		})?;
+		let chain = w(&self.chain)?;
+		let min_height = chain
+			.head()?
+			.height
+			.saturating_sub(libp2p_connection::INTEGRITY_FEE_VALID_BLOCKS);
+		let kernel_validation_fn =
+			move |excess: &Commitment| -> Result<Option<TxKernel>, P2pError> {
+				Ok(chain
+					.get_kernel_by_excess(excess)?
+					.filter(|(_, height, _)| *height >= min_height)
+					.map(|(kernel, _, _)| kernel))
+			};
+		Ok(libp2p_mailbox::post_message(
+			&data,
+			Arc::new(kernel_validation_fn),
+			fee_base,
+		)?)
+	}
+
+	/// Returns the mailbox messages for the recipient. Messages are sealed for the recipient
+	/// onion address key, open them with
+	/// [`open_message`](../grin_p2p/libp2p_mailbox/fn.open_message.html).
+	///
+	/// # Arguments
+	/// * `recipient` - recipient onion address.
+	/// * `since` - return the messages that were received after this unix time only.
+	/// * `timestamp` - signing time of the request, unix time. The request is rejected if it
+	/// differs from the node time by more than 5 minutes.
+	/// * `signature` - signature of the request with the recipient onion address key, hex.
+	/// Build it with
+	/// [`sign_fetch_request`](../grin_p2p/libp2p_mailbox/fn.sign_fetch_request.html).
+	///
+	/// # Returns
+	/// * Result Containing:
+	/// * A vector of [`MailboxMessage`](../grin_p2p/libp2p_mailbox/struct.MailboxMessage.html)
+	/// * or [`Error`](struct.Error.html) if an error is encountered.
+	///
+
+	pub fn get_mailbox_messages(
+		&self,
+		recipient: String,
+		since: Option<i64>,
+		timestamp: i64,
+		signature: String,
+	) -> Result<Vec<MailboxMessage>, Error> {
+		Ok(libp2p_mailbox::get_messages(
+			&recipient, since, timestamp, &signature,
+		)?)
+	}
 }
@@ -18,14 +18,18 @@ use crate::core::core::hash::Hash;
 use crate::core::core::transaction::Transaction;
 use crate::core::core::verifier_cache::VerifierCache;
 use crate::foreign::Foreign;
-use crate::pool::PoolEntry;
 use crate::pool::{BlockChain, PoolAdapter};
+use crate::pool::{FeeEstimate, PoolEntry};
 use crate::rest::ErrorKind;
 use crate::types::{
-	BlockHeaderPrintable, BlockPrintable, LocatedTxKernel, OutputListing, OutputPrintable, Tip,
-	Version,
+	BlockHeaderPrintable, BlockListing, BlockPrintable, ChainStats, CoinbaseMerkleProof,
+	DecodedTransaction, KernelMerkleProof, KernelStatus, LocatedTxKernel, OutputCursorListing,
+	OutputListing, OutputPrintable, OutputRangeListing, Tip, Version,
 };
 use crate::{util, Libp2pMessages, Libp2pPeers};
+use chrono::{DateTime, Utc};
+use easy_jsonrpc_mw::{Handler, MaybeReply};
+use grin_p2p::libp2p_mailbox::MailboxMessage;
 
 /// Public definition used to generate Node jsonrpc api.
 /// * When running `grin` with defaults, the V2 api is available at
@@ -401,6 +405,166 @@ pub trait ForeignRpc: Sync + Send {
 	 */
 	fn get_version(&self) -> Result<Version, ErrorKind>;
 
+	/**
+	Networked version of [Foreign::get_blocks](struct.Foreign.html#method.get_blocks).
+
+	# Json rpc example
+
+	```
+	# grin_api::doctest_helper_json_rpc_foreign_assert_response!(
+	# r#"
+	{
+		"jsonrpc": "2.0",
+		"method": "get_blocks",
+		"params": [374274, 374274, 10, false],
+		"id": 1
+	}
+	# "#
+	# ,
+	# r#"
+	{
+		"id": 1,
+		"jsonrpc": "2.0",
+		"result": {
+			"Ok": {
+			"highest_height": 374280,
+			"last_retrieved_height": 374274,
+			"blocks": [
+				{
+					"header": {
+						"cuckoo_solution": [
+						1263501,
+						14648727,
+						42430559,
+						58137254,
+						68666726,
+						72784903,
+						101936839,
+						104273571,
+						123886748,
+						131179768,
+						155443226,
+						162493783,
+						164784425,
+						167313215,
+						169806918,
+						183041591,
+						184403611,
+						210351649,
+						215159650,
+						239995384,
+						240935454,
+						257742462,
+						280820644,
+						300143903,
+						303146496,
+						311804841,
+						341039986,
+						354918290,
+						363508555,
+						377618528,
+						396693709,
+						397417856,
+						399875872,
+						413238540,
+						413767813,
+						432697194,
+						436903767,
+						447257325,
+						453337210,
+						459401597,
+						496068509,
+						511300624
+						],
+						"edge_bits": 29,
+						"hash": "000001e16cb374e38c979c353a0aaffbf5b939da7688f69ad99efda6c112ea9b",
+						"height": 374274,
+						"kernel_root": "e17920c0e456a6feebf19e24a46f510a85f21cb60e81012f843c00fe2c4cad6e",
+						"nonce": 4354431877761457166,
+						"output_root": "1e9daee31b80c6b83573eacfd3048a4af57c614bd36f9acd5fb50fbd236beb16",
+						"prev_root": "9827b8ffab942e264b6ac81f2b487e3de65e411145c514092ce783df9344fa8a",
+						"previous": "00001266a73ba6a8032ef8b4d4f5508407ffb1c270c105dac06f4669c17af020",
+						"range_proof_root": "3491b8c46a3919df637a636ca72824377f89c4967dcfe4857379a4a82b510069",
+						"secondary_scaling": 571,
+						"timestamp": "2019-10-03T15:15:35+00:00",
+						"total_difficulty": 1133438031814173,
+						"total_kernel_offset": "63315ca0be65c9f6ddf2d3306876caf9f458a01d1a0bf50cc4d3c9b699161958",
+						"version": 2
+					},
+					"inputs": [],
+					"kernels": [
+						{
+						"excess": "08761e9cb1eea5bfcf771d1218b5ec802798d6eecaf75faae50ba3a1997aaef009",
+						"excess_sig": "971317046c533d21dff3e449cc9380c2be10b0274f70e009aa2453f755239e3299883c09a1785b15a141d89d563cdd59395886c7d63aba9c2b6438575555e2c4",
+						"features": "Coinbase",
+						"fee": 0,
+						"lock_height": 0
+						}
+					],
+					"outputs": [
+						{
+						"block_height": 374274,
+						"commit": "09d33615563ba2d65acc2b295a024337166b9f520122d49730c73e8bfb43017610",
+						"merkle_proof": null,
+						"mmr_index": 4091742,
+						"output_type": "Coinbase",
+						"proof": null,
+						"proof_hash": "cfd97db403c274220bb0dbaf3ecc88e483c0b707d8e6f16dfda37cd4f2c3211c",
+						"spent": false
+						}
+					]
+				}
+			]
+			}
+		}
+	}
+	# "#
+	# );
+	```
+	*/
+	fn get_blocks(
+		&self,
+		start_height: u64,
+		end_height: Option<u64>,
+		max: u64,
+		include_proof: Option<bool>,
+	) -> Result<BlockListing, ErrorKind>;
+
+	/**
+	Networked version of [Foreign::get_block_first_seen](struct.Foreign.html#method.get_block_first_seen).
+
+	# Json rpc example
+
+	```
+	# grin_api::doctest_helper_json_rpc_foreign_assert_response!(
+	# r#"
+	{
+		"jsonrpc": "2.0",
+		"method": "get_block_first_seen",
+		"params": [374274, null, null],
+		"id": 1
+	}
+	# "#
+	# ,
+	# r#"
+	{
+		"id": 1,
+		"jsonrpc": "2.0",
+		"result": {
+			"Ok": "2019-10-03T15:15:37.412Z"
+		}
+	}
+	# "#
+	# );
+	```
+	*/
+	fn get_block_first_seen(
+		&self,
+		height: Option<u64>,
+		hash: Option<String>,
+		commit: Option<String>,
+	) -> Result<Option<DateTime<Utc>>, ErrorKind>;
+
 	/**
 	Networked version of [Foreign::get_tip](struct.Foreign.html#method.get_tip).
 
@@ -436,6 +600,45 @@ pub trait ForeignRpc: Sync + Send {
 	 */
 	fn get_tip(&self) -> Result<Tip, ErrorKind>;
 
+	/**
+	Networked version of [Foreign::get_chain_stats](struct.Foreign.html#method.get_chain_stats).
+
+	# Json rpc example
+
+	```
+	# grin_api::doctest_helper_json_rpc_foreign_assert_response!(
+	# r#"
+	{
+		"jsonrpc": "2.0",
+		"method": "get_chain_stats",
+		"params": [374274],
+		"id": 1
+	}
+	# "#
+	# ,
+	# r#"
+	{
+		"id": 1,
+		"jsonrpc": "2.0",
+		"result": {
+			"Ok": {
+			"height": 374274,
+			"hash": "000001e16cb374e38c979c353a0aaffbf5b939da7688f69ad99efda6c112ea9b",
+			"utxo_count": 186512,
+			"kernel_count": 2045871,
+			"total_supply": 10898275000000000,
+			"output_mmr_size": 4091743,
+			"rproof_mmr_size": 4091743,
+			"kernel_mmr_size": 4091737
+			}
+		}
+	}
+	# "#
+	# );
+	```
+	*/
+	fn get_chain_stats(&self, height: u64) -> Result<ChainStats, ErrorKind>;
+
 	/**
 	Networked version of [Foreign::get_kernel](struct.Foreign.html#method.get_kernel).
 
@@ -479,6 +682,71 @@ pub trait ForeignRpc: Sync + Send {
 		max_height: Option<u64>,
 	) -> Result<LocatedTxKernel, ErrorKind>;
 
+	/**
+	Networked version of [Foreign::get_kernel_status](struct.Foreign.html#method.get_kernel_status).
+
+	# Json rpc example
+
+	```
+	# grin_api::doctest_helper_json_rpc_foreign_assert_response!(
+	# r#"
+	{
+		"jsonrpc": "2.0",
+		"method": "get_kernel_status",
+		"params": ["08b3b8b83c622f630141a66c9cad96e19c78f745e4e2ddea85439f05d14a404640"],
+		"id": 1
+	}
+	# "#
+	# ,
+	# r#"
+	{
+		"id": 1,
+		"jsonrpc": "2.0",
+		"result": {
+			"Ok": {
+			"excess": "08b3b8b83c622f630141a66c9cad96e19c78f745e4e2ddea85439f05d14a404640",
+			"state": "Chain",
+			"height": 374274,
+			"confirmations": 1
+			}
+		}
+	}
+	# "#
+	# );
+	```
+	*/
+	fn get_kernel_status(&self, excess: String) -> Result<KernelStatus, ErrorKind>;
+
+	/**
+	Networked version of [Foreign::get_tx_first_seen](struct.Foreign.html#method.get_tx_first_seen).
+
+	# Json rpc example
+
+	```
+	# grin_api::doctest_helper_json_rpc_foreign_assert_response!(
+	# r#"
+	{
+		"jsonrpc": "2.0",
+		"method": "get_tx_first_seen",
+		"params": ["08b3b8b83c622f630141a66c9cad96e19c78f745e4e2ddea85439f05d14a404640"],
+		"id": 1
+	}
+	# "#
+	# ,
+	# r#"
+	{
+		"id": 1,
+		"jsonrpc": "2.0",
+		"result": {
+			"Ok": "2019-10-03T15:14:02.905Z"
+		}
+	}
+	# "#
+	# );
+	```
+	*/
+	fn get_tx_first_seen(&self, excess: String) -> Result<Option<DateTime<Utc>>, ErrorKind>;
+
 	/**
 	Networked version of [Foreign::get_outputs](struct.Foreign.html#method.get_outputs).
 
@@ -628,7 +896,7 @@ pub trait ForeignRpc: Sync + Send {
 	) -> Result<OutputListing, ErrorKind>;
 
 	/**
-	Networked version of [Foreign::get_pmmr_indices](struct.Foreign.html#method.get_pmmr_indices).
+	Networked version of [Foreign::get_unspent_outputs_cursor](struct.Foreign.html#method.get_unspent_outputs_cursor).
 
 	# Json rpc example
 
@@ -637,8 +905,8 @@ pub trait ForeignRpc: Sync + Send {
 	# r#"
 	{
 		"jsonrpc": "2.0",
-		"method": "get_pmmr_indices",
-		"params": [0, 100],
+		"method": "get_unspent_outputs_cursor",
+		"params": ["00000000003e6f34000000000005b5fe00000a3c5f1e8d2b7a4c6e9f0d3b5a7c9e1f2d4b6a8c0e2f4d6b8a0c2e4f6a8b", 1, false],
 		"id": 1
 	}
 	# "#
@@ -647,25 +915,37 @@ pub trait ForeignRpc: Sync + Send {
 	{
 		"id": 1,
 		"jsonrpc": "2.0",
-		  "result": {
+		"result": {
 			"Ok": {
-				  "highest_index": 398,
-				  "last_retrieved_index": 2,
-				  "outputs": []
+			"next_cursor": "00000000003e6f5e000000000005b602000001e16cb374e38c979c353a0aaffbf5b939da7688f69ad99efda6c112ea9b",
+			"outputs": [
+				{
+					"output_type": "Coinbase",
+					"commit": "09d33615563ba2d65acc2b295a024337166b9f520122d49730c73e8bfb43017610",
+					"spent": false,
+					"proof": null,
+					"proof_hash": "cfd97db403c274220bb0dbaf3ecc88e483c0b707d8e6f16dfda37cd4f2c3211c",
+					"block_height": 374274,
+					"merkle_proof": null,
+					"mmr_index": 4091742
+				}
+			]
 			}
+		}
 	}
 	# "#
 	# );
 	```
-	 */
-	fn get_pmmr_indices(
+	*/
+	fn get_unspent_outputs_cursor(
 		&self,
-		start_block_height: u64,
-		end_block_height: Option<u64>,
-	) -> Result<OutputListing, ErrorKind>;
+		cursor: Option<String>,
+		max: u64,
+		include_proof: Option<bool>,
+	) -> Result<OutputCursorListing, ErrorKind>;
 
 	/**
-	Networked version of [Foreign::get_pool_size](struct.Foreign.html#method.get_pool_size).
+	Networked version of [Foreign::get_historical_output](struct.Foreign.html#method.get_historical_output).
 
 	# Json rpc example
 
@@ -674,8 +954,8 @@ pub trait ForeignRpc: Sync + Send {
 	# r#"
 	{
 		"jsonrpc": "2.0",
-		"method": "get_pool_size",
-		"params": [],
+		"method": "get_historical_output",
+		"params": [4091742, false],
 		"id": 1
 	}
 	# "#
@@ -685,17 +965,30 @@ pub trait ForeignRpc: Sync + Send {
 		"id": 1,
 		"jsonrpc": "2.0",
 		"result": {
-			"Ok": 1
+			"Ok": {
+			"output_type": "Coinbase",
+			"commit": "09d33615563ba2d65acc2b295a024337166b9f520122d49730c73e8bfb43017610",
+			"spent": true,
+			"proof": null,
+			"proof_hash": "cfd97db403c274220bb0dbaf3ecc88e483c0b707d8e6f16dfda37cd4f2c3211c",
+			"block_height": 374274,
+			"merkle_proof": null,
+			"mmr_index": 4091742
+			}
 		}
 	}
 	# "#
 	# );
 	```
-	 */
-	fn get_pool_size(&self) -> Result<usize, ErrorKind>;
+	*/
+	fn get_historical_output(
+		&self,
+		mmr_index: u64,
+		include_proof: Option<bool>,
+	) -> Result<OutputPrintable, ErrorKind>;
 
 	/**
-	Networked version of [Foreign::get_stempool_size](struct.Foreign.html#method.get_stempool_size).
+	Networked version of [Foreign::get_outputs_in_range](struct.Foreign.html#method.get_outputs_in_range).
 
 	# Json rpc example
 
@@ -704,8 +997,8 @@ pub trait ForeignRpc: Sync + Send {
 	# r#"
 	{
 		"jsonrpc": "2.0",
-		"method": "get_stempool_size",
-		"params": [],
+		"method": "get_outputs_in_range",
+		"params": [1, 1, false, false],
 		"id": 1
 	}
 	# "#
@@ -715,17 +1008,37 @@ pub trait ForeignRpc: Sync + Send {
 		"id": 1,
 		"jsonrpc": "2.0",
 		"result": {
-			"Ok": 0
+			"Ok": {
+			"next_height": null,
+			"outputs": [
+				{
+				"block_height": 1,
+				"commit": "08b7e57c448db5ef25aa119dde2312c64d7ff1b890c416c6dda5ec73cbfed2edea",
+				"merkle_proof": null,
+				"mmr_index": 1,
+				"output_type": "Coinbase",
+				"proof": null,
+				"proof_hash": "",
+				"spent": false
+				}
+			]
+			}
 		}
 	}
 	# "#
 	# );
 	```
-	 */
-	fn get_stempool_size(&self) -> Result<usize, ErrorKind>;
+	*/
+	fn get_outputs_in_range(
+		&self,
+		start_height: u64,
+		end_height: u64,
+		include_proof: Option<bool>,
+		include_spent: Option<bool>,
+	) -> Result<OutputRangeListing, ErrorKind>;
 
 	/**
-	Networked version of [Foreign::get_unconfirmed_transactions](struct.Foreign.html#method.get_unconfirmed_transactions).
+	Networked version of [Foreign::get_output_height](struct.Foreign.html#method.get_output_height).
 
 	# Json rpc example
 
@@ -734,8 +1047,8 @@ pub trait ForeignRpc: Sync + Send {
 	# r#"
 	{
 		"jsonrpc": "2.0",
-		"method": "get_unconfirmed_transactions",
-		"params": [],
+		"method": "get_output_height",
+		"params": ["09d33615563ba2d65acc2b295a024337166b9f520122d49730c73e8bfb43017610"],
 		"id": 1
 	}
 	# "#
@@ -745,60 +1058,17 @@ pub trait ForeignRpc: Sync + Send {
 		"id": 1,
 		"jsonrpc": "2.0",
 		"result": {
-			"Ok": [
-			{
-				"src": "Broadcast",
-				"tx": {
-				"body": {
-					"inputs": [
-					{
-						"commit": "0992ce1827ec349e9f339ce183ffd01db39bf43999799d8191bfc267a58f0a715c",
-						"features": "Coinbase"
-					},
-					{
-						"commit": "0943a3c4ee4a22a5b086c26f8e6dc534204dafde0cf4c07e0c468d224dd79127ec",
-						"features": "Plain"
-					}
-					],
-					"kernels": [
-					{
-						"excess": "083c49eaaf6380d44596f52cce4cf278cfac6dd34fbef73981002d8f1e8ee8abe4",
-						"excess_sig": "3f011e7e288231d67f42cb4f6416c4720e6170d5e3c805a52d33aa4521328f9be0303be654bc8ddcd3111aadc27c848b9cf07e0a70885ef79be70b7bb70f8c75",
-						"features": {
-						"Plain": {
-							"fee": 7000000
-						}
-						}
-					}
-					],
-					"outputs": [
-					{
-						"commit": "0873fafd4a0e4f365939e24c68eeb18aafc6674ca244a364dcdbfa8fa525e7bae1",
-						"features": "Plain",
-						"proof": "4b675be40672d5965c43d9f03880560a8ac784ee3de8768e28c236a4bc43b8c3d4bc83dee00d2b96530af9607c3b91d9a828f0234bf2aaf7e7c0e9cf936db69c04ca1b267668fbdb2f08ce05c8b119c9d886ceaafb4634b7fae7ea01966ad825dddc9ffab8093155d9c5d268160b86fcad95f4f5e66bf46ff642a51629dbdfd7bba7936846915b925d547337a1b95c33030fad4178468825936242e631797aa3a8f0a5ae0d23040938622648c8432fc247a902abad27e383affb4ec518e4f6f55f55e264bc0f99957be203cfb26d4b8e561fb36da55a50b6ef5861134c484556d701133e1dceda5ea53e731184e0a11f33d06e13ca37d03d39dd047170580534b049862fcd6c73decc7c0af45a267ed148fe6ef2cc375ffebfa8187d2fa0a134428a036d2ec1f65d3ce036b955730fc1ee43b23b574bae2b58b7adfa2a7a45cdec393d9b658857c911560aa3c44cf4435a99d68f3dbc81c82ea43e426ef0198148a90336ee72472aab5f7feea1df93ec830fe5ec642c93c1046dec955df361bfdc3ab74477f847a1b72e8735ef65a8a6d1680745c0152bfb5cbb2a4b4671491a253a1a09d5a07d55f4872c9f0a3d25e07b257926629d5bb96aed96f5debab02503eb0ac45033323cc5a46c8e5d4469ee9f3dd618a20d54d6f5740c010fe5a0fe853efeb253a6df196bd24469ac51c1be8ba84737cecdb5ab73d7c52570d2273621fb69bd7ed985bbc6999dbd2d6fd2687ae44a391d604ff232cc6b3fbedd5d1cd0cd8c658c5d56069b5a5099cc5c9f48bbf7d7e83b4f9a7bdef6eabd164c8395468f818e8cd8c1c800bc3adfd66dbcb247d1bda5a7af38c288c0beb8e0d9160bf67500094530a0f8be52e97b5c2114f5a4a333a11c7f37f4c47a437422455d8cbcfa770cdc85ec55accf48cf14550b07f1346a02fccdf280fcb24c1fb38751d889a17e"
-					},
-					{
-						"commit": "08de9e42d361cabd99e566c67f7f8599c7e6985cd285a841277f1aeb89ad6c8fe3",
-						"features": "Plain",
-						"proof": "5eb7afa00e9681e3b6425fb4256c96905303505787d6a065e88a50154410b9a371b0f879d3f97cfa00425e9c8266e180188656acdbb46cacfdfb159fb135c5eb03b08be3c231c4b21df777da2e2afe8d30db91e602dc4ceed71aeb1b45a0266cfeadc4acbf9fdf7a67f67408fbbea7bf14182bc407373d243c6875373b655695604deb575369a9b28274885601b338882219c7f508aa2a0ae1d02736af2249327145f1d3d00093f9587f0e0b408692700fac0f2a048c329e81cabaa4b997dd88923fe97420125f394e21b4835e36cce9de383d9e223df1b5a6ba6f48ffeac315991189dc2716cc7ec07f6ccc8062344d5ed4fcaddf9070f44f0c59ffe8160d1f6fdfe42b40066f51e687d38b6b5255771800ac060bd8034cd68d14eee1b2f43b6d7bf20d71549ea9a50006dd30b9a795e785385801546eb9a83721a09fc34d3b69d4ccdc0ff0fb74d224048aeb66ecff5515296cadd57f42e0717cbba7c70719a10c007db4520e868efe98a51001b67952d7bda3174195a3d76b93ee4dac60137a38b2e8309cad13ef1cfb6c467f1969385e5b334b52f4fd55da440e036d2a428e9f3be905d79f717c169060468acc6d469636fed098b1aba5cd055a120314bcab55d5b8b6889321edf373517e93ef67fbe74557ec6c0211265efefa25a34ac267cf1db891c47163bfed20d2b535abfe60390c2844dcef5f0aad5fa7f1db9f726d7f223c025861069603936a22377707cdd3915e762e7061132124c716212b0e91bb7fc5d7816366f5d169d93fe75669a6ba19057bb2450958aa6f5ada09042570f46215af5a41b623d140be574b7a8c9ab24ea48da416dbe6ec0fa3b889206fb804df8d69805ceb80f1e9d4e8b664b3939491cba946d87585c830e3dab0638fa279b5e911642f18452e2731764aa62f92bbcf194c97f344c90c1931fd2c3af4bcf6b0"
-					}
-					]
-				},
-				"offset": "0eb2c2669ce918675c72697891e5527bd13da5a499396381409219b8bbbd8129"
-				},
-				"tx_at": "2019-10-07T16:20:08.709114Z"
-			}
-			]
+			"Ok": 374274
 		}
 	}
 	# "#
 	# );
 	```
-	 */
-	fn get_unconfirmed_transactions(&self) -> Result<Vec<PoolEntry>, ErrorKind>;
+	*/
+	fn get_output_height(&self, commit: String) -> Result<Option<u64>, ErrorKind>;
 
 	/**
-	Networked version of [Foreign::push_transaction](struct.Foreign.html#method.push_transaction).
+	Networked version of [Foreign::get_pmmr_indices](struct.Foreign.html#method.get_pmmr_indices).
 
 	# Json rpc example
 
@@ -807,7 +1077,213 @@ pub trait ForeignRpc: Sync + Send {
 	# r#"
 	{
 		"jsonrpc": "2.0",
-		"method": "push_transaction",
+		"method": "get_pmmr_indices",
+		"params": [0, 100],
+		"id": 1
+	}
+	# "#
+	# ,
+	# r#"
+	{
+		"id": 1,
+		"jsonrpc": "2.0",
+		  "result": {
+			"Ok": {
+				  "highest_index": 398,
+				  "last_retrieved_index": 2,
+				  "outputs": []
+			}
+	}
+	# "#
+	# );
+	```
+	 */
+	fn get_pmmr_indices(
+		&self,
+		start_block_height: u64,
+		end_block_height: Option<u64>,
+	) -> Result<OutputListing, ErrorKind>;
+
+	/**
+	Networked version of [Foreign::get_pool_size](struct.Foreign.html#method.get_pool_size).
+
+	# Json rpc example
+
+	```
+	# grin_api::doctest_helper_json_rpc_foreign_assert_response!(
+	# r#"
+	{
+		"jsonrpc": "2.0",
+		"method": "get_pool_size",
+		"params": [],
+		"id": 1
+	}
+	# "#
+	# ,
+	# r#"
+	{
+		"id": 1,
+		"jsonrpc": "2.0",
+		"result": {
+			"Ok": 1
+		}
+	}
+	# "#
+	# );
+	```
+	 */
+	fn get_pool_size(&self) -> Result<usize, ErrorKind>;
+
+	/**
+	Networked version of [Foreign::get_stempool_size](struct.Foreign.html#method.get_stempool_size).
+
+	# Json rpc example
+
+	```
+	# grin_api::doctest_helper_json_rpc_foreign_assert_response!(
+	# r#"
+	{
+		"jsonrpc": "2.0",
+		"method": "get_stempool_size",
+		"params": [],
+		"id": 1
+	}
+	# "#
+	# ,
+	# r#"
+	{
+		"id": 1,
+		"jsonrpc": "2.0",
+		"result": {
+			"Ok": 0
+		}
+	}
+	# "#
+	# );
+	```
+	 */
+	fn get_stempool_size(&self) -> Result<usize, ErrorKind>;
+
+	/**
+	Networked version of [Foreign::get_unconfirmed_transactions](struct.Foreign.html#method.get_unconfirmed_transactions).
+
+	# Json rpc example
+
+	```
+	# grin_api::doctest_helper_json_rpc_foreign_assert_response!(
+	# r#"
+	{
+		"jsonrpc": "2.0",
+		"method": "get_unconfirmed_transactions",
+		"params": [],
+		"id": 1
+	}
+	# "#
+	# ,
+	# r#"
+	{
+		"id": 1,
+		"jsonrpc": "2.0",
+		"result": {
+			"Ok": [
+			{
+				"src": "Broadcast",
+				"tx": {
+				"body": {
+					"inputs": [
+					{
+						"commit": "0992ce1827ec349e9f339ce183ffd01db39bf43999799d8191bfc267a58f0a715c",
+						"features": "Coinbase"
+					},
+					{
+						"commit": "0943a3c4ee4a22a5b086c26f8e6dc534204dafde0cf4c07e0c468d224dd79127ec",
+						"features": "Plain"
+					}
+					],
+					"kernels": [
+					{
+						"excess": "083c49eaaf6380d44596f52cce4cf278cfac6dd34fbef73981002d8f1e8ee8abe4",
+						"excess_sig": "3f011e7e288231d67f42cb4f6416c4720e6170d5e3c805a52d33aa4521328f9be0303be654bc8ddcd3111aadc27c848b9cf07e0a70885ef79be70b7bb70f8c75",
+						"features": {
+						"Plain": {
+							"fee": 7000000
+						}
+						}
+					}
+					],
+					"outputs": [
+					{
+						"commit": "0873fafd4a0e4f365939e24c68eeb18aafc6674ca244a364dcdbfa8fa525e7bae1",
+						"features": "Plain",
+						"proof": "4b675be40672d5965c43d9f03880560a8ac784ee3de8768e28c236a4bc43b8c3d4bc83dee00d2b96530af9607c3b91d9a828f0234bf2aaf7e7c0e9cf936db69c04ca1b267668fbdb2f08ce05c8b119c9d886ceaafb4634b7fae7ea01966ad825dddc9ffab8093155d9c5d268160b86fcad95f4f5e66bf46ff642a51629dbdfd7bba7936846915b925d547337a1b95c33030fad4178468825936242e631797aa3a8f0a5ae0d23040938622648c8432fc247a902abad27e383affb4ec518e4f6f55f55e264bc0f99957be203cfb26d4b8e561fb36da55a50b6ef5861134c484556d701133e1dceda5ea53e731184e0a11f33d06e13ca37d03d39dd047170580534b049862fcd6c73decc7c0af45a267ed148fe6ef2cc375ffebfa8187d2fa0a134428a036d2ec1f65d3ce036b955730fc1ee43b23b574bae2b58b7adfa2a7a45cdec393d9b658857c911560aa3c44cf4435a99d68f3dbc81c82ea43e426ef0198148a90336ee72472aab5f7feea1df93ec830fe5ec642c93c1046dec955df361bfdc3ab74477f847a1b72e8735ef65a8a6d1680745c0152bfb5cbb2a4b4671491a253a1a09d5a07d55f4872c9f0a3d25e07b257926629d5bb96aed96f5debab02503eb0ac45033323cc5a46c8e5d4469ee9f3dd618a20d54d6f5740c010fe5a0fe853efeb253a6df196bd24469ac51c1be8ba84737cecdb5ab73d7c52570d2273621fb69bd7ed985bbc6999dbd2d6fd2687ae44a391d604ff232cc6b3fbedd5d1cd0cd8c658c5d56069b5a5099cc5c9f48bbf7d7e83b4f9a7bdef6eabd164c8395468f818e8cd8c1c800bc3adfd66dbcb247d1bda5a7af38c288c0beb8e0d9160bf67500094530a0f8be52e97b5c2114f5a4a333a11c7f37f4c47a437422455d8cbcfa770cdc85ec55accf48cf14550b07f1346a02fccdf280fcb24c1fb38751d889a17e"
+					},
+					{
+						"commit": "08de9e42d361cabd99e566c67f7f8599c7e6985cd285a841277f1aeb89ad6c8fe3",
+						"features": "Plain",
+						"proof": "5eb7afa00e9681e3b6425fb4256c96905303505787d6a065e88a50154410b9a371b0f879d3f97cfa00425e9c8266e180188656acdbb46cacfdfb159fb135c5eb03b08be3c231c4b21df777da2e2afe8d30db91e602dc4ceed71aeb1b45a0266cfeadc4acbf9fdf7a67f67408fbbea7bf14182bc407373d243c6875373b655695604deb575369a9b28274885601b338882219c7f508aa2a0ae1d02736af2249327145f1d3d00093f9587f0e0b408692700fac0f2a048c329e81cabaa4b997dd88923fe97420125f394e21b4835e36cce9de383d9e223df1b5a6ba6f48ffeac315991189dc2716cc7ec07f6ccc8062344d5ed4fcaddf9070f44f0c59ffe8160d1f6fdfe42b40066f51e687d38b6b5255771800ac060bd8034cd68d14eee1b2f43b6d7bf20d71549ea9a50006dd30b9a795e785385801546eb9a83721a09fc34d3b69d4ccdc0ff0fb74d224048aeb66ecff5515296cadd57f42e0717cbba7c70719a10c007db4520e868efe98a51001b67952d7bda3174195a3d76b93ee4dac60137a38b2e8309cad13ef1cfb6c467f1969385e5b334b52f4fd55da440e036d2a428e9f3be905d79f717c169060468acc6d469636fed098b1aba5cd055a120314bcab55d5b8b6889321edf373517e93ef67fbe74557ec6c0211265efefa25a34ac267cf1db891c47163bfed20d2b535abfe60390c2844dcef5f0aad5fa7f1db9f726d7f223c025861069603936a22377707cdd3915e762e7061132124c716212b0e91bb7fc5d7816366f5d169d93fe75669a6ba19057bb2450958aa6f5ada09042570f46215af5a41b623d140be574b7a8c9ab24ea48da416dbe6ec0fa3b889206fb804df8d69805ceb80f1e9d4e8b664b3939491cba946d87585c830e3dab0638fa279b5e911642f18452e2731764aa62f92bbcf194c97f344c90c1931fd2c3af4bcf6b0"
+					}
+					]
+				},
+				"offset": "0eb2c2669ce918675c72697891e5527bd13da5a499396381409219b8bbbd8129"
+				},
+				"tx_at": "2019-10-07T16:20:08.709114Z"
+			}
+			]
+		}
+	}
+	# "#
+	# );
+	```
+	 */
+	fn get_unconfirmed_transactions(&self) -> Result<Vec<PoolEntry>, ErrorKind>;
+
+	/**
+	Networked version of [Foreign::get_fee_estimate](struct.Foreign.html#method.get_fee_estimate).
+
+	# Json rpc example
+
+	```
+	# grin_api::doctest_helper_json_rpc_foreign_assert_response!(
+	# r#"
+	{
+		"jsonrpc": "2.0",
+		"method": "get_fee_estimate",
+		"params": [3],
+		"id": 1
+	}
+	# "#
+	# ,
+	# r#"
+	{
+		"id": 1,
+		"jsonrpc": "2.0",
+		"result": {
+			"Ok": {
+			"blocks_target": 3,
+			"fee_base": 500000,
+			"min_fee_base": 500000,
+			"pool_weight": 0,
+			"blocks_sampled": 0
+			}
+		}
+	}
+	# "#
+	# );
+	```
+	*/
+	fn get_fee_estimate(&self, blocks_target: u64) -> Result<FeeEstimate, ErrorKind>;
+
+	/**
+	Networked version of [Foreign::push_transaction](struct.Foreign.html#method.push_transaction).
+
+	# Json rpc example
+
+	```
+	# grin_api::doctest_helper_json_rpc_foreign_assert_response!(
+	# r#"
+	{
+		"jsonrpc": "2.0",
+		"method": "push_transaction",
 		"params": [ {
 		"body": {
 				"inputs": [
@@ -862,6 +1338,172 @@ pub trait ForeignRpc: Sync + Send {
 	 */
 	fn push_transaction(&self, tx: Transaction, fluff: Option<bool>) -> Result<(), ErrorKind>;
 
+	/**
+	Networked version of [Foreign::get_coinbase_merkle_proof](struct.Foreign.html#method.get_coinbase_merkle_proof).
+
+	Returns the merkle proof of the unspent coinbase output against the output root of the block that includes it.
+
+	# Json rpc example
+
+	```
+	# grin_api::doctest_helper_json_rpc_foreign_assert_response!(
+	# r#"
+	{
+		"jsonrpc": "2.0",
+		"method": "get_coinbase_merkle_proof",
+		"params": ["09d33615563ba2d65acc2b295a024337166b9f520122d49730c73e8bfb43017610"],
+		"id": 1
+	}
+	# "#
+	# ,
+	# r#"
+	{
+		"id": 1,
+		"jsonrpc": "2.0",
+		"result": {
+			"Ok": {
+			"commit": "09d33615563ba2d65acc2b295a024337166b9f520122d49730c73e8bfb43017610",
+			"block_hash": "000001e16cb374e38c979c353a0aaffbf5b939da7688f69ad99efda6c112ea9b",
+			"block_height": 374274,
+			"maturity_height": 375714,
+			"output_root": "1e9daee31b80c6b83573eacfd3048a4af57c614bd36f9acd5fb50fbd236beb16",
+			"mmr_index": 4091742,
+			"merkle_proof": "00000000003e6f5e000000000000000f60fe09a7601a519d9be71135404580ad9de0964c70a7619b1731dca2cd8c1ae1dce9f544df671d63ff0e05b58f070cb48e163ca8f44fb4446c9fe1fc9cfef90e4b81e7119e8cf60acb9515363ecaea1ce20d2a8ea2f6f638f79a33a19d0d7b54cfff3daf8d21c243ba4ccd2c0fbda833edfa2506b1b326059d124e0c2e27cda90268e66f2dcc7576efac9ebbb831894d7776c191671c3294c2ca0af23201498a7f5ce98d5440ca24116b40ac98b1c5e38b28c8b560afc4f4684b81ab34f8cf162201040d4779195ba0e4967d1dd8184b579208e9ebebafa2f5004c51f5902a94bf268fd498f0247e8ba1a46efec8d88fa44d5ecb206fbe728ee56c24af36442eba416ea4d06e1ea267309bc2e6f961c57069e2525d17e78748254729d7fdec56720aa85fe6d89b2756a7eeed0a7aa5d13cfb874e3c65576ec8a15d6df17d7d4856653696b10fb9ec205f5e4d1c7a1f3e2dd2994b12eeed93e84776d8dcd8a5d78aecd4f96ae95c0b090d104adf2aa84f0a1fbd8d319fea5476d1a306b2800716e60b00115a5cca678617361c5a89660b4536c56254bc8dd7035d96f05de62b042d16acaeff57c111fdf243b859984063e3fcfdf40c4c4a52889706857a7c3e90e264f30f40cc87bd20e74689f14284bc5ea0a540950dfcc8d33c503477eb1c60"
+			}
+		}
+	}
+	# "#
+	# );
+	```
+	 */
+	fn get_coinbase_merkle_proof(&self, commit: String) -> Result<CoinbaseMerkleProof, ErrorKind>;
+
+	/**
+	Networked version of [Foreign::verify_coinbase_merkle_proof](struct.Foreign.html#method.verify_coinbase_merkle_proof).
+
+	Verifies the coinbase merkle proof against the block on the current chain.
+	 */
+	fn verify_coinbase_merkle_proof(&self, proof: CoinbaseMerkleProof) -> Result<bool, ErrorKind>;
+
+	/**
+	Networked version of [Foreign::get_kernel_merkle_proof](struct.Foreign.html#method.get_kernel_merkle_proof).
+
+	Returns the merkle proof of the kernel at the kernel MMR index against the kernel root of the block at the given height.
+
+	# Json rpc example
+
+	```
+	# grin_api::doctest_helper_json_rpc_foreign_assert_response!(
+	# r#"
+	{
+		"jsonrpc": "2.0",
+		"method": "get_kernel_merkle_proof",
+		"params": [4091737, 374274],
+		"id": 1
+	}
+	# "#
+	# ,
+	# r#"
+	{
+		"id": 1,
+		"jsonrpc": "2.0",
+		"result": {
+			"Ok": {
+			"kernel": {
+				"excess": "08761e9cb1eea5bfcf771d1218b5ec802798d6eecaf75faae50ba3a1997aaef009",
+				"excess_sig": "971317046c533d21dff3e449cc9380c2be10b0274f70e009aa2453f755239e3299883c09a1785b15a141d89d563cdd59395886c7d63aba9c2b6438575555e2c4",
+				"features": "Coinbase"
+			},
+			"block_hash": "000001e16cb374e38c979c353a0aaffbf5b939da7688f69ad99efda6c112ea9b",
+			"block_height": 374274,
+			"kernel_root": "e17920c0e456a6feebf19e24a46f510a85f21cb60e81012f843c00fe2c4cad6e",
+			"mmr_index": 4091737,
+			"merkle_proof": "00000000003e6f5900000000000000106a4bc1d8c3f1e2b7aa09c7d0fbe3a2c9d1e8f7b6c5d4a3b2e1f0a9b8c7d6e5f43b8e1c7d92a0f4e6b5c3d1a9f8e7d6c5b4a39281706f5e4d3c2b1a09f8e7d6c5b4a3928170f1e2d3c4b5a69788796a5b4c3d2e1f0a9b8c7d6e5f4a3b2c1d0e9f8a7b6c5d4e3f2a1b0c9d8e7f6a5b4c3d2e1f0a9b8c7d6e5f4a3b2c1d0e9f8a7b6c5d4e3f2a1b0c9d8e7f6a5b4c3d2e1f0a9b8c7d6e5f4a3b2c1d0e9f8a7b6c5d4e3f2a1b0c9d8e7f6a5b4c3d2e1f0a9b8c7d6e5f4a3b2c1d0e9f8a7b6c5d4e3f2a1b0c9d8e7f6a5b4c3d2e1f0"
+			}
+		}
+	}
+	# "#
+	# );
+	```
+	 */
+	fn get_kernel_merkle_proof(
+		&self,
+		mmr_index: u64,
+		height: u64,
+	) -> Result<KernelMerkleProof, ErrorKind>;
+
+	/**
+	Networked version of [Foreign::verify_kernel_merkle_proof](struct.Foreign.html#method.verify_kernel_merkle_proof).
+
+	Verifies the kernel merkle proof against the block on the current chain.
+	 */
+	fn verify_kernel_merkle_proof(&self, proof: KernelMerkleProof) -> Result<bool, ErrorKind>;
+
+	/**
+	Networked version of [Foreign::decode_tx](struct.Foreign.html#method.decode_tx).
+
+	Decodes hex encoded binary transaction and returns its structured view
+	(inputs, outputs, kernels, fees, weights). Transaction is not submitted to the pool.
+
+	# Json rpc example
+
+	```
+	# grin_api::doctest_helper_json_rpc_foreign_assert_response!(
+	# r#"
+	{
+		"jsonrpc": "2.0",
+		"method": "decode_tx",
+		"params": ["d202964900000000d302964900000000d402964900000000d5029649000000000000000000000001000000000000000100000000000000010008e1da9e6dc4d6e808a718b2f110a1f64f3b5ba1e1e6e8f3c3f1b8b54a0bc2bd1c00094be57c91787fc2033d5d97fae099f1a6ddb37ea48370f1a138f09524c767fdd300000000000002a3b80d5ece853e9c3b62dbf0f508538e78f4f24d68953fd155b444b416886818e2769545fe9ea4ae0f3224861da675aae4e5ef514a5d72b9a33ca125a94f0d3d1589b4a874c027df89b318debcd5b4013b7183f3c8a0308a9e2ee9d2d83422c4c9fe3c072a6085d671ac465a697fad34c92c013f9f7dd10e5c6e6d2f77f3a897a715124ae53a8779ae2ac8360402283c22ae8f2a0fc1e53ba0236c938d20ad606a5720e9376b6d1bd2d181487bc47be7508f2766c4df8c3025827075d3e37663fef467b2aeb5f3c53d7b5ea5a7c73cf025b6ef83d79286a4318bf5d5ea260e64ca257648ef07ef669271623e0c8f82a39a6751f251cfe3ebfa7fdba9ede1de31c75db260f0e7e4773759cf520acebe0cdf6cc478100a094927d8e93c409b848e4bee13ee037d54cd7588c20bf3164a18525c7a673fe7cc6c84de34944f212859e3c287a1178549a5aea14690de5466bd6b51ea9995d46fd5d40582ccad13a7b4fb7b3a58bc65a063940b23b0ebe44543d2129920bf3ab74e7e6b290da53f9f0626a127c205ac6f648530e8f60dafb43348f5aa7f78d4e0ff9314330182a02bb5d67a4cce2ccb3f67e1fab7cd5311bd5fbd08bdd7feb47a7c3e944adbe720be979bce5452f6dd1bcd7b10efb502cbedfd19416c7d2b34281ffa203f32e74bfbf9e2d355f7df712c251c67128087395e0266705269b984bab1bb8ec52ac510d7c760e294caf211c5ca4a1b32aa0b673c418a7f0a4236c29aa85023ccff936cb1cc7a393f951d04bfc00fa4acc6e306443dcca4556524ce298f0105426d76759f6ffe092b20d0f6fd9d4d3e063b97bff414cfd8866b399db6a28118c51ffbb0311629e6216dd793051c3c8b69ab9c72aff3199efdcf6aa7df19968d5142d80bece9555e9514706ac50cf5ee91d0fb16028f02bf01d1f4c06b7a4c799c60ab70a1a888e138b90000000000006acfc008b3b8b83c622f630141a66c9cad96e19c78f745e4e2ddea85439f05d14a40464066074d25a751c4743342c90ad8ead9454daa00d9b9aed29bca321036d16c4b4d1f1ac30ec6809c5e1a983a83af0deb0635b892e5e0ea3a3bd7f68be99f721348"],
+		"id": 1
+	}
+	# "#
+	# ,
+	# r#"
+	{
+		"id": 1,
+		"jsonrpc": "2.0",
+		"result": {
+			"Ok": {
+			"hash": "c3b9f0aa2a40c5ba15a6c5d88f8a3e5c1e0a7a34d1a7f2ad1b5c2d0e9f8a7b6c",
+			"offset": "d202964900000000d302964900000000d402964900000000d502964900000000",
+			"inputs": [
+				{
+					"commit": "08e1da9e6dc4d6e808a718b2f110a1f64f3b5ba1e1e6e8f3c3f1b8b54a0bc2bd1c",
+					"output_type": "Transaction"
+				}
+			],
+			"outputs": [
+				{
+					"output_type": "Transaction",
+					"commit": "094be57c91787fc2033d5d97fae099f1a6ddb37ea48370f1a138f09524c767fdd3",
+					"proof_hash": "7c58e1d6a7a4f3b2c9e0d1f8a6b5c4d3e2f1a0b9c8d7e6f5a4b3c2d1e0f9a8b7"
+				}
+			],
+			"kernels": [
+				{
+					"features": "Plain",
+					"fee": 7000000,
+					"lock_height": 0,
+					"excess": "08b3b8b83c622f630141a66c9cad96e19c78f745e4e2ddea85439f05d14a404640",
+					"excess_sig": "66074d25a751c4743342c90ad8ead9454daa00d9b9aed29bca321036d16c4b4d1f1ac30ec6809c5e1a983a83af0deb0635b892e5e0ea3a3bd7f68be99f721348"
+				}
+			],
+			"fee": 7000000,
+			"weight": 46,
+			"weight_as_block": 29,
+			"fee_to_weight": 152173,
+			"lock_height": 0,
+			"protocol_version": 2,
+			"validation_error": null
+			}
+		}
+	}
+	# "#
+	# );
+	```
+	 */
+	fn decode_tx(&self, tx_hex: String) -> Result<DecodedTransaction, ErrorKind>;
+
 	/**
 	Networked version of [Owner::get_libp2p_peers](struct.Owner.html#method.get_libp2p_peers).
 
@@ -901,6 +1543,93 @@ pub trait ForeignRpc: Sync + Send {
 		// No example because if current time dynamic nature.
 	*/
 	fn get_libp2p_messages(&self) -> Result<Libp2pMessages, ErrorKind>;
+
+	/**
+	Networked version of [Foreign::post_mailbox_message](struct.Foreign.html#method.post_mailbox_message).
+
+	# Json rpc example
+
+	```
+	# grin_api::doctest_helper_json_rpc_foreign_assert_response!(
+	# r#"
+	{
+		"jsonrpc": "2.0",
+		"method": "post_mailbox_message",
+		"params": ["0002c1d3f39e63a2b1e8e8ef1b0f1ab7c8a6e5f43b2d9c1e0f7a6b5c4d3e2f1a0b9c8d7e6f5a4b3c2d1e0f9a8b7c6d5e4f3a2b1c0d9e8f7a6b5c4d3e2f1a08d2b"],
+		"id": 1
+	}
+	# "#
+	# ,
+	# r#"
+	{
+		"id": 1,
+		"jsonrpc": "2.0",
+		"result": {
+			"Ok": {
+				"id": "a3f2c9d1e6b84f7a0c5d2e9b1f4a7c3d8e0b6f2a9c4d7e1b5f8a3c6d0e9b2f4a",
+				"sender": "fqm7xtnpqnv2hmgyhfdkk6enp3xkpenoktiuyi7ttujqjuyqnhv2t7ad",
+				"recipient": "xmgkz7hnnbuqxsacurhpqgbvghflkwssxfl3lyujkm6d6ufbbljhvhid",
+				"fee": 10000000,
+				"timestamp": 1602769481,
+				"message": "0003c1d3f39e63a2b1e8e8ef1b0f1ab7c8a6e5f43b2d9c1e0f7a6b5c4d3e2f1a0b9c8d7e6f5a4b3c2d1e0f9a8b7c6d5e4f3a2b1c0d9e8f7a6b5c4d3e2f1a0"
+			}
+		}
+	}
+	# "#
+	# );
+	```
+	*/
+	fn post_mailbox_message(&self, message: String) -> Result<MailboxMessage, ErrorKind>;
+
+	/**
+	Networked version of [Foreign::get_mailbox_messages](struct.Foreign.html#method.get_mailbox_messages).
+
+	# Json rpc example
+
+	```
+	# grin_api::doctest_helper_json_rpc_foreign_assert_response!(
+	# r#"
+	{
+		"jsonrpc": "2.0",
+		"method": "get_mailbox_messages",
+		"params": [
+			"xmgkz7hnnbuqxsacurhpqgbvghflkwssxfl3lyujkm6d6ufbbljhvhid",
+			1602760000,
+			1602769500,
+			"5b8f0c3a9e2d7f41b6c0a8e3d5f29b7c41e6a0d8f3b5c27e9a1d4f6b08c3e5a7d2f9b1c4e6a8d0f3b5c7e9a2d4f6b8c0e1a3d5f7b9c2e4a6d8f0b3c5e7a9d1f"
+		],
+		"id": 1
+	}
+	# "#
+	# ,
+	# r#"
+	{
+		"id": 1,
+		"jsonrpc": "2.0",
+		"result": {
+			"Ok": [
+				{
+					"id": "a3f2c9d1e6b84f7a0c5d2e9b1f4a7c3d8e0b6f2a9c4d7e1b5f8a3c6d0e9b2f4a",
+					"sender": "fqm7xtnpqnv2hmgyhfdkk6enp3xkpenoktiuyi7ttujqjuyqnhv2t7ad",
+					"recipient": "xmgkz7hnnbuqxsacurhpqgbvghflkwssxfl3lyujkm6d6ufbbljhvhid",
+					"fee": 10000000,
+					"timestamp": 1602769481,
+					"message": "0003c1d3f39e63a2b1e8e8ef1b0f1ab7c8a6e5f43b2d9c1e0f7a6b5c4d3e2f1a0b9c8d7e6f5a4b3c2d1e0f9a8b7c6d5e4f3a2b1c0d9e8f7a6b5c4d3e2f1a0"
+				}
+			]
+		}
+	}
+	# "#
+	# );
+	```
+	*/
+	fn get_mailbox_messages(
+		&self,
+		recipient: String,
+		since: Option<i64>,
+		timestamp: i64,
+		signature: String,
+	) -> Result<Vec<MailboxMessage>, ErrorKind>;
 }
 
 impl<B, P, V> ForeignRpc for Foreign<B, P, V>
@@ -957,6 +1686,41 @@ where
 		.map_err(|e| e.kind().clone())
 	}
 
+	fn get_blocks(
+		&self,
+		start_height: u64,
+		end_height: Option<u64>,
+		max: u64,
+		include_proof: Option<bool>,
+	) -> Result<BlockListing, ErrorKind> {
+		Foreign::get_blocks(self, start_height, end_height, max, include_proof)
+			.map_err(|e| e.kind().clone())
+	}
+
+	fn get_block_first_seen(
+		&self,
+		height: Option<u64>,
+		hash: Option<String>,
+		commit: Option<String>,
+	) -> Result<Option<DateTime<Utc>>, ErrorKind> {
+		let mut parsed_hash: Option<Hash> = None;
+		if let Some(hash) = hash {
+			let vec = util::from_hex(&hash)
+				.map_err(|e| ErrorKind::Argument(format!("invalid block hash: {}", e)))?;
+			parsed_hash = Some(Hash::from_vec(&vec));
+		}
+		Foreign::get_block_first_seen(self, height, parsed_hash, commit)
+			.map_err(|e| e.kind().clone())
+	}
+
+	fn get_kernel_status(&self, excess: String) -> Result<KernelStatus, ErrorKind> {
+		Foreign::get_kernel_status(self, excess).map_err(|e| e.kind().clone())
+	}
+
+	fn get_tx_first_seen(&self, excess: String) -> Result<Option<DateTime<Utc>>, ErrorKind> {
+		Foreign::get_tx_first_seen(self, excess).map_err(|e| e.kind().clone())
+	}
+
 	fn get_version(&self) -> Result<Version, ErrorKind> {
 		Foreign::get_version(self).map_err(|e| e.kind().clone())
 	}
@@ -965,6 +1729,10 @@ where
 		Foreign::get_tip(self).map_err(|e| e.kind().clone())
 	}
 
+	fn get_chain_stats(&self, height: u64) -> Result<ChainStats, ErrorKind> {
+		Foreign::get_chain_stats(self, height).map_err(|e| e.kind().clone())
+	}
+
 	fn get_kernel(
 		&self,
 		excess: String,
@@ -1004,6 +1772,39 @@ where
 			.map_err(|e| e.kind().clone())
 	}
 
+	fn get_unspent_outputs_cursor(
+		&self,
+		cursor: Option<String>,
+		max: u64,
+		include_proof: Option<bool>,
+	) -> Result<OutputCursorListing, ErrorKind> {
+		Foreign::get_unspent_outputs_cursor(self, cursor, max, include_proof)
+			.map_err(|e| e.kind().clone())
+	}
+
+	fn get_historical_output(
+		&self,
+		mmr_index: u64,
+		include_proof: Option<bool>,
+	) -> Result<OutputPrintable, ErrorKind> {
+		Foreign::get_historical_output(self, mmr_index, include_proof).map_err(|e| e.kind().clone())
+	}
+
+	fn get_outputs_in_range(
+		&self,
+		start_height: u64,
+		end_height: u64,
+		include_proof: Option<bool>,
+		include_spent: Option<bool>,
+	) -> Result<OutputRangeListing, ErrorKind> {
+		Foreign::get_outputs_in_range(self, start_height, end_height, include_proof, include_spent)
+			.map_err(|e| e.kind().clone())
+	}
+
+	fn get_output_height(&self, commit: String) -> Result<Option<u64>, ErrorKind> {
+		Foreign::get_output_height(self, commit).map_err(|e| e.kind().clone())
+	}
+
 	fn get_pmmr_indices(
 		&self,
 		start_block_height: u64,
@@ -1024,10 +1825,38 @@ where
 	fn get_unconfirmed_transactions(&self) -> Result<Vec<PoolEntry>, ErrorKind> {
 		Foreign::get_unconfirmed_transactions(self).map_err(|e| e.kind().clone())
 	}
+
+	fn get_fee_estimate(&self, blocks_target: u64) -> Result<FeeEstimate, ErrorKind> {
+		Foreign::get_fee_estimate(self, blocks_target).map_err(|e| e.kind().clone())
+	}
 	fn push_transaction(&self, tx: Transaction, fluff: Option<bool>) -> Result<(), ErrorKind> {
 		Foreign::push_transaction(self, tx, fluff).map_err(|e| e.kind().clone())
 	}
 
+	fn get_coinbase_merkle_proof(&self, commit: String) -> Result<CoinbaseMerkleProof, ErrorKind> {
+		Foreign::get_coinbase_merkle_proof(self, commit).map_err(|e| e.kind().clone())
+	}
+
+	fn verify_coinbase_merkle_proof(&self, proof: CoinbaseMerkleProof) -> Result<bool, ErrorKind> {
+		Foreign::verify_coinbase_merkle_proof(self, proof).map_err(|e| e.kind().clone())
+	}
+
+	fn get_kernel_merkle_proof(
+		&self,
+		mmr_index: u64,
+		height: u64,
+	) -> Result<KernelMerkleProof, ErrorKind> {
+		Foreign::get_kernel_merkle_proof(self, mmr_index, height).map_err(|e| e.kind().clone())
+	}
+
+	fn verify_kernel_merkle_proof(&self, proof: KernelMerkleProof) -> Result<bool, ErrorKind> {
+		Foreign::verify_kernel_merkle_proof(self, proof).map_err(|e| e.kind().clone())
+	}
+
+	fn decode_tx(&self, tx_hex: String) -> Result<DecodedTransaction, ErrorKind> {
+		Foreign::decode_tx(self, tx_hex).map_err(|e| e.kind().clone())
+	}
+
 	fn get_libp2p_peers(&self) -> Result<Libp2pPeers, ErrorKind> {
 		Foreign::get_libp2p_peers(self).map_err(|e| e.kind().clone())
 	}
@@ -1035,6 +1864,39 @@ where
 	fn get_libp2p_messages(&self) -> Result<Libp2pMessages, ErrorKind> {
 		Foreign::get_libp2p_messages(self).map_err(|e| e.kind().clone())
 	}
+
+	fn post_mailbox_message(&self, message: String) -> Result<MailboxMessage, ErrorKind> {
+		Foreign::post_mailbox_message(self, message).map_err(|e| e.kind().clone())
+	}
+
+	fn get_mailbox_messages(
+		&self,
+		recipient: String,
+		since: Option<i64>,
+		timestamp: i64,
+		signature: String,
+	) -> Result<Vec<MailboxMessage>, ErrorKind> {
+		Foreign::get_mailbox_messages(self, recipient, since, timestamp, signature)
+			.map_err(|e| e.kind().clone())
+	}
+}
+
+/// Process json-rpc request with the Foreign API. Used to serve the API over non HTTP
+/// transports (libp2p request-response).
+pub fn foreign_rpc_request<B, P, V>(
+	api: &Foreign<B, P, V>,
+	request: serde_json::Value,
+) -> serde_json::Value
+where
+	B: BlockChain,
+	P: PoolAdapter,
+	V: VerifierCache + 'static,
+{
+	let foreign_api = api as &dyn ForeignRpc;
+	match foreign_api.handle_request(request) {
+		MaybeReply::Reply(r) => r,
+		MaybeReply::DontReply => serde_json::json!([]),
+	}
 }
 
 #[doc(hidden)]
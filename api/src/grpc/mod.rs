@@ -0,0 +1,436 @@
+// Copyright 2021 The MWC Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! gRPC interface for the subset of the foreign API: tip, blocks, outputs, transaction push
+//! and the stream of the accepted blocks. Built with the `grpc` feature, protobuf definitions
+//! are in `foreign.proto`. Calls are served by the same `Foreign` API as JSON-RPC, the block
+//! stream shares the WebSocket events. The foreign API secret, the TLS certificate and the
+//! rate limits of the REST API apply to the gRPC calls as well.
+
+use crate::auth::get_foreign_api_credentials;
+use crate::chain::SyncState;
+use crate::core::core::hash::Hash;
+use crate::core::core::transaction::Transaction;
+use crate::core::core::verifier_cache::VerifierCache;
+use crate::core::ser::{self, ProtocolVersion};
+use crate::foreign::Foreign;
+use crate::pool::{self, BlockChain, PoolAdapter};
+use crate::rate_limit::{get_api_rate_limiter, RateLimited, HEAVY_FOREIGN_METHODS};
+use crate::rest::{Error, ErrorKind, TLSConfig};
+use crate::types::{
+	BlockHeaderPrintable, BlockPrintable, OutputPrintable, OutputType, Tip, TxKernelPrintable,
+};
+use crate::util::{self, RwLock};
+use crate::ws::{self, WS_TOPIC_BLOCK_ACCEPTED};
+use chrono::Utc;
+use std::fs;
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use tokio::runtime::Handle;
+use tokio::sync::{broadcast, mpsc};
+use tonic::transport::{Identity, Server, ServerTlsConfig};
+use tonic::{Request, Response, Status};
+
+/// Generated protobuf messages and the service traits
+pub mod proto {
+	tonic::include_proto!("mwc.foreign");
+}
+
+use self::proto::foreign_server::{Foreign as ForeignService, ForeignServer};
+
+/// Buffered block notifications per subscriber
+const BLOCK_NOTIFICATIONS_BUFFER: usize = 64;
+
+/// Max number of the block stream subscribers
+const MAX_BLOCK_SUBSCRIBERS: usize = 100;
+
+/// Foreign API served over gRPC
+pub struct ForeignGrpc<B, P, V>
+where
+	B: BlockChain,
+	P: PoolAdapter,
+	V: VerifierCache + 'static,
+{
+	foreign: Arc<Foreign<B, P, V>>,
+	subscribers: Arc<AtomicUsize>,
+}
+
+impl<B, P, V> ForeignGrpc<B, P, V>
+where
+	B: BlockChain,
+	P: PoolAdapter,
+	V: VerifierCache + 'static,
+{
+	/// Create the service on top of the foreign API
+	pub fn new(foreign: Foreign<B, P, V>) -> Self {
+		ForeignGrpc {
+			foreign: Arc::new(foreign),
+			subscribers: Arc::new(AtomicUsize::new(0)),
+		}
+	}
+}
+
+// Releases the block stream subscriber slot when the stream is gone
+struct SubscriberSlot(Arc<AtomicUsize>);
+
+impl Drop for SubscriberSlot {
+	fn drop(&mut self) {
+		self.0.fetch_sub(1, Ordering::SeqCst);
+	}
+}
+
+// Foreign API secret and the rate limits, the same as for the json-rpc method
+fn check_request<T>(request: &Request<T>, method: &str) -> Result<(), Status> {
+	if let Some(credentials) = get_foreign_api_credentials() {
+		let authorized = request
+			.metadata()
+			.get("authorization")
+			.map(|auth| credentials.verify(auth.as_bytes(), Utc::now().timestamp()))
+			.unwrap_or(false);
+		if !authorized {
+			return Err(Status::unauthenticated("invalid or missing credentials"));
+		}
+	}
+	if let (Some(limiter), Some(addr)) = (get_api_rate_limiter(), request.remote_addr()) {
+		let heavy = HEAVY_FOREIGN_METHODS.contains(&method) as u32;
+		let res = limiter
+			.check(addr.ip())
+			.and_then(|_| limiter.check_heavy(addr.ip(), heavy));
+		match res {
+			Ok(_) => {}
+			Err(RateLimited::RetryAfter(secs)) => {
+				return Err(Status::resource_exhausted(format!(
+					"too many requests, retry after {} seconds",
+					secs
+				)))
+			}
+			Err(RateLimited::TooLarge) => {
+				return Err(Status::resource_exhausted(
+					"request is over the rate limit budget",
+				))
+			}
+		}
+	}
+	Ok(())
+}
+
+// Chain and pool calls block on the locks and the db, they don't run on the executor
+async fn blocking<T, F>(f: F) -> Result<T, Status>
+where
+	F: FnOnce() -> Result<T, Error> + Send + 'static,
+	T: Send + 'static,
+{
+	tokio::task::spawn_blocking(f)
+		.await
+		.map_err(|e| Status::internal(format!("gRPC call failed, {}", e)))?
+		.map_err(to_status)
+}
+
+fn to_status(e: Error) -> Status {
+	match e.kind() {
+		ErrorKind::NotFound(msg) => Status::not_found(msg.clone()),
+		ErrorKind::Argument(msg) | ErrorKind::RequestError(msg) => {
+			Status::invalid_argument(msg.clone())
+		}
+		_ => Status::internal(format!("{}", e)),
+	}
+}
+
+fn decode_tx(tx_hex: &str) -> Result<Transaction, Status> {
+	let tx_bin = util::from_hex(tx_hex)
+		.map_err(|e| Status::invalid_argument(format!("Invalid transaction hex, {}", e)))?;
+	// All wallet api interaction explicitly uses protocol version 1 for now.
+	ser::deserialize(&mut &tx_bin[..], ProtocolVersion(1))
+		.map_err(|e| Status::invalid_argument(format!("Unable to deserialize transaction, {}", e)))
+}
+
+impl From<Tip> for proto::Tip {
+	fn from(tip: Tip) -> Self {
+		proto::Tip {
+			height: tip.height,
+			last_block_pushed: tip.last_block_pushed,
+			prev_block_to_last: tip.prev_block_to_last,
+			total_difficulty: tip.total_difficulty,
+		}
+	}
+}
+
+impl From<BlockHeaderPrintable> for proto::BlockHeader {
+	fn from(h: BlockHeaderPrintable) -> Self {
+		proto::BlockHeader {
+			hash: h.hash,
+			version: h.version as u32,
+			height: h.height,
+			previous: h.previous,
+			prev_root: h.prev_root,
+			timestamp: h.timestamp,
+			output_root: h.output_root,
+			output_mmr_size: h.output_mmr_size,
+			range_proof_root: h.range_proof_root,
+			kernel_root: h.kernel_root,
+			kernel_mmr_size: h.kernel_mmr_size,
+			nonce: h.nonce,
+			edge_bits: h.edge_bits as u32,
+			cuckoo_solution: h.cuckoo_solution,
+			total_difficulty: h.total_difficulty,
+			secondary_scaling: h.secondary_scaling,
+			total_kernel_offset: h.total_kernel_offset,
+		}
+	}
+}
+
+impl From<OutputPrintable> for proto::Output {
+	fn from(o: OutputPrintable) -> Self {
+		let output_type = match o.output_type {
+			OutputType::Coinbase => "Coinbase",
+			OutputType::Transaction => "Transaction",
+		};
+		proto::Output {
+			output_type: output_type.to_string(),
+			commit: util::to_hex(&o.commit.0),
+			spent: o.spent,
+			proof: o.proof,
+			proof_hash: o.proof_hash,
+			block_height: o.block_height,
+			merkle_proof: o.merkle_proof.map(|p| p.to_hex()),
+			mmr_index: o.mmr_index,
+		}
+	}
+}
+
+impl From<TxKernelPrintable> for proto::Kernel {
+	fn from(k: TxKernelPrintable) -> Self {
+		proto::Kernel {
+			features: k.features,
+			fee: k.fee,
+			lock_height: k.lock_height,
+			excess: k.excess,
+			excess_sig: k.excess_sig,
+		}
+	}
+}
+
+impl From<BlockPrintable> for proto::Block {
+	fn from(b: BlockPrintable) -> Self {
+		proto::Block {
+			header: Some(b.header.into()),
+			inputs: b.inputs,
+			outputs: b.outputs.into_iter().map(|o| o.into()).collect(),
+			kernels: b.kernels.into_iter().map(|k| k.into()).collect(),
+		}
+	}
+}
+
+// Accepted block event, as published by the chain hooks
+#[derive(Deserialize)]
+struct BlockAcceptedEvent {
+	hash: String,
+	height: u64,
+	prev_hash: String,
+	status: String,
+}
+
+impl From<BlockAcceptedEvent> for proto::BlockNotification {
+	fn from(e: BlockAcceptedEvent) -> Self {
+		proto::BlockNotification {
+			hash: e.hash,
+			height: e.height,
+			prev_hash: e.prev_hash,
+			status: e.status,
+		}
+	}
+}
+
+#[tonic::async_trait]
+impl<B, P, V> ForeignService for ForeignGrpc<B, P, V>
+where
+	B: BlockChain + 'static,
+	P: PoolAdapter + 'static,
+	V: VerifierCache + 'static,
+{
+	async fn get_tip(
+		&self,
+		request: Request<proto::GetTipRequest>,
+	) -> Result<Response<proto::Tip>, Status> {
+		check_request(&request, "get_tip")?;
+		let foreign = self.foreign.clone();
+		let tip = blocking(move || foreign.get_tip()).await?;
+		Ok(Response::new(tip.into()))
+	}
+
+	async fn get_block(
+		&self,
+		request: Request<proto::GetBlockRequest>,
+	) -> Result<Response<proto::Block>, Status> {
+		check_request(&request, "get_block")?;
+		let request = request.into_inner();
+		let (height, hash, commit) = match request.id {
+			Some(proto::get_block_request::Id::Height(height)) => (Some(height), None, None),
+			Some(proto::get_block_request::Id::Hash(hash)) => {
+				let hash = Hash::from_hex(&hash).map_err(|e| {
+					Status::invalid_argument(format!("Invalid hash {}, {}", hash, e))
+				})?;
+				(None, Some(hash), None)
+			}
+			Some(proto::get_block_request::Id::Commit(commit)) => (None, None, Some(commit)),
+			None => {
+				return Err(Status::invalid_argument(
+					"Block height, hash or commit is required",
+				))
+			}
+		};
+		let foreign = self.foreign.clone();
+		let block = blocking(move || {
+			foreign.get_block(
+				height,
+				hash,
+				commit,
+				Some(request.include_proof),
+				Some(request.include_merkle_proof),
+			)
+		})
+		.await?;
+		Ok(Response::new(block.into()))
+	}
+
+	async fn push_transaction(
+		&self,
+		request: Request<proto::PushTransactionRequest>,
+	) -> Result<Response<proto::PushTransactionResponse>, Status> {
+		check_request(&request, "push_transaction")?;
+		let request = request.into_inner();
+		let tx = decode_tx(&request.tx_hex)?;
+		let foreign = self.foreign.clone();
+		blocking(move || foreign.push_transaction(tx, Some(request.fluff))).await?;
+		Ok(Response::new(proto::PushTransactionResponse {}))
+	}
+
+	async fn get_outputs(
+		&self,
+		request: Request<proto::GetOutputsRequest>,
+	) -> Result<Response<proto::GetOutputsResponse>, Status> {
+		check_request(&request, "get_outputs")?;
+		let request = request.into_inner();
+		let commits = if request.commits.is_empty() {
+			None
+		} else {
+			Some(request.commits)
+		};
+		let foreign = self.foreign.clone();
+		let outputs = blocking(move || {
+			foreign.get_outputs(
+				commits,
+				request.start_height,
+				request.end_height,
+				Some(request.include_proof),
+				Some(request.include_merkle_proof),
+			)
+		})
+		.await?;
+		Ok(Response::new(proto::GetOutputsResponse {
+			outputs: outputs.into_iter().map(|o| o.into()).collect(),
+		}))
+	}
+
+	type SubscribeBlocksStream = mpsc::Receiver<Result<proto::BlockNotification, Status>>;
+
+	async fn subscribe_blocks(
+		&self,
+		request: Request<proto::SubscribeBlocksRequest>,
+	) -> Result<Response<Self::SubscribeBlocksStream>, Status> {
+		check_request(&request, "subscribe_blocks")?;
+		if self.subscribers.fetch_add(1, Ordering::SeqCst) >= MAX_BLOCK_SUBSCRIBERS {
+			self.subscribers.fetch_sub(1, Ordering::SeqCst);
+			return Err(Status::resource_exhausted("too many block subscribers"));
+		}
+		let slot = SubscriberSlot(self.subscribers.clone());
+		let mut events = ws::subscribe_ws_events();
+		let (mut tx, rx) = mpsc::channel(BLOCK_NOTIFICATIONS_BUFFER);
+		tokio::spawn(async move {
+			let _slot = slot;
+			loop {
+				let event = match events.recv().await {
+					Ok(event) => event,
+					Err(broadcast::RecvError::Lagged(skipped)) => {
+						warn!("gRPC block subscriber lagged, {} events skipped", skipped);
+						continue;
+					}
+					Err(broadcast::RecvError::Closed) => break,
+				};
+				if event.topic != WS_TOPIC_BLOCK_ACCEPTED {
+					continue;
+				}
+				let notification = match serde_json::from_value::<BlockAcceptedEvent>(event.data) {
+					Ok(e) => e.into(),
+					Err(e) => {
+						error!("Unable to parse block accepted event, {}", e);
+						continue;
+					}
+				};
+				// Error means that the client is gone
+				if tx.send(Ok(notification)).await.is_err() {
+					break;
+				}
+			}
+		});
+		Ok(Response::new(rx))
+	}
+}
+
+/// Start the gRPC foreign API server at addr as a task on the node runtime. Served over TLS
+/// when the config is provided, the client certificates are not checked.
+pub fn start_grpc_server<B, P, V>(
+	addr: SocketAddr,
+	chain: Arc<crate::chain::Chain>,
+	tx_pool: Arc<RwLock<pool::TransactionPool<B, P, V>>>,
+	peers: Arc<crate::p2p::Peers>,
+	sync_state: Arc<SyncState>,
+	tls_config: Option<TLSConfig>,
+	runtime: Handle,
+) -> Result<(), Error>
+where
+	B: BlockChain + 'static,
+	P: PoolAdapter + 'static,
+	V: VerifierCache + 'static,
+{
+	let foreign = Foreign::new(
+		Arc::downgrade(&peers),
+		Arc::downgrade(&chain),
+		Arc::downgrade(&tx_pool),
+		Arc::downgrade(&sync_state),
+	);
+	let service = ForeignServer::new(ForeignGrpc::new(foreign));
+	let mut builder = Server::builder();
+	if let Some(tls_config) = tls_config {
+		let read = |file: &str| {
+			fs::read(file).map_err(|e| {
+				ErrorKind::Internal(format!("Unable to read gRPC TLS file {}, {}", file, e))
+			})
+		};
+		let identity = Identity::from_pem(
+			read(&tls_config.certificate)?,
+			read(&tls_config.private_key)?,
+		);
+		builder = builder.tls_config(ServerTlsConfig::new().identity(identity));
+	}
+	info!("Starting gRPC foreign API server at {}", addr);
+	let server = builder.add_service(service).serve(addr);
+	runtime.spawn(async move {
+		if let Err(e) = server.await {
+			error!("gRPC API server failed, {}", e);
+		}
+	});
+	Ok(())
+}
@@ -14,6 +14,7 @@
 
 pub mod blocks_api;
 pub mod chain_api;
+pub mod health_api;
 pub mod peers_api;
 pub mod pool_api;
 pub mod server_api;
@@ -28,6 +29,9 @@ use self::chain_api::ChainHandler;
 use self::chain_api::ChainValidationHandler;
 use self::chain_api::KernelHandler;
 use self::chain_api::OutputHandler;
+use self::health_api::{
+	HealthConfig, LivenessHandler, ReadinessHandler, HEALTH_LIVE_URI, HEALTH_READY_URI,
+};
 use self::peers_api::PeerHandler;
 use self::peers_api::PeersAllHandler;
 use self::peers_api::PeersConnectedHandler;
@@ -37,8 +41,14 @@ use self::server_api::IndexHandler;
 use self::server_api::StatusHandler;
 use self::transactions_api::TxHashSetHandler;
 use self::version_api::VersionHandler;
+use crate::audit::{
+	audit_recorded, caller_identity, caller_ip, get_owner_api_audit_log, set_owner_api_audit_log,
+	AuditLog, AuditMiddleware,
+};
 use crate::auth::{
-	BasicAuthMiddleware, BasicAuthURIMiddleware, MWC_BASIC_REALM, MWC_FOREIGN_BASIC_REALM,
+	client_permission, is_read_only_owner_request, set_foreign_api_credentials,
+	set_owner_api_credentials, BasicAuthCredentials, BasicAuthMiddleware, BasicAuthURIMiddleware,
+	ClientCertMiddleware, MWC_BASIC_REALM, MWC_FOREIGN_BASIC_REALM,
 };
 use crate::chain;
 use crate::chain::{Chain, SyncState};
@@ -47,24 +57,31 @@ use crate::core::global;
 use crate::core::stratum;
 use crate::foreign::Foreign;
 use crate::foreign_rpc::ForeignRpc;
-use crate::owner::Owner;
+use crate::json_rpc;
+use crate::owner::{Owner, StemPoolView};
 use crate::owner_rpc::OwnerRpc;
 use crate::p2p;
 use crate::pool;
 use crate::pool::{BlockChain, PoolAdapter};
-use crate::rest::{ApiServer, Error, ErrorKind, TLSConfig};
+use crate::rate_limit::{
+	client_ip, get_api_rate_limiter, heavy_foreign_calls, set_api_rate_limiter, too_many_requests,
+	RateLimitConfig, RateLimitMiddleware, RateLimiter,
+};
+use crate::rest::{ApiServer, ClientPermission, Error, ErrorKind, TLSConfig};
 use crate::router::ResponseFuture;
 use crate::router::{Router, RouterError};
+use crate::spec::ApiSpecHandler;
 use crate::stratum::Stratum;
 use crate::stratum_rpc::StratumRpc;
-use crate::util::to_base64;
 use crate::util::RwLock;
 use crate::web::*;
+use crate::ws::{start_sync_status_watch, WsHandler};
 use easy_jsonrpc_mw::{Handler, MaybeReply};
 use hyper::{Body, Request, Response, StatusCode};
 use serde::Serialize;
 use std::net::SocketAddr;
 use std::sync::{Arc, Weak};
+use tokio::runtime::Handle;
 
 /// Listener version, providing same API but listening for requests on a
 /// port and wrapping the calls
@@ -74,11 +91,15 @@ pub fn node_apis<B, P, V>(
 	tx_pool: Arc<RwLock<pool::TransactionPool<B, P, V>>>,
 	peers: Arc<p2p::Peers>,
 	sync_state: Arc<chain::SyncState>,
-	api_secret: Option<String>,
-	foreign_api_secret: Option<String>,
+	api_secret: Option<(String, String)>,
+	foreign_api_secret: Option<(String, String)>,
 	tls_config: Option<TLSConfig>,
 	allow_to_stop: bool,
 	stratum_ip_pool: Arc<stratum::connections::StratumIpPool>,
+	audit_log_path: Option<String>,
+	rate_limit: Option<RateLimitConfig>,
+	health_config: HealthConfig,
+	runtime: Handle,
 ) -> Result<(), Error>
 where
 	B: BlockChain + 'static,
@@ -104,45 +125,131 @@ where
 		"mwc"
 	};
 
-	// Add basic auth to v1 API and owner v2 API
-	if let Some(api_secret) = api_secret {
-		let api_basic_auth = format!(
-			"Basic {}",
-			to_base64(&format!("{}:{}", basic_auth_key, api_secret))
-		);
+	// Per IP rate limits, the owner API clients are trusted
+	let rate_limiter = rate_limit.map(|config| Arc::new(RateLimiter::new(config)));
+	if let Some(limiter) = rate_limiter.clone() {
+		router.add_middleware(Arc::new(RateLimitMiddleware::new(
+			limiter,
+			vec![
+				"/v2/owner".to_string(),
+				"/v3/owner".to_string(),
+				"/v2/ws".to_string(),
+				"/v1/peers".to_string(),
+				"/v1/status".to_string(),
+				"/v1/chain/compact".to_string(),
+				"/v1/chain/validate".to_string(),
+				HEALTH_LIVE_URI.to_string(),
+				HEALTH_READY_URI.to_string(),
+			],
+		)));
+	}
+	set_api_rate_limiter(rate_limiter);
 
+	// Record the owner API calls into the audit log. The middleware goes before the basic
+	// auth, so the rejected requests are recorded too.
+	let audit_log = match audit_log_path {
+		Some(path) => Some(Arc::new(AuditLog::open(&path)?)),
+		None => None,
+	};
+	if let Some(audit_log) = audit_log.clone() {
+		router.add_middleware(Arc::new(AuditMiddleware::new(
+			audit_log,
+			vec![
+				"/v2/foreign".into(),
+				HEALTH_LIVE_URI.into(),
+				HEALTH_READY_URI.into(),
+			],
+		)));
+	}
+	set_owner_api_audit_log(audit_log);
+
+	// Add basic auth to v1 API and owner v2, v3 APIs
+	let owner_credentials = api_secret.map(|(api_secret, secret_path)| {
+		Arc::new(BasicAuthCredentials::new(
+			basic_auth_key,
+			&api_secret,
+			&secret_path,
+		))
+	});
+	if let Some(credentials) = owner_credentials.clone() {
 		let basic_auth_middleware = Arc::new(BasicAuthMiddleware::new(
-			api_basic_auth,
+			credentials,
 			&MWC_BASIC_REALM,
-			Some("/v2/foreign".into()),
+			vec![
+				"/v2/foreign".into(),
+				HEALTH_LIVE_URI.into(),
+				HEALTH_READY_URI.into(),
+			],
 		));
 		router.add_middleware(basic_auth_middleware);
 	}
+	set_owner_api_credentials(owner_credentials);
+
+	// Clients with the read-only TLS certificate, json-rpc APIs check the methods themselves
+	if tls_config
+		.as_ref()
+		.map(|c| c.client_auth.is_some())
+		.unwrap_or(false)
+	{
+		router.add_middleware(Arc::new(ClientCertMiddleware::new(vec![
+			"/v2/owner".to_string(),
+			"/v3/owner".to_string(),
+			"/v2/foreign".to_string(),
+		])));
+	}
 
+	let stempool_view: Arc<dyn StemPoolView> = tx_pool.clone();
 	let api_handler_v2 = OwnerAPIHandlerV2::new(
 		Arc::downgrade(&chain),
+		Arc::downgrade(&stempool_view),
 		Arc::downgrade(&peers),
 		Arc::downgrade(&sync_state),
 	);
 	router.add_route("/v2/owner", Arc::new(api_handler_v2))?;
+	let api_handler_v3 = OwnerAPIHandlerV3::new(OwnerAPIHandlerV2::new(
+		Arc::downgrade(&chain),
+		Arc::downgrade(&stempool_view),
+		Arc::downgrade(&peers),
+		Arc::downgrade(&sync_state),
+	));
+	router.add_route("/v3/owner", Arc::new(api_handler_v3))?;
+
+	// Event stream, the owner API credentials are required
+	router.add_route("/v2/ws", Arc::new(WsHandler))?;
+	start_sync_status_watch(Arc::downgrade(&sync_state));
+
+	// Probes for the orchestrators, no credentials are required
+	router.add_route(HEALTH_LIVE_URI, Arc::new(LivenessHandler))?;
+	router.add_route(
+		HEALTH_READY_URI,
+		Arc::new(ReadinessHandler {
+			chain: Arc::downgrade(&chain),
+			peers: Arc::downgrade(&peers),
+			sync_state: Arc::downgrade(&sync_state),
+			config: health_config,
+		}),
+	)?;
 
 	let stratum_handler_v2 = StratumAPIHandlerV2::new(stratum_ip_pool);
 	router.add_route("/v2/stratum", Arc::new(stratum_handler_v2))?;
 
 	// Add basic auth to v2 foreign API only
-	if let Some(api_secret) = foreign_api_secret {
-		let api_basic_auth = format!(
-			"Basic {}",
-			to_base64(&format!("{}:{}", basic_auth_key, api_secret))
-		);
-
+	let foreign_credentials = foreign_api_secret.map(|(api_secret, secret_path)| {
+		Arc::new(BasicAuthCredentials::new(
+			basic_auth_key,
+			&api_secret,
+			&secret_path,
+		))
+	});
+	if let Some(credentials) = foreign_credentials.clone() {
 		let basic_auth_middleware = Arc::new(BasicAuthURIMiddleware::new(
-			api_basic_auth,
+			credentials,
 			&MWC_FOREIGN_BASIC_REALM,
 			"/v2/foreign".into(),
 		));
 		router.add_middleware(basic_auth_middleware);
 	}
+	set_foreign_api_credentials(foreign_credentials);
 
 	let api_handler_v2 = ForeignAPIHandlerV2::new(
 		Arc::downgrade(&peers),
@@ -152,7 +259,10 @@ where
 	);
 	router.add_route("/v2/foreign", Arc::new(api_handler_v2))?;
 
-	let mut apis = ApiServer::new();
+	// OpenAPI document of the v2 APIs
+	router.add_route("/v2/spec", Arc::new(ApiSpecHandler))?;
+
+	let mut apis = ApiServer::with_runtime(runtime);
 	warn!("Starting HTTP Node APIs server at {}.", addr);
 	let socket_addr: SocketAddr = addr.parse().expect("unable to parse socket address");
 	let api_thread = apis.start(socket_addr, router, tls_config);
@@ -171,34 +281,68 @@ where
 /// V2 API Handler/Wrapper for owner functions
 pub struct OwnerAPIHandlerV2 {
 	pub chain: Weak<Chain>,
+	pub tx_pool: Weak<dyn StemPoolView>,
 	pub peers: Weak<p2p::Peers>,
 	pub sync_state: Weak<SyncState>,
 }
 
 impl OwnerAPIHandlerV2 {
 	/// Create a new owner API handler for GET methods
-	pub fn new(chain: Weak<Chain>, peers: Weak<p2p::Peers>, sync_state: Weak<SyncState>) -> Self {
+	pub fn new(
+		chain: Weak<Chain>,
+		tx_pool: Weak<dyn StemPoolView>,
+		peers: Weak<p2p::Peers>,
+		sync_state: Weak<SyncState>,
+	) -> Self {
 		OwnerAPIHandlerV2 {
 			chain,
+			tx_pool,
 			peers,
 			sync_state,
 		}
 	}
 }
 
-impl crate::router::Handler for OwnerAPIHandlerV2 {
-	fn post(&self, req: Request<Body>) -> ResponseFuture {
+impl OwnerAPIHandlerV2 {
+	/// Process the json-rpc request, the batch requests are accepted if batch is true
+	fn post_request(&self, req: Request<Body>, batch: bool) -> ResponseFuture {
 		let api = Owner::new(
 			self.chain.clone(),
+			self.tx_pool.clone(),
 			self.peers.clone(),
 			self.sync_state.clone(),
 		);
+		let audit_log = get_owner_api_audit_log();
+		let identity = caller_identity(&req);
+		let source_ip = caller_ip(&req);
+		let read_only = client_permission(&req) == Some(ClientPermission::ReadOnly);
 
 		Box::pin(async move {
 			match parse_body(req).await {
+				Ok(val) if read_only && !is_read_only_owner_request(&val) => {
+					if let Some(audit_log) = audit_log {
+						audit_log
+							.record_call_async(
+								identity,
+								source_ip,
+								Some(&val),
+								Err("forbidden, read-only client certificate".to_string()),
+							)
+							.await;
+					}
+					Ok(audit_recorded(just_response(
+						StatusCode::FORBIDDEN,
+						"client certificate has the read-only permission",
+					)))
+				}
 				Ok(val) => {
 					let owner_api = &api as &dyn OwnerRpc;
-					let res = match owner_api.handle_request(val) {
+					let reply = if batch {
+						json_rpc::handle_request(owner_api, val.clone())
+					} else {
+						owner_api.handle_request(val.clone())
+					};
+					let res = match reply {
 						MaybeReply::Reply(r) => r,
 						MaybeReply::DontReply => {
 							// Since it's http, we need to return something. We return [] because jsonrpc
@@ -206,15 +350,58 @@ impl crate::router::Handler for OwnerAPIHandlerV2 {
 							serde_json::json!([])
 						}
 					};
-					Ok(json_response_pretty(&res))
+					if let Some(audit_log) = audit_log {
+						audit_log
+							.record_call_async(identity, source_ip, Some(&val), Ok(&res))
+							.await;
+					}
+					Ok(audit_recorded(json_response_pretty(&res)))
 				}
 				Err(e) => {
 					error!("Request Error: {:?}", e);
-					Ok(create_error_response(e))
+					if let Some(audit_log) = audit_log {
+						audit_log
+							.record_call_async(
+								identity,
+								source_ip,
+								None,
+								Err(format!("error: {}", e)),
+							)
+							.await;
+					}
+					Ok(audit_recorded(create_error_response(e)))
 				}
 			}
 		})
 	}
+}
+
+impl crate::router::Handler for OwnerAPIHandlerV2 {
+	fn post(&self, req: Request<Body>) -> ResponseFuture {
+		self.post_request(req, false)
+	}
+
+	fn options(&self, _req: Request<Body>) -> ResponseFuture {
+		Box::pin(async { Ok(create_ok_response("{}")) })
+	}
+}
+
+/// V3 API Handler for owner functions, the V2 methods plus the json-rpc batch requests
+pub struct OwnerAPIHandlerV3 {
+	pub handler: OwnerAPIHandlerV2,
+}
+
+impl OwnerAPIHandlerV3 {
+	/// Create a new owner API handler that accepts the batch requests
+	pub fn new(handler: OwnerAPIHandlerV2) -> Self {
+		OwnerAPIHandlerV3 { handler }
+	}
+}
+
+impl crate::router::Handler for OwnerAPIHandlerV3 {
+	fn post(&self, req: Request<Body>) -> ResponseFuture {
+		self.handler.post_request(req, true)
+	}
 
 	fn options(&self, _req: Request<Body>) -> ResponseFuture {
 		Box::pin(async { Ok(create_ok_response("{}")) })
@@ -269,10 +456,17 @@ where
 			self.tx_pool.clone(),
 			self.sync_state.clone(),
 		);
+		let rate_limit = get_api_rate_limiter().zip(client_ip(&req));
 
 		Box::pin(async move {
 			match parse_body(req).await {
 				Ok(val) => {
+					// Heavyweight methods have their own budget
+					if let Some((limiter, ip)) = rate_limit {
+						if let Err(limited) = limiter.check_heavy(ip, heavy_foreign_calls(&val)) {
+							return Ok(too_many_requests(limited));
+						}
+					}
 					let foreign_api = &api as &dyn ForeignRpc;
 					let res = match foreign_api.handle_request(val) {
 						MaybeReply::Reply(r) => r,
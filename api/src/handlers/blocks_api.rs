@@ -20,8 +20,10 @@ use crate::router::{Handler, ResponseFuture};
 use crate::types::*;
 use crate::util;
 use crate::web::*;
+use chrono::{DateTime, Utc};
 use hyper::{Body, Request, StatusCode};
 use regex::Regex;
+use std::cmp;
 use std::sync::Weak;
 
 /// Gets block headers given either a hash or height or an output commit.
@@ -171,6 +173,72 @@ impl BlockHandler {
 		)
 	}
 
+	// Blocks of the main chain by height, at most `max` of them
+	pub fn get_blocks(
+		&self,
+		start_height: u64,
+		end_height: Option<u64>,
+		mut max: u64,
+		include_proof: bool,
+	) -> Result<BlockListing, Error> {
+		if max == 0 {
+			return Err(ErrorKind::Argument("max must be greater than 0".to_string()).into());
+		}
+		//set a limit here
+		if max > 1_000 {
+			max = 1_000;
+		}
+		let chain = w(&self.chain)?;
+		let head = chain
+			.head()
+			.map_err(|e| ErrorKind::Internal(format!("can't get head: {}", e)))?;
+		let end = cmp::min(
+			end_height.unwrap_or(head.height),
+			start_height.saturating_add(max - 1),
+		);
+		let blocks = chain.get_blocks_range(start_height, end).map_err(|e| {
+			ErrorKind::NotFound(format!(
+				"Blocks for heights {}-{}, {}",
+				start_height, end, e
+			))
+		})?;
+		// Blocks below the tail are pruned, they are skipped instead of failing the page
+		let tail_height = chain.tail().map(|t| t.height).unwrap_or(0);
+		let mut listing = BlockListing {
+			highest_height: head.height,
+			last_retrieved_height: start_height.saturating_sub(1),
+			blocks: vec![],
+		};
+		for (height, block) in (start_height..).zip(blocks) {
+			let block = match block {
+				Ok(block) => block,
+				Err(_) if height < tail_height => {
+					listing.last_retrieved_height = height;
+					continue;
+				}
+				Err(e) => {
+					return Err(ErrorKind::NotFound(format!("Block, {}", e)).into());
+				}
+			};
+			let printable = BlockPrintable::from_block(&block, &chain, include_proof, false)
+				.map_err(|e| {
+					ErrorKind::Internal(format!(
+						"chain error, broken block at height {}. {}",
+						height, e
+					))
+				})?;
+			listing.blocks.push(printable);
+			listing.last_retrieved_height = height;
+		}
+		Ok(listing)
+	}
+
+	pub fn get_block_first_seen(&self, h: &Hash) -> Result<Option<DateTime<Utc>>, Error> {
+		w(&self.chain)?.block_first_seen(h).map_err(|e| {
+			ErrorKind::Internal(format!("Block first seen for hash {}, {}", h, e)).into()
+		})
+	}
+
 	fn get_compact_block(&self, h: &Hash) -> Result<CompactBlockPrintable, Error> {
 		let chain = w(&self.chain)?;
 		let block = chain
@@ -15,15 +15,25 @@
 use super::utils::{get_output, get_output_v2, w};
 use crate::chain;
 use crate::core::core::hash::Hashed;
+use crate::core::core::Output;
 use crate::rest::*;
 use crate::router::{Handler, ResponseFuture};
 use crate::types::*;
 use crate::util;
 use crate::util::secp::pedersen::Commitment;
+use crate::util::ToHex;
 use crate::web::*;
+use chrono::{DateTime, Utc};
+use grin_p2p::libp2p_connection;
 use hyper::{Body, Request, StatusCode};
+use std::cmp;
 use std::sync::Weak;
 
+/// Max number of blocks in a single outputs range request
+pub const MAX_OUTPUTS_RANGE_BLOCKS: u64 = 1000;
+/// No more blocks are read for get_outputs_in_range once this many outputs are collected
+pub const MAX_OUTPUTS_RANGE_OUTPUTS: u64 = 1000;
+
 /// Chain handler. Get the head details.
 /// GET /v1/chain
 pub struct ChainHandler {
@@ -37,6 +47,13 @@ impl ChainHandler {
 			.map_err(|e| ErrorKind::Internal(format!("can't get tip: {}", e)))?;
 		Ok(Tip::from_tip(head))
 	}
+
+	pub fn get_chain_stats(&self, height: u64) -> Result<ChainStats, Error> {
+		let stats = w(&self.chain)?
+			.get_chain_stats(height)
+			.map_err(|e| ErrorKind::Internal(format!("can't get chain stats: {}", e)))?;
+		Ok(ChainStats::from_stats(stats))
+	}
 }
 
 impl Handler for ChainHandler {
@@ -84,6 +101,12 @@ impl ChainCompactHandler {
 			.compact()
 			.map_err(|e| ErrorKind::Internal(format!("compact chain error {}", e)).into())
 	}
+
+	pub fn rebuild_kernel_index(&self) -> Result<(), Error> {
+		w(&self.chain)?
+			.rebuild_kernel_pos_index()
+			.map_err(|e| ErrorKind::Internal(format!("rebuild kernel index error {}", e)).into())
+	}
 }
 
 impl Handler for ChainCompactHandler {
@@ -205,6 +228,199 @@ impl OutputHandler {
 		Ok(out)
 	}
 
+	// unspent outputs traversal with an opaque cursor, survives compaction and reorgs
+	pub fn get_unspent_outputs_cursor(
+		&self,
+		cursor: Option<String>,
+		mut max: u64,
+		include_proof: Option<bool>,
+	) -> Result<OutputCursorListing, Error> {
+		//set a limit here
+		if max > 10_000 {
+			max = 10_000;
+		}
+		let cursor = match cursor {
+			Some(cursor) => Some(
+				chain::UtxoCursor::from_hex(&cursor)
+					.map_err(|e| ErrorKind::RequestError(format!("{}", e)))?,
+			),
+			None => None,
+		};
+		let chain = w(&self.chain)?;
+		// The proof hash is always reported, so the proofs are always read.
+		let (outputs, next_cursor) = chain
+			.unspent_outputs_cursor(cursor, max, true)
+			.map_err(|e| ErrorKind::NotFound(format!("Unspent outputs after cursor, {}", e)))?;
+		let outputs = outputs
+			.into_iter()
+			.filter_map(|x| {
+				let output = Output::new(x.features, x.commit, x.proof?);
+				let output_type = if output.is_coinbase() {
+					OutputType::Coinbase
+				} else {
+					OutputType::Transaction
+				};
+				let proof = if include_proof.unwrap_or(false) {
+					Some(output.proof_bytes().to_hex())
+				} else {
+					None
+				};
+				Some(OutputPrintable {
+					output_type,
+					commit: output.commitment(),
+					spent: false,
+					proof,
+					proof_hash: output.proof.hash().to_hex(),
+					block_height: Some(x.height),
+					merkle_proof: None,
+					mmr_index: x.pos,
+				})
+			})
+			.collect();
+		Ok(OutputCursorListing {
+			next_cursor: next_cursor.map(|c| c.to_hex()),
+			outputs,
+		})
+	}
+
+	// output at the given output MMR index, spent or not, only available on archive nodes
+	pub fn get_historical_output(
+		&self,
+		mmr_index: u64,
+		include_proof: Option<bool>,
+	) -> Result<OutputPrintable, Error> {
+		let chain = w(&self.chain)?;
+		if !chain.archive_mode() {
+			return Err(ErrorKind::RequestError(
+				"historical outputs are only available on archive nodes".to_string(),
+			)
+			.into());
+		}
+		let (output, spent, height) = chain.get_historical_output(mmr_index).map_err(|e| {
+			ErrorKind::NotFound(format!("Output at MMR index {}, {}", mmr_index, e))
+		})?;
+		let output_type = if output.is_coinbase() {
+			OutputType::Coinbase
+		} else {
+			OutputType::Transaction
+		};
+		let proof = if include_proof.unwrap_or(false) {
+			Some(output.proof_bytes().to_hex())
+		} else {
+			None
+		};
+		Ok(OutputPrintable {
+			output_type,
+			commit: output.commitment(),
+			spent,
+			proof,
+			proof_hash: output.proof.hash().to_hex(),
+			block_height: Some(height),
+			merkle_proof: None,
+			mmr_index,
+		})
+	}
+
+	// outputs created by the blocks in the height range, spent outputs only if requested.
+	// a range cut short by the block or output limits comes with the height to continue from
+	pub fn get_outputs_in_range(
+		&self,
+		start_height: u64,
+		end_height: u64,
+		include_proof: Option<bool>,
+		include_spent: Option<bool>,
+	) -> Result<OutputRangeListing, Error> {
+		if start_height > end_height {
+			return Err(ErrorKind::RequestError(format!(
+				"invalid height range {}-{}",
+				start_height, end_height
+			))
+			.into());
+		}
+		//set a limit here
+		let last_height = cmp::min(
+			end_height,
+			start_height.saturating_add(MAX_OUTPUTS_RANGE_BLOCKS - 1),
+		);
+		let chain = w(&self.chain)?;
+		let (outputs, next_height) = chain
+			.outputs_in_height_range(
+				start_height,
+				last_height,
+				include_spent.unwrap_or(false),
+				include_proof.unwrap_or(false),
+				MAX_OUTPUTS_RANGE_OUTPUTS,
+			)
+			.map_err(|e| {
+				ErrorKind::NotFound(format!(
+					"Outputs in range {}-{}, {}",
+					start_height, last_height, e
+				))
+			})?;
+		let head_height = chain
+			.head()
+			.map_err(|e| ErrorKind::Internal(e.to_string()))?
+			.height;
+		let next_height = match next_height {
+			Some(h) => Some(h),
+			None if last_height < end_height && last_height < head_height => Some(last_height + 1),
+			None => None,
+		};
+		let outputs = outputs
+			.into_iter()
+			.map(|x| {
+				let output_type = if x.output.is_coinbase() {
+					OutputType::Coinbase
+				} else {
+					OutputType::Transaction
+				};
+				// The proof hash is only known when the proof is read
+				let proof_hash = x
+					.proof
+					.as_ref()
+					.map(|p| p.hash().to_hex())
+					.unwrap_or_default();
+				OutputPrintable {
+					output_type,
+					commit: x.output.commitment(),
+					spent: x.spent,
+					proof: x.proof.map(|p| p.proof[..].to_hex()),
+					proof_hash,
+					block_height: Some(x.height),
+					merkle_proof: None,
+					mmr_index: x.pos,
+				}
+			})
+			.collect();
+		Ok(OutputRangeListing {
+			next_height,
+			outputs,
+		})
+	}
+
+	// height of the block that includes the unspent output with the given commitment
+	pub fn get_output_height(&self, commit_s: String) -> Result<Option<u64>, Error> {
+		let commit = util::from_hex(&commit_s).map_err(|e| {
+			ErrorKind::RequestError(format!("invalid commit hex {}, {}", commit_s, e))
+		})?;
+		if commit.len() != 33 {
+			return Err(ErrorKind::RequestError(format!(
+				"invalid commit {}, get length {}, expected 33",
+				commit_s,
+				commit.len()
+			))
+			.into());
+		}
+		let commit = Commitment::from_vec(commit);
+		w(&self.chain)?.get_output_height(&commit).map_err(|e| {
+			ErrorKind::Internal(format!(
+				"Unable to get output height for commit {}, {}",
+				commit_s, e
+			))
+			.into()
+		})
+	}
+
 	fn outputs_by_ids(&self, req: &Request<Body>) -> Result<Vec<Output>, Error> {
 		let mut commitments: Vec<String> = vec![];
 
@@ -516,6 +732,83 @@ impl KernelHandler {
 			)))
 		})
 	}
+
+	pub fn get_tx_first_seen(&self, excess_s: String) -> Result<Option<DateTime<Utc>>, Error> {
+		let excess = util::from_hex(&excess_s).map_err(|e| {
+			ErrorKind::RequestError(format!("invalid excess hex {}, {}", excess_s, e))
+		})?;
+		if excess.len() != 33 {
+			return Err(ErrorKind::RequestError(format!(
+				"invalid excess {}, get length {}, expected 33",
+				excess_s,
+				excess.len()
+			))
+			.into());
+		}
+		let excess = Commitment::from_vec(excess);
+		w(&self.chain)?.tx_first_seen(&excess).map_err(|e| {
+			ErrorKind::Internal(format!(
+				"Unable to get first seen time for excess {}, {}",
+				excess_s, e
+			))
+			.into()
+		})
+	}
+
+	/// Check if the kernel can be used as an integrity kernel for the gossip messages.
+	/// Kernel must be mined during last INTEGRITY_FEE_VALID_BLOCKS blocks and pay at least
+	/// INTEGRITY_FEE_MIN_X base fees.
+	pub fn check_integrity_kernel(
+		&self,
+		excess_s: String,
+		fee_base: u64,
+	) -> Result<IntegrityKernelStatus, Error> {
+		let chain = w(&self.chain)?;
+		let tip_height = chain
+			.head()
+			.map_err(|e| ErrorKind::Internal(format!("can't get tip: {}", e)))?
+			.height;
+		let min_height = tip_height.saturating_sub(libp2p_connection::INTEGRITY_FEE_VALID_BLOCKS);
+		let min_fee = fee_base * libp2p_connection::INTEGRITY_FEE_MIN_X;
+
+		let mut status = IntegrityKernelStatus {
+			excess: excess_s.clone(),
+			height: None,
+			fee: None,
+			min_fee,
+			valid_until_height: None,
+			valid: false,
+			reason: None,
+		};
+
+		match self.get_kernel_v2(excess_s, Some(min_height), None) {
+			Ok(kernel) => {
+				let fee = kernel.tx_kernel.features.get_fee();
+				status.height = Some(kernel.height);
+				status.fee = Some(fee);
+				status.valid_until_height =
+					Some(kernel.height + libp2p_connection::INTEGRITY_FEE_VALID_BLOCKS);
+				if fee < min_fee {
+					status.reason = Some(format!(
+						"Kernel fee {} is below the minimal integrity fee {}",
+						fee, min_fee
+					));
+				} else {
+					status.valid = true;
+				}
+			}
+			Err(e) => match e.kind() {
+				ErrorKind::NotFound(_) => {
+					status.reason = Some(format!(
+						"Kernel is not found at the last {} blocks",
+						libp2p_connection::INTEGRITY_FEE_VALID_BLOCKS
+					));
+				}
+				_ => return Err(e),
+			},
+		}
+		Ok(status)
+	}
 }
 
 impl Handler for KernelHandler {
@@ -0,0 +1,182 @@
+// Copyright 2021 The MWC Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use super::server_api::sync_status_to_api;
+use super::utils::w;
+use crate::chain::{Chain, SyncState};
+use crate::p2p;
+use crate::rest::*;
+use crate::router::{Handler, ResponseFuture};
+use crate::types::Readiness;
+use crate::web::*;
+use hyper::{Body, Request, StatusCode};
+use std::sync::Weak;
+
+/// Liveness probe endpoint
+pub const HEALTH_LIVE_URI: &str = "/v2/health/live";
+/// Readiness probe endpoint
+pub const HEALTH_READY_URI: &str = "/v2/health/ready";
+
+/// Node is ready if it is not more than that number of blocks behind the peers
+pub const HEALTH_MAX_BLOCKS_BEHIND: u64 = 5;
+/// Node is ready if it has at least that number of connected peers
+pub const HEALTH_MIN_PEERS: u32 = 1;
+
+/// Readiness thresholds
+#[derive(Debug, Clone, PartialEq)]
+pub struct HealthConfig {
+	/// Max number of blocks the node can be behind the most work peer
+	pub max_blocks_behind: u64,
+	/// Min number of the connected peers
+	pub min_peers: u32,
+}
+
+impl Default for HealthConfig {
+	fn default() -> HealthConfig {
+		HealthConfig {
+			max_blocks_behind: HEALTH_MAX_BLOCKS_BEHIND,
+			min_peers: HEALTH_MIN_PEERS,
+		}
+	}
+}
+
+/// Readiness of the node for the chain height (None if the db is not reachable), the height
+/// of the most work peer and the number of the connected peers
+pub fn check_readiness(
+	config: &HealthConfig,
+	height: Option<u64>,
+	network_height: Option<u64>,
+	peers: u32,
+	sync_status: String,
+) -> Readiness {
+	let mut failures = vec![];
+	match height {
+		Some(height) => {
+			let behind = network_height.unwrap_or(height).saturating_sub(height);
+			if behind > config.max_blocks_behind {
+				failures.push(format!(
+					"Node is {} blocks behind the network tip, limit {}",
+					behind, config.max_blocks_behind
+				));
+			}
+		}
+		None => failures.push("Chain database is not reachable".to_string()),
+	}
+	if peers < config.min_peers {
+		failures.push(format!(
+			"{} peers are connected, required {}",
+			peers, config.min_peers
+		));
+	}
+	Readiness {
+		ready: failures.is_empty(),
+		height,
+		network_height,
+		peers,
+		sync_status,
+		failures,
+	}
+}
+
+/// Liveness probe, the node answers the requests
+/// GET /v2/health/live
+pub struct LivenessHandler;
+
+impl Handler for LivenessHandler {
+	fn get(&self, _req: Request<Body>) -> ResponseFuture {
+		response(StatusCode::OK, "{\"alive\":true}")
+	}
+}
+
+/// Readiness probe, the node is synced and connected. Responds with
+/// `503 Service Unavailable` if the node is not ready.
+/// GET /v2/health/ready
+pub struct ReadinessHandler {
+	pub chain: Weak<Chain>,
+	pub peers: Weak<p2p::Peers>,
+	pub sync_state: Weak<SyncState>,
+	pub config: HealthConfig,
+}
+
+impl ReadinessHandler {
+	pub fn get_readiness(&self) -> Result<Readiness, Error> {
+		let chain = w(&self.chain)?;
+		let peers = w(&self.peers)?;
+		// Header is read from the db, it checks that the db is reachable
+		let height = chain.head_header().ok().map(|header| header.height);
+		let network_height = peers.most_work_peer().map(|peer| peer.info.height());
+		let (sync_status, _) = sync_status_to_api(w(&self.sync_state)?.status());
+		Ok(check_readiness(
+			&self.config,
+			height,
+			network_height,
+			peers.peer_count(),
+			sync_status,
+		))
+	}
+}
+
+impl Handler for ReadinessHandler {
+	fn get(&self, _req: Request<Body>) -> ResponseFuture {
+		match self.get_readiness() {
+			Ok(readiness) => {
+				let status = if readiness.ready {
+					StatusCode::OK
+				} else {
+					StatusCode::SERVICE_UNAVAILABLE
+				};
+				match serde_json::to_string(&readiness) {
+					Ok(json) => response(status, json),
+					Err(e) => response(
+						StatusCode::INTERNAL_SERVER_ERROR,
+						format!("Unable to build respond json, {}", e),
+					),
+				}
+			}
+			Err(e) => response(StatusCode::SERVICE_UNAVAILABLE, format!("{}", e)),
+		}
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+
+	#[test]
+	fn test_check_readiness() {
+		let config = HealthConfig {
+			max_blocks_behind: 5,
+			min_peers: 2,
+		};
+		let ready = check_readiness(&config, Some(100), Some(105), 3, "no_sync".to_string());
+		assert!(ready.ready);
+		assert!(ready.failures.is_empty());
+
+		let behind = check_readiness(&config, Some(100), Some(106), 3, "syncing".to_string());
+		assert!(!behind.ready);
+		assert_eq!(behind.failures.len(), 1);
+
+		// No peers, the network tip is not known
+		let isolated = check_readiness(&config, Some(100), None, 0, "no_sync".to_string());
+		assert!(!isolated.ready);
+		assert_eq!(isolated.failures.len(), 1);
+
+		let no_db = check_readiness(&config, None, Some(100), 1, "no_sync".to_string());
+		assert!(!no_db.ready);
+		assert_eq!(no_db.failures.len(), 2);
+
+		// The node can be ahead of its peers
+		assert!(check_readiness(&config, Some(110), Some(100), 2, String::new()).ready);
+	}
+}
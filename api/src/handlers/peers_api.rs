@@ -13,7 +13,10 @@
 // limitations under the License.
 
 use super::utils::w;
-use crate::p2p::types::{PeerAddr, PeerInfoDisplay, ReasonForBan};
+use crate::p2p::types::{
+	Capabilities, PeerAddr, PeerDiversityReport, PeerFilter, PeerInfoDisplay, PeerStoreStats,
+	ReasonForBan,
+};
 use crate::p2p::{self, PeerData};
 use crate::rest::*;
 use crate::router::{Handler, ResponseFuture};
@@ -28,6 +31,12 @@ pub struct PeersAllHandler {
 	pub peers: Weak<p2p::Peers>,
 }
 
+impl PeersAllHandler {
+	pub fn get_peer_store_stats(&self) -> Result<PeerStoreStats, Error> {
+		Ok(w(&self.peers)?.store_stats())
+	}
+}
+
 impl Handler for PeersAllHandler {
 	fn get(&self, _req: Request<Body>) -> ResponseFuture {
 		let peers = &w_fut!(&self.peers).all_peers();
@@ -41,82 +50,108 @@ pub struct PeersConnectedHandler {
 
 impl PeersConnectedHandler {
 	pub fn get_connected_peers(&self) -> Result<Vec<PeerInfoDisplayLegacy>, Error> {
-		let peers = w(&self.peers)?
+		Ok(w(&self.peers)?
 			.connected_peers()
 			.iter()
-			.map(|p| p.info.clone().into())
-			.collect::<Vec<PeerInfoDisplay>>();
-
-		let mut peers_ret: Vec<PeerInfoDisplayLegacy> = Vec::new();
-
-		for peer in peers {
-			let peer_addr_str = match peer.addr {
-				// for tor we just return this because older wallets
-				// can't process this.
-				PeerAddr::Onion(_) => format!("127.0.0.1:{}", 3414),
-				PeerAddr::Ip(ip) => format!("{}:{}", ip.ip(), ip.port()),
-			};
-
-			let peer_direction = if peer.direction == Direction::OutboundTor {
-				Direction::Outbound
-			} else if peer.direction == Direction::InboundTor {
-				Direction::Inbound
-			} else {
-				peer.direction
-			};
-
-			let peer_display = PeerInfoDisplayLegacy {
-				capabilities: peer.capabilities,
-				user_agent: peer.user_agent,
-				version: peer.version,
-				addr: peer_addr_str,
-				direction: peer_direction,
-				total_difficulty: peer.total_difficulty,
-				height: peer.height,
-			};
-			peers_ret.push(peer_display);
+			.map(|p| legacy_peer_display(p.info.clone().into()))
+			.collect())
+	}
+
+	pub fn get_peer_diversity(&self) -> Result<PeerDiversityReport, Error> {
+		Ok(w(&self.peers)?.diversity_report())
+	}
+
+	// connected peers that match the query filters, paged with limit and offset.
+	// without parameters, all the connected peers as get_connected_peers returns them
+	fn get_filtered_peers(&self, req: &Request<Body>) -> Result<Vec<PeerInfoDisplayLegacy>, Error> {
+		let query = req.uri().query().unwrap_or_default();
+		if query.is_empty() {
+			return self.get_connected_peers();
 		}
-		Ok(peers_ret)
+		let (filter, offset, limit) = peer_query(&QueryParams::from(query))?;
+		Ok(w(&self.peers)?
+			.filtered_peers(&filter)
+			.iter()
+			.skip(offset)
+			.take(limit)
+			.map(|p| legacy_peer_display(p.info.clone().into()))
+			.collect())
 	}
 }
 
-impl Handler for PeersConnectedHandler {
-	fn get(&self, _req: Request<Body>) -> ResponseFuture {
-		let peers: Vec<PeerInfoDisplay> = w_fut!(&self.peers)
-			.connected_peers()
-			.iter()
-			.map(|p| p.info.clone().into())
-			.collect();
-
-		let mut peers_ret: Vec<PeerInfoDisplayLegacy> = Vec::new();
-		for peer in peers.clone() {
-			let peer_addr_str = match peer.addr {
-				// for tor we just return this because older wallets
-				// can't process this.
-				PeerAddr::Onion(_) => format!("127.0.0.1:{}", 3414),
-				PeerAddr::Ip(ip) => format!("{}:{}", ip.ip(), ip.port()),
-			};
-
-			let peer_direction = if peer.direction == Direction::OutboundTor {
-				Direction::Outbound
-			} else if peer.direction == Direction::InboundTor {
-				Direction::Inbound
-			} else {
-				peer.direction
-			};
-
-			let peer_display = PeerInfoDisplayLegacy {
-				capabilities: peer.capabilities,
-				user_agent: peer.user_agent,
-				version: peer.version,
-				addr: peer_addr_str,
-				direction: peer_direction,
-				total_difficulty: peer.total_difficulty,
-				height: peer.height,
-			};
-			peers_ret.push(peer_display);
+// filter and paging (offset, limit) of the connected peers query
+fn peer_query(params: &QueryParams) -> Result<(PeerFilter, usize, usize), Error> {
+	let direction = match params.get("direction").map(|d| d.as_str()) {
+		None => None,
+		Some("inbound") => Some(Direction::Inbound),
+		Some("outbound") => Some(Direction::Outbound),
+		Some(d) => {
+			return Err(ErrorKind::RequestError(format!(
+				"invalid direction {}, expected inbound or outbound",
+				d
+			))
+			.into())
 		}
-		json_response(&peers_ret)
+	};
+	let capabilities = match params.get("capabilities") {
+		None => None,
+		Some(_) => Some(Capabilities::from_bits_truncate(parse_param!(
+			params,
+			"capabilities",
+			0
+		))),
+	};
+	let banned = match params.get("banned") {
+		None => None,
+		Some(_) => Some(parse_param!(params, "banned", false)),
+	};
+	let filter = PeerFilter {
+		direction,
+		capabilities,
+		banned,
+		user_agent: params.get("user_agent").cloned(),
+	};
+	let offset = parse_param!(params, "offset", 0);
+	let limit = parse_param!(params, "limit", usize::MAX);
+	Ok((filter, offset, limit))
+}
+
+// Older wallets can't process the onion addresses and the tor directions
+fn legacy_peer_display(peer: PeerInfoDisplay) -> PeerInfoDisplayLegacy {
+	let peer_addr_str = match peer.addr {
+		// for tor we just return this because older wallets
+		// can't process this.
+		PeerAddr::Onion(_) => format!("127.0.0.1:{}", 3414),
+		PeerAddr::Ip(ip) => format!("{}:{}", ip.ip(), ip.port()),
+	};
+
+	let peer_direction = if peer.direction == Direction::OutboundTor {
+		Direction::Outbound
+	} else if peer.direction == Direction::InboundTor {
+		Direction::Inbound
+	} else {
+		peer.direction
+	};
+
+	PeerInfoDisplayLegacy {
+		capabilities: peer.capabilities,
+		user_agent: peer.user_agent,
+		version: peer.version,
+		addr: peer_addr_str,
+		direction: peer_direction,
+		total_difficulty: peer.total_difficulty,
+		height: peer.height,
+	}
+}
+
+/// Connected peers
+/// GET /v1/peers/connected
+/// GET /v1/peers/connected?direction=inbound&capabilities=15&banned=false&user_agent=mwc&offset=0&limit=20
+/// All parameters are optional. `direction` is `inbound` or `outbound`, `capabilities` are the
+/// capability flags that the peers must have.
+impl Handler for PeersConnectedHandler {
+	fn get(&self, req: Request<Body>) -> ResponseFuture {
+		result_to_response(self.get_filtered_peers(&req))
 	}
 }
 
@@ -250,3 +285,53 @@ impl Handler for PeerHandler {
 		};
 	}
 }
+
+#[cfg(test)]
+mod test {
+	use super::*;
+	use crate::core::pow::Difficulty;
+	use crate::core::ser::ProtocolVersion;
+	use crate::p2p::types::{PeerInfo, PeerLiveInfo};
+	use crate::util::RwLock;
+	use std::sync::atomic::AtomicUsize;
+	use std::sync::Arc;
+	use std::time::Instant;
+
+	#[test]
+	fn test_peer_query() {
+		let info = PeerInfo {
+			capabilities: Capabilities::HEADER_HIST | Capabilities::TXHASHSET_HIST,
+			user_agent: "MW/MWC 4.1.0".to_string(),
+			version: ProtocolVersion::local(),
+			addr: PeerAddr::Onion("abcdef.onion".to_string()),
+			direction: Direction::InboundTor,
+			live_info: Arc::new(RwLock::new(PeerLiveInfo::new(Difficulty::min()))),
+			header_sync_requested: Arc::new(AtomicUsize::new(0)),
+			last_header: Arc::new(std::sync::Mutex::new(Instant::now())),
+			last_header_reset: Arc::new(std::sync::Mutex::new(Instant::now())),
+		};
+		let query = |q: &str| peer_query(&QueryParams::from(q));
+
+		let (filter, offset, limit) = query("offset=2").unwrap();
+		assert!(filter.matches(&info, false));
+		assert!(filter.matches(&info, true));
+		assert_eq!((offset, limit), (2, usize::MAX));
+
+		let (filter, offset, limit) =
+			query("direction=inbound&capabilities=1&banned=false&user_agent=mwc+4.1&limit=20")
+				.unwrap();
+		assert!(filter.matches(&info, false));
+		assert!(!filter.matches(&info, true));
+		assert_eq!((offset, limit), (0, 20));
+
+		let (filter, _, _) = query("direction=outbound").unwrap();
+		assert!(!filter.matches(&info, false));
+		let (filter, _, _) = query("capabilities=4").unwrap();
+		assert!(!filter.matches(&info, false));
+		let (filter, _, _) = query("user_agent=grin").unwrap();
+		assert!(!filter.matches(&info, false));
+
+		assert!(query("direction=sideways").is_err());
+		assert!(query("limit=-1").is_err());
+	}
+}
@@ -17,11 +17,12 @@ use crate::core::core::hash::Hashed;
 use crate::core::core::verifier_cache::VerifierCache;
 use crate::core::core::Transaction;
 use crate::core::ser::{self, ProtocolVersion};
-use crate::pool::{self, BlockChain, PoolAdapter, PoolEntry};
+use crate::pool::{self, BlockChain, FeeEstimate, PoolAdapter, PoolEntry};
 use crate::rest::*;
 use crate::router::{Handler, ResponseFuture};
 use crate::types::*;
 use crate::util;
+use crate::util::secp::pedersen::Commitment;
 use crate::util::RwLock;
 use crate::web::*;
 use hyper::{Body, Request, StatusCode};
@@ -83,7 +84,22 @@ where
 		// will only read from txpool
 		let pool_arc = w(&self.tx_pool)?;
 		let txpool = pool_arc.read();
-		Ok(txpool.txpool.entries.clone())
+		Ok(txpool.txpool.entries().to_vec())
+	}
+	pub fn get_kernel_state(&self, excess: &Commitment) -> Result<Option<KernelState>, Error> {
+		let pool_arc = w(&self.tx_pool)?;
+		let pool = pool_arc.read();
+		// The stempool is not looked at, a stem tx must not be revealed
+		if pool.txpool.contains_kernel(excess) {
+			Ok(Some(KernelState::Pool))
+		} else {
+			Ok(None)
+		}
+	}
+	pub fn get_fee_estimate(&self, blocks_target: u64) -> Result<FeeEstimate, Error> {
+		let pool_arc = w(&self.tx_pool)?;
+		let pool = pool_arc.read();
+		Ok(pool.estimate_fee(blocks_target))
 	}
 	pub fn push_transaction(&self, tx: Transaction, fluff: Option<bool>) -> Result<(), Error> {
 		let pool_arc = w(&self.tx_pool)?;
@@ -98,6 +114,10 @@ where
 		);
 
 		let tx_hash = tx.hash().clone();
+		// The policy plugins are consulted before the pool lock is taken, they can be slow
+		let policies = pool_arc.read().policies.clone();
+		pool::check_tx_policies(&policies, &tx, source, !fluff.unwrap_or(false))
+			.map_err(|e| ErrorKind::Internal(format!("Failed to update pool, {}", e)))?;
 		//  Push to tx pool.
 		let mut tx_pool = pool_arc.write();
 		let header = tx_pool
@@ -169,6 +189,10 @@ where
 		tx.kernels().len(),
 	);
 
+	// The policy plugins are consulted before the pool lock is taken, they can be slow
+	let policies = pool.read().policies.clone();
+	pool::check_tx_policies(&policies, &tx, source, !fluff)
+		.map_err(|e| ErrorKind::Internal(format!("Failed to update pool, {}", e)))?;
 	//  Push to tx pool.
 	let mut tx_pool = pool.write();
 	let header = tx_pool
@@ -50,17 +50,23 @@ pub struct StatusHandler {
 
 impl StatusHandler {
 	pub fn get_status(&self) -> Result<Status, Error> {
-		let head = w(&self.chain)?
+		let chain = w(&self.chain)?;
+		let head = chain
 			.head()
 			.map_err(|e| ErrorKind::Internal(format!("Unable to get chain tip, {}", e)))?;
 		let sync_status = w(&self.sync_state)?.status();
 		let (api_sync_status, api_sync_info) = sync_status_to_api(sync_status);
-		Ok(Status::from_tip_and_peers(
+		let mut status = Status::from_tip_and_peers(
 			head,
 			w(&self.peers)?.peer_count(),
 			api_sync_status,
 			api_sync_info,
-		))
+		);
+		status.assumed_utxo_height = chain
+			.assumed_utxo()
+			.map_err(|e| ErrorKind::Internal(format!("Unable to get utxo snapshot, {}", e)))?
+			.map(|assumed| assumed.height);
+		Ok(status)
 	}
 }
 
@@ -111,7 +117,7 @@ impl Handler for StatusHandler {
 }
 
 /// Convert a SyncStatus in a readable API representation
-fn sync_status_to_api(sync_status: SyncStatus) -> (String, Option<serde_json::Value>) {
+pub(crate) fn sync_status_to_api(sync_status: SyncStatus) -> (String, Option<serde_json::Value>) {
 	match sync_status {
 		SyncStatus::NoSync => ("no_sync".to_string(), None),
 		SyncStatus::AwaitingPeers(_) => ("awaiting_peers".to_string(), None),
@@ -14,11 +14,15 @@
 
 use super::utils::w;
 use crate::chain;
+use crate::core::core::hash::{Hash, Hashed};
+use crate::core::core::merkle_proof::MerkleProof;
+use crate::core::core::{OutputFeatures, OutputIdentifier};
+use crate::core::global;
 use crate::rest::*;
 use crate::router::{Handler, ResponseFuture};
 use crate::types::*;
-use crate::util;
 use crate::util::secp::pedersen::Commitment;
+use crate::util::{self, ToHex};
 use crate::web::*;
 use hyper::{Body, Request, StatusCode};
 use std::sync::Weak;
@@ -157,6 +161,125 @@ impl TxHashSetHandler {
 			mmr_index: output_pos,
 		})
 	}
+
+	/// Build the merkle proof for the unspent coinbase output against the block that includes it
+	pub fn get_coinbase_merkle_proof(&self, commit: String) -> Result<CoinbaseMerkleProof, Error> {
+		let c = util::from_hex(&commit).map_err(|e| {
+			ErrorKind::Argument(format!("Not a valid commitment {}, {}", commit, e))
+		})?;
+		let chain = w(&self.chain)?;
+		let (out_id, pos) = chain
+			.get_unspent(Commitment::from_vec(c))
+			.map_err(|e| ErrorKind::Internal(format!("chain error, {}", e)))?
+			.ok_or_else(|| ErrorKind::NotFound(format!("Unspent output {}", commit)))?;
+		if !out_id.is_coinbase() {
+			return Err(ErrorKind::Argument(format!("Output {} is not a coinbase", commit)).into());
+		}
+		let header = chain
+			.get_header_by_height(pos.height)
+			.map_err(|e| ErrorKind::NotFound(format!("Header at height {}, {}", pos.height, e)))?;
+		let merkle_proof = chain
+			.get_output_merkle_proof_at(pos.pos, &header)
+			.map_err(|e| {
+				ErrorKind::NotFound(format!(
+					"Unable to get a merkle proof for commit {}, {}",
+					commit, e
+				))
+			})?;
+		Ok(CoinbaseMerkleProof {
+			commit,
+			block_hash: header.hash().to_hex(),
+			block_height: header.height,
+			maturity_height: header.height + global::coinbase_maturity(),
+			output_root: header.output_root.to_hex(),
+			mmr_index: pos.pos,
+			merkle_proof: merkle_proof.to_hex(),
+		})
+	}
+
+	/// Verify the coinbase merkle proof against the output root of the block on the current chain
+	pub fn verify_coinbase_merkle_proof(&self, proof: CoinbaseMerkleProof) -> Result<bool, Error> {
+		let commit = util::from_hex(&proof.commit).map_err(|e| {
+			ErrorKind::Argument(format!("Not a valid commitment {}, {}", proof.commit, e))
+		})?;
+		let block_hash = Hash::from_hex(&proof.block_hash).map_err(|e| {
+			ErrorKind::Argument(format!(
+				"Not a valid block hash {}, {}",
+				proof.block_hash, e
+			))
+		})?;
+		let merkle_proof = MerkleProof::from_hex(&proof.merkle_proof)
+			.map_err(|e| ErrorKind::Argument(format!("Not a valid merkle proof, {}", e)))?;
+
+		let chain = w(&self.chain)?;
+		let header = chain
+			.get_block_header(&block_hash)
+			.map_err(|e| ErrorKind::NotFound(format!("Header {}, {}", proof.block_hash, e)))?;
+		if header.height != proof.block_height
+			|| header.output_mmr_size != merkle_proof.mmr_size
+			|| chain.is_on_current_chain(&header).is_err()
+		{
+			return Ok(false);
+		}
+
+		let out_id = OutputIdentifier::new(OutputFeatures::Coinbase, &Commitment::from_vec(commit));
+		Ok(merkle_proof
+			.verify(header.output_root, &out_id, proof.mmr_index)
+			.is_ok())
+	}
+
+	/// Build the merkle proof for the kernel at the kernel MMR index against the kernel root
+	/// of the block at the given height
+	pub fn get_kernel_merkle_proof(
+		&self,
+		mmr_index: u64,
+		height: u64,
+	) -> Result<KernelMerkleProof, Error> {
+		let chain = w(&self.chain)?;
+		let (kernel, header, merkle_proof) = chain
+			.get_kernel_merkle_proof(mmr_index, height)
+			.map_err(|e| {
+				ErrorKind::NotFound(format!(
+					"Unable to get a merkle proof for kernel at {} against height {}, {}",
+					mmr_index, height, e
+				))
+			})?;
+		Ok(KernelMerkleProof {
+			kernel,
+			block_hash: header.hash().to_hex(),
+			block_height: header.height,
+			kernel_root: header.kernel_root.to_hex(),
+			mmr_index,
+			merkle_proof: merkle_proof.to_hex(),
+		})
+	}
+
+	/// Verify the kernel merkle proof against the kernel root of the block on the current chain
+	pub fn verify_kernel_merkle_proof(&self, proof: KernelMerkleProof) -> Result<bool, Error> {
+		let block_hash = Hash::from_hex(&proof.block_hash).map_err(|e| {
+			ErrorKind::Argument(format!(
+				"Not a valid block hash {}, {}",
+				proof.block_hash, e
+			))
+		})?;
+		let merkle_proof = MerkleProof::from_hex(&proof.merkle_proof)
+			.map_err(|e| ErrorKind::Argument(format!("Not a valid merkle proof, {}", e)))?;
+
+		let chain = w(&self.chain)?;
+		let header = chain
+			.get_block_header(&block_hash)
+			.map_err(|e| ErrorKind::NotFound(format!("Header {}, {}", proof.block_hash, e)))?;
+		if header.height != proof.block_height
+			|| header.kernel_mmr_size != merkle_proof.mmr_size
+			|| chain.is_on_current_chain(&header).is_err()
+		{
+			return Ok(false);
+		}
+
+		Ok(merkle_proof
+			.verify(header.kernel_root, &proof.kernel, proof.mmr_index)
+			.is_ok())
+	}
 }
 
 impl Handler for TxHashSetHandler {
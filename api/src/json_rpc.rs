@@ -0,0 +1,165 @@
+// Copyright 2021 The MWC Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! JSON-RPC batch requests. A batch is an array of calls, every call is dispatched
+//! to the handler and the replies are returned as an array in the same order.
+//! Notifications (calls without id) don't have a reply.
+
+use easy_jsonrpc_mw::{Handler, MaybeReply};
+use serde_json::Value;
+
+/// Max number of calls in a single batch request
+pub const MAX_BATCH_SIZE: usize = 100;
+
+/// JSON-RPC 'Invalid Request' error code
+const INVALID_REQUEST_CODE: i64 = -32600;
+
+/// Handle a single JSON-RPC call or a batch of calls.
+pub fn handle_request<H: Handler + ?Sized>(handler: &H, request: Value) -> MaybeReply {
+	match request {
+		Value::Array(calls) => {
+			if calls.is_empty() {
+				return MaybeReply::Reply(invalid_request("Empty batch"));
+			}
+			if calls.len() > MAX_BATCH_SIZE {
+				return MaybeReply::Reply(invalid_request(&format!(
+					"Batch has {} calls, max is {}",
+					calls.len(),
+					MAX_BATCH_SIZE
+				)));
+			}
+			let replies: Vec<Value> = calls
+				.into_iter()
+				.filter_map(|call| {
+					if call.is_array() {
+						// nested batches are not allowed
+						return Some(invalid_request("Nested batch"));
+					}
+					match handler.handle_request(call) {
+						MaybeReply::Reply(r) => Some(r),
+						MaybeReply::DontReply => None,
+					}
+				})
+				.collect();
+			if replies.is_empty() {
+				MaybeReply::DontReply
+			} else {
+				MaybeReply::Reply(Value::Array(replies))
+			}
+		}
+		call => handler.handle_request(call),
+	}
+}
+
+fn invalid_request(message: &str) -> Value {
+	serde_json::json!({
+		"jsonrpc": "2.0",
+		"error": {
+			"code": INVALID_REQUEST_CODE,
+			"message": message,
+		},
+		"id": null,
+	})
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+	use crate::owner::{Owner, StemPoolView};
+	use crate::owner_rpc::OwnerRpc;
+	use crate::pool::StemPoolStatus;
+	use std::sync::Weak;
+
+	struct EmptyStemPool;
+
+	impl StemPoolView for EmptyStemPool {
+		fn stempool_status(&self) -> StemPoolStatus {
+			StemPoolStatus::default()
+		}
+	}
+
+	fn call(method: &str, id: Option<u64>) -> Value {
+		match id {
+			Some(id) => serde_json::json!({
+				"jsonrpc": "2.0",
+				"method": method,
+				"params": [],
+				"id": id,
+			}),
+			None => serde_json::json!({
+				"jsonrpc": "2.0",
+				"method": method,
+				"params": [],
+			}),
+		}
+	}
+
+	fn reply(res: MaybeReply) -> Value {
+		match res {
+			MaybeReply::Reply(r) => r,
+			MaybeReply::DontReply => panic!("reply is expected"),
+		}
+	}
+
+	#[test]
+	fn test_batch_request() {
+		let tx_pool: Weak<EmptyStemPool> = Weak::new();
+		let owner = Owner::new(Weak::new(), tx_pool, Weak::new(), Weak::new());
+		let owner_api = &owner as &dyn OwnerRpc;
+
+		// replies are in the call order, notifications don't have a reply
+		let batch = Value::Array(vec![
+			call("get_stempool_status", Some(1)),
+			call("get_tip_freeze_status", None),
+			call("no_such_method", Some(2)),
+		]);
+		let res = reply(handle_request(owner_api, batch));
+		let replies = res.as_array().unwrap();
+		assert_eq!(replies.len(), 2);
+		assert_eq!(replies[0]["id"], 1);
+		assert_eq!(replies[0]["result"]["Ok"]["count"], 0);
+		assert_eq!(replies[1]["id"], 2);
+		assert!(replies[1]["error"].is_object());
+
+		// single call is not wrapped into an array
+		let res = reply(handle_request(
+			owner_api,
+			call("get_stempool_status", Some(3)),
+		));
+		assert_eq!(res["id"], 3);
+
+		// batch of notifications only
+		let batch = Value::Array(vec![call("get_stempool_status", None)]);
+		match handle_request(owner_api, batch) {
+			MaybeReply::Reply(_) => panic!("no reply is expected"),
+			MaybeReply::DontReply => (),
+		}
+
+		// invalid batches
+		let res = reply(handle_request(owner_api, Value::Array(vec![])));
+		assert_eq!(res["error"]["code"], INVALID_REQUEST_CODE);
+		let batch = Value::Array(vec![
+			call("get_stempool_status", Some(1));
+			MAX_BATCH_SIZE + 1
+		]);
+		let res = reply(handle_request(owner_api, batch));
+		assert_eq!(res["error"]["code"], INVALID_REQUEST_CODE);
+		let batch = Value::Array(vec![Value::Array(vec![call(
+			"get_stempool_status",
+			Some(1),
+		)])]);
+		let res = reply(handle_request(owner_api, batch));
+		assert_eq!(res[0]["error"]["code"], INVALID_REQUEST_CODE);
+	}
+}
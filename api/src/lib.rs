@@ -33,29 +33,41 @@ extern crate log;
 
 #[macro_use]
 mod web;
+pub mod audit;
 pub mod auth;
 pub mod client;
 mod foreign;
 mod foreign_rpc;
+#[cfg(feature = "grpc")]
+pub mod grpc;
 mod handlers;
+pub mod json_rpc;
+pub mod node_client;
 mod owner;
 mod owner_rpc;
+pub mod rate_limit;
 mod rest;
 mod router;
+pub mod spec;
 mod stratum;
 mod stratum_rpc;
 mod types;
+pub mod ws;
 
+pub use crate::audit::{AuditLog, AuditLogEntry, AuditLogPage};
 pub use crate::auth::{
-	BasicAuthMiddleware, BasicAuthURIMiddleware, MWC_BASIC_REALM, MWC_FOREIGN_BASIC_REALM,
+	BasicAuthCredentials, BasicAuthMiddleware, BasicAuthURIMiddleware, MWC_BASIC_REALM,
+	MWC_FOREIGN_BASIC_REALM,
 };
 pub use crate::foreign::Foreign;
-pub use crate::foreign_rpc::ForeignRpc;
+pub use crate::foreign_rpc::{foreign_rpc_request, ForeignRpc};
+pub use crate::handlers::health_api::HealthConfig;
 pub use crate::handlers::node_apis;
-pub use crate::owner::Owner;
+pub use crate::node_client::NodeClient;
 pub use crate::owner::{
-	get_server_onion_address, reset_server_onion_address, set_server_onion_address,
+	get_server_onion_address, reset_server_onion_address, set_build_info, set_server_onion_address,
 };
+pub use crate::owner::{Owner, StemPoolView};
 pub use crate::owner_rpc::OwnerRpc;
 pub use crate::rest::*;
 pub use crate::router::*;
@@ -0,0 +1,384 @@
+// Copyright 2021 The MWC Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Typed client for the node owner and foreign JSON-RPC APIs
+
+use crate::client::{self, RequestFailure};
+use crate::core::core::Transaction;
+use crate::p2p::types::{PeerDiversityReport, PeerInfoDisplayLegacy, PeerStoreStats};
+use crate::p2p::PeerData;
+use crate::rest::{Error, ErrorKind};
+use crate::types::{
+	BlockHeaderPrintable, BlockListing, BlockPrintable, KernelMerkleProof, LocatedTxKernel,
+	OutputPrintable, Status, Tip, Version,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::net::SocketAddr;
+use std::thread;
+use std::time::Duration;
+
+/// Number of retries of a request failed at the transport level
+const DEFAULT_RETRIES: u32 = 3;
+
+/// Methods that change the node state, they are not retried after a timeout because
+/// the node might have processed them already
+const NON_IDEMPOTENT_METHODS: &[&str] = &["push_transaction"];
+
+/// Delay between the request retries
+const DEFAULT_RETRY_DELAY: Duration = Duration::from_secs(1);
+
+/// Client for the node owner (`/v2/owner`) and foreign (`/v2/foreign`) JSON-RPC APIs.
+/// Use an `https://` node url for the nodes serving the API over TLS.
+/// Requests that fail to connect or get a 5xx response are retried, so are the timed out
+/// requests that don't change the node state. Errors returned by the node are not retried.
+#[derive(Clone, Debug)]
+pub struct NodeClient {
+	node_url: String,
+	owner_api_secret: Option<String>,
+	foreign_api_secret: Option<String>,
+	retries: u32,
+	retry_delay: Duration,
+}
+
+impl NodeClient {
+	/// Client for the node API at `node_url`, e.g. `http://127.0.0.1:3413`.
+	pub fn new(
+		node_url: &str,
+		owner_api_secret: Option<String>,
+		foreign_api_secret: Option<String>,
+	) -> NodeClient {
+		NodeClient {
+			node_url: node_url.trim_end_matches('/').to_string(),
+			owner_api_secret,
+			foreign_api_secret,
+			retries: DEFAULT_RETRIES,
+			retry_delay: DEFAULT_RETRY_DELAY,
+		}
+	}
+
+	/// Set the number of retries of the failed requests and the delay between them.
+	pub fn with_retries(mut self, retries: u32, retry_delay: Duration) -> NodeClient {
+		self.retries = retries;
+		self.retry_delay = retry_delay;
+		self
+	}
+
+	/// See [Owner::get_status](struct.Owner.html#method.get_status).
+	pub fn get_status(&self) -> Result<Status, Error> {
+		self.owner_call("get_status", json!([]))
+	}
+
+	/// See [Owner::validate_chain](struct.Owner.html#method.validate_chain).
+	pub fn validate_chain(&self) -> Result<(), Error> {
+		self.owner_call("validate_chain", json!([]))
+	}
+
+	/// See [Owner::compact_chain](struct.Owner.html#method.compact_chain).
+	pub fn compact_chain(&self) -> Result<(), Error> {
+		self.owner_call("compact_chain", json!([]))
+	}
+
+	/// See [Owner::get_peers](struct.Owner.html#method.get_peers).
+	pub fn get_peers(&self, peer_addr: Option<SocketAddr>) -> Result<Vec<PeerData>, Error> {
+		self.owner_call("get_peers", json!([peer_addr]))
+	}
+
+	/// See [Owner::get_connected_peers](struct.Owner.html#method.get_connected_peers).
+	pub fn get_connected_peers(&self) -> Result<Vec<PeerInfoDisplayLegacy>, Error> {
+		self.owner_call("get_connected_peers", json!([]))
+	}
+
+	/// See [Owner::get_peer_diversity](struct.Owner.html#method.get_peer_diversity).
+	pub fn get_peer_diversity(&self) -> Result<PeerDiversityReport, Error> {
+		self.owner_call("get_peer_diversity", json!([]))
+	}
+
+	/// See [Owner::get_peer_store_stats](struct.Owner.html#method.get_peer_store_stats).
+	pub fn get_peer_store_stats(&self) -> Result<PeerStoreStats, Error> {
+		self.owner_call("get_peer_store_stats", json!([]))
+	}
+
+	/// See [Owner::ban_peer](struct.Owner.html#method.ban_peer).
+	pub fn ban_peer(&self, peer_addr: SocketAddr) -> Result<(), Error> {
+		self.owner_call("ban_peer", json!([peer_addr]))
+	}
+
+	/// See [Owner::unban_peer](struct.Owner.html#method.unban_peer).
+	pub fn unban_peer(&self, peer_addr: SocketAddr) -> Result<(), Error> {
+		self.owner_call("unban_peer", json!([peer_addr]))
+	}
+
+	/// See [Foreign::get_version](struct.Foreign.html#method.get_version).
+	pub fn get_version(&self) -> Result<Version, Error> {
+		self.foreign_call("get_version", json!([]))
+	}
+
+	/// See [Foreign::get_tip](struct.Foreign.html#method.get_tip).
+	pub fn get_tip(&self) -> Result<Tip, Error> {
+		self.foreign_call("get_tip", json!([]))
+	}
+
+	/// See [Foreign::get_header](struct.Foreign.html#method.get_header).
+	pub fn get_header(
+		&self,
+		height: Option<u64>,
+		hash: Option<String>,
+		commit: Option<String>,
+	) -> Result<BlockHeaderPrintable, Error> {
+		self.foreign_call("get_header", json!([height, hash, commit]))
+	}
+
+	/// See [Foreign::get_block](struct.Foreign.html#method.get_block).
+	pub fn get_block(
+		&self,
+		height: Option<u64>,
+		hash: Option<String>,
+		commit: Option<String>,
+	) -> Result<BlockPrintable, Error> {
+		self.foreign_call("get_block", json!([height, hash, commit]))
+	}
+
+	/// See [Foreign::get_blocks](struct.Foreign.html#method.get_blocks).
+	pub fn get_blocks(
+		&self,
+		start_height: u64,
+		end_height: Option<u64>,
+		max: u64,
+		include_proof: Option<bool>,
+	) -> Result<BlockListing, Error> {
+		self.foreign_call(
+			"get_blocks",
+			json!([start_height, end_height, max, include_proof]),
+		)
+	}
+
+	/// See [Foreign::get_kernel](struct.Foreign.html#method.get_kernel).
+	pub fn get_kernel(
+		&self,
+		excess: String,
+		min_height: Option<u64>,
+		max_height: Option<u64>,
+	) -> Result<LocatedTxKernel, Error> {
+		self.foreign_call("get_kernel", json!([excess, min_height, max_height]))
+	}
+
+	/// See [Foreign::get_kernel_merkle_proof](struct.Foreign.html#method.get_kernel_merkle_proof).
+	pub fn get_kernel_merkle_proof(
+		&self,
+		mmr_index: u64,
+		height: u64,
+	) -> Result<KernelMerkleProof, Error> {
+		self.foreign_call("get_kernel_merkle_proof", json!([mmr_index, height]))
+	}
+
+	/// See [Foreign::verify_kernel_merkle_proof](struct.Foreign.html#method.verify_kernel_merkle_proof).
+	pub fn verify_kernel_merkle_proof(&self, proof: &KernelMerkleProof) -> Result<bool, Error> {
+		self.foreign_call("verify_kernel_merkle_proof", json!([proof]))
+	}
+
+	/// See [Foreign::get_outputs](struct.Foreign.html#method.get_outputs).
+	pub fn get_outputs(
+		&self,
+		commits: Option<Vec<String>>,
+		start_height: Option<u64>,
+		end_height: Option<u64>,
+		include_proof: Option<bool>,
+		include_merkle_proof: Option<bool>,
+	) -> Result<Vec<OutputPrintable>, Error> {
+		self.foreign_call(
+			"get_outputs",
+			json!([
+				commits,
+				start_height,
+				end_height,
+				include_proof,
+				include_merkle_proof
+			]),
+		)
+	}
+
+	/// See [Foreign::get_output_height](struct.Foreign.html#method.get_output_height).
+	pub fn get_output_height(&self, commit: String) -> Result<Option<u64>, Error> {
+		self.foreign_call("get_output_height", json!([commit]))
+	}
+
+	/// See [Foreign::get_pool_size](struct.Foreign.html#method.get_pool_size).
+	pub fn get_pool_size(&self) -> Result<usize, Error> {
+		self.foreign_call("get_pool_size", json!([]))
+	}
+
+	/// See [Foreign::get_stempool_size](struct.Foreign.html#method.get_stempool_size).
+	pub fn get_stempool_size(&self) -> Result<usize, Error> {
+		self.foreign_call("get_stempool_size", json!([]))
+	}
+
+	/// See [Foreign::push_transaction](struct.Foreign.html#method.push_transaction).
+	pub fn push_transaction(&self, tx: &Transaction, fluff: Option<bool>) -> Result<(), Error> {
+		self.foreign_call("push_transaction", json!([tx, fluff]))
+	}
+
+	fn owner_call<T>(&self, method: &str, params: Value) -> Result<T, Error>
+	where
+		for<'de> T: Deserialize<'de>,
+	{
+		let url = format!("{}/v2/owner", self.node_url);
+		self.call(&url, self.owner_api_secret.clone(), method, params)
+	}
+
+	fn foreign_call<T>(&self, method: &str, params: Value) -> Result<T, Error>
+	where
+		for<'de> T: Deserialize<'de>,
+	{
+		let url = format!("{}/v2/foreign", self.node_url);
+		self.call(&url, self.foreign_api_secret.clone(), method, params)
+	}
+
+	fn call<T>(
+		&self,
+		url: &str,
+		api_secret: Option<String>,
+		method: &str,
+		params: Value,
+	) -> Result<T, Error>
+	where
+		for<'de> T: Deserialize<'de>,
+	{
+		let request = RpcRequest {
+			jsonrpc: "2.0",
+			method,
+			id: 1,
+			params,
+		};
+		let mut attempt = 0;
+		let response = loop {
+			let req = client::create_post_request(url, api_secret.clone(), &request)?;
+			match client::send_request_with_failure(req) {
+				Ok(response) => {
+					break serde_json::from_str::<Value>(&response).map_err(|e| {
+						ErrorKind::ResponseError(format!(
+							"Cannot parse response: {}, {}",
+							response, e
+						))
+					})?
+				}
+				Err((e, failure)) => {
+					if !is_retriable(method, failure) || attempt >= self.retries {
+						return Err(e);
+					}
+					attempt += 1;
+					debug!(
+						"{} request to {} failed, retry {} of {}, {}",
+						method, url, attempt, self.retries, e
+					);
+					thread::sleep(self.retry_delay);
+				}
+			}
+		};
+		parse_rpc_response(method, response)
+	}
+}
+
+/// Check if the failed request can be sent again
+fn is_retriable(method: &str, failure: Option<RequestFailure>) -> bool {
+	match failure {
+		Some(RequestFailure::Connect) => true,
+		Some(RequestFailure::Status(code)) => code >= 500,
+		Some(RequestFailure::Timeout) => !NON_IDEMPOTENT_METHODS.contains(&method),
+		Some(RequestFailure::Other) | None => false,
+	}
+}
+
+#[derive(Serialize)]
+struct RpcRequest<'a> {
+	jsonrpc: &'a str,
+	method: &'a str,
+	id: u32,
+	params: Value,
+}
+
+/// Extract the method result from the node JSON-RPC response. The methods
+/// return `{"Ok": result}` or `{"Err": error}`.
+fn parse_rpc_response<T>(method: &str, response: Value) -> Result<T, Error>
+where
+	for<'de> T: Deserialize<'de>,
+{
+	if let Some(err) = response.get("error") {
+		return Err(ErrorKind::ResponseError(format!("{} failed, {}", method, err)).into());
+	}
+	let result = response.get("result").ok_or_else(|| {
+		ErrorKind::ResponseError(format!("{}: no result in response {}", method, response))
+	})?;
+	if let Some(err) = result.get("Err") {
+		let kind = serde_json::from_value::<ErrorKind>(err.clone())
+			.unwrap_or_else(|_| ErrorKind::ResponseError(format!("{} failed, {}", method, err)));
+		return Err(kind.into());
+	}
+	let ok = result.get("Ok").ok_or_else(|| {
+		ErrorKind::ResponseError(format!("{}: unexpected result {}", method, result))
+	})?;
+	serde_json::from_value(ok.clone()).map_err(|e| {
+		ErrorKind::ResponseError(format!("{}: cannot parse result {}, {}", method, ok, e)).into()
+	})
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+
+	#[test]
+	fn test_parse_rpc_response() {
+		let ok: Option<u64> =
+			parse_rpc_response("get_output_height", json!({"result": {"Ok": 5}})).unwrap();
+		assert_eq!(ok, Some(5));
+
+		parse_rpc_response::<()>("ban_peer", json!({"result": {"Ok": null}})).unwrap();
+
+		let err =
+			parse_rpc_response::<u64>("get_tip", json!({"result": {"Err": {"NotFound": "tip"}}}))
+				.unwrap_err();
+		assert_eq!(err.kind(), &ErrorKind::NotFound("tip".to_string()));
+
+		let err = parse_rpc_response::<u64>(
+			"get_tip",
+			json!({"error": {"code": -32601, "message": "Method not found"}}),
+		)
+		.unwrap_err();
+		match err.kind() {
+			ErrorKind::ResponseError(_) => {}
+			kind => panic!("unexpected error {:?}", kind),
+		}
+
+		assert!(parse_rpc_response::<u64>("get_tip", json!({"result": {"Ok": "x"}})).is_err());
+	}
+
+	#[test]
+	fn test_is_retriable() {
+		assert!(is_retriable("get_tip", Some(RequestFailure::Connect)));
+		assert!(is_retriable(
+			"push_transaction",
+			Some(RequestFailure::Connect)
+		));
+		assert!(is_retriable("get_tip", Some(RequestFailure::Status(503))));
+		assert!(!is_retriable("get_tip", Some(RequestFailure::Status(401))));
+		assert!(!is_retriable("get_tip", Some(RequestFailure::Status(404))));
+		assert!(is_retriable("get_tip", Some(RequestFailure::Timeout)));
+		assert!(!is_retriable(
+			"push_transaction",
+			Some(RequestFailure::Timeout)
+		));
+		assert!(!is_retriable("get_tip", Some(RequestFailure::Other)));
+		assert!(!is_retriable("get_tip", None));
+	}
+}
@@ -14,20 +14,51 @@
 
 //! Owner API External Definition
 
-use crate::chain::{Chain, SyncState};
-use crate::handlers::chain_api::{ChainCompactHandler, ChainValidationHandler};
-use crate::handlers::peers_api::{PeerHandler, PeersConnectedHandler};
+use crate::audit::{self, AuditLogPage};
+use crate::auth::{self, BasicAuthCredentials};
+use crate::chain::watch::{BloomFilter, WatchEventPage, WatchFilterInfo, WatchMatcher};
+use crate::chain::{BlockRejection, Chain, ReorgRecord, SyncState, TipFreezeStatus};
+use crate::core::core::hash::Hashed;
+use crate::core::core::verifier_cache::VerifierCache;
+use crate::core::core::{Block, TxKernel};
+use crate::core::global;
+use crate::core::ser::{self, ProtocolVersion};
+use crate::handlers::chain_api::{ChainCompactHandler, ChainValidationHandler, KernelHandler};
+use crate::handlers::peers_api::{PeerHandler, PeersAllHandler, PeersConnectedHandler};
 use crate::handlers::server_api::StatusHandler;
-use crate::p2p::{self, PeerData};
+use crate::handlers::utils::w;
+use crate::p2p::{self, PeerAddr, PeerData};
+use crate::pool::{self, BlockChain, PoolAdapter, StemPoolStatus};
 use crate::rest::*;
-use crate::types::Status;
-use grin_p2p::types::PeerInfoDisplayLegacy;
-use grin_util::Mutex;
+use crate::types::{
+	ApiSecretRotation, BlockValidation, BuildInfo, IntegrityKernelStatus, MessageSimulation,
+	MinedBlockPrintable, NodeState, Status, StatusAndPeers,
+};
+use crate::util;
+use crate::util::secp::constants::PEDERSEN_COMMITMENT_SIZE;
+use crate::util::secp::pedersen::Commitment;
+use chrono::{TimeZone, Utc};
+use grin_p2p::libp2p_connection::{self, GossipTraceEntry, RelayDenyList};
+use grin_p2p::types::{
+	PeerDiversityReport, PeerInfoDisplayLegacy, PeerStoreStats, SimulationCheck,
+};
+use grin_util::{Mutex, RwLock};
 use std::net::SocketAddr;
-use std::sync::Weak;
+use std::sync::{Arc, Weak};
+
+/// Default time while the previous API secret is still accepted after the rotation, seconds
+const DEFAULT_SECRET_GRACE_PERIOD: u64 = 300;
+
+/// Max time while the previous API secret is still accepted after the rotation, seconds
+const MAX_SECRET_GRACE_PERIOD: u64 = 7 * 24 * 3600;
 
 lazy_static! {
 	static ref SERVER_ONION_ADDRESS: Mutex<Option<String>> = Mutex::new(None);
+	static ref BUILD_INFO: Mutex<Option<BuildInfo>> = Mutex::new(None);
+}
+
+pub fn set_build_info(build_info: BuildInfo) {
+	BUILD_INFO.lock().replace(build_info);
 }
 
 pub fn get_server_onion_address() -> Option<String> {
@@ -52,10 +83,28 @@ pub fn reset_server_onion_address() {
 
 pub struct Owner {
 	pub chain: Weak<Chain>,
+	pub tx_pool: Weak<dyn StemPoolView>,
 	pub peers: Weak<p2p::Peers>,
 	pub sync_state: Weak<SyncState>,
 }
 
+/// Stempool access of the owner API, the owner API is not generic over the pool types.
+pub trait StemPoolView: Send + Sync {
+	/// Content of the stempool with the embargo timers of the entries.
+	fn stempool_status(&self) -> StemPoolStatus;
+}
+
+impl<B, P, V> StemPoolView for RwLock<pool::TransactionPool<B, P, V>>
+where
+	B: BlockChain,
+	P: PoolAdapter,
+	V: VerifierCache + 'static,
+{
+	fn stempool_status(&self) -> StemPoolStatus {
+		self.read().stempool_status()
+	}
+}
+
 impl Owner {
 	/// Create a new API instance with the chain, transaction pool, peers and `sync_state`. All subsequent
 	/// API calls will operate on this instance of node API.
@@ -70,9 +119,15 @@ impl Owner {
 	/// * An instance of the Node holding references to the current chain, transaction pool, peers and sync_state.
 	///
 
-	pub fn new(chain: Weak<Chain>, peers: Weak<p2p::Peers>, sync_state: Weak<SyncState>) -> Self {
+	pub fn new(
+		chain: Weak<Chain>,
+		tx_pool: Weak<dyn StemPoolView>,
+		peers: Weak<p2p::Peers>,
+		sync_state: Weak<SyncState>,
+	) -> Self {
 		Owner {
 			chain,
+			tx_pool,
 			peers,
 			sync_state,
 		}
@@ -126,6 +181,22 @@ impl Owner {
 		chain_compact_handler.compact_chain()
 	}
 
+	/// Rebuild the kernel excess index from the full kernel MMR. Normally the index is
+	/// maintained on block accept and rewind, the rebuild is needed only if it is out of sync.
+	///
+	/// # Returns
+	/// * Result Containing:
+	/// * `Ok(())` if the index was rebuilt successfully
+	/// * or [`Error`](struct.Error.html) if an error is encountered.
+	///
+
+	pub fn rebuild_kernel_index(&self) -> Result<(), Error> {
+		let chain_compact_handler = ChainCompactHandler {
+			chain: self.chain.clone(),
+		};
+		chain_compact_handler.rebuild_kernel_index()
+	}
+
 	/// Retrieves information about stored peers.
 	/// If `None` is provided, will list all stored peers.
 	///
@@ -160,6 +231,40 @@ impl Owner {
 		peers_connected_handler.get_connected_peers()
 	}
 
+	/// Reports the mix of connected peers by latency (measured with pings on idle
+	/// connections), address class and user agent. Operators can use it to judge
+	/// whether one more node in their location improves the network topology.
+	///
+	/// # Returns
+	/// * Result Containing:
+	/// * A [`PeerDiversityReport`](types/struct.PeerDiversityReport.html)
+	/// * or [`Error`](struct.Error.html) if an error is encountered.
+	///
+
+	pub fn get_peer_diversity(&self) -> Result<PeerDiversityReport, Error> {
+		let peers_connected_handler = PeersConnectedHandler {
+			peers: self.peers.clone(),
+		};
+		peers_connected_handler.get_peer_diversity()
+	}
+
+	/// Reports the size of the peer database and the number of peers evicted by its
+	/// retention policies (expired defunct peers, never connected peers, size cap)
+	/// since the node start.
+	///
+	/// # Returns
+	/// * Result Containing:
+	/// * A [`PeerStoreStats`](types/struct.PeerStoreStats.html)
+	/// * or [`Error`](struct.Error.html) if an error is encountered.
+	///
+
+	pub fn get_peer_store_stats(&self) -> Result<PeerStoreStats, Error> {
+		let peers_all_handler = PeersAllHandler {
+			peers: self.peers.clone(),
+		};
+		peers_all_handler.get_peer_store_stats()
+	}
+
 	/// Bans a specific peer.
 	///
 	/// # Arguments
@@ -195,4 +300,611 @@ impl Owner {
 		};
 		peer_handler.unban_peer(addr)
 	}
+
+	/// Checks if the kernel qualifies as a valid integrity kernel for the libp2p gossip messages right now.
+	/// Wallets can verify the paid integrity fee before publishing the messages.
+	///
+	/// # Arguments
+	/// * `excess` - hex encoded kernel excess.
+	///
+	/// # Returns
+	/// * Result Containing:
+	/// * An [`IntegrityKernelStatus`](types/struct.IntegrityKernelStatus.html)
+	/// * or [`Error`](struct.Error.html) if an error is encountered.
+	///
+
+	pub fn check_integrity_kernel(&self, excess: String) -> Result<IntegrityKernelStatus, Error> {
+		let fee_base = libp2p_connection::get_integrity_fee_base().ok_or_else(|| {
+			ErrorKind::P2pError(
+				"libp2p node is not running, integrity kernels are not accepted".to_string(),
+			)
+		})?;
+		let kernel_handler = KernelHandler {
+			chain: self.chain.clone(),
+		};
+		kernel_handler.check_integrity_kernel(excess, fee_base)
+	}
+
+	/// Runs the full contextual validation of the block against the current chain head without
+	/// saving or relaying it. Mining pools can verify the externally assembled blocks before
+	/// broadcasting them.
+	///
+	/// # Arguments
+	/// * `serialized_block` - hex encoded block, serialized with the local protocol version.
+	///
+	/// # Returns
+	/// * Result Containing:
+	/// * A [`BlockValidation`](types/struct.BlockValidation.html)
+	/// * or [`Error`](struct.Error.html) if the block can't be deserialized.
+	///
+
+	pub fn validate_block(&self, serialized_block: String) -> Result<BlockValidation, Error> {
+		let data = util::from_hex(&serialized_block)
+			.map_err(|e| ErrorKind::Argument(format!("Invalid block hex, {}", e)))?;
+		let block: Block = ser::deserialize(&mut &data[..], ProtocolVersion::local())
+			.map_err(|e| ErrorKind::Argument(format!("Unable to deserialize block, {}", e)))?;
+
+		let hash = block.hash().to_hex();
+		let height = block.header.height;
+		let res = w(&self.chain)?.validate_block(block);
+		let (error_kind, error) = match res {
+			Ok(_) => (None, None),
+			Err(e) => {
+				let kind = format!("{:?}", e.kind());
+				let kind_name = kind
+					.split(|c: char| !c.is_alphanumeric())
+					.next()
+					.unwrap_or("")
+					.to_string();
+				debug!("Block {} at {} is not valid, {}", hash, height, e);
+				(Some(kind_name), Some(format!("{}", e)))
+			}
+		};
+		Ok(BlockValidation {
+			hash,
+			height,
+			valid: error.is_none(),
+			error_kind,
+			error,
+		})
+	}
+
+	/// Returns the last blocks rejected as invalid by the block processing pipeline, most
+	/// recent first, with the failing validation stage and the roots the node expected.
+	/// With `dump_rejected_blocks` enabled in the chain config the blocks and their context
+	/// are dumped to the `forensics` directory too.
+	///
+	/// # Arguments
+	/// * `limit` - max number of rejections to return, all kept rejections by default.
+	///
+	/// # Returns
+	/// * Result Containing:
+	/// * A vector of [`BlockRejection`](../grin_chain/types/struct.BlockRejection.html)
+	/// * or [`Error`](struct.Error.html) if an error is encountered.
+	///
+
+	pub fn get_rejected_blocks(&self, limit: Option<usize>) -> Result<Vec<BlockRejection>, Error> {
+		let mut rejections = w(&self.chain)?.rejected_blocks();
+		if let Some(limit) = limit {
+			rejections.truncate(limit);
+		}
+		Ok(rejections)
+	}
+
+	/// Returns the last reorgs of the chain head, most recent first, with the number of
+	/// the orphaned blocks and the hashes of the old and new tips. The node keeps up to
+	/// 100 reorgs since its start.
+	///
+	/// # Arguments
+	/// * `limit` - max number of reorgs to return, all kept reorgs by default.
+	///
+	/// # Returns
+	/// * Result Containing:
+	/// * A vector of [`ReorgRecord`](../grin_chain/types/struct.ReorgRecord.html)
+	/// * or [`Error`](struct.Error.html) if an error is encountered.
+	///
+
+	pub fn get_reorg_history(&self, limit: Option<usize>) -> Result<Vec<ReorgRecord>, Error> {
+		let mut history = w(&self.chain)?.reorg_history();
+		if let Some(limit) = limit {
+			history.truncate(limit);
+		}
+		Ok(history)
+	}
+
+	/// Runs a raw p2p or gossip message through the validation pipeline without processing it
+	/// and reports which checks it would trip. Available on testnets only.
+	///
+	/// # Arguments
+	/// * `peer` - address of the peer that sends the message, ip:port or onion address.
+	/// * `message_hex` - hex encoded message. p2p messages must include the header,
+	/// messages without p2p magic are validated as gossip integrity messages.
+	///
+	/// # Returns
+	/// * Result Containing:
+	/// * A [`MessageSimulation`](types/struct.MessageSimulation.html)
+	/// * or [`Error`](struct.Error.html) if an error is encountered.
+	///
+
+	pub fn simulate_message(
+		&self,
+		peer: String,
+		message_hex: String,
+	) -> Result<MessageSimulation, Error> {
+		if global::is_mainnet() {
+			return Err(ErrorKind::RequestError(
+				"simulate_message is available on testnets only".to_string(),
+			)
+			.into());
+		}
+
+		let data = util::from_hex(&message_hex).map_err(|e| {
+			ErrorKind::Argument(format!("Invalid message hex {}, {}", message_hex, e))
+		})?;
+
+		let mut checks = vec![];
+		if w(&self.peers)?.is_banned(PeerAddr::from_str(&peer)) {
+			checks.push(SimulationCheck::failed(
+				"peer_banned",
+				format!("Peer {} is banned", peer),
+			));
+		} else {
+			checks.push(SimulationCheck::passed("peer_banned"));
+		}
+
+		let (protocol, message_type) = if p2p::msg::has_message_magic(&data) {
+			let (msg_type, msg_checks) =
+				p2p::msg::simulate_message(&data, ProtocolVersion::local());
+			checks.extend(msg_checks);
+			("p2p", msg_type.map(|t| format!("{:?}", t)))
+		} else {
+			let fee_base = libp2p_connection::get_integrity_fee_base().ok_or_else(|| {
+				ErrorKind::P2pError(
+					"libp2p node is not running, unable to validate gossip message".to_string(),
+				)
+			})?;
+			let chain = w(&self.chain)?;
+			let min_height = chain
+				.head()?
+				.height
+				.saturating_sub(libp2p_connection::INTEGRITY_FEE_VALID_BLOCKS);
+			let kernel_validation_fn =
+				move |excess: &Commitment| -> Result<Option<TxKernel>, p2p::Error> {
+					Ok(chain
+						.get_kernel_by_excess(excess)?
+						.filter(|(_, height, _)| *height >= min_height)
+						.map(|(kernel, _, _)| kernel))
+				};
+			checks.extend(libp2p_connection::simulate_integrity_message(
+				&data,
+				Arc::new(kernel_validation_fn),
+				fee_base,
+			));
+			("gossip", None)
+		};
+
+		let valid = checks.iter().all(|c| c.error.is_none());
+		Ok(MessageSimulation {
+			protocol: protocol.to_string(),
+			message_type,
+			checks,
+			valid,
+		})
+	}
+
+	/// Returns the traced gossip messages, most recent first. Tracing is a debug mode enabled
+	/// with the `libp2p_gossip_trace_size` config parameter, the list is empty otherwise.
+	///
+	/// # Arguments
+	/// * `topic` - return the messages for this topic only.
+	/// * `peer_id` - return the messages propagated by this peer only.
+	/// * `limit` - max number of messages to return, all traced messages by default.
+	///
+	/// # Returns
+	/// * Result Containing:
+	/// * A vector of [`GossipTraceEntry`](../grin_p2p/libp2p_connection/struct.GossipTraceEntry.html)
+	/// * or [`Error`](struct.Error.html) if an error is encountered.
+	///
+
+	pub fn get_gossip_trace(
+		&self,
+		topic: Option<String>,
+		peer_id: Option<String>,
+		limit: Option<usize>,
+	) -> Result<Vec<GossipTraceEntry>, Error> {
+		Ok(libp2p_connection::get_gossip_trace(topic, peer_id, limit))
+	}
+
+	/// Returns the emergency relay deny list of the libp2p node.
+	///
+	/// # Returns
+	/// * Result Containing:
+	/// * A [`RelayDenyList`](../grin_p2p/libp2p_connection/struct.RelayDenyList.html)
+	/// * or [`Error`](struct.Error.html) if an error is encountered.
+	///
+
+	pub fn get_relay_deny_list(&self) -> Result<RelayDenyList, Error> {
+		Ok(libp2p_connection::get_relay_deny_list())
+	}
+
+	/// Replaces the emergency relay deny list of the libp2p node. Gossip messages for the denied
+	/// topics, or with the message ID that matches a denied pattern, are not processed and not relayed.
+	/// The change is applied immediately, it is not saved into the config file.
+	///
+	/// # Arguments
+	/// * `deny_list` - denied topics and message ID patterns, `*` matches any sequence of characters.
+	///
+	/// # Returns
+	/// * Result Containing:
+	/// * `Ok(())` if the deny list was updated
+	/// * or [`Error`](struct.Error.html) if an error is encountered.
+	///
+
+	pub fn set_relay_deny_list(&self, deny_list: RelayDenyList) -> Result<(), Error> {
+		libp2p_connection::set_relay_deny_list(deny_list);
+		Ok(())
+	}
+
+	/// Generates a new owner API secret and writes it into the api secret file. The previous secret
+	/// is still accepted during the grace period, so the clients have time to read the new secret.
+	///
+	/// # Arguments
+	/// * `grace_period_secs` - time while the previous secret is still valid, 300 seconds by default,
+	/// 7 days max.
+	///
+	/// # Returns
+	/// * Result Containing:
+	/// * An [`ApiSecretRotation`](types/struct.ApiSecretRotation.html)
+	/// * or [`Error`](struct.Error.html) if an error is encountered.
+	///
+
+	pub fn rotate_api_secret(
+		&self,
+		grace_period_secs: Option<u64>,
+	) -> Result<ApiSecretRotation, Error> {
+		let credentials = auth::get_owner_api_credentials().ok_or_else(|| {
+			ErrorKind::RequestError("Owner API is not protected by the secret".to_string())
+		})?;
+		Owner::rotate_secret(&credentials, grace_period_secs)
+	}
+
+	/// Generates a new foreign API secret and writes it into the foreign api secret file. The previous
+	/// secret is still accepted during the grace period, so the wallets have time to read the new secret.
+	///
+	/// # Arguments
+	/// * `grace_period_secs` - time while the previous secret is still valid, 300 seconds by default,
+	/// 7 days max.
+	///
+	/// # Returns
+	/// * Result Containing:
+	/// * An [`ApiSecretRotation`](types/struct.ApiSecretRotation.html)
+	/// * or [`Error`](struct.Error.html) if an error is encountered.
+	///
+
+	pub fn rotate_foreign_api_secret(
+		&self,
+		grace_period_secs: Option<u64>,
+	) -> Result<ApiSecretRotation, Error> {
+		let credentials = auth::get_foreign_api_credentials().ok_or_else(|| {
+			ErrorKind::RequestError("Foreign API is not protected by the secret".to_string())
+		})?;
+		Owner::rotate_secret(&credentials, grace_period_secs)
+	}
+
+	/// Returns the entries of the owner API audit log. Every owner API call is recorded with the
+	/// caller identity, its IP address and the outcome. The entries are hash chained, the whole
+	/// log is verified on every query.
+	///
+	/// # Arguments
+	/// * `start_index` - return the entries starting from this index.
+	/// * `method` - return the calls of this method only.
+	/// * `limit` - max number of entries to return, 100 by default.
+	///
+	/// # Returns
+	/// * Result Containing:
+	/// * An [`AuditLogPage`](audit/struct.AuditLogPage.html)
+	/// * or [`Error`](struct.Error.html) if an error is encountered.
+	///
+
+	pub fn get_audit_log(
+		&self,
+		start_index: Option<u64>,
+		method: Option<String>,
+		limit: Option<usize>,
+	) -> Result<AuditLogPage, Error> {
+		let audit_log = audit::get_owner_api_audit_log().ok_or_else(|| {
+			ErrorKind::RequestError("Owner API audit log is not enabled".to_string())
+		})?;
+		audit_log.query(start_index, method, limit)
+	}
+
+	/// Returns the provenance of the node binary: the commit it was built from, the compiler,
+	/// the features and the reproducibility hash to compare with the official builds.
+	///
+	/// # Returns
+	/// * Result Containing:
+	/// * A [`BuildInfo`](types/struct.BuildInfo.html)
+	/// * or [`Error`](struct.Error.html) if an error is encountered.
+	///
+
+	pub fn get_build_info(&self) -> Result<BuildInfo, Error> {
+		BUILD_INFO
+			.lock()
+			.clone()
+			.ok_or_else(|| ErrorKind::Internal("Build info is not available".to_string()).into())
+	}
+
+	/// Registers a watch-only filter of the output commitments, persisted in the chain db.
+	/// The node records the events of the matching outputs (created, spent, reorged) for the
+	/// blocks accepted after the registration. The filter is either a list of commitments or
+	/// a bloom filter (see [`BloomFilter`](../grin_chain/watch/struct.BloomFilter.html) for
+	/// the bit positions of a commitment).
+	///
+	/// # Arguments
+	/// * `name` - name of the filter.
+	/// * `commitments` - hex encoded output commitments to watch.
+	/// * `bloom_filter` - hex encoded bit array of the bloom filter.
+	/// * `bloom_hashes` - number of the hash functions of the bloom filter.
+	///
+	/// # Returns
+	/// * Result Containing:
+	/// * A [`WatchFilterInfo`](../grin_chain/watch/struct.WatchFilterInfo.html)
+	/// * or [`Error`](struct.Error.html) if the filter is not valid.
+	///
+
+	pub fn add_watch_filter(
+		&self,
+		name: String,
+		commitments: Option<Vec<String>>,
+		bloom_filter: Option<String>,
+		bloom_hashes: Option<u32>,
+	) -> Result<WatchFilterInfo, Error> {
+		let commitments = match commitments {
+			Some(commitments) => Some(
+				commitments
+					.iter()
+					.map(|c| {
+						util::from_hex(c)
+							.ok()
+							.filter(|bytes| bytes.len() == PEDERSEN_COMMITMENT_SIZE)
+							.map(Commitment::from_vec)
+							.ok_or_else(|| ErrorKind::Argument(format!("Invalid commitment {}", c)))
+					})
+					.collect::<Result<Vec<_>, _>>()?,
+			),
+			None => None,
+		};
+		let bloom = match bloom_filter {
+			Some(bits) => Some(BloomFilter {
+				bits: util::from_hex(&bits)
+					.map_err(|e| ErrorKind::Argument(format!("Invalid bloom filter hex, {}", e)))?,
+				hashes: bloom_hashes.unwrap_or(0),
+			}),
+			None => None,
+		};
+		let matcher = WatchMatcher::new(commitments, bloom)
+			.map_err(|e| ErrorKind::Argument(format!("{}", e)))?;
+		w(&self.chain)?
+			.add_watch_filter(name, matcher)
+			.map_err(|e| ErrorKind::Internal(format!("Unable to add watch filter, {}", e)).into())
+	}
+
+	/// Deletes the watch filter with its recorded events.
+	///
+	/// # Arguments
+	/// * `id` - id of the filter.
+	///
+	/// # Returns
+	/// * Result Containing:
+	/// * `Ok(())` if the filter was deleted
+	/// * or [`Error`](struct.Error.html) if an error is encountered.
+	///
+
+	pub fn delete_watch_filter(&self, id: u64) -> Result<(), Error> {
+		w(&self.chain)?
+			.delete_watch_filter(id)
+			.map_err(|e| ErrorKind::NotFound(format!("{}", e)).into())
+	}
+
+	/// Returns the registered watch filters.
+	///
+	/// # Returns
+	/// * Result Containing:
+	/// * A vector of [`WatchFilterInfo`](../grin_chain/watch/struct.WatchFilterInfo.html)
+	/// * or [`Error`](struct.Error.html) if an error is encountered.
+	///
+
+	pub fn get_watch_filters(&self) -> Result<Vec<WatchFilterInfo>, Error> {
+		w(&self.chain)?
+			.watch_filters()
+			.map_err(|e| ErrorKind::Internal(format!("Unable to read watch filters, {}", e)).into())
+	}
+
+	/// Returns the events recorded by the watch filter, starting from the cursor. The
+	/// `next_cursor` of the returned page continues with the events recorded later.
+	///
+	/// # Arguments
+	/// * `id` - id of the filter.
+	/// * `cursor` - number of the first event to return, 0 by default.
+	/// * `limit` - max number of events to return, 100 by default, 1000 at most.
+	///
+	/// # Returns
+	/// * Result Containing:
+	/// * A [`WatchEventPage`](../grin_chain/watch/struct.WatchEventPage.html)
+	/// * or [`Error`](struct.Error.html) if an error is encountered.
+	///
+
+	pub fn get_watch_events(
+		&self,
+		id: u64,
+		cursor: Option<u64>,
+		limit: Option<usize>,
+	) -> Result<WatchEventPage, Error> {
+		let limit = limit.unwrap_or(100).min(1000);
+		w(&self.chain)?
+			.watch_events(id, cursor.unwrap_or(0), limit)
+			.map_err(|e| ErrorKind::NotFound(format!("{}", e)).into())
+	}
+
+	/// Temporarily stops accepting the new blocks into the chain, so the chain can be
+	/// backed up or audited at a fixed height. The blocks received in the meantime are
+	/// buffered and processed once the freeze ends. Calling it on the frozen tip extends
+	/// the freeze. A freeze lasts one hour at most in total and ends early if too many
+	/// blocks are buffered. The tip can't be frozen while the node is syncing.
+	///
+	/// # Arguments
+	/// * `duration` - freeze duration, seconds.
+	///
+	/// # Returns
+	/// * Result Containing:
+	/// * A [`TipFreezeStatus`](../grin_chain/types/struct.TipFreezeStatus.html)
+	/// * or [`Error`](struct.Error.html) if an error is encountered.
+	///
+
+	pub fn freeze_tip(&self, duration: u64) -> Result<TipFreezeStatus, Error> {
+		if w(&self.sync_state)?.is_syncing() {
+			return Err(ErrorKind::RequestError(
+				"Chain tip can't be frozen while syncing".to_string(),
+			)
+			.into());
+		}
+		w(&self.chain)?
+			.freeze_tip(duration)
+			.map_err(|e| ErrorKind::Argument(format!("{}", e)).into())
+	}
+
+	/// Ends the tip freeze now, processing the blocks buffered during the freeze.
+	///
+	/// # Returns
+	/// * Result Containing:
+	/// * A [`TipFreezeStatus`](../grin_chain/types/struct.TipFreezeStatus.html)
+	/// * or [`Error`](struct.Error.html) if an error is encountered.
+	///
+
+	pub fn unfreeze_tip(&self) -> Result<TipFreezeStatus, Error> {
+		w(&self.chain)?
+			.unfreeze_tip()
+			.map_err(|e| ErrorKind::Internal(format!("Unable to unfreeze the tip, {}", e)).into())
+	}
+
+	/// Returns the freeze state of the chain tip.
+	///
+	/// # Returns
+	/// * Result Containing:
+	/// * A [`TipFreezeStatus`](../grin_chain/types/struct.TipFreezeStatus.html)
+	/// * or [`Error`](struct.Error.html) if an error is encountered.
+	///
+
+	pub fn get_tip_freeze_status(&self) -> Result<TipFreezeStatus, Error> {
+		w(&self.chain)?.tip_freeze_status().map_err(|e| {
+			ErrorKind::Internal(format!("Unable to read the chain head, {}", e)).into()
+		})
+	}
+
+	/// Returns the blocks mined by this node (with the stratum server or the test miner)
+	/// between the heights, the lowest first. The orphaned blocks, reorged out of the
+	/// current chain, are included and marked.
+	///
+	/// # Arguments
+	/// * `start_height` - min height of the blocks, 0 if not set.
+	/// * `end_height` - max height of the blocks, the chain head if not set.
+	///
+	/// # Returns
+	/// * Result Containing:
+	/// * A vector of [`MinedBlockPrintable`](types/struct.MinedBlockPrintable.html)
+	/// * or [`Error`](struct.Error.html) if an error is encountered.
+	///
+
+	pub fn get_mined_blocks(
+		&self,
+		start_height: Option<u64>,
+		end_height: Option<u64>,
+	) -> Result<Vec<MinedBlockPrintable>, Error> {
+		let chain = w(&self.chain)?;
+		let head = chain
+			.head()
+			.map_err(|e| ErrorKind::Internal(format!("can't get tip: {}", e)))?;
+		let blocks = chain
+			.get_mined_blocks(start_height.unwrap_or(0), end_height.unwrap_or(head.height))
+			.map_err(|e| ErrorKind::Internal(format!("Unable to read the mined blocks, {}", e)))?;
+		Ok(blocks
+			.iter()
+			.map(|(block, on_chain)| {
+				MinedBlockPrintable::from_mined_block(block, *on_chain, head.height)
+			})
+			.collect())
+	}
+
+	/// Returns the transactions in the Dandelion stempool, with the time their embargo
+	/// timers expire. A transaction the network doesn't fluff is fluffed by this node
+	/// once its embargo expires.
+	///
+	/// # Returns
+	/// * Result Containing:
+	/// * A [`StemPoolStatus`](../grin_pool/dandelion/struct.StemPoolStatus.html)
+	/// * or [`Error`](struct.Error.html) if an error is encountered.
+	///
+
+	pub fn get_stempool_status(&self) -> Result<StemPoolStatus, Error> {
+		Ok(w(&self.tx_pool)?.stempool_status())
+	}
+
+	/// Returns the node status together with the connected peers, so a monitoring
+	/// client gets both in one round trip.
+	///
+	/// # Returns
+	/// * Result Containing:
+	/// * A [`StatusAndPeers`](types/struct.StatusAndPeers.html)
+	/// * or [`Error`](struct.Error.html) if an error is encountered.
+	///
+
+	pub fn get_status_and_peers(&self) -> Result<StatusAndPeers, Error> {
+		Ok(StatusAndPeers {
+			status: self.get_status()?,
+			connected_peers: self.get_connected_peers()?,
+		})
+	}
+
+	/// Returns the full node state: the status, the connected peers with their diversity
+	/// report, the stempool and the tip freeze state.
+	///
+	/// # Returns
+	/// * Result Containing:
+	/// * A [`NodeState`](types/struct.NodeState.html)
+	/// * or [`Error`](struct.Error.html) if an error is encountered.
+	///
+
+	pub fn get_node_state(&self) -> Result<NodeState, Error> {
+		Ok(NodeState {
+			status: self.get_status()?,
+			connected_peers: self.get_connected_peers()?,
+			peer_diversity: self.get_peer_diversity()?,
+			stempool: self.get_stempool_status()?,
+			tip_freeze: self.get_tip_freeze_status()?,
+		})
+	}
+
+	fn rotate_secret(
+		credentials: &BasicAuthCredentials,
+		grace_period_secs: Option<u64>,
+	) -> Result<ApiSecretRotation, Error> {
+		let grace_period_secs = grace_period_secs.unwrap_or(DEFAULT_SECRET_GRACE_PERIOD);
+		if grace_period_secs > MAX_SECRET_GRACE_PERIOD {
+			return Err(ErrorKind::Argument(format!(
+				"Grace period {} seconds is longer than {} seconds",
+				grace_period_secs, MAX_SECRET_GRACE_PERIOD
+			))
+			.into());
+		}
+		let valid_until = credentials.rotate(grace_period_secs as i64)?;
+		info!(
+			"API secret at {} is rotated, previous secret is valid for {} seconds",
+			credentials.secret_path(),
+			grace_period_secs
+		);
+		Ok(ApiSecretRotation {
+			secret_path: credentials.secret_path().to_string(),
+			previous_secret_valid_until: Utc.timestamp(valid_until, 0).to_rfc3339(),
+		})
+	}
 }
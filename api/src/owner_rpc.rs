@@ -14,17 +14,27 @@
 
 //! JSON-RPC Stub generation for the Owner API
 
+use crate::audit::AuditLogPage;
+use crate::chain::watch::{WatchEventPage, WatchFilterInfo};
+use crate::chain::{BlockRejection, ReorgRecord, TipFreezeStatus};
 use crate::owner::Owner;
 use crate::p2p::PeerData;
+use crate::pool::StemPoolStatus;
 use crate::rest::ErrorKind;
-use crate::types::Status;
-use grin_p2p::types::PeerInfoDisplayLegacy;
+use crate::types::{
+	ApiSecretRotation, BlockValidation, BuildInfo, IntegrityKernelStatus, MessageSimulation,
+	MinedBlockPrintable, NodeState, Status, StatusAndPeers,
+};
+use grin_p2p::libp2p_connection::{GossipTraceEntry, RelayDenyList};
+use grin_p2p::types::{PeerDiversityReport, PeerInfoDisplayLegacy, PeerStoreStats};
 use std::net::SocketAddr;
 
 /// Public definition used to generate Node jsonrpc api.
 /// * When running `grin` with defaults, the V2 api is available at
 /// `localhost:3413/v2/owner`
 /// * The endpoint only supports POST operations, with the json-rpc request as the body
+/// * The V3 api at `localhost:3413/v3/owner` has the same methods and accepts a batch as
+/// the body, an array of up to 100 requests, the replies are returned as an array in the same order
 #[easy_jsonrpc_mw::rpc]
 pub trait OwnerRpc: Sync + Send {
 	/**
@@ -132,6 +142,36 @@ pub trait OwnerRpc: Sync + Send {
 	 */
 	fn compact_chain(&self) -> Result<(), ErrorKind>;
 
+	/**
+	Networked version of [Owner::rebuild_kernel_index](struct.Owner.html#method.rebuild_kernel_index).
+
+	# Json rpc example
+
+	```
+	# grin_api::doctest_helper_json_rpc_owner_assert_response!(
+	# r#"
+	{
+		"jsonrpc": "2.0",
+		"method": "rebuild_kernel_index",
+		"params": [],
+		"id": 1
+	}
+	# "#
+	# ,
+	# r#"
+	{
+		"id": 1,
+		"jsonrpc": "2.0",
+		"result": {
+			"Ok": null
+		}
+	}
+	# "#
+	# );
+	```
+	 */
+	fn rebuild_kernel_index(&self) -> Result<(), ErrorKind>;
+
 	/**
 	Networked version of [Owner::get_peers](struct.Owner.html#method.get_peers).
 
@@ -163,6 +203,7 @@ pub trait OwnerRpc: Sync + Send {
 				"flags": "Defunct",
 				"last_banned": 0,
 				"last_connected": 1570129317,
+				"latency_ms": null,
 				"user_agent": "MW/Grin 2.0.0"
 			}
 			]
@@ -293,6 +334,88 @@ pub trait OwnerRpc: Sync + Send {
 	 */
 	fn get_connected_peers(&self) -> Result<Vec<PeerInfoDisplayLegacy>, ErrorKind>;
 
+	/**
+	Networked version of [Owner::get_peer_diversity](struct.Owner.html#method.get_peer_diversity).
+
+	# Json rpc example
+
+	```
+	# grin_api::doctest_helper_json_rpc_owner_assert_response!(
+	# r#"
+	{
+		"jsonrpc": "2.0",
+		"method": "get_peer_diversity",
+		"params": [],
+		"id": 1
+	}
+	# "#
+	# ,
+	# r#"
+	{
+		"id": 1,
+		"jsonrpc": "2.0",
+		"result": {
+			"Ok": {
+			"peer_count": 8,
+			"latency_under_50ms": 1,
+			"latency_50_150ms": 4,
+			"latency_150_300ms": 2,
+			"latency_over_300ms": 0,
+			"latency_unknown": 1,
+			"ipv4": 6,
+			"ipv6": 1,
+			"onion": 1,
+			"distinct_networks": 6,
+			"user_agents": {
+				"MW/MWC 4.4.0": 5,
+				"MW/MWC 5.0.0": 3
+			}
+			}
+		}
+	}
+	# "#
+	# );
+	```
+	*/
+	fn get_peer_diversity(&self) -> Result<PeerDiversityReport, ErrorKind>;
+
+	/**
+	Networked version of [Owner::get_peer_store_stats](struct.Owner.html#method.get_peer_store_stats).
+
+	# Json rpc example
+
+	```
+	# grin_api::doctest_helper_json_rpc_owner_assert_response!(
+	# r#"
+	{
+		"jsonrpc": "2.0",
+		"method": "get_peer_store_stats",
+		"params": [],
+		"id": 1
+	}
+	# "#
+	# ,
+	# r#"
+	{
+		"id": 1,
+		"jsonrpc": "2.0",
+		"result": {
+			"Ok": {
+			"size": 2841,
+			"max_size": 10000,
+			"evicted_expired": 112,
+			"evicted_unconnected": 5307,
+			"evicted_over_cap": 0,
+			"last_pruned": 1760519512
+			}
+		}
+	}
+	# "#
+	# );
+	```
+	*/
+	fn get_peer_store_stats(&self) -> Result<PeerStoreStats, ErrorKind>;
+
 	/**
 	Networked version of [Owner::ban_peer](struct.Owner.html#method.ban_peer).
 
@@ -352,35 +475,844 @@ pub trait OwnerRpc: Sync + Send {
 	```
 	 */
 	fn unban_peer(&self, peer_addr: SocketAddr) -> Result<(), ErrorKind>;
-}
 
-impl OwnerRpc for Owner {
-	fn get_status(&self) -> Result<Status, ErrorKind> {
-		Owner::get_status(self).map_err(|e| e.kind().clone())
+	/**
+	Networked version of [Owner::check_integrity_kernel](struct.Owner.html#method.check_integrity_kernel).
+
+	# Json rpc example
+
+	```
+	# grin_api::doctest_helper_json_rpc_owner_assert_response!(
+	# r#"
+	{
+		"jsonrpc": "2.0",
+		"method": "check_integrity_kernel",
+		"params": ["08b3b8b83c622f630141a66c9cad96e19c78f745e4e2ddea85439f05d14a404640"],
+		"id": 1
 	}
+	# "#
+	# ,
+	# r#"
+	{
+		"id": 1,
+		"jsonrpc": "2.0",
+		"result": {
+			"Ok": {
+			"excess": "08b3b8b83c622f630141a66c9cad96e19c78f745e4e2ddea85439f05d14a404640",
+			"height": 1083120,
+			"fee": 10000000,
+			"min_fee": 10000000,
+			"valid_until_height": 1084563,
+			"valid": true,
+			"reason": null
+			}
+		}
+	}
+	# "#
+	# );
+	```
+	*/
+	fn check_integrity_kernel(&self, excess: String) -> Result<IntegrityKernelStatus, ErrorKind>;
 
-	fn validate_chain(&self) -> Result<(), ErrorKind> {
-		Owner::validate_chain(self).map_err(|e| e.kind().clone())
+	/**
+	Networked version of [Owner::validate_block](struct.Owner.html#method.validate_block).
+
+	# Json rpc example
+
+	```
+	# grin_api::doctest_helper_json_rpc_owner_assert_response!(
+	# r#"
+	{
+		"jsonrpc": "2.0",
+		"method": "validate_block",
+		"params": ["000200000000001086f10000000068ef6290000001e16cb374e38c979c353a0aaffbf5b939da7688f69ad99efda6c112ea9bcc37a75d63c54c1d4cb361aaff80b41a345d60265c504975f616f9f40e31f62a8db76b342879f23290c81e8678dc8386fbdd20f04e270c3b049565483cb68beb32746b24dc0fde3c21426f811fad588a44fa11f964126e785403a7d970beed98f5f23e79915f04fbc867b763168f67afcc0601f4da4f275e5d6d97c5e867534c8b487b16f281d9ec4867b08f0a639219a12a58fbd9876d0669e11f8d4f9f3f64b90886113023c8a2a25f7d7ca5f6314070fbef4f50846ce9e8a5706ffce2e6c00890b04cdb8eed52319c6423d33b2ebd26137c69811afb2b686d01ec6bd8bb1c169c17c7e5514b1383c969b0930cac2160e13512cbaed2f0b4e6ae7662cdbc569dfab048fbbd913e814f8e263966bf851947cc6e175788fffe278b81e5337972b660995953b87a76a44fe18c4bab142f9d8a1fed43a0c78955e4a54d247cec0b3a7dfd5e7d6d47826977ec4088c4560e63d22e2426755df2263d327029a1720a511e1fc09ee44a0738ed3830d09e998b4bfb3d8b775831aab7e5a3843323663f1cb6f267e86b90e9a3865a4e89a2451ab6ebdfa244103c11088809ee39c38f306b12e484a82764b58a2c0a02a3685f789a8e3a4226b45545908bcc0f5ee8c172387db29a3ca85da66472724b0470bd8108287b8cbc1b93e81f422bdc6f5d344ce6a698b36d6e00ee380b083cd2c733b27984a0a6eabbf8a432fbc02cc69bf1d8b5e3f39eeabe88602553efcd60ef15fed60c9a70b390daf6df200a1f15a091c527bb2938a57526ed998b4b78d31f04b07f8b0736fcffca4418c4c154d352451af533521d6d6ee2da531375e16f190c235a3c9c80382da66430d996efa342f7b13f1447258500b0f3fc2cc67e01c69a41659ab80f0a8379de512e7d1f7cd92c8f9aeef400b63b112a794f8d725e520b90d23acf1206ef1d3e5f5f2ebbab386d332aa6419a48aba4ed5a9ae5ca41fc1f41494d4d1319ab5d36b0934cf1cf9e2da7d229b43452f544cebd8813f3cde9187634e553e4098c9dc4b80b728afb7d174abef9e4be53ab342dd3e6a0ee91a9ed521744a47cd1fd59df1a5009c85e41441ea7fc06a626db9a7ecc876673acc8536ed450af52e377421a04fe5653b54d129ef7d4ce1c879f40bfc48da4e43695be2057d2c50d26733703d12f31f8c9f6d42cd86b943ca1632a8a75adf0b5ac5182695bf1858bf910352d938f20737f3df4983c1d97f402fa4135013b20d791bc36e14f8adfc067064291340c3ef0262574229ae19b2ba93b0f180193c0589f248533cfde1f424092ff0e94256619e372da0b522fce25887b67aead6f1addb8a7e601032c73009cbff9d66f7dfc263af50d57907eab90f07ea788dd661843770f9dd23b7e1f67"],
+		"id": 1
+	}
+	# "#
+	# ,
+	# r#"
+	{
+		"id": 1,
+		"jsonrpc": "2.0",
+		"result": {
+			"Ok": {
+			"hash": "0000036bf1f74ca7a6d5c4fdc1a8e2b76b8e4c57d1f5d3e0e3c2f4a8b9d6e1f2",
+			"height": 1083121,
+			"valid": false,
+			"error_kind": "InvalidPow",
+			"error": "Invalid PoW"
+			}
+		}
 	}
+	# "#
+	# );
+	```
+	*/
+	fn validate_block(&self, serialized_block: String) -> Result<BlockValidation, ErrorKind>;
 
-	fn compact_chain(&self) -> Result<(), ErrorKind> {
-		Owner::compact_chain(self).map_err(|e| e.kind().clone())
+	/**
+	Networked version of [Owner::get_rejected_blocks](struct.Owner.html#method.get_rejected_blocks).
+
+	# Json rpc example
+
+	```
+	# grin_api::doctest_helper_json_rpc_owner_assert_response!(
+	# r#"
+	{
+		"jsonrpc": "2.0",
+		"method": "get_rejected_blocks",
+		"params": [10],
+		"id": 1
+	}
+	# "#
+	# ,
+	# r#"
+	{
+		"id": 1,
+		"jsonrpc": "2.0",
+		"result": {
+			"Ok": [
+			{
+				"hash": "0000036bf1f74ca7a6d5c4fdc1a8e2b76b8e4c57d1f5d3e0e3c2f4a8b9d6e1f2",
+				"height": 374275,
+				"prev_hash": "000001e16cb374e38c979c353a0aaffbf5b939da7688f69ad99efda6c112ea9b",
+				"stage": "utxo",
+				"error": "Invalid Root",
+				"rejected_at": 1570115790,
+				"expected_roots": {
+					"output_root": "5b9c1b2e4f7a0d3c6e9f2a5b8c1d4e7f0a3b6c9d2e5f8a1b4c7d0e3f6a9b2c5d",
+					"bitmap_root": "a1e4c7d0f3b6a9c2e5f8b1d4a7c0e3f6b9d2a5c8e1f4b7d0a3c6e9f2b5d8a1c4",
+					"rproof_root": "3491b8c46a3919df637a636ca72824377f89c4967dcfe4857379a4a82b510069",
+					"kernel_root": "e17920c0e456a6feebf19e24a46f510a85f21cb60e81012f843c00fe2c4cad6e",
+					"output_mmr_size": 4091745,
+					"kernel_mmr_size": 4091739
+				},
+				"dump_dir": null
+			}
+			]
+		}
 	}
+	# "#
+	# );
+	```
+	*/
+	fn get_rejected_blocks(&self, limit: Option<usize>) -> Result<Vec<BlockRejection>, ErrorKind>;
 
-	fn get_peers(&self, addr: Option<SocketAddr>) -> Result<Vec<PeerData>, ErrorKind> {
-		Owner::get_peers(self, addr).map_err(|e| e.kind().clone())
+	/**
+	Networked version of [Owner::get_reorg_history](struct.Owner.html#method.get_reorg_history).
+
+	# Json rpc example
+
+	```
+	# grin_api::doctest_helper_json_rpc_owner_assert_response!(
+	# r#"
+	{
+		"jsonrpc": "2.0",
+		"method": "get_reorg_history",
+		"params": [10],
+		"id": 1
+	}
+	# "#
+	# ,
+	# r#"
+	{
+		"id": 1,
+		"jsonrpc": "2.0",
+		"result": {
+			"Ok": [
+				{
+					"depth": 2,
+					"old_tip_hash": "0c7ba3ea8a3a4b9b5e04d0dc3d59a1e6ad3e7a5e0f9b4f1de5f3a2c8dbd61f7c",
+					"old_tip_height": 1204,
+					"new_tip_hash": "1a5b4c4d8c72f9d3e0bb1f2c31c7b9e0a7d2b9f4c5e6a3d8b1f0e9c2d7a6b5c4",
+					"new_tip_height": 1205,
+					"fork_point_hash": "09d5ff5a4b2bf6a4b2b8d12cd0a3c7fa64f0b1df1d6b9b0ce7c4f6a1b2e3d4c5",
+					"fork_point_height": 1202,
+					"timestamp": 1602769481
+				}
+			]
+		}
 	}
+	# "#
+	# );
+	```
+	 */
+	fn get_reorg_history(&self, limit: Option<usize>) -> Result<Vec<ReorgRecord>, ErrorKind>;
 
-	fn get_connected_peers(&self) -> Result<Vec<PeerInfoDisplayLegacy>, ErrorKind> {
-		Owner::get_connected_peers(self).map_err(|e| e.kind().clone())
+	/**
+	Networked version of [Owner::simulate_message](struct.Owner.html#method.simulate_message).
+	*/
+	fn simulate_message(
+		&self,
+		peer: String,
+		message_hex: String,
+	) -> Result<MessageSimulation, ErrorKind>;
+
+	/**
+	Networked version of [Owner::get_gossip_trace](struct.Owner.html#method.get_gossip_trace).
+	*/
+	fn get_gossip_trace(
+		&self,
+		topic: Option<String>,
+		peer_id: Option<String>,
+		limit: Option<usize>,
+	) -> Result<Vec<GossipTraceEntry>, ErrorKind>;
+
+	/**
+	Networked version of [Owner::get_relay_deny_list](struct.Owner.html#method.get_relay_deny_list).
+
+	# Json rpc example
+
+	```
+	# grin_api::doctest_helper_json_rpc_owner_assert_response!(
+	# r#"
+	{
+		"jsonrpc": "2.0",
+		"method": "get_relay_deny_list",
+		"params": [],
+		"id": 1
+	}
+	# "#
+	# ,
+	# r#"
+	{
+		"id": 1,
+		"jsonrpc": "2.0",
+		"result": {
+			"Ok": {
+			"topics": [
+				"SwapMarketplace"
+			],
+			"message_id_patterns": [
+				"8a3f*"
+			]
+			}
+		}
 	}
+	# "#
+	# );
+	```
+	*/
+	fn get_relay_deny_list(&self) -> Result<RelayDenyList, ErrorKind>;
 
-	fn ban_peer(&self, addr: SocketAddr) -> Result<(), ErrorKind> {
-		Owner::ban_peer(self, addr).map_err(|e| e.kind().clone())
+	/**
+	Networked version of [Owner::set_relay_deny_list](struct.Owner.html#method.set_relay_deny_list).
+
+	# Json rpc example
+
+	```
+	# grin_api::doctest_helper_json_rpc_owner_assert_response!(
+	# r#"
+	{
+		"jsonrpc": "2.0",
+		"method": "set_relay_deny_list",
+		"params": [{"topics": ["SwapMarketplace"], "message_id_patterns": ["8a3f*"]}],
+		"id": 1
+	}
+	# "#
+	# ,
+	# r#"
+	{
+		"id": 1,
+		"jsonrpc": "2.0",
+		"result": {
+			"Ok": null
+		}
 	}
+	# "#
+	# );
+	```
+	*/
+	fn set_relay_deny_list(&self, deny_list: RelayDenyList) -> Result<(), ErrorKind>;
 
-	fn unban_peer(&self, addr: SocketAddr) -> Result<(), ErrorKind> {
-		Owner::unban_peer(self, addr).map_err(|e| e.kind().clone())
+	/**
+	Networked version of [Owner::rotate_api_secret](struct.Owner.html#method.rotate_api_secret).
+	*/
+	fn rotate_api_secret(
+		&self,
+		grace_period_secs: Option<u64>,
+	) -> Result<ApiSecretRotation, ErrorKind>;
+
+	/**
+	Networked version of [Owner::rotate_foreign_api_secret](struct.Owner.html#method.rotate_foreign_api_secret).
+	*/
+	fn rotate_foreign_api_secret(
+		&self,
+		grace_period_secs: Option<u64>,
+	) -> Result<ApiSecretRotation, ErrorKind>;
+
+	/**
+	Networked version of [Owner::get_audit_log](struct.Owner.html#method.get_audit_log).
+	*/
+	fn get_audit_log(
+		&self,
+		start_index: Option<u64>,
+		method: Option<String>,
+		limit: Option<usize>,
+	) -> Result<AuditLogPage, ErrorKind>;
+
+	/**
+	Networked version of [Owner::get_build_info](struct.Owner.html#method.get_build_info).
+
+	# Json rpc example
+
+	```
+	# grin_api::doctest_helper_json_rpc_owner_assert_response!(
+	# r#"
+	{
+		"jsonrpc": "2.0",
+		"method": "get_build_info",
+		"params": [],
+		"id": 1
+	}
+	# "#
+	# ,
+	# r#"
+	{
+		"id": 1,
+		"jsonrpc": "2.0",
+		"result": {
+			"Ok": {
+			"version": "4.4.0",
+			"git_commit": "8bade45f3c1e6b0a5d2e4f7a9c3b1d0e2f4a6c8b",
+			"git_dirty": false,
+			"rustc_version": "rustc 1.59.0 (9d1b2106e 2022-02-23)",
+			"target": "x86_64-unknown-linux-gnu",
+			"profile": "release",
+			"features": "DEFAULT",
+			"build_time": "Thu, 15 Oct 2026 09:00:00 +0000",
+			"reproducible": true,
+			"reproducibility_hash": "3c8e2d1f0a9b8c7d6e5f4a3b2c1d0e9f8a7b6c5d4e3f2a1b0c9d8e7f6a5b4c3d"
+			}
+		}
+	}
+	# "#
+	# );
+	```
+	*/
+	fn get_build_info(&self) -> Result<BuildInfo, ErrorKind>;
+
+	/**
+	Networked version of [Owner::add_watch_filter](struct.Owner.html#method.add_watch_filter).
+	*/
+	fn add_watch_filter(
+		&self,
+		name: String,
+		commitments: Option<Vec<String>>,
+		bloom_filter: Option<String>,
+		bloom_hashes: Option<u32>,
+	) -> Result<WatchFilterInfo, ErrorKind>;
+
+	/**
+	Networked version of [Owner::delete_watch_filter](struct.Owner.html#method.delete_watch_filter).
+	*/
+	fn delete_watch_filter(&self, id: u64) -> Result<(), ErrorKind>;
+
+	/**
+	Networked version of [Owner::get_watch_filters](struct.Owner.html#method.get_watch_filters).
+	*/
+	fn get_watch_filters(&self) -> Result<Vec<WatchFilterInfo>, ErrorKind>;
+
+	/**
+	Networked version of [Owner::get_watch_events](struct.Owner.html#method.get_watch_events).
+	*/
+	fn get_watch_events(
+		&self,
+		id: u64,
+		cursor: Option<u64>,
+		limit: Option<usize>,
+	) -> Result<WatchEventPage, ErrorKind>;
+
+	/**
+	Networked version of [Owner::freeze_tip](struct.Owner.html#method.freeze_tip).
+
+	# Json rpc example
+
+	```
+	# grin_api::doctest_helper_json_rpc_owner_assert_response!(
+	# r#"
+	{
+		"jsonrpc": "2.0",
+		"method": "freeze_tip",
+		"params": [600],
+		"id": 1
+	}
+	# "#
+	# ,
+	# r#"
+	{
+		"id": 1,
+		"jsonrpc": "2.0",
+		"result": {
+			"Ok": {
+			"frozen": true,
+			"height": 374274,
+			"hash": "000001e16cb374e38c979c353a0aaffbf5b939da7688f69ad99efda6c112ea9b",
+			"frozen_until": 1634122236,
+			"buffered_blocks": 0
+			}
+		}
+	}
+	# "#
+	# );
+	```
+	*/
+	fn freeze_tip(&self, duration: u64) -> Result<TipFreezeStatus, ErrorKind>;
+
+	/**
+	Networked version of [Owner::unfreeze_tip](struct.Owner.html#method.unfreeze_tip).
+
+	# Json rpc example
+
+	```
+	# grin_api::doctest_helper_json_rpc_owner_assert_response!(
+	# r#"
+	{
+		"jsonrpc": "2.0",
+		"method": "unfreeze_tip",
+		"params": [],
+		"id": 1
+	}
+	# "#
+	# ,
+	# r#"
+	{
+		"id": 1,
+		"jsonrpc": "2.0",
+		"result": {
+			"Ok": {
+			"frozen": false,
+			"height": 374274,
+			"hash": "000001e16cb374e38c979c353a0aaffbf5b939da7688f69ad99efda6c112ea9b",
+			"frozen_until": null,
+			"buffered_blocks": 0
+			}
+		}
+	}
+	# "#
+	# );
+	```
+	*/
+	fn unfreeze_tip(&self) -> Result<TipFreezeStatus, ErrorKind>;
+
+	/**
+	Networked version of [Owner::get_tip_freeze_status](struct.Owner.html#method.get_tip_freeze_status).
+
+	# Json rpc example
+
+	```
+	# grin_api::doctest_helper_json_rpc_owner_assert_response!(
+	# r#"
+	{
+		"jsonrpc": "2.0",
+		"method": "get_tip_freeze_status",
+		"params": [],
+		"id": 1
+	}
+	# "#
+	# ,
+	# r#"
+	{
+		"id": 1,
+		"jsonrpc": "2.0",
+		"result": {
+			"Ok": {
+			"frozen": false,
+			"height": 374274,
+			"hash": "000001e16cb374e38c979c353a0aaffbf5b939da7688f69ad99efda6c112ea9b",
+			"frozen_until": null,
+			"buffered_blocks": 0
+			}
+		}
+	}
+	# "#
+	# );
+	```
+	*/
+	fn get_tip_freeze_status(&self) -> Result<TipFreezeStatus, ErrorKind>;
+
+	/**
+	Networked version of [Owner::get_mined_blocks](struct.Owner.html#method.get_mined_blocks).
+	*/
+	fn get_mined_blocks(
+		&self,
+		start_height: Option<u64>,
+		end_height: Option<u64>,
+	) -> Result<Vec<MinedBlockPrintable>, ErrorKind>;
+
+	/**
+	Networked version of [Owner::get_stempool_status](struct.Owner.html#method.get_stempool_status).
+
+	# Json rpc example
+
+	```
+	# grin_api::doctest_helper_json_rpc_owner_assert_response!(
+	# r#"
+	{
+		"jsonrpc": "2.0",
+		"method": "get_stempool_status",
+		"params": [],
+		"id": 1
+	}
+	# "#
+	# ,
+	# r#"
+	{
+		"id": 1,
+		"jsonrpc": "2.0",
+		"result": {
+			"Ok": {
+			"count": 1,
+			"total_weight": 46,
+			"next_embargo_expiry": "2021-10-13T10:53:56Z",
+			"entries": [
+				{
+					"excess": "08b3b8b83c622f630141a66c9cad96e19c78f745e4e2ddea85439f05d14a404640",
+					"weight": 46,
+					"fee": 7000000,
+					"tx_at": "2021-10-13T10:50:56Z",
+					"embargo_expiry": "2021-10-13T10:53:56Z"
+				}
+			]
+			}
+		}
+	}
+	# "#
+	# );
+	```
+	*/
+	fn get_stempool_status(&self) -> Result<StemPoolStatus, ErrorKind>;
+
+	/**
+	Networked version of [Owner::get_status_and_peers](struct.Owner.html#method.get_status_and_peers).
+
+	# Json rpc example
+
+	```
+	# grin_api::doctest_helper_json_rpc_owner_assert_response!(
+	# r#"
+	{
+		"jsonrpc": "2.0",
+		"method": "get_status_and_peers",
+		"params": [],
+		"id": 1
+	}
+	# "#
+	# ,
+	# r#"
+	{
+		"id": 1,
+		"jsonrpc": "2.0",
+		"result": {
+			"Ok": {
+			"status": {
+				"protocol_version": 3,
+				"user_agent": "MW/MWC 4.4.0",
+				"connections": 2,
+				"tip": {
+					"height": 374274,
+					"last_block_pushed": "000001e16cb374e38c979c353a0aaffbf5b939da7688f69ad99efda6c112ea9b",
+					"prev_block_to_last": "0000029f51bacee81c49a27b4bc9c6c446e03183867c922890f90bb17108d89f",
+					"total_difficulty": 1133954621205750
+				},
+				"sync_status": "no_sync"
+			},
+			"connected_peers": [
+				{
+					"capabilities": {
+						"bits": 15
+					},
+					"user_agent": "MW/MWC 4.4.0",
+					"version": 3,
+					"addr": "35.176.195.242:3414",
+					"direction": "Outbound",
+					"total_difficulty": 1133954621205750,
+					"height": 374274
+				},
+				{
+					"capabilities": {
+						"bits": 15
+					},
+					"user_agent": "MW/MWC 4.4.0",
+					"version": 3,
+					"addr": "47.97.198.21:3414",
+					"direction": "Outbound",
+					"total_difficulty": 1133954621205750,
+					"height": 374274
+				}
+			]
+			}
+		}
+	}
+	# "#
+	# );
+	```
+	*/
+	fn get_status_and_peers(&self) -> Result<StatusAndPeers, ErrorKind>;
+
+	/**
+	Networked version of [Owner::get_node_state](struct.Owner.html#method.get_node_state).
+
+	# Json rpc example
+
+	```
+	# grin_api::doctest_helper_json_rpc_owner_assert_response!(
+	# r#"
+	{
+		"jsonrpc": "2.0",
+		"method": "get_node_state",
+		"params": [],
+		"id": 1
+	}
+	# "#
+	# ,
+	# r#"
+	{
+		"id": 1,
+		"jsonrpc": "2.0",
+		"result": {
+			"Ok": {
+			"status": {
+				"protocol_version": 3,
+				"user_agent": "MW/MWC 4.4.0",
+				"connections": 2,
+				"tip": {
+					"height": 374274,
+					"last_block_pushed": "000001e16cb374e38c979c353a0aaffbf5b939da7688f69ad99efda6c112ea9b",
+					"prev_block_to_last": "0000029f51bacee81c49a27b4bc9c6c446e03183867c922890f90bb17108d89f",
+					"total_difficulty": 1133954621205750
+				},
+				"sync_status": "no_sync"
+			},
+			"connected_peers": [
+				{
+					"capabilities": {
+						"bits": 15
+					},
+					"user_agent": "MW/MWC 4.4.0",
+					"version": 3,
+					"addr": "35.176.195.242:3414",
+					"direction": "Outbound",
+					"total_difficulty": 1133954621205750,
+					"height": 374274
+				},
+				{
+					"capabilities": {
+						"bits": 15
+					},
+					"user_agent": "MW/MWC 4.4.0",
+					"version": 3,
+					"addr": "47.97.198.21:3414",
+					"direction": "Outbound",
+					"total_difficulty": 1133954621205750,
+					"height": 374274
+				}
+			],
+			"peer_diversity": {
+				"peer_count": 2,
+				"latency_under_50ms": 0,
+				"latency_50_150ms": 2,
+				"latency_150_300ms": 0,
+				"latency_over_300ms": 0,
+				"latency_unknown": 0,
+				"ipv4": 2,
+				"ipv6": 0,
+				"onion": 0,
+				"distinct_networks": 2,
+				"user_agents": {
+					"MW/MWC 4.4.0": 2
+				}
+			},
+			"stempool": {
+				"count": 0,
+				"total_weight": 0,
+				"next_embargo_expiry": null,
+				"entries": []
+			},
+			"tip_freeze": {
+				"frozen": false,
+				"height": 374274,
+				"hash": "000001e16cb374e38c979c353a0aaffbf5b939da7688f69ad99efda6c112ea9b",
+				"frozen_until": null,
+				"buffered_blocks": 0
+			}
+			}
+		}
+	}
+	# "#
+	# );
+	```
+	*/
+	fn get_node_state(&self) -> Result<NodeState, ErrorKind>;
+}
+
+impl OwnerRpc for Owner {
+	fn get_status(&self) -> Result<Status, ErrorKind> {
+		Owner::get_status(self).map_err(|e| e.kind().clone())
+	}
+
+	fn validate_chain(&self) -> Result<(), ErrorKind> {
+		Owner::validate_chain(self).map_err(|e| e.kind().clone())
+	}
+
+	fn compact_chain(&self) -> Result<(), ErrorKind> {
+		Owner::compact_chain(self).map_err(|e| e.kind().clone())
+	}
+
+	fn rebuild_kernel_index(&self) -> Result<(), ErrorKind> {
+		Owner::rebuild_kernel_index(self).map_err(|e| e.kind().clone())
+	}
+
+	fn get_peers(&self, addr: Option<SocketAddr>) -> Result<Vec<PeerData>, ErrorKind> {
+		Owner::get_peers(self, addr).map_err(|e| e.kind().clone())
+	}
+
+	fn get_connected_peers(&self) -> Result<Vec<PeerInfoDisplayLegacy>, ErrorKind> {
+		Owner::get_connected_peers(self).map_err(|e| e.kind().clone())
+	}
+
+	fn get_peer_diversity(&self) -> Result<PeerDiversityReport, ErrorKind> {
+		Owner::get_peer_diversity(self).map_err(|e| e.kind().clone())
+	}
+
+	fn get_peer_store_stats(&self) -> Result<PeerStoreStats, ErrorKind> {
+		Owner::get_peer_store_stats(self).map_err(|e| e.kind().clone())
+	}
+
+	fn ban_peer(&self, addr: SocketAddr) -> Result<(), ErrorKind> {
+		Owner::ban_peer(self, addr).map_err(|e| e.kind().clone())
+	}
+
+	fn unban_peer(&self, addr: SocketAddr) -> Result<(), ErrorKind> {
+		Owner::unban_peer(self, addr).map_err(|e| e.kind().clone())
+	}
+
+	fn check_integrity_kernel(&self, excess: String) -> Result<IntegrityKernelStatus, ErrorKind> {
+		Owner::check_integrity_kernel(self, excess).map_err(|e| e.kind().clone())
+	}
+
+	fn validate_block(&self, serialized_block: String) -> Result<BlockValidation, ErrorKind> {
+		Owner::validate_block(self, serialized_block).map_err(|e| e.kind().clone())
+	}
+
+	fn get_rejected_blocks(&self, limit: Option<usize>) -> Result<Vec<BlockRejection>, ErrorKind> {
+		Owner::get_rejected_blocks(self, limit).map_err(|e| e.kind().clone())
+	}
+
+	fn get_reorg_history(&self, limit: Option<usize>) -> Result<Vec<ReorgRecord>, ErrorKind> {
+		Owner::get_reorg_history(self, limit).map_err(|e| e.kind().clone())
+	}
+
+	fn simulate_message(
+		&self,
+		peer: String,
+		message_hex: String,
+	) -> Result<MessageSimulation, ErrorKind> {
+		Owner::simulate_message(self, peer, message_hex).map_err(|e| e.kind().clone())
+	}
+
+	fn get_gossip_trace(
+		&self,
+		topic: Option<String>,
+		peer_id: Option<String>,
+		limit: Option<usize>,
+	) -> Result<Vec<GossipTraceEntry>, ErrorKind> {
+		Owner::get_gossip_trace(self, topic, peer_id, limit).map_err(|e| e.kind().clone())
+	}
+
+	fn get_relay_deny_list(&self) -> Result<RelayDenyList, ErrorKind> {
+		Owner::get_relay_deny_list(self).map_err(|e| e.kind().clone())
+	}
+
+	fn set_relay_deny_list(&self, deny_list: RelayDenyList) -> Result<(), ErrorKind> {
+		Owner::set_relay_deny_list(self, deny_list).map_err(|e| e.kind().clone())
+	}
+
+	fn rotate_api_secret(
+		&self,
+		grace_period_secs: Option<u64>,
+	) -> Result<ApiSecretRotation, ErrorKind> {
+		Owner::rotate_api_secret(self, grace_period_secs).map_err(|e| e.kind().clone())
+	}
+
+	fn rotate_foreign_api_secret(
+		&self,
+		grace_period_secs: Option<u64>,
+	) -> Result<ApiSecretRotation, ErrorKind> {
+		Owner::rotate_foreign_api_secret(self, grace_period_secs).map_err(|e| e.kind().clone())
+	}
+
+	fn get_audit_log(
+		&self,
+		start_index: Option<u64>,
+		method: Option<String>,
+		limit: Option<usize>,
+	) -> Result<AuditLogPage, ErrorKind> {
+		Owner::get_audit_log(self, start_index, method, limit).map_err(|e| e.kind().clone())
+	}
+
+	fn get_build_info(&self) -> Result<BuildInfo, ErrorKind> {
+		Owner::get_build_info(self).map_err(|e| e.kind().clone())
+	}
+
+	fn add_watch_filter(
+		&self,
+		name: String,
+		commitments: Option<Vec<String>>,
+		bloom_filter: Option<String>,
+		bloom_hashes: Option<u32>,
+	) -> Result<WatchFilterInfo, ErrorKind> {
+		Owner::add_watch_filter(self, name, commitments, bloom_filter, bloom_hashes)
+			.map_err(|e| e.kind().clone())
+	}
+
+	fn delete_watch_filter(&self, id: u64) -> Result<(), ErrorKind> {
+		Owner::delete_watch_filter(self, id).map_err(|e| e.kind().clone())
+	}
+
+	fn get_watch_filters(&self) -> Result<Vec<WatchFilterInfo>, ErrorKind> {
+		Owner::get_watch_filters(self).map_err(|e| e.kind().clone())
+	}
+
+	fn get_watch_events(
+		&self,
+		id: u64,
+		cursor: Option<u64>,
+		limit: Option<usize>,
+	) -> Result<WatchEventPage, ErrorKind> {
+		Owner::get_watch_events(self, id, cursor, limit).map_err(|e| e.kind().clone())
+	}
+
+	fn freeze_tip(&self, duration: u64) -> Result<TipFreezeStatus, ErrorKind> {
+		Owner::freeze_tip(self, duration).map_err(|e| e.kind().clone())
+	}
+
+	fn unfreeze_tip(&self) -> Result<TipFreezeStatus, ErrorKind> {
+		Owner::unfreeze_tip(self).map_err(|e| e.kind().clone())
+	}
+
+	fn get_tip_freeze_status(&self) -> Result<TipFreezeStatus, ErrorKind> {
+		Owner::get_tip_freeze_status(self).map_err(|e| e.kind().clone())
+	}
+
+	fn get_mined_blocks(
+		&self,
+		start_height: Option<u64>,
+		end_height: Option<u64>,
+	) -> Result<Vec<MinedBlockPrintable>, ErrorKind> {
+		Owner::get_mined_blocks(self, start_height, end_height).map_err(|e| e.kind().clone())
+	}
+
+	fn get_stempool_status(&self) -> Result<StemPoolStatus, ErrorKind> {
+		Owner::get_stempool_status(self).map_err(|e| e.kind().clone())
+	}
+
+	fn get_status_and_peers(&self) -> Result<StatusAndPeers, ErrorKind> {
+		Owner::get_status_and_peers(self).map_err(|e| e.kind().clone())
+	}
+
+	fn get_node_state(&self) -> Result<NodeState, ErrorKind> {
+		Owner::get_node_state(self).map_err(|e| e.kind().clone())
 	}
 }
 
@@ -0,0 +1,408 @@
+// Copyright 2021 The MWC Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Per IP rate limiting of the API requests. Every client IP has a budget of requests per
+//! minute and a separate, usually smaller, budget for the heavyweight requests like the
+//! txhashset and output range queries. Requests above the budget get `429 Too Many Requests`
+//! with the `Retry-After` header, a json-rpc batch that needs more than the whole budget gets
+//! `413 Payload Too Large`. IPv6 clients are counted by their /64 network. The same budgets
+//! apply to the gRPC clients and, by the peer id, to the libp2p node API requests.
+
+use crate::router::{Handler, HandlerObj, RemoteAddr, ResponseFuture};
+use crate::util::{Mutex, RwLock};
+use crate::web::response;
+use futures::future::ok;
+use hyper::header::RETRY_AFTER;
+use hyper::{Body, Request, Response, StatusCode};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::net::{IpAddr, Ipv6Addr};
+use std::sync::Arc;
+use std::time::Instant;
+
+/// Foreign json-rpc methods that use the heavyweight budget
+pub const HEAVY_FOREIGN_METHODS: &[&str] = &[
+	"get_blocks",
+	"get_outputs",
+	"get_outputs_in_range",
+	"get_unspent_outputs",
+	"get_unspent_outputs_cursor",
+	"get_pmmr_indices",
+	"get_kernel",
+	"get_kernel_status",
+	"get_chain_stats",
+	"get_coinbase_merkle_proof",
+	"get_kernel_merkle_proof",
+	"get_historical_output",
+];
+
+/// v1 URI prefixes that use the heavyweight budget
+pub const HEAVY_URI_PREFIXES: &[&str] = &["/v1/txhashset", "/v1/chain/outputs"];
+
+/// Json-rpc error code of the rate limited requests that are not served over HTTP
+pub const RATE_LIMITED_RPC_CODE: i64 = -32029;
+
+/// Max number of tracked clients. When the limit is reached the clients with the full
+/// budget are dropped, then the least recently seen ones.
+const MAX_TRACKED_CLIENTS: usize = 100_000;
+
+lazy_static! {
+	/// Rate limiter of the running API server, the foreign json-rpc handler uses it for the
+	/// heavyweight methods
+	static ref API_RATE_LIMITER: RwLock<Option<Arc<RateLimiter>>> = RwLock::new(None);
+}
+
+/// Set the rate limiter for the API handlers, None disables the rate limiting
+pub fn set_api_rate_limiter(limiter: Option<Arc<RateLimiter>>) {
+	*API_RATE_LIMITER.write() = limiter;
+}
+
+/// Rate limiter of the running API server
+pub fn get_api_rate_limiter() -> Option<Arc<RateLimiter>> {
+	API_RATE_LIMITER.read().clone()
+}
+
+/// Why the request is refused by the rate limiter
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RateLimited {
+	/// The client is over the budget, retry after that many seconds
+	RetryAfter(u64),
+	/// The request needs more than the whole budget, retrying doesn't help
+	TooLarge,
+}
+
+/// Rate limits, requests per minute for a single client IP. 0 means no limit.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RateLimitConfig {
+	/// All requests
+	pub requests_per_minute: u32,
+	/// Heavyweight requests, counted in addition to the general budget
+	pub heavy_requests_per_minute: u32,
+}
+
+// Client the budget is counted for
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+enum ClientKey {
+	Ip(IpAddr),
+	Peer(String),
+}
+
+// Token bucket, refilled continuously up to the capacity
+struct Bucket {
+	tokens: f64,
+	updated: Instant,
+}
+
+struct Budget {
+	per_minute: u32,
+	buckets: Mutex<HashMap<ClientKey, Bucket>>,
+}
+
+impl Budget {
+	fn new(per_minute: u32) -> Budget {
+		Budget {
+			per_minute,
+			buckets: Mutex::new(HashMap::new()),
+		}
+	}
+
+	// Take n tokens. Returns the seconds to wait if there are not enough tokens.
+	fn take(&self, ip: IpAddr, n: u32, now: Instant) -> Result<(), RateLimited> {
+		self.take_key(ClientKey::Ip(client_key(ip)), n, now)
+	}
+
+	fn take_key(&self, key: ClientKey, n: u32, now: Instant) -> Result<(), RateLimited> {
+		if self.per_minute == 0 || n == 0 {
+			return Ok(());
+		}
+		if n > self.per_minute {
+			return Err(RateLimited::TooLarge);
+		}
+		let capacity = self.per_minute as f64;
+		let rate = capacity / 60.0;
+		let mut buckets = self.buckets.lock();
+		if buckets.len() >= MAX_TRACKED_CLIENTS && !buckets.contains_key(&key) {
+			// Full buckets are the same as the missing ones
+			buckets.retain(|_, b| {
+				b.tokens + now.duration_since(b.updated).as_secs_f64() * rate < capacity
+			});
+			// Still full, dropping the least recently seen tenth
+			if buckets.len() >= MAX_TRACKED_CLIENTS {
+				let mut updated: Vec<Instant> = buckets.values().map(|b| b.updated).collect();
+				updated.sort_unstable();
+				let cutoff = updated[MAX_TRACKED_CLIENTS / 10];
+				buckets.retain(|_, b| b.updated > cutoff);
+			}
+		}
+		let bucket = buckets.entry(key).or_insert(Bucket {
+			tokens: capacity,
+			updated: now,
+		});
+		let elapsed = now.duration_since(bucket.updated).as_secs_f64();
+		bucket.tokens = (bucket.tokens + elapsed * rate).min(capacity);
+		bucket.updated = now;
+		let n = n as f64;
+		if bucket.tokens >= n {
+			bucket.tokens -= n;
+			Ok(())
+		} else {
+			let wait = ((n - bucket.tokens) / rate).ceil();
+			Err(RateLimited::RetryAfter(wait.max(1.0) as u64))
+		}
+	}
+}
+
+/// Per client IP rate limiter with the general and the heavyweight budgets
+pub struct RateLimiter {
+	general: Budget,
+	heavy: Budget,
+}
+
+impl RateLimiter {
+	pub fn new(config: RateLimitConfig) -> RateLimiter {
+		RateLimiter {
+			general: Budget::new(config.requests_per_minute),
+			heavy: Budget::new(config.heavy_requests_per_minute),
+		}
+	}
+
+	/// Count a request from the client. Returns the seconds to wait if the client is over the budget.
+	pub fn check(&self, ip: IpAddr) -> Result<(), RateLimited> {
+		self.general.take(ip, 1, Instant::now())
+	}
+
+	/// Count n heavyweight requests from the client. Returns the seconds to wait if the client
+	/// is over the heavyweight budget, TooLarge if n is over the whole budget.
+	pub fn check_heavy(&self, ip: IpAddr, n: u32) -> Result<(), RateLimited> {
+		self.heavy.take(ip, n, Instant::now())
+	}
+
+	/// Count a json-rpc request of the libp2p peer with n heavyweight calls in it
+	pub fn check_peer(&self, peer: &str, n: u32) -> Result<(), RateLimited> {
+		let now = Instant::now();
+		let key = ClientKey::Peer(peer.to_string());
+		self.general
+			.take_key(key.clone(), 1, now)
+			.and_then(|_| self.heavy.take_key(key, n, now))
+	}
+}
+
+// IPv6 clients get the whole /64 network, counting every address separately lets a single
+// client spray the addresses of its network.
+fn client_key(ip: IpAddr) -> IpAddr {
+	match ip {
+		IpAddr::V6(ip) => {
+			let segments = ip.segments();
+			IpAddr::V6(Ipv6Addr::new(
+				segments[0],
+				segments[1],
+				segments[2],
+				segments[3],
+				0,
+				0,
+				0,
+				0,
+			))
+		}
+		ip => ip,
+	}
+}
+
+/// Number of heavyweight calls in the foreign json-rpc request, a batch can have many of them
+pub fn heavy_foreign_calls(request: &Value) -> u32 {
+	match request {
+		Value::Array(calls) => calls.iter().map(heavy_foreign_calls).sum(),
+		call => {
+			let method = call.get("method").and_then(|m| m.as_str()).unwrap_or("");
+			HEAVY_FOREIGN_METHODS.contains(&method) as u32
+		}
+	}
+}
+
+/// Client IP of the request, if known
+pub fn client_ip(req: &Request<Body>) -> Option<IpAddr> {
+	req.extensions().get::<RemoteAddr>().map(|addr| addr.0.ip())
+}
+
+/// `429 Too Many Requests` response with the `Retry-After` header, or `413 Payload Too Large`
+/// for the requests that are over the whole budget
+pub fn too_many_requests(limited: RateLimited) -> Response<Body> {
+	match limited {
+		RateLimited::RetryAfter(retry_after) => Response::builder()
+			.status(StatusCode::TOO_MANY_REQUESTS)
+			.header(RETRY_AFTER, retry_after.to_string())
+			.body(Body::from("too many requests"))
+			.unwrap(),
+		RateLimited::TooLarge => Response::builder()
+			.status(StatusCode::PAYLOAD_TOO_LARGE)
+			.body(Body::from("request is over the rate limit budget"))
+			.unwrap(),
+	}
+}
+
+/// Json-rpc error response for the rate limited request, for the transports without HTTP status
+pub fn rate_limited_rpc_response(request: &Value, limited: RateLimited) -> Value {
+	let id = match request {
+		Value::Array(_) => Value::Null,
+		call => call.get("id").cloned().unwrap_or(Value::Null),
+	};
+	let message = match limited {
+		RateLimited::RetryAfter(secs) => format!("too many requests, retry after {} seconds", secs),
+		RateLimited::TooLarge => "request is over the rate limit budget".to_string(),
+	};
+	serde_json::json!({
+		"jsonrpc": "2.0",
+		"id": id,
+		"error": {
+			"code": RATE_LIMITED_RPC_CODE,
+			"message": message,
+		}
+	})
+}
+
+// The URI is the prefix or is under it
+fn uri_under(path: &str, prefix: &str) -> bool {
+	path.starts_with(prefix)
+		&& (path.len() == prefix.len()
+			|| prefix.ends_with('/')
+			|| path[prefix.len()..].starts_with('/'))
+}
+
+/// Rate limits the requests by the client IP. Heavyweight v1 URIs are counted here, the json-rpc
+/// API counts the heavyweight methods itself, after the body is parsed. The ignored URIs are
+/// prefixes, the URIs under them are not limited.
+pub struct RateLimitMiddleware {
+	limiter: Arc<RateLimiter>,
+	ignore_uris: Vec<String>,
+}
+
+impl RateLimitMiddleware {
+	pub fn new(limiter: Arc<RateLimiter>, ignore_uris: Vec<String>) -> RateLimitMiddleware {
+		RateLimitMiddleware {
+			limiter,
+			ignore_uris,
+		}
+	}
+}
+
+impl Handler for RateLimitMiddleware {
+	fn call(
+		&self,
+		req: Request<Body>,
+		mut handlers: Box<dyn Iterator<Item = HandlerObj>>,
+	) -> ResponseFuture {
+		let next_handler = match handlers.next() {
+			Some(h) => h,
+			None => return response(StatusCode::INTERNAL_SERVER_ERROR, "no handler found"),
+		};
+		let path = req.uri().path();
+		if let Some(ip) = client_ip(&req) {
+			if !self.ignore_uris.iter().any(|u| uri_under(path, u)) {
+				let heavy = HEAVY_URI_PREFIXES.iter().any(|p| path.starts_with(p)) as u32;
+				let res = self
+					.limiter
+					.check(ip)
+					.and_then(|_| self.limiter.check_heavy(ip, heavy));
+				if let Err(limited) = res {
+					debug!("Client {} is rate limited at {}, {:?}", ip, path, limited);
+					return Box::pin(ok(too_many_requests(limited)));
+				}
+			}
+		}
+		next_handler.call(req, handlers)
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+	use std::time::Duration;
+
+	#[test]
+	fn test_rate_limit_budget() {
+		let ip: IpAddr = "10.0.0.1".parse().unwrap();
+		let other: IpAddr = "10.0.0.2".parse().unwrap();
+		let budget = Budget::new(60);
+		let start = Instant::now();
+
+		for _ in 0..60 {
+			assert!(budget.take(ip, 1, start).is_ok());
+		}
+		// one token per second
+		assert_eq!(budget.take(ip, 1, start), Err(RateLimited::RetryAfter(1)));
+		assert!(budget.take(other, 1, start).is_ok());
+		assert!(budget.take(ip, 1, start + Duration::from_secs(1)).is_ok());
+		assert_eq!(
+			budget.take(ip, 5, start + Duration::from_secs(1)),
+			Err(RateLimited::RetryAfter(5))
+		);
+		// over the whole budget, never succeeds
+		assert_eq!(
+			budget.take(other, 61, start + Duration::from_secs(600)),
+			Err(RateLimited::TooLarge)
+		);
+		// refilled up to the capacity only
+		assert!(budget
+			.take(ip, 60, start + Duration::from_secs(600))
+			.is_ok());
+		assert!(budget
+			.take(ip, 1, start + Duration::from_secs(600))
+			.is_err());
+
+		// IPv6 clients share the budget of the /64 network
+		let budget = Budget::new(1);
+		let v6: IpAddr = "2001:db8:1:2::1".parse().unwrap();
+		let v6_same_net: IpAddr = "2001:db8:1:2:ffff::7".parse().unwrap();
+		let v6_other_net: IpAddr = "2001:db8:1:3::1".parse().unwrap();
+		assert!(budget.take(v6, 1, start).is_ok());
+		assert!(budget.take(v6_same_net, 1, start).is_err());
+		assert!(budget.take(v6_other_net, 1, start).is_ok());
+
+		// libp2p peers have their own buckets
+		let budget = Budget::new(1);
+		let peer = ClientKey::Peer("peer".to_string());
+		assert!(budget.take_key(peer.clone(), 1, start).is_ok());
+		assert!(budget.take_key(peer, 1, start).is_err());
+		assert!(budget
+			.take_key(ClientKey::Peer("other".to_string()), 1, start)
+			.is_ok());
+
+		// no limit
+		let budget = Budget::new(0);
+		assert!(budget.take(ip, 1000, start).is_ok());
+	}
+
+	#[test]
+	fn test_uri_under() {
+		assert!(uri_under("/v2/owner", "/v2/owner"));
+		assert!(uri_under("/v1/peers/all", "/v1/peers"));
+		assert!(uri_under("/v1/peers/all", "/v1/peers/"));
+		assert!(!uri_under("/v2/ownerx", "/v2/owner"));
+		assert!(!uri_under("/v1/chain/outputs/byids", "/v1/chain/compact"));
+	}
+
+	#[test]
+	fn test_heavy_foreign_calls() {
+		let call = |method: &str| serde_json::json!({"jsonrpc": "2.0", "method": method, "id": 1});
+		assert_eq!(heavy_foreign_calls(&call("get_tip")), 0);
+		assert_eq!(heavy_foreign_calls(&call("get_outputs_in_range")), 1);
+		assert_eq!(heavy_foreign_calls(&call("get_coinbase_merkle_proof")), 1);
+		let batch = Value::Array(vec![
+			call("get_unspent_outputs"),
+			call("get_tip"),
+			call("get_blocks"),
+		]);
+		assert_eq!(heavy_foreign_calls(&batch), 2);
+	}
+}
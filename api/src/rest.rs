@@ -19,25 +19,34 @@
 //! register them on a ApiServer.
 
 use crate::p2p::Error as P2pError;
-use crate::router::{Handler, HandlerObj, ResponseFuture, Router, RouterError};
+use crate::router::{ClientCert, Handler, HandlerObj, ResponseFuture, Router, RouterError};
+use crate::util::ToHex;
 use crate::web::response;
 use failure::{Backtrace, Context, Fail};
 use futures::channel::oneshot;
-use futures::TryStreamExt;
+use futures::{Future, TryStreamExt};
 use hyper::server::accept;
+use hyper::server::conn::AddrStream;
 use hyper::service::make_service_fn;
 use hyper::{Body, Request, Server, StatusCode};
+use ring::digest;
 use rustls;
 use rustls::internal::pemfile;
-use rustls::{NoClientAuth, ServerConfig};
+use rustls::{
+	AllowAnyAnonymousOrAuthenticatedClient, AllowAnyAuthenticatedClient, NoClientAuth,
+	RootCertStore, Session,
+};
+use schemars::JsonSchema;
+use std::collections::HashMap;
 use std::convert::Infallible;
 use std::fmt::{self, Display};
 use std::fs::File;
 use std::net::SocketAddr;
 use std::sync::Arc;
 use std::{io, thread};
-use tokio::net::TcpListener;
-use tokio::runtime::Runtime;
+use tokio::net::{TcpListener, TcpStream};
+use tokio::runtime::{Handle, Runtime};
+use tokio_rustls::server::TlsStream;
 use tokio_rustls::TlsAcceptor;
 
 /// Errors that can be returned by an ApiEndpoint implementation.
@@ -46,7 +55,7 @@ pub struct Error {
 	inner: Context<ErrorKind>,
 }
 
-#[derive(Clone, Eq, PartialEq, Debug, Fail, Serialize, Deserialize)]
+#[derive(Clone, Eq, PartialEq, Debug, Fail, Serialize, Deserialize, JsonSchema)]
 pub enum ErrorKind {
 	#[fail(display = "API Internal error: {}", _0)]
 	Internal(String),
@@ -124,11 +133,81 @@ impl From<P2pError> for Error {
 	}
 }
 
+/// Permission of the API client that is authenticated with the TLS client certificate
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum ClientPermission {
+	/// GET requests and the read-only owner API methods
+	ReadOnly,
+	/// Full owner API access
+	Owner,
+}
+
+/// SHA256 fingerprint of the certificate as it is matched, lowercase hex without
+/// the separators, so "AB:CD:..." and "abcd..." are the same fingerprint.
+pub fn normalize_fingerprint(fingerprint: &str) -> String {
+	fingerprint
+		.chars()
+		.filter(|c| c.is_ascii_hexdigit())
+		.collect::<String>()
+		.to_lowercase()
+}
+
+/// Mutual TLS, the clients are authenticated with the certificates signed by the CA
+#[derive(Clone, Debug)]
+pub struct TLSClientAuth {
+	/// CA bundle file (PEM) that signs the client certificates
+	pub ca_bundle: String,
+	/// Reject the connections without a client certificate
+	pub required: bool,
+	/// Permissions by the SHA256 fingerprint of the client certificate, normalized
+	/// with `normalize_fingerprint`. Certificates that are not listed are read-only.
+	pub permissions: HashMap<String, ClientPermission>,
+}
+
+impl TLSClientAuth {
+	fn load_roots(&self) -> Result<RootCertStore, Error> {
+		let cafile = File::open(&self.ca_bundle).map_err(|e| {
+			ErrorKind::Internal(format!(
+				"load_roots failed to open file {}, {}",
+				self.ca_bundle, e
+			))
+		})?;
+		let mut reader = io::BufReader::new(cafile);
+		let mut roots = RootCertStore::empty();
+		let (valid, _invalid) = roots.add_pem_file(&mut reader).map_err(|_| {
+			ErrorKind::Internal(format!("failed to load CA bundle {}", self.ca_bundle))
+		})?;
+		if valid == 0 {
+			return Err(ErrorKind::Internal(format!(
+				"CA bundle {} has no valid certificates",
+				self.ca_bundle
+			)))?;
+		}
+		Ok(roots)
+	}
+
+	/// Identity of the client with this certificate chain, the client certificate goes first
+	pub fn client_cert(&self, certs: Option<Vec<rustls::Certificate>>) -> Option<ClientCert> {
+		let cert = certs?.into_iter().next()?;
+		let fingerprint = digest::digest(&digest::SHA256, &cert.0).as_ref().to_hex();
+		let permission = self
+			.permissions
+			.get(&fingerprint)
+			.cloned()
+			.unwrap_or(ClientPermission::ReadOnly);
+		Some(ClientCert {
+			fingerprint,
+			permission,
+		})
+	}
+}
+
 /// TLS config
 #[derive(Clone)]
 pub struct TLSConfig {
 	pub certificate: String,
 	pub private_key: String,
+	pub client_auth: Option<TLSClientAuth>,
 }
 
 impl TLSConfig {
@@ -136,9 +215,16 @@ impl TLSConfig {
 		TLSConfig {
 			certificate,
 			private_key,
+			client_auth: None,
 		}
 	}
 
+	/// Require or accept the client certificates
+	pub fn with_client_auth(mut self, client_auth: TLSClientAuth) -> TLSConfig {
+		self.client_auth = Some(client_auth);
+		self
+	}
+
 	fn load_certs(&self) -> Result<Vec<rustls::Certificate>, Error> {
 		let certfile = File::open(&self.certificate).map_err(|e| {
 			ErrorKind::Internal(format!(
@@ -175,7 +261,12 @@ impl TLSConfig {
 	pub fn build_server_config(&self) -> Result<Arc<rustls::ServerConfig>, Error> {
 		let certs = self.load_certs()?;
 		let key = self.load_private_key()?;
-		let mut cfg = rustls::ServerConfig::new(rustls::NoClientAuth::new());
+		let client_verifier = match &self.client_auth {
+			Some(auth) if auth.required => AllowAnyAuthenticatedClient::new(auth.load_roots()?),
+			Some(auth) => AllowAnyAnonymousOrAuthenticatedClient::new(auth.load_roots()?),
+			None => NoClientAuth::new(),
+		};
+		let mut cfg = rustls::ServerConfig::new(client_verifier);
 		cfg.set_single_cert(certs, key)
 			.map_err(|e| ErrorKind::Internal(format!("set single certificate failed, {}", e)))?;
 		Ok(Arc::new(cfg))
@@ -185,6 +276,7 @@ impl TLSConfig {
 /// HTTP server allowing the registration of ApiEndpoint implementations.
 pub struct ApiServer {
 	shutdown_sender: Option<oneshot::Sender<()>>,
+	runtime: Option<Handle>,
 }
 
 impl ApiServer {
@@ -193,6 +285,16 @@ impl ApiServer {
 	pub fn new() -> ApiServer {
 		ApiServer {
 			shutdown_sender: None,
+			runtime: None,
+		}
+	}
+
+	/// Creates a new ApiServer that runs on the provided tokio runtime instead
+	/// of its own one.
+	pub fn with_runtime(runtime: Handle) -> ApiServer {
+		ApiServer {
+			shutdown_sender: None,
+			runtime: Some(runtime),
 		}
 	}
 
@@ -224,26 +326,23 @@ impl ApiServer {
 		}
 		let (tx, _rx) = oneshot::channel::<()>();
 		self.shutdown_sender = Some(tx);
+		let runtime = self.runtime.clone();
 		thread::Builder::new()
 			.name("apis".to_string())
 			.spawn(move || {
 				let server = async move {
-					let server = Server::bind(&addr).serve(make_service_fn(move |_| {
-						let router = router.clone();
-						async move { Ok::<_, Infallible>(router) }
-					}));
+					let server =
+						Server::bind(&addr).serve(make_service_fn(move |conn: &AddrStream| {
+							let router = router.with_remote_addr(Some(conn.remote_addr()));
+							async move { Ok::<_, Infallible>(router) }
+						}));
 					// TODO graceful shutdown is unstable, investigate
 					//.with_graceful_shutdown(rx)
 
 					server.await
 				};
 
-				let mut rt = Runtime::new()
-					.map_err(|e| error!("HTTP API server error: {}", e))
-					.unwrap();
-				if let Err(e) = rt.block_on(server) {
-					error!("HTTP API server error: {}", e)
-				}
+				run_server(runtime, server);
 			})
 			.map_err(|e| ErrorKind::Internal(format!("failed to spawn API thread. {}", e)).into())
 	}
@@ -263,14 +362,9 @@ impl ApiServer {
 			.into());
 		}
 
-		let certs = conf.load_certs()?;
-		let keys = conf.load_private_key()?;
-
-		let mut config = ServerConfig::new(NoClientAuth::new());
-		config
-			.set_single_cert(certs, keys)
-			.expect("invalid key or certificate");
-		let acceptor = TlsAcceptor::from(Arc::new(config));
+		let acceptor = TlsAcceptor::from(conf.build_server_config()?);
+		let client_auth = conf.client_auth.clone();
+		let runtime = self.runtime.clone();
 
 		thread::Builder::new()
 			.name("apis".to_string())
@@ -280,8 +374,14 @@ impl ApiServer {
 					let listener = listener.incoming().and_then(move |s| acceptor.accept(s));
 
 					let server = Server::builder(accept::from_stream(listener)).serve(
-						make_service_fn(move |_| {
-							let router = router.clone();
+						make_service_fn(move |conn: &TlsStream<TcpStream>| {
+							let (stream, session) = conn.get_ref();
+							let client_cert = client_auth
+								.as_ref()
+								.and_then(|auth| auth.client_cert(session.get_peer_certificates()));
+							let router = router
+								.with_remote_addr(stream.peer_addr().ok())
+								.with_client_cert(client_cert);
 							async move { Ok::<_, Infallible>(router) }
 						}),
 					);
@@ -289,12 +389,7 @@ impl ApiServer {
 					server.await
 				};
 
-				let mut rt = Runtime::new()
-					.map_err(|e| error!("HTTP API server error: {}", e))
-					.unwrap();
-				if let Err(e) = rt.block_on(server) {
-					error!("HTTP API server error: {}", e)
-				}
+				run_server(runtime, server);
 			})
 			.map_err(|e| ErrorKind::Internal(format!("failed to spawn API thread. {}", e)).into())
 	}
@@ -314,6 +409,28 @@ impl ApiServer {
 	}
 }
 
+/// Run the server future until it's done, on the shared runtime if there is one.
+fn run_server<F>(runtime: Option<Handle>, server: F)
+where
+	F: Future<Output = Result<(), hyper::Error>> + Send + 'static,
+{
+	match runtime {
+		Some(runtime) => match futures::executor::block_on(runtime.spawn(server)) {
+			Ok(Ok(_)) => {}
+			Ok(Err(e)) => error!("HTTP API server error: {}", e),
+			Err(e) => error!("HTTP API server task failed: {}", e),
+		},
+		None => {
+			let mut rt = Runtime::new()
+				.map_err(|e| error!("HTTP API server error: {}", e))
+				.unwrap();
+			if let Err(e) = rt.block_on(server) {
+				error!("HTTP API server error: {}", e)
+			}
+		}
+	}
+}
+
 pub struct LoggingMiddleware {}
 
 impl Handler for LoggingMiddleware {
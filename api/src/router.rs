@@ -12,12 +12,15 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use crate::rest::ClientPermission;
 use futures::future::{self, Future};
 use hyper;
 use hyper::service::Service;
 use hyper::{Body, Method, Request, Response, StatusCode};
+use schemars::JsonSchema;
 use std::collections::hash_map::DefaultHasher;
 use std::hash::{Hash, Hasher};
+use std::net::SocketAddr;
 use std::pin::Pin;
 use std::sync::Arc;
 use std::task::{Context, Poll};
@@ -87,7 +90,7 @@ pub trait Handler {
 	}
 }
 
-#[derive(Clone, Fail, Eq, Debug, PartialEq, Serialize, Deserialize)]
+#[derive(Clone, Fail, Eq, Debug, PartialEq, Serialize, Deserialize, JsonSchema)]
 pub enum RouterError {
 	#[fail(display = "Route {} already exists", _0)]
 	RouteAlreadyExists(String),
@@ -97,9 +100,25 @@ pub enum RouterError {
 	NoValue(String),
 }
 
+/// Address of the client, available in the extensions of the requests
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RemoteAddr(pub SocketAddr);
+
+/// Client that is authenticated with the TLS client certificate, available in the
+/// extensions of the requests
+#[derive(Debug, Clone, PartialEq)]
+pub struct ClientCert {
+	/// SHA256 fingerprint of the certificate, hex
+	pub fingerprint: String,
+	/// Permission of the client
+	pub permission: ClientPermission,
+}
+
 #[derive(Clone)]
 pub struct Router {
 	nodes: Vec<Node>,
+	remote_addr: Option<SocketAddr>,
+	client_cert: Option<ClientCert>,
 }
 
 #[derive(Debug, Clone, Copy)]
@@ -123,7 +142,26 @@ impl Router {
 		let root = Node::new(calculate_hash(&""), None);
 		let mut nodes = vec![];
 		nodes.push(root);
-		Router { nodes }
+		Router {
+			nodes,
+			remote_addr: None,
+			client_cert: None,
+		}
+	}
+
+	/// Router for the connection of the client at the address
+	pub fn with_remote_addr(&self, remote_addr: Option<SocketAddr>) -> Router {
+		Router {
+			nodes: self.nodes.clone(),
+			remote_addr,
+			client_cert: self.client_cert.clone(),
+		}
+	}
+
+	/// Router for the connection of the client with the TLS client certificate
+	pub fn with_client_cert(mut self, client_cert: Option<ClientCert>) -> Router {
+		self.client_cert = client_cert;
+		self
 	}
 
 	pub fn add_middleware(&mut self, mw: HandlerObj) {
@@ -216,7 +254,13 @@ impl Service<Request<Body>> for Router {
 		Poll::Ready(Ok(()))
 	}
 
-	fn call(&mut self, req: Request<Body>) -> Self::Future {
+	fn call(&mut self, mut req: Request<Body>) -> Self::Future {
+		if let Some(addr) = self.remote_addr {
+			req.extensions_mut().insert(RemoteAddr(addr));
+		}
+		if let Some(client_cert) = self.client_cert.clone() {
+			req.extensions_mut().insert(client_cert);
+		}
 		match self.get(req.uri().path()) {
 			Err(_) => not_found(),
 			Ok(mut handlers) => match handlers.next() {
@@ -0,0 +1,539 @@
+// Copyright 2021 The MWC Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! OpenAPI 3.1 document of the v2 json-rpc APIs, served at `/v2/spec`. Every API is a single
+//! POST path, `/v2/owner` and `/v2/foreign`, its request body is one of the method requests,
+//! discriminated by the `method` field. The parameter and result schemas are derived from the
+//! Rust types with `schemars`, the method tables below are type checked against the `OwnerRpc`
+//! and `ForeignRpc` traits, so a changed signature doesn't compile until the table is updated.
+
+use crate::audit::AuditLogPage;
+use crate::chain::watch::{WatchEventPage, WatchFilterInfo};
+use crate::chain::{BlockRejection, ReorgRecord, TipFreezeStatus};
+use crate::core::core::transaction::Transaction;
+use crate::foreign_rpc::ForeignRpc;
+use crate::owner_rpc::OwnerRpc;
+use crate::p2p::PeerData;
+use crate::pool::{FeeEstimate, PoolEntry, StemPoolStatus};
+use crate::rest::ErrorKind;
+use crate::router::{Handler, ResponseFuture};
+use crate::types::{
+	ApiSecretRotation, BlockHeaderPrintable, BlockListing, BlockPrintable, BlockValidation,
+	BuildInfo, ChainStats, CoinbaseMerkleProof, DecodedTransaction, IntegrityKernelStatus,
+	KernelMerkleProof, KernelStatus, Libp2pMessages, Libp2pPeers, LocatedTxKernel,
+	MessageSimulation, MinedBlockPrintable, NodeState, OutputCursorListing, OutputListing,
+	OutputPrintable, OutputRangeListing, Status, StatusAndPeers, Tip, Version,
+};
+use crate::web::*;
+use chrono::{DateTime, Utc};
+use grin_p2p::libp2p_connection::{GossipTraceEntry, RelayDenyList};
+use grin_p2p::libp2p_mailbox::MailboxMessage;
+use grin_p2p::types::{PeerDiversityReport, PeerInfoDisplayLegacy, PeerStoreStats};
+use hyper::{Body, Request};
+use schemars::gen::{SchemaGenerator, SchemaSettings};
+use schemars::schema::Schema;
+use serde_json::{json, Map, Value};
+use std::net::SocketAddr;
+
+/// Prefix of the references to the component schemas
+const COMPONENTS_PATH: &str = "#/components/schemas/";
+
+lazy_static! {
+	/// The document is built once, the API can't change at runtime
+	static ref API_SPEC: Value = api_spec();
+}
+
+/// json-rpc method of the API
+pub struct RpcMethod {
+	/// Method name
+	pub name: &'static str,
+	/// Short description of the method
+	pub summary: String,
+	/// Parameter names and their schemas, in the call order
+	pub params: Vec<(&'static str, Schema)>,
+	/// Schema of the result, `{"Ok": ...}` or `{"Err": ErrorKind}`
+	pub result: Schema,
+}
+
+// Every entry is checked against the trait method: a missing method or a different parameter
+// or result type fails to compile.
+macro_rules! rpc_methods {
+	(
+		$rpc:ty, $gen:expr,
+		$(#[doc = $doc:expr] fn $name:ident($($param:ident: $ty:ty),*) -> $ret:ty;)*
+	) => {
+		vec![$({
+			let _: fn(&$rpc $(, $ty)*) -> Result<$ret, ErrorKind> =
+				|rpc $(, $param)*| rpc.$name($($param),*);
+			RpcMethod {
+				name: stringify!($name),
+				summary: $doc.trim().to_string(),
+				params: vec![$((stringify!($param), $gen.subschema_for::<$ty>())),*],
+				result: $gen.subschema_for::<Result<$ret, ErrorKind>>(),
+			}
+		}),*]
+	};
+}
+
+/// Methods of the owner API
+pub fn owner_methods(gen: &mut SchemaGenerator) -> Vec<RpcMethod> {
+	rpc_methods!(
+		dyn OwnerRpc,
+		gen,
+		/// Returns various information about the node, the network and the current sync status.
+		fn get_status() -> Status;,
+		/// Triggers a validation of the chain state.
+		fn validate_chain() -> ();,
+		/// Triggers a compaction of the chain state to regain storage space.
+		fn compact_chain() -> ();,
+		/// Rebuilds the kernel excess index from the full kernel MMR.
+		fn rebuild_kernel_index() -> ();,
+		/// Retrieves information about the stored peers.
+		fn get_peers(peer_addr: Option<SocketAddr>) -> Vec<PeerData>;,
+		/// Retrieves a list of all connected peers.
+		fn get_connected_peers() -> Vec<PeerInfoDisplayLegacy>;,
+		/// Reports the mix of the connected peers by latency, address class and user agent.
+		fn get_peer_diversity() -> PeerDiversityReport;,
+		/// Reports the size of the peer database and the number of the evicted peers.
+		fn get_peer_store_stats() -> PeerStoreStats;,
+		/// Bans a specific peer.
+		fn ban_peer(peer_addr: SocketAddr) -> ();,
+		/// Unbans a specific peer.
+		fn unban_peer(peer_addr: SocketAddr) -> ();,
+		/// Checks if the kernel qualifies as a valid integrity kernel for the libp2p gossip messages.
+		fn check_integrity_kernel(excess: String) -> IntegrityKernelStatus;,
+		/// Runs the full contextual validation of the block without adding it to the chain.
+		fn validate_block(serialized_block: String) -> BlockValidation;,
+		/// Returns the last blocks rejected as invalid, most recent first.
+		fn get_rejected_blocks(limit: Option<usize>) -> Vec<BlockRejection>;,
+		/// Returns the last reorgs of the chain head, most recent first.
+		fn get_reorg_history(limit: Option<usize>) -> Vec<ReorgRecord>;,
+		/// Runs a raw p2p or gossip message through the validation pipeline without processing it.
+		fn simulate_message(peer: String, message_hex: String) -> MessageSimulation;,
+		/// Returns the traced gossip messages, most recent first.
+		fn get_gossip_trace(
+			topic: Option<String>,
+			peer_id: Option<String>,
+			limit: Option<usize>,
+		) -> Vec<GossipTraceEntry>;,
+		/// Returns the emergency relay deny list of the libp2p node.
+		fn get_relay_deny_list() -> RelayDenyList;,
+		/// Replaces the emergency relay deny list of the libp2p node.
+		fn set_relay_deny_list(deny_list: RelayDenyList) -> ();,
+		/// Generates a new owner API secret, the previous one stays valid for the grace period.
+		fn rotate_api_secret(grace_period_secs: Option<u64>) -> ApiSecretRotation;,
+		/// Generates a new foreign API secret, the previous one stays valid for the grace period.
+		fn rotate_foreign_api_secret(grace_period_secs: Option<u64>) -> ApiSecretRotation;,
+		/// Returns the entries of the owner API audit log.
+		fn get_audit_log(
+			start_index: Option<u64>,
+			method: Option<String>,
+			limit: Option<usize>,
+		) -> AuditLogPage;,
+		/// Returns the provenance of the node binary.
+		fn get_build_info() -> BuildInfo;,
+		/// Registers a watch-only filter of the output commitments.
+		fn add_watch_filter(
+			name: String,
+			commitments: Option<Vec<String>>,
+			bloom_filter: Option<String>,
+			bloom_hashes: Option<u32>,
+		) -> WatchFilterInfo;,
+		/// Deletes the watch filter with its recorded events.
+		fn delete_watch_filter(id: u64) -> ();,
+		/// Returns the registered watch filters.
+		fn get_watch_filters() -> Vec<WatchFilterInfo>;,
+		/// Returns the events recorded by the watch filter, starting from the cursor.
+		fn get_watch_events(id: u64, cursor: Option<u64>, limit: Option<usize>) -> WatchEventPage;,
+		/// Temporarily stops accepting the new blocks into the chain.
+		fn freeze_tip(duration: u64) -> TipFreezeStatus;,
+		/// Ends the tip freeze now, processing the buffered blocks.
+		fn unfreeze_tip() -> TipFreezeStatus;,
+		/// Returns the freeze state of the chain tip.
+		fn get_tip_freeze_status() -> TipFreezeStatus;,
+		/// Returns the blocks mined by this node.
+		fn get_mined_blocks(
+			start_height: Option<u64>,
+			end_height: Option<u64>,
+		) -> Vec<MinedBlockPrintable>;,
+		/// Returns the transactions in the Dandelion stempool.
+		fn get_stempool_status() -> StemPoolStatus;,
+		/// Returns the node status together with the connected peers.
+		fn get_status_and_peers() -> StatusAndPeers;,
+		/// Returns the full node state.
+		fn get_node_state() -> NodeState;
+	)
+}
+
+/// Methods of the foreign API
+pub fn foreign_methods(gen: &mut SchemaGenerator) -> Vec<RpcMethod> {
+	rpc_methods!(
+		dyn ForeignRpc,
+		gen,
+		/// Gets the block header by height, hash or an unspent output commitment.
+		fn get_header(
+			height: Option<u64>,
+			hash: Option<String>,
+			commit: Option<String>,
+		) -> BlockHeaderPrintable;,
+		/// Gets the block by height, hash or an unspent output commitment.
+		fn get_block(
+			height: Option<u64>,
+			hash: Option<String>,
+			commit: Option<String>,
+		) -> BlockPrintable;,
+		/// Gets the block, optionally with the range proofs and the merkle proofs of the outputs.
+		fn get_block_ex(
+			height: Option<u64>,
+			hash: Option<String>,
+			commit: Option<String>,
+			include_proof: Option<bool>,
+			include_merkle_proof: Option<bool>,
+		) -> BlockPrintable;,
+		/// Returns the node version and the block header version.
+		fn get_version() -> Version;,
+		/// Gets the blocks of the main chain in a range of heights.
+		fn get_blocks(
+			start_height: u64,
+			end_height: Option<u64>,
+			max: u64,
+			include_proof: Option<bool>,
+		) -> BlockListing;,
+		/// Gets the time this node first saw the block.
+		fn get_block_first_seen(
+			height: Option<u64>,
+			hash: Option<String>,
+			commit: Option<String>,
+		) -> Option<DateTime<Utc>>;,
+		/// Returns details about the state of the current fork tip.
+		fn get_tip() -> Tip;,
+		/// Returns the chain statistics as of the block at the height.
+		fn get_chain_stats(height: u64) -> ChainStats;,
+		/// Returns the kernel with the excess and its location in the chain.
+		fn get_kernel(
+			excess: String,
+			min_height: Option<u64>,
+			max_height: Option<u64>,
+		) -> LocatedTxKernel;,
+		/// Returns the status of the transaction identified by its kernel excess.
+		fn get_kernel_status(excess: String) -> KernelStatus;,
+		/// Gets the time this node first saw the transaction.
+		fn get_tx_first_seen(excess: String) -> Option<DateTime<Utc>>;,
+		/// Retrieves the outputs by commitments or by the heights of the blocks.
+		fn get_outputs(
+			commits: Option<Vec<String>>,
+			start_height: Option<u64>,
+			end_height: Option<u64>,
+			include_proof: Option<bool>,
+			include_merkle_proof: Option<bool>,
+		) -> Vec<OutputPrintable>;,
+		/// UTXO traversal by the output MMR index.
+		fn get_unspent_outputs(
+			start_index: u64,
+			end_index: Option<u64>,
+			max: u64,
+			include_proof: Option<bool>,
+		) -> OutputListing;,
+		/// UTXO traversal with a cursor.
+		fn get_unspent_outputs_cursor(
+			cursor: Option<String>,
+			max: u64,
+			include_proof: Option<bool>,
+		) -> OutputCursorListing;,
+		/// Retrieves the output at the output MMR index, spent or not.
+		fn get_historical_output(mmr_index: u64, include_proof: Option<bool>) -> OutputPrintable;,
+		/// Retrieves the outputs created by the blocks in the height range.
+		fn get_outputs_in_range(
+			start_height: u64,
+			end_height: u64,
+			include_proof: Option<bool>,
+			include_spent: Option<bool>,
+		) -> OutputRangeListing;,
+		/// Gets the height of the block that includes the unspent output.
+		fn get_output_height(commit: String) -> Option<u64>;,
+		/// Retrieves the PMMR indices of the blocks in the height range.
+		fn get_pmmr_indices(
+			start_block_height: u64,
+			end_block_height: Option<u64>,
+		) -> OutputListing;,
+		/// Returns the number of transactions in the transaction pool.
+		fn get_pool_size() -> usize;,
+		/// Returns the number of transactions in the stem transaction pool.
+		fn get_stempool_size() -> usize;,
+		/// Returns the unconfirmed transactions in the transaction pool.
+		fn get_unconfirmed_transactions() -> Vec<PoolEntry>;,
+		/// Estimates the fee for a transaction to be mined within the target number of blocks.
+		fn get_fee_estimate(blocks_target: u64) -> FeeEstimate;,
+		/// Pushes the transaction to the transaction pool.
+		fn push_transaction(tx: Transaction, fluff: Option<bool>) -> ();,
+		/// Builds the merkle proof of the unspent coinbase output.
+		fn get_coinbase_merkle_proof(commit: String) -> CoinbaseMerkleProof;,
+		/// Verifies the coinbase merkle proof.
+		fn verify_coinbase_merkle_proof(proof: CoinbaseMerkleProof) -> bool;,
+		/// Builds the merkle proof of the kernel at the kernel MMR index.
+		fn get_kernel_merkle_proof(mmr_index: u64, height: u64) -> KernelMerkleProof;,
+		/// Verifies the kernel merkle proof.
+		fn verify_kernel_merkle_proof(proof: KernelMerkleProof) -> bool;,
+		/// Decodes the raw transaction without submitting it to the pool.
+		fn decode_tx(tx_hex: String) -> DecodedTransaction;,
+		/// Returns the libp2p peers and the node peers with the tor addresses.
+		fn get_libp2p_peers() -> Libp2pPeers;,
+		/// Returns the messages received from the libp2p gossip network.
+		fn get_libp2p_messages() -> Libp2pMessages;,
+		/// Posts the direct message for another wallet into the mailbox.
+		fn post_mailbox_message(message: String) -> MailboxMessage;,
+		/// Returns the mailbox messages for the recipient.
+		fn get_mailbox_messages(
+			recipient: String,
+			since: Option<i64>,
+			timestamp: i64,
+			signature: String,
+		) -> Vec<MailboxMessage>;
+	)
+}
+
+fn component_ref(name: &str) -> Value {
+	json!({ "$ref": format!("{}{}", COMPONENTS_PATH, name) })
+}
+
+fn to_value(schema: &Schema) -> Value {
+	serde_json::to_value(schema).unwrap_or(Value::Null)
+}
+
+// Request and response schemas of the method
+fn method_schemas(method: &RpcMethod) -> (Value, Value) {
+	let params: Vec<Value> = method
+		.params
+		.iter()
+		.map(|(name, schema)| {
+			let mut schema = to_value(schema);
+			if let Value::Object(map) = &mut schema {
+				map.insert("title".to_string(), json!(name));
+			}
+			schema
+		})
+		.collect();
+	let params_len = params.len();
+	let request = json!({
+		"type": "object",
+		"description": method.summary,
+		"required": ["jsonrpc", "method", "id", "params"],
+		"properties": {
+			"jsonrpc": { "const": "2.0" },
+			"method": { "const": method.name },
+			"id": { "type": ["integer", "string"] },
+			"params": {
+				"type": "array",
+				"prefixItems": params,
+				"minItems": params_len,
+				"maxItems": params_len,
+			},
+		},
+	});
+	let response = json!({
+		"type": "object",
+		"required": ["jsonrpc", "id"],
+		"properties": {
+			"jsonrpc": { "const": "2.0" },
+			"id": { "type": ["integer", "string"] },
+			"result": to_value(&method.result),
+			"error": component_ref("JsonRpcError"),
+		},
+	});
+	(request, response)
+}
+
+// Path item of the API, a POST with one of the method requests
+fn api_path(
+	api: &str,
+	methods: &[RpcMethod],
+	optional_auth: bool,
+	schemas: &mut Map<String, Value>,
+) -> Value {
+	let mut requests = vec![];
+	let mut responses = vec![];
+	let mut mapping = Map::new();
+	for method in methods {
+		let (request, response) = method_schemas(method);
+		let request_name = format!("{}_{}_request", api, method.name);
+		let response_name = format!("{}_{}_response", api, method.name);
+		schemas.insert(request_name.clone(), request);
+		schemas.insert(response_name.clone(), response);
+		requests.push(component_ref(&request_name));
+		responses.push(component_ref(&response_name));
+		mapping.insert(
+			method.name.to_string(),
+			json!(format!("{}{}", COMPONENTS_PATH, request_name)),
+		);
+	}
+	let security = if optional_auth {
+		json!([{}, { "basicAuth": [] }])
+	} else {
+		json!([{ "basicAuth": [] }])
+	};
+	json!({
+		"post": {
+			"operationId": api,
+			"summary": format!("json-rpc {} API", api),
+			"tags": [api],
+			"security": security,
+			"requestBody": {
+				"required": true,
+				"content": {
+					"application/json": {
+						"schema": {
+							"oneOf": requests,
+							"discriminator": { "propertyName": "method", "mapping": mapping },
+						},
+					},
+				},
+			},
+			"responses": {
+				"200": {
+					"description": "json-rpc response",
+					"content": { "application/json": { "schema": { "oneOf": responses } } },
+				},
+			},
+		}
+	})
+}
+
+/// OpenAPI document of the v2 owner and foreign APIs
+pub fn api_spec() -> Value {
+	let mut gen = SchemaSettings::draft2019_09()
+		.with(|s| s.definitions_path = COMPONENTS_PATH.to_string())
+		.into_generator();
+	let owner = owner_methods(&mut gen);
+	let foreign = foreign_methods(&mut gen);
+
+	let mut schemas = Map::new();
+	for (name, schema) in gen.take_definitions() {
+		schemas.insert(name, to_value(&schema));
+	}
+	schemas.insert(
+		"JsonRpcError".to_string(),
+		json!({
+			"type": "object",
+			"properties": {
+				"code": { "type": "integer" },
+				"message": { "type": "string" },
+				"data": {},
+			},
+		}),
+	);
+
+	// Foreign API secret is optional, the owner API one is required
+	let mut paths = Map::new();
+	paths.insert(
+		"/v2/owner".to_string(),
+		api_path("owner", &owner, false, &mut schemas),
+	);
+	paths.insert(
+		"/v2/foreign".to_string(),
+		api_path("foreign", &foreign, true, &mut schemas),
+	);
+
+	json!({
+		"openapi": "3.1.0",
+		"info": {
+			"title": "MWC node API",
+			"version": env!("CARGO_PKG_VERSION"),
+			"description": "json-rpc v2 owner and foreign APIs of the MWC node",
+		},
+		"paths": paths,
+		"components": {
+			"schemas": schemas,
+			"securitySchemes": {
+				"basicAuth": { "type": "http", "scheme": "basic" },
+			},
+		},
+	})
+}
+
+/// Serves the OpenAPI document of the v2 APIs
+pub struct ApiSpecHandler;
+
+impl Handler for ApiSpecHandler {
+	fn get(&self, _req: Request<Body>) -> ResponseFuture {
+		json_response_pretty(&*API_SPEC)
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+
+	fn collect_refs(value: &Value, refs: &mut Vec<String>) {
+		match value {
+			Value::Object(map) => {
+				for (key, v) in map {
+					match (key.as_str(), v) {
+						("$ref", Value::String(r)) => refs.push(r.clone()),
+						_ => collect_refs(v, refs),
+					}
+				}
+			}
+			Value::Array(items) => items.iter().for_each(|v| collect_refs(v, refs)),
+			_ => (),
+		}
+	}
+
+	#[test]
+	fn test_api_spec() {
+		let spec = api_spec();
+		let paths = spec["paths"].as_object().unwrap();
+		let mut keys: Vec<&String> = paths.keys().collect();
+		keys.sort();
+		assert_eq!(keys, vec!["/v2/foreign", "/v2/owner"]);
+
+		let schemas = spec["components"]["schemas"].as_object().unwrap();
+		let get_tip = &schemas["foreign_get_tip_request"];
+		assert_eq!(get_tip["properties"]["method"]["const"], json!("get_tip"));
+		assert_eq!(get_tip["properties"]["params"]["maxItems"], json!(0));
+
+		let get_header = &schemas["foreign_get_header_request"];
+		let params = get_header["properties"]["params"]["prefixItems"]
+			.as_array()
+			.unwrap();
+		assert_eq!(params.len(), 3);
+		assert_eq!(params[1]["title"], json!("hash"));
+
+		// Real schemas of the structs, not the placeholders
+		let tip = schemas["Tip"]["properties"].as_object().unwrap();
+		assert!(tip.contains_key("height"));
+		assert!(tip.contains_key("last_block_pushed"));
+		let kernel = schemas["TxKernel"]["properties"].as_object().unwrap();
+		assert_eq!(kernel["excess"]["type"], json!("string"));
+
+		let owner_requests = paths["/v2/owner"]["post"]["requestBody"]["content"]
+			["application/json"]["schema"]["oneOf"]
+			.as_array()
+			.unwrap();
+		let mut gen = SchemaGenerator::default();
+		assert_eq!(owner_requests.len(), owner_methods(&mut gen).len());
+
+		// Every reference resolves
+		let mut refs = vec![];
+		collect_refs(&spec, &mut refs);
+		assert!(!refs.is_empty());
+		for r in refs {
+			assert!(r.starts_with(COMPONENTS_PATH), "{}", r);
+			assert!(
+				schemas.contains_key(&r[COMPONENTS_PATH.len()..]),
+				"unresolved {}",
+				r
+			);
+		}
+	}
+}
@@ -21,6 +21,8 @@ use crate::p2p;
 use crate::util::secp::pedersen;
 use crate::util::{self, ToHex};
 use grin_p2p::libp2p_connection;
+use grin_p2p::types::SimulationCheck;
+use schemars::JsonSchema;
 use serde;
 use serde::de::MapAccess;
 use serde::ser::SerializeStruct;
@@ -35,7 +37,7 @@ macro_rules! no_dup {
 }
 
 /// API Version Information
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone, JsonSchema)]
 pub struct Version {
 	/// Current node API Version (api crate version)
 	pub node_version: String,
@@ -44,7 +46,7 @@ pub struct Version {
 }
 
 /// The state of the current fork tip
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone, JsonSchema)]
 pub struct Tip {
 	/// Height of the tip (max height of the fork)
 	pub height: u64,
@@ -67,8 +69,44 @@ impl Tip {
 	}
 }
 
+/// Chain statistics as of a given block
+#[derive(Serialize, Deserialize, Debug, Clone, JsonSchema)]
+pub struct ChainStats {
+	/// Height of the block
+	pub height: u64,
+	/// Hash of the block
+	pub hash: String,
+	/// Number of unspent outputs right after this block
+	pub utxo_count: u64,
+	/// Number of kernels up to and including this block
+	pub kernel_count: u64,
+	/// Total coin supply (in nanocoins) up to and including this block
+	pub total_supply: u64,
+	/// Size of the output MMR
+	pub output_mmr_size: u64,
+	/// Size of the rangeproof MMR
+	pub rproof_mmr_size: u64,
+	/// Size of the kernel MMR
+	pub kernel_mmr_size: u64,
+}
+
+impl ChainStats {
+	pub fn from_stats(stats: chain::ChainStats) -> ChainStats {
+		ChainStats {
+			height: stats.height,
+			hash: stats.hash.to_hex(),
+			utxo_count: stats.utxo_count,
+			kernel_count: stats.kernel_count,
+			total_supply: stats.total_supply,
+			output_mmr_size: stats.output_mmr_size,
+			rproof_mmr_size: stats.output_mmr_size,
+			kernel_mmr_size: stats.kernel_mmr_size,
+		}
+	}
+}
+
 /// Status page containing different server information
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone, JsonSchema)]
 pub struct Status {
 	// The protocol version
 	pub protocol_version: u32,
@@ -83,6 +121,29 @@ pub struct Status {
 	// Additional sync information
 	#[serde(skip_serializing_if = "Option::is_none")]
 	pub sync_info: Option<serde_json::Value>,
+	// libp2p node statistics
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub libp2p: Option<libp2p_connection::Libp2pStats>,
+	// Height of the trusted txhashset snapshot the node started from, until its history is validated
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub assumed_utxo_height: Option<u64>,
+}
+
+/// Readiness of the node to serve the requests
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Readiness {
+	/// true if all checks passed
+	pub ready: bool,
+	/// Height of the chain head, None if the chain database is not reachable
+	pub height: Option<u64>,
+	/// Height of the most work peer, None if there are no peers
+	pub network_height: Option<u64>,
+	/// The current number of connections
+	pub peers: u32,
+	/// The current sync status
+	pub sync_status: String,
+	/// Failed checks
+	pub failures: Vec<String>,
 }
 
 impl Status {
@@ -99,6 +160,12 @@ impl Status {
 			tip: Tip::from_tip(current_tip),
 			sync_status,
 			sync_info,
+			libp2p: if libp2p_connection::get_libp2p_running() {
+				Some(libp2p_connection::get_libp2p_stats())
+			} else {
+				None
+			},
+			assumed_utxo_height: None,
 		}
 	}
 }
@@ -169,7 +236,7 @@ impl TxHashSetNode {
 	}
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, JsonSchema)]
 pub enum OutputType {
 	Coinbase,
 	Transaction,
@@ -259,12 +326,13 @@ impl<'de> serde::de::Visitor<'de> for PrintableCommitmentVisitor {
 }
 
 // As above, except formatted a bit better for human viewing
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, JsonSchema)]
 pub struct OutputPrintable {
 	/// The type of output Coinbase|Transaction
 	pub output_type: OutputType,
 	/// The homomorphic commitment representing the output's amount
 	/// (as hex string)
+	#[schemars(with = "String")]
 	pub commit: pedersen::Commitment,
 	/// Whether the output has been spent
 	pub spent: bool,
@@ -275,6 +343,7 @@ pub struct OutputPrintable {
 	/// Block height at which the output is found
 	pub block_height: Option<u64>,
 	/// Merkle Proof
+	#[schemars(with = "Option<String>")]
 	pub merkle_proof: Option<MerkleProof>,
 	/// MMR Position
 	pub mmr_index: u64,
@@ -525,7 +594,7 @@ impl<'de> serde::de::Deserialize<'de> for OutputPrintable {
 }
 
 // Printable representation of a block
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, JsonSchema)]
 pub struct TxKernelPrintable {
 	pub features: String,
 	pub fee: u64,
@@ -577,7 +646,7 @@ impl BlockHeaderInfo {
 	}
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, JsonSchema)]
 pub struct BlockHeaderPrintable {
 	// Hash
 	pub hash: String,
@@ -640,7 +709,7 @@ impl BlockHeaderPrintable {
 }
 
 // Printable representation of a block
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, JsonSchema)]
 pub struct BlockPrintable {
 	/// The block header
 	pub header: BlockHeaderPrintable,
@@ -740,7 +809,7 @@ pub struct BlockOutputs {
 
 // For traversing all outputs in the UTXO set
 // transactions in the block
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, JsonSchema)]
 pub struct OutputListing {
 	/// The last available output index
 	pub highest_index: u64,
@@ -750,13 +819,68 @@ pub struct OutputListing {
 	pub outputs: Vec<OutputPrintable>,
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+// Outputs created by a range of blocks
+#[derive(Debug, Serialize, Deserialize, Clone, JsonSchema)]
+pub struct OutputRangeListing {
+	/// Height to request the rest of the range from when it was cut short by the
+	/// limits, None if the range is complete
+	pub next_height: Option<u64>,
+	/// A printable version of the outputs
+	pub outputs: Vec<OutputPrintable>,
+}
+
+// For traversing the UTXO set with a cursor
+#[derive(Debug, Serialize, Deserialize, Clone, JsonSchema)]
+pub struct OutputCursorListing {
+	/// Cursor to request the next batch with. An empty batch means the chain head is
+	/// reached, the same cursor returns the outputs created since later on.
+	pub next_cursor: Option<String>,
+	/// A printable version of the outputs
+	pub outputs: Vec<OutputPrintable>,
+}
+
+// For traversing the blocks of the main chain by height
+#[derive(Debug, Serialize, Deserialize, Clone, JsonSchema)]
+pub struct BlockListing {
+	/// The height of the chain head
+	pub highest_height: u64,
+	/// The height of the last block retrieved
+	pub last_retrieved_height: u64,
+	/// A printable version of the blocks
+	pub blocks: Vec<BlockPrintable>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, JsonSchema)]
 pub struct LocatedTxKernel {
 	pub tx_kernel: TxKernel,
 	pub height: u64,
 	pub mmr_index: u64,
 }
 
+/// Where the node knows the kernel from. The stem pool is never reported, it would
+/// break the Dandelion privacy, a stem transaction is unknown.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, JsonSchema)]
+pub enum KernelState {
+	/// In the transaction pool, waiting to be mined
+	Pool,
+	/// In a block on the current chain
+	Chain,
+	/// Neither in the pool nor on the chain
+	Unknown,
+}
+
+/// Status of the transaction kernel, from the pool and the chain
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, JsonSchema)]
+pub struct KernelStatus {
+	/// Kernel excess
+	pub excess: String,
+	pub state: KernelState,
+	/// Height of the block with the kernel, if confirmed
+	pub height: Option<u64>,
+	/// Number of confirmations, 1 for the kernel in the head block
+	pub confirmations: Option<u64>,
+}
+
 #[derive(Serialize, Deserialize)]
 pub struct PoolInfo {
 	/// Size of the pool
@@ -766,7 +890,7 @@ pub struct PoolInfo {
 /// Libp2p peers from the node
 /// There are libp2p peers node  is connected to and node peers with tor addresses
 /// libp2p peers are preferable, nodes wit tor addresses can be used to expand the network
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, JsonSchema)]
 pub struct Libp2pPeers {
 	/// Libp2p prres
 	pub libp2p_peers: Vec<String>,
@@ -775,7 +899,7 @@ pub struct Libp2pPeers {
 }
 
 /// Libp2p message from this node
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, JsonSchema)]
 pub struct Libp2pMessages {
 	/// Libp2p peers
 	pub current_time: i64,
@@ -783,6 +907,300 @@ pub struct Libp2pMessages {
 	pub libp2p_messages: Vec<libp2p_connection::ReceivedMessage>,
 }
 
+/// Input of the decoded transaction
+#[derive(Debug, Serialize, Deserialize, Clone, JsonSchema)]
+pub struct DecodedInput {
+	/// Commitment of the output being spent
+	pub commit: String,
+	/// Features of the output being spent. Not available for "commit only" inputs.
+	pub output_type: Option<OutputType>,
+}
+
+/// Output of the decoded transaction
+#[derive(Debug, Serialize, Deserialize, Clone, JsonSchema)]
+pub struct DecodedOutput {
+	/// The type of output Coinbase|Transaction
+	pub output_type: OutputType,
+	/// Output commitment
+	pub commit: String,
+	/// Rangeproof hash
+	pub proof_hash: String,
+}
+
+/// Structured view of the raw transaction. Transaction is not submitted to the pool.
+#[derive(Debug, Serialize, Deserialize, Clone, JsonSchema)]
+pub struct DecodedTransaction {
+	/// Transaction hash
+	pub hash: String,
+	/// Kernel offset
+	pub offset: String,
+	/// Transaction inputs
+	pub inputs: Vec<DecodedInput>,
+	/// Transaction outputs
+	pub outputs: Vec<DecodedOutput>,
+	/// Transaction kernels
+	pub kernels: Vec<TxKernelPrintable>,
+	/// Total fee of all kernels
+	pub fee: u64,
+	/// Transaction weight
+	pub weight: u64,
+	/// Transaction weight in terms of the block weight
+	pub weight_as_block: u64,
+	/// Fee per unit of weight, pool uses it to prioritize the transactions
+	pub fee_to_weight: u64,
+	/// Max lock height of the kernels
+	pub lock_height: u64,
+	/// Protocol version that was used to decode the transaction
+	pub protocol_version: u32,
+	/// Validation error, None if transaction is valid
+	pub validation_error: Option<String>,
+}
+
+impl DecodedTransaction {
+	pub fn from_tx(
+		tx: &core::Transaction,
+		protocol_version: ser::ProtocolVersion,
+		validation_error: Option<String>,
+	) -> DecodedTransaction {
+		let inputs = match tx.inputs() {
+			core::Inputs::CommitOnly(inputs) => inputs
+				.iter()
+				.map(|i| DecodedInput {
+					commit: i.commitment().to_hex(),
+					output_type: None,
+				})
+				.collect(),
+			core::Inputs::FeaturesAndCommit(inputs) => inputs
+				.iter()
+				.map(|i| DecodedInput {
+					commit: i.commitment().to_hex(),
+					output_type: Some(if i.is_coinbase() {
+						OutputType::Coinbase
+					} else {
+						OutputType::Transaction
+					}),
+				})
+				.collect(),
+		};
+		let outputs = tx
+			.outputs()
+			.iter()
+			.map(|o| DecodedOutput {
+				output_type: if o.is_coinbase() {
+					OutputType::Coinbase
+				} else {
+					OutputType::Transaction
+				},
+				commit: o.commitment().to_hex(),
+				proof_hash: o.proof.hash().to_hex(),
+			})
+			.collect();
+		DecodedTransaction {
+			hash: tx.hash().to_hex(),
+			offset: tx.offset.to_hex(),
+			inputs,
+			outputs,
+			kernels: tx
+				.kernels()
+				.iter()
+				.map(|k| TxKernelPrintable::from_txkernel(k))
+				.collect(),
+			fee: tx.fee(),
+			weight: tx.tx_weight(),
+			weight_as_block: tx.tx_weight_as_block(),
+			fee_to_weight: tx.fee_to_weight(),
+			lock_height: tx.lock_height(),
+			protocol_version: protocol_version.value(),
+			validation_error,
+		}
+	}
+}
+
+/// Merkle proof of the coinbase output against the output root of the block that includes it.
+/// Wallets are using it for proving the coinbase maturity.
+#[derive(Debug, Serialize, Deserialize, Clone, JsonSchema)]
+pub struct CoinbaseMerkleProof {
+	/// The output commitment
+	pub commit: String,
+	/// Hash of the block that includes the output
+	pub block_hash: String,
+	/// Height of the block that includes the output
+	pub block_height: u64,
+	/// Height when the output can be spent
+	pub maturity_height: u64,
+	/// Output MMR root of the block header
+	pub output_root: String,
+	/// MMR index of the output
+	pub mmr_index: u64,
+	/// Hex encoded merkle proof
+	pub merkle_proof: String,
+}
+
+/// Merkle proof of the kernel at a kernel MMR position against the kernel root of a block.
+/// Protocols anchoring external commitments in kernels use it as the proof of inclusion.
+#[derive(Debug, Serialize, Deserialize, Clone, JsonSchema)]
+pub struct KernelMerkleProof {
+	/// The kernel
+	pub kernel: TxKernel,
+	/// Hash of the block the proof is built against
+	pub block_hash: String,
+	/// Height of the block the proof is built against
+	pub block_height: u64,
+	/// Kernel MMR root of the block header
+	pub kernel_root: String,
+	/// MMR index of the kernel
+	pub mmr_index: u64,
+	/// Hex encoded merkle proof
+	pub merkle_proof: String,
+}
+
+/// Status of the kernel as an integrity kernel for the libp2p gossip messages
+#[derive(Debug, Serialize, Deserialize, Clone, JsonSchema)]
+pub struct IntegrityKernelStatus {
+	/// Kernel excess
+	pub excess: String,
+	/// Height of the block with the kernel, None if kernel is not found during the valid period
+	pub height: Option<u64>,
+	/// Kernel fee
+	pub fee: Option<u64>,
+	/// Minimal fee that is accepted for the integrity kernel
+	pub min_fee: u64,
+	/// Last height when the kernel is still valid
+	pub valid_until_height: Option<u64>,
+	/// true if the kernel can be used for the gossip messages now
+	pub valid: bool,
+	/// Reason why the kernel is not valid
+	pub reason: Option<String>,
+}
+
+/// Block mined by this node
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, JsonSchema)]
+pub struct MinedBlockPrintable {
+	/// Block height
+	pub height: u64,
+	/// Block hash
+	pub hash: String,
+	/// Commitments of the coinbase outputs
+	pub coinbase_outputs: Vec<String>,
+	/// Excess of the coinbase kernels
+	pub coinbase_kernels: Vec<String>,
+	/// When the block was mined, unix timestamp in milliseconds
+	pub mined_at: i64,
+	/// true if the block is not on the current chain any more
+	pub orphan: bool,
+	/// Number of confirmations, None for the orphaned block
+	pub confirmations: Option<u64>,
+}
+
+impl MinedBlockPrintable {
+	pub fn from_mined_block(
+		block: &chain::MinedBlock,
+		on_chain: bool,
+		head_height: u64,
+	) -> MinedBlockPrintable {
+		MinedBlockPrintable {
+			height: block.height,
+			hash: block.hash.to_hex(),
+			coinbase_outputs: block.coinbase_outputs.iter().map(|c| c.to_hex()).collect(),
+			coinbase_kernels: block.coinbase_kernels.iter().map(|c| c.to_hex()).collect(),
+			mined_at: block.mined_at,
+			orphan: !on_chain,
+			confirmations: if on_chain {
+				Some(head_height.saturating_sub(block.height) + 1)
+			} else {
+				None
+			},
+		}
+	}
+}
+
+/// Node status with the connected peers, gathered in a single call
+#[derive(Debug, Serialize, Deserialize, Clone, JsonSchema)]
+pub struct StatusAndPeers {
+	/// Node and sync status
+	pub status: Status,
+	/// Connected peers
+	pub connected_peers: Vec<p2p::types::PeerInfoDisplayLegacy>,
+}
+
+/// Full node state for the monitoring dashboards, gathered in a single call
+#[derive(Debug, Serialize, Deserialize, Clone, JsonSchema)]
+pub struct NodeState {
+	/// Node and sync status
+	pub status: Status,
+	/// Connected peers
+	pub connected_peers: Vec<p2p::types::PeerInfoDisplayLegacy>,
+	/// Mix of the connected peers by latency, address class and user agent
+	pub peer_diversity: p2p::types::PeerDiversityReport,
+	/// Dandelion stempool with the embargo timers
+	pub stempool: crate::pool::StemPoolStatus,
+	/// Freeze state of the chain tip
+	pub tip_freeze: chain::TipFreezeStatus,
+}
+
+/// Result of running a raw p2p or gossip message through the validation pipeline
+#[derive(Debug, Serialize, Deserialize, Clone, JsonSchema)]
+pub struct MessageSimulation {
+	/// Message protocol, "p2p" or "gossip"
+	pub protocol: String,
+	/// p2p message type if the header was read
+	pub message_type: Option<String>,
+	/// Checks that was done, in the validation order. Validation stops at the first failed check.
+	pub checks: Vec<SimulationCheck>,
+	/// true if the message passes all checks
+	pub valid: bool,
+}
+
+/// Result of the block validation against the current chain head
+#[derive(Debug, Serialize, Deserialize, Clone, JsonSchema)]
+pub struct BlockValidation {
+	/// Block hash
+	pub hash: String,
+	/// Block height
+	pub height: u64,
+	/// true if the block can be accepted as the next block
+	pub valid: bool,
+	/// Chain error kind if the block is not valid, for example 'InvalidBlockProof'
+	pub error_kind: Option<String>,
+	/// Error details if the block is not valid
+	pub error: Option<String>,
+}
+
+/// Result of the API secret rotation
+#[derive(Debug, Serialize, Deserialize, Clone, JsonSchema)]
+pub struct ApiSecretRotation {
+	/// Path to the secret file with the new secret
+	pub secret_path: String,
+	/// rfc3339 timestamp until the previous secret is accepted
+	pub previous_secret_valid_until: String,
+}
+
+/// Provenance of the node binary
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, JsonSchema)]
+pub struct BuildInfo {
+	/// Version of the node
+	pub version: String,
+	/// Git commit the binary was built from
+	pub git_commit: Option<String>,
+	/// Whether the sources had uncommitted changes
+	pub git_dirty: Option<bool>,
+	/// Version of the compiler
+	pub rustc_version: String,
+	/// Target triple
+	pub target: String,
+	/// Cargo profile
+	pub profile: String,
+	/// Enabled cargo features
+	pub features: String,
+	/// rfc2822 build time, the commit time for the reproducible builds
+	pub build_time: String,
+	/// Whether the binary was built with SOURCE_DATE_EPOCH set, by the reproducible build script
+	pub reproducible: bool,
+	/// Hash of the build inputs: version, commit, compiler, target, profile, features and the
+	/// dependencies. Builds with the same hash are expected to produce the same binary.
+	pub reproducibility_hash: String,
+}
+
 #[cfg(test)]
 mod test {
 	use super::*;
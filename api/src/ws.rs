@@ -0,0 +1,341 @@
+// Copyright 2021 The MWC Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! WebSocket stream of the node events. A client connects to `/v2/ws`, optionally with
+//! the topics filter `?topics=block_accepted,reorg`, and receives the JSON events of those
+//! topics. The filter can be changed later with the `{"subscribe": [..]}` and
+//! `{"unsubscribe": [..]}` text messages.
+
+use crate::chain::SyncState;
+use crate::handlers::server_api::sync_status_to_api;
+use crate::router::{Handler, ResponseFuture};
+use crate::web::*;
+use chrono::Utc;
+use futures::{SinkExt, StreamExt};
+use hyper::header::{CONNECTION, SEC_WEBSOCKET_ACCEPT, SEC_WEBSOCKET_KEY, UPGRADE};
+use hyper::{Body, Request, Response, StatusCode};
+use ring::digest;
+use serde_json::{json, Value};
+use std::collections::HashSet;
+use std::sync::Weak;
+use std::thread;
+use std::time::Duration;
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::sync::broadcast;
+use tokio_tungstenite::tungstenite::protocol::Role;
+use tokio_tungstenite::tungstenite::Message;
+use tokio_tungstenite::WebSocketStream;
+
+/// Block is accepted by the chain, it might be the new head, a fork or a reorg
+pub const WS_TOPIC_BLOCK_ACCEPTED: &str = "block_accepted";
+/// Chain head is switched to another fork
+pub const WS_TOPIC_REORG: &str = "reorg";
+/// Transaction is accepted by the pool
+pub const WS_TOPIC_TX_POOL_ACCEPTED: &str = "tx_pool_accepted";
+/// Sync status is changed
+pub const WS_TOPIC_SYNC_STATUS: &str = "sync_status";
+
+/// All topics of the event stream
+pub const WS_TOPICS: [&str; 4] = [
+	WS_TOPIC_BLOCK_ACCEPTED,
+	WS_TOPIC_REORG,
+	WS_TOPIC_TX_POOL_ACCEPTED,
+	WS_TOPIC_SYNC_STATUS,
+];
+
+/// Number of the events that are buffered for a slow client. Older events are dropped.
+const WS_EVENTS_CAPACITY: usize = 1024;
+
+/// Sync status polling interval
+const WS_SYNC_STATUS_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Magic string of the WebSocket handshake, RFC 6455
+const WS_GUID: &str = "258EAFA5-E914-47DA-95CA-C5AB0DC85B11";
+
+lazy_static! {
+	static ref WS_EVENTS: broadcast::Sender<WsEvent> = broadcast::channel(WS_EVENTS_CAPACITY).0;
+}
+
+/// Event that is streamed to the WebSocket clients
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct WsEvent {
+	/// Event topic
+	pub topic: String,
+	/// Unix timestamp when the event is published
+	pub timestamp: i64,
+	/// Event data, depends on the topic
+	pub data: Value,
+}
+
+/// true if there are connected WebSocket clients. Publishers can skip building the events otherwise.
+pub fn has_ws_clients() -> bool {
+	WS_EVENTS.receiver_count() > 0
+}
+
+/// Publish the event to the connected WebSocket clients
+pub fn publish_ws_event(topic: &str, data: Value) {
+	if !has_ws_clients() {
+		return;
+	}
+	// Error means that there are no clients, nothing to do
+	let _ = WS_EVENTS.send(WsEvent {
+		topic: topic.to_string(),
+		timestamp: Utc::now().timestamp(),
+		data,
+	});
+}
+
+/// Subscribe to the published events. Other streaming APIs use it to share the WebSocket events.
+pub fn subscribe_ws_events() -> broadcast::Receiver<WsEvent> {
+	WS_EVENTS.subscribe()
+}
+
+/// Publish the sync status changes. The status is polled, the thread exits when the
+/// sync state is dropped.
+pub fn start_sync_status_watch(sync_state: Weak<SyncState>) {
+	let res = thread::Builder::new()
+		.name("ws_sync_status".to_string())
+		.spawn(move || {
+			let mut last_status = None;
+			loop {
+				let status = match sync_state.upgrade() {
+					Some(sync_state) => sync_state.status(),
+					None => break,
+				};
+				if last_status != Some(status) {
+					last_status = Some(status);
+					let (status, info) = sync_status_to_api(status);
+					publish_ws_event(
+						WS_TOPIC_SYNC_STATUS,
+						json!({ "status": status, "info": info }),
+					);
+				}
+				thread::sleep(WS_SYNC_STATUS_INTERVAL);
+			}
+		});
+	if let Err(e) = res {
+		error!("Unable to start the sync status watch thread, {}", e);
+	}
+}
+
+/// Topics that the WebSocket client is subscribed to
+#[derive(Debug, Clone, PartialEq)]
+pub struct WsTopicFilter {
+	topics: HashSet<String>,
+}
+
+impl WsTopicFilter {
+	/// Filter from the comma separated topics list, all topics if the list is not set.
+	pub fn from_topics(topics: Option<&str>) -> Result<WsTopicFilter, String> {
+		let mut filter = WsTopicFilter {
+			topics: HashSet::new(),
+		};
+		match topics {
+			Some(topics) => filter.subscribe(topics.split(',').filter(|t| !t.is_empty()))?,
+			None => filter.subscribe(WS_TOPICS.iter().cloned())?,
+		}
+		Ok(filter)
+	}
+
+	/// true if the event with this topic must be sent to the client
+	pub fn matches(&self, topic: &str) -> bool {
+		self.topics.contains(topic)
+	}
+
+	/// Subscribed topics, sorted
+	pub fn topics(&self) -> Vec<String> {
+		let mut topics: Vec<String> = self.topics.iter().cloned().collect();
+		topics.sort();
+		topics
+	}
+
+	/// Apply the client request: `{"subscribe": [..]}` or `{"unsubscribe": [..]}`
+	pub fn apply_request(&mut self, request: &str) -> Result<(), String> {
+		let request: Value = serde_json::from_str(request)
+			.map_err(|e| format!("Unable to parse the request, {}", e))?;
+		let topics = |name: &str| -> Result<Option<Vec<String>>, String> {
+			match request.get(name) {
+				Some(topics) => serde_json::from_value(topics.clone())
+					.map(Some)
+					.map_err(|e| format!("Invalid '{}' topics, {}", name, e)),
+				None => Ok(None),
+			}
+		};
+		let subscribe = topics("subscribe")?;
+		let unsubscribe = topics("unsubscribe")?;
+		if subscribe.is_none() && unsubscribe.is_none() {
+			return Err("Expected 'subscribe' or 'unsubscribe' topics".to_string());
+		}
+		if let Some(topics) = subscribe {
+			self.subscribe(topics.iter().map(|t| t.as_str()))?;
+		}
+		if let Some(topics) = unsubscribe {
+			for t in topics {
+				self.topics.remove(&t);
+			}
+		}
+		Ok(())
+	}
+
+	fn subscribe<'a, I>(&mut self, topics: I) -> Result<(), String>
+	where
+		I: Iterator<Item = &'a str>,
+	{
+		for t in topics {
+			if !WS_TOPICS.contains(&t) {
+				return Err(format!(
+					"Unknown topic '{}', expected one of {}",
+					t,
+					WS_TOPICS.join(", ")
+				));
+			}
+			self.topics.insert(t.to_string());
+		}
+		Ok(())
+	}
+}
+
+/// Sec-WebSocket-Accept header value for the client key
+fn ws_accept_key(key: &[u8]) -> String {
+	let mut ctx = digest::Context::new(&digest::SHA1_FOR_LEGACY_USE_ONLY);
+	ctx.update(key);
+	ctx.update(WS_GUID.as_bytes());
+	base64::encode(ctx.finish().as_ref())
+}
+
+/// Handler of the `/v2/ws` endpoint, upgrades the connection to WebSocket
+pub struct WsHandler;
+
+impl Handler for WsHandler {
+	fn get(&self, req: Request<Body>) -> ResponseFuture {
+		let is_upgrade = req
+			.headers()
+			.get(UPGRADE)
+			.and_then(|v| v.to_str().ok())
+			.map(|v| v.eq_ignore_ascii_case("websocket"))
+			.unwrap_or(false);
+		let accept_key = match req.headers().get(SEC_WEBSOCKET_KEY) {
+			Some(key) if is_upgrade => ws_accept_key(key.as_bytes()),
+			_ => return response(StatusCode::BAD_REQUEST, "expected WebSocket upgrade"),
+		};
+		let params = QueryParams::from(req.uri().query());
+		let filter = match WsTopicFilter::from_topics(params.get("topics").map(|t| t.as_str())) {
+			Ok(filter) => filter,
+			Err(e) => return response(StatusCode::BAD_REQUEST, e),
+		};
+
+		// Subscribing before the upgrade, so the events are not missed
+		let events = WS_EVENTS.subscribe();
+		tokio::spawn(async move {
+			match req.into_body().on_upgrade().await {
+				Ok(upgraded) => {
+					let ws = WebSocketStream::from_raw_socket(upgraded, Role::Server, None).await;
+					serve_ws(ws, filter, events).await;
+				}
+				Err(e) => warn!("WebSocket upgrade failed, {}", e),
+			}
+		});
+
+		let resp = Response::builder()
+			.status(StatusCode::SWITCHING_PROTOCOLS)
+			.header(UPGRADE, "websocket")
+			.header(CONNECTION, "Upgrade")
+			.header(SEC_WEBSOCKET_ACCEPT, accept_key)
+			.body(Body::empty());
+		match resp {
+			Ok(resp) => Box::pin(async { Ok(resp) }),
+			Err(e) => response(StatusCode::INTERNAL_SERVER_ERROR, format!("{}", e)),
+		}
+	}
+}
+
+async fn serve_ws<S>(
+	ws: WebSocketStream<S>,
+	mut filter: WsTopicFilter,
+	mut events: broadcast::Receiver<WsEvent>,
+) where
+	S: AsyncRead + AsyncWrite + Unpin,
+{
+	let (mut sink, mut stream) = ws.split();
+	loop {
+		let reply = tokio::select! {
+			msg = stream.next() => match msg {
+				Some(Ok(Message::Text(request))) => match filter.apply_request(&request) {
+					Ok(_) => json!({ "topics": filter.topics() }),
+					Err(e) => json!({ "error": e }),
+				},
+				Some(Ok(Message::Close(_))) | Some(Err(_)) | None => break,
+				// Pings are answered by the protocol layer
+				Some(Ok(_)) => continue,
+			},
+			event = events.recv() => match event {
+				Ok(event) => {
+					if !filter.matches(&event.topic) {
+						continue;
+					}
+					match serde_json::to_value(&event) {
+						Ok(event) => event,
+						Err(e) => {
+							error!("Unable to serialize WebSocket event, {}", e);
+							continue;
+						}
+					}
+				}
+				Err(broadcast::RecvError::Lagged(n)) => {
+					json!({ "error": format!("{} events are dropped, the client is too slow", n) })
+				}
+				Err(broadcast::RecvError::Closed) => break,
+			},
+		};
+		if sink.send(Message::Text(reply.to_string())).await.is_err() {
+			break;
+		}
+	}
+	let _ = sink.close().await;
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+
+	#[test]
+	fn test_ws_topic_filter() {
+		let filter = WsTopicFilter::from_topics(None).unwrap();
+		assert!(WS_TOPICS.iter().all(|t| filter.matches(t)));
+
+		let mut filter = WsTopicFilter::from_topics(Some("reorg,block_accepted")).unwrap();
+		assert!(filter.matches(WS_TOPIC_REORG));
+		assert!(!filter.matches(WS_TOPIC_TX_POOL_ACCEPTED));
+		assert!(WsTopicFilter::from_topics(Some("reorg,unknown")).is_err());
+
+		filter
+			.apply_request(r#"{"subscribe": ["sync_status"], "unsubscribe": ["reorg"]}"#)
+			.unwrap();
+		assert_eq!(filter.topics(), vec!["block_accepted", "sync_status"]);
+		assert!(filter
+			.apply_request(r#"{"subscribe": ["unknown"]}"#)
+			.is_err());
+		assert!(filter.apply_request(r#"{"topics": []}"#).is_err());
+		assert!(filter.apply_request("not a json").is_err());
+	}
+
+	#[test]
+	fn test_ws_accept_key() {
+		// Example from RFC 6455
+		assert_eq!(
+			ws_accept_key(b"dGhlIHNhbXBsZSBub25jZQ=="),
+			"s3pPLMBmrGYsKT1zUmKNxRR+8Yk="
+		);
+	}
+}
@@ -0,0 +1,24 @@
+#!/bin/sh
+# Reproducible release build of the mwc node. Builds of the same commit with the same
+# rust toolchain and target produce the same binary, so anybody can verify the
+# official binaries. Compare 'mwc --build-info' and the sha256 printed at the end.
+
+set -e
+
+if [ -n "$(git status --porcelain --untracked-files=no)" ]; then
+	echo "The sources have uncommitted changes, commit or stash them first"
+	exit 1
+fi
+
+# The build time is the commit time
+export SOURCE_DATE_EPOCH=$(git log -1 --format=%ct)
+# The binary must not depend on the location of the sources and the cargo home
+export RUSTFLAGS="--remap-path-prefix=$(pwd)=/mwc-node --remap-path-prefix=${CARGO_HOME:-$HOME/.cargo}=/cargo -C link-arg=-Wl,--build-id=none"
+export CARGO_INCREMENTAL=0
+export CARGO_PROFILE_RELEASE_CODEGEN_UNITS=1
+
+cargo clean --release
+cargo build --release --locked
+
+./target/release/mwc --build-info
+sha256sum target/release/mwc
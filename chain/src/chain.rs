@@ -19,26 +19,39 @@ use crate::core::core::hash::{Hash, Hashed, ZERO_HASH};
 use crate::core::core::merkle_proof::MerkleProof;
 use crate::core::core::verifier_cache::VerifierCache;
 use crate::core::core::{
+	pmmr::{self, Segment, SegmentError, SegmentIdentifier},
 	Block, BlockHeader, BlockSums, Committed, Inputs, KernelFeatures, Output, OutputIdentifier,
 	Transaction, TxKernel,
 };
 use crate::core::global;
 use crate::core::pow;
-use crate::core::ser::ProtocolVersion;
+use crate::core::ser::{self, ProtocolVersion};
 use crate::error::{Error, ErrorKind};
+use crate::forensics::BlockForensics;
+use crate::freeze::TipFreeze;
 use crate::pipe;
 use crate::store;
 use crate::txhashset;
-use crate::txhashset::{PMMRHandle, TxHashSet};
+use crate::txhashset::{Desegmenter, PMMRHandle, SegmentType, Segmenter, TxHashSet};
 use crate::types::{
-	BlockStatus, ChainAdapter, CommitPos, NoStatus, Options, Tip, TxHashsetWriteStatus,
+	BlockRejection, BlockStatus, BlockTimingStats, BlockTimings, ChainAdapter, ChainStats,
+	CommitPos, HashHeight, MinedBlock, NoStatus, Options, OutputRoots, RangeOutput, ReorgEvent,
+	ReorgListener, ReorgRecord, Tip, TipFreezeStatus, TxHashsetWriteStatus, UnspentOutput,
+	UtxoCursor,
 };
 use crate::util::secp::pedersen::{Commitment, RangeProof};
-use crate::{util::RwLock, ChainStore};
+use crate::watch::{
+	WatchEvent, WatchEventKind, WatchEventPage, WatchFilter, WatchFilterInfo, WatchMatcher,
+};
+use crate::{util::Mutex, util::RwLock, ChainStore};
+use chrono::prelude::{DateTime, TimeZone, Utc};
+use grin_store::encryption::EncryptionKey;
 use grin_store::Error::NotFoundErr;
 use grin_util::ToHex;
-use std::collections::HashMap;
+use std::cmp;
+use std::collections::{BTreeMap, HashMap, VecDeque};
 use std::fs::{self, File};
+use std::io::{Seek, SeekFrom};
 use std::path::{Path, PathBuf};
 use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::Arc;
@@ -50,23 +63,82 @@ pub const MAX_ORPHAN_SIZE: usize = 200;
 /// When evicting, very old orphans are evicted first
 const MAX_ORPHAN_AGE_SECS: u64 = 300;
 
+/// Number of headers applied at once when the header MMR is rebuilt
+const HEADER_MMR_REBUILD_CHUNK: usize = 10_000;
+
+/// Default memory cap of the orphan pool, bytes
+pub const ORPHANS_MAX_SIZE_BYTES: usize = 100 * 1024 * 1024;
+
+/// Number of blocks above the fork point body sync looks at in archive mode,
+/// where the whole chain is synced as full blocks.
+const ARCHIVE_SYNC_WINDOW: u64 = 1_000;
+
+/// How long we keep the first seen timestamps of transactions. Blocks first seen
+/// timestamps are kept forever.
+const TX_FIRST_SEEN_RETENTION_SECS: i64 = 7 * 24 * 3600;
+
+/// First seen times of the txs are written to the db in batches of this size,
+const TX_FIRST_SEEN_FLUSH_SIZE: usize = 100;
+/// or when the last write is that old.
+const TX_FIRST_SEEN_FLUSH_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Max number of the blocks scanned by a single chunk of the background compaction
+const COMPACTION_CHUNK_BLOCKS: usize = 1_000;
+
+/// Folder (under the db root) keeping a copy of the trusted txhashset snapshot
+/// until its history is validated.
+const ASSUMED_UTXO_SUBDIR: &str = "assumeutxo";
+
+/// Number of the last reorgs kept in the reorg history
+pub const REORG_HISTORY_SIZE: usize = 100;
+
 /// Banned block. We don't accept any blockchain with this has
 pub const BLOCK_TO_BAN: &str = "00020440a401086e57e1b7a92ebb0277c7f7fd47a38269ecc6789c2a80333725";
 
+// Number of outputs spent by the last blocks of the chain, for the chain stats.
+// spent[i] is the number of outputs spent by the i blocks up to and including the tip.
+struct SpentCounts {
+	tip: Hash,
+	spent: Vec<u64>,
+}
+
 #[derive(Debug, Clone)]
 struct Orphan {
 	block: Block,
 	opts: Options,
 	added: Instant,
+	// estimated memory used by the block, bytes
+	size: usize,
+}
+
+/// Orphan pool statistics.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct OrphanStats {
+	/// Number of orphans in the pool
+	pub count: usize,
+	/// Estimated memory used by the orphans, bytes
+	pub size_bytes: usize,
+	/// Memory cap of the pool, bytes
+	pub max_size_bytes: usize,
+	/// Orphans processed once their parent arrived
+	pub hits: usize,
+	/// Orphans evicted (expired or over the caps) without being processed
+	pub misses: usize,
 }
 
 pub struct OrphanBlockPool {
 	// blocks indexed by their hash
 	orphans: RwLock<HashMap<Hash, Orphan>>,
-	// additional index of height -> hash
-	// so we can efficiently identify a child block (ex-orphan) after processing a block
-	height_idx: RwLock<HashMap<u64, Vec<Hash>>>,
-	// accumulated number of evicted block because of MAX_ORPHAN_SIZE limitation
+	// orphans bucketed by height, ordered so we can efficiently identify a child
+	// block (ex-orphan) after processing a block and the orphans to evict
+	height_idx: RwLock<BTreeMap<u64, Vec<Hash>>>,
+	// estimated memory used by the orphans
+	size_bytes: AtomicUsize,
+	// memory cap of the pool
+	max_size_bytes: AtomicUsize,
+	// accumulated number of orphans processed after their parent arrived
+	hits: AtomicUsize,
+	// accumulated number of evicted orphans
 	evicted: AtomicUsize,
 }
 
@@ -74,7 +146,10 @@ impl OrphanBlockPool {
 	fn new() -> OrphanBlockPool {
 		OrphanBlockPool {
 			orphans: RwLock::new(HashMap::new()),
-			height_idx: RwLock::new(HashMap::new()),
+			height_idx: RwLock::new(BTreeMap::new()),
+			size_bytes: AtomicUsize::new(0),
+			max_size_bytes: AtomicUsize::new(ORPHANS_MAX_SIZE_BYTES),
+			hits: AtomicUsize::new(0),
 			evicted: AtomicUsize::new(0),
 		}
 	}
@@ -88,53 +163,113 @@ impl OrphanBlockPool {
 		self.evicted.load(Ordering::Relaxed)
 	}
 
-	fn add(&self, orphan: Orphan) {
+	fn set_max_size_bytes(&self, max_size_bytes: usize) {
+		self.max_size_bytes.store(max_size_bytes, Ordering::Relaxed);
+	}
+
+	fn stats(&self) -> OrphanStats {
+		OrphanStats {
+			count: self.len(),
+			size_bytes: self.size_bytes.load(Ordering::Relaxed),
+			max_size_bytes: self.max_size_bytes.load(Ordering::Relaxed),
+			hits: self.hits.load(Ordering::Relaxed),
+			misses: self.evicted.load(Ordering::Relaxed),
+		}
+	}
+
+	fn is_full(&self, orphans: &HashMap<Hash, Orphan>) -> bool {
+		orphans.len() > MAX_ORPHAN_SIZE
+			|| self.size_bytes.load(Ordering::Relaxed) > self.max_size_bytes.load(Ordering::Relaxed)
+	}
+
+	// Remove the orphans from the pool, keeping the index and the size in sync.
+	fn remove_orphans(
+		&self,
+		orphans: &mut HashMap<Hash, Orphan>,
+		height_idx: &mut BTreeMap<u64, Vec<Hash>>,
+		height: u64,
+	) -> Vec<Orphan> {
+		let removed: Vec<Orphan> = height_idx
+			.remove(&height)
+			.unwrap_or_default()
+			.iter()
+			.filter_map(|h| orphans.remove(h))
+			.collect();
+		let size: usize = removed.iter().map(|x| x.size).sum();
+		self.size_bytes.fetch_sub(size, Ordering::Relaxed);
+		removed
+	}
+
+	/// Add an orphan. When the pool is over its caps the expired orphans are
+	/// evicted first, then the orphans furthest from the head height.
+	fn add(&self, orphan: Orphan, head_height: u64) {
 		let mut orphans = self.orphans.write();
 		let mut height_idx = self.height_idx.write();
-		{
-			let height_hashes = height_idx
-				.entry(orphan.block.header.height)
-				.or_insert_with(|| vec![]);
-			height_hashes.push(orphan.block.hash());
-			orphans.insert(orphan.block.hash(), orphan);
+		let hash = orphan.block.hash();
+		if orphans.contains_key(&hash) {
+			return;
+		}
+		height_idx
+			.entry(orphan.block.header.height)
+			.or_insert_with(|| vec![])
+			.push(hash);
+		self.size_bytes.fetch_add(orphan.size, Ordering::Relaxed);
+		orphans.insert(hash, orphan);
+
+		if !self.is_full(&orphans) {
+			return;
 		}
+		let old_len = orphans.len();
 
-		if orphans.len() > MAX_ORPHAN_SIZE {
-			let old_len = orphans.len();
+		// evict too old
+		let expired: Vec<Hash> = orphans
+			.iter()
+			.filter(|(_, x)| x.added.elapsed() >= Duration::from_secs(MAX_ORPHAN_AGE_SECS))
+			.map(|(h, _)| h.clone())
+			.collect();
+		for h in expired {
+			if let Some(x) = orphans.remove(&h) {
+				self.size_bytes.fetch_sub(x.size, Ordering::Relaxed);
+			}
+		}
+		height_idx.retain(|_, xs| {
+			xs.retain(|x| orphans.contains_key(x));
+			!xs.is_empty()
+		});
 
-			// evict too old
-			orphans.retain(|_, ref mut x| {
-				x.added.elapsed() < Duration::from_secs(MAX_ORPHAN_AGE_SECS)
-			});
-			// evict too far ahead
-			let mut heights = height_idx.keys().cloned().collect::<Vec<u64>>();
-			heights.sort_unstable();
-			for h in heights.iter().rev() {
-				if let Some(hs) = height_idx.remove(h) {
-					for h in hs {
-						let _ = orphans.remove(&h);
+		// evict furthest from the head, the lowest and the highest buckets are the candidates
+		while self.is_full(&orphans) {
+			let lowest = height_idx.keys().next().cloned();
+			let highest = height_idx.keys().next_back().cloned();
+			let height = match (lowest, highest) {
+				(Some(lowest), Some(highest)) => {
+					if head_height.saturating_sub(lowest) > highest.saturating_sub(head_height) {
+						lowest
+					} else {
+						highest
 					}
 				}
-				if orphans.len() < MAX_ORPHAN_SIZE {
-					break;
-				}
-			}
-			// cleanup index
-			height_idx.retain(|_, ref mut xs| xs.iter().any(|x| orphans.contains_key(&x)));
-
-			self.evicted
-				.fetch_add(old_len - orphans.len(), Ordering::Relaxed);
+				_ => break,
+			};
+			self.remove_orphans(&mut orphans, &mut height_idx, height);
 		}
+
+		self.evicted
+			.fetch_add(old_len - orphans.len(), Ordering::Relaxed);
 	}
 
-	/// Get an orphan from the pool indexed by the hash of its parent, removing
-	/// it at the same time, preventing clone
+	/// Get the orphans at the given height, removing them from the pool at the
+	/// same time, preventing clone
 	fn remove_by_height(&self, height: u64) -> Option<Vec<Orphan>> {
 		let mut orphans = self.orphans.write();
 		let mut height_idx = self.height_idx.write();
-		height_idx
-			.remove(&height)
-			.map(|hs| hs.iter().filter_map(|h| orphans.remove(h)).collect())
+		let removed = self.remove_orphans(&mut orphans, &mut height_idx, height);
+		if removed.is_empty() {
+			None
+		} else {
+			self.hits.fetch_add(removed.len(), Ordering::Relaxed);
+			Some(removed)
+		}
 	}
 
 	pub fn contains(&self, hash: &Hash) -> bool {
@@ -143,6 +278,61 @@ impl OrphanBlockPool {
 	}
 }
 
+/// Progress of a chain compaction run in chunks with `Chain::compact_chunk`.
+#[derive(Debug, Default)]
+pub struct CompactionState {
+	// Whether the compaction was checked to be due
+	started: bool,
+	// Next part of the compaction
+	step: CompactionStep,
+	// New body tail, the blocks below it are removed
+	tail: Option<BlockHeader>,
+	// Horizon of the txhashset compaction
+	horizon_header: Option<BlockHeader>,
+	// Db key of the last scanned block, the historical blocks scan resumes from it
+	scanned_key: Option<Vec<u8>>,
+}
+
+/// Parts of the chain compaction, every part runs with its own db batch.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum CompactionStep {
+	RemoveBlocks,
+	CompactOutputs,
+	CompactRangeproofs,
+	OutputPosIndex,
+	RecentIndexes,
+}
+
+impl Default for CompactionStep {
+	fn default() -> CompactionStep {
+		CompactionStep::RemoveBlocks
+	}
+}
+
+/// Iterator over the full blocks of a height range of the main chain,
+/// see `Chain::get_blocks_range`.
+pub struct BlocksRange {
+	store: Arc<store::ChainStore>,
+	hashes: std::vec::IntoIter<Hash>,
+}
+
+impl Iterator for BlocksRange {
+	type Item = Result<Block, Error>;
+
+	fn next(&mut self) -> Option<Self::Item> {
+		let hash = self.hashes.next()?;
+		Some(
+			self.store
+				.get_block(&hash)
+				.map_err(|e| ErrorKind::StoreErr(e, "chain get block".to_owned()).into()),
+		)
+	}
+
+	fn size_hint(&self) -> (usize, Option<usize>) {
+		self.hashes.size_hint()
+	}
+}
+
 /// Facade to the blockchain block processing pipeline and storage. Provides
 /// the current view of the TxHashSet according to the chain state. Also
 /// maintains locking for the pipeline to avoid conflicting processing.
@@ -150,15 +340,47 @@ pub struct Chain {
 	db_root: String,
 	store: Arc<store::ChainStore>,
 	adapter: Arc<dyn ChainAdapter + Send + Sync>,
+	reorg_listeners: RwLock<Vec<Arc<dyn ReorgListener + Send + Sync>>>,
+	// last reorgs of the chain head
+	reorg_history: RwLock<VecDeque<ReorgRecord>>,
 	orphans: Arc<OrphanBlockPool>,
+	// per-stage timings of the accepted blocks
+	block_timings: RwLock<BlockTimingStats>,
+	// last blocks rejected by the pipeline
+	forensics: BlockForensics,
 	txhashset: Arc<RwLock<txhashset::TxHashSet>>,
 	header_pmmr: Arc<RwLock<txhashset::PMMRHandle<BlockHeader>>>,
 	sync_pmmr: Arc<RwLock<txhashset::PMMRHandle<BlockHeader>>>,
+	// txhashset being rebuilt from segments during state sync
+	desegmenter: Arc<RwLock<Option<Desegmenter>>>,
+	// segments served to the peers, built once per txhashset archive header
+	segmenter: RwLock<Option<Arc<Segmenter>>>,
+	// hash of the txhashset zip served to the peers, by header hash
+	txhashset_zip_hash: RwLock<Option<(Hash, Hash)>>,
+	// spent output counts of the last blocks, follows the tip
+	spent_counts: Mutex<Option<SpentCounts>>,
 	verifier_cache: Arc<RwLock<dyn VerifierCache>>,
 	// POW verification function
 	pow_verifier: fn(&BlockHeader) -> Result<(), pow::Error>,
 	archive_mode: bool,
 	genesis: BlockHeader,
+	// cached watch filters, None until they are loaded from the db. The lock serializes
+	// the updates of the watch filters.
+	watch_filters: Mutex<Option<Vec<WatchFilter>>>,
+	// blocks buffered while the tip is frozen
+	tip_freeze: TipFreeze,
+	// first seen times of the txs (by kernel excess) that are not written yet, and the
+	// time of the last write
+	tx_first_seen_pending: Mutex<(HashMap<Commitment, i64>, Instant)>,
+}
+
+impl Drop for Chain {
+	fn drop(&mut self) {
+		let mut pending = self.tx_first_seen_pending.lock();
+		if let Err(e) = self.flush_tx_first_seen(&mut pending) {
+			warn!("Unable to write the first seen times of the txs, {}", e);
+		}
+	}
 }
 
 impl Chain {
@@ -173,7 +395,29 @@ impl Chain {
 		verifier_cache: Arc<RwLock<dyn VerifierCache>>,
 		archive_mode: bool,
 	) -> Result<Chain, Error> {
-		let store = Arc::new(store::ChainStore::new(&db_root)?);
+		Chain::init_with_key(
+			db_root,
+			adapter,
+			genesis,
+			pow_verifier,
+			verifier_cache,
+			archive_mode,
+			None,
+		)
+	}
+
+	/// Initializes the blockchain like `init`, with the chain db encrypted with the
+	/// provided key.
+	pub fn init_with_key(
+		db_root: String,
+		adapter: Arc<dyn ChainAdapter + Send + Sync>,
+		genesis: Block,
+		pow_verifier: fn(&BlockHeader) -> Result<(), pow::Error>,
+		verifier_cache: Arc<RwLock<dyn VerifierCache>>,
+		archive_mode: bool,
+		encryption: Option<Arc<EncryptionKey>>,
+	) -> Result<Chain, Error> {
+		let store = Arc::new(store::ChainStore::new_with_key(&db_root, encryption)?);
 
 		// DB migrations to be run prior to the chain being used.
 		// Migrate full blocks to protocol version v3.
@@ -182,17 +426,38 @@ impl Chain {
 		// open the txhashset, creating a new one if necessary
 		let mut txhashset = txhashset::TxHashSet::open(db_root.clone(), store.clone(), None)?;
 
+		// The header MMR is rebuilt from the headers in the db if it doesn't match the
+		// header head. Other errors (the files can't be opened) are not fixed by the rebuild.
+		let header_path = Path::new(&db_root).join("header").join("header_head");
 		let mut header_pmmr = PMMRHandle::new(
-			Path::new(&db_root).join("header").join("header_head"),
+			&header_path,
 			false,
 			ProtocolVersion(1),
 			None,
+			store.encryption_key().as_deref(),
 		)?;
+		if let Ok(head) = store.header_head() {
+			if let Err(e) = header_pmmr.init_head(&head) {
+				match e.kind() {
+					ErrorKind::HeaderMMRCorrupted(_) => {
+						warn!(
+							"init: header MMR is corrupted, {}, rebuilding it from the db",
+							e
+						);
+						// The rebuild replaces the MMR files
+						drop(header_pmmr);
+						header_pmmr = Chain::rebuild_header_pmmr(&store, &header_path)?;
+					}
+					_ => return Err(e),
+				}
+			}
+		}
 		let mut sync_pmmr = PMMRHandle::new(
 			Path::new(&db_root).join("header").join("sync_head"),
 			false,
 			ProtocolVersion(1),
 			None,
+			store.encryption_key().as_deref(),
 		)?;
 
 		setup_head(
@@ -203,27 +468,41 @@ impl Chain {
 			&mut txhashset,
 		)?;
 
-		// Initialize the output_pos index based on UTXO set
-		// and NRD kernel_pos index based recent kernel history.
+		// Initialize the output_pos index based on UTXO set,
+		// NRD kernel_pos index based recent kernel history
+		// and the kernel excess index based on the full kernel MMR.
 		{
 			let batch = store.batch()?;
 			txhashset.init_output_pos_index(&header_pmmr, &batch)?;
 			txhashset.init_recent_kernel_pos_index(&header_pmmr, &batch)?;
+			txhashset.init_kernel_pos_index(&header_pmmr, &batch, false)?;
 			batch.commit()?;
 		}
 
+		let forensics = BlockForensics::new(&db_root);
 		let chain = Chain {
 			db_root,
 			store,
 			adapter,
+			reorg_listeners: RwLock::new(vec![]),
+			reorg_history: RwLock::new(VecDeque::new()),
 			orphans: Arc::new(OrphanBlockPool::new()),
+			block_timings: RwLock::new(BlockTimingStats::default()),
+			forensics,
 			txhashset: Arc::new(RwLock::new(txhashset)),
 			header_pmmr: Arc::new(RwLock::new(header_pmmr)),
 			sync_pmmr: Arc::new(RwLock::new(sync_pmmr)),
+			desegmenter: Arc::new(RwLock::new(None)),
+			segmenter: RwLock::new(None),
+			txhashset_zip_hash: RwLock::new(None),
+			spent_counts: Mutex::new(None),
 			pow_verifier,
 			verifier_cache,
 			archive_mode,
 			genesis: genesis.header,
+			watch_filters: Mutex::new(None),
+			tip_freeze: TipFreeze::new(),
+			tx_first_seen_pending: Mutex::new((HashMap::new(), Instant::now())),
 		};
 
 		// If known bad block exists on "current chain" then rewind prior to this.
@@ -238,6 +517,75 @@ impl Chain {
 		Ok(chain)
 	}
 
+	/// Register a listener to be notified on every chain reorg.
+	pub fn register_reorg_listener(&self, listener: Arc<dyn ReorgListener + Send + Sync>) {
+		self.reorg_listeners.write().push(listener);
+	}
+
+	/// Add the reorg to the reorg history, the oldest records are dropped.
+	fn record_reorg(&self, prev_head: &Tip, head: &Tip, fork_point: &Tip) {
+		let record = ReorgRecord {
+			depth: prev_head.height.saturating_sub(fork_point.height),
+			old_tip_hash: prev_head.last_block_h.to_hex(),
+			old_tip_height: prev_head.height,
+			new_tip_hash: head.last_block_h.to_hex(),
+			new_tip_height: head.height,
+			fork_point_hash: fork_point.last_block_h.to_hex(),
+			fork_point_height: fork_point.height,
+			timestamp: Utc::now().timestamp(),
+		};
+		let mut history = self.reorg_history.write();
+		history.push_back(record);
+		while history.len() > REORG_HISTORY_SIZE {
+			history.pop_front();
+		}
+	}
+
+	/// Last reorgs of the chain head, the latest first.
+	pub fn reorg_history(&self) -> Vec<ReorgRecord> {
+		self.reorg_history.read().iter().rev().cloned().collect()
+	}
+
+	/// Notify the reorg listeners about the chain head moving from prev_head
+	/// to head, both descending from fork_point.
+	fn notify_reorg(&self, prev_head: &Tip, head: &Tip, fork_point: &Tip) -> Result<(), Error> {
+		let listeners = self.reorg_listeners.read().clone();
+		if listeners.is_empty() {
+			return Ok(());
+		}
+
+		let disconnected = self.fork_hashes(prev_head, fork_point)?;
+		let mut connected = self.fork_hashes(head, fork_point)?;
+		connected.reverse();
+
+		let event = ReorgEvent {
+			old_tip: *prev_head,
+			new_tip: *head,
+			disconnected,
+			connected,
+		};
+		for listener in listeners {
+			listener.on_reorg(&event);
+		}
+		Ok(())
+	}
+
+	/// Hashes of the blocks from tip down to fork_point (excluded), tip first.
+	fn fork_hashes(&self, tip: &Tip, fork_point: &Tip) -> Result<Vec<Hash>, Error> {
+		let mut hashes = vec![];
+		let mut header = self.get_block_header(&tip.last_block_h)?;
+		while header.height > fork_point.height {
+			hashes.push(header.hash());
+			header = self.get_previous_header(&header)?;
+		}
+		Ok(hashes)
+	}
+
+	/// Whether the node keeps the full chain history (all blocks and spent outputs).
+	pub fn archive_mode(&self) -> bool {
+		self.archive_mode
+	}
+
 	/// Return our shared header MMR handle.
 	pub fn header_pmmr(&self) -> Arc<RwLock<PMMRHandle<BlockHeader>>> {
 		self.header_pmmr.clone()
@@ -253,6 +601,76 @@ impl Chain {
 		self.store.clone()
 	}
 
+	/// Rebuild the header MMR of the chain at `db_root` from the headers stored in the db,
+	/// so the headers don't need to be synced from the network again when the MMR files
+	/// are corrupted. Node must be stopped. Returns the height of the header head.
+	/// `encryption` is the key of the chain db, if it is encrypted.
+	pub fn rebuild_header_mmr(
+		db_root: &str,
+		encryption: Option<Arc<EncryptionKey>>,
+	) -> Result<u64, Error> {
+		let store = store::ChainStore::new_with_key(db_root, encryption)?;
+		let header_path = Path::new(db_root).join("header").join("header_head");
+		Chain::rebuild_header_pmmr(&store, &header_path)?;
+		Ok(store.header_head()?.height)
+	}
+
+	// Walk back from the header head to the genesis and apply the headers
+	// to an empty header MMR, in chunks.
+	fn rebuild_header_pmmr(
+		store: &store::ChainStore,
+		path: &Path,
+	) -> Result<PMMRHandle<BlockHeader>, Error> {
+		let head = store.header_head()?;
+		let mut hashes = Vec::with_capacity(head.height as usize + 1);
+		let mut hash = head.hash();
+		loop {
+			let header = store.get_block_header(&hash)?;
+			hashes.push(hash);
+			if header.height == 0 {
+				break;
+			}
+			hash = header.prev_hash;
+		}
+		if hashes.len() as u64 != head.height + 1 {
+			return Err(ErrorKind::Other(format!(
+				"header chain from {} at {} is broken, {} headers found",
+				head.hash(),
+				head.height,
+				hashes.len()
+			))
+			.into());
+		}
+		hashes.reverse();
+
+		if path.exists() {
+			fs::remove_dir_all(path)?;
+		}
+		let mut header_pmmr = PMMRHandle::new(
+			path,
+			false,
+			ProtocolVersion(1),
+			None,
+			store.encryption_key().as_deref(),
+		)?;
+		for chunk in hashes.chunks(HEADER_MMR_REBUILD_CHUNK) {
+			let mut batch = store.batch()?;
+			txhashset::header_extending(&mut header_pmmr, &mut batch, |ext, batch| {
+				for hash in chunk {
+					ext.apply_header(&batch.get_block_header(hash)?)?;
+				}
+				Ok(())
+			})?;
+			batch.commit()?;
+		}
+		info!(
+			"rebuild_header_mmr: rebuilt header MMR up to {} at {}",
+			head.hash(),
+			head.height
+		);
+		Ok(header_pmmr)
+	}
+
 	/// Known bad block that we must rewind prior to if seen on "current chain".
 	fn rewind_bad_block(&self) -> Result<(), Error> {
 		let hash = Hash::from_hex(BLOCK_TO_BAN)?;
@@ -372,8 +790,24 @@ impl Chain {
 	}
 
 	/// Processes a single block, then checks for orphans, processing
-	/// those as well if they're found
+	/// those as well if they're found. While the tip is frozen the block
+	/// is buffered and Ok(None) is returned, it is processed when the freeze ends.
+	/// Only the blocks with the valid proof of work that extend a known or a buffered
+	/// block are buffered.
 	pub fn process_block(&self, b: Block, opts: Options) -> Result<Option<Tip>, Error> {
+		if self.tip_freeze.is_frozen() {
+			self.validate_frozen_block(&b, opts)?;
+		}
+		if self.tip_freeze.buffer(&b, opts) {
+			info!(
+				"process_block: chain tip is frozen, block {} at {} is buffered",
+				b.hash(),
+				b.header.height
+			);
+			return Ok(None);
+		}
+		self.check_tip_freeze();
+
 		let height = b.header.height;
 		let res = self.process_block_single(b, opts);
 		if res.is_ok() {
@@ -382,6 +816,105 @@ impl Chain {
 		res
 	}
 
+	// Cheap checks of the block that is about to be buffered while the tip is frozen
+	fn validate_frozen_block(&self, b: &Block, opts: Options) -> Result<(), Error> {
+		let prev_height = match self.tip_freeze.buffered_height(&b.header.prev_hash) {
+			Some(height) => height,
+			None => {
+				self.get_previous_header(&b.header)
+					.map_err(|_| {
+						ErrorKind::Orphan(format!(
+							"tip is frozen, block {} doesn't extend a known block",
+							b.hash()
+						))
+					})?
+					.height
+			}
+		};
+		if b.header.height != prev_height + 1 {
+			return Err(ErrorKind::InvalidBlockHeight.into());
+		}
+		if !opts.contains(Options::SKIP_POW) && (self.pow_verifier)(&b.header).is_err() {
+			return Err(ErrorKind::InvalidPow.into());
+		}
+		Ok(())
+	}
+
+	/// Stop accepting the new blocks into the chain for duration_secs, so the chain
+	/// can be backed up or audited at a fixed height. The blocks received in the meantime
+	/// are buffered and processed once the freeze ends. Freezing the frozen tip extends
+	/// the freeze. The freeze is limited to MAX_TIP_FREEZE_SECS in total and ends early
+	/// when MAX_FROZEN_BLOCKS blocks are buffered.
+	pub fn freeze_tip(&self, duration_secs: u64) -> Result<TipFreezeStatus, Error> {
+		self.tip_freeze.freeze(duration_secs)?;
+		let status = self.tip_freeze_status()?;
+		info!(
+			"freeze_tip: chain tip {} at {} is frozen until {:?}",
+			status.hash, status.height, status.frozen_until
+		);
+		Ok(status)
+	}
+
+	/// End the tip freeze now, processing the buffered blocks.
+	pub fn unfreeze_tip(&self) -> Result<TipFreezeStatus, Error> {
+		self.process_unfrozen_blocks(self.tip_freeze.take_unfrozen(true));
+		self.tip_freeze_status()
+	}
+
+	/// Current freeze state of the chain tip.
+	pub fn tip_freeze_status(&self) -> Result<TipFreezeStatus, Error> {
+		Ok(self.tip_freeze.status(&self.head()?))
+	}
+
+	/// End the tip freeze if it's expired, processing the buffered blocks.
+	/// Called periodically, so the freeze ends in time without new blocks coming.
+	pub fn check_tip_freeze(&self) {
+		self.process_unfrozen_blocks(self.tip_freeze.take_unfrozen(false));
+	}
+
+	fn process_unfrozen_blocks(&self, blocks: Vec<(Block, Options)>) {
+		if blocks.is_empty() {
+			return;
+		}
+		info!(
+			"tip freeze ended, processing {} buffered blocks",
+			blocks.len()
+		);
+		for (b, opts) in blocks {
+			let height = b.header.height;
+			let hash = b.hash();
+			match self.process_block_single(b, opts) {
+				Ok(_) => self.check_orphans(height + 1),
+				Err(e) => debug!(
+					"tip freeze: buffered block {} at {} is refused, {}",
+					hash, height, e
+				),
+			}
+		}
+	}
+
+	/// Validates the block as the next block after the current head, the same way as
+	/// process_block does, but the block is not saved, relayed or added to the orphans.
+	pub fn validate_block(&self, b: Block) -> Result<(), Error> {
+		let head = self.head()?;
+		if b.header.prev_hash != head.last_block_h {
+			return Err(ErrorKind::Unfit(format!(
+				"block is not built on the current head {}",
+				head.last_block_h
+			))
+			.into());
+		}
+
+		let b = self.convert_block_v2(b)?;
+
+		let mut header_pmmr = self.header_pmmr.write();
+		let mut txhashset = self.txhashset.write();
+		let batch = self.store.batch()?;
+		let mut ctx = self.new_ctx(Options::NONE, batch, &mut header_pmmr, &mut txhashset)?;
+		// batch is dropped without commit
+		pipe::validate_block_dry_run(&b, &mut ctx)
+	}
+
 	/// We plan to support receiving blocks with CommitOnly inputs.
 	/// We also need to support relaying blocks with FeaturesAndCommit inputs to peers.
 	/// So we need a way to convert blocks from CommitOnly to FeaturesAndCommit.
@@ -473,12 +1006,16 @@ impl Chain {
 		}
 
 		let block_hash = block.hash();
+		let size = ser::ser_vec(block, ProtocolVersion::local())
+			.map(|x| x.len())
+			.unwrap_or(0);
 		let orphan = Orphan {
 			block: block.clone(),
 			opts,
 			added: Instant::now(),
+			size,
 		};
-		self.orphans.add(orphan);
+		self.orphans.add(orphan, head.height);
 
 		debug!(
 			"is_orphan: {:?}, # orphans {}{}",
@@ -515,40 +1052,83 @@ impl Chain {
 		// This conversion also ensures a block received in "v2" has valid input features (prevents malleability).
 		let b = self.convert_block_v2(b)?;
 
-		let (maybe_new_head, prev_head) = {
+		let first_seen = Utc::now().timestamp_millis();
+
+		let (maybe_new_head, prev_head, stage, expected_roots) = {
 			let mut header_pmmr = self.header_pmmr.write();
 			let mut txhashset = self.txhashset.write();
 			let batch = self.store.batch()?;
 			let prev_head = batch.head()?;
 			let mut ctx = self.new_ctx(opts, batch, &mut header_pmmr, &mut txhashset)?;
 
-			let maybe_new_head = pipe::process_block(&b, &mut ctx);
+			let maybe_new_head =
+				pipe::process_block(&b, &mut ctx).and_then(|(head, fork_point)| {
+					let prev = ctx.batch.get_previous_header(&b.header)?;
+					let status = self.determine_status(
+						head,
+						Tip::from_header(&prev),
+						prev_head,
+						Tip::from_header(&fork_point),
+					);
+					Ok((head, status))
+				});
 
 			// We have flushed txhashset extension changes to disk
 			// but not yet committed the batch.
 			// A node shutdown at this point can be catastrophic...
 			// We prevent this via the stop_lock (see above).
-			if maybe_new_head.is_ok() {
+			if let Ok((_, status)) = maybe_new_head {
+				let start = Instant::now();
+				// Watch events commit in the same batch as the block that produced them.
+				self.update_watch_filters(&ctx.batch, &b, status)?;
+				if ctx.batch.get_block_first_seen(&b.hash())?.is_none() {
+					ctx.batch.save_block_first_seen(&b.hash(), first_seen)?;
+				}
+				if opts.contains(Options::MINE) {
+					ctx.batch
+						.save_mined_block(&MinedBlock::from_block(&b, first_seen))?;
+				}
 				ctx.batch.commit()?;
+				ctx.timings.commit += start.elapsed();
+				self.record_block_timings(&b, &ctx.timings);
 			}
 
 			// release the lock and let the batch go before post-processing
-			(maybe_new_head, prev_head)
+			(
+				maybe_new_head,
+				prev_head,
+				ctx.stage,
+				ctx.expected_roots.take(),
+			)
 		};
 
-		match maybe_new_head {
-			Ok((head, fork_point)) => {
-				let prev = self.get_previous_header(&b.header)?;
-				let status = self.determine_status(
-					head,
-					Tip::from_header(&prev),
-					prev_head,
-					Tip::from_header(&fork_point),
-				);
+		if let Err(ref e) = maybe_new_head {
+			if e.is_bad_data() {
+				let prev = self.get_previous_header(&b.header).ok();
+				self.forensics
+					.record(&b, prev.as_ref(), stage, e, expected_roots);
+			}
+		}
 
+		match maybe_new_head {
+			Ok((head, status)) => {
 				// notifying other parts of the system of the update
 				self.adapter.block_accepted(&b, status, opts);
 
+				if let BlockStatus::Reorg {
+					prev_head,
+					fork_point,
+					..
+				} = status
+				{
+					if let Some(head) = head {
+						self.record_reorg(&prev_head, &head, &fork_point);
+						if let Err(e) = self.notify_reorg(&prev_head, &head, &fork_point) {
+							warn!("Unable to notify reorg listeners, {}", e);
+						}
+					}
+				}
+
 				Ok(head)
 			}
 			Err(e) => match e.kind() {
@@ -638,6 +1218,9 @@ impl Chain {
 			header_pmmr,
 			txhashset,
 			batch,
+			timings: BlockTimings::default(),
+			stage: "",
+			expected_roots: None,
 		})
 	}
 
@@ -651,6 +1234,48 @@ impl Chain {
 		self.orphans.len_evicted()
 	}
 
+	/// Orphan pool statistics
+	pub fn orphans_stats(&self) -> OrphanStats {
+		self.orphans.stats()
+	}
+
+	/// Set the memory cap of the orphan pool, bytes
+	pub fn set_orphans_max_size_bytes(&self, max_size_bytes: usize) {
+		self.orphans.set_max_size_bytes(max_size_bytes);
+	}
+
+	/// Set whether the blocks rejected by the pipeline are dumped to the forensics
+	/// directory and how many of the last rejections are kept.
+	pub fn set_rejected_blocks_forensics(&self, dump: bool, max_rejected_blocks: usize) {
+		self.forensics.configure(dump, max_rejected_blocks);
+	}
+
+	/// Last blocks rejected by the pipeline as invalid, the latest first.
+	pub fn rejected_blocks(&self) -> Vec<BlockRejection> {
+		self.forensics.rejections()
+	}
+
+	/// Aggregate per-stage timings of the blocks accepted by the pipeline
+	pub fn block_timing_stats(&self) -> BlockTimingStats {
+		self.block_timings.read().clone()
+	}
+
+	fn record_block_timings(&self, b: &Block, timings: &BlockTimings) {
+		debug!(
+			"Block {} at {} processed in {} ms (pow {}, header {}, rangeproofs {}, txhashset {}, kernel sums {}, commit {})",
+			b.hash(),
+			b.header.height,
+			timings.total().as_millis(),
+			timings.pow.as_millis(),
+			timings.header.as_millis(),
+			timings.rangeproofs.as_millis(),
+			timings.txhashset.as_millis(),
+			timings.kernel_sums.as_millis(),
+			timings.commit.as_millis(),
+		);
+		self.block_timings.write().record(timings);
+	}
+
 	/// Check for orphans, once a block is successfully added
 	fn check_orphans(&self, mut height: u64) {
 		let initial_height = height;
@@ -725,6 +1350,125 @@ impl Chain {
 		})
 	}
 
+	/// Output at the provided output MMR position, spent or not, with the height
+	/// of the block that created it. Spent outputs are removed by compaction, so
+	/// the full output history is only available in archive mode.
+	pub fn get_historical_output(&self, pos: u64) -> Result<(Output, bool, u64), Error> {
+		let header_pmmr = self.header_pmmr.read();
+		let txhashset = self.txhashset.read();
+		let (output, spent) = txhashset
+			.historical_output_at(pos)
+			.ok_or_else(|| ErrorKind::OutputNotFound(format!("No output data at pos {}", pos)))?;
+
+		// Lowest height with the output in its output MMR.
+		let mut low = 0;
+		let mut high = self.head()?.height;
+		while low < high {
+			let mid = low + (high - low) / 2;
+			let hash = header_pmmr.get_header_hash_by_height(mid)?;
+			if self.get_block_header(&hash)?.output_mmr_size < pos {
+				low = mid + 1;
+			} else {
+				high = mid;
+			}
+		}
+		Ok((output, spent, low))
+	}
+
+	/// Chain statistics as of the block at the provided height on the current chain.
+	/// The UTXO count is rebuilt from the current leaf set and the spent index of
+	/// every later block, so the height must be within the horizon unless we are
+	/// running in archive mode. The spent counts are cached, they follow the tip.
+	pub fn get_chain_stats(&self, height: u64) -> Result<ChainStats, Error> {
+		let header_pmmr = self.header_pmmr.read();
+		let txhashset = self.txhashset.read();
+		let head_header = self.head_header()?;
+		if height > head_header.height {
+			return Err(ErrorKind::InvalidBlockHeight.into());
+		}
+		let hash = header_pmmr.get_header_hash_by_height(height)?;
+		let header = self.get_block_header(&hash)?;
+
+		// Outputs unspent now, minus the outputs created after this block, plus the outputs
+		// spent after this block.
+		let created =
+			pmmr::n_leaves(head_header.output_mmr_size) - pmmr::n_leaves(header.output_mmr_size);
+		let spent = self.spent_since(&header_pmmr, &head_header, height)?;
+		let utxo_count = txhashset.unspent_count() + spent - created;
+
+		let total_supply = header
+			.total_overage(self.genesis.kernel_mmr_size > 0)
+			.checked_neg()
+			.unwrap_or(0) as u64;
+
+		Ok(ChainStats {
+			height,
+			hash,
+			utxo_count,
+			kernel_count: pmmr::n_leaves(header.kernel_mmr_size),
+			total_supply,
+			output_mmr_size: header.output_mmr_size,
+			kernel_mmr_size: header.kernel_mmr_size,
+		})
+	}
+
+	// Number of outputs spent by the blocks after the height, up to the head
+	fn spent_since(
+		&self,
+		header_pmmr: &txhashset::PMMRHandle<BlockHeader>,
+		head: &BlockHeader,
+		height: u64,
+	) -> Result<u64, Error> {
+		let batch = self.store.batch()?;
+		let spent_index_len = |header: &BlockHeader| -> Result<u64, Error> {
+			let spent = batch.get_spent_index(&header.hash()).map_err(|_| {
+				ErrorKind::Other(format!(
+					"No spent index at height {}, chain stats are not available at height {}",
+					header.height, height
+				))
+			})?;
+			Ok(spent.len() as u64)
+		};
+
+		let mut cache = self.spent_counts.lock();
+		// Follow the tip by a block, start over on anything else
+		let counts = match cache.take() {
+			Some(c) if c.tip == head.hash() => c,
+			Some(c) if c.tip == head.prev_hash => {
+				let n = spent_index_len(head)?;
+				let mut spent = Vec::with_capacity(c.spent.len() + 1);
+				spent.push(0);
+				spent.extend(c.spent.iter().map(|x| x + n));
+				SpentCounts {
+					tip: head.hash(),
+					spent,
+				}
+			}
+			_ => SpentCounts {
+				tip: head.hash(),
+				spent: vec![0],
+			},
+		};
+		let counts = cache.get_or_insert(counts);
+
+		let depth = (head.height - height) as usize;
+		if counts.spent.len() <= depth {
+			let mut current = self.get_block_header(
+				&header_pmmr
+					.get_header_hash_by_height(head.height + 1 - counts.spent.len() as u64)?,
+			)?;
+			while counts.spent.len() <= depth {
+				let n = spent_index_len(&current)?;
+				let last = counts.spent.last().cloned().unwrap_or(0);
+				counts.spent.push(last + n);
+				if counts.spent.len() <= depth {
+					current = batch.get_previous_header(&current)?;
+				}
+			}
+		}
+		Ok(counts.spent[depth])
+	}
+
 	/// Validate the tx against the current UTXO set and recent kernels (NRD relative lock heights).
 	pub fn validate_tx(&self, tx: &Transaction) -> Result<(), Error> {
 		self.validate_tx_against_utxo(tx)?;
@@ -732,12 +1476,16 @@ impl Chain {
 		Ok(())
 	}
 
+	fn validate_tx_kernels(&self, tx: &Transaction) -> Result<(), Error> {
+		self.validate_nrd_kernels(tx.kernels())
+	}
+
 	/// Validates NRD relative height locks against "recent" kernel history.
 	/// Applies the kernels to the current kernel MMR in a readonly extension.
 	/// The extension and the db batch are discarded.
-	/// The batch ensures duplicate NRD kernels within the tx are handled correctly.
-	fn validate_tx_kernels(&self, tx: &Transaction) -> Result<(), Error> {
-		let has_nrd_kernel = tx.kernels().iter().any(|k| match k.features {
+	/// The batch ensures duplicate NRD kernels within the kernels are handled correctly.
+	pub fn validate_nrd_kernels(&self, kernels: &[TxKernel]) -> Result<(), Error> {
+		let has_nrd_kernel = kernels.iter().any(|k| match k.features {
 			KernelFeatures::NoRecentDuplicate { .. } => true,
 			_ => false,
 		});
@@ -748,7 +1496,7 @@ impl Chain {
 		let mut txhashset = self.txhashset.write();
 		txhashset::extending_readonly(&mut header_pmmr, &mut txhashset, |ext, batch| {
 			let height = self.next_block_height()?;
-			ext.extension.apply_kernels(tx.kernels(), height, batch)
+			ext.extension.apply_kernels(kernels, height, batch)
 		})
 	}
 
@@ -918,6 +1666,19 @@ impl Chain {
 		Ok(merkle_proof)
 	}
 
+	/// Return a merkle proof of the unspent output at the output MMR position `pos` against
+	/// the output root of the header. Served from the MMR files under the read lock, the
+	/// header must be on the current chain.
+	pub fn get_output_merkle_proof_at(
+		&self,
+		pos: u64,
+		header: &BlockHeader,
+	) -> Result<MerkleProof, Error> {
+		self.txhashset
+			.read()
+			.output_merkle_proof(pos, header.output_mmr_size)
+	}
+
 	/// Return a merkle proof valid for the current output pmmr state at the
 	/// given pos
 	pub fn get_merkle_proof_for_pos(&self, commit: Commitment) -> Result<MerkleProof, Error> {
@@ -925,6 +1686,20 @@ impl Chain {
 		txhashset.merkle_proof(commit)
 	}
 
+	/// Return a merkle proof of the kernel at the kernel MMR position `pos` against the
+	/// kernel root of the header at `height`. The kernel MMR is never pruned, so the proof
+	/// is available for any historical header, on the compacted nodes as well.
+	pub fn get_kernel_merkle_proof(
+		&self,
+		pos: u64,
+		height: u64,
+	) -> Result<(TxKernel, BlockHeader, MerkleProof), Error> {
+		let header = self.get_header_by_height(height)?;
+		let mut txhashset = self.txhashset.write();
+		let (kernel, merkle_proof) = txhashset.kernel_merkle_proof(pos, header.kernel_mmr_size)?;
+		Ok((kernel, header, merkle_proof))
+	}
+
 	/// Provides a reading view into the current txhashset state as well as
 	/// the required indexes for a consumer to rewind to a consistent state
 	/// at the provided block hash.
@@ -943,11 +1718,25 @@ impl Chain {
 			ext.extension.snapshot(batch)?;
 
 			// prepare the zip
-			txhashset::zip_read(self.db_root.clone(), &header)
+			let encryption = self.store.encryption_key();
+			txhashset::zip_read(self.db_root.clone(), &header, encryption.as_deref())
 				.map(|file| (header.output_mmr_size, header.kernel_mmr_size, file))
 		})
 	}
 
+	/// Hash of the txhashset zip provided by txhashset_read for the provided block hash,
+	/// so the peers downloading it in parts can check the whole archive.
+	pub fn txhashset_zip_hash(&self, h: &Hash, zip: &File) -> Result<Hash, Error> {
+		if let Some((hash, zip_hash)) = *self.txhashset_zip_hash.read() {
+			if hash == *h {
+				return Ok(zip_hash);
+			}
+		}
+		let zip_hash = txhashset::zip_hash(zip)?;
+		*self.txhashset_zip_hash.write() = Some((*h, zip_hash));
+		Ok(zip_hash)
+	}
+
 	/// To support the ability to download the txhashset from multiple peers in parallel,
 	/// the peers must all agree on the exact binary representation of the txhashset.
 	/// This means compacting and rewinding to the exact same header.
@@ -969,12 +1758,114 @@ impl Chain {
 		self.get_header_by_height(txhashset_height)
 	}
 
-	// Special handling to make sure the whole kernel set matches each of its
-	// roots in each block header, without truncation. We go back header by
-	// header, rewind and check each root. This fixes a potential weakness in
-	// fast sync where a reorg past the horizon could allow a whole rewrite of
-	// the kernel set.
-	fn validate_kernel_history(
+	// Segments are only served for the txhashset archive header, so all the
+	// peers serve the exact same MMRs.
+	fn check_segment_request(
+		&self,
+		block_hash: &Hash,
+		id: &SegmentIdentifier,
+		segment_type: SegmentType,
+	) -> Result<BlockHeader, Error> {
+		let header = self.txhashset_archive_header()?;
+		if header.hash() != *block_hash {
+			return Err(ErrorKind::TxHashSetErr(format!(
+				"segment requested for {}, archive header is {}",
+				block_hash,
+				header.hash()
+			))
+			.into());
+		}
+		if id.height != segment_type.height() {
+			return Err(ErrorKind::Segment(SegmentError::InvalidHeight(id.height)).into());
+		}
+		Ok(header)
+	}
+
+	// Segmenter of the archive header. The txhashset is rewound to the header only
+	// when the archive header changes, the segments are then served under the read lock.
+	fn segmenter(&self, header: &BlockHeader) -> Result<Arc<Segmenter>, Error> {
+		if let Some(segmenter) = self.segmenter.read().as_ref() {
+			if segmenter.header().hash() == header.hash() {
+				return Ok(segmenter.clone());
+			}
+		}
+
+		let mut cache = self.segmenter.write();
+		if let Some(segmenter) = cache.as_ref() {
+			if segmenter.header().hash() == header.hash() {
+				return Ok(segmenter.clone());
+			}
+		}
+		let (unspent, bitmap_accumulator, output_roots) = {
+			let mut header_pmmr = self.header_pmmr.write();
+			let mut txhashset = self.txhashset.write();
+			txhashset::extending_readonly(&mut header_pmmr, &mut txhashset, |ext, batch| {
+				pipe::rewind_and_apply_fork(header, ext, batch)?;
+				let roots = ext.extension.roots()?;
+				Ok((
+					ext.extension.unspent_leaf_bitmap(),
+					ext.extension.bitmap_accumulator(),
+					roots.output_roots,
+				))
+			})?
+		};
+		debug!(
+			"segmenter: built for archive header {} at {}",
+			header.hash(),
+			header.height
+		);
+		let segmenter = Arc::new(Segmenter::new(
+			self.txhashset.clone(),
+			header.clone(),
+			unspent,
+			bitmap_accumulator,
+			output_roots,
+		));
+		*cache = Some(segmenter.clone());
+		Ok(segmenter)
+	}
+
+	/// Output segment of the txhashset archive header with the provided block
+	/// hash. Also returns the positions of the spent outputs still having their
+	/// data in the segment, the merkle proof of the bitmap chunks covering the
+	/// segment and the output roots, required to validate it.
+	pub fn get_output_segment(
+		&self,
+		block_hash: &Hash,
+		id: SegmentIdentifier,
+	) -> Result<(Segment<OutputIdentifier>, Vec<u64>, Vec<Hash>, OutputRoots), Error> {
+		let header = self.check_segment_request(block_hash, &id, SegmentType::Output)?;
+		self.segmenter(&header)?.output_segment(id)
+	}
+
+	/// Rangeproof segment of the txhashset archive header with the provided
+	/// block hash.
+	pub fn get_rangeproof_segment(
+		&self,
+		block_hash: &Hash,
+		id: SegmentIdentifier,
+	) -> Result<Segment<RangeProof>, Error> {
+		let header = self.check_segment_request(block_hash, &id, SegmentType::RangeProof)?;
+		self.segmenter(&header)?.rangeproof_segment(id)
+	}
+
+	/// Kernel segment of the txhashset archive header with the provided block
+	/// hash.
+	pub fn get_kernel_segment(
+		&self,
+		block_hash: &Hash,
+		id: SegmentIdentifier,
+	) -> Result<Segment<TxKernel>, Error> {
+		let header = self.check_segment_request(block_hash, &id, SegmentType::Kernel)?;
+		self.segmenter(&header)?.kernel_segment(id)
+	}
+
+	// Special handling to make sure the whole kernel set matches each of its
+	// roots in each block header, without truncation. We go back header by
+	// header, rewind and check each root. This fixes a potential weakness in
+	// fast sync where a reorg past the horizon could allow a whole rewrite of
+	// the kernel set.
+	fn validate_kernel_history(
 		&self,
 		header: &BlockHeader,
 		txhashset: &txhashset::TxHashSet,
@@ -1078,6 +1969,23 @@ impl Chain {
 			current = self.get_previous_header(&header);
 		}
 
+		// In archive mode the whole chain is synced as full blocks, the txhashset
+		// is never needed. Only look at the next blocks above the fork point, the
+		// header chain can be the whole chain here.
+		if self.archive_mode {
+			if let Some(hs) = hashes {
+				let header_pmmr = self.header_pmmr.read();
+				let top = cmp::min(
+					header_head.height,
+					oldest_height.saturating_add(ARCHIVE_SYNC_WINDOW),
+				);
+				for height in (oldest_height + 1..=top).rev() {
+					hs.push(header_pmmr.get_header_hash_by_height(height)?);
+				}
+			}
+			return Ok(false);
+		}
+
 		// Traverse back through the header chain from header_head back to this fork point.
 		// These are the blocks that we need to request in body sync (we have the header but not the full block).
 		if let Some(hs) = hashes {
@@ -1179,28 +2087,208 @@ impl Chain {
 		// Write txhashset to sandbox (in the Grin specific tmp dir)
 		let sandbox_dir = self.get_tmp_dir();
 		txhashset::clean_txhashset_folder(&sandbox_dir);
-		txhashset::zip_write(sandbox_dir.clone(), txhashset_data.try_clone()?, &header)?;
+		let encryption = self.store.encryption_key();
+		txhashset::zip_write(
+			sandbox_dir.clone(),
+			txhashset_data.try_clone()?,
+			&header,
+			encryption.as_deref(),
+		)?;
+
+		self.install_txhashset(&header, sandbox_dir, status, false)?;
+		Ok(false)
+	}
+
+	/// Start from a trusted txhashset snapshot (zip archive) at the provided header,
+	/// without validating the kernel history, the rangeproofs and the kernel signatures
+	/// first. The roots and the sums are still checked against the header. A copy of the
+	/// snapshot is kept, so its history can be validated in background with
+	/// validate_assumed_utxo while the chain follows the network from there.
+	pub fn load_utxo_snapshot(
+		&self,
+		h: Hash,
+		snapshot: File,
+		status: &dyn TxHashsetWriteStatus,
+	) -> Result<(), Error> {
+		status.on_setup();
+
+		let header = self.get_block_header(&h)?;
+		self.is_on_current_chain(&header)?;
+		if self.head()?.height >= header.height {
+			return Err(ErrorKind::InvalidTxHashSet(
+				"utxo snapshot is behind our chain head".to_owned(),
+			)
+			.into());
+		}
+
+		let encryption = self.store.encryption_key();
+		let assumed_dir = self.get_assumed_utxo_dir();
+		txhashset::clean_txhashset_folder(&assumed_dir);
+		txhashset::zip_write(
+			assumed_dir,
+			snapshot.try_clone()?,
+			&header,
+			encryption.as_deref(),
+		)?;
+
+		let sandbox_dir = self.get_tmp_dir();
+		txhashset::clean_txhashset_folder(&sandbox_dir);
+		let mut snapshot = snapshot;
+		snapshot.seek(SeekFrom::Start(0))?;
+		txhashset::zip_write(
+			sandbox_dir.clone(),
+			snapshot,
+			&header,
+			encryption.as_deref(),
+		)?;
+
+		self.install_txhashset(&header, sandbox_dir, status, true)?;
+
+		info!(
+			"load_utxo_snapshot: started from the snapshot at {} {}, history is not validated yet",
+			header.height, h
+		);
+		Ok(())
+	}
+
+	/// The trusted txhashset snapshot we started from, as long as its history
+	/// is not validated yet.
+	pub fn assumed_utxo(&self) -> Result<Option<HashHeight>, Error> {
+		self.store.get_assumed_utxo()
+	}
+
+	/// Validate the history of the trusted txhashset snapshot we started from: the kernel
+	/// roots of every header back to genesis, the rangeproofs and the kernel signatures.
+	/// Runs on the copy of the snapshot, without holding the chain locks, and resumes
+	/// from its checkpoint if interrupted. Once done, the chain is fully validated.
+	pub fn validate_assumed_utxo(&self, status: &dyn TxHashsetWriteStatus) -> Result<(), Error> {
+		let assumed = match self.assumed_utxo()? {
+			Some(assumed) => assumed,
+			None => return Ok(()),
+		};
+		let header = self.get_block_header(&assumed.hash)?;
+		let assumed_dir = self.get_assumed_utxo_dir();
+		{
+			let txhashset = txhashset::TxHashSet::open(
+				assumed_dir
+					.to_str()
+					.expect("invalid assumeutxo folder")
+					.to_owned(),
+				self.store.clone(),
+				Some(&header),
+			)?;
+
+			let count = txhashset.validate_kernel_roots(&header)?;
+			debug!(
+				"validate_assumed_utxo: validated kernel root on {} headers",
+				count
+			);
+
+			if !txhashset.verify_proofs(&header, status)? {
+				return Err(ErrorKind::InvalidTxHashSet(
+					"utxo snapshot doesn't match its header".to_owned(),
+				)
+				.into());
+			}
+		}
+
+		let batch = self.store.batch()?;
+		batch.delete_assumed_utxo()?;
+		batch.delete_validation_checkpoint()?;
+		batch.commit()?;
+
+		if let Err(e) = fs::remove_dir_all(&assumed_dir) {
+			warn!(
+				"validate_assumed_utxo: fail to clean {:?}, {}",
+				assumed_dir, e
+			);
+		}
+
+		info!(
+			"validate_assumed_utxo: history of the snapshot at {} {} is valid",
+			assumed.height, assumed.hash
+		);
+		Ok(())
+	}
+
+	fn get_assumed_utxo_dir(&self) -> PathBuf {
+		Path::new(&self.db_root).join(ASSUMED_UTXO_SUBDIR)
+	}
+
+	/// Start rebuilding the txhashset for the provided (archive) header from
+	/// segments, replacing any previous attempt.
+	pub fn init_desegmenter(&self, header: &BlockHeader) -> Result<(), Error> {
+		let desegmenter = Desegmenter::new(header.clone(), self.get_tmp_dir(), self.store.clone())?;
+		*self.desegmenter.write() = Some(desegmenter);
+		Ok(())
+	}
+
+	/// The txhashset being rebuilt from segments, if any.
+	pub fn desegmenter(&self) -> Arc<RwLock<Option<Desegmenter>>> {
+		self.desegmenter.clone()
+	}
 
+	/// Drop the txhashset being rebuilt from segments, if any.
+	pub fn reset_desegmenter(&self) {
+		*self.desegmenter.write() = None;
+	}
+
+	/// Fully validate the txhashset rebuilt from segments and install it the
+	/// same way as a txhashset received as a zip file.
+	pub fn txhashset_segments_write(&self, status: &dyn TxHashsetWriteStatus) -> Result<(), Error> {
+		status.on_setup();
+
+		let desegmenter = self.desegmenter.write().take().ok_or_else(|| {
+			ErrorKind::InvalidTxHashSet("no txhashset segments to write".to_owned())
+		})?;
+
+		let mut hashes: Option<Vec<Hash>> = None;
+		if !self.check_txhashset_needed("txhashset_segments_write".to_owned(), &mut hashes)? {
+			warn!("txhashset_segments_write: txhashset rebuilt but it's not needed! ignored.");
+			return Err(ErrorKind::InvalidTxHashSet("not needed".to_owned()).into());
+		}
+
+		let (header, sandbox_dir) = desegmenter.finish()?;
+		self.install_txhashset(&header, sandbox_dir, status, false)
+	}
+
+	// Fully validate the txhashset in the sandbox folder against the provided
+	// header and replace our txhashset with it. A trusted (assumed valid) txhashset
+	// skips the kernel history, rangeproofs and kernel signatures, which are
+	// validated later in background.
+	fn install_txhashset(
+		&self,
+		header: &BlockHeader,
+		sandbox_dir: PathBuf,
+		status: &dyn TxHashsetWriteStatus,
+		assume_valid: bool,
+	) -> Result<(), Error> {
 		let mut txhashset = txhashset::TxHashSet::open(
 			sandbox_dir
 				.to_str()
 				.expect("invalid sandbox folder")
 				.to_owned(),
 			self.store.clone(),
-			Some(&header),
+			Some(header),
 		)?;
 
 		// Validate the full kernel history.
 		// Check kernel MMR root for every block header.
 		// Check NRD relative height rules for full kernel history.
 		{
-			self.validate_kernel_history(&header, &txhashset)?;
+			if !assume_valid {
+				self.validate_kernel_history(header, &txhashset)?;
+			}
 
 			let header_pmmr = self.header_pmmr.read();
 			let batch = self.store.batch()?;
 			txhashset.verify_kernel_pos_index(&self.genesis, &header_pmmr, &batch)?;
 		}
 
+		// Verify rangeproofs and kernel signatures before opening the writeable batch,
+		// checkpointing the progress so an interrupted validation can resume on restart.
+		let proofs_verified = assume_valid || txhashset.verify_proofs(header, status)?;
+
 		// all good, prepare a new batch and update all the required records
 		debug!("txhashset_write: rewinding a 2nd time (writeable)");
 
@@ -1212,12 +2300,12 @@ impl Chain {
 			&mut batch,
 			|ext, batch| {
 				let extension = &mut ext.extension;
-				extension.rewind(&header, batch)?;
+				extension.rewind(header, batch)?;
 
 				// Validate the extension, generating the utxo_sum and kernel_sum.
-				// Full validation, including rangeproofs and kernel signature verification.
+				// Rangeproofs and kernel signatures are skipped if already verified above.
 				let (utxo_sum, kernel_sum) =
-					extension.validate(&self.genesis, false, status, &header)?;
+					extension.validate(&self.genesis, proofs_verified, status, header)?;
 
 				// Save the block_sums (utxo_sum, kernel_sum) to the db for use later.
 				batch.save_block_sums(
@@ -1238,7 +2326,7 @@ impl Chain {
 
 		// Save the new head to the db and rebuild the header by height index.
 		{
-			let tip = Tip::from_header(&header);
+			let tip = Tip::from_header(header);
 			batch.save_body_head(&tip)?;
 
 			// Reset the body tail to the body head after a txhashset write
@@ -1251,6 +2339,22 @@ impl Chain {
 		// Rebuild our NRD kernel_pos index based on recent kernel history.
 		txhashset.init_recent_kernel_pos_index(&header_pmmr, &batch)?;
 
+		// Rebuild our kernel excess index based on the full kernel MMR.
+		txhashset.init_kernel_pos_index(&header_pmmr, &batch, true)?;
+
+		// Validation is complete, its checkpoint (if any) is not needed anymore.
+		batch.delete_validation_checkpoint()?;
+
+		// Remember a trusted txhashset until its history is validated.
+		if assume_valid {
+			batch.save_assumed_utxo(&HashHeight {
+				hash: header.hash(),
+				height: header.height,
+			})?;
+		} else {
+			let _ = batch.delete_assumed_utxo();
+		}
+
 		// Commit all the changes to the db.
 		batch.commit()?;
 
@@ -1268,11 +2372,8 @@ impl Chain {
 			txhashset::txhashset_replace(sandbox_dir, PathBuf::from(self.db_root.clone()))?;
 
 			// Re-open on db root dir
-			txhashset = txhashset::TxHashSet::open(
-				self.db_root.clone(),
-				self.store.clone(),
-				Some(&header),
-			)?;
+			txhashset =
+				txhashset::TxHashSet::open(self.db_root.clone(), self.store.clone(), Some(header))?;
 
 			// Replace the chain txhashset with the newly built one.
 			*txhashset_ref = txhashset;
@@ -1282,7 +2383,7 @@ impl Chain {
 
 		status.on_done();
 
-		Ok(false)
+		Ok(())
 	}
 
 	/// Cleanup old blocks from the db.
@@ -1322,7 +2423,8 @@ impl Chain {
 
 		// Remove old blocks (including short lived fork blocks) which height < tail.height
 		// here b is a block
-		for (_, b) in batch.blocks_iter()? {
+		for res in batch.blocks_iter()? {
+			let (_, b) = res?;
 			if b.header.height < tail.height {
 				let _ = batch.delete_block(&b.hash());
 				count += 1;
@@ -1339,16 +2441,11 @@ impl Chain {
 		Ok(())
 	}
 
-	/// Triggers chain compaction.
-	///
-	/// * compacts the txhashset based on current prune_list
-	/// * removes historical blocks and associated data from the db (unless archive mode)
-	///
-	pub fn compact(&self) -> Result<(), Error> {
-		// A node may be restarted multiple times in a short period of time.
-		// We compact at most once per 60 blocks in this situation by comparing
-		// current "head" and "tail" height to our cut-through horizon and
-		// allowing an additional 60 blocks in height before allowing a further compaction.
+	// A node may be restarted multiple times in a short period of time.
+	// We compact at most once per 60 blocks in this situation by comparing
+	// current "head" and "tail" height to our cut-through horizon and
+	// allowing an additional 60 blocks in height before allowing a further compaction.
+	fn compaction_due(&self) -> bool {
 		if let (Ok(tail), Ok(head)) = (self.tail(), self.head()) {
 			let horizon = global::cut_through_horizon() as u64;
 			let threshold = horizon.saturating_add(60);
@@ -1358,17 +2455,22 @@ impl Chain {
 					"compact: skipping startup compaction (next at {})",
 					next_compact
 				);
-				return Ok(());
+				return false;
 			}
 		}
+		true
+	}
 
-		// Take a write lock on the txhashet and start a new writeable db batch.
-		let header_pmmr = self.header_pmmr.read();
-		let mut txhashset = self.txhashset.write();
-		let batch = self.store.batch()?;
-
+	// Compact the txhashset and bring the indexes depending on it up to date.
+	fn compact_txhashset(
+		&self,
+		header_pmmr: &txhashset::PMMRHandle<BlockHeader>,
+		txhashset: &mut txhashset::TxHashSet,
+		batch: &store::Batch<'_>,
+	) -> Result<(), Error> {
 		// Compact the txhashset itself (rewriting the pruned backend files).
-		{
+		// In archive mode the spent outputs are kept, nothing is compacted.
+		if !self.archive_mode {
 			let head_header = batch.head_header()?;
 			let current_height = head_header.height;
 			let horizon_height =
@@ -1376,19 +2478,48 @@ impl Chain {
 			let horizon_hash = header_pmmr.get_header_hash_by_height(horizon_height)?;
 			let horizon_header = batch.get_block_header(&horizon_hash)?;
 
-			txhashset.compact(&horizon_header, &batch)?;
+			txhashset.compact(&horizon_header, batch)?;
+		}
+
+		// Make sure our output_pos index is consistent with the UTXO set.
+		txhashset.init_output_pos_index(header_pmmr, batch)?;
+
+		// Forget about the transactions we have seen long ago.
+		let cutoff = Utc::now().timestamp_millis() - TX_FIRST_SEEN_RETENTION_SECS * 1000;
+		for res in batch.tx_first_seen_iter()? {
+			let (key, first_seen) = res?;
+			if first_seen < cutoff {
+				batch.delete(&key)?;
+			}
 		}
 
+		// Rebuild our NRD kernel_pos index based on recent kernel history.
+		txhashset.init_recent_kernel_pos_index(header_pmmr, batch)?;
+
+		Ok(())
+	}
+
+	/// Triggers chain compaction.
+	///
+	/// * compacts the txhashset based on current prune_list
+	/// * removes historical blocks and associated data from the db (unless archive mode)
+	///
+	pub fn compact(&self) -> Result<(), Error> {
+		if !self.compaction_due() {
+			return Ok(());
+		}
+
+		// Take a write lock on the txhashet and start a new writeable db batch.
+		let header_pmmr = self.header_pmmr.read();
+		let mut txhashset = self.txhashset.write();
+		let batch = self.store.batch()?;
+
 		// If we are not in archival mode remove historical blocks from the db.
 		if !self.archive_mode {
 			self.remove_historical_blocks(&header_pmmr, &batch)?;
 		}
 
-		// Make sure our output_pos index is consistent with the UTXO set.
-		txhashset.init_output_pos_index(&header_pmmr, &batch)?;
-
-		// Rebuild our NRD kernel_pos index based on recent kernel history.
-		txhashset.init_recent_kernel_pos_index(&header_pmmr, &batch)?;
+		self.compact_txhashset(&header_pmmr, &mut txhashset, &batch)?;
 
 		// Commit all the above db changes.
 		batch.commit()?;
@@ -1396,6 +2527,137 @@ impl Chain {
 		Ok(())
 	}
 
+	/// Runs the next chunk of the chain compaction, spending about `max_duration` on it,
+	/// so the compaction can run in background between the block arrivals.
+	/// The historical blocks are scanned at most COMPACTION_CHUNK_BLOCKS per chunk, the
+	/// scan resumes from the last block key. Then the txhashset is compacted part by part,
+	/// every chunk takes the txhashset write lock for a single part only.
+	/// Returns true once the compaction is complete (or not needed).
+	pub fn compact_chunk(
+		&self,
+		state: &mut CompactionState,
+		max_duration: Duration,
+	) -> Result<bool, Error> {
+		let start = Instant::now();
+
+		if !state.started {
+			if !self.compaction_due() {
+				return Ok(true);
+			}
+			state.started = true;
+
+			let head = self.head()?;
+			let horizon = global::cut_through_horizon() as u64;
+			let horizon_hash = self
+				.header_pmmr
+				.read()
+				.get_header_hash_by_height(head.height.saturating_sub(horizon))?;
+			let horizon_header = self.get_block_header(&horizon_hash)?;
+			if self.archive_mode {
+				// Nothing is removed, only the indexes are brought up to date
+				state.step = CompactionStep::OutputPosIndex;
+			} else {
+				if head.height > horizon {
+					state.tail = Some(horizon_header.clone());
+				} else {
+					state.step = CompactionStep::CompactOutputs;
+				}
+				state.horizon_header = Some(horizon_header);
+			}
+			return Ok(false);
+		}
+
+		match state.step {
+			CompactionStep::RemoveBlocks => {
+				let tail = match state.tail.as_ref() {
+					Some(tail) => tail,
+					None => {
+						return Err(ErrorKind::Other("compaction tail is unknown".into()).into())
+					}
+				};
+				let batch = self.store.batch()?;
+				let mut scanned = 0;
+				let mut done = true;
+				// Old blocks (including short lived fork blocks) which height < tail.height
+				for res in self.store.blocks_iter_from(state.scanned_key.as_deref())? {
+					let (key, b) = res?;
+					if state.scanned_key.as_ref() == Some(&key) {
+						continue;
+					}
+					if b.header.height < tail.height {
+						let _ = batch.delete_block(&b.hash());
+					}
+					state.scanned_key = Some(key);
+					scanned += 1;
+					if scanned >= COMPACTION_CHUNK_BLOCKS || start.elapsed() >= max_duration {
+						done = false;
+						break;
+					}
+				}
+				if done {
+					batch.save_body_tail(&Tip::from_header(tail))?;
+					debug!("compact_chunk: removed the blocks below {}", tail.height);
+					state.step = CompactionStep::CompactOutputs;
+				}
+				batch.commit()?;
+				Ok(false)
+			}
+			CompactionStep::CompactOutputs | CompactionStep::CompactRangeproofs => {
+				let horizon_header = match state.horizon_header.as_ref() {
+					Some(header) => header,
+					None => {
+						return Err(ErrorKind::Other("compaction horizon is unknown".into()).into())
+					}
+				};
+				let mut txhashset = self.txhashset.write();
+				let batch = self.store.batch()?;
+				if state.step == CompactionStep::CompactOutputs {
+					txhashset.compact_output_pmmr(horizon_header, &batch)?;
+					state.step = CompactionStep::CompactRangeproofs;
+				} else {
+					txhashset.compact_rproof_pmmr(horizon_header, &batch)?;
+					state.step = CompactionStep::OutputPosIndex;
+				}
+				batch.commit()?;
+				Ok(false)
+			}
+			CompactionStep::OutputPosIndex => {
+				let header_pmmr = self.header_pmmr.read();
+				let txhashset = self.txhashset.read();
+				let batch = self.store.batch()?;
+				// Make sure our output_pos index is consistent with the UTXO set.
+				txhashset.init_output_pos_index(&header_pmmr, &batch)?;
+				batch.commit()?;
+				state.step = CompactionStep::RecentIndexes;
+				Ok(false)
+			}
+			CompactionStep::RecentIndexes => {
+				{
+					let header_pmmr = self.header_pmmr.read();
+					let txhashset = self.txhashset.read();
+					let batch = self.store.batch()?;
+
+					// Forget about the transactions we have seen long ago.
+					let cutoff =
+						Utc::now().timestamp_millis() - TX_FIRST_SEEN_RETENTION_SECS * 1000;
+					for res in batch.tx_first_seen_iter()? {
+						let (key, first_seen) = res?;
+						if first_seen < cutoff {
+							batch.delete(&key)?;
+						}
+					}
+
+					// Rebuild our NRD kernel_pos index based on recent kernel history.
+					txhashset.init_recent_kernel_pos_index(&header_pmmr, &batch)?;
+
+					batch.commit()?;
+				}
+				*state = CompactionState::default();
+				Ok(true)
+			}
+		}
+	}
+
 	/// returns the last n nodes inserted into the output sum tree
 	pub fn get_last_n_output(&self, distance: u64) -> Vec<(Hash, OutputIdentifier)> {
 		self.txhashset.read().last_n_output(distance)
@@ -1416,6 +2678,15 @@ impl Chain {
 		Ok(self.txhashset.read().get_output_pos(commit)?)
 	}
 
+	/// Height of the block the unspent output with the provided commitment was included in.
+	/// Lookup is done via the output_pos index, None if the output is unknown or spent.
+	pub fn get_output_height(&self, commit: &Commitment) -> Result<Option<u64>, Error> {
+		Ok(self
+			.store
+			.get_output_pos_height(commit)?
+			.map(|pos| pos.height))
+	}
+
 	/// outputs by insertion index
 	pub fn unspent_outputs_by_pmmr_index(
 		&self,
@@ -1444,6 +2715,120 @@ impl Chain {
 		Ok((outputs.0, last_index, output_vec))
 	}
 
+	/// Traverse the unspent outputs in the output MMR order, up to `max` outputs per batch,
+	/// starting after the cursor returned with the previous batch (from the first output
+	/// without a cursor). Unlike the MMR index paging the cursor survives compaction, and
+	/// after a reorg of the scanned blocks the traversal resumes from the fork point, so some
+	/// outputs may be returned again but none are missed.
+	/// Returns the batch and the cursor of the next one. An empty batch means the traversal
+	/// reached the chain head, it can be resumed with the same cursor later on.
+	pub fn unspent_outputs_cursor(
+		&self,
+		cursor: Option<UtxoCursor>,
+		max: u64,
+		include_proof: bool,
+	) -> Result<(Vec<UnspentOutput>, Option<UtxoCursor>), Error> {
+		let start_pos = match cursor {
+			None => 0,
+			Some(ref cursor) => {
+				// Headers of the forks stay in the db, walk back to the current chain.
+				let mut header = self.get_block_header(&cursor.hash)?;
+				while self.is_on_current_chain(&header).is_err() {
+					header = self.get_previous_header(&header)?;
+				}
+				if header.hash() == cursor.hash {
+					cursor.pos
+				} else {
+					cmp::min(cursor.pos, header.output_mmr_size)
+				}
+			}
+		};
+
+		let header_pmmr = self.header_pmmr.read();
+		let txhashset = self.txhashset.read();
+		let mut outputs = vec![];
+		for (pos, output, proof) in txhashset.unspent_outputs_after(start_pos, max, include_proof) {
+			let height = self
+				.store
+				.get_output_pos_height(&output.commit)?
+				.map(|pos| pos.height)
+				.ok_or_else(|| {
+					ErrorKind::OutputNotFound(format!("No output pos for {:?}", output.commit))
+				})?;
+			outputs.push(UnspentOutput {
+				commit: output.commit,
+				features: output.features,
+				height,
+				pos,
+				proof,
+			});
+		}
+
+		let next_cursor = match outputs.last() {
+			Some(last) => Some(UtxoCursor {
+				pos: last.pos,
+				height: last.height,
+				hash: header_pmmr.get_header_hash_by_height(last.height)?,
+			}),
+			None => cursor,
+		};
+		Ok((outputs, next_cursor))
+	}
+
+	/// Outputs created by the blocks in the height range `[start_height, end_height]` on the
+	/// current chain, in the MMR order, with their rangeproofs if requested. Spent outputs
+	/// are included only if requested and only while their data is kept, i.e. within the
+	/// horizon or on archive nodes.
+	/// No more blocks are read once `max_outputs` outputs are collected, the height of the
+	/// next block is returned to continue from. The outputs of a block are never split.
+	pub fn outputs_in_height_range(
+		&self,
+		start_height: u64,
+		end_height: u64,
+		include_spent: bool,
+		include_proof: bool,
+		max_outputs: u64,
+	) -> Result<(Vec<RangeOutput>, Option<u64>), Error> {
+		let header_pmmr = self.header_pmmr.read();
+		let txhashset = self.txhashset.read();
+		let head_height = self.head()?.height;
+		if start_height > head_height {
+			return Ok((vec![], None));
+		}
+		let end_height = cmp::min(end_height, head_height);
+		let mut prev_mmr_size = match start_height.checked_sub(1) {
+			Some(h) => {
+				let hash = header_pmmr.get_header_hash_by_height(h)?;
+				self.get_block_header(&hash)?.output_mmr_size
+			}
+			None => 0,
+		};
+		let mut outputs = vec![];
+		for height in start_height..=end_height {
+			if outputs.len() as u64 >= max_outputs {
+				return Ok((outputs, Some(height)));
+			}
+			let hash = header_pmmr.get_header_hash_by_height(height)?;
+			let mmr_size = self.get_block_header(&hash)?.output_mmr_size;
+			for (pos, output, proof, spent) in txhashset.outputs_in_pos_range(
+				prev_mmr_size,
+				mmr_size,
+				include_spent,
+				include_proof,
+			) {
+				outputs.push(RangeOutput {
+					output,
+					proof,
+					height,
+					pos,
+					spent,
+				});
+			}
+			prev_mmr_size = mmr_size;
+		}
+		Ok((outputs, None))
+	}
+
 	/// Return unspent outputs as above, but bounded between a particular range of blocks
 	pub fn block_height_range_to_pmmr_indices(
 		&self,
@@ -1538,12 +2923,36 @@ impl Chain {
 		self.header_pmmr.read().get_header_hash_by_height(height)
 	}
 
+	/// Full blocks of the main chain from `start_height` to `end_height` inclusive,
+	/// `end_height` is capped at the chain head. The block hashes are resolved from the
+	/// header MMR under a single read lock, the blocks are read from the db lazily.
+	pub fn get_blocks_range(
+		&self,
+		start_height: u64,
+		end_height: u64,
+	) -> Result<BlocksRange, Error> {
+		let head = self.head()?;
+		let end_height = cmp::min(end_height, head.height);
+		let mut hashes = vec![];
+		if start_height <= end_height {
+			let header_pmmr = self.header_pmmr.read();
+			for height in start_height..=end_height {
+				hashes.push(header_pmmr.get_header_hash_by_height(height)?);
+			}
+		}
+		Ok(BlocksRange {
+			store: self.store.clone(),
+			hashes: hashes.into_iter(),
+		})
+	}
+
 	/// Migrate our local db from v2 to v3.
 	/// "commit only" inputs.
 	fn migrate_db_v2_v3(store: &ChainStore) -> Result<(), Error> {
 		let store_v2 = store.with_version(ProtocolVersion(2));
 		let batch = store_v2.batch()?;
-		for (_, block) in batch.blocks_iter()? {
+		for res in batch.blocks_iter()? {
+			let (_, block) = res?;
 			batch.migrate_block(&block, ProtocolVersion(3))?;
 		}
 		batch.commit()?;
@@ -1568,6 +2977,261 @@ impl Chain {
 		Ok(self.get_block_header(&hash)?)
 	}
 
+	/// Wall-clock time this block was first seen (and accepted) by this node.
+	/// Unlike the header timestamp it is our local time, so it shows how the
+	/// block propagated through the network.
+	pub fn block_first_seen(&self, h: &Hash) -> Result<Option<DateTime<Utc>>, Error> {
+		Ok(self
+			.store
+			.get_block_first_seen(h)?
+			.map(|ts| Utc.timestamp_millis(ts)))
+	}
+
+	/// Blocks mined by this node between the heights (inclusive), the lowest first,
+	/// with whether the block is on the current chain. The blocks that are not are
+	/// orphaned (reorged out).
+	pub fn get_mined_blocks(
+		&self,
+		start_height: u64,
+		end_height: u64,
+	) -> Result<Vec<(MinedBlock, bool)>, Error> {
+		let head = self.head()?;
+		let mut blocks = vec![];
+		for res in self.store.mined_blocks_iter(start_height)? {
+			let (_, block) = res?;
+			if block.height > end_height {
+				break;
+			}
+			let on_chain = block.height <= head.height
+				&& self.get_header_hash_by_height(block.height)? == block.hash;
+			blocks.push((block, on_chain));
+		}
+		Ok(blocks)
+	}
+
+	/// Wall-clock time the transaction with the provided kernel excess was
+	/// first seen by this node.
+	pub fn tx_first_seen(&self, excess: &Commitment) -> Result<Option<DateTime<Utc>>, Error> {
+		if let Some(ts) = self.tx_first_seen_pending.lock().0.get(excess) {
+			return Ok(Some(Utc.timestamp_millis(*ts)));
+		}
+		Ok(self
+			.store
+			.get_tx_first_seen(excess)?
+			.map(|ts| Utc.timestamp_millis(ts)))
+	}
+
+	/// Records `at` as the time the transaction was first seen, unless we already
+	/// know about it. Transactions are identified by their first kernel excess.
+	/// Returns the time the transaction was first seen. The records are written
+	/// to the db in batches.
+	pub fn record_tx_first_seen(
+		&self,
+		tx: &Transaction,
+		at: DateTime<Utc>,
+	) -> Result<DateTime<Utc>, Error> {
+		let excess = match tx.kernels().first() {
+			Some(kernel) => kernel.excess(),
+			None => return Ok(at),
+		};
+		let mut pending = self.tx_first_seen_pending.lock();
+		if let Some(ts) = pending.0.get(&excess) {
+			return Ok(Utc.timestamp_millis(*ts));
+		}
+		if let Some(ts) = self.store.get_tx_first_seen(&excess)? {
+			return Ok(Utc.timestamp_millis(ts));
+		}
+		pending.0.insert(excess, at.timestamp_millis());
+		if pending.0.len() >= TX_FIRST_SEEN_FLUSH_SIZE
+			|| pending.1.elapsed() >= TX_FIRST_SEEN_FLUSH_INTERVAL
+		{
+			self.flush_tx_first_seen(&mut pending)?;
+		}
+		Ok(at)
+	}
+
+	/// Write the pending first seen times of the txs in a single batch.
+	fn flush_tx_first_seen(
+		&self,
+		pending: &mut (HashMap<Commitment, i64>, Instant),
+	) -> Result<(), Error> {
+		if !pending.0.is_empty() {
+			let batch = self.store.batch()?;
+			for (excess, ts) in pending.0.iter() {
+				batch.save_tx_first_seen(excess, *ts)?;
+			}
+			batch.commit()?;
+			pending.0.clear();
+		}
+		pending.1 = Instant::now();
+		Ok(())
+	}
+
+	/// Register a watch filter, its events are matched from the next block on.
+	pub fn add_watch_filter(
+		&self,
+		name: String,
+		matcher: WatchMatcher,
+	) -> Result<WatchFilterInfo, Error> {
+		let mut cache = self.watch_filters.lock();
+		let id = self
+			.store
+			.watch_filters_iter()?
+			.map(|res| res.map(|(_, filter)| filter.id))
+			.collect::<Result<Vec<_>, _>>()?
+			.into_iter()
+			.max()
+			.unwrap_or(0)
+			+ 1;
+		let filter = WatchFilter {
+			id,
+			name,
+			matcher,
+			created_height: self.head()?.height,
+			next_seq: 0,
+		};
+		let batch = self.store.batch()?;
+		batch.save_watch_filter(&filter)?;
+		batch.commit()?;
+		*cache = None;
+		Ok(filter.info())
+	}
+
+	/// Delete the watch filter and its events.
+	pub fn delete_watch_filter(&self, id: u64) -> Result<(), Error> {
+		let mut cache = self.watch_filters.lock();
+		let batch = self.store.batch()?;
+		if batch.get_watch_filter(id)?.is_none() {
+			return Err(ErrorKind::Other(format!("watch filter {} not found", id)).into());
+		}
+		batch.delete_watch_filter(id)?;
+		batch.commit()?;
+		*cache = None;
+		Ok(())
+	}
+
+	/// Registered watch filters.
+	pub fn watch_filters(&self) -> Result<Vec<WatchFilterInfo>, Error> {
+		Ok(self
+			.store
+			.watch_filters_iter()?
+			.map(|res| res.map(|(_, filter)| filter.info()))
+			.collect::<Result<_, _>>()?)
+	}
+
+	/// Events of the watch filter starting from the cursor (event seq), at most limit events.
+	pub fn watch_events(
+		&self,
+		id: u64,
+		cursor: u64,
+		limit: usize,
+	) -> Result<WatchEventPage, Error> {
+		let batch = self.store.batch()?;
+		if batch.get_watch_filter(id)?.is_none() {
+			return Err(ErrorKind::Other(format!("watch filter {} not found", id)).into());
+		}
+		let events: Vec<WatchEvent> = self
+			.store
+			.watch_events_iter(id, cursor)?
+			.take(limit)
+			.map(|res| res.map(|(_, event)| event))
+			.collect::<Result<_, _>>()?;
+		let next_cursor = events.last().map(|e| e.seq + 1).unwrap_or(cursor);
+		Ok(WatchEventPage {
+			events,
+			next_cursor,
+		})
+	}
+
+	/// Match the watch filters against the block that just updated the chain head. On reorg
+	/// the matches of the disconnected blocks are reported as reorged, then the connected
+	/// blocks are matched. The events are written through the batch of the block.
+	/// The filters are cached until they are changed. The batch might be not committed, so
+	/// the filters with the new events are reloaded from the db.
+	fn update_watch_filters(
+		&self,
+		batch: &store::Batch<'_>,
+		b: &Block,
+		status: BlockStatus,
+	) -> Result<(), Error> {
+		let mut cache = self.watch_filters.lock();
+		if cache.is_none() {
+			let filters: Vec<WatchFilter> = batch
+				.watch_filters_iter()?
+				.map(|res| res.map(|(_, filter)| filter))
+				.collect::<Result<_, _>>()?;
+			*cache = Some(filters);
+		}
+		let mut filters = match &*cache {
+			Some(filters) if !filters.is_empty() => filters.clone(),
+			_ => return Ok(()),
+		};
+
+		let (disconnected, connected) = match status {
+			BlockStatus::Next { .. } => (vec![], vec![b.hash()]),
+			BlockStatus::Reorg {
+				prev_head,
+				fork_point,
+				..
+			} => {
+				let mut connected = self.fork_hashes(&Tip::from_header(&b.header), &fork_point)?;
+				connected.reverse();
+				(self.fork_hashes(&prev_head, &fork_point)?, connected)
+			}
+			BlockStatus::Fork { .. } => return Ok(()),
+		};
+
+		let mut updated = vec![false; filters.len()];
+		let blocks = disconnected
+			.iter()
+			.map(|h| (h, true))
+			.chain(connected.iter().map(|h| (h, false)));
+		for (hash, reorged) in blocks {
+			let stored;
+			let block = if *hash == b.hash() {
+				b
+			} else {
+				stored = batch.get_block(hash)?;
+				&stored
+			};
+			for (filter, updated) in filters.iter_mut().zip(updated.iter_mut()) {
+				if block.header.height <= filter.created_height {
+					continue;
+				}
+				let (created, spent) = filter.match_block(block);
+				let matches = created
+					.into_iter()
+					.map(|c| (WatchEventKind::Created, c))
+					.chain(spent.into_iter().map(|c| (WatchEventKind::Spent, c)));
+				for (kind, commit) in matches {
+					let event = WatchEvent {
+						seq: filter.next_seq,
+						kind: if reorged {
+							WatchEventKind::Reorged
+						} else {
+							kind
+						},
+						commit,
+						height: block.header.height,
+						block_hash: *hash,
+					};
+					batch.save_watch_event(filter.id, &event)?;
+					filter.next_seq += 1;
+					*updated = true;
+				}
+			}
+		}
+		if updated.iter().any(|u| *u) {
+			*cache = None;
+		}
+		for (filter, updated) in filters.iter().zip(updated) {
+			if updated {
+				batch.save_watch_filter(filter)?;
+			}
+		}
+		Ok(())
+	}
+
 	/// Gets the kernel with a given excess and the block height it is included in.
 	pub fn get_kernel_height(
 		&self,
@@ -1621,6 +3285,38 @@ impl Chain {
 
 		Ok(Some((kernel, header.height, mmr_index)))
 	}
+
+	/// Gets the kernel with a given excess via the kernel excess index,
+	/// together with the block height it is included in and its kernel MMR pos.
+	/// For a reused excess the first kernel is returned.
+	pub fn get_kernel_by_excess(
+		&self,
+		excess: &Commitment,
+	) -> Result<Option<(TxKernel, u64, u64)>, Error> {
+		let pos = match self.store.get_kernel_pos_height(excess)? {
+			Some(pos) => pos,
+			None => return Ok(None),
+		};
+		match self.txhashset.read().kernel_at(pos.pos) {
+			Some(kernel) if kernel.excess == *excess => Ok(Some((kernel, pos.height, pos.pos))),
+			_ => Err(ErrorKind::Other(format!(
+				"kernel excess index is inconsistent at pos {}, rebuild is required",
+				pos.pos
+			))
+			.into()),
+		}
+	}
+
+	/// Rebuild the kernel excess index from the full kernel MMR.
+	pub fn rebuild_kernel_pos_index(&self) -> Result<(), Error> {
+		let header_pmmr = self.header_pmmr.read();
+		let txhashset = self.txhashset.read();
+		let batch = self.store.batch()?;
+		txhashset.init_kernel_pos_index(&header_pmmr, &batch, true)?;
+		batch.commit()?;
+		Ok(())
+	}
+
 	/// Gets the block header in which a given kernel mmr index appears in the txhashset.
 	pub fn get_header_for_kernel_index(
 		&self,
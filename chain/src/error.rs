@@ -13,6 +13,7 @@
 // limitations under the License.
 
 //! Error types for chain
+use crate::core::core::pmmr::SegmentError;
 use crate::core::core::{block, committed, transaction};
 use crate::core::ser;
 use crate::keychain;
@@ -116,6 +117,9 @@ pub enum ErrorKind {
 	/// Internal issue when trying to save or load data from append only files
 	#[fail(display = "Chain File Read Error: {}", _0)]
 	FileReadErr(String),
+	/// Header MMR doesn't match the header head in the db
+	#[fail(display = "Header MMR Corrupted: {}", _0)]
+	HeaderMMRCorrupted(String),
 	/// Error serializing or deserializing a type
 	#[fail(display = "Chain Serialization Error, {}", _0)]
 	SerErr(ser::Error),
@@ -155,6 +159,9 @@ pub enum ErrorKind {
 	/// Error during chain sync
 	#[fail(display = "Sync error")]
 	SyncError(String),
+	/// Invalid or unavailable txhashset segment
+	#[fail(display = "Segment error: {}", _0)]
+	Segment(SegmentError),
 }
 
 impl Display for Error {
@@ -198,6 +205,7 @@ impl Error {
 			| ErrorKind::StoreErr(_, _)
 			| ErrorKind::SerErr(_)
 			| ErrorKind::TxHashSetErr(_)
+			| ErrorKind::HeaderMMRCorrupted(_)
 			| ErrorKind::GenesisBlockRequired
 			| ErrorKind::Other(_) => false,
 			_ => true,
@@ -279,6 +287,14 @@ impl From<ser::Error> for Error {
 	}
 }
 
+impl From<SegmentError> for Error {
+	fn from(error: SegmentError) -> Error {
+		Error {
+			inner: Context::new(ErrorKind::Segment(error)),
+		}
+	}
+}
+
 impl From<secp::Error> for Error {
 	fn from(e: secp::Error) -> Error {
 		Error {
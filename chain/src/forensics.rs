@@ -0,0 +1,172 @@
+// Copyright 2021 The MWC Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Records of the blocks rejected by the block processing pipeline. Optionally the
+//! rejected blocks are dumped to disk with their context, so the invalid blocks
+//! reported by the peers can be investigated without a custom build.
+
+use std::collections::VecDeque;
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+use chrono::Utc;
+
+use crate::core::core::hash::Hashed;
+use crate::core::core::{Block, BlockHeader};
+use crate::core::ser::{self, ProtocolVersion};
+use crate::error::Error;
+use crate::types::{default_max_rejected_blocks, BlockRejection, ExpectedRoots};
+use crate::util::{RwLock, ToHex};
+
+/// Directory of the dumps, under the db root.
+pub const FORENSICS_SUBDIR: &str = "forensics";
+
+const BLOCK_FILE: &str = "block.bin";
+const PREV_HEADER_FILE: &str = "prev_header.bin";
+const REJECTION_FILE: &str = "rejection.json";
+
+/// The last blocks rejected by the pipeline, the oldest first.
+pub struct BlockForensics {
+	dir: PathBuf,
+	// whether the rejected blocks are dumped, max number of the rejections kept
+	settings: RwLock<(bool, usize)>,
+	rejections: RwLock<VecDeque<BlockRejection>>,
+}
+
+impl BlockForensics {
+	/// Rejections of the chain at db root, not dumped until configured.
+	pub fn new(db_root: &str) -> BlockForensics {
+		BlockForensics {
+			dir: Path::new(db_root).join(FORENSICS_SUBDIR),
+			settings: RwLock::new((false, default_max_rejected_blocks())),
+			rejections: RwLock::new(VecDeque::new()),
+		}
+	}
+
+	/// Set whether the rejected blocks are dumped and how many of them are kept.
+	/// With the dumps enabled the rejections dumped by the previous runs are loaded.
+	pub fn configure(&self, dump: bool, max: usize) {
+		*self.settings.write() = (dump, max);
+		let mut rejections = self.rejections.write();
+		if dump {
+			let mut loaded = self.load_dumps();
+			loaded.retain(|r| !rejections.iter().any(|x| x.dump_dir == r.dump_dir));
+			loaded.extend(rejections.drain(..));
+			loaded.sort_by_key(|r| r.rejected_at);
+			*rejections = loaded.into_iter().collect();
+		}
+		self.trim(&mut rejections, max);
+	}
+
+	/// Record a rejected block, dumping it if configured.
+	pub fn record(
+		&self,
+		b: &Block,
+		prev: Option<&BlockHeader>,
+		stage: &str,
+		error: &Error,
+		expected_roots: Option<ExpectedRoots>,
+	) {
+		let (dump, max) = *self.settings.read();
+		let mut rejection = BlockRejection {
+			hash: b.hash().to_hex(),
+			height: b.header.height,
+			prev_hash: b.header.prev_hash.to_hex(),
+			stage: stage.to_owned(),
+			error: format!("{}", error),
+			rejected_at: Utc::now().timestamp(),
+			expected_roots,
+			dump_dir: None,
+		};
+		if dump {
+			let dir = self
+				.dir
+				.join(format!("{}_{}", b.header.height, b.hash().to_hex()));
+			rejection.dump_dir = Some(dir.to_string_lossy().into_owned());
+			if let Err(e) = Self::dump(&dir, b, prev, &rejection) {
+				warn!("Unable to dump the rejected block {}, {}", b.hash(), e);
+				let _ = fs::remove_dir_all(&dir);
+				rejection.dump_dir = None;
+			}
+		}
+
+		// A block sent again replaces its previous rejection (and dump).
+		let mut rejections = self.rejections.write();
+		rejections.retain(|r| r.hash != rejection.hash);
+		rejections.push_back(rejection);
+		self.trim(&mut rejections, max);
+	}
+
+	/// The last rejected blocks, the latest first.
+	pub fn rejections(&self) -> Vec<BlockRejection> {
+		self.rejections.read().iter().rev().cloned().collect()
+	}
+
+	// Drop the oldest rejections above max, together with their dumps.
+	fn trim(&self, rejections: &mut VecDeque<BlockRejection>, max: usize) {
+		while rejections.len() > max {
+			if let Some(dir) = rejections.pop_front().and_then(|r| r.dump_dir) {
+				if let Err(e) = fs::remove_dir_all(&dir) {
+					warn!("Unable to remove the rejected block dump {}, {}", dir, e);
+				}
+			}
+		}
+	}
+
+	fn dump(
+		dir: &Path,
+		b: &Block,
+		prev: Option<&BlockHeader>,
+		rejection: &BlockRejection,
+	) -> io::Result<()> {
+		fs::create_dir_all(dir)?;
+		let to_io_err = |e: ser::Error| io::Error::new(io::ErrorKind::Other, e.to_string());
+		let block = ser::ser_vec(b, ProtocolVersion::local()).map_err(to_io_err)?;
+		File::create(dir.join(BLOCK_FILE))?.write_all(&block)?;
+		if let Some(prev) = prev {
+			let prev = ser::ser_vec(prev, ProtocolVersion::local()).map_err(to_io_err)?;
+			File::create(dir.join(PREV_HEADER_FILE))?.write_all(&prev)?;
+		}
+		let json = serde_json::to_vec_pretty(rejection)
+			.map_err(|e| io::Error::new(io::ErrorKind::Other, e.to_string()))?;
+		File::create(dir.join(REJECTION_FILE))?.write_all(&json)?;
+		Ok(())
+	}
+
+	// Rejections dumped to the forensics directory.
+	fn load_dumps(&self) -> Vec<BlockRejection> {
+		let entries = match fs::read_dir(&self.dir) {
+			Ok(entries) => entries,
+			Err(_) => return vec![],
+		};
+		entries
+			.filter_map(|entry| entry.ok())
+			.filter_map(|entry| {
+				let json = fs::read(entry.path().join(REJECTION_FILE)).ok()?;
+				match serde_json::from_slice::<BlockRejection>(&json) {
+					Ok(rejection) => Some(rejection),
+					Err(e) => {
+						warn!(
+							"Unable to read the rejected block dump {}, {}",
+							entry.path().display(),
+							e
+						);
+						None
+					}
+				}
+			})
+			.collect()
+	}
+}
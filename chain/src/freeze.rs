@@ -0,0 +1,169 @@
+// Copyright 2021 The MWC Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Temporary freeze of the chain tip. While the tip is frozen the new blocks are
+//! buffered instead of processed, so the external systems can backup or audit the
+//! chain at a fixed height. The buffered blocks are processed once the freeze ends.
+
+use std::collections::HashMap;
+
+use chrono::Utc;
+
+use crate::core::core::hash::{Hash, Hashed};
+use crate::core::core::Block;
+use crate::error::{Error, ErrorKind};
+use crate::types::{Options, Tip, TipFreezeStatus};
+use crate::util::{Mutex, ToHex};
+
+/// Max duration of a freeze, the tip can't be frozen for longer in total.
+pub const MAX_TIP_FREEZE_SECS: u64 = 3600;
+
+/// Max number of the blocks buffered during a freeze. The freeze ends when the
+/// buffer is full, so the node doesn't fall too far behind the network.
+pub const MAX_FROZEN_BLOCKS: usize = 100;
+
+struct FrozenTip {
+	// unix timestamps, seconds
+	frozen_at: i64,
+	frozen_until: i64,
+	blocks: Vec<(Block, Options)>,
+	// heights of the buffered blocks
+	heights: HashMap<Hash, u64>,
+}
+
+/// Freeze state of the chain tip.
+pub struct TipFreeze {
+	state: Mutex<Option<FrozenTip>>,
+}
+
+impl TipFreeze {
+	/// Not frozen.
+	pub fn new() -> TipFreeze {
+		TipFreeze {
+			state: Mutex::new(None),
+		}
+	}
+
+	/// Freeze the tip for duration_secs. Freezing the frozen tip extends the freeze,
+	/// up to MAX_TIP_FREEZE_SECS since it started.
+	pub fn freeze(&self, duration_secs: u64) -> Result<(), Error> {
+		if duration_secs == 0 || duration_secs > MAX_TIP_FREEZE_SECS {
+			return Err(ErrorKind::Other(format!(
+				"tip freeze duration must be between 1 and {} seconds",
+				MAX_TIP_FREEZE_SECS
+			))
+			.into());
+		}
+		let now = Utc::now().timestamp();
+		let mut state = self.state.lock();
+		match state.as_mut() {
+			Some(frozen) => {
+				if now + duration_secs as i64 > frozen.frozen_at + MAX_TIP_FREEZE_SECS as i64 {
+					return Err(ErrorKind::Other(format!(
+						"tip is frozen since {}, it can't be frozen for more than {} seconds",
+						frozen.frozen_at, MAX_TIP_FREEZE_SECS
+					))
+					.into());
+				}
+				frozen.frozen_until = now + duration_secs as i64;
+			}
+			None => {
+				*state = Some(FrozenTip {
+					frozen_at: now,
+					frozen_until: now + duration_secs as i64,
+					blocks: vec![],
+					heights: HashMap::new(),
+				});
+			}
+		}
+		Ok(())
+	}
+
+	/// True if the tip is frozen now.
+	pub fn is_frozen(&self) -> bool {
+		let now = Utc::now().timestamp();
+		self.state
+			.lock()
+			.as_ref()
+			.map(|frozen| frozen.frozen_until > now)
+			.unwrap_or(false)
+	}
+
+	/// Height of the buffered block.
+	pub fn buffered_height(&self, hash: &Hash) -> Option<u64> {
+		self.state
+			.lock()
+			.as_ref()
+			.and_then(|frozen| frozen.heights.get(hash).cloned())
+	}
+
+	/// Buffer the block if the tip is frozen. Returns false if the block should be
+	/// processed. A full buffer ends the freeze. The caller validates the block first.
+	pub fn buffer(&self, b: &Block, opts: Options) -> bool {
+		let mut state = self.state.lock();
+		let frozen = match state.as_mut() {
+			Some(frozen) => frozen,
+			None => return false,
+		};
+		if frozen.frozen_until <= Utc::now().timestamp() {
+			return false;
+		}
+		let hash = b.hash();
+		if frozen.heights.contains_key(&hash) {
+			return true;
+		}
+		if frozen.blocks.len() >= MAX_FROZEN_BLOCKS {
+			warn!(
+				"tip freeze: {} blocks are buffered, ending the freeze",
+				frozen.blocks.len()
+			);
+			frozen.frozen_until = 0;
+			return false;
+		}
+		frozen.heights.insert(hash, b.header.height);
+		frozen.blocks.push((b.clone(), opts));
+		true
+	}
+
+	/// End the freeze if it's expired or force is set.
+	/// Returns the buffered blocks to process, in the order they were received.
+	pub fn take_unfrozen(&self, force: bool) -> Vec<(Block, Options)> {
+		let mut state = self.state.lock();
+		let expired = match state.as_ref() {
+			Some(frozen) => force || frozen.frozen_until <= Utc::now().timestamp(),
+			None => false,
+		};
+		if !expired {
+			return vec![];
+		}
+		state.take().map(|frozen| frozen.blocks).unwrap_or_default()
+	}
+
+	/// Freeze status at the chain head.
+	pub fn status(&self, head: &Tip) -> TipFreezeStatus {
+		let state = self.state.lock();
+		let now = Utc::now().timestamp();
+		let frozen = state.as_ref().filter(|frozen| frozen.frozen_until > now);
+		TipFreezeStatus {
+			frozen: frozen.is_some(),
+			height: head.height,
+			hash: head.last_block_h.to_hex(),
+			frozen_until: frozen.map(|frozen| frozen.frozen_until),
+			buffered_blocks: state
+				.as_ref()
+				.map(|frozen| frozen.blocks.len())
+				.unwrap_or(0),
+		}
+	}
+}
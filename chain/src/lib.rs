@@ -40,18 +40,27 @@ use grin_util as util;
 
 mod chain;
 mod error;
+mod forensics;
+mod freeze;
 pub mod linked_list;
 pub mod pipe;
 pub mod store;
 pub mod txhashset;
 pub mod types;
+pub mod watch;
 
 // Re-export the base interface
 
-pub use crate::chain::{Chain, BLOCK_TO_BAN, MAX_ORPHAN_SIZE};
+pub use crate::chain::{
+	BlocksRange, Chain, CompactionState, OrphanStats, BLOCK_TO_BAN, MAX_ORPHAN_SIZE,
+	ORPHANS_MAX_SIZE_BYTES,
+};
 pub use crate::error::{Error, ErrorKind};
+pub use crate::freeze::{MAX_FROZEN_BLOCKS, MAX_TIP_FREEZE_SECS};
 pub use crate::store::ChainStore;
 pub use crate::types::{
-	BlockStatus, ChainAdapter, Options, SyncState, SyncStatus, Tip, TxHashsetDownloadStats,
-	TxHashsetWriteStatus,
+	BlockRejection, BlockStatus, BlockTimingStats, BlockTimings, ChainAdapter, ChainConfig,
+	ChainStats, ExpectedRoots, MinedBlock, Options, RangeOutput, ReorgEvent, ReorgListener,
+	ReorgRecord, SyncState, SyncStatus, Tip, TipFreezeStatus, TxHashsetDownloadStats,
+	TxHashsetWriteStatus, UnspentOutput, UtxoCursor, ValidationCheckpoint,
 };
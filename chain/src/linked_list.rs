@@ -394,12 +394,14 @@ impl<T: PosEntry> PruneableListIndex for MultiIndex<T> {
 		let mut list_count = 0;
 		let mut entry_count = 0;
 		let prefix = to_key(self.list_prefix, "");
-		for (key, _) in batch.db.iter::<ListWrapper<T>>(&prefix)? {
+		for res in batch.db.iter::<ListWrapper<T>>(&prefix)? {
+			let (key, _) = res?;
 			let _ = batch.delete(&key);
 			list_count += 1;
 		}
 		let prefix = to_key(self.entry_prefix, "");
-		for (key, _) in batch.db.iter::<ListEntry<T>>(&prefix)? {
+		for res in batch.db.iter::<ListEntry<T>>(&prefix)? {
+			let (key, _) = res?;
 			let _ = batch.delete(&key);
 			entry_count += 1;
 		}
@@ -26,12 +26,13 @@ use crate::core::pow;
 use crate::error::{Error, ErrorKind};
 use crate::store;
 use crate::txhashset;
-use crate::types::{CommitPos, Options, Tip};
+use crate::types::{BlockTimings, CommitPos, ExpectedRoots, Options, Tip};
 use crate::util::RwLock;
 use grin_core::core::hash::Hash;
 use std::collections::HashSet;
 use std::iter::FromIterator;
 use std::sync::Arc;
+use std::time::Instant;
 
 /// Contextual information required to process a new block and either reject or
 /// accept it.
@@ -48,6 +49,12 @@ pub struct BlockContext<'a> {
 	pub batch: store::Batch<'a>,
 	/// The verifier cache (caching verifier for rangeproofs and kernel signatures)
 	pub verifier_cache: Arc<RwLock<dyn VerifierCache>>,
+	/// Time spent in each stage of the block processing
+	pub timings: BlockTimings,
+	/// Stage of the block processing in progress, the failing one if the block is rejected
+	pub stage: &'static str,
+	/// Txhashset roots and sizes after applying the block, to compare with the header ones
+	pub expected_roots: Option<ExpectedRoots>,
 }
 
 lazy_static! {
@@ -182,56 +189,79 @@ pub fn process_block(
 	let head = ctx.batch.head()?;
 
 	// Check if we have already processed this block previously.
+	ctx.stage = "known";
 	check_known(&b.header, &head, ctx)?;
 
 	// Quick pow validation. No point proceeding if this is invalid.
 	// We want to do this before we add the block to the orphan pool so we
 	// want to do this now and not later during header validation.
+	let start = Instant::now();
+	ctx.stage = "pow";
 	validate_pow_only(&b.header, ctx)?;
+	ctx.timings.pow = start.elapsed();
 
 	// Get previous header from the db.
+	ctx.stage = "prev_header";
 	let prev = prev_header_store(&b.header, &mut ctx.batch)?;
 
 	// Process the header for the block.
 	// Note: We still want to process the full block if we have seen this header before
 	// as we may have processed it "header first" and not yet processed the full block.
+	let start = Instant::now();
+	ctx.stage = "header";
 	process_block_header(&b.header, ctx)?;
+	ctx.timings.header = start.elapsed();
 
 	// Validate the block itself, make sure it is internally consistent.
 	// Use the verifier_cache for verifying rangeproofs and kernel signatures.
+	let start = Instant::now();
+	ctx.stage = "block";
 	validate_block(b, ctx)?;
+	ctx.timings.rangeproofs = start.elapsed();
 
 	// Start a chain extension unit of work dependent on the success of the
 	// internal validation and saving operations
 	let header_pmmr = &mut ctx.header_pmmr;
 	let txhashset = &mut ctx.txhashset;
 	let batch = &mut ctx.batch;
+	let timings = &mut ctx.timings;
+	let stage = &mut ctx.stage;
+	let expected_roots = &mut ctx.expected_roots;
+	let start = Instant::now();
 	let fork_point = txhashset::extending(header_pmmr, txhashset, batch, |ext, batch| {
+		*stage = "fork";
 		let fork_point_local_blocks = rewind_and_apply_fork(&prev, ext, batch)?;
 		let fork_point = fork_point_local_blocks.0;
 		let local_branch_blocks = fork_point_local_blocks.1;
 
+		*stage = "replay_attack";
 		replay_attack_check(b, fork_point.height, local_branch_blocks, ext, batch)?;
 
 		// Check any coinbase being spent have matured sufficiently.
 		// This needs to be done within the context of a potentially
 		// rewound txhashset extension to reflect chain state prior
 		// to applying the new block.
+		*stage = "coinbase_maturity";
 		verify_coinbase_maturity(b, ext, batch)?;
 
 		// Validate the block against the UTXO set.
+		*stage = "utxo";
 		validate_utxo(b, ext, batch)?;
 
 		// Using block_sums (utxo_sum, kernel_sum) for the previous block from the db
 		// we can verify_kernel_sums across the full UTXO sum and full kernel sum
 		// accounting for inputs/outputs/kernels in this new block.
 		// We know there are no double-spends etc. if this verifies successfully.
+		let sums_start = Instant::now();
+		*stage = "kernel_sums";
 		verify_block_sums(b, batch)?;
+		timings.kernel_sums = sums_start.elapsed();
 
 		// Apply the block to the txhashset state.
 		// Validate the txhashset roots and sizes against the block header.
 		// Block is invalid if there are any discrepencies.
-		apply_block_to_txhashset(b, ext, batch)?;
+		*stage = "txhashset";
+		apply_block_to_txhashset(b, ext, batch, expected_roots)?;
 
 		// If applying this block does not increase the work on the chain then
 		// we know we have not yet updated the chain to produce a new chain head.
@@ -244,11 +274,14 @@ pub fn process_block(
 
 		Ok(fork_point)
 	})?;
+	ctx.timings.txhashset = start.elapsed() - ctx.timings.kernel_sums;
 
 	// Add the validated block to the db.
 	// Note we do this in the outer batch, not the child batch from the extension
 	// as we only commit the child batch if the extension increases total work.
 	// We want to save the block to the db regardless.
+	let start = Instant::now();
+	ctx.stage = "save";
 	add_block(b, &ctx.batch)?;
 
 	// If we have no "tail" then set it now.
@@ -256,13 +289,50 @@ pub fn process_block(
 		update_body_tail(&b.header, &ctx.batch)?;
 	}
 
-	if has_more_work(&b.header, &head) {
+	let new_head = if has_more_work(&b.header, &head) {
 		let head = Tip::from_header(&b.header);
 		update_head(&head, &mut ctx.batch)?;
-		Ok((Some(head), fork_point))
+		Some(head)
 	} else {
-		Ok((None, fork_point))
+		None
+	};
+	// The batch is committed by the caller, it adds its commit time on top.
+	ctx.timings.commit = start.elapsed();
+
+	Ok((new_head, fork_point))
+}
+
+/// Runs the block validation without saving the block. The block must be the next block after
+/// the current chain head. Changes to the txhashset are done in the readonly extension,
+/// so they are discarded together with the batch.
+pub fn validate_block_dry_run(b: &Block, ctx: &mut BlockContext<'_>) -> Result<(), Error> {
+	let head = ctx.batch.head()?;
+	check_known(&b.header, &head, ctx)?;
+
+	if b.header.prev_hash != head.last_block_h {
+		return Err(ErrorKind::Unfit(format!(
+			"block is not built on the current head {}",
+			head.last_block_h
+		))
+		.into());
 	}
+
+	validate_pow_only(&b.header, ctx)?;
+	validate_header(&b.header, ctx)?;
+	validate_block(b, ctx)?;
+
+	let prev = prev_header_store(&b.header, &mut ctx.batch)?;
+	let expected_roots = &mut ctx.expected_roots;
+	txhashset::extending_readonly(&mut ctx.header_pmmr, &mut ctx.txhashset, |ext, batch| {
+		let (fork_point, local_branch_blocks) = rewind_and_apply_fork(&prev, ext, batch)?;
+		ext.header_extension.validate_root(&b.header)?;
+		replay_attack_check(b, fork_point.height, local_branch_blocks, ext, batch)?;
+		verify_coinbase_maturity(b, ext, batch)?;
+		validate_utxo(b, ext, batch)?;
+		verify_block_sums(b, batch)?;
+		apply_block_to_txhashset(b, ext, batch, expected_roots)?;
+		Ok(())
+	})
 }
 
 ///
@@ -580,13 +650,18 @@ fn verify_block_sums(b: &Block, batch: &store::Batch<'_>) -> Result<(), Error> {
 
 /// Fully validate the block by applying it to the txhashset extension.
 /// Check both the txhashset roots and sizes are correct after applying the block.
+/// The roots and sizes the header is expected to commit to are kept for the forensics.
 fn apply_block_to_txhashset(
 	block: &Block,
 	ext: &mut txhashset::ExtensionPair<'_>,
 	batch: &store::Batch<'_>,
+	expected_roots: &mut Option<ExpectedRoots>,
 ) -> Result<(), Error> {
 	ext.extension
 		.apply_block(block, ext.header_extension, batch)?;
+	if let Ok(roots) = ext.extension.roots() {
+		*expected_roots = Some(ExpectedRoots::new(&roots, ext.extension.sizes()));
+	}
 	ext.extension.validate_roots(&block.header)?;
 	ext.extension.validate_sizes(&block.header)?;
 	Ok(())
@@ -726,7 +801,7 @@ pub fn rewind_and_apply_fork(
 		// Re-verify block_sums to set the block_sums up on this fork correctly.
 		verify_block_sums(&fb, batch)?;
 		// Re-apply the blocks.
-		apply_block_to_txhashset(&fb, ext, batch)?;
+		apply_block_to_txhashset(&fb, ext, batch, &mut None)?;
 	}
 
 	Ok((fork_point, fork_hashes)) //change the signature so we can have the local branch information.
@@ -20,12 +20,14 @@ use crate::core::core::{Block, BlockHeader, BlockSums, Inputs};
 use crate::core::pow::Difficulty;
 use crate::core::ser::ProtocolVersion;
 use crate::linked_list::MultiIndex;
-use crate::types::{CommitPos, HashHeight, Tip};
+use crate::types::{CommitPos, HashHeight, MinedBlock, Tip, ValidationCheckpoint};
 use crate::util::secp::pedersen::Commitment;
+use crate::watch::{WatchEvent, WatchFilter};
 
 use croaring::Bitmap;
 use grin_store as store;
-use grin_store::{option_to_not_found, to_key, Error, SerIterator};
+use grin_store::encryption::EncryptionKey;
+use grin_store::{option_to_not_found, to_key, to_key_u64, u64_to_key, Error, SerIterator};
 use std::convert::TryInto;
 use std::sync::Arc;
 
@@ -37,6 +39,7 @@ const HEAD_PREFIX: u8 = b'H';
 const TAIL_PREFIX: u8 = b'T';
 const HEADER_HEAD_PREFIX: u8 = b'G';
 const OUTPUT_POS_PREFIX: u8 = b'p';
+const KERNEL_POS_PREFIX: u8 = b'e';
 
 /// Prefix for NRD kernel pos index lists.
 pub const NRD_KERNEL_LIST_PREFIX: u8 = b'K';
@@ -47,6 +50,13 @@ const BLOCK_INPUT_BITMAP_PREFIX: u8 = b'B';
 const BLOCK_SUMS_PREFIX: u8 = b'M';
 const BLOCK_SPENT_PREFIX: u8 = b'S';
 const BLOCK_SPENT_COMMITMENT_PREFIX: u8 = b'C';
+const BLOCK_FIRST_SEEN_PREFIX: u8 = b'f';
+const TX_FIRST_SEEN_PREFIX: u8 = b'F';
+const VALIDATION_CHECKPOINT_PREFIX: u8 = b'V';
+const ASSUMED_UTXO_PREFIX: u8 = b'A';
+const WATCH_FILTER_PREFIX: u8 = b'w';
+const WATCH_EVENT_PREFIX: u8 = b'W';
+const MINED_BLOCK_PREFIX: u8 = b'm';
 
 /// All chain-related database operations
 pub struct ChainStore {
@@ -56,10 +66,23 @@ pub struct ChainStore {
 impl ChainStore {
 	/// Create new chain store
 	pub fn new(db_root: &str) -> Result<ChainStore, Error> {
-		let db = store::Store::new(db_root, None, Some(STORE_SUBPATH), None)?;
+		ChainStore::new_with_key(db_root, None)
+	}
+
+	/// Create new chain store, encrypted with the provided key
+	pub fn new_with_key(
+		db_root: &str,
+		encryption: Option<Arc<EncryptionKey>>,
+	) -> Result<ChainStore, Error> {
+		let db = store::Store::new_with_key(db_root, None, Some(STORE_SUBPATH), None, encryption)?;
 		Ok(ChainStore { db })
 	}
 
+	/// Key the chain store is encrypted with, the chain PMMR files are encrypted with it too
+	pub fn encryption_key(&self) -> Option<Arc<EncryptionKey>> {
+		self.db.encryption_key()
+	}
+
 	/// Create a new instance of the chain store based on this instance
 	/// but with the provided protocol version. This is used when migrating
 	/// data in the db to a different protocol version, reading using one version and
@@ -140,6 +163,66 @@ impl ChainStore {
 		self.db.get_ser(&to_key(OUTPUT_POS_PREFIX, commit))
 	}
 
+	/// Get kernel MMR pos and block height for the given kernel excess.
+	pub fn get_kernel_pos_height(&self, excess: &Commitment) -> Result<Option<CommitPos>, Error> {
+		self.db.get_ser(&to_key(KERNEL_POS_PREFIX, excess))
+	}
+
+	/// Wall-clock time (in milliseconds) the block was first seen by this node.
+	pub fn get_block_first_seen(&self, h: &Hash) -> Result<Option<i64>, Error> {
+		self.db.get_ser(&to_key(BLOCK_FIRST_SEEN_PREFIX, h))
+	}
+
+	/// Wall-clock time (in milliseconds) the transaction with the provided kernel
+	/// excess was first seen by this node.
+	pub fn get_tx_first_seen(&self, excess: &Commitment) -> Result<Option<i64>, Error> {
+		self.db.get_ser(&to_key(TX_FIRST_SEEN_PREFIX, excess))
+	}
+
+	/// Progress of the full txhashset validation, if any.
+	pub fn get_validation_checkpoint(&self) -> Result<Option<ValidationCheckpoint>, Error> {
+		self.db.get_ser(&[VALIDATION_CHECKPOINT_PREFIX])
+	}
+
+	/// The trusted txhashset snapshot we started from, as long as its history
+	/// is not validated yet.
+	pub fn get_assumed_utxo(&self) -> Result<Option<HashHeight>, Error> {
+		self.db.get_ser(&[ASSUMED_UTXO_PREFIX])
+	}
+
+	/// An iterator over the blocks in the db, starting from the db key (all the blocks
+	/// if None). It doesn't hold the db write lock, so the long scans can be done in parts.
+	pub fn blocks_iter_from(&self, from: Option<&[u8]>) -> Result<SerIterator<Block>, Error> {
+		let prefix = to_key(BLOCK_PREFIX, "");
+		match from {
+			Some(from) => self.db.iter_from(&prefix, from),
+			None => self.db.iter(&prefix),
+		}
+	}
+
+	/// Iterator over the watch filters.
+	pub fn watch_filters_iter(&self) -> Result<SerIterator<WatchFilter>, Error> {
+		let key = to_key(WATCH_FILTER_PREFIX, "");
+		self.db.iter(&key)
+	}
+
+	/// Iterator over the events of the watch filter, starting from the event seq.
+	pub fn watch_events_iter(&self, id: u64, seq: u64) -> Result<SerIterator<WatchEvent>, Error> {
+		let prefix = u64_to_key(WATCH_EVENT_PREFIX, id);
+		self.db.iter_from(
+			&prefix,
+			&to_key_u64(WATCH_EVENT_PREFIX, id.to_be_bytes(), seq),
+		)
+	}
+
+	/// Iterator over the blocks mined by this node, starting from the height.
+	pub fn mined_blocks_iter(&self, height: u64) -> Result<SerIterator<MinedBlock>, Error> {
+		self.db.iter_from(
+			&to_key(MINED_BLOCK_PREFIX, ""),
+			&u64_to_key(MINED_BLOCK_PREFIX, height),
+		)
+	}
+
 	/// Builds a new batch to be used with this store.
 	pub fn batch(&self) -> Result<Batch<'_>, Error> {
 		Ok(Batch {
@@ -331,6 +414,28 @@ impl<'a> Batch<'a> {
 		self.db.delete(&to_key(OUTPUT_POS_PREFIX, commit))
 	}
 
+	/// Save kernel MMR pos and block height to the kernel excess index.
+	pub fn save_kernel_pos_height(&self, excess: &Commitment, pos: CommitPos) -> Result<(), Error> {
+		self.db
+			.put_ser(&to_key(KERNEL_POS_PREFIX, excess)[..], &pos)
+	}
+
+	/// Delete the kernel excess index entry for a rewound kernel.
+	pub fn delete_kernel_pos_height(&self, excess: &Commitment) -> Result<(), Error> {
+		self.db.delete(&to_key(KERNEL_POS_PREFIX, excess))
+	}
+
+	/// Get kernel MMR pos and block height for the given kernel excess.
+	pub fn get_kernel_pos_height(&self, excess: &Commitment) -> Result<Option<CommitPos>, Error> {
+		self.db.get_ser(&to_key(KERNEL_POS_PREFIX, excess))
+	}
+
+	/// Iterator over the kernel excess index.
+	pub fn kernel_pos_iter(&self) -> Result<SerIterator<(u64, u64)>, Error> {
+		let key = to_key(KERNEL_POS_PREFIX, "");
+		self.db.iter(&key)
+	}
+
 	/// Delete the commitment for a spent output.
 	pub fn delete_spent_commitments(&self, spent: &Commitment, hash: &Hash) -> Result<(), Error> {
 		let hash_list = self.get_spent_commitments(spent)?;
@@ -361,6 +466,113 @@ impl<'a> Batch<'a> {
 		commit_key == key
 	}
 
+	/// Save the wall-clock time (in milliseconds) the block was first seen.
+	pub fn save_block_first_seen(&self, h: &Hash, ts: i64) -> Result<(), Error> {
+		self.db
+			.put_ser(&to_key(BLOCK_FIRST_SEEN_PREFIX, h)[..], &ts)
+	}
+
+	/// Wall-clock time (in milliseconds) the block was first seen by this node.
+	pub fn get_block_first_seen(&self, h: &Hash) -> Result<Option<i64>, Error> {
+		self.db.get_ser(&to_key(BLOCK_FIRST_SEEN_PREFIX, h))
+	}
+
+	/// Save the wall-clock time (in milliseconds) the transaction with the
+	/// provided kernel excess was first seen.
+	pub fn save_tx_first_seen(&self, excess: &Commitment, ts: i64) -> Result<(), Error> {
+		self.db
+			.put_ser(&to_key(TX_FIRST_SEEN_PREFIX, excess)[..], &ts)
+	}
+
+	/// Wall-clock time (in milliseconds) the transaction with the provided kernel
+	/// excess was first seen by this node.
+	pub fn get_tx_first_seen(&self, excess: &Commitment) -> Result<Option<i64>, Error> {
+		self.db.get_ser(&to_key(TX_FIRST_SEEN_PREFIX, excess))
+	}
+
+	/// Save the progress of the full txhashset validation.
+	pub fn save_validation_checkpoint(
+		&self,
+		checkpoint: &ValidationCheckpoint,
+	) -> Result<(), Error> {
+		self.db.put_ser(&[VALIDATION_CHECKPOINT_PREFIX], checkpoint)
+	}
+
+	/// Delete the progress of the full txhashset validation once it is done.
+	/// Nothing to do if there is no checkpoint.
+	pub fn delete_validation_checkpoint(&self) -> Result<(), Error> {
+		if self.db.exists(&[VALIDATION_CHECKPOINT_PREFIX])? {
+			self.db.delete(&[VALIDATION_CHECKPOINT_PREFIX])?;
+		}
+		Ok(())
+	}
+
+	/// Save the trusted txhashset snapshot we are starting from.
+	pub fn save_assumed_utxo(&self, snapshot: &HashHeight) -> Result<(), Error> {
+		self.db.put_ser(&[ASSUMED_UTXO_PREFIX], snapshot)
+	}
+
+	/// Delete the trusted txhashset snapshot once its history is validated.
+	pub fn delete_assumed_utxo(&self) -> Result<(), Error> {
+		self.db.delete(&[ASSUMED_UTXO_PREFIX])
+	}
+
+	/// Get the watch filter.
+	pub fn get_watch_filter(&self, id: u64) -> Result<Option<WatchFilter>, Error> {
+		self.db.get_ser(&u64_to_key(WATCH_FILTER_PREFIX, id))
+	}
+
+	/// Save the watch filter.
+	pub fn save_watch_filter(&self, filter: &WatchFilter) -> Result<(), Error> {
+		self.db
+			.put_ser(&u64_to_key(WATCH_FILTER_PREFIX, filter.id), filter)
+	}
+
+	/// Save the block mined by this node.
+	pub fn save_mined_block(&self, block: &MinedBlock) -> Result<(), Error> {
+		self.db.put_ser(
+			&to_key(
+				MINED_BLOCK_PREFIX,
+				[&block.height.to_be_bytes()[..], block.hash.as_ref()].concat(),
+			),
+			block,
+		)
+	}
+
+	/// Save the event of the watch filter.
+	pub fn save_watch_event(&self, id: u64, event: &WatchEvent) -> Result<(), Error> {
+		self.db.put_ser(
+			&to_key_u64(WATCH_EVENT_PREFIX, id.to_be_bytes(), event.seq),
+			event,
+		)
+	}
+
+	/// Delete the watch filter with all its events.
+	pub fn delete_watch_filter(&self, id: u64) -> Result<(), Error> {
+		let prefix = u64_to_key(WATCH_EVENT_PREFIX, id);
+		let keys: Vec<Vec<u8>> = self
+			.db
+			.iter::<WatchEvent>(&prefix)?
+			.map(|res| res.map(|(key, _)| key))
+			.collect::<Result<_, _>>()?;
+		for key in keys {
+			self.db.delete(&key)?;
+		}
+		self.db.delete(&u64_to_key(WATCH_FILTER_PREFIX, id))
+	}
+
+	/// Iterator over the watch filters.
+	pub fn watch_filters_iter(&self) -> Result<SerIterator<WatchFilter>, Error> {
+		let key = to_key(WATCH_FILTER_PREFIX, "");
+		self.db.iter(&key)
+	}
+
+	/// Iterator over the transaction first seen index.
+	pub fn tx_first_seen_iter(&self) -> Result<SerIterator<i64>, Error> {
+		let key = to_key(TX_FIRST_SEEN_PREFIX, "");
+		self.db.iter(&key)
+	}
+
 	/// Iterator over the output_pos index.
 	pub fn output_pos_iter(&self) -> Result<SerIterator<(u64, u64)>, Error> {
 		let key = to_key(OUTPUT_POS_PREFIX, "");
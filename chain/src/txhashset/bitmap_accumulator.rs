@@ -19,7 +19,7 @@ use bit_vec::BitVec;
 use croaring::Bitmap;
 
 use crate::core::core::hash::{DefaultHashable, Hash};
-use crate::core::core::pmmr::{self, ReadonlyPMMR, VecBackend, PMMR};
+use crate::core::core::pmmr::{self, ReadonlyPMMR, Segment, SegmentIdentifier, VecBackend, PMMR};
 use crate::core::ser::{self, PMMRable, Readable, Reader, Writeable, Writer};
 use crate::error::{Error, ErrorKind};
 
@@ -180,6 +180,13 @@ impl BitmapAccumulator {
 	pub fn root(&self) -> Hash {
 		ReadonlyPMMR::at(&self.backend, self.backend.size()).root()
 	}
+
+	/// Merkle proof of the chunks covered by the segment against the root, so
+	/// the chunks rebuilt by the receiver can be validated.
+	pub fn segment_proof(&self, id: SegmentIdentifier) -> Result<Vec<Hash>, Error> {
+		let pmmr = ReadonlyPMMR::at(&self.backend, self.backend.size());
+		Ok(Segment::<BitmapChunk>::proof_from_pmmr(id, &pmmr)?)
+	}
 }
 
 /// A bitmap "chunk" representing 1024 contiguous bits of the overall bitmap.
@@ -0,0 +1,399 @@
+// Copyright 2021 The MWC Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Rebuilds the txhashset from segments downloaded (in any order, from any
+//! number of peers) for the txhashset archive header. Every segment is
+//! validated against the header roots as soon as it is received, segments
+//! are then applied in order into a sandbox txhashset.
+
+use std::cmp::min;
+use std::collections::{BTreeMap, HashSet};
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use croaring::Bitmap;
+
+use crate::core::core::hash::Hash;
+use crate::core::core::pmmr::{self, Segment, SegmentError, SegmentIdentifier};
+use crate::core::core::{BlockHeader, OutputIdentifier, TxKernel};
+use crate::core::ser::{self, ProtocolVersion, Writeable};
+use crate::error::Error;
+use crate::store::ChainStore;
+use crate::txhashset::{clean_txhashset_folder, BitmapChunk, TxHashSet};
+use crate::types::OutputRoots;
+use crate::util::secp::pedersen::RangeProof;
+
+/// Height of the output segments, 2048 outputs per segment.
+pub const OUTPUT_SEGMENT_HEIGHT: u8 = 11;
+/// Height of the rangeproof segments, 128 rangeproofs per segment.
+pub const RANGEPROOF_SEGMENT_HEIGHT: u8 = 7;
+/// Height of the kernel segments, 512 kernels per segment.
+pub const KERNEL_SEGMENT_HEIGHT: u8 = 9;
+/// Height of the output bitmap segments. A bitmap chunk covers 1024 outputs,
+/// so every output segment is covered by the bitmap segment with its index.
+pub const BITMAP_SEGMENT_HEIGHT: u8 = OUTPUT_SEGMENT_HEIGHT - 10;
+
+/// Identifier of the bitmap segment covering the output segment.
+pub fn bitmap_segment_id(output_id: SegmentIdentifier) -> SegmentIdentifier {
+	SegmentIdentifier {
+		height: BITMAP_SEGMENT_HEIGHT,
+		idx: output_id.idx,
+	}
+}
+
+/// The txhashset MMR a segment belongs to.
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub enum SegmentType {
+	/// Output MMR
+	Output,
+	/// Rangeproof MMR
+	RangeProof,
+	/// Kernel MMR
+	Kernel,
+}
+
+impl SegmentType {
+	/// Height of the segments of this type.
+	pub fn height(&self) -> u8 {
+		match self {
+			SegmentType::Output => OUTPUT_SEGMENT_HEIGHT,
+			SegmentType::RangeProof => RANGEPROOF_SEGMENT_HEIGHT,
+			SegmentType::Kernel => KERNEL_SEGMENT_HEIGHT,
+		}
+	}
+
+	/// Identifier of the segment with the provided index.
+	pub fn segment_id(&self, idx: u64) -> SegmentIdentifier {
+		SegmentIdentifier {
+			height: self.height(),
+			idx,
+		}
+	}
+}
+
+// Segments of a single MMR, received but not applied yet.
+struct SegmentQueue<T> {
+	next_idx: u64,
+	count: u64,
+	pending: BTreeMap<u64, Segment<T>>,
+}
+
+impl<T> SegmentQueue<T> {
+	fn new(height: u8, mmr_size: u64) -> SegmentQueue<T> {
+		SegmentQueue {
+			next_idx: 0,
+			count: SegmentIdentifier::count_for_mmr_size(height, mmr_size),
+			pending: BTreeMap::new(),
+		}
+	}
+
+	fn is_complete(&self) -> bool {
+		self.next_idx >= self.count
+	}
+
+	fn is_needed(&self, idx: u64) -> bool {
+		idx >= self.next_idx && idx < self.count && !self.pending.contains_key(&idx)
+	}
+
+	fn missing(&self) -> impl Iterator<Item = u64> + '_ {
+		(self.next_idx..self.count).filter(move |x| !self.pending.contains_key(x))
+	}
+
+	// Pop the next segment to apply, if we have it.
+	fn pop_next(&mut self) -> Option<Segment<T>> {
+		let segment = self.pending.remove(&self.next_idx)?;
+		self.next_idx += 1;
+		Some(segment)
+	}
+}
+
+/// Collects the txhashset segments for the archive header and rebuilds the
+/// output, rangeproof and kernel MMRs in a sandbox folder.
+pub struct Desegmenter {
+	header: BlockHeader,
+	sandbox_dir: PathBuf,
+	txhashset: TxHashSet,
+	output_pmmr_root: Option<Hash>,
+	outputs: SegmentQueue<OutputIdentifier>,
+	rangeproofs: SegmentQueue<RangeProof>,
+	kernels: SegmentQueue<TxKernel>,
+	spent: Bitmap,
+	received_segments: u64,
+	received_bytes: u64,
+}
+
+impl Desegmenter {
+	/// Create a new desegmenter for the provided header, using a fresh
+	/// txhashset in the sandbox folder.
+	pub fn new(
+		header: BlockHeader,
+		sandbox_dir: PathBuf,
+		store: Arc<ChainStore>,
+	) -> Result<Desegmenter, Error> {
+		clean_txhashset_folder(&sandbox_dir);
+		let txhashset = TxHashSet::open(
+			sandbox_dir
+				.to_str()
+				.expect("invalid sandbox folder")
+				.to_owned(),
+			store,
+			None,
+		)?;
+		Ok(Desegmenter {
+			outputs: SegmentQueue::new(OUTPUT_SEGMENT_HEIGHT, header.output_mmr_size),
+			rangeproofs: SegmentQueue::new(RANGEPROOF_SEGMENT_HEIGHT, header.output_mmr_size),
+			kernels: SegmentQueue::new(KERNEL_SEGMENT_HEIGHT, header.kernel_mmr_size),
+			header,
+			sandbox_dir,
+			txhashset,
+			output_pmmr_root: None,
+			spent: Bitmap::create(),
+			received_segments: 0,
+			received_bytes: 0,
+		})
+	}
+
+	/// Header the txhashset is rebuilt for.
+	pub fn header(&self) -> &BlockHeader {
+		&self.header
+	}
+
+	/// All the segments have been received and applied.
+	pub fn is_complete(&self) -> bool {
+		self.outputs.is_complete() && self.rangeproofs.is_complete() && self.kernels.is_complete()
+	}
+
+	/// Whether the segment is still needed (not received yet).
+	pub fn is_needed(&self, segment_type: SegmentType, idx: u64) -> bool {
+		match segment_type {
+			SegmentType::Output => self.outputs.is_needed(idx),
+			SegmentType::RangeProof => self.rangeproofs.is_needed(idx),
+			SegmentType::Kernel => self.kernels.is_needed(idx),
+		}
+	}
+
+	/// Up to max segments we still need, the next ones to apply first.
+	/// Kernels, outputs and rangeproofs are interleaved so all MMRs progress.
+	pub fn missing_segments(&self, max: usize) -> Vec<(SegmentType, SegmentIdentifier)> {
+		let mut kernels = self.kernels.missing().map(|x| (SegmentType::Kernel, x));
+		let mut outputs = self.outputs.missing().map(|x| (SegmentType::Output, x));
+		let mut rangeproofs = self
+			.rangeproofs
+			.missing()
+			.map(|x| (SegmentType::RangeProof, x));
+
+		let mut res = vec![];
+		while res.len() < max {
+			let next: Vec<_> = vec![kernels.next(), outputs.next(), rangeproofs.next()]
+				.into_iter()
+				.flatten()
+				.collect();
+			if next.is_empty() {
+				break;
+			}
+			res.extend(next.into_iter().map(|(t, idx)| (t, t.segment_id(idx))));
+		}
+		res.truncate(max);
+		res
+	}
+
+	/// Download progress, (received bytes, estimated total bytes).
+	pub fn progress(&self) -> (u64, u64) {
+		let total_segments = self.outputs.count + self.rangeproofs.count + self.kernels.count;
+		let estimate = if self.received_segments == 0 {
+			0
+		} else {
+			self.received_bytes / self.received_segments * total_segments
+		};
+		(self.received_bytes, estimate)
+	}
+
+	/// Validate and queue an output segment. The output roots are provided by
+	/// the peer and checked against the header, the segment is then validated
+	/// against the output PMMR root and the spent outputs against the bitmap
+	/// root.
+	pub fn add_output_segment(
+		&mut self,
+		segment: Segment<OutputIdentifier>,
+		spent: &[u64],
+		bitmap_proof: Vec<Hash>,
+		output_roots: OutputRoots,
+	) -> Result<(), Error> {
+		let id = self.check_id(SegmentType::Output, segment.identifier())?;
+		if self.is_duplicate(SegmentType::Output, id.idx) {
+			return Ok(());
+		}
+		if output_roots.root(&self.header) != self.header.output_root {
+			return Err(SegmentError::RootMismatch.into());
+		}
+		if let Some(root) = self.output_pmmr_root {
+			if root != output_roots.pmmr_root {
+				return Err(SegmentError::RootMismatch.into());
+			}
+		}
+		segment.validate(self.header.output_mmr_size, output_roots.pmmr_root)?;
+		if spent
+			.iter()
+			.any(|pos| !segment.leaves().any(|(x, _)| x == *pos))
+		{
+			return Err(SegmentError::Malformed("spent output not in segment".to_owned()).into());
+		}
+		self.validate_bitmap(&segment, spent, bitmap_proof, output_roots.bitmap_root)?;
+
+		self.output_pmmr_root = Some(output_roots.pmmr_root);
+		self.on_received(&segment);
+		self.spent
+			.add_many(&spent.iter().map(|x| *x as u32).collect::<Vec<_>>());
+		self.outputs.pending.insert(id.idx, segment);
+		while let Some(segment) = self.outputs.pop_next() {
+			self.txhashset.apply_output_segment(&segment, &self.spent)?;
+		}
+		Ok(())
+	}
+
+	/// Validate and queue a rangeproof segment.
+	pub fn add_rangeproof_segment(&mut self, segment: Segment<RangeProof>) -> Result<(), Error> {
+		let id = self.check_id(SegmentType::RangeProof, segment.identifier())?;
+		if self.is_duplicate(SegmentType::RangeProof, id.idx) {
+			return Ok(());
+		}
+		segment.validate(self.header.output_mmr_size, self.header.range_proof_root)?;
+
+		self.on_received(&segment);
+		self.rangeproofs.pending.insert(id.idx, segment);
+		while let Some(segment) = self.rangeproofs.pop_next() {
+			self.txhashset.apply_rangeproof_segment(&segment)?;
+		}
+		Ok(())
+	}
+
+	/// Validate and queue a kernel segment.
+	pub fn add_kernel_segment(&mut self, segment: Segment<TxKernel>) -> Result<(), Error> {
+		let id = self.check_id(SegmentType::Kernel, segment.identifier())?;
+		if self.is_duplicate(SegmentType::Kernel, id.idx) {
+			return Ok(());
+		}
+		segment.validate(self.header.kernel_mmr_size, self.header.kernel_root)?;
+
+		self.on_received(&segment);
+		self.kernels.pending.insert(id.idx, segment);
+		while let Some(segment) = self.kernels.pop_next() {
+			self.txhashset.apply_kernel_segment(&segment)?;
+		}
+		Ok(())
+	}
+
+	/// Finalize the sandbox txhashset once all the segments are applied.
+	/// Returns the header and the sandbox folder, ready for full validation.
+	pub fn finish(mut self) -> Result<(BlockHeader, PathBuf), Error> {
+		if !self.is_complete() {
+			return Err(SegmentError::Malformed("txhashset segments missing".to_owned()).into());
+		}
+		let (output_size, rproof_size, kernel_size) = self.txhashset.sizes();
+		if output_size != self.header.output_mmr_size
+			|| rproof_size != self.header.output_mmr_size
+			|| kernel_size != self.header.kernel_mmr_size
+		{
+			return Err(SegmentError::Malformed(format!(
+				"rebuilt txhashset sizes {} {} {} do not match header",
+				output_size, rproof_size, kernel_size
+			))
+			.into());
+		}
+		self.txhashset.finish_segments(&self.spent)?;
+		self.txhashset.release_backend_files();
+		Ok((self.header, self.sandbox_dir))
+	}
+
+	// Rebuild the bitmap chunks covering the output segment from its unspent
+	// outputs and validate them against the bitmap root with the peer proof.
+	fn validate_bitmap(
+		&self,
+		segment: &Segment<OutputIdentifier>,
+		spent: &[u64],
+		bitmap_proof: Vec<Hash>,
+		bitmap_root: Hash,
+	) -> Result<(), Error> {
+		// The last output is created by the header block and is unspent, so the
+		// accumulator has a chunk for every started 1024 outputs.
+		let n_chunks = (pmmr::n_leaves(self.header.output_mmr_size) + 1023) / 1024;
+		let bitmap_size = pmmr::insertion_to_pmmr_index(n_chunks + 1) - 1;
+		let id = bitmap_segment_id(segment.identifier());
+		let first_chunk = id.first_leaf_idx();
+		let last_chunk = min(first_chunk + id.capacity(), n_chunks);
+
+		let spent: HashSet<u64> = spent.iter().cloned().collect();
+		let mut chunks: Vec<BitmapChunk> = (first_chunk..last_chunk)
+			.map(|_| BitmapChunk::new())
+			.collect();
+		for (pos, _) in segment.leaves().filter(|(pos, _)| !spent.contains(pos)) {
+			let idx = pmmr::n_leaves(pos) - 1;
+			match chunks.get_mut((idx / 1024).saturating_sub(first_chunk) as usize) {
+				Some(chunk) => chunk.set(idx % 1024, true),
+				None => {
+					return Err(
+						SegmentError::Malformed("output outside of bitmap".to_owned()).into(),
+					)
+				}
+			}
+		}
+		let leaves = chunks
+			.into_iter()
+			.enumerate()
+			.map(|(i, chunk)| {
+				let pos = pmmr::insertion_to_pmmr_index(first_chunk + i as u64 + 1);
+				(pos, chunk)
+			})
+			.collect();
+		Segment::from_leaves(id, leaves, bitmap_proof).validate(bitmap_size, bitmap_root)?;
+		Ok(())
+	}
+
+	fn check_id(
+		&self,
+		segment_type: SegmentType,
+		id: SegmentIdentifier,
+	) -> Result<SegmentIdentifier, Error> {
+		if id.height != segment_type.height() {
+			return Err(SegmentError::InvalidHeight(id.height).into());
+		}
+		Ok(id)
+	}
+
+	// Already applied or waiting to be applied.
+	fn is_duplicate(&self, segment_type: SegmentType, idx: u64) -> bool {
+		let (next_idx, pending) = match segment_type {
+			SegmentType::Output => (
+				self.outputs.next_idx,
+				self.outputs.pending.contains_key(&idx),
+			),
+			SegmentType::RangeProof => (
+				self.rangeproofs.next_idx,
+				self.rangeproofs.pending.contains_key(&idx),
+			),
+			SegmentType::Kernel => (
+				self.kernels.next_idx,
+				self.kernels.pending.contains_key(&idx),
+			),
+		};
+		idx < next_idx || pending
+	}
+
+	fn on_received<T: Writeable>(&mut self, segment: &Segment<T>) {
+		self.received_segments += 1;
+		self.received_bytes += ser::ser_vec(segment, ProtocolVersion::local())
+			.map(|x| x.len() as u64)
+			.unwrap_or(0);
+	}
+}
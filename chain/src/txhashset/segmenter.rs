@@ -0,0 +1,96 @@
+// Copyright 2021 The MWC Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Serves the txhashset segments of the archive header to the peers. The state
+//! of the output MMR at the archive header (unspent leaves, bitmap accumulator
+//! and roots) is captured once, segments are then built from the MMR files
+//! under the txhashset read lock, without rewinding.
+
+use std::sync::Arc;
+
+use croaring::Bitmap;
+
+use crate::core::core::hash::Hash;
+use crate::core::core::pmmr::{self, Segment, SegmentIdentifier};
+use crate::core::core::{BlockHeader, OutputIdentifier, TxKernel};
+use crate::error::Error;
+use crate::txhashset::{bitmap_segment_id, BitmapAccumulator, TxHashSet};
+use crate::types::OutputRoots;
+use crate::util::secp::pedersen::RangeProof;
+use crate::util::RwLock;
+
+/// Segments of the txhashset at the provided header.
+pub struct Segmenter {
+	txhashset: Arc<RwLock<TxHashSet>>,
+	header: BlockHeader,
+	// insertion indexes of the outputs unspent at the header
+	unspent: Bitmap,
+	bitmap_accumulator: BitmapAccumulator,
+	output_roots: OutputRoots,
+}
+
+impl Segmenter {
+	/// Create the segmenter from the state of the output MMR at the header.
+	pub fn new(
+		txhashset: Arc<RwLock<TxHashSet>>,
+		header: BlockHeader,
+		unspent: Bitmap,
+		bitmap_accumulator: BitmapAccumulator,
+		output_roots: OutputRoots,
+	) -> Segmenter {
+		Segmenter {
+			txhashset,
+			header,
+			unspent,
+			bitmap_accumulator,
+			output_roots,
+		}
+	}
+
+	/// Header the segments are served for.
+	pub fn header(&self) -> &BlockHeader {
+		&self.header
+	}
+
+	/// Output segment, the positions of the outputs spent since the header
+	/// that are still in the segment, the merkle proof of the bitmap chunks
+	/// covering the segment and the output roots.
+	pub fn output_segment(
+		&self,
+		id: SegmentIdentifier,
+	) -> Result<(Segment<OutputIdentifier>, Vec<u64>, Vec<Hash>, OutputRoots), Error> {
+		let segment = self.txhashset.read().output_segment_at(&self.header, id)?;
+		let spent = segment
+			.leaves()
+			.map(|(pos, _)| pos)
+			.filter(|pos| !self.unspent.contains((pmmr::n_leaves(*pos) - 1) as u32))
+			.collect();
+		let bitmap_proof = self
+			.bitmap_accumulator
+			.segment_proof(bitmap_segment_id(id))?;
+		Ok((segment, spent, bitmap_proof, self.output_roots.clone()))
+	}
+
+	/// Rangeproof segment.
+	pub fn rangeproof_segment(&self, id: SegmentIdentifier) -> Result<Segment<RangeProof>, Error> {
+		self.txhashset
+			.read()
+			.rangeproof_segment_at(&self.header, id)
+	}
+
+	/// Kernel segment.
+	pub fn kernel_segment(&self, id: SegmentIdentifier) -> Result<Segment<TxKernel>, Error> {
+		self.txhashset.read().kernel_segment_at(&self.header, id)
+	}
+}
@@ -17,24 +17,33 @@
 
 use crate::core::consensus::WEEK_HEIGHT;
 use crate::core::core::committed::Committed;
-use crate::core::core::hash::{Hash, Hashed};
+use crate::core::core::hash::{Hash, HashWriter, Hashed};
 use crate::core::core::merkle_proof::MerkleProof;
-use crate::core::core::pmmr::{self, Backend, ReadonlyPMMR, RewindablePMMR, PMMR};
+use crate::core::core::pmmr::{
+	self, Backend, ReadonlyPMMR, RewindablePMMR, Segment, SegmentEntry, SegmentError,
+	SegmentIdentifier, PMMR,
+};
 use crate::core::core::{Block, BlockHeader, KernelFeatures, Output, OutputIdentifier, TxKernel};
 use crate::core::global;
-use crate::core::ser::{PMMRable, ProtocolVersion};
+use crate::core::ser::{PMMRable, ProtocolVersion, Writer};
 use crate::error::{Error, ErrorKind};
 use crate::linked_list::{ListIndex, PruneableListIndex, RewindableListIndex};
 use crate::store::{self, Batch, ChainStore};
 use crate::txhashset::bitmap_accumulator::BitmapAccumulator;
 use crate::txhashset::{RewindableKernelView, UTXOView};
-use crate::types::{CommitPos, HashHeight, OutputRoots, Tip, TxHashSetRoots, TxHashsetWriteStatus};
+use crate::types::{
+	CommitPos, HashHeight, OutputRoots, Tip, TxHashSetRoots, TxHashsetWriteStatus,
+	ValidationCheckpoint,
+};
 use crate::util::secp::pedersen::{Commitment, RangeProof};
 use crate::util::{file, secp_static, zip};
 use croaring::Bitmap;
 use grin_store;
-use grin_store::pmmr::{clean_files_by_prefix, PMMRBackend};
+use grin_store::encryption::EncryptionKey;
+use grin_store::pmmr::{clean_files_by_prefix, PMMRBackend, PMMR_ENCRYPTED_FILES};
+use rayon::prelude::*;
 use std::fs::{self, File};
+use std::io::{Read, Seek, SeekFrom};
 use std::path::{Path, PathBuf};
 use std::sync::Arc;
 use std::time::Instant;
@@ -47,6 +56,17 @@ const KERNEL_SUBDIR: &str = "kernel";
 
 const TXHASHSET_ZIP: &str = "txhashset_snapshot";
 
+/// Number of kernel signatures in a single verification batch
+const KERNEL_BATCH_SIZE: usize = 5_000;
+/// Number of rangeproofs in a single verification batch
+const RPROOF_BATCH_SIZE: usize = 1_000;
+/// Number of kernel signatures in a single verification batch in the low memory mode
+const KERNEL_BATCH_SIZE_LOW_MEMORY: usize = 500;
+/// Number of rangeproofs in a single verification batch in the low memory mode
+const RPROOF_BATCH_SIZE_LOW_MEMORY: usize = 100;
+/// Number of the most recent kernels that are checked against the kernel excess index at startup
+const KERNEL_POS_INDEX_CHECK_LEAVES: usize = 1_000;
+
 /// Convenience wrapper around a single prunable MMR backend.
 pub struct PMMRHandle<T: PMMRable> {
 	/// The backend storage for the MMR.
@@ -58,14 +78,16 @@ pub struct PMMRHandle<T: PMMRable> {
 impl<T: PMMRable> PMMRHandle<T> {
 	/// Constructor to create a PMMR handle from an existing directory structure on disk.
 	/// Creates the backend files as necessary if they do not already exist.
+	/// The files are encrypted with the provided key, if any.
 	pub fn new<P: AsRef<Path>>(
 		path: P,
 		prunable: bool,
 		version: ProtocolVersion,
 		header: Option<&BlockHeader>,
+		encryption: Option<&EncryptionKey>,
 	) -> Result<PMMRHandle<T>, Error> {
 		fs::create_dir_all(&path)?;
-		let backend = PMMRBackend::new(&path, prunable, version, header)?;
+		let backend = PMMRBackend::new_with_key(&path, prunable, version, header, encryption)?;
 		let last_pos = backend.unpruned_size();
 		Ok(PMMRHandle { backend, last_pos })
 	}
@@ -73,9 +95,14 @@ impl<T: PMMRable> PMMRHandle<T> {
 
 impl PMMRHandle<BlockHeader> {
 	/// Used during chain init to ensure the header PMMR is consistent with header_head in the db.
+	/// Fails with HeaderMMRCorrupted if the MMR doesn't match the header head.
 	pub fn init_head(&mut self, head: &Tip) -> Result<(), Error> {
-		let head_hash = self.head_hash()?;
-		let expected_hash = self.get_header_hash_by_height(head.height)?;
+		let head_hash = self
+			.head_hash()
+			.map_err(|e| ErrorKind::HeaderMMRCorrupted(e.to_string()))?;
+		let expected_hash = self
+			.get_header_hash_by_height(head.height)
+			.map_err(|e| ErrorKind::HeaderMMRCorrupted(e.to_string()))?;
 		if head.hash() != expected_hash {
 			error!(
 				"header PMMR inconsistent: {} vs {} at {}",
@@ -83,7 +110,9 @@ impl PMMRHandle<BlockHeader> {
 				head.hash(),
 				head.height
 			);
-			return Err(ErrorKind::Other("header PMMR inconsistent".to_string()).into());
+			return Err(
+				ErrorKind::HeaderMMRCorrupted("header PMMR inconsistent".to_string()).into(),
+			);
 		}
 
 		// 1-indexed pos and we want to account for subsequent parent hash pos.
@@ -161,6 +190,7 @@ impl TxHashSet {
 		commit_index: Arc<ChainStore>,
 		header: Option<&BlockHeader>,
 	) -> Result<TxHashSet, Error> {
+		let encryption = commit_index.encryption_key();
 		let output_pmmr_h = PMMRHandle::new(
 			Path::new(&root_dir)
 				.join(TXHASHSET_SUBDIR)
@@ -168,6 +198,7 @@ impl TxHashSet {
 			true,
 			ProtocolVersion(1),
 			header,
+			encryption.as_deref(),
 		)?;
 
 		let rproof_pmmr_h = PMMRHandle::new(
@@ -177,6 +208,7 @@ impl TxHashSet {
 			true,
 			ProtocolVersion(1),
 			header,
+			encryption.as_deref(),
 		)?;
 
 		// Initialize the bitmap accumulator from the current output PMMR.
@@ -192,6 +224,7 @@ impl TxHashSet {
 				false, // not prunable
 				version,
 				None,
+				encryption.as_deref(),
 			)?;
 			if handle.last_pos == 0 {
 				debug!(
@@ -254,6 +287,60 @@ impl TxHashSet {
 		self.kernel_pmmr_h.backend.release_files();
 	}
 
+	/// Current sizes of the output, rangeproof and kernel MMRs.
+	pub fn sizes(&self) -> (u64, u64, u64) {
+		(
+			self.output_pmmr_h.last_pos,
+			self.rproof_pmmr_h.last_pos,
+			self.kernel_pmmr_h.last_pos,
+		)
+	}
+
+	/// Append an (already validated) output segment to the output MMR.
+	/// Segments must be applied in order. Spent outputs are removed from the
+	/// leaf set right away.
+	pub fn apply_output_segment(
+		&mut self,
+		segment: &Segment<OutputIdentifier>,
+		spent: &Bitmap,
+	) -> Result<(), Error> {
+		apply_segment(&mut self.output_pmmr_h, segment, true)?;
+		let mut pmmr = PMMR::at(&mut self.output_pmmr_h.backend, self.output_pmmr_h.last_pos);
+		for (pos, _) in segment.leaves().filter(|(x, _)| spent.contains(*x as u32)) {
+			pmmr.prune(pos).map_err(ErrorKind::TxHashSetErr)?;
+		}
+		Ok(())
+	}
+
+	/// Append an (already validated) rangeproof segment to the rangeproof MMR.
+	/// Segments must be applied in order.
+	pub fn apply_rangeproof_segment(&mut self, segment: &Segment<RangeProof>) -> Result<(), Error> {
+		apply_segment(&mut self.rproof_pmmr_h, segment, true)
+	}
+
+	/// Append an (already validated) kernel segment to the kernel MMR.
+	/// Segments must be applied in order.
+	pub fn apply_kernel_segment(&mut self, segment: &Segment<TxKernel>) -> Result<(), Error> {
+		apply_segment(&mut self.kernel_pmmr_h, segment, false)
+	}
+
+	/// Done applying segments. Removes the rangeproofs of the spent outputs and
+	/// saves everything to disk.
+	pub fn finish_segments(&mut self, spent: &Bitmap) -> Result<(), Error> {
+		{
+			let mut pmmr = PMMR::at(&mut self.rproof_pmmr_h.backend, self.rproof_pmmr_h.last_pos);
+			for pos in spent.iter() {
+				pmmr.prune(pos as u64).map_err(ErrorKind::TxHashSetErr)?;
+			}
+		}
+		self.output_pmmr_h.backend.sync()?;
+		self.output_pmmr_h.backend.sync_prune_list()?;
+		self.rproof_pmmr_h.backend.sync()?;
+		self.rproof_pmmr_h.backend.sync_prune_list()?;
+		self.kernel_pmmr_h.backend.sync()?;
+		Ok(())
+	}
+
 	/// Check if an output is unspent.
 	/// We look in the index to find the output MMR pos.
 	/// Then we check the entry in the output MMR and confirm the hash matches.
@@ -280,6 +367,37 @@ impl TxHashSet {
 		}
 	}
 
+	/// Output segment of the output MMR at the header. Segments are read from
+	/// the MMR files, the outputs spent since the header are still there.
+	pub fn output_segment_at(
+		&self,
+		header: &BlockHeader,
+		id: SegmentIdentifier,
+	) -> Result<Segment<OutputIdentifier>, Error> {
+		let pmmr = ReadonlyPMMR::at(&self.output_pmmr_h.backend, header.output_mmr_size);
+		Ok(Segment::from_pmmr(id, &pmmr)?)
+	}
+
+	/// Rangeproof segment of the rangeproof MMR at the header.
+	pub fn rangeproof_segment_at(
+		&self,
+		header: &BlockHeader,
+		id: SegmentIdentifier,
+	) -> Result<Segment<RangeProof>, Error> {
+		let pmmr = ReadonlyPMMR::at(&self.rproof_pmmr_h.backend, header.output_mmr_size);
+		Ok(Segment::from_pmmr(id, &pmmr)?)
+	}
+
+	/// Kernel segment of the kernel MMR at the header.
+	pub fn kernel_segment_at(
+		&self,
+		header: &BlockHeader,
+		id: SegmentIdentifier,
+	) -> Result<Segment<TxKernel>, Error> {
+		let pmmr = ReadonlyPMMR::at(&self.kernel_pmmr_h.backend, header.kernel_mmr_size);
+		Ok(Segment::from_pmmr(id, &pmmr)?)
+	}
+
 	/// returns the last N nodes inserted into the tree (i.e. the 'bottom'
 	/// nodes at level 0
 	/// TODO: These need to return the actual data from the flat-files instead
@@ -301,6 +419,24 @@ impl TxHashSet {
 			.get_last_n_insertions(distance)
 	}
 
+	/// Output at the provided position, spent or not, as long as its data has
+	/// not been compacted away. Also returns whether the output is spent.
+	pub fn historical_output_at(&self, pos: u64) -> Option<(Output, bool)> {
+		let output_pmmr =
+			ReadonlyPMMR::at(&self.output_pmmr_h.backend, self.output_pmmr_h.last_pos);
+		let rproof_pmmr =
+			ReadonlyPMMR::at(&self.rproof_pmmr_h.backend, self.rproof_pmmr_h.last_pos);
+		let out = output_pmmr.get_data_from_file(pos)?;
+		let proof = rproof_pmmr.get_data_from_file(pos)?;
+		let spent = output_pmmr.get_data(pos).is_none();
+		Some((out.into_output(proof), spent))
+	}
+
+	/// Number of currently unspent outputs.
+	pub fn unspent_count(&self) -> u64 {
+		self.output_pmmr_h.backend.n_unpruned_leaves()
+	}
+
 	/// Convenience function to query the db for a header by its hash.
 	pub fn get_block_header(&self, hash: &Hash) -> Result<BlockHeader, Error> {
 		Ok(self.commit_index.get_block_header(&hash)?)
@@ -319,6 +455,65 @@ impl TxHashSet {
 			.elements_from_pmmr_index(start_index, max_count, max_index)
 	}
 
+	/// Unspent outputs after the provided output MMR position, in the MMR order, with their
+	/// positions and, if requested, their rangeproofs. Pruned positions are skipped.
+	pub fn unspent_outputs_after(
+		&self,
+		pos: u64,
+		max_count: u64,
+		include_proof: bool,
+	) -> Vec<(u64, OutputIdentifier, Option<RangeProof>)> {
+		let last_pos = self.output_pmmr_h.last_pos;
+		let output_pmmr = ReadonlyPMMR::at(&self.output_pmmr_h.backend, last_pos);
+		let rproof_pmmr = ReadonlyPMMR::at(&self.rproof_pmmr_h.backend, last_pos);
+		output_pmmr
+			.leaf_idx_iter(pmmr::n_leaves(pos))
+			.map(|idx| pmmr::insertion_to_pmmr_index(idx + 1))
+			.take_while(|pos| *pos <= last_pos)
+			.filter_map(|pos| {
+				let output = output_pmmr.get_data(pos)?;
+				let proof = if include_proof {
+					Some(rproof_pmmr.get_data(pos)?)
+				} else {
+					None
+				};
+				Some((pos, output, proof))
+			})
+			.take(max_count as usize)
+			.collect()
+	}
+
+	/// Outputs at the output MMR positions in the range `(start_pos, end_pos]`, with their
+	/// rangeproofs if requested and the spent flag. Spent outputs are included only if
+	/// requested and if their data is not compacted away yet.
+	pub fn outputs_in_pos_range(
+		&self,
+		start_pos: u64,
+		end_pos: u64,
+		include_spent: bool,
+		include_proof: bool,
+	) -> Vec<(u64, OutputIdentifier, Option<RangeProof>, bool)> {
+		let last_pos = self.output_pmmr_h.last_pos;
+		let output_pmmr = ReadonlyPMMR::at(&self.output_pmmr_h.backend, last_pos);
+		let rproof_pmmr = ReadonlyPMMR::at(&self.rproof_pmmr_h.backend, last_pos);
+		(start_pos + 1..=end_pos.min(last_pos))
+			.filter(|pos| pmmr::is_leaf(*pos))
+			.filter_map(|pos| {
+				let (out, spent) = match output_pmmr.get_data(pos) {
+					Some(out) => (out, false),
+					None if include_spent => (output_pmmr.get_data_from_file(pos)?, true),
+					None => return None,
+				};
+				let proof = if include_proof {
+					Some(rproof_pmmr.get_data_from_file(pos)?)
+				} else {
+					None
+				};
+				Some((pos, out, proof, spent))
+			})
+			.collect()
+	}
+
 	/// highest output insertion index available
 	pub fn highest_output_insertion_index(&self) -> u64 {
 		self.output_pmmr_h.last_pos
@@ -358,6 +553,11 @@ impl TxHashSet {
 		None
 	}
 
+	/// Get the kernel at the provided kernel MMR position, if any.
+	pub fn kernel_at(&self, pos: u64) -> Option<TxKernel> {
+		ReadonlyPMMR::at(&self.kernel_pmmr_h.backend, self.kernel_pmmr_h.last_pos).get_data(pos)
+	}
+
 	/// Get MMR roots.
 	pub fn roots(&self) -> TxHashSetRoots {
 		let output_pmmr =
@@ -377,6 +577,99 @@ impl TxHashSet {
 		}
 	}
 
+	/// Verify the rangeproofs of the unspent outputs and the kernel signatures against the
+	/// provided header, resuming from the validation checkpoint saved by a previous attempt.
+	/// The progress is checkpointed in the db, so this method must not be called while a db
+	/// batch is open. Returns false if the txhashset doesn't match the header and nothing
+	/// was verified.
+	pub fn verify_proofs(
+		&self,
+		header: &BlockHeader,
+		status: &dyn TxHashsetWriteStatus,
+	) -> Result<bool, Error> {
+		if header.height == 0
+			|| self.output_pmmr_h.last_pos != header.output_mmr_size
+			|| self.kernel_pmmr_h.last_pos != header.kernel_mmr_size
+		{
+			return Ok(false);
+		}
+		// Checkpoints are trusted for this exact txhashset state only.
+		self.roots().validate(header)?;
+
+		let header_hash = header.hash();
+		let mut checkpoint = match self.commit_index.get_validation_checkpoint()? {
+			Some(checkpoint) if checkpoint.hash == header_hash => checkpoint,
+			_ => ValidationCheckpoint {
+				hash: header_hash,
+				rproof_pos: 0,
+				kernel_pos: 0,
+			},
+		};
+		if checkpoint.rproof_pos > 0 || checkpoint.kernel_pos > 0 {
+			info!(
+				"txhashset: resuming validation for {} at rproof pos {}, kernel pos {}",
+				header_hash, checkpoint.rproof_pos, checkpoint.kernel_pos,
+			);
+		}
+
+		let save_checkpoint = |checkpoint: &ValidationCheckpoint| -> Result<(), Error> {
+			let batch = self.commit_index.batch()?;
+			batch.save_validation_checkpoint(checkpoint)?;
+			batch.commit()?;
+			Ok(())
+		};
+
+		let output_pmmr =
+			ReadonlyPMMR::at(&self.output_pmmr_h.backend, self.output_pmmr_h.last_pos);
+		let rproof_pmmr =
+			ReadonlyPMMR::at(&self.rproof_pmmr_h.backend, self.rproof_pmmr_h.last_pos);
+		let kernel_pmmr =
+			ReadonlyPMMR::at(&self.kernel_pmmr_h.backend, self.kernel_pmmr_h.last_pos);
+
+		verify_rangeproofs_from(
+			&output_pmmr,
+			&rproof_pmmr,
+			checkpoint.rproof_pos,
+			status,
+			&mut |pos| {
+				checkpoint.rproof_pos = pos;
+				save_checkpoint(&checkpoint)
+			},
+		)?;
+		verify_kernel_signatures_from(&kernel_pmmr, checkpoint.kernel_pos, status, &mut |pos| {
+			checkpoint.kernel_pos = pos;
+			save_checkpoint(&checkpoint)
+		})?;
+
+		Ok(true)
+	}
+
+	/// Check the kernel MMR root of every header from the provided one back to genesis,
+	/// like the kernel history validation of a fast sync. Only reads the db, so the
+	/// block processing is not blocked while this runs.
+	pub fn validate_kernel_roots(&self, header: &BlockHeader) -> Result<u64, Error> {
+		let mut kernel_pmmr =
+			RewindablePMMR::at(&self.kernel_pmmr_h.backend, self.kernel_pmmr_h.last_pos);
+		let mut count = 0;
+		let mut current = header.clone();
+		while current.height > 0 {
+			kernel_pmmr
+				.rewind(current.kernel_mmr_size)
+				.map_err(|e| ErrorKind::TxHashSetErr(e))?;
+			let root = kernel_pmmr.root().map_err(|e| ErrorKind::InvalidRoot(e))?;
+			if root != current.kernel_root {
+				return Err(ErrorKind::InvalidTxHashSet(format!(
+					"Kernel root at {} does not match",
+					current.height
+				))
+				.into());
+			}
+			current = self.commit_index.get_previous_header(&current)?;
+			count += 1;
+		}
+		Ok(count)
+	}
+
 	/// Return Commit's MMR position
 	pub fn get_output_pos(&self, commit: &Commitment) -> Result<u64, Error> {
 		Ok(self.commit_index.get_output_pos(&commit)?)
@@ -392,6 +685,45 @@ impl TxHashSet {
 			})
 	}
 
+	/// Build a merkle proof for the unspent output at the given position, against the output
+	/// MMR of the given (historical) size. Read only, no rewind is needed.
+	pub fn output_merkle_proof(&self, pos: u64, mmr_size: u64) -> Result<MerkleProof, Error> {
+		if pos == 0 || pos > mmr_size || mmr_size > self.output_pmmr_h.last_pos {
+			return Err(ErrorKind::MerkleProof(format!(
+				"Output pos {} is out of the MMR of size {}",
+				pos, mmr_size
+			))
+			.into());
+		}
+		ReadonlyPMMR::at(&self.output_pmmr_h.backend, mmr_size)
+			.merkle_proof(pos)
+			.map_err(|e| ErrorKind::MerkleProof(format!("Output pos {}, {}", pos, e)).into())
+	}
+
+	/// Build a merkle proof for the kernel at the given position, against the kernel MMR
+	/// of the given (historical) size.
+	pub fn kernel_merkle_proof(
+		&mut self,
+		pos: u64,
+		mmr_size: u64,
+	) -> Result<(TxKernel, MerkleProof), Error> {
+		if pos == 0 || pos > mmr_size || mmr_size > self.kernel_pmmr_h.last_pos {
+			return Err(ErrorKind::MerkleProof(format!(
+				"Kernel pos {} is out of the MMR of size {}",
+				pos, mmr_size
+			))
+			.into());
+		}
+		let pmmr = PMMR::at(&mut self.kernel_pmmr_h.backend, mmr_size);
+		let kernel = pmmr
+			.get_data(pos)
+			.ok_or_else(|| ErrorKind::MerkleProof(format!("No kernel at pos {}", pos)))?;
+		let merkle_proof = pmmr
+			.merkle_proof(pos)
+			.map_err(|e| ErrorKind::MerkleProof(format!("Kernel pos {}, {}", pos, e)))?;
+		Ok((kernel, merkle_proof))
+	}
+
 	/// Compact the MMR data files and flush the rm logs
 	pub fn compact(
 		&mut self,
@@ -400,22 +732,43 @@ impl TxHashSet {
 	) -> Result<(), Error> {
 		debug!("txhashset: starting compaction...");
 
-		let head_header = batch.head_header()?;
+		self.compact_output_pmmr(horizon_header, batch)?;
+		self.compact_rproof_pmmr(horizon_header, batch)?;
+
+		debug!("txhashset: ... compaction finished");
 
+		Ok(())
+	}
+
+	/// Compact the output MMR backend, the first part of `compact`.
+	pub fn compact_output_pmmr(
+		&mut self,
+		horizon_header: &BlockHeader,
+		batch: &Batch<'_>,
+	) -> Result<(), Error> {
+		let head_header = batch.head_header()?;
 		let rewind_rm_pos = input_pos_to_rewind(&horizon_header, &head_header, batch)?;
 
 		debug!("txhashset: check_compact output mmr backend...");
 		self.output_pmmr_h
 			.backend
 			.check_compact(horizon_header.output_mmr_size, &rewind_rm_pos)?;
+		Ok(())
+	}
+
+	/// Compact the rangeproof MMR backend, the second part of `compact`.
+	pub fn compact_rproof_pmmr(
+		&mut self,
+		horizon_header: &BlockHeader,
+		batch: &Batch<'_>,
+	) -> Result<(), Error> {
+		let head_header = batch.head_header()?;
+		let rewind_rm_pos = input_pos_to_rewind(&horizon_header, &head_header, batch)?;
 
 		debug!("txhashset: check_compact rangeproof mmr backend...");
 		self.rproof_pmmr_h
 			.backend
 			.check_compact(horizon_header.output_mmr_size, &rewind_rm_pos)?;
-
-		debug!("txhashset: ... compaction finished");
-
 		Ok(())
 	}
 
@@ -515,7 +868,8 @@ impl TxHashSet {
 		// Iterate over the current output_pos index, removing any entries that
 		// do not point to to the expected output.
 		let mut removed_count = 0;
-		for (key, (pos, _)) in batch.output_pos_iter()? {
+		for res in batch.output_pos_iter()? {
+			let (key, (pos, _)) = res?;
 			if let Some(out) = output_pmmr.get_data(pos) {
 				if let Ok(pos_via_mmr) = batch.get_output_pos(&out.commitment()) {
 					// If the pos matches and the index key matches the commitment
@@ -589,6 +943,94 @@ impl TxHashSet {
 		);
 		Ok(())
 	}
+
+	/// Initialize the kernel excess index (excess -> first kernel pos and block height).
+	/// Nothing to do if the first kernel and the most recent kernels are indexed consistently,
+	/// unless a full rebuild is requested. Otherwise the index is cleared and rebuilt
+	/// from the full kernel MMR.
+	pub fn init_kernel_pos_index(
+		&self,
+		header_pmmr: &PMMRHandle<BlockHeader>,
+		batch: &Batch<'_>,
+		rebuild: bool,
+	) -> Result<(), Error> {
+		let now = Instant::now();
+
+		let kernel_pmmr =
+			ReadonlyPMMR::at(&self.kernel_pmmr_h.backend, self.kernel_pmmr_h.last_pos);
+		let last_pos = self.kernel_pmmr_h.last_pos;
+
+		if !rebuild {
+			// Reused excess is indexed at its first pos, so the entry can point before the kernel
+			let check_leaves = (1..=last_pos)
+				.rev()
+				.filter(|x| pmmr::is_leaf(*x))
+				.take(KERNEL_POS_INDEX_CHECK_LEAVES)
+				.chain(std::iter::once(1).filter(|_| last_pos > 0));
+			let mut indexed = true;
+			for pos in check_leaves {
+				let kernel = match kernel_pmmr.get_data(pos) {
+					Some(kernel) => kernel,
+					None => continue,
+				};
+				indexed = match batch.get_kernel_pos_height(&kernel.excess)? {
+					Some(p) if p.pos == pos => true,
+					Some(p) if p.pos < pos => kernel_pmmr
+						.get_data(p.pos)
+						.map_or(false, |k| k.excess == kernel.excess),
+					_ => false,
+				};
+				if !indexed {
+					break;
+				}
+			}
+			if indexed {
+				return Ok(());
+			}
+		}
+
+		let mut removed_count = 0;
+		for res in batch.kernel_pos_iter()? {
+			let (key, _) = res?;
+			batch.delete(&key)?;
+			removed_count += 1;
+		}
+		debug!(
+			"init_kernel_pos_index: removed {} index entries",
+			removed_count
+		);
+
+		// Kernel MMR is not prunable, every leaf pos has its kernel data.
+		let max_height = batch.head()?.height;
+		let mut height = 0;
+		let mut header = batch.get_block_header(&header_pmmr.get_header_hash_by_height(0)?)?;
+		let mut kernel_count = 0;
+		for pos in (1..=last_pos).filter(|x| pmmr::is_leaf(*x)) {
+			while pos > header.kernel_mmr_size && height < max_height {
+				height += 1;
+				let hash = header_pmmr.get_header_hash_by_height(height)?;
+				header = batch.get_block_header(&hash)?;
+			}
+			if let Some(kernel) = kernel_pmmr.get_data(pos) {
+				if batch.get_kernel_pos_height(&kernel.excess)?.is_none() {
+					batch.save_kernel_pos_height(
+						&kernel.excess,
+						CommitPos {
+							pos,
+							height: header.height,
+						},
+					)?;
+				}
+				kernel_count += 1;
+			}
+		}
+		debug!(
+			"init_kernel_pos_index: added entries for {} kernels, took {}s",
+			kernel_count,
+			now.elapsed().as_secs(),
+		);
+		Ok(())
+	}
 }
 
 /// Starts a new unit of work to extend (or rewind) the chain with additional
@@ -637,6 +1079,48 @@ where
 	res
 }
 
+// Append the segment entries to the MMR. Pruned subtree roots shared with the
+// previous segment (segments below the same pruned subtree) are skipped.
+fn apply_segment<T>(
+	handle: &mut PMMRHandle<T>,
+	segment: &Segment<T>,
+	prunable: bool,
+) -> Result<(), Error>
+where
+	T: PMMRable,
+{
+	let mut pmmr = PMMR::at(&mut handle.backend, handle.last_pos);
+	for (pos, entry) in segment.entries() {
+		match entry {
+			SegmentEntry::Hash(hash) => {
+				if !prunable {
+					return Err(SegmentError::Malformed(format!(
+						"pruned subtree at {} in non-prunable MMR",
+						pos
+					))
+					.into());
+				}
+				if pos > pmmr.last_pos {
+					pmmr.push_pruned_subtree(*hash, pos)
+						.map_err(ErrorKind::TxHashSetErr)?;
+				}
+			}
+			SegmentEntry::Leaf(elmt) => {
+				let leaf_pos = pmmr.push(elmt).map_err(ErrorKind::TxHashSetErr)?;
+				if leaf_pos != pos {
+					return Err(SegmentError::Malformed(format!(
+						"leaf at {}, expected {}",
+						pos, leaf_pos
+					))
+					.into());
+				}
+			}
+		}
+	}
+	handle.last_pos = pmmr.last_pos;
+	Ok(())
+}
+
 /// Readonly view on the UTXO set.
 /// Based on the current txhashset output_pmmr.
 pub fn utxo_view<F, T>(
@@ -1241,6 +1725,10 @@ impl<'a> Extension<'a> {
 			let pos = self.apply_kernel(kernel)?;
 			let commit_pos = CommitPos { pos, height };
 			apply_kernel_rules(kernel, commit_pos, batch)?;
+			// Reused excess keeps its first pos, so rewind never loses an older kernel
+			if batch.get_kernel_pos_height(&kernel.excess)?.is_none() {
+				batch.save_kernel_pos_height(&kernel.excess, commit_pos)?;
+			}
 		}
 		Ok(())
 	}
@@ -1395,6 +1883,18 @@ impl<'a> Extension<'a> {
 			}
 		}
 
+		// Remove any entries from the kernel excess index created by the block being rewound.
+		// Entries point to the first pos of the excess, the entry of a reused excess that is
+		// before the rewound block stays as is.
+		for kernel in block.kernels() {
+			let excess = kernel.excess();
+			if let Some(pos) = batch.get_kernel_pos_height(&excess)? {
+				if pos.pos > prev_header.kernel_mmr_size {
+					batch.delete_kernel_pos_height(&excess)?;
+				}
+			}
+		}
+
 		// Update output_pos based on "unspending" all spent pos from this block.
 		// This is necessary to ensure the output_pos index correctly reflects a
 		// reused output commitment. For example an output at pos 1, spent, reused at pos 2.
@@ -1453,6 +1953,20 @@ impl<'a> Extension<'a> {
 		})
 	}
 
+	/// Insertion indexes of the unspent outputs in the current state of the
+	/// output MMR.
+	pub fn unspent_leaf_bitmap(&self) -> Bitmap {
+		self.output_pmmr
+			.leaf_idx_iter(0)
+			.map(|x| x as u32)
+			.collect()
+	}
+
+	/// Bitmap accumulator of the current state of the output MMR.
+	pub fn bitmap_accumulator(&self) -> BitmapAccumulator {
+		self.bitmap_accumulator.clone()
+	}
+
 	/// Validate the MMR (output, rangeproof, kernel) roots against the latest header.
 	pub fn validate_roots(&self, header: &BlockHeader) -> Result<(), Error> {
 		if header.height == 0 {
@@ -1599,119 +2113,228 @@ impl<'a> Extension<'a> {
 	}
 
 	fn verify_kernel_signatures(&self, status: &dyn TxHashsetWriteStatus) -> Result<(), Error> {
-		let now = Instant::now();
-		const KERNEL_BATCH_SIZE: usize = 5_000;
+		verify_kernel_signatures_from(
+			&self.kernel_pmmr.readonly_pmmr(),
+			0,
+			status,
+			&mut |_| Ok(()),
+		)
+	}
 
-		let mut kern_count = 0;
-		let total_kernels = pmmr::n_leaves(self.kernel_pmmr.unpruned_size());
-		let mut tx_kernels: Vec<TxKernel> = Vec::with_capacity(KERNEL_BATCH_SIZE);
-		for n in 1..self.kernel_pmmr.unpruned_size() + 1 {
-			if pmmr::is_leaf(n) {
-				let kernel = self
-					.kernel_pmmr
-					.get_data(n)
-					.ok_or_else(|| ErrorKind::TxKernelNotFound)?;
-				tx_kernels.push(kernel);
-			}
+	fn verify_rangeproofs(&self, status: &dyn TxHashsetWriteStatus) -> Result<(), Error> {
+		verify_rangeproofs_from(
+			&self.output_pmmr.readonly_pmmr(),
+			&self.rproof_pmmr.readonly_pmmr(),
+			0,
+			status,
+			&mut |_| Ok(()),
+		)
+	}
+}
 
-			if tx_kernels.len() >= KERNEL_BATCH_SIZE || n >= self.kernel_pmmr.unpruned_size() {
-				TxKernel::batch_sig_verify(&tx_kernels)?;
-				kern_count += tx_kernels.len() as u64;
-				tx_kernels.clear();
-				status.on_validation_kernels(kern_count, total_kernels);
-				debug!(
-					"txhashset: verify_kernel_signatures: verified {} signatures",
-					kern_count,
-				);
-			}
+/// Verify the kernel signatures after the `from_pos` kernel MMR position. `checkpoint` is called
+/// with the last verified position every time a group of batches is verified.
+fn verify_kernel_signatures_from<B: Backend<TxKernel>>(
+	kernel_pmmr: &ReadonlyPMMR<'_, TxKernel, B>,
+	from_pos: u64,
+	status: &dyn TxHashsetWriteStatus,
+	checkpoint: &mut dyn FnMut(u64) -> Result<(), Error>,
+) -> Result<(), Error> {
+	let now = Instant::now();
+
+	let size = kernel_pmmr.unpruned_size();
+	let mut kern_count = pmmr::n_leaves(from_pos);
+	let total_kernels = pmmr::n_leaves(size);
+	let max_batches = parallel_batches_number();
+	let batch_size = kernel_batch_size();
+	let mut batches: Vec<Vec<TxKernel>> = Vec::with_capacity(max_batches);
+	let mut tx_kernels: Vec<TxKernel> = Vec::with_capacity(batch_size);
+	for n in from_pos + 1..size + 1 {
+		if pmmr::is_leaf(n) {
+			let kernel = kernel_pmmr
+				.get_data(n)
+				.ok_or_else(|| ErrorKind::TxKernelNotFound)?;
+			tx_kernels.push(kernel);
 		}
 
-		debug!(
-			"txhashset: verified {} kernel signatures, pmmr size {}, took {}s",
-			kern_count,
-			self.kernel_pmmr.unpruned_size(),
-			now.elapsed().as_secs(),
-		);
+		if tx_kernels.len() >= batch_size || (n >= size && !tx_kernels.is_empty()) {
+			batches.push(std::mem::replace(
+				&mut tx_kernels,
+				Vec::with_capacity(batch_size),
+			));
+		}
 
-		Ok(())
+		// Collected batches are verified in parallel, each thread with its own secp instance.
+		if batches.len() >= max_batches || n >= size {
+			batches.par_iter().try_for_each(|kernels| {
+				secp_static::with_thread_secp(|secp| {
+					TxKernel::batch_sig_verify_with_secp(secp, kernels)
+				})
+			})?;
+			kern_count += batches.iter().map(|b| b.len() as u64).sum::<u64>();
+			batches.clear();
+			checkpoint(n)?;
+			status.on_validation_kernels(kern_count, total_kernels);
+			debug!(
+				"txhashset: verify_kernel_signatures: verified {} signatures",
+				kern_count,
+			);
+		}
 	}
 
-	fn verify_rangeproofs(&self, status: &dyn TxHashsetWriteStatus) -> Result<(), Error> {
-		let now = Instant::now();
-
-		let mut commits: Vec<Commitment> = Vec::with_capacity(1_000);
-		let mut proofs: Vec<RangeProof> = Vec::with_capacity(1_000);
+	debug!(
+		"txhashset: verified {} kernel signatures, pmmr size {}, took {}s",
+		kern_count,
+		size,
+		now.elapsed().as_secs(),
+	);
 
-		let mut proof_count = 0;
-		let total_rproofs = self.output_pmmr.n_unpruned_leaves();
+	Ok(())
+}
 
-		for pos in self.output_pmmr.leaf_pos_iter() {
-			let output = self.output_pmmr.get_data(pos);
-			let proof = self.rproof_pmmr.get_data(pos);
-
-			// Output and corresponding rangeproof *must* exist.
-			// It is invalid for either to be missing and we fail immediately in this case.
-			match (output, proof) {
-				(None, _) => {
-					return Err(ErrorKind::OutputNotFound(format!(
-						"at verify_rangeproofs for pos {}",
-						pos
-					))
-					.into())
-				}
-				(_, None) => {
-					return Err(ErrorKind::RangeproofNotFound(format!(
-						"at verify_rangeproofs for pos {}",
-						pos
-					))
-					.into())
-				}
-				(Some(output), Some(proof)) => {
-					commits.push(output.commit);
-					proofs.push(proof);
-				}
+/// Verify the rangeproofs of the unspent outputs after the `from_pos` output MMR position.
+/// `checkpoint` is called with the last verified position every time a group of batches is verified.
+fn verify_rangeproofs_from<OB: Backend<OutputIdentifier>, RB: Backend<RangeProof>>(
+	output_pmmr: &ReadonlyPMMR<'_, OutputIdentifier, OB>,
+	rproof_pmmr: &ReadonlyPMMR<'_, RangeProof, RB>,
+	from_pos: u64,
+	status: &dyn TxHashsetWriteStatus,
+	checkpoint: &mut dyn FnMut(u64) -> Result<(), Error>,
+) -> Result<(), Error> {
+	let now = Instant::now();
+
+	let max_batches = parallel_batches_number();
+	let batch_size = rproof_batch_size();
+	let mut batches: Vec<(Vec<Commitment>, Vec<RangeProof>)> = Vec::with_capacity(max_batches);
+	let mut commits: Vec<Commitment> = Vec::with_capacity(batch_size);
+	let mut proofs: Vec<RangeProof> = Vec::with_capacity(batch_size);
+	// Last position of the collected batches
+	let mut batches_pos = from_pos;
+
+	let mut proof_count = output_pmmr
+		.leaf_pos_iter()
+		.take_while(|pos| *pos <= from_pos)
+		.count() as u64;
+	let total_rproofs = output_pmmr.leaf_pos_iter().count() as u64;
+
+	for pos in output_pmmr
+		.leaf_pos_iter()
+		.skip_while(|pos| *pos <= from_pos)
+	{
+		let output = output_pmmr.get_data(pos);
+		let proof = rproof_pmmr.get_data(pos);
+
+		// Output and corresponding rangeproof *must* exist.
+		// It is invalid for either to be missing and we fail immediately in this case.
+		match (output, proof) {
+			(None, _) => {
+				return Err(ErrorKind::OutputNotFound(format!(
+					"at verify_rangeproofs for pos {}",
+					pos
+				))
+				.into())
 			}
-
-			proof_count += 1;
-
-			if proofs.len() >= 1_000 {
-				Output::batch_verify_proofs(&commits, &proofs)?;
-				commits.clear();
-				proofs.clear();
-				info!(
-					"txhashset: verify_rangeproofs: verified {} rangeproofs",
-					proof_count,
-				);
-				if proof_count % 1_000 == 0 {
-					status.on_validation_rproofs(proof_count, total_rproofs);
-				}
+			(_, None) => {
+				return Err(ErrorKind::RangeproofNotFound(format!(
+					"at verify_rangeproofs for pos {}",
+					pos
+				))
+				.into())
+			}
+			(Some(output), Some(proof)) => {
+				commits.push(output.commit);
+				proofs.push(proof);
 			}
 		}
 
-		// remaining part which not full of 1000 range proofs
-		if !proofs.is_empty() {
-			Output::batch_verify_proofs(&commits, &proofs)?;
-			commits.clear();
-			proofs.clear();
+		if proofs.len() >= batch_size {
+			batches.push((
+				std::mem::replace(&mut commits, Vec::with_capacity(batch_size)),
+				std::mem::replace(&mut proofs, Vec::with_capacity(batch_size)),
+			));
+			batches_pos = pos;
+		}
+
+		if batches.len() >= max_batches {
+			proof_count += verify_rangeproof_batches(&batches)?;
+			batches.clear();
+			checkpoint(batches_pos)?;
 			info!(
 				"txhashset: verify_rangeproofs: verified {} rangeproofs",
 				proof_count,
 			);
+			status.on_validation_rproofs(proof_count, total_rproofs);
 		}
+	}
 
-		debug!(
-			"txhashset: verified {} rangeproofs, pmmr size {}, took {}s",
+	// remaining part which not full of the batches
+	if !proofs.is_empty() {
+		batches.push((commits, proofs));
+	}
+	if !batches.is_empty() {
+		proof_count += verify_rangeproof_batches(&batches)?;
+		checkpoint(output_pmmr.unpruned_size())?;
+		info!(
+			"txhashset: verify_rangeproofs: verified {} rangeproofs",
 			proof_count,
-			self.rproof_pmmr.unpruned_size(),
-			now.elapsed().as_secs(),
 		);
-		Ok(())
+		status.on_validation_rproofs(proof_count, total_rproofs);
 	}
+
+	debug!(
+		"txhashset: verified {} rangeproofs, pmmr size {}, took {}s",
+		proof_count,
+		rproof_pmmr.unpruned_size(),
+		now.elapsed().as_secs(),
+	);
+	Ok(())
+}
+
+/// Number of the verification batches that are collected before they are verified in parallel
+fn parallel_batches_number() -> usize {
+	// Low memory mode keeps a single window of batches, one per thread.
+	if global::is_low_memory_mode() {
+		rayon::current_num_threads()
+	} else {
+		rayon::current_num_threads() * 2
+	}
+}
+
+fn kernel_batch_size() -> usize {
+	if global::is_low_memory_mode() {
+		KERNEL_BATCH_SIZE_LOW_MEMORY
+	} else {
+		KERNEL_BATCH_SIZE
+	}
+}
+
+fn rproof_batch_size() -> usize {
+	if global::is_low_memory_mode() {
+		RPROOF_BATCH_SIZE_LOW_MEMORY
+	} else {
+		RPROOF_BATCH_SIZE
+	}
+}
+
+/// Verify rangeproof batches in parallel, each thread with its own secp instance.
+/// Returns the number of verified rangeproofs.
+fn verify_rangeproof_batches(batches: &[(Vec<Commitment>, Vec<RangeProof>)]) -> Result<u64, Error> {
+	batches.par_iter().try_for_each(|(commits, proofs)| {
+		secp_static::with_thread_secp(|secp| {
+			Output::batch_verify_proofs_with_secp(secp, commits, proofs)
+		})
+	})?;
+	Ok(batches.iter().map(|(_, proofs)| proofs.len() as u64).sum())
 }
 
 /// Packages the txhashset data files into a zip and returns a Read to the
-/// resulting file
-pub fn zip_read(root_dir: String, header: &BlockHeader) -> Result<File, Error> {
+/// resulting file. The files are decrypted with the provided key if they are encrypted,
+/// the zip is sent to the peers.
+pub fn zip_read(
+	root_dir: String,
+	header: &BlockHeader,
+	encryption: Option<&EncryptionKey>,
+) -> Result<File, Error> {
 	let txhashset_zip = format!("{}_{}.zip", TXHASHSET_ZIP, header.hash().to_string());
 
 	let txhashset_path = Path::new(&root_dir).join(TXHASHSET_SUBDIR);
@@ -1760,6 +2383,9 @@ pub fn zip_read(root_dir: String, header: &BlockHeader) -> Result<File, Error> {
 
 		// Explicit list of files to add to our zip archive.
 		let files = file_list(header);
+		if let Some(key) = encryption {
+			apply_file_ciphers(key, &temp_txhashset_path, &files)?;
+		}
 
 		zip::create_zip(&zip_file, &temp_txhashset_path, files)?;
 
@@ -1812,11 +2438,12 @@ fn file_list(header: &BlockHeader) -> Vec<PathBuf> {
 }
 
 /// Extract the txhashset data from a zip file and writes the content into the
-/// txhashset storage dir
+/// txhashset storage dir, encrypting the files with the provided key if any.
 pub fn zip_write(
 	root_dir: PathBuf,
 	txhashset_data: File,
 	header: &BlockHeader,
+	encryption: Option<&EncryptionKey>,
 ) -> Result<(), Error> {
 	debug!("zip_write on path: {:?}", root_dir);
 	let txhashset_path = root_dir.join(TXHASHSET_SUBDIR);
@@ -1829,10 +2456,47 @@ pub fn zip_write(
 	// No attempt is made to be permissive or forgiving with "alternative" paths.
 	// These are the *only* files we will attempt to extract from the zip file.
 	// If any of these are missing we will attempt to continue as some are potentially optional.
-	zip::extract_files(txhashset_data, &txhashset_path, files)?;
+	zip::extract_files(txhashset_data, &txhashset_path, files.clone())?;
+	if let Some(key) = encryption {
+		apply_file_ciphers(key, &txhashset_path, &files)?;
+	}
+	Ok(())
+}
+
+// Encrypt (or decrypt) in place the files of the list encrypted at rest.
+fn apply_file_ciphers(key: &EncryptionKey, dir: &Path, files: &[PathBuf]) -> Result<(), Error> {
+	for file in files {
+		let encrypted = file
+			.file_name()
+			.and_then(|name| name.to_str())
+			.map(|name| PMMR_ENCRYPTED_FILES.contains(&name))
+			.unwrap_or(false);
+		let path = dir.join(file);
+		if encrypted && path.exists() {
+			key.file_cipher(&path)?.apply_to_file(&path)?;
+		}
+	}
 	Ok(())
 }
 
+/// Hash (blake2b) of a txhashset zip file, so a downloaded archive can be checked
+/// before it is unpacked. The file is read from its start and left at its start.
+pub fn zip_hash(zip: &File) -> Result<Hash, Error> {
+	let mut zip = zip.try_clone()?;
+	zip.seek(SeekFrom::Start(0))?;
+	let mut hasher = HashWriter::default();
+	let mut buf = vec![0u8; 1024 * 1024];
+	loop {
+		let n = zip.read(&mut buf)?;
+		if n == 0 {
+			break;
+		}
+		hasher.write_fixed_bytes(&buf[..n])?;
+	}
+	zip.seek(SeekFrom::Start(0))?;
+	Ok(hasher.into_hash())
+}
+
 /// Overwrite txhashset folders in "to" folder with "from" folder
 pub fn txhashset_replace(from: PathBuf, to: PathBuf) -> Result<(), Error> {
 	debug!("txhashset_replace: move from {:?} to {:?}", from, to);
@@ -16,12 +16,16 @@
 
 use chrono::prelude::{DateTime, Utc};
 
+use crate::chain::ORPHANS_MAX_SIZE_BYTES;
 use crate::core::core::hash::{Hash, Hashed, ZERO_HASH};
-use crate::core::core::{Block, BlockHeader, HeaderVersion};
+use crate::core::core::{Block, BlockHeader, HeaderVersion, OutputFeatures, OutputIdentifier};
 use crate::core::pow::Difficulty;
 use crate::core::ser::{self, PMMRIndexHashable, Readable, Reader, Writeable, Writer};
 use crate::error::{Error, ErrorKind};
-use crate::util::{RwLock, RwLockWriteGuard};
+use crate::util::secp::pedersen::{Commitment, RangeProof};
+use crate::util::{self, RwLock, RwLockWriteGuard, ToHex};
+use schemars::JsonSchema;
+use std::time::Duration;
 
 bitflags! {
 /// Options for block validation
@@ -269,7 +273,7 @@ impl TxHashSetRoots {
 }
 
 /// A helper for the various output roots.
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub struct OutputRoots {
 	/// The output PMMR root
 	pub pmmr_root: Hash,
@@ -354,6 +358,338 @@ impl Writeable for HashHeight {
 	}
 }
 
+/// Compact the chain in background by default
+const BACKGROUND_COMPACTION: bool = true;
+
+/// Target interval between the background compactions, seconds
+const COMPACTION_INTERVAL_SECS: u64 = 24 * 3600;
+
+/// Max duration of a single background compaction chunk, milliseconds
+const COMPACTION_MAX_CHUNK_MS: u64 = 100;
+
+/// Number of the last rejected blocks kept by default
+const MAX_REJECTED_BLOCKS: usize = 20;
+
+/// Chain configuration
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ChainConfig {
+	/// Compact the chain in background, in chunks between the block arrivals,
+	/// instead of compacting it at once on a random block.
+	#[serde(default = "default_background_compaction")]
+	pub background_compaction: bool,
+	/// Target interval between the background compactions, seconds
+	#[serde(default = "default_compaction_interval_secs")]
+	pub compaction_interval_secs: u64,
+	/// Max duration of a single background compaction chunk, milliseconds
+	#[serde(default = "default_compaction_max_chunk_ms")]
+	pub compaction_max_chunk_ms: u64,
+	/// Memory cap of the orphan blocks pool, bytes
+	#[serde(default = "default_orphans_max_size_bytes")]
+	pub orphans_max_size_bytes: u64,
+	/// Dump the blocks rejected by the pipeline, with the failing stage and their context,
+	/// to the `forensics` directory under the db root
+	#[serde(default)]
+	pub dump_rejected_blocks: bool,
+	/// Number of the last rejected blocks kept, in memory and on disk
+	#[serde(default = "default_max_rejected_blocks")]
+	pub max_rejected_blocks: usize,
+}
+
+impl Default for ChainConfig {
+	fn default() -> ChainConfig {
+		ChainConfig {
+			background_compaction: default_background_compaction(),
+			compaction_interval_secs: default_compaction_interval_secs(),
+			compaction_max_chunk_ms: default_compaction_max_chunk_ms(),
+			orphans_max_size_bytes: default_orphans_max_size_bytes(),
+			dump_rejected_blocks: false,
+			max_rejected_blocks: default_max_rejected_blocks(),
+		}
+	}
+}
+
+fn default_background_compaction() -> bool {
+	BACKGROUND_COMPACTION
+}
+
+fn default_compaction_interval_secs() -> u64 {
+	COMPACTION_INTERVAL_SECS
+}
+
+fn default_compaction_max_chunk_ms() -> u64 {
+	COMPACTION_MAX_CHUNK_MS
+}
+
+fn default_orphans_max_size_bytes() -> u64 {
+	ORPHANS_MAX_SIZE_BYTES as u64
+}
+
+/// Number of the last rejected blocks kept by default.
+pub fn default_max_rejected_blocks() -> usize {
+	MAX_REJECTED_BLOCKS
+}
+
+/// Txhashset roots and MMR sizes the node got applying a rejected block, the ones
+/// the block header is expected to commit to.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct ExpectedRoots {
+	/// Output MMR root
+	pub output_root: String,
+	/// Bitmap accumulator root
+	pub bitmap_root: String,
+	/// Rangeproof MMR root
+	pub rproof_root: String,
+	/// Kernel MMR root
+	pub kernel_root: String,
+	/// Output MMR size
+	pub output_mmr_size: u64,
+	/// Kernel MMR size
+	pub kernel_mmr_size: u64,
+}
+
+impl ExpectedRoots {
+	/// Roots and sizes of the txhashset extension the block was applied to.
+	pub fn new(roots: &TxHashSetRoots, sizes: (u64, u64, u64)) -> ExpectedRoots {
+		ExpectedRoots {
+			output_root: roots.output_roots.pmmr_root.to_hex(),
+			bitmap_root: roots.output_roots.bitmap_root.to_hex(),
+			rproof_root: roots.rproof_root.to_hex(),
+			kernel_root: roots.kernel_root.to_hex(),
+			output_mmr_size: sizes.0,
+			kernel_mmr_size: sizes.2,
+		}
+	}
+}
+
+/// Freeze state of the chain tip, see `Chain::freeze_tip`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct TipFreezeStatus {
+	/// Whether the new blocks are buffered instead of processed
+	pub frozen: bool,
+	/// Height of the chain head
+	pub height: u64,
+	/// Hash of the chain head
+	pub hash: String,
+	/// Time the freeze ends automatically, unix timestamp seconds
+	pub frozen_until: Option<i64>,
+	/// Number of the blocks buffered during the freeze
+	pub buffered_blocks: usize,
+}
+
+/// A block rejected by the block processing pipeline.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct BlockRejection {
+	/// Block hash
+	pub hash: String,
+	/// Block height
+	pub height: u64,
+	/// Hash of the previous block
+	pub prev_hash: String,
+	/// Stage of the pipeline the block failed at
+	pub stage: String,
+	/// Validation error
+	pub error: String,
+	/// Time of the rejection, unix timestamp seconds
+	pub rejected_at: i64,
+	/// Roots the header is expected to commit to, if the block was applied to the txhashset
+	pub expected_roots: Option<ExpectedRoots>,
+	/// Directory the block and its context are dumped to, if dumps are enabled
+	pub dump_dir: Option<String>,
+}
+
+/// Progress of the full txhashset validation (rangeproofs and kernel signatures) for the
+/// txhashset at the provided block header. Positions up to the checkpoint are verified already,
+/// so validation can resume after restart.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ValidationCheckpoint {
+	/// Header hash the txhashset is validated against
+	pub hash: Hash,
+	/// Last verified rangeproof MMR position
+	pub rproof_pos: u64,
+	/// Last verified kernel MMR position
+	pub kernel_pos: u64,
+}
+
+impl Readable for ValidationCheckpoint {
+	fn read<R: Reader>(reader: &mut R) -> Result<ValidationCheckpoint, ser::Error> {
+		let hash = Hash::read(reader)?;
+		let rproof_pos = reader.read_u64()?;
+		let kernel_pos = reader.read_u64()?;
+		Ok(ValidationCheckpoint {
+			hash,
+			rproof_pos,
+			kernel_pos,
+		})
+	}
+}
+
+impl Writeable for ValidationCheckpoint {
+	fn write<W: Writer>(&self, writer: &mut W) -> Result<(), ser::Error> {
+		self.hash.write(writer)?;
+		writer.write_u64(self.rproof_pos)?;
+		writer.write_u64(self.kernel_pos)?;
+		Ok(())
+	}
+}
+
+/// Block mined by this node, recorded when the block is processed with `Options::MINE`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MinedBlock {
+	/// Block height
+	pub height: u64,
+	/// Block hash
+	pub hash: Hash,
+	/// Commitments of the coinbase outputs
+	pub coinbase_outputs: Vec<Commitment>,
+	/// Excess of the coinbase kernels
+	pub coinbase_kernels: Vec<Commitment>,
+	/// When the block was mined, unix timestamp in milliseconds
+	pub mined_at: i64,
+}
+
+impl MinedBlock {
+	/// Mined block with the coinbase of the block.
+	pub fn from_block(b: &Block, mined_at: i64) -> MinedBlock {
+		MinedBlock {
+			height: b.header.height,
+			hash: b.hash(),
+			coinbase_outputs: b
+				.outputs()
+				.iter()
+				.filter(|o| o.is_coinbase())
+				.map(|o| o.commitment())
+				.collect(),
+			coinbase_kernels: b
+				.kernels()
+				.iter()
+				.filter(|k| k.is_coinbase())
+				.map(|k| k.excess())
+				.collect(),
+			mined_at,
+		}
+	}
+}
+
+impl Readable for MinedBlock {
+	fn read<R: Reader>(reader: &mut R) -> Result<MinedBlock, ser::Error> {
+		let height = reader.read_u64()?;
+		let hash = Hash::read(reader)?;
+		let outputs_len = reader.read_u64()?;
+		let mut coinbase_outputs = Vec::with_capacity(outputs_len.min(16) as usize);
+		for _ in 0..outputs_len {
+			coinbase_outputs.push(Commitment::read(reader)?);
+		}
+		let kernels_len = reader.read_u64()?;
+		let mut coinbase_kernels = Vec::with_capacity(kernels_len.min(16) as usize);
+		for _ in 0..kernels_len {
+			coinbase_kernels.push(Commitment::read(reader)?);
+		}
+		let mined_at = reader.read_i64()?;
+		Ok(MinedBlock {
+			height,
+			hash,
+			coinbase_outputs,
+			coinbase_kernels,
+			mined_at,
+		})
+	}
+}
+
+impl Writeable for MinedBlock {
+	fn write<W: Writer>(&self, writer: &mut W) -> Result<(), ser::Error> {
+		writer.write_u64(self.height)?;
+		self.hash.write(writer)?;
+		writer.write_u64(self.coinbase_outputs.len() as u64)?;
+		for c in &self.coinbase_outputs {
+			c.write(writer)?;
+		}
+		writer.write_u64(self.coinbase_kernels.len() as u64)?;
+		for c in &self.coinbase_kernels {
+			c.write(writer)?;
+		}
+		writer.write_i64(self.mined_at)?;
+		Ok(())
+	}
+}
+
+/// Position of the cursor based traversal of the unspent outputs: the output MMR position
+/// of the last returned output and the block that created it. The positions don't change
+/// with compaction, the block tells whether the scanned part of the chain was reorged.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct UtxoCursor {
+	/// Output MMR position of the last returned output
+	pub pos: u64,
+	/// Height of the block that created the output
+	pub height: u64,
+	/// Hash of the block that created the output
+	pub hash: Hash,
+}
+
+impl UtxoCursor {
+	/// Opaque hex representation of the cursor for the API clients.
+	pub fn to_hex(&self) -> String {
+		ser::ser_vec(self, ser::ProtocolVersion::local())
+			.map(|bytes| bytes.to_hex())
+			.unwrap_or_default()
+	}
+
+	/// Parse a cursor from its hex representation.
+	pub fn from_hex(hex: &str) -> Result<UtxoCursor, Error> {
+		let bytes = util::from_hex(hex)
+			.map_err(|e| ErrorKind::Other(format!("invalid utxo cursor, {}", e)))?;
+		ser::deserialize(&mut &bytes[..], ser::ProtocolVersion::local())
+			.map_err(|e| ErrorKind::Other(format!("invalid utxo cursor, {}", e)).into())
+	}
+}
+
+impl Readable for UtxoCursor {
+	fn read<R: Reader>(reader: &mut R) -> Result<UtxoCursor, ser::Error> {
+		let pos = reader.read_u64()?;
+		let height = reader.read_u64()?;
+		let hash = Hash::read(reader)?;
+		Ok(UtxoCursor { pos, height, hash })
+	}
+}
+
+impl Writeable for UtxoCursor {
+	fn write<W: Writer>(&self, writer: &mut W) -> Result<(), ser::Error> {
+		writer.write_u64(self.pos)?;
+		writer.write_u64(self.height)?;
+		self.hash.write(writer)?;
+		Ok(())
+	}
+}
+
+/// An unspent output returned by the cursor based traversal of the unspent outputs.
+#[derive(Debug, Clone, PartialEq)]
+pub struct UnspentOutput {
+	/// Output commitment
+	pub commit: Commitment,
+	/// Output features
+	pub features: OutputFeatures,
+	/// Height of the block that created the output
+	pub height: u64,
+	/// Output MMR position
+	pub pos: u64,
+	/// Output rangeproof, if requested
+	pub proof: Option<RangeProof>,
+}
+
+/// An output returned by the block range query, spent or not.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RangeOutput {
+	/// The output
+	pub output: OutputIdentifier,
+	/// Its rangeproof, if requested
+	pub proof: Option<RangeProof>,
+	/// Height of the block that created the output
+	pub height: u64,
+	/// Output MMR position
+	pub pos: u64,
+	/// Whether the output is spent
+	pub spent: bool,
+}
+
 /// The tip of a fork. A handle to the fork ancestry from its leaf in the
 /// blockchain tree. References the max height and the latest and previous
 /// blocks
@@ -425,6 +761,176 @@ impl ser::Readable for Tip {
 	}
 }
 
+/// Chain statistics as of a given block on the current chain.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ChainStats {
+	/// Height of the block
+	pub height: u64,
+	/// Hash of the block
+	pub hash: Hash,
+	/// Number of unspent outputs right after this block
+	pub utxo_count: u64,
+	/// Number of kernels up to and including this block
+	pub kernel_count: u64,
+	/// Total coin supply (in nanocoins) up to and including this block
+	pub total_supply: u64,
+	/// Size of the output MMR (the rangeproof MMR has the same size)
+	pub output_mmr_size: u64,
+	/// Size of the kernel MMR
+	pub kernel_mmr_size: u64,
+}
+
+/// Upper bounds (inclusive, milliseconds) of the block processing time histogram buckets.
+/// Longer durations are counted in the last, unbounded bucket.
+pub const BLOCK_TIMING_BUCKETS_MS: [u64; 12] =
+	[1, 5, 10, 25, 50, 100, 250, 500, 1000, 2500, 5000, 10000];
+
+/// Time spent in each stage of the block acceptance pipeline for a single block.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct BlockTimings {
+	/// Proof of work check
+	pub pow: Duration,
+	/// Header validation and the header MMR update
+	pub header: Duration,
+	/// Block body validation, the rangeproof batch and the kernel signatures
+	pub rangeproofs: Duration,
+	/// Rewind to the fork point and apply the block to the txhashset
+	pub txhashset: Duration,
+	/// Kernel sums verification
+	pub kernel_sums: Duration,
+	/// Saving the block and committing the db batch
+	pub commit: Duration,
+}
+
+impl BlockTimings {
+	/// Total time spent in the pipeline.
+	pub fn total(&self) -> Duration {
+		self.pow + self.header + self.rangeproofs + self.txhashset + self.kernel_sums + self.commit
+	}
+}
+
+/// Histogram of the durations of a block processing stage.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TimingHistogram {
+	/// Number of samples per bucket, see `BLOCK_TIMING_BUCKETS_MS`. The last bucket counts
+	/// the samples over the largest bound.
+	pub buckets: Vec<u64>,
+	/// Number of samples
+	pub count: u64,
+	/// Sum of the samples, milliseconds
+	pub total_ms: u64,
+	/// Largest sample, milliseconds
+	pub max_ms: u64,
+}
+
+impl Default for TimingHistogram {
+	fn default() -> Self {
+		TimingHistogram {
+			buckets: vec![0; BLOCK_TIMING_BUCKETS_MS.len() + 1],
+			count: 0,
+			total_ms: 0,
+			max_ms: 0,
+		}
+	}
+}
+
+impl TimingHistogram {
+	/// Add a sample to the histogram.
+	pub fn record(&mut self, duration: Duration) {
+		let ms = duration.as_millis() as u64;
+		let bucket = BLOCK_TIMING_BUCKETS_MS
+			.iter()
+			.position(|bound| ms <= *bound)
+			.unwrap_or(BLOCK_TIMING_BUCKETS_MS.len());
+		self.buckets[bucket] += 1;
+		self.count += 1;
+		self.total_ms += ms;
+		self.max_ms = self.max_ms.max(ms);
+	}
+
+	/// Average sample, milliseconds.
+	pub fn average_ms(&self) -> u64 {
+		if self.count == 0 {
+			0
+		} else {
+			self.total_ms / self.count
+		}
+	}
+}
+
+/// Aggregate timings of the blocks accepted by the pipeline, per stage.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct BlockTimingStats {
+	/// Proof of work check
+	pub pow: TimingHistogram,
+	/// Header validation and the header MMR update
+	pub header: TimingHistogram,
+	/// Block body validation, the rangeproof batch and the kernel signatures
+	pub rangeproofs: TimingHistogram,
+	/// Rewind to the fork point and apply the block to the txhashset
+	pub txhashset: TimingHistogram,
+	/// Kernel sums verification
+	pub kernel_sums: TimingHistogram,
+	/// Saving the block and committing the db batch
+	pub commit: TimingHistogram,
+	/// The whole pipeline
+	pub total: TimingHistogram,
+}
+
+impl BlockTimingStats {
+	/// Add the stage timings of an accepted block.
+	pub fn record(&mut self, timings: &BlockTimings) {
+		self.pow.record(timings.pow);
+		self.header.record(timings.header);
+		self.rangeproofs.record(timings.rangeproofs);
+		self.txhashset.record(timings.txhashset);
+		self.kernel_sums.record(timings.kernel_sums);
+		self.commit.record(timings.commit);
+		self.total.record(timings.total());
+	}
+}
+
+/// Chain head moving to a different fork.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ReorgEvent {
+	/// Chain head before the reorg.
+	pub old_tip: Tip,
+	/// Chain head after the reorg.
+	pub new_tip: Tip,
+	/// Blocks removed from the chain, from the old head down to the fork point (excluded).
+	pub disconnected: Vec<Hash>,
+	/// Blocks added to the chain, from the fork point (excluded) up to the new head.
+	pub connected: Vec<Hash>,
+}
+
+/// Reorg of the chain head, as kept in the chain reorg history.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct ReorgRecord {
+	/// Number of blocks removed from the chain
+	pub depth: u64,
+	/// Hash of the chain head before the reorg
+	pub old_tip_hash: String,
+	/// Height of the chain head before the reorg
+	pub old_tip_height: u64,
+	/// Hash of the chain head after the reorg
+	pub new_tip_hash: String,
+	/// Height of the chain head after the reorg
+	pub new_tip_height: u64,
+	/// Hash of the last block common to both forks
+	pub fork_point_hash: String,
+	/// Height of the last block common to both forks
+	pub fork_point_height: u64,
+	/// Time of the reorg, unix timestamp seconds
+	pub timestamp: i64,
+}
+
+/// Listener for chain reorgs, registered with the chain to get notified
+/// every time the head moves to a different fork.
+pub trait ReorgListener {
+	/// The chain head just moved to a different fork.
+	fn on_reorg(&self, event: &ReorgEvent);
+}
+
 /// Bridge between the chain pipeline and the rest of the system. Handles
 /// downstream processing of valid blocks by the rest of the system, most
 /// importantly the broadcasting of blocks to our peers.
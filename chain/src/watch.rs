@@ -0,0 +1,350 @@
+// Copyright 2021 The MWC Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Watch-only filters of the output commitments. A filter is either a list of
+//! commitments or a bloom filter, it is persisted in the chain db together with the
+//! events matched by the blocks processed since the filter was registered: the output
+//! was created, spent, or the block that created or spent it was reorged out.
+//! Events of a filter are numbered, the number is the cursor to query them.
+
+use crate::core::core::hash::{Hash, HashWriter};
+use crate::core::core::{Block, CommitWrapper};
+use crate::core::libtx::secp_ser;
+use crate::core::ser::{self, Readable, Reader, Writeable, Writer};
+use crate::error::{Error, ErrorKind};
+use crate::util::secp::pedersen::Commitment;
+use schemars::JsonSchema;
+use serde::{Deserialize, Deserializer};
+
+/// Max number of the commitments of a filter
+pub const MAX_WATCH_COMMITMENTS: usize = 100_000;
+
+/// Max size of the bloom filter bit array in bytes
+pub const MAX_BLOOM_FILTER_BYTES: usize = 1 << 20;
+
+/// Max number of the hash functions of a bloom filter
+pub const MAX_BLOOM_FILTER_HASHES: u32 = 32;
+
+/// Bloom filter of the commitments. The bit positions of a commitment are
+/// `(h1 + i * h2) mod m` for i in 0..hashes, where h1 and h2 are the first and second
+/// 8 bytes (little endian) of the blake2b-256 hash of the 33 bytes of the commitment and
+/// m is the number of bits. Bit n is `bits[n / 8] & (1 << (n % 8))`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BloomFilter {
+	/// Bit array
+	pub bits: Vec<u8>,
+	/// Number of the hash functions
+	pub hashes: u32,
+}
+
+impl BloomFilter {
+	/// Whether the commitment may be in the filter.
+	pub fn contains(&self, commit: &Commitment) -> bool {
+		let m = self.bits.len() as u64 * 8;
+		if m == 0 {
+			return false;
+		}
+		let mut hasher = HashWriter::default();
+		if hasher.write_fixed_bytes(commit).is_err() {
+			return false;
+		}
+		let hash = hasher.into_hash();
+		let mut h = [0u8; 8];
+		h.copy_from_slice(&hash.as_ref()[0..8]);
+		let h1 = u64::from_le_bytes(h);
+		h.copy_from_slice(&hash.as_ref()[8..16]);
+		let h2 = u64::from_le_bytes(h);
+		(0..self.hashes as u64).all(|i| {
+			let n = h1.wrapping_add(i.wrapping_mul(h2)) % m;
+			self.bits[(n / 8) as usize] & (1 << (n % 8)) != 0
+		})
+	}
+}
+
+/// What a filter matches
+#[derive(Debug, Clone, PartialEq)]
+pub enum WatchMatcher {
+	/// Sorted list of the commitments
+	Commitments(Vec<Commitment>),
+	/// Bloom filter, may match commitments that are not watched
+	Bloom(BloomFilter),
+}
+
+impl WatchMatcher {
+	/// Build a matcher, validating its size.
+	pub fn new(
+		commitments: Option<Vec<Commitment>>,
+		bloom: Option<BloomFilter>,
+	) -> Result<WatchMatcher, Error> {
+		match (commitments, bloom) {
+			(Some(mut commitments), None) => {
+				if commitments.is_empty() || commitments.len() > MAX_WATCH_COMMITMENTS {
+					return Err(ErrorKind::Other(format!(
+						"watch filter must have 1 to {} commitments",
+						MAX_WATCH_COMMITMENTS
+					))
+					.into());
+				}
+				commitments.sort();
+				commitments.dedup();
+				Ok(WatchMatcher::Commitments(commitments))
+			}
+			(None, Some(bloom)) => {
+				if bloom.bits.is_empty() || bloom.bits.len() > MAX_BLOOM_FILTER_BYTES {
+					return Err(ErrorKind::Other(format!(
+						"bloom filter must have 1 to {} bytes",
+						MAX_BLOOM_FILTER_BYTES
+					))
+					.into());
+				}
+				if bloom.hashes == 0 || bloom.hashes > MAX_BLOOM_FILTER_HASHES {
+					return Err(ErrorKind::Other(format!(
+						"bloom filter must have 1 to {} hashes",
+						MAX_BLOOM_FILTER_HASHES
+					))
+					.into());
+				}
+				Ok(WatchMatcher::Bloom(bloom))
+			}
+			_ => Err(ErrorKind::Other(
+				"watch filter needs either commitments or a bloom filter".to_string(),
+			)
+			.into()),
+		}
+	}
+
+	/// Whether the commitment is watched.
+	pub fn matches(&self, commit: &Commitment) -> bool {
+		match self {
+			WatchMatcher::Commitments(commitments) => commitments.binary_search(commit).is_ok(),
+			WatchMatcher::Bloom(bloom) => bloom.contains(commit),
+		}
+	}
+}
+
+/// Watch filter registered by the node operator
+#[derive(Debug, Clone, PartialEq)]
+pub struct WatchFilter {
+	/// Filter id, assigned by the node
+	pub id: u64,
+	/// Name given by the operator
+	pub name: String,
+	/// What the filter matches
+	pub matcher: WatchMatcher,
+	/// Chain height when the filter was registered, events are matched from the next block
+	pub created_height: u64,
+	/// Number of the next event
+	pub next_seq: u64,
+}
+
+impl WatchFilter {
+	/// Match the block outputs and inputs. Returns the created and the spent commitments.
+	pub fn match_block(&self, block: &Block) -> (Vec<Commitment>, Vec<Commitment>) {
+		let created = block
+			.outputs()
+			.iter()
+			.map(|o| o.commitment())
+			.filter(|c| self.matcher.matches(c))
+			.collect();
+		let inputs: Vec<CommitWrapper> = block.inputs().into();
+		let spent = inputs
+			.iter()
+			.map(|i| i.commitment())
+			.filter(|c| self.matcher.matches(c))
+			.collect();
+		(created, spent)
+	}
+
+	/// Summary of the filter for the API.
+	pub fn info(&self) -> WatchFilterInfo {
+		let (commitments, bloom_bytes, bloom_hashes) = match &self.matcher {
+			WatchMatcher::Commitments(commitments) => (Some(commitments.len()), None, None),
+			WatchMatcher::Bloom(bloom) => (None, Some(bloom.bits.len()), Some(bloom.hashes)),
+		};
+		WatchFilterInfo {
+			id: self.id,
+			name: self.name.clone(),
+			commitments,
+			bloom_bytes,
+			bloom_hashes,
+			created_height: self.created_height,
+			events: self.next_seq,
+		}
+	}
+}
+
+impl Writeable for WatchFilter {
+	fn write<W: Writer>(&self, writer: &mut W) -> Result<(), ser::Error> {
+		writer.write_u64(self.id)?;
+		writer.write_bytes(self.name.as_bytes())?;
+		writer.write_u64(self.created_height)?;
+		writer.write_u64(self.next_seq)?;
+		match &self.matcher {
+			WatchMatcher::Commitments(commitments) => {
+				writer.write_u8(0)?;
+				writer.write_u64(commitments.len() as u64)?;
+				for commit in commitments {
+					commit.write(writer)?;
+				}
+			}
+			WatchMatcher::Bloom(bloom) => {
+				writer.write_u8(1)?;
+				writer.write_u32(bloom.hashes)?;
+				writer.write_bytes(&bloom.bits)?;
+			}
+		}
+		Ok(())
+	}
+}
+
+impl Readable for WatchFilter {
+	fn read<R: Reader>(reader: &mut R) -> Result<WatchFilter, ser::Error> {
+		let id = reader.read_u64()?;
+		let name = String::from_utf8(reader.read_bytes_len_prefix()?)
+			.map_err(|_| ser::Error::CorruptedData("watch filter name".to_string()))?;
+		let created_height = reader.read_u64()?;
+		let next_seq = reader.read_u64()?;
+		let matcher = match reader.read_u8()? {
+			0 => {
+				let count = reader.read_u64()?;
+				WatchMatcher::Commitments(ser::read_multi(reader, count)?)
+			}
+			1 => {
+				let hashes = reader.read_u32()?;
+				let bits = reader.read_bytes_len_prefix()?;
+				WatchMatcher::Bloom(BloomFilter { bits, hashes })
+			}
+			_ => {
+				return Err(ser::Error::CorruptedData(
+					"watch filter matcher".to_string(),
+				))
+			}
+		};
+		Ok(WatchFilter {
+			id,
+			name,
+			matcher,
+			created_height,
+			next_seq,
+		})
+	}
+}
+
+/// Summary of a watch filter
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct WatchFilterInfo {
+	/// Filter id
+	pub id: u64,
+	/// Name given by the operator
+	pub name: String,
+	/// Number of the watched commitments, for the commitment list filters
+	pub commitments: Option<usize>,
+	/// Size of the bit array, for the bloom filters
+	pub bloom_bytes: Option<usize>,
+	/// Number of the hash functions, for the bloom filters
+	pub bloom_hashes: Option<u32>,
+	/// Chain height when the filter was registered
+	pub created_height: u64,
+	/// Number of the matched events
+	pub events: u64,
+}
+
+/// What happened to a watched output
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub enum WatchEventKind {
+	/// Output was created by a block
+	Created,
+	/// Output was spent by a block
+	Spent,
+	/// Block that created or spent the output was reorged out
+	Reorged,
+}
+
+/// Event matched by a watch filter
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct WatchEvent {
+	/// Event number, the cursor of the event
+	pub seq: u64,
+	/// What happened
+	pub kind: WatchEventKind,
+	/// Output commitment
+	#[serde(
+		serialize_with = "secp_ser::as_hex",
+		deserialize_with = "secp_ser::commitment_from_hex"
+	)]
+	#[schemars(with = "String")]
+	pub commit: Commitment,
+	/// Height of the block
+	pub height: u64,
+	/// Hash of the block
+	#[serde(
+		serialize_with = "secp_ser::as_hex",
+		deserialize_with = "hash_from_hex"
+	)]
+	#[schemars(with = "String")]
+	pub block_hash: Hash,
+}
+
+impl Writeable for WatchEvent {
+	fn write<W: Writer>(&self, writer: &mut W) -> Result<(), ser::Error> {
+		writer.write_u64(self.seq)?;
+		writer.write_u8(match self.kind {
+			WatchEventKind::Created => 0,
+			WatchEventKind::Spent => 1,
+			WatchEventKind::Reorged => 2,
+		})?;
+		self.commit.write(writer)?;
+		writer.write_u64(self.height)?;
+		self.block_hash.write(writer)?;
+		Ok(())
+	}
+}
+
+impl Readable for WatchEvent {
+	fn read<R: Reader>(reader: &mut R) -> Result<WatchEvent, ser::Error> {
+		let seq = reader.read_u64()?;
+		let kind = match reader.read_u8()? {
+			0 => WatchEventKind::Created,
+			1 => WatchEventKind::Spent,
+			2 => WatchEventKind::Reorged,
+			_ => return Err(ser::Error::CorruptedData("watch event kind".to_string())),
+		};
+		Ok(WatchEvent {
+			seq,
+			kind,
+			commit: Commitment::read(reader)?,
+			height: reader.read_u64()?,
+			block_hash: Hash::read(reader)?,
+		})
+	}
+}
+
+/// Page of the events of a watch filter
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct WatchEventPage {
+	/// Events, ordered by their number
+	pub events: Vec<WatchEvent>,
+	/// Cursor to query the next events with
+	pub next_cursor: u64,
+}
+
+fn hash_from_hex<'de, D>(deserializer: D) -> Result<Hash, D::Error>
+where
+	D: Deserializer<'de>,
+{
+	use serde::de::Error;
+	String::deserialize(deserializer).and_then(|s| {
+		Hash::from_hex(&s).map_err(|e| Error::custom(format!("Invalid hash {}, {}", s, e)))
+	})
+}
@@ -12,8 +12,9 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use self::chain::txhashset::BitmapAccumulator;
+use self::chain::txhashset::{BitmapAccumulator, BitmapChunk};
 use self::core::core::hash::Hash;
+use self::core::core::pmmr::{self, Segment, SegmentIdentifier};
 use self::core::ser::PMMRIndexHashable;
 use bit_vec::BitVec;
 use grin_chain as chain;
@@ -186,3 +187,47 @@ fn test_bitmap_accumulator() {
 	};
 	assert_eq!(accumulator.root(), expected_hash);
 }
+
+#[test]
+fn test_bitmap_segment_proof() {
+	util::init_test_logger();
+
+	// 5 chunks, the last one partial
+	let size = 4 * 1024 + 100;
+	let idx: Vec<u64> = (0..size).filter(|x| x % 3 == 0 || x % 7 == 0).collect();
+	let mut accumulator = BitmapAccumulator::new();
+	accumulator.init(idx.clone(), size).unwrap();
+	let n_chunks = 5;
+	let mmr_size = pmmr::insertion_to_pmmr_index(n_chunks + 1) - 1;
+
+	let chunks = |first: u64, last: u64, flip: Option<u64>| {
+		(first..last)
+			.map(|chunk_idx| {
+				let mut chunk = BitmapChunk::new();
+				for x in idx.iter().filter(|x| **x / 1024 == chunk_idx) {
+					chunk.set(x % 1024, true);
+				}
+				if let Some(bit) = flip.filter(|b| b / 1024 == chunk_idx) {
+					chunk.set(bit % 1024, false);
+				}
+				(pmmr::insertion_to_pmmr_index(chunk_idx + 1), chunk)
+			})
+			.collect::<Vec<_>>()
+	};
+
+	for seg_idx in 0..3 {
+		let id = SegmentIdentifier {
+			height: 1,
+			idx: seg_idx,
+		};
+		let proof = accumulator.segment_proof(id).unwrap();
+		let last = std::cmp::min(2 * seg_idx + 2, n_chunks);
+		let segment = Segment::from_leaves(id, chunks(2 * seg_idx, last, None), proof.clone());
+		assert!(segment.validate(mmr_size, accumulator.root()).is_ok());
+
+		// Any flipped bit fails the validation
+		let set_bit = idx.iter().find(|x| **x >= 2048 * seg_idx).cloned();
+		let tampered = Segment::from_leaves(id, chunks(2 * seg_idx, last, set_bit), proof);
+		assert!(tampered.validate(mmr_size, accumulator.root()).is_err());
+	}
+}
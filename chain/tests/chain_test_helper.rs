@@ -12,21 +12,19 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use self::chain::types::NoopAdapter;
-use self::chain::types::Options;
+use self::chain::types::{NoopAdapter, Options};
 use self::chain::Chain;
 use self::core::core::hash::Hashed;
 use self::core::core::verifier_cache::LruVerifierCache;
 use self::core::core::Block;
 use self::core::genesis;
-use self::core::global::ChainTypes;
 use self::core::libtx::{self, reward};
-use self::core::{consensus, global, pow};
-use self::keychain::{ExtKeychainPath, Keychain};
+use self::core::pow;
+use self::keychain::Keychain;
 use self::util::RwLock;
-use chrono::Duration;
 use grin_chain as chain;
 use grin_core as core;
+use grin_fixtures::FixtureSpec;
 use grin_keychain as keychain;
 use grin_util as util;
 use std::fs;
@@ -70,53 +68,20 @@ where
 
 /// Mine a chain of specified length to assist with automated tests.
 /// Probably a good idea to call clean_output_dir at the beginning and end of each test.
+/// The blocks come from the linear fixture, they are processed and checked one by one.
 #[allow(dead_code)]
 pub fn mine_chain(dir_name: &str, chain_length: u64) -> Chain {
-	global::set_local_chain_type(ChainTypes::AutomatedTesting);
-	let keychain = keychain::ExtKeychain::from_random_seed(false).unwrap();
-	let genesis = genesis_block(&keychain);
-	let mut chain = init_chain(dir_name, genesis.clone());
-	mine_some_on_top(&mut chain, chain_length, &keychain);
-	chain
-}
-
-#[allow(dead_code)]
-fn mine_some_on_top<K>(chain: &mut Chain, chain_length: u64, keychain: &K)
-where
-	K: Keychain,
-{
-	for n in 1..chain_length {
-		let prev = chain.head_header().unwrap();
-		let next_header_info = consensus::next_difficulty(1, chain.difficulty_iter().unwrap());
-		let pk = ExtKeychainPath::new(1, n as u32, 0, 0, 0).to_identifier();
-		let reward = libtx::reward::output(
-			keychain,
-			&libtx::ProofBuilder::new(keychain),
-			&pk,
-			0,
-			false,
-			n,
-		)
-		.unwrap();
-		let mut b = core::core::Block::new(&prev, &[], next_header_info.clone().difficulty, reward)
-			.unwrap();
-		b.header.timestamp = prev.timestamp + Duration::seconds(60);
-		b.header.pow.secondary_scaling = next_header_info.secondary_scaling;
-
-		chain.set_txhashset_roots(&mut b).unwrap();
-
-		let edge_bits = global::min_edge_bits();
-		b.header.pow.proof.edge_bits = edge_bits;
-		pow::pow_size(
-			&mut b.header,
-			next_header_info.difficulty,
-			global::proofsize(),
-			edge_bits,
-		)
-		.unwrap();
+	let gen_dir = format!("{}.gen", dir_name);
+	clean_output_dir(&gen_dir);
+	let spec = FixtureSpec::linear(chain_length.saturating_sub(1));
+	let (gen_chain, fixture) = grin_fixtures::generate(&spec, &gen_dir).unwrap();
+	drop(gen_chain);
+	clean_output_dir(&gen_dir);
 
+	let chain = init_chain(dir_name, fixture.genesis.clone());
+	for (b, n) in fixture.blocks.iter().zip(1..) {
 		let bhash = b.hash();
-		chain.process_block(b, Options::MINE).unwrap();
+		chain.process_block(b.clone(), Options::MINE).unwrap();
 
 		// checking our new head
 		let head = chain.head().unwrap();
@@ -140,4 +105,6 @@ where
 
 		chain.validate(false).unwrap();
 	}
+	assert_eq!(chain.head().unwrap().last_block_h, fixture.head);
+	chain
 }
@@ -22,10 +22,10 @@ use self::core::libtx::{self, build, ProofBuilder};
 use self::core::pow::Difficulty;
 use self::core::{consensus, global, pow};
 use self::keychain::{ExtKeychain, ExtKeychainPath, Keychain};
-use self::util::RwLock;
+use self::util::{RwLock, ToHex};
 use chrono::Duration;
 use grin_chain as chain;
-use grin_chain::{BlockStatus, ChainAdapter, Options};
+use grin_chain::{BlockStatus, ChainAdapter, Options, ReorgEvent, ReorgListener};
 use grin_core as core;
 use grin_keychain as keychain;
 use grin_util as util;
@@ -52,6 +52,18 @@ impl ChainAdapter for StatusAdapter {
 	}
 }
 
+/// Reorg listener to retrieve last reorg event
+#[derive(Default)]
+pub struct ReorgRecorder {
+	pub last_event: RwLock<Option<ReorgEvent>>,
+}
+
+impl ReorgListener for ReorgRecorder {
+	fn on_reorg(&self, event: &ReorgEvent) {
+		*self.last_event.write() = Some(event.clone());
+	}
+}
+
 /// Creates a `Chain` instance with `StatusAdapter` attached to it.
 fn setup_with_status_adapter(dir_name: &str, genesis: Block, adapter: Arc<StatusAdapter>) -> Chain {
 	util::init_test_logger();
@@ -356,14 +368,20 @@ fn mine_reorg() {
 		let last_status = RwLock::new(None);
 		let adapter = Arc::new(StatusAdapter::new(last_status));
 		let chain = setup_with_status_adapter(DIR_NAME, genesis.clone(), adapter.clone());
+		let recorder = Arc::new(ReorgRecorder::default());
+		chain.register_reorg_listener(recorder.clone());
 
 		// Add blocks to main chain with gradually increasing difficulty
 		let mut prev = chain.head_header().unwrap();
+		let mut main_hashes = vec![];
 		for n in 1..=NUM_BLOCKS_MAIN {
 			let b = prepare_block(&kc, &prev, &chain, n);
 			prev = b.header.clone();
+			main_hashes.push(b.hash());
 			chain.process_block(b, chain::Options::SKIP_POW).unwrap();
 		}
+		assert_eq!(*recorder.last_event.read(), None);
+		assert!(chain.reorg_history().is_empty());
 
 		let head = chain.head().unwrap();
 		assert_eq!(head.height, NUM_BLOCKS_MAIN);
@@ -395,6 +413,28 @@ fn mine_reorg() {
 		let head = chain.head().unwrap();
 		assert_eq!(head.height, NUM_BLOCKS_MAIN - REORG_DEPTH + 1);
 		assert_eq!(head.hash(), reorg_head.hash());
+
+		// Listeners get the blocks switched by the reorg
+		let mut disconnected = main_hashes[1..].to_vec();
+		disconnected.reverse();
+		assert_eq!(
+			*recorder.last_event.read(),
+			Some(ReorgEvent {
+				old_tip: Tip::from_header(&prev),
+				new_tip: head,
+				disconnected,
+				connected: vec![reorg_head.hash()],
+			})
+		);
+
+		// Reorg is kept in the chain reorg history
+		let history = chain.reorg_history();
+		assert_eq!(history.len(), 1);
+		assert_eq!(history[0].depth, REORG_DEPTH);
+		assert_eq!(history[0].old_tip_hash, prev.hash().to_hex());
+		assert_eq!(history[0].old_tip_height, NUM_BLOCKS_MAIN);
+		assert_eq!(history[0].new_tip_hash, reorg_head.hash().to_hex());
+		assert_eq!(history[0].fork_point_height, NUM_BLOCKS_MAIN - REORG_DEPTH);
 	}
 
 	// Cleanup chain directory
@@ -0,0 +1,66 @@
+// Copyright 2021 The MWC Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+use grin_chain as chain;
+
+mod chain_test_helper;
+
+use self::chain::types::{TimingHistogram, BLOCK_TIMING_BUCKETS_MS};
+use self::chain_test_helper::{clean_output_dir, mine_chain};
+use std::time::Duration;
+
+#[test]
+fn test_timing_histogram() {
+	let mut histogram = TimingHistogram::default();
+	assert_eq!(histogram.buckets.len(), BLOCK_TIMING_BUCKETS_MS.len() + 1);
+	assert_eq!(histogram.average_ms(), 0);
+
+	histogram.record(Duration::from_micros(300));
+	histogram.record(Duration::from_millis(5));
+	histogram.record(Duration::from_millis(7));
+	histogram.record(Duration::from_secs(60));
+
+	assert_eq!(histogram.count, 4);
+	assert_eq!(histogram.buckets[0], 1);
+	assert_eq!(histogram.buckets[1], 1);
+	assert_eq!(histogram.buckets[2], 1);
+	assert_eq!(histogram.buckets[BLOCK_TIMING_BUCKETS_MS.len()], 1);
+	assert_eq!(histogram.total_ms, 60_012);
+	assert_eq!(histogram.max_ms, 60_000);
+	assert_eq!(histogram.average_ms(), 15_003);
+}
+
+#[test]
+fn test_block_timing_stats() {
+	let chain_dir = ".grin.block_timings";
+	clean_output_dir(chain_dir);
+	let chain = mine_chain(chain_dir, 10);
+
+	// Every block after the genesis went through the pipeline.
+	let stats = chain.block_timing_stats();
+	for histogram in &[
+		&stats.pow,
+		&stats.header,
+		&stats.rangeproofs,
+		&stats.txhashset,
+		&stats.kernel_sums,
+		&stats.commit,
+		&stats.total,
+	] {
+		assert_eq!(histogram.count, 9);
+		assert_eq!(histogram.buckets.iter().sum::<u64>(), 9);
+	}
+	assert!(stats.total.total_ms >= stats.txhashset.total_ms);
+
+	clean_output_dir(chain_dir);
+}
@@ -0,0 +1,53 @@
+// Copyright 2021 The MWC Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+use grin_chain as chain;
+use grin_core as core;
+
+mod chain_test_helper;
+
+use self::chain_test_helper::{clean_output_dir, mine_chain};
+use self::core::core::hash::Hashed;
+
+#[test]
+fn test_blocks_range() {
+	let chain_dir = ".grin.blocks_range";
+	clean_output_dir(chain_dir);
+	let chain = mine_chain(chain_dir, 10);
+	let head = chain.head().unwrap();
+	assert_eq!(head.height, 9);
+
+	let blocks = chain
+		.get_blocks_range(2, 5)
+		.unwrap()
+		.collect::<Result<Vec<_>, _>>()
+		.unwrap();
+	assert_eq!(blocks.len(), 4);
+	for (block, height) in blocks.iter().zip(2..=5) {
+		assert_eq!(block.header.height, height);
+		assert_eq!(
+			block.hash(),
+			chain.get_header_by_height(height).unwrap().hash()
+		);
+	}
+
+	// The end height is capped at the chain head.
+	let range = chain.get_blocks_range(7, 100).unwrap();
+	assert_eq!(range.size_hint(), (3, Some(3)));
+	assert_eq!(range.last().unwrap().unwrap().hash(), head.last_block_h);
+
+	// An empty range.
+	assert_eq!(chain.get_blocks_range(20, 30).unwrap().count(), 0);
+
+	clean_output_dir(chain_dir);
+}
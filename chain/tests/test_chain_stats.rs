@@ -0,0 +1,53 @@
+// Copyright 2021 The MWC Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use grin_core as core;
+
+mod chain_test_helper;
+
+use self::chain_test_helper::{clean_output_dir, mine_chain};
+use self::core::core::hash::Hashed;
+use self::core::core::pmmr;
+
+#[test]
+fn test_chain_stats() {
+	let chain_dir = ".grin.chain_stats";
+	clean_output_dir(chain_dir);
+	let chain = mine_chain(chain_dir, 5);
+	assert_eq!(chain.head().unwrap().height, 4);
+
+	let mut prev_supply = 0;
+	for height in 0..5 {
+		let header = chain.get_header_by_height(height).unwrap();
+		let stats = chain.get_chain_stats(height).unwrap();
+		assert_eq!(stats.height, height);
+		assert_eq!(stats.hash, header.hash());
+		assert_eq!(stats.output_mmr_size, header.output_mmr_size);
+		assert_eq!(stats.kernel_mmr_size, header.kernel_mmr_size);
+		assert_eq!(stats.kernel_count, pmmr::n_leaves(header.kernel_mmr_size));
+
+		// Only coinbase outputs, nothing spent yet.
+		assert_eq!(stats.utxo_count, pmmr::n_leaves(header.output_mmr_size));
+
+		if height > 0 {
+			assert!(stats.total_supply > prev_supply);
+		}
+		prev_supply = stats.total_supply;
+	}
+
+	// Nothing to report beyond the chain head.
+	assert!(chain.get_chain_stats(5).is_err());
+
+	clean_output_dir(chain_dir);
+}
@@ -0,0 +1,63 @@
+// Copyright 2021 The MWC Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+use grin_chain as chain;
+use grin_core as core;
+
+mod chain_test_helper;
+
+use self::chain::CompactionState;
+use self::chain_test_helper::{clean_output_dir, mine_chain};
+use self::core::core::hash::Hashed;
+use self::core::global;
+use std::time::Duration;
+
+#[test]
+fn test_compact_chunk() {
+	let chain_dir = ".grin.compact_chunk";
+	clean_output_dir(chain_dir);
+	let chain = mine_chain(chain_dir, 100);
+	let head = chain.head().unwrap();
+	assert_eq!(head.height, 99);
+
+	// Tiny chunks, every chunk scans a single block or compacts a single part.
+	let mut state = CompactionState::default();
+	let mut chunks = 0;
+	while !chain
+		.compact_chunk(&mut state, Duration::from_millis(0))
+		.unwrap()
+	{
+		chunks += 1;
+		assert!(chunks < 1000);
+	}
+	assert!(chunks > 100);
+
+	let tail_height = head.height - global::cut_through_horizon() as u64;
+	assert_eq!(chain.tail().unwrap().height, tail_height);
+	for height in 1..tail_height {
+		let header = chain.get_header_by_height(height).unwrap();
+		assert!(chain.get_block(&header.hash()).is_err());
+	}
+	for height in tail_height..=head.height {
+		let header = chain.get_header_by_height(height).unwrap();
+		assert!(chain.get_block(&header.hash()).is_ok());
+	}
+
+	// Compaction is not due right after the previous one.
+	let mut state = CompactionState::default();
+	assert!(chain
+		.compact_chunk(&mut state, Duration::from_millis(0))
+		.unwrap());
+
+	clean_output_dir(chain_dir);
+}
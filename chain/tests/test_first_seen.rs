@@ -0,0 +1,60 @@
+// Copyright 2021 The MWC Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use grin_core as core;
+
+mod chain_test_helper;
+
+use self::chain_test_helper::{clean_output_dir, init_chain, mine_chain};
+use self::core::core::hash::Hashed;
+use self::core::core::Transaction;
+use chrono::prelude::{TimeZone, Utc};
+
+#[test]
+fn test_first_seen() {
+	let chain_dir = ".grin.first_seen";
+	clean_output_dir(chain_dir);
+	let chain = mine_chain(chain_dir, 5);
+	assert_eq!(chain.head().unwrap().height, 4);
+
+	// Genesis is not received from anybody, all the mined blocks are.
+	let genesis = chain.get_header_by_height(0).unwrap();
+	assert_eq!(chain.block_first_seen(&genesis.hash()).unwrap(), None);
+	for height in 1..5 {
+		let header = chain.get_header_by_height(height).unwrap();
+		assert!(chain.block_first_seen(&header.hash()).unwrap().is_some());
+	}
+
+	// The first recorded time wins.
+	let block = chain
+		.get_block(&chain.head().unwrap().last_block_h)
+		.unwrap();
+	let kernel = block.kernels()[0].clone();
+	let tx = Transaction::empty().with_kernel(kernel.clone());
+	assert_eq!(chain.tx_first_seen(&kernel.excess()).unwrap(), None);
+
+	let first = Utc.timestamp(1_600_000_000, 0);
+	let second = Utc.timestamp(1_600_000_100, 0);
+	assert_eq!(chain.record_tx_first_seen(&tx, first).unwrap(), first);
+	assert_eq!(chain.record_tx_first_seen(&tx, second).unwrap(), first);
+	assert_eq!(chain.tx_first_seen(&kernel.excess()).unwrap(), Some(first));
+
+	// Records are written in batches, the pending ones when the chain is closed.
+	let genesis = chain.get_block(&genesis.hash()).unwrap();
+	drop(chain);
+	let chain = init_chain(chain_dir, genesis);
+	assert_eq!(chain.tx_first_seen(&kernel.excess()).unwrap(), Some(first));
+
+	clean_output_dir(chain_dir);
+}
@@ -0,0 +1,50 @@
+// Copyright 2021 The MWC Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+use grin_core as core;
+use grin_util as util;
+
+mod chain_test_helper;
+
+use self::chain_test_helper::{clean_output_dir, mine_chain};
+use self::core::core::hash::Hashed;
+use util::secp::pedersen::Commitment;
+
+#[test]
+fn test_get_output_height() {
+	let chain_dir = ".grin.get_output_height";
+	clean_output_dir(chain_dir);
+	let chain = mine_chain(chain_dir, 5);
+	assert_eq!(chain.head().unwrap().height, 4);
+
+	for height in 1..5 {
+		let header = chain.get_header_by_height(height).unwrap();
+		let block = chain.get_block(&header.hash()).unwrap();
+		for output in block.outputs() {
+			assert_eq!(
+				chain.get_output_height(&output.commitment()).unwrap(),
+				Some(height)
+			);
+		}
+	}
+
+	// Unknown output is not in the index.
+	assert_eq!(
+		None,
+		chain
+			.get_output_height(&Commitment::from_vec(vec![]))
+			.unwrap()
+	);
+
+	clean_output_dir(chain_dir);
+}
@@ -14,59 +14,31 @@
 
 use grin_chain as chain;
 use grin_core as core;
-use grin_keychain as keychain;
 
 mod chain_test_helper;
 
-use self::chain_test_helper::{clean_output_dir, mine_chain};
-use crate::chain::{Chain, ErrorKind, Options};
-use crate::core::{
-	consensus,
-	core::{block, Block},
-	global,
-	libtx::{reward, ProofBuilder},
-	pow,
-};
-use crate::keychain::{ExtKeychain, ExtKeychainPath, Keychain};
-use chrono::Duration;
-
-fn build_block(chain: &Chain) -> Block {
-	let keychain = ExtKeychain::from_random_seed(false).unwrap();
-	let pk = ExtKeychainPath::new(1, 1, 0, 0, 0).to_identifier();
-
-	let prev = chain.head_header().unwrap();
-	let next_header_info = consensus::next_difficulty(1, chain.difficulty_iter().unwrap());
-	let reward =
-		reward::output(&keychain, &ProofBuilder::new(&keychain), &pk, 0, false, 1).unwrap();
-	let mut block = Block::new(&prev, &[], next_header_info.clone().difficulty, reward).unwrap();
-
-	block.header.timestamp = prev.timestamp + Duration::seconds(60);
-	block.header.pow.secondary_scaling = next_header_info.secondary_scaling;
-
-	chain.set_txhashset_roots(&mut block).unwrap();
-
-	let edge_bits = global::min_edge_bits();
-	block.header.pow.proof.edge_bits = edge_bits;
-	pow::pow_size(
-		&mut block.header,
-		next_header_info.difficulty,
-		global::proofsize(),
-		edge_bits,
-	)
-	.unwrap();
-
-	block
-}
+use self::chain_test_helper::{clean_output_dir, init_chain};
+use crate::chain::{ErrorKind, Options};
+use crate::core::core::block;
+use grin_fixtures::FixtureSpec;
 
 #[test]
 fn test_header_weight_validation() {
+	let gen_dir = ".grin.header_weight_gen";
 	let chain_dir = ".grin.header_weight";
+	clean_output_dir(gen_dir);
 	clean_output_dir(chain_dir);
-	let chain = mine_chain(chain_dir, 5);
+
+	// The last fixture block is the next block of the chain
+	let (gen_chain, fixture) = grin_fixtures::generate(&FixtureSpec::linear(5), gen_dir).unwrap();
+	drop(gen_chain);
+	let chain = init_chain(chain_dir, fixture.genesis.clone());
+	for b in &fixture.blocks[..4] {
+		chain.process_block(b.clone(), Options::NONE).unwrap();
+	}
 	assert_eq!(chain.head().unwrap().height, 4);
 
-	let block = build_block(&chain);
-	let mut header = block.header;
+	let mut header = fixture.blocks[4].header.clone();
 
 	// Artificially set the output_mmr_size too large for a valid block.
 	// Note: We will validate this even if just processing the header.
@@ -79,5 +51,6 @@ fn test_header_weight_validation() {
 	// Weight validation is done via transaction body and results in a slightly counter-intuitive tx error.
 	assert_eq!(res, Err(ErrorKind::Block(block::Error::TooHeavy)));
 
+	clean_output_dir(gen_dir);
 	clean_output_dir(chain_dir);
 }
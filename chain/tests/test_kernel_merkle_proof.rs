@@ -0,0 +1,55 @@
+// Copyright 2021 The MWC Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+use grin_chain as chain;
+use grin_core as core;
+
+mod chain_test_helper;
+
+use self::chain_test_helper::{clean_output_dir, mine_chain};
+use self::core::core::hash::Hashed;
+
+#[test]
+fn test_kernel_merkle_proof() {
+	let chain_dir = ".grin.kernel_merkle_proof";
+	clean_output_dir(chain_dir);
+	let chain = mine_chain(chain_dir, 10);
+
+	// The genesis kernel proven against the roots of the later headers.
+	for height in 0..10 {
+		let (kernel, header, proof) = chain.get_kernel_merkle_proof(1, height).unwrap();
+		assert_eq!(header.height, height);
+		assert_eq!(
+			header.hash(),
+			chain.get_header_by_height(height).unwrap().hash()
+		);
+		assert_eq!(proof.mmr_size, header.kernel_mmr_size);
+		assert!(proof.verify(header.kernel_root, &kernel, 1).is_ok());
+	}
+
+	// The kernel of the head block is not in the kernel MMR of the earlier header.
+	let head = chain.head_header().unwrap();
+	let prev = chain.get_previous_header(&head).unwrap();
+	let pos = prev.kernel_mmr_size + 1;
+	assert!(chain.get_kernel_merkle_proof(pos, prev.height).is_err());
+	let (kernel, _, proof) = chain.get_kernel_merkle_proof(pos, head.height).unwrap();
+	assert!(proof.verify(prev.kernel_root, &kernel, pos).is_err());
+	assert!(proof.verify(head.kernel_root, &kernel, pos).is_ok());
+
+	// The kernel MMR is not pruned, proofs are available after the compaction.
+	chain.compact().unwrap();
+	let (kernel, header, proof) = chain.get_kernel_merkle_proof(1, 5).unwrap();
+	assert!(proof.verify(header.kernel_root, &kernel, 1).is_ok());
+
+	clean_output_dir(chain_dir);
+}
@@ -0,0 +1,67 @@
+// Copyright 2021 The MWC Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+use grin_core as core;
+use grin_util as util;
+
+mod chain_test_helper;
+
+use self::chain_test_helper::{clean_output_dir, mine_chain};
+use self::core::core::hash::Hashed;
+use util::secp::pedersen::Commitment;
+
+#[test]
+fn test_kernel_pos_index() {
+	let chain_dir = ".grin.kernel_pos_index";
+	clean_output_dir(chain_dir);
+	let chain = mine_chain(chain_dir, 5);
+	assert_eq!(chain.head().unwrap().height, 4);
+
+	let mut kernels = vec![];
+	for height in 1..5 {
+		let header = chain.get_header_by_height(height).unwrap();
+		let block = chain.get_block(&header.hash()).unwrap();
+		for kernel in block.kernels() {
+			kernels.push((kernel.clone(), height));
+		}
+	}
+	assert!(!kernels.is_empty());
+
+	let check = |kernels: &[(core::core::TxKernel, u64)]| {
+		for (kernel, height) in kernels {
+			let (found, found_height, pos) =
+				chain.get_kernel_by_excess(&kernel.excess).unwrap().unwrap();
+			assert_eq!(&found, kernel);
+			assert_eq!(found_height, *height);
+			assert_eq!(
+				chain.get_kernel_height(&kernel.excess, None, None).unwrap(),
+				Some((found, found_height, pos))
+			);
+		}
+	};
+	check(&kernels);
+
+	// Unknown excess is not in the index.
+	assert_eq!(
+		None,
+		chain
+			.get_kernel_by_excess(&Commitment::from_vec(vec![]))
+			.unwrap()
+	);
+
+	// Full rebuild produces the same index.
+	chain.rebuild_kernel_pos_index().unwrap();
+	check(&kernels);
+
+	clean_output_dir(chain_dir);
+}
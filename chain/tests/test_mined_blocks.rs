@@ -0,0 +1,70 @@
+// Copyright 2021 The MWC Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+mod chain_test_helper;
+
+use self::chain_test_helper::{clean_output_dir, init_chain};
+use chain::Options;
+use grin_chain as chain;
+use grin_core::core::hash::Hashed;
+use grin_fixtures::{generate, FixtureSpec, ForkSpec};
+use grin_util as util;
+
+#[test]
+fn test_mined_blocks() {
+	let gen_dir = ".grin.mined_blocks_gen";
+	let chain_dir = ".grin.mined_blocks";
+	util::init_test_logger();
+	clean_output_dir(gen_dir);
+	clean_output_dir(chain_dir);
+
+	// Main chain 1..3, then a longer branch from 1 reorgs 2 and 3 out
+	let spec = FixtureSpec {
+		name: "mined_blocks".to_string(),
+		height: 3,
+		forks: vec![ForkSpec {
+			fork_height: 1,
+			length: 4,
+		}],
+		..FixtureSpec::default()
+	};
+	let (_, fixture) = generate(&spec, gen_dir).unwrap();
+	let chain = init_chain(chain_dir, fixture.genesis.clone());
+
+	// The main chain blocks are "mined" by us, the branch comes from the peers
+	for (i, b) in fixture.blocks.iter().enumerate() {
+		let opts = if i < 3 { Options::MINE } else { Options::NONE };
+		chain.process_block(b.clone(), opts).unwrap();
+	}
+	assert_eq!(chain.head().unwrap().last_block_h, fixture.head);
+
+	let mined = chain.get_mined_blocks(0, 10).unwrap();
+	assert_eq!(mined.len(), 3);
+	for (i, (block, on_chain)) in mined.iter().enumerate() {
+		let b = &fixture.blocks[i];
+		assert_eq!(block.height, b.header.height);
+		assert_eq!(block.hash, b.hash());
+		assert_eq!(block.coinbase_outputs, vec![b.outputs()[0].commitment()]);
+		assert_eq!(block.coinbase_kernels, vec![b.kernels()[0].excess()]);
+		// Only the block below the fork is still on the chain
+		assert_eq!(*on_chain, i == 0);
+	}
+
+	let mined = chain.get_mined_blocks(2, 2).unwrap();
+	assert_eq!(mined.len(), 1);
+	assert_eq!(mined[0].0.height, 2);
+
+	clean_output_dir(gen_dir);
+	clean_output_dir(chain_dir);
+}
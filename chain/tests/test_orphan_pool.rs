@@ -0,0 +1,78 @@
+// Copyright 2021 The MWC Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+use grin_chain as chain;
+use grin_core as core;
+
+mod chain_test_helper;
+
+use self::chain::types::Options;
+use self::chain_test_helper::{clean_output_dir, init_chain, mine_chain};
+use self::core::core::hash::Hashed;
+use self::core::ser::{self, ProtocolVersion};
+
+#[test]
+fn test_orphan_pool_eviction() {
+	let chain_dir = ".grin.orphan_pool";
+	let chain_dir_2 = ".grin.orphan_pool_2";
+	clean_output_dir(chain_dir);
+	clean_output_dir(chain_dir_2);
+	let chain = mine_chain(chain_dir, 11);
+	let blocks = (0..=10)
+		.map(|height| {
+			let header = chain.get_header_by_height(height).unwrap();
+			chain.get_block(&header.hash()).unwrap()
+		})
+		.collect::<Vec<_>>();
+
+	// Second node only has the genesis, room for 3 orphans.
+	let chain_2 = init_chain(chain_dir_2, blocks[0].clone());
+	let block_size = ser::ser_vec(&blocks[10], ProtocolVersion::local())
+		.unwrap()
+		.len();
+	chain_2.set_orphans_max_size_bytes(block_size * 7 / 2);
+	let headers = blocks[1..]
+		.iter()
+		.map(|b| b.header.clone())
+		.collect::<Vec<_>>();
+	chain_2.sync_block_headers(&headers, Options::SYNC).unwrap();
+
+	// Blocks arrive in reverse order, the orphans furthest from the head are evicted.
+	for block in blocks[2..].iter().rev() {
+		assert!(chain_2.process_block(block.clone(), Options::NONE).is_err());
+	}
+	let stats = chain_2.orphans_stats();
+	assert_eq!(stats.count, 3);
+	assert_eq!(stats.misses, 6);
+	assert_eq!(stats.hits, 0);
+	assert!(stats.size_bytes <= stats.max_size_bytes);
+	for height in 2..=4 {
+		assert!(chain_2.is_orphan(&blocks[height].hash()));
+	}
+	for height in 5..=10 {
+		assert!(!chain_2.is_orphan(&blocks[height].hash()));
+	}
+
+	// The missing parent arrives, the remaining orphans are processed.
+	chain_2
+		.process_block(blocks[1].clone(), Options::NONE)
+		.unwrap();
+	assert_eq!(chain_2.head().unwrap().height, 4);
+	let stats = chain_2.orphans_stats();
+	assert_eq!(stats.count, 0);
+	assert_eq!(stats.size_bytes, 0);
+	assert_eq!(stats.hits, 3);
+
+	clean_output_dir(chain_dir);
+	clean_output_dir(chain_dir_2);
+}
@@ -0,0 +1,73 @@
+// Copyright 2021 The MWC Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+mod chain_test_helper;
+use self::chain_test_helper::{clean_output_dir, mine_chain};
+use grin_chain as chain;
+use grin_util as util;
+
+#[test]
+fn outputs_in_height_range() {
+	let chain_dir = ".grin.outputs_in_range";
+	util::init_test_logger();
+	clean_output_dir(chain_dir);
+
+	let chain = mine_chain(chain_dir, 10);
+	let (_, _, expected) = chain.unspent_outputs_by_pmmr_index(1, 1000, None).unwrap();
+
+	// the whole chain, the end height past the head is truncated
+	let (outputs, next_height) = chain
+		.outputs_in_height_range(0, 100, true, true, 1000)
+		.unwrap();
+	assert_eq!(next_height, None);
+	assert_eq!(outputs.len(), expected.len());
+	for (output, expected) in outputs.iter().zip(expected.iter()) {
+		assert_eq!(output.output.commitment(), expected.commitment());
+		assert_eq!(output.proof.as_ref(), Some(&expected.proof));
+		assert!(!output.spent);
+		assert_eq!(
+			chain
+				.get_output_height(&output.output.commitment())
+				.unwrap(),
+			Some(output.height)
+		);
+	}
+
+	// every mined block has a single coinbase output
+	let (outputs, _) = chain
+		.outputs_in_height_range(3, 5, false, false, 1000)
+		.unwrap();
+	let heights: Vec<u64> = outputs.iter().map(|o| o.height).collect();
+	assert_eq!(heights, vec![3, 4, 5]);
+	assert!(outputs.iter().all(|o| o.proof.is_none()));
+
+	// the range is cut short by the output limit, with the height to continue from
+	let (outputs, next_height) = chain
+		.outputs_in_height_range(3, 8, false, false, 2)
+		.unwrap();
+	let heights: Vec<u64> = outputs.iter().map(|o| o.height).collect();
+	assert_eq!(heights, vec![3, 4]);
+	assert_eq!(next_height, Some(5));
+
+	// a range past the head is empty
+	for start_height in 10..13 {
+		let (outputs, next_height) = chain
+			.outputs_in_height_range(start_height, 20, false, false, 1000)
+			.unwrap();
+		assert!(outputs.is_empty());
+		assert_eq!(next_height, None);
+	}
+
+	clean_output_dir(chain_dir);
+}
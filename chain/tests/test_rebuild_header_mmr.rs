@@ -0,0 +1,72 @@
+// Copyright 2021 The MWC Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+use grin_chain as chain;
+use grin_core as core;
+
+mod chain_test_helper;
+
+use self::chain::Chain;
+use self::chain_test_helper::{clean_output_dir, init_chain, mine_chain};
+use self::core::core::hash::Hashed;
+use std::fs;
+use std::path::Path;
+
+// Truncate the header MMR files, as if the node crashed while writing them.
+fn corrupt_header_mmr(chain_dir: &str) {
+	let dir = Path::new(chain_dir).join("header").join("header_head");
+	for entry in fs::read_dir(dir).unwrap() {
+		let path = entry.unwrap().path();
+		fs::OpenOptions::new()
+			.write(true)
+			.open(path)
+			.unwrap()
+			.set_len(0)
+			.unwrap();
+	}
+}
+
+#[test]
+fn test_rebuild_header_mmr() {
+	let chain_dir = ".grin.rebuild_header_mmr";
+	clean_output_dir(chain_dir);
+	let chain = mine_chain(chain_dir, 20);
+	let genesis = chain
+		.get_block(&chain.get_header_by_height(0).unwrap().hash())
+		.unwrap();
+	let hashes = (0..20)
+		.map(|height| chain.get_header_by_height(height).unwrap().hash())
+		.collect::<Vec<_>>();
+	drop(chain);
+
+	// Explicit rebuild.
+	corrupt_header_mmr(chain_dir);
+	assert_eq!(Chain::rebuild_header_mmr(chain_dir, None).unwrap(), 19);
+	let chain = init_chain(chain_dir, genesis.clone());
+	for (height, hash) in hashes.iter().enumerate() {
+		assert_eq!(
+			chain.get_header_by_height(height as u64).unwrap().hash(),
+			*hash
+		);
+	}
+	drop(chain);
+
+	// The corrupted header MMR is detected and rebuilt on init.
+	corrupt_header_mmr(chain_dir);
+	let chain = init_chain(chain_dir, genesis);
+	assert_eq!(chain.header_head().unwrap().height, 19);
+	assert_eq!(chain.get_header_by_height(19).unwrap().hash(), hashes[19]);
+	drop(chain);
+
+	clean_output_dir(chain_dir);
+}
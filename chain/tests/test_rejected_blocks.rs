@@ -0,0 +1,97 @@
+// Copyright 2021 The MWC Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+mod chain_test_helper;
+use self::chain_test_helper::{clean_output_dir, init_chain, mine_chain};
+use chain::Tip;
+use grin_chain as chain;
+use grin_core::core::hash::Hashed;
+use grin_util as util;
+use grin_util::ToHex;
+use std::path::Path;
+
+#[test]
+fn rejected_blocks_forensics() {
+	let chain_dir = ".grin.rejected_blocks";
+	util::init_test_logger();
+	clean_output_dir(chain_dir);
+
+	// mine some blocks
+	let (latest, genesis) = {
+		let chain = mine_chain(chain_dir, 3);
+		let genesis = chain
+			.get_block(&chain.get_header_by_height(0).unwrap().hash())
+			.unwrap();
+		let head = chain.head().unwrap();
+		let latest = chain.get_block(&head.last_block_h).unwrap();
+		(latest, genesis)
+	};
+
+	// reset chain head to earlier state, so the latest block is the next one
+	{
+		let chain = init_chain(chain_dir, genesis.clone());
+		let store = chain.store();
+		let batch = store.batch().unwrap();
+		let head_header = chain.head_header().unwrap();
+		let prev = batch.get_previous_header(&head_header).unwrap();
+		batch.save_body_head(&Tip::from_header(&prev)).unwrap();
+		batch.commit().unwrap();
+	}
+
+	{
+		let chain = init_chain(chain_dir, genesis.clone());
+		chain.set_rejected_blocks_forensics(true, 5);
+
+		// block without its coinbase output fails the body validation
+		let mut tampered = latest.clone();
+		tampered.body = tampered.body.replace_outputs(&[]);
+		assert!(chain.process_block(tampered, chain::Options::NONE).is_err());
+
+		let rejections = chain.rejected_blocks();
+		assert_eq!(rejections.len(), 1);
+		let rejection = &rejections[0];
+		assert_eq!(rejection.hash, latest.hash().to_hex());
+		assert_eq!(rejection.height, latest.header.height);
+		assert_eq!(rejection.prev_hash, latest.header.prev_hash.to_hex());
+		assert_eq!(rejection.stage, "block");
+		assert_eq!(rejection.expected_roots, None);
+
+		let dump_dir = Path::new(rejection.dump_dir.as_ref().unwrap());
+		assert!(dump_dir.join("block.bin").exists());
+		assert!(dump_dir.join("prev_header.bin").exists());
+		assert!(dump_dir.join("rejection.json").exists());
+
+		// the valid block is accepted and not recorded
+		chain
+			.process_block(latest.clone(), chain::Options::NONE)
+			.unwrap();
+		assert_eq!(chain.rejected_blocks().len(), 1);
+	}
+
+	// the dumped rejections are loaded after restart, the kept ones are trimmed
+	{
+		let chain = init_chain(chain_dir, genesis.clone());
+		assert!(chain.rejected_blocks().is_empty());
+		chain.set_rejected_blocks_forensics(true, 5);
+		let rejections = chain.rejected_blocks();
+		assert_eq!(rejections.len(), 1);
+		let dump_dir = rejections[0].dump_dir.clone().unwrap();
+
+		chain.set_rejected_blocks_forensics(true, 0);
+		assert!(chain.rejected_blocks().is_empty());
+		assert!(!Path::new(&dump_dir).exists());
+	}
+
+	clean_output_dir(chain_dir);
+}
@@ -0,0 +1,89 @@
+// Copyright 2021 The MWC Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+mod chain_test_helper;
+
+use self::chain_test_helper::{clean_output_dir, init_chain};
+use chain::{Options, MAX_TIP_FREEZE_SECS};
+use grin_chain as chain;
+use grin_core::core::hash::Hashed;
+use grin_fixtures::{generate, FixtureSpec};
+use grin_util as util;
+use grin_util::ToHex;
+use std::{thread, time};
+
+#[test]
+fn test_tip_freeze() {
+	let gen_dir = ".grin.tip_freeze_gen";
+	let chain_dir = ".grin.tip_freeze";
+	util::init_test_logger();
+	clean_output_dir(gen_dir);
+	clean_output_dir(chain_dir);
+
+	let (_, fixture) = generate(&FixtureSpec::linear(6), gen_dir).unwrap();
+	let chain = init_chain(chain_dir, fixture.genesis.clone());
+	for b in &fixture.blocks[..2] {
+		chain.process_block(b.clone(), Options::NONE).unwrap();
+	}
+
+	assert!(chain.freeze_tip(0).is_err());
+	assert!(chain.freeze_tip(MAX_TIP_FREEZE_SECS + 1).is_err());
+	let status = chain.freeze_tip(60).unwrap();
+	assert!(status.frozen);
+	assert_eq!(status.height, 2);
+	assert_eq!(status.hash, fixture.blocks[1].hash().to_hex());
+
+	// The new blocks are buffered, the head stays
+	for b in &fixture.blocks[2..4] {
+		let res = chain.process_block(b.clone(), Options::NONE);
+		assert_eq!(res.unwrap(), None);
+	}
+	let _ = chain.process_block(fixture.blocks[3].clone(), Options::NONE);
+	// The blocks that don't extend the chain are not buffered
+	let mut junk = fixture.blocks[4].clone();
+	junk.header.prev_hash = fixture.blocks[0].hash();
+	assert!(chain.process_block(junk, Options::NONE).is_err());
+	let status = chain.tip_freeze_status().unwrap();
+	assert!(status.frozen);
+	assert_eq!(status.height, 2);
+	assert_eq!(status.buffered_blocks, 2);
+
+	// Extending the freeze keeps the buffered blocks
+	assert!(chain.freeze_tip(120).unwrap().frozen);
+	assert_eq!(chain.tip_freeze_status().unwrap().buffered_blocks, 2);
+
+	// Unfreeze processes the buffered blocks
+	let status = chain.unfreeze_tip().unwrap();
+	assert!(!status.frozen);
+	assert_eq!(status.height, 4);
+	assert_eq!(status.buffered_blocks, 0);
+	chain
+		.process_block(fixture.blocks[4].clone(), Options::NONE)
+		.unwrap();
+
+	// The freeze ends automatically
+	chain.freeze_tip(1).unwrap();
+	let res = chain.process_block(fixture.blocks[5].clone(), Options::NONE);
+	assert_eq!(res.unwrap(), None);
+	assert_eq!(chain.head().unwrap().height, 5);
+	thread::sleep(time::Duration::from_secs(2));
+	chain.check_tip_freeze();
+	let status = chain.tip_freeze_status().unwrap();
+	assert!(!status.frozen);
+	assert_eq!(status.height, 6);
+	assert_eq!(chain.head().unwrap().last_block_h, fixture.head);
+
+	clean_output_dir(gen_dir);
+	clean_output_dir(chain_dir);
+}
@@ -43,13 +43,15 @@ fn test_unexpected_zip() {
 		txhashset::TxHashSet::open(db_root.clone(), store.clone(), None).unwrap();
 		let head = BlockHeader::default();
 		// First check if everything works out of the box
-		assert!(txhashset::zip_read(db_root.clone(), &head).is_ok());
+		assert!(txhashset::zip_read(db_root.clone(), &head, None).is_ok());
 		let zip_path = Path::new(&db_root).join(format!(
 			"txhashset_snapshot_{}.zip",
 			head.hash().to_string()
 		));
 		let zip_file = File::open(&zip_path).unwrap();
-		assert!(txhashset::zip_write(PathBuf::from(db_root.clone()), zip_file, &head).is_ok());
+		assert!(
+			txhashset::zip_write(PathBuf::from(db_root.clone()), zip_file, &head, None).is_ok()
+		);
 		// Remove temp txhashset dir
 		let _ = fs::remove_dir_all(
 			Path::new(&db_root).join(format!("txhashset_zip_{}", head.hash().to_string())),
@@ -85,13 +87,15 @@ fn test_unexpected_zip() {
 				.collect::<Vec<_>>()
 		);
 
-		assert!(txhashset::zip_read(db_root.clone(), &head).is_ok());
+		assert!(txhashset::zip_read(db_root.clone(), &head, None).is_ok());
 		let _ = fs::remove_dir_all(
 			Path::new(&db_root).join(format!("txhashset_zip_{}", head.hash().to_string())),
 		);
 		let zip_file = File::open(zip_path).unwrap();
 		let _ = fs::remove_dir_all(Path::new(&db_root).join("txhashset"));
-		assert!(txhashset::zip_write(PathBuf::from(db_root.clone()), zip_file, &head).is_ok());
+		assert!(
+			txhashset::zip_write(PathBuf::from(db_root.clone()), zip_file, &head, None).is_ok()
+		);
 
 		// Check that the new txhashset dir contains *only* the expected files
 		// No "badfiles" and no "size" file.
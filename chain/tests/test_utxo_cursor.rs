@@ -0,0 +1,64 @@
+// Copyright 2021 The MWC Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+mod chain_test_helper;
+use self::chain_test_helper::{clean_output_dir, mine_chain};
+use chain::UtxoCursor;
+use grin_chain as chain;
+use grin_util as util;
+
+#[test]
+fn unspent_outputs_cursor() {
+	let chain_dir = ".grin.utxo_cursor";
+	util::init_test_logger();
+	clean_output_dir(chain_dir);
+
+	let chain = mine_chain(chain_dir, 10);
+	let (_, _, expected) = chain.unspent_outputs_by_pmmr_index(1, 1000, None).unwrap();
+
+	// traverse in small batches, passing the cursor through its hex representation
+	let mut cursor = None;
+	let mut outputs = vec![];
+	loop {
+		let (batch, next_cursor) = chain.unspent_outputs_cursor(cursor, 3, true).unwrap();
+		if batch.is_empty() {
+			assert_eq!(next_cursor, cursor);
+			break;
+		}
+		assert!(batch.len() <= 3);
+		let next_cursor = next_cursor.unwrap();
+		assert_eq!(next_cursor.pos, batch.last().unwrap().pos);
+		cursor = Some(UtxoCursor::from_hex(&next_cursor.to_hex()).unwrap());
+		outputs.extend(batch);
+	}
+
+	assert_eq!(outputs.len(), expected.len());
+	for (output, expected) in outputs.iter().zip(expected.iter()) {
+		assert_eq!(output.commit, expected.commitment());
+		assert_eq!(output.features, expected.features());
+		assert_eq!(output.proof, Some(expected.proof));
+		assert_eq!(
+			chain.get_output_height(&output.commit).unwrap(),
+			Some(output.height)
+		);
+	}
+
+	// proofs are optional
+	let (batch, _) = chain.unspent_outputs_cursor(None, 1, false).unwrap();
+	assert_eq!(batch[0].proof, None);
+
+	assert!(UtxoCursor::from_hex("00").is_err());
+
+	clean_output_dir(chain_dir);
+}
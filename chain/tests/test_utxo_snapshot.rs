@@ -0,0 +1,60 @@
+// Copyright 2021 The MWC Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+use grin_chain as chain;
+use grin_core as core;
+
+mod chain_test_helper;
+
+use self::chain::types::{NoStatus, Options};
+use self::chain_test_helper::{clean_output_dir, init_chain, mine_chain};
+use self::core::core::hash::Hashed;
+
+#[test]
+fn test_utxo_snapshot() {
+	let source_dir = ".grin.utxo_snapshot_source";
+	let chain_dir = ".grin.utxo_snapshot";
+	clean_output_dir(source_dir);
+	clean_output_dir(chain_dir);
+
+	let source = mine_chain(source_dir, 10);
+	let head = source.head_header().unwrap();
+	let genesis = source
+		.get_block(&source.get_header_by_height(0).unwrap().hash())
+		.unwrap();
+	let headers: Vec<_> = (1..=head.height)
+		.map(|height| source.get_header_by_height(height).unwrap())
+		.collect();
+	let (_, _, snapshot) = source.txhashset_read(head.hash()).unwrap();
+
+	let chain = init_chain(chain_dir, genesis);
+	chain.sync_block_headers(&headers, Options::NONE).unwrap();
+	assert_eq!(chain.assumed_utxo().unwrap(), None);
+
+	// The chain follows from the snapshot right away, its history is not validated yet.
+	chain
+		.load_utxo_snapshot(head.hash(), snapshot, &NoStatus)
+		.unwrap();
+	assert_eq!(chain.head().unwrap().last_block_h, head.hash());
+	let assumed = chain.assumed_utxo().unwrap().unwrap();
+	assert_eq!(assumed.hash, head.hash());
+	assert_eq!(assumed.height, head.height);
+
+	// Once validated in background the chain is fully validated.
+	chain.validate_assumed_utxo(&NoStatus).unwrap();
+	assert_eq!(chain.assumed_utxo().unwrap(), None);
+	assert_eq!(chain.head().unwrap().last_block_h, head.hash());
+
+	clean_output_dir(source_dir);
+	clean_output_dir(chain_dir);
+}
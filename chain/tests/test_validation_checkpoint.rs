@@ -0,0 +1,95 @@
+// Copyright 2021 The MWC Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+use grin_chain as chain;
+use grin_core as core;
+
+mod chain_test_helper;
+
+use self::chain::types::NoStatus;
+use self::chain::ValidationCheckpoint;
+use self::chain_test_helper::{clean_output_dir, mine_chain};
+use self::core::core::hash::Hashed;
+use self::core::global;
+
+#[test]
+fn test_validation_checkpoint() {
+	let chain_dir = ".grin.validation_checkpoint";
+	clean_output_dir(chain_dir);
+	let chain = mine_chain(chain_dir, 5);
+	let store = chain.store();
+	let head = chain.head_header().unwrap();
+	assert_eq!(store.get_validation_checkpoint().unwrap(), None);
+
+	// Full verification checkpoints the progress up to the end of both MMRs.
+	{
+		let txhashset = chain.txhashset();
+		let txhashset = txhashset.read();
+		assert!(txhashset.verify_proofs(&head, &NoStatus).unwrap());
+	}
+	let checkpoint = store.get_validation_checkpoint().unwrap().unwrap();
+	assert_eq!(checkpoint.hash, head.hash());
+	assert_eq!(checkpoint.rproof_pos, head.output_mmr_size);
+	assert_eq!(checkpoint.kernel_pos, head.kernel_mmr_size);
+
+	// A checkpoint for another header is ignored and replaced.
+	let prev = chain.get_previous_header(&head).unwrap();
+	{
+		let batch = store.batch().unwrap();
+		batch
+			.save_validation_checkpoint(&ValidationCheckpoint {
+				hash: prev.hash(),
+				rproof_pos: 1,
+				kernel_pos: 1,
+			})
+			.unwrap();
+		batch.commit().unwrap();
+	}
+	{
+		let txhashset = chain.txhashset();
+		let txhashset = txhashset.read();
+		assert!(txhashset.verify_proofs(&head, &NoStatus).unwrap());
+
+		// Nothing is verified for a header not matching the txhashset.
+		assert!(!txhashset.verify_proofs(&prev, &NoStatus).unwrap());
+	}
+	assert_eq!(
+		store.get_validation_checkpoint().unwrap().unwrap(),
+		checkpoint
+	);
+
+	clean_output_dir(chain_dir);
+}
+
+#[test]
+fn test_validation_low_memory_mode() {
+	let chain_dir = ".grin.validation_low_memory";
+	clean_output_dir(chain_dir);
+	global::set_local_low_memory_mode(true);
+	let chain = mine_chain(chain_dir, 5);
+	let head = chain.head_header().unwrap();
+
+	// Small windows verify everything the same way.
+	{
+		let txhashset = chain.txhashset();
+		let txhashset = txhashset.read();
+		assert!(txhashset.verify_proofs(&head, &NoStatus).unwrap());
+	}
+	let checkpoint = chain.store().get_validation_checkpoint().unwrap().unwrap();
+	assert_eq!(checkpoint.rproof_pos, head.output_mmr_size);
+	assert_eq!(checkpoint.kernel_pos, head.kernel_mmr_size);
+	chain.validate(false).unwrap();
+
+	global::set_local_low_memory_mode(false);
+	clean_output_dir(chain_dir);
+}
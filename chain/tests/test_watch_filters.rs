@@ -0,0 +1,166 @@
+// Copyright 2021 The MWC Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+mod chain_test_helper;
+
+use self::chain::watch::{BloomFilter, WatchEventKind, WatchMatcher};
+use self::chain::{Chain, Options};
+use self::chain_test_helper::{clean_output_dir, init_chain};
+use self::core::core::hash::{HashWriter, Hashed};
+use self::core::core::{Block, BlockHeader};
+use self::core::global::{self, ChainTypes};
+use self::core::libtx::{self, ProofBuilder};
+use self::core::pow::{self, Difficulty};
+use self::core::ser::Writer;
+use self::keychain::{ExtKeychain, ExtKeychainPath, Keychain};
+use self::util::secp::pedersen::Commitment;
+use chrono::Duration;
+use grin_chain as chain;
+use grin_core as core;
+use grin_keychain as keychain;
+use grin_util as util;
+
+fn prepare_block(kc: &ExtKeychain, prev: &BlockHeader, chain: &Chain, diff: u64) -> Block {
+	let key_id = ExtKeychainPath::new(1, diff as u32, 0, 0, 0).to_identifier();
+	let reward = libtx::reward::output(
+		kc,
+		&ProofBuilder::new(kc),
+		&key_id,
+		0,
+		false,
+		prev.height + 1,
+	)
+	.unwrap();
+	let mut b = Block::new(prev, &[], Difficulty::from_num(diff), reward).unwrap();
+	b.header.timestamp = prev.timestamp + Duration::seconds(60);
+	b.header.pow.total_difficulty = prev.total_difficulty() + Difficulty::from_num(diff);
+	b.header.pow.proof = pow::Proof::random(global::proofsize());
+	chain.set_txhashset_roots(&mut b).unwrap();
+	b
+}
+
+// Bloom filter with the bits of the commitment set, as documented for BloomFilter.
+fn bloom_filter(commit: &Commitment, bytes: usize, hashes: u32) -> BloomFilter {
+	let mut hasher = HashWriter::default();
+	hasher.write_fixed_bytes(commit).unwrap();
+	let hash = hasher.into_hash();
+	let mut h = [0u8; 8];
+	h.copy_from_slice(&hash.as_ref()[0..8]);
+	let h1 = u64::from_le_bytes(h);
+	h.copy_from_slice(&hash.as_ref()[8..16]);
+	let h2 = u64::from_le_bytes(h);
+	let m = bytes as u64 * 8;
+	let mut bits = vec![0u8; bytes];
+	for i in 0..hashes as u64 {
+		let n = h1.wrapping_add(i.wrapping_mul(h2)) % m;
+		bits[(n / 8) as usize] |= 1 << (n % 8);
+	}
+	BloomFilter { bits, hashes }
+}
+
+#[test]
+fn test_watch_filters() {
+	let chain_dir = ".grin.watch_filters";
+	util::init_test_logger();
+	clean_output_dir(chain_dir);
+	global::set_local_chain_type(ChainTypes::AutomatedTesting);
+	let kc = ExtKeychain::from_random_seed(false).unwrap();
+	let genesis = pow::mine_genesis_block().unwrap();
+
+	{
+		let chain = init_chain(chain_dir, genesis.clone());
+		let mut prev = chain.head_header().unwrap();
+		for n in 1..3 {
+			let b = prepare_block(&kc, &prev, &chain, n);
+			prev = b.header.clone();
+			chain.process_block(b, Options::SKIP_POW).unwrap();
+		}
+
+		// Watch the coinbase output of the next block
+		let b3 = prepare_block(&kc, &prev, &chain, 3);
+		let commit = b3.outputs()[0].commitment();
+
+		assert!(WatchMatcher::new(None, None).is_err());
+		assert!(WatchMatcher::new(Some(vec![]), None).is_err());
+		let list = chain
+			.add_watch_filter(
+				"list".to_string(),
+				WatchMatcher::new(Some(vec![commit, commit]), None).unwrap(),
+			)
+			.unwrap();
+		assert_eq!(list.id, 1);
+		assert_eq!(list.commitments, Some(1));
+		assert_eq!(list.created_height, 2);
+		let bloom = bloom_filter(&commit, 1024, 3);
+		assert!(bloom.contains(&commit));
+		let bloom = chain
+			.add_watch_filter(
+				"bloom".to_string(),
+				WatchMatcher::new(None, Some(bloom)).unwrap(),
+			)
+			.unwrap();
+		assert_eq!(bloom.id, 2);
+		assert_eq!(bloom.bloom_bytes, Some(1024));
+
+		chain.process_block(b3.clone(), Options::SKIP_POW).unwrap();
+
+		// Reorg b3 out with a heavier fork
+		let fork_head = chain.get_header_by_height(2).unwrap();
+		let b3_fork = prepare_block(&kc, &fork_head, &chain, 30);
+		chain
+			.process_block(b3_fork.clone(), Options::SKIP_POW)
+			.unwrap();
+		let b4_fork = prepare_block(&kc, &b3_fork.header, &chain, 40);
+		chain.process_block(b4_fork, Options::SKIP_POW).unwrap();
+		assert_eq!(chain.head().unwrap().height, 4);
+
+		for id in &[list.id, bloom.id] {
+			let page = chain.watch_events(*id, 0, 100).unwrap();
+			let kinds: Vec<_> = page.events.iter().map(|e| e.kind).collect();
+			assert_eq!(
+				kinds,
+				vec![WatchEventKind::Created, WatchEventKind::Reorged]
+			);
+			assert!(page.events.iter().all(|e| e.commit == commit));
+			assert!(page.events.iter().all(|e| e.block_hash == b3.hash()));
+			assert_eq!(page.events[1].seq, 1);
+			assert_eq!(page.next_cursor, 2);
+		}
+
+		// Paging by cursor
+		let page = chain.watch_events(list.id, 0, 1).unwrap();
+		assert_eq!(page.events.len(), 1);
+		let page = chain.watch_events(list.id, page.next_cursor, 1).unwrap();
+		assert_eq!(page.events[0].kind, WatchEventKind::Reorged);
+		let page = chain.watch_events(list.id, page.next_cursor, 1).unwrap();
+		assert!(page.events.is_empty());
+		assert_eq!(page.next_cursor, 2);
+
+		chain.delete_watch_filter(bloom.id).unwrap();
+		assert!(chain.watch_events(bloom.id, 0, 100).is_err());
+		assert!(chain.delete_watch_filter(bloom.id).is_err());
+	}
+
+	// Filters and their events are persisted
+	{
+		let chain = init_chain(chain_dir, genesis);
+		let filters = chain.watch_filters().unwrap();
+		assert_eq!(filters.len(), 1);
+		assert_eq!(filters[0].name, "list");
+		assert_eq!(filters[0].events, 2);
+		assert_eq!(chain.watch_events(1, 0, 100).unwrap().events.len(), 2);
+	}
+
+	clean_output_dir(chain_dir);
+}
@@ -40,6 +40,26 @@ fn comments() -> HashMap<String, String> {
 		.to_string(),
 	);
 
+	retval.insert(
+		"db_encryption_key_path".to_string(),
+		"
+#key file to encrypt the chain and peer databases and the txhashset and header
+#PMMR files at rest, generated if missing.
+#the databases have to be removed and synced again to turn the encryption on or off.
+"
+		.to_string(),
+	);
+
+	retval.insert(
+		"db_encryption_keystore_entry".to_string(),
+		"
+#alternatively, name of the OS keystore entry holding the encryption key, generated if
+#missing. uses the macOS keychain, or the Secret Service through secret-tool on Linux.
+#db_encryption_keystore_entry = \"mwc_chain\"
+"
+		.to_string(),
+	);
+
 	retval.insert(
 		"api_http_addr".to_string(),
 		"
@@ -48,6 +68,17 @@ fn comments() -> HashMap<String, String> {
 #private key for the TLS certificate
 #tls_certificate_key = \"\"
 
+#CA bundle (PEM) that signs the TLS client certificates. If set, the API asks the clients
+#for a certificate. The client certificates are read-only (GET requests and 'get_' owner
+#methods) unless their SHA256 fingerprint is listed at tls_client_owner_certs. The clients
+#without a certificate are read-only too.
+#tls_client_ca_file = \"\"
+#reject the connections without a valid client certificate
+#tls_client_cert_required = false
+#SHA256 fingerprints (hex, with or without ':') of the client certificates with the full
+#owner API access
+#tls_client_owner_certs = []
+
 #the address on which services will listen, e.g. Transaction Pool
 "
 		.to_string(),
@@ -65,6 +96,26 @@ fn comments() -> HashMap<String, String> {
 	retval.insert(
 		"foreign_api_secret_path".to_string(),
 		"
+#path of the append only audit log of the owner API calls (method or route, caller,
+#source IP and outcome, the rejected requests included), the entries are hash
+#chained to detect the modifications
+#api_audit_log_path = \"api_audit.log\"
+
+#max API requests per minute from a single IP, the owner API is not limited
+#api_rate_limit_rpm = 600
+#max heavyweight API requests (txhashset and output queries) per minute from a single IP
+#api_heavy_rate_limit_rpm = 60
+
+#readiness probe /v2/health/ready fails if the node is more blocks behind the peers
+#or has less connected peers
+#health_max_blocks_behind = 5
+#health_min_peers = 1
+
+#listen address of the gRPC foreign API (tip, blocks, outputs, transaction push and
+#the accepted blocks stream), the node must be built with the grpc feature. The foreign
+#API secret, the TLS certificate and the API rate limits apply to the gRPC calls too.
+#grpc_api_addr = \"127.0.0.1:3421\"
+
 #path of the secret token used by the Foreign API to authenticate the calls
 #comment the it to disable basic auth
 "
@@ -87,6 +138,8 @@ fn comments() -> HashMap<String, String> {
 #parameters used for mining as well as wallet output coinbase maturity. Can be:
 #AutomatedTesting - For CI builds and instant blockchain creation
 #UserTesting - For regular user testing (cuckoo 16)
+#CustomTesting - For integration tests and private devnets, automated testing
+#parameters with the [server.consensus_overrides] applied
 #Floonet - For the long term floonet test network
 #Mainnet - For mainnet
 "
@@ -108,6 +161,18 @@ fn comments() -> HashMap<String, String> {
 		"archive_mode".to_string(),
 		"
 #run the node in \"full archive\" mode (default is fast-sync, pruned node)
+#archive nodes sync all blocks from genesis, keep every spent output and
+#serve the full chain history to other nodes
+"
+		.to_string(),
+	);
+
+	retval.insert(
+		"low_memory_mode".to_string(),
+		"
+#run the node in the low memory mode, for the devices with about 1GB of RAM (Raspberry Pi).
+#the txhashset is validated in small windows, the db file grows in smaller steps and the
+#verifier and header caches are disabled. Validation and sync are slower. (default is false)
 "
 		.to_string(),
 	);
@@ -128,6 +193,24 @@ fn comments() -> HashMap<String, String> {
 		.to_string(),
 	);
 
+	retval.insert(
+		"header_sync_parallel_peers".to_string(),
+		"
+#number of peers to download the headers from in parallel during header sync.
+#1 means single peer header sync. Parallel header sync requires the header cache. (default is 1)
+"
+		.to_string(),
+	);
+
+	retval.insert(
+		"pibd_sync".to_string(),
+		"
+#download the txhashset as segments from all the peers supporting it, instead of
+#a single zip archive from one peer. Falls back to the zip archive if no peer serves segments. (default is false)
+"
+		.to_string(),
+	);
+
 	retval.insert(
 		"duration_sync_short".to_string(),
 		"
@@ -156,7 +239,7 @@ fn comments() -> HashMap<String, String> {
 	retval.insert(
 		"libp2p_enabled".to_string(),
 		"
-#Start gossipsub libp2p node. Node will be started only if Tor is enabled. Default value: true
+#Start gossipsub libp2p node. Node will be started only if Tor or clearnet transport is enabled. Default value: true
 "
 		.to_string(),
 	);
@@ -169,6 +252,115 @@ fn comments() -> HashMap<String, String> {
 			.to_string(),
 	);
 
+	retval.insert(
+		"libp2p_node_api".to_string(),
+		"
+#Serve selected foreign API methods (get_tip, get_header, get_kernel, get_outputs, push_transaction,
+#get_mailbox_messages) to the wallets over libp2p. Default value: true
+"
+		.to_string(),
+	);
+
+	retval.insert(
+		"libp2p_tx_receipts".to_string(),
+		"
+#Publish receipts for transactions accepted into the pool to the libp2p TxReceipts topic,
+#so wallets can learn that their transactions propagated. Only the receipts for the
+#transactions known to the pool or the chain are relayed. Default value: false
+"
+		.to_string(),
+	);
+
+	retval.insert(
+		"libp2p_mailbox".to_string(),
+		"
+#Store the encrypted direct messages between the wallets (libp2p Mailbox topic) until the
+#recipients fetch them. Storage is bounded per recipient, messages are paid with the
+#integrity fees. Default value: true
+"
+		.to_string(),
+	);
+
+	retval.insert(
+		"libp2p_clearnet_port".to_string(),
+		"
+#TCP port for the clearnet libp2p transport. With clearnet transport the libp2p node can run
+#without Tor. If not set, libp2p node works only over Tor.
+#libp2p_clearnet_port = 3419
+"
+		.to_string(),
+	);
+
+	retval.insert(
+		"libp2p_clearnet_address".to_string(),
+		"
+#Public clearnet address of this node that is announced to the libp2p peers, multiaddress format.
+#libp2p_clearnet_address = \"/ip4/1.2.3.4/tcp/3419\"
+"
+		.to_string(),
+	);
+
+	retval.insert(
+		"libp2p_clearnet_seeds".to_string(),
+		"
+#Clearnet libp2p seeds in the format <onion_address>@<multiaddress>. Needed to bootstrap without Tor.
+#libp2p_clearnet_seeds = [\"xxxx.onion@/ip4/1.2.3.4/tcp/3419\"]
+"
+		.to_string(),
+	);
+
+	retval.insert(
+		"libp2p_message_dedup_ttl".to_string(),
+		"
+#TTL in seconds for the libp2p message dedup cache. The same message payload received again
+#during that time is dropped before validation. Default value: 120
+#libp2p_message_dedup_ttl = 120
+"
+		.to_string(),
+	);
+
+	retval.insert(
+		"libp2p_gossip_trace_size".to_string(),
+		"
+#Debug mode, the last libp2p_gossip_trace_size gossip messages are traced with their ID, topic,
+#source peer, validation verdict and handling time. The trace is available with the owner API
+#get_gossip_trace. Default value: disabled
+#libp2p_gossip_trace_size = 1000
+"
+		.to_string(),
+	);
+
+	retval.insert(
+		"libp2p_forward_bandwidth".to_string(),
+		"
+#Bandwidth limit in bytes per second for forwarding of the libp2p messages that pay the integrity fee.
+#Every forwarded message is counted once per gossipsub mesh peer. When the limit is reached, the messages
+#with the higher fee are forwarded first. Messages that wait longer than half of the gossipsub message
+#cache time (gossip_history_length heartbeats) are forwarded before the others. Default value: no limit
+#libp2p_forward_bandwidth = 100000
+"
+		.to_string(),
+	);
+
+	retval.insert(
+		"libp2p_relay_deny_topics".to_string(),
+		"
+#Emergency lever against the abuse of the relay network. Gossip messages for these topics
+#are not processed and not relayed. Can be updated at runtime with the owner API set_relay_deny_list.
+#libp2p_relay_deny_topics = [\"SwapMarketplace\"]
+"
+		.to_string(),
+	);
+
+	retval.insert(
+		"libp2p_relay_deny_message_ids".to_string(),
+		"
+#Gossip message ID patterns that are not processed and not relayed, '*' matches any characters.
+#libp2p_relay_deny_message_ids = [\"1a2b*\"]
+"
+		.to_string(),
+	);
+
 	retval.insert(
 		"run_tui".to_string(),
 		"
@@ -213,7 +405,27 @@ fn comments() -> HashMap<String, String> {
 #The url where a POST request will be sent when a new block is received by a peer.
 #block_received_url = \"http://127.0.0.1:8080/block\"
 
-#The number of worker threads that will be assigned to making the http requests.
+#The url where a POST request will be sent when a transaction is accepted to the txpool.
+#The transaction pool events are sent once per minute for the same transaction and event,
+#up to 20 requests per second, the rest are dropped.
+#tx_pool_accepted_url = \"http://127.0.0.1:8080/poolaccepted\"
+
+#The url where a POST request will be sent when a transaction is rejected by the pool,
+#the reason of the rejection is included.
+#tx_pool_rejected_url = \"http://127.0.0.1:8080/poolrejected\"
+
+#The url where a POST request will be sent when a stem transaction is relayed to the next peer.
+#tx_stem_broadcast_url = \"http://127.0.0.1:8080/stem\"
+
+#The url where a POST request will be sent when a transaction is evicted from the txpool
+#by a transaction paying a higher fee rate.
+#tx_evicted_url = \"http://127.0.0.1:8080/evicted\"
+
+#The url where a POST request will be sent on the libp2p peer events: peer is connected or
+#disconnected, gossip message from the peer is rejected, dialing the peer failed.
+#libp2p_peer_event_url = \"http://127.0.0.1:8080/libp2p\"
+
+#The number of http requests that can run at once, they share the node tokio runtime.
 "
 		.to_string(),
 	);
@@ -226,6 +438,126 @@ fn comments() -> HashMap<String, String> {
 		.to_string(),
 	);
 
+	retval.insert(
+		"[server.chain_config]".to_string(),
+		"
+#########################################
+### CHAIN CONFIGURATION               ###
+#########################################
+"
+		.to_string(),
+	);
+
+	retval.insert(
+		"background_compaction".to_string(),
+		"
+#compact the chain in background, in short chunks between the block arrivals,
+#instead of compacting it at once on a random block
+"
+		.to_string(),
+	);
+
+	retval.insert(
+		"compaction_interval_secs".to_string(),
+		"
+#target interval between the background compactions, seconds. The first one
+#runs at startup
+"
+		.to_string(),
+	);
+
+	retval.insert(
+		"compaction_max_chunk_ms".to_string(),
+		"
+#max duration of a single background compaction chunk, milliseconds
+"
+		.to_string(),
+	);
+
+	retval.insert(
+		"orphans_max_size_bytes".to_string(),
+		"
+#memory cap of the orphan blocks pool, bytes. When it is reached the orphans
+#furthest from the chain head are evicted first
+"
+		.to_string(),
+	);
+
+	retval.insert(
+		"dump_rejected_blocks".to_string(),
+		"
+#dump the blocks rejected as invalid, with the failing validation stage, the previous
+#header and the expected roots, to the forensics directory under the db_root
+"
+		.to_string(),
+	);
+
+	retval.insert(
+		"max_rejected_blocks".to_string(),
+		"
+#number of the last rejected blocks kept, in memory and in the forensics directory
+"
+		.to_string(),
+	);
+
+	retval.insert(
+		"[server.consensus_overrides]".to_string(),
+		"
+#########################################
+### CONSENSUS OVERRIDES               ###
+#########################################
+
+#consensus parameters of the CustomTesting chain, ignored by the other chains.
+#Unset values fall back to the automated testing ones. Available keys:
+#block_time_sec = 60 - target block time, seconds
+#difficulty_adjust_window = 60 - number of blocks of the difficulty adjustment
+#coinbase_maturity = 3 - blocks before a coinbase output can be spent
+#cut_through_horizon = 20 - blocks kept before the full local pruning
+#future_time_limit_sec = 720 - how far in the future a block timestamp is accepted
+"
+		.to_string(),
+	);
+
+	retval.insert(
+		"[server.utxo_snapshot]".to_string(),
+		"
+#########################################
+### UTXO SNAPSHOT                     ###
+#########################################
+
+#trusted txhashset snapshot (zip archive) to start from instead of downloading
+#the txhashset from the peers. The node follows the chain from the snapshot
+#right away and validates its history back to genesis in background.
+#path = \"/path/to/txhashset.zip\" - the snapshot archive
+#block_hash = \"...\" - hash of the block the snapshot is taken at
+#archive_hash = \"...\" - blake2b hash of the archive
+#signature = \"...\" - publisher signature of the hash of (block_hash, archive_hash)
+#the publisher key is not taken from here, it must be pinned in the
+#utxo_snapshot_trusted_keys file in the node home dir, one hex key per line.
+#If the snapshot history turns out to be invalid, the node stops.
+"
+		.to_string(),
+	);
+
+	retval.insert(
+		"[server.sandbox]".to_string(),
+		"
+#########################################
+### SANDBOX CONFIGURATION             ###
+#########################################
+
+#hardening of the node process on Linux (5.13+ for landlock), for public-facing
+#nodes. With this section the filesystem access is restricted to the listed paths
+#and the node home dir, and the syscalls not needed by a running node (exec,
+#ptrace, mounts, kernel modules...) are denied.
+#The logger and the UI threads start before the sandbox is applied and keep the
+#unrestricted filesystem access.
+#read_paths = [\"/etc\", \"/usr\", \"/lib\", \"/lib64\", \"/bin\", \"/proc\", \"/sys\", \"/dev\"]
+#write_paths = [\"/var/log/mwc\"]
+"
+		.to_string(),
+	);
+
 	retval.insert(
 		"[server.dandelion_config]".to_string(),
 		"
@@ -346,12 +678,196 @@ fn comments() -> HashMap<String, String> {
 #amount of incoming connections temporarily allowed to exceed peer_max_inbound_count
 #peer_listener_buffer_count = 8
 
+#max number of blocks served to a single peer per minute
+#serve_block_quota = 1200
+
+#max number of blocks served to all peers per minute. When it is reached,
+#every peer gets only its fair share, so a single syncing peer can't take all bandwidth
+#serve_block_capacity = 3600
+
+#max number of txhashset archives served to a single peer per hour
+#serve_txhashset_quota = 2
+
+#max number of tx messages (txs and tx kernel announcements) a single peer can relay
+#per minute, the messages above the quota are dropped
+#tx_relay_count_per_min = 600
+
+#max bytes of tx messages a single peer can relay per minute, must be above the
+#size of the largest transaction
+#tx_relay_bytes_per_min = 10000000
+
+#max number of peer addresses kept in the peer database, the least useful
+#ones (defunct, never connected, not connected for the longest time) are evicted first
+#peer_store_max_count = 10000
+
+#seconds after which a peer address we never managed to connect to is removed
+#from the peer database
+#peer_store_unconnected_expiration = 259200
+
+#named peer groups with their own connection limits and relay policies. The node
+#keeps min_connections to the members of a group, drops the connections above
+#max_connections, and relays the txs / blocks only to the groups allowing them.
+#The group without peers takes all the peers not listed in the other groups.
+#[[server.p2p_config.peer_groups]]
+#name = \"internal\"
+#peers = [\"10.0.0.2:3414\", \"10.0.0.3:3414\"]
+#min_connections = 2
+#relay_txs = true
+#relay_blocks = true
+#[[server.p2p_config.peer_groups]]
+#name = \"public\"
+#max_connections = 16
+#relay_txs = false
+#relay_blocks = true
+
 # 15 = Bit flags for FULL_NODE
 #This structure needs to be changed internally, to make it more configurable
 
 # A preferred dandelion_peer, mainly used for testing dandelion
 # dandelion_peer = \"10.0.0.1:13144\"
 
+"
+		.to_string(),
+	);
+
+	retval.insert(
+		"[server.p2p_config.libp2p]".to_string(),
+		"
+#########################################
+### LIBP2P GOSSIPSUB CONFIGURATION    ###
+#########################################
+#Values out of the valid ranges are clamped at startup. For high-latency Tor links
+#a longer heartbeat and a larger history are preferable.
+"
+		.to_string(),
+	);
+
+	retval.insert(
+		"gossip_heartbeat_secs".to_string(),
+		"
+#gossipsub heartbeat interval in seconds, 1..60
+"
+		.to_string(),
+	);
+
+	retval.insert(
+		"gossip_mesh_n".to_string(),
+		"
+#target number of the gossipsub mesh peers, 4..100
+"
+		.to_string(),
+	);
+
+	retval.insert(
+		"gossip_mesh_n_low".to_string(),
+		"
+#min number of the mesh peers, 2..gossip_mesh_n
+"
+		.to_string(),
+	);
+
+	retval.insert(
+		"gossip_mesh_n_high".to_string(),
+		"
+#max number of the mesh peers, gossip_mesh_n..200. The node keeps that many libp2p connections
+"
+		.to_string(),
+	);
+
+	retval.insert(
+		"gossip_history_length".to_string(),
+		"
+#number of heartbeats the messages are kept at the gossip cache, 3..100
+"
+		.to_string(),
+	);
+
+	retval.insert(
+		"gossip_max_message_size".to_string(),
+		"
+#max gossip message size in bytes, 1024..4194304
+"
+		.to_string(),
+	);
+
+	retval.insert(
+		"gossip_flood_publish".to_string(),
+		"
+#publish own messages to all the topic peers, not only to the mesh peers.
+#Relay-heavy nodes with a dense mesh might disable it to save the bandwidth
+"
+		.to_string(),
+	);
+
+	retval.insert(
+		"pinned_peers".to_string(),
+		"
+#onion addresses of the preferred peers (for example your wallets) that are kept
+#connected and redialed with a short backoff. They are never evicted from the peer list.
+#pinned_peers = [\"<onion address>\"]
+"
+		.to_string(),
+	);
+
+	retval.insert(
+		"[server.p2p_config.peer_targets]".to_string(),
+		"
+#########################################
+### DYNAMIC PEER CONNECTION TARGETS   ###
+#########################################
+#When enabled, the node keeps between the min targets and peer_max_inbound_count /
+#peer_max_outbound_count connections, depending on its load. The targets are cut when
+#the CPU, the validation backlog or the bandwidth is over its limit, and grow back
+#while there is headroom.
+"
+		.to_string(),
+	);
+
+	retval.insert(
+		"auto_adjust_peers".to_string(),
+		"
+#adjust the connection targets to the load, otherwise the max counts are used
+"
+		.to_string(),
+	);
+
+	retval.insert(
+		"min_inbound_target".to_string(),
+		"
+#lowest target of the inbound connections
+"
+		.to_string(),
+	);
+
+	retval.insert(
+		"min_outbound_target".to_string(),
+		"
+#lowest target of the outbound connections
+"
+		.to_string(),
+	);
+
+	retval.insert(
+		"peer_bandwidth_limit".to_string(),
+		"
+#bandwidth available for the peer connections in bytes per second, sent and received
+#peer_bandwidth_limit = 1000000
+"
+		.to_string(),
+	);
+
+	retval.insert(
+		"max_cpu_load".to_string(),
+		"
+#CPU load in percent above which the connection targets are reduced
+"
+		.to_string(),
+	);
+
+	retval.insert(
+		"max_validation_backlog".to_string(),
+		"
+#number of the blocks waiting for validation above which the connection targets are reduced
 "
 		.to_string(),
 	);
@@ -385,7 +901,8 @@ fn comments() -> HashMap<String, String> {
 	retval.insert(
 		"max_pool_size".to_string(),
 		"
-#maximum number of transactions allowed in the pool
+#maximum number of transactions allowed in the pool, when the pool is full
+#the transactions with the lowest fee rate are evicted for the better paying ones
 "
 		.to_string(),
 	);
@@ -406,6 +923,97 @@ fn comments() -> HashMap<String, String> {
 		.to_string(),
 	);
 
+	retval.insert(
+		"tx_policy_url".to_string(),
+		"
+#url of an external transaction policy plugin, consulted before a transaction
+#is accepted and relayed. The transaction summary is posted as json, the
+#plugin replies with \"Accept\", {\"Reject\": \"reason\"} or {\"FeeFloor\": fee}.
+#tx_policy_url = \"http://127.0.0.1:3420/policy\"
+"
+		.to_string(),
+	);
+
+	retval.insert(
+		"tx_policy_timeout_ms".to_string(),
+		"
+#timeout in milliseconds of the transaction policy plugin call, the
+#transaction is rejected if the plugin doesn't answer in time
+"
+		.to_string(),
+	);
+
+	retval.insert(
+		"replace_by_fee".to_string(),
+		"
+#accept a transaction spending the same inputs as the txpool transactions if it
+#pays a higher fee (replace-by-fee), the replaced transactions and the transactions
+#depending on them are evicted. Disabled by default, set to true to opt in
+"
+		.to_string(),
+	);
+
+	retval.insert(
+		"rbf_fee_rate_premium".to_string(),
+		"
+#fee rate premium in percent the replacement transaction must pay over the
+#replaced transactions. It must also pay at least their total fee.
+"
+		.to_string(),
+	);
+
+	retval.insert(
+		"journal".to_string(),
+		"
+#journal the txpool transactions in the data dir, so they are restored
+#(and revalidated against the chain) when the node restarts
+"
+		.to_string(),
+	);
+
+	retval.insert(
+		"acceptance_policy".to_string(),
+		"
+#acceptance policy of the pool: \"Default\" for the relay rules, \"Strict\" for
+#the strict_* limits below on top of them (for the mining pools)
+"
+		.to_string(),
+	);
+
+	retval.insert(
+		"strict_fee_base".to_string(),
+		"
+#strict policy: base fee that's accepted into the pool, accept_fee_base if not set
+#strict_fee_base = 2000000
+"
+		.to_string(),
+	);
+
+	retval.insert(
+		"strict_max_tx_weight".to_string(),
+		"
+#strict policy: max weight of a transaction, only the consensus limit if not set
+#strict_max_tx_weight = 1000
+"
+		.to_string(),
+	);
+
+	retval.insert(
+		"strict_allow_height_locked".to_string(),
+		"
+#strict policy: accept the transactions with height locked kernels
+"
+		.to_string(),
+	);
+
+	retval.insert(
+		"strict_allow_nrd".to_string(),
+		"
+#strict policy: accept the transactions with no recent duplicate (NRD) kernels
+"
+		.to_string(),
+	);
+
 	retval.insert(
 		"[server.stratum_mining_config]".to_string(),
 		"
@@ -437,6 +1045,27 @@ fn comments() -> HashMap<String, String> {
 		"
 #the amount of time, in seconds, to attempt to mine on a particular
 #header before stopping and re-collecting transactions from the pool
+#(the maximum age of a mining job)
+"
+		.to_string(),
+	);
+
+	retval.insert(
+		"job_refresh_fee_threshold".to_string(),
+		"
+#rebuild the mining job before attempt_time_per_block expires once the fees
+#of the transactions added to the pool since the current job reach this
+#amount, in nanocoins. 0 disables the fee driven refresh
+"
+		.to_string(),
+	);
+
+	retval.insert(
+		"incremental_jobs".to_string(),
+		"
+#send the jobs rebuilt at the same height as the changes of the previous job
+#(the pre_pow patch, the kernels and outputs added) to the miners that log in
+#with \"incremental_jobs\": true, and keep the current job if no transaction changed
 "
 		.to_string(),
 	);
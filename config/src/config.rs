@@ -183,7 +183,7 @@ impl GlobalConfig {
 					.unwrap()
 					.wallet_listener_url = "http://127.0.0.1:13415".to_owned();
 			}
-			global::ChainTypes::UserTesting => {
+			global::ChainTypes::UserTesting | global::ChainTypes::CustomTesting => {
 				defaults.api_http_addr = "127.0.0.1:23413".to_owned();
 				defaults.libp2p_port = Some(23417);
 				defaults.p2p_config.port = 23414;
@@ -203,6 +203,9 @@ impl GlobalConfig {
 				panic!("Can't run automated testing directly");
 			}
 		}
+		if *chain_type == global::ChainTypes::CustomTesting {
+			defaults.consensus_overrides = Some(global::ConsensusOverrides::default());
+		}
 		defaults_conf
 	}
 
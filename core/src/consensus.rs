@@ -158,7 +158,9 @@ pub fn header_version(height: u64) -> HeaderVersion {
 			}
 		}
 		// Note!!!! We need that to cover NRD tests.
-		global::ChainTypes::AutomatedTesting | global::ChainTypes::UserTesting => {
+		global::ChainTypes::AutomatedTesting
+		| global::ChainTypes::UserTesting
+		| global::ChainTypes::CustomTesting => {
 			if height < TESTING_FIRST_HARD_FORK {
 				HeaderVersion(1)
 			} else if height < TESTING_SECOND_HARD_FORK {
@@ -172,6 +174,17 @@ pub fn header_version(height: u64) -> HeaderVersion {
 	}
 }
 
+/// Height NRD kernels are valid from. NRD kernels need a hard fork to be activated
+/// on the MWC mainnet and floonet, until it is scheduled here they are not valid there.
+pub fn nrd_activation_height() -> u64 {
+	match global::get_chain_type() {
+		global::ChainTypes::Mainnet | global::ChainTypes::Floonet => u64::MAX,
+		global::ChainTypes::AutomatedTesting
+		| global::ChainTypes::UserTesting
+		| global::ChainTypes::CustomTesting => TESTING_THIRD_HARD_FORK,
+	}
+}
+
 /// Check whether the block version is valid at a given height.
 /// Currently we only use the default version. No hard forks planned.
 pub fn valid_header_version(height: u64, version: HeaderVersion) -> bool {
@@ -185,7 +198,9 @@ pub fn valid_header_version(height: u64, version: HeaderVersion) -> bool {
 			}
 		}
 		// Note!!!! We need that to cover NRD tests.
-		global::ChainTypes::AutomatedTesting | global::ChainTypes::UserTesting => {
+		global::ChainTypes::AutomatedTesting
+		| global::ChainTypes::UserTesting
+		| global::ChainTypes::CustomTesting => {
 			if height < TESTING_FIRST_HARD_FORK {
 				version == HeaderVersion(1)
 			} else if height < TESTING_SECOND_HARD_FORK {
@@ -343,7 +358,7 @@ where
 
 	// Get the timestamp delta across the window
 	let ts_delta: u64 =
-		diff_data[DIFFICULTY_ADJUST_WINDOW as usize].timestamp - diff_data[0].timestamp;
+		diff_data[global::difficulty_adjust_window() as usize].timestamp - diff_data[0].timestamp;
 
 	// Get the difficulty sum of the last DIFFICULTY_ADJUST_WINDOW elements
 	let diff_sum: u64 = diff_data
@@ -353,13 +368,14 @@ where
 		.sum();
 
 	// adjust time delta toward goal subject to dampening and clamping
+	let block_time_window = global::block_time_window();
 	let adj_ts = clamp(
-		damp(ts_delta, BLOCK_TIME_WINDOW, DIFFICULTY_DAMP_FACTOR),
-		BLOCK_TIME_WINDOW,
+		damp(ts_delta, block_time_window, DIFFICULTY_DAMP_FACTOR),
+		block_time_window,
 		CLAMP_FACTOR,
 	);
 	// minimum difficulty avoids getting stuck due to dampening
-	let difficulty = max(MIN_DIFFICULTY, diff_sum * BLOCK_TIME_SEC / adj_ts);
+	let difficulty = max(MIN_DIFFICULTY, diff_sum * global::block_time_sec() / adj_ts);
 
 	HeaderInfo::from_diff_scaling(Difficulty::from_num(difficulty), sec_pow_scaling)
 }
@@ -376,7 +392,7 @@ pub fn secondary_pow_scaling(height: u64, diff_data: &[HeaderInfo]) -> u32 {
 
 	// compute ideal 2nd_pow_fraction in pct and across window
 	let target_pct = secondary_pow_ratio(height);
-	let target_count = DIFFICULTY_ADJUST_WINDOW * target_pct;
+	let target_count = global::difficulty_adjust_window() * target_pct;
 
 	// Get the secondary count across the window, adjusting count toward goal
 	// subject to dampening and clamping.
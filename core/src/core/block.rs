@@ -460,14 +460,14 @@ pub struct UntrustedBlockHeader(BlockHeader);
 impl Readable for UntrustedBlockHeader {
 	fn read<R: Reader>(reader: &mut R) -> Result<UntrustedBlockHeader, ser::Error> {
 		let header = read_block_header(reader)?;
-		if header.timestamp
-			> Utc::now() + Duration::seconds(12 * (consensus::BLOCK_TIME_SEC as i64))
-		{
+		let ftl = global::future_time_limit_sec();
+		if header.timestamp > Utc::now() + Duration::seconds(ftl as i64) {
 			// refuse blocks more than 12 blocks intervals in future (as in bitcoin)
 			// TODO add warning in p2p code if local time is too different from peers
 			let error_msg = format!(
-				"block header {} validation error: block time is more than 12 blocks in future",
-				header.hash()
+				"block header {} validation error: block time is more than {} seconds in future",
+				header.hash(),
+				ftl
 			);
 			error!("{}", error_msg);
 			return Err(ser::Error::CorruptedData(error_msg));
@@ -847,14 +847,14 @@ impl Block {
 	}
 
 	// NRD kernels are not valid if the global feature flag is disabled.
-	// NRD kernels were introduced in HF3 and are not valid for block version < 4.
-	// Blocks prior to HF3 containing any NRD kernel(s) are invalid.
+	// NRD kernels are activated by a hard fork, see consensus::nrd_activation_height.
+	// Blocks prior to the activation height containing any NRD kernel(s) are invalid.
 	fn verify_nrd_kernels_for_header_version(&self) -> Result<(), Error> {
 		if self.kernels().iter().any(|k| k.is_nrd()) {
 			if !global::is_nrd_enabled() {
 				return Err(Error::NRDKernelNotEnabled);
 			}
-			if self.header.version < HeaderVersion(4) {
+			if self.header.height < consensus::nrd_activation_height() {
 				return Err(Error::NRDKernelPreHF3);
 			}
 		}
@@ -17,9 +17,15 @@
 use crate::core::block::{Block, BlockHeader, Error, UntrustedBlockHeader};
 use crate::core::hash::{DefaultHashable, Hashed};
 use crate::core::id::ShortIdentifiable;
-use crate::core::{Output, ShortId, TxKernel};
-use crate::ser::{self, read_multi, Readable, Reader, VerifySortedAndUnique, Writeable, Writer};
+use crate::core::{Output, ShortId, Transaction, TxKernel};
+use crate::ser::{
+	self, read_multi, ProtocolVersion, Readable, Reader, VerifySortedAndUnique, Writeable, Writer,
+};
 use rand::{thread_rng, Rng};
+use std::collections::HashSet;
+
+/// Max number of transactions a compact block can carry in full.
+pub const MAX_PREFILLED_TXS: usize = 32;
 
 /// Container for full (full) outputs and kernels and kern_ids for a compact block.
 #[derive(Debug, Clone)]
@@ -31,6 +37,10 @@ pub struct CompactBlockBody {
 	/// List of transaction kernels, excluding those in the full list
 	/// (short_ids)
 	pub kern_ids: Vec<ShortId>,
+	/// Transactions sent in full because the receiver is likely missing them.
+	/// Their kernels are not part of kern_ids. Only sent to peers with protocol
+	/// version 4 or above.
+	pub prefilled: Vec<Transaction>,
 }
 
 impl CompactBlockBody {
@@ -38,12 +48,14 @@ impl CompactBlockBody {
 		out_full: Vec<Output>,
 		kern_full: Vec<TxKernel>,
 		kern_ids: Vec<ShortId>,
+		prefilled: Vec<Transaction>,
 		verify_sorted: bool,
 	) -> Result<Self, Error> {
 		let body = CompactBlockBody {
 			out_full,
 			kern_full,
 			kern_ids,
+			prefilled,
 		};
 
 		if verify_sorted {
@@ -100,10 +112,24 @@ impl Readable for CompactBlockBody {
 		let kern_full = read_multi(reader, kern_full_len)?;
 		let kern_ids = read_multi(reader, kern_id_len)?;
 
+		let prefilled = match reader.protocol_version().value() {
+			0..=3 => vec![],
+			4..=ProtocolVersion::MAX => {
+				let prefilled_len = reader.read_u64()?;
+				if prefilled_len > MAX_PREFILLED_TXS as u64 {
+					return Err(ser::Error::TooLargeReadErr(format!(
+						"CompactBlockBody has too many prefilled txs: {}",
+						prefilled_len
+					)));
+				}
+				read_multi(reader, prefilled_len)?
+			}
+		};
+
 		// Initialize compact block body, verifying sort order.
-		let body = CompactBlockBody::init(out_full, kern_full, kern_ids, true).map_err(|e| {
-			ser::Error::CorruptedData(format!("Unable to read compact block, {}", e))
-		})?;
+		let body = CompactBlockBody::init(out_full, kern_full, kern_ids, prefilled, true).map_err(
+			|e| ser::Error::CorruptedData(format!("Unable to read compact block, {}", e)),
+		)?;
 
 		Ok(body)
 	}
@@ -134,6 +160,27 @@ impl Writeable for CompactBlockBody {
 		self.kern_full.write(writer)?;
 		self.kern_ids.write(writer)?;
 
+		match writer.protocol_version().value() {
+			0..=3 => {
+				if !self.prefilled.is_empty() {
+					return Err(ser::Error::UnsupportedProtocolVersion(format!(
+						"get version {}, expecting version >=4 for prefilled txs",
+						writer.protocol_version().value()
+					)));
+				}
+			}
+			4..=ProtocolVersion::MAX => {
+				if self.prefilled.len() > MAX_PREFILLED_TXS {
+					return Err(ser::Error::TooLargeWriteErr(format!(
+						"CompactBlockBody has too many prefilled txs: {}",
+						self.prefilled.len()
+					)));
+				}
+				writer.write_u64(self.prefilled.len() as u64)?;
+				self.prefilled.write(writer)?;
+			}
+		}
+
 		Ok(())
 	}
 }
@@ -182,10 +229,16 @@ impl CompactBlock {
 	pub fn out_full(&self) -> &[Output] {
 		&self.body.out_full
 	}
-}
 
-impl From<Block> for CompactBlock {
-	fn from(block: Block) -> Self {
+	/// Get the transactions sent in full
+	pub fn prefilled(&self) -> &[Transaction] {
+		&self.body.prefilled
+	}
+
+	/// Builds a compact block from a full block, sending the provided transactions
+	/// in full instead of their kernel short_ids. Transactions that are not entirely
+	/// part of the block are skipped, at most MAX_PREFILLED_TXS are kept.
+	pub fn with_prefilled(block: Block, txs: Vec<Transaction>) -> CompactBlock {
 		let header = block.header.clone();
 		let nonce = thread_rng().gen();
 
@@ -196,19 +249,41 @@ impl From<Block> for CompactBlock {
 			.cloned()
 			.collect::<Vec<_>>();
 
+		let block_kernels: HashSet<_> = block
+			.kernels()
+			.iter()
+			.filter(|k| !k.is_coinbase())
+			.map(|k| k.excess())
+			.collect();
+		let mut prefilled_kernels = HashSet::new();
+		let mut prefilled = vec![];
+		for tx in txs {
+			if prefilled.len() >= MAX_PREFILLED_TXS {
+				break;
+			}
+			let excesses: Vec<_> = tx.kernels().iter().map(|k| k.excess()).collect();
+			if excesses
+				.iter()
+				.all(|x| block_kernels.contains(x) && !prefilled_kernels.contains(x))
+			{
+				prefilled_kernels.extend(excesses);
+				prefilled.push(tx);
+			}
+		}
+
 		let mut kern_full = vec![];
 		let mut kern_ids = vec![];
 
 		for k in block.kernels() {
 			if k.is_coinbase() {
 				kern_full.push(k.clone());
-			} else {
+			} else if !prefilled_kernels.contains(&k.excess()) {
 				kern_ids.push(k.short_id(&header.hash(), nonce));
 			}
 		}
 
 		// Initialize a compact block body and sort everything.
-		let body = CompactBlockBody::init(out_full, kern_full, kern_ids, false)
+		let body = CompactBlockBody::init(out_full, kern_full, kern_ids, prefilled, false)
 			.expect("sorting, not verifying");
 
 		CompactBlock {
@@ -217,6 +292,32 @@ impl From<Block> for CompactBlock {
 			body,
 		}
 	}
+
+	/// The body as understood by peers not supporting prefilled txs, the kernels
+	/// of the prefilled txs are sent as short_ids.
+	fn body_without_prefilled(&self) -> CompactBlockBody {
+		let hash = self.hash();
+		let mut kern_ids = self.body.kern_ids.clone();
+		for tx in &self.body.prefilled {
+			for k in tx.kernels() {
+				kern_ids.push(k.short_id(&hash, self.nonce));
+			}
+		}
+		CompactBlockBody::init(
+			self.body.out_full.clone(),
+			self.body.kern_full.clone(),
+			kern_ids,
+			vec![],
+			false,
+		)
+		.expect("sorting, not verifying")
+	}
+}
+
+impl From<Block> for CompactBlock {
+	fn from(block: Block) -> Self {
+		CompactBlock::with_prefilled(block, vec![])
+	}
 }
 
 /// Implementation of Writeable for a compact block, defines how to write the
@@ -228,7 +329,12 @@ impl Writeable for CompactBlock {
 
 		if writer.serialization_mode() != ser::SerializationMode::Hash {
 			writer.write_u64(self.nonce)?;
-			self.body.write(writer)?;
+			match writer.protocol_version().value() {
+				0..=3 if !self.body.prefilled.is_empty() => {
+					self.body_without_prefilled().write(writer)?
+				}
+				_ => self.body.write(writer)?,
+			}
 		}
 
 		Ok(())
@@ -99,12 +99,29 @@ impl MerkleProof {
 		root: Hash,
 		element: &dyn PMMRIndexHashable,
 		node_pos: u64,
+	) -> Result<(), MerkleProofError> {
+		let node_hash = if node_pos > self.mmr_size {
+			element.hash_with_index(self.mmr_size)
+		} else {
+			element.hash_with_index(node_pos - 1)
+		};
+		self.verify_hash(root, node_hash, node_pos)
+	}
+
+	/// Verifies the Merkle proof against the provided root hash, starting
+	/// from the (already computed) hash of the node at the provided position.
+	/// Useful when the node is not a leaf and its children are not known.
+	pub fn verify_hash(
+		&self,
+		root: Hash,
+		node_hash: Hash,
+		node_pos: u64,
 	) -> Result<(), MerkleProofError> {
 		let mut proof = self.clone();
 		// calculate the peaks once as these are based on overall MMR size
 		// (and will not change)
 		let peaks_pos = pmmr::peaks(self.mmr_size);
-		proof.verify_consume(root, element, node_pos, &peaks_pos)
+		proof.verify_consume(root, node_hash, node_pos, &peaks_pos)
 	}
 
 	/// Consumes the Merkle proof while verifying it.
@@ -113,16 +130,10 @@ impl MerkleProof {
 	fn verify_consume(
 		&mut self,
 		root: Hash,
-		element: &dyn PMMRIndexHashable,
+		node_hash: Hash,
 		node_pos: u64,
 		peaks_pos: &[u64],
 	) -> Result<(), MerkleProofError> {
-		let node_hash = if node_pos > self.mmr_size {
-			element.hash_with_index(self.mmr_size)
-		} else {
-			element.hash_with_index(node_pos - 1)
-		};
-
 		// handle special case of only a single entry in the MMR
 		// (no siblings to hash together)
 		if self.path.is_empty() {
@@ -136,23 +147,25 @@ impl MerkleProof {
 		let sibling = self.path.remove(0);
 		let (parent_pos, sibling_pos) = pmmr::family(node_pos);
 
-		if let Ok(x) = peaks_pos.binary_search(&node_pos) {
-			let parent = if x == peaks_pos.len() - 1 {
+		let parent = if let Ok(x) = peaks_pos.binary_search(&node_pos) {
+			if x == peaks_pos.len() - 1 {
 				(sibling, node_hash)
 			} else {
 				(node_hash, sibling)
-			};
-			self.verify(root, &parent, parent_pos)
+			}
 		} else if parent_pos > self.mmr_size {
-			let parent = (sibling, node_hash);
-			self.verify(root, &parent, parent_pos)
+			(sibling, node_hash)
+		} else if pmmr::is_left_sibling(sibling_pos) {
+			(sibling, node_hash)
 		} else {
-			let parent = if pmmr::is_left_sibling(sibling_pos) {
-				(sibling, node_hash)
-			} else {
-				(node_hash, sibling)
-			};
-			self.verify(root, &parent, parent_pos)
-		}
+			(node_hash, sibling)
+		};
+
+		let parent_hash = if parent_pos > self.mmr_size {
+			parent.hash_with_index(self.mmr_size)
+		} else {
+			parent.hash_with_index(parent_pos - 1)
+		};
+		self.verify_consume(root, parent_hash, parent_pos, peaks_pos)
 	}
 }
@@ -29,6 +29,14 @@ pub trait Backend<T: PMMRable> {
 	/// help the implementation.
 	fn append(&mut self, data: &T, hashes: Vec<Hash>) -> Result<(), String>;
 
+	/// Append the root hash of a subtree that has been pruned entirely, ending
+	/// at the provided position. None of the nodes below it are stored.
+	/// Used when rebuilding a pruned MMR from segments.
+	fn append_pruned_subtree(&mut self, hash: Hash, pos: u64) -> Result<(), String>;
+
+	/// Append a single (non-leaf) hash to the backend storage.
+	fn append_hash(&mut self, hash: Hash) -> Result<(), String>;
+
 	/// Rewind the backend state to a previous position, as if all append
 	/// operations after that had been canceled. Expects a position in the PMMR
 	/// to rewind to as well as bitmaps representing the positions added and
@@ -215,6 +215,39 @@ where
 		Ok(elmt_pos)
 	}
 
+	/// Push the root hash of a fully pruned subtree ending at the provided
+	/// position. The subtree must start right after the current last position.
+	/// Computes new related peaks at the same time if applicable.
+	pub fn push_pruned_subtree(&mut self, hash: Hash, pos: u64) -> Result<(), String> {
+		if bintree_leftmost(pos) != self.last_pos + 1 {
+			return Err(format!(
+				"pruned subtree at {} does not follow last pos {}",
+				pos, self.last_pos
+			));
+		}
+		self.backend.append_pruned_subtree(hash, pos)?;
+		self.last_pos = pos;
+
+		// hash with all immediately preceding peaks, the same way push does
+		let mut current_pos = pos;
+		let mut current_hash = hash;
+		loop {
+			let (parent, sibling) = family(current_pos);
+			if parent != current_pos + 1 {
+				break;
+			}
+			let left_hash = self
+				.backend
+				.get_from_file(sibling)
+				.ok_or("missing left sibling in tree, should not have been pruned")?;
+			current_hash = (left_hash, current_hash).hash_with_index(parent - 1);
+			self.backend.append_hash(current_hash)?;
+			current_pos = parent;
+			self.last_pos = parent;
+		}
+		Ok(())
+	}
+
 	/// Saves a snapshot of the MMR tagged with the block hash.
 	/// Specifically - snapshots the utxo file as we need this rewound before
 	/// sending the txhashset zip file to another node for fast-sync.
@@ -17,7 +17,8 @@
 use std::marker;
 
 use crate::core::hash::{Hash, ZERO_HASH};
-use crate::core::pmmr::pmmr::{bintree_rightmost, peaks};
+use crate::core::merkle_proof::MerkleProof;
+use crate::core::pmmr::pmmr::{bintree_rightmost, family_branch, peaks};
 use crate::core::pmmr::{is_leaf, Backend};
 use crate::ser::{PMMRIndexHashable, PMMRable};
 
@@ -96,6 +97,16 @@ where
 		}
 	}
 
+	/// Get the data element from the underlying MMR file, ignoring the leafset.
+	/// Some entries may have been removed from the leafset but not yet pruned from the file.
+	pub fn get_data_from_file(&self, pos: u64) -> Option<T::E> {
+		if pos > self.last_pos || !is_leaf(pos) {
+			None
+		} else {
+			self.backend.get_data_from_file(pos)
+		}
+	}
+
 	/// Iterator over current (unpruned, unremoved) leaf positions.
 	pub fn leaf_pos_iter(&self) -> impl Iterator<Item = u64> + '_ {
 		self.backend.leaf_pos_iter()
@@ -146,6 +157,50 @@ where
 		self.last_pos
 	}
 
+	/// Build a Merkle proof for the element at the given position, the same as
+	/// `PMMR::merkle_proof`, without the write access to the backend.
+	pub fn merkle_proof(&self, pos: u64) -> Result<MerkleProof, String> {
+		if !is_leaf(pos) {
+			return Err(format!("not a mmr leaf at pos {}", pos));
+		}
+		self.get_hash(pos)
+			.ok_or_else(|| format!("no element at pos {}", pos))?;
+
+		let family_branch = family_branch(pos, self.last_pos);
+		let mut path = family_branch
+			.iter()
+			.filter_map(|x| self.get_from_file(x.1))
+			.collect::<Vec<_>>();
+		let peak_pos = match family_branch.last() {
+			Some(&(x, _)) => x,
+			None => pos,
+		};
+
+		// peaks on the left, then the bagged peaks on the right
+		let rhs = peaks(self.last_pos)
+			.into_iter()
+			.filter(|x| *x > peak_pos)
+			.filter_map(|x| self.backend.get_from_file(x))
+			.rev()
+			.fold(None, |res, peak| match res {
+				None => Some(peak),
+				Some(rhash) => Some((peak, rhash).hash_with_index(self.unpruned_size())),
+			});
+		let mut peak_path = peaks(self.last_pos)
+			.into_iter()
+			.filter(|x| *x < peak_pos)
+			.filter_map(|x| self.backend.get_from_file(x))
+			.collect::<Vec<_>>();
+		peak_path.extend(rhs);
+		peak_path.reverse();
+		path.append(&mut peak_path);
+
+		Ok(MerkleProof {
+			mmr_size: self.unpruned_size(),
+			path,
+		})
+	}
+
 	/// Helper function which returns un-pruned nodes from the insertion index
 	/// forward
 	/// returns last pmmr index returned along with data
@@ -0,0 +1,534 @@
+// Copyright 2021 The MWC Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Segments of a PMMR, used to download the txhashset in parcels from
+//! multiple peers. A segment covers a fixed range of leaves and carries
+//! the unpruned leaf data, the hashes of the pruned subtrees in that range
+//! and a Merkle proof linking the segment to the root of the full MMR.
+
+use std::collections::{BTreeMap, HashMap};
+
+use crate::core::hash::Hash;
+use crate::core::merkle_proof::MerkleProof;
+use crate::core::pmmr::{
+	bintree_leftmost, bintree_postorder_height, family_branch, insertion_to_pmmr_index, is_leaf,
+	n_leaves, peaks, Backend, ReadonlyPMMR,
+};
+use crate::ser::{self, PMMRIndexHashable, PMMRable, Readable, Reader, Writeable, Writer};
+
+/// Max supported segment height, 2^15 leaves per segment is way beyond
+/// anything that fits in a single p2p message.
+pub const MAX_SEGMENT_HEIGHT: u8 = 15;
+
+/// Possible errors when building or validating a segment.
+#[derive(Fail, Clone, Debug, Eq, PartialEq)]
+pub enum SegmentError {
+	/// Segment height is out of the supported range
+	#[fail(display = "Segment height {} is not supported", _0)]
+	InvalidHeight(u8),
+	/// Segment index is beyond the last leaf of the MMR
+	#[fail(display = "Segment {} is beyond the end of the MMR", _0)]
+	NonExistent(u64),
+	/// Hash required to build or validate the segment is missing
+	#[fail(display = "Missing hash at pos {}", _0)]
+	MissingHash(u64),
+	/// Leaf data required to build the segment is missing
+	#[fail(display = "Missing leaf data at pos {}", _0)]
+	MissingLeaf(u64),
+	/// Segment content is not sorted, overlaps or is out of range
+	#[fail(display = "Malformed segment, {}", _0)]
+	Malformed(String),
+	/// Segment root does not match the MMR root
+	#[fail(display = "Segment root mismatch")]
+	RootMismatch,
+}
+
+/// Identifies a segment by its height (log2 of the number of leaves covered)
+/// and its index among the segments of the same height.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq, Serialize, Deserialize)]
+pub struct SegmentIdentifier {
+	/// Height of the segment, the segment covers 2^height leaves
+	pub height: u8,
+	/// Zero-based index of the segment
+	pub idx: u64,
+}
+
+impl SegmentIdentifier {
+	/// Number of segments of the provided height required to cover
+	/// an MMR of the provided size.
+	pub fn count_for_mmr_size(height: u8, mmr_size: u64) -> u64 {
+		let leaves = n_leaves(mmr_size);
+		let capacity = 1u64 << height;
+		(leaves + capacity - 1) / capacity
+	}
+
+	/// Maximum number of leaves covered by this segment.
+	pub fn capacity(&self) -> u64 {
+		1 << self.height
+	}
+
+	/// Zero-based index of the first leaf covered by this segment.
+	pub fn first_leaf_idx(&self) -> u64 {
+		self.idx << self.height
+	}
+
+	/// Whether this segment covers a full subtree of the MMR of the
+	/// provided size. Only the last segment may be partial.
+	pub fn is_full(&self, mmr_size: u64) -> bool {
+		self.first_leaf_idx() + self.capacity() <= n_leaves(mmr_size)
+	}
+
+	/// First and last positions (inclusive) of the MMR nodes covered by
+	/// this segment in an MMR of the provided size.
+	pub fn pos_range(&self, mmr_size: u64) -> Result<(u64, u64), SegmentError> {
+		if self.height > MAX_SEGMENT_HEIGHT {
+			return Err(SegmentError::InvalidHeight(self.height));
+		}
+		if self.first_leaf_idx() >= n_leaves(mmr_size) {
+			return Err(SegmentError::NonExistent(self.idx));
+		}
+		let first_pos = insertion_to_pmmr_index(self.first_leaf_idx() + 1);
+		let last_pos = if self.is_full(mmr_size) {
+			first_pos + 2 * self.capacity() - 2
+		} else {
+			mmr_size
+		};
+		Ok((first_pos, last_pos))
+	}
+}
+
+impl Writeable for SegmentIdentifier {
+	fn write<W: Writer>(&self, writer: &mut W) -> Result<(), ser::Error> {
+		writer.write_u8(self.height)?;
+		writer.write_u64(self.idx)
+	}
+}
+
+impl Readable for SegmentIdentifier {
+	fn read<R: Reader>(reader: &mut R) -> Result<SegmentIdentifier, ser::Error> {
+		let height = reader.read_u8()?;
+		if height > MAX_SEGMENT_HEIGHT {
+			return Err(ser::Error::CorruptedData(format!(
+				"segment height {} is not supported",
+				height
+			)));
+		}
+		let idx = reader.read_u64()?;
+		Ok(SegmentIdentifier { height, idx })
+	}
+}
+
+/// A segment of a PMMR. Leaf data is provided for every leaf with data
+/// available, the other nodes are covered by the root hashes of the
+/// pruned subtrees. If the whole segment lies below a pruned subtree, the
+/// segment carries the single hash of that subtree root instead.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Segment<T> {
+	identifier: SegmentIdentifier,
+	hash_pos: Vec<u64>,
+	hashes: Vec<Hash>,
+	leaf_pos: Vec<u64>,
+	leaf_data: Vec<T>,
+	proof: Vec<Hash>,
+}
+
+impl<T> Segment<T> {
+	/// Identifier of the segment.
+	pub fn identifier(&self) -> SegmentIdentifier {
+		self.identifier
+	}
+
+	/// Positions and hashes of the pruned subtree roots in this segment.
+	pub fn hashes(&self) -> impl Iterator<Item = (u64, &Hash)> + '_ {
+		self.hash_pos.iter().cloned().zip(self.hashes.iter())
+	}
+
+	/// Positions and data of the leaves in this segment.
+	pub fn leaves(&self) -> impl Iterator<Item = (u64, &T)> + '_ {
+		self.leaf_pos.iter().cloned().zip(self.leaf_data.iter())
+	}
+
+	/// Number of leaves with data in this segment.
+	pub fn leaves_len(&self) -> usize {
+		self.leaf_pos.len()
+	}
+
+	/// All the segment entries in MMR order, either a pruned subtree root
+	/// hash or a leaf.
+	pub fn entries(&self) -> Vec<(u64, SegmentEntry<'_, T>)> {
+		let mut entries: Vec<_> = self
+			.hashes()
+			.map(|(pos, h)| (pos, SegmentEntry::Hash(h)))
+			.chain(self.leaves().map(|(pos, l)| (pos, SegmentEntry::Leaf(l))))
+			.collect();
+		entries.sort_by_key(|(pos, _)| *pos);
+		entries
+	}
+}
+
+/// A single entry of a segment.
+pub enum SegmentEntry<'a, T> {
+	/// Root hash of a pruned subtree
+	Hash(&'a Hash),
+	/// Leaf data
+	Leaf(&'a T),
+}
+
+impl<T> Segment<T>
+where
+	T: PMMRable<E = T>,
+{
+	/// Build the segment with the provided identifier from the PMMR.
+	pub fn from_pmmr<B: Backend<T>>(
+		identifier: SegmentIdentifier,
+		pmmr: &ReadonlyPMMR<'_, T, B>,
+	) -> Result<Segment<T>, SegmentError> {
+		let mmr_size = pmmr.unpruned_size();
+		let (first_pos, last_pos) = identifier.pos_range(mmr_size)?;
+		let mut segment = Segment {
+			identifier,
+			hash_pos: vec![],
+			hashes: vec![],
+			leaf_pos: vec![],
+			leaf_data: vec![],
+			proof: vec![],
+		};
+
+		if identifier.is_full(mmr_size) && pmmr.get_from_file(last_pos).is_none() {
+			// The whole segment lies below a pruned subtree, provide the root of that subtree.
+			let (anchor, hash) = family_branch(last_pos, mmr_size)
+				.into_iter()
+				.find_map(|(parent, _)| pmmr.get_from_file(parent).map(|h| (parent, h)))
+				.ok_or(SegmentError::MissingHash(last_pos))?;
+			segment.hash_pos.push(anchor);
+			segment.hashes.push(hash);
+			segment.proof = Segment::node_proof(pmmr, anchor)?;
+			return Ok(segment);
+		}
+
+		// Collect the leaf data once, so we know which subtrees are fully pruned.
+		let mut data = BTreeMap::new();
+		for pos in (first_pos..=last_pos).filter(|x| is_leaf(*x)) {
+			if let Some(elmt) = pmmr.get_data_from_file(pos) {
+				data.insert(pos, elmt);
+			}
+		}
+
+		let roots = if identifier.is_full(mmr_size) {
+			vec![last_pos]
+		} else {
+			peaks(mmr_size)
+				.into_iter()
+				.filter(|x| *x >= first_pos)
+				.collect()
+		};
+		for root in roots {
+			segment.collect(pmmr, &mut data, root)?;
+		}
+
+		segment.proof = Segment::proof_from_pmmr(identifier, pmmr)?;
+		Ok(segment)
+	}
+
+	/// Build the segment with the provided identifier from all its leaves and
+	/// the merkle proof of the segment. Used when the leaves are rebuilt
+	/// locally and only the proof is provided by the peer.
+	pub fn from_leaves(
+		identifier: SegmentIdentifier,
+		leaves: Vec<(u64, T)>,
+		proof: Vec<Hash>,
+	) -> Segment<T> {
+		let (leaf_pos, leaf_data) = leaves.into_iter().unzip();
+		Segment {
+			identifier,
+			hash_pos: vec![],
+			hashes: vec![],
+			leaf_pos,
+			leaf_data,
+			proof,
+		}
+	}
+
+	/// Merkle proof of the segment with the provided identifier against the
+	/// PMMR root, without the segment content.
+	pub fn proof_from_pmmr<B: Backend<T>>(
+		identifier: SegmentIdentifier,
+		pmmr: &ReadonlyPMMR<'_, T, B>,
+	) -> Result<Vec<Hash>, SegmentError> {
+		let mmr_size = pmmr.unpruned_size();
+		let (first_pos, last_pos) = identifier.pos_range(mmr_size)?;
+		if identifier.is_full(mmr_size) {
+			Segment::node_proof(pmmr, last_pos)
+		} else {
+			// The left peaks, the segment peaks get bagged with these.
+			peaks(mmr_size)
+				.into_iter()
+				.filter(|x| *x < first_pos)
+				.map(|x| pmmr.get_from_file(x).ok_or(SegmentError::MissingHash(x)))
+				.collect()
+		}
+	}
+
+	// Walk down the subtree at pos, collecting the leaves with data and
+	// the roots of the subtrees without any.
+	fn collect<B: Backend<T>>(
+		&mut self,
+		pmmr: &ReadonlyPMMR<'_, T, B>,
+		data: &mut BTreeMap<u64, T>,
+		pos: u64,
+	) -> Result<(), SegmentError> {
+		if is_leaf(pos) {
+			let elmt = data.remove(&pos).ok_or(SegmentError::MissingLeaf(pos))?;
+			self.leaf_pos.push(pos);
+			self.leaf_data.push(elmt);
+			return Ok(());
+		}
+		let leftmost = bintree_leftmost(pos);
+		if data.range(leftmost..=pos).next().is_some() {
+			let height = bintree_postorder_height(pos);
+			self.collect(pmmr, data, pos - (1 << height))?;
+			self.collect(pmmr, data, pos - 1)
+		} else {
+			let hash = pmmr
+				.get_from_file(pos)
+				.ok_or(SegmentError::MissingHash(pos))?;
+			self.hash_pos.push(pos);
+			self.hashes.push(hash);
+			Ok(())
+		}
+	}
+
+	// Merkle proof for the node at pos, same layout as PMMR::merkle_proof.
+	fn node_proof<B: Backend<T>>(
+		pmmr: &ReadonlyPMMR<'_, T, B>,
+		pos: u64,
+	) -> Result<Vec<Hash>, SegmentError> {
+		let mmr_size = pmmr.unpruned_size();
+		let branch = family_branch(pos, mmr_size);
+		let mut proof = branch
+			.iter()
+			.map(|(_, sibling)| {
+				pmmr.get_from_file(*sibling)
+					.ok_or(SegmentError::MissingHash(*sibling))
+			})
+			.collect::<Result<Vec<_>, _>>()?;
+
+		let peak_pos = branch.last().map(|(x, _)| *x).unwrap_or(pos);
+		let all_peaks = peaks(mmr_size);
+		let mut rhs = None;
+		for peak in all_peaks.iter().filter(|x| **x > peak_pos).rev() {
+			let hash = pmmr
+				.get_from_file(*peak)
+				.ok_or(SegmentError::MissingHash(*peak))?;
+			rhs = match rhs {
+				None => Some(hash),
+				Some(rhash) => Some((hash, rhash).hash_with_index(mmr_size)),
+			};
+		}
+		let mut peak_path = all_peaks
+			.iter()
+			.filter(|x| **x < peak_pos)
+			.map(|x| pmmr.get_from_file(*x).ok_or(SegmentError::MissingHash(*x)))
+			.collect::<Result<Vec<_>, _>>()?;
+		if let Some(rhs) = rhs {
+			peak_path.push(rhs);
+		}
+		peak_path.reverse();
+		proof.append(&mut peak_path);
+		Ok(proof)
+	}
+}
+
+impl<T> Segment<T>
+where
+	T: PMMRIndexHashable,
+{
+	/// Validate the segment against the root of the MMR of the provided size.
+	/// Checks the segment content is well formed and complete and that
+	/// it hashes up to the MMR root.
+	pub fn validate(&self, mmr_size: u64, mmr_root: Hash) -> Result<(), SegmentError> {
+		let (first_pos, last_pos) = self.identifier.pos_range(mmr_size)?;
+		if self.hash_pos.len() != self.hashes.len() || self.leaf_pos.len() != self.leaf_data.len() {
+			return Err(SegmentError::Malformed("length mismatch".to_owned()));
+		}
+		let full = self.identifier.is_full(mmr_size);
+
+		// A segment below a pruned subtree only carries the root of that subtree.
+		if full && self.leaf_pos.is_empty() && self.hash_pos.len() == 1 {
+			let anchor = self.hash_pos[0];
+			if anchor > last_pos {
+				if !family_branch(last_pos, mmr_size)
+					.iter()
+					.any(|(parent, _)| *parent == anchor)
+				{
+					return Err(SegmentError::Malformed(format!(
+						"pos {} is not an ancestor of the segment",
+						anchor
+					)));
+				}
+				return self.validate_proof(mmr_size, mmr_root, anchor, self.hashes[0]);
+			}
+		}
+
+		// Entries must be in order, within range and must not overlap.
+		let mut nodes = HashMap::new();
+		let mut prev_pos = first_pos - 1;
+		let mut entries: Vec<(u64, Hash)> = self
+			.hashes()
+			.map(|(pos, h)| (pos, *h))
+			.chain(
+				self.leaves()
+					.map(|(pos, l)| (pos, l.hash_with_index(pos - 1))),
+			)
+			.collect();
+		if self.leaf_pos.iter().any(|x| !is_leaf(*x)) {
+			return Err(SegmentError::Malformed("leaf at non-leaf pos".to_owned()));
+		}
+		entries.sort_by_key(|(pos, _)| *pos);
+		for (pos, hash) in entries {
+			if pos > last_pos || bintree_leftmost(pos) <= prev_pos {
+				return Err(SegmentError::Malformed(format!("unexpected pos {}", pos)));
+			}
+			prev_pos = pos;
+			nodes.insert(pos, hash);
+		}
+
+		// Hash up the nodes we have all the children for.
+		for pos in first_pos..=last_pos {
+			let height = bintree_postorder_height(pos);
+			if height == 0 || nodes.contains_key(&pos) {
+				continue;
+			}
+			let left = nodes.get(&(pos - (1 << height)));
+			let right = nodes.get(&(pos - 1));
+			if let (Some(left), Some(right)) = (left, right) {
+				let hash = (*left, *right).hash_with_index(pos - 1);
+				nodes.insert(pos, hash);
+			}
+		}
+
+		if full {
+			let hash = *nodes
+				.get(&last_pos)
+				.ok_or(SegmentError::MissingHash(last_pos))?;
+			self.validate_proof(mmr_size, mmr_root, last_pos, hash)
+		} else {
+			// Bag the segment peaks, then the left peaks from the proof.
+			let mut root = None;
+			let segment_peaks = peaks(mmr_size)
+				.into_iter()
+				.filter(|x| *x >= first_pos)
+				.map(|x| nodes.get(&x).cloned().ok_or(SegmentError::MissingHash(x)))
+				.collect::<Result<Vec<_>, _>>()?;
+			for hash in segment_peaks.iter().rev().chain(self.proof.iter().rev()) {
+				root = match root {
+					None => Some(*hash),
+					Some(rhash) => Some((*hash, rhash).hash_with_index(mmr_size)),
+				};
+			}
+			if root == Some(mmr_root) {
+				Ok(())
+			} else {
+				Err(SegmentError::RootMismatch)
+			}
+		}
+	}
+
+	fn validate_proof(
+		&self,
+		mmr_size: u64,
+		mmr_root: Hash,
+		pos: u64,
+		hash: Hash,
+	) -> Result<(), SegmentError> {
+		let proof = MerkleProof {
+			mmr_size,
+			path: self.proof.clone(),
+		};
+		proof
+			.verify_hash(mmr_root, hash, pos)
+			.map_err(|_| SegmentError::RootMismatch)
+	}
+}
+
+impl<T: Writeable> Writeable for Segment<T> {
+	fn write<W: Writer>(&self, writer: &mut W) -> Result<(), ser::Error> {
+		self.identifier.write(writer)?;
+		writer.write_u64(self.hash_pos.len() as u64)?;
+		for pos in &self.hash_pos {
+			writer.write_u64(*pos)?;
+		}
+		for hash in &self.hashes {
+			hash.write(writer)?;
+		}
+		writer.write_u64(self.leaf_pos.len() as u64)?;
+		for pos in &self.leaf_pos {
+			writer.write_u64(*pos)?;
+		}
+		for elmt in &self.leaf_data {
+			elmt.write(writer)?;
+		}
+		writer.write_u64(self.proof.len() as u64)?;
+		for hash in &self.proof {
+			hash.write(writer)?;
+		}
+		Ok(())
+	}
+}
+
+impl<T: Readable> Readable for Segment<T> {
+	fn read<R: Reader>(reader: &mut R) -> Result<Segment<T>, ser::Error> {
+		let identifier = SegmentIdentifier::read(reader)?;
+		// A segment never holds more nodes than a full subtree of its height.
+		let max_len = 2 * identifier.capacity();
+
+		let read_len = |reader: &mut R, max: u64| -> Result<u64, ser::Error> {
+			let len = reader.read_u64()?;
+			if len > max {
+				return Err(ser::Error::TooLargeReadErr(format!(
+					"segment entries {} exceed {}",
+					len, max
+				)));
+			}
+			Ok(len)
+		};
+
+		let n_hashes = read_len(reader, max_len)?;
+		let mut hash_pos = Vec::with_capacity(n_hashes as usize);
+		for _ in 0..n_hashes {
+			hash_pos.push(reader.read_u64()?);
+		}
+		let hashes = ser::read_multi(reader, n_hashes)?;
+
+		let n_leaves = read_len(reader, identifier.capacity())?;
+		let mut leaf_pos = Vec::with_capacity(n_leaves as usize);
+		for _ in 0..n_leaves {
+			leaf_pos.push(reader.read_u64()?);
+		}
+		let leaf_data = ser::read_multi(reader, n_leaves)?;
+
+		// Proof is bounded by the height of the MMR plus its peaks.
+		let n_proof = read_len(reader, 128)?;
+		let proof = ser::read_multi(reader, n_proof)?;
+
+		Ok(Segment {
+			identifier,
+			hash_pos,
+			hashes,
+			leaf_pos,
+			leaf_data,
+			proof,
+		})
+	}
+}
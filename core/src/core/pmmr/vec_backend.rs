@@ -43,6 +43,15 @@ impl<T: PMMRable> Backend<T> for VecBackend<T> {
 		Ok(())
 	}
 
+	fn append_pruned_subtree(&mut self, _hash: Hash, _pos: u64) -> Result<(), String> {
+		Err("vec backend does not support pruned subtrees".to_owned())
+	}
+
+	fn append_hash(&mut self, hash: Hash) -> Result<(), String> {
+		self.hashes.push(hash);
+		Ok(())
+	}
+
 	fn get_hash(&self, position: u64) -> Option<Hash> {
 		if self.removed.contains(&position) {
 			None
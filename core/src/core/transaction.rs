@@ -25,6 +25,7 @@ use crate::ser::{
 use crate::{consensus, global};
 use enum_primitive::FromPrimitive;
 use keychain::{self, BlindingFactor};
+use schemars::JsonSchema;
 use std::cmp::Ordering;
 use std::cmp::{max, min};
 use std::convert::{TryFrom, TryInto};
@@ -38,7 +39,7 @@ use util::ToHex;
 
 /// Relative height field on NRD kernel variant.
 /// u16 representing a height between 1 and MAX (consensus::WEEK_HEIGHT).
-#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, JsonSchema)]
 pub struct NRDRelativeHeight(u16);
 
 impl DefaultHashable for NRDRelativeHeight {}
@@ -103,7 +104,7 @@ impl NRDRelativeHeight {
 }
 
 /// Various tx kernel variants.
-#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, JsonSchema)]
 pub enum KernelFeatures {
 	/// Plain kernel (the default for Grin txs).
 	Plain {
@@ -461,7 +462,7 @@ impl From<committed::Error> for Error {
 /// amount to zero.
 /// The signature signs the fee and the lock_height, which are retained for
 /// signature validation.
-#[derive(Serialize, Deserialize, Debug, Clone, Copy)]
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, JsonSchema)]
 pub struct TxKernel {
 	/// Options for a kernel's structure or use
 	pub features: KernelFeatures,
@@ -472,10 +473,12 @@ pub struct TxKernel {
 		serialize_with = "secp_ser::as_hex",
 		deserialize_with = "secp_ser::commitment_from_hex"
 	)]
+	#[schemars(with = "String")]
 	pub excess: Commitment,
 	/// The signature proving the excess is a valid public key, which signs
 	/// the transaction fee.
 	#[serde(with = "secp_ser::sig_serde")]
+	#[schemars(with = "String")]
 	pub excess_sig: secp::Signature,
 }
 
@@ -617,21 +620,29 @@ impl TxKernel {
 
 	/// Batch signature verification.
 	pub fn batch_sig_verify(tx_kernels: &[TxKernel]) -> Result<(), Error> {
+		let secp = static_secp_instance();
+		let secp = secp.lock();
+		TxKernel::batch_sig_verify_with_secp(&secp, tx_kernels)
+	}
+
+	/// Batch signature verification with the provided secp instance.
+	/// Batches can be verified in parallel, each with its own instance.
+	pub fn batch_sig_verify_with_secp(
+		secp: &secp::Secp256k1,
+		tx_kernels: &[TxKernel],
+	) -> Result<(), Error> {
 		let len = tx_kernels.len();
 		let mut sigs = Vec::with_capacity(len);
 		let mut pubkeys = Vec::with_capacity(len);
 		let mut msgs = Vec::with_capacity(len);
 
-		let secp = static_secp_instance();
-		let secp = secp.lock();
-
 		for tx_kernel in tx_kernels {
 			sigs.push(tx_kernel.excess_sig);
 			pubkeys.push(tx_kernel.excess.to_pubkey()?);
 			msgs.push(tx_kernel.msg_to_sign()?);
 		}
 
-		if !aggsig::verify_batch(&secp, &sigs, &msgs, &pubkeys) {
+		if !aggsig::verify_batch(secp, &sigs, &msgs, &pubkeys) {
 			return Err(Error::IncorrectSignature);
 		}
 
@@ -673,7 +684,7 @@ pub enum Weighting {
 }
 
 /// TransactionBody is a common abstraction for transaction and block
-#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, JsonSchema)]
 pub struct TransactionBody {
 	/// List of inputs spent by the transaction.
 	pub inputs: Inputs,
@@ -1138,7 +1149,7 @@ impl TransactionBody {
 }
 
 /// A transaction
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone, JsonSchema)]
 pub struct Transaction {
 	/// The kernel "offset" k2
 	/// excess is k1G after splitting the key k = k1 + k2
@@ -1146,6 +1157,7 @@ pub struct Transaction {
 		serialize_with = "secp_ser::as_hex",
 		deserialize_with = "secp_ser::blind_from_hex"
 	)]
+	#[schemars(with = "String")]
 	pub offset: BlindingFactor,
 	/// The transaction body - inputs/outputs/kernels
 	pub body: TransactionBody,
@@ -1557,7 +1569,7 @@ pub fn deaggregate(mk_tx: Transaction, txs: &[Transaction]) -> Result<Transactio
 /// A transaction input.
 ///
 /// Primarily a reference to an output being spent by the transaction.
-#[derive(Serialize, Deserialize, Debug, Clone, Copy)]
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, JsonSchema)]
 pub struct Input {
 	/// The features of the output being spent.
 	/// We will check maturity for coinbase output.
@@ -1567,6 +1579,7 @@ pub struct Input {
 		serialize_with = "secp_ser::as_hex",
 		deserialize_with = "secp_ser::commitment_from_hex"
 	)]
+	#[schemars(with = "String")]
 	pub commit: Commitment,
 }
 
@@ -1639,13 +1652,14 @@ impl Input {
 }
 
 /// We need to wrap commitments so they can be sorted with hashable_ord.
-#[derive(Serialize, Deserialize, Debug, Clone, Copy)]
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, JsonSchema)]
 #[serde(transparent)]
 pub struct CommitWrapper {
 	#[serde(
 		serialize_with = "secp_ser::as_hex",
 		deserialize_with = "secp_ser::commitment_from_hex"
 	)]
+	#[schemars(with = "String")]
 	commit: Commitment,
 }
 
@@ -1726,7 +1740,7 @@ impl CommitWrapper {
 	}
 }
 /// Wrapper around a vec of inputs.
-#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, JsonSchema)]
 #[serde(untagged)]
 pub enum Inputs {
 	/// Vec of commitments.
@@ -1849,7 +1863,7 @@ impl Inputs {
 // Enum of various supported kernel "features".
 enum_from_primitive! {
 	/// Various flavors of tx kernel.
-	#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+	#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, JsonSchema)]
 	#[repr(u8)]
 	pub enum OutputFeatures {
 		/// Plain output (the default for Grin txs).
@@ -1879,7 +1893,7 @@ impl Readable for OutputFeatures {
 /// transferred. The commitment is a blinded value for the output while the
 /// range proof guarantees the commitment includes a positive value without
 /// overflow and the ownership of the private key.
-#[derive(Debug, Copy, Clone, Serialize, Deserialize)]
+#[derive(Debug, Copy, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct Output {
 	/// Output identifier (features and commitment).
 	#[serde(flatten)]
@@ -1889,6 +1903,7 @@ pub struct Output {
 		serialize_with = "secp_ser::as_hex",
 		deserialize_with = "secp_ser::rangeproof_from_hex"
 	)]
+	#[schemars(with = "String")]
 	pub proof: RangeProof,
 }
 
@@ -2006,8 +2021,18 @@ impl Output {
 	/// Batch validates the range proofs using the commitments
 	pub fn batch_verify_proofs(commits: &[Commitment], proofs: &[RangeProof]) -> Result<(), Error> {
 		let secp = static_secp_instance();
-		secp.lock()
-			.verify_bullet_proof_multi(commits.to_vec(), proofs.to_vec(), None)?;
+		let secp = secp.lock();
+		Output::batch_verify_proofs_with_secp(&secp, commits, proofs)
+	}
+
+	/// Batch validates the range proofs with the provided secp instance.
+	/// Batches can be verified in parallel, each with its own instance.
+	pub fn batch_verify_proofs_with_secp(
+		secp: &secp::Secp256k1,
+		commits: &[Commitment],
+		proofs: &[RangeProof],
+	) -> Result<(), Error> {
+		secp.verify_bullet_proof_multi(commits.to_vec(), proofs.to_vec(), None)?;
 		Ok(())
 	}
 }
@@ -2021,7 +2046,7 @@ impl AsRef<OutputIdentifier> for Output {
 /// An output_identifier can be build from either an input _or_ an output and
 /// contains everything we need to uniquely identify an output being spent.
 /// Needed because it is not sufficient to pass a commitment around.
-#[derive(Serialize, Deserialize, Debug, Clone, Copy)]
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, JsonSchema)]
 pub struct OutputIdentifier {
 	/// Output features (coinbase vs. regular transaction output)
 	/// We need to include this when hashing to ensure coinbase maturity can be
@@ -2032,6 +2057,7 @@ pub struct OutputIdentifier {
 		serialize_with = "secp_ser::as_hex",
 		deserialize_with = "secp_ser::commitment_from_hex"
 	)]
+	#[schemars(with = "String")]
 	pub commit: Commitment,
 }
 
@@ -48,9 +48,15 @@ impl LruVerifierCache {
 	/// TODO how big should these caches be?
 	/// They need to be *at least* large enough to cover a maxed out block.
 	pub fn new() -> LruVerifierCache {
+		LruVerifierCache::with_capacity(50_000)
+	}
+
+	/// Cache of the provided number of the kernels and of the rangeproofs.
+	/// Zero capacity disables the cache, everything is verified again.
+	pub fn with_capacity(capacity: usize) -> LruVerifierCache {
 		LruVerifierCache {
-			kernel_sig_verification_cache: LruCache::new(50_000),
-			rangeproof_verification_cache: LruCache::new(50_000),
+			kernel_sig_verification_cache: LruCache::new(capacity),
+			rangeproof_verification_cache: LruCache::new(capacity),
 		}
 	}
 }
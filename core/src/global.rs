@@ -40,8 +40,9 @@ use util::OneTime;
 /// We negotiate compatible versions with each peer via Hand/Shake.
 /// Note: We also use a specific (possible different) protocol version
 /// for both the backend database and MMR data files.
-/// NOTE, grin bump the protocol version to 1000, but in any case fo far 1,2,3 are supported.
-pub const PROTOCOL_VERSION: ProtocolVersion = ProtocolVersion(3);
+/// NOTE, grin bump the protocol version to 1000, but in any case fo far 1,2,3,4 are supported.
+/// Version 4 adds prefilled transactions to compact blocks.
+pub const PROTOCOL_VERSION: ProtocolVersion = ProtocolVersion(4);
 
 /// Automated testing edge_bits
 pub const AUTOMATED_TESTING_MIN_EDGE_BITS: u8 = 10;
@@ -133,6 +134,9 @@ pub enum ChainTypes {
 	AutomatedTesting,
 	/// For User testing
 	UserTesting,
+	/// For integration tests and private devnets, automated testing parameters with the
+	/// consensus overrides loaded from the config (see `ConsensusOverrides`)
+	CustomTesting,
 	/// Protocol testing network
 	Floonet,
 	/// Main production network
@@ -145,6 +149,7 @@ impl ChainTypes {
 		match *self {
 			ChainTypes::AutomatedTesting => "auto".to_owned(),
 			ChainTypes::UserTesting => "user".to_owned(),
+			ChainTypes::CustomTesting => "custom".to_owned(),
 			ChainTypes::Floonet => "floo".to_owned(),
 			ChainTypes::Mainnet => "main".to_owned(),
 		}
@@ -157,28 +162,91 @@ impl Default for ChainTypes {
 	}
 }
 
+/// Consensus parameters of the `CustomTesting` chain, loaded from the
+/// `[server.consensus_overrides]` config section. Unset values fall back to
+/// the automated testing ones. Ignored by the other chain types.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq)]
+pub struct ConsensusOverrides {
+	/// Target block time, seconds
+	pub block_time_sec: Option<u64>,
+	/// Number of blocks used to calculate difficulty adjustments
+	pub difficulty_adjust_window: Option<u64>,
+	/// Number of blocks before a coinbase output can be spent
+	pub coinbase_maturity: Option<u64>,
+	/// Horizon at which we can cut-through and do full local pruning, blocks
+	pub cut_through_horizon: Option<u32>,
+	/// How far in the future a block timestamp is accepted, seconds
+	pub future_time_limit_sec: Option<u64>,
+}
+
+impl ConsensusOverrides {
+	/// Check the overrides define a chain that can be mined.
+	pub fn validate(&self) -> Result<(), String> {
+		if self.block_time_sec == Some(0) {
+			return Err("block_time_sec must be positive".to_string());
+		}
+		if self
+			.difficulty_adjust_window
+			.map(|w| w < 2)
+			.unwrap_or(false)
+		{
+			return Err("difficulty_adjust_window must be at least 2 blocks".to_string());
+		}
+		if self.future_time_limit_sec == Some(0) {
+			return Err("future_time_limit_sec must be positive".to_string());
+		}
+		let maturity = self
+			.coinbase_maturity
+			.unwrap_or(AUTOMATED_TESTING_COINBASE_MATURITY);
+		let horizon = self
+			.cut_through_horizon
+			.unwrap_or(AUTOMATED_TESTING_CUT_THROUGH_HORIZON);
+		if horizon as u64 <= maturity {
+			return Err(format!(
+				"cut_through_horizon {} must be above the coinbase_maturity {}",
+				horizon, maturity
+			));
+		}
+		Ok(())
+	}
+}
+
 lazy_static! {
 	/// Global chain_type that must be initialized once on node startup.
 	/// This is accessed via get_chain_type() which allows the global value
 	/// to be overridden on a per-thread basis (for testing).
 	pub static ref GLOBAL_CHAIN_TYPE: OneTime<ChainTypes> = OneTime::new();
 
+	/// Global consensus overrides of the CustomTesting chain.
+	pub static ref GLOBAL_CONSENSUS_OVERRIDES: OneTime<ConsensusOverrides> = OneTime::new();
+
 	/// Global feature flag for NRD kernel support.
 	/// If enabled NRD kernels are treated as valid after HF3 (based on header version).
 	/// If disabled NRD kernels are invalid regardless of header version or block height.
 	pub static ref GLOBAL_NRD_FEATURE_ENABLED: OneTime<bool> = OneTime::new();
 
+	/// Global low memory mode for the devices with little RAM.
+	/// If enabled the txhashset is validated in small windows, the LMDB map grows in
+	/// smaller steps and the in-memory caches are disabled.
+	pub static ref GLOBAL_LOW_MEMORY_MODE: OneTime<bool> = OneTime::new();
+
 	/// Running flag for MWC node.
 	pub static ref SERVER_RUNNING: Arc<AtomicBool> =
 			Arc::new(AtomicBool::new(true));
 }
 
 thread_local! {
-	/// Mainnet|Floonet|UserTesting|AutomatedTesting
+	/// Mainnet|Floonet|UserTesting|AutomatedTesting|CustomTesting
 	pub static CHAIN_TYPE: Cell<Option<ChainTypes>> = Cell::new(None);
 
+	/// Local consensus overrides of the CustomTesting chain.
+	pub static CONSENSUS_OVERRIDES: Cell<Option<ConsensusOverrides>> = Cell::new(None);
+
 	/// Local feature flag for NRD kernel support.
 	pub static NRD_FEATURE_ENABLED: Cell<Option<bool>> = Cell::new(None);
+
+	/// Local low memory mode.
+	pub static LOW_MEMORY_MODE: Cell<Option<bool>> = Cell::new(None);
 }
 
 /// Set the chain type on a per-thread basis via thread_local storage.
@@ -214,6 +282,41 @@ pub fn init_global_nrd_enabled(enabled: bool) {
 	GLOBAL_NRD_FEATURE_ENABLED.init(enabled)
 }
 
+/// One time initialization of the global low memory mode.
+/// Will panic if we attempt to re-initialize this (via OneTime).
+pub fn init_global_low_memory_mode(enabled: bool) {
+	GLOBAL_LOW_MEMORY_MODE.init(enabled)
+}
+
+/// One time initialization of the global consensus overrides.
+/// Will panic if we attempt to re-initialize this (via OneTime).
+pub fn init_global_consensus_overrides(overrides: ConsensusOverrides) {
+	GLOBAL_CONSENSUS_OVERRIDES.init(overrides)
+}
+
+/// Set the consensus overrides on a per-thread basis via thread_local storage.
+pub fn set_local_consensus_overrides(overrides: ConsensusOverrides) {
+	CONSENSUS_OVERRIDES.with(|o| o.set(Some(overrides)))
+}
+
+/// Consensus overrides of the CustomTesting chain.
+/// Look at thread local config first. If not set fallback to global config.
+/// Default to no overrides if global config unset.
+pub fn get_consensus_overrides() -> ConsensusOverrides {
+	CONSENSUS_OVERRIDES.with(|o| match o.get() {
+		None => {
+			if GLOBAL_CONSENSUS_OVERRIDES.is_init() {
+				let overrides = GLOBAL_CONSENSUS_OVERRIDES.borrow();
+				o.set(Some(overrides));
+				overrides
+			} else {
+				ConsensusOverrides::default()
+			}
+		}
+		Some(overrides) => overrides,
+	})
+}
+
 /// Explicitly enable the NRD global feature flag.
 pub fn set_local_nrd_enabled(enabled: bool) {
 	NRD_FEATURE_ENABLED.with(|flag| flag.set(Some(enabled)))
@@ -238,6 +341,29 @@ pub fn is_nrd_enabled() -> bool {
 	})
 }
 
+/// Set the low memory mode on a per-thread basis via thread_local storage.
+pub fn set_local_low_memory_mode(enabled: bool) {
+	LOW_MEMORY_MODE.with(|flag| flag.set(Some(enabled)))
+}
+
+/// Is the low memory mode enabled?
+/// Look at thread local config first. If not set fallback to global config.
+/// Default to false if global config unset.
+pub fn is_low_memory_mode() -> bool {
+	LOW_MEMORY_MODE.with(|flag| match flag.get() {
+		None => {
+			if GLOBAL_LOW_MEMORY_MODE.is_init() {
+				let global_flag = GLOBAL_LOW_MEMORY_MODE.borrow();
+				flag.set(Some(global_flag));
+				global_flag
+			} else {
+				false
+			}
+		}
+		Some(flag) => flag,
+	})
+}
+
 /// Return either a cuckoo context or a cuckatoo context
 /// Single change point
 /// MWC: We modify this to launch with cuckarood only on both floonet and mainnet
@@ -265,7 +391,7 @@ pub fn create_pow_context<T>(
 /// The minimum acceptable edge_bits
 pub fn min_edge_bits() -> u8 {
 	match get_chain_type() {
-		ChainTypes::AutomatedTesting => AUTOMATED_TESTING_MIN_EDGE_BITS,
+		ChainTypes::AutomatedTesting | ChainTypes::CustomTesting => AUTOMATED_TESTING_MIN_EDGE_BITS,
 		ChainTypes::UserTesting => USER_TESTING_MIN_EDGE_BITS,
 		_ => DEFAULT_MIN_EDGE_BITS,
 	}
@@ -276,7 +402,7 @@ pub fn min_edge_bits() -> u8 {
 /// base_edge_bits is a hard fork.
 pub fn base_edge_bits() -> u8 {
 	match get_chain_type() {
-		ChainTypes::AutomatedTesting => AUTOMATED_TESTING_MIN_EDGE_BITS,
+		ChainTypes::AutomatedTesting | ChainTypes::CustomTesting => AUTOMATED_TESTING_MIN_EDGE_BITS,
 		ChainTypes::UserTesting => USER_TESTING_MIN_EDGE_BITS,
 		_ => BASE_EDGE_BITS,
 	}
@@ -285,7 +411,7 @@ pub fn base_edge_bits() -> u8 {
 /// The proofsize
 pub fn proofsize() -> usize {
 	match get_chain_type() {
-		ChainTypes::AutomatedTesting => AUTOMATED_TESTING_PROOF_SIZE,
+		ChainTypes::AutomatedTesting | ChainTypes::CustomTesting => AUTOMATED_TESTING_PROOF_SIZE,
 		ChainTypes::UserTesting => USER_TESTING_PROOF_SIZE,
 		_ => PROOFSIZE,
 	}
@@ -296,6 +422,9 @@ pub fn coinbase_maturity() -> u64 {
 	match get_chain_type() {
 		ChainTypes::AutomatedTesting => AUTOMATED_TESTING_COINBASE_MATURITY,
 		ChainTypes::UserTesting => USER_TESTING_COINBASE_MATURITY,
+		ChainTypes::CustomTesting => get_consensus_overrides()
+			.coinbase_maturity
+			.unwrap_or(AUTOMATED_TESTING_COINBASE_MATURITY),
 		_ => COINBASE_MATURITY,
 	}
 }
@@ -305,6 +434,7 @@ pub fn initial_block_difficulty() -> u64 {
 	match get_chain_type() {
 		ChainTypes::AutomatedTesting => TESTING_INITIAL_DIFFICULTY,
 		ChainTypes::UserTesting => TESTING_INITIAL_DIFFICULTY,
+		ChainTypes::CustomTesting => TESTING_INITIAL_DIFFICULTY,
 		ChainTypes::Floonet => INITIAL_DIFFICULTY,
 		ChainTypes::Mainnet => INITIAL_DIFFICULTY,
 	}
@@ -314,6 +444,7 @@ pub fn initial_graph_weight() -> u32 {
 	match get_chain_type() {
 		ChainTypes::AutomatedTesting => TESTING_INITIAL_GRAPH_WEIGHT,
 		ChainTypes::UserTesting => TESTING_INITIAL_GRAPH_WEIGHT,
+		ChainTypes::CustomTesting => TESTING_INITIAL_GRAPH_WEIGHT,
 		ChainTypes::Floonet => graph_weight(0, SECOND_POW_EDGE_BITS) as u32,
 		ChainTypes::Mainnet => graph_weight(0, SECOND_POW_EDGE_BITS) as u32,
 	}
@@ -324,6 +455,7 @@ pub fn max_block_weight() -> u64 {
 	match get_chain_type() {
 		ChainTypes::AutomatedTesting => TESTING_MAX_BLOCK_WEIGHT,
 		ChainTypes::UserTesting => TESTING_MAX_BLOCK_WEIGHT,
+		ChainTypes::CustomTesting => TESTING_MAX_BLOCK_WEIGHT,
 		ChainTypes::Floonet => MAX_BLOCK_WEIGHT,
 		ChainTypes::Mainnet => MAX_BLOCK_WEIGHT,
 	}
@@ -340,15 +472,55 @@ pub fn cut_through_horizon() -> u32 {
 	match get_chain_type() {
 		ChainTypes::AutomatedTesting => AUTOMATED_TESTING_CUT_THROUGH_HORIZON,
 		ChainTypes::UserTesting => USER_TESTING_CUT_THROUGH_HORIZON,
+		ChainTypes::CustomTesting => get_consensus_overrides()
+			.cut_through_horizon
+			.unwrap_or(AUTOMATED_TESTING_CUT_THROUGH_HORIZON),
 		_ => CUT_THROUGH_HORIZON,
 	}
 }
 
+/// Target block time, seconds
+pub fn block_time_sec() -> u64 {
+	match get_chain_type() {
+		ChainTypes::CustomTesting => get_consensus_overrides()
+			.block_time_sec
+			.unwrap_or(BLOCK_TIME_SEC),
+		_ => BLOCK_TIME_SEC,
+	}
+}
+
+/// Number of blocks used to calculate difficulty adjustments
+pub fn difficulty_adjust_window() -> u64 {
+	match get_chain_type() {
+		ChainTypes::CustomTesting => get_consensus_overrides()
+			.difficulty_adjust_window
+			.unwrap_or(DIFFICULTY_ADJUST_WINDOW),
+		_ => DIFFICULTY_ADJUST_WINDOW,
+	}
+}
+
+/// Average time span of the difficulty adjustment window, seconds
+pub fn block_time_window() -> u64 {
+	difficulty_adjust_window() * block_time_sec()
+}
+
+/// How far in the future a block timestamp is accepted, seconds.
+/// 12 block intervals (as in bitcoin) unless overridden.
+pub fn future_time_limit_sec() -> u64 {
+	match get_chain_type() {
+		ChainTypes::CustomTesting => get_consensus_overrides()
+			.future_time_limit_sec
+			.unwrap_or(12 * block_time_sec()),
+		_ => 12 * BLOCK_TIME_SEC,
+	}
+}
+
 /// Threshold at which we can request a txhashset (and full blocks from)
 pub fn state_sync_threshold() -> u32 {
 	match get_chain_type() {
 		ChainTypes::AutomatedTesting => TESTING_STATE_SYNC_THRESHOLD,
 		ChainTypes::UserTesting => TESTING_STATE_SYNC_THRESHOLD,
+		ChainTypes::CustomTesting => TESTING_STATE_SYNC_THRESHOLD,
 		_ => STATE_SYNC_THRESHOLD,
 	}
 }
@@ -358,6 +530,7 @@ pub fn txhashset_archive_interval() -> u64 {
 	match get_chain_type() {
 		ChainTypes::AutomatedTesting => TESTING_TXHASHSET_ARCHIVE_INTERVAL,
 		ChainTypes::UserTesting => TESTING_TXHASHSET_ARCHIVE_INTERVAL,
+		ChainTypes::CustomTesting => TESTING_TXHASHSET_ARCHIVE_INTERVAL,
 		_ => TXHASHSET_ARCHIVE_INTERVAL,
 	}
 }
@@ -405,6 +578,7 @@ pub fn get_network_name() -> String {
 	let name = match get_chain_type() {
 		ChainTypes::AutomatedTesting => "automatedtests",
 		ChainTypes::UserTesting => "usertestnet",
+		ChainTypes::CustomTesting => "customtestnet",
 		ChainTypes::Floonet => "floonet",
 		ChainTypes::Mainnet => "mainnet",
 	};
@@ -420,7 +594,7 @@ where
 	T: IntoIterator<Item = HeaderInfo>,
 {
 	// Convert iterator to vector, so we can append to it if necessary
-	let needed_block_count = DIFFICULTY_ADJUST_WINDOW as usize + 1;
+	let needed_block_count = difficulty_adjust_window() as usize + 1;
 	let mut last_n: Vec<HeaderInfo> = cursor.into_iter().take(needed_block_count).collect();
 
 	// Only needed just after blockchain launch... basically ensures there's
@@ -431,7 +605,7 @@ where
 		let last_ts_delta = if n > 1 {
 			last_n[0].timestamp - last_n[1].timestamp
 		} else {
-			BLOCK_TIME_SEC
+			block_time_sec()
 		};
 		let last_diff = last_n[0].difficulty;
 
@@ -18,6 +18,9 @@ use crate::global;
 use crate::pow::error::Error;
 use crate::ser::{self, Readable, Reader, Writeable, Writer};
 use rand::{thread_rng, Rng};
+use schemars::gen::SchemaGenerator;
+use schemars::schema::Schema;
+use schemars::JsonSchema;
 use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
 /// Types for a Cuck(at)oo proof of work and its encapsulation as a fully usable
 /// proof of work within a block header.
@@ -166,6 +169,20 @@ impl Serialize for Difficulty {
 	}
 }
 
+impl JsonSchema for Difficulty {
+	fn is_referenceable() -> bool {
+		false
+	}
+
+	fn schema_name() -> String {
+		"Difficulty".to_string()
+	}
+
+	fn json_schema(gen: &mut SchemaGenerator) -> Schema {
+		u64::json_schema(gen)
+	}
+}
+
 impl<'de> Deserialize<'de> for Difficulty {
 	fn deserialize<D>(deserializer: D) -> Result<Difficulty, D::Error>
 	where
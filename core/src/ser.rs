@@ -23,6 +23,7 @@ use crate::core::hash::{DefaultHashable, Hash, Hashed};
 use crate::global::PROTOCOL_VERSION;
 use byteorder::{BigEndian, ByteOrder, ReadBytesExt};
 use keychain::{BlindingFactor, Identifier, IDENTIFIER_SIZE};
+use schemars::JsonSchema;
 use serde::__private::from_utf8_lossy;
 use std::convert::TryInto;
 use std::fmt::{self, Debug};
@@ -321,7 +322,9 @@ where
 /// the p2p layer and our local db storage layer.
 /// We may speak multiple versions to various peers and a potentially *different*
 /// version for our local db.
-#[derive(Clone, Copy, Debug, Deserialize, Eq, Ord, PartialOrd, PartialEq, Serialize)]
+#[derive(
+	Clone, Copy, Debug, Deserialize, Eq, Ord, PartialOrd, PartialEq, Serialize, JsonSchema,
+)]
 pub struct ProtocolVersion(pub u32);
 
 impl ProtocolVersion {
@@ -592,7 +592,7 @@ fn empty_compact_block_serialized_size() {
 	let cb: CompactBlock = b.into();
 	let mut vec = Vec::new();
 	ser::serialize_default(&mut vec, &cb).expect("serialization failed");
-	assert_eq!(vec.len(), 1_104);
+	assert_eq!(vec.len(), 1_112);
 }
 
 #[test]
@@ -607,7 +607,7 @@ fn compact_block_single_tx_serialized_size() {
 	let cb: CompactBlock = b.into();
 	let mut vec = Vec::new();
 	ser::serialize_default(&mut vec, &cb).expect("serialization failed");
-	assert_eq!(vec.len(), 1_110);
+	assert_eq!(vec.len(), 1_118);
 }
 
 #[test]
@@ -649,7 +649,7 @@ fn compact_block_10_tx_serialized_size() {
 	let cb: CompactBlock = b.into();
 	let mut vec = Vec::new();
 	ser::serialize_default(&mut vec, &cb).expect("serialization failed");
-	assert_eq!(vec.len(), 1_164);
+	assert_eq!(vec.len(), 1_172);
 }
 
 #[test]
@@ -751,6 +751,53 @@ fn serialize_deserialize_compact_block() {
 	assert_eq!(cb1.kern_ids(), cb2.kern_ids());
 }
 
+#[test]
+fn compact_block_prefilled_txs() {
+	test_setup();
+	let keychain = ExtKeychain::from_random_seed(false).unwrap();
+	let builder = ProofBuilder::new(&keychain);
+	let tx1 = tx1i2o();
+	let tx2 = tx1i2o();
+	let prev = BlockHeader::default();
+	let key_id = ExtKeychain::derive_key_id(1, 1, 0, 0, 0);
+	let b = new_block(
+		&[tx1.clone(), tx2.clone()],
+		&keychain,
+		&builder,
+		&prev,
+		&key_id,
+	);
+
+	// txs not in the block are not prefilled
+	let cb = CompactBlock::with_prefilled(b.clone(), vec![tx1.clone(), tx2i1o()]);
+	assert_eq!(cb.prefilled().len(), 1);
+	assert_eq!(cb.kern_ids().len(), 1);
+	assert_eq!(
+		cb.kern_ids()[0],
+		tx2.kernels()[0].short_id(&cb.hash(), cb.nonce)
+	);
+
+	// prefilled txs are sent in full to peers supporting them
+	let vec = ser::ser_vec(&cb, ser::ProtocolVersion(4)).unwrap();
+	let cb2: CompactBlock = ser::deserialize(&mut &vec[..], ser::ProtocolVersion(4)).unwrap();
+	assert_eq!(cb2.prefilled().len(), 1);
+	assert_eq!(cb2.kern_ids(), cb.kern_ids());
+
+	// older peers get the short_ids of the prefilled kernels instead
+	let vec = ser::ser_vec(&cb, ser::ProtocolVersion(3)).unwrap();
+	let cb3: CompactBlock = ser::deserialize(&mut &vec[..], ser::ProtocolVersion(3)).unwrap();
+	assert!(cb3.prefilled().is_empty());
+	assert_eq!(cb3.kern_ids().len(), 2);
+
+	// hydrating with the prefilled txs and the pool txs gives back the block
+	let mut txs = cb2.prefilled().to_vec();
+	txs.push(tx2);
+	let hb = Block::hydrate_from(cb2, &txs).unwrap();
+	assert_eq!(hb.hash(), b.hash());
+	assert_eq!(hb.outputs(), b.outputs());
+	assert_eq!(hb.kernels(), b.kernels());
+}
+
 // Duplicate a range proof from a valid output into another of the same amount
 #[test]
 fn same_amount_outputs_copy_range_proof() {
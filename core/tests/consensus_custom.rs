@@ -0,0 +1,118 @@
+// Copyright 2021 The MWC Developers
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use chrono::Utc;
+use grin_core::consensus::{next_difficulty, HeaderInfo, BLOCK_TIME_SEC};
+use grin_core::global::{self, ConsensusOverrides};
+use grin_core::pow::Difficulty;
+
+fn one_second_blocks() -> ConsensusOverrides {
+	ConsensusOverrides {
+		block_time_sec: Some(1),
+		difficulty_adjust_window: Some(10),
+		coinbase_maturity: Some(2),
+		cut_through_horizon: Some(5),
+		future_time_limit_sec: None,
+	}
+}
+
+#[test]
+fn custom_testing_overrides() {
+	global::set_local_chain_type(global::ChainTypes::CustomTesting);
+	global::set_local_consensus_overrides(one_second_blocks());
+
+	assert_eq!(global::block_time_sec(), 1);
+	assert_eq!(global::difficulty_adjust_window(), 10);
+	assert_eq!(global::block_time_window(), 10);
+	assert_eq!(global::coinbase_maturity(), 2);
+	assert_eq!(global::cut_through_horizon(), 5);
+	// 12 block intervals by default
+	assert_eq!(global::future_time_limit_sec(), 12);
+
+	// Unset values fall back to the automated testing ones
+	global::set_local_consensus_overrides(ConsensusOverrides::default());
+	assert_eq!(global::block_time_sec(), BLOCK_TIME_SEC);
+	assert_eq!(
+		global::coinbase_maturity(),
+		global::AUTOMATED_TESTING_COINBASE_MATURITY
+	);
+	assert_eq!(
+		global::cut_through_horizon(),
+		global::AUTOMATED_TESTING_CUT_THROUGH_HORIZON
+	);
+
+	// The other chain types ignore the overrides
+	global::set_local_chain_type(global::ChainTypes::AutomatedTesting);
+	global::set_local_consensus_overrides(one_second_blocks());
+	assert_eq!(global::block_time_sec(), BLOCK_TIME_SEC);
+	assert_eq!(global::future_time_limit_sec(), 12 * BLOCK_TIME_SEC);
+	assert_eq!(
+		global::coinbase_maturity(),
+		global::AUTOMATED_TESTING_COINBASE_MATURITY
+	);
+}
+
+#[test]
+fn custom_testing_next_difficulty() {
+	global::set_local_chain_type(global::ChainTypes::CustomTesting);
+	global::set_local_consensus_overrides(one_second_blocks());
+	let hi = HeaderInfo::from_ts_diff(1, Difficulty::from_num(10000));
+
+	// 1 second blocks over the window, difficulty stays constant
+	assert_eq!(
+		next_difficulty(1, repeat(1, hi.clone(), 11)).difficulty,
+		Difficulty::from_num(10000)
+	);
+
+	// too slow, diff goes down
+	assert!(next_difficulty(1, repeat(2, hi.clone(), 11)).difficulty < hi.difficulty);
+
+	// too fast, diff goes up
+	let mut fast = repeat(0, hi.clone(), 11);
+	fast[0].timestamp += 5;
+	assert!(next_difficulty(1, fast).difficulty > hi.difficulty);
+}
+
+#[test]
+fn consensus_overrides_validation() {
+	assert!(ConsensusOverrides::default().validate().is_ok());
+	assert!(one_second_blocks().validate().is_ok());
+
+	let mut overrides = one_second_blocks();
+	overrides.block_time_sec = Some(0);
+	assert!(overrides.validate().is_err());
+
+	let mut overrides = one_second_blocks();
+	overrides.difficulty_adjust_window = Some(1);
+	assert!(overrides.validate().is_err());
+
+	let mut overrides = one_second_blocks();
+	overrides.cut_through_horizon = Some(2);
+	assert!(overrides.validate().is_err());
+}
+
+fn repeat(interval: u64, diff: HeaderInfo, len: u64) -> Vec<HeaderInfo> {
+	let cur_time = Utc::now().timestamp() as u64;
+	(0..len)
+		.rev()
+		.map(|n| {
+			HeaderInfo::new(
+				diff.block_hash,
+				cur_time + n * interval,
+				diff.difficulty,
+				diff.secondary_scaling,
+				diff.is_secondary,
+			)
+		})
+		.collect()
+}
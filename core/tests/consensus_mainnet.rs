@@ -620,6 +620,7 @@ fn hard_forks() {
 		assert!(valid_header_version(YEAR_HEIGHT, HeaderVersion(2)));
 		assert!(valid_header_version(YEAR_HEIGHT * 10, HeaderVersion(2)));
 		assert!(valid_header_version(YEAR_HEIGHT * 100, HeaderVersion(2)));
+		assert_eq!(nrd_activation_height(), u64::MAX);
 	}
 	// Tests for floonet chain type.
 	{
@@ -629,5 +630,12 @@ fn hard_forks() {
 		assert!(valid_header_version(YEAR_HEIGHT, HeaderVersion(2)));
 		assert!(valid_header_version(YEAR_HEIGHT * 10, HeaderVersion(2)));
 		assert!(valid_header_version(YEAR_HEIGHT * 100, HeaderVersion(2)));
+		assert_eq!(nrd_activation_height(), u64::MAX);
+	}
+	// Tests for automated testing chain type, NRD kernels are activated by HF3.
+	{
+		global::set_local_chain_type(global::ChainTypes::AutomatedTesting);
+		assert_eq!(nrd_activation_height(), TESTING_THIRD_HARD_FORK);
+		assert_eq!(header_version(nrd_activation_height()), HeaderVersion(4));
 	}
 }
@@ -15,7 +15,7 @@
 mod common;
 
 use self::core::core::merkle_proof::MerkleProof;
-use self::core::core::pmmr::{VecBackend, PMMR};
+use self::core::core::pmmr::{ReadonlyPMMR, VecBackend, PMMR};
 use self::core::ser::{self, PMMRIndexHashable};
 use crate::common::TestElem;
 use grin_core as core;
@@ -187,3 +187,30 @@ fn pmmr_merkle_proof() {
 	assert_eq!(proof.path, vec![pos_9, pos_6]);
 	assert!(proof.verify(pmmr.root().unwrap(), &elems[6], 11).is_ok());
 }
+
+#[test]
+fn readonly_pmmr_merkle_proof() {
+	let mut ba = VecBackend::new();
+	let mut pmmr = PMMR::new(&mut ba);
+	for i in 0..9 {
+		pmmr.push(&TestElem([0, 0, 0, i])).unwrap();
+	}
+	let size = pmmr.unpruned_size();
+	let root = pmmr.root().unwrap();
+	for pos in [1, 5, 8, 11, 16].iter() {
+		let proof = pmmr.merkle_proof(*pos).unwrap();
+		assert_eq!(proof, pmmr.readonly_pmmr().merkle_proof(*pos).unwrap());
+	}
+
+	// proof against the smaller historical MMR
+	for i in 9..15 {
+		pmmr.push(&TestElem([0, 0, 0, i])).unwrap();
+	}
+	let proof = ReadonlyPMMR::at(&ba, size).merkle_proof(5).unwrap();
+	assert_eq!(proof.mmr_size, size);
+	assert!(proof.verify(root, &TestElem([0, 0, 0, 3]), 5).is_ok());
+	// not a leaf
+	assert!(ReadonlyPMMR::<TestElem, _>::at(&ba, size)
+		.merkle_proof(3)
+		.is_err());
+}
@@ -0,0 +1,100 @@
+// Copyright 2021 The MWC Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+mod common;
+
+use self::core::core::hash::Hash;
+use self::core::core::pmmr::{
+	Segment, SegmentEntry, SegmentError, SegmentIdentifier, VecBackend, PMMR,
+};
+use self::core::ser::{self, ProtocolVersion};
+use crate::common::TestElem;
+use grin_core as core;
+
+#[test]
+fn segment_validate_and_rebuild() {
+	let mut backend = VecBackend::new();
+	let mut pmmr = PMMR::new(&mut backend);
+	for x in 0..13u32 {
+		pmmr.push(&TestElem([0, 0, 0, x])).unwrap();
+	}
+	let mmr_size = pmmr.unpruned_size();
+	let root = pmmr.root().unwrap();
+
+	let height = 2;
+	let count = SegmentIdentifier::count_for_mmr_size(height, mmr_size);
+	assert_eq!(count, 4);
+
+	let mut rebuilt_backend = VecBackend::new();
+	let mut rebuilt = PMMR::new(&mut rebuilt_backend);
+	for idx in 0..count {
+		let id = SegmentIdentifier { height, idx };
+		let segment = Segment::from_pmmr(id, &pmmr.readonly_pmmr()).unwrap();
+		assert_eq!(segment.leaves_len(), if idx < 3 { 4 } else { 1 });
+
+		// Serialization round trip.
+		let bytes = ser::ser_vec(&segment, ProtocolVersion(1)).unwrap();
+		let segment: Segment<TestElem> =
+			ser::deserialize(&mut &bytes[..], ProtocolVersion(1)).unwrap();
+
+		assert_eq!(segment.validate(mmr_size, root), Ok(()));
+		assert_eq!(
+			segment.validate(mmr_size, Hash::default()),
+			Err(SegmentError::RootMismatch)
+		);
+
+		for (_, entry) in segment.entries() {
+			match entry {
+				SegmentEntry::Leaf(x) => {
+					rebuilt.push(x).unwrap();
+				}
+				SegmentEntry::Hash(_) => panic!("unexpected pruned subtree"),
+			}
+		}
+	}
+	assert_eq!(rebuilt.root().unwrap(), root);
+
+	assert_eq!(
+		Segment::from_pmmr(
+			SegmentIdentifier { height, idx: count },
+			&pmmr.readonly_pmmr()
+		),
+		Err(SegmentError::NonExistent(count))
+	);
+}
+
+#[test]
+fn segment_from_other_mmr() {
+	let mut backend = VecBackend::new();
+	let mut pmmr = PMMR::new(&mut backend);
+	let mut other_backend = VecBackend::new();
+	let mut other = PMMR::new(&mut other_backend);
+	for x in 0..8u32 {
+		pmmr.push(&TestElem([0, 0, 0, x])).unwrap();
+		other.push(&TestElem([0, 0, 1, x])).unwrap();
+	}
+	let mmr_size = pmmr.unpruned_size();
+	let root = pmmr.root().unwrap();
+
+	let id = SegmentIdentifier { height: 2, idx: 1 };
+	let segment = Segment::from_pmmr(id, &other.readonly_pmmr()).unwrap();
+	assert_eq!(
+		segment.validate(other.unpruned_size(), other.root().unwrap()),
+		Ok(())
+	);
+	assert_eq!(
+		segment.validate(mmr_size, root),
+		Err(SegmentError::RootMismatch)
+	);
+}
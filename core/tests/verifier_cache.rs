@@ -59,3 +59,23 @@ fn test_verifier_cache_rangeproofs() {
 		assert_eq!(unverified, vec![]);
 	}
 }
+
+#[test]
+fn test_verifier_cache_disabled() {
+	let cache = Arc::new(RwLock::new(LruVerifierCache::with_capacity(0)));
+
+	let keychain = ExtKeychain::from_random_seed(false).unwrap();
+	let key_id = ExtKeychain::derive_key_id(1, 1, 0, 0, 0);
+	let switch = SwitchCommitmentType::Regular;
+	let commit = keychain.commit(5, &key_id, switch).unwrap();
+	let builder = proof::ProofBuilder::new(&keychain);
+	let proof = proof::create(&keychain, &builder, 5, &key_id, switch, commit, None).unwrap();
+
+	let out = Output::new(OutputFeatures::Plain, commit, proof);
+
+	// Nothing is kept, the output must be verified again.
+	let mut cache = cache.write();
+	cache.add_rangeproof_verified(vec![out]);
+	let unverified = cache.filter_rangeproof_unverified(&[out]);
+	assert_eq!(unverified, vec![out]);
+}
@@ -0,0 +1,61 @@
+// Copyright 2021 The MWC Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Generates the fixture files from a JSON list of the fixture specs.
+//!
+//! Usage: gen_fixtures <specs.json> <output dir>
+
+use grin_fixtures::{generate, FixtureSpec};
+use std::fs::{self, File};
+use std::path::Path;
+use std::process::exit;
+
+fn run(specs_path: &str, out_dir: &str) -> Result<(), String> {
+	let file =
+		File::open(specs_path).map_err(|e| format!("Unable to open {}, {}", specs_path, e))?;
+	let specs: Vec<FixtureSpec> = serde_json::from_reader(file)
+		.map_err(|e| format!("Unable to parse {}, {}", specs_path, e))?;
+	fs::create_dir_all(out_dir).map_err(|e| format!("Unable to create {}, {}", out_dir, e))?;
+
+	for spec in specs {
+		let db_root = Path::new(out_dir).join(format!(".{}_db", spec.name));
+		let db_root = db_root.to_str().ok_or("Invalid output dir")?;
+		let _ = fs::remove_dir_all(db_root);
+		let (_, fixture) = generate(&spec, db_root).map_err(|e| e.to_string())?;
+		let _ = fs::remove_dir_all(db_root);
+
+		let path = Path::new(out_dir).join(format!("{}.bin", spec.name));
+		fixture.write_file(&path).map_err(|e| e.to_string())?;
+		println!(
+			"{}: {} blocks, {} pool txs, head {}",
+			path.display(),
+			fixture.blocks.len(),
+			fixture.pool_txs.len(),
+			fixture.head
+		);
+	}
+	Ok(())
+}
+
+fn main() {
+	let args: Vec<String> = std::env::args().collect();
+	if args.len() != 3 {
+		eprintln!("Usage: gen_fixtures <specs.json> <output dir>");
+		exit(2);
+	}
+	if let Err(e) = run(&args[1], &args[2]) {
+		eprintln!("{}", e);
+		exit(1);
+	}
+}
@@ -0,0 +1,142 @@
+// Copyright 2021 The MWC Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Generated fixture chain, its file format and loading into a chain.
+
+use crate::chain::types::{NoopAdapter, Options};
+use crate::chain::Chain;
+use crate::core::core::hash::{Hash, Hashed};
+use crate::core::core::verifier_cache::LruVerifierCache;
+use crate::core::core::{Block, Transaction};
+use crate::core::global::{self, ChainTypes};
+use crate::core::pow;
+use crate::core::ser::{self, Readable, Reader, Writeable, Writer};
+use crate::util::RwLock;
+use crate::Error;
+use std::fs::File;
+use std::io::{BufReader, BufWriter};
+use std::path::Path;
+use std::sync::Arc;
+
+/// Version of the fixture file format
+const FIXTURE_VERSION: u8 = 1;
+
+/// Generated fixture chain. The blocks are stored in the order they were mined,
+/// the blocks of the forks follow the main chain.
+#[derive(Debug, Clone)]
+pub struct Fixture {
+	/// Name of the fixture
+	pub name: String,
+	/// Genesis block of the chain
+	pub genesis: Block,
+	/// Blocks in the mining order
+	pub blocks: Vec<Block>,
+	/// Head of the chain once all the blocks are processed
+	pub head: Hash,
+	/// Valid txs spending the outputs at the head, not mined
+	pub pool_txs: Vec<Transaction>,
+}
+
+impl Writeable for Fixture {
+	fn write<W: Writer>(&self, writer: &mut W) -> Result<(), ser::Error> {
+		writer.write_u8(FIXTURE_VERSION)?;
+		writer.write_bytes(self.name.as_bytes())?;
+		self.genesis.write(writer)?;
+		writer.write_u64(self.blocks.len() as u64)?;
+		for block in &self.blocks {
+			block.write(writer)?;
+		}
+		self.head.write(writer)?;
+		writer.write_u64(self.pool_txs.len() as u64)?;
+		for tx in &self.pool_txs {
+			tx.write(writer)?;
+		}
+		Ok(())
+	}
+}
+
+impl Readable for Fixture {
+	fn read<R: Reader>(reader: &mut R) -> Result<Fixture, ser::Error> {
+		reader.expect_u8(FIXTURE_VERSION)?;
+		let name = String::from_utf8(reader.read_bytes_len_prefix()?)
+			.map_err(|e| ser::Error::CorruptedData(format!("Invalid fixture name, {}", e)))?;
+		let genesis = Block::read(reader)?;
+		let count = reader.read_u64()?;
+		let blocks = ser::read_multi(reader, count)?;
+		let head = Hash::read(reader)?;
+		let count = reader.read_u64()?;
+		let pool_txs = ser::read_multi(reader, count)?;
+		Ok(Fixture {
+			name,
+			genesis,
+			blocks,
+			head,
+			pool_txs,
+		})
+	}
+}
+
+impl Fixture {
+	/// Write the fixture to the file
+	pub fn write_file<P: AsRef<Path>>(&self, path: P) -> Result<(), Error> {
+		let mut file = BufWriter::new(File::create(path)?);
+		ser::serialize_default(&mut file, self)?;
+		Ok(())
+	}
+
+	/// Read the fixture from the file. Fixtures are always AutomatedTesting chains,
+	/// the chain type of the current thread is set accordingly.
+	pub fn read_file<P: AsRef<Path>>(path: P) -> Result<Fixture, Error> {
+		global::set_local_chain_type(ChainTypes::AutomatedTesting);
+		let mut file = BufReader::new(File::open(path)?);
+		Ok(ser::deserialize_default(&mut file)?)
+	}
+
+	/// Last block of the main chain or of the fork the chain ends on
+	pub fn head_block(&self) -> Option<&Block> {
+		self.blocks.iter().find(|b| b.hash() == self.head)
+	}
+
+	/// Create a new chain at db_root and process all the blocks, fully validated.
+	/// The head of the chain is checked against the fixture.
+	pub fn init_chain(&self, db_root: &str) -> Result<Chain, Error> {
+		global::set_local_chain_type(ChainTypes::AutomatedTesting);
+		let chain = init_chain(db_root, self.genesis.clone())?;
+		for block in &self.blocks {
+			chain.process_block(block.clone(), Options::NONE)?;
+		}
+		let head = chain.head()?.last_block_h;
+		if head != self.head {
+			return Err(Error::Chain(format!(
+				"fixture {} head {} doesn't match the chain head {}",
+				self.name, self.head, head
+			)));
+		}
+		Ok(chain)
+	}
+}
+
+/// Chain with the fixture settings, the test chains are never archive nodes
+pub(crate) fn init_chain(db_root: &str, genesis: Block) -> Result<Chain, Error> {
+	let verifier_cache = Arc::new(RwLock::new(LruVerifierCache::new()));
+	let chain = Chain::init(
+		db_root.to_string(),
+		Arc::new(NoopAdapter {}),
+		genesis,
+		pow::verify_size,
+		verifier_cache,
+		false,
+	)?;
+	Ok(chain)
+}
@@ -0,0 +1,282 @@
+// Copyright 2021 The MWC Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Mining of the fixture chains. Everything random in the regular block and tx building
+//! (keychain seed, kernel excesses and signature nonces, timestamps, pow nonces) is
+//! derived from the spec instead, so a spec always produces the same blocks.
+
+use crate::chain::store::DifficultyIter;
+use crate::chain::types::Options;
+use crate::chain::Chain;
+use crate::core::consensus;
+use crate::core::core::hash::Hashed;
+use crate::core::core::{Block, BlockHeader, KernelFeatures, Transaction, TxKernel};
+use crate::core::genesis;
+use crate::core::global::{self, ChainTypes};
+use crate::core::libtx::{aggsig, build, reward, ProofBuilder};
+use crate::core::pow;
+use crate::fixture::{self, Fixture};
+use crate::keychain::{
+	BlindingFactor, ExtKeychain, ExtKeychainPath, Identifier, Keychain, SwitchCommitmentType,
+};
+use crate::spec::FixtureSpec;
+use crate::util::secp::key::SecretKey;
+use crate::util::secp::pedersen::Commitment;
+use crate::Error;
+use blake2_rfc::blake2b::blake2b;
+use chrono::Duration;
+
+/// Output mined by the generator
+struct OwnedOutput {
+	key_id: Identifier,
+	value: u64,
+	commit: Commitment,
+	height: u64,
+	coinbase: bool,
+}
+
+struct Generator<'a> {
+	spec: &'a FixtureSpec,
+	keychain: ExtKeychain,
+	chain: Chain,
+	/// Outputs of the main chain in the mining order
+	outputs: Vec<OwnedOutput>,
+	blocks: Vec<Block>,
+}
+
+/// Mine the fixture chain described by the spec in a new chain at db_root.
+/// Returns the chain and the fixture, the same spec always gives the same fixture.
+pub fn generate(spec: &FixtureSpec, db_root: &str) -> Result<(Chain, Fixture), Error> {
+	spec.validate()?;
+	global::set_local_chain_type(ChainTypes::AutomatedTesting);
+
+	let seed = derive_bytes(spec.seed, "keychain");
+	let keychain = ExtKeychain::from_seed(&seed, false)?;
+	let genesis = genesis_block(&keychain)?;
+	let chain = fixture::init_chain(db_root, genesis.clone())?;
+	let mut generator = Generator {
+		spec,
+		keychain,
+		chain,
+		outputs: vec![],
+		blocks: vec![],
+	};
+
+	for height in 1..=spec.height {
+		let txs = generator.block_txs(height)?;
+		let prev = generator.chain.head_header()?;
+		let key_id = ExtKeychainPath::new(1, height as u32, 0, 0, 0).to_identifier();
+		generator.mine_block(&prev, key_id, txs)?;
+	}
+
+	// Coinbase only branches, a longer branch reorgs the chain
+	for (i, fork) in spec.forks.iter().enumerate() {
+		let mut prev = generator.chain.get_header_by_height(fork.fork_height)?;
+		for _ in 0..fork.length {
+			let key_id =
+				ExtKeychainPath::new(2, i as u32 + 1, prev.height as u32 + 1, 0, 0).to_identifier();
+			prev = generator.mine_block(&prev, key_id, vec![])?.header;
+		}
+	}
+
+	let head = generator.chain.head_header()?;
+	let pool_txs = generator.pool_txs(head.height + 1)?;
+	let fixture = Fixture {
+		name: spec.name.clone(),
+		genesis,
+		blocks: generator.blocks,
+		head: head.hash(),
+		pool_txs,
+	};
+	Ok((generator.chain, fixture))
+}
+
+impl<'a> Generator<'a> {
+	/// Mine the block with the txs on top of prev and process it
+	fn mine_block(
+		&mut self,
+		prev: &BlockHeader,
+		key_id: Identifier,
+		txs: Vec<Transaction>,
+	) -> Result<Block, Error> {
+		let height = prev.height + 1;
+		let fees = txs.iter().map(|tx| tx.fee()).sum();
+		let builder = ProofBuilder::new(&self.keychain);
+		// Test mode signs with a fixed nonce
+		let reward = reward::output(&self.keychain, &builder, &key_id, fees, true, height)?;
+		let reward_value = consensus::reward(fees, height);
+		let commit = reward.0.commitment();
+
+		let diff_iter = DifficultyIter::from(prev.hash(), self.chain.store());
+		let next_header_info = consensus::next_difficulty(height, diff_iter);
+		let mut b = Block::new(prev, &txs, next_header_info.difficulty, reward)?;
+		b.header.timestamp = prev.timestamp + Duration::seconds(60);
+		b.header.pow.secondary_scaling = next_header_info.secondary_scaling;
+		self.chain.set_txhashset_roots(&mut b)?;
+
+		let edge_bits = global::min_edge_bits();
+		b.header.pow.nonce = 0;
+		b.header.pow.proof.edge_bits = edge_bits;
+		pow::pow_size(
+			&mut b.header,
+			next_header_info.difficulty,
+			global::proofsize(),
+			edge_bits,
+		)?;
+		self.chain.process_block(b.clone(), Options::MINE)?;
+
+		// Only the main chain outputs are spent by the later txs
+		if key_id.to_path().depth == 1 {
+			self.outputs.push(OwnedOutput {
+				key_id,
+				value: reward_value,
+				commit,
+				height,
+				coinbase: true,
+			});
+		}
+		self.blocks.push(b.clone());
+		Ok(b)
+	}
+
+	/// Txs of the main chain block at the height
+	fn block_txs(&mut self, height: u64) -> Result<Vec<Transaction>, Error> {
+		let inputs = self.spendable(height, self.spec.txs_per_block)?;
+		let mut txs = vec![];
+		for (i, input) in inputs.into_iter().enumerate() {
+			let key_ids = (0..self.spec.outputs_per_tx)
+				.map(|o| {
+					ExtKeychainPath::new(3, height as u32, i as u32, o as u32, 0).to_identifier()
+				})
+				.collect();
+			txs.push(self.build_tx(input, key_ids, height, &format!("{}-{}", height, i))?);
+		}
+		Ok(txs)
+	}
+
+	/// Unmined txs spending the outputs at the head
+	fn pool_txs(&mut self, height: u64) -> Result<Vec<Transaction>, Error> {
+		let inputs = self.spendable(height, self.spec.pool_txs)?;
+		let mut txs = vec![];
+		for (i, input) in inputs.into_iter().enumerate() {
+			let key_ids = (0..self.spec.outputs_per_tx)
+				.map(|o| ExtKeychainPath::new(4, i as u32, o as u32, 0, 0).to_identifier())
+				.collect();
+			txs.push(self.build_tx(input, key_ids, height, &format!("pool-{}", i))?);
+		}
+		Ok(txs)
+	}
+
+	/// Indexes of the first count outputs unspent at the current head, matured for a
+	/// block at the height and big enough to pay the fee
+	fn spendable(&self, height: u64, count: usize) -> Result<Vec<usize>, Error> {
+		let mut res = vec![];
+		for (idx, out) in self.outputs.iter().enumerate() {
+			if res.len() >= count {
+				break;
+			}
+			if out.coinbase && out.height + global::coinbase_maturity() > height {
+				continue;
+			}
+			if out.value < self.spec.fee + self.spec.outputs_per_tx as u64 {
+				continue;
+			}
+			if self.chain.get_unspent(out.commit)?.is_some() {
+				res.push(idx);
+			}
+		}
+		Ok(res)
+	}
+
+	/// Tx spending the output into the outputs with the provided keys, the value is
+	/// split evenly. The kernel excess and nonce are derived from the tag.
+	fn build_tx(
+		&mut self,
+		input: usize,
+		key_ids: Vec<Identifier>,
+		height: u64,
+		tag: &str,
+	) -> Result<Transaction, Error> {
+		let (input_key_id, input_value, coinbase) = {
+			let out = &self.outputs[input];
+			(out.key_id.clone(), out.value, out.coinbase)
+		};
+		let amount = input_value - self.spec.fee;
+		let count = key_ids.len() as u64;
+
+		let mut elems = vec![match coinbase {
+			true => build::coinbase_input(input_value, input_key_id),
+			false => build::input(input_value, input_key_id),
+		}];
+		let mut outputs = vec![];
+		for (i, key_id) in key_ids.into_iter().enumerate() {
+			let value = match i as u64 == count - 1 {
+				true => amount - amount / count * (count - 1),
+				false => amount / count,
+			};
+			let commit = self
+				.keychain
+				.commit(value, &key_id, SwitchCommitmentType::Regular)?;
+			elems.push(build::output(value, key_id.clone()));
+			outputs.push(OwnedOutput {
+				key_id,
+				value,
+				commit,
+				height,
+				coinbase: false,
+			});
+		}
+
+		let excess = BlindingFactor::from_secret_key(derive_secret(
+			self.spec.seed,
+			&format!("excess-{}", tag),
+		)?);
+		let nonce = derive_secret(self.spec.seed, &format!("nonce-{}", tag))?;
+		let mut kernel = TxKernel::with_features(KernelFeatures::Plain { fee: self.spec.fee });
+		let msg = kernel.msg_to_sign()?;
+		let secp = self.keychain.secp();
+		kernel.excess = secp.commit(0, excess.secret_key()?)?;
+		let pubkey = kernel.excess.to_pubkey()?;
+		kernel.excess_sig = aggsig::sign_single(
+			secp,
+			&msg,
+			&excess.secret_key()?,
+			Some(&nonce),
+			Some(&pubkey),
+		)?;
+		kernel.verify()?;
+
+		let builder = ProofBuilder::new(&self.keychain);
+		let tx = build::transaction_with_kernel(&elems, kernel, excess, &self.keychain, &builder)?;
+		self.outputs.extend(outputs);
+		Ok(tx)
+	}
+}
+
+/// Genesis block with a reward, like the mainnet one
+fn genesis_block(keychain: &ExtKeychain) -> Result<Block, Error> {
+	let key_id = ExtKeychainPath::new(0, 1, 0, 0, 0).to_identifier();
+	let reward = reward::output(keychain, &ProofBuilder::new(keychain), &key_id, 0, true, 0)?;
+	Ok(genesis::genesis_dev().with_reward(reward.0, reward.1))
+}
+
+fn derive_bytes(seed: u64, tag: &str) -> Vec<u8> {
+	let mut data = seed.to_le_bytes().to_vec();
+	data.extend_from_slice(tag.as_bytes());
+	blake2b(32, &[], &data).as_bytes().to_vec()
+}
+
+fn derive_secret(seed: u64, tag: &str) -> Result<SecretKey, Error> {
+	Ok(SecretKey::from_slice(&derive_bytes(seed, tag))?)
+}
@@ -0,0 +1,117 @@
+// Copyright 2021 The MWC Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Deterministic mini-chain fixtures for the tests. A fixture is described by a
+//! `FixtureSpec` (height, txs per block, forks, unmined txs) and generated on the
+//! AutomatedTesting chain with the keys, the kernel nonces and the timestamps derived
+//! from the spec, so the same spec always produces the same blocks. Fixtures are
+//! written to a binary file by the `gen_fixtures` tool and loaded into a fresh chain
+//! by the chain, pool and api tests.
+
+#![deny(non_upper_case_globals)]
+#![deny(non_camel_case_types)]
+#![deny(non_snake_case)]
+#![deny(unused_mut)]
+#![warn(missing_docs)]
+
+#[macro_use]
+extern crate serde_derive;
+
+use failure::Fail;
+use grin_chain as chain;
+use grin_core as core;
+use grin_keychain as keychain;
+use grin_util as util;
+
+mod fixture;
+mod generator;
+mod spec;
+
+pub use crate::fixture::Fixture;
+pub use crate::generator::generate;
+pub use crate::spec::{FixtureSpec, ForkSpec};
+
+/// Fixture generation and loading errors
+#[derive(Debug, Fail)]
+pub enum Error {
+	/// Chain error while mining or loading the blocks
+	#[fail(display = "Fixture chain error, {}", _0)]
+	Chain(String),
+	/// Error building the blocks or the txs
+	#[fail(display = "Fixture build error, {}", _0)]
+	Build(String),
+	/// Fixture file (de)serialization error
+	#[fail(display = "Fixture serialization error, {}", _0)]
+	Ser(String),
+	/// Fixture file read or write error
+	#[fail(display = "Fixture IO error, {}", _0)]
+	IO(String),
+	/// The spec can't be generated
+	#[fail(display = "Fixture spec error, {}", _0)]
+	Spec(String),
+}
+
+impl From<chain::Error> for Error {
+	fn from(e: chain::Error) -> Error {
+		Error::Chain(e.to_string())
+	}
+}
+
+impl From<core::libtx::Error> for Error {
+	fn from(e: core::libtx::Error) -> Error {
+		Error::Build(e.to_string())
+	}
+}
+
+impl From<core::core::block::Error> for Error {
+	fn from(e: core::core::block::Error) -> Error {
+		Error::Build(e.to_string())
+	}
+}
+
+impl From<core::core::transaction::Error> for Error {
+	fn from(e: core::core::transaction::Error) -> Error {
+		Error::Build(e.to_string())
+	}
+}
+
+impl From<core::pow::Error> for Error {
+	fn from(e: core::pow::Error) -> Error {
+		Error::Build(e.to_string())
+	}
+}
+
+impl From<keychain::Error> for Error {
+	fn from(e: keychain::Error) -> Error {
+		Error::Build(e.to_string())
+	}
+}
+
+impl From<util::secp::Error> for Error {
+	fn from(e: util::secp::Error) -> Error {
+		Error::Build(e.to_string())
+	}
+}
+
+impl From<core::ser::Error> for Error {
+	fn from(e: core::ser::Error) -> Error {
+		Error::Ser(e.to_string())
+	}
+}
+
+impl From<std::io::Error> for Error {
+	fn from(e: std::io::Error) -> Error {
+		Error::IO(e.to_string())
+	}
+}
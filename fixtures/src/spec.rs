@@ -0,0 +1,97 @@
+// Copyright 2021 The MWC Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Description of a fixture chain.
+
+use crate::Error;
+
+/// Fork mined after the main chain, a branch of coinbase only blocks
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ForkSpec {
+	/// Height of the block of the current chain the branch is mined on
+	pub fork_height: u64,
+	/// Number of the blocks of the branch. A branch longer than the rest of the
+	/// current chain reorgs it.
+	pub length: u64,
+}
+
+/// Description of a fixture chain
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FixtureSpec {
+	/// Name of the fixture, also the name of its file
+	pub name: String,
+	/// Seed of the keys and of the kernel nonces
+	pub seed: u64,
+	/// Height of the main chain
+	pub height: u64,
+	/// Max number of the txs in every main chain block, each spends a single
+	/// matured output of the earlier blocks
+	pub txs_per_block: usize,
+	/// Number of the outputs of every tx
+	pub outputs_per_tx: usize,
+	/// Fee of every tx
+	pub fee: u64,
+	/// Forks mined in order after the main chain
+	pub forks: Vec<ForkSpec>,
+	/// Number of the unmined txs spending the outputs at the head, for the pool tests
+	pub pool_txs: usize,
+}
+
+impl Default for FixtureSpec {
+	fn default() -> FixtureSpec {
+		FixtureSpec {
+			name: "default".to_string(),
+			seed: 0,
+			height: 10,
+			txs_per_block: 0,
+			outputs_per_tx: 2,
+			fee: 1_000_000,
+			forks: vec![],
+			pool_txs: 0,
+		}
+	}
+}
+
+impl FixtureSpec {
+	/// Coinbase only chain of the provided height
+	pub fn linear(height: u64) -> FixtureSpec {
+		FixtureSpec {
+			name: format!("linear_{}", height),
+			height,
+			..FixtureSpec::default()
+		}
+	}
+
+	/// Check the spec can be generated
+	pub fn validate(&self) -> Result<(), Error> {
+		if self.name.is_empty() {
+			return Err(Error::Spec("empty name".to_string()));
+		}
+		if self.outputs_per_tx == 0 {
+			return Err(Error::Spec("txs need at least one output".to_string()));
+		}
+		let mut height = self.height;
+		for fork in &self.forks {
+			if fork.fork_height > height {
+				return Err(Error::Spec(format!(
+					"fork at {} is above the chain height {}",
+					fork.fork_height, height
+				)));
+			}
+			height = height.max(fork.fork_height + fork.length);
+		}
+		Ok(())
+	}
+}
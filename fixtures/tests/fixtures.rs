@@ -0,0 +1,119 @@
+// Copyright 2021 The MWC Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use grin_core as core;
+use grin_fixtures::{generate, Fixture, FixtureSpec, ForkSpec};
+
+use self::core::core::hash::Hashed;
+use self::core::ser;
+use std::fs;
+
+fn clean_output_dir(dir_name: &str) {
+	let _ = fs::remove_dir_all(dir_name);
+}
+
+fn reorg_spec() -> FixtureSpec {
+	FixtureSpec {
+		name: "reorg".to_string(),
+		height: 12,
+		txs_per_block: 2,
+		forks: vec![
+			ForkSpec {
+				fork_height: 8,
+				length: 2,
+			},
+			ForkSpec {
+				fork_height: 9,
+				length: 5,
+			},
+		],
+		pool_txs: 2,
+		..FixtureSpec::default()
+	}
+}
+
+#[test]
+fn test_fixture_deterministic() {
+	let dir_1 = ".grin_fixture_deterministic_1";
+	let dir_2 = ".grin_fixture_deterministic_2";
+	clean_output_dir(dir_1);
+	clean_output_dir(dir_2);
+
+	let (_, fixture_1) = generate(&reorg_spec(), dir_1).unwrap();
+	let (_, fixture_2) = generate(&reorg_spec(), dir_2).unwrap();
+	assert_eq!(
+		ser::ser_vec(&fixture_1, ser::ProtocolVersion::local()).unwrap(),
+		ser::ser_vec(&fixture_2, ser::ProtocolVersion::local()).unwrap()
+	);
+
+	// Another seed, other blocks
+	let spec = FixtureSpec {
+		seed: 1,
+		..reorg_spec()
+	};
+	clean_output_dir(dir_2);
+	let (_, fixture_2) = generate(&spec, dir_2).unwrap();
+	assert_ne!(fixture_1.head, fixture_2.head);
+
+	clean_output_dir(dir_1);
+	clean_output_dir(dir_2);
+}
+
+#[test]
+fn test_fixture_reorg() {
+	let dir = ".grin_fixture_reorg";
+	clean_output_dir(dir);
+
+	let (chain, fixture) = generate(&reorg_spec(), dir).unwrap();
+	// The first fork is shorter, the second one reorgs the chain
+	assert_eq!(fixture.blocks.len(), 12 + 2 + 5);
+	let head = chain.head_header().unwrap();
+	assert_eq!(head.height, 14);
+	assert_eq!(head.hash(), fixture.head);
+	assert_eq!(fixture.head_block().unwrap().header.height, 14);
+	assert_eq!(
+		chain.get_header_by_height(9).unwrap().hash(),
+		fixture.blocks[8].hash()
+	);
+	// Main chain blocks above the minimum maturity carry the txs
+	assert!(fixture.blocks[11].kernels().len() > 1);
+	assert_eq!(fixture.pool_txs.len(), 2);
+	chain.validate(false).unwrap();
+
+	clean_output_dir(dir);
+}
+
+#[test]
+fn test_fixture_file() {
+	let dir = ".grin_fixture_file";
+	clean_output_dir(dir);
+	fs::create_dir_all(dir).unwrap();
+
+	let (chain, fixture) = generate(&reorg_spec(), &format!("{}/gen", dir)).unwrap();
+	drop(chain);
+	let path = format!("{}/reorg.bin", dir);
+	fixture.write_file(&path).unwrap();
+
+	let loaded = Fixture::read_file(&path).unwrap();
+	assert_eq!(loaded.name, fixture.name);
+	assert_eq!(loaded.head, fixture.head);
+	assert_eq!(loaded.blocks.len(), fixture.blocks.len());
+	assert_eq!(loaded.pool_txs, fixture.pool_txs);
+
+	// Fully validated in a new chain
+	let chain = loaded.init_chain(&format!("{}/load", dir)).unwrap();
+	assert_eq!(chain.head().unwrap().last_block_h, fixture.head);
+
+	clean_output_dir(dir);
+}
@@ -42,25 +42,38 @@ extern crate lazy_static;
 mod conn;
 pub mod handshake;
 pub mod libp2p_connection;
+pub mod libp2p_fragments;
+pub mod libp2p_mailbox;
+pub mod libp2p_node_api;
 pub mod msg;
 mod peer;
+mod peer_targets;
 mod peers;
 mod protocol;
+pub mod seen_cache;
 mod serv;
+mod serve_limiter;
 mod store;
+mod tx_relay_limiter;
 pub mod types;
 
 pub use crate::conn::SEND_CHANNEL_CAP;
 pub use crate::peer::Peer;
+pub use crate::peer_targets::{PeerLoad, PeerTargets};
 pub use crate::peers::Peers;
+pub use crate::seen_cache::{seen_cache, SeenCache, SeenCacheStats, SeenNetwork, SeenObject};
 pub use crate::serv::{DummyAdapter, Server};
-pub use crate::store::{PeerData, State};
+pub use crate::store::{GossipTopicData, PeerData, State};
+pub use crate::tx_relay_limiter::{TxRelayLimiter, TxRelayStats};
 pub use crate::types::{
-	Capabilities, ChainAdapter, Direction, Error, P2PConfig, PeerAddr, PeerInfo, ReasonForBan,
-	Seeding, TxHashSetRead, MAX_BLOCK_HEADERS, MAX_LOCATORS, MAX_PEER_ADDRS,
+	Capabilities, ChainAdapter, Direction, Error, Libp2pConfig, P2PConfig, PeerAddr,
+	PeerDiversityReport, PeerFilter, PeerGroupConfig, PeerInfo, PeerStoreStats, PeerTargetsConfig,
+	ReasonForBan, Seeding, TxHashSetRead, MAX_BLOCK_HEADERS, MAX_LOCATORS, MAX_PEER_ADDRS,
 };
 
 pub use crate::libp2p_connection::{
 	add_new_peer, build_integrity_message, get_libp2p_connections, read_message_data,
-	run_libp2p_node, set_seed_list,
+	run_libp2p_node, send_node_api_request, set_seed_list,
 };
+pub use crate::libp2p_fragments::{build_fragments, fragment_data_size};
+pub use libp2p::PeerId;
@@ -16,6 +16,8 @@
 //! the peer-to-peer server, the blockchain and the transaction pool) and acts
 //! as a facade.
 
+use libp2p::core::multiaddr::Protocol;
+use libp2p::core::transport::OptionalTransport;
 use libp2p::core::Multiaddr;
 use libp2p::{
 	core::{
@@ -28,8 +30,9 @@ use libp2p::{
 	mplex::MplexConfig,
 	noise::{self, NoiseConfig, X25519Spec},
 	swarm::SwarmBuilder,
+	tcp::TokioTcpConfig,
 	yamux::YamuxConfig,
-	PeerId, Swarm, Transport,
+	NetworkBehaviour, PeerId, Swarm, Transport,
 };
 use libp2p_tokio_socks5::Socks5TokioTcpConfig;
 
@@ -39,24 +42,30 @@ use libp2p::gossipsub::{
 use libp2p::gossipsub::{Gossipsub, MessageAcceptance, TopicHash};
 
 use crate::core::global;
-use crate::types::Error;
+use crate::libp2p_fragments::fragmented_gossip_handler;
+use crate::libp2p_node_api::{self, NodeApiBehaviour, NodeApiEvent};
+use crate::store::GossipTopicData;
+use crate::types::{Error, Libp2pConfig, SimulationCheck};
 use crate::PeerAddr;
-use async_std::task;
 use chrono::Utc;
 use ed25519_dalek::PublicKey as DalekPublicKey;
 use futures::{future, prelude::*};
-use grin_core::core::hash::Hash;
+use grin_core::core::hash::{Hash, Hashed};
 use grin_core::core::TxKernel;
 use grin_core::libtx::aggsig;
+use grin_util::secp::constants::PEDERSEN_COMMITMENT_SIZE;
 use grin_util::secp::pedersen::Commitment;
-use grin_util::secp::rand::Rng;
 use grin_util::secp::{ContextFlag, Message, Secp256k1, Signature};
-use grin_util::RwLock;
 use grin_util::{Mutex, OnionV3Address, OnionV3AddressError, ToHex};
+use grin_util::{RwLock, StopState};
 use libp2p::core::network::NetworkInfo;
+use lru_cache::LruCache;
 use rand::seq::SliceRandom;
-use std::collections::VecDeque;
+use schemars::JsonSchema;
+use std::collections::{HashSet, VecDeque};
 use std::convert::TryInto;
+use std::fmt;
+use std::sync::atomic::{AtomicBool, AtomicU32, AtomicU64, Ordering};
 use std::sync::Arc;
 use std::time::Instant;
 use std::{
@@ -73,8 +82,65 @@ impl libp2p::core::Executor for TokioExecutor {
 	}
 }
 
+/// Network behaviour of the mwc libp2p node. Gossipsub for messaging and
+/// request-response for the node API calls.
+#[derive(NetworkBehaviour)]
+#[behaviour(out_event = "MwcBehaviourEvent", event_process = false)]
+pub struct MwcBehaviour {
+	/// Gossip messaging
+	pub gossipsub: Gossipsub,
+	/// Foreign API mirroring
+	pub node_api: NodeApiBehaviour,
+}
+
+/// Events that are produced by MwcBehaviour
+#[derive(Debug)]
+pub enum MwcBehaviourEvent {
+	/// Gossipsub event
+	Gossipsub(GossipsubEvent),
+	/// Node API request-response event
+	NodeApi(NodeApiEvent),
+}
+
+impl From<GossipsubEvent> for MwcBehaviourEvent {
+	fn from(event: GossipsubEvent) -> Self {
+		MwcBehaviourEvent::Gossipsub(event)
+	}
+}
+
+impl From<NodeApiEvent> for MwcBehaviourEvent {
+	fn from(event: NodeApiEvent) -> Self {
+		MwcBehaviourEvent::NodeApi(event)
+	}
+}
+
+/// Discovered libp2p peer onion address with dialing history
+#[derive(Clone, Debug)]
+pub struct Libp2pPeerEntry {
+	/// Onion address of the peer
+	pub address: String,
+	/// Number of failed dials in a row
+	pub failures: u32,
+	/// Unix time when the next dial attempt is allowed
+	pub next_dial: i64,
+	/// Clearnet multiaddress announced by the peer. If both sides support clearnet,
+	/// it is dialed instead of the onion address.
+	pub clearnet_address: Option<String>,
+}
+
+impl Libp2pPeerEntry {
+	fn new(address: String) -> Self {
+		Libp2pPeerEntry {
+			address,
+			failures: 0,
+			next_dial: 0,
+			clearnet_address: None,
+		}
+	}
+}
+
 /// Message that was received from libp2p gossipsub network
-#[derive(Clone, Debug, Serialize, Deserialize)]
+#[derive(Clone, Debug, Serialize, Deserialize, JsonSchema)]
 pub struct ReceivedMessage {
 	/// Unix timestamp when this message was received
 	pub timestamp: i64,
@@ -90,25 +156,583 @@ pub struct ReceivedMessage {
 
 const MESSAGING_RECEIVED_LIMIT: usize = 1000;
 
+/// Per topic gossip message counters
+#[derive(Clone, Debug, Default, Serialize, Deserialize, JsonSchema)]
+pub struct Libp2pTopicStats {
+	/// Number of received messages
+	pub received: u64,
+	/// Number of messages that pass validation and was forwarded
+	pub accepted: u64,
+	/// Number of invalid messages, senders are penalized for them
+	pub rejected: u64,
+	/// Number of messages that we was unable to validate
+	pub ignored: u64,
+}
+
+/// libp2p node statistics. Updated by the swarm polling loop.
+#[derive(Clone, Debug, Default, Serialize, Deserialize, JsonSchema)]
+pub struct Libp2pStats {
+	/// Number of established connections
+	pub connections: u32,
+	/// Number of outgoing connections that are in progress
+	pub dials_in_flight: u32,
+	/// Number of received gossip messages
+	pub messages_received: u64,
+	/// Number of gossip messages that pass validation
+	pub messages_accepted: u64,
+	/// Number of invalid gossip messages
+	pub messages_rejected: u64,
+	/// Number of gossip messages that we was unable to validate
+	pub messages_ignored: u64,
+	/// Size of received gossip messages in bytes
+	pub bytes_in: u64,
+	/// Size of published gossip messages in bytes
+	pub bytes_out: u64,
+	/// Counters per topic
+	pub topics: HashMap<String, Libp2pTopicStats>,
+	/// Integrity messages history per kernel, used for the message rate limit
+	#[serde(default)]
+	pub request_cache: IntegrityRequestCacheStats,
+}
+
+/// Statistics of the integrity messages history cache
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct IntegrityRequestCacheStats {
+	/// Number of the tracked integrity kernels
+	pub kernels: usize,
+	/// Number of the messages in the history of all kernels
+	pub calls: usize,
+	/// Kernels dropped because they were reverted by the chain reorg
+	pub purged_reorg: u64,
+	/// Kernels dropped because they are below the INTEGRITY_FEE_VALID_BLOCKS horizon
+	pub purged_horizon: u64,
+	/// Kernels dropped because their history is expired
+	pub purged_expired: u64,
+}
+
+impl Libp2pStats {
+	fn record_message(&mut self, topic: &TopicHash, size: usize, acceptance: &MessageAcceptance) {
+		self.messages_received += 1;
+		self.bytes_in += size as u64;
+		let topic_stats = self
+			.topics
+			.entry(topic.as_str().to_string())
+			.or_insert(Libp2pTopicStats::default());
+		topic_stats.received += 1;
+		match acceptance {
+			MessageAcceptance::Accept => {
+				self.messages_accepted += 1;
+				topic_stats.accepted += 1;
+			}
+			MessageAcceptance::Reject => {
+				self.messages_rejected += 1;
+				topic_stats.rejected += 1;
+			}
+			MessageAcceptance::Ignore => {
+				self.messages_ignored += 1;
+				topic_stats.ignored += 1;
+			}
+		}
+	}
+}
+
+/// Gossip message record, collected when the gossip tracing is enabled
+#[derive(Clone, Debug, Serialize, Deserialize, JsonSchema)]
+pub struct GossipTraceEntry {
+	/// Unix timestamp (milliseconds) when the message processing was finished
+	pub timestamp: i64,
+	/// Gossipsub message ID
+	pub message_id: String,
+	/// Message topic
+	pub topic: String,
+	/// Peer that propagated the message to us
+	pub peer_id: String,
+	/// Peer that published the message, if known
+	pub source: Option<String>,
+	/// Message size in bytes
+	pub size: usize,
+	/// Validation verdict: Accept, Reject or Ignore
+	pub verdict: String,
+	/// Time spent on the message validation and handling, microseconds
+	pub handler_latency_us: u64,
+}
+
+/// Bounded ring buffer with the most recent gossip messages
+pub struct GossipTrace {
+	entries: VecDeque<GossipTraceEntry>,
+	capacity: usize,
+}
+
+impl GossipTrace {
+	/// Create the trace that keeps up to capacity messages
+	pub fn new(capacity: usize) -> Self {
+		GossipTrace {
+			entries: VecDeque::with_capacity(capacity),
+			capacity,
+		}
+	}
+
+	/// Add the message, the oldest one is dropped if the trace is full
+	pub fn push(&mut self, entry: GossipTraceEntry) {
+		if self.entries.len() >= self.capacity {
+			self.entries.pop_front();
+		}
+		self.entries.push_back(entry);
+	}
+
+	/// Most recent messages first, optionally filtered by topic and propagation peer
+	pub fn query(
+		&self,
+		topic: Option<&str>,
+		peer_id: Option<&str>,
+		limit: Option<usize>,
+	) -> Vec<GossipTraceEntry> {
+		self.entries
+			.iter()
+			.rev()
+			.filter(|e| topic.map_or(true, |t| e.topic == t))
+			.filter(|e| peer_id.map_or(true, |p| e.peer_id == p))
+			.take(limit.unwrap_or(self.capacity))
+			.cloned()
+			.collect()
+	}
+}
+
+/// Emergency relay deny list. Gossip messages for the denied topics, or with the message ID that
+/// matches a denied pattern, are not processed and not relayed.
+#[derive(Clone, Debug, Default, Serialize, Deserialize, PartialEq, JsonSchema)]
+pub struct RelayDenyList {
+	/// Denied topics
+	pub topics: Vec<String>,
+	/// Denied message ID patterns, `*` matches any sequence of characters
+	pub message_id_patterns: Vec<String>,
+}
+
+impl RelayDenyList {
+	/// Check if the message with this topic and ID must not be relayed
+	pub fn is_denied(&self, topic: &str, message_id: &str) -> bool {
+		self.topics.iter().any(|t| t == topic)
+			|| self
+				.message_id_patterns
+				.iter()
+				.any(|p| wildcard_match(p, message_id))
+	}
+}
+
+/// Match the text with the pattern where `*` matches any sequence of characters
+fn wildcard_match(pattern: &str, text: &str) -> bool {
+	let parts: Vec<&str> = pattern.split('*').collect();
+	if parts.len() == 1 {
+		return pattern == text;
+	}
+	let first = parts[0];
+	let last = parts[parts.len() - 1];
+	if !text.starts_with(first) || !text.ends_with(last) || text.len() < first.len() + last.len() {
+		return false;
+	}
+	let mut rest = &text[first.len()..text.len() - last.len()];
+	for part in &parts[1..parts.len() - 1] {
+		match rest.find(part) {
+			Some(i) => rest = &rest[i + part.len()..],
+			None => return false,
+		}
+	}
+	true
+}
+
+/// Cache of recently seen gossip messages. The same payload can reach us many times through
+/// the mesh, duplicates are dropped before any validation work.
+pub struct MessageDedupCache {
+	/// Hash of topic and payload => unix time when the message was seen
+	cache: LruCache<Hash, i64>,
+	/// How long the message is considered as a duplicate, seconds
+	ttl: i64,
+}
+
+impl MessageDedupCache {
+	/// Create dedup cache
+	pub fn new(capacity: usize, ttl: i64) -> Self {
+		MessageDedupCache {
+			cache: LruCache::new(capacity),
+			ttl,
+		}
+	}
+
+	/// Check if the message was seen during the TTL. New message is registered as seen.
+	pub fn is_duplicate(&mut self, topic: &TopicHash, data: &[u8], now: i64) -> bool {
+		let mut key = topic.as_str().as_bytes().to_vec();
+		key.extend_from_slice(data);
+		let key = key.hash();
+		if let Some(seen) = self.cache.get_mut(&key) {
+			if now - *seen <= self.ttl {
+				return true;
+			}
+		}
+		self.cache.insert(key, now);
+		false
+	}
+}
+
+/// Cache of the verified integrity kernels. Peers normally reuse the same kernel for many messages,
+/// so the kernel MMR search is done once per ttl_blocks. The cache must be cleared on the chain reorg.
+pub struct IntegrityKernelCache {
+	/// Kernel excess => kernel and the chain height when it was verified
+	cache: LruCache<Commitment, (TxKernel, u64)>,
+	/// For how many blocks the verified kernel is trusted
+	ttl_blocks: u64,
+}
+
+impl IntegrityKernelCache {
+	/// Create kernel cache
+	pub fn new(capacity: usize, ttl_blocks: u64) -> Self {
+		IntegrityKernelCache {
+			cache: LruCache::new(capacity),
+			ttl_blocks,
+		}
+	}
+
+	/// Get the kernel from the cache or look it up with kernel_validation_fn. Only found kernels are cached.
+	/// height - current chain height
+	pub fn lookup(
+		&mut self,
+		excess: &Commitment,
+		height: u64,
+		kernel_validation_fn: &impl Fn(&Commitment) -> Result<Option<TxKernel>, Error>,
+	) -> Result<Option<TxKernel>, Error> {
+		if let Some((kernel, verified)) = self.cache.get_mut(excess) {
+			if height.saturating_sub(*verified) <= self.ttl_blocks {
+				return Ok(Some(kernel.clone()));
+			}
+		}
+		let kernel = (kernel_validation_fn)(excess)?;
+		match &kernel {
+			Some(kernel) => {
+				self.cache.insert(excess.clone(), (kernel.clone(), height));
+			}
+			None => {
+				self.cache.remove(excess);
+			}
+		}
+		Ok(kernel)
+	}
+
+	/// Drop the cached kernel
+	pub fn remove(&mut self, excess: &Commitment) {
+		self.cache.remove(excess);
+	}
+
+	/// Drop all cached kernels
+	pub fn clear(&mut self) {
+		self.cache.clear();
+	}
+}
+
+/// Rejected integrity messages history per publisher. Publisher that repeatedly sends
+/// invalid messages is banned.
+pub struct IntegrityRejects {
+	/// Publisher => timestamps of the rejected messages
+	rejects: HashMap<PeerId, VecDeque<i64>>,
+	/// Number of rejects that triggers the ban
+	limit: usize,
+	/// Time window for the rejects, seconds
+	period: i64,
+}
+
+impl IntegrityRejects {
+	/// Create rejects history
+	pub fn new(limit: usize, period: i64) -> Self {
+		IntegrityRejects {
+			rejects: HashMap::new(),
+			limit,
+			period,
+		}
+	}
+
+	/// Register rejected message. Returns true if the peer reached the limit and must be banned.
+	pub fn register(&mut self, peer: &PeerId, now: i64) -> bool {
+		let period = self.period;
+		let history = self.rejects.entry(peer.clone()).or_insert(VecDeque::new());
+		history.push_back(now);
+		while history.front().map_or(false, |t| *t <= now - period) {
+			history.pop_front();
+		}
+		if history.len() >= self.limit {
+			self.rejects.remove(peer);
+			return true;
+		}
+		false
+	}
+
+	/// Remove the expired history
+	pub fn cleanup(&mut self, now: i64) {
+		let period = self.period;
+		self.rejects
+			.retain(|_peer, history| *history.back().unwrap_or(&0) > now - period);
+	}
+}
+
+/// Message that is validated and waits to be forwarded
+struct ForwardEntry<T> {
+	item: T,
+	size: usize,
+	fee_multiple: u64,
+	/// Unix time in milliseconds when the message was queued
+	queued: i64,
+}
+
+/// Starvation limit and expiration time of the forward queue, milliseconds. Gossipsub keeps
+/// the messages at its cache for history_length heartbeats, the message can get there just
+/// before the heartbeat. The starving messages get the second half of that time.
+pub fn forward_queue_timing(config: &gossipsub::GossipsubConfig) -> (i64, i64) {
+	let heartbeat = config.heartbeat_interval().as_millis() as i64;
+	let expire = std::cmp::max(config.history_length() as i64 - 1, 1) * heartbeat;
+	(expire / 2, expire)
+}
+
+/// Forwarding queue for the validated gossip messages when the forwarding bandwidth is limited.
+/// Messages with the higher integrity fee (in base fees) are forwarded first. Messages that are
+/// waiting longer than `max_wait` are forwarded before any others, so low fee messages are not starving.
+pub struct ForwardQueue<T> {
+	entries: Vec<ForwardEntry<T>>,
+	capacity: usize,
+	/// Forwarding budget, bytes per second. Up to one second of budget can be accumulated.
+	bytes_per_sec: u64,
+	/// Number of peers every message is forwarded to, the mesh size
+	fanout: u64,
+	/// Starvation limit, milliseconds
+	max_wait: i64,
+	/// Available budget, bytes. Can go negative after a large message.
+	budget: i64,
+	/// Unix time in milliseconds of the last budget refill
+	last_refill: i64,
+}
+
+impl<T> ForwardQueue<T> {
+	/// Create forwarding queue
+	pub fn new(capacity: usize, bytes_per_sec: u64, fanout: u64, max_wait: i64, now: i64) -> Self {
+		ForwardQueue {
+			entries: vec![],
+			capacity,
+			bytes_per_sec,
+			fanout: std::cmp::max(fanout, 1),
+			max_wait,
+			budget: bytes_per_sec as i64,
+			last_refill: now,
+		}
+	}
+
+	/// Number of the waiting messages
+	pub fn len(&self) -> usize {
+		self.entries.len()
+	}
+
+	/// Check if there are no waiting messages
+	pub fn is_empty(&self) -> bool {
+		self.entries.is_empty()
+	}
+
+	/// Queue the message. If the queue is full, the message with the lowest priority
+	/// (possibly the new one) is dropped and returned, so its validation can be reported.
+	pub fn push(&mut self, item: T, size: usize, fee_multiple: u64, now: i64) -> Option<T> {
+		self.entries.push(ForwardEntry {
+			item,
+			size,
+			fee_multiple,
+			queued: now,
+		});
+		if self.entries.len() <= self.capacity {
+			return None;
+		}
+		// Lowest fee, the most recent one is dropped first
+		let idx = self
+			.entries
+			.iter()
+			.enumerate()
+			.min_by_key(|(i, e)| (e.fee_multiple, std::cmp::Reverse(*i)))
+			.map(|(i, _)| i)?;
+		Some(self.entries.remove(idx).item)
+	}
+
+	/// Messages that can be forwarded now with the available budget, in the forwarding order.
+	pub fn pop_ready(&mut self, now: i64) -> Vec<T> {
+		let elapsed = std::cmp::max(now - self.last_refill, 0);
+		self.last_refill = now;
+		self.budget = std::cmp::min(
+			self.budget + elapsed * self.bytes_per_sec as i64 / 1000,
+			self.bytes_per_sec as i64,
+		);
+
+		let mut res = vec![];
+		while self.budget > 0 && !self.entries.is_empty() {
+			let starve_limit = now - self.max_wait;
+			// Starving messages go first, the oldest one first. Then the highest fee, FIFO for the same fee.
+			let idx = match self.entries.iter().position(|e| e.queued <= starve_limit) {
+				Some(i) => i,
+				None => self
+					.entries
+					.iter()
+					.enumerate()
+					.max_by_key(|(i, e)| (e.fee_multiple, std::cmp::Reverse(*i)))
+					.map(|(i, _)| i)
+					.unwrap_or(0),
+			};
+			let entry = self.entries.remove(idx);
+			self.budget -= (entry.size as u64 * self.fanout) as i64;
+			res.push(entry.item);
+		}
+		res
+	}
+
+	/// Remove messages that are waiting longer than `expire` milliseconds. Gossipsub doesn't keep
+	/// them any more, so they can't be forwarded.
+	pub fn expire(&mut self, expire: i64, now: i64) -> Vec<T> {
+		let mut res = vec![];
+		let mut i = 0;
+		while i < self.entries.len() {
+			if self.entries[i].queued <= now - expire {
+				res.push(self.entries.remove(i).item);
+			} else {
+				i += 1;
+			}
+		}
+		res
+	}
+}
+
+/// Ban list that is shared with the classic p2p layer. libp2p peers are identified by the onion address.
+pub trait Libp2pBanList: Send + Sync {
+	/// Check if the peer with this onion address is banned
+	fn is_onion_banned(&self, onion_address: &str) -> bool;
+
+	/// Ban the peer with this onion address
+	fn ban_onion(&self, onion_address: &str);
+}
+
+/// Storage of the dynamically subscribed topics, so the node resubscribes after the restart.
+pub trait Libp2pTopicStore: Send + Sync {
+	/// Save the subscribed topic
+	fn save_topic(&self, topic: &GossipTopicData);
+
+	/// Delete the topic that we are not subscribed to any more
+	fn delete_topic(&self, topic: &str);
+
+	/// All stored topics
+	fn load_topics(&self) -> Vec<GossipTopicData>;
+}
+
+/// Gossip message handler. Arguments: sender address, topic hash, message (no header),
+/// paid integrity fee. Error means that the message can't be processed, it is handled
+/// according to the handler error policy.
+pub type GossipHandler =
+	Arc<dyn Fn(&String, &TopicHash, Vec<u8>, u64) -> Result<(), Error> + Send + Sync>;
+
+/// Box the closure into the gossip message handler
+pub fn gossip_handler<F>(handler: F) -> GossipHandler
+where
+	F: Fn(&String, &TopicHash, Vec<u8>, u64) -> Result<(), Error> + Send + Sync + 'static,
+{
+	Arc::new(handler)
+}
+
+/// What to do when the gossip message handler fails
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum HandlerErrorPolicy {
+	/// Log the failure, the message is not forwarded
+	Log,
+	/// Reject the message, gossipsub penalizes the peer that sent it
+	Penalize,
+	/// Log the failure and unsubscribe from the topic after that many failures in a row
+	Unsubscribe(u32),
+}
+
+/// Handler of the subscribed topic
+#[derive(Clone)]
+struct TopicHandler {
+	handler: GossipHandler,
+	policy: HandlerErrorPolicy,
+	/// Handler failures in a row
+	failures: Arc<AtomicU32>,
+}
+
+#[allow(unused_variables)]
+/// Observer for the libp2p node lifecycle events. Callbacks are called from the swarm polling
+/// loop, so they must be fast and must not call the methods that access the swarm.
+pub trait Libp2pObserver: Send + Sync {
+	/// Connection with the peer is established
+	fn peer_connected(&self, peer: &PeerId) {}
+
+	/// All connections with the peer are closed
+	fn peer_disconnected(&self, peer: &PeerId) {}
+
+	/// Gossip message is received from the peer, before validation
+	fn message_received(&self, peer: &PeerId, topic: &str, size: usize) {}
+
+	/// Gossip message from the peer is rejected as invalid
+	fn message_rejected(&self, peer: &PeerId, topic: &str) {}
+
+	/// Unable to connect to the peer with onion address
+	fn dial_failed(&self, address: &str) {}
+}
+
 lazy_static! {
-	static ref LIBP2P_SWARM: Mutex<Option<Swarm<Gossipsub>>> = Mutex::new(None);
+	static ref LIBP2P_SWARM: Mutex<Option<Swarm<MwcBehaviour>>> = Mutex::new(None);
 	/// Discovered Peer Onion addresses
-	static ref LIBP2P_PEERS: RwLock<HashMap<String, (Vec<String>, u64)>> =
+	static ref LIBP2P_PEERS: RwLock<HashMap<String, (Vec<Libp2pPeerEntry>, u64)>> =
 		RwLock::new(HashMap::new());
 
 	static ref THIS_PEER_ID: RwLock<Option<PeerId>> = RwLock::new(None);
-	// Message handlers arguments: topic hash, message (no header), paid integrity fee
-	//   Handler must return false if the message is incorrect, so the peer must be banned.
-	static ref LIBP2P_MESSAGE_HANDLERS: RwLock<HashMap<TopicHash, (fn(sender_address: &String, topic: &TopicHash, Vec<u8>, u64) -> bool, Topic)>> = RwLock::new(HashMap::new());
+	// Message handlers of the subscribed topics
+	static ref LIBP2P_MESSAGE_HANDLERS: RwLock<HashMap<TopicHash, (TopicHandler, Topic)>> = RwLock::new(HashMap::new());
 
 	/// Seeds peer list. Will use it if not connections are available.
 	static ref SEED_LIST: RwLock<Vec<PeerAddr>> = RwLock::new(vec![]);
+	/// Clearnet seeds, (onion address, clearnet multiaddress)
+	static ref CLEARNET_SEED_LIST: RwLock<Vec<(String, String)>> = RwLock::new(vec![]);
+	/// Pinned peers by the onion address. They are kept connected and never evicted.
+	static ref PINNED_PEERS: RwLock<HashMap<String, Libp2pPeerEntry>> = RwLock::new(HashMap::new());
 
 	// Topics that we are listening now
 	static ref MESSAGING_TOPICS: RwLock<HashMap<TopicHash, (String, Topic, u64)>> = RwLock::new(HashMap::new());
 
 	/// Received messages
 	static ref MESSAGING_RECEIVED: RwLock<VecDeque<ReceivedMessage>> = RwLock::new(VecDeque::new());
+
+	/// libp2p node statistics
+	static ref LIBP2P_STATS: RwLock<Libp2pStats> = RwLock::new(Libp2pStats::default());
+
+	/// Observers for the libp2p node events
+	static ref LIBP2P_OBSERVERS: RwLock<Vec<Arc<dyn Libp2pObserver>>> = RwLock::new(vec![]);
+
+	/// TTL for the message dedup cache records, seconds
+	static ref MESSAGE_DEDUP_TTL: RwLock<i64> = RwLock::new(MESSAGE_DEDUP_TTL_DEFAULT);
+
+	/// Fee base that the running libp2p node uses for the integrity fee validation
+	static ref INTEGRITY_FEE_BASE: RwLock<Option<u64>> = RwLock::new(None);
+
+	/// Gossipsub mesh configuration
+	static ref LIBP2P_CONFIG: RwLock<Libp2pConfig> = RwLock::new(Libp2pConfig::default());
+
+	/// Ban list of the classic p2p layer
+	static ref LIBP2P_BAN_LIST: RwLock<Option<Arc<dyn Libp2pBanList>>> = RwLock::new(None);
+
+	/// Storage of the subscribed topics
+	static ref LIBP2P_TOPIC_STORE: RwLock<Option<Arc<dyn Libp2pTopicStore>>> = RwLock::new(None);
+
+	/// Named handlers of the plugin topics
+	static ref LIBP2P_TOPIC_HANDLERS: RwLock<HashMap<String, (GossipHandler, HandlerErrorPolicy)>> = RwLock::new(HashMap::new());
+
+	/// Trace of the recent gossip messages, None if the gossip tracing is disabled
+	static ref GOSSIP_TRACE: RwLock<Option<GossipTrace>> = RwLock::new(None);
+
+	// Topics and message IDs that are not relayed
+	static ref RELAY_DENY_LIST: RwLock<RelayDenyList> = RwLock::new(RelayDenyList::default());
+
+	// Bandwidth limit for the forwarded integrity messages, bytes per second. 0 - no limit.
+	static ref FORWARD_BANDWIDTH: RwLock<u64> = RwLock::new(0);
+
+	/// Calls history of the integrity kernels. The gossip messages and the messages that are
+	/// posted with the API share it, so a kernel has a single rate limit.
+	pub(crate) static ref INTEGRITY_REQUESTS: Mutex<HashMap<Commitment, VecDeque<i64>>> = Mutex::new(HashMap::new());
 }
 
 // Message with same integrity output consensus
@@ -117,6 +741,66 @@ pub const INTEGRITY_CALL_HISTORY_LEN_LIMIT: usize = 10;
 // call interval limit, in second.
 pub const INTEGRITY_CALL_MAX_PERIOD: i64 = 15;
 
+/// Dial backoff for the first failure, seconds. Doubled with every next failure.
+pub const LIBP2P_DIAL_BACKOFF_BASE: i64 = 30;
+/// Max dial backoff, seconds
+pub const LIBP2P_DIAL_BACKOFF_MAX: i64 = 6 * 3600;
+/// Peer address is evicted after that many failed dials in a row
+pub const LIBP2P_DIAL_MAX_FAILURES: u32 = 8;
+/// Interval of the stop request checks by the swarm polling task
+const LIBP2P_STOP_CHECK_INTERVAL: Duration = Duration::from_secs(1);
+/// Interval of the integrity requests purge and the request cache stats update
+const INTEGRITY_PURGE_INTERVAL: Duration = Duration::from_secs(60);
+/// Dial backoff of the pinned peers for the first failure, seconds. Grows linearly.
+pub const LIBP2P_PINNED_DIAL_BACKOFF_BASE: i64 = 15;
+/// Max dial backoff of the pinned peers, seconds
+pub const LIBP2P_PINNED_DIAL_BACKOFF_MAX: i64 = 120;
+
+/// Gossip topic for the tx receipts
+pub const TX_RECEIPT_TOPIC: &str = "TxReceipts";
+/// Receipt with timestamp older than that (seconds) will be rejected
+pub const TX_RECEIPT_MAX_AGE: i64 = 600;
+/// Max number of kernels at a single receipt
+pub const TX_RECEIPT_KERNELS_LIMIT: usize = 100;
+/// Max size of the receipt message, bytes. Includes the serialization overhead of the
+/// version, timestamp, kernels counter and the kernel length prefixes.
+pub const TX_RECEIPT_MAX_SIZE: usize =
+	32 + TX_RECEIPT_KERNELS_LIMIT * (PEDERSEN_COMMITMENT_SIZE + 8);
+
+/// Gossip topic for the clearnet address announcements
+pub const CLEARNET_ADDRESS_TOPIC: &str = "ClearnetAddress";
+/// Interval between our clearnet address announcements, seconds
+pub const CLEARNET_ADDRESS_ANNOUNCE_INTERVAL: u64 = 1800;
+/// Announcement with timestamp older than that (seconds) will be rejected
+pub const CLEARNET_ADDRESS_MAX_AGE: i64 = 600;
+/// Max number of the unknown peers that we keep from the clearnet address announcements
+pub const CLEARNET_SELF_PEERS_MAX: usize = 256;
+
+/// Gossip topic for the signed peer exchange
+pub const PEER_EXCHANGE_TOPIC: &str = "PeerExchangeV2";
+/// Interval between our peer exchange publishing, seconds
+pub const PEER_EXCHANGE_INTERVAL: u64 = 600;
+/// Peer exchange records with timestamp older than that (seconds) will be rejected
+pub const PEER_EXCHANGE_MAX_AGE: i64 = 600;
+
+/// Max number of messages at the dedup cache
+pub const MESSAGE_DEDUP_CACHE_SIZE: usize = 10_000;
+/// Default TTL for the dedup cache records, seconds
+pub const MESSAGE_DEDUP_TTL_DEFAULT: i64 = 120;
+
+/// Peer is banned after that many rejected integrity messages during INTEGRITY_REJECT_PERIOD
+pub const INTEGRITY_REJECT_BAN_LIMIT: usize = 5;
+/// Time window for the rejected integrity messages, seconds
+pub const INTEGRITY_REJECT_PERIOD: i64 = 3600;
+
+/// Max number of kernels at the integrity kernel cache
+pub const INTEGRITY_KERNEL_CACHE_SIZE: usize = 1000;
+/// For how many blocks the verified integrity kernel is trusted without the chain lookup
+pub const INTEGRITY_KERNEL_CACHE_TTL_BLOCKS: u64 = 5;
+
+/// Max number of the validated messages that are waiting to be forwarded
+pub const FORWARD_QUEUE_SIZE: usize = 1000;
+
 /// Number of top block when integrity fee is valid.  24 hours + 3 blocks. 3 blocks it is a minimum confirmations numbers that is required
 pub const INTEGRITY_FEE_VALID_BLOCKS: u64 = 1443;
 /// Minimum integrity fee value in term of Base fees
@@ -130,12 +814,43 @@ pub fn set_this_peer_id(peer_id: &PeerId) {
 }
 
 /// Init Swarm instance. App expecting to have only single instance for everybody.
-pub fn init_libp2p_swarm(swarm: Swarm<Gossipsub>) {
+pub fn init_libp2p_swarm(swarm: Swarm<MwcBehaviour>) {
 	LIBP2P_SWARM.lock().replace(swarm);
 }
 /// Report that libp2p connection is done
 pub fn reset_libp2p_swarm() {
 	LIBP2P_SWARM.lock().take();
+	libp2p_node_api::reset_node_api_requests();
+	let mut stats = LIBP2P_STATS.write();
+	stats.connections = 0;
+	stats.dials_in_flight = 0;
+}
+
+/// Graceful stop for the swarm. Unsubscribing from all topics so the peers will know that we are leaving
+/// and dropping the swarm. Swarm drop closes all connections (and Tor circuits).
+pub fn shutdown_libp2p_swarm() {
+	// Collecting the topics first, we don't want to hold the handlers lock with the swarm one
+	let topics: Vec<Topic> = LIBP2P_MESSAGE_HANDLERS
+		.read()
+		.values()
+		.map(|(_fn, topic)| topic.clone())
+		.collect();
+	if let Some(swarm) = &mut *LIBP2P_SWARM.lock() {
+		for topic in &topics {
+			if let Err(e) = swarm.gossipsub.unsubscribe(topic) {
+				debug!(
+					"Unable to unsubscribe from the topic {} on shutdown, {}",
+					topic, e
+				);
+			}
+		}
+		let peers = Swarm::network_info(swarm).into_peers();
+		info!(
+			"Shutting down libp2p swarm, closing {} connections",
+			peers.len()
+		);
+	}
+	reset_libp2p_swarm();
 }
 
 /// Report the seed list. We will add them as a found peers. That should be enough for bootstraping
@@ -154,12 +869,205 @@ pub fn set_seed_list(seed_list: &Vec<PeerAddr>, update_seed_list: bool) {
 			_ => {}
 		}
 	}
+
+	for (onion_address, clearnet_address) in CLEARNET_SEED_LIST.read().iter() {
+		set_peer_clearnet_address(onion_address, clearnet_address);
+	}
+}
+
+/// Report the clearnet seeds list, entries are (onion address, clearnet multiaddress) pairs.
+/// The onion address is used as the peer identity, the node doesn't need Tor to dial the seed.
+pub fn set_clearnet_seed_list(seed_list: &Vec<(String, String)>) {
+	*CLEARNET_SEED_LIST.write() = seed_list.clone();
+	for (onion_address, clearnet_address) in seed_list {
+		set_peer_clearnet_address(onion_address, clearnet_address);
+	}
+}
+
+/// Register observer for the libp2p node events
+pub fn register_observer(observer: Arc<dyn Libp2pObserver>) {
+	LIBP2P_OBSERVERS.write().push(observer);
+}
+
+fn notify_observers<F: Fn(&dyn Libp2pObserver)>(f: F) {
+	for observer in LIBP2P_OBSERVERS.read().iter() {
+		f(observer.as_ref());
+	}
+}
+
+/// Update stats with the message validation result, trace the message if gossip tracing
+/// is enabled and notify observers about rejected messages
+fn record_message_result(
+	peer_id: &PeerId,
+	id: &dyn fmt::Display,
+	source: Option<&PeerId>,
+	topic: &TopicHash,
+	size: usize,
+	received: Instant,
+	acceptance: &MessageAcceptance,
+) {
+	LIBP2P_STATS.write().record_message(topic, size, acceptance);
+	if let Some(trace) = GOSSIP_TRACE.write().as_mut() {
+		trace.push(GossipTraceEntry {
+			timestamp: Utc::now().timestamp_millis(),
+			message_id: id.to_string(),
+			topic: topic.as_str().to_string(),
+			peer_id: peer_id.to_string(),
+			source: source.map(|s| s.to_string()),
+			size,
+			verdict: format!("{:?}", acceptance),
+			handler_latency_us: received.elapsed().as_micros() as u64,
+		});
+	}
+	if let MessageAcceptance::Reject = acceptance {
+		notify_observers(|o| o.message_rejected(peer_id, topic.as_str()));
+	}
+}
+
+/// Enable gossip message tracing with a ring buffer of the provided size, 0 disables it.
+pub fn set_gossip_trace_size(size: usize) {
+	*GOSSIP_TRACE.write() = if size > 0 {
+		Some(GossipTrace::new(size))
+	} else {
+		None
+	};
+}
+
+/// Traced gossip messages, most recent first, optionally filtered by topic and by
+/// the peer that propagated the message. Empty if the gossip tracing is disabled.
+pub fn get_gossip_trace(
+	topic: Option<String>,
+	peer_id: Option<String>,
+	limit: Option<usize>,
+) -> Vec<GossipTraceEntry> {
+	match GOSSIP_TRACE.read().as_ref() {
+		Some(trace) => trace.query(topic.as_deref(), peer_id.as_deref(), limit),
+		None => vec![],
+	}
+}
+
+/// Set TTL for the gossip message dedup cache. Applied at the next libp2p node start.
+pub fn set_message_dedup_ttl(ttl: i64) {
+	*MESSAGE_DEDUP_TTL.write() = ttl;
+}
+
+/// Replace the relay deny list. Applied to the running libp2p node immediately.
+pub fn set_relay_deny_list(deny_list: RelayDenyList) {
+	info!(
+		"libp2p relay deny list is updated, {} topics, {} message ID patterns",
+		deny_list.topics.len(),
+		deny_list.message_id_patterns.len()
+	);
+	*RELAY_DENY_LIST.write() = deny_list;
+}
+
+/// Current relay deny list
+pub fn get_relay_deny_list() -> RelayDenyList {
+	RELAY_DENY_LIST.read().clone()
+}
+
+/// Limit the bandwidth (bytes per second) for forwarding of the integrity fee messages, 0 disables the limit.
+/// When limited, the messages with the higher fee are forwarded first. Applied at the next libp2p node start.
+pub fn set_forward_bandwidth(bytes_per_sec: u64) {
+	*FORWARD_BANDWIDTH.write() = bytes_per_sec;
+}
+
+/// Set by the chain on reorg, the libp2p node drops the cached integrity kernels
+static CHAIN_REORG_NOTIFIED: AtomicBool = AtomicBool::new(false);
+
+/// Notify libp2p node about the chain reorg. Cached integrity kernels might be not on the chain any more.
+pub fn notify_chain_reorg() {
+	CHAIN_REORG_NOTIFIED.store(true, Ordering::Relaxed);
+}
+
+/// Chain tip height, every new tip moves the INTEGRITY_FEE_VALID_BLOCKS horizon
+static CHAIN_TIP_HEIGHT: AtomicU64 = AtomicU64::new(0);
+
+/// Notify libp2p node about the new chain tip. Integrity kernels that are below the horizon now
+/// are dropped from the messages history.
+pub fn notify_chain_tip(height: u64) {
+	CHAIN_TIP_HEIGHT.store(height, Ordering::Relaxed);
+}
+
+/// Set gossipsub mesh configuration, values are clamped into the valid ranges. Applied at the next libp2p node start.
+pub fn set_libp2p_config(config: &Libp2pConfig) {
+	*LIBP2P_CONFIG.write() = config.validated();
+	set_pinned_peers(&config.pinned_peers);
+}
+
+/// Set the pinned peers onion addresses. The libp2p node keeps them connected regardless of
+/// the connections number and redials them with a short backoff. Pinned peers are gossipsub
+/// explicit peers and don't count against the regular connections. Invalid addresses are skipped.
+pub fn set_pinned_peers(addresses: &[String]) {
+	let mut pinned = PINNED_PEERS.write();
+	let mut peers = HashMap::new();
+	for address in addresses {
+		let res: Result<OnionV3Address, OnionV3AddressError> = address.as_str().try_into();
+		if let Err(e) = res {
+			warn!("Invalid pinned libp2p peer address {}, {}", address, e);
+			continue;
+		}
+		let entry = pinned
+			.remove(address)
+			.unwrap_or(Libp2pPeerEntry::new(address.clone()));
+		peers.insert(address.clone(), entry);
+	}
+	*pinned = peers;
+}
+
+/// Pinned peers with their dialing history
+pub fn get_pinned_peers() -> Vec<Libp2pPeerEntry> {
+	let mut peers: Vec<Libp2pPeerEntry> = PINNED_PEERS.read().values().cloned().collect();
+	peers.sort_by(|a, b| a.address.cmp(&b.address));
+	peers
+}
+
+/// Get current libp2p node statistics
+pub fn get_libp2p_stats() -> Libp2pStats {
+	LIBP2P_STATS.read().clone()
 }
 
 pub fn get_libp2p_running() -> bool {
 	LIBP2P_SWARM.lock().is_some()
 }
 
+/// Share the ban list of the classic p2p layer with libp2p node. Banned peers are disconnected and
+/// never dialed, peers that are banned by libp2p node are added into that list.
+pub fn set_ban_list(ban_list: Arc<dyn Libp2pBanList>) {
+	LIBP2P_BAN_LIST.write().replace(ban_list);
+}
+
+/// Check if the peer is banned by the classic p2p layer
+fn is_onion_banned(onion_address: &str) -> bool {
+	match &*LIBP2P_BAN_LIST.read() {
+		Some(ban_list) => ban_list.is_onion_banned(onion_address),
+		None => false,
+	}
+}
+
+/// Check if the libp2p peer is banned by the classic p2p layer
+fn is_peer_banned(peer: &PeerId) -> bool {
+	match peer.as_onion_address() {
+		Ok(address) => is_onion_banned(&address),
+		Err(_) => false,
+	}
+}
+
+/// Ban the libp2p peer at the classic p2p layer
+fn ban_peer(peer: &PeerId) {
+	if let Ok(address) = peer.as_onion_address() {
+		if let Some(ban_list) = &*LIBP2P_BAN_LIST.read() {
+			warn!("Banning libp2p peer {}", address);
+			ban_list.ban_onion(&address);
+		}
+	}
+}
+
+/// Fee base that is used by libp2p node for the integrity fee validation. None if node was never started.
+pub fn get_integrity_fee_base() -> Option<u64> {
+	INTEGRITY_FEE_BASE.read().clone()
+}
+
 /// Get topics that we are listening
 pub fn get_topics() -> Vec<(String, Topic, u64)> {
 	MESSAGING_TOPICS
@@ -169,7 +1077,7 @@ pub fn get_topics() -> Vec<(String, Topic, u64)> {
 		.collect()
 }
 
-fn get_message_version() -> u16 {
+pub(crate) fn get_message_version() -> u16 {
 	if global::is_mainnet() {
 		1
 	} else {
@@ -177,16 +1085,19 @@ fn get_message_version() -> u16 {
 	}
 }
 
-fn listener_handler(sender_address: &String, topic: &TopicHash, data: Vec<u8>, fee: u64) -> bool {
+fn listener_handler(
+	sender_address: &String,
+	topic: &TopicHash,
+	data: Vec<u8>,
+	fee: u64,
+) -> Result<(), Error> {
 	if let Some((topic_str, _topic, min_fee)) = MESSAGING_TOPICS.read().get(topic) {
 		if fee >= *min_fee {
 			// Parse message. It should be Json string
-			let message_str = match String::from_utf8(data) {
-				Ok(s) => s,
-				Err(_) => return false,
-			};
+			let message_str = String::from_utf8(data)
+				.map_err(|_| Error::Libp2pError("Message is not a string".into()))?;
 			if serde_json::from_str::<serde_json::Value>(&message_str).is_err() {
-				return false;
+				return Err(Error::Libp2pError("Message is not a json".into()));
 			}
 
 			debug!(
@@ -211,20 +1122,46 @@ fn listener_handler(sender_address: &String, topic: &TopicHash, data: Vec<u8>, f
 			}
 		}
 	}
-	true
+	Ok(())
 }
 
-/// Start listening on the topic
+/// Handler name of the messaging topics at the topic store
+pub const MESSAGING_TOPIC_HANDLER: &str = "messaging";
+
+/// Start listening on the topic. The subscription is stored, so it is restored after the restart.
 pub fn add_topic(topic_str: &String, min_fee: u64) -> bool {
+	subscribe_messaging_topic(topic_str, min_fee, true)
+}
+
+/// Start listening on the topic from the config. The subscription is not stored.
+pub fn add_config_topic(topic_str: &String, min_fee: u64) -> bool {
+	subscribe_messaging_topic(topic_str, min_fee, false)
+}
+
+fn subscribe_messaging_topic(topic_str: &String, min_fee: u64, store: bool) -> bool {
 	let topic = Topic::new(topic_str.clone());
 
+	if store {
+		save_stored_topic(GossipTopicData {
+			topic: topic_str.clone(),
+			min_fee,
+			handler: MESSAGING_TOPIC_HANDLER.to_string(),
+		});
+	}
+
 	match MESSAGING_TOPICS
 		.write()
 		.insert(topic.hash(), (topic_str.clone(), topic, min_fee))
 	{
 		Some(_) => (), // Data updated, already subscribed
 		None => {
-			add_topic_to_libp2p(&topic_str, listener_handler);
+			// Invalid messages are rejected, so the peers that send them are penalized.
+			// Messages larger than the gossip frame come as fragments.
+			add_topic_to_libp2p(
+				&topic_str,
+				fragmented_gossip_handler(listener_handler),
+				HandlerErrorPolicy::Penalize,
+			);
 			return true;
 		}
 	}
@@ -238,6 +1175,7 @@ pub fn remove_topic(topic_str: &String) -> bool {
 	match MESSAGING_TOPICS.write().remove(&topic.hash()) {
 		Some(_) => {
 			remove_topic_from_libp2p(&topic_str);
+			delete_stored_topic(&topic_str);
 			return true;
 		}
 		None => (),
@@ -245,6 +1183,81 @@ pub fn remove_topic(topic_str: &String) -> bool {
 	return false;
 }
 
+/// Set the storage of the subscribed topics and resubscribe to the stored topics. Messaging
+/// topics are restored right away, plugin topics when their handler is registered.
+pub fn set_topic_store(store: Arc<dyn Libp2pTopicStore>) {
+	let topics = store.load_topics();
+	LIBP2P_TOPIC_STORE.write().replace(store);
+	for t in &topics {
+		if t.handler == MESSAGING_TOPIC_HANDLER {
+			let topic = Topic::new(t.topic.clone());
+			let subscribed = MESSAGING_TOPICS.read().contains_key(&topic.hash());
+			if !subscribed {
+				subscribe_messaging_topic(&t.topic, t.min_fee, false);
+			}
+		} else {
+			let handler = LIBP2P_TOPIC_HANDLERS.read().get(&t.handler).cloned();
+			if let Some((handler, policy)) = handler {
+				add_topic_to_libp2p(&t.topic, handler, policy);
+			}
+		}
+	}
+	info!("Restored {} stored gossip topics", topics.len());
+}
+
+/// Register the named handler for the plugin topics. Stored topics of this handler are resubscribed.
+pub fn register_topic_handler(name: &str, handler: GossipHandler, policy: HandlerErrorPolicy) {
+	LIBP2P_TOPIC_HANDLERS
+		.write()
+		.insert(name.to_string(), (handler.clone(), policy));
+	let topics = match &*LIBP2P_TOPIC_STORE.read() {
+		Some(store) => store.load_topics(),
+		None => vec![],
+	};
+	for t in topics.iter().filter(|t| t.handler == name) {
+		add_topic_to_libp2p(&t.topic, handler.clone(), policy);
+	}
+}
+
+/// Start listening on the topic with the registered plugin handler. The subscription is stored.
+pub fn add_plugin_topic(topic: &str, handler_name: &str) -> bool {
+	let (handler, policy) = match LIBP2P_TOPIC_HANDLERS.read().get(handler_name) {
+		Some(handler) => handler.clone(),
+		None => {
+			warn!(
+				"Unable to subscribe to the topic {}, handler {} is not registered",
+				topic, handler_name
+			);
+			return false;
+		}
+	};
+	add_topic_to_libp2p(topic, handler, policy);
+	save_stored_topic(GossipTopicData {
+		topic: topic.to_string(),
+		min_fee: 0,
+		handler: handler_name.to_string(),
+	});
+	true
+}
+
+/// Stop listening on the plugin topic, the subscription is removed from the store.
+pub fn remove_plugin_topic(topic: &str) {
+	remove_topic_from_libp2p(topic);
+	delete_stored_topic(topic);
+}
+
+fn save_stored_topic(topic: GossipTopicData) {
+	if let Some(store) = &*LIBP2P_TOPIC_STORE.read() {
+		store.save_topic(&topic);
+	}
+}
+
+fn delete_stored_topic(topic: &str) {
+	if let Some(store) = &*LIBP2P_TOPIC_STORE.read() {
+		store.delete_topic(topic);
+	}
+}
+
 pub fn inject_received_messaged(inject_msgs: Vec<ReceivedMessage>) {
 	let mut messages = MESSAGING_RECEIVED.write();
 
@@ -277,6 +1290,184 @@ pub fn get_received_messages_num() -> usize {
 	MESSAGING_RECEIVED.read().len()
 }
 
+/// Gossip message that is processed by the topic handler on the blocking pool. The validation
+/// result is reported by the swarm polling loop when the handler is done.
+struct HandledMessage {
+	id: MessageId,
+	peer_id: PeerId,
+	source: Option<PeerId>,
+	topic: TopicHash,
+	size: usize,
+	received: Instant,
+	/// Integrity fee multiple, the integrity messages go through the forward queue
+	fee_multiple: Option<u64>,
+	acceptance: MessageAcceptance,
+	/// Integrity message without a valid fee, its publisher is throttled
+	integrity_rejected: bool,
+	/// Time when the throttled publisher can send again
+	retry_after: Option<i64>,
+}
+
+/// Run the topic handler on the blocking pool. The swarm is not locked while the handler
+/// runs, so it can subscribe or unsubscribe the topics.
+fn spawn_topic_handler(
+	results: &futures::channel::mpsc::UnboundedSender<HandledMessage>,
+	mut message: HandledMessage,
+	sender_address: String,
+	data: Vec<u8>,
+	fee: u64,
+) {
+	let results = results.clone();
+	tokio::task::spawn_blocking(move || {
+		message.acceptance = call_topic_handler(&sender_address, &message.topic, data, fee);
+		if results.unbounded_send(message).is_err() {
+			debug!("Unable to report the gossip message validation, libp2p node is stopped");
+		}
+	});
+}
+
+/// Validate the integrity message and run its topic handler on the blocking pool, the
+/// kernel lookups go to the chain db. The result is reported by the swarm loop.
+fn spawn_integrity_validation<F>(
+	results: &futures::channel::mpsc::UnboundedSender<HandledMessage>,
+	mut message: HandledMessage,
+	data: Vec<u8>,
+	kernel_cache: Arc<Mutex<IntegrityKernelCache>>,
+	kernel_validation_fn: Arc<F>,
+	fee_base: u64,
+) where
+	F: Fn(&Commitment) -> Result<Option<TxKernel>, Error> + Send + Sync + 'static,
+{
+	let results = results.clone();
+	tokio::task::spawn_blocking(move || {
+		let height = CHAIN_TIP_HEIGHT.load(Ordering::Relaxed);
+		let cached_validation_fn = Arc::new(|excess: &Commitment| {
+			kernel_cache
+				.lock()
+				.lookup(excess, height, kernel_validation_fn.as_ref())
+		});
+		let validation = validate_integrity_message(
+			&message.peer_id,
+			&data,
+			cached_validation_fn,
+			&mut INTEGRITY_REQUESTS.lock(),
+			fee_base,
+		);
+		match validation {
+			Ok((integrity_fee, sender_address)) if integrity_fee > 0 => {
+				message.fee_multiple = Some(integrity_fee / std::cmp::max(fee_base, 1));
+				message.acceptance = call_topic_handler(
+					&sender_address,
+					&message.topic,
+					read_message_data(&data),
+					integrity_fee,
+				);
+			}
+			Ok(_) => {
+				message.acceptance = MessageAcceptance::Reject;
+				message.integrity_rejected = true;
+				message.retry_after = integrity_rate_limit_retry_after(
+					&data,
+					&INTEGRITY_REQUESTS.lock(),
+					Utc::now().timestamp(),
+				);
+			}
+			Err(e) => {
+				warn!(
+					"Message is skipped, Unable to verify the message because of some error. {:?}",
+					e
+				);
+				message.acceptance = MessageAcceptance::Ignore;
+			}
+		}
+		if results.unbounded_send(message).is_err() {
+			debug!("Unable to report the gossip message validation, libp2p node is stopped");
+		}
+	});
+}
+
+/// Process the validated message with the topic handler. Handler failure is processed
+/// according to its policy. Must be called without the swarm lock.
+fn call_topic_handler(
+	sender_address: &String,
+	topic: &TopicHash,
+	data: Vec<u8>,
+	fee: u64,
+) -> MessageAcceptance {
+	// Handler is called without the handlers lock, it might subscribe to other topics
+	let handler = match LIBP2P_MESSAGE_HANDLERS.read().get(topic) {
+		Some((handler, _topic)) => handler.clone(),
+		None => return MessageAcceptance::Accept,
+	};
+	let err = match (handler.handler)(sender_address, topic, data, fee) {
+		Ok(_) => {
+			handler.failures.store(0, Ordering::Relaxed);
+			return MessageAcceptance::Accept;
+		}
+		Err(e) => e,
+	};
+	let failures = handler.failures.fetch_add(1, Ordering::Relaxed) + 1;
+	warn!(
+		"Gossip handler of the topic {} failed to process the message from {}, {}",
+		topic, sender_address, err
+	);
+	match handler.policy {
+		HandlerErrorPolicy::Log => MessageAcceptance::Ignore,
+		HandlerErrorPolicy::Penalize => MessageAcceptance::Reject,
+		HandlerErrorPolicy::Unsubscribe(max_failures) => {
+			if failures >= max_failures {
+				warn!(
+					"Unsubscribing from the topic {} after {} handler failures",
+					topic, failures
+				);
+				// The topic is not restored from the store on the next start
+				MESSAGING_TOPICS.write().remove(topic);
+				remove_topic_from_libp2p(topic.as_str());
+				delete_stored_topic(topic.as_str());
+			}
+			MessageAcceptance::Ignore
+		}
+	}
+}
+
+/// Report the validation result of the integrity message. Accepted messages wait at the
+/// forward queue if the forwarding bandwidth is limited.
+fn report_integrity_validation(
+	gossip: &mut Gossipsub,
+	forward_queue: Option<&mut ForwardQueue<(MessageId, PeerId)>>,
+	id: MessageId,
+	peer_id: PeerId,
+	size: usize,
+	fee_multiple: u64,
+	acceptance: MessageAcceptance,
+) {
+	let queue = match acceptance {
+		MessageAcceptance::Accept => forward_queue,
+		_ => None,
+	};
+	match queue {
+		Some(queue) => {
+			// Forwarding is delayed until the bandwidth budget allows it
+			if let Some((id, peer_id)) = queue.push(
+				(id, peer_id),
+				size,
+				fee_multiple,
+				Utc::now().timestamp_millis(),
+			) {
+				debug!("Forward queue is full, message {} is not forwarded", id);
+				let _ = gossip.report_message_validation_result(
+					&id,
+					&peer_id,
+					MessageAcceptance::Ignore,
+				);
+			}
+		}
+		None => {
+			let _ = gossip.report_message_validation_result(&id, &peer_id, acceptance);
+		}
+	}
+}
+
 /// Stop listening on the topic
 pub fn remove_topic_from_libp2p(topic: &str) {
 	// remove topic and handler
@@ -285,7 +1476,7 @@ pub fn remove_topic_from_libp2p(topic: &str) {
 	if handlers.remove(&topic.hash()).is_some() {
 		// Let's Unregister in the swarm
 		match &mut *LIBP2P_SWARM.lock() {
-			Some(swarm) => match swarm.unsubscribe(&topic) {
+			Some(swarm) => match swarm.gossipsub.unsubscribe(&topic) {
 				Ok(res) => {
 					if !res {
 						warn!("Not found expected subscribed topic {}", topic);
@@ -298,20 +1489,20 @@ pub fn remove_topic_from_libp2p(topic: &str) {
 	}
 }
 
-/// Start listen on topic
-/// Message handlers arguments: topic hash, message (no header), paid integrity fee
-//   Handler must return false if the message is incorrect, so the peer must be banned.
-pub fn add_topic_to_libp2p(
-	topic: &str,
-	handler: fn(sender_address: &String, topic: &TopicHash, Vec<u8>, u64) -> bool,
-) {
+/// Start listen on topic. Handler failures are processed according to the policy.
+pub fn add_topic_to_libp2p(topic: &str, handler: GossipHandler, policy: HandlerErrorPolicy) {
 	let mut handlers = LIBP2P_MESSAGE_HANDLERS.write();
 	let topic = Topic::new(topic);
+	let handler = TopicHandler {
+		handler,
+		policy,
+		failures: Arc::new(AtomicU32::new(0)),
+	};
 	let _ = handlers.insert(topic.hash(), (handler, topic.clone()));
 
 	// Let's Unregister in the swarm
 	match &mut *LIBP2P_SWARM.lock() {
-		Some(swarm) => match swarm.subscribe(&topic) {
+		Some(swarm) => match swarm.gossipsub.subscribe(&topic) {
 			Ok(_res) => (),
 			Err(e) => warn!("Unable to subscribe to the topic {:?}", e),
 		},
@@ -319,10 +1510,27 @@ pub fn add_topic_to_libp2p(
 	}
 }
 
+/// Publish the message to the topic. Nothing is published while the peers rate limit our
+/// messages on the topic.
 pub fn publish_message(topic: &Topic, integrity_message: Vec<u8>) -> Option<MessageId> {
+	let topic_name = topic.hash().into_string();
+	if let Some(feedback) = libp2p_node_api::get_rate_limit_feedback(&topic_name) {
+		warn!(
+			"Not publishing to {}, the messages are rate limited, retry after {} seconds",
+			topic_name, feedback.retry_after
+		);
+		return None;
+	}
 	match &mut *LIBP2P_SWARM.lock() {
-		Some(swarm) => match swarm.publish(topic.clone(), integrity_message) {
-			Ok(msg_id) => Some(msg_id),
+		Some(swarm) => match swarm
+			.gossipsub
+			.publish(topic.clone(), integrity_message.clone())
+		{
+			Ok(msg_id) => {
+				libp2p_node_api::register_published_topic(&topic_name);
+				LIBP2P_STATS.write().bytes_out += integrity_message.len() as u64;
+				Some(msg_id)
+			}
 			Err(e) => {
 				warn!("Unable to publish libp2p message, {}", e);
 				None
@@ -332,6 +1540,401 @@ pub fn publish_message(topic: &Topic, integrity_message: Vec<u8>) -> Option<Mess
 	}
 }
 
+/// Build tx receipt message. Receipt is published by the node when transaction is accepted into the pool.
+/// kernels - kernel excesses of the accepted transaction
+/// timestamp - unix time when transaction was accepted
+pub fn build_tx_receipt(kernels: &Vec<Commitment>, timestamp: i64) -> Vec<u8> {
+	let mut ser = SimplePushSerializer::new(get_message_version());
+	ser.push_vec(&timestamp.to_be_bytes());
+	ser.push_u16(kernels.len() as u16);
+	for k in kernels {
+		ser.push_vec(&k.0);
+	}
+	ser.to_vec()
+}
+
+/// Parse tx receipt message. Returns None if the message is invalid or timestamp is too far from now.
+pub fn parse_tx_receipt(message: &Vec<u8>) -> Option<(Vec<Commitment>, i64)> {
+	if message.len() > TX_RECEIPT_MAX_SIZE {
+		return None;
+	}
+	let mut ser = SimplePopSerializer::new(message);
+	if ser.version != get_message_version() {
+		return None;
+	}
+	let ts_data = ser.pop_vec();
+	if ts_data.len() != 8 {
+		return None;
+	}
+	let mut ts_bytes = [0u8; 8];
+	ts_bytes.copy_from_slice(&ts_data);
+	let timestamp = i64::from_be_bytes(ts_bytes);
+	if (Utc::now().timestamp() - timestamp).abs() > TX_RECEIPT_MAX_AGE {
+		return None;
+	}
+
+	let sz = ser.pop_u16() as usize;
+	if sz == 0 || sz > TX_RECEIPT_KERNELS_LIMIT {
+		return None;
+	}
+	let mut kernels = Vec::with_capacity(sz);
+	for _ in 0..sz {
+		let data = ser.pop_vec();
+		if data.len() != PEDERSEN_COMMITMENT_SIZE {
+			return None;
+		}
+		kernels.push(Commitment::from_vec(data));
+	}
+	Some((kernels, timestamp))
+}
+
+/// Publish tx receipt for the accepted transaction. Does nothing if libp2p is not running.
+pub fn publish_tx_receipt(kernels: &Vec<Commitment>) -> Option<MessageId> {
+	if kernels.is_empty() || kernels.len() > TX_RECEIPT_KERNELS_LIMIT {
+		return None;
+	}
+	let receipt = build_tx_receipt(kernels, Utc::now().timestamp());
+	publish_message(&Topic::new(TX_RECEIPT_TOPIC), receipt)
+}
+
+/// Check if the address is a clearnet TCP multiaddress, like /ip4/1.2.3.4/tcp/3419
+pub fn is_clearnet_address(address: &Multiaddr) -> bool {
+	let mut iter = address.iter();
+	let host = match iter.next() {
+		Some(Protocol::Ip4(_))
+		| Some(Protocol::Ip6(_))
+		| Some(Protocol::Dns(_))
+		| Some(Protocol::Dns4(_))
+		| Some(Protocol::Dns6(_)) => true,
+		_ => false,
+	};
+	let port = match iter.next() {
+		Some(Protocol::Tcp(_)) => true,
+		_ => false,
+	};
+	host && port && iter.next().is_none()
+}
+
+/// Build clearnet address announcement message.
+pub fn build_clearnet_address_message(address: &str, timestamp: i64) -> Vec<u8> {
+	let mut ser = SimplePushSerializer::new(get_message_version());
+	ser.push_vec(&timestamp.to_be_bytes());
+	ser.push_vec(address.as_bytes());
+	ser.to_vec()
+}
+
+/// Parse clearnet address announcement. Returns None if the message is invalid or expired.
+pub fn parse_clearnet_address_message(message: &Vec<u8>) -> Option<String> {
+	let mut ser = SimplePopSerializer::new(message);
+	if ser.version != get_message_version() {
+		return None;
+	}
+	let ts_data = ser.pop_vec();
+	if ts_data.len() != 8 {
+		return None;
+	}
+	let mut ts_bytes = [0u8; 8];
+	ts_bytes.copy_from_slice(&ts_data);
+	let timestamp = i64::from_be_bytes(ts_bytes);
+	if (Utc::now().timestamp() - timestamp).abs() > CLEARNET_ADDRESS_MAX_AGE {
+		return None;
+	}
+	let address = String::from_utf8(ser.pop_vec()).ok()?;
+	match address.parse::<Multiaddr>() {
+		Ok(addr) if is_clearnet_address(&addr) => Some(address),
+		_ => None,
+	}
+}
+
+/// Update the clearnet address for the peer entries. If the peer is not known yet, it is added
+/// as our own discovered peer.
+pub fn set_peer_clearnet_address(onion_address: &str, clearnet_address: &str) {
+	let mut peer_list = LIBP2P_PEERS.write();
+	let mut found = false;
+	for (entries, _) in peer_list.values_mut() {
+		for e in entries.iter_mut().filter(|e| e.address == onion_address) {
+			e.clearnet_address = Some(clearnet_address.to_string());
+			found = true;
+		}
+	}
+	if !found {
+		let mut entry = Libp2pPeerEntry::new(onion_address.to_string());
+		entry.clearnet_address = Some(clearnet_address.to_string());
+		let cur_time = Utc::now().timestamp() as u64;
+		let self_peers = peer_list
+			.entry("SELF".to_string())
+			.or_insert((vec![], cur_time));
+		// Announcements are cheap to spam, evicting the oldest announced entries
+		let announced = self_peers
+			.0
+			.iter()
+			.filter(|e| e.clearnet_address.is_some())
+			.count();
+		if announced >= CLEARNET_SELF_PEERS_MAX {
+			if let Some(pos) = self_peers
+				.0
+				.iter()
+				.position(|e| e.clearnet_address.is_some())
+			{
+				self_peers.0.remove(pos);
+			}
+		}
+		self_peers.0.push(entry);
+		self_peers.1 = cur_time;
+	}
+}
+
+/// Clearnet address of the peer if it was announced
+fn get_peer_clearnet_address(
+	peer_list: &HashMap<String, (Vec<Libp2pPeerEntry>, u64)>,
+	onion_address: &str,
+) -> Option<String> {
+	peer_list
+		.values()
+		.flat_map(|(entries, _)| entries.iter())
+		.filter(|e| e.address == onion_address)
+		.find_map(|e| e.clearnet_address.clone())
+}
+
+/// Build the dial address for the peer onion address. Clearnet is preferable if both sides
+/// support it. Returns the multiaddress, the peer id and the clearnet flag.
+fn get_peer_dial_address(
+	tor_address: &str,
+	clearnet: bool,
+	tor: bool,
+) -> Option<(Multiaddr, PeerId, bool)> {
+	let res: Result<OnionV3Address, OnionV3AddressError> = tor_address.try_into();
+	let p = match res {
+		Ok(onion_addr) => match onion_addr.to_ed25519() {
+			Ok(pk) => PeerId::from_public_key(libp2p::identity::PublicKey::Ed25519(
+				libp2p::identity::ed25519::PublicKey(pk),
+			)),
+			Err(e) => {
+				error!(
+					"Unable to build PeerId form onion address {}, {}",
+					tor_address, e
+				);
+				return None;
+			}
+		},
+		Err(e) => {
+			error!(
+				"Unable to build PeerId form onion address {}, {}",
+				tor_address, e
+			);
+			return None;
+		}
+	};
+
+	let peer_clearnet_address = if clearnet {
+		get_peer_clearnet_address(&LIBP2P_PEERS.read(), tor_address)
+	} else {
+		None
+	};
+
+	let multiaddress = match peer_clearnet_address {
+		Some(clearnet_address) => clearnet_address,
+		None => {
+			if !tor {
+				// Without Tor only clearnet peers are reachable
+				return None;
+			}
+			let address = match p.get_address() {
+				Ok(addr) => addr,
+				Err(e) => {
+					warn!(
+						"Unable to get peer address to connect . Will skip it, {}",
+						e
+					);
+					return None;
+				}
+			};
+			format!("/onion3/{}:{}", address, global::get_tor_libp2p_port())
+		}
+	};
+	match multiaddress.parse::<Multiaddr>() {
+		Ok(addr) => {
+			let clearnet = is_clearnet_address(&addr);
+			Some((addr, p, clearnet))
+		}
+		Err(e) => {
+			warn!(
+				"Unable to construct onion multiaddress from {} the peer address. Will skip it, {}",
+				multiaddress, e
+			);
+			None
+		}
+	}
+}
+
+/// Forget the clearnet address of the peer, next time the onion address will be dialed.
+fn reset_peer_clearnet_address(
+	peer_list: &mut HashMap<String, (Vec<Libp2pPeerEntry>, u64)>,
+	onion_address: &str,
+) {
+	for (entries, _) in peer_list.values_mut() {
+		for e in entries.iter_mut().filter(|e| e.address == onion_address) {
+			e.clearnet_address = None;
+		}
+	}
+}
+
+/// Serializer version for the signed peer exchange, v2 of the PEER_TOPIC format.
+fn get_peer_exchange_version() -> u16 {
+	get_message_version() + 1
+}
+
+/// Ed25519 public key of the peer. Mwc peers are identified by the onion address key.
+fn get_peer_public_key(peer: &PeerId) -> Result<libp2p::identity::PublicKey, Error> {
+	let onion_address = peer
+		.as_onion_address()
+		.map_err(|e| Error::Libp2pError(format!("Peer {} doesn't have Dalek PK, {}", peer, e)))?;
+	let res: Result<OnionV3Address, OnionV3AddressError> = onion_address.as_str().try_into();
+	let pk = res.and_then(|addr| addr.to_ed25519()).map_err(|e| {
+		Error::Libp2pError(format!("Invalid onion address {}, {}", onion_address, e))
+	})?;
+	Ok(libp2p::identity::PublicKey::Ed25519(
+		libp2p::identity::ed25519::PublicKey(pk),
+	))
+}
+
+/// Data that is signed for every peer exchange record. Advertiser is included, so the
+/// record can't be replayed by another node.
+fn peer_exchange_record_data(advertiser: &PeerId, peer: &PeerId, timestamp: i64) -> Vec<u8> {
+	let mut data = PEER_EXCHANGE_TOPIC.as_bytes().to_vec();
+	data.extend_from_slice(&advertiser.to_bytes());
+	data.extend_from_slice(&peer.to_bytes());
+	data.extend_from_slice(&timestamp.to_be_bytes());
+	data
+}
+
+/// Build signed peer exchange message. Every peer record is signed by the advertising node key.
+pub fn build_peer_exchange_message(
+	keys: &Keypair,
+	peers: &Vec<PeerId>,
+	timestamp: i64,
+) -> Result<Vec<u8>, Error> {
+	let advertiser = PeerId::from_public_key(keys.public());
+	let mut ser = SimplePushSerializer::new(get_peer_exchange_version());
+	ser.push_vec(&timestamp.to_be_bytes());
+	ser.push_u16(peers.len() as u16);
+	for peer in peers {
+		let signature = keys
+			.sign(&peer_exchange_record_data(&advertiser, peer, timestamp))
+			.map_err(|e| Error::Libp2pError(format!("Unable to sign peer record, {}", e)))?;
+		ser.push_vec(&peer.to_bytes());
+		ser.push_vec(&signature);
+	}
+	Ok(ser.to_vec())
+}
+
+/// Parse signed peer exchange message from the advertiser. Returns onion addresses of the
+/// advertised peers. Message is rejected if any record can't be verified.
+pub fn parse_peer_exchange_message(
+	advertiser: &PeerId,
+	message: &Vec<u8>,
+) -> Result<Vec<String>, Error> {
+	let mut ser = SimplePopSerializer::new(message);
+	if ser.version != get_peer_exchange_version() {
+		return Err(Error::Libp2pError(format!(
+			"Unexpected peer exchange version {}",
+			ser.version
+		)));
+	}
+	let ts_data = ser.pop_vec();
+	if ts_data.len() != 8 {
+		return Err(Error::Libp2pError("Invalid peer exchange timestamp".into()));
+	}
+	let mut ts_bytes = [0u8; 8];
+	ts_bytes.copy_from_slice(&ts_data);
+	let timestamp = i64::from_be_bytes(ts_bytes);
+	if (Utc::now().timestamp() - timestamp).abs() > PEER_EXCHANGE_MAX_AGE {
+		return Err(Error::Libp2pError(
+			"Peer exchange message is expired".into(),
+		));
+	}
+
+	let sz = ser.pop_u16() as usize;
+	if sz > gossipsub::PEER_EXCHANGE_NUMBER_LIMIT {
+		return Err(Error::Libp2pError(format!(
+			"Too many peers in the peer exchange, {}",
+			sz
+		)));
+	}
+
+	let advertiser_pk = get_peer_public_key(advertiser)?;
+	let mut peers = Vec::with_capacity(sz);
+	for _ in 0..sz {
+		let peer = PeerId::from_bytes(&ser.pop_vec())
+			.map_err(|e| Error::Libp2pError(format!("Unable to decode the peer, {}", e)))?;
+		let signature = ser.pop_vec();
+		if !advertiser_pk.verify(
+			&peer_exchange_record_data(advertiser, &peer, timestamp),
+			&signature,
+		) {
+			return Err(Error::Libp2pError(format!(
+				"Invalid signature for the peer record {}",
+				peer
+			)));
+		}
+		let address = peer.as_onion_address().map_err(|e| {
+			Error::Libp2pError(format!("Peer {} doesn't have Dalek PK, {}", peer, e))
+		})?;
+		peers.push(address);
+	}
+	Ok(peers)
+}
+
+/// Parse legacy unsigned peer exchange message, v1 of the PEER_TOPIC format. It is still
+/// published by the old nodes. Returns onion addresses of the advertised peers.
+pub fn parse_legacy_peer_message(message: &Vec<u8>) -> Result<Vec<String>, Error> {
+	let mut ser = SimplePopSerializer::new(message);
+	if ser.version != 1 {
+		return Err(Error::Libp2pError(format!(
+			"Unexpected legacy peer exchange version {}",
+			ser.version
+		)));
+	}
+	let sz = ser.pop_u16() as usize;
+	if sz > gossipsub::PEER_EXCHANGE_NUMBER_LIMIT {
+		return Err(Error::Libp2pError(format!(
+			"Too many peers in the legacy peer exchange, {}",
+			sz
+		)));
+	}
+	let mut peers = Vec::with_capacity(sz);
+	for _ in 0..sz {
+		match PeerId::from_bytes(&ser.pop_vec()) {
+			Ok(peer) => match peer.as_onion_address() {
+				Ok(address) => peers.push(address),
+				Err(e) => debug!("Get from libp2p peer without Dalek PK {}, {}", peer, e),
+			},
+			Err(e) => debug!(
+				"Unable to decode the libp2p peer form the peer update message, {}",
+				e
+			),
+		}
+	}
+	Ok(peers)
+}
+
+/// Send node API json-rpc request to the peer. Response will be delivered with returned receiver.
+/// Only methods from libp2p_node_api::NODE_API_METHODS are served by the nodes.
+pub fn send_node_api_request(
+	peer: &PeerId,
+	request: &serde_json::Value,
+) -> Result<futures::channel::oneshot::Receiver<Result<serde_json::Value, Error>>, Error> {
+	match &mut *LIBP2P_SWARM.lock() {
+		Some(swarm) => {
+			let request_id = swarm
+				.node_api
+				.send_request(peer, request.to_string().into_bytes());
+			Ok(libp2p_node_api::register_node_api_request(request_id))
+		}
+		None => Err(Error::Libp2pError("libp2p node is not running".to_string())),
+	}
+}
+
 /// Request number of established connections to libp2p
 pub fn get_libp2p_connections() -> Vec<PeerId> {
 	match &*LIBP2P_SWARM.lock() {
@@ -353,27 +1956,139 @@ pub fn add_new_peer(peer: &PeerAddr) -> Result<(), Error> {
 	let cur_time = Utc::now().timestamp() as u64;
 	let mut peer_list = LIBP2P_PEERS.write();
 	if let Some((peers, time)) = peer_list.get_mut("SELF") {
-		if !peers.contains(&addr) {
-			peers.push(addr);
+		if !peers.iter().any(|p| p.address == addr) {
+			peers.push(Libp2pPeerEntry::new(addr));
 		}
 		*time = cur_time;
 	} else {
-		peer_list.insert("SELF".to_string(), (vec![addr], cur_time));
+		peer_list.insert(
+			"SELF".to_string(),
+			(vec![Libp2pPeerEntry::new(addr)], cur_time),
+		);
 	}
 
 	Ok(())
 }
 
-/// Created libp2p listener for Socks5 tor address.
-/// tor_socks_port - listener port, param from  SocksPort 127.0.0.1:51234
+/// Backoff interval for the number of failures.
+fn get_dial_backoff(failures: u32) -> i64 {
+	if failures == 0 {
+		return LIBP2P_DIAL_BACKOFF_BASE;
+	}
+	let shift = std::cmp::min(failures, 16);
+	std::cmp::min(LIBP2P_DIAL_BACKOFF_BASE << shift, LIBP2P_DIAL_BACKOFF_MAX)
+}
+
+/// Backoff interval of the pinned peers for the number of failures.
+fn get_pinned_dial_backoff(failures: u32) -> i64 {
+	std::cmp::min(
+		LIBP2P_PINNED_DIAL_BACKOFF_BASE * (failures as i64 + 1),
+		LIBP2P_PINNED_DIAL_BACKOFF_MAX,
+	)
+}
+
+/// Mark that we start dialing the pinned peer.
+fn report_pinned_dial_attempt(
+	pinned: &mut HashMap<String, Libp2pPeerEntry>,
+	address: &str,
+	now: i64,
+) {
+	if let Some(e) = pinned.get_mut(address) {
+		e.next_dial = now + get_pinned_dial_backoff(e.failures);
+	}
+}
+
+/// Update dialing history of the pinned peer with a dial result. Pinned peers are never evicted.
+fn report_pinned_dial_result(
+	pinned: &mut HashMap<String, Libp2pPeerEntry>,
+	address: &str,
+	success: bool,
+	now: i64,
+) {
+	if let Some(e) = pinned.get_mut(address) {
+		if success {
+			e.failures = 0;
+			e.next_dial = 0;
+		} else {
+			e.failures += 1;
+			e.next_dial = now + get_pinned_dial_backoff(e.failures);
+		}
+	}
+}
+
+/// Build the peer entries for the reported addresses. Dialing history is kept
+/// if we already know those addresses.
+fn merge_peer_entries(
+	peer_list: &HashMap<String, (Vec<Libp2pPeerEntry>, u64)>,
+	addresses: Vec<String>,
+) -> Vec<Libp2pPeerEntry> {
+	addresses
+		.into_iter()
+		.map(|address| {
+			peer_list
+				.values()
+				.flat_map(|(entries, _)| entries.iter())
+				.find(|e| e.address == address)
+				.cloned()
+				.unwrap_or(Libp2pPeerEntry::new(address))
+		})
+		.collect()
+}
+
+/// Mark that we start dialing the address. It will not be selected again until backoff is expired.
+fn report_dial_attempt(
+	peer_list: &mut HashMap<String, (Vec<Libp2pPeerEntry>, u64)>,
+	address: &str,
+	now: i64,
+) {
+	for (entries, _) in peer_list.values_mut() {
+		for e in entries.iter_mut().filter(|e| e.address == address) {
+			e.next_dial = now + get_dial_backoff(e.failures);
+		}
+	}
+}
+
+/// Update dialing history with a dial result. Failed addresses are evicted
+/// after LIBP2P_DIAL_MAX_FAILURES failures in a row.
+fn report_dial_result(
+	peer_list: &mut HashMap<String, (Vec<Libp2pPeerEntry>, u64)>,
+	address: &str,
+	success: bool,
+	now: i64,
+) {
+	for (entries, _) in peer_list.values_mut() {
+		for e in entries.iter_mut().filter(|e| e.address == address) {
+			if success {
+				e.failures = 0;
+				e.next_dial = 0;
+			} else {
+				e.failures += 1;
+				e.next_dial = now + get_dial_backoff(e.failures);
+			}
+		}
+		entries.retain(|e| e.failures < LIBP2P_DIAL_MAX_FAILURES);
+	}
+	peer_list.retain(|_k, v| !v.0.is_empty());
+}
+
+/// Created libp2p listener for Socks5 tor address and optional clearnet TCP listener.
+/// tor_socks_port - listener port, param from  SocksPort 127.0.0.1:51234. None if node is running without Tor.
+/// tor_secret - node identity key. Onion address is derived from it even if Tor is not running.
+/// clearnet_port - TCP port for the clearnet listener. None if clearnet transport is disabled.
+/// clearnet_address - public clearnet multiaddress that we announce to the peers.
 /// output_validation_fn - kernel excess validation method. Return height RangeProof if that output was seen during last 24 hours (last 1440 blocks)
+/// stop_state - node stop state. Polling loop exits on stop, unsubscribes from the topics and closes all connections.
 pub async fn run_libp2p_node(
-	tor_socks_port: u16,
+	tor_socks_port: Option<u16>,
 	tor_secret: &[u8; 32],
 	libp2p_port: u16,
+	clearnet_port: Option<u16>,
+	clearnet_address: Option<String>,
 	fee_base: u64,
-	kernel_validation_fn: Arc<impl Fn(&Commitment) -> Result<Option<TxKernel>, Error>>,
-	stop_mutex: std::sync::Arc<std::sync::Mutex<u32>>,
+	kernel_validation_fn: Arc<
+		impl Fn(&Commitment) -> Result<Option<TxKernel>, Error> + Send + Sync + 'static,
+	>,
+	stop_state: Arc<StopState>,
 ) -> Result<(), Error> {
 	// Generate Onion address.
 	let onion_address = OnionV3Address::from_private(tor_secret)
@@ -399,6 +2114,7 @@ pub async fn run_libp2p_node(
 		.map_err(|e| Error::Libp2pError(format!("Unable to build ed25519 key pairs, {}", e)))?;
 	let this_peer_id = PeerId::from_public_key(id_keys.public());
 	set_this_peer_id(&this_peer_id);
+	*INTEGRITY_FEE_BASE.write() = Some(fee_base);
 
 	warn!("Starting libp2p, this peer: {}", this_peer_id);
 	debug_assert_eq!(this_peer_id.to_string(), onion_address.to_string());
@@ -408,10 +2124,20 @@ pub async fn run_libp2p_node(
 		.into_authentic(&id_keys)
 		.map_err(|e| Error::Libp2pError(format!("Unable to build p2p keys, {}", e)))?;
 	let noise = NoiseConfig::xx(dh_keys).into_authenticated();
-	let tcp = Socks5TokioTcpConfig::new(tor_socks_port)
-		.nodelay(true)
-		.onion_map(map);
-	let transport = DnsConfig::new(tcp)
+	let socks = match tor_socks_port {
+		Some(tor_socks_port) => OptionalTransport::some(
+			Socks5TokioTcpConfig::new(tor_socks_port)
+				.nodelay(true)
+				.onion_map(map),
+		),
+		None => OptionalTransport::none(),
+	};
+	// Clearnet transport goes first, it doesn't support onion addresses, so they will be handled by socks
+	let tcp = match clearnet_port {
+		Some(_) => OptionalTransport::some(TokioTcpConfig::new().nodelay(true)),
+		None => OptionalTransport::none(),
+	};
+	let transport = DnsConfig::new(tcp.or_transport(socks))
 		.map_err(|e| Error::Libp2pError(format!("Unable to build a transport, {}", e)))?;
 
 	let transport = transport
@@ -435,30 +2161,74 @@ pub async fn run_libp2p_node(
 	*/
 
 	// Set a custom gossipsub
+	let libp2p_config = LIBP2P_CONFIG.read().clone();
 	let gossipsub_config = gossipsub::GossipsubConfigBuilder::default()
-		.heartbeat_interval(Duration::from_secs(3)) // Default is 1, but we don't want overload network much. Instead we prefer slow adaptation network
+		.heartbeat_interval(Duration::from_secs(libp2p_config.gossip_heartbeat_secs)) // Default is 1, but we don't want overload network much. Instead we prefer slow adaptation network
+		.mesh_n(libp2p_config.gossip_mesh_n)
+		.mesh_n_low(libp2p_config.gossip_mesh_n_low)
+		.mesh_n_high(libp2p_config.gossip_mesh_n_high)
+		.history_length(libp2p_config.gossip_history_length)
+		.max_transmit_size(libp2p_config.gossip_max_message_size)
+		.flood_publish(libp2p_config.gossip_flood_publish)
 		.validation_mode(ValidationMode::Strict) // This sets the kind of message validation. The default is Strict (enforce message signing)
 		.validate_messages() // !!!!! Now we are responsible for validation of all incoming traffic!!!!
 		.accept_dalek_pk_peers_only()
 		.build()
-		.expect("Valid gossip config");
+		.map_err(|e| Error::Libp2pError(format!("Invalid gossipsub config, {}", e)))?;
 
 	// Here are how many connection we will try to keep...
 	let connections_number_low = gossipsub_config.mesh_n_high();
+	// Forwarding queue follows the message cache of gossipsub
+	let (forward_max_wait, forward_expire) = forward_queue_timing(&gossipsub_config);
+	let forward_fanout = gossipsub_config.mesh_n() as u64;
+
+	// Peer exchange records are signed with the node identity key
+	let peer_exchange_keys = id_keys.clone();
 
 	// build a gossipsub network behaviour
 	let gossipsub: gossipsub::Gossipsub =
 		gossipsub::Gossipsub::new(MessageAuthenticity::Signed(id_keys), gossipsub_config)
 			.expect("Correct configuration");
 
+	let behaviour = MwcBehaviour {
+		gossipsub,
+		node_api: libp2p_node_api::build_node_api_behaviour(),
+	};
+
 	// subscribes to our topic
 
-	let mut swarm = SwarmBuilder::new(transport, gossipsub, this_peer_id.clone())
+	let mut swarm = SwarmBuilder::new(transport, behaviour, this_peer_id.clone())
 		.executor(Box::new(TokioExecutor))
 		.build();
 
-	Swarm::listen_on(&mut swarm, addr.clone())
-		.map_err(|e| Error::Libp2pError(format!("Unable to start listening, {}", e)))?;
+	if tor_socks_port.is_some() {
+		Swarm::listen_on(&mut swarm, addr.clone())
+			.map_err(|e| Error::Libp2pError(format!("Unable to start listening, {}", e)))?;
+	}
+	if let Some(clearnet_port) = clearnet_port {
+		let clearnet_listen_addr = format!("/ip4/0.0.0.0/tcp/{}", clearnet_port)
+			.parse::<Multiaddr>()
+			.map_err(|e| {
+				Error::Internal(format!("Unable to construct clearnet multiaddress, {}", e))
+			})?;
+		Swarm::listen_on(&mut swarm, clearnet_listen_addr).map_err(|e| {
+			Error::Libp2pError(format!("Unable to start clearnet listening, {}", e))
+		})?;
+	}
+	// Our clearnet address that we announce, if it is valid
+	let clearnet_address = match clearnet_address {
+		Some(address) => match address.parse::<Multiaddr>() {
+			Ok(addr) if clearnet_port.is_some() && is_clearnet_address(&addr) => Some(address),
+			_ => {
+				warn!(
+					"Clearnet address {} is invalid or clearnet port is not set, it will not be announced",
+					address
+				);
+				None
+			}
+		},
+		None => None,
+	};
 
 	/*   // It is ping pong handler
 	 future::poll_fn(move |cx: &mut Context<'_>| loop {
@@ -473,39 +2243,180 @@ pub async fn run_libp2p_node(
 	// Special topic for peer reporting. We don't need to listen on it and we
 	// don't want the node forward that message as well
 	let peer_topic = Topic::new(libp2p::gossipsub::PEER_TOPIC).hash();
+	// Topic for tx receipts, they have own format and validation
+	let receipt_topic = Topic::new(TX_RECEIPT_TOPIC).hash();
+	// Clearnet address announcements. Every node listen on it, so the announcements can reach all peers
+	let clearnet_topic = Topic::new(CLEARNET_ADDRESS_TOPIC);
+	if let Err(e) = swarm.gossipsub.subscribe(&clearnet_topic) {
+		error!("Unable subscribe to the clearnet address topic, {:?}", e);
+	}
+	let clearnet_topic_hash = clearnet_topic.hash();
+	// Signed peer exchange. Every node listen on it, peers are published to the mesh peers.
+	let peer_exchange_topic = Topic::new(PEER_EXCHANGE_TOPIC);
+	if let Err(e) = swarm.gossipsub.subscribe(&peer_exchange_topic) {
+		error!("Unable subscribe to the peer exchange topic, {:?}", e);
+	}
+	let peer_exchange_topic_hash = peer_exchange_topic.hash();
 
 	// Subscribe to the topics that we are ready to listen
 	LIBP2P_MESSAGE_HANDLERS
 		.read()
 		.iter()
 		.for_each(|(_topic_hash, (_fn, topic))| {
-			if let Err(e) = swarm.subscribe(&topic) {
+			if let Err(e) = swarm.gossipsub.subscribe(&topic) {
 				error!("Unable initial subscribe to the topic, {:?}", e);
 			}
 		});
 
 	init_libp2p_swarm(swarm);
 
-	let mut requests_cash: HashMap<Commitment, VecDeque<i64>> = HashMap::new();
+	// Calls history of the tx receipts publishers, by the sender address
+	let mut receipt_requests: HashMap<String, VecDeque<i64>> = HashMap::new();
+	// Publishers that got the rate limit feedback, until their retry-after expires
+	let mut rate_limit_notified: HashMap<PeerId, i64> = HashMap::new();
 	let mut last_cash_clean = Instant::now();
 	let mut last_reconnect = Instant::now();
+	let mut last_clearnet_announce: Option<Instant> = None;
+	let mut last_peer_exchange: Option<Instant> = None;
+	// Connected peers, used for connect/disconnect notifications
+	let mut connected_peers: HashSet<PeerId> = HashSet::new();
+	// Pinned peers that are registered as the gossipsub explicit peers
+	let mut explicit_peers: HashSet<PeerId> = HashSet::new();
+	// Peers that publish the signed peer exchange, their legacy records are skipped
+	let mut signed_exchange_peers: HashSet<PeerId> = HashSet::new();
+	let mut dedup_cache =
+		MessageDedupCache::new(MESSAGE_DEDUP_CACHE_SIZE, *MESSAGE_DEDUP_TTL.read());
+	let mut integrity_rejects =
+		IntegrityRejects::new(INTEGRITY_REJECT_BAN_LIMIT, INTEGRITY_REJECT_PERIOD);
+	let kernel_cache = Arc::new(Mutex::new(IntegrityKernelCache::new(
+		INTEGRITY_KERNEL_CACHE_SIZE,
+		INTEGRITY_KERNEL_CACHE_TTL_BLOCKS,
+	)));
+	// Cache is empty, reorgs that happened before are not relevant
+	CHAIN_REORG_NOTIFIED.store(false, Ordering::Relaxed);
+	let mut last_purge_tip = CHAIN_TIP_HEIGHT.load(Ordering::Relaxed);
+	let mut last_purge = Instant::now();
+	let mut purge_reorg = false;
+	// Dials that are in progress, peer => (onion address, is clearnet dial)
+	let mut pending_dials: HashMap<PeerId, (String, bool)> = HashMap::new();
+	// Accepted integrity messages are forwarded by fee priority if the bandwidth is limited
+	let forward_bandwidth = *FORWARD_BANDWIDTH.read();
+	let mut forward_queue: Option<ForwardQueue<(MessageId, PeerId)>> = if forward_bandwidth > 0 {
+		Some(ForwardQueue::new(
+			FORWARD_QUEUE_SIZE,
+			forward_bandwidth,
+			forward_fanout,
+			forward_max_wait,
+			Utc::now().timestamp_millis(),
+		))
+	} else {
+		None
+	};
+	// The swarm wakes the task on its events, the interval guarantees that the stop request
+	// is noticed on a quiet network.
+	let mut stop_check = tokio::time::interval(LIBP2P_STOP_CHECK_INTERVAL);
+	// Node API requests are processed on the blocking thread pool, the responses come back here
+	let (node_api_responses_tx, mut node_api_responses_rx) = futures::channel::mpsc::unbounded();
+	// Integrity checks and topic handlers run on the blocking thread pool, the validation
+	// results come back here
+	let (handled_messages_tx, mut handled_messages_rx) =
+		futures::channel::mpsc::unbounded::<HandledMessage>();
 	// Kick it off
-	// Event processing future...
-	task::block_on(future::poll_fn(move |cx: &mut Context<'_>| {
+	// Event processing future, it runs on the tokio runtime of the caller
+	future::poll_fn(move |cx: &mut Context<'_>| {
+		while stop_check.poll_tick(cx).is_ready() {}
+		if stop_state.is_stopped() {
+			info!("Exiting libp2p polling task");
+			return Poll::Ready(());
+		}
+
+		if CHAIN_REORG_NOTIFIED.swap(false, Ordering::Relaxed) {
+			debug!("Chain reorg, clearing integrity kernel cache");
+			kernel_cache.lock().clear();
+			purge_reorg = true;
+		}
+
 		let mut swarm = LIBP2P_SWARM.lock();
 		match &mut *swarm {
 			Some(swarm) => {
+				while let Poll::Ready(Some((channel, response))) =
+					node_api_responses_rx.poll_next_unpin(cx)
+				{
+					if swarm.node_api.send_response(channel, response).is_err() {
+						debug!("Unable to send node API response, channel is closed");
+					}
+				}
+				while let Poll::Ready(Some(handled)) = handled_messages_rx.poll_next_unpin(cx) {
+					if handled.integrity_rejected {
+						// Invalid message. The publisher that keeps sending them is banned.
+						// Forwarding peers get the gossipsub score penalty for the rejected
+						// message only, they can't check the kernels we know.
+						if let Some(publisher) = handled.source.as_ref() {
+							if integrity_rejects.register(publisher, Utc::now().timestamp()) {
+								ban_peer(publisher);
+								swarm.gossipsub.disconnect_peer(publisher.clone(), true);
+							}
+						}
+						// Publisher is notified, so well behaved wallets can throttle themselves
+						if let (Some(retry_after), Some(source)) =
+							(handled.retry_after, handled.source.as_ref())
+						{
+							send_rate_limit_feedback(
+								swarm,
+								source,
+								&handled.topic,
+								retry_after,
+								&mut rate_limit_notified,
+							);
+						}
+					}
+					record_message_result(
+						&handled.peer_id,
+						&handled.id,
+						handled.source.as_ref(),
+						&handled.topic,
+						handled.size,
+						handled.received,
+						&handled.acceptance,
+					);
+					match handled.fee_multiple {
+						Some(fee_multiple) => report_integrity_validation(
+							&mut swarm.gossipsub,
+							forward_queue.as_mut(),
+							handled.id,
+							handled.peer_id,
+							handled.size,
+							fee_multiple,
+							handled.acceptance,
+						),
+						None => {
+							let _ = swarm.gossipsub.report_message_validation_result(
+								&handled.id,
+								&handled.peer_id,
+								handled.acceptance,
+							);
+						}
+					}
+				}
 				loop {
 					let event = swarm.poll_next_unpin(cx);
 					//debug!("swarm.poll_next_unpin event: {:?}", event);
 					match event {
-						Poll::Ready(Some(gossip_event)) => match gossip_event {
-							GossipsubEvent::Message {
-								propagation_source: peer_id,
-								message_id: id,
-								message,
-							} => {
-								debug!(
+						Poll::Ready(Some(MwcBehaviourEvent::NodeApi(node_api_event))) => {
+							libp2p_node_api::handle_node_api_event(
+								node_api_event,
+								&node_api_responses_tx,
+							);
+						}
+						Poll::Ready(Some(MwcBehaviourEvent::Gossipsub(gossip_event))) => {
+							match gossip_event {
+								GossipsubEvent::Message {
+									propagation_source: peer_id,
+									message_id: id,
+									message,
+								} => {
+									let received = Instant::now();
+									debug!(
 									"Get libp2p message from {}, with ID {}, topic {}, data: {}",
 									peer_id,
 									id,
@@ -514,136 +2425,408 @@ pub async fn run_libp2p_node(
 										.to_string(),
 								);
 
-								if message.topic == peer_topic {
-									// We get new peers to connect. Let's update that
-									if !Swarm::is_connected(&swarm, &peer_id) {
-										error!(
-											"Get topic from nodes that we are not connected to."
+									notify_observers(|o| {
+										o.message_received(
+											&peer_id,
+											message.topic.as_str(),
+											message.data.len(),
+										)
+									});
+
+									// Messages from the banned peers are not processed. The source is checked as well,
+									// so the banned peer can't reach us through the mesh.
+									if is_peer_banned(&peer_id)
+										|| message.source.as_ref().map_or(false, is_peer_banned)
+									{
+										debug!(
+											"Rejecting message {} from banned peer {}",
+											id, peer_id
 										);
-										let gossip = swarm.get_behaviour();
+										record_message_result(
+											&peer_id,
+											&id,
+											message.source.as_ref(),
+											&message.topic,
+											message.data.len(),
+											received,
+											&MessageAcceptance::Reject,
+										);
+										let gossip = &mut swarm.gossipsub;
 										let _ = gossip.report_message_validation_result(
 											&id,
 											&peer_id,
 											MessageAcceptance::Reject,
 										);
-										gossip.disconnect_peer(peer_id, true);
+										if is_peer_banned(&peer_id) {
+											gossip.disconnect_peer(peer_id, true);
+										}
 										continue;
-									} else {
-										// report validation for this message
-										let gossip = swarm.get_behaviour();
-										if let Err(e) = gossip.report_message_validation_result(
+									}
+
+									// Operator denied the topic or the message, it is not processed and not
+									// relayed. The peer is not penalized for that.
+									if RELAY_DENY_LIST
+										.read()
+										.is_denied(message.topic.as_str(), &id.to_string())
+									{
+										debug!(
+											"Message {} for topic {} from {} is denied",
+											id, message.topic, peer_id
+										);
+										record_message_result(
+											&peer_id,
+											&id,
+											message.source.as_ref(),
+											&message.topic,
+											message.data.len(),
+											received,
+											&MessageAcceptance::Ignore,
+										);
+										let _ = swarm.gossipsub.report_message_validation_result(
 											&id,
 											&peer_id,
 											MessageAcceptance::Ignore,
-										) {
-											error!("report_message_validation_result failed for error {}", e);
-										}
-									}
-
-									let mut serializer = SimplePopSerializer::new(&message.data);
-									if serializer.version != 1 {
-										warn!("Get peer info data of unexpected version. Probably your client need to be upgraded");
+										);
 										continue;
 									}
 
-									let sz = serializer.pop_u16() as usize;
-									if sz > gossipsub::PEER_EXCHANGE_NUMBER_LIMIT {
-										warn!("Get too many peers from {}", peer_id);
-										// let's ban it, probably it is an attacker...
-										let gossip = swarm.get_behaviour();
-										gossip.disconnect_peer(peer_id, true);
+									// Duplicated payloads are not processed and not forwarded
+									if dedup_cache.is_duplicate(
+										&message.topic,
+										&message.data,
+										Utc::now().timestamp(),
+									) {
+										debug!(
+											"Skipping duplicated message {} from {}",
+											id, peer_id
+										);
+										record_message_result(
+											&peer_id,
+											&id,
+											message.source.as_ref(),
+											&message.topic,
+											message.data.len(),
+											received,
+											&MessageAcceptance::Ignore,
+										);
+										let _ = swarm.gossipsub.report_message_validation_result(
+											&id,
+											&peer_id,
+											MessageAcceptance::Ignore,
+										);
 										continue;
 									}
 
-									let mut peer_arr = vec![];
-									for _i in 0..sz {
-										let peer_data = serializer.pop_vec();
-										match PeerId::from_bytes(&peer_data) {
-											Ok(peer) => match peer.as_onion_address() {
-												Ok(addr) => peer_arr.push(addr),
-												Err(e) => {
-													error!("Get from libp2p peer without Dalek PK {}, {}", peer, e);
-													continue;
+									if message.topic == peer_topic {
+										// We get new peers to connect. Let's update that
+										if !Swarm::is_connected(&swarm, &peer_id) {
+											error!(
+											"Get topic from nodes that we are not connected to."
+										);
+											record_message_result(
+												&peer_id,
+												&id,
+												message.source.as_ref(),
+												&message.topic,
+												message.data.len(),
+												received,
+												&MessageAcceptance::Reject,
+											);
+											let gossip = &mut swarm.gossipsub;
+											let _ = gossip.report_message_validation_result(
+												&id,
+												&peer_id,
+												MessageAcceptance::Reject,
+											);
+											gossip.disconnect_peer(peer_id, true);
+											continue;
+										} else {
+											// report validation for this message
+											record_message_result(
+												&peer_id,
+												&id,
+												message.source.as_ref(),
+												&message.topic,
+												message.data.len(),
+												received,
+												&MessageAcceptance::Ignore,
+											);
+											let gossip = &mut swarm.gossipsub;
+											if let Err(e) = gossip.report_message_validation_result(
+												&id,
+												&peer_id,
+												MessageAcceptance::Ignore,
+											) {
+												error!("report_message_validation_result failed for error {}", e);
+											}
+										}
+
+										// Legacy peer exchange records are not signed, they are accepted only from
+										// the connected old nodes. Upgraded nodes are trusted with the signed exchange.
+										if signed_exchange_peers.contains(&peer_id) {
+											continue;
+										}
+										match parse_legacy_peer_message(&message.data) {
+											Ok(peer_arr) => {
+												info!("Get {} legacy peers from {}. Will process them later when we will need to increase connection number", peer_arr.len(), peer_id);
+												if let Ok(addr) = peer_id.as_onion_address() {
+													let mut new_peers_list = LIBP2P_PEERS.write();
+													let peer_entries = merge_peer_entries(
+														&new_peers_list,
+														peer_arr,
+													);
+													(*new_peers_list).insert(
+														addr,
+														(
+															peer_entries,
+															Utc::now().timestamp() as u64,
+														),
+													);
 												}
-											},
+											}
 											Err(e) => {
-												warn!("Unable to decode the libp2p peer form the peer update message, {}", e);
-												continue;
+												warn!(
+													"Get invalid legacy peer exchange from {}, {}",
+													peer_id, e
+												);
+												// let's ban it, probably it is an attacker...
+												swarm.gossipsub.disconnect_peer(peer_id, true);
 											}
 										}
-									}
-									info!("Get {} peers from {}. Will process them later when we will need to increase connection number", peer_arr.len(), peer_id);
-
-									if let Ok(addr) = peer_id.as_onion_address() {
-										let mut new_peers_list = LIBP2P_PEERS.write();
-
-										(*new_peers_list).insert(
-											addr,
-											(peer_arr, Utc::now().timestamp() as u64),
+									} else if message.topic == peer_exchange_topic_hash {
+										// Message source is verified by the strict gossipsub validation, it must be
+										// our direct peer because peer exchange is never forwarded.
+										let res = if message.source.as_ref() != Some(&peer_id) {
+											Err(Error::Libp2pError(
+												"Peer exchange from not connected peer".into(),
+											))
+										} else {
+											parse_peer_exchange_message(&peer_id, &message.data)
+										};
+										let acceptance = match res {
+											Ok(peer_arr) => {
+												signed_exchange_peers.insert(peer_id);
+												info!("Get {} peers from {}. Will process them later when we will need to increase connection number", peer_arr.len(), peer_id);
+												if let Ok(addr) = peer_id.as_onion_address() {
+													let mut new_peers_list = LIBP2P_PEERS.write();
+													let peer_entries = merge_peer_entries(
+														&new_peers_list,
+														peer_arr,
+													);
+													(*new_peers_list).insert(
+														addr,
+														(
+															peer_entries,
+															Utc::now().timestamp() as u64,
+														),
+													);
+												}
+												// Peer exchange is not forwarded
+												MessageAcceptance::Ignore
+											}
+											Err(e) => {
+												warn!(
+													"Get invalid peer exchange from {}, {}",
+													peer_id, e
+												);
+												MessageAcceptance::Reject
+											}
+										};
+										record_message_result(
+											&peer_id,
+											&id,
+											message.source.as_ref(),
+											&message.topic,
+											message.data.len(),
+											received,
+											&acceptance,
 										);
-									} else {
-										error!(
-											"Internal Error. Getting peer without onion address {}",
-											peer_id
+										let _ = swarm.gossipsub.report_message_validation_result(
+											&id, &peer_id, acceptance,
 										);
-									}
-								} else {
-									// We get the regular message and we need to validate it now.
-
-									let gossip = swarm.get_behaviour();
-
-									let acceptance = match validate_integrity_message(
-										&peer_id,
-										&message.data,
-										kernel_validation_fn.clone(),
-										&mut requests_cash,
-										fee_base,
-									) {
-										Ok((integrity_fee, sender_address)) => {
-											if integrity_fee > 0 {
-												let mut acceptance = MessageAcceptance::Accept;
-
-												if let Some((handler, _topic)) =
-													LIBP2P_MESSAGE_HANDLERS
-														.read()
-														.get(&message.topic)
-												{
-													if !(handler)(
-														&sender_address,
-														&message.topic,
-														read_message_data(&message.data),
-														integrity_fee,
-													) {
-														// false mean that message was invalid, so we can ban the peer
-														acceptance = MessageAcceptance::Reject;
-													}
+									} else if message.topic == clearnet_topic_hash {
+										// Source is verified by the strict gossipsub validation,
+										// nobody can announce the address for another peer
+										let source = message
+											.source
+											.clone()
+											.and_then(|s| s.as_onion_address().ok());
+										let acceptance = match (
+											source,
+											parse_clearnet_address_message(&message.data),
+										) {
+											(Some(source), Some(address)) => {
+												debug!(
+													"Get clearnet address {} for the peer {}",
+													address, source
+												);
+												set_peer_clearnet_address(&source, &address);
+												MessageAcceptance::Accept
+											}
+											_ => MessageAcceptance::Reject,
+										};
+										record_message_result(
+											&peer_id,
+											&id,
+											message.source.as_ref(),
+											&message.topic,
+											message.data.len(),
+											received,
+											&acceptance,
+										);
+										let _ = swarm.gossipsub.report_message_validation_result(
+											&id, &peer_id, acceptance,
+										);
+									} else if message.topic == receipt_topic {
+										// Receipts are signed by the publishing node (strict gossipsub validation),
+										// no integrity fee is required for them.
+										let acceptance = match parse_tx_receipt(&message.data) {
+											Some((kernels, _timestamp)) => {
+												let source = message
+													.source
+													.clone()
+													.unwrap_or(peer_id.clone())
+													.as_onion_address()
+													.unwrap_or(String::new());
+												debug!(
+													"Get tx receipt from {} for {} kernels",
+													source,
+													kernels.len()
+												);
+												if source.is_empty() {
+													Some(MessageAcceptance::Reject)
+												} else if let Some(call_period) = register_call(
+													&mut receipt_requests,
+													&source,
+													Utc::now().timestamp(),
+												) {
+													// Same per sender limit as the integrity messages have
+													debug!(
+														"Tx receipts from {} are sent every {} seconds, limit {}",
+														source, call_period, INTEGRITY_CALL_MAX_PERIOD
+													);
+													Some(MessageAcceptance::Ignore)
+												} else {
+													// Validation is reported when the handler is done
+													spawn_topic_handler(
+														&handled_messages_tx,
+														HandledMessage {
+															id: id.clone(),
+															peer_id: peer_id.clone(),
+															source: message.source.clone(),
+															topic: message.topic.clone(),
+															size: message.data.len(),
+															received,
+															fee_multiple: None,
+															acceptance: MessageAcceptance::Ignore,
+															integrity_rejected: false,
+															retry_after: None,
+														},
+														source,
+														message.data.clone(),
+														0,
+													);
+													None
 												}
-												acceptance
-											} else {
-												// Invalid message
-												MessageAcceptance::Reject
 											}
+											None => Some(MessageAcceptance::Reject),
+										};
+										if let Some(acceptance) = acceptance {
+											record_message_result(
+												&peer_id,
+												&id,
+												message.source.as_ref(),
+												&message.topic,
+												message.data.len(),
+												received,
+												&acceptance,
+											);
+											let _ = swarm
+												.gossipsub
+												.report_message_validation_result(
+													&id, &peer_id, acceptance,
+												);
 										}
-										Err(e) => {
-											warn!("Message is skipped, Unable to verify the message because of some error. {:?}", e);
-											MessageAcceptance::Ignore
-										}
-									};
-
-									debug!("report_message_validation_result as {:?}", acceptance);
-									let _ = gossip.report_message_validation_result(
-										&id, &peer_id, acceptance,
-									);
+									} else {
+										// We get the regular message, it is validated on the blocking
+										// pool because of the kernel lookups.
+										spawn_integrity_validation(
+											&handled_messages_tx,
+											HandledMessage {
+												id: id.clone(),
+												peer_id: peer_id.clone(),
+												source: message.source.clone(),
+												topic: message.topic.clone(),
+												size: message.data.len(),
+												received,
+												fee_multiple: None,
+												acceptance: MessageAcceptance::Ignore,
+												integrity_rejected: false,
+												retry_after: None,
+											},
+											message.data.clone(),
+											kernel_cache.clone(),
+											kernel_validation_fn.clone(),
+											fee_base,
+										);
+									}
 								}
+								_ => {}
 							}
-							_ => {}
-						},
+						}
 						Poll::Ready(None) | Poll::Pending => {
 							break;
 						}
 					}
 				}
 
+				// Forwarding the queued messages within the bandwidth budget. The queue is checked on
+				// every swarm wake up, the gossipsub heartbeat guarantees it at least once per heartbeat.
+				if let Some(queue) = forward_queue.as_mut() {
+					let now_ms = Utc::now().timestamp_millis();
+					for (id, peer_id) in queue.expire(forward_expire, now_ms) {
+						debug!("Message {} is expired at the forward queue", id);
+						let _ = swarm.gossipsub.report_message_validation_result(
+							&id,
+							&peer_id,
+							MessageAcceptance::Ignore,
+						);
+					}
+					for (id, peer_id) in queue.pop_ready(now_ms) {
+						let _ = swarm.gossipsub.report_message_validation_result(
+							&id,
+							&peer_id,
+							MessageAcceptance::Accept,
+						);
+					}
+				}
+
+				// Connection changes are detected by the peers set
+				{
+					let peers: HashSet<PeerId> = Swarm::network_info(&swarm)
+						.into_peers()
+						.into_iter()
+						.collect();
+					for peer in peers.difference(&connected_peers) {
+						notify_observers(|o| o.peer_connected(peer));
+						if is_peer_banned(peer) {
+							debug!("Disconnecting banned libp2p peer {}", peer);
+							swarm.gossipsub.disconnect_peer(peer.clone(), true);
+						}
+					}
+					for peer in connected_peers.difference(&peers) {
+						notify_observers(|o| o.peer_disconnected(peer));
+					}
+					connected_peers = peers;
+				}
+
+				{
+					let nw_info: NetworkInfo = Swarm::network_info(&swarm);
+					let mut stats = LIBP2P_STATS.write();
+					stats.connections = nw_info.connection_counters().num_connections();
+					stats.dials_in_flight = nw_info.connection_counters().num_pending_outgoing();
+				}
+
 				// cleanup expired requests_cash values
 				let history_time_limit = Utc::now().timestamp()
 					- INTEGRITY_CALL_HISTORY_LEN_LIMIT as i64 * INTEGRITY_CALL_MAX_PERIOD;
@@ -651,9 +2834,53 @@ pub async fn run_libp2p_node(
 				if last_cash_clean + Duration::from_secs(600) < now {
 					last_cash_clean = now;
 					// Let's do clean up...
+					let mut requests_cash = INTEGRITY_REQUESTS.lock();
+					let kernels = requests_cash.len();
 					requests_cash.retain(|_commit, history| {
 						*history.back().unwrap_or(&0) > history_time_limit
 					});
+					LIBP2P_STATS.write().request_cache.purged_expired +=
+						(kernels - requests_cash.len()) as u64;
+					receipt_requests.retain(|_sender, history| {
+						*history.back().unwrap_or(&0) > history_time_limit
+					});
+					integrity_rejects.cleanup(Utc::now().timestamp());
+				}
+				if last_purge + INTEGRITY_PURGE_INTERVAL < now {
+					last_purge = now;
+					{
+						let requests_cash = INTEGRITY_REQUESTS.lock();
+						let mut stats = LIBP2P_STATS.write();
+						stats.request_cache.kernels = requests_cash.len();
+						stats.request_cache.calls =
+							requests_cash.values().map(|h| h.len()).sum();
+					}
+					// The kernels that are not valid at the new tip are dropped. The chain
+					// lookups run on the blocking pool, the kernel cache entries expire by TTL.
+					let tip = CHAIN_TIP_HEIGHT.load(Ordering::Relaxed);
+					if purge_reorg || tip != last_purge_tip {
+						let reorg = purge_reorg;
+						purge_reorg = false;
+						last_purge_tip = tip;
+						let validation_fn = kernel_validation_fn.clone();
+						tokio::task::spawn_blocking(move || {
+							let purged =
+								purge_integrity_requests(&INTEGRITY_REQUESTS, validation_fn.as_ref());
+							if !purged.is_empty() {
+								debug!(
+									"Dropped messages history of {} integrity kernels that are not valid at height {}",
+									purged.len(),
+									tip
+								);
+								let mut stats = LIBP2P_STATS.write();
+								if reorg {
+									stats.request_cache.purged_reorg += purged.len() as u64;
+								} else {
+									stats.request_cache.purged_horizon += purged.len() as u64;
+								}
+							}
+						});
+					}
 				}
 
 				// Will try to reconnect if needed every 15 seconds.
@@ -678,79 +2905,191 @@ pub async fn run_libp2p_node(
 						return Poll::Ready(()); // Exiting
 					}
 
-					if nw_info.connection_counters().num_connections()
-						< connections_number_low as u32
+					// Checking the results of the dials that we made before
 					{
-						// Let's try to connect to somebody if we can...
-						let mut address_to_connect: Option<Multiaddr> = None;
-						loop {
-							// cloned to unblock the mutex
-							let mut libp2p_peers = LIBP2P_PEERS.write();
-							let peers: Vec<String> = libp2p_peers.keys().cloned().collect();
-							if let Some(peer_id) = peers.choose(&mut rng) {
-								if let Some(peers) = libp2p_peers.get_mut(peer_id) {
-									if !peers.0.is_empty() {
-										let tor_address =
-											peers.0.remove(rng.gen::<usize>() % peers.0.len());
-
-										let res: Result<OnionV3Address, OnionV3AddressError> =
-											tor_address.as_str().try_into();
-										let p = match res {
-											Ok(onion_addr) => match onion_addr.to_ed25519() {
-												Ok(pk) => PeerId::from_public_key(
-													libp2p::identity::PublicKey::Ed25519(
-														libp2p::identity::ed25519::PublicKey(pk),
-													),
-												),
-												Err(e) => {
-													error!("Unable to build PeerId form onion address {}, {}", tor_address, e);
-													continue;
-												}
-											},
-											Err(e) => {
-												error!("Unable to build PeerId form onion address {}, {}", tor_address, e);
-												continue;
-											}
-										};
-
-										if Swarm::is_connected(&swarm, &p)
-											|| Swarm::is_dialing(&swarm, &p) || p == this_peer_id
-										{
-											continue;
-										}
+						let now_ts = Utc::now().timestamp();
+						let mut libp2p_peers = LIBP2P_PEERS.write();
+						let mut pinned_peers = PINNED_PEERS.write();
+						pending_dials.retain(|peer, (tor_address, clearnet)| {
+							if Swarm::is_connected(&swarm, peer) {
+								report_dial_result(&mut libp2p_peers, tor_address, true, now_ts);
+								report_pinned_dial_result(
+									&mut pinned_peers,
+									tor_address,
+									true,
+									now_ts,
+								);
+								false
+							} else if Swarm::is_dialing(&swarm, peer) {
+								true
+							} else {
+								debug!("Unable to connect to libp2p peer {}", tor_address);
+								notify_observers(|o| o.dial_failed(tor_address));
+								report_dial_result(&mut libp2p_peers, tor_address, false, now_ts);
+								report_pinned_dial_result(
+									&mut pinned_peers,
+									tor_address,
+									false,
+									now_ts,
+								);
+								// Clearnet address might be stale, falling back to Tor
+								if *clearnet && tor_socks_port.is_some() {
+									reset_peer_clearnet_address(&mut libp2p_peers, tor_address);
+								}
+								false
+							}
+						});
+					}
 
-										let address = match p.get_address() {
-											Ok(addr) => addr,
-											Err(e) => {
-												warn!("Unable to get peer address to connect . Will skip it, {}", e);
-												continue;
-											}
-										};
+					// Announcing our clearnet address, so the peers can dial us without Tor
+					if let Some(address) = &clearnet_address {
+						if last_clearnet_announce.map_or(true, |t| {
+							t + Duration::from_secs(CLEARNET_ADDRESS_ANNOUNCE_INTERVAL) < now
+						}) && nw_info.connection_counters().num_connections() > 0
+						{
+							let message =
+								build_clearnet_address_message(address, Utc::now().timestamp());
+							let size = message.len() as u64;
+							match swarm.gossipsub.publish(clearnet_topic.clone(), message) {
+								Ok(_) => {
+									last_clearnet_announce = Some(now);
+									LIBP2P_STATS.write().bytes_out += size;
+								}
+								Err(e) => {
+									debug!("Unable to announce the clearnet address, {}", e)
+								}
+							}
+						}
+					}
 
-										let multiaddress = format!(
-											"/onion3/{}:{}",
-											address,
-											global::get_tor_libp2p_port()
-										);
-										match multiaddress.parse::<Multiaddr>() {
-											Ok(addr) => {
-												address_to_connect = Some(addr);
-												break;
-											}
-											Err(e) => {
-												warn!("Unable to construct onion multiaddress from {} the peer address. Will skip it, {}", multiaddress, e);
-												continue;
-											}
-										}
-									} else {
-										libp2p_peers.remove(peer_id);
-										continue;
+					// Publishing our connected peers, every record is signed by us
+					if last_peer_exchange.map_or(true, |t| {
+						t + Duration::from_secs(PEER_EXCHANGE_INTERVAL) < now
+					}) && nw_info.connection_counters().num_connections() > 0
+					{
+						signed_exchange_peers.retain(|p| Swarm::is_connected(&swarm, p));
+						let mut peers = Swarm::network_info(&swarm).into_peers();
+						peers.truncate(gossipsub::PEER_EXCHANGE_NUMBER_LIMIT);
+						match build_peer_exchange_message(
+							&peer_exchange_keys,
+							&peers,
+							Utc::now().timestamp(),
+						) {
+							Ok(message) => {
+								let size = message.len() as u64;
+								match swarm
+									.gossipsub
+									.publish(peer_exchange_topic.clone(), message)
+								{
+									Ok(_) => {
+										last_peer_exchange = Some(now);
+										LIBP2P_STATS.write().bytes_out += size;
 									}
+									Err(e) => debug!("Unable to publish peer exchange, {}", e),
 								}
+							}
+							Err(e) => error!("Unable to build peer exchange message, {}", e),
+						}
+					}
+
+					// Pinned peers are gossipsub explicit peers and are kept connected regardless of the
+					// connections number. They don't take the slots of the regular connections.
+					let mut pinned_connections: u32 = 0;
+					{
+						let now_ts = Utc::now().timestamp();
+						let pinned: Vec<(String, bool)> = PINNED_PEERS
+							.read()
+							.values()
+							.filter(|e| !is_onion_banned(&e.address))
+							.map(|e| (e.address.clone(), e.next_dial <= now_ts))
+							.collect();
+						let mut pinned_ids: HashSet<PeerId> = HashSet::new();
+						let mut dial_limit_reached = false;
+						for (tor_address, can_dial) in pinned {
+							let (addr, p, clearnet) = match get_peer_dial_address(
+								&tor_address,
+								clearnet_port.is_some(),
+								tor_socks_port.is_some(),
+							) {
+								Some(res) => res,
+								None => continue,
+							};
+							if p == this_peer_id {
+								continue;
+							}
+							if !explicit_peers.contains(&p) {
+								swarm.gossipsub.add_explicit_peer(&p);
+							}
+							pinned_ids.insert(p.clone());
+							if Swarm::is_connected(&swarm, &p) {
+								pinned_connections += 1;
+								continue;
+							}
+							if !can_dial || dial_limit_reached || Swarm::is_dialing(&swarm, &p) {
+								continue;
+							}
+							match Swarm::dial_addr(swarm, addr.clone()) {
+								Ok(_) => {
+									info!("Dialling to the pinned peer {}", addr);
+									report_pinned_dial_attempt(
+										&mut PINNED_PEERS.write(),
+										&tor_address,
+										now_ts,
+									);
+									pending_dials.insert(p, (tor_address, clearnet));
+								}
+								Err(con_limit) => {
+									error!("Unable deal to the pinned peer {}. Connected to {} peers, connection limit {}", tor_address, con_limit.current, con_limit.limit);
+									dial_limit_reached = true;
+								}
+							}
+						}
+						// Unpinned or banned peers are regular peers again
+						for p in explicit_peers.difference(&pinned_ids) {
+							swarm.gossipsub.remove_explicit_peer(p);
+						}
+						explicit_peers = pinned_ids;
+					}
+
+					if nw_info
+						.connection_counters()
+						.num_connections()
+						.saturating_sub(pinned_connections)
+						< connections_number_low as u32
+					{
+						// Let's try to connect to somebody if we can...
+						let mut address_to_connect: Option<(Multiaddr, PeerId, String, bool)> =
+							None;
+						let now_ts = Utc::now().timestamp();
+						// Candidates are all known addresses that are not in backoff
+						let mut candidates: Vec<String> = LIBP2P_PEERS
+							.read()
+							.values()
+							.flat_map(|(entries, _)| entries.iter())
+							.filter(|e| e.next_dial <= now_ts && !is_onion_banned(&e.address))
+							.map(|e| e.address.clone())
+							.collect();
+						candidates.sort();
+						candidates.dedup();
+						candidates.shuffle(&mut rng);
+
+						for tor_address in candidates {
+							let (addr, p, clearnet) = match get_peer_dial_address(
+								&tor_address,
+								clearnet_port.is_some(),
+								tor_socks_port.is_some(),
+							) {
+								Some(res) => res,
+								None => continue,
+							};
+							if Swarm::is_connected(&swarm, &p)
+								|| Swarm::is_dialing(&swarm, &p)
+								|| p == this_peer_id
+							{
 								continue;
-							} else {
-								break; // no data is found...
 							}
+							address_to_connect = Some((addr, p, tor_address, clearnet));
+							break;
 						}
 
 						if address_to_connect.is_none()
@@ -762,10 +3101,16 @@ pub async fn run_libp2p_node(
 						}
 
 						// The address of a new peer is selected, we can deal to it.
-						if let Some(addr) = address_to_connect {
+						if let Some((addr, peer, tor_address, clearnet)) = address_to_connect {
 							match Swarm::dial_addr(swarm, addr.clone()) {
 								Ok(_) => {
 									info!("Dialling to a new peer {}", addr);
+									report_dial_attempt(
+										&mut LIBP2P_PEERS.write(),
+										&tor_address,
+										now_ts,
+									);
+									pending_dials.insert(peer, (tor_address, clearnet));
 								}
 								Err(con_limit) => {
 									error!("Unable deal to a new peer. Connected to {} peers, connection limit {}", con_limit.current, con_limit.limit);
@@ -775,53 +3120,54 @@ pub async fn run_libp2p_node(
 					}
 				}
 			}
-			None => (),
+			None => {
+				// Swarm was reset, nothing to poll any more
+				info!("libp2p swarm is gone, exiting libp2p polling task");
+				return Poll::Ready(());
+			}
 		};
 
-		if *stop_mutex.lock().unwrap() == 0 {
-			info!("Exiting libp2p polling task");
-			Poll::Ready(()) // Exiting
-		} else {
-			Poll::Pending as Poll<()>
-		}
-	}));
+		Poll::Pending as Poll<()>
+	})
+	.await;
 
-	reset_libp2p_swarm();
+	shutdown_libp2p_swarm();
 
 	Ok(())
 }
 
-// return paid fee if this message is valid. It is caller responsibility to make sure that valid_outputs cache is well maintained
-//  Otherwise return 0, fee is invalid
-// output_validation_fn  - lookup for the kernel excess and returns it's height
-pub fn validate_integrity_message(
-	peer_id: &PeerId,
+/// Integrity message validation checks, in the order they are applied
+pub const INTEGRITY_MESSAGE_CHECKS: &[&str] = &[
+	"version",
+	"integrity_kernel",
+	"sender_address",
+	"signature",
+	"kernel_on_chain",
+	"integrity_fee",
+	"rate_limit",
+];
+
+// Index of the failed check from INTEGRITY_MESSAGE_CHECKS and the reason
+pub(crate) type IntegrityCheckFailure = (usize, String);
+
+// Run the integrity message through the validation checks. Return paid fee and sender address
+// if the message is valid. Otherwise return the failed check.
+pub(crate) fn check_integrity_message(
 	message: &Vec<u8>,
 	output_validation_fn: Arc<impl Fn(&Commitment) -> Result<Option<TxKernel>, Error>>,
 	requests_cash: &mut HashMap<Commitment, VecDeque<i64>>,
 	fee_base: u64,
-) -> Result<(u64, String), Error> {
+) -> Result<Result<(u64, String), IntegrityCheckFailure>, Error> {
 	let mut ser = SimplePopSerializer::new(message);
 	if ser.version != get_message_version() {
-		debug!(
-			"Get message with invalid version {} from peer {}",
-			ser.version, peer_id
-		);
-		// Will be rejected and peer will be banned
-		return Ok((0, String::new()));
+		return Ok(Err((0, format!("invalid version {}", ser.version))));
 	}
 
 	// Let's check signature first. The kernel search might take time. Signature checking should be faster.
 	let integrity_kernel_excess = Commitment::from_vec(ser.pop_vec());
 	let integrity_pk = match integrity_kernel_excess.to_pubkey() {
-		Ok(pk) => pk,
-		Err(e) => {
-			debug!(
-				"Get invalid message from peer {}. integrity_kernel is not valid, {}",
-				peer_id, e
-			);
-			return Ok((0, String::new()));
-		}
+		Ok(pk) => pk,
+		Err(e) => return Ok(Err((1, format!("integrity_kernel is not valid, {}", e)))),
 	};
 
 	let secp = Secp256k1::with_caps(ContextFlag::VerifyOnly);
@@ -830,36 +3176,23 @@ pub fn validate_integrity_message(
 	let sender_address_pk = match DalekPublicKey::from_bytes(&ser.pop_vec()) {
 		Ok(pk) => pk,
 		Err(e) => {
-			debug!(
-				"Get invalid message from peer {}. Unable to decode sender address PK, {}",
-				peer_id, e
-			);
-			return Ok((0, String::new()));
+			return Ok(Err((
+				2,
+				format!("Unable to decode sender address PK, {}", e),
+			)))
 		}
 	};
 	let msg_hash = Hash::from_vec(&sender_address_pk.to_bytes());
 	let msg_message = match Message::from_slice(msg_hash.as_bytes()) {
 		Ok(m) => m,
-		Err(e) => {
-			debug!(
-				"Get invalid message from peer {}. Unable to build a message, {}",
-				peer_id, e
-			);
-			return Ok((0, String::new()));
-		}
+		Err(e) => return Ok(Err((2, format!("Unable to build a message, {}", e)))),
 	};
 
 	let sender_address = PeerId::onion_v3_from_pubkey(&sender_address_pk);
 
 	let signature = match Signature::from_compact(&ser.pop_vec()) {
 		Ok(s) => s,
-		Err(e) => {
-			debug!(
-				"Get invalid message from peer {}. Unable to read signature, {}",
-				peer_id, e
-			);
-			return Ok((0, String::new()));
-		}
+		Err(e) => return Ok(Err((3, format!("Unable to read signature, {}", e)))),
 	};
 
 	match aggsig::verify_completed_sig(
@@ -871,69 +3204,218 @@ pub fn validate_integrity_message(
 	) {
 		Ok(()) => (),
 		Err(e) => {
-			debug!(
-				"Get invalid message from peer {}. Integrity kernel signature is invalid, {}",
-				peer_id, e
-			);
-			return Ok((0, String::new()));
+			return Ok(Err((
+				3,
+				format!("Integrity kernel signature is invalid, {}", e),
+			)))
 		}
 	}
 
 	let integrity_kernel = match (output_validation_fn)(&integrity_kernel_excess)? {
 		Some(r) => r.clone(),
 		None => {
-			debug!(
-				"Get invalid message from peer {}. integrity_kernel {} is not found at the blockchain",
-				peer_id, integrity_kernel_excess.to_hex()
-			);
-			return Ok((0, String::new()));
+			return Ok(Err((
+				4,
+				format!(
+					"integrity_kernel {} is not found at the blockchain",
+					integrity_kernel_excess.to_hex()
+				),
+			)))
 		}
 	};
 
 	let integrity_fee = integrity_kernel.features.get_fee();
 
 	if integrity_fee < fee_base * INTEGRITY_FEE_MIN_X {
-		debug!(
-			"Get invalid message from peer {}. integrity_kernel fee is below minimal level of 10X accepted base fee",
-			peer_id
-		);
-		return Ok((0, String::new()));
+		return Ok(Err((
+			5,
+			"integrity_kernel fee is below minimal level of 10X accepted base fee".to_string(),
+		)));
+	}
+
+	// Checking if ths peer sent too many messages
+	if let Some(call_period) = register_call(
+		requests_cash,
+		&integrity_kernel_excess,
+		Utc::now().timestamp(),
+	) {
+		return Ok(Err((
+			6,
+			format!(
+				"Message sending period is {}, limit {}",
+				call_period, INTEGRITY_CALL_MAX_PERIOD
+			),
+		)));
+	}
+
+	Ok(Ok((integrity_fee, sender_address)))
+}
+
+/// Add the call to the calls history of the key (integrity kernel, sender address). Returns
+/// the average period between the last calls if it is below INTEGRITY_CALL_MAX_PERIOD.
+pub(crate) fn register_call<K: std::hash::Hash + Eq + Clone>(
+	requests_cash: &mut HashMap<K, VecDeque<i64>>,
+	key: &K,
+	now: i64,
+) -> Option<i64> {
+	let call_history = requests_cash
+		.entry(key.clone())
+		.or_insert_with(VecDeque::new);
+	call_history.push_back(now);
+	while call_history.len() > INTEGRITY_CALL_HISTORY_LEN_LIMIT {
+		call_history.pop_front();
+	}
+	if call_history.len() < INTEGRITY_CALL_HISTORY_LEN_LIMIT {
+		return None;
+	}
+	let call_period =
+		(call_history.back()? - call_history.front()?) / (call_history.len() - 1) as i64;
+	if call_period < INTEGRITY_CALL_MAX_PERIOD {
+		Some(call_period)
+	} else {
+		None
+	}
+}
+
+/// Drop the messages history of the integrity kernels that are not valid any more, they were
+/// reverted by the reorg or fell below the INTEGRITY_FEE_VALID_BLOCKS horizon. Kernels that
+/// can't be checked now are kept. The lock is not held during the kernel lookups.
+/// Returns the dropped kernels.
+fn purge_integrity_requests(
+	requests_cash: &Mutex<HashMap<Commitment, VecDeque<i64>>>,
+	kernel_validation_fn: &impl Fn(&Commitment) -> Result<Option<TxKernel>, Error>,
+) -> Vec<Commitment> {
+	let kernels: Vec<Commitment> = requests_cash.lock().keys().cloned().collect();
+	let purged: Vec<Commitment> = kernels
+		.into_iter()
+		.filter(|excess| match (kernel_validation_fn)(excess) {
+			Ok(kernel) => kernel.is_none(),
+			Err(_) => false,
+		})
+		.collect();
+	let mut requests_cash = requests_cash.lock();
+	for excess in &purged {
+		requests_cash.remove(excess);
+	}
+	purged
+}
+
+/// Seconds until the next message with the same integrity kernel is accepted, if the kernel
+/// exceeds the message rate limit. The calls history is updated by the message validation.
+fn integrity_rate_limit_retry_after(
+	message: &Vec<u8>,
+	requests_cash: &HashMap<Commitment, VecDeque<i64>>,
+	now: i64,
+) -> Option<i64> {
+	let mut ser = SimplePopSerializer::new(message);
+	if ser.version != get_message_version() {
+		return None;
 	}
+	let integrity_kernel_excess = Commitment::from_vec(ser.pop_vec());
+	let call_history = requests_cash.get(&integrity_kernel_excess)?;
+	if call_history.len() < INTEGRITY_CALL_HISTORY_LEN_LIMIT {
+		return None;
+	}
+	let call_period =
+		(call_history.back()? - call_history.front()?) / (call_history.len() - 1) as i64;
+	if call_period >= INTEGRITY_CALL_MAX_PERIOD {
+		return None;
+	}
+	// The next call pushes the oldest one out of the history
+	let retry_at = call_history.get(1)?
+		+ INTEGRITY_CALL_MAX_PERIOD * (INTEGRITY_CALL_HISTORY_LEN_LIMIT as i64 - 1);
+	Some(std::cmp::max(retry_at - now, 1))
+}
 
-	// Updating calls history cash.
+/// Send the rate limit feedback to the message publisher. Only connected publishers are
+/// notified, once per retry-after period.
+fn send_rate_limit_feedback(
+	swarm: &mut Swarm<MwcBehaviour>,
+	publisher: &PeerId,
+	topic: &TopicHash,
+	retry_after: i64,
+	notified: &mut HashMap<PeerId, i64>,
+) {
 	let now = Utc::now().timestamp();
-	match requests_cash.get_mut(&integrity_kernel_excess) {
-		Some(calls) => {
-			calls.push_back(now);
-			while calls.len() > INTEGRITY_CALL_HISTORY_LEN_LIMIT {
-				calls.pop_front();
-			}
-		}
-		None => {
-			let mut calls: VecDeque<i64> = VecDeque::new();
-			calls.push_back(now);
-			requests_cash.insert(integrity_kernel_excess.clone(), calls);
-		}
+	notified.retain(|_, until| *until > now);
+	if notified.contains_key(publisher) || !Swarm::is_connected(swarm, publisher) {
+		return;
 	}
-	// Checking if ths peer sent too many messages
-	let call_history = requests_cash.get(&integrity_kernel_excess).unwrap();
-	if call_history.len() >= INTEGRITY_CALL_HISTORY_LEN_LIMIT {
-		let call_period = (call_history.back().unwrap() - call_history.front().unwrap())
-			/ (call_history.len() - 1) as i64;
-		if call_period < INTEGRITY_CALL_MAX_PERIOD {
+	notified.insert(publisher.clone(), now + retry_after);
+	debug!(
+		"Sending rate limit feedback to {}, topic {}, retry after {}",
+		publisher, topic, retry_after
+	);
+	let request = libp2p_node_api::build_rate_limit_request(&libp2p_node_api::RateLimitFeedback {
+		topic: topic.as_str().to_string(),
+		min_period: INTEGRITY_CALL_MAX_PERIOD,
+		retry_after,
+	});
+	// Response is not expected, the request is not registered
+	swarm
+		.node_api
+		.send_request(publisher, request.to_string().into_bytes());
+}
+
+// return paid fee if this message is valid. It is caller responsibility to make sure that valid_outputs cache is well maintained
+//  Otherwise return 0, fee is invalid
+// output_validation_fn  - lookup for the kernel excess and returns it's height
+pub fn validate_integrity_message(
+	peer_id: &PeerId,
+	message: &Vec<u8>,
+	output_validation_fn: Arc<impl Fn(&Commitment) -> Result<Option<TxKernel>, Error>>,
+	requests_cash: &mut HashMap<Commitment, VecDeque<i64>>,
+	fee_base: u64,
+) -> Result<(u64, String), Error> {
+	match check_integrity_message(message, output_validation_fn, requests_cash, fee_base)? {
+		Ok((integrity_fee, sender_address)) => {
 			debug!(
-				"Get invalid message from peer {}. Message sending period is {}, limit {}",
-				peer_id, call_period, INTEGRITY_CALL_MAX_PERIOD
+				"Validated the message from peer {} with integrity fee {}, sender address {}",
+				peer_id, integrity_fee, sender_address
 			);
-			return Ok((0, String::new()));
+			Ok((integrity_fee, sender_address))
+		}
+		Err((check, reason)) => {
+			// Will be rejected and peer will be banned
+			debug!(
+				"Get invalid message from peer {}. Check {} is failed, {}",
+				peer_id, INTEGRITY_MESSAGE_CHECKS[check], reason
+			);
+			Ok((0, String::new()))
 		}
 	}
+}
 
-	debug!(
-		"Validated the message from peer {} with integrity fee {}, sender address {}",
-		peer_id, integrity_fee, sender_address
-	);
-	return Ok((integrity_fee, sender_address));
+/// Run the gossip integrity message through the validation without processing it.
+/// Returns the checks that was done, checks are stopped at the first failure.
+/// Message rate is checked against the empty history.
+pub fn simulate_integrity_message(
+	message: &Vec<u8>,
+	output_validation_fn: Arc<impl Fn(&Commitment) -> Result<Option<TxKernel>, Error>>,
+	fee_base: u64,
+) -> Vec<SimulationCheck> {
+	let mut requests_cash = HashMap::new();
+	let (passed, failed) = match check_integrity_message(
+		message,
+		output_validation_fn,
+		&mut requests_cash,
+		fee_base,
+	) {
+		Ok(Ok(_)) => (INTEGRITY_MESSAGE_CHECKS.len(), None),
+		Ok(Err((check, reason))) => (check, Some((check, reason))),
+		Err(e) => (4, Some((4, format!("Unable to validate, {}", e)))),
+	};
+	let mut checks: Vec<SimulationCheck> = INTEGRITY_MESSAGE_CHECKS[..passed]
+		.iter()
+		.map(|c| SimulationCheck::passed(c))
+		.collect();
+	if let Some((check, reason)) = failed {
+		checks.push(SimulationCheck::failed(
+			INTEGRITY_MESSAGE_CHECKS[check],
+			reason,
+		));
+	}
+	checks
 }
 
 /// Skip the header and return the message data
@@ -1131,3 +3613,680 @@ fn test_integrity() -> Result<(), Error> {
 
 	Ok(())
 }
+
+#[test]
+fn test_libp2p_stats_record_message() {
+	let mut stats = Libp2pStats::default();
+	let topic = Topic::new("SwapMarketplace").hash();
+	stats.record_message(&topic, 100, &MessageAcceptance::Accept);
+	stats.record_message(&topic, 50, &MessageAcceptance::Reject);
+	stats.record_message(&topic, 10, &MessageAcceptance::Ignore);
+
+	assert_eq!(stats.messages_received, 3);
+	assert_eq!(stats.messages_accepted, 1);
+	assert_eq!(stats.messages_rejected, 1);
+	assert_eq!(stats.messages_ignored, 1);
+	assert_eq!(stats.bytes_in, 160);
+	let topic_stats = stats.topics.get("SwapMarketplace").unwrap();
+	assert_eq!(topic_stats.received, 3);
+	assert_eq!(topic_stats.accepted, 1);
+}
+
+#[test]
+fn test_tx_receipt() {
+	let kernels = vec![
+		Commitment::from_vec(vec![8; PEDERSEN_COMMITMENT_SIZE]),
+		Commitment::from_vec(vec![9; PEDERSEN_COMMITMENT_SIZE]),
+	];
+	let now = Utc::now().timestamp();
+	let receipt = build_tx_receipt(&kernels, now);
+	assert_eq!(parse_tx_receipt(&receipt), Some((kernels.clone(), now)));
+
+	// Expired receipt must be rejected
+	let receipt = build_tx_receipt(&kernels, now - TX_RECEIPT_MAX_AGE - 10);
+	assert_eq!(parse_tx_receipt(&receipt), None);
+
+	// Empty receipt is invalid
+	let receipt = build_tx_receipt(&vec![], now);
+	assert_eq!(parse_tx_receipt(&receipt), None);
+
+	// Receipt with the max number of kernels fits the size limit, padded one doesn't
+	let kernels =
+		vec![Commitment::from_vec(vec![8; PEDERSEN_COMMITMENT_SIZE]); TX_RECEIPT_KERNELS_LIMIT];
+	let mut receipt = build_tx_receipt(&kernels, now);
+	assert_eq!(parse_tx_receipt(&receipt), Some((kernels, now)));
+	receipt.resize(TX_RECEIPT_MAX_SIZE + 1, 0);
+	assert_eq!(parse_tx_receipt(&receipt), None);
+}
+
+#[test]
+fn test_register_call() {
+	let mut requests: HashMap<String, VecDeque<i64>> = HashMap::new();
+	let sender = "sender".to_string();
+	for i in 0..INTEGRITY_CALL_HISTORY_LEN_LIMIT as i64 - 1 {
+		assert_eq!(register_call(&mut requests, &sender, 1000 + i), None);
+	}
+	// History is full, calls are too frequent
+	assert_eq!(register_call(&mut requests, &sender, 1100), Some(100 / 9));
+	// Other senders are not affected
+	assert_eq!(
+		register_call(&mut requests, &"other".to_string(), 1100),
+		None
+	);
+}
+
+#[test]
+fn test_libp2p_dial_backoff() {
+	let mut peer_list: HashMap<String, (Vec<Libp2pPeerEntry>, u64)> = HashMap::new();
+	let address = "test_address".to_string();
+	peer_list.insert(
+		"SELF".to_string(),
+		(vec![Libp2pPeerEntry::new(address.clone())], 0),
+	);
+
+	let now = 1000;
+	report_dial_attempt(&mut peer_list, &address, now);
+	assert_eq!(
+		peer_list.get("SELF").unwrap().0[0].next_dial,
+		now + LIBP2P_DIAL_BACKOFF_BASE
+	);
+
+	report_dial_result(&mut peer_list, &address, false, now);
+	let entry = peer_list.get("SELF").unwrap().0[0].clone();
+	assert_eq!(entry.failures, 1);
+	assert_eq!(entry.next_dial, now + 2 * LIBP2P_DIAL_BACKOFF_BASE);
+
+	// History is kept when the address is reported by another peer
+	let merged = merge_peer_entries(&peer_list, vec![address.clone(), "other".to_string()]);
+	assert_eq!(merged[0].failures, 1);
+	assert_eq!(merged[1].failures, 0);
+
+	report_dial_result(&mut peer_list, &address, true, now);
+	assert_eq!(peer_list.get("SELF").unwrap().0[0].failures, 0);
+
+	// Dead address is evicted
+	for _ in 0..LIBP2P_DIAL_MAX_FAILURES {
+		report_dial_result(&mut peer_list, &address, false, now);
+	}
+	assert!(peer_list.is_empty());
+	assert_eq!(get_dial_backoff(100), LIBP2P_DIAL_BACKOFF_MAX);
+}
+
+#[test]
+fn test_libp2p_pinned_dial_backoff() {
+	let mut pinned: HashMap<String, Libp2pPeerEntry> = HashMap::new();
+	let address = "pinned_address".to_string();
+	pinned.insert(address.clone(), Libp2pPeerEntry::new(address.clone()));
+
+	let now = 1000;
+	report_pinned_dial_attempt(&mut pinned, &address, now);
+	assert_eq!(
+		pinned.get(&address).unwrap().next_dial,
+		now + LIBP2P_PINNED_DIAL_BACKOFF_BASE
+	);
+
+	// Pinned peer is never evicted, backoff is capped
+	for _ in 0..LIBP2P_DIAL_MAX_FAILURES * 2 {
+		report_pinned_dial_result(&mut pinned, &address, false, now);
+	}
+	let entry = pinned.get(&address).unwrap().clone();
+	assert_eq!(entry.failures, LIBP2P_DIAL_MAX_FAILURES * 2);
+	assert_eq!(entry.next_dial, now + LIBP2P_PINNED_DIAL_BACKOFF_MAX);
+
+	report_pinned_dial_result(&mut pinned, &address, true, now);
+	let entry = pinned.get(&address).unwrap().clone();
+	assert_eq!(entry.failures, 0);
+	assert_eq!(entry.next_dial, 0);
+}
+
+#[test]
+fn test_clearnet_address() {
+	let valid = [
+		"/ip4/1.2.3.4/tcp/3419",
+		"/ip6/::1/tcp/3419",
+		"/dns4/node.mwc.mw/tcp/3419",
+	];
+	for addr in &valid {
+		assert!(is_clearnet_address(&addr.parse::<Multiaddr>().unwrap()));
+	}
+	let invalid = [
+		"/ip4/1.2.3.4/udp/3419",
+		"/ip4/1.2.3.4",
+		"/ip4/1.2.3.4/tcp/3419/ws",
+	];
+	for addr in &invalid {
+		assert!(!is_clearnet_address(&addr.parse::<Multiaddr>().unwrap()));
+	}
+
+	let now = Utc::now().timestamp();
+	let msg = build_clearnet_address_message(valid[0], now);
+	assert_eq!(
+		parse_clearnet_address_message(&msg),
+		Some(valid[0].to_string())
+	);
+	// expired
+	let msg = build_clearnet_address_message(valid[0], now - CLEARNET_ADDRESS_MAX_AGE - 10);
+	assert!(parse_clearnet_address_message(&msg).is_none());
+	// not a clearnet address
+	let msg = build_clearnet_address_message(invalid[0], now);
+	assert!(parse_clearnet_address_message(&msg).is_none());
+
+	let mut peer_list: HashMap<String, (Vec<Libp2pPeerEntry>, u64)> = HashMap::new();
+	let mut entry = Libp2pPeerEntry::new("test_address".to_string());
+	entry.clearnet_address = Some(valid[0].to_string());
+	peer_list.insert("SELF".to_string(), (vec![entry], 0));
+	assert_eq!(
+		get_peer_clearnet_address(&peer_list, "test_address"),
+		Some(valid[0].to_string())
+	);
+	reset_peer_clearnet_address(&mut peer_list, "test_address");
+	assert!(get_peer_clearnet_address(&peer_list, "test_address").is_none());
+
+	// Announced unknown peers are capped, the oldest ones are evicted
+	for i in 0..CLEARNET_SELF_PEERS_MAX + 10 {
+		set_peer_clearnet_address(&format!("clearnet_cap_test_{}", i), valid[0]);
+	}
+	let peers = LIBP2P_PEERS.read();
+	let self_peers = &peers.get("SELF").unwrap().0;
+	assert!(
+		self_peers
+			.iter()
+			.filter(|e| e.clearnet_address.is_some())
+			.count() <= CLEARNET_SELF_PEERS_MAX
+	);
+	assert!(!self_peers
+		.iter()
+		.any(|e| e.address == "clearnet_cap_test_0"));
+	assert!(self_peers
+		.iter()
+		.any(|e| e.address == format!("clearnet_cap_test_{}", CLEARNET_SELF_PEERS_MAX + 9)));
+}
+
+#[test]
+fn test_peer_exchange_signing() {
+	let advertiser_keys = Keypair::ed25519_from_secret(&mut [1u8; 32]).unwrap();
+	let advertiser = PeerId::from_public_key(advertiser_keys.public());
+	let other_keys = Keypair::ed25519_from_secret(&mut [2u8; 32]).unwrap();
+	let other = PeerId::from_public_key(other_keys.public());
+	let peer = PeerId::from_public_key(
+		Keypair::ed25519_from_secret(&mut [3u8; 32])
+			.unwrap()
+			.public(),
+	);
+
+	let now = Utc::now().timestamp();
+	let msg = build_peer_exchange_message(&advertiser_keys, &vec![peer.clone()], now).unwrap();
+	assert_eq!(
+		parse_peer_exchange_message(&advertiser, &msg).unwrap(),
+		vec![peer.as_onion_address().unwrap()]
+	);
+	// Records are signed by the advertiser, nobody else can publish them
+	assert!(parse_peer_exchange_message(&other, &msg).is_err());
+	let msg = build_peer_exchange_message(&other_keys, &vec![peer.clone()], now).unwrap();
+	assert!(parse_peer_exchange_message(&advertiser, &msg).is_err());
+	// expired
+	let msg = build_peer_exchange_message(
+		&advertiser_keys,
+		&vec![peer.clone()],
+		now - PEER_EXCHANGE_MAX_AGE - 10,
+	)
+	.unwrap();
+	assert!(parse_peer_exchange_message(&advertiser, &msg).is_err());
+	// legacy unsigned format
+	let mut ser = SimplePushSerializer::new(get_message_version());
+	ser.push_u16(1);
+	ser.push_vec(&peer.to_bytes());
+	assert!(parse_peer_exchange_message(&advertiser, &ser.to_vec()).is_err());
+}
+
+#[test]
+fn test_legacy_peer_message() {
+	let peer = PeerId::from_public_key(
+		Keypair::ed25519_from_secret(&mut [3u8; 32])
+			.unwrap()
+			.public(),
+	);
+
+	let mut ser = SimplePushSerializer::new(1);
+	ser.push_u16(1);
+	ser.push_vec(&peer.to_bytes());
+	assert_eq!(
+		parse_legacy_peer_message(&ser.to_vec()).unwrap(),
+		vec![peer.as_onion_address().unwrap()]
+	);
+	// signed format is not a legacy message
+	let keys = Keypair::ed25519_from_secret(&mut [1u8; 32]).unwrap();
+	let msg =
+		build_peer_exchange_message(&keys, &vec![peer.clone()], Utc::now().timestamp()).unwrap();
+	assert!(parse_legacy_peer_message(&msg).is_err());
+	// too many peers
+	let mut ser = SimplePushSerializer::new(1);
+	ser.push_u16((gossipsub::PEER_EXCHANGE_NUMBER_LIMIT + 1) as u16);
+	assert!(parse_legacy_peer_message(&ser.to_vec()).is_err());
+}
+
+#[test]
+fn test_message_dedup_cache() {
+	let topic = Topic::new("test").hash();
+	let other_topic = Topic::new("other").hash();
+	let mut cache = MessageDedupCache::new(2, 10);
+	let now = Utc::now().timestamp();
+
+	assert!(!cache.is_duplicate(&topic, b"msg1", now));
+	assert!(cache.is_duplicate(&topic, b"msg1", now + 5));
+	// Same payload at another topic is a different message
+	assert!(!cache.is_duplicate(&other_topic, b"msg1", now));
+	// TTL is expired
+	assert!(!cache.is_duplicate(&topic, b"msg1", now + 20));
+	assert!(cache.is_duplicate(&topic, b"msg1", now + 21));
+	// Cache is bounded, oldest record is evicted
+	assert!(!cache.is_duplicate(&topic, b"msg2", now));
+	assert!(!cache.is_duplicate(&topic, b"msg3", now));
+	assert!(!cache.is_duplicate(&topic, b"msg1", now + 21));
+}
+
+#[test]
+fn test_integrity_kernel_cache() {
+	use std::sync::atomic::AtomicUsize;
+
+	let lookups = AtomicUsize::new(0);
+	let known_excess = Commitment::from_vec(vec![1; PEDERSEN_COMMITMENT_SIZE]);
+	let unknown_excess = Commitment::from_vec(vec![2; PEDERSEN_COMMITMENT_SIZE]);
+	let kernel_validation_fn = |excess: &Commitment| -> Result<Option<TxKernel>, Error> {
+		lookups.fetch_add(1, Ordering::Relaxed);
+		if *excess == known_excess {
+			Ok(Some(TxKernel::empty()))
+		} else {
+			Ok(None)
+		}
+	};
+
+	let mut cache = IntegrityKernelCache::new(10, 5);
+	let height = 1000;
+	assert!(cache
+		.lookup(&known_excess, height, &kernel_validation_fn)
+		.unwrap()
+		.is_some());
+	assert!(cache
+		.lookup(&known_excess, height + 5, &kernel_validation_fn)
+		.unwrap()
+		.is_some());
+	assert_eq!(lookups.load(Ordering::Relaxed), 1);
+	// Not found kernels are not cached
+	assert!(cache
+		.lookup(&unknown_excess, height, &kernel_validation_fn)
+		.unwrap()
+		.is_none());
+	assert!(cache
+		.lookup(&unknown_excess, height, &kernel_validation_fn)
+		.unwrap()
+		.is_none());
+	assert_eq!(lookups.load(Ordering::Relaxed), 3);
+	// Expired and cleared kernels are looked up again
+	cache
+		.lookup(&known_excess, height + 6, &kernel_validation_fn)
+		.unwrap();
+	assert_eq!(lookups.load(Ordering::Relaxed), 4);
+	cache.clear();
+	cache
+		.lookup(&known_excess, height + 6, &kernel_validation_fn)
+		.unwrap();
+	assert_eq!(lookups.load(Ordering::Relaxed), 5);
+}
+
+#[test]
+fn test_integrity_rejects() {
+	let peer = PeerId::random();
+	let other_peer = PeerId::random();
+	let mut rejects = IntegrityRejects::new(3, 100);
+	let now = Utc::now().timestamp();
+
+	assert!(!rejects.register(&peer, now));
+	assert!(!rejects.register(&peer, now + 1));
+	assert!(!rejects.register(&other_peer, now + 1));
+	assert!(rejects.register(&peer, now + 2));
+	// History is reset after the ban
+	assert!(!rejects.register(&peer, now + 3));
+	// Expired rejects are not counted
+	assert!(!rejects.register(&other_peer, now + 200));
+	assert!(!rejects.register(&other_peer, now + 201));
+	assert!(rejects.register(&other_peer, now + 202));
+
+	rejects.cleanup(now + 1000);
+	assert!(rejects.rejects.is_empty());
+}
+
+#[test]
+fn test_libp2p_observer() {
+	struct RejectCounter(std::sync::atomic::AtomicUsize);
+	impl Libp2pObserver for RejectCounter {
+		fn message_rejected(&self, _peer: &PeerId, topic: &str) {
+			assert_eq!(topic, "observer_test");
+			self.0.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+		}
+	}
+
+	let counter = Arc::new(RejectCounter(std::sync::atomic::AtomicUsize::new(0)));
+	register_observer(counter.clone());
+
+	let peer = PeerId::random();
+	let topic = Topic::new("observer_test").hash();
+	let now = Instant::now();
+	record_message_result(
+		&peer,
+		&"1",
+		None,
+		&topic,
+		10,
+		now,
+		&MessageAcceptance::Accept,
+	);
+	record_message_result(
+		&peer,
+		&"2",
+		None,
+		&topic,
+		10,
+		now,
+		&MessageAcceptance::Ignore,
+	);
+	record_message_result(
+		&peer,
+		&"3",
+		None,
+		&topic,
+		10,
+		now,
+		&MessageAcceptance::Reject,
+	);
+	assert_eq!(counter.0.load(std::sync::atomic::Ordering::Relaxed), 1);
+}
+
+#[test]
+fn test_gossip_trace() {
+	let entry = |id: &str, topic: &str, peer_id: &str| GossipTraceEntry {
+		timestamp: 0,
+		message_id: id.to_string(),
+		topic: topic.to_string(),
+		peer_id: peer_id.to_string(),
+		source: None,
+		size: 10,
+		verdict: "Accept".to_string(),
+		handler_latency_us: 5,
+	};
+	let ids = |entries: Vec<GossipTraceEntry>| -> Vec<String> {
+		entries.into_iter().map(|e| e.message_id).collect()
+	};
+
+	let mut trace = GossipTrace::new(3);
+	trace.push(entry("1", "a", "p1"));
+	trace.push(entry("2", "b", "p1"));
+	trace.push(entry("3", "a", "p2"));
+	assert_eq!(ids(trace.query(None, None, None)), vec!["3", "2", "1"]);
+	// Trace is bounded, oldest message is dropped
+	trace.push(entry("4", "a", "p1"));
+	assert_eq!(ids(trace.query(None, None, None)), vec!["4", "3", "2"]);
+	assert_eq!(ids(trace.query(Some("a"), None, None)), vec!["4", "3"]);
+	assert_eq!(ids(trace.query(Some("a"), Some("p1"), None)), vec!["4"]);
+	assert_eq!(ids(trace.query(None, None, Some(1))), vec!["4"]);
+}
+
+#[test]
+fn test_forward_queue() {
+	// 1000 bytes per second, 5 seconds starvation limit
+	let mut queue: ForwardQueue<u32> = ForwardQueue::new(3, 1000, 1, 5_000, 0);
+	assert!(queue.is_empty());
+
+	assert_eq!(queue.push(1, 600, 10, 0), None);
+	assert_eq!(queue.push(2, 600, 50, 0), None);
+	assert_eq!(queue.push(3, 600, 20, 0), None);
+	// Queue is full, the lowest fee message is dropped
+	assert_eq!(queue.push(4, 600, 15, 0), Some(1));
+	assert_eq!(queue.len(), 3);
+
+	// One second of budget, the highest fee goes first. Budget can be overdrawn by one message.
+	assert_eq!(queue.pop_ready(0), vec![2, 3]);
+	// Budget is negative, nothing is forwarded until it is refilled
+	assert_eq!(queue.pop_ready(100), Vec::<u32>::new());
+
+	// Low fee message is starving and goes before the higher fee one
+	assert_eq!(queue.push(5, 600, 100, 5_000), None);
+	assert_eq!(queue.pop_ready(6_000), vec![4, 5]);
+	assert!(queue.is_empty());
+
+	// Expired messages are removed
+	assert_eq!(queue.push(6, 100, 10, 10_000), None);
+	assert_eq!(queue.push(7, 100, 10, 20_000), None);
+	assert_eq!(queue.expire(12_000, 22_000), vec![6]);
+	assert_eq!(queue.pop_ready(22_000), vec![7]);
+
+	// Every message is sent to the mesh peers, the budget is spent for each of them
+	let mut queue: ForwardQueue<u32> = ForwardQueue::new(3, 1000, 3, 5_000, 0);
+	assert_eq!(queue.push(1, 400, 10, 0), None);
+	assert_eq!(queue.push(2, 400, 10, 0), None);
+	assert_eq!(queue.pop_ready(0), vec![1]);
+	assert_eq!(queue.pop_ready(1_200), vec![2]);
+}
+
+#[test]
+fn test_forward_queue_timing() {
+	// Messages are kept at the cache for 5 heartbeats of 2 seconds
+	let config = gossipsub::GossipsubConfigBuilder::default()
+		.heartbeat_interval(Duration::from_secs(2))
+		.history_length(5)
+		.build()
+		.unwrap();
+	assert_eq!(forward_queue_timing(&config), (4_000, 8_000));
+}
+
+#[test]
+fn test_relay_deny_list() {
+	let deny_list = RelayDenyList {
+		topics: vec!["SwapMarketplace".to_string()],
+		message_id_patterns: vec![
+			"abc*".to_string(),
+			"*ff*00".to_string(),
+			"exact".to_string(),
+		],
+	};
+	assert!(deny_list.is_denied("SwapMarketplace", "123"));
+	assert!(!deny_list.is_denied("Other", "123"));
+	assert!(deny_list.is_denied("Other", "abc"));
+	assert!(deny_list.is_denied("Other", "abcdef"));
+	assert!(!deny_list.is_denied("Other", "xabc"));
+	assert!(deny_list.is_denied("Other", "01ff2300"));
+	assert!(!deny_list.is_denied("Other", "01ff23"));
+	assert!(!deny_list.is_denied("Other", "ff0"));
+	assert!(deny_list.is_denied("Other", "exact"));
+	assert!(!deny_list.is_denied("Other", "exactly"));
+	assert!(!RelayDenyList::default().is_denied("SwapMarketplace", "abc"));
+}
+
+#[test]
+fn test_gossip_handler_policy() {
+	let sender = String::from("sender");
+	let failing = gossip_handler(|_sender, _topic, data, _fee| {
+		if data.is_empty() {
+			Err(Error::Libp2pError("empty message".into()))
+		} else {
+			Ok(())
+		}
+	});
+
+	// Handler can capture its state
+	let calls = Arc::new(AtomicU32::new(0));
+	let handler_calls = calls.clone();
+	add_topic_to_libp2p(
+		"policy_log",
+		gossip_handler(move |_sender, _topic, _data, _fee| {
+			handler_calls.fetch_add(1, Ordering::Relaxed);
+			Err(Error::Libp2pError("not processed".into()))
+		}),
+		HandlerErrorPolicy::Log,
+	);
+	let topic = Topic::new("policy_log").hash();
+	let res = call_topic_handler(&sender, &topic, vec![1], 0);
+	assert!(matches!(res, MessageAcceptance::Ignore));
+	assert_eq!(calls.load(Ordering::Relaxed), 1);
+
+	add_topic_to_libp2p(
+		"policy_penalize",
+		failing.clone(),
+		HandlerErrorPolicy::Penalize,
+	);
+	let topic = Topic::new("policy_penalize").hash();
+	let res = call_topic_handler(&sender, &topic, vec![], 0);
+	assert!(matches!(res, MessageAcceptance::Reject));
+	let res = call_topic_handler(&sender, &topic, vec![1], 0);
+	assert!(matches!(res, MessageAcceptance::Accept));
+
+	// Failures counter is reset by the processed message
+	add_topic_to_libp2p(
+		"policy_unsubscribe",
+		failing,
+		HandlerErrorPolicy::Unsubscribe(2),
+	);
+	let topic = Topic::new("policy_unsubscribe").hash();
+	MESSAGING_TOPICS.write().insert(
+		topic.clone(),
+		(
+			"policy_unsubscribe".into(),
+			Topic::new("policy_unsubscribe"),
+			0,
+		),
+	);
+	call_topic_handler(&sender, &topic, vec![], 0);
+	call_topic_handler(&sender, &topic, vec![1], 0);
+	call_topic_handler(&sender, &topic, vec![], 0);
+	assert!(LIBP2P_MESSAGE_HANDLERS.read().contains_key(&topic));
+	call_topic_handler(&sender, &topic, vec![], 0);
+	assert!(!LIBP2P_MESSAGE_HANDLERS.read().contains_key(&topic));
+	assert!(!MESSAGING_TOPICS.read().contains_key(&topic));
+
+	remove_topic_from_libp2p("policy_log");
+	remove_topic_from_libp2p("policy_penalize");
+}
+
+#[test]
+fn test_plugin_topics() {
+	struct MemTopicStore(RwLock<Vec<GossipTopicData>>);
+	impl Libp2pTopicStore for MemTopicStore {
+		fn save_topic(&self, topic: &GossipTopicData) {
+			self.delete_topic(&topic.topic);
+			self.0.write().push(topic.clone());
+		}
+		fn delete_topic(&self, topic: &str) {
+			self.0.write().retain(|t| t.topic != topic);
+		}
+		fn load_topics(&self) -> Vec<GossipTopicData> {
+			self.0.read().clone()
+		}
+	}
+
+	let store = Arc::new(MemTopicStore(RwLock::new(vec![GossipTopicData {
+		topic: "plugin_stored".to_string(),
+		min_fee: 0,
+		handler: "test_plugin".to_string(),
+	}])));
+	set_topic_store(store.clone());
+	let stored = Topic::new("plugin_stored").hash();
+	let added = Topic::new("plugin_added").hash();
+
+	// Stored topic waits for its handler
+	assert!(!LIBP2P_MESSAGE_HANDLERS.read().contains_key(&stored));
+	assert!(!add_plugin_topic("plugin_added", "test_plugin"));
+	assert_eq!(store.load_topics().len(), 1);
+
+	let calls = Arc::new(AtomicU32::new(0));
+	let handler_calls = calls.clone();
+	register_topic_handler(
+		"test_plugin",
+		gossip_handler(move |_sender, _topic, _data, _fee| {
+			handler_calls.fetch_add(1, Ordering::Relaxed);
+			Ok(())
+		}),
+		HandlerErrorPolicy::Log,
+	);
+	assert!(LIBP2P_MESSAGE_HANDLERS.read().contains_key(&stored));
+	let res = call_topic_handler(&String::from("sender"), &stored, vec![1], 0);
+	assert!(matches!(res, MessageAcceptance::Accept));
+	assert_eq!(calls.load(Ordering::Relaxed), 1);
+
+	assert!(add_plugin_topic("plugin_added", "test_plugin"));
+	assert!(LIBP2P_MESSAGE_HANDLERS.read().contains_key(&added));
+	assert!(store
+		.load_topics()
+		.iter()
+		.any(|t| t.topic == "plugin_added" && t.handler == "test_plugin"));
+
+	remove_plugin_topic("plugin_stored");
+	remove_plugin_topic("plugin_added");
+	assert!(!LIBP2P_MESSAGE_HANDLERS.read().contains_key(&stored));
+	assert!(!LIBP2P_MESSAGE_HANDLERS.read().contains_key(&added));
+	assert!(store.load_topics().is_empty());
+}
+
+#[test]
+fn test_integrity_rate_limit_retry_after() {
+	let excess = Commitment::from_vec(vec![7; PEDERSEN_COMMITMENT_SIZE]);
+	let mut ser = SimplePushSerializer::new(get_message_version());
+	ser.push_vec(&excess.0);
+	let message = ser.to_vec();
+
+	let mut requests_cash = HashMap::new();
+	assert_eq!(
+		integrity_rate_limit_retry_after(&message, &requests_cash, 1000),
+		None
+	);
+
+	// Messages every 5 seconds, the limit is an average period of 15 seconds
+	let calls: VecDeque<i64> = (0..INTEGRITY_CALL_HISTORY_LEN_LIMIT as i64)
+		.map(|i| 1000 + i * 5)
+		.collect();
+	let last = *calls.back().unwrap();
+	requests_cash.insert(excess.clone(), calls);
+	let retry_after = integrity_rate_limit_retry_after(&message, &requests_cash, last).unwrap();
+	assert_eq!(
+		retry_after,
+		1005 + INTEGRITY_CALL_MAX_PERIOD * (INTEGRITY_CALL_HISTORY_LEN_LIMIT as i64 - 1) - last
+	);
+
+	// Slow sender is not limited
+	let calls: VecDeque<i64> = (0..INTEGRITY_CALL_HISTORY_LEN_LIMIT as i64)
+		.map(|i| 1000 + i * INTEGRITY_CALL_MAX_PERIOD)
+		.collect();
+	requests_cash.insert(excess, calls);
+	assert_eq!(
+		integrity_rate_limit_retry_after(&message, &requests_cash, 2000),
+		None
+	);
+}
+
+#[test]
+fn test_purge_integrity_requests() {
+	let valid = Commitment::from_vec(vec![1; PEDERSEN_COMMITMENT_SIZE]);
+	let reverted = Commitment::from_vec(vec![2; PEDERSEN_COMMITMENT_SIZE]);
+	let unknown = Commitment::from_vec(vec![3; PEDERSEN_COMMITMENT_SIZE]);
+	let kernel_validation_fn = |excess: &Commitment| -> Result<Option<TxKernel>, Error> {
+		if *excess == valid {
+			Ok(Some(TxKernel::empty()))
+		} else if *excess == reverted {
+			Ok(None)
+		} else {
+			Err(Error::Libp2pError("chain is not available".to_string()))
+		}
+	};
+
+	let mut requests_cash: HashMap<Commitment, VecDeque<i64>> = HashMap::new();
+	for excess in &[&valid, &reverted, &unknown] {
+		requests_cash.insert((*excess).clone(), vec![1000].into_iter().collect());
+	}
+	let requests_cash = Mutex::new(requests_cash);
+	let purged = purge_integrity_requests(&requests_cash, &kernel_validation_fn);
+	assert_eq!(purged, vec![reverted]);
+	let requests_cash = requests_cash.lock();
+	// Kernels that can't be checked are kept
+	assert!(requests_cash.contains_key(&valid));
+	assert!(requests_cash.contains_key(&unknown));
+	assert_eq!(requests_cash.len(), 2);
+}
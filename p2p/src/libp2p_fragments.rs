@@ -0,0 +1,474 @@
+// Copyright 2021 The MWC Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Fragmentation of the gossip payloads that don't fit into a single gossip message, like
+//! slatepacks or proofs. The payload is split into the ordered fragments, every fragment is
+//! published as a regular integrity message. Fragments carry the payload ID (hash of the payload)
+//! and the manifest (fragments count, payload size and timestamp) that is signed by the sender
+//! onion address key, so nobody else can inject the fragments into the payload.
+//! Every fragment pays the integrity message rate limit, a payload is limited to MAX_FRAGMENTS,
+//! that fits into the allowed burst of the messages for a single integrity kernel.
+
+use crate::libp2p_connection::{get_message_version, gossip_handler, GossipHandler};
+use crate::types::Error;
+use grin_core::core::hash::{Hash, Hashed};
+use grin_util::{Mutex, OnionV3Address, OnionV3AddressError};
+use libp2p::core::{SimplePopSerializer, SimplePushSerializer};
+use libp2p::gossipsub::TopicHash;
+use libp2p::identity::Keypair;
+use std::collections::HashMap;
+use std::convert::TryInto;
+use std::sync::Arc;
+
+/// Max number of fragments of the payload. Fragments are published at once, so the number
+/// is below the integrity message history limit.
+pub const MAX_FRAGMENTS: u16 = 8;
+
+/// Space that is needed for the integrity message and the fragment headers, bytes
+pub const FRAGMENT_OVERHEAD: usize = 512;
+
+/// How long the fragments of the incomplete payload are kept, seconds. Manifests that are
+/// older are rejected.
+pub const FRAGMENT_TIMEOUT: i64 = 120;
+
+/// Max number of the incomplete payloads of a single sender that are reassembled at the same
+/// time. A sender can't evict the payloads of the others.
+pub const MAX_PENDING_PAYLOADS: usize = 4;
+
+/// Serializer version of the fragment, the regular messages are never confused with fragments
+fn get_fragment_version() -> u16 {
+	get_message_version() + 2
+}
+
+/// Check if the gossip message data is a fragment
+pub fn is_fragment(data: &Vec<u8>) -> bool {
+	data.len() > 2 && SimplePopSerializer::new(data).version == get_fragment_version()
+}
+
+/// Max payload bytes in one fragment for the gossip max message size
+pub fn fragment_data_size(max_message_size: usize) -> usize {
+	max_message_size.saturating_sub(FRAGMENT_OVERHEAD)
+}
+
+// Data that is signed by the sender. Topic is included, the fragments can't be replayed
+// into another topic.
+fn manifest_data(
+	topic: &TopicHash,
+	payload_id: &Hash,
+	count: u16,
+	size: u64,
+	timestamp: i64,
+) -> Vec<u8> {
+	let mut data = b"fragment_manifest".to_vec();
+	data.extend_from_slice(topic.as_str().as_bytes());
+	data.extend_from_slice(payload_id.as_bytes());
+	data.extend_from_slice(&count.to_be_bytes());
+	data.extend_from_slice(&size.to_be_bytes());
+	data.extend_from_slice(&timestamp.to_be_bytes());
+	data
+}
+
+/// Split the payload into the fragments, every fragment is the data of a separate integrity
+/// message. keys - ed25519 keys of the sender onion address.
+pub fn build_fragments(
+	keys: &Keypair,
+	topic: &TopicHash,
+	payload: &[u8],
+	fragment_size: usize,
+	timestamp: i64,
+) -> Result<Vec<Vec<u8>>, Error> {
+	if fragment_size == 0 || payload.is_empty() {
+		return Err(Error::Libp2pError("Nothing to fragment".to_string()));
+	}
+	let count = (payload.len() + fragment_size - 1) / fragment_size;
+	if count > MAX_FRAGMENTS as usize {
+		return Err(Error::Libp2pError(format!(
+			"Payload of {} bytes needs {} fragments, the limit is {}",
+			payload.len(),
+			count,
+			MAX_FRAGMENTS
+		)));
+	}
+	let count = count as u16;
+	let size = payload.len() as u64;
+	let payload_id = payload.to_vec().hash();
+	let signature = keys
+		.sign(&manifest_data(topic, &payload_id, count, size, timestamp))
+		.map_err(|e| Error::Libp2pError(format!("Unable to sign fragments manifest, {}", e)))?;
+
+	Ok(payload
+		.chunks(fragment_size)
+		.enumerate()
+		.map(|(index, chunk)| {
+			let mut ser = SimplePushSerializer::new(get_fragment_version());
+			ser.push_vec(payload_id.as_bytes());
+			ser.push_u16(index as u16);
+			ser.push_u16(count);
+			ser.push_vec(&size.to_be_bytes());
+			ser.push_vec(&timestamp.to_be_bytes());
+			ser.push_vec(&signature);
+			ser.push_vec(chunk);
+			ser.to_vec()
+		})
+		.collect())
+}
+
+// Fragment as it is read from the message
+struct Fragment {
+	payload_id: Hash,
+	index: u16,
+	count: u16,
+	size: u64,
+	timestamp: i64,
+	signature: Vec<u8>,
+	data: Vec<u8>,
+}
+
+fn read_fragment(data: &Vec<u8>) -> Result<Fragment, Error> {
+	let mut ser = SimplePopSerializer::new(data);
+	if ser.version != get_fragment_version() {
+		return Err(Error::Libp2pError("Not a fragment".to_string()));
+	}
+	let payload_id = ser.pop_vec();
+	let index = ser.pop_u16();
+	let count = ser.pop_u16();
+	let size: [u8; 8] = ser.pop_vec()[..]
+		.try_into()
+		.map_err(|_| Error::Libp2pError("Invalid fragment payload size".to_string()))?;
+	let timestamp: [u8; 8] = ser.pop_vec()[..]
+		.try_into()
+		.map_err(|_| Error::Libp2pError("Invalid fragment timestamp".to_string()))?;
+	let signature = ser.pop_vec();
+	let data = ser.pop_vec();
+	if payload_id.len() != 32 {
+		return Err(Error::Libp2pError(
+			"Invalid fragment payload ID".to_string(),
+		));
+	}
+	Ok(Fragment {
+		payload_id: Hash::from_vec(&payload_id),
+		index,
+		count,
+		size: u64::from_be_bytes(size),
+		timestamp: i64::from_be_bytes(timestamp),
+		signature,
+		data,
+	})
+}
+
+// Ed25519 key of the sender onion address
+fn sender_public_key(sender: &String) -> Result<libp2p::identity::PublicKey, Error> {
+	let res: Result<OnionV3Address, OnionV3AddressError> = sender.as_str().try_into();
+	let pk = res
+		.and_then(|addr| addr.to_ed25519())
+		.map_err(|e| Error::Libp2pError(format!("Invalid sender address {}, {}", sender, e)))?;
+	Ok(libp2p::identity::PublicKey::Ed25519(
+		libp2p::identity::ed25519::PublicKey(pk),
+	))
+}
+
+struct PendingPayload {
+	count: u16,
+	size: u64,
+	timestamp: i64,
+	signature: Vec<u8>,
+	fragments: Vec<Option<Vec<u8>>>,
+	received: usize,
+	started: i64,
+}
+
+/// Collects the fragments until the payload is complete
+pub struct FragmentReassembler {
+	/// (sender, payload ID) => received fragments
+	pending: HashMap<(String, Hash), PendingPayload>,
+	/// How long the incomplete payload is kept, seconds
+	timeout: i64,
+	/// Max number of the incomplete payloads per sender
+	max_pending: usize,
+}
+
+impl FragmentReassembler {
+	/// Create reassembler
+	pub fn new(timeout: i64, max_pending: usize) -> Self {
+		FragmentReassembler {
+			pending: HashMap::new(),
+			timeout,
+			max_pending,
+		}
+	}
+
+	/// Add the fragment from the sender. Returns the payload when all its fragments are received.
+	/// Error if the fragment is invalid, the sender of such fragments should be penalized.
+	pub fn add_fragment(
+		&mut self,
+		sender: &String,
+		topic: &TopicHash,
+		data: &Vec<u8>,
+		now: i64,
+	) -> Result<Option<Vec<u8>>, Error> {
+		let fragment = read_fragment(data)?;
+		if fragment.count == 0 || fragment.count > MAX_FRAGMENTS || fragment.index >= fragment.count
+		{
+			return Err(Error::Libp2pError(format!(
+				"Invalid fragment {} of {}",
+				fragment.index, fragment.count
+			)));
+		}
+		if (now - fragment.timestamp).abs() > self.timeout {
+			return Err(Error::Libp2pError(
+				"Fragment manifest is expired".to_string(),
+			));
+		}
+
+		let key = (sender.clone(), fragment.payload_id);
+		match self.pending.get(&key) {
+			Some(pending) => {
+				// The manifest was verified with the first fragment
+				if pending.signature != fragment.signature
+					|| pending.count != fragment.count
+					|| pending.size != fragment.size
+					|| pending.timestamp != fragment.timestamp
+				{
+					return Err(Error::Libp2pError(format!(
+						"Fragment manifest doesn't match for payload {}",
+						fragment.payload_id
+					)));
+				}
+			}
+			None => {
+				let manifest = manifest_data(
+					topic,
+					&fragment.payload_id,
+					fragment.count,
+					fragment.size,
+					fragment.timestamp,
+				);
+				if !sender_public_key(sender)?.verify(&manifest, &fragment.signature) {
+					return Err(Error::Libp2pError(format!(
+						"Invalid fragment manifest signature from {}",
+						sender
+					)));
+				}
+				let sender_pending = self.pending.keys().filter(|(s, _)| s == sender).count();
+				if sender_pending >= self.max_pending {
+					// Dropping the oldest payload of this sender
+					if let Some(oldest) = self
+						.pending
+						.iter()
+						.filter(|((s, _), _)| s == sender)
+						.min_by_key(|(_, p)| p.started)
+						.map(|(k, _)| k.clone())
+					{
+						self.pending.remove(&oldest);
+					}
+				}
+				self.pending.insert(
+					key.clone(),
+					PendingPayload {
+						count: fragment.count,
+						size: fragment.size,
+						timestamp: fragment.timestamp,
+						signature: fragment.signature.clone(),
+						fragments: vec![None; fragment.count as usize],
+						received: 0,
+						started: now,
+					},
+				);
+			}
+		}
+
+		let pending = self.pending.get_mut(&key).unwrap();
+		let slot = &mut pending.fragments[fragment.index as usize];
+		if slot.is_none() {
+			*slot = Some(fragment.data);
+			pending.received += 1;
+		}
+		if pending.received < pending.count as usize {
+			return Ok(None);
+		}
+
+		let pending = self.pending.remove(&key).unwrap();
+		let payload: Vec<u8> = pending.fragments.into_iter().flatten().flatten().collect();
+		if payload.len() as u64 != pending.size || payload.hash() != key.1 {
+			return Err(Error::Libp2pError(format!(
+				"Reassembled payload {} doesn't match its ID",
+				key.1
+			)));
+		}
+		Ok(Some(payload))
+	}
+
+	/// Drop the incomplete payloads that are waiting longer than the timeout
+	pub fn cleanup(&mut self, now: i64) {
+		let timeout = self.timeout;
+		self.pending.retain(|_, p| now - p.started <= timeout);
+	}
+
+	/// Number of the incomplete payloads of all senders
+	pub fn pending_count(&self) -> usize {
+		self.pending.len()
+	}
+}
+
+/// Gossip handler that is called with the reassembled payloads. Messages that are not
+/// fragments are passed to the handler as they are. Invalid fragments are reported as the
+/// handler errors, so the topic error policy is applied to them.
+pub fn fragmented_gossip_handler<F>(handler: F) -> GossipHandler
+where
+	F: Fn(&String, &TopicHash, Vec<u8>, u64) -> Result<(), Error> + Send + Sync + 'static,
+{
+	let reassembler = Arc::new(Mutex::new(FragmentReassembler::new(
+		FRAGMENT_TIMEOUT,
+		MAX_PENDING_PAYLOADS,
+	)));
+	gossip_handler(move |sender, topic, data, fee| {
+		if !is_fragment(&data) {
+			return (handler)(sender, topic, data, fee);
+		}
+		let now = chrono::Utc::now().timestamp();
+		let payload = {
+			let mut reassembler = reassembler.lock();
+			reassembler.cleanup(now);
+			reassembler.add_fragment(sender, topic, &data, now)?
+		};
+		match payload {
+			Some(payload) => (handler)(sender, topic, payload, fee),
+			None => Ok(()),
+		}
+	})
+}
+
+#[test]
+fn test_fragments_reassembly() {
+	use libp2p::gossipsub::IdentTopic as Topic;
+	use libp2p::PeerId;
+
+	let keys = Keypair::ed25519_from_secret(&mut [1u8; 32]).unwrap();
+	let sender = PeerId::from_public_key(keys.public())
+		.as_onion_address()
+		.unwrap();
+	let other_keys = Keypair::ed25519_from_secret(&mut [2u8; 32]).unwrap();
+	let topic = Topic::new("fragments").hash();
+	let now = 1_600_000_000;
+
+	let payload: Vec<u8> = (0..2500u32).map(|i| (i % 251) as u8).collect();
+	let fragments = build_fragments(&keys, &topic, &payload, 1000, now).unwrap();
+	assert_eq!(fragments.len(), 3);
+	assert!(fragments.iter().all(|f| is_fragment(f)));
+	assert!(!is_fragment(&payload));
+
+	// Out of order and duplicated fragments
+	let mut reassembler = FragmentReassembler::new(FRAGMENT_TIMEOUT, 10);
+	assert_eq!(
+		reassembler
+			.add_fragment(&sender, &topic, &fragments[2], now)
+			.unwrap(),
+		None
+	);
+	assert_eq!(
+		reassembler
+			.add_fragment(&sender, &topic, &fragments[0], now)
+			.unwrap(),
+		None
+	);
+	assert_eq!(
+		reassembler
+			.add_fragment(&sender, &topic, &fragments[0], now)
+			.unwrap(),
+		None
+	);
+	assert_eq!(reassembler.pending_count(), 1);
+	assert_eq!(
+		reassembler
+			.add_fragment(&sender, &topic, &fragments[1], now)
+			.unwrap(),
+		Some(payload.clone())
+	);
+	assert_eq!(reassembler.pending_count(), 0);
+
+	// Fragments signed by somebody else, or replayed into another topic
+	let forged = build_fragments(&other_keys, &topic, &payload, 1000, now).unwrap();
+	assert!(reassembler
+		.add_fragment(&sender, &topic, &forged[0], now)
+		.is_err());
+	let other_topic = Topic::new("other").hash();
+	assert!(reassembler
+		.add_fragment(&sender, &other_topic, &fragments[0], now)
+		.is_err());
+	// Expired manifest
+	assert!(reassembler
+		.add_fragment(&sender, &topic, &fragments[0], now + FRAGMENT_TIMEOUT + 1)
+		.is_err());
+
+	// Incomplete payloads are dropped after the timeout
+	reassembler
+		.add_fragment(&sender, &topic, &fragments[0], now)
+		.unwrap();
+	reassembler.cleanup(now + FRAGMENT_TIMEOUT);
+	assert_eq!(reassembler.pending_count(), 1);
+	reassembler.cleanup(now + FRAGMENT_TIMEOUT + 1);
+	assert_eq!(reassembler.pending_count(), 0);
+
+	// Later fragment with the manifest timestamp changed
+	let f = read_fragment(&fragments[1]).unwrap();
+	let mut ser = SimplePushSerializer::new(get_fragment_version());
+	ser.push_vec(f.payload_id.as_bytes());
+	ser.push_u16(f.index);
+	ser.push_u16(f.count);
+	ser.push_vec(&f.size.to_be_bytes());
+	ser.push_vec(&(f.timestamp + 1).to_be_bytes());
+	ser.push_vec(&f.signature);
+	ser.push_vec(&f.data);
+	reassembler
+		.add_fragment(&sender, &topic, &fragments[0], now)
+		.unwrap();
+	assert!(reassembler
+		.add_fragment(&sender, &topic, &ser.to_vec(), now)
+		.is_err());
+	reassembler.cleanup(now + FRAGMENT_TIMEOUT + 1);
+
+	// Pending limit is per sender, the payloads of the others are kept
+	let other_sender = PeerId::from_public_key(other_keys.public())
+		.as_onion_address()
+		.unwrap();
+	let mut reassembler = FragmentReassembler::new(FRAGMENT_TIMEOUT, 2);
+	let other_fragments = build_fragments(&other_keys, &topic, &payload, 1000, now).unwrap();
+	reassembler
+		.add_fragment(&other_sender, &topic, &other_fragments[0], now)
+		.unwrap();
+	for i in 0..3u8 {
+		let p = vec![i; 2500];
+		let f = build_fragments(&keys, &topic, &p, 1000, now).unwrap();
+		reassembler
+			.add_fragment(&sender, &topic, &f[0], now + i as i64)
+			.unwrap();
+	}
+	assert_eq!(reassembler.pending_count(), 3);
+	assert_eq!(
+		reassembler
+			.add_fragment(&other_sender, &topic, &other_fragments[1], now)
+			.unwrap(),
+		None
+	);
+	assert_eq!(
+		reassembler
+			.add_fragment(&other_sender, &topic, &other_fragments[2], now)
+			.unwrap(),
+		Some(payload.clone())
+	);
+
+	// Too large payload
+	let large = vec![0u8; 1000 * MAX_FRAGMENTS as usize + 1];
+	assert!(build_fragments(&keys, &topic, &large, 1000, now).is_err());
+}
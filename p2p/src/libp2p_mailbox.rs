@@ -0,0 +1,649 @@
+// Copyright 2021 The MWC Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Store-and-forward mailbox for the direct messages between the wallets. A message is sealed
+//! for the recipient onion address key (X25519 + XChaCha20-Poly1305), the content is readable
+//! by the recipient only. The metadata is not private: the recipient and the sender addresses,
+//! the size and the time of every message are visible to all nodes of the Mailbox gossip topic.
+//! Messages are posted as integrity messages into the Mailbox gossip topic, every node that runs
+//! the mailbox keeps them until the recipient fetches them with the foreign API. Fetching is
+//! signed by the recipient onion address key, so the API doesn't list the messages of others.
+//! The storage is bounded per recipient, when the quota is exhausted the messages that paid the
+//! lower integrity fee per byte are dropped first.
+
+use crate::libp2p_connection::{
+	self, check_integrity_message, get_message_version, gossip_handler, read_message_data,
+	GossipHandler, HandlerErrorPolicy, INTEGRITY_MESSAGE_CHECKS,
+};
+use crate::types::Error;
+use chacha20poly1305::aead::generic_array::GenericArray;
+use chacha20poly1305::aead::{Aead, NewAead};
+use chacha20poly1305::XChaCha20Poly1305;
+use chrono::Utc;
+use curve25519_dalek::constants::X25519_BASEPOINT;
+use curve25519_dalek::edwards::CompressedEdwardsY;
+use curve25519_dalek::montgomery::MontgomeryPoint;
+use curve25519_dalek::scalar::Scalar;
+use ed25519_dalek::{
+	ExpandedSecretKey, PublicKey as DalekPublicKey, SecretKey as DalekSecretKey,
+	Signature as DalekSignature, Verifier,
+};
+use grin_core::core::hash::{Hash, Hashed};
+use grin_core::core::TxKernel;
+use grin_util::secp::pedersen::Commitment;
+use grin_util::{from_hex, Mutex, OnionV3Address, OnionV3AddressError, ToHex};
+use libp2p::core::{SimplePopSerializer, SimplePushSerializer};
+use libp2p::gossipsub::IdentTopic as Topic;
+use libp2p::PeerId;
+use rand::{thread_rng, Rng};
+use schemars::JsonSchema;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::convert::TryInto;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+/// Gossip topic of the mailbox messages
+pub const MAILBOX_TOPIC: &str = "Mailbox";
+
+/// Max size of the sealed message, bytes
+pub const MAILBOX_MESSAGE_MAX_SIZE: usize = 32 * 1024;
+
+/// Max number of the stored messages for a single recipient
+pub const MAILBOX_RECIPIENT_MAX_MESSAGES: usize = 50;
+
+/// Max size of the stored messages for a single recipient, bytes
+pub const MAILBOX_RECIPIENT_MAX_BYTES: usize = 256 * 1024;
+
+/// Max size of all stored messages, bytes
+pub const MAILBOX_MAX_BYTES: usize = 64 * 1024 * 1024;
+
+/// How long the messages are kept, seconds. It matches the integrity fee validity period.
+pub const MAILBOX_MESSAGE_TTL: i64 = 24 * 3600;
+
+/// Fetch request is accepted within that many seconds of its signing time (both directions)
+pub const MAILBOX_FETCH_REQUEST_TTL: i64 = 5 * 60;
+
+const NONCE_SIZE: usize = 24;
+
+lazy_static! {
+	static ref MAILBOX: Mutex<Mailbox> = Mutex::new(Mailbox::new());
+}
+
+static MAILBOX_ENABLED: AtomicBool = AtomicBool::new(false);
+
+/// Serializer version of the sealed message, it is never confused with other gossip data
+fn get_mailbox_version() -> u16 {
+	get_message_version() + 3
+}
+
+/// Message that is stored for the recipient
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct MailboxMessage {
+	/// Hash of the sealed message, hex
+	pub id: String,
+	/// Sender onion address, as it was signed by the integrity kernel
+	pub sender: String,
+	/// Recipient onion address
+	pub recipient: String,
+	/// Paid integrity fee
+	pub fee: u64,
+	/// Unix timestamp when the message was received by this node
+	pub timestamp: i64,
+	/// Sealed message, hex. Open it with the recipient onion address key.
+	pub message: String,
+}
+
+impl MailboxMessage {
+	/// Build the stored message from the sealed message that was received from the sender
+	pub fn from_sealed(
+		sender: &str,
+		fee: u64,
+		sealed: &Vec<u8>,
+		timestamp: i64,
+	) -> Result<MailboxMessage, Error> {
+		if sealed.len() > MAILBOX_MESSAGE_MAX_SIZE {
+			return Err(Error::Libp2pError(format!(
+				"Mailbox message size {} is over the limit {}",
+				sealed.len(),
+				MAILBOX_MESSAGE_MAX_SIZE
+			)));
+		}
+		let recipient = read_sealed(sealed)?.recipient;
+		Ok(MailboxMessage {
+			id: sealed.hash().to_hex(),
+			sender: sender.to_string(),
+			recipient: PeerId::onion_v3_from_pubkey(&recipient),
+			fee,
+			timestamp,
+			message: sealed.to_hex(),
+		})
+	}
+
+	fn size(&self) -> usize {
+		self.message.len() / 2
+	}
+}
+
+/// Mailbox usage
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct MailboxStats {
+	/// Number of the recipients with the stored messages
+	pub recipients: usize,
+	/// Number of the stored messages
+	pub messages: usize,
+	/// Size of the stored messages, bytes
+	pub bytes: usize,
+	/// Messages that were dropped because the recipient quota was taken by the higher fee messages
+	pub evicted: u64,
+	/// Messages that were rejected because of the quota
+	pub rejected: u64,
+}
+
+#[derive(Default)]
+struct RecipientBox {
+	messages: VecDeque<MailboxMessage>,
+	bytes: usize,
+}
+
+/// Stored messages by recipient
+pub struct Mailbox {
+	boxes: HashMap<String, RecipientBox>,
+	ids: HashSet<String>,
+	stats: MailboxStats,
+}
+
+// true if a pays more per byte than b
+fn pays_more(a_fee: u64, a_size: usize, b_fee: u64, b_size: usize) -> bool {
+	a_fee as u128 * b_size as u128 > b_fee as u128 * a_size as u128
+}
+
+impl Mailbox {
+	/// Create empty mailbox
+	pub fn new() -> Mailbox {
+		Mailbox {
+			boxes: HashMap::new(),
+			ids: HashSet::new(),
+			stats: MailboxStats::default(),
+		}
+	}
+
+	/// Store the message. Returns false if the message is already stored, error if there is
+	/// no room for it.
+	pub fn add(&mut self, message: MailboxMessage, now: i64) -> Result<bool, Error> {
+		self.cleanup(now);
+		if self.ids.contains(&message.id) {
+			return Ok(false);
+		}
+		let size = message.size();
+		if self.stats.bytes + size > MAILBOX_MAX_BYTES {
+			self.stats.rejected += 1;
+			return Err(Error::Libp2pError("Mailbox is full".to_string()));
+		}
+
+		let recipient_box = self.boxes.entry(message.recipient.clone()).or_default();
+		let mut evicted = vec![];
+		let mut bytes = recipient_box.bytes;
+		let mut count = recipient_box.messages.len();
+		// Selecting the cheapest messages that can be replaced by the new one
+		let mut candidates: Vec<&MailboxMessage> = recipient_box.messages.iter().collect();
+		candidates.sort_by(|a, b| {
+			(a.fee as u128 * b.size() as u128).cmp(&(b.fee as u128 * a.size() as u128))
+		});
+		for m in candidates {
+			if bytes + size <= MAILBOX_RECIPIENT_MAX_BYTES && count < MAILBOX_RECIPIENT_MAX_MESSAGES
+			{
+				break;
+			}
+			if !pays_more(message.fee, size, m.fee, m.size()) {
+				break;
+			}
+			bytes -= m.size();
+			count -= 1;
+			evicted.push(m.id.clone());
+		}
+		if bytes + size > MAILBOX_RECIPIENT_MAX_BYTES || count >= MAILBOX_RECIPIENT_MAX_MESSAGES {
+			self.stats.rejected += 1;
+			return Err(Error::Libp2pError(format!(
+				"Mailbox quota of {} is exhausted",
+				message.recipient
+			)));
+		}
+
+		let evicted_bytes = recipient_box.bytes - bytes;
+		recipient_box.messages.retain(|m| !evicted.contains(&m.id));
+		recipient_box.bytes = bytes + size;
+		recipient_box.messages.push_back(message.clone());
+		for id in &evicted {
+			self.ids.remove(id);
+		}
+		self.ids.insert(message.id);
+		self.stats.evicted += evicted.len() as u64;
+		self.stats.messages = self.ids.len();
+		self.stats.bytes = self.stats.bytes + size - evicted_bytes;
+		Ok(true)
+	}
+
+	/// Messages of the recipient that were received after since (unix time)
+	pub fn get(&mut self, recipient: &str, since: i64, now: i64) -> Vec<MailboxMessage> {
+		self.cleanup(now);
+		match self.boxes.get(recipient) {
+			Some(b) => b
+				.messages
+				.iter()
+				.filter(|m| m.timestamp > since)
+				.cloned()
+				.collect(),
+			None => vec![],
+		}
+	}
+
+	/// Drop the expired messages
+	pub fn cleanup(&mut self, now: i64) {
+		let expire_before = now - MAILBOX_MESSAGE_TTL;
+		let ids = &mut self.ids;
+		for b in self.boxes.values_mut() {
+			while let Some(m) = b.messages.front() {
+				if m.timestamp >= expire_before {
+					break;
+				}
+				b.bytes -= m.size();
+				ids.remove(&m.id);
+				b.messages.pop_front();
+			}
+		}
+		self.boxes.retain(|_, b| !b.messages.is_empty());
+		self.stats.recipients = self.boxes.len();
+		self.stats.messages = self.ids.len();
+		self.stats.bytes = self.boxes.values().map(|b| b.bytes).sum();
+	}
+
+	/// Current usage
+	pub fn stats(&self) -> MailboxStats {
+		MailboxStats {
+			recipients: self.boxes.len(),
+			..self.stats.clone()
+		}
+	}
+}
+
+// Sealed message as it is read from the gossip data
+struct SealedMessage {
+	recipient: DalekPublicKey,
+	ephemeral_pk: [u8; 32],
+	nonce: Vec<u8>,
+	ciphertext: Vec<u8>,
+}
+
+fn read_sealed(data: &Vec<u8>) -> Result<SealedMessage, Error> {
+	let mut ser = SimplePopSerializer::new(data);
+	if ser.version != get_mailbox_version() {
+		return Err(Error::Libp2pError("Not a mailbox message".to_string()));
+	}
+	let recipient = DalekPublicKey::from_bytes(&ser.pop_vec())
+		.map_err(|e| Error::Libp2pError(format!("Invalid mailbox recipient, {}", e)))?;
+	let ephemeral_pk: [u8; 32] = ser.pop_vec()[..]
+		.try_into()
+		.map_err(|_| Error::Libp2pError("Invalid mailbox ephemeral key".to_string()))?;
+	let nonce = ser.pop_vec();
+	let ciphertext = ser.pop_vec();
+	if nonce.len() != NONCE_SIZE {
+		return Err(Error::Libp2pError("Invalid mailbox nonce".to_string()));
+	}
+	Ok(SealedMessage {
+		recipient,
+		ephemeral_pk,
+		nonce,
+		ciphertext,
+	})
+}
+
+// X25519 point of the ed25519 public key
+fn montgomery_point(pk: &DalekPublicKey) -> Result<MontgomeryPoint, Error> {
+	CompressedEdwardsY(pk.to_bytes())
+		.decompress()
+		.map(|p| p.to_montgomery())
+		.ok_or_else(|| Error::Libp2pError("Invalid ed25519 public key".to_string()))
+}
+
+fn clamped_scalar(mut bytes: [u8; 32]) -> Scalar {
+	bytes[0] &= 248;
+	bytes[31] &= 127;
+	bytes[31] |= 64;
+	Scalar::from_bits(bytes)
+}
+
+// Symmetric key for the shared secret. Both public keys are included, the key is bound
+// to the recipient.
+fn sealing_key(
+	shared: &MontgomeryPoint,
+	ephemeral_pk: &[u8; 32],
+	recipient: &DalekPublicKey,
+) -> Hash {
+	let mut data = b"mwc_mailbox".to_vec();
+	data.extend_from_slice(shared.as_bytes());
+	data.extend_from_slice(ephemeral_pk);
+	data.extend_from_slice(recipient.as_bytes());
+	data.hash()
+}
+
+/// Seal the message for the recipient onion address key. The result is the data of the
+/// integrity message that is posted into the mailbox.
+pub fn seal_message(recipient: &DalekPublicKey, message: &[u8]) -> Result<Vec<u8>, Error> {
+	let recipient_point = montgomery_point(recipient)?;
+	let mut ephemeral_sk = [0u8; 32];
+	thread_rng().fill(&mut ephemeral_sk);
+	let ephemeral_sk = clamped_scalar(ephemeral_sk);
+	let ephemeral_pk = (&X25519_BASEPOINT * &ephemeral_sk).to_bytes();
+	let key = sealing_key(
+		&(&recipient_point * &ephemeral_sk),
+		&ephemeral_pk,
+		recipient,
+	);
+
+	let mut nonce = [0u8; NONCE_SIZE];
+	thread_rng().fill(&mut nonce);
+	let ciphertext = XChaCha20Poly1305::new(GenericArray::from_slice(key.as_bytes()))
+		.encrypt(GenericArray::from_slice(&nonce), message)
+		.map_err(|_| Error::Libp2pError("Unable to seal mailbox message".to_string()))?;
+
+	let mut ser = SimplePushSerializer::new(get_mailbox_version());
+	ser.push_vec(recipient.as_bytes());
+	ser.push_vec(&ephemeral_pk);
+	ser.push_vec(&nonce);
+	ser.push_vec(&ciphertext);
+	Ok(ser.to_vec())
+}
+
+/// Open the sealed message with the recipient onion address secret key
+pub fn open_message(recipient_sk: &DalekSecretKey, sealed: &Vec<u8>) -> Result<Vec<u8>, Error> {
+	let sealed = read_sealed(sealed)?;
+	if DalekPublicKey::from(recipient_sk) != sealed.recipient {
+		return Err(Error::Libp2pError(
+			"Mailbox message is sealed for another recipient".to_string(),
+		));
+	}
+	let expanded = ExpandedSecretKey::from(recipient_sk).to_bytes();
+	let mut scalar = [0u8; 32];
+	scalar.copy_from_slice(&expanded[..32]);
+	let shared = &MontgomeryPoint(sealed.ephemeral_pk) * &clamped_scalar(scalar);
+	let key = sealing_key(&shared, &sealed.ephemeral_pk, &sealed.recipient);
+	XChaCha20Poly1305::new(GenericArray::from_slice(key.as_bytes()))
+		.decrypt(
+			GenericArray::from_slice(&sealed.nonce),
+			&sealed.ciphertext[..],
+		)
+		.map_err(|_| Error::Libp2pError("Unable to open mailbox message".to_string()))
+}
+
+/// Start storing the mailbox messages from the gossip network
+pub fn start_mailbox() {
+	MAILBOX_ENABLED.store(true, Ordering::Relaxed);
+	libp2p_connection::add_topic_to_libp2p(
+		MAILBOX_TOPIC,
+		mailbox_gossip_handler(),
+		HandlerErrorPolicy::Log,
+	);
+}
+
+/// true if this node stores the mailbox messages
+pub fn is_mailbox_enabled() -> bool {
+	MAILBOX_ENABLED.load(Ordering::Relaxed)
+}
+
+/// Gossip handler of the Mailbox topic, stores the received messages
+pub fn mailbox_gossip_handler() -> GossipHandler {
+	gossip_handler(move |sender, _topic, data, fee| {
+		let now = Utc::now().timestamp();
+		let message = MailboxMessage::from_sealed(sender, fee, &data, now)?;
+		MAILBOX.lock().add(message, now)?;
+		Ok(())
+	})
+}
+
+/// Store the integrity message that is posted by the wallet with the API and publish it to
+/// the gossip network. The message is validated the same way as the gossip messages.
+pub fn post_message(
+	integrity_message: &Vec<u8>,
+	kernel_validation_fn: Arc<impl Fn(&Commitment) -> Result<Option<TxKernel>, Error>>,
+	fee_base: u64,
+) -> Result<MailboxMessage, Error> {
+	if !is_mailbox_enabled() {
+		return Err(Error::Libp2pError("Mailbox is not running".to_string()));
+	}
+	let now = Utc::now().timestamp();
+	// The calls history is shared with the gossip path, the API posts and the gossip
+	// messages count against the same rate limit of the kernel.
+	let check = check_integrity_message(
+		integrity_message,
+		kernel_validation_fn,
+		&mut libp2p_connection::INTEGRITY_REQUESTS.lock(),
+		fee_base,
+	)?;
+	let (fee, sender) = check.map_err(|(check, reason)| {
+		Error::Libp2pError(format!(
+			"Integrity check {} is failed, {}",
+			INTEGRITY_MESSAGE_CHECKS[check], reason
+		))
+	})?;
+
+	let data = read_message_data(integrity_message);
+	let message = MailboxMessage::from_sealed(&sender, fee, &data, now)?;
+	if MAILBOX.lock().add(message.clone(), now)? {
+		if libp2p_connection::publish_message(&Topic::new(MAILBOX_TOPIC), integrity_message.clone())
+			.is_none()
+		{
+			warn!(
+				"Mailbox message {} is stored, but not published",
+				message.id
+			);
+		}
+	}
+	Ok(message)
+}
+
+// Data that the recipient signs to fetch its messages
+fn fetch_request_data(recipient: &str, since: i64, timestamp: i64) -> Vec<u8> {
+	let mut data = b"mailbox_fetch".to_vec();
+	data.extend_from_slice(recipient.as_bytes());
+	data.extend_from_slice(&since.to_be_bytes());
+	data.extend_from_slice(&timestamp.to_be_bytes());
+	data
+}
+
+/// Sign the request to fetch the messages with the recipient onion address key. timestamp is
+/// the signing time (unix time), the request expires after MAILBOX_FETCH_REQUEST_TTL.
+/// Returns the signature hex for `get_messages`.
+pub fn sign_fetch_request(
+	recipient_sk: &DalekSecretKey,
+	since: Option<i64>,
+	timestamp: i64,
+) -> String {
+	let recipient_pk = DalekPublicKey::from(recipient_sk);
+	let recipient = PeerId::onion_v3_from_pubkey(&recipient_pk);
+	ExpandedSecretKey::from(recipient_sk)
+		.sign(
+			&fetch_request_data(&recipient, since.unwrap_or(0), timestamp),
+			&recipient_pk,
+		)
+		.to_bytes()
+		.to_hex()
+}
+
+/// Stored messages for the recipient onion address that were received after since (unix time).
+/// timestamp - signing time of the request, unix time. Expired requests are rejected, so the
+/// captured request can't be replayed later.
+/// signature - hex signature of the recipient onion address key, see `sign_fetch_request`.
+pub fn get_messages(
+	recipient: &str,
+	since: Option<i64>,
+	timestamp: i64,
+	signature: &str,
+) -> Result<Vec<MailboxMessage>, Error> {
+	let address: Result<OnionV3Address, OnionV3AddressError> = recipient.try_into();
+	let address = address.map_err(|e| {
+		Error::Libp2pError(format!("Invalid recipient address {}, {}", recipient, e))
+	})?;
+	let recipient_pk = address.to_ed25519().map_err(|e| {
+		Error::Libp2pError(format!("Invalid recipient address {}, {}", recipient, e))
+	})?;
+	let now = Utc::now().timestamp();
+	if (now - timestamp).abs() > MAILBOX_FETCH_REQUEST_TTL {
+		return Err(Error::Libp2pError(format!(
+			"Fetch request is expired, signed at {}, current time {}",
+			timestamp, now
+		)));
+	}
+	let since = since.unwrap_or(0);
+	let signature = from_hex(signature)
+		.ok()
+		.and_then(|s| DalekSignature::from_bytes(&s).ok())
+		.ok_or_else(|| Error::Libp2pError("Invalid fetch request signature".to_string()))?;
+	recipient_pk
+		.verify(
+			&fetch_request_data(&address.to_string(), since, timestamp),
+			&signature,
+		)
+		.map_err(|_| {
+			Error::Libp2pError(format!(
+				"Fetch request is not signed by the recipient {}",
+				recipient
+			))
+		})?;
+	Ok(MAILBOX.lock().get(&address.to_string(), since, now))
+}
+
+/// Current mailbox usage
+pub fn get_mailbox_stats() -> MailboxStats {
+	MAILBOX.lock().stats()
+}
+
+#[test]
+fn test_mailbox_seal() {
+	let recipient_sk = DalekSecretKey::from_bytes(&[1u8; 32]).unwrap();
+	let recipient_pk = DalekPublicKey::from(&recipient_sk);
+	let other_sk = DalekSecretKey::from_bytes(&[2u8; 32]).unwrap();
+
+	let sealed = seal_message(&recipient_pk, b"hello wallet").unwrap();
+	assert_eq!(
+		open_message(&recipient_sk, &sealed).unwrap(),
+		b"hello wallet".to_vec()
+	);
+	assert!(open_message(&other_sk, &sealed).is_err());
+	// Every message has own ephemeral key
+	assert_ne!(
+		sealed,
+		seal_message(&recipient_pk, b"hello wallet").unwrap()
+	);
+
+	// Tampered ciphertext is rejected
+	let mut tampered = sealed.clone();
+	let last = tampered.len() - 1;
+	tampered[last] ^= 1;
+	assert!(open_message(&recipient_sk, &tampered).is_err());
+
+	let message = MailboxMessage::from_sealed("sender", 100, &sealed, 10).unwrap();
+	assert_eq!(
+		message.recipient,
+		PeerId::onion_v3_from_pubkey(&recipient_pk)
+	);
+	assert!(MailboxMessage::from_sealed("sender", 100, &vec![1, 2, 3], 10).is_err());
+}
+
+#[test]
+fn test_mailbox_fetch_signature() {
+	let recipient_sk = DalekSecretKey::from_bytes(&[1u8; 32]).unwrap();
+	let recipient = PeerId::onion_v3_from_pubkey(&DalekPublicKey::from(&recipient_sk));
+	let other_sk = DalekSecretKey::from_bytes(&[2u8; 32]).unwrap();
+
+	let now = Utc::now().timestamp();
+
+	let signature = sign_fetch_request(&recipient_sk, Some(10), now);
+	assert!(get_messages(&recipient, Some(10), now, &signature).is_ok());
+	// Signature is bound to the since time, to the signing time and to the recipient key
+	assert!(get_messages(&recipient, Some(0), now, &signature).is_err());
+	assert!(get_messages(&recipient, None, now, &signature).is_err());
+	assert!(get_messages(&recipient, Some(10), now - 1, &signature).is_err());
+	assert!(get_messages(
+		&recipient,
+		Some(10),
+		now,
+		&sign_fetch_request(&other_sk, Some(10), now)
+	)
+	.is_err());
+	assert!(get_messages(&recipient, Some(10), now, "00").is_err());
+	assert!(get_messages(
+		&recipient,
+		None,
+		now,
+		&sign_fetch_request(&recipient_sk, None, now)
+	)
+	.is_ok());
+
+	// Stale requests are rejected, even with the valid signature
+	let stale = now - MAILBOX_FETCH_REQUEST_TTL - 10;
+	let signature = sign_fetch_request(&recipient_sk, None, stale);
+	assert!(get_messages(&recipient, None, stale, &signature).is_err());
+	let future = now + MAILBOX_FETCH_REQUEST_TTL + 10;
+	let signature = sign_fetch_request(&recipient_sk, None, future);
+	assert!(get_messages(&recipient, None, future, &signature).is_err());
+}
+
+#[test]
+fn test_mailbox_quota() {
+	let message =
+		|id: usize, recipient: &str, fee: u64, size: usize, timestamp: i64| MailboxMessage {
+			id: format!("{}", id),
+			sender: "sender".to_string(),
+			recipient: recipient.to_string(),
+			fee,
+			timestamp,
+			message: "00".repeat(size),
+		};
+
+	let mut mailbox = Mailbox::new();
+	let size = MAILBOX_RECIPIENT_MAX_BYTES / 4;
+	for i in 0..4 {
+		assert!(mailbox.add(message(i, "a", 100, size, 10), 10).unwrap());
+	}
+	// Duplicate is ignored
+	assert!(!mailbox.add(message(0, "a", 100, size, 10), 10).unwrap());
+	// The same fee doesn't buy the space
+	assert!(mailbox.add(message(4, "a", 100, size, 10), 10).is_err());
+	// Another recipient has own quota
+	assert!(mailbox.add(message(5, "b", 100, size, 10), 10).unwrap());
+	// Higher fee replaces the cheapest message
+	assert!(mailbox.add(message(6, "a", 200, size, 11), 11).unwrap());
+	let stats = mailbox.stats();
+	assert_eq!(stats.recipients, 2);
+	assert_eq!(stats.messages, 5);
+	assert_eq!(stats.bytes, size * 5);
+	assert_eq!(stats.evicted, 1);
+	assert_eq!(stats.rejected, 1);
+
+	assert_eq!(mailbox.get("a", 0, 11).len(), 4);
+	assert_eq!(mailbox.get("a", 10, 11).len(), 1);
+	assert!(mailbox.get("c", 0, 11).is_empty());
+
+	// Messages expire
+	assert_eq!(mailbox.get("a", 0, 11 + MAILBOX_MESSAGE_TTL).len(), 1);
+	mailbox.cleanup(12 + MAILBOX_MESSAGE_TTL);
+	assert_eq!(
+		mailbox.stats(),
+		MailboxStats {
+			evicted: 1,
+			rejected: 1,
+			..MailboxStats::default()
+		}
+	);
+}
@@ -0,0 +1,568 @@
+// Copyright 2021 The MWC Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! libp2p request-response protocol that mirrors selected foreign API methods.
+//! Wallets that are connected to the gossip network can query headers, kernels and
+//! push transactions directly over a libp2p stream, without knowing the node HTTP
+//! foreign API endpoint. Requests and responses are json-rpc 2.0 bodies, exactly as
+//! they are sent to `/v2/foreign`.
+
+use crate::types::Error;
+use async_trait::async_trait;
+use chrono::Utc;
+use futures::channel::{mpsc, oneshot};
+use futures::prelude::*;
+use grin_util::{Mutex, RwLock};
+use libp2p::core::upgrade::{read_one, write_one};
+use libp2p::core::ProtocolName;
+use libp2p::request_response::{
+	ProtocolSupport, RequestId, RequestResponse, RequestResponseCodec, RequestResponseConfig,
+	RequestResponseEvent, RequestResponseMessage, ResponseChannel,
+};
+use libp2p::PeerId;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+use std::{io, iter};
+
+/// Protocol name that is negotiated for the node API streams
+pub const NODE_API_PROTOCOL_NAME: &[u8] = b"/mwc/node-api/1.0.0";
+/// Max size of the request or response. Must fit the largest transaction we accept into the pool.
+pub const NODE_API_MAX_MESSAGE_SIZE: usize = 4_000_000;
+/// Foreign API methods that can be called over libp2p. Everything else is rejected.
+/// Note, libp2p methods are excluded, the API handler must not depend on the libp2p swarm.
+pub const NODE_API_METHODS: &[&str] = &[
+	"get_version",
+	"get_tip",
+	"get_header",
+	"get_kernel",
+	"get_outputs",
+	"push_transaction",
+	"get_mailbox_messages",
+];
+
+/// Method of the request that the node sends to the publisher of the integrity messages that
+/// exceed the rate limit. It is served by wallets, so they can throttle themselves.
+pub const RATE_LIMIT_METHOD: &str = "rate_limit_exceeded";
+/// Max retry-after that is accepted from the rate limit feedback, seconds. Any peer can send
+/// the feedback, so it can't stop our publishing for long.
+pub const RATE_LIMIT_MAX_RETRY_AFTER: i64 = 600;
+/// Max number of the topics with the active rate limit feedback
+const RATE_LIMIT_FEEDBACK_MAX_TOPICS: usize = 100;
+
+/// Max number of the incoming requests that are processed at once
+const NODE_API_MAX_IN_FLIGHT: usize = 32;
+/// Max number of the incoming requests of a single peer that are processed at once
+const NODE_API_MAX_IN_FLIGHT_PER_PEER: usize = 4;
+
+/// Json-rpc error code for the methods that are not mirrored over libp2p
+const METHOD_NOT_FOUND: i64 = -32601;
+/// Json-rpc error code for the malformed requests
+const INVALID_REQUEST: i64 = -32600;
+/// Json-rpc error code for the requests that are over the in-flight limits
+const SERVER_BUSY: i64 = -32000;
+
+/// Node API request response behaviour type
+pub type NodeApiBehaviour = RequestResponse<NodeApiCodec>;
+/// Node API events that are produced by the behaviour
+pub type NodeApiEvent = RequestResponseEvent<Vec<u8>, Vec<u8>>;
+/// Responses of the processed incoming requests, they are sent by the swarm polling loop
+pub type NodeApiResponseSender = mpsc::UnboundedSender<(ResponseChannel<Vec<u8>>, Vec<u8>)>;
+
+/// Handler that processing json-rpc requests of the peer. Implemented by the node on top of the
+/// foreign API, the node rate limits the requests by the peer.
+pub type NodeApiHandler =
+	Arc<dyn Fn(&PeerId, serde_json::Value) -> serde_json::Value + Send + Sync>;
+
+lazy_static! {
+	/// Node API handler. Without handler the node doesn't serve the requests (wallet mode).
+	static ref NODE_API_HANDLER: RwLock<Option<NodeApiHandler>> = RwLock::new(None);
+	/// Requests that are sent by us and waiting for response
+	static ref NODE_API_PENDING: RwLock<HashMap<RequestId, oneshot::Sender<Result<serde_json::Value, Error>>>> =
+		RwLock::new(HashMap::new());
+	/// Received rate limit feedback by topic, with the timestamp when we can publish again
+	static ref RATE_LIMIT_FEEDBACK: RwLock<HashMap<String, (i64, RateLimitFeedback)>> =
+		RwLock::new(HashMap::new());
+	/// Topics we published to, with the time of the last message. The feedback is accepted
+	/// for these topics only.
+	static ref PUBLISHED_TOPICS: RwLock<HashMap<String, i64>> = RwLock::new(HashMap::new());
+	/// Incoming requests that are processed now
+	static ref NODE_API_IN_FLIGHT: Mutex<InFlight> = Mutex::new(InFlight::default());
+}
+
+#[derive(Default)]
+struct InFlight {
+	total: usize,
+	by_peer: HashMap<PeerId, usize>,
+}
+
+// Processing slot of the incoming request, released on drop
+struct InFlightSlot(PeerId);
+
+impl InFlightSlot {
+	// None if the node or the peer is over the in-flight limit
+	fn acquire(peer: &PeerId) -> Option<InFlightSlot> {
+		let mut in_flight = NODE_API_IN_FLIGHT.lock();
+		let by_peer = in_flight.by_peer.get(peer).cloned().unwrap_or(0);
+		if in_flight.total >= NODE_API_MAX_IN_FLIGHT || by_peer >= NODE_API_MAX_IN_FLIGHT_PER_PEER {
+			return None;
+		}
+		in_flight.total += 1;
+		in_flight.by_peer.insert(peer.clone(), by_peer + 1);
+		Some(InFlightSlot(peer.clone()))
+	}
+}
+
+impl Drop for InFlightSlot {
+	fn drop(&mut self) {
+		let mut in_flight = NODE_API_IN_FLIGHT.lock();
+		in_flight.total = in_flight.total.saturating_sub(1);
+		let remove = match in_flight.by_peer.get_mut(&self.0) {
+			Some(n) => {
+				*n = n.saturating_sub(1);
+				*n == 0
+			}
+			None => false,
+		};
+		if remove {
+			in_flight.by_peer.remove(&self.0);
+		}
+	}
+}
+
+/// Rate limit feedback, the node rejected our integrity message because we publish too often
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct RateLimitFeedback {
+	/// Topic of the rejected message
+	pub topic: String,
+	/// Violated limit, min average period between the messages with the same integrity kernel, seconds
+	pub min_period: i64,
+	/// Seconds to wait before the next message with the same integrity kernel is accepted
+	pub retry_after: i64,
+}
+
+/// Node API protocol name
+#[derive(Debug, Clone)]
+pub struct NodeApiProtocol();
+
+impl ProtocolName for NodeApiProtocol {
+	fn protocol_name(&self) -> &[u8] {
+		NODE_API_PROTOCOL_NAME
+	}
+}
+
+/// Codec for node API. Request and responses are length prefixed json strings.
+#[derive(Clone)]
+pub struct NodeApiCodec();
+
+#[async_trait]
+impl RequestResponseCodec for NodeApiCodec {
+	type Protocol = NodeApiProtocol;
+	type Request = Vec<u8>;
+	type Response = Vec<u8>;
+
+	async fn read_request<T>(&mut self, _: &NodeApiProtocol, io: &mut T) -> io::Result<Vec<u8>>
+	where
+		T: AsyncRead + Unpin + Send,
+	{
+		read_one(io, NODE_API_MAX_MESSAGE_SIZE)
+			.map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+			.await
+	}
+
+	async fn read_response<T>(&mut self, _: &NodeApiProtocol, io: &mut T) -> io::Result<Vec<u8>>
+	where
+		T: AsyncRead + Unpin + Send,
+	{
+		read_one(io, NODE_API_MAX_MESSAGE_SIZE)
+			.map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+			.await
+	}
+
+	async fn write_request<T>(
+		&mut self,
+		_: &NodeApiProtocol,
+		io: &mut T,
+		data: Vec<u8>,
+	) -> io::Result<()>
+	where
+		T: AsyncWrite + Unpin + Send,
+	{
+		write_one(io, data).await
+	}
+
+	async fn write_response<T>(
+		&mut self,
+		_: &NodeApiProtocol,
+		io: &mut T,
+		data: Vec<u8>,
+	) -> io::Result<()>
+	where
+		T: AsyncWrite + Unpin + Send,
+	{
+		write_one(io, data).await
+	}
+}
+
+/// Build the node API behaviour. Tor is slow, so timeout is pretty large.
+pub fn build_node_api_behaviour() -> NodeApiBehaviour {
+	let mut config = RequestResponseConfig::default();
+	config.set_request_timeout(Duration::from_secs(60));
+	RequestResponse::new(
+		NodeApiCodec(),
+		iter::once((NodeApiProtocol(), ProtocolSupport::Full)),
+		config,
+	)
+}
+
+/// Set the handler for incoming requests. Node should call it with foreign API handler.
+pub fn set_node_api_handler(handler: NodeApiHandler) {
+	NODE_API_HANDLER.write().replace(handler);
+}
+
+/// Reset the handler, node will stop serve the requests.
+pub fn reset_node_api_handler() {
+	NODE_API_HANDLER.write().take();
+}
+
+/// Return true if this node is serving node API requests
+pub fn is_node_api_served() -> bool {
+	NODE_API_HANDLER.read().is_some()
+}
+
+fn build_error_response(id: serde_json::Value, code: i64, message: &str) -> serde_json::Value {
+	serde_json::json!({
+		"jsonrpc": "2.0",
+		"id": id,
+		"error": {
+			"code": code,
+			"message": message,
+		}
+	})
+}
+
+/// Process the incoming request. Return the json-rpc response data.
+pub fn process_node_api_request(peer: &PeerId, request: &[u8]) -> Vec<u8> {
+	let request: serde_json::Value = match serde_json::from_slice(request) {
+		Ok(r) => r,
+		Err(e) => {
+			debug!("Get invalid node API request from {}, {}", peer, e);
+			return build_error_response(serde_json::Value::Null, INVALID_REQUEST, "Invalid json")
+				.to_string()
+				.into_bytes();
+		}
+	};
+
+	let id = request
+		.get("id")
+		.cloned()
+		.unwrap_or(serde_json::Value::Null);
+	let method = request
+		.get("method")
+		.and_then(|m| m.as_str())
+		.unwrap_or("")
+		.to_string();
+
+	let response = if method == RATE_LIMIT_METHOD {
+		process_rate_limit_feedback(peer, id, &request)
+	} else if !NODE_API_METHODS.contains(&method.as_str()) {
+		debug!(
+			"Peer {} requested method {} that is not available over libp2p",
+			peer, method
+		);
+		build_error_response(id, METHOD_NOT_FOUND, "Method not available over libp2p")
+	} else {
+		let handler = NODE_API_HANDLER.read().clone();
+		match handler {
+			Some(handler) => {
+				debug!("Processing node API request {} from {}", method, peer);
+				(handler)(peer, request)
+			}
+			None => build_error_response(id, METHOD_NOT_FOUND, "Node API is not served"),
+		}
+	};
+
+	response.to_string().into_bytes()
+}
+
+fn process_rate_limit_feedback(
+	peer: &PeerId,
+	id: serde_json::Value,
+	request: &serde_json::Value,
+) -> serde_json::Value {
+	let feedback = request
+		.get("params")
+		.cloned()
+		.and_then(|p| serde_json::from_value::<RateLimitFeedback>(p).ok());
+	match feedback {
+		Some(feedback) => {
+			let now = Utc::now().timestamp();
+			// A peer can limit only our own recent messages
+			let published = PUBLISHED_TOPICS
+				.read()
+				.get(&feedback.topic)
+				.map(|t| *t > now - RATE_LIMIT_MAX_RETRY_AFTER)
+				.unwrap_or(false);
+			if !published {
+				debug!(
+					"Node {} sent the rate limit feedback for topic {} we didn't publish to",
+					peer, feedback.topic
+				);
+				return build_error_response(id, INVALID_REQUEST, "Topic is not published");
+			}
+			info!(
+				"Node {} rate limited our messages on topic {}, retry after {} seconds",
+				peer, feedback.topic, feedback.retry_after
+			);
+			let retry_at = now + feedback.retry_after.max(0).min(RATE_LIMIT_MAX_RETRY_AFTER);
+			let mut feedbacks = RATE_LIMIT_FEEDBACK.write();
+			feedbacks.retain(|_, (t, _)| *t > now);
+			if feedbacks.len() >= RATE_LIMIT_FEEDBACK_MAX_TOPICS
+				&& !feedbacks.contains_key(&feedback.topic)
+			{
+				return build_error_response(id, INVALID_REQUEST, "Too many rate limited topics");
+			}
+			let later = feedbacks
+				.get(&feedback.topic)
+				.map(|(t, _)| *t < retry_at)
+				.unwrap_or(true);
+			if later {
+				feedbacks.insert(feedback.topic.clone(), (retry_at, feedback));
+			}
+			serde_json::json!({"jsonrpc": "2.0", "id": id, "result": {"Ok": null}})
+		}
+		None => build_error_response(id, INVALID_REQUEST, "Invalid rate limit feedback"),
+	}
+}
+
+/// Build the rate limit feedback request for the publisher of the rejected message
+pub fn build_rate_limit_request(feedback: &RateLimitFeedback) -> serde_json::Value {
+	serde_json::json!({
+		"jsonrpc": "2.0",
+		"method": RATE_LIMIT_METHOD,
+		"params": feedback,
+		"id": 1,
+	})
+}
+
+/// Register the message that we published to the topic, so the rate limit feedback for it is
+/// accepted
+pub fn register_published_topic(topic: &str) {
+	let now = Utc::now().timestamp();
+	let mut published = PUBLISHED_TOPICS.write();
+	published.retain(|_, t| *t > now - RATE_LIMIT_MAX_RETRY_AFTER);
+	published.insert(topic.to_string(), now);
+}
+
+/// Active rate limit feedback for the topic, with the remaining retry-after. None if we can publish.
+pub fn get_rate_limit_feedback(topic: &str) -> Option<RateLimitFeedback> {
+	let now = Utc::now().timestamp();
+	let mut feedbacks = RATE_LIMIT_FEEDBACK.write();
+	feedbacks.retain(|_, (retry_at, _)| *retry_at > now);
+	feedbacks
+		.get(topic)
+		.map(|(retry_at, feedback)| RateLimitFeedback {
+			retry_after: retry_at - now,
+			..feedback.clone()
+		})
+}
+
+/// Register the request that was sent to the peer. Response will be delivered to the returned receiver.
+pub fn register_node_api_request(
+	request_id: RequestId,
+) -> oneshot::Receiver<Result<serde_json::Value, Error>> {
+	let (sender, receiver) = oneshot::channel();
+	NODE_API_PENDING.write().insert(request_id, sender);
+	receiver
+}
+
+fn complete_node_api_request(request_id: &RequestId, result: Result<serde_json::Value, Error>) {
+	if let Some(sender) = NODE_API_PENDING.write().remove(request_id) {
+		// Receiver might be dropped, it is fine
+		let _ = sender.send(result);
+	}
+}
+
+/// Drop all pending requests. Called when the swarm is gone.
+pub fn reset_node_api_requests() {
+	let pending: Vec<_> = NODE_API_PENDING.write().drain().collect();
+	for (_id, sender) in pending {
+		let _ = sender.send(Err(Error::Libp2pError(
+			"libp2p node is stopped".to_string(),
+		)));
+	}
+}
+
+/// Process the node API event. Incoming requests are processed on the blocking thread pool,
+/// their responses are delivered to `responses`. The requests over the in-flight limits, for
+/// the node and for the peer, are answered with the busy error right away.
+pub fn handle_node_api_event(event: NodeApiEvent, responses: &NodeApiResponseSender) {
+	match event {
+		RequestResponseEvent::Message { peer, message } => match message {
+			RequestResponseMessage::Request {
+				request, channel, ..
+			} => {
+				let slot = match InFlightSlot::acquire(&peer) {
+					Some(slot) => slot,
+					None => {
+						debug!("Node API is busy, rejecting the request from {}", peer);
+						let response = build_error_response(
+							serde_json::Value::Null,
+							SERVER_BUSY,
+							"Node API is busy",
+						);
+						if responses
+							.unbounded_send((channel, response.to_string().into_bytes()))
+							.is_err()
+						{
+							debug!("Unable to send node API response, libp2p node is stopped");
+						}
+						return;
+					}
+				};
+				// The API handler reads the chain and the pool, it must not stall the swarm polling loop
+				let responses = responses.clone();
+				tokio::task::spawn_blocking(move || {
+					let _slot = slot;
+					let response = process_node_api_request(&peer, &request);
+					if responses.unbounded_send((channel, response)).is_err() {
+						debug!("Unable to send node API response, libp2p node is stopped");
+					}
+				});
+			}
+			RequestResponseMessage::Response {
+				request_id,
+				response,
+			} => {
+				let result = serde_json::from_slice::<serde_json::Value>(&response).map_err(|e| {
+					Error::Libp2pError(format!("Unable to parse node API response, {}", e))
+				});
+				complete_node_api_request(&request_id, result);
+			}
+		},
+		RequestResponseEvent::OutboundFailure {
+			peer,
+			request_id,
+			error,
+		} => {
+			debug!("Node API request to {} is failed, {:?}", peer, error);
+			complete_node_api_request(
+				&request_id,
+				Err(Error::Libp2pError(format!(
+					"Node API request is failed, {:?}",
+					error
+				))),
+			);
+		}
+		RequestResponseEvent::InboundFailure { peer, error, .. } => {
+			debug!("Node API request from {} is failed, {:?}", peer, error);
+		}
+		RequestResponseEvent::ResponseSent { .. } => {}
+	}
+}
+
+#[test]
+fn test_node_api_method_filter() {
+	let peer = PeerId::random();
+	reset_node_api_handler();
+
+	let req =
+		serde_json::json!({"jsonrpc": "2.0", "method": "get_libp2p_peers", "params": [], "id": 1});
+	let resp: serde_json::Value =
+		serde_json::from_slice(&process_node_api_request(&peer, req.to_string().as_bytes()))
+			.unwrap();
+	assert_eq!(resp["error"]["code"], METHOD_NOT_FOUND);
+	assert_eq!(resp["id"], 1);
+
+	let resp: serde_json::Value =
+		serde_json::from_slice(&process_node_api_request(&peer, b"not a json")).unwrap();
+	assert_eq!(resp["error"]["code"], INVALID_REQUEST);
+
+	set_node_api_handler(Arc::new(
+		|_peer: &PeerId, req: serde_json::Value| serde_json::json!({"jsonrpc": "2.0", "id": req["id"], "result": {"Ok": null}}),
+	));
+	let req = serde_json::json!({"jsonrpc": "2.0", "method": "get_tip", "params": [], "id": 2});
+	let resp: serde_json::Value =
+		serde_json::from_slice(&process_node_api_request(&peer, req.to_string().as_bytes()))
+			.unwrap();
+	assert_eq!(resp["id"], 2);
+	assert!(resp.get("error").is_none());
+	reset_node_api_handler();
+}
+
+#[test]
+fn test_node_api_in_flight_limits() {
+	let peer = PeerId::random();
+	let slots: Vec<_> = (0..NODE_API_MAX_IN_FLIGHT_PER_PEER)
+		.map(|_| InFlightSlot::acquire(&peer).unwrap())
+		.collect();
+	assert!(InFlightSlot::acquire(&peer).is_none());
+	// Other peers are still served
+	let other = InFlightSlot::acquire(&PeerId::random()).unwrap();
+	drop(slots);
+	drop(other);
+	assert!(InFlightSlot::acquire(&peer).is_some());
+	assert!(!NODE_API_IN_FLIGHT.lock().by_peer.contains_key(&peer));
+}
+
+#[test]
+fn test_rate_limit_feedback() {
+	let peer = PeerId::random();
+	reset_node_api_handler();
+	assert_eq!(get_rate_limit_feedback("rate_limit_topic"), None);
+
+	// Feedback for the topic we didn't publish to is rejected
+	let req = build_rate_limit_request(&RateLimitFeedback {
+		topic: "not_published_topic".to_string(),
+		min_period: 15,
+		retry_after: 30,
+	});
+	let resp: serde_json::Value =
+		serde_json::from_slice(&process_node_api_request(&peer, req.to_string().as_bytes()))
+			.unwrap();
+	assert_eq!(resp["error"]["code"], INVALID_REQUEST);
+	assert_eq!(get_rate_limit_feedback("not_published_topic"), None);
+	register_published_topic("rate_limit_topic");
+
+	// Served without the node API handler, it is the wallet side
+	let feedback = RateLimitFeedback {
+		topic: "rate_limit_topic".to_string(),
+		min_period: 15,
+		retry_after: 30,
+	};
+	let req = build_rate_limit_request(&feedback);
+	let resp: serde_json::Value =
+		serde_json::from_slice(&process_node_api_request(&peer, req.to_string().as_bytes()))
+			.unwrap();
+	assert!(resp.get("error").is_none());
+	let active = get_rate_limit_feedback("rate_limit_topic").unwrap();
+	assert!(active.retry_after > 0 && active.retry_after <= 30);
+	assert_eq!(get_rate_limit_feedback("other_topic"), None);
+
+	// Retry-after is capped
+	let req = build_rate_limit_request(&RateLimitFeedback {
+		retry_after: 100_000,
+		..feedback
+	});
+	process_node_api_request(&peer, req.to_string().as_bytes());
+	let active = get_rate_limit_feedback("rate_limit_topic").unwrap();
+	assert!(active.retry_after <= RATE_LIMIT_MAX_RETRY_AFTER);
+
+	let req =
+		serde_json::json!({"jsonrpc": "2.0", "method": RATE_LIMIT_METHOD, "params": [], "id": 3});
+	let resp: serde_json::Value =
+		serde_json::from_slice(&process_node_api_request(&peer, req.to_string().as_bytes()))
+			.unwrap();
+	assert_eq!(resp["error"]["code"], INVALID_REQUEST);
+}
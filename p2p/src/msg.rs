@@ -14,17 +14,31 @@
 
 //! Message types that transit over the network and related serialization code.
 
+use crate::chain::txhashset::{
+	KERNEL_SEGMENT_HEIGHT, OUTPUT_SEGMENT_HEIGHT, RANGEPROOF_SEGMENT_HEIGHT,
+};
+use crate::chain::types::OutputRoots;
 use crate::conn::Tracker;
 use crate::core::core::hash::Hash;
-use crate::core::core::BlockHeader;
+use crate::core::core::pmmr::{Segment, SegmentIdentifier};
+use crate::core::core::transaction::Weighting;
+use crate::core::core::verifier_cache::LruVerifierCache;
+use crate::core::core::{
+	BlockHeader, Output, OutputIdentifier, ShortId, Transaction, TxKernel, UntrustedBlock,
+	UntrustedBlockHeader, UntrustedCompactBlock,
+};
 use crate::core::pow::Difficulty;
 use crate::core::ser::{
 	self, ProtocolVersion, Readable, Reader, StreamingReader, Writeable, Writer,
 };
 use crate::core::{consensus, global};
 use crate::types::{
-	Capabilities, Error, PeerAddr, ReasonForBan, MAX_BLOCK_HEADERS, MAX_LOCATORS, MAX_PEER_ADDRS,
+	Capabilities, Error, PeerAddr, ReasonForBan, SimulationCheck, MAX_BLOCK_HEADERS, MAX_LOCATORS,
+	MAX_PEER_ADDRS,
 };
+use crate::util::secp::constants::MAX_PROOF_SIZE;
+use crate::util::secp::pedersen::RangeProof;
+use crate::util::RwLock;
 use num::FromPrimitive;
 use std::fs::File;
 use std::io::{Read, Write};
@@ -67,6 +81,18 @@ enum_from_primitive! {
 		GetTransaction = 19,
 		TransactionKernel = 20,
 		TorAddress = 23,
+		GetOutputSegment = 24,
+		OutputSegment = 25,
+		GetRangeProofSegment = 26,
+		RangeProofSegment = 27,
+		GetKernelSegment = 28,
+		KernelSegment = 29,
+		GetHistoricalOutput = 30,
+		HistoricalOutput = 31,
+		GetBlockTransactions = 32,
+		BlockTransactions = 33,
+		GetTxHashSetPart = 34,
+		TxHashSetPart = 35,
 	}
 }
 
@@ -75,6 +101,13 @@ fn max_block_size() -> u64 {
 	(global::max_block_weight() / consensus::BLOCK_OUTPUT_WEIGHT * 708) as u64
 }
 
+/// Max size of a segment of the provided height, with leaves of the provided
+/// max size. Every node of the segment can be a hash, plus the merkle proof.
+fn max_segment_size(height: u8, max_leaf_size: u64) -> u64 {
+	let capacity = 1u64 << height;
+	32 + 9 + 8 + 2 * capacity * (8 + 32) + 8 + capacity * (8 + max_leaf_size) + 8 + 128 * 32
+}
+
 // Max msg size when msg type is unknown.
 fn default_max_msg_size() -> u64 {
 	max_block_size()
@@ -96,7 +129,8 @@ fn max_msg_size(msg_type: Type) -> u64 {
 		Type::GetBlock => 32,
 		Type::Block => max_block_size(),
 		Type::GetCompactBlock => 32,
-		Type::CompactBlock => max_block_size() / 10,
+		// a compact block with prefilled txs is still a subset of the full block
+		Type::CompactBlock => max_block_size(),
 		Type::StemTransaction => max_block_size(),
 		Type::Transaction => max_block_size(),
 		Type::TxHashSetRequest => 40,
@@ -105,6 +139,26 @@ fn max_msg_size(msg_type: Type) -> u64 {
 		Type::GetTransaction => 32,
 		Type::TransactionKernel => 32,
 		Type::TorAddress => 128,
+		Type::GetOutputSegment => 41,
+		Type::OutputSegment => {
+			max_segment_size(OUTPUT_SEGMENT_HEIGHT, 34)
+				+ 64 + 8 + 8 * (1 << OUTPUT_SEGMENT_HEIGHT)
+				+ 8 + 128 * 32
+		}
+		Type::GetRangeProofSegment => 41,
+		Type::RangeProofSegment => {
+			max_segment_size(RANGEPROOF_SEGMENT_HEIGHT, 8 + MAX_PROOF_SIZE as u64)
+		}
+		Type::GetKernelSegment => 41,
+		Type::KernelSegment => max_segment_size(KERNEL_SEGMENT_HEIGHT, 114),
+		Type::GetHistoricalOutput => 8,
+		Type::HistoricalOutput => 8 + 8 + 1 + 1 + 33 + 8 + MAX_PROOF_SIZE as u64,
+		Type::GetBlockTransactions => {
+			32 + 8 + 8 + 6 * (global::max_block_weight() / consensus::BLOCK_KERNEL_WEIGHT)
+		}
+		Type::BlockTransactions => max_block_size(),
+		Type::GetTxHashSetPart => 56,
+		Type::TxHashSetPart => 96,
 	}
 }
 
@@ -120,6 +174,7 @@ pub struct Msg {
 	header: MsgHeader,
 	body: Vec<u8>,
 	attachment: Option<File>,
+	attachment_len: Option<u64>,
 	version: ProtocolVersion,
 }
 
@@ -134,6 +189,7 @@ impl Msg {
 			header: MsgHeader::new(msg_type, body.len() as u64),
 			body,
 			attachment: None,
+			attachment_len: None,
 			version,
 		})
 	}
@@ -141,6 +197,178 @@ impl Msg {
 	pub fn add_attachment(&mut self, attachment: File) {
 		self.attachment = Some(attachment)
 	}
+
+	/// Attach only the provided number of bytes of the file, from its current position.
+	pub fn add_attachment_part(&mut self, attachment: File, len: u64) {
+		self.attachment = Some(attachment);
+		self.attachment_len = Some(len);
+	}
+}
+
+/// Check if the data starts with the p2p message magic numbers of the current network
+pub fn has_message_magic(data: &[u8]) -> bool {
+	data.len() >= 2 && data[..2] == magic()
+}
+
+fn simulate_body<T: Readable>(
+	body: &[u8],
+	version: ProtocolVersion,
+	checks: &mut Vec<SimulationCheck>,
+) -> Option<T> {
+	let mut reader = body;
+	match ser::deserialize::<T, _>(&mut reader, version) {
+		Ok(item) => {
+			checks.push(SimulationCheck::passed("body"));
+			if reader.is_empty() {
+				checks.push(SimulationCheck::passed("body_size"));
+				Some(item)
+			} else {
+				checks.push(SimulationCheck::failed(
+					"body_size",
+					format!("{} bytes are not consumed", reader.len()),
+				));
+				None
+			}
+		}
+		Err(e) => {
+			checks.push(SimulationCheck::failed("body", format!("{}", e)));
+			None
+		}
+	}
+}
+
+/// Run the raw p2p message (header and body) through the message validation without processing it.
+/// Returns the message type and the checks that was done. Checks are stopped at the first failure.
+/// Note, validation that depends on the chain state (block connectivity, UTXO) is not simulated.
+pub fn simulate_message(
+	data: &[u8],
+	version: ProtocolVersion,
+) -> (Option<Type>, Vec<SimulationCheck>) {
+	let mut checks = vec![];
+	let mut reader = data;
+	let header = match ser::deserialize::<MsgHeaderWrapper, _>(&mut reader, version) {
+		Ok(MsgHeaderWrapper::Known(header)) => header,
+		Ok(MsgHeaderWrapper::Unknown(_, msg_type)) => {
+			checks.push(SimulationCheck::failed(
+				"header",
+				format!("Unknown message type {}", msg_type),
+			));
+			return (None, checks);
+		}
+		Err(e) => {
+			checks.push(SimulationCheck::failed("header", format!("{}", e)));
+			return (None, checks);
+		}
+	};
+	checks.push(SimulationCheck::passed("header"));
+	let msg_type = Some(header.msg_type);
+
+	if reader.len() as u64 != header.msg_len {
+		checks.push(SimulationCheck::failed(
+			"msg_len",
+			format!(
+				"Header declares {} bytes, body has {} bytes",
+				header.msg_len,
+				reader.len()
+			),
+		));
+		return (msg_type, checks);
+	}
+	checks.push(SimulationCheck::passed("msg_len"));
+
+	match header.msg_type {
+		Type::Hand => {
+			simulate_body::<Hand>(reader, version, &mut checks);
+		}
+		Type::Shake => {
+			simulate_body::<Shake>(reader, version, &mut checks);
+		}
+		Type::Ping => {
+			simulate_body::<Ping>(reader, version, &mut checks);
+		}
+		Type::Pong => {
+			simulate_body::<Pong>(reader, version, &mut checks);
+		}
+		Type::GetPeerAddrs => {
+			simulate_body::<GetPeerAddrs>(reader, version, &mut checks);
+		}
+		Type::PeerAddrs => {
+			simulate_body::<PeerAddrs>(reader, version, &mut checks);
+		}
+		Type::GetHeaders => {
+			simulate_body::<Locator>(reader, version, &mut checks);
+		}
+		Type::Header => {
+			simulate_body::<UntrustedBlockHeader>(reader, version, &mut checks);
+		}
+		Type::GetBlock | Type::GetCompactBlock | Type::GetTransaction | Type::TransactionKernel => {
+			simulate_body::<Hash>(reader, version, &mut checks);
+		}
+		Type::Block => {
+			simulate_body::<UntrustedBlock>(reader, version, &mut checks);
+		}
+		Type::CompactBlock => {
+			simulate_body::<UntrustedCompactBlock>(reader, version, &mut checks);
+		}
+		Type::Transaction | Type::StemTransaction => {
+			if let Some(tx) = simulate_body::<Transaction>(reader, version, &mut checks) {
+				let verifier_cache = Arc::new(RwLock::new(LruVerifierCache::new()));
+				match tx.validate(Weighting::AsTransaction, verifier_cache) {
+					Ok(_) => checks.push(SimulationCheck::passed("tx_validate")),
+					Err(e) => checks.push(SimulationCheck::failed("tx_validate", format!("{}", e))),
+				}
+			}
+		}
+		Type::TxHashSetRequest => {
+			simulate_body::<TxHashSetRequest>(reader, version, &mut checks);
+		}
+		Type::TxHashSetArchive => {
+			simulate_body::<TxHashSetArchive>(reader, version, &mut checks);
+		}
+		Type::BanReason => {
+			simulate_body::<BanReason>(reader, version, &mut checks);
+		}
+		Type::TorAddress => {
+			simulate_body::<TorAddress>(reader, version, &mut checks);
+		}
+		Type::GetOutputSegment | Type::GetRangeProofSegment | Type::GetKernelSegment => {
+			simulate_body::<SegmentRequest>(reader, version, &mut checks);
+		}
+		Type::OutputSegment => {
+			simulate_body::<OutputSegmentResponse>(reader, version, &mut checks);
+		}
+		Type::RangeProofSegment => {
+			simulate_body::<SegmentResponse<RangeProof>>(reader, version, &mut checks);
+		}
+		Type::KernelSegment => {
+			simulate_body::<SegmentResponse<TxKernel>>(reader, version, &mut checks);
+		}
+		Type::GetHistoricalOutput => {
+			simulate_body::<HistoricalOutputRequest>(reader, version, &mut checks);
+		}
+		Type::HistoricalOutput => {
+			simulate_body::<HistoricalOutput>(reader, version, &mut checks);
+		}
+		Type::GetBlockTransactions => {
+			simulate_body::<BlockTransactionsRequest>(reader, version, &mut checks);
+		}
+		Type::BlockTransactions => {
+			simulate_body::<BlockTransactions>(reader, version, &mut checks);
+		}
+		Type::GetTxHashSetPart => {
+			simulate_body::<TxHashSetPartRequest>(reader, version, &mut checks);
+		}
+		Type::TxHashSetPart => {
+			simulate_body::<TxHashSetPart>(reader, version, &mut checks);
+		}
+		Type::Headers | Type::Error => {
+			checks.push(SimulationCheck::failed(
+				"body",
+				format!("Simulation is not supported for {:?}", header.msg_type),
+			));
+		}
+	}
+	(msg_type, checks)
 }
 
 /// Read a header from the provided stream without blocking if the
@@ -221,7 +449,9 @@ pub fn write_message<W: Write>(
 	stream.write_all(&buf[..])?;
 	tracker.inc_sent(buf.len() as u64);
 	if let Some(file) = &msg.attachment {
-		let mut file = file.try_clone()?;
+		let mut file = file
+			.try_clone()?
+			.take(msg.attachment_len.unwrap_or(u64::MAX));
 		let mut buf = [0u8; 8000];
 		loop {
 			match file.read(&mut buf[..]) {
@@ -749,6 +979,96 @@ impl Readable for TxHashSetArchive {
 	}
 }
 
+/// Request for a byte range of the txhashset archive, so an interrupted
+/// download can be resumed.
+pub struct TxHashSetPartRequest {
+	/// Hash of the block for which the txhashset should be provided
+	pub hash: Hash,
+	/// Height of the corresponding block
+	pub height: u64,
+	/// Offset of the range in the archive
+	pub offset: u64,
+	/// Max length of the range, up to `TXHASHSET_PART_SIZE`
+	pub length: u64,
+}
+
+impl Writeable for TxHashSetPartRequest {
+	fn write<W: Writer>(&self, writer: &mut W) -> Result<(), ser::Error> {
+		self.hash.write(writer)?;
+		ser_multiwrite!(
+			writer,
+			[write_u64, self.height],
+			[write_u64, self.offset],
+			[write_u64, self.length]
+		);
+		Ok(())
+	}
+}
+
+impl Readable for TxHashSetPartRequest {
+	fn read<R: Reader>(reader: &mut R) -> Result<TxHashSetPartRequest, ser::Error> {
+		let hash = Hash::read(reader)?;
+		let (height, offset, length) = ser_multiread!(reader, read_u64, read_u64, read_u64);
+
+		Ok(TxHashSetPartRequest {
+			hash,
+			height,
+			offset,
+			length,
+		})
+	}
+}
+
+/// Response to a txhashset part request, must include the requested range of
+/// the archive after the message body.
+pub struct TxHashSetPart {
+	/// Hash of the block for which the txhashset are provided
+	pub hash: Hash,
+	/// Height of the corresponding block
+	pub height: u64,
+	/// Size in bytes of the whole archive
+	pub bytes: u64,
+	/// Hash of the whole archive, checked once all the parts are received
+	pub archive_hash: Hash,
+	/// Offset of the range in the archive
+	pub offset: u64,
+	/// Length of the range
+	pub length: u64,
+}
+
+impl Writeable for TxHashSetPart {
+	fn write<W: Writer>(&self, writer: &mut W) -> Result<(), ser::Error> {
+		self.hash.write(writer)?;
+		ser_multiwrite!(writer, [write_u64, self.height], [write_u64, self.bytes]);
+		self.archive_hash.write(writer)?;
+		ser_multiwrite!(writer, [write_u64, self.offset], [write_u64, self.length]);
+		Ok(())
+	}
+}
+
+impl Readable for TxHashSetPart {
+	fn read<R: Reader>(reader: &mut R) -> Result<TxHashSetPart, ser::Error> {
+		let hash = Hash::read(reader)?;
+		let (height, bytes) = ser_multiread!(reader, read_u64, read_u64);
+		let archive_hash = Hash::read(reader)?;
+		let (offset, length) = ser_multiread!(reader, read_u64, read_u64);
+		if offset.checked_add(length).map_or(true, |end| end > bytes) {
+			return Err(ser::Error::CorruptedData(
+				"txhashset part is out of the archive".to_string(),
+			));
+		}
+
+		Ok(TxHashSetPart {
+			hash,
+			height,
+			bytes,
+			archive_hash,
+			offset,
+			length,
+		})
+	}
+}
+
 #[derive(Debug)]
 pub struct TorAddress {
 	pub address: String,
@@ -778,3 +1098,270 @@ impl Readable for TorAddress {
 		}
 	}
 }
+
+/// Request for a segment of one of the txhashset MMRs, at the txhashset
+/// archive header with the provided hash.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SegmentRequest {
+	/// Hash of the block the segment is requested for
+	pub block_hash: Hash,
+	/// Identifier of the requested segment
+	pub identifier: SegmentIdentifier,
+}
+
+impl Writeable for SegmentRequest {
+	fn write<W: Writer>(&self, writer: &mut W) -> Result<(), ser::Error> {
+		self.block_hash.write(writer)?;
+		self.identifier.write(writer)
+	}
+}
+
+impl Readable for SegmentRequest {
+	fn read<R: Reader>(reader: &mut R) -> Result<SegmentRequest, ser::Error> {
+		let block_hash = Hash::read(reader)?;
+		let identifier = SegmentIdentifier::read(reader)?;
+		Ok(SegmentRequest {
+			block_hash,
+			identifier,
+		})
+	}
+}
+
+/// Response to a segment request.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SegmentResponse<T> {
+	/// Hash of the block the segment belongs to
+	pub block_hash: Hash,
+	/// The MMR segment
+	pub segment: Segment<T>,
+}
+
+impl<T: Writeable> Writeable for SegmentResponse<T> {
+	fn write<W: Writer>(&self, writer: &mut W) -> Result<(), ser::Error> {
+		self.block_hash.write(writer)?;
+		self.segment.write(writer)
+	}
+}
+
+impl<T: Readable> Readable for SegmentResponse<T> {
+	fn read<R: Reader>(reader: &mut R) -> Result<SegmentResponse<T>, ser::Error> {
+		let block_hash = Hash::read(reader)?;
+		let segment = Segment::read(reader)?;
+		Ok(SegmentResponse {
+			block_hash,
+			segment,
+		})
+	}
+}
+
+/// Response to an output segment request. Also carries the output roots, so
+/// the segment can be validated against the header output root, and the
+/// positions of the spent outputs the segment still has the data of.
+#[derive(Debug, Clone, PartialEq)]
+pub struct OutputSegmentResponse {
+	/// The segment response
+	pub response: SegmentResponse<OutputIdentifier>,
+	/// Root of the output PMMR
+	pub output_root: Hash,
+	/// Root of the output bitmap accumulator
+	pub bitmap_root: Hash,
+	/// Positions of the spent outputs in the segment
+	pub spent: Vec<u64>,
+	/// Merkle proof of the bitmap chunks covering the segment
+	pub bitmap_proof: Vec<Hash>,
+}
+
+impl OutputSegmentResponse {
+	/// The output roots of this response.
+	pub fn output_roots(&self) -> OutputRoots {
+		OutputRoots {
+			pmmr_root: self.output_root,
+			bitmap_root: self.bitmap_root,
+		}
+	}
+}
+
+impl Writeable for OutputSegmentResponse {
+	fn write<W: Writer>(&self, writer: &mut W) -> Result<(), ser::Error> {
+		self.response.write(writer)?;
+		self.output_root.write(writer)?;
+		self.bitmap_root.write(writer)?;
+		writer.write_u64(self.spent.len() as u64)?;
+		for pos in &self.spent {
+			writer.write_u64(*pos)?;
+		}
+		writer.write_u64(self.bitmap_proof.len() as u64)?;
+		for hash in &self.bitmap_proof {
+			hash.write(writer)?;
+		}
+		Ok(())
+	}
+}
+
+impl Readable for OutputSegmentResponse {
+	fn read<R: Reader>(reader: &mut R) -> Result<OutputSegmentResponse, ser::Error> {
+		let response = SegmentResponse::read(reader)?;
+		let output_root = Hash::read(reader)?;
+		let bitmap_root = Hash::read(reader)?;
+		let n_spent = reader.read_u64()?;
+		if n_spent > response.segment.leaves_len() as u64 {
+			return Err(ser::Error::TooLargeReadErr(format!(
+				"too many spent outputs in segment, {}",
+				n_spent
+			)));
+		}
+		let mut spent = Vec::with_capacity(n_spent as usize);
+		for _ in 0..n_spent {
+			spent.push(reader.read_u64()?);
+		}
+		let n_proof = reader.read_u64()?;
+		if n_proof > 128 {
+			return Err(ser::Error::TooLargeReadErr(format!(
+				"bitmap proof too long, {}",
+				n_proof
+			)));
+		}
+		let bitmap_proof = ser::read_multi(reader, n_proof)?;
+		Ok(OutputSegmentResponse {
+			response,
+			output_root,
+			bitmap_root,
+			spent,
+			bitmap_proof,
+		})
+	}
+}
+
+/// Request for the output (spent or not) at the provided output MMR position.
+#[derive(Debug, Clone, PartialEq)]
+pub struct HistoricalOutputRequest {
+	/// Output MMR position
+	pub pos: u64,
+}
+
+impl Writeable for HistoricalOutputRequest {
+	fn write<W: Writer>(&self, writer: &mut W) -> Result<(), ser::Error> {
+		writer.write_u64(self.pos)
+	}
+}
+
+impl Readable for HistoricalOutputRequest {
+	fn read<R: Reader>(reader: &mut R) -> Result<HistoricalOutputRequest, ser::Error> {
+		Ok(HistoricalOutputRequest {
+			pos: reader.read_u64()?,
+		})
+	}
+}
+
+/// Response to a historical output request.
+#[derive(Debug, Clone, PartialEq)]
+pub struct HistoricalOutput {
+	/// Output MMR position
+	pub pos: u64,
+	/// Height of the block that created the output
+	pub height: u64,
+	/// Whether the output is spent
+	pub spent: bool,
+	/// The output, with its rangeproof
+	pub output: Output,
+}
+
+impl Writeable for HistoricalOutput {
+	fn write<W: Writer>(&self, writer: &mut W) -> Result<(), ser::Error> {
+		ser_multiwrite!(
+			writer,
+			[write_u64, self.pos],
+			[write_u64, self.height],
+			[write_u8, self.spent as u8]
+		);
+		self.output.write(writer)
+	}
+}
+
+impl Readable for HistoricalOutput {
+	fn read<R: Reader>(reader: &mut R) -> Result<HistoricalOutput, ser::Error> {
+		let (pos, height, spent) = ser_multiread!(reader, read_u64, read_u64, read_u8);
+		let output = Output::read(reader)?;
+		Ok(HistoricalOutput {
+			pos,
+			height,
+			spent: spent != 0,
+			output,
+		})
+	}
+}
+
+/// Second round of a compact block hydration, request for the transactions
+/// matching the kernel short_ids the receiver didn't find in its pool.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BlockTransactionsRequest {
+	/// Hash of the compact block
+	pub block_hash: Hash,
+	/// Nonce of the compact block, the short_ids are based on it
+	pub nonce: u64,
+	/// Short_ids of the missing kernels
+	pub kern_ids: Vec<ShortId>,
+}
+
+impl Writeable for BlockTransactionsRequest {
+	fn write<W: Writer>(&self, writer: &mut W) -> Result<(), ser::Error> {
+		self.block_hash.write(writer)?;
+		ser_multiwrite!(
+			writer,
+			[write_u64, self.nonce],
+			[write_u64, self.kern_ids.len() as u64]
+		);
+		self.kern_ids.write(writer)
+	}
+}
+
+impl Readable for BlockTransactionsRequest {
+	fn read<R: Reader>(reader: &mut R) -> Result<BlockTransactionsRequest, ser::Error> {
+		let block_hash = Hash::read(reader)?;
+		let (nonce, len) = ser_multiread!(reader, read_u64, read_u64);
+		if len > ser::READ_VEC_SIZE_LIMIT {
+			return Err(ser::Error::TooLargeReadErr(format!(
+				"BlockTransactionsRequest has too many kernel ids: {}",
+				len
+			)));
+		}
+		let kern_ids = ser::read_multi(reader, len)?;
+		Ok(BlockTransactionsRequest {
+			block_hash,
+			nonce,
+			kern_ids,
+		})
+	}
+}
+
+/// Transactions requested to hydrate a compact block.
+#[derive(Debug, Clone)]
+pub struct BlockTransactions {
+	/// Hash of the compact block
+	pub block_hash: Hash,
+	/// Transactions matching the requested kernel short_ids
+	pub txs: Vec<Transaction>,
+}
+
+impl Writeable for BlockTransactions {
+	fn write<W: Writer>(&self, writer: &mut W) -> Result<(), ser::Error> {
+		self.block_hash.write(writer)?;
+		writer.write_u64(self.txs.len() as u64)?;
+		self.txs.write(writer)
+	}
+}
+
+impl Readable for BlockTransactions {
+	fn read<R: Reader>(reader: &mut R) -> Result<BlockTransactions, ser::Error> {
+		let block_hash = Hash::read(reader)?;
+		let len = reader.read_u64()?;
+		if len > ser::READ_VEC_SIZE_LIMIT {
+			return Err(ser::Error::TooLargeReadErr(format!(
+				"BlockTransactions has too many txs: {}",
+				len
+			)));
+		}
+		let txs = ser::read_multi(reader, len)?;
+		Ok(BlockTransactions { block_hash, txs })
+	}
+}
@@ -24,23 +24,35 @@ use std::sync::Arc;
 use lru_cache::LruCache;
 
 use crate::chain;
+use crate::chain::types::OutputRoots;
 use crate::conn;
 use crate::core::core::hash::{Hash, Hashed};
+use crate::core::core::pmmr::{Segment, SegmentIdentifier};
+use crate::core::core::{OutputIdentifier, ShortId, TxKernel};
 use crate::core::pow::Difficulty;
 use crate::core::ser::Writeable;
 use crate::core::{core, global};
 use crate::handshake::Handshake;
-use crate::msg::{self, BanReason, GetPeerAddrs, Locator, Msg, Ping, TxHashSetRequest, Type};
-use crate::protocol::Protocol;
+use crate::msg::{
+	self, BanReason, BlockTransactionsRequest, GetPeerAddrs, HistoricalOutput,
+	HistoricalOutputRequest, Locator, Msg, Ping, SegmentRequest, TxHashSetPartRequest,
+	TxHashSetRequest, Type,
+};
+use crate::protocol::{self, Protocol};
+use crate::tx_relay_limiter::{TxRelayLimiter, TxRelayStats};
 use crate::types::{
 	Capabilities, ChainAdapter, Error, NetAdapter, P2PConfig, PeerAddr, PeerInfo, ReasonForBan,
 	TxHashSetRead,
 };
+use crate::util::secp::pedersen::RangeProof;
 use chrono::prelude::{DateTime, Utc};
-use std::time::Instant;
+use std::time::{Duration, Instant};
 
 const MAX_TRACK_SIZE: usize = 30;
 const MAX_PEER_MSG_PER_MIN: u64 = 500;
+/// Connections with less traffic than that are idle enough for a latency probe,
+/// the pong would be queued behind the other messages otherwise.
+const LATENCY_PROBE_MAX_BYTES_PER_MIN: u64 = 100_000;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 /// Remind: don't mix up this 'State' with that 'State' in p2p/src/store.rs,
@@ -64,6 +76,8 @@ pub struct Peer {
 	stop_handle: Mutex<conn::StopHandle>,
 	// Whether or not we requested a txhashset from this peer
 	state_sync_requested: Arc<AtomicBool>,
+	// Quotas of the txs relayed to us by this peer
+	tx_relay: Arc<Mutex<TxRelayLimiter>>,
 }
 
 impl fmt::Debug for Peer {
@@ -84,10 +98,15 @@ impl Peer {
 		let state = Arc::new(RwLock::new(State::Connected));
 		let state_sync_requested = Arc::new(AtomicBool::new(false));
 		let tracking_adapter = TrackingAdapter::new(adapter);
+		let tx_relay = Arc::new(Mutex::new(TxRelayLimiter::from_config(
+			&server.config,
+			Utc::now().timestamp_millis(),
+		)));
 		let handler = Protocol::new(
 			Arc::new(tracking_adapter.clone()),
 			info.clone(),
 			state_sync_requested.clone(),
+			tx_relay.clone(),
 			header_cache_size,
 			server,
 		);
@@ -103,6 +122,7 @@ impl Peer {
 			send_handle,
 			stop_handle,
 			state_sync_requested,
+			tx_relay,
 		})
 	}
 
@@ -259,6 +279,11 @@ impl Peer {
 		Some((sent_bytes.count_per_min(), received_bytes.count_per_min()))
 	}
 
+	/// Counters of the tx messages this peer relayed to us
+	pub fn tx_relay_stats(&self) -> TxRelayStats {
+		self.tx_relay.lock().stats()
+	}
+
 	/// Set this peer status to banned
 	pub fn set_banned(&self) {
 		*self.state.write() = State::Banned;
@@ -277,6 +302,11 @@ impl Peer {
 			total_difficulty,
 			height,
 		};
+		let traffic = self.tracker.sent_bytes.read().bytes_per_min()
+			+ self.tracker.received_bytes.read().bytes_per_min();
+		if traffic < LATENCY_PROBE_MAX_BYTES_PER_MIN {
+			self.info.ping_sent();
+		}
 		self.send(ping_msg, msg::Type::Ping)
 	}
 
@@ -410,12 +440,109 @@ impl Peer {
 			self.info.addr, height, hash
 		);
 		self.state_sync_requested.store(true, Ordering::Relaxed);
+		if self
+			.info
+			.capabilities
+			.contains(Capabilities::TXHASHSET_RANGE)
+		{
+			// Resume a previous download of this txhashset, if any.
+			let offset =
+				protocol::txhashset_part_offset(&self.tracking_adapter.get_tmp_dir(), &hash);
+			if offset > 0 {
+				info!("Resuming txhashset archive download at {} bytes.", offset);
+			}
+			return self.send(
+				&TxHashSetPartRequest {
+					hash,
+					height,
+					offset,
+					length: protocol::TXHASHSET_PART_SIZE,
+				},
+				msg::Type::GetTxHashSetPart,
+			);
+		}
 		self.send(
 			&TxHashSetRequest { hash, height },
 			msg::Type::TxHashSetRequest,
 		)
 	}
 
+	/// Sends a request for an output segment of the txhashset archive header.
+	pub fn send_output_segment_request(
+		&self,
+		block_hash: Hash,
+		identifier: SegmentIdentifier,
+	) -> Result<(), Error> {
+		self.send(
+			&SegmentRequest {
+				block_hash,
+				identifier,
+			},
+			msg::Type::GetOutputSegment,
+		)
+	}
+
+	/// Sends a request for a rangeproof segment of the txhashset archive header.
+	pub fn send_rangeproof_segment_request(
+		&self,
+		block_hash: Hash,
+		identifier: SegmentIdentifier,
+	) -> Result<(), Error> {
+		self.send(
+			&SegmentRequest {
+				block_hash,
+				identifier,
+			},
+			msg::Type::GetRangeProofSegment,
+		)
+	}
+
+	/// Sends a request for a kernel segment of the txhashset archive header.
+	pub fn send_kernel_segment_request(
+		&self,
+		block_hash: Hash,
+		identifier: SegmentIdentifier,
+	) -> Result<(), Error> {
+		self.send(
+			&SegmentRequest {
+				block_hash,
+				identifier,
+			},
+			msg::Type::GetKernelSegment,
+		)
+	}
+
+	/// Asks for the transactions we are missing to hydrate a compact block.
+	pub fn send_block_transactions_request(
+		&self,
+		block_hash: Hash,
+		nonce: u64,
+		kern_ids: Vec<ShortId>,
+	) -> Result<(), Error> {
+		debug!(
+			"Requesting {} txs of compact block {} from {}",
+			kern_ids.len(),
+			block_hash,
+			self.info.addr
+		);
+		self.send(
+			&BlockTransactionsRequest {
+				block_hash,
+				nonce,
+				kern_ids,
+			},
+			msg::Type::GetBlockTransactions,
+		)
+	}
+
+	/// Asks an archive peer for the output at the provided output MMR position.
+	pub fn send_historical_output_request(&self, pos: u64) -> Result<(), Error> {
+		self.send(
+			&HistoricalOutputRequest { pos },
+			msg::Type::GetHistoricalOutput,
+		)
+	}
+
 	/// Stops the peer
 	pub fn stop(&self) {
 		debug!("Stopping peer {:?}", self.info.addr);
@@ -499,6 +626,7 @@ impl ChainAdapter for TrackingAdapter {
 		&self,
 		tx: core::Transaction,
 		stem: bool,
+		peer_info: &PeerInfo,
 	) -> Result<bool, chain::Error> {
 		// Do not track the tx hash for stem txs.
 		// Otherwise we fail to handle the subsequent fluff or embargo expiration
@@ -507,7 +635,7 @@ impl ChainAdapter for TrackingAdapter {
 			let kernel = &tx.kernels()[0];
 			self.push_recv(kernel.hash());
 		}
-		self.adapter.transaction_received(tx, stem)
+		self.adapter.transaction_received(tx, stem, peer_info)
 	}
 
 	fn block_received(
@@ -638,6 +766,101 @@ impl ChainAdapter for TrackingAdapter {
 			.txhashset_download_update(start_time, downloaded_size, total_size)
 	}
 
+	fn get_output_segment(
+		&self,
+		hash: Hash,
+		id: SegmentIdentifier,
+	) -> Result<(Segment<OutputIdentifier>, Vec<u64>, Vec<Hash>, OutputRoots), chain::Error> {
+		self.adapter.get_output_segment(hash, id)
+	}
+
+	fn get_rangeproof_segment(
+		&self,
+		hash: Hash,
+		id: SegmentIdentifier,
+	) -> Result<Segment<RangeProof>, chain::Error> {
+		self.adapter.get_rangeproof_segment(hash, id)
+	}
+
+	fn get_kernel_segment(
+		&self,
+		hash: Hash,
+		id: SegmentIdentifier,
+	) -> Result<Segment<TxKernel>, chain::Error> {
+		self.adapter.get_kernel_segment(hash, id)
+	}
+
+	fn receive_output_segment(
+		&self,
+		block_hash: Hash,
+		segment: Segment<OutputIdentifier>,
+		spent: Vec<u64>,
+		bitmap_proof: Vec<Hash>,
+		output_roots: OutputRoots,
+		peer_info: &PeerInfo,
+	) -> Result<bool, chain::Error> {
+		self.adapter.receive_output_segment(
+			block_hash,
+			segment,
+			spent,
+			bitmap_proof,
+			output_roots,
+			peer_info,
+		)
+	}
+
+	fn receive_rangeproof_segment(
+		&self,
+		block_hash: Hash,
+		segment: Segment<RangeProof>,
+		peer_info: &PeerInfo,
+	) -> Result<bool, chain::Error> {
+		self.adapter
+			.receive_rangeproof_segment(block_hash, segment, peer_info)
+	}
+
+	fn receive_kernel_segment(
+		&self,
+		block_hash: Hash,
+		segment: Segment<TxKernel>,
+		peer_info: &PeerInfo,
+	) -> Result<bool, chain::Error> {
+		self.adapter
+			.receive_kernel_segment(block_hash, segment, peer_info)
+	}
+
+	fn get_historical_output(&self, pos: u64) -> Option<HistoricalOutput> {
+		self.adapter.get_historical_output(pos)
+	}
+
+	fn historical_output_received(
+		&self,
+		output: HistoricalOutput,
+		peer_info: &PeerInfo,
+	) -> Result<bool, chain::Error> {
+		self.adapter.historical_output_received(output, peer_info)
+	}
+
+	fn get_block_transactions(
+		&self,
+		block_hash: Hash,
+		nonce: u64,
+		kern_ids: &[ShortId],
+	) -> Vec<core::Transaction> {
+		self.adapter
+			.get_block_transactions(block_hash, nonce, kern_ids)
+	}
+
+	fn block_transactions_received(
+		&self,
+		block_hash: Hash,
+		txs: Vec<core::Transaction>,
+		peer_info: &PeerInfo,
+	) -> Result<bool, chain::Error> {
+		self.adapter
+			.block_transactions_received(block_hash, txs, peer_info)
+	}
+
 	fn get_tmp_dir(&self) -> PathBuf {
 		self.adapter.get_tmp_dir()
 	}
@@ -660,6 +883,10 @@ impl NetAdapter for TrackingAdapter {
 		self.adapter.peer_difficulty(addr, diff, height)
 	}
 
+	fn peer_latency(&self, addr: PeerAddr, latency: Duration) {
+		self.adapter.peer_latency(addr, latency)
+	}
+
 	fn is_banned(&self, addr: PeerAddr) -> bool {
 		self.adapter.is_banned(addr)
 	}
@@ -0,0 +1,144 @@
+// Copyright 2021 The MWC Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Dynamic targets of the peer connection counts. A static max count either wastes a
+//! big server or overloads a small board, so when enabled the targets follow the load of
+//! the node: they are cut quickly when the CPU, the validation backlog or the bandwidth
+//! is over its limit, and grow slowly back to the configured max counts while there is
+//! headroom. The bandwidth limit also caps the total number of the connections, based on
+//! the measured bandwidth per peer.
+
+use crate::types::{P2PConfig, PeerTargetsConfig};
+use std::cmp;
+use std::sync::atomic::{AtomicU32, Ordering};
+
+/// Inbound target growth per update
+const INBOUND_STEP: u32 = 4;
+/// Outbound target growth per update
+const OUTBOUND_STEP: u32 = 1;
+/// Targets grow only while the CPU load is this many percent below its limit
+const CPU_HEADROOM: u8 = 15;
+/// Share (percent) of the bandwidth limit the connections are planned for
+const BANDWIDTH_USAGE_PERCENT: u64 = 80;
+
+/// Load of the node, measured by the caller
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PeerLoad {
+	/// Bandwidth used by the peer connections (sent and received), bytes per second
+	pub bandwidth: u64,
+	/// Number of the connected peers
+	pub peer_count: u32,
+	/// CPU load, percent
+	pub cpu_load: u8,
+	/// Number of the blocks waiting for validation
+	pub validation_backlog: usize,
+}
+
+/// Current targets of the inbound and outbound connection counts
+pub struct PeerTargets {
+	config: PeerTargetsConfig,
+	max_inbound: u32,
+	max_outbound: u32,
+	inbound: AtomicU32,
+	outbound: AtomicU32,
+}
+
+impl PeerTargets {
+	/// Targets starting at the configured max counts
+	pub fn from_config(config: &P2PConfig) -> PeerTargets {
+		let max_inbound = config.peer_max_inbound_count();
+		let max_outbound = config.peer_max_outbound_count();
+		PeerTargets {
+			config: config.peer_targets.clone(),
+			max_inbound,
+			max_outbound,
+			inbound: AtomicU32::new(max_inbound),
+			outbound: AtomicU32::new(max_outbound),
+		}
+	}
+
+	/// Whether the targets follow the load
+	pub fn is_enabled(&self) -> bool {
+		self.config.auto_adjust_peers
+	}
+
+	/// Current target of the inbound connections
+	pub fn inbound(&self) -> u32 {
+		self.inbound.load(Ordering::Relaxed)
+	}
+
+	/// Current target of the outbound connections
+	pub fn outbound(&self) -> u32 {
+		self.outbound.load(Ordering::Relaxed)
+	}
+
+	/// Adjust the targets to the load. Returns whether the targets changed.
+	pub fn update(&self, load: &PeerLoad) -> bool {
+		if !self.is_enabled() {
+			return false;
+		}
+		let min_inbound = cmp::min(self.config.min_inbound_target, self.max_inbound);
+		let min_outbound = cmp::min(self.config.min_outbound_target, self.max_outbound);
+		let inbound = self.inbound();
+		let outbound = self.outbound();
+
+		let over_bandwidth = self
+			.config
+			.peer_bandwidth_limit
+			.map_or(false, |limit| load.bandwidth > limit);
+		let overloaded = load.cpu_load > self.config.max_cpu_load
+			|| load.validation_backlog > self.config.max_validation_backlog
+			|| over_bandwidth;
+
+		let (new_inbound, new_outbound) = if overloaded {
+			// Back off quickly, the inbound connections are the cheapest to lose
+			(
+				cmp::max(min_inbound, inbound - inbound / 4),
+				cmp::max(min_outbound, outbound.saturating_sub(OUTBOUND_STEP)),
+			)
+		} else if load.cpu_load.saturating_add(CPU_HEADROOM) <= self.config.max_cpu_load
+			&& load.validation_backlog == 0
+		{
+			// Number of the connections the bandwidth limit allows at the current per peer usage
+			let capacity = match self.config.peer_bandwidth_limit {
+				Some(limit) if load.bandwidth > 0 && load.peer_count > 0 => {
+					let per_peer = cmp::max(1, load.bandwidth / load.peer_count as u64);
+					(limit.saturating_mul(BANDWIDTH_USAGE_PERCENT) / 100 / per_peer)
+						.min(u32::MAX as u64) as u32
+				}
+				_ => u32::MAX,
+			};
+			// Outbound connections first, they are the ones we chose
+			let mut room = capacity.saturating_sub(inbound + outbound);
+			let new_outbound =
+				cmp::min(self.max_outbound, outbound + cmp::min(OUTBOUND_STEP, room));
+			room -= new_outbound - outbound;
+			let new_inbound = cmp::min(self.max_inbound, inbound + cmp::min(INBOUND_STEP, room));
+			(new_inbound, new_outbound)
+		} else {
+			(inbound, outbound)
+		};
+
+		if new_inbound == inbound && new_outbound == outbound {
+			return false;
+		}
+		info!(
+			"peer targets: inbound {} -> {}, outbound {} -> {} ({:?})",
+			inbound, new_inbound, outbound, new_outbound, load
+		);
+		self.inbound.store(new_inbound, Ordering::Relaxed);
+		self.outbound.store(new_outbound, Ordering::Relaxed);
+		true
+	}
+}
@@ -13,25 +13,35 @@
 // limitations under the License.
 
 use crate::util::RwLock;
-use std::collections::HashMap;
+use std::cmp;
+use std::collections::{HashMap, HashSet};
 use std::fs::File;
 use std::path::PathBuf;
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::Arc;
 
 use rand::seq::SliceRandom;
 use rand::thread_rng;
 
 use crate::chain;
+use crate::chain::types::OutputRoots;
 use crate::core::core;
 use crate::core::core::hash::{Hash, Hashed};
+use crate::core::core::pmmr::{Segment, SegmentIdentifier};
+use crate::core::core::{OutputIdentifier, ShortId, TxKernel};
 use crate::core::global;
 use crate::core::pow::Difficulty;
+use crate::libp2p_connection::{Libp2pBanList, Libp2pTopicStore};
+use crate::msg::HistoricalOutput;
 use crate::peer::Peer;
-use crate::store::{PeerData, PeerStore, State};
+use crate::peer_targets::{PeerLoad, PeerTargets};
+use crate::store::{GossipTopicData, PeerData, PeerStore, State};
 use crate::types::{
-	Capabilities, ChainAdapter, Error, NetAdapter, P2PConfig, PeerAddr, PeerInfo, ReasonForBan,
-	TxHashSetRead, MAX_PEER_ADDRS,
+	Capabilities, ChainAdapter, Error, NetAdapter, P2PConfig, PeerAddr, PeerDiversityReport,
+	PeerFilter, PeerGroupConfig, PeerInfo, PeerStoreStats, ReasonForBan, TxHashSetRead,
+	MAX_PEER_ADDRS,
 };
+use crate::util::secp::pedersen::RangeProof;
 use chrono::prelude::*;
 use chrono::Duration;
 use grin_util::StopState;
@@ -44,6 +54,10 @@ pub struct Peers {
 	peers: RwLock<HashMap<PeerAddr, Arc<Peer>>>,
 	config: P2PConfig,
 	stop_state: Arc<StopState>,
+	store_stats: RwLock<PeerStoreStats>,
+	/// Size of the peer storage since the last pruning plus the saved received addresses
+	store_size: AtomicUsize,
+	targets: PeerTargets,
 }
 
 impl Peers {
@@ -53,12 +67,16 @@ impl Peers {
 		config: P2PConfig,
 		stop_state: Arc<StopState>,
 	) -> Peers {
+		let store_size = store.peer_count().unwrap_or(0) as usize;
 		Peers {
 			adapter,
 			store,
+			targets: PeerTargets::from_config(&config),
 			config,
 			peers: RwLock::new(HashMap::new()),
 			stop_state,
+			store_stats: RwLock::new(PeerStoreStats::default()),
+			store_size: AtomicUsize::new(store_size),
 		}
 	}
 
@@ -73,6 +91,15 @@ impl Peers {
 		if self.is_banned(peer.info.addr.clone()) {
 			return Err(Error::Banned);
 		}
+		// Keep the latency we measured during the previous connections.
+		let latency_ms = self
+			.get_peer(peer.info.addr.clone())
+			.ok()
+			.and_then(|p| p.latency_ms);
+		if let Some(latency_ms) = latency_ms {
+			peer.info
+				.init_latency(std::time::Duration::from_millis(latency_ms as u64));
+		}
 		let peer_data = PeerData {
 			addr: peer.info.addr.clone(),
 			capabilities: peer.info.capabilities,
@@ -81,6 +108,7 @@ impl Peers {
 			last_banned: 0,
 			ban_reason: ReasonForBan::None,
 			last_connected: Utc::now().timestamp(),
+			latency_ms,
 		};
 		debug!("Saving newly connected peer {}.", peer_data.addr);
 		self.save_peer(&peer_data)?;
@@ -100,6 +128,7 @@ impl Peers {
 			last_banned: Utc::now().timestamp(),
 			ban_reason,
 			last_connected: Utc::now().timestamp(),
+			latency_ms: None,
 		};
 		debug!("Banning peer {}, ban_reason={:?}", addr, ban_reason);
 		self.save_peer(&peer_data)
@@ -138,6 +167,33 @@ impl Peers {
 		res
 	}
 
+	/// Connected peers that match the filter, sorted by address so the list can be paged.
+	/// The banned peers that are not disconnected yet are included only when the filter
+	/// asks for the banned peers.
+	pub fn filtered_peers(&self, filter: &PeerFilter) -> Vec<Arc<Peer>> {
+		let peers = match self.peers.try_read_for(LOCK_TIMEOUT) {
+			Some(peers) => peers,
+			None => {
+				if !self.stop_state.is_stopped() {
+					// When stopped, peers access is locked by stopped thread
+					error!("filtered_peers: failed to get peers lock");
+				}
+				return vec![];
+			}
+		};
+		let mut res = peers
+			.values()
+			.filter(|p| {
+				let banned = p.is_banned();
+				let live = p.is_connected() || (banned && filter.banned == Some(true));
+				live && filter.matches(&p.info, banned)
+			})
+			.cloned()
+			.collect::<Vec<_>>();
+		res.sort_by_cached_key(|p| p.info.addr.to_string());
+		res
+	}
+
 	/// Get vec of peers we currently have an outgoing connection with.
 	pub fn outgoing_connected_peers(&self) -> Vec<Arc<Peer>> {
 		self.connected_peers()
@@ -302,13 +358,86 @@ impl Peers {
 		}
 	}
 
-	fn broadcast<F>(&self, obj_name: &str, inner: F) -> u32
+	/// Whether the relay policy of the peer group allows sending the transactions
+	/// (or the blocks and headers) to the peer.
+	pub fn relays_to(&self, peer: &Peer, txs: bool) -> bool {
+		match self.config.peer_group(&peer.info.addr) {
+			Some(group) if txs => group.relay_txs,
+			Some(group) => group.relay_blocks,
+			None => true,
+		}
+	}
+
+	fn is_in_group(&self, addr: &PeerAddr, group: &PeerGroupConfig) -> bool {
+		self.config
+			.peer_group(addr)
+			.map_or(false, |g| std::ptr::eq(g, group))
+	}
+
+	/// Whether the peer is in a group that has all its max connections taken.
+	pub fn is_group_full(&self, addr: &PeerAddr) -> bool {
+		let group = match self.config.peer_group(addr) {
+			Some(group) => group,
+			None => return false,
+		};
+		match group.max_connections {
+			Some(max) => {
+				let connected = self
+					.connected_peers()
+					.iter()
+					.filter(|p| self.is_in_group(&p.info.addr, group))
+					.count();
+				connected >= max as usize
+			}
+			None => false,
+		}
+	}
+
+	/// Not connected members of the peer groups that are below their min connections.
+	pub fn group_connect_candidates(&self) -> Vec<PeerAddr> {
+		let groups = match self.config.peer_groups {
+			Some(ref groups) => groups,
+			None => return vec![],
+		};
+		let connected: Vec<PeerAddr> = self
+			.connected_peers()
+			.iter()
+			.map(|p| p.info.addr.clone())
+			.collect();
+		let mut res = vec![];
+		for group in groups {
+			let members: Vec<_> = group
+				.members()
+				.iter()
+				.filter(|a| self.is_in_group(a, group))
+				.collect();
+			let connected_count = members.iter().filter(|a| connected.contains(a)).count();
+			if connected_count < group.min_connections as usize {
+				debug!(
+					"peer group {}: {} connected, {} required",
+					group.name, connected_count, group.min_connections
+				);
+				res.extend(
+					members
+						.into_iter()
+						.filter(|a| !connected.contains(a))
+						.cloned(),
+				);
+			}
+		}
+		res
+	}
+
+	fn broadcast<F>(&self, obj_name: &str, txs: bool, inner: F) -> u32
 	where
 		F: Fn(&Peer) -> Result<bool, Error>,
 	{
 		let mut count = 0;
 
 		for p in self.connected_peers().iter() {
+			if !self.relays_to(p, txs) {
+				continue;
+			}
 			match inner(&p) {
 				Ok(true) => count += 1,
 				Ok(false) => (),
@@ -336,7 +465,7 @@ impl Peers {
 	/// Broadcast a compact block to all our connected peers.
 	/// This is only used when initially broadcasting a newly mined block.
 	pub fn broadcast_compact_block(&self, b: &core::CompactBlock) {
-		let count = self.broadcast("compact block", |p| p.send_compact_block(b));
+		let count = self.broadcast("compact block", false, |p| p.send_compact_block(b));
 		debug!(
 			"broadcast_compact_block: {}, {} at {}, to {} peers, done.",
 			b.hash(),
@@ -350,7 +479,7 @@ impl Peers {
 	/// A peer implementation may drop the broadcast request
 	/// if it knows the remote peer already has the header.
 	pub fn broadcast_header(&self, bh: &core::BlockHeader) {
-		let count = self.broadcast("header", |p| p.send_header(bh));
+		let count = self.broadcast("header", false, |p| p.send_header(bh));
 		debug!(
 			"broadcast_header: {}, {} at {}, to {} peers, done.",
 			bh.hash(),
@@ -364,7 +493,7 @@ impl Peers {
 	/// A peer implementation may drop the broadcast request
 	/// if it knows the remote peer already has the transaction.
 	pub fn broadcast_transaction(&self, tx: &core::Transaction) {
-		let count = self.broadcast("transaction", |p| p.send_transaction(tx));
+		let count = self.broadcast("transaction", true, |p| p.send_transaction(tx));
 		debug!(
 			"broadcast_transaction: {} to {} peers, done.",
 			tx.hash(),
@@ -391,6 +520,16 @@ impl Peers {
 		}
 	}
 
+	/// Latency, address class and user agent mix of our connected peers.
+	pub fn diversity_report(&self) -> PeerDiversityReport {
+		let peers: Vec<_> = self
+			.connected_peers()
+			.iter()
+			.map(|p| p.info.clone())
+			.collect();
+		PeerDiversityReport::from_peers(&peers)
+	}
+
 	/// All peer information we have in storage
 	pub fn all_peers(&self) -> Vec<PeerData> {
 		match self.store.all_peers() {
@@ -446,6 +585,14 @@ impl Peers {
 	) {
 		let mut rm = vec![];
 
+		// Members of the groups with min connections are kept like the preferred peers.
+		let mut preferred_peers = preferred_peers.to_vec();
+		if let Some(ref groups) = self.config.peer_groups {
+			for group in groups.iter().filter(|g| g.min_connections > 0) {
+				preferred_peers.extend_from_slice(group.members());
+			}
+		}
+
 		// build a list of peers to be cleaned up
 		{
 			let peers = match self.peers.try_read_for(LOCK_TIMEOUT) {
@@ -487,6 +634,37 @@ impl Peers {
 			}
 		}
 
+		// drop the connections above the max connections of the peer groups
+		if let Some(ref groups) = self.config.peer_groups {
+			let connected = self.connected_peers();
+			for group in groups {
+				let max = match group.max_connections {
+					Some(max) => max as usize,
+					None => continue,
+				};
+				let members: Vec<_> = connected
+					.iter()
+					.filter(|p| self.is_in_group(&p.info.addr, group))
+					.filter(|p| !rm.contains(&p.info.addr))
+					.collect();
+				if members.len() > max {
+					debug!(
+						"clean_peers: peer group {} has {} connections, max {}",
+						group.name,
+						members.len(),
+						max
+					);
+					let mut addrs: Vec<_> = members
+						.iter()
+						.filter(|p| !preferred_peers.contains(&p.info.addr))
+						.take(members.len() - max)
+						.map(|p| p.info.addr.clone())
+						.collect();
+					rm.append(&mut addrs);
+				}
+			}
+		}
+
 		// check here to make sure we don't have too many outgoing connections
 		let excess_outgoing_count =
 			(self.peer_outbound_count() as usize).saturating_sub(max_outbound_count);
@@ -543,32 +721,233 @@ impl Peers {
 
 	/// We have enough outbound connected peers
 	pub fn enough_outbound_peers(&self) -> bool {
-		self.peer_outbound_count() >= self.config.peer_min_preferred_outbound_count()
+		let min_count = self.config.peer_min_preferred_outbound_count();
+		if self.targets.is_enabled() {
+			self.peer_outbound_count() >= cmp::min(min_count, self.targets.outbound())
+		} else {
+			self.peer_outbound_count() >= min_count
+		}
+	}
+
+	/// Dynamic targets of the connection counts
+	pub fn peer_targets(&self) -> &PeerTargets {
+		&self.targets
+	}
+
+	/// Max number of the inbound connections, the current target if the targets are dynamic
+	pub fn max_inbound_count(&self) -> u32 {
+		self.targets.inbound()
+	}
+
+	/// Max number of the outbound connections, the current target if the targets are dynamic
+	pub fn max_outbound_count(&self) -> u32 {
+		self.targets.outbound()
+	}
+
+	/// Adjust the connection targets to the load of the node. The bandwidth is measured
+	/// over the connected peers, the CPU load (percent) and the number of the blocks
+	/// waiting for validation are provided by the caller.
+	pub fn update_peer_targets(&self, cpu_load: u8, validation_backlog: usize) -> bool {
+		let peers = self.connected_peers();
+		let bytes_per_min: u64 = peers
+			.iter()
+			.map(|p| {
+				p.last_min_sent_bytes().unwrap_or(0) + p.last_min_received_bytes().unwrap_or(0)
+			})
+			.sum();
+		self.targets.update(&PeerLoad {
+			bandwidth: bytes_per_min / 60,
+			peer_count: peers.len() as u32,
+			cpu_load,
+			validation_backlog,
+		})
 	}
 
-	/// Removes those peers that seem to have expired
-	pub fn remove_expired(&self) {
+	/// Applies the peer storage retention policies: removes the expired defunct peers
+	/// and the peers we never connected to, then evicts the least useful peers
+	/// if the storage is still above its max size. Connected and banned peers are kept.
+	pub fn prune_store(&self) {
 		let now = Utc::now();
+		let connected: HashSet<PeerAddr> = self
+			.connected_peers()
+			.iter()
+			.map(|p| p.info.addr.clone())
+			.collect();
 
 		// Delete defunct peers from storage
-		let _ = self.store.delete_peers(|peer| {
-			let diff = now - Utc.timestamp(peer.last_connected, 0);
+		let evicted_expired = self
+			.store
+			.delete_peers(|peer| {
+				let diff = now - Utc.timestamp(peer.last_connected, 0);
 
-			let should_remove = peer.flags == State::Defunct
-				&& diff > Duration::seconds(global::PEER_EXPIRATION_REMOVE_TIME);
+				let should_remove = peer.flags == State::Defunct
+					&& diff > Duration::seconds(global::PEER_EXPIRATION_REMOVE_TIME);
 
-			if should_remove {
-				debug!(
-					"removing peer {:?}: last connected {} days {} hours {} minutes ago.",
-					peer.addr,
-					diff.num_days(),
-					diff.num_hours(),
-					diff.num_minutes()
+				if should_remove {
+					debug!(
+						"removing peer {:?}: last connected {} days {} hours {} minutes ago.",
+						peer.addr,
+						diff.num_days(),
+						diff.num_hours(),
+						diff.num_minutes()
+					);
+				}
+
+				should_remove
+			})
+			.unwrap_or_else(|e| {
+				error!("prune_store: failed to remove expired peers: {:?}", e);
+				0
+			});
+
+		// Delete peers we received the address of, but never managed to connect to
+		let unconnected_expiration =
+			Duration::seconds(self.config.peer_store_unconnected_expiration());
+		let evicted_unconnected = self
+			.store
+			.delete_peers(|peer| {
+				peer.flags == State::Healthy
+					&& is_never_connected(peer)
+					&& !connected.contains(&peer.addr)
+					&& now - Utc.timestamp(peer.last_connected, 0) > unconnected_expiration
+			})
+			.unwrap_or_else(|e| {
+				error!(
+					"prune_store: failed to remove never connected peers: {:?}",
+					e
 				);
+				0
+			});
+
+		// Cap the storage size, the least useful peers go first
+		let max_count = self.config.peer_store_max_count() as usize;
+		let stored = self.all_peers();
+		let mut evicted_over_cap = 0;
+		if stored.len() > max_count {
+			let mut candidates: Vec<&PeerData> = stored
+				.iter()
+				.filter(|p| p.flags != State::Banned && !connected.contains(&p.addr))
+				.collect();
+			candidates.sort_by_key(|p| (eviction_rank(p), p.last_connected));
+			let evict: HashSet<PeerAddr> = candidates
+				.iter()
+				.take(stored.len() - max_count)
+				.map(|p| p.addr.clone())
+				.collect();
+			evicted_over_cap = self
+				.store
+				.delete_peers(|peer| evict.contains(&peer.addr))
+				.unwrap_or_else(|e| {
+					error!("prune_store: failed to evict peers over the cap: {:?}", e);
+					0
+				});
+		}
+
+		self.store_size.store(
+			stored.len().saturating_sub(evicted_over_cap as usize),
+			Ordering::Relaxed,
+		);
+
+		let mut stats = self.store_stats.write();
+		stats.evicted_expired += evicted_expired;
+		stats.evicted_unconnected += evicted_unconnected;
+		stats.evicted_over_cap += evicted_over_cap;
+		stats.last_pruned = now.timestamp();
+
+		debug!(
+			"prune_store: removed {} expired, {} never connected, {} over the cap of {} peers",
+			evicted_expired, evicted_unconnected, evicted_over_cap, max_count,
+		);
+	}
+
+	/// Size of the peer storage and the eviction stats of its retention policies
+	pub fn store_stats(&self) -> PeerStoreStats {
+		let mut stats = self.store_stats.read().clone();
+		stats.size = match self.store.peer_count() {
+			Ok(count) => count,
+			Err(e) => {
+				error!("store_stats: failed to count peers: {:?}", e);
+				0
 			}
+		};
+		stats.max_size = self.config.peer_store_max_count() as u64;
+		stats
+	}
+}
+
+/// Peers saved from the received addresses have no capabilities and user agent
+/// until we complete a handshake with them.
+fn is_never_connected(peer: &PeerData) -> bool {
+	peer.capabilities == Capabilities::UNKNOWN && peer.user_agent.is_empty()
+}
 
-			should_remove
-		});
+/// Eviction order of the stored peers, the lower rank is evicted first
+fn eviction_rank(peer: &PeerData) -> u8 {
+	match peer.flags {
+		State::Defunct => 0,
+		_ if is_never_connected(peer) => 1,
+		_ => 2,
+	}
+}
+
+/// Onion peer can be stored with or without '.onion' suffix
+fn onion_peer_addrs(onion_address: &str) -> Vec<PeerAddr> {
+	let address = onion_address.trim_end_matches(".onion");
+	vec![
+		PeerAddr::Onion(address.to_string()),
+		PeerAddr::Onion(format!("{}.onion", address)),
+	]
+}
+
+impl Libp2pBanList for Peers {
+	fn is_onion_banned(&self, onion_address: &str) -> bool {
+		onion_peer_addrs(onion_address)
+			.into_iter()
+			.any(|addr| self.is_banned(addr))
+	}
+
+	fn ban_onion(&self, onion_address: &str) {
+		let known_addr = onion_peer_addrs(onion_address)
+			.into_iter()
+			.find(|addr| self.exists_peer(addr.clone()).unwrap_or(false));
+		let res = match known_addr {
+			Some(addr) => match self.ban_peer(addr, ReasonForBan::BadGossip) {
+				// Peer is not connected with classic p2p, the state is updated anyway
+				Err(Error::PeerNotFound) => Ok(()),
+				res => res,
+			},
+			None => self.add_banned(
+				PeerAddr::Onion(onion_address.to_string()),
+				ReasonForBan::BadGossip,
+			),
+		};
+		if let Err(e) = res {
+			error!("Unable to ban libp2p peer {}, {}", onion_address, e);
+		}
+	}
+}
+
+impl Libp2pTopicStore for Peers {
+	fn save_topic(&self, topic: &GossipTopicData) {
+		if let Err(e) = self.store.save_gossip_topic(topic) {
+			error!("Unable to save gossip topic {}, {}", topic.topic, e);
+		}
+	}
+
+	fn delete_topic(&self, topic: &str) {
+		if let Err(e) = self.store.delete_gossip_topic(topic) {
+			error!("Unable to delete gossip topic {}, {}", topic, e);
+		}
+	}
+
+	fn load_topics(&self) -> Vec<GossipTopicData> {
+		match self.store.all_gossip_topics() {
+			Ok(topics) => topics,
+			Err(e) => {
+				error!("Unable to read gossip topics, {}", e);
+				vec![]
+			}
+		}
 	}
 }
 
@@ -597,8 +976,9 @@ impl ChainAdapter for Peers {
 		&self,
 		tx: core::Transaction,
 		stem: bool,
+		peer_info: &PeerInfo,
 	) -> Result<bool, chain::Error> {
-		self.adapter.transaction_received(tx, stem)
+		self.adapter.transaction_received(tx, stem, peer_info)
 	}
 
 	fn block_received(
@@ -740,6 +1120,148 @@ impl ChainAdapter for Peers {
 			.txhashset_download_update(start_time, downloaded_size, total_size)
 	}
 
+	fn get_output_segment(
+		&self,
+		hash: Hash,
+		id: SegmentIdentifier,
+	) -> Result<(Segment<OutputIdentifier>, Vec<u64>, Vec<Hash>, OutputRoots), chain::Error> {
+		self.adapter.get_output_segment(hash, id)
+	}
+
+	fn get_rangeproof_segment(
+		&self,
+		hash: Hash,
+		id: SegmentIdentifier,
+	) -> Result<Segment<RangeProof>, chain::Error> {
+		self.adapter.get_rangeproof_segment(hash, id)
+	}
+
+	fn get_kernel_segment(
+		&self,
+		hash: Hash,
+		id: SegmentIdentifier,
+	) -> Result<Segment<TxKernel>, chain::Error> {
+		self.adapter.get_kernel_segment(hash, id)
+	}
+
+	fn receive_output_segment(
+		&self,
+		block_hash: Hash,
+		segment: Segment<OutputIdentifier>,
+		spent: Vec<u64>,
+		bitmap_proof: Vec<Hash>,
+		output_roots: OutputRoots,
+		peer_info: &PeerInfo,
+	) -> Result<bool, chain::Error> {
+		if !self.adapter.receive_output_segment(
+			block_hash,
+			segment,
+			spent,
+			bitmap_proof,
+			output_roots,
+			peer_info,
+		)? {
+			debug!(
+				"Received a bad output segment from {}, the peer will be banned",
+				peer_info.addr.clone(),
+			);
+			self.ban_peer(peer_info.addr.clone(), ReasonForBan::BadTxHashSet)
+				.map_err(|e| chain::ErrorKind::Other(format!("ban peer error {}", e)))?;
+			Ok(false)
+		} else {
+			Ok(true)
+		}
+	}
+
+	fn receive_rangeproof_segment(
+		&self,
+		block_hash: Hash,
+		segment: Segment<RangeProof>,
+		peer_info: &PeerInfo,
+	) -> Result<bool, chain::Error> {
+		if !self
+			.adapter
+			.receive_rangeproof_segment(block_hash, segment, peer_info)?
+		{
+			debug!(
+				"Received a bad rangeproof segment from {}, the peer will be banned",
+				peer_info.addr.clone(),
+			);
+			self.ban_peer(peer_info.addr.clone(), ReasonForBan::BadTxHashSet)
+				.map_err(|e| chain::ErrorKind::Other(format!("ban peer error {}", e)))?;
+			Ok(false)
+		} else {
+			Ok(true)
+		}
+	}
+
+	fn receive_kernel_segment(
+		&self,
+		block_hash: Hash,
+		segment: Segment<TxKernel>,
+		peer_info: &PeerInfo,
+	) -> Result<bool, chain::Error> {
+		if !self
+			.adapter
+			.receive_kernel_segment(block_hash, segment, peer_info)?
+		{
+			debug!(
+				"Received a bad kernel segment from {}, the peer will be banned",
+				peer_info.addr.clone(),
+			);
+			self.ban_peer(peer_info.addr.clone(), ReasonForBan::BadTxHashSet)
+				.map_err(|e| chain::ErrorKind::Other(format!("ban peer error {}", e)))?;
+			Ok(false)
+		} else {
+			Ok(true)
+		}
+	}
+
+	fn get_historical_output(&self, pos: u64) -> Option<HistoricalOutput> {
+		self.adapter.get_historical_output(pos)
+	}
+
+	fn historical_output_received(
+		&self,
+		output: HistoricalOutput,
+		peer_info: &PeerInfo,
+	) -> Result<bool, chain::Error> {
+		self.adapter.historical_output_received(output, peer_info)
+	}
+
+	fn get_block_transactions(
+		&self,
+		block_hash: Hash,
+		nonce: u64,
+		kern_ids: &[ShortId],
+	) -> Vec<core::Transaction> {
+		self.adapter
+			.get_block_transactions(block_hash, nonce, kern_ids)
+	}
+
+	fn block_transactions_received(
+		&self,
+		block_hash: Hash,
+		txs: Vec<core::Transaction>,
+		peer_info: &PeerInfo,
+	) -> Result<bool, chain::Error> {
+		if !self
+			.adapter
+			.block_transactions_received(block_hash, txs, peer_info)?
+		{
+			debug!(
+				"Received bad txs for compact block {} from {}, the peer will be banned",
+				block_hash,
+				peer_info.addr.clone()
+			);
+			self.ban_peer(peer_info.addr.clone(), ReasonForBan::BadCompactBlock)
+				.map_err(|e| chain::ErrorKind::Other(format!("ban peer error {}", e)))?;
+			Ok(false)
+		} else {
+			Ok(true)
+		}
+	}
+
 	fn get_tmp_dir(&self) -> PathBuf {
 		self.adapter.get_tmp_dir()
 	}
@@ -775,11 +1297,19 @@ impl NetAdapter for Peers {
 				last_banned: 0,
 				ban_reason: ReasonForBan::None,
 				last_connected: Utc::now().timestamp(),
+				latency_ms: None,
 			};
-			if let Err(e) = self.save_peer(&peer) {
-				error!("Could not save received peer address: {:?}", e);
+			match self.save_peer(&peer) {
+				Ok(_) => {
+					self.store_size.fetch_add(1, Ordering::Relaxed);
+				}
+				Err(e) => error!("Could not save received peer address: {:?}", e),
 			}
 		}
+		// Scheduled pruning is not frequent, the cap is enforced as soon as it is reached
+		if self.store_size.load(Ordering::Relaxed) > self.config.peer_store_max_count() as usize {
+			self.prune_store();
+		}
 	}
 
 	fn peer_difficulty(&self, addr: PeerAddr, diff: Difficulty, height: u64) {
@@ -788,6 +1318,15 @@ impl NetAdapter for Peers {
 		}
 	}
 
+	fn peer_latency(&self, addr: PeerAddr, latency: std::time::Duration) {
+		if let Err(e) = self
+			.store
+			.update_latency(addr.clone(), latency.as_millis() as u32)
+		{
+			debug!("Could not save latency of peer {}: {:?}", addr, e);
+		}
+	}
+
 	fn is_banned(&self, addr: PeerAddr) -> bool {
 		if let Ok(peer) = self.get_peer(addr) {
 			peer.flags == State::Banned
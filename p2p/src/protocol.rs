@@ -16,31 +16,45 @@ use crate::chain;
 use crate::conn::{Message, MessageHandler, Tracker};
 use crate::core::core::{self, hash::Hash, hash::Hashed, CompactBlock};
 use crate::serv::Server;
+use crate::tx_relay_limiter::TxRelayLimiter;
 use crate::types::PeerAddr::Onion;
 
 use crate::msg::{
-	BanReason, GetPeerAddrs, Headers, Locator, Msg, PeerAddrs, Ping, Pong, TorAddress,
-	TxHashSetArchive, TxHashSetRequest, Type,
+	BanReason, BlockTransactions, BlockTransactionsRequest, GetPeerAddrs, Headers,
+	HistoricalOutput, HistoricalOutputRequest, Locator, Msg, OutputSegmentResponse, PeerAddrs,
+	Ping, Pong, SegmentRequest, SegmentResponse, TorAddress, TxHashSetArchive, TxHashSetPart,
+	TxHashSetPartRequest, TxHashSetRequest, Type,
 };
 
 use crate::types::Capabilities;
 use crate::types::PeerAddr;
 use crate::types::{Error, NetAdapter, PeerInfo};
-use chrono::prelude::Utc;
+use crate::util::secp::pedersen::RangeProof;
+use crate::util::{Mutex, ToHex};
+use chrono::prelude::{DateTime, Utc};
 use rand::{thread_rng, Rng};
 use std::cmp;
 use std::fs::{self, File, OpenOptions};
-use std::io::{BufWriter, Read};
+use std::io::{self, BufWriter, Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
 use std::time::Instant;
 
+/// Max length of a txhashset archive part. An interrupted download is resumed
+/// from the last received byte.
+pub const TXHASHSET_PART_SIZE: u64 = 16 * 1024 * 1024;
+
 pub struct Protocol {
 	adapter: Arc<dyn NetAdapter>,
 	peer_info: PeerInfo,
 	state_sync_requested: Arc<AtomicBool>,
+	// quotas of the txs relayed by this peer
+	tx_relay: Arc<Mutex<TxRelayLimiter>>,
 	header_cache_size: u64,
 	server: Server,
+	// when the txhashset download in parts from this peer started
+	txhashset_part_start: Option<DateTime<Utc>>,
 }
 
 impl Protocol {
@@ -48,6 +62,7 @@ impl Protocol {
 		adapter: Arc<dyn NetAdapter>,
 		peer_info: PeerInfo,
 		state_sync_requested: Arc<AtomicBool>,
+		tx_relay: Arc<Mutex<TxRelayLimiter>>,
 		header_cache_size: u64,
 		server: Server,
 	) -> Protocol {
@@ -55,8 +70,63 @@ impl Protocol {
 			adapter,
 			peer_info,
 			state_sync_requested,
+			tx_relay,
 			header_cache_size,
 			server,
+			txhashset_part_start: None,
+		}
+	}
+}
+
+// A partial txhashset download is named after the archive hash, so the parts of
+// different archives (zipped by different peers) are never mixed.
+fn txhashset_part_path(tmp_dir: &Path, hash: &Hash, archive_hash: &Hash) -> PathBuf {
+	tmp_dir.join(format!(
+		"txhashset-{}-{}.part",
+		hash.to_hex(),
+		archive_hash.to_hex()
+	))
+}
+
+// Partial txhashset downloads in the tmp dir, all of them or only the ones of the
+// provided block hash.
+fn txhashset_parts(tmp_dir: &Path, hash: Option<&Hash>) -> Vec<PathBuf> {
+	let prefix = match hash {
+		Some(hash) => format!("txhashset-{}-", hash.to_hex()),
+		None => "txhashset-".to_string(),
+	};
+	match fs::read_dir(tmp_dir) {
+		Ok(entries) => entries
+			.filter_map(|entry| entry.ok())
+			.map(|entry| entry.path())
+			.filter(|path| {
+				path.file_name()
+					.and_then(|name| name.to_str())
+					.map(|name| name.starts_with(&prefix) && name.ends_with(".part"))
+					.unwrap_or(false)
+			})
+			.collect(),
+		Err(_) => vec![],
+	}
+}
+
+/// Number of bytes of the txhashset archive for the provided block hash already
+/// downloaded, to resume the download from there.
+pub fn txhashset_part_offset(tmp_dir: &Path, hash: &Hash) -> u64 {
+	txhashset_parts(tmp_dir, Some(hash))
+		.iter()
+		.filter_map(|path| fs::metadata(path).ok())
+		.map(|metadata| metadata.len())
+		.max()
+		.unwrap_or(0)
+}
+
+fn clean_txhashset_parts(tmp_dir: &Path, keep: Option<&Path>) {
+	for path in txhashset_parts(tmp_dir, None) {
+		if Some(path.as_path()) != keep {
+			if let Err(e) = fs::remove_file(&path) {
+				warn!("fail to remove txhashset part: {:?}. err: {}", path, e);
+			}
 		}
 	}
 }
@@ -82,6 +152,25 @@ impl MessageHandler for Protocol {
 			return Ok(None);
 		}
 
+		// Txs over the relay quota of the peer are dropped unprocessed.
+		if let Type::Transaction | Type::StemTransaction | Type::TransactionKernel =
+			msg.header.msg_type
+		{
+			let msg_len = msg.header.msg_len;
+			if !self
+				.tx_relay
+				.lock()
+				.try_relay(msg_len, Utc::now().timestamp_millis())
+			{
+				debug!(
+					"handle_payload: {:?} from {} is over the tx relay quota, dropping. msg_len: {}",
+					msg.header.msg_type, self.peer_info.addr, msg_len
+				);
+				msg.copy_attachment(msg_len as usize, &mut io::sink())?;
+				return Ok(None);
+			}
+		}
+
 		match msg.header.msg_type {
 			Type::Ping => {
 				let ping: Ping = msg.body()?;
@@ -108,6 +197,9 @@ impl MessageHandler for Protocol {
 					pong.total_difficulty,
 					pong.height,
 				);
+				if let Some(latency) = self.peer_info.pong_received() {
+					adapter.peer_latency(self.peer_info.addr.clone(), latency);
+				}
 				Ok(None)
 			}
 
@@ -151,7 +243,7 @@ impl MessageHandler for Protocol {
 					msg.header.msg_len
 				);
 				let tx: core::Transaction = msg.body()?;
-				adapter.transaction_received(tx, false)?;
+				adapter.transaction_received(tx, false, &self.peer_info)?;
 				Ok(None)
 			}
 
@@ -161,7 +253,7 @@ impl MessageHandler for Protocol {
 					msg.header.msg_len
 				);
 				let tx: core::Transaction = msg.body()?;
-				adapter.transaction_received(tx, true)?;
+				adapter.transaction_received(tx, true, &self.peer_info)?;
 				Ok(None)
 			}
 
@@ -173,6 +265,19 @@ impl MessageHandler for Protocol {
 					msg.header.msg_len,
 				);
 
+				if !self
+					.server
+					.serve_limiter
+					.try_serve_block(&self.peer_info.addr, Utc::now().timestamp())
+				{
+					debug!(
+						"handle_payload: GetBlock: block quota is exceeded for peer {:?}, skipping. Dropped {} block requests total",
+						self.peer_info.addr,
+						self.server.serve_limiter.dropped_blocks()
+					);
+					return Ok(None);
+				}
+
 				let bo = adapter.get_block(h, &self.peer_info);
 				if let Some(b) = bo {
 					return Ok(Some(Msg::new(Type::Block, b, self.peer_info.version)?));
@@ -353,6 +458,19 @@ impl MessageHandler for Protocol {
 					sm_req.hash, sm_req.height
 				);
 
+				if !self
+					.server
+					.serve_limiter
+					.try_serve_txhashset(&self.peer_info.addr, Utc::now().timestamp())
+				{
+					warn!(
+						"handle_payload: txhashset quota is exceeded for peer {:?}, skipping. Dropped {} txhashset requests total",
+						self.peer_info.addr,
+						self.server.serve_limiter.dropped_txhashsets()
+					);
+					return Ok(None);
+				}
+
 				let txhashset_header = self.adapter.txhashset_archive_header()?;
 				let txhashset_header_hash = txhashset_header.hash();
 				let txhashset = self.adapter.txhashset_read(txhashset_header_hash);
@@ -479,6 +597,415 @@ impl MessageHandler for Protocol {
 
 				Ok(None)
 			}
+			Type::GetTxHashSetPart => {
+				let req: TxHashSetPartRequest = msg.body()?;
+				debug!(
+					"handle_payload: txhashset part req for {} at {}, offset {}",
+					req.hash, req.height, req.offset
+				);
+
+				if req.length == 0 {
+					warn!(
+						"handle_payload: empty txhashset part requested by {:?}",
+						self.peer_info.addr
+					);
+					return Err(Error::BadMessage);
+				}
+
+				let txhashset_header = self.adapter.txhashset_archive_header()?;
+				let txhashset_header_hash = txhashset_header.hash();
+				let txhashset = self.adapter.txhashset_read(txhashset_header_hash);
+
+				if let Some(txhashset) = txhashset {
+					let file_sz = txhashset.reader.metadata()?.len();
+					// A request for another archive starts over with the one we serve.
+					let offset = if req.hash == txhashset_header_hash && req.offset < file_sz {
+						req.offset
+					} else {
+						0
+					};
+					let length =
+						cmp::min(cmp::min(req.length, TXHASHSET_PART_SIZE), file_sz - offset);
+
+					// Every part counts into the txhashset quota by its size
+					if !self.server.serve_limiter.try_serve_txhashset_part(
+						&self.peer_info.addr,
+						Utc::now().timestamp(),
+						length,
+						file_sz,
+					) {
+						warn!(
+							"handle_payload: txhashset quota is exceeded for peer {:?}, skipping. Dropped {} txhashset requests total",
+							self.peer_info.addr,
+							self.server.serve_limiter.dropped_txhashsets()
+						);
+						return Ok(None);
+					}
+
+					let mut reader = txhashset.reader;
+					reader.seek(SeekFrom::Start(offset))?;
+					let mut resp = Msg::new(
+						Type::TxHashSetPart,
+						&TxHashSetPart {
+							hash: txhashset_header_hash,
+							height: txhashset_header.height,
+							bytes: file_sz,
+							archive_hash: txhashset.archive_hash,
+							offset,
+							length,
+						},
+						self.peer_info.version,
+					)?;
+					resp.add_attachment_part(reader, length);
+					Ok(Some(resp))
+				} else {
+					Ok(None)
+				}
+			}
+
+			Type::TxHashSetPart => {
+				let part: TxHashSetPart = msg.body()?;
+				debug!(
+					"handle_payload: txhashset part for {} at {}, {}/{}",
+					part.hash,
+					part.height,
+					part.offset + part.length,
+					part.bytes,
+				);
+				if !self.adapter.txhashset_receive_ready() {
+					error!(
+						"handle_payload: txhashset part received but SyncStatus not on TxHashsetDownload",
+					);
+					return Err(Error::BadMessage);
+				}
+				if !self.state_sync_requested.load(Ordering::Relaxed) {
+					error!("handle_payload: txhashset part received but from the wrong peer",);
+					return Err(Error::BadMessage);
+				}
+
+				let tmp_dir = self.adapter.get_tmp_dir();
+				fs::create_dir_all(&tmp_dir)?;
+				let path = txhashset_part_path(&tmp_dir, &part.hash, &part.archive_hash);
+				let downloaded = fs::metadata(&path).map(|m| m.len()).unwrap_or(0);
+
+				// The part doesn't follow what we have (likely the peer serves another
+				// archive than the one we started with), drop it and start over.
+				if part.offset != downloaded {
+					info!(
+						"handle_payload: txhashset part at {} doesn't follow the {} bytes we have, restarting the download",
+						part.offset, downloaded
+					);
+					msg.copy_attachment(part.length as usize, &mut io::sink())?;
+					tracker.inc_quiet_received(part.length);
+					clean_txhashset_parts(&tmp_dir, None);
+					let req = Msg::new(
+						Type::GetTxHashSetPart,
+						&TxHashSetPartRequest {
+							hash: part.hash,
+							height: part.height,
+							offset: 0,
+							length: TXHASHSET_PART_SIZE,
+						},
+						self.peer_info.version,
+					)?;
+					return Ok(Some(req));
+				}
+				if part.length == 0 && part.offset < part.bytes {
+					error!("handle_payload: empty txhashset part received");
+					return Err(Error::BadMessage);
+				}
+				if part.offset == 0 {
+					clean_txhashset_parts(&tmp_dir, Some(&path));
+				}
+
+				let download_start_time = *self.txhashset_part_start.get_or_insert_with(Utc::now);
+				let mut now = Instant::now();
+				let mut save_part_to_file = |file: &Path| -> Result<(), Error> {
+					let mut part_file =
+						BufWriter::new(OpenOptions::new().create(true).append(true).open(file)?);
+					let total_size = part.length as usize;
+					let mut downloaded_size: usize = 0;
+					let mut request_size = cmp::min(48_000, total_size);
+					while request_size > 0 {
+						let size = msg.copy_attachment(request_size, &mut part_file)?;
+						downloaded_size += size;
+						request_size = cmp::min(48_000, total_size - downloaded_size);
+						self.adapter.txhashset_download_update(
+							download_start_time,
+							part.offset + downloaded_size as u64,
+							part.bytes,
+						);
+						if now.elapsed().as_secs() > 10 {
+							now = Instant::now();
+							debug!(
+								"handle_payload: txhashset archive: {}/{}",
+								part.offset + downloaded_size as u64,
+								part.bytes
+							);
+						}
+						// Increase received bytes quietly (without affecting the counters).
+						// Otherwise we risk banning a peer as "abusive".
+						tracker.inc_quiet_received(size as u64);
+
+						// check the close channel
+						if stopped.load(Ordering::Relaxed) {
+							debug!("stopping txhashset download early");
+							return Err(Error::ConnectionClose);
+						}
+					}
+					part_file
+						.into_inner()
+						.map_err(|e| {
+							Error::Internal(format!("Unable to save txhashset data, {}", e))
+						})?
+						.sync_all()?;
+					Ok(())
+				};
+
+				if let Err(e) = save_part_to_file(&path) {
+					error!(
+						"handle_payload: txhashset part save to file fail. err={:?}",
+						e
+					);
+					return Err(e);
+				}
+
+				// Ask the same peer for the next part.
+				let downloaded = part.offset + part.length;
+				if downloaded < part.bytes {
+					let req = Msg::new(
+						Type::GetTxHashSetPart,
+						&TxHashSetPartRequest {
+							hash: part.hash,
+							height: part.height,
+							offset: downloaded,
+							length: TXHASHSET_PART_SIZE,
+						},
+						self.peer_info.version,
+					)?;
+					return Ok(Some(req));
+				}
+
+				debug!(
+					"handle_payload: txhashset archive: {}/{} ... DONE",
+					downloaded, part.bytes
+				);
+				self.state_sync_requested.store(false, Ordering::Relaxed);
+				self.txhashset_part_start = None;
+
+				let tmp_zip = File::open(&path)?;
+				let archive_hash = chain::txhashset::zip_hash(&tmp_zip).map_err(|e| {
+					Error::Internal(format!("Unable to hash txhashset data, {}", e))
+				})?;
+				if archive_hash != part.archive_hash {
+					error!(
+						"handle_payload: txhashset archive for {} at {} doesn't match its hash",
+						part.hash, part.height
+					);
+					if let Err(e) = fs::remove_file(&path) {
+						warn!("fail to remove tmp file: {:?}. err: {}", path, e);
+					}
+					return Err(Error::BadMessage);
+				}
+
+				let res = self
+					.adapter
+					.txhashset_write(part.hash, tmp_zip, &self.peer_info)?;
+
+				info!(
+					"handle_payload: txhashset archive for {} at {}, DONE. Data Ok: {}",
+					part.hash, part.height, res
+				);
+
+				if let Err(e) = fs::remove_file(&path) {
+					warn!("fail to remove tmp file: {:?}. err: {}", path, e);
+				}
+
+				Ok(None)
+			}
+			Type::GetOutputSegment | Type::GetRangeProofSegment | Type::GetKernelSegment => {
+				let req: SegmentRequest = msg.body()?;
+				debug!(
+					"handle_payload: {:?} {:?} for {}",
+					msg.header.msg_type, req.identifier, req.block_hash
+				);
+
+				// Segments are about the size of a block, count them into the block quota.
+				if !self
+					.server
+					.serve_limiter
+					.try_serve_block(&self.peer_info.addr, Utc::now().timestamp())
+				{
+					debug!(
+						"handle_payload: {:?}: block quota is exceeded for peer {:?}, skipping. Dropped {} block requests total",
+						msg.header.msg_type,
+						self.peer_info.addr,
+						self.server.serve_limiter.dropped_blocks()
+					);
+					return Ok(None);
+				}
+
+				let SegmentRequest {
+					block_hash,
+					identifier,
+				} = req;
+				let res = match msg.header.msg_type {
+					Type::GetOutputSegment => adapter
+						.get_output_segment(block_hash, identifier)
+						.map(|(segment, spent, bitmap_proof, roots)| {
+							Msg::new(
+								Type::OutputSegment,
+								OutputSegmentResponse {
+									response: SegmentResponse {
+										block_hash,
+										segment,
+									},
+									output_root: roots.pmmr_root,
+									bitmap_root: roots.bitmap_root,
+									spent,
+									bitmap_proof,
+								},
+								self.peer_info.version,
+							)
+						}),
+					Type::GetRangeProofSegment => adapter
+						.get_rangeproof_segment(block_hash, identifier)
+						.map(|segment| {
+							Msg::new(
+								Type::RangeProofSegment,
+								SegmentResponse {
+									block_hash,
+									segment,
+								},
+								self.peer_info.version,
+							)
+						}),
+					_ => adapter
+						.get_kernel_segment(block_hash, identifier)
+						.map(|segment| {
+							Msg::new(
+								Type::KernelSegment,
+								SegmentResponse {
+									block_hash,
+									segment,
+								},
+								self.peer_info.version,
+							)
+						}),
+				};
+				match res {
+					Ok(resp) => Ok(Some(resp?)),
+					Err(e) => {
+						debug!(
+							"handle_payload: failed to serve segment {:?} to {:?}, {}",
+							identifier, self.peer_info.addr, e
+						);
+						Ok(None)
+					}
+				}
+			}
+
+			Type::OutputSegment => {
+				let resp: OutputSegmentResponse = msg.body()?;
+				let output_roots = resp.output_roots();
+				debug!(
+					"handle_payload: received output segment {:?} for {}",
+					resp.response.segment.identifier(),
+					resp.response.block_hash
+				);
+				adapter.receive_output_segment(
+					resp.response.block_hash,
+					resp.response.segment,
+					resp.spent,
+					resp.bitmap_proof,
+					output_roots,
+					&self.peer_info,
+				)?;
+				Ok(None)
+			}
+
+			Type::RangeProofSegment => {
+				let resp: SegmentResponse<RangeProof> = msg.body()?;
+				debug!(
+					"handle_payload: received rangeproof segment {:?} for {}",
+					resp.segment.identifier(),
+					resp.block_hash
+				);
+				adapter.receive_rangeproof_segment(
+					resp.block_hash,
+					resp.segment,
+					&self.peer_info,
+				)?;
+				Ok(None)
+			}
+
+			Type::KernelSegment => {
+				let resp: SegmentResponse<core::TxKernel> = msg.body()?;
+				debug!(
+					"handle_payload: received kernel segment {:?} for {}",
+					resp.segment.identifier(),
+					resp.block_hash
+				);
+				adapter.receive_kernel_segment(resp.block_hash, resp.segment, &self.peer_info)?;
+				Ok(None)
+			}
+
+			Type::GetHistoricalOutput => {
+				let req: HistoricalOutputRequest = msg.body()?;
+				debug!(
+					"handle_payload: GetHistoricalOutput: pos {} from {}",
+					req.pos, self.peer_info.addr
+				);
+				match adapter.get_historical_output(req.pos) {
+					Some(output) => Ok(Some(Msg::new(
+						Type::HistoricalOutput,
+						output,
+						self.peer_info.version,
+					)?)),
+					None => Ok(None),
+				}
+			}
+
+			Type::HistoricalOutput => {
+				let output: HistoricalOutput = msg.body()?;
+				debug!(
+					"handle_payload: received historical output at pos {}",
+					output.pos
+				);
+				adapter.historical_output_received(output, &self.peer_info)?;
+				Ok(None)
+			}
+
+			Type::GetBlockTransactions => {
+				let req: BlockTransactionsRequest = msg.body()?;
+				debug!(
+					"handle_payload: GetBlockTransactions: {} txs of {} from {}",
+					req.kern_ids.len(),
+					req.block_hash,
+					self.peer_info.addr
+				);
+				let txs = adapter.get_block_transactions(req.block_hash, req.nonce, &req.kern_ids);
+				Ok(Some(Msg::new(
+					Type::BlockTransactions,
+					BlockTransactions {
+						block_hash: req.block_hash,
+						txs,
+					},
+					self.peer_info.version,
+				)?))
+			}
+
+			Type::BlockTransactions => {
+				let resp: BlockTransactions = msg.body()?;
+				debug!(
+					"handle_payload: received {} txs for compact block {}",
+					resp.txs.len(),
+					resp.block_hash
+				);
+				adapter.block_transactions_received(resp.block_hash, resp.txs, &self.peer_info)?;
+				Ok(None)
+			}
+
 			Type::Error | Type::Hand | Type::Shake => {
 				debug!("Received an unexpected msg: {:?}", msg.header.msg_type);
 				Ok(None)
@@ -0,0 +1,175 @@
+// Copyright 2021 The MWC Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Recently seen blocks and transactions, shared by the classic p2p network and the gossip
+//! relays. The same object can arrive from both networks, the cache lets the second arrival be
+//! dropped before it is validated and relayed again. Entries expire after a few minutes, by
+//! then the chain and the pool are the source of truth for the known objects.
+
+use crate::core::core::hash::Hash;
+use crate::util::Mutex;
+use std::collections::{HashMap, VecDeque};
+
+/// Max number of tracked objects, the oldest ones are dropped first
+pub const SEEN_CACHE_CAPACITY: usize = 20_000;
+
+/// How long an object stays in the cache, ms
+pub const SEEN_CACHE_EXPIRY_MS: i64 = 180_000;
+
+lazy_static! {
+	/// Cache shared by the p2p adapter and the gossip relays
+	static ref SEEN_CACHE: SeenCache = SeenCache::new(SEEN_CACHE_CAPACITY, SEEN_CACHE_EXPIRY_MS);
+}
+
+/// Node wide cache of the recently seen blocks and transactions
+pub fn seen_cache() -> &'static SeenCache {
+	&SEEN_CACHE
+}
+
+/// Network the object arrived from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SeenNetwork {
+	/// Classic p2p network
+	P2p,
+	/// libp2p gossip
+	Gossip,
+}
+
+/// Kind of the tracked object
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SeenObject {
+	/// Block, tracked by the block hash
+	Block,
+	/// Transaction, tracked by the tx hash
+	Transaction,
+}
+
+/// Duplicate counters of the seen cache
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct SeenCacheStats {
+	/// Number of the tracked objects
+	pub size: usize,
+	/// Blocks that were dropped as already seen
+	pub block_duplicates: u64,
+	/// Transactions that were dropped as already seen
+	pub tx_duplicates: u64,
+	/// Duplicates that were first seen from the other network
+	pub cross_network_duplicates: u64,
+}
+
+struct SeenEntry {
+	network: SeenNetwork,
+	time: i64,
+}
+
+struct SeenCacheData {
+	entries: HashMap<Hash, SeenEntry>,
+	// Insertion order, for the expiration and the capacity limit
+	order: VecDeque<Hash>,
+	stats: SeenCacheStats,
+}
+
+impl SeenCacheData {
+	fn evict(&mut self, capacity: usize, expire_before: i64) {
+		while let Some(hash) = self.order.front().cloned() {
+			let expired = self
+				.entries
+				.get(&hash)
+				.map(|e| e.time < expire_before)
+				.unwrap_or(true);
+			if !expired && self.order.len() < capacity {
+				break;
+			}
+			self.order.pop_front();
+			self.entries.remove(&hash);
+		}
+	}
+}
+
+/// Recently seen objects with the network they came from first
+pub struct SeenCache {
+	capacity: usize,
+	expiry_ms: i64,
+	data: Mutex<SeenCacheData>,
+}
+
+impl SeenCache {
+	/// Create the cache with the capacity and the expiration time, ms
+	pub fn new(capacity: usize, expiry_ms: i64) -> SeenCache {
+		SeenCache {
+			capacity,
+			expiry_ms,
+			data: Mutex::new(SeenCacheData {
+				entries: HashMap::new(),
+				order: VecDeque::new(),
+				stats: SeenCacheStats::default(),
+			}),
+		}
+	}
+
+	/// Check if the object arriving from the network at now (ms) was already seen. Duplicates
+	/// are counted into the stats.
+	pub fn is_seen(&self, hash: &Hash, object: SeenObject, network: SeenNetwork, now: i64) -> bool {
+		let mut data = self.data.lock();
+		let first_network = match data.entries.get(hash) {
+			Some(e) if e.time >= now - self.expiry_ms => e.network,
+			_ => return false,
+		};
+		match object {
+			SeenObject::Block => data.stats.block_duplicates += 1,
+			SeenObject::Transaction => data.stats.tx_duplicates += 1,
+		}
+		if first_network != network {
+			data.stats.cross_network_duplicates += 1;
+		}
+		true
+	}
+
+	/// Remember the object that was accepted from the network at now (ms). The network of the
+	/// first arrival is kept.
+	pub fn add(&self, hash: Hash, network: SeenNetwork, now: i64) {
+		let mut data = self.data.lock();
+		data.evict(self.capacity, now - self.expiry_ms);
+		if !data.entries.contains_key(&hash) {
+			data.entries.insert(hash, SeenEntry { network, time: now });
+			data.order.push_back(hash);
+		}
+	}
+
+	/// Check and remember the object in one step, for the relays that can drop the
+	/// object before it is validated. Returns true if the object was already seen.
+	pub fn check_and_add(
+		&self,
+		hash: Hash,
+		object: SeenObject,
+		network: SeenNetwork,
+		now: i64,
+	) -> bool {
+		if self.is_seen(&hash, object, network, now) {
+			true
+		} else {
+			self.add(hash, network, now);
+			false
+		}
+	}
+
+	/// Current stats of the cache
+	pub fn stats(&self) -> SeenCacheStats {
+		let data = self.data.lock();
+		SeenCacheStats {
+			size: data.entries.len(),
+			..data.stats.clone()
+		}
+	}
+}
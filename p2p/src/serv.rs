@@ -22,20 +22,27 @@ use std::thread;
 use std::time::Duration;
 
 use crate::chain;
+use crate::chain::types::OutputRoots;
 use crate::core::core;
 use crate::core::core::hash::Hash;
+use crate::core::core::pmmr::{Segment, SegmentIdentifier};
+use crate::core::core::{OutputIdentifier, ShortId, TxKernel};
 use crate::core::global;
 use crate::core::pow::Difficulty;
 use crate::handshake::Handshake;
+use crate::msg::HistoricalOutput;
 use crate::peer::Peer;
 use crate::peers::Peers;
+use crate::serve_limiter::ServeLimiter;
 use crate::store::PeerStore;
 use crate::types::{
 	Capabilities, ChainAdapter, Error, NetAdapter, P2PConfig, PeerAddr, PeerInfo, ReasonForBan,
 	TxHashSetRead,
 };
+use crate::util::secp::pedersen::RangeProof;
 use crate::util::StopState;
 use chrono::prelude::{DateTime, Utc};
+use grin_store::encryption::EncryptionKey;
 
 /// P2P server implementation, handling bootstrapping to find and connect to
 /// peers, receiving connections from other peers and keep track of all of them.
@@ -48,6 +55,7 @@ pub struct Server {
 	pub peers: Arc<Peers>,
 	stop_state: Arc<StopState>,
 	pub self_onion_address: Option<String>,
+	pub serve_limiter: Arc<ServeLimiter>,
 }
 
 // TODO TLS
@@ -62,7 +70,9 @@ impl Server {
 		stop_state: Arc<StopState>,
 		socks_port: u16,
 		onion_address: Option<String>,
+		encryption: Option<Arc<EncryptionKey>>,
 	) -> Result<Server, Error> {
+		let serve_limiter = Arc::new(ServeLimiter::from_config(&config));
 		Ok(Server {
 			config: config.clone(),
 			capabilities: capab,
@@ -72,7 +82,7 @@ impl Server {
 				onion_address.clone(),
 			)),
 			peers: Arc::new(Peers::new(
-				PeerStore::new(db_root)?,
+				PeerStore::new(db_root, encryption)?,
 				adapter,
 				config,
 				stop_state.clone(),
@@ -80,6 +90,7 @@ impl Server {
 			stop_state,
 			socks_port,
 			self_onion_address: onion_address,
+			serve_limiter,
 		})
 	}
 
@@ -327,7 +338,7 @@ impl Server {
 	/// duplicate connections, malicious or not.
 	fn check_undesirable(&self, stream: &TcpStream) -> bool {
 		if self.peers.peer_inbound_count()
-			>= self.config.peer_max_inbound_count() + self.config.peer_listener_buffer_count()
+			>= self.peers.max_inbound_count() + self.config.peer_listener_buffer_count()
 		{
 			debug!("Accepting new connection will exceed peer limit, refusing connection.");
 			return true;
@@ -393,6 +404,7 @@ impl ChainAdapter for DummyAdapter {
 		&self,
 		_: core::Transaction,
 		_stem: bool,
+		_peer_info: &PeerInfo,
 	) -> Result<bool, chain::Error> {
 		Ok(true)
 	}
@@ -470,6 +482,90 @@ impl ChainAdapter for DummyAdapter {
 		false
 	}
 
+	fn get_output_segment(
+		&self,
+		_hash: Hash,
+		_id: SegmentIdentifier,
+	) -> Result<(Segment<OutputIdentifier>, Vec<u64>, Vec<Hash>, OutputRoots), chain::Error> {
+		Err(chain::ErrorKind::TxHashSetErr("segments are not served".to_string()).into())
+	}
+
+	fn get_rangeproof_segment(
+		&self,
+		_hash: Hash,
+		_id: SegmentIdentifier,
+	) -> Result<Segment<RangeProof>, chain::Error> {
+		Err(chain::ErrorKind::TxHashSetErr("segments are not served".to_string()).into())
+	}
+
+	fn get_kernel_segment(
+		&self,
+		_hash: Hash,
+		_id: SegmentIdentifier,
+	) -> Result<Segment<TxKernel>, chain::Error> {
+		Err(chain::ErrorKind::TxHashSetErr("segments are not served".to_string()).into())
+	}
+
+	fn receive_output_segment(
+		&self,
+		_block_hash: Hash,
+		_segment: Segment<OutputIdentifier>,
+		_spent: Vec<u64>,
+		_bitmap_proof: Vec<Hash>,
+		_output_roots: OutputRoots,
+		_peer_info: &PeerInfo,
+	) -> Result<bool, chain::Error> {
+		Ok(true)
+	}
+
+	fn receive_rangeproof_segment(
+		&self,
+		_block_hash: Hash,
+		_segment: Segment<RangeProof>,
+		_peer_info: &PeerInfo,
+	) -> Result<bool, chain::Error> {
+		Ok(true)
+	}
+
+	fn receive_kernel_segment(
+		&self,
+		_block_hash: Hash,
+		_segment: Segment<TxKernel>,
+		_peer_info: &PeerInfo,
+	) -> Result<bool, chain::Error> {
+		Ok(true)
+	}
+
+	fn get_historical_output(&self, _pos: u64) -> Option<HistoricalOutput> {
+		None
+	}
+
+	fn historical_output_received(
+		&self,
+		_output: HistoricalOutput,
+		_peer_info: &PeerInfo,
+	) -> Result<bool, chain::Error> {
+		Ok(true)
+	}
+
+	fn get_block_transactions(
+		&self,
+		_block_hash: Hash,
+		_nonce: u64,
+		_kern_ids: &[ShortId],
+	) -> Vec<core::Transaction> {
+		vec![]
+	}
+
+	fn block_transactions_received(
+		&self,
+		_block_hash: Hash,
+		_txs: Vec<core::Transaction>,
+		_peer_info: &PeerInfo,
+	) -> Result<bool, chain::Error> {
+		Ok(true)
+	}
+
 	fn get_tmp_dir(&self) -> PathBuf {
 		unimplemented!()
 	}
@@ -485,6 +581,7 @@ impl NetAdapter for DummyAdapter {
 	}
 	fn peer_addrs_received(&self, _: Vec<PeerAddr>) {}
 	fn peer_difficulty(&self, _: PeerAddr, _: Difficulty, _: u64) {}
+	fn peer_latency(&self, _: PeerAddr, _: Duration) {}
 	fn is_banned(&self, _: PeerAddr) -> bool {
 		false
 	}
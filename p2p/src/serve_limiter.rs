@@ -0,0 +1,234 @@
+// Copyright 2021 The MWC Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Fair serving of the sync data (blocks and txhashset archives) to the peers.
+//! Every peer has a quota for the time window. When the node is busy (total number of
+//! served blocks reached the capacity), only the peers that got less than the fair share
+//! are served, so a single peer that resyncing from scratch can't take all disk and
+//! upload bandwidth.
+
+use crate::types::{P2PConfig, PeerAddr};
+use crate::util::Mutex;
+use std::collections::{HashMap, VecDeque};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// Time window for the block quotas, seconds
+const BLOCK_WINDOW: i64 = 60;
+/// Time window for the txhashset quotas, seconds
+const TXHASHSET_WINDOW: i64 = 3600;
+
+#[derive(Default)]
+struct ServedRequests {
+	/// Timestamps of the served blocks
+	blocks: VecDeque<i64>,
+	/// Timestamps of the served txhashset archives
+	txhashsets: VecDeque<i64>,
+	/// Timestamps and sizes of the served txhashset archive parts
+	txhashset_parts: VecDeque<(i64, u64)>,
+}
+
+impl ServedRequests {
+	fn expire(&mut self, now: i64) {
+		while self
+			.blocks
+			.front()
+			.map_or(false, |t| *t <= now - BLOCK_WINDOW)
+		{
+			self.blocks.pop_front();
+		}
+		while self
+			.txhashsets
+			.front()
+			.map_or(false, |t| *t <= now - TXHASHSET_WINDOW)
+		{
+			self.txhashsets.pop_front();
+		}
+		while self
+			.txhashset_parts
+			.front()
+			.map_or(false, |(t, _)| *t <= now - TXHASHSET_WINDOW)
+		{
+			self.txhashset_parts.pop_front();
+		}
+	}
+
+	fn is_empty(&self) -> bool {
+		self.blocks.is_empty() && self.txhashsets.is_empty() && self.txhashset_parts.is_empty()
+	}
+}
+
+/// Per peer quotas for the served blocks and txhashset archives
+pub struct ServeLimiter {
+	/// Max blocks per peer per minute
+	block_quota: usize,
+	/// Max blocks for all peers per minute before fair share is applied
+	block_capacity: usize,
+	/// Max txhashset archives per peer per hour
+	txhashset_quota: usize,
+	/// Served requests, peer key => history
+	served: Mutex<HashMap<String, ServedRequests>>,
+	/// Number of the block requests that were dropped because of the quotas
+	dropped_blocks: AtomicU64,
+	/// Number of the txhashset requests that were dropped because of the quotas
+	dropped_txhashsets: AtomicU64,
+}
+
+impl ServeLimiter {
+	/// Create limiter with quotas
+	pub fn new(block_quota: u32, block_capacity: u32, txhashset_quota: u32) -> ServeLimiter {
+		ServeLimiter {
+			block_quota: block_quota as usize,
+			block_capacity: block_capacity as usize,
+			txhashset_quota: txhashset_quota as usize,
+			served: Mutex::new(HashMap::new()),
+			dropped_blocks: AtomicU64::new(0),
+			dropped_txhashsets: AtomicU64::new(0),
+		}
+	}
+
+	/// Create limiter with quotas from the config
+	pub fn from_config(config: &P2PConfig) -> ServeLimiter {
+		ServeLimiter::new(
+			config.serve_block_quota(),
+			config.serve_block_capacity(),
+			config.serve_txhashset_quota(),
+		)
+	}
+
+	/// Check if the block can be served to the peer. If yes, it is counted into the peer quota.
+	pub fn try_serve_block(&self, peer: &PeerAddr, now: i64) -> bool {
+		let mut served = self.served.lock();
+		served.values_mut().for_each(|s| s.expire(now));
+		served.retain(|_, s| !s.is_empty());
+
+		let key = peer.as_key();
+		let peer_served = served.get(&key).map_or(0, |s| s.blocks.len());
+		if peer_served >= self.block_quota {
+			self.dropped_blocks.fetch_add(1, Ordering::Relaxed);
+			return false;
+		}
+
+		let total: usize = served.values().map(|s| s.blocks.len()).sum();
+		if total >= self.block_capacity {
+			// Node is busy, serving only the peers that got less than the fair share
+			let active = served.values().filter(|s| !s.blocks.is_empty()).count();
+			if peer_served >= self.block_capacity / std::cmp::max(active, 1) {
+				self.dropped_blocks.fetch_add(1, Ordering::Relaxed);
+				return false;
+			}
+		}
+
+		served.entry(key).or_default().blocks.push_back(now);
+		true
+	}
+
+	/// Check if the txhashset archive can be served to the peer. If yes, it is counted into the peer quota.
+	pub fn try_serve_txhashset(&self, peer: &PeerAddr, now: i64) -> bool {
+		let mut served = self.served.lock();
+		let key = peer.as_key();
+		let peer_served = served.entry(key).or_default();
+		peer_served.expire(now);
+		if peer_served.txhashsets.len() >= self.txhashset_quota {
+			self.dropped_txhashsets.fetch_add(1, Ordering::Relaxed);
+			return false;
+		}
+		peer_served.txhashsets.push_back(now);
+		true
+	}
+
+	/// Check if the part of the txhashset archive can be served to the peer. The parts share
+	/// the txhashset quota, counted in bytes of the archive.
+	pub fn try_serve_txhashset_part(
+		&self,
+		peer: &PeerAddr,
+		now: i64,
+		length: u64,
+		archive_size: u64,
+	) -> bool {
+		let mut served = self.served.lock();
+		let key = peer.as_key();
+		let peer_served = served.entry(key).or_default();
+		peer_served.expire(now);
+		let quota = (self.txhashset_quota as u64).saturating_mul(archive_size);
+		let served_bytes: u64 = peer_served.txhashset_parts.iter().map(|(_, l)| *l).sum();
+		if served_bytes.saturating_add(length) > quota {
+			self.dropped_txhashsets.fetch_add(1, Ordering::Relaxed);
+			return false;
+		}
+		peer_served.txhashset_parts.push_back((now, length));
+		true
+	}
+
+	/// Total number of the dropped block requests, segments are included
+	pub fn dropped_blocks(&self) -> u64 {
+		self.dropped_blocks.load(Ordering::Relaxed)
+	}
+
+	/// Total number of the dropped txhashset requests, archive parts are included
+	pub fn dropped_txhashsets(&self) -> u64 {
+		self.dropped_txhashsets.load(Ordering::Relaxed)
+	}
+}
+
+#[test]
+fn test_block_quota() {
+	let limiter = ServeLimiter::new(3, 100, 1);
+	let peer = PeerAddr::from_str("10.0.0.1:3414");
+	let now = 1_000_000;
+	for _ in 0..3 {
+		assert!(limiter.try_serve_block(&peer, now));
+	}
+	assert!(!limiter.try_serve_block(&peer, now));
+	// Quota is restored after the window
+	assert!(limiter.try_serve_block(&peer, now + BLOCK_WINDOW));
+
+	assert!(limiter.try_serve_txhashset(&peer, now));
+	assert!(!limiter.try_serve_txhashset(&peer, now + 10));
+	assert!(limiter.try_serve_txhashset(&peer, now + TXHASHSET_WINDOW));
+	assert_eq!(limiter.dropped_blocks(), 1);
+	assert_eq!(limiter.dropped_txhashsets(), 1);
+}
+
+#[test]
+fn test_txhashset_part_quota() {
+	let limiter = ServeLimiter::new(3, 100, 1);
+	let peer = PeerAddr::from_str("10.0.0.1:3414");
+	let now = 1_000_000;
+	// One archive of 100 bytes per window, in parts
+	for i in 0..4 {
+		assert!(limiter.try_serve_txhashset_part(&peer, now + i, 25, 100));
+	}
+	assert!(!limiter.try_serve_txhashset_part(&peer, now + 10, 25, 100));
+	assert!(limiter.try_serve_txhashset_part(&peer, now + TXHASHSET_WINDOW, 25, 100));
+}
+
+#[test]
+fn test_fair_share() {
+	let limiter = ServeLimiter::new(100, 10, 1);
+	let leecher = PeerAddr::from_str("10.0.0.1:3414");
+	let peer = PeerAddr::from_str("10.0.0.2:3414");
+	let now = 1_000_000;
+	// Leecher took all capacity
+	for _ in 0..10 {
+		assert!(limiter.try_serve_block(&leecher, now));
+	}
+	assert!(!limiter.try_serve_block(&leecher, now));
+	// Other peers still get the fair share
+	for _ in 0..5 {
+		assert!(limiter.try_serve_block(&peer, now));
+	}
+	assert!(!limiter.try_serve_block(&peer, now));
+	assert!(!limiter.try_serve_block(&leecher, now));
+	assert_eq!(limiter.dropped_blocks(), 3);
+}
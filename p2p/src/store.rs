@@ -18,19 +18,23 @@ use chrono::Utc;
 use num::FromPrimitive;
 use rand::seq::SliceRandom;
 use rand::thread_rng;
+use schemars::JsonSchema;
 
 use crate::core::ser::{self, Readable, Reader, Writeable, Writer};
 use crate::types::{Capabilities, PeerAddr, ReasonForBan};
+use grin_store::encryption::EncryptionKey;
 use grin_store::{self, option_to_not_found, to_key, Error};
+use std::sync::Arc;
 
 const DB_NAME: &str = "peerV2";
 const STORE_SUBPATH: &str = "peers";
 
 const PEER_PREFIX: u8 = b'P';
+const GOSSIP_TOPIC_PREFIX: u8 = b'G';
 
 // Types of messages
 enum_from_primitive! {
-	#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+	#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, JsonSchema)]
 	pub enum State {
 		Healthy = 0,
 		Banned = 1,
@@ -39,7 +43,7 @@ enum_from_primitive! {
 }
 
 /// Data stored for any given peer we've encountered.
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct PeerData {
 	/// Network address of the peer.
 	pub addr: PeerAddr,
@@ -56,6 +60,9 @@ pub struct PeerData {
 	pub ban_reason: ReasonForBan,
 	/// Time when we last connected to this peer.
 	pub last_connected: i64,
+	/// Rolling average latency (round trip time) we measured to this peer.
+	#[serde(default)]
+	pub latency_ms: Option<u32>,
 }
 
 impl Writeable for PeerData {
@@ -76,6 +83,13 @@ impl Writeable for PeerData {
 			[write_i32, self.ban_reason as i32],
 			[write_i64, self.last_connected]
 		);
+		match self.latency_ms {
+			Some(latency_ms) => {
+				writer.write_u8(1)?;
+				writer.write_u32(latency_ms)?;
+			}
+			None => writer.write_u8(0)?,
+		}
 		Ok(())
 	}
 }
@@ -94,6 +108,11 @@ impl Readable for PeerData {
 			Err(_) => Utc::now().timestamp(),
 			Ok(lc) => lc,
 		};
+		// latency is optional as well, missing for the peers saved by older versions
+		let latency_ms = match reader.read_u8() {
+			Ok(1) => Some(reader.read_u32()?),
+			_ => None,
+		};
 
 		let user_agent = String::from_utf8(ua)
 			.map_err(|e| ser::Error::CorruptedData(format!("Fail to read user agent, {}", e)))?;
@@ -111,6 +130,7 @@ impl Readable for PeerData {
 				last_banned: lb,
 				ban_reason,
 				last_connected,
+				latency_ms,
 			}),
 			None => Err(ser::Error::CorruptedData(
 				"Unable to read PeerData State".to_string(),
@@ -119,6 +139,44 @@ impl Readable for PeerData {
 	}
 }
 
+/// Dynamically subscribed gossip topic. It is stored so the libp2p node resubscribes
+/// after the restart.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct GossipTopicData {
+	/// Topic name
+	pub topic: String,
+	/// Min integrity fee of the messages
+	pub min_fee: u64,
+	/// Name of the registered handler that processes the topic messages
+	pub handler: String,
+}
+
+impl Writeable for GossipTopicData {
+	fn write<W: Writer>(&self, writer: &mut W) -> Result<(), ser::Error> {
+		writer.write_bytes(&self.topic)?;
+		writer.write_u64(self.min_fee)?;
+		writer.write_bytes(&self.handler)?;
+		Ok(())
+	}
+}
+
+impl Readable for GossipTopicData {
+	fn read<R: Reader>(reader: &mut R) -> Result<GossipTopicData, ser::Error> {
+		let topic = reader.read_bytes_len_prefix()?;
+		let min_fee = reader.read_u64()?;
+		let handler = reader.read_bytes_len_prefix()?;
+		Ok(GossipTopicData {
+			topic: String::from_utf8(topic).map_err(|e| {
+				ser::Error::CorruptedData(format!("Fail to read gossip topic, {}", e))
+			})?,
+			min_fee,
+			handler: String::from_utf8(handler).map_err(|e| {
+				ser::Error::CorruptedData(format!("Fail to read gossip topic handler, {}", e))
+			})?,
+		})
+	}
+}
+
 /// Storage facility for peer data.
 pub struct PeerStore {
 	db: grin_store::Store,
@@ -126,8 +184,15 @@ pub struct PeerStore {
 
 impl PeerStore {
 	/// Instantiates a new peer store under the provided root path.
-	pub fn new(db_root: &str) -> Result<PeerStore, Error> {
-		let db = grin_store::Store::new(db_root, Some(DB_NAME), Some(STORE_SUBPATH), None)?;
+	/// Values are encrypted with the provided key, if any.
+	pub fn new(db_root: &str, encryption: Option<Arc<EncryptionKey>>) -> Result<PeerStore, Error> {
+		let db = grin_store::Store::new_with_key(
+			db_root,
+			Some(DB_NAME),
+			Some(STORE_SUBPATH),
+			None,
+			encryption,
+		)?;
 		Ok(PeerStore { db: db })
 	}
 
@@ -135,25 +200,26 @@ impl PeerStore {
 		debug!("save_peer: {:?} marked {:?}", p.addr.clone(), p.flags);
 
 		let batch = self.db.batch()?;
-		batch.put_ser(&peer_key(p.addr.clone())[..], p)?;
+		batch.put_ser(&self.peer_key(p.addr.clone())?[..], p)?;
 		batch.commit()
 	}
 
 	pub fn get_peer(&self, peer_addr: PeerAddr) -> Result<PeerData, Error> {
-		option_to_not_found(self.db.get_ser(&peer_key(peer_addr.clone())[..]), || {
-			format!("Peer at address: {}", peer_addr)
-		})
+		option_to_not_found(
+			self.db.get_ser(&self.peer_key(peer_addr.clone())?[..]),
+			|| format!("Peer at address: {}", peer_addr),
+		)
 	}
 
 	pub fn exists_peer(&self, peer_addr: PeerAddr) -> Result<bool, Error> {
-		self.db.exists(&peer_key(peer_addr)[..])
+		self.db.exists(&self.peer_key(peer_addr)?[..])
 	}
 
 	/// TODO - allow below added to avoid github issue reports
 	#[allow(dead_code)]
 	pub fn delete_peer(&self, peer_addr: PeerAddr) -> Result<(), Error> {
 		let batch = self.db.batch()?;
-		batch.delete(&peer_key(peer_addr)[..])?;
+		batch.delete(&self.peer_key(peer_addr)?[..])?;
 		batch.commit()
 	}
 
@@ -166,7 +232,9 @@ impl PeerStore {
 		let mut peers = self
 			.db
 			.iter::<PeerData>(&to_key(PEER_PREFIX, ""))?
-			.map(|(_, v)| v)
+			.map(|res| res.map(|(_, v)| v))
+			.collect::<Result<Vec<_>, _>>()?
+			.into_iter()
 			.filter(|p| p.flags == state && p.capabilities.contains(cap))
 			.collect::<Vec<_>>();
 		peers[..].shuffle(&mut thread_rng());
@@ -177,11 +245,16 @@ impl PeerStore {
 	/// Used for /v1/peers/all api endpoint
 	pub fn all_peers(&self) -> Result<Vec<PeerData>, Error> {
 		let key = to_key(PEER_PREFIX, "");
-		Ok(self
-			.db
+		self.db
 			.iter::<PeerData>(&key)?
-			.map(|(_, v)| v)
-			.collect::<Vec<_>>())
+			.map(|res| res.map(|(_, v)| v))
+			.collect::<Result<Vec<_>, _>>()
+	}
+
+	/// Number of the stored peers
+	pub fn peer_count(&self) -> Result<u64, Error> {
+		let key = to_key(PEER_PREFIX, "");
+		Ok(self.db.iter::<PeerData>(&key)?.count() as u64)
 	}
 
 	/// Convenience method to load a peer data, update its status and save it
@@ -190,7 +263,7 @@ impl PeerStore {
 		let batch = self.db.batch()?;
 
 		let mut peer = option_to_not_found(
-			batch.get_ser::<PeerData>(&peer_key(peer_addr.clone())[..]),
+			batch.get_ser::<PeerData>(&self.peer_key(peer_addr.clone())?[..]),
 			|| format!("Peer at address: {}", peer_addr),
 		)?;
 		peer.flags = new_state;
@@ -198,12 +271,27 @@ impl PeerStore {
 			peer.last_banned = Utc::now().timestamp();
 		}
 
-		batch.put_ser(&peer_key(peer_addr)[..], &peer)?;
+		batch.put_ser(&self.peer_key(peer_addr)?[..], &peer)?;
+		batch.commit()
+	}
+
+	/// Convenience method to load a peer data, update its latency and save it back.
+	pub fn update_latency(&self, peer_addr: PeerAddr, latency_ms: u32) -> Result<(), Error> {
+		let batch = self.db.batch()?;
+
+		let mut peer = option_to_not_found(
+			batch.get_ser::<PeerData>(&self.peer_key(peer_addr.clone())?[..]),
+			|| format!("Peer at address: {}", peer_addr),
+		)?;
+		peer.latency_ms = Some(latency_ms);
+
+		batch.put_ser(&self.peer_key(peer_addr)?[..], &peer)?;
 		batch.commit()
 	}
 
-	/// Deletes peers from the storage that satisfy some condition `predicate`
-	pub fn delete_peers<F>(&self, predicate: F) -> Result<(), Error>
+	/// Deletes peers from the storage that satisfy some condition `predicate`.
+	/// Returns the number of deleted peers.
+	pub fn delete_peers<F>(&self, predicate: F) -> Result<u64, Error>
 	where
 		F: Fn(&PeerData) -> bool,
 	{
@@ -215,22 +303,52 @@ impl PeerStore {
 			}
 		}
 
+		let count = to_remove.len() as u64;
+
 		// Delete peers in single batch
 		if !to_remove.is_empty() {
 			let batch = self.db.batch()?;
 
 			for peer in to_remove {
-				batch.delete(&peer_key(peer.addr)[..])?;
+				batch.delete(&self.peer_key(peer.addr)?[..])?;
 			}
 
 			batch.commit()?;
 		}
 
-		Ok(())
+		Ok(count)
 	}
-}
 
-// Ignore the port unless ip is loopback address.
-fn peer_key(peer_addr: PeerAddr) -> Vec<u8> {
-	to_key(PEER_PREFIX, &peer_addr.as_key())
+	/// Save the subscribed gossip topic
+	pub fn save_gossip_topic(&self, topic: &GossipTopicData) -> Result<(), Error> {
+		let batch = self.db.batch()?;
+		batch.put_ser(&self.gossip_topic_key(&topic.topic)?[..], topic)?;
+		batch.commit()
+	}
+
+	/// Delete the gossip topic that we are not subscribed to any more
+	pub fn delete_gossip_topic(&self, topic: &str) -> Result<(), Error> {
+		let batch = self.db.batch()?;
+		batch.delete(&self.gossip_topic_key(topic)?[..])?;
+		batch.commit()
+	}
+
+	/// All subscribed gossip topics
+	pub fn all_gossip_topics(&self) -> Result<Vec<GossipTopicData>, Error> {
+		let key = to_key(GOSSIP_TOPIC_PREFIX, "");
+		self.db
+			.iter::<GossipTopicData>(&key)?
+			.map(|res| res.map(|(_, v)| v))
+			.collect::<Result<Vec<_>, _>>()
+	}
+
+	fn gossip_topic_key(&self, topic: &str) -> Result<Vec<u8>, Error> {
+		self.db.to_masked_key(GOSSIP_TOPIC_PREFIX, topic)
+	}
+
+	// Ignore the port unless ip is loopback address.
+	// The address is masked if the store is encrypted.
+	fn peer_key(&self, peer_addr: PeerAddr) -> Result<Vec<u8>, Error> {
+		self.db.to_masked_key(PEER_PREFIX, &peer_addr.as_key())
+	}
 }
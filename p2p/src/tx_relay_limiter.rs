@@ -0,0 +1,126 @@
+// Copyright 2021 The MWC Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Shaping of the transactions relayed to us by a peer. Every peer has two token
+//! buckets, one for the number of the tx messages (txs, stem txs and tx kernel
+//! announcements) and one for their bytes. A bucket holds a minute worth of tokens and
+//! is refilled continuously, so a peer can burst up to its per minute quota and then
+//! is limited to the refill rate. The messages above the quota are dropped unprocessed,
+//! a single spammy peer can't make the node validate an unbounded number of txs.
+
+use crate::types::P2PConfig;
+
+/// Token bucket refilled at a constant rate up to its capacity
+#[derive(Debug, Clone)]
+struct TokenBucket {
+	/// Max number of tokens, also the refill per minute
+	capacity: u64,
+	/// Available tokens, in 1/60000 of a token so a millisecond refill is never lost
+	tokens: u128,
+	/// Time of the last refill, ms
+	last_refill: i64,
+}
+
+impl TokenBucket {
+	const SCALE: u128 = 60_000;
+
+	fn new(capacity: u64, now: i64) -> TokenBucket {
+		TokenBucket {
+			capacity,
+			tokens: capacity as u128 * TokenBucket::SCALE,
+			last_refill: now,
+		}
+	}
+
+	fn refill(&mut self, now: i64) {
+		let elapsed = now.saturating_sub(self.last_refill).max(0) as u128;
+		let max = self.capacity as u128 * TokenBucket::SCALE;
+		self.tokens = (self.tokens + elapsed * self.capacity as u128).min(max);
+		self.last_refill = now;
+	}
+
+	fn has(&self, amount: u64) -> bool {
+		self.tokens >= amount as u128 * TokenBucket::SCALE
+	}
+
+	fn take(&mut self, amount: u64) {
+		self.tokens = self
+			.tokens
+			.saturating_sub(amount as u128 * TokenBucket::SCALE);
+	}
+}
+
+/// Counters of the tx messages a peer relayed to us
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct TxRelayStats {
+	/// Number of the accepted tx messages
+	pub accepted: u64,
+	/// Bytes of the accepted tx messages
+	pub accepted_bytes: u64,
+	/// Number of the tx messages dropped over the quota
+	pub dropped: u64,
+	/// Bytes of the tx messages dropped over the quota
+	pub dropped_bytes: u64,
+}
+
+/// Per peer quotas of the relayed tx messages
+#[derive(Debug, Clone)]
+pub struct TxRelayLimiter {
+	count: TokenBucket,
+	bytes: TokenBucket,
+	stats: TxRelayStats,
+}
+
+impl TxRelayLimiter {
+	/// Create limiter with the quotas per minute
+	pub fn new(count_per_min: u32, bytes_per_min: u64, now: i64) -> TxRelayLimiter {
+		TxRelayLimiter {
+			count: TokenBucket::new(count_per_min as u64, now),
+			bytes: TokenBucket::new(bytes_per_min, now),
+			stats: TxRelayStats::default(),
+		}
+	}
+
+	/// Create limiter with the quotas from the config
+	pub fn from_config(config: &P2PConfig, now: i64) -> TxRelayLimiter {
+		TxRelayLimiter::new(
+			config.tx_relay_count_per_min(),
+			config.tx_relay_bytes_per_min(),
+			now,
+		)
+	}
+
+	/// Check if the tx message of the provided size (bytes) can be processed at now (ms).
+	/// If yes, it is taken from the quotas. Counted into the stats either way.
+	pub fn try_relay(&mut self, msg_len: u64, now: i64) -> bool {
+		self.count.refill(now);
+		self.bytes.refill(now);
+		if self.count.has(1) && self.bytes.has(msg_len) {
+			self.count.take(1);
+			self.bytes.take(msg_len);
+			self.stats.accepted += 1;
+			self.stats.accepted_bytes += msg_len;
+			true
+		} else {
+			self.stats.dropped += 1;
+			self.stats.dropped_bytes += msg_len;
+			false
+		}
+	}
+
+	/// Counters of the relayed tx messages
+	pub fn stats(&self) -> TxRelayStats {
+		self.stats.clone()
+	}
+}
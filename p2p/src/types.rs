@@ -26,6 +26,7 @@ use std::sync::Arc;
 use std::sync::Mutex;
 
 use chrono::prelude::*;
+use schemars::JsonSchema;
 use serde::de::{SeqAccess, Visitor};
 use serde::{Deserialize, Deserializer};
 use std::sync::atomic::AtomicUsize;
@@ -33,14 +34,19 @@ use std::sync::atomic::AtomicUsize;
 use grin_store;
 
 use crate::chain;
+use crate::chain::types::OutputRoots;
 use crate::core::core;
 use crate::core::core::hash::Hash;
+use crate::core::core::pmmr::{Segment, SegmentIdentifier};
+use crate::core::core::{OutputIdentifier, ShortId, TxKernel};
 use crate::core::global;
 use crate::core::pow::Difficulty;
 use crate::core::ser::{self, ProtocolVersion, Readable, Reader, Writeable, Writer};
-use crate::msg::PeerAddrs;
+use crate::msg::{HistoricalOutput, PeerAddrs};
+use crate::util::secp::pedersen::RangeProof;
 use crate::util::RwLock;
-use std::time::Instant;
+use std::collections::{BTreeMap, HashSet};
+use std::time::{Duration, Instant};
 
 /// Maximum number of block headers a peer should ever send
 pub const MAX_BLOCK_HEADERS: u32 = 512;
@@ -71,6 +77,71 @@ const PEER_MIN_PREFERRED_OUTBOUND_COUNT: u32 = 8;
 /// than allowed by PEER_MAX_INBOUND_COUNT to encourage network bootstrapping.
 const PEER_LISTENER_BUFFER_COUNT: u32 = 8;
 
+/// Max number of the tx messages a peer can relay to us per minute
+const TX_RELAY_COUNT_PER_MIN: u32 = 600;
+
+/// Max bytes of the tx messages a peer can relay to us per minute.
+/// Must be above the size of the largest tx.
+const TX_RELAY_BYTES_PER_MIN: u64 = 10_000_000;
+
+/// Default lowest inbound connections target
+const PEER_MIN_INBOUND_TARGET: u32 = 8;
+
+/// Default lowest outbound connections target
+const PEER_MIN_OUTBOUND_TARGET: u32 = 4;
+
+/// Default CPU load (percent) above which the peer targets are reduced
+const PEER_TARGETS_MAX_CPU_LOAD: u8 = 80;
+
+/// Default number of the blocks waiting for validation above which the peer targets are reduced
+const PEER_TARGETS_MAX_VALIDATION_BACKLOG: usize = 20;
+
+/// Max number of the peers kept in the peer storage
+const PEER_STORE_MAX_COUNT: u32 = 10_000;
+
+/// Seconds after which a peer we never connected to is removed from the peer storage
+const PEER_STORE_UNCONNECTED_EXPIRATION: i64 = 3 * 24 * 3600;
+
+/// Max number of blocks served to a single peer per minute
+const SERVE_BLOCK_QUOTA: u32 = 1200;
+
+/// Max number of blocks served to all peers per minute. When it is reached, the peers
+/// are served with their fair share only.
+const SERVE_BLOCK_CAPACITY: u32 = 3600;
+
+/// Max number of txhashset archives served to a single peer per hour
+const SERVE_TXHASHSET_QUOTA: u32 = 2;
+
+/// Gossipsub heartbeat interval, seconds. Slow adaptation is preferable, we don't want to overload the network.
+const GOSSIP_HEARTBEAT_SECS: u64 = 3;
+
+/// Gossipsub target number of the mesh peers
+const GOSSIP_MESH_N: usize = 6;
+
+/// Gossipsub min number of the mesh peers
+const GOSSIP_MESH_N_LOW: usize = 5;
+
+/// Gossipsub max number of the mesh peers. The node is trying to keep that many connections.
+const GOSSIP_MESH_N_HIGH: usize = 12;
+
+/// Gossipsub number of heartbeats to keep the messages at the cache
+const GOSSIP_HISTORY_LENGTH: usize = 5;
+
+/// Gossipsub max message size, bytes
+const GOSSIP_MAX_MESSAGE_SIZE: usize = 65536;
+
+/// Gossipsub publishes own messages to all the topic peers, not only to the mesh peers
+const GOSSIP_FLOOD_PUBLISH: bool = true;
+
+/// Number of heartbeats that gossipsub advertises the cached messages for. History length can't be lower.
+const GOSSIP_HISTORY_GOSSIP: usize = 3;
+
+/// Min number of outbound mesh peers that gossipsub requires. Mesh low bound can't be lower.
+const GOSSIP_MESH_OUTBOUND_MIN: usize = 2;
+
+/// Weight of the latency rolling average, every new sample counts for 1/LATENCY_AVG_WEIGHT.
+const LATENCY_AVG_WEIGHT: u32 = 8;
+
 #[derive(Debug, Fail)]
 pub enum Error {
 	#[fail(display = "p2p Serialization error, {}", _0)]
@@ -133,7 +204,7 @@ impl From<io::Error> for Error {
 	}
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub enum PeerAddr {
 	Ip(SocketAddr),
 	Onion(String),
@@ -382,6 +453,33 @@ pub struct P2PConfig {
 	pub peer_listener_buffer_count: Option<u32>,
 
 	pub dandelion_peer: Option<PeerAddr>,
+
+	pub serve_block_quota: Option<u32>,
+
+	pub serve_block_capacity: Option<u32>,
+
+	pub serve_txhashset_quota: Option<u32>,
+
+	/// Max number of the tx messages (txs and tx kernels) a peer can relay to us per minute
+	pub tx_relay_count_per_min: Option<u32>,
+
+	/// Max bytes of the tx messages a peer can relay to us per minute
+	pub tx_relay_bytes_per_min: Option<u64>,
+
+	pub peer_store_max_count: Option<u32>,
+
+	pub peer_store_unconnected_expiration: Option<i64>,
+
+	/// Named groups of peers with their own connection limits and relay policies
+	pub peer_groups: Option<Vec<PeerGroupConfig>>,
+
+	/// libp2p gossipsub mesh configuration
+	#[serde(default)]
+	pub libp2p: Libp2pConfig,
+
+	/// Dynamic peer connection targets
+	#[serde(default)]
+	pub peer_targets: PeerTargetsConfig,
 }
 
 /// Default address for peer-to-peer connections.
@@ -403,6 +501,16 @@ impl Default for P2PConfig {
 			peer_min_preferred_outbound_count: None,
 			peer_listener_buffer_count: None,
 			dandelion_peer: None,
+			serve_block_quota: None,
+			serve_block_capacity: None,
+			serve_txhashset_quota: None,
+			tx_relay_count_per_min: None,
+			tx_relay_bytes_per_min: None,
+			peer_store_max_count: None,
+			peer_store_unconnected_expiration: None,
+			peer_groups: None,
+			libp2p: Libp2pConfig::default(),
+			peer_targets: PeerTargetsConfig::default(),
 		}
 	}
 }
@@ -418,6 +526,16 @@ impl P2PConfig {
 		}
 	}
 
+	/// Group of the peer: the first group listing it as a member, otherwise
+	/// the group of all the other peers (if configured)
+	pub fn peer_group(&self, addr: &PeerAddr) -> Option<&PeerGroupConfig> {
+		let groups = self.peer_groups.as_ref()?;
+		groups
+			.iter()
+			.find(|g| g.members().contains(addr))
+			.or_else(|| groups.iter().find(|g| g.peers.is_none()))
+	}
+
 	/// return maximum inbound peer connections count
 	pub fn peer_max_inbound_count(&self) -> u32 {
 		match self.peer_max_inbound_count {
@@ -449,6 +567,313 @@ impl P2PConfig {
 			None => PEER_LISTENER_BUFFER_COUNT,
 		}
 	}
+
+	/// return max number of blocks served to a peer per minute
+	pub fn serve_block_quota(&self) -> u32 {
+		match self.serve_block_quota {
+			Some(n) => n,
+			None => SERVE_BLOCK_QUOTA,
+		}
+	}
+
+	/// return max number of blocks served to all peers per minute
+	pub fn serve_block_capacity(&self) -> u32 {
+		match self.serve_block_capacity {
+			Some(n) => n,
+			None => SERVE_BLOCK_CAPACITY,
+		}
+	}
+
+	/// return max number of txhashset archives served to a peer per hour
+	pub fn serve_txhashset_quota(&self) -> u32 {
+		match self.serve_txhashset_quota {
+			Some(n) => n,
+			None => SERVE_TXHASHSET_QUOTA,
+		}
+	}
+
+	/// return max number of the tx messages a peer can relay to us per minute
+	pub fn tx_relay_count_per_min(&self) -> u32 {
+		match self.tx_relay_count_per_min {
+			Some(n) => n,
+			None => TX_RELAY_COUNT_PER_MIN,
+		}
+	}
+
+	/// return max bytes of the tx messages a peer can relay to us per minute
+	pub fn tx_relay_bytes_per_min(&self) -> u64 {
+		match self.tx_relay_bytes_per_min {
+			Some(n) => n,
+			None => TX_RELAY_BYTES_PER_MIN,
+		}
+	}
+
+	/// return max number of the peers kept in the peer storage
+	pub fn peer_store_max_count(&self) -> u32 {
+		match self.peer_store_max_count {
+			Some(n) => n,
+			None => PEER_STORE_MAX_COUNT,
+		}
+	}
+
+	/// return seconds after which a never connected peer is removed from the peer storage
+	pub fn peer_store_unconnected_expiration(&self) -> i64 {
+		match self.peer_store_unconnected_expiration {
+			Some(n) => n,
+			None => PEER_STORE_UNCONNECTED_EXPIRATION,
+		}
+	}
+}
+
+/// Named group of peers with its own connection limits and relay policy. For example
+/// an exchange can keep its broadcasting node hidden behind the "internal" group
+/// that gets the transactions, while the "public" group gets the blocks only.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct PeerGroupConfig {
+	/// Name of the group
+	pub name: String,
+	/// Members of the group. The group without members takes all the peers that
+	/// are not members of the other groups.
+	#[serde(default)]
+	pub peers: Option<PeerAddrs>,
+	/// Number of the members the node keeps connected, connecting to them if needed.
+	/// Applies to the groups with members.
+	#[serde(default)]
+	pub min_connections: u32,
+	/// Max number of the connected members, no limit if not set
+	#[serde(default)]
+	pub max_connections: Option<u32>,
+	/// Whether the transactions are relayed to the group
+	#[serde(default = "default_relay")]
+	pub relay_txs: bool,
+	/// Whether the blocks and headers are relayed to the group
+	#[serde(default = "default_relay")]
+	pub relay_blocks: bool,
+}
+
+fn default_relay() -> bool {
+	true
+}
+
+impl PeerGroupConfig {
+	/// Members of the group, empty for the group of all the other peers.
+	pub fn members(&self) -> &[PeerAddr] {
+		self.peers.as_ref().map_or(&[], |p| &p.peers)
+	}
+}
+
+/// Dynamic peer connection targets. The node adjusts the number of the inbound and
+/// outbound connections it keeps between the configured minimums and
+/// peer_max_inbound_count / peer_max_outbound_count, depending on the measured
+/// bandwidth, CPU load and validation backlog.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct PeerTargetsConfig {
+	/// Whether the targets are adjusted, otherwise the max counts are used
+	#[serde(default)]
+	pub auto_adjust_peers: bool,
+	/// Lowest inbound connections target
+	#[serde(default = "default_min_inbound_target")]
+	pub min_inbound_target: u32,
+	/// Lowest outbound connections target
+	#[serde(default = "default_min_outbound_target")]
+	pub min_outbound_target: u32,
+	/// Bandwidth available for the peer connections (sent and received), bytes per second.
+	/// No limit if not set.
+	#[serde(default)]
+	pub peer_bandwidth_limit: Option<u64>,
+	/// CPU load (percent) above which the targets are reduced
+	#[serde(default = "default_max_cpu_load")]
+	pub max_cpu_load: u8,
+	/// Number of the blocks waiting for validation above which the targets are reduced
+	#[serde(default = "default_max_validation_backlog")]
+	pub max_validation_backlog: usize,
+}
+
+impl Default for PeerTargetsConfig {
+	fn default() -> PeerTargetsConfig {
+		PeerTargetsConfig {
+			auto_adjust_peers: false,
+			min_inbound_target: default_min_inbound_target(),
+			min_outbound_target: default_min_outbound_target(),
+			peer_bandwidth_limit: None,
+			max_cpu_load: default_max_cpu_load(),
+			max_validation_backlog: default_max_validation_backlog(),
+		}
+	}
+}
+
+fn default_min_inbound_target() -> u32 {
+	PEER_MIN_INBOUND_TARGET
+}
+
+fn default_min_outbound_target() -> u32 {
+	PEER_MIN_OUTBOUND_TARGET
+}
+
+fn default_max_cpu_load() -> u8 {
+	PEER_TARGETS_MAX_CPU_LOAD
+}
+
+fn default_max_validation_backlog() -> usize {
+	PEER_TARGETS_MAX_VALIDATION_BACKLOG
+}
+
+/// Gossipsub mesh configuration for the libp2p node
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Libp2pConfig {
+	/// Heartbeat interval, seconds
+	#[serde(default = "default_gossip_heartbeat_secs")]
+	pub gossip_heartbeat_secs: u64,
+	/// Target number of the mesh peers
+	#[serde(default = "default_gossip_mesh_n")]
+	pub gossip_mesh_n: usize,
+	/// Min number of the mesh peers
+	#[serde(default = "default_gossip_mesh_n_low")]
+	pub gossip_mesh_n_low: usize,
+	/// Max number of the mesh peers
+	#[serde(default = "default_gossip_mesh_n_high")]
+	pub gossip_mesh_n_high: usize,
+	/// Number of heartbeats to keep the messages at the cache
+	#[serde(default = "default_gossip_history_length")]
+	pub gossip_history_length: usize,
+	/// Max message size, bytes
+	#[serde(default = "default_gossip_max_message_size")]
+	pub gossip_max_message_size: usize,
+	/// Publish own messages to all the topic peers instead of the mesh peers only
+	#[serde(default = "default_gossip_flood_publish")]
+	pub gossip_flood_publish: bool,
+	/// Onion addresses of the peers that are kept connected and redialed with a short backoff
+	#[serde(default)]
+	pub pinned_peers: Vec<String>,
+}
+
+impl Default for Libp2pConfig {
+	fn default() -> Libp2pConfig {
+		Libp2pConfig {
+			gossip_heartbeat_secs: default_gossip_heartbeat_secs(),
+			gossip_mesh_n: default_gossip_mesh_n(),
+			gossip_mesh_n_low: default_gossip_mesh_n_low(),
+			gossip_mesh_n_high: default_gossip_mesh_n_high(),
+			gossip_history_length: default_gossip_history_length(),
+			gossip_max_message_size: default_gossip_max_message_size(),
+			gossip_flood_publish: default_gossip_flood_publish(),
+			pinned_peers: vec![],
+		}
+	}
+}
+
+fn default_gossip_heartbeat_secs() -> u64 {
+	GOSSIP_HEARTBEAT_SECS
+}
+
+fn default_gossip_mesh_n() -> usize {
+	GOSSIP_MESH_N
+}
+
+fn default_gossip_mesh_n_low() -> usize {
+	GOSSIP_MESH_N_LOW
+}
+
+fn default_gossip_mesh_n_high() -> usize {
+	GOSSIP_MESH_N_HIGH
+}
+
+fn default_gossip_history_length() -> usize {
+	GOSSIP_HISTORY_LENGTH
+}
+
+fn default_gossip_max_message_size() -> usize {
+	GOSSIP_MAX_MESSAGE_SIZE
+}
+
+fn default_gossip_flood_publish() -> bool {
+	GOSSIP_FLOOD_PUBLISH
+}
+
+fn clamp_config_value<T: Ord + Copy + fmt::Display>(name: &str, value: T, min: T, max: T) -> T {
+	let res = std::cmp::min(std::cmp::max(value, min), max);
+	if res != value {
+		warn!(
+			"libp2p config {} value {} is out of range [{}, {}], using {}",
+			name, value, min, max, res
+		);
+	}
+	res
+}
+
+impl Libp2pConfig {
+	/// Return the config with values clamped into the ranges that gossipsub accepts.
+	/// Mesh bounds are adjusted to keep mesh_n_low <= mesh_n <= mesh_n_high.
+	pub fn validated(&self) -> Libp2pConfig {
+		let gossip_mesh_n = clamp_config_value(
+			"gossip_mesh_n",
+			self.gossip_mesh_n,
+			GOSSIP_MESH_OUTBOUND_MIN * 2,
+			100,
+		);
+		Libp2pConfig {
+			gossip_heartbeat_secs: clamp_config_value(
+				"gossip_heartbeat_secs",
+				self.gossip_heartbeat_secs,
+				1,
+				60,
+			),
+			gossip_mesh_n,
+			gossip_mesh_n_low: clamp_config_value(
+				"gossip_mesh_n_low",
+				self.gossip_mesh_n_low,
+				GOSSIP_MESH_OUTBOUND_MIN,
+				gossip_mesh_n,
+			),
+			gossip_mesh_n_high: clamp_config_value(
+				"gossip_mesh_n_high",
+				self.gossip_mesh_n_high,
+				gossip_mesh_n,
+				200,
+			),
+			gossip_history_length: clamp_config_value(
+				"gossip_history_length",
+				self.gossip_history_length,
+				GOSSIP_HISTORY_GOSSIP,
+				100,
+			),
+			gossip_max_message_size: clamp_config_value(
+				"gossip_max_message_size",
+				self.gossip_max_message_size,
+				1024,
+				4 * 1024 * 1024,
+			),
+			gossip_flood_publish: self.gossip_flood_publish,
+			pinned_peers: self.pinned_peers.clone(),
+		}
+	}
+}
+
+/// Result of a single validation check for the simulated message
+#[derive(Clone, Debug, Serialize, Deserialize, JsonSchema)]
+pub struct SimulationCheck {
+	/// Name of the check
+	pub check: String,
+	/// Error if the message doesn't pass the check
+	pub error: Option<String>,
+}
+
+impl SimulationCheck {
+	/// Message passed the check
+	pub fn passed(check: &str) -> SimulationCheck {
+		SimulationCheck {
+			check: check.to_string(),
+			error: None,
+		}
+	}
+
+	/// Message failed the check
+	pub fn failed(check: &str, error: String) -> SimulationCheck {
+		SimulationCheck {
+			check: check.to_string(),
+			error: Some(error),
+		}
+	}
 }
 
 /// Type of seeding the server will use to find other peers on the network.
@@ -472,7 +897,7 @@ impl Default for Seeding {
 
 bitflags! {
 	/// Options for what type of interaction a peer supports
-	#[derive(Serialize, Deserialize)]
+	#[derive(Serialize, Deserialize, JsonSchema)]
 	pub struct Capabilities: u32 {
 		/// We don't know (yet) what the peer can do.
 		const UNKNOWN = 0b0000_0000;
@@ -488,6 +913,16 @@ bitflags! {
 		const TX_KERNEL_HASH = 0b0000_1000;
 		/// Can send/receive tor addresses
 		const TOR_ADDRESS = 0b0001_0000;
+		/// Can provide the segments of the txhashset MMRs for the txhashset
+		/// archive header, so a syncing node can download them from several
+		/// peers.
+		const PIBD_HIST = 0b0010_0000;
+		/// Archive node, keeps the full chain history and can provide any
+		/// historical block or output.
+		const ARCHIVE_HIST = 0b0100_0000;
+		/// Can provide byte ranges of the txhashset archive, so an interrupted
+		/// download can be resumed.
+		const TXHASHSET_RANGE = 0b1000_0000;
 
 		/// All nodes right now are "full nodes".
 		/// Some nodes internally may maintain longer block histories (archival_mode)
@@ -503,7 +938,7 @@ bitflags! {
 
 // Types of connection
 enum_from_primitive! {
-	#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+	#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, JsonSchema)]
 	pub enum Direction {
 		Inbound = 0,
 		Outbound = 1,
@@ -514,7 +949,7 @@ enum_from_primitive! {
 
 // Ban reason
 enum_from_primitive! {
-	#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+	#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, JsonSchema)]
 	pub enum ReasonForBan {
 		None = 0,
 		BadBlock = 1,
@@ -524,6 +959,7 @@ enum_from_primitive! {
 		ManualBan = 5,
 		FraudHeight = 6,
 		BadHandshake = 7,
+		BadGossip = 8,
 	}
 }
 
@@ -534,6 +970,10 @@ pub struct PeerLiveInfo {
 	pub last_seen: DateTime<Utc>,
 	pub stuck_detector: DateTime<Utc>,
 	pub first_seen: DateTime<Utc>,
+	/// Time the pending latency probe (ping) was sent
+	pub ping_sent: Option<Instant>,
+	/// Rolling average of the round trip time
+	pub latency: Option<Duration>,
 }
 
 /// General information about a connected peer that's useful to other modules.
@@ -558,6 +998,8 @@ impl PeerLiveInfo {
 			first_seen: Utc::now(),
 			last_seen: Utc::now(),
 			stuck_detector: Utc::now(),
+			ping_sent: None,
+			latency: None,
 		}
 	}
 }
@@ -602,10 +1044,43 @@ impl PeerInfo {
 		live_info.total_difficulty = total_difficulty;
 		live_info.last_seen = Utc::now()
 	}
+
+	/// Rolling average of the round trip time to this peer, measured with
+	/// pings on idle connections.
+	pub fn latency(&self) -> Option<Duration> {
+		self.live_info.read().latency
+	}
+
+	/// Seed the rolling average latency, usually with the value we stored
+	/// for this peer last time.
+	pub fn init_latency(&self, latency: Duration) {
+		let mut live_info = self.live_info.write();
+		if live_info.latency.is_none() {
+			live_info.latency = Some(latency);
+		}
+	}
+
+	/// A latency probe (ping) was just sent to this peer.
+	pub fn ping_sent(&self) {
+		self.live_info.write().ping_sent = Some(Instant::now());
+	}
+
+	/// Pong received, completes the pending latency probe if any.
+	/// Returns the rolling average latency updated with the new sample.
+	pub fn pong_received(&self) -> Option<Duration> {
+		let mut live_info = self.live_info.write();
+		let rtt = live_info.ping_sent.take()?.elapsed();
+		let latency = match live_info.latency {
+			Some(avg) => (avg * (LATENCY_AVG_WEIGHT - 1) + rtt) / LATENCY_AVG_WEIGHT,
+			None => rtt,
+		};
+		live_info.latency = Some(latency);
+		Some(latency)
+	}
 }
 
 /// This is needed for legacy purposes
-#[derive(Clone, Debug, Serialize, Deserialize)]
+#[derive(Clone, Debug, Serialize, Deserialize, JsonSchema)]
 pub struct PeerInfoDisplayLegacy {
 	pub capabilities: Capabilities,
 	pub user_agent: String,
@@ -627,6 +1102,8 @@ pub struct PeerInfoDisplay {
 	pub direction: Direction,
 	pub total_difficulty: Difficulty,
 	pub height: u64,
+	#[serde(default)]
+	pub latency_ms: Option<u64>,
 }
 
 impl From<PeerInfo> for PeerInfoDisplay {
@@ -639,7 +1116,126 @@ impl From<PeerInfo> for PeerInfoDisplay {
 			direction: info.direction,
 			total_difficulty: info.total_difficulty(),
 			height: info.height(),
+			latency_ms: info.latency().map(|l| l.as_millis() as u64),
+		}
+	}
+}
+
+/// Size of the peer storage and the number of peers evicted by its retention policies
+/// since the node start.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct PeerStoreStats {
+	/// Number of the stored peers
+	pub size: u64,
+	/// Max number of the stored peers
+	pub max_size: u64,
+	/// Defunct peers removed after the expiration time
+	pub evicted_expired: u64,
+	/// Peers we never connected to, removed after the expiration time
+	pub evicted_unconnected: u64,
+	/// Peers removed to keep the storage under its max size
+	pub evicted_over_cap: u64,
+	/// Time of the last pruning, 0 if the storage wasn't pruned yet
+	pub last_pruned: i64,
+}
+
+/// Mix of the connected peers, by latency, address class and user agent.
+/// Tells a would-be operator whether one more node in their location
+/// would improve the network topology.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct PeerDiversityReport {
+	/// Number of connected peers
+	pub peer_count: u32,
+	/// Peers with average latency below 50ms
+	pub latency_under_50ms: u32,
+	/// Peers with average latency between 50ms and 150ms
+	pub latency_50_150ms: u32,
+	/// Peers with average latency between 150ms and 300ms
+	pub latency_150_300ms: u32,
+	/// Peers with average latency above 300ms
+	pub latency_over_300ms: u32,
+	/// Peers not probed yet
+	pub latency_unknown: u32,
+	/// Peers connected over IPv4
+	pub ipv4: u32,
+	/// Peers connected over IPv6
+	pub ipv6: u32,
+	/// Peers connected over Tor
+	pub onion: u32,
+	/// Number of distinct IPv4 /16 and IPv6 /32 networks the peers are in
+	pub distinct_networks: u32,
+	/// Number of connected peers for every user agent
+	pub user_agents: BTreeMap<String, u32>,
+}
+
+impl PeerDiversityReport {
+	/// Build the report from the connected peers info.
+	pub fn from_peers(peers: &[PeerInfo]) -> PeerDiversityReport {
+		let mut report = PeerDiversityReport::default();
+		let mut networks = HashSet::new();
+		for info in peers {
+			report.peer_count += 1;
+			match info.latency().map(|l| l.as_millis()) {
+				None => report.latency_unknown += 1,
+				Some(ms) if ms < 50 => report.latency_under_50ms += 1,
+				Some(ms) if ms < 150 => report.latency_50_150ms += 1,
+				Some(ms) if ms < 300 => report.latency_150_300ms += 1,
+				Some(_) => report.latency_over_300ms += 1,
+			}
+			match &info.addr {
+				Ip(SocketAddr::V4(addr)) => {
+					report.ipv4 += 1;
+					let octets = addr.ip().octets();
+					networks.insert(vec![octets[0], octets[1]]);
+				}
+				Ip(SocketAddr::V6(addr)) => {
+					report.ipv6 += 1;
+					networks.insert(addr.ip().octets()[..4].to_vec());
+				}
+				Onion(_) => report.onion += 1,
+			}
+			*report
+				.user_agents
+				.entry(info.user_agent.clone())
+				.or_insert(0) += 1;
 		}
+		report.distinct_networks = networks.len() as u32;
+		report
+	}
+}
+
+/// Filter of the live peers, the fields that are not set match any peer.
+#[derive(Debug, Clone, Default)]
+pub struct PeerFilter {
+	/// Inbound or outbound peers, Tor connections are included
+	pub direction: Option<Direction>,
+	/// Peers that have all these capabilities
+	pub capabilities: Option<Capabilities>,
+	/// Banned or not banned peers. Banned peers are live until they are disconnected.
+	pub banned: Option<bool>,
+	/// Case insensitive substring of the user agent
+	pub user_agent: Option<String>,
+}
+
+impl PeerFilter {
+	/// Whether the peer with this info and ban state matches the filter
+	pub fn matches(&self, info: &PeerInfo, banned: bool) -> bool {
+		let direction = match self.direction {
+			Some(Direction::Inbound) | Some(Direction::InboundTor) => info.is_inbound(),
+			Some(Direction::Outbound) | Some(Direction::OutboundTor) => info.is_outbound(),
+			None => true,
+		};
+		let capabilities = self
+			.capabilities
+			.map(|c| info.capabilities.contains(c))
+			.unwrap_or(true);
+		let ban = self.banned.map(|b| b == banned).unwrap_or(true);
+		let user_agent = self
+			.user_agent
+			.as_ref()
+			.map(|ua| info.user_agent.to_lowercase().contains(&ua.to_lowercase()))
+			.unwrap_or(true);
+		direction && capabilities && ban && user_agent
 	}
 }
 
@@ -652,6 +1248,8 @@ pub struct TxHashSetRead {
 	pub kernel_index: u64,
 	/// Binary stream for the txhashset zipped data
 	pub reader: File,
+	/// Hash of the txhashset zipped data
+	pub archive_hash: Hash,
 }
 
 /// Bridge between the networking layer and the rest of the system. Handles the
@@ -665,8 +1263,12 @@ pub trait ChainAdapter: Sync + Send {
 	fn total_height(&self) -> Result<u64, chain::Error>;
 
 	/// A valid transaction has been received from one of our peers
-	fn transaction_received(&self, tx: core::Transaction, stem: bool)
-		-> Result<bool, chain::Error>;
+	fn transaction_received(
+		&self,
+		tx: core::Transaction,
+		stem: bool,
+		peer_info: &PeerInfo,
+	) -> Result<bool, chain::Error>;
 
 	fn get_transaction(&self, kernel_hash: Hash) -> Option<core::Transaction>;
 
@@ -757,6 +1359,88 @@ pub trait ChainAdapter: Sync + Send {
 		peer_peer_info: &PeerInfo,
 	) -> Result<bool, chain::Error>;
 
+	/// Output segment of the txhashset archive header with the provided hash,
+	/// with the positions of the spent outputs in the segment, the merkle proof
+	/// of the bitmap chunks covering it and the output roots, required to
+	/// validate it.
+	fn get_output_segment(
+		&self,
+		hash: Hash,
+		id: SegmentIdentifier,
+	) -> Result<(Segment<OutputIdentifier>, Vec<u64>, Vec<Hash>, OutputRoots), chain::Error>;
+
+	/// Rangeproof segment of the txhashset archive header with the provided hash.
+	fn get_rangeproof_segment(
+		&self,
+		hash: Hash,
+		id: SegmentIdentifier,
+	) -> Result<Segment<RangeProof>, chain::Error>;
+
+	/// Kernel segment of the txhashset archive header with the provided hash.
+	fn get_kernel_segment(
+		&self,
+		hash: Hash,
+		id: SegmentIdentifier,
+	) -> Result<Segment<TxKernel>, chain::Error>;
+
+	/// An output segment has been received from one of our peers. Returns
+	/// false if the segment is invalid, the peer may then be banned.
+	fn receive_output_segment(
+		&self,
+		block_hash: Hash,
+		segment: Segment<OutputIdentifier>,
+		spent: Vec<u64>,
+		bitmap_proof: Vec<Hash>,
+		output_roots: OutputRoots,
+		peer_info: &PeerInfo,
+	) -> Result<bool, chain::Error>;
+
+	/// A rangeproof segment has been received from one of our peers. Returns
+	/// false if the segment is invalid, the peer may then be banned.
+	fn receive_rangeproof_segment(
+		&self,
+		block_hash: Hash,
+		segment: Segment<RangeProof>,
+		peer_info: &PeerInfo,
+	) -> Result<bool, chain::Error>;
+
+	/// A kernel segment has been received from one of our peers. Returns
+	/// false if the segment is invalid, the peer may then be banned.
+	fn receive_kernel_segment(
+		&self,
+		block_hash: Hash,
+		segment: Segment<TxKernel>,
+		peer_info: &PeerInfo,
+	) -> Result<bool, chain::Error>;
+
+	/// Output (spent or not) at the provided output MMR position, served by
+	/// archive nodes.
+	fn get_historical_output(&self, pos: u64) -> Option<HistoricalOutput>;
+
+	/// A historical output has been received from one of our peers.
+	fn historical_output_received(
+		&self,
+		output: HistoricalOutput,
+		peer_info: &PeerInfo,
+	) -> Result<bool, chain::Error>;
+
+	/// Transactions of a recent block matching the provided kernel short_ids
+	/// (built from the block hash and the compact block nonce).
+	fn get_block_transactions(
+		&self,
+		block_hash: Hash,
+		nonce: u64,
+		kern_ids: &[ShortId],
+	) -> Vec<core::Transaction>;
+
+	/// Transactions requested to hydrate a compact block have been received.
+	fn block_transactions_received(
+		&self,
+		block_hash: Hash,
+		txs: Vec<core::Transaction>,
+		peer_info: &PeerInfo,
+	) -> Result<bool, chain::Error>;
+
 	/// Get the Grin specific tmp dir
 	fn get_tmp_dir(&self) -> PathBuf;
 
@@ -778,6 +1462,9 @@ pub trait NetAdapter: ChainAdapter {
 	/// Heard total_difficulty from a connected peer (via ping/pong).
 	fn peer_difficulty(&self, _: PeerAddr, _: Difficulty, _: u64);
 
+	/// Measured the average latency of a connected peer (via ping/pong).
+	fn peer_latency(&self, _: PeerAddr, _: Duration);
+
 	/// Is this peer currently banned?
 	fn is_banned(&self, addr: PeerAddr) -> bool;
 }
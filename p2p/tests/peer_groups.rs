@@ -0,0 +1,61 @@
+// Copyright 2021 The MWC Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use grin_p2p as p2p;
+
+use crate::p2p::msg::PeerAddrs;
+use crate::p2p::types::{P2PConfig, PeerAddr, PeerGroupConfig};
+
+fn group(name: &str, peers: Option<Vec<&str>>, relay_txs: bool) -> PeerGroupConfig {
+	PeerGroupConfig {
+		name: name.to_string(),
+		peers: peers.map(|peers| PeerAddrs {
+			peers: peers.into_iter().map(PeerAddr::from_str).collect(),
+		}),
+		min_connections: 0,
+		max_connections: None,
+		relay_txs,
+		relay_blocks: true,
+	}
+}
+
+#[test]
+fn test_peer_group_lookup() {
+	let internal = PeerAddr::from_str("10.0.0.2:3414");
+	// inbound connections come from an ephemeral port
+	let internal_inbound = PeerAddr::from_str("10.0.0.2:51234");
+	let other = PeerAddr::from_str("192.168.1.1:3414");
+
+	let mut config = P2PConfig::default();
+	assert!(config.peer_group(&internal).is_none());
+
+	config.peer_groups = Some(vec![group("internal", Some(vec!["10.0.0.2:3414"]), true)]);
+	assert_eq!(config.peer_group(&internal).unwrap().name, "internal");
+	assert_eq!(
+		config.peer_group(&internal_inbound).unwrap().name,
+		"internal"
+	);
+	assert!(config.peer_group(&other).is_none());
+
+	// The group without members takes all the other peers.
+	config.peer_groups = Some(vec![
+		group("public", None, false),
+		group("internal", Some(vec!["10.0.0.2:3414"]), true),
+	]);
+	assert_eq!(config.peer_group(&internal).unwrap().name, "internal");
+	let public = config.peer_group(&other).unwrap();
+	assert_eq!(public.name, "public");
+	assert!(public.members().is_empty());
+	assert!(!public.relay_txs);
+}
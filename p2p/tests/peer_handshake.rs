@@ -66,6 +66,7 @@ fn peer_handshake() {
 		Arc::new(StopState::new()),
 		0,
 		None,
+		None,
 	)
 	.unwrap();
 	let server = Arc::new(server_inner.clone());
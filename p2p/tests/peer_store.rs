@@ -0,0 +1,173 @@
+// Copyright 2021 The MWC Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use grin_core as core;
+use grin_p2p as p2p;
+
+use grin_util::StopState;
+
+use chrono::Utc;
+use std::fs;
+use std::sync::Arc;
+
+use crate::core::core::hash::Hash;
+use crate::p2p::libp2p_connection::{self, Libp2pTopicStore};
+use crate::p2p::types::{NetAdapter, PeerAddr};
+use crate::p2p::{Capabilities, GossipTopicData, PeerData, ReasonForBan, State};
+
+fn peer_data(port: u16, flags: State, connected: bool, age: i64) -> PeerData {
+	PeerData {
+		addr: PeerAddr::Ip(format!("10.0.0.1:{}", port).parse().unwrap()),
+		capabilities: if connected {
+			Capabilities::FULL_NODE
+		} else {
+			Capabilities::UNKNOWN
+		},
+		user_agent: if connected {
+			"MW/MWC 4.4.0".to_string()
+		} else {
+			"".to_string()
+		},
+		flags,
+		last_banned: 0,
+		ban_reason: ReasonForBan::None,
+		last_connected: Utc::now().timestamp() - age,
+		latency_ms: None,
+	}
+}
+
+// Never connected peers expire first, then the least useful peers are evicted
+// down to the max size. Banned peers are always kept.
+#[test]
+fn peer_store_prune() {
+	let db_root = ".grin_peer_store_prune";
+	let _ = fs::remove_dir_all(db_root);
+
+	let p2p_config = p2p::P2PConfig {
+		peer_store_max_count: Some(3),
+		peer_store_unconnected_expiration: Some(3600),
+		..p2p::P2PConfig::default()
+	};
+	let server = p2p::Server::new(
+		db_root,
+		p2p::Capabilities::UNKNOWN,
+		p2p_config,
+		Arc::new(p2p::DummyAdapter {}),
+		Hash::from_vec(&vec![]),
+		Arc::new(StopState::new()),
+		0,
+		None,
+		None,
+	)
+	.unwrap();
+	let peers = server.peers.clone();
+
+	let recent = peer_data(1, State::Healthy, true, 100);
+	let unconnected_old = peer_data(2, State::Healthy, false, 7200);
+	let unconnected_new = peer_data(3, State::Healthy, false, 10);
+	let defunct = peer_data(4, State::Defunct, true, 100);
+	let old = peer_data(5, State::Healthy, true, 5000);
+	let banned = peer_data(6, State::Banned, true, 10_000);
+	for p in &[
+		&recent,
+		&unconnected_old,
+		&unconnected_new,
+		&defunct,
+		&old,
+		&banned,
+	] {
+		peers.save_peer(p).unwrap();
+	}
+	assert_eq!(peers.store_stats().size, 6);
+
+	peers.prune_store();
+
+	let addrs: Vec<PeerAddr> = peers.all_peers().into_iter().map(|p| p.addr).collect();
+	assert_eq!(addrs.len(), 3);
+	assert!(addrs.contains(&recent.addr));
+	assert!(addrs.contains(&old.addr));
+	assert!(addrs.contains(&banned.addr));
+
+	let stats = peers.store_stats();
+	assert_eq!(stats.size, 3);
+	assert_eq!(stats.max_size, 3);
+	assert_eq!(stats.evicted_expired, 0);
+	assert_eq!(stats.evicted_unconnected, 1);
+	assert_eq!(stats.evicted_over_cap, 2);
+	assert!(stats.last_pruned > 0);
+
+	// Received addresses over the cap are pruned right away, not by the schedule
+	let received: Vec<PeerAddr> = (10..15)
+		.map(|port| PeerAddr::Ip(format!("10.0.0.2:{}", port).parse().unwrap()))
+		.collect();
+	peers.peer_addrs_received(received);
+	assert_eq!(peers.store_stats().size, 3);
+
+	let _ = fs::remove_dir_all(db_root);
+}
+
+// Dynamically subscribed topics are stored and restored after the restart,
+// topics from the config are not stored.
+#[test]
+fn gossip_topic_store() {
+	let db_root = ".grin_gossip_topic_store";
+	let _ = fs::remove_dir_all(db_root);
+
+	let server = p2p::Server::new(
+		db_root,
+		p2p::Capabilities::UNKNOWN,
+		p2p::P2PConfig::default(),
+		Arc::new(p2p::DummyAdapter {}),
+		Hash::from_vec(&vec![]),
+		Arc::new(StopState::new()),
+		0,
+		None,
+		None,
+	)
+	.unwrap();
+	let peers = server.peers.clone();
+
+	let stored = GossipTopicData {
+		topic: "stored_topic".to_string(),
+		min_fee: 5,
+		handler: libp2p_connection::MESSAGING_TOPIC_HANDLER.to_string(),
+	};
+	peers.save_topic(&stored);
+	peers.save_topic(&GossipTopicData {
+		topic: "plugin_topic".to_string(),
+		min_fee: 0,
+		handler: "test_plugin".to_string(),
+	});
+	assert_eq!(peers.load_topics().len(), 2);
+
+	libp2p_connection::set_topic_store(peers.clone());
+	let topics = libp2p_connection::get_topics();
+	assert_eq!(topics.len(), 1);
+	assert_eq!(topics[0].0, "stored_topic");
+	assert_eq!(topics[0].2, 5);
+
+	assert!(libp2p_connection::add_config_topic(
+		&"config_topic".to_string(),
+		1
+	));
+	assert!(libp2p_connection::add_topic(&"new_topic".to_string(), 2));
+	let mut names: Vec<String> = peers.load_topics().into_iter().map(|t| t.topic).collect();
+	names.sort();
+	assert_eq!(names, vec!["new_topic", "plugin_topic", "stored_topic"]);
+
+	assert!(libp2p_connection::remove_topic(&"stored_topic".to_string()));
+	assert!(!peers.load_topics().contains(&stored));
+
+	let _ = fs::remove_dir_all(db_root);
+}
@@ -0,0 +1,119 @@
+// Copyright 2021 The MWC Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use grin_p2p as p2p;
+
+use crate::p2p::types::{P2PConfig, PeerTargetsConfig};
+use crate::p2p::{PeerLoad, PeerTargets};
+
+fn idle(peer_count: u32, bandwidth: u64) -> PeerLoad {
+	PeerLoad {
+		bandwidth,
+		peer_count,
+		cpu_load: 10,
+		validation_backlog: 0,
+	}
+}
+
+#[test]
+fn test_peer_targets_disabled() {
+	let targets = PeerTargets::from_config(&P2PConfig::default());
+	let load = PeerLoad {
+		cpu_load: 100,
+		validation_backlog: 1_000,
+		..PeerLoad::default()
+	};
+	assert!(!targets.update(&load));
+	assert_eq!(targets.inbound(), 128);
+	assert_eq!(targets.outbound(), 8);
+}
+
+#[test]
+fn test_peer_targets_load() {
+	let config = P2PConfig {
+		peer_max_inbound_count: Some(32),
+		peer_max_outbound_count: Some(8),
+		peer_targets: PeerTargetsConfig {
+			auto_adjust_peers: true,
+			min_inbound_target: 4,
+			min_outbound_target: 2,
+			..PeerTargetsConfig::default()
+		},
+		..P2PConfig::default()
+	};
+	let targets = PeerTargets::from_config(&config);
+	assert_eq!((targets.inbound(), targets.outbound()), (32, 8));
+
+	// Nothing to grow into at the max counts
+	assert!(!targets.update(&idle(40, 0)));
+
+	// Validation backlog builds up, the targets are cut down to the minimums
+	let backlog = PeerLoad {
+		validation_backlog: 50,
+		..idle(40, 0)
+	};
+	assert!(targets.update(&backlog));
+	assert_eq!((targets.inbound(), targets.outbound()), (24, 7));
+	for _ in 0..20 {
+		targets.update(&backlog);
+	}
+	assert_eq!((targets.inbound(), targets.outbound()), (4, 2));
+
+	// Busy CPU, but within its limit, holds the targets
+	let busy = PeerLoad {
+		cpu_load: 75,
+		..idle(6, 0)
+	};
+	assert!(!targets.update(&busy));
+
+	// Headroom, the targets grow back step by step
+	assert!(targets.update(&idle(6, 0)));
+	assert_eq!((targets.inbound(), targets.outbound()), (8, 3));
+	for _ in 0..20 {
+		targets.update(&idle(6, 0));
+	}
+	assert_eq!((targets.inbound(), targets.outbound()), (32, 8));
+}
+
+#[test]
+fn test_peer_targets_bandwidth() {
+	let config = P2PConfig {
+		peer_max_inbound_count: Some(32),
+		peer_max_outbound_count: Some(8),
+		peer_targets: PeerTargetsConfig {
+			auto_adjust_peers: true,
+			min_inbound_target: 4,
+			min_outbound_target: 2,
+			// 100 KB/s
+			peer_bandwidth_limit: Some(100_000),
+			..PeerTargetsConfig::default()
+		},
+		..P2PConfig::default()
+	};
+	let targets = PeerTargets::from_config(&config);
+
+	// Over the limit
+	assert!(targets.update(&idle(40, 200_000)));
+	assert_eq!((targets.inbound(), targets.outbound()), (24, 7));
+	for _ in 0..20 {
+		targets.update(&idle(40, 200_000));
+	}
+	assert_eq!((targets.inbound(), targets.outbound()), (4, 2));
+
+	// 5 KB/s per peer, 80% of the limit is enough for 16 connections
+	for _ in 0..20 {
+		targets.update(&idle(6, 30_000));
+	}
+	assert_eq!((targets.inbound(), targets.outbound()), (12, 4));
+}
@@ -0,0 +1,68 @@
+// Copyright 2021 The MWC Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use grin_core as core;
+use grin_p2p as p2p;
+
+use crate::core::core::hash::Hash;
+use crate::p2p::{SeenCache, SeenCacheStats, SeenNetwork, SeenObject};
+
+fn hash(n: u8) -> Hash {
+	Hash::from_vec(&[n; 32])
+}
+
+#[test]
+fn test_seen_cache_duplicates() {
+	let now = 1_000_000;
+	let cache = SeenCache::new(100, 60_000);
+
+	assert!(!cache.is_seen(&hash(1), SeenObject::Block, SeenNetwork::P2p, now));
+	cache.add(hash(1), SeenNetwork::P2p, now);
+	assert!(cache.is_seen(&hash(1), SeenObject::Block, SeenNetwork::P2p, now));
+	assert!(cache.is_seen(&hash(1), SeenObject::Block, SeenNetwork::Gossip, now));
+
+	// The first network is kept
+	assert!(!cache.check_and_add(hash(2), SeenObject::Transaction, SeenNetwork::Gossip, now));
+	cache.add(hash(2), SeenNetwork::P2p, now);
+	assert!(cache.is_seen(&hash(2), SeenObject::Transaction, SeenNetwork::P2p, now));
+
+	assert_eq!(
+		cache.stats(),
+		SeenCacheStats {
+			size: 2,
+			block_duplicates: 2,
+			tx_duplicates: 1,
+			cross_network_duplicates: 2,
+		}
+	);
+
+	// Expired entries are not duplicates and are dropped on the next add
+	let later = now + 60_001;
+	assert!(!cache.is_seen(&hash(1), SeenObject::Block, SeenNetwork::P2p, later));
+	cache.add(hash(3), SeenNetwork::P2p, later);
+	assert_eq!(cache.stats().size, 1);
+}
+
+#[test]
+fn test_seen_cache_capacity() {
+	let now = 1_000_000;
+	let cache = SeenCache::new(10, 60_000);
+	for n in 0..20 {
+		cache.add(hash(n), SeenNetwork::P2p, now + n as i64);
+	}
+	assert_eq!(cache.stats().size, 10);
+	// The oldest are dropped first
+	assert!(!cache.is_seen(&hash(9), SeenObject::Block, SeenNetwork::P2p, now + 20));
+	assert!(cache.is_seen(&hash(10), SeenObject::Block, SeenNetwork::P2p, now + 20));
+}
@@ -12,9 +12,23 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use grin_core as core;
 use grin_p2p as p2p;
+use grin_util as util;
+
+use crate::core::core::hash::Hash;
+use crate::core::core::pmmr::SegmentIdentifier;
+use crate::core::core::ShortId;
+use crate::core::pow::Difficulty;
+use crate::core::ser::{self, ProtocolVersion};
+use crate::util::secp::pedersen::{Commitment, RangeProof};
+use crate::util::RwLock;
 
 use num::FromPrimitive;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::sync::atomic::AtomicUsize;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 
 // Test that Healthy == 0.
 #[test]
@@ -47,7 +61,7 @@ fn test_capabilities() {
 		p2p::types::Capabilities::UNKNOWN
 	);
 	assert_eq!(
-		p2p::types::Capabilities::from_bits_truncate(0b10000000 as u32),
+		p2p::types::Capabilities::from_bits_truncate(0b100000000 as u32),
 		p2p::types::Capabilities::UNKNOWN
 	);
 
@@ -62,10 +76,13 @@ fn test_capabilities() {
 	assert_eq!(
 		p2p::types::Capabilities::from_bits_truncate(0b11111111 as u32),
 		p2p::types::Capabilities::FULL_NODE
+			| p2p::types::Capabilities::PIBD_HIST
+			| p2p::types::Capabilities::ARCHIVE_HIST
+			| p2p::types::Capabilities::TXHASHSET_RANGE
 	);
 	assert_eq!(
 		p2p::types::Capabilities::from_bits_truncate(0b01011111 as u32),
-		p2p::types::Capabilities::FULL_NODE
+		p2p::types::Capabilities::FULL_NODE | p2p::types::Capabilities::ARCHIVE_HIST
 	);
 
 	assert!(
@@ -78,3 +95,256 @@ fn test_capabilities() {
 			.contains(p2p::types::Capabilities::TX_KERNEL_HASH)
 	);
 }
+
+#[test]
+fn test_simulate_message() {
+	core::global::set_local_chain_type(core::global::ChainTypes::AutomatedTesting);
+	let version = ProtocolVersion::local();
+	let ping = p2p::msg::Ping {
+		total_difficulty: Difficulty::min(),
+		height: 10,
+	};
+	let body = ser::ser_vec(&ping, version).unwrap();
+	let header = p2p::msg::MsgHeader::new(p2p::msg::Type::Ping, body.len() as u64);
+	let mut data = ser::ser_vec(&header, version).unwrap();
+	data.extend_from_slice(&body);
+	assert!(p2p::msg::has_message_magic(&data));
+
+	let (msg_type, checks) = p2p::msg::simulate_message(&data, version);
+	assert_eq!(msg_type, Some(p2p::msg::Type::Ping));
+	assert!(checks.iter().all(|c| c.error.is_none()));
+	assert_eq!(checks.last().unwrap().check, "body_size");
+
+	// Truncated body must fail at the length check
+	let (_, checks) = p2p::msg::simulate_message(&data[..data.len() - 1], version);
+	assert_eq!(checks.last().unwrap().check, "msg_len");
+	assert!(checks.last().unwrap().error.is_some());
+}
+
+#[test]
+fn test_segment_request() {
+	core::global::set_local_chain_type(core::global::ChainTypes::AutomatedTesting);
+	let version = ProtocolVersion::local();
+	let req = p2p::msg::SegmentRequest {
+		block_hash: Hash::default(),
+		identifier: SegmentIdentifier { height: 11, idx: 3 },
+	};
+	let body = ser::ser_vec(&req, version).unwrap();
+	let req2: p2p::msg::SegmentRequest = ser::deserialize(&mut &body[..], version).unwrap();
+	assert_eq!(req, req2);
+
+	let header = p2p::msg::MsgHeader::new(p2p::msg::Type::GetOutputSegment, body.len() as u64);
+	let mut data = ser::ser_vec(&header, version).unwrap();
+	data.extend_from_slice(&body);
+	let (msg_type, checks) = p2p::msg::simulate_message(&data, version);
+	assert_eq!(msg_type, Some(p2p::msg::Type::GetOutputSegment));
+	assert!(checks.iter().all(|c| c.error.is_none()));
+
+	// Segment height above the max is rejected
+	let mut body = body;
+	body[32] = 64;
+	assert!(ser::deserialize::<p2p::msg::SegmentRequest, _>(&mut &body[..], version).is_err());
+}
+
+#[test]
+fn test_historical_output() {
+	core::global::set_local_chain_type(core::global::ChainTypes::AutomatedTesting);
+	let version = ProtocolVersion::local();
+	let resp = p2p::msg::HistoricalOutput {
+		pos: 42,
+		height: 7,
+		spent: true,
+		output: core::core::Output::new(
+			core::core::OutputFeatures::Plain,
+			Commitment::from_vec(vec![9; 33]),
+			RangeProof::zero(),
+		),
+	};
+	let body = ser::ser_vec(&resp, version).unwrap();
+	let resp2: p2p::msg::HistoricalOutput = ser::deserialize(&mut &body[..], version).unwrap();
+	assert_eq!(resp, resp2);
+
+	let header = p2p::msg::MsgHeader::new(p2p::msg::Type::HistoricalOutput, body.len() as u64);
+	let mut data = ser::ser_vec(&header, version).unwrap();
+	data.extend_from_slice(&body);
+	let (msg_type, checks) = p2p::msg::simulate_message(&data, version);
+	assert_eq!(msg_type, Some(p2p::msg::Type::HistoricalOutput));
+	assert!(checks.iter().all(|c| c.error.is_none()));
+}
+
+#[test]
+fn test_block_transactions_request() {
+	core::global::set_local_chain_type(core::global::ChainTypes::AutomatedTesting);
+	let version = ProtocolVersion::local();
+	let req = p2p::msg::BlockTransactionsRequest {
+		block_hash: Hash::default(),
+		nonce: 12,
+		kern_ids: vec![ShortId::zero(), ShortId::from_bytes(&[1, 2, 3, 4, 5, 6])],
+	};
+	let body = ser::ser_vec(&req, version).unwrap();
+	let req2: p2p::msg::BlockTransactionsRequest =
+		ser::deserialize(&mut &body[..], version).unwrap();
+	assert_eq!(req, req2);
+
+	let header = p2p::msg::MsgHeader::new(p2p::msg::Type::GetBlockTransactions, body.len() as u64);
+	let mut data = ser::ser_vec(&header, version).unwrap();
+	data.extend_from_slice(&body);
+	let (msg_type, checks) = p2p::msg::simulate_message(&data, version);
+	assert_eq!(msg_type, Some(p2p::msg::Type::GetBlockTransactions));
+	assert!(checks.iter().all(|c| c.error.is_none()));
+}
+
+#[test]
+fn test_txhashset_part() {
+	core::global::set_local_chain_type(core::global::ChainTypes::AutomatedTesting);
+	let version = ProtocolVersion::local();
+	let req = p2p::msg::TxHashSetPartRequest {
+		hash: Hash::default(),
+		height: 1440,
+		offset: 1_000,
+		length: 16 * 1024 * 1024,
+	};
+	let body = ser::ser_vec(&req, version).unwrap();
+	let header = p2p::msg::MsgHeader::new(p2p::msg::Type::GetTxHashSetPart, body.len() as u64);
+	let mut data = ser::ser_vec(&header, version).unwrap();
+	data.extend_from_slice(&body);
+	let (msg_type, checks) = p2p::msg::simulate_message(&data, version);
+	assert_eq!(msg_type, Some(p2p::msg::Type::GetTxHashSetPart));
+	assert!(checks.iter().all(|c| c.error.is_none()));
+
+	let part = p2p::msg::TxHashSetPart {
+		hash: Hash::default(),
+		height: 1440,
+		bytes: 5_000,
+		archive_hash: Hash::from_vec(&[7; 32]),
+		offset: 1_000,
+		length: 4_000,
+	};
+	let body = ser::ser_vec(&part, version).unwrap();
+	let part2: p2p::msg::TxHashSetPart = ser::deserialize(&mut &body[..], version).unwrap();
+	assert_eq!(part2.archive_hash, part.archive_hash);
+	assert_eq!((part2.offset, part2.length), (1_000, 4_000));
+
+	let header = p2p::msg::MsgHeader::new(p2p::msg::Type::TxHashSetPart, body.len() as u64);
+	let mut data = ser::ser_vec(&header, version).unwrap();
+	data.extend_from_slice(&body);
+	let (msg_type, checks) = p2p::msg::simulate_message(&data, version);
+	assert_eq!(msg_type, Some(p2p::msg::Type::TxHashSetPart));
+	assert!(checks.iter().all(|c| c.error.is_none()));
+
+	// A part beyond the end of the archive is rejected
+	let part = p2p::msg::TxHashSetPart {
+		length: 4_001,
+		..part
+	};
+	let body = ser::ser_vec(&part, version).unwrap();
+	assert!(ser::deserialize::<p2p::msg::TxHashSetPart, _>(&mut &body[..], version).is_err());
+}
+
+#[test]
+fn test_libp2p_config_validated() {
+	let config = p2p::Libp2pConfig::default();
+	assert_eq!(config.validated(), config);
+
+	let config = p2p::Libp2pConfig {
+		gossip_heartbeat_secs: 0,
+		gossip_mesh_n: 8,
+		gossip_mesh_n_low: 10,
+		gossip_mesh_n_high: 4,
+		gossip_history_length: 1,
+		gossip_max_message_size: 10,
+		gossip_flood_publish: false,
+		pinned_peers: vec!["peer".to_string()],
+	}
+	.validated();
+	assert_eq!(config.gossip_heartbeat_secs, 1);
+	assert_eq!(config.gossip_mesh_n_low, 8);
+	assert_eq!(config.gossip_mesh_n_high, 8);
+	assert_eq!(config.gossip_history_length, 3);
+	assert_eq!(config.gossip_max_message_size, 1024);
+	assert!(!config.gossip_flood_publish);
+	assert_eq!(config.pinned_peers, vec!["peer".to_string()]);
+}
+
+#[test]
+fn test_peer_data_latency() {
+	let version = ProtocolVersion::local();
+	let mut peer = p2p::PeerData {
+		addr: p2p::PeerAddr::from_ip(IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1))),
+		capabilities: p2p::Capabilities::FULL_NODE,
+		user_agent: "MW/MWC 4.0.0".to_string(),
+		flags: p2p::State::Healthy,
+		last_banned: 0,
+		ban_reason: p2p::types::ReasonForBan::None,
+		last_connected: 1_600_000_000,
+		latency_ms: Some(42),
+	};
+	let body = ser::ser_vec(&peer, version).unwrap();
+	let peer2: p2p::PeerData = ser::deserialize(&mut &body[..], version).unwrap();
+	assert_eq!(peer2.latency_ms, Some(42));
+	assert_eq!(peer2.last_connected, peer.last_connected);
+
+	// Peers saved by older versions don't have the latency.
+	peer.latency_ms = None;
+	let body = ser::ser_vec(&peer, version).unwrap();
+	let peer2: p2p::PeerData = ser::deserialize(&mut &body[..body.len() - 1], version).unwrap();
+	assert_eq!(peer2.latency_ms, None);
+	assert_eq!(peer2.last_connected, peer.last_connected);
+}
+
+#[test]
+fn test_peer_diversity_report() {
+	let peer = |addr: p2p::PeerAddr, user_agent: &str, latency_ms: Option<u64>| {
+		let info = p2p::PeerInfo {
+			capabilities: p2p::Capabilities::FULL_NODE,
+			user_agent: user_agent.to_string(),
+			version: ProtocolVersion::local(),
+			addr,
+			direction: p2p::Direction::Outbound,
+			live_info: Arc::new(RwLock::new(
+				p2p::types::PeerLiveInfo::new(Difficulty::min()),
+			)),
+			header_sync_requested: Arc::new(AtomicUsize::new(0)),
+			last_header: Arc::new(std::sync::Mutex::new(Instant::now())),
+			last_header_reset: Arc::new(std::sync::Mutex::new(Instant::now())),
+		};
+		if let Some(ms) = latency_ms {
+			info.init_latency(Duration::from_millis(ms));
+		}
+		info
+	};
+	let peers = vec![
+		peer(
+			p2p::PeerAddr::from_ip(IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1))),
+			"MW/MWC 4.0.0",
+			Some(20),
+		),
+		peer(
+			p2p::PeerAddr::from_ip(IpAddr::V4(Ipv4Addr::new(10, 0, 7, 1))),
+			"MW/MWC 4.0.0",
+			Some(400),
+		),
+		peer(
+			p2p::PeerAddr::from_ip(IpAddr::V6(Ipv6Addr::LOCALHOST)),
+			"MW/MWC 3.0.0",
+			Some(100),
+		),
+		peer(
+			p2p::PeerAddr::Onion("abcdef.onion".to_string()),
+			"MW/MWC 4.0.0",
+			None,
+		),
+	];
+
+	let report = p2p::PeerDiversityReport::from_peers(&peers);
+	assert_eq!(report.peer_count, 4);
+	assert_eq!(report.latency_under_50ms, 1);
+	assert_eq!(report.latency_50_150ms, 1);
+	assert_eq!(report.latency_150_300ms, 0);
+	assert_eq!(report.latency_over_300ms, 1);
+	assert_eq!(report.latency_unknown, 1);
+	assert_eq!((report.ipv4, report.ipv6, report.onion), (2, 1, 1));
+	assert_eq!(report.distinct_networks, 2);
+	assert_eq!(report.user_agents.get("MW/MWC 4.0.0"), Some(&3));
+	assert_eq!(report.user_agents.get("MW/MWC 3.0.0"), Some(&1));
+}
@@ -0,0 +1,81 @@
+// Copyright 2021 The MWC Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use grin_p2p as p2p;
+
+use crate::p2p::types::P2PConfig;
+use crate::p2p::{TxRelayLimiter, TxRelayStats};
+
+#[test]
+fn test_tx_relay_count_quota() {
+	let now = 1_000_000;
+	let mut limiter = TxRelayLimiter::new(60, 1_000_000, now);
+
+	// Burst up to the quota per minute
+	for _ in 0..60 {
+		assert!(limiter.try_relay(1_000, now));
+	}
+	assert!(!limiter.try_relay(1_000, now));
+
+	// Refilled at one tx per second
+	assert!(!limiter.try_relay(1_000, now + 999));
+	assert!(limiter.try_relay(1_000, now + 1_000));
+	assert!(!limiter.try_relay(1_000, now + 1_500));
+	assert!(limiter.try_relay(1_000, now + 2_000));
+
+	// Never refilled above the quota
+	let later = now + 3_600_000;
+	for _ in 0..60 {
+		assert!(limiter.try_relay(1_000, later));
+	}
+	assert!(!limiter.try_relay(1_000, later));
+
+	assert_eq!(
+		limiter.stats(),
+		TxRelayStats {
+			accepted: 122,
+			accepted_bytes: 122_000,
+			dropped: 4,
+			dropped_bytes: 4_000,
+		}
+	);
+}
+
+#[test]
+fn test_tx_relay_bytes_quota() {
+	let now = 1_000_000;
+	let mut limiter = TxRelayLimiter::new(1_000, 60_000, now);
+
+	assert!(limiter.try_relay(50_000, now));
+	// Not enough bytes left for a large tx, a small one still fits
+	assert!(!limiter.try_relay(20_000, now));
+	assert!(limiter.try_relay(5_000, now));
+	// 1000 bytes per second
+	assert!(limiter.try_relay(20_000, now + 15_000));
+	assert_eq!(limiter.stats().dropped_bytes, 20_000);
+}
+
+#[test]
+fn test_tx_relay_config() {
+	let config = P2PConfig::default();
+	assert_eq!(config.tx_relay_count_per_min(), 600);
+	let config = P2PConfig {
+		tx_relay_count_per_min: Some(2),
+		..P2PConfig::default()
+	};
+	let mut limiter = TxRelayLimiter::from_config(&config, 0);
+	assert!(limiter.try_relay(100, 0));
+	assert!(limiter.try_relay(100, 0));
+	assert!(!limiter.try_relay(100, 0));
+}
@@ -0,0 +1,168 @@
+// Copyright 2021 The MWC Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Dandelion++ routing decisions. Every epoch the node draws a random secret,
+//! the stem/fluff decision and the relay of each inbound peer are derived from it,
+//! so they stay the same for the whole epoch (a peer sending several txs can't
+//! learn more by observing different routes) and change with the next epoch.
+
+use self::core::core::hash::{Hash, Hashed};
+use crate::types::{DandelionConfig, PoolEntry};
+use chrono::prelude::{DateTime, Utc};
+use grin_core as core;
+use rand::{thread_rng, Rng};
+use schemars::JsonSchema;
+
+/// Number of the outbound relays of an epoch, as in the Dandelion++ paper.
+pub const DANDELION_RELAY_COUNT: usize = 2;
+
+/// Max random extension of the embargo timer of a transaction, seconds.
+pub const DANDELION_EMBARGO_JITTER_SECS: u64 = 30;
+
+/// Upper bounds (seconds) of the stem duration histogram buckets, the last bucket is unbounded.
+pub const STEM_DURATION_BUCKETS: [i64; 4] = [10, 30, 60, 180];
+
+/// Random secret the routing decisions are derived from.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct DandelionSecret(Hash);
+
+impl DandelionSecret {
+	/// New random secret.
+	pub fn random() -> DandelionSecret {
+		let mut bytes = [0u8; 32];
+		thread_rng().fill(&mut bytes);
+		DandelionSecret(Hash::from_vec(&bytes))
+	}
+
+	fn derive(&self, data: &[u8]) -> u64 {
+		(self.0, data.to_vec()).hash().to_u64()
+	}
+
+	/// Whether the epoch of this secret is a "stem" epoch, with the configured probability.
+	pub fn is_stem(&self, stem_probability: u8) -> bool {
+		self.derive(b"stem") % 100 < stem_probability as u64
+	}
+
+	/// Pick `count` distinct items out of `n`, in a pseudorandom order.
+	pub fn select(&self, n: usize, count: usize) -> Vec<usize> {
+		let mut indexes: Vec<usize> = (0..n).collect();
+		indexes.sort_by_key(|i| self.derive(&(*i as u64).to_le_bytes()));
+		indexes.truncate(count);
+		indexes
+	}
+
+	/// Relay slot of the txs received from the source peer. Our own txs (no source)
+	/// always go to the first slot.
+	pub fn relay_slot(&self, source: Option<&[u8]>, slots: usize) -> usize {
+		match source {
+			Some(source) if slots > 0 => (self.derive(source) % slots as u64) as usize,
+			_ => 0,
+		}
+	}
+
+	/// Embargo timer of a transaction, seconds. Each transaction gets its own random
+	/// extension of the configured embargo, so the nodes on the stem path don't fluff
+	/// a lost transaction all at the same time.
+	pub fn embargo_secs(&self, config: &DandelionConfig, kernel_hash: &Hash) -> u64 {
+		config.embargo_secs as u64
+			+ self.derive(kernel_hash.as_bytes()) % (DANDELION_EMBARGO_JITTER_SECS + 1)
+	}
+
+	/// Time the embargo timer of the stempool entry expires, unix timestamp.
+	pub fn embargo_expiry(&self, config: &DandelionConfig, entry: &PoolEntry) -> i64 {
+		let embargo_secs = entry
+			.tx
+			.kernels()
+			.first()
+			.map_or(0, |k| self.embargo_secs(config, &k.hash()));
+		entry.tx_at.timestamp() + embargo_secs as i64
+	}
+}
+
+/// Transaction waiting in the stempool.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct StemPoolEntry {
+	/// Excess of the first kernel of the transaction, hex
+	pub excess: String,
+	/// Transaction weight
+	pub weight: u64,
+	/// Transaction fee
+	pub fee: u64,
+	/// When the transaction was added to the stempool
+	pub tx_at: DateTime<Utc>,
+	/// When the embargo timer expires and the node fluffs the transaction
+	/// unless the network fluffs it first
+	pub embargo_expiry: DateTime<Utc>,
+}
+
+/// Content of the stempool, the entries ordered by the embargo expiry.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct StemPoolStatus {
+	/// Number of the transactions
+	pub count: usize,
+	/// Total weight of the transactions
+	pub total_weight: u64,
+	/// The earliest embargo expiry
+	pub next_embargo_expiry: Option<DateTime<Utc>>,
+	/// The transactions, the earliest embargo expiry first
+	pub entries: Vec<StemPoolEntry>,
+}
+
+/// Statistics of the Dandelion routing of the transactions through this node.
+/// Hop counts of the stem paths are not visible on the wire, the length of the stem
+/// path of a transaction is measured as the time from its stemming by this node to
+/// its fluff by the network.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct DandelionStats {
+	/// Transactions accepted to the stempool and relayed to the next stem peer
+	pub stem_relayed: u64,
+	/// Stem transactions fluffed right away because no stem relay was available
+	pub stem_failed: u64,
+	/// Aggregated fluffs of the stem transactions ending their stem path at this node
+	pub fluffed: u64,
+	/// Stem transactions fluffed by this node because their embargo timer expired
+	pub embargo_expired: u64,
+	/// Stem transactions seen fluffed by the network
+	pub stem_completed: u64,
+	/// Total stem duration of the completed transactions, seconds
+	pub stem_secs_total: u64,
+	/// Longest stem duration, seconds
+	pub stem_secs_max: u64,
+	/// Stem durations histogram, see STEM_DURATION_BUCKETS
+	pub stem_secs_buckets: [u64; STEM_DURATION_BUCKETS.len() + 1],
+}
+
+impl DandelionStats {
+	/// Record a stem transaction seen fluffed by the network after the stem duration.
+	pub fn record_stem_duration(&mut self, secs: i64) {
+		let secs = secs.max(0);
+		let bucket = STEM_DURATION_BUCKETS
+			.iter()
+			.position(|b| secs < *b)
+			.unwrap_or(STEM_DURATION_BUCKETS.len());
+		self.stem_secs_buckets[bucket] += 1;
+		self.stem_completed += 1;
+		self.stem_secs_total += secs as u64;
+		self.stem_secs_max = self.stem_secs_max.max(secs as u64);
+	}
+
+	/// Average stem duration of the completed transactions, seconds.
+	pub fn stem_secs_avg(&self) -> u64 {
+		if self.stem_completed == 0 {
+			0
+		} else {
+			self.stem_secs_total / self.stem_completed
+		}
+	}
+}
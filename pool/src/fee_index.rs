@@ -0,0 +1,197 @@
+// Copyright 2021 The MWC Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Index of the pool entries ordered by fee_to_weight, maintained along with the
+//! entries, so the eviction and the fee estimates don't scan and sort the whole pool.
+
+use self::core::core::Transaction;
+use self::util::secp::pedersen::Commitment;
+use crate::types::PoolError;
+use grin_core as core;
+use grin_util as util;
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+/// Entries are identified by the excess of their first kernel.
+pub fn entry_id(tx: &Transaction) -> Option<Commitment> {
+	tx.kernels().first().map(|k| k.excess())
+}
+
+struct IndexedEntry {
+	fee_to_weight: u64,
+	weight: u64,
+	inputs: Vec<Commitment>,
+	outputs: Vec<Commitment>,
+}
+
+/// Pool entries by fee_to_weight, with the spends between the entries.
+#[derive(Default)]
+pub struct FeeIndex {
+	// entry ids by fee_to_weight, in the insertion order
+	by_rate: BTreeMap<u64, Vec<Commitment>>,
+	entries: HashMap<Commitment, IndexedEntry>,
+	// entry creating the output
+	creators: HashMap<Commitment, Commitment>,
+	// entry spending the output
+	spenders: HashMap<Commitment, Commitment>,
+}
+
+impl FeeIndex {
+	/// Number of the indexed entries.
+	pub fn len(&self) -> usize {
+		self.entries.len()
+	}
+
+	/// Add the tx, the newest entry.
+	pub fn insert(&mut self, tx: &Transaction) {
+		let id = match entry_id(tx) {
+			Some(id) => id,
+			None => return,
+		};
+		if self.entries.contains_key(&id) {
+			return;
+		}
+		let entry = IndexedEntry {
+			fee_to_weight: tx.fee_to_weight(),
+			weight: tx.tx_weight_as_block(),
+			inputs: tx.inputs_committed(),
+			outputs: tx.outputs_committed(),
+		};
+		for c in &entry.inputs {
+			self.spenders.insert(*c, id);
+		}
+		for c in &entry.outputs {
+			self.creators.insert(*c, id);
+		}
+		self.by_rate
+			.entry(entry.fee_to_weight)
+			.or_insert_with(|| vec![])
+			.push(id);
+		self.entries.insert(id, entry);
+	}
+
+	/// Remove the entry with the id.
+	pub fn remove(&mut self, id: &Commitment) {
+		let entry = match self.entries.remove(id) {
+			Some(entry) => entry,
+			None => return,
+		};
+		for c in &entry.inputs {
+			if self.spenders.get(c) == Some(id) {
+				self.spenders.remove(c);
+			}
+		}
+		for c in &entry.outputs {
+			if self.creators.get(c) == Some(id) {
+				self.creators.remove(c);
+			}
+		}
+		if let Some(ids) = self.by_rate.get_mut(&entry.fee_to_weight) {
+			ids.retain(|x| x != id);
+			if ids.is_empty() {
+				self.by_rate.remove(&entry.fee_to_weight);
+			}
+		}
+	}
+
+	/// Ids of the entries to evict so there are less than max_size entries left for the
+	/// new tx. The entry with the lowest fee_to_weight goes first (the newest one on a tie).
+	/// Only the entries no other entry depends on are evicted, a parent can only go after
+	/// all its dependents, and the parents of the new tx are never evicted.
+	/// Fails with OverCapacity if the new tx doesn't pay a higher fee_to_weight than
+	/// the entries that would have to be evicted.
+	pub fn eviction_candidates(
+		&self,
+		tx: &Transaction,
+		max_size: usize,
+	) -> Result<Vec<Commitment>, PoolError> {
+		if self.len() < max_size {
+			return Ok(vec![]);
+		}
+
+		// Parents of the new tx, direct or not
+		let mut parents = HashSet::new();
+		let mut outputs = tx.inputs_committed();
+		while let Some(c) = outputs.pop() {
+			if let Some(id) = self.creators.get(&c) {
+				if parents.insert(*id) {
+					outputs.extend(self.entries[id].inputs.iter().cloned());
+				}
+			}
+		}
+
+		let fee_to_weight = tx.fee_to_weight();
+		let mut evicted: Vec<Commitment> = vec![];
+		while self.len() - evicted.len() >= max_size {
+			let candidate = self
+				.by_rate
+				.range(..fee_to_weight)
+				.flat_map(|(_, ids)| ids.iter().rev())
+				.find(|id| {
+					!evicted.contains(id)
+						&& !parents.contains(*id)
+						&& !self.entries[*id].outputs.iter().any(|c| {
+							self.spenders
+								.get(c)
+								.map(|spender| !evicted.contains(spender))
+								.unwrap_or(false)
+						})
+				});
+			match candidate {
+				Some(id) => evicted.push(*id),
+				None => return Err(PoolError::OverCapacity),
+			}
+		}
+		Ok(evicted)
+	}
+
+	/// Ids of the entries by fee_to_weight, the highest first (the oldest one on a tie).
+	/// Parents in the pool always go before the entries spending their outputs.
+	pub fn by_fee(&self) -> Vec<Commitment> {
+		let mut ordered = Vec::with_capacity(self.len());
+		let mut visited = HashSet::new();
+		for id in self.by_rate.values().rev().flat_map(|ids| ids.iter()) {
+			if visited.contains(id) {
+				continue;
+			}
+			// Depth first, the chains of the pool txs can be long
+			let mut stack = vec![*id];
+			while let Some(id) = stack.last().cloned() {
+				let parent = self.entries[&id]
+					.inputs
+					.iter()
+					.filter_map(|c| self.creators.get(c))
+					.find(|parent| !visited.contains(*parent));
+				match parent {
+					Some(parent) => stack.push(*parent),
+					None => {
+						stack.pop();
+						visited.insert(id);
+						ordered.push(id);
+					}
+				}
+			}
+		}
+		ordered
+	}
+
+	/// Fee_to_weight and weight (as in a block) of the entries, the highest
+	/// fee_to_weight first.
+	pub fn rates(&self) -> Vec<(u64, u64)> {
+		self.by_rate
+			.iter()
+			.rev()
+			.flat_map(|(rate, ids)| ids.iter().map(move |id| (*rate, self.entries[id].weight)))
+			.collect()
+	}
+}
@@ -0,0 +1,226 @@
+// Copyright 2021 The MWC Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Fee estimation from the content of the recent blocks and of the txpool.
+//!
+//! Fee rates are tracked as fee_to_weight (fee * 1000 / tx weight), the estimate is
+//! returned as a fee base, the fee per unit of tx weight, same unit as the
+//! `accept_fee_base` of the pool config. The fee of a tx is `tx_weight * fee_base`.
+//!
+//! Two estimates are combined, the highest wins:
+//! * pool: the fee rate needed to get ahead of the txpool txs that don't fit into
+//!   the target number of blocks.
+//! * blocks: the lowest fee rates of the recent full blocks. A tx paying a rate above
+//!   the lowest rate of a block would have been included in that block.
+
+use self::core::consensus;
+use self::core::core::{Block, TransactionBody};
+use self::core::global;
+use self::util::secp::pedersen::Commitment;
+use crate::types::PoolEntry;
+use grin_core as core;
+use grin_util as util;
+use schemars::JsonSchema;
+use std::collections::{HashMap, VecDeque};
+
+/// Number of the recent blocks tracked by the fee estimator
+pub const FEE_ESTIMATE_BLOCKS: usize = 60;
+
+/// Block is considered full when its weight is above this percent of the max block weight
+pub const FULL_BLOCK_WEIGHT_PERCENT: u64 = 90;
+
+/// Probability (percent) the estimated fee gets the tx mined within the target blocks
+pub const FEE_ESTIMATE_CONFIDENCE: f64 = 95.0;
+
+/// Fee rates of a block accepted by the chain
+#[derive(Clone, Debug)]
+pub struct BlockFees {
+	/// Block height
+	pub height: u64,
+	/// Block weight (consensus weighting)
+	pub weight: u64,
+	/// Lowest fee_to_weight of the block txs, None for the blocks without txs
+	pub min_fee_to_weight: Option<u64>,
+}
+
+/// Fee estimate for a tx to be mined within the target number of blocks
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, JsonSchema)]
+pub struct FeeEstimate {
+	/// Number of blocks the estimate is for
+	pub blocks_target: u64,
+	/// Estimated fee per unit of tx weight
+	pub fee_base: u64,
+	/// Lowest fee per unit of tx weight accepted by the pool
+	pub min_fee_base: u64,
+	/// Total weight (consensus weighting) of the txpool txs
+	pub pool_weight: u64,
+	/// Number of the recent blocks the estimate is based on
+	pub blocks_sampled: usize,
+}
+
+/// Tracks the fee rates of the recent blocks
+#[derive(Clone, Debug, Default)]
+pub struct FeeEstimator {
+	blocks: VecDeque<BlockFees>,
+}
+
+impl FeeEstimator {
+	/// New estimator without any block
+	pub fn new() -> FeeEstimator {
+		FeeEstimator {
+			blocks: VecDeque::with_capacity(FEE_ESTIMATE_BLOCKS),
+		}
+	}
+
+	/// Tracked blocks, oldest first
+	pub fn blocks(&self) -> &VecDeque<BlockFees> {
+		&self.blocks
+	}
+
+	/// Record the fee rates of a block accepted by the chain. Must be called before the block
+	/// is reconciled with the txpool, the fee rates of the block txs we know are taken from
+	/// the txpool entries (txs are aggregated in the block). The rates of the other txs are
+	/// approximated by the aggregated fee rate of the block.
+	pub fn record_block(&mut self, block: &Block, txpool: &[PoolEntry]) {
+		let height = block.header.height;
+		// Blocks at or above this height were rewound by a reorg
+		while self.blocks.back().map(|b| b.height >= height) == Some(true) {
+			self.blocks.pop_back();
+		}
+
+		let kernels: HashMap<Commitment, u64> = block
+			.kernels()
+			.iter()
+			.filter(|k| !k.is_coinbase())
+			.map(|k| (k.excess(), k.features.get_fee()))
+			.collect();
+
+		let mut min_fee_to_weight: Option<u64> = None;
+		let mut known_fee = 0;
+		let mut known_kernels = 0;
+		let mut known_inputs = 0;
+		let mut known_outputs = 0;
+		for entry in txpool {
+			let tx_kernels = entry.tx.kernels();
+			if tx_kernels.is_empty()
+				|| !tx_kernels.iter().all(|k| kernels.contains_key(&k.excess()))
+			{
+				continue;
+			}
+			let ftw = entry.tx.fee_to_weight();
+			min_fee_to_weight = Some(min_fee_to_weight.map_or(ftw, |m| m.min(ftw)));
+			known_fee += entry.tx.fee();
+			known_kernels += tx_kernels.len();
+			known_inputs += entry.tx.inputs().len();
+			known_outputs += entry.tx.outputs().len();
+		}
+
+		// The txs we haven't seen, aggregated
+		let other_kernels = kernels.len().saturating_sub(known_kernels);
+		if other_kernels > 0 {
+			let other_fee = kernels.values().sum::<u64>().saturating_sub(known_fee);
+			let coinbase_outputs = block.outputs().iter().filter(|o| o.is_coinbase()).count();
+			let other_weight = TransactionBody::weight(
+				block.inputs().len().saturating_sub(known_inputs) as u64,
+				block
+					.outputs()
+					.len()
+					.saturating_sub(known_outputs + coinbase_outputs) as u64,
+				other_kernels as u64,
+			);
+			let ftw = other_fee * 1_000 / other_weight;
+			min_fee_to_weight = Some(min_fee_to_weight.map_or(ftw, |m| m.min(ftw)));
+		}
+
+		if self.blocks.len() >= FEE_ESTIMATE_BLOCKS {
+			self.blocks.pop_front();
+		}
+		self.blocks.push_back(BlockFees {
+			height,
+			weight: TransactionBody::weight_as_block(
+				block.inputs().len() as u64,
+				block.outputs().len() as u64,
+				block.kernels().len() as u64,
+			),
+			min_fee_to_weight,
+		});
+	}
+
+	/// Estimate the fee for a tx to be mined within blocks_target blocks (1 to
+	/// FEE_ESTIMATE_BLOCKS) with the given txpool fee rates (fee_to_weight and weight
+	/// of the txs, the highest fee_to_weight first). The estimate is never below the
+	/// accept_fee_base of the pool.
+	pub fn estimate(
+		&self,
+		blocks_target: u64,
+		pool_rates: &[(u64, u64)],
+		accept_fee_base: u64,
+		mineable_max_weight: u64,
+	) -> FeeEstimate {
+		let blocks_target = blocks_target.max(1).min(FEE_ESTIMATE_BLOCKS as u64);
+		let min_fee_to_weight = accept_fee_base * 1_000;
+
+		// Weight available for the txs in a block, the coinbase output and kernel excluded
+		let block_capacity = global::max_block_weight()
+			.min(mineable_max_weight)
+			.saturating_sub(consensus::BLOCK_OUTPUT_WEIGHT + consensus::BLOCK_KERNEL_WEIGHT);
+
+		// Pool: the rate of the first tx that doesn't fit into the target blocks
+		let pool_weight: u64 = pool_rates.iter().map(|(_, w)| w).sum();
+		let capacity = block_capacity.saturating_mul(blocks_target);
+		let mut weight = 0;
+		let mut pool_fee_to_weight = 0;
+		for &(ftw, tx_weight) in pool_rates {
+			weight += tx_weight;
+			if weight > capacity {
+				pool_fee_to_weight = ftw + 1;
+				break;
+			}
+		}
+
+		// Blocks: a tx paying below the lowest rate of a full block would have missed it.
+		// The tx misses all the target blocks with the probability (share of the blocks
+		// above its rate)^blocks_target, that share is bounded by the confidence.
+		let full_weight = global::max_block_weight() * FULL_BLOCK_WEIGHT_PERCENT / 100;
+		let mut block_rates: Vec<u64> = self
+			.blocks
+			.iter()
+			.map(|b| {
+				if b.weight >= full_weight {
+					b.min_fee_to_weight.unwrap_or(0)
+				} else {
+					0
+				}
+			})
+			.collect();
+		block_rates.sort_unstable();
+		let mut blocks_fee_to_weight = 0;
+		if !block_rates.is_empty() {
+			let miss = (1.0 - FEE_ESTIMATE_CONFIDENCE / 100.0).powf(1.0 / blocks_target as f64);
+			let idx = ((1.0 - miss) * block_rates.len() as f64).ceil() as usize;
+			blocks_fee_to_weight = block_rates[idx.min(block_rates.len()).saturating_sub(1)];
+		}
+
+		let fee_to_weight = min_fee_to_weight
+			.max(pool_fee_to_weight)
+			.max(blocks_fee_to_weight);
+		FeeEstimate {
+			blocks_target,
+			fee_base: (fee_to_weight + 999) / 1_000,
+			min_fee_base: accept_fee_base,
+			pool_weight,
+			blocks_sampled: self.blocks.len(),
+		}
+	}
+}
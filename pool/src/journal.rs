@@ -0,0 +1,127 @@
+// Copyright 2021 The MWC Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Journal of the txpool entries, so the pool survives the node restarts.
+//! The accepted entries are appended to the journal file as json lines. Entries are not
+//! removed from the journal when they leave the pool (mined, evicted), instead the journal
+//! is revalidated when it is loaded and rewritten with the entries that are still valid.
+//! It is also rewritten when it grows well above the pool size.
+
+use crate::types::{PoolEntry, PoolError};
+use std::fs::{self, File, OpenOptions};
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+/// Number of journaled entries below which the journal is never compacted
+pub const JOURNAL_COMPACTION_MIN: usize = 1_000;
+
+/// Append only journal file of the txpool entries
+pub struct PoolJournal {
+	path: PathBuf,
+	file: File,
+	len: usize,
+}
+
+impl PoolJournal {
+	/// Open the journal at path, created if missing. Returns the journal with the journaled
+	/// entries. Unreadable entries (e.g. the last one, half written when the node crashed)
+	/// are skipped.
+	pub fn open<P: AsRef<Path>>(path: P) -> Result<(PoolJournal, Vec<PoolEntry>), PoolError> {
+		let path = path.as_ref().to_path_buf();
+		let mut entries = vec![];
+		if path.exists() {
+			let file = File::open(&path).map_err(|e| journal_error(&path, e))?;
+			for line in BufReader::new(file).lines() {
+				let line = line.map_err(|e| journal_error(&path, e))?;
+				match serde_json::from_str::<PoolEntry>(&line) {
+					Ok(entry) => entries.push(entry),
+					Err(e) => warn!(
+						"Skipping unreadable entry of the pool journal {}, {}",
+						path.display(),
+						e
+					),
+				}
+			}
+		}
+		let file = open_append(&path)?;
+		let journal = PoolJournal {
+			path,
+			file,
+			len: entries.len(),
+		};
+		Ok((journal, entries))
+	}
+
+	/// Number of the journaled entries
+	pub fn len(&self) -> usize {
+		self.len
+	}
+
+	/// Whether the journal should be rewritten with the entries of the pool of the given size.
+	pub fn needs_compaction(&self, pool_size: usize) -> bool {
+		self.len > JOURNAL_COMPACTION_MIN.max(2 * pool_size)
+	}
+
+	/// Append the entry to the journal.
+	pub fn append(&mut self, entry: &PoolEntry) -> Result<(), PoolError> {
+		let line = serde_json::to_string(entry)
+			.map_err(|e| PoolError::Other(format!("Unable to serialize pool entry, {}", e)))?;
+		writeln!(self.file, "{}", line)
+			.and_then(|_| self.file.flush())
+			.map_err(|e| journal_error(&self.path, e))?;
+		self.len += 1;
+		Ok(())
+	}
+
+	/// Replace the content of the journal with the entries. The entries are written into
+	/// a temporary file first, so the journal is never left half written.
+	pub fn rewrite(&mut self, entries: &[PoolEntry]) -> Result<(), PoolError> {
+		let tmp_path = self.path.with_extension("tmp");
+		let write_tmp = || -> Result<(), PoolError> {
+			let mut writer =
+				BufWriter::new(File::create(&tmp_path).map_err(|e| journal_error(&tmp_path, e))?);
+			for entry in entries {
+				let line = serde_json::to_string(entry).map_err(|e| {
+					PoolError::Other(format!("Unable to serialize pool entry, {}", e))
+				})?;
+				writeln!(writer, "{}", line).map_err(|e| journal_error(&tmp_path, e))?;
+			}
+			writer
+				.into_inner()
+				.map_err(|e| PoolError::Other(format!("Unable to write pool journal, {}", e)))?
+				.sync_all()
+				.map_err(|e| journal_error(&tmp_path, e))
+		};
+		if let Err(e) = write_tmp() {
+			let _ = fs::remove_file(&tmp_path);
+			return Err(e);
+		}
+		fs::rename(&tmp_path, &self.path).map_err(|e| journal_error(&self.path, e))?;
+		self.file = open_append(&self.path)?;
+		self.len = entries.len();
+		Ok(())
+	}
+}
+
+fn open_append(path: &Path) -> Result<File, PoolError> {
+	OpenOptions::new()
+		.create(true)
+		.append(true)
+		.open(path)
+		.map_err(|e| journal_error(path, e))
+}
+
+fn journal_error(path: &Path, e: std::io::Error) -> PoolError {
+	PoolError::Other(format!("Pool journal {} error, {}", path.display(), e))
+}
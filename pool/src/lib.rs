@@ -30,12 +30,22 @@ extern crate serde_derive;
 #[macro_use]
 extern crate log;
 
+pub mod dandelion;
+mod fee_index;
+pub mod fees;
+pub mod journal;
+pub mod policy;
 mod pool;
 pub mod transaction_pool;
 pub mod types;
 
+pub use crate::dandelion::{DandelionSecret, DandelionStats, StemPoolEntry, StemPoolStatus};
+pub use crate::fees::{FeeEstimate, FeeEstimator};
+pub use crate::journal::PoolJournal;
+pub use crate::policy::{AcceptancePolicy, AcceptancePolicyType};
 pub use crate::pool::Pool;
-pub use crate::transaction_pool::TransactionPool;
+pub use crate::transaction_pool::{check_tx_policies, TransactionPool};
 pub use crate::types::{
-	BlockChain, DandelionConfig, PoolAdapter, PoolConfig, PoolEntry, PoolError, TxSource,
+	BlockChain, DandelionConfig, PoolAdapter, PoolConfig, PoolEntry, PoolError, ReorgTxStats,
+	TxPolicy, TxPolicyDecision, TxPolicySummary, TxSource,
 };
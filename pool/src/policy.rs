@@ -0,0 +1,184 @@
+// Copyright 2021 The MWC Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Acceptance policy of the pool: the fee, weight and kernel rules a valid transaction
+//! must also meet to be accepted. The relay nodes run the default policy, the mining
+//! pools can select the strict one, or set their own implementation.
+
+use self::core::core::{KernelFeatures, Transaction};
+use crate::types::{PoolConfig, PoolError};
+use grin_core as core;
+use std::sync::Arc;
+
+/// Acceptance policy of the pool. The rules are checked before the transaction is
+/// validated against the chain, on top of the consensus rules.
+pub trait AcceptancePolicy: Sync + Send {
+	/// Name of the policy, for the logs.
+	fn name(&self) -> &str;
+
+	/// Min fee the transaction must pay.
+	fn min_fee(&self, tx: &Transaction) -> u64;
+
+	/// Max weight of the transaction, None if only the consensus limit applies.
+	fn max_weight(&self) -> Option<u64>;
+
+	/// Whether the transactions with a kernel with these features are accepted.
+	fn allows_kernel(&self, features: &KernelFeatures) -> bool;
+
+	/// Whether a transaction with the lock height is accepted when the next block
+	/// is at next_height. Consensus doesn't allow the lock height above next_height.
+	fn allows_lock_height(&self, lock_height: u64, next_height: u64) -> bool;
+
+	/// Check the transaction against the policy.
+	fn check(&self, tx: &Transaction, next_height: u64) -> Result<(), PoolError> {
+		let min_fee = self.min_fee(tx);
+		if tx.fee() < min_fee {
+			return Err(PoolError::LowFeeTransaction(min_fee));
+		}
+		if let Some(max_weight) = self.max_weight() {
+			if tx.tx_weight() > max_weight {
+				return Err(PoolError::PolicyRejected(format!(
+					"tx weight {} is above {}",
+					tx.tx_weight(),
+					max_weight
+				)));
+			}
+		}
+		for k in tx.kernels() {
+			if !self.allows_kernel(&k.features) {
+				return Err(PoolError::PolicyRejected(format!(
+					"{} kernels are not accepted",
+					k.features.as_string()
+				)));
+			}
+		}
+		if !self.allows_lock_height(tx.lock_height(), next_height) {
+			return Err(PoolError::ImmatureTransaction);
+		}
+		Ok(())
+	}
+}
+
+/// Acceptance policy selected by the config.
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq)]
+pub enum AcceptancePolicyType {
+	/// The relay rules, the fee base of the pool config.
+	Default,
+	/// The relay rules, with the strict_* limits of the pool config on top.
+	Strict,
+}
+
+impl Default for AcceptancePolicyType {
+	fn default() -> AcceptancePolicyType {
+		AcceptancePolicyType::Default
+	}
+}
+
+/// Policy of the relay nodes: the transaction pays the fee base for its weight,
+/// any kernel is accepted.
+pub struct DefaultAcceptancePolicy {
+	accept_fee_base: u64,
+}
+
+impl DefaultAcceptancePolicy {
+	/// Policy with the fee base.
+	pub fn new(accept_fee_base: u64) -> DefaultAcceptancePolicy {
+		DefaultAcceptancePolicy { accept_fee_base }
+	}
+}
+
+impl AcceptancePolicy for DefaultAcceptancePolicy {
+	fn name(&self) -> &str {
+		"default"
+	}
+
+	fn min_fee(&self, tx: &Transaction) -> u64 {
+		// for a basic transaction (1 input, 2 outputs) -
+		// (-1 * 1) + (4 * 2) + 1 = 8
+		// 8 * 10 = 80
+		tx.tx_weight() * self.accept_fee_base
+	}
+
+	fn max_weight(&self) -> Option<u64> {
+		None
+	}
+
+	fn allows_kernel(&self, _features: &KernelFeatures) -> bool {
+		true
+	}
+
+	fn allows_lock_height(&self, lock_height: u64, next_height: u64) -> bool {
+		lock_height <= next_height
+	}
+}
+
+/// Policy for the mining pools: a higher fee base, max transaction weight and
+/// a choice of the accepted kernel features.
+pub struct StrictAcceptancePolicy {
+	fee_base: u64,
+	max_tx_weight: Option<u64>,
+	allow_height_locked: bool,
+	allow_nrd: bool,
+}
+
+impl StrictAcceptancePolicy {
+	/// Policy with the strict_* limits of the config.
+	pub fn new(config: &PoolConfig) -> StrictAcceptancePolicy {
+		StrictAcceptancePolicy {
+			fee_base: config
+				.strict_fee_base
+				.unwrap_or(config.accept_fee_base)
+				.max(config.accept_fee_base),
+			max_tx_weight: config.strict_max_tx_weight,
+			allow_height_locked: config.strict_allow_height_locked,
+			allow_nrd: config.strict_allow_nrd,
+		}
+	}
+}
+
+impl AcceptancePolicy for StrictAcceptancePolicy {
+	fn name(&self) -> &str {
+		"strict"
+	}
+
+	fn min_fee(&self, tx: &Transaction) -> u64 {
+		tx.tx_weight() * self.fee_base
+	}
+
+	fn max_weight(&self) -> Option<u64> {
+		self.max_tx_weight
+	}
+
+	fn allows_kernel(&self, features: &KernelFeatures) -> bool {
+		match features {
+			KernelFeatures::HeightLocked { .. } => self.allow_height_locked,
+			KernelFeatures::NoRecentDuplicate { .. } => self.allow_nrd,
+			_ => true,
+		}
+	}
+
+	fn allows_lock_height(&self, lock_height: u64, next_height: u64) -> bool {
+		lock_height <= next_height
+	}
+}
+
+/// Acceptance policy selected by the config.
+pub fn from_config(config: &PoolConfig) -> Arc<dyn AcceptancePolicy> {
+	match config.acceptance_policy {
+		AcceptancePolicyType::Default => {
+			Arc::new(DefaultAcceptancePolicy::new(config.accept_fee_base))
+		}
+		AcceptancePolicyType::Strict => Arc::new(StrictAcceptancePolicy::new(config)),
+	}
+}
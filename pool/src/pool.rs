@@ -22,8 +22,11 @@ use self::core::core::verifier_cache::VerifierCache;
 use self::core::core::{
 	Block, BlockHeader, BlockSums, Committed, OutputIdentifier, Transaction, TxKernel, Weighting,
 };
+use self::util::secp::pedersen::Commitment;
 use self::util::RwLock;
+use crate::fee_index::{entry_id, FeeIndex};
 use crate::types::{BlockChain, PoolEntry, PoolError};
+use chrono::prelude::{DateTime, Utc};
 use grin_core as core;
 use grin_util as util;
 use std::cmp::Reverse;
@@ -36,11 +39,19 @@ where
 	V: VerifierCache,
 {
 	/// Entries in the pool (tx + info + timer) in simple insertion order.
-	pub entries: Vec<PoolEntry>,
+	/// Changed through the pool methods, which keep the fee index in sync.
+	entries: Vec<PoolEntry>,
 	/// The blockchain
 	pub blockchain: Arc<B>,
 	pub verifier_cache: Arc<RwLock<V>>,
 	pub name: String,
+	/// Incremented on every change of the entries, the views of the pool cached
+	/// by the callers (like the mineable txs) are keyed on it.
+	pub generation: u64,
+	/// Entries by fee_to_weight
+	fee_index: FeeIndex,
+	/// Time the entries were first seen, by the entry id
+	entry_times: HashMap<Commitment, DateTime<Utc>>,
 }
 
 impl<B, V> Pool<B, V>
@@ -54,6 +65,9 @@ where
 			blockchain: chain,
 			verifier_cache,
 			name,
+			generation: 0,
+			fee_index: FeeIndex::default(),
+			entry_times: HashMap::new(),
 		}
 	}
 
@@ -63,6 +77,18 @@ where
 		self.entries.iter().any(|x| x.tx.kernels() == tx.kernels())
 	}
 
+	/// Time the entry with the same kernels as the tx was first seen, if the pool has it.
+	pub fn entry_tx_at(&self, tx: &Transaction) -> Option<DateTime<Utc>> {
+		entry_id(tx).and_then(|id| self.entry_times.get(&id).cloned())
+	}
+
+	/// Does the pool contain a transaction with the kernel excess?
+	pub fn contains_kernel(&self, excess: &Commitment) -> bool {
+		self.entries
+			.iter()
+			.any(|x| x.tx.kernels().iter().any(|k| k.excess == *excess))
+	}
+
 	/// Query the tx pool for an individual tx matching the given kernel hash.
 	pub fn retrieve_tx_by_kernel_hash(&self, hash: Hash) -> Option<Transaction> {
 		for x in &self.entries {
@@ -168,20 +194,38 @@ where
 	// Aggregate this new tx with all existing txs in the pool.
 	// If we can validate the aggregated tx against the current chain state
 	// then we can safely add the tx to the pool.
+	// If the pool is limited to max_size entries and is full, the entries with a lower
+	// fee_to_weight than the new tx are evicted to make space for it.
+	// Returns the evicted entries.
 	pub fn add_to_pool(
 		&mut self,
 		entry: PoolEntry,
 		extra_tx: Option<Transaction>,
 		header: &BlockHeader,
-	) -> Result<(), PoolError> {
-		// Combine all the txs from the pool with any extra txs provided.
-		let mut txs = self.all_transactions();
-
+		max_size: Option<usize>,
+	) -> Result<Vec<PoolEntry>, PoolError> {
 		// Quick check to see if we have seen this tx before.
-		if txs.contains(&entry.tx) {
+		if self.entries.iter().any(|x| x.tx == entry.tx) {
 			return Err(PoolError::DuplicateTx);
 		}
 
+		let evicted = match max_size {
+			Some(max_size) => self.fee_index.eviction_candidates(&entry.tx, max_size)?,
+			None => vec![],
+		};
+		let is_evicted = |x: &PoolEntry| match entry_id(&x.tx) {
+			Some(id) => evicted.contains(&id),
+			None => false,
+		};
+
+		// Combine all the txs from the pool (minus the evicted ones) with any extra txs provided.
+		let mut txs: Vec<_> = self
+			.entries
+			.iter()
+			.filter(|x| !is_evicted(x))
+			.map(|x| x.tx.clone())
+			.collect();
+
 		// Make sure we take extra_tx into consideration here.
 		// When adding to stempool we need to account for current txpool.
 		txs.extend(extra_tx);
@@ -199,11 +243,42 @@ where
 		// Validate aggregated tx (existing pool + new tx), ignoring tx weight limits.
 		// Validate against known chain state at the provided header.
 		self.validate_raw_tx(&agg_tx, header, Weighting::NoLimit)?;
-		// If we get here successfully then we can safely add the entry to the pool.
+
+		// If we get here successfully then we can safely evict the entries and add
+		// the new entry to the pool.
+		let mut evicted_entries = vec![];
+		if !evicted.is_empty() {
+			for x in std::mem::replace(&mut self.entries, vec![]) {
+				if is_evicted(&x) {
+					evicted_entries.push(x);
+				} else {
+					self.entries.push(x);
+				}
+			}
+			for id in &evicted {
+				self.fee_index.remove(id);
+				self.entry_times.remove(id);
+			}
+		}
+		for x in &evicted_entries {
+			debug!(
+				"add_to_pool [{}]: {} evicted for {}, fee_to_weight {} < {}",
+				self.name,
+				x.tx.hash(),
+				entry.tx.hash(),
+				x.tx.fee_to_weight(),
+				entry.tx.fee_to_weight(),
+			);
+		}
 		self.log_pool_add(&entry, header);
+		self.fee_index.insert(&entry.tx);
+		if let Some(id) = entry_id(&entry.tx) {
+			self.entry_times.entry(id).or_insert(entry.tx_at);
+		}
 		self.entries.push(entry);
+		self.generation += 1;
 
-		Ok(())
+		Ok(evicted_entries)
 	}
 
 	fn log_pool_add(&self, entry: &PoolEntry, header: &BlockHeader) {
@@ -327,21 +402,15 @@ where
 	) -> Result<(), PoolError> {
 		let existing_entries = self.entries.clone();
 		self.entries.clear();
+		self.fee_index = FeeIndex::default();
+		self.entry_times.clear();
+		self.generation += 1;
 		for x in existing_entries {
-			let _ = self.add_to_pool(x, extra_tx.clone(), header);
+			let _ = self.add_to_pool(x, extra_tx.clone(), header, None);
 		}
 		Ok(())
 	}
 
-	// Use our bucket logic to identify the best transaction for eviction and evict it.
-	// We want to avoid evicting a transaction where another transaction depends on it.
-	// We want to evict a transaction with low fee_to_weight.
-	pub fn evict_transaction(&mut self) {
-		if let Some(evictable_transaction) = self.bucket_transactions(Weighting::NoLimit).last() {
-			self.entries.retain(|x| x.tx != *evictable_transaction);
-		};
-	}
-
 	/// Buckets consist of a vec of txs and track the aggregate fee_to_weight.
 	/// We aggregate (cut-through) dependent transactions within a bucket *unless* adding a tx
 	/// would reduce the aggregate fee_to_weight, in which case we start a new bucket.
@@ -354,7 +423,18 @@ where
 		let mut output_commits = HashMap::new();
 		let mut rejected = HashSet::new();
 
-		for entry in &self.entries {
+		// Entries in the fee index order, the parents go before the txs spending their outputs.
+		let entries: HashMap<_, _> = self
+			.entries
+			.iter()
+			.filter_map(|x| entry_id(&x.tx).map(|id| (id, x)))
+			.collect();
+		for entry in self
+			.fee_index
+			.by_fee()
+			.iter()
+			.filter_map(|id| entries.get(id))
+		{
 			// check the commits index to find parents and their position
 			// if single parent then we are good, we can bucket it with its parent
 			// if multiple parents then we need to combine buckets, but for now simply reject it (rare case)
@@ -440,7 +520,7 @@ where
 		// Sort buckets by fee_to_weight (descending) and age (oldest first).
 		// Txs with highest fee_to_weight will be prioritied.
 		// Aggregation that increases the fee_to_weight of a bucket will prioritize the bucket.
-		// Oldest (based on pool insertion time) will then be prioritized.
+		// Then the fee index order is kept, the oldest tx first on equal fee_to_weight.
 		tx_buckets.sort_unstable_by_key(|x| (Reverse(x.fee_to_weight), x.age_idx));
 
 		tx_buckets.into_iter().flat_map(|x| x.raw_txs).collect()
@@ -473,13 +553,57 @@ where
 		// Also reject any txs where we see a conflicting tx,
 		// where an input is spent in a different tx.
 		let block_inputs: Vec<_> = block.inputs().into();
-		self.entries.retain(|x| {
+		self.retain(|x| {
 			let tx_inputs: Vec<_> = x.tx.inputs().into();
 			!x.tx.kernels().iter().any(|y| block.kernels().contains(y))
 				&& !tx_inputs.iter().any(|y| block_inputs.contains(y))
 		});
 	}
 
+	/// Keep only the entries matching the predicate.
+	pub fn retain<F>(&mut self, mut f: F)
+	where
+		F: FnMut(&PoolEntry) -> bool,
+	{
+		let fee_index = &mut self.fee_index;
+		let entry_times = &mut self.entry_times;
+		self.entries.retain(|x| {
+			let keep = f(x);
+			if !keep {
+				if let Some(id) = entry_id(&x.tx) {
+					fee_index.remove(&id);
+					entry_times.remove(&id);
+				}
+			}
+			keep
+		});
+		self.generation += 1;
+	}
+
+	/// Entries in the pool in insertion order.
+	pub fn entries(&self) -> &[PoolEntry] {
+		&self.entries
+	}
+
+	/// Replace all the entries of the pool.
+	pub fn set_entries(&mut self, entries: Vec<PoolEntry>) {
+		let ids: HashSet<_> = entries.iter().filter_map(|x| entry_id(&x.tx)).collect();
+		for id in self.entries.iter().filter_map(|x| entry_id(&x.tx)) {
+			if !ids.contains(&id) {
+				self.fee_index.remove(&id);
+				self.entry_times.remove(&id);
+			}
+		}
+		for x in &entries {
+			self.fee_index.insert(&x.tx);
+			if let Some(id) = entry_id(&x.tx) {
+				self.entry_times.entry(id).or_insert(x.tx_at);
+			}
+		}
+		self.entries = entries;
+		self.generation += 1;
+	}
+
 	/// Size of the pool.
 	pub fn size(&self) -> usize {
 		self.entries.len()
@@ -491,6 +615,17 @@ where
 		self.entries.iter().map(|x| x.tx.kernels().len()).sum()
 	}
 
+	/// Total fees of the transactions in the pool.
+	pub fn total_fees(&self) -> u64 {
+		self.entries.iter().map(|x| x.tx.fee()).sum()
+	}
+
+	/// Fee_to_weight and weight (as in a block) of the txs in the pool,
+	/// the highest fee_to_weight first.
+	pub fn fee_rates(&self) -> Vec<(u64, u64)> {
+		self.fee_index.rates()
+	}
+
 	/// Is the pool empty?
 	pub fn is_empty(&self) -> bool {
 		self.entries.is_empty()
@@ -506,8 +641,8 @@ struct Bucket {
 impl Bucket {
 	/// Construct a new bucket with the given tx.
 	/// also specifies an "age_idx" so we can sort buckets by age
-	/// as well as fee_to_weight. Txs are maintainedin the pool in insert order
-	/// so buckets with low age_idx contain oldest txs.
+	/// as well as fee_to_weight. Txs are bucketed in the fee index order
+	/// so buckets with low age_idx contain the higher fee (then the oldest) txs.
 	fn new(tx: Transaction, age_idx: usize) -> Bucket {
 		Bucket {
 			fee_to_weight: tx.fee_to_weight(),
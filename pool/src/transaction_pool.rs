@@ -17,25 +17,43 @@
 //! resulting tx pool can be added to the current chain state to produce a
 //! valid chain state.
 
+use self::core::consensus;
 use self::core::core::hash::{Hash, Hashed};
-use self::core::core::id::ShortId;
+use self::core::core::id::{ShortId, ShortIdentifiable};
 use self::core::core::verifier_cache::VerifierCache;
 use self::core::core::{
-	transaction, Block, BlockHeader, HeaderVersion, OutputIdentifier, Transaction, Weighting,
+	committed, transaction, Block, BlockHeader, OutputIdentifier, Transaction, Weighting,
 };
 use self::core::global;
-use self::util::RwLock;
+use self::util::secp::pedersen::Commitment;
+use self::util::{RwLock, ToHex};
+use crate::dandelion::{DandelionSecret, DandelionStats, StemPoolEntry, StemPoolStatus};
+use crate::fees::{FeeEstimate, FeeEstimator};
+use crate::journal::PoolJournal;
+use crate::policy::{self, AcceptancePolicy};
 use crate::pool::Pool;
-use crate::types::{BlockChain, PoolAdapter, PoolConfig, PoolEntry, PoolError, TxSource};
+use crate::types::{
+	BlockChain, DandelionConfig, PoolAdapter, PoolConfig, PoolEntry, PoolError, ReorgTxStats,
+	TxPolicy, TxPolicyDecision, TxPolicySummary, TxSource,
+};
 use chrono::prelude::*;
 use grin_core as core;
 use grin_core::ser;
 use grin_keychain::base58;
 use grin_util as util;
 use lru_cache::LruCache;
-use std::collections::VecDeque;
+use std::cmp;
+use std::collections::{HashSet, VecDeque};
 use std::sync::Arc;
 
+/// Mineable txs of the txpool, valid on top of the head at the txpool generation.
+struct MineableCache {
+	generation: u64,
+	head: Hash,
+	max_weight: u64,
+	txs: Vec<Transaction>,
+}
+
 /// Transaction pool implementation.
 pub struct TransactionPool<B, P, V>
 where
@@ -51,6 +69,9 @@ where
 	pub stempool: Pool<B, V>,
 	/// Cache of previous txs in case of a re-org.
 	pub reorg_cache: Arc<RwLock<VecDeque<PoolEntry>>>,
+	/// Hashes of the txs that failed the block assembly checks for good.
+	/// Such txs are never mined and evicted from the txpool on the next block.
+	pub quarantine: Arc<RwLock<HashSet<Hash>>>,
 	/// The blockchain
 	pub blockchain: Arc<B>,
 	pub verifier_cache: Arc<RwLock<V>>,
@@ -58,6 +79,23 @@ where
 	pub adapter: Arc<P>,
 	///the replay attack cache
 	pub replay_verifier_cache: Arc<RwLock<LruCache<[u8; 32], ()>>>,
+	/// Fee, weight and kernel rules of the accepted txs
+	pub acceptance_policy: Arc<dyn AcceptancePolicy>,
+	/// Transaction policy plugins, consulted before a tx is accepted
+	pub policies: Vec<Arc<dyn TxPolicy>>,
+	/// Statistics of the Dandelion routing
+	pub dandelion_stats: DandelionStats,
+	/// Dandelion config, for the embargo timers of the stempool entries
+	pub dandelion_config: DandelionConfig,
+	/// Secret of the per tx embargo timers, for the lifetime of the node
+	pub embargo_secret: DandelionSecret,
+	/// Journal of the txpool entries, if enabled
+	pub journal: Option<PoolJournal>,
+	/// Fee rates of the recent blocks, for the fee estimates
+	pub fee_estimator: FeeEstimator,
+	/// Last prepared mineable txs, reused by the mining job refreshes until
+	/// the txpool or the chain head changes.
+	mineable_cache: RwLock<Option<MineableCache>>,
 }
 
 impl<B, P, V> TransactionPool<B, P, V>
@@ -73,6 +111,7 @@ where
 		verifier_cache: Arc<RwLock<V>>,
 		adapter: Arc<P>,
 	) -> Self {
+		let acceptance_policy = policy::from_config(&config);
 		TransactionPool {
 			config,
 			txpool: Pool::new(chain.clone(), verifier_cache.clone(), "txpool".to_string()),
@@ -82,13 +121,72 @@ where
 				"stempool".to_string(),
 			),
 			reorg_cache: Arc::new(RwLock::new(VecDeque::new())),
+			quarantine: Arc::new(RwLock::new(HashSet::new())),
 			blockchain: chain,
 			verifier_cache,
 			adapter,
 			replay_verifier_cache: Arc::new(RwLock::new(LruCache::new(100))),
+			acceptance_policy,
+			policies: vec![],
+			dandelion_stats: DandelionStats::default(),
+			dandelion_config: DandelionConfig::default(),
+			embargo_secret: DandelionSecret::random(),
+			journal: None,
+			fee_estimator: FeeEstimator::new(),
+			mineable_cache: RwLock::new(None),
 		}
 	}
 
+	/// Replace the acceptance policy selected by the config with a custom one.
+	pub fn set_acceptance_policy(&mut self, acceptance_policy: Arc<dyn AcceptancePolicy>) {
+		self.acceptance_policy = acceptance_policy;
+	}
+
+	/// Set the Dandelion config the embargo timers are based on.
+	pub fn set_dandelion_config(&mut self, dandelion_config: DandelionConfig) {
+		self.dandelion_config = dandelion_config;
+	}
+
+	/// Time the embargo timer of the stempool entry expires, unix timestamp.
+	pub fn embargo_expiry(&self, entry: &PoolEntry) -> i64 {
+		self.embargo_secret
+			.embargo_expiry(&self.dandelion_config, entry)
+	}
+
+	/// Content of the stempool with the embargo timers of the entries.
+	pub fn stempool_status(&self) -> StemPoolStatus {
+		let mut entries: Vec<StemPoolEntry> = self
+			.stempool
+			.entries()
+			.iter()
+			.map(|x| StemPoolEntry {
+				excess: x
+					.tx
+					.kernels()
+					.first()
+					.map(|k| k.excess.to_hex())
+					.unwrap_or_default(),
+				weight: x.tx.tx_weight(),
+				fee: x.tx.fee(),
+				tx_at: x.tx_at,
+				embargo_expiry: Utc.timestamp(self.embargo_expiry(x), 0),
+			})
+			.collect();
+		entries.sort_by_key(|x| x.embargo_expiry);
+		StemPoolStatus {
+			count: entries.len(),
+			total_weight: entries.iter().map(|x| x.weight).sum(),
+			next_embargo_expiry: entries.first().map(|x| x.embargo_expiry),
+			entries,
+		}
+	}
+
+	/// Register a transaction policy plugin. All the plugins must accept a tx
+	/// for it to be added to the pool, see `check_tx_policies`.
+	pub fn add_policy(&mut self, policy: Arc<dyn TxPolicy>) {
+		self.policies.push(policy);
+	}
+
 	pub fn chain_head(&self) -> Result<BlockHeader, PoolError> {
 		self.blockchain.chain_head()
 	}
@@ -100,7 +198,9 @@ where
 		header: &BlockHeader,
 		extra_tx: Option<Transaction>,
 	) -> Result<(), PoolError> {
-		self.stempool.add_to_pool(entry.clone(), extra_tx, header)
+		self.stempool
+			.add_to_pool(entry.clone(), extra_tx, header, None)
+			.map(|_| ())
 	}
 
 	fn add_to_reorg_cache(&mut self, entry: &PoolEntry) {
@@ -114,6 +214,43 @@ where
 		}
 	}
 
+	fn add_to_journal(&mut self, entry: &PoolEntry) {
+		if let Some(journal) = self.journal.as_mut() {
+			let res = if journal.needs_compaction(self.txpool.size()) {
+				journal.rewrite(self.txpool.entries())
+			} else {
+				journal.append(entry)
+			};
+			if let Err(e) = res {
+				warn!("Unable to journal tx {}, {}", entry.tx.hash(), e);
+			}
+		}
+	}
+
+	/// Start journaling the txpool. The previously journaled entries are revalidated
+	/// against the current chain state and added back to the txpool, the journal is
+	/// rewritten with the entries that are still valid. Returns the number of the
+	/// restored entries.
+	pub fn load_journal(
+		&mut self,
+		mut journal: PoolJournal,
+		entries: Vec<PoolEntry>,
+		header: &BlockHeader,
+	) -> Result<usize, PoolError> {
+		self.journal = None;
+		let mut restored = 0;
+		for entry in entries {
+			let tx_hash = entry.tx.hash();
+			match self.add_to_pool(TxSource::Journal, entry.tx, false, header) {
+				Ok(_) => restored += 1,
+				Err(e) => debug!("load_journal: tx {} is dropped, {}", tx_hash, e),
+			}
+		}
+		journal.rewrite(self.txpool.entries())?;
+		self.journal = Some(journal);
+		Ok(restored)
+	}
+
 	// Deaggregate this tx against the txpool.
 	// Returns the new deaggregated tx or the original tx if no deaggregation.
 	fn deaggregate_tx(&self, entry: PoolEntry) -> Result<PoolEntry, PoolError> {
@@ -128,18 +265,27 @@ where
 	}
 
 	fn add_to_txpool(&mut self, entry: &PoolEntry, header: &BlockHeader) -> Result<(), PoolError> {
-		self.txpool.add_to_pool(entry.clone(), None, header)?;
+		// A full txpool evicts the entries paying a lower fee rate than this tx.
+		let evicted = self.txpool.add_to_pool(
+			entry.clone(),
+			None,
+			header,
+			Some(self.config.max_pool_size),
+		)?;
 
 		// We now need to reconcile the stempool based on the new state of the txpool.
 		// Some stempool txs may no longer be valid and we need to evict them.
 		let txpool_agg = self.txpool.all_transactions_aggregate(None)?;
 		self.stempool.reconcile(txpool_agg, header)?;
 
+		for evicted_entry in &evicted {
+			self.adapter.tx_evicted(evicted_entry);
+		}
 		Ok(())
 	}
 
 	/// Verify the tx kernel variants and ensure they can all be accepted to the txpool/stempool
-	/// with respect to current header version.
+	/// with respect to current header height.
 	fn verify_kernel_variants(
 		&self,
 		tx: &Transaction,
@@ -149,7 +295,7 @@ where
 			if !global::is_nrd_enabled() {
 				return Err(PoolError::NRDKernelNotEnabled);
 			}
-			if header.version < HeaderVersion(4) {
+			if header.height < consensus::nrd_activation_height() {
 				return Err(PoolError::NRDKernelPreHF3);
 			}
 		}
@@ -157,23 +303,43 @@ where
 	}
 
 	/// Add the given tx to the pool, directing it to either the stempool or
-	/// txpool based on stem flag provided.
+	/// txpool based on stem flag provided. The adapter is notified about the rejected txs.
 	pub fn add_to_pool(
 		&mut self,
 		src: TxSource,
 		tx: Transaction,
 		stem: bool,
 		header: &BlockHeader,
+	) -> Result<(), PoolError> {
+		let res = self.add_to_pool_inner(src, tx.clone(), stem, header);
+		match res {
+			Err(PoolError::DuplicateTx) | Ok(_) => {}
+			Err(ref e) => self.adapter.tx_rejected(&tx, &src, e),
+		}
+		res
+	}
+
+	fn add_to_pool_inner(
+		&mut self,
+		src: TxSource,
+		tx: Transaction,
+		stem: bool,
+		header: &BlockHeader,
 	) -> Result<(), PoolError> {
 		// Quick check for duplicate txs.
 		// Our stempool is private and we do not want to reveal anything about the txs contained.
 		// If this is a stem tx and is already present in stempool then fluff by adding to txpool.
 		// Otherwise if already present in txpool return a "duplicate tx" error.
 		if stem && self.stempool.contains_tx(&tx) {
-			return self.add_to_pool(src, tx, false, header);
+			return self.add_to_pool_inner(src, tx, false, header);
 		} else if self.txpool.contains_tx(&tx) {
 			return Err(PoolError::DuplicateTx);
 		}
+		let stem_entry_at = if stem {
+			None
+		} else {
+			self.stempool.entry_tx_at(&tx)
+		};
 
 		// Attempt to deaggregate the tx if not stem tx.
 		let entry = if stem {
@@ -188,13 +354,8 @@ where
 		self.verify_kernel_variants(tx, header)?;
 
 		// Do we have the capacity to accept this transaction?
-		let acceptability = self.is_acceptable(tx, stem);
-		let mut evict = false;
-		if !stem && acceptability.as_ref().err() == Some(&PoolError::OverCapacity) {
-			evict = true;
-		} else if acceptability.is_err() {
-			return acceptability;
-		}
+		// A full txpool is handled when the tx is added, by evicting the lower fee rate txs.
+		self.is_acceptable(tx, stem, header)?;
 
 		// Make sure the transaction is valid before anything else.
 		// Validate tx accounting for max tx weight.
@@ -223,6 +384,103 @@ where
 			}
 		}
 
+		// Replace-by-fee, evict the txpool txs double spent by this tx if it pays enough more.
+		// The replaced txs are public already, so there is no point to stem the replacement.
+		let replaced = self.replaced_entries(tx)?;
+		if replaced.is_empty() {
+			return self.add_valid_entry(entry, stem, stem_entry_at, header);
+		}
+		let tx_hash = tx.hash();
+		let prev_entries = self.txpool.entries().to_vec();
+		self.txpool
+			.retain(|e| !replaced.iter().any(|r| r.tx == e.tx));
+		let res = self.add_valid_entry(entry, false, stem_entry_at, header);
+		match res {
+			Ok(_) => {
+				debug!(
+					"add_to_pool: tx {} replaced {} txpool entries",
+					tx_hash,
+					replaced.len()
+				);
+				for e in &replaced {
+					self.adapter.tx_evicted(e);
+				}
+			}
+			Err(_) => self.txpool.set_entries(prev_entries),
+		}
+		res
+	}
+
+	/// Txpool entries replaced by the tx: the entries spending any of its inputs and
+	/// the entries depending on them. Fails if the tx doesn't pay enough to replace them,
+	/// it must pay the configured fee rate premium over the entries spending its inputs
+	/// and at least the total fee of all the replaced entries.
+	fn replaced_entries(&self, tx: &Transaction) -> Result<Vec<PoolEntry>, PoolError> {
+		if !self.config.replace_by_fee {
+			return Ok(vec![]);
+		}
+		let inputs: HashSet<Commitment> = tx.inputs_committed().into_iter().collect();
+		let spends_any = |entry: &PoolEntry, commits: &HashSet<Commitment>| {
+			entry
+				.tx
+				.inputs_committed()
+				.iter()
+				.any(|c| commits.contains(c))
+		};
+		let (conflicts, mut rest): (Vec<_>, Vec<_>) = self
+			.txpool
+			.entries()
+			.iter()
+			.cloned()
+			.partition(|e| spends_any(e, &inputs));
+		if conflicts.is_empty() {
+			return Ok(vec![]);
+		}
+
+		// Dependents spend the outputs of the replaced entries, they can't stay without them.
+		let mut replaced = conflicts.clone();
+		loop {
+			let outputs: HashSet<Commitment> = replaced
+				.iter()
+				.flat_map(|e| e.tx.outputs_committed())
+				.collect();
+			let (dependents, others): (Vec<_>, Vec<_>) =
+				rest.into_iter().partition(|e| spends_any(e, &outputs));
+			rest = others;
+			if dependents.is_empty() {
+				break;
+			}
+			replaced.extend(dependents);
+		}
+
+		let max_fee_to_weight = conflicts
+			.iter()
+			.map(|e| e.tx.fee_to_weight())
+			.max()
+			.unwrap_or(0);
+		let min_fee_to_weight = max_fee_to_weight * (100 + self.config.rbf_fee_rate_premium) / 100;
+		let replaced_fee: u64 = replaced.iter().map(|e| e.tx.fee()).sum();
+		// fee_to_weight is scaled by 1000
+		let min_fee = cmp::max(
+			replaced_fee,
+			(min_fee_to_weight * tx.tx_weight() + 999) / 1000,
+		);
+		if tx.fee() < min_fee {
+			return Err(PoolError::LowFeeReplacement(min_fee));
+		}
+		Ok(replaced)
+	}
+
+	// Add the validated tx to the stempool or txpool.
+	fn add_valid_entry(
+		&mut self,
+		entry: PoolEntry,
+		stem: bool,
+		stem_entry_at: Option<DateTime<Utc>>,
+		header: &BlockHeader,
+	) -> Result<(), PoolError> {
+		let ref tx = entry.tx;
+
 		// If stem we want to account for the txpool.
 		let extra_tx = if stem {
 			self.txpool.all_transactions_aggregate(None)?
@@ -247,25 +505,40 @@ where
 			.verify_coinbase_maturity(&coinbase_inputs.as_slice().into())?;
 
 		// Convert the tx to "v2" compatibility with "features and commit" inputs.
-		let ref entry = self.convert_tx_v2(entry, &spent_pool, &spent_utxo)?;
+		let mut entry = self.convert_tx_v2(entry, &spent_pool, &spent_utxo)?;
 
 		// If this is a stem tx then attempt to add it to stempool.
 		// If the adapter fails to accept the new stem tx then fallback to fluff via txpool.
 		if stem {
-			self.add_to_stempool(entry, header, extra_tx)?;
-			if self.adapter.stem_tx_accepted(entry).is_ok() {
+			self.add_to_stempool(&entry, header, extra_tx)?;
+			if self.adapter.stem_tx_accepted(&entry).is_ok() {
+				self.dandelion_stats.stem_relayed += 1;
 				return Ok(());
 			}
+			self.dandelion_stats.stem_failed += 1;
 		}
 
+		// Age the entry from the first time we saw this tx. Stem txs are private
+		// and only recorded once fluffed.
+		entry.tx_at = self.blockchain.record_tx_first_seen(&entry.tx, entry.tx_at);
+
 		// Add tx to txpool.
-		self.add_to_txpool(entry, header)?;
-		self.add_to_reorg_cache(entry);
-		self.adapter.tx_accepted(entry);
+		self.add_to_txpool(&entry, header)?;
+		self.add_to_reorg_cache(&entry);
+		self.add_to_journal(&entry);
+		self.adapter.tx_accepted(&entry);
 
-		// Transaction passed all the checks but we have to make space for it
-		if evict {
-			self.evict_from_txpool();
+		// End of the stem path of a stem tx that went through this node.
+		match entry.src {
+			TxSource::Fluff => self.dandelion_stats.fluffed += 1,
+			TxSource::EmbargoExpired => self.dandelion_stats.embargo_expired += 1,
+			TxSource::Broadcast => {
+				if let Some(stem_at) = stem_entry_at {
+					self.dandelion_stats
+						.record_stem_duration((Utc::now() - stem_at).num_seconds());
+				}
+			}
+			_ => {}
 		}
 
 		Ok(())
@@ -300,14 +573,11 @@ where
 		// Validate the tx to ensure our converted inputs are correct.
 		tx.validate(Weighting::AsTransaction, self.verifier_cache.clone())?;
 
-		Ok(PoolEntry::new(tx, entry.src))
-	}
-
-	// Evict a transaction from the txpool.
-	// Uses bucket logic to identify the "last" transaction.
-	// No other tx depends on it and it has low fee_to_weight.
-	pub fn evict_from_txpool(&mut self) {
-		self.txpool.evict_transaction()
+		Ok(PoolEntry {
+			src: entry.src,
+			tx_at: entry.tx_at,
+			tx,
+		})
 	}
 
 	// Old txs will "age out" after 30 mins.
@@ -332,6 +602,9 @@ where
 			header.hash(),
 		);
 		for entry in entries {
+			if self.txpool.contains_tx(&entry.tx) {
+				continue;
+			}
 			let _ = self.add_to_txpool(&entry, header);
 		}
 		debug!(
@@ -341,6 +614,148 @@ where
 		Ok(())
 	}
 
+	/// Re-insert transactions from the blocks that were disconnected by the reorg.
+	/// Transactions that we have seen before are taken from the reorg cache and resurrected
+	/// one at a time. The rest of the block body can't be split because the block carries
+	/// only the aggregated offset, it is resurrected as a single transaction and counted
+	/// by its kernels. Transactions that are confirmed again by the new fork or are already
+	/// in the pool are skipped.
+	pub fn resurrect_reorg_txs(
+		&mut self,
+		header: &BlockHeader,
+		prev_head: &Hash,
+		fork_point: &Hash,
+	) -> Result<ReorgTxStats, PoolError> {
+		let fork_height = self.blockchain.get_block_header(fork_point)?.height;
+
+		// Kernels that are confirmed by the new fork
+		let mut confirmed = HashSet::new();
+		for block in self.get_fork_blocks(&header.hash(), fork_height)? {
+			confirmed.extend(block.kernels().iter().map(|k| k.excess()));
+		}
+
+		let mut disconnected = self.get_fork_blocks(prev_head, fork_height)?;
+		disconnected.reverse();
+
+		let cache = self.reorg_cache.read().iter().cloned().collect::<Vec<_>>();
+		let mut stats = ReorgTxStats::default();
+
+		for block in disconnected {
+			let kernels: HashSet<_> = block
+				.kernels()
+				.iter()
+				.filter(|k| !k.is_coinbase())
+				.map(|k| k.excess())
+				.collect();
+			if kernels.iter().all(|k| confirmed.contains(k)) {
+				continue;
+			}
+
+			// Txs from the cache are already converted and validated, they can go directly to the txpool
+			let cached: Vec<_> = cache
+				.iter()
+				.filter(|e| e.tx.kernels().iter().all(|k| kernels.contains(&k.excess())))
+				.cloned()
+				.collect();
+			for entry in &cached {
+				if entry
+					.tx
+					.kernels()
+					.iter()
+					.all(|k| confirmed.contains(&k.excess()))
+					|| self.txpool.contains_tx(&entry.tx)
+				{
+					continue;
+				}
+				match self.add_to_txpool(entry, header) {
+					Ok(_) => stats.resurrected += 1,
+					Err(e) => {
+						debug!(
+							"resurrect_reorg_txs: tx {} is dropped, {}",
+							entry.tx.hash(),
+							e
+						);
+						stats.failed += 1;
+					}
+				}
+			}
+
+			// The rest of the block that we never seen as a transactions
+			let block_tx = self.block_as_tx(&block)?;
+			let cached_txs: Vec<_> = cached.into_iter().map(|e| e.tx).collect();
+			let rest_tx = if cached_txs.is_empty() {
+				block_tx
+			} else {
+				transaction::deaggregate(block_tx, &cached_txs)?
+			};
+			if rest_tx.kernels().is_empty()
+				|| rest_tx.kernels().iter().all(|k| {
+					confirmed.contains(&k.excess()) || self.txpool.contains_kernel(&k.excess())
+				}) {
+				continue;
+			}
+			let rest_hash = rest_tx.hash();
+			let rest_count = rest_tx.kernels().len();
+			match self.add_to_pool(TxSource::Reorg, rest_tx, false, header) {
+				Ok(_) => stats.resurrected += rest_count,
+				Err(e) => {
+					debug!(
+						"resurrect_reorg_txs: block {} tx {} is dropped, {}",
+						block.hash(),
+						rest_hash,
+						e
+					);
+					stats.failed += rest_count;
+				}
+			}
+		}
+
+		debug!(
+			"resurrect_reorg_txs: block: {:?}, resurrected: {}, failed: {}",
+			header.hash(),
+			stats.resurrected,
+			stats.failed
+		);
+		Ok(stats)
+	}
+
+	// Blocks of the fork starting from the hash down to the fork point (exclusive).
+	fn get_fork_blocks(&self, hash: &Hash, fork_height: u64) -> Result<Vec<Block>, PoolError> {
+		let mut blocks = vec![];
+		let mut hash = *hash;
+		loop {
+			let block = self.blockchain.get_block(&hash)?;
+			if block.header.height <= fork_height {
+				break;
+			}
+			hash = block.header.prev_hash;
+			blocks.push(block);
+		}
+		Ok(blocks)
+	}
+
+	// Non coinbase part of the block as a single transaction.
+	fn block_as_tx(&self, block: &Block) -> Result<Transaction, PoolError> {
+		let prev = self.blockchain.get_block_header(&block.header.prev_hash)?;
+		let offset = committed::sum_kernel_offsets(
+			vec![block.header.total_kernel_offset()],
+			vec![prev.total_kernel_offset()],
+		)?;
+		let outputs: Vec<_> = block
+			.outputs()
+			.iter()
+			.filter(|o| !o.is_coinbase())
+			.cloned()
+			.collect();
+		let kernels: Vec<_> = block
+			.kernels()
+			.iter()
+			.filter(|k| !k.is_coinbase())
+			.cloned()
+			.collect();
+		Ok(Transaction::new(block.inputs(), &outputs, &kernels).with_offset(offset))
+	}
+
 	/// Reconcile the transaction pool (both txpool and stempool) against the
 	/// provided block.
 	pub fn reconcile_block(&mut self, block: &Block) -> Result<(), PoolError> {
@@ -355,13 +770,24 @@ where
 				debug!("  reorg_cache tx: {:?}", pe);
 			}
 
-			debug!("txpool size: {}", self.txpool.entries.len());
-			for pe in &self.txpool.entries {
+			debug!("txpool size: {}", self.txpool.entries().len());
+			for pe in self.txpool.entries() {
 				debug!("  txpool tx: {:?}", pe);
 			}
 			debug!("---------------- BEFORE END --------------");
 		}
 
+		// The txpool still has the block txs, their fee rates are known.
+		self.fee_estimator
+			.record_block(block, self.txpool.entries());
+
+		// Quarantined txs are not going to be mined, evict them before reconcile,
+		// so their dependent txs will be evicted as well.
+		let quarantine: HashSet<_> = self.quarantine.write().drain().collect();
+		if !quarantine.is_empty() {
+			self.txpool.retain(|e| !quarantine.contains(&e.tx.hash()));
+		}
+
 		// First reconcile the txpool.
 		self.txpool.reconcile_block(block);
 		self.txpool.reconcile(None, &block.header)?;
@@ -381,8 +807,8 @@ where
 			for pe in reorg_cache.iter() {
 				debug!("  reorg_cache tx: {:?}", pe);
 			}
-			debug!("txpool size: {}", self.txpool.entries.len());
-			for pe in &self.txpool.entries {
+			debug!("txpool size: {}", self.txpool.entries().len());
+			for pe in self.txpool.entries() {
 				debug!("  txpool tx: {:?}", pe);
 			}
 			debug!("---------------- AFTER END --------------");
@@ -408,30 +834,57 @@ where
 		self.txpool.retrieve_transactions(hash, nonce, kern_ids)
 	}
 
-	/// Whether the transaction is acceptable to the pool, given both how
-	/// full the pool is and the transaction weight.
-	fn is_acceptable(&self, tx: &Transaction, stem: bool) -> Result<(), PoolError> {
-		if self.total_size() > self.config.max_pool_size {
-			return Err(PoolError::OverCapacity);
+	/// Retrieve the transactions of a recent block matching the provided kernel
+	/// short_ids. The txs of an accepted block are no longer in the txpool, so
+	/// we also look into the reorg cache.
+	pub fn retrieve_block_transactions(
+		&self,
+		hash: Hash,
+		nonce: u64,
+		kern_ids: &[ShortId],
+	) -> Vec<Transaction> {
+		let (mut txs, missing_ids) = self.txpool.retrieve_transactions(hash, nonce, kern_ids);
+		if !missing_ids.is_empty() {
+			let cache = self.reorg_cache.read();
+			for entry in cache.iter() {
+				if entry
+					.tx
+					.kernels()
+					.iter()
+					.any(|k| missing_ids.contains(&k.short_id(&hash, nonce)))
+				{
+					txs.push(entry.tx.clone());
+				}
+			}
 		}
+		txs
+	}
+
+	/// Transactions of the txpool first seen after the provided time.
+	pub fn recent_transactions(&self, since: DateTime<Utc>) -> Vec<Transaction> {
+		self.txpool
+			.entries()
+			.iter()
+			.filter(|x| x.tx_at >= since)
+			.map(|x| x.tx.clone())
+			.collect()
+	}
 
+	/// Whether the transaction is acceptable to the pool, given both how
+	/// full the stempool is and the transaction weight.
+	fn is_acceptable(
+		&self,
+		tx: &Transaction,
+		stem: bool,
+		header: &BlockHeader,
+	) -> Result<(), PoolError> {
 		// Check that the stempool can accept this transaction
-		if stem && self.stempool.size() > self.config.max_stempool_size
-			|| self.total_size() > self.config.max_pool_size
-		{
+		if stem && self.stempool.size() > self.config.max_stempool_size {
 			return Err(PoolError::OverCapacity);
 		}
 
-		// for a basic transaction (1 input, 2 outputs) -
-		// (-1 * 1) + (4 * 2) + 1 = 8
-		// 8 * 10 = 80
-		if self.config.accept_fee_base > 0 {
-			let threshold = (tx.tx_weight() as u64) * self.config.accept_fee_base;
-			if tx.fee() < threshold {
-				return Err(PoolError::LowFeeTransaction(threshold));
-			}
-		}
-		Ok(())
+		// Fee, weight and kernels are checked by the acceptance policy
+		self.acceptance_policy.check(tx, header.height + 1)
 	}
 
 	/// Get the total size of the pool.
@@ -440,10 +893,146 @@ where
 		self.txpool.size()
 	}
 
+	/// Estimate the fee base (fee per unit of tx weight) for a tx to be mined within
+	/// blocks_target blocks, from the txpool and the recent blocks.
+	pub fn estimate_fee(&self, blocks_target: u64) -> FeeEstimate {
+		self.fee_estimator.estimate(
+			blocks_target,
+			&self.txpool.fee_rates(),
+			self.config.accept_fee_base,
+			self.config.mineable_max_weight,
+		)
+	}
+
 	/// Returns a vector of transactions from the txpool so we can build a
-	/// block from them.
+	/// block from them. The result is cached until the txpool or the chain head
+	/// changes, so the mining job refreshes don't revalidate the whole pool.
 	pub fn prepare_mineable_transactions(&self) -> Result<Vec<Transaction>, PoolError> {
-		self.txpool
-			.prepare_mineable_transactions(self.config.mineable_max_weight)
+		let header = self.blockchain.chain_head()?;
+		let head = header.hash();
+		let generation = self.txpool.generation;
+		let max_weight = self.config.mineable_max_weight;
+		if let Some(cache) = self.mineable_cache.read().as_ref() {
+			if cache.generation == generation
+				&& cache.head == head
+				&& cache.max_weight == max_weight
+			{
+				return Ok(cache.txs.clone());
+			}
+		}
+
+		let txs = self.txpool.prepare_mineable_transactions(max_weight)?;
+		let txs = self.verify_mineable_transactions(txs, header.height + 1);
+		*self.mineable_cache.write() = Some(MineableCache {
+			generation,
+			head,
+			max_weight,
+			txs: txs.clone(),
+		});
+		Ok(txs)
+	}
+
+	/// Final check of the txs that are going into the block at the given height.
+	/// Because of the races with the chain updates, pool might have txs with immature lock height
+	/// or NRD kernels that conflict with the chain or inside the block. Such txs would make the
+	/// block invalid, so they are excluded. Immature txs stay in the pool and are tried again
+	/// with the next blocks, txs that can never be mined are quarantined.
+	pub fn verify_mineable_transactions(
+		&self,
+		txs: Vec<Transaction>,
+		height: u64,
+	) -> Vec<Transaction> {
+		let mut nrd_excess = HashSet::new();
+		// Outputs of the excluded txs, the txs spending them are excluded as well
+		let mut excluded_outputs: HashSet<Commitment> = HashSet::new();
+		let mut valid_txs = vec![];
+
+		for tx in txs {
+			let tx_nrd: Vec<_> = tx
+				.kernels()
+				.iter()
+				.filter(|k| k.is_nrd())
+				.cloned()
+				.collect();
+			let tx_nrd_excess: HashSet<_> = tx_nrd.iter().map(|k| k.excess()).collect();
+
+			let res = if tx
+				.inputs_committed()
+				.iter()
+				.any(|c| excluded_outputs.contains(c))
+			{
+				Err(PoolError::Other("spends an excluded tx".to_string()))
+			} else if tx.lock_height() > height {
+				Err(PoolError::ImmatureTransaction)
+			} else if tx_nrd_excess.len() != tx_nrd.len() {
+				// Duplicate NRD kernels in the tx itself, it is never valid
+				warn!(
+					"verify_mineable_transactions: tx {} is quarantined at height {}, duplicate NRD kernels",
+					tx.hash(),
+					height,
+				);
+				self.quarantine.write().insert(tx.hash());
+				Err(PoolError::NRDKernelRelativeHeight)
+			} else if tx_nrd_excess.iter().any(|e| nrd_excess.contains(e)) {
+				// The tx that is already in the block keeps the excess, this one waits
+				// for its relative lock to mature, the same as for the recent chain kernels.
+				Err(PoolError::NRDKernelRelativeHeight)
+			} else if !tx_nrd.is_empty() {
+				self.blockchain.validate_nrd_kernels(&tx_nrd)
+			} else {
+				Ok(())
+			};
+
+			match res {
+				Ok(_) => {
+					nrd_excess.extend(tx_nrd_excess);
+					valid_txs.push(tx);
+				}
+				Err(e) => {
+					// Not quarantined txs stay in the pool and are tried with the next blocks
+					debug!(
+						"verify_mineable_transactions: tx {} is excluded at height {}, {}",
+						tx.hash(),
+						height,
+						e
+					);
+					excluded_outputs.extend(tx.outputs_committed());
+				}
+			}
+		}
+		valid_txs
+	}
+}
+
+/// Consult the transaction policy plugins about the tx. The plugins can be slow, the callers
+/// check the tx before they take the pool lock. The txs that were in the pool before
+/// (reorg, journal) are not checked again.
+pub fn check_tx_policies(
+	policies: &[Arc<dyn TxPolicy>],
+	tx: &Transaction,
+	src: TxSource,
+	stem: bool,
+) -> Result<(), PoolError> {
+	match src {
+		TxSource::Reorg | TxSource::Journal => return Ok(()),
+		_ => {}
+	}
+	if policies.is_empty() {
+		return Ok(());
+	}
+	let summary = TxPolicySummary::new(tx, src, stem);
+	for policy in policies {
+		match policy.check(&summary) {
+			TxPolicyDecision::Accept => {}
+			TxPolicyDecision::Reject(reason) => {
+				return Err(PoolError::PolicyRejected(reason));
+			}
+			TxPolicyDecision::FeeFloor(fee) => {
+				if summary.fee < fee {
+					return Err(PoolError::LowFeeTransaction(fee));
+				}
+			}
+		}
 	}
+	Ok(())
 }
@@ -17,14 +17,17 @@
 
 use self::core::consensus;
 use self::core::core::block;
-use self::core::core::committed;
+use self::core::core::committed::{self, Committed};
 use self::core::core::hash::Hash;
-use self::core::core::transaction::{self, Transaction};
-use self::core::core::{BlockHeader, BlockSums, Inputs, OutputIdentifier};
+use self::core::core::transaction::{self, Transaction, TxKernel};
+use self::core::core::{Block, BlockHeader, BlockSums, Inputs, OutputIdentifier};
+use crate::policy::AcceptancePolicyType;
 use chrono::prelude::*;
 use failure::Fail;
 use grin_core as core;
 use grin_keychain as keychain;
+use grin_util::ToHex;
+use schemars::JsonSchema;
 
 /// Dandelion "epoch" length.
 const DANDELION_EPOCH_SECS: u16 = 600;
@@ -124,6 +127,51 @@ pub struct PoolConfig {
 	/// blocks.
 	#[serde(default = "default_mineable_max_weight")]
 	pub mineable_max_weight: u64,
+
+	/// Url of the external transaction policy plugin. When set, the summary of every
+	/// transaction is posted to it before the transaction is accepted and relayed.
+	#[serde(default)]
+	pub tx_policy_url: Option<String>,
+
+	/// Timeout in milliseconds of the transaction policy plugin call.
+	#[serde(default = "default_tx_policy_timeout_ms")]
+	pub tx_policy_timeout_ms: u64,
+
+	/// Accept a transaction double spending the inputs of txpool transactions if it pays
+	/// enough more, the replaced transactions and their dependents are evicted. Disabled by default.
+	#[serde(default = "default_replace_by_fee")]
+	pub replace_by_fee: bool,
+
+	/// Fee rate premium (percent) a replacement transaction must pay over the fee rate
+	/// of the transactions it replaces.
+	#[serde(default = "default_rbf_fee_rate_premium")]
+	pub rbf_fee_rate_premium: u64,
+
+	/// Journal the txpool transactions into the data dir, they are reloaded and
+	/// revalidated when the node restarts.
+	#[serde(default)]
+	pub journal: bool,
+
+	/// Acceptance policy of the pool, Default for the relay rules or Strict for
+	/// the strict_* limits on top of them.
+	#[serde(default)]
+	pub acceptance_policy: AcceptancePolicyType,
+
+	/// Strict policy: base fee for a transaction to be accepted, accept_fee_base if not set.
+	#[serde(default)]
+	pub strict_fee_base: Option<u64>,
+
+	/// Strict policy: max weight of a transaction, only the consensus limit if not set.
+	#[serde(default)]
+	pub strict_max_tx_weight: Option<u64>,
+
+	/// Strict policy: accept the transactions with height locked kernels.
+	#[serde(default = "default_strict_allow_height_locked")]
+	pub strict_allow_height_locked: bool,
+
+	/// Strict policy: accept the transactions with NRD kernels.
+	#[serde(default)]
+	pub strict_allow_nrd: bool,
 }
 
 impl Default for PoolConfig {
@@ -134,6 +182,16 @@ impl Default for PoolConfig {
 			reorg_cache_timeout: default_reorg_cache_timeout(),
 			max_stempool_size: default_max_stempool_size(),
 			mineable_max_weight: default_mineable_max_weight(),
+			tx_policy_url: None,
+			tx_policy_timeout_ms: default_tx_policy_timeout_ms(),
+			replace_by_fee: default_replace_by_fee(),
+			rbf_fee_rate_premium: default_rbf_fee_rate_premium(),
+			journal: false,
+			acceptance_policy: AcceptancePolicyType::default(),
+			strict_fee_base: None,
+			strict_max_tx_weight: None,
+			strict_allow_height_locked: default_strict_allow_height_locked(),
+			strict_allow_nrd: false,
 		}
 	}
 }
@@ -153,10 +211,22 @@ fn default_max_stempool_size() -> usize {
 fn default_mineable_max_weight() -> u64 {
 	consensus::MAX_BLOCK_WEIGHT
 }
+fn default_tx_policy_timeout_ms() -> u64 {
+	1_000
+}
+fn default_replace_by_fee() -> bool {
+	false
+}
+fn default_rbf_fee_rate_premium() -> u64 {
+	25
+}
+fn default_strict_allow_height_locked() -> bool {
+	true
+}
 
 /// Represents a single entry in the pool.
 /// A single (possibly aggregated) transaction.
-#[derive(Clone, Debug, Serialize, Deserialize)]
+#[derive(Clone, Debug, Serialize, Deserialize, JsonSchema)]
 pub struct PoolEntry {
 	/// Info on where this tx originated from.
 	pub src: TxSource,
@@ -182,13 +252,15 @@ impl PoolEntry {
 ///
 /// Most likely this will evolve to contain some sort of network identifier,
 /// once we get a better sense of what transaction building might look like.
-#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize, JsonSchema)]
 pub enum TxSource {
 	PushApi,
 	Broadcast,
 	Fluff,
 	EmbargoExpired,
 	Deaggregate,
+	Reorg,
+	Journal,
 }
 
 impl TxSource {
@@ -201,6 +273,68 @@ impl TxSource {
 	}
 }
 
+/// Summary of a transaction submitted to the transaction policy plugins.
+/// Commitments are hex encoded.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct TxPolicySummary {
+	/// Where the transaction comes from.
+	pub src: TxSource,
+	/// Whether the transaction goes to the stempool.
+	pub stem: bool,
+	/// Total fee of the transaction.
+	pub fee: u64,
+	/// Weight of the transaction.
+	pub weight: u64,
+	/// Commitments of the spent outputs.
+	pub inputs: Vec<String>,
+	/// Commitments of the new outputs.
+	pub outputs: Vec<String>,
+	/// Kernel excesses.
+	pub kernels: Vec<String>,
+}
+
+impl TxPolicySummary {
+	pub fn new(tx: &Transaction, src: TxSource, stem: bool) -> TxPolicySummary {
+		TxPolicySummary {
+			src,
+			stem,
+			fee: tx.fee(),
+			weight: tx.tx_weight() as u64,
+			inputs: tx.inputs_committed().iter().map(|c| c.to_hex()).collect(),
+			outputs: tx.outputs_committed().iter().map(|c| c.to_hex()).collect(),
+			kernels: tx.kernels_committed().iter().map(|c| c.to_hex()).collect(),
+		}
+	}
+}
+
+/// Decision of a transaction policy plugin.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum TxPolicyDecision {
+	/// Accept the transaction.
+	Accept,
+	/// Reject the transaction, with the reason.
+	Reject(String),
+	/// Accept the transaction only if its fee is at least the given amount.
+	FeeFloor(u64),
+}
+
+/// Transaction policy plugin, consulted by the pool before a transaction is accepted
+/// (and so relayed). Allows filtering the transactions without changing the pool code.
+pub trait TxPolicy: Sync + Send {
+	/// Decide whether the transaction can be accepted.
+	fn check(&self, summary: &TxPolicySummary) -> TxPolicyDecision;
+}
+
+/// Counts of the transactions from the disconnected blocks that were processed by the pool
+/// after the chain reorg.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct ReorgTxStats {
+	/// Transactions that were re-inserted into the pool.
+	pub resurrected: usize,
+	/// Transactions that are not valid any more and were dropped.
+	pub failed: usize,
+}
+
 /// Possible errors when interacting with the transaction pool.
 #[derive(Debug, Fail, PartialEq)]
 pub enum PoolError {
@@ -250,6 +384,12 @@ pub enum PoolError {
 	/// NRD kernels are not valid if relative_height rule not met.
 	#[fail(display = "NRD kernel relative height")]
 	NRDKernelRelativeHeight,
+	/// Replacement transaction doesn't pay enough more than the transactions it replaces.
+	#[fail(display = "Tx Pool Low fee replacement, fee {} required", _0)]
+	LowFeeReplacement(u64),
+	/// Transaction rejected by a transaction policy plugin.
+	#[fail(display = "Tx Pool Rejected by policy {}", _0)]
+	PolicyRejected(String),
 	/// Other kinds of error (not yet pulled out into meaningful errors).
 	#[fail(display = "Tx Pool General error {}", _0)]
 	Other(String),
@@ -295,6 +435,10 @@ pub trait BlockChain: Sync + Send {
 	/// Validate a transaction against the current utxo.
 	fn validate_tx(&self, tx: &Transaction) -> Result<(), PoolError>;
 
+	/// Validate the NRD relative height locks of the kernels against the recent
+	/// kernel history, for the next block.
+	fn validate_nrd_kernels(&self, kernels: &[TxKernel]) -> Result<(), PoolError>;
+
 	/// Validate inputs against the current utxo.
 	/// Returns the vec of output identifiers that would be spent
 	/// by these inputs if they can all be successfully spent.
@@ -303,8 +447,13 @@ pub trait BlockChain: Sync + Send {
 	fn chain_head(&self) -> Result<BlockHeader, PoolError>;
 
 	fn get_block_header(&self, hash: &Hash) -> Result<BlockHeader, PoolError>;
+	fn get_block(&self, hash: &Hash) -> Result<Block, PoolError>;
 	fn get_block_sums(&self, hash: &Hash) -> Result<BlockSums, PoolError>;
 	fn replay_attack_check(&self, tx: &Transaction) -> Result<(), PoolError>;
+
+	/// Records `at` as the time this tx was first seen unless it was seen before
+	/// (possibly before a restart). Returns the time the tx was first seen.
+	fn record_tx_first_seen(&self, tx: &Transaction, at: DateTime<Utc>) -> DateTime<Utc>;
 }
 
 /// Bridge between the transaction pool and the rest of the system. Handles
@@ -316,6 +465,14 @@ pub trait PoolAdapter: Send + Sync {
 
 	/// The stem transaction pool has accepted this transactions as valid.
 	fn stem_tx_accepted(&self, entry: &PoolEntry) -> Result<(), PoolError>;
+
+	/// The transaction was evicted from the txpool by a transaction paying a higher
+	/// fee rate, either from the full txpool or replaced by fee.
+	fn tx_evicted(&self, entry: &PoolEntry);
+
+	/// The pool has rejected this transaction. Duplicates of the txs already in the
+	/// txpool are not reported.
+	fn tx_rejected(&self, tx: &Transaction, src: &TxSource, err: &PoolError);
 }
 
 /// Dummy adapter used as a placeholder for real implementations
@@ -327,4 +484,6 @@ impl PoolAdapter for NoopPoolAdapter {
 	fn stem_tx_accepted(&self, _entry: &PoolEntry) -> Result<(), PoolError> {
 		Ok(())
 	}
+	fn tx_evicted(&self, _entry: &PoolEntry) {}
+	fn tx_rejected(&self, _tx: &Transaction, _src: &TxSource, _err: &PoolError) {}
 }
@@ -0,0 +1,137 @@
+// Copyright 2021 The MWC Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+pub mod common;
+
+use self::core::core::verifier_cache::LruVerifierCache;
+use self::core::core::{KernelFeatures, Transaction};
+use self::core::global;
+use self::keychain::{ExtKeychain, Keychain};
+use self::pool::policy::{AcceptancePolicy, DefaultAcceptancePolicy};
+use self::pool::types::{NoopPoolAdapter, PoolConfig, PoolError};
+use self::pool::{AcceptancePolicyType, TransactionPool};
+use self::util::RwLock;
+use crate::common::*;
+use grin_core as core;
+use grin_keychain as keychain;
+use grin_pool as pool;
+use grin_util as util;
+use std::sync::Arc;
+
+/// Default policy, with a max tx weight
+struct LightTxPolicy {
+	inner: DefaultAcceptancePolicy,
+	max_weight: u64,
+}
+
+impl AcceptancePolicy for LightTxPolicy {
+	fn name(&self) -> &str {
+		"light"
+	}
+
+	fn min_fee(&self, tx: &Transaction) -> u64 {
+		self.inner.min_fee(tx)
+	}
+
+	fn max_weight(&self) -> Option<u64> {
+		Some(self.max_weight)
+	}
+
+	fn allows_kernel(&self, features: &KernelFeatures) -> bool {
+		self.inner.allows_kernel(features)
+	}
+
+	fn allows_lock_height(&self, lock_height: u64, next_height: u64) -> bool {
+		self.inner.allows_lock_height(lock_height, next_height)
+	}
+}
+
+#[test]
+fn test_acceptance_policy() {
+	util::init_test_logger();
+	global::set_local_chain_type(global::ChainTypes::AutomatedTesting);
+	let keychain: ExtKeychain = Keychain::from_random_seed(false).unwrap();
+
+	let db_root = "target/.acceptance_policy";
+	clean_output_dir(db_root.into());
+
+	let genesis = genesis_block(&keychain);
+	let chain = Arc::new(init_chain(db_root, genesis));
+	let verifier_cache = Arc::new(RwLock::new(LruVerifierCache::new()));
+
+	let mut pool = TransactionPool::new(
+		PoolConfig {
+			accept_fee_base: 0,
+			acceptance_policy: AcceptancePolicyType::Strict,
+			strict_fee_base: Some(2),
+			strict_allow_height_locked: false,
+			..PoolConfig::default()
+		},
+		Arc::new(ChainAdapter {
+			chain: chain.clone(),
+		}),
+		verifier_cache,
+		Arc::new(NoopPoolAdapter {}),
+	);
+	assert_eq!(pool.acceptance_policy.name(), "strict");
+
+	add_some_blocks(&chain, 3, &keychain);
+	let header_1 = chain.get_header_by_height(1).unwrap();
+	let initial_tx =
+		test_transaction_spending_coinbase(&keychain, &header_1, vec![100, 200, 300, 400]);
+	add_block(&chain, &[initial_tx], &keychain);
+	let header = chain.head_header().unwrap();
+
+	// Accepted by the default policy with the fee base 0, not by the strict one.
+	let tx = test_transaction(&keychain, vec![100], vec![99]);
+	let min_fee = tx.tx_weight() * 2;
+	assert_eq!(
+		pool.add_to_pool(test_source(), tx, false, &header),
+		Err(PoolError::LowFeeTransaction(min_fee))
+	);
+
+	// Height locked kernels are not allowed.
+	let tx = test_transaction_with_kernel_features(
+		&keychain,
+		vec![200],
+		vec![150],
+		KernelFeatures::HeightLocked {
+			fee: 50,
+			lock_height: header.height,
+		},
+	);
+	match pool.add_to_pool(test_source(), tx, false, &header) {
+		Err(PoolError::PolicyRejected(_)) => {}
+		res => panic!("unexpected result {:?}", res),
+	}
+
+	let tx = test_transaction(&keychain, vec![300], vec![250]);
+	pool.add_to_pool(test_source(), tx, false, &header).unwrap();
+	assert_eq!(pool.total_size(), 1);
+
+	// Custom policy
+	pool.set_acceptance_policy(Arc::new(LightTxPolicy {
+		inner: DefaultAcceptancePolicy::new(0),
+		max_weight: 1,
+	}));
+	let tx = test_transaction(&keychain, vec![400], vec![350]);
+	match pool.add_to_pool(test_source(), tx, false, &header) {
+		Err(PoolError::PolicyRejected(_)) => {}
+		res => panic!("unexpected result {:?}", res),
+	}
+	assert_eq!(pool.total_size(), 1);
+
+	// Cleanup db directory
+	clean_output_dir(db_root.into());
+}
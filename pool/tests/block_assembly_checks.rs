@@ -0,0 +1,119 @@
+// Copyright 2021 The MWC Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+pub mod common;
+
+use self::core::core::hash::Hashed;
+use self::core::core::verifier_cache::LruVerifierCache;
+use self::core::core::{KernelFeatures, NRDRelativeHeight};
+use self::core::global;
+use self::keychain::{ExtKeychain, Keychain};
+use self::pool::types::{PoolEntry, PoolError};
+use self::util::RwLock;
+use crate::common::*;
+use grin_core as core;
+use grin_keychain as keychain;
+use grin_pool as pool;
+use grin_util as util;
+use std::sync::Arc;
+
+#[test]
+fn test_block_assembly_checks() -> Result<(), PoolError> {
+	util::init_test_logger();
+	global::set_local_chain_type(global::ChainTypes::AutomatedTesting);
+	let keychain: ExtKeychain = Keychain::from_random_seed(false).unwrap();
+
+	let db_root = "target/.block_assembly_checks";
+	clean_output_dir(db_root.into());
+
+	let genesis = genesis_block(&keychain);
+	let chain = Arc::new(init_chain(db_root, genesis));
+	let verifier_cache = Arc::new(RwLock::new(LruVerifierCache::new()));
+
+	let mut pool = init_transaction_pool(
+		Arc::new(ChainAdapter {
+			chain: chain.clone(),
+		}),
+		verifier_cache,
+	);
+
+	add_some_blocks(&chain, 3, &keychain);
+
+	let header_1 = chain.get_header_by_height(1).unwrap();
+	let initial_tx = test_transaction_spending_coinbase(&keychain, &header_1, vec![10, 20, 30, 40]);
+	add_block(&chain, &[initial_tx], &keychain);
+
+	let header = chain.head_header().unwrap();
+
+	let valid_tx = test_transaction(&keychain, vec![10], vec![8]);
+	pool.add_to_pool(test_source(), valid_tx.clone(), false, &header)?;
+
+	// Simulating the race, the tx was accepted by the pool but now its lock height is immature.
+	let locked_tx = test_transaction_with_kernel_features(
+		&keychain,
+		vec![20],
+		vec![18],
+		KernelFeatures::HeightLocked {
+			fee: 2,
+			lock_height: header.height + 10,
+		},
+	);
+	let mut entries = pool.txpool.entries().to_vec();
+	entries.push(PoolEntry::new(locked_tx.clone(), test_source()));
+	pool.txpool.set_entries(entries);
+	assert_eq!(pool.total_size(), 2);
+
+	// The immature tx is excluded, but it stays in the pool until the lock height
+	let txs = pool.prepare_mineable_transactions()?;
+	assert_eq!(txs.len(), 1);
+	assert_eq!(txs[0].kernels(), valid_tx.kernels());
+	assert!(pool.quarantine.read().is_empty());
+
+	add_block(&chain, &txs, &keychain);
+	let block = chain.get_block(&chain.head().unwrap().hash()).unwrap();
+	pool.reconcile_block(&block)?;
+	assert_eq!(pool.total_size(), 1);
+	assert!(pool
+		.verify_mineable_transactions(vec![locked_tx.clone()], header.height + 2)
+		.is_empty());
+	assert_eq!(
+		pool.verify_mineable_transactions(vec![locked_tx.clone()], header.height + 10),
+		vec![locked_tx.clone()]
+	);
+
+	// Duplicate NRD kernels can't go into the same block, the first tx keeps the excess.
+	let nrd_tx = test_transaction_with_kernel_features(
+		&keychain,
+		vec![30],
+		vec![28],
+		KernelFeatures::NoRecentDuplicate {
+			fee: 2,
+			relative_height: NRDRelativeHeight::new(2)?,
+		},
+	);
+	let txs = pool.verify_mineable_transactions(vec![nrd_tx.clone(), nrd_tx.clone()], 6);
+	assert_eq!(txs, vec![nrd_tx.clone()]);
+	assert!(pool.quarantine.read().is_empty());
+
+	// The tx with the duplicate NRD kernels is never valid, it is quarantined
+	let bad_nrd_tx = nrd_tx.clone().with_kernel(nrd_tx.kernels()[0].clone());
+	let txs = pool.verify_mineable_transactions(vec![bad_nrd_tx.clone(), nrd_tx.clone()], 6);
+	assert_eq!(txs, vec![nrd_tx.clone()]);
+	assert!(pool.quarantine.read().contains(&bad_nrd_tx.hash()));
+	assert!(!pool.quarantine.read().contains(&nrd_tx.hash()));
+
+	// Cleanup db directory
+	clean_output_dir(db_root.into());
+	Ok(())
+}
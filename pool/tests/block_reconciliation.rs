@@ -143,16 +143,16 @@ fn test_transaction_pool_block_reconciliation() {
 	assert_eq!(pool.total_size(), 4);
 	// Compare the various txs by their kernels as entries in the pool are "v2" compatibility.
 	assert_eq!(
-		pool.txpool.entries[0].tx.kernels(),
+		pool.txpool.entries()[0].tx.kernels(),
 		valid_transaction.kernels()
 	);
-	assert_eq!(pool.txpool.entries[1].tx.kernels(), pool_child.kernels());
+	assert_eq!(pool.txpool.entries()[1].tx.kernels(), pool_child.kernels());
 	assert_eq!(
-		pool.txpool.entries[2].tx.kernels(),
+		pool.txpool.entries()[2].tx.kernels(),
 		conflict_valid_child.kernels()
 	);
 	assert_eq!(
-		pool.txpool.entries[3].tx.kernels(),
+		pool.txpool.entries()[3].tx.kernels(),
 		valid_child_valid.kernels()
 	);
 
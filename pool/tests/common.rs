@@ -28,9 +28,9 @@ use self::core::libtx::{build, reward, ProofBuilder};
 use self::core::pow;
 use self::keychain::{BlindingFactor, ExtKeychain, ExtKeychainPath, Keychain};
 use self::pool::types::*;
-use self::pool::TransactionPool;
+use self::pool::{AcceptancePolicyType, TransactionPool};
 use self::util::RwLock;
-use chrono::Duration;
+use chrono::{DateTime, Duration, Utc};
 use grin_chain as chain;
 use grin_core as core;
 use grin_keychain as keychain;
@@ -78,6 +78,19 @@ where
 	K: Keychain,
 {
 	let prev = chain.head_header().unwrap();
+	add_block_with_prev(chain, &prev, txs, keychain);
+}
+
+/// Add block on top of the provided header, might be a fork.
+pub fn add_block_with_prev<K>(
+	chain: &Chain,
+	prev: &BlockHeader,
+	txs: &[Transaction],
+	keychain: &K,
+) -> BlockHeader
+where
+	K: Keychain,
+{
 	let height = prev.height + 1;
 	let next_header_info = consensus::next_difficulty(height, chain.difficulty_iter().unwrap());
 	let fee = txs.iter().map(|x| x.fee()).sum();
@@ -92,7 +105,7 @@ where
 	)
 	.unwrap();
 
-	let mut block = Block::new(&prev, txs, next_header_info.clone().difficulty, reward).unwrap();
+	let mut block = Block::new(prev, txs, next_header_info.clone().difficulty, reward).unwrap();
 
 	block.header.timestamp = prev.timestamp + Duration::seconds(60);
 	block.header.pow.secondary_scaling = next_header_info.secondary_scaling;
@@ -109,7 +122,9 @@ where
 	)
 	.unwrap();
 
+	let header = block.header.clone();
 	chain.process_block(block, Options::NONE).unwrap();
+	header
 }
 
 #[derive(Clone)]
@@ -130,6 +145,12 @@ impl BlockChain for ChainAdapter {
 			.map_err(|e| PoolError::Other(format!("failed to get block header, {}", e)))
 	}
 
+	fn get_block(&self, hash: &Hash) -> Result<Block, PoolError> {
+		self.chain
+			.get_block(hash)
+			.map_err(|e| PoolError::Other(format!("failed to get block, {}", e)))
+	}
+
 	fn get_block_sums(&self, hash: &Hash) -> Result<BlockSums, PoolError> {
 		self.chain
 			.get_block_sums(hash)
@@ -144,6 +165,15 @@ impl BlockChain for ChainAdapter {
 		})
 	}
 
+	fn validate_nrd_kernels(&self, kernels: &[TxKernel]) -> Result<(), PoolError> {
+		self.chain
+			.validate_nrd_kernels(kernels)
+			.map_err(|e| match e.kind() {
+				chain::ErrorKind::NRDRelativeHeight => PoolError::NRDKernelRelativeHeight,
+				_ => PoolError::Other("failed to validate kernels".into()),
+			})
+	}
+
 	fn validate_inputs(&self, inputs: &Inputs) -> Result<Vec<OutputIdentifier>, PoolError> {
 		self.chain
 			.validate_inputs(inputs)
@@ -167,6 +197,10 @@ impl BlockChain for ChainAdapter {
 			.replay_attack_check(tx)
 			.map_err(|_| PoolError::DuplicateKernelOrDuplicateSpent)
 	}
+
+	fn record_tx_first_seen(&self, tx: &Transaction, at: DateTime<Utc>) -> DateTime<Utc> {
+		self.chain.record_tx_first_seen(tx, at).unwrap_or(at)
+	}
 }
 
 pub fn init_transaction_pool<B, V>(
@@ -184,6 +218,16 @@ where
 			max_pool_size: 50,
 			max_stempool_size: 50,
 			mineable_max_weight: 10_000,
+			tx_policy_url: None,
+			tx_policy_timeout_ms: 1_000,
+			replace_by_fee: false,
+			rbf_fee_rate_premium: 25,
+			journal: false,
+			acceptance_policy: AcceptancePolicyType::Default,
+			strict_fee_base: None,
+			strict_max_tx_weight: None,
+			strict_allow_height_locked: true,
+			strict_allow_nrd: false,
 		},
 		chain.clone(),
 		verifier_cache.clone(),
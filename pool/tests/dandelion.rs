@@ -0,0 +1,137 @@
+// Copyright 2021 The MWC Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+pub mod common;
+
+use self::core::core::hash::Hashed;
+use self::core::core::verifier_cache::LruVerifierCache;
+use self::pool::dandelion::{DANDELION_EMBARGO_JITTER_SECS, DANDELION_RELAY_COUNT};
+use self::pool::{DandelionConfig, DandelionSecret, DandelionStats};
+use self::util::RwLock;
+use crate::common::*;
+use grin_core as core;
+use grin_fixtures::{generate, FixtureSpec};
+use grin_pool as pool;
+use grin_util as util;
+use std::sync::Arc;
+
+#[test]
+fn dandelion_epoch_decisions() {
+	let secret = DandelionSecret::random();
+
+	// Decisions are stable for the epoch secret.
+	assert!(secret.is_stem(100));
+	assert!(!secret.is_stem(0));
+	assert_eq!(secret.is_stem(50), secret.is_stem(50));
+
+	let relays = secret.select(8, DANDELION_RELAY_COUNT);
+	assert_eq!(relays.len(), DANDELION_RELAY_COUNT);
+	assert_ne!(relays[0], relays[1]);
+	assert!(relays.iter().all(|i| *i < 8));
+	assert_eq!(relays, secret.select(8, DANDELION_RELAY_COUNT));
+	assert_eq!(secret.select(1, DANDELION_RELAY_COUNT), vec![0]);
+	assert!(secret.select(0, DANDELION_RELAY_COUNT).is_empty());
+
+	// Each inbound peer is mapped to a single relay, our txs to the first one.
+	let source = b"10.0.0.1:3414";
+	let slot = secret.relay_slot(Some(source), DANDELION_RELAY_COUNT);
+	assert!(slot < DANDELION_RELAY_COUNT);
+	for _ in 0..10 {
+		assert_eq!(secret.relay_slot(Some(source), DANDELION_RELAY_COUNT), slot);
+	}
+	assert_eq!(secret.relay_slot(None, DANDELION_RELAY_COUNT), 0);
+	assert_eq!(secret.relay_slot(Some(source), 0), 0);
+
+	// Embargo timers are per tx, within the jitter range.
+	let config = DandelionConfig::default();
+	let kernel_hash = 1u64.hash();
+	let embargo = secret.embargo_secs(&config, &kernel_hash);
+	assert!(embargo >= config.embargo_secs as u64);
+	assert!(embargo <= config.embargo_secs as u64 + DANDELION_EMBARGO_JITTER_SECS);
+	assert_eq!(secret.embargo_secs(&config, &kernel_hash), embargo);
+}
+
+#[test]
+fn dandelion_stem_durations() {
+	let mut stats = DandelionStats::default();
+	assert_eq!(stats.stem_secs_avg(), 0);
+
+	stats.record_stem_duration(5);
+	stats.record_stem_duration(40);
+	stats.record_stem_duration(1000);
+	stats.record_stem_duration(-1);
+
+	assert_eq!(stats.stem_completed, 4);
+	assert_eq!(stats.stem_secs_max, 1000);
+	assert_eq!(stats.stem_secs_avg(), 261);
+	assert_eq!(stats.stem_secs_buckets, [2, 0, 1, 0, 1]);
+}
+
+#[test]
+fn dandelion_stempool_status() {
+	util::init_test_logger();
+
+	let db_root = "target/.dandelion_stempool_status";
+	clean_output_dir(db_root.into());
+
+	let spec = FixtureSpec {
+		name: "stempool".to_string(),
+		height: 8,
+		pool_txs: 2,
+		..FixtureSpec::default()
+	};
+	let (chain, fixture) = generate(&spec, db_root).unwrap();
+	let verifier_cache = Arc::new(RwLock::new(LruVerifierCache::new()));
+	let mut pool = init_transaction_pool(
+		Arc::new(ChainAdapter {
+			chain: Arc::new(chain),
+		}),
+		verifier_cache,
+	);
+	let config = DandelionConfig::default();
+	pool.set_dandelion_config(config.clone());
+	assert_eq!(pool.stempool_status().count, 0);
+	assert_eq!(pool.stempool_status().next_embargo_expiry, None);
+
+	let header = pool.blockchain.chain_head().unwrap();
+	for tx in &fixture.pool_txs {
+		pool.add_to_pool(test_source(), tx.clone(), true, &header)
+			.unwrap();
+	}
+	assert_eq!(pool.total_size(), 0);
+
+	let status = pool.stempool_status();
+	assert_eq!(status.count, 2);
+	assert_eq!(
+		status.total_weight,
+		fixture
+			.pool_txs
+			.iter()
+			.map(|tx| tx.tx_weight())
+			.sum::<u64>()
+	);
+	assert_eq!(
+		status.next_embargo_expiry,
+		Some(status.entries[0].embargo_expiry)
+	);
+	assert!(status.entries[0].embargo_expiry <= status.entries[1].embargo_expiry);
+	for entry in &status.entries {
+		let embargo = (entry.embargo_expiry - entry.tx_at).num_seconds();
+		assert!(embargo >= config.embargo_secs as i64 - 1);
+		assert!(embargo <= (config.embargo_secs as u64 + DANDELION_EMBARGO_JITTER_SECS) as i64);
+	}
+
+	// Clean up after ourselves.
+	clean_output_dir(db_root.into());
+}
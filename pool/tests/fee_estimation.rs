@@ -0,0 +1,118 @@
+// Copyright 2021 The MWC Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Test coverage for the fee estimation from the txpool and the recent blocks.
+
+pub mod common;
+use self::core::core::hash::Hashed;
+use self::core::core::verifier_cache::LruVerifierCache;
+use self::core::global;
+use self::keychain::{ExtKeychain, Keychain};
+use self::util::RwLock;
+use crate::common::*;
+use grin_core as core;
+use grin_keychain as keychain;
+use grin_util as util;
+use std::sync::Arc;
+
+#[test]
+fn test_fee_estimation() {
+	util::init_test_logger();
+	global::set_local_chain_type(global::ChainTypes::AutomatedTesting);
+
+	let keychain: ExtKeychain = Keychain::from_random_seed(false).unwrap();
+
+	let db_root = "target/.fee_estimation";
+	clean_output_dir(db_root.into());
+
+	let genesis = genesis_block(&keychain);
+	let chain = Arc::new(init_chain(db_root, genesis));
+	let verifier_cache = Arc::new(RwLock::new(LruVerifierCache::new()));
+
+	let mut pool = init_transaction_pool(
+		Arc::new(ChainAdapter {
+			chain: chain.clone(),
+		}),
+		verifier_cache,
+	);
+
+	add_some_blocks(&chain, 3, &keychain);
+
+	let input_values: Vec<u64> = (1..=12).map(|i| 10_000 + 1_000 * i).collect();
+	// Outputs to spend, 6 per block to stay below the max block weight.
+	for (height, values) in input_values.chunks(6).enumerate() {
+		let header = chain.get_header_by_height(height as u64 + 1).unwrap();
+		let initial_tx = test_transaction_spending_coinbase(&keychain, &header, values.to_vec());
+		add_block(&chain, &[initial_tx], &keychain);
+	}
+
+	// Nothing to compete with, the pool min fee is enough.
+	let estimate = pool.estimate_fee(1);
+	assert_eq!(estimate.fee_base, pool.config.accept_fee_base);
+	assert_eq!(estimate.pool_weight, 0);
+
+	// 12 txs with the fees 10 to 120, a block fits only 9 of them.
+	let header = chain.head_header().unwrap();
+	let mut txs = vec![];
+	for (i, value) in input_values.iter().enumerate() {
+		let fee = 10 * (i as u64 + 1);
+		let tx = test_transaction(&keychain, vec![*value], vec![*value - fee]);
+		pool.add_to_pool(test_source(), tx.clone(), false, &header)
+			.unwrap();
+		txs.push(tx);
+	}
+	assert_eq!(pool.total_size(), 12);
+
+	// Must outbid the tx with the fee 30 (weight 4) to get into the next block.
+	let estimate = pool.estimate_fee(1);
+	assert_eq!(estimate.blocks_target, 1);
+	assert_eq!(estimate.pool_weight, 12 * 25);
+	assert_eq!(estimate.fee_base, 8);
+	// All the pool txs fit into 2 blocks.
+	assert_eq!(pool.estimate_fee(2).fee_base, pool.config.accept_fee_base);
+
+	// The next block takes the 9 best paying txs and is full.
+	add_block(&chain, &txs[3..], &keychain);
+	let block = chain
+		.get_block(&chain.head_header().unwrap().hash())
+		.unwrap();
+	pool.reconcile_block(&block).unwrap();
+	assert_eq!(pool.total_size(), 3);
+
+	let blocks = pool.fee_estimator.blocks();
+	assert_eq!(blocks.len(), 1);
+	assert_eq!(blocks[0].weight, 249);
+	assert_eq!(blocks[0].min_fee_to_weight, Some(10_000));
+
+	// The remaining pool txs fit, but a tx below the lowest rate of the full block
+	// could miss the next block.
+	assert_eq!(pool.estimate_fee(1).fee_base, 10);
+
+	for _ in 0..3 {
+		add_block(&chain, &[], &keychain);
+		let block = chain
+			.get_block(&chain.head_header().unwrap().hash())
+			.unwrap();
+		pool.reconcile_block(&block).unwrap();
+	}
+	assert_eq!(pool.fee_estimator.blocks().len(), 4);
+	assert_eq!(pool.estimate_fee(1).fee_base, 10);
+	// One full block out of 4, very likely to be mined within 3 blocks with the min fee.
+	let estimate = pool.estimate_fee(3);
+	assert_eq!(estimate.fee_base, pool.config.accept_fee_base);
+	assert_eq!(estimate.blocks_sampled, 4);
+
+	// Cleanup db directory
+	clean_output_dir(db_root.into());
+}
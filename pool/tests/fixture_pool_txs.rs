@@ -0,0 +1,68 @@
+// Copyright 2021 The MWC Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+pub mod common;
+
+use self::core::core::verifier_cache::LruVerifierCache;
+use self::pool::PoolError;
+use self::util::RwLock;
+use crate::common::*;
+use grin_core as core;
+use grin_fixtures::{generate, FixtureSpec};
+use grin_pool as pool;
+use grin_util as util;
+use std::sync::Arc;
+
+#[test]
+fn test_fixture_pool_txs() -> Result<(), PoolError> {
+	util::init_test_logger();
+
+	let db_root = "target/.fixture_pool_txs";
+	clean_output_dir(db_root.into());
+
+	let spec = FixtureSpec {
+		name: "pool".to_string(),
+		height: 8,
+		txs_per_block: 1,
+		pool_txs: 3,
+		..FixtureSpec::default()
+	};
+	let (chain, fixture) = generate(&spec, db_root).unwrap();
+	assert_eq!(fixture.pool_txs.len(), 3);
+
+	let verifier_cache = Arc::new(RwLock::new(LruVerifierCache::new()));
+	let mut pool = init_transaction_pool(
+		Arc::new(ChainAdapter {
+			chain: Arc::new(chain),
+		}),
+		verifier_cache,
+	);
+
+	// The fixture txs are valid at the fixture head
+	let header = pool.blockchain.chain_head()?;
+	for tx in &fixture.pool_txs {
+		pool.add_to_pool(test_source(), tx.clone(), false, &header)?;
+	}
+	assert_eq!(pool.total_size(), 3);
+	assert_eq!(pool.prepare_mineable_transactions()?.len(), 3);
+	for tx in &fixture.pool_txs {
+		let excess = tx.kernels()[0].excess;
+		assert!(pool.txpool.contains_kernel(&excess));
+		assert!(!pool.stempool.contains_kernel(&excess));
+	}
+
+	// Clean up after ourselves.
+	clean_output_dir(db_root.into());
+	Ok(())
+}
@@ -0,0 +1,91 @@
+// Copyright 2021 The MWC Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+pub mod common;
+
+use self::core::core::verifier_cache::LruVerifierCache;
+use self::core::global;
+use self::keychain::{ExtKeychain, Keychain};
+use self::pool::PoolError;
+use self::util::RwLock;
+use crate::common::*;
+use grin_core as core;
+use grin_keychain as keychain;
+use grin_pool as pool;
+use grin_util as util;
+use std::sync::Arc;
+
+#[test]
+fn test_mineable_cache() -> Result<(), PoolError> {
+	util::init_test_logger();
+	global::set_local_chain_type(global::ChainTypes::AutomatedTesting);
+	let keychain: ExtKeychain = Keychain::from_random_seed(false).unwrap();
+
+	let db_root = "target/.mineable_cache";
+	clean_output_dir(db_root.into());
+
+	let genesis = genesis_block(&keychain);
+	let chain = Arc::new(init_chain(db_root, genesis));
+	let verifier_cache = Arc::new(RwLock::new(LruVerifierCache::new()));
+
+	let mut pool = init_transaction_pool(
+		Arc::new(ChainAdapter {
+			chain: chain.clone(),
+		}),
+		verifier_cache,
+	);
+
+	add_some_blocks(&chain, 3, &keychain);
+	let header_1 = chain.get_header_by_height(1).unwrap();
+	let initial_tx = test_transaction_spending_coinbase(&keychain, &header_1, vec![10, 20, 30]);
+	add_block(&chain, &[initial_tx], &keychain);
+	let header = chain.head_header().unwrap();
+
+	let tx_1 = test_transaction(&keychain, vec![10], vec![8]);
+	let tx_2 = test_transaction(&keychain, vec![20], vec![17]);
+	let tx_3 = test_transaction(&keychain, vec![30], vec![25]);
+
+	pool.add_to_pool(test_source(), tx_1.clone(), false, &header)?;
+	let txs = pool.prepare_mineable_transactions()?;
+	assert_eq!(txs.len(), 1);
+
+	// The cached txs are reused while the txpool generation and the head are the same.
+	let generation = pool.txpool.generation;
+	assert_eq!(pool.prepare_mineable_transactions()?, txs);
+	assert_eq!(pool.txpool.generation, generation);
+
+	// Any txpool change invalidates the cache
+	pool.add_to_pool(test_source(), tx_2.clone(), false, &header)?;
+	pool.add_to_pool(test_source(), tx_3.clone(), false, &header)?;
+	assert!(pool.txpool.generation > generation);
+	let txs = pool.prepare_mineable_transactions()?;
+	assert_eq!(txs.len(), 3);
+
+	// So does a new head, the mined txs are still in the pool until reconcile,
+	// but they are not valid on top of the new head anymore.
+	add_block(&chain, &[tx_1.clone()], &keychain);
+	let txs = pool.prepare_mineable_transactions()?;
+	assert_eq!(txs.len(), 2);
+	assert!(!txs.iter().any(|tx| tx.kernels() == tx_1.kernels()));
+
+	let block = chain.get_block(&chain.head().unwrap().hash()).unwrap();
+	pool.reconcile_block(&block)?;
+	assert_eq!(pool.total_size(), 2);
+	assert_eq!(pool.prepare_mineable_transactions()?.len(), 2);
+
+	// Cleanup db directory
+	clean_output_dir(db_root.into());
+
+	Ok(())
+}
@@ -0,0 +1,178 @@
+// Copyright 2021 The MWC Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Test coverage for the fee rate eviction of the full txpool.
+
+pub mod common;
+use self::core::core::verifier_cache::LruVerifierCache;
+use self::core::core::{Transaction, TxKernel};
+use self::core::global;
+use self::keychain::{ExtKeychain, Keychain};
+use self::pool::types::{PoolAdapter, PoolConfig, PoolEntry, PoolError, TxSource};
+use self::pool::TransactionPool;
+use self::util::RwLock;
+use crate::common::*;
+use grin_core as core;
+use grin_keychain as keychain;
+use grin_pool as pool;
+use grin_util as util;
+use std::sync::Arc;
+
+#[derive(Default)]
+struct EvictionAdapter {
+	evicted: RwLock<Vec<Transaction>>,
+	rejected: RwLock<Vec<(Transaction, String)>>,
+}
+
+impl EvictionAdapter {
+	fn evicted(&self) -> Vec<TxKernel> {
+		kernels(&self.evicted.read())
+	}
+}
+
+fn kernels(txs: &[Transaction]) -> Vec<TxKernel> {
+	txs.iter().flat_map(|tx| tx.kernels().to_vec()).collect()
+}
+
+impl PoolAdapter for EvictionAdapter {
+	fn tx_accepted(&self, _entry: &PoolEntry) {}
+	fn stem_tx_accepted(&self, _entry: &PoolEntry) -> Result<(), PoolError> {
+		Ok(())
+	}
+	fn tx_evicted(&self, entry: &PoolEntry) {
+		self.evicted.write().push(entry.tx.clone());
+	}
+	fn tx_rejected(&self, tx: &Transaction, _src: &TxSource, err: &PoolError) {
+		self.rejected.write().push((tx.clone(), err.to_string()));
+	}
+}
+
+#[test]
+fn test_pool_eviction() {
+	util::init_test_logger();
+	global::set_local_chain_type(global::ChainTypes::AutomatedTesting);
+
+	let keychain: ExtKeychain = Keychain::from_random_seed(false).unwrap();
+
+	let db_root = "target/.pool_eviction";
+	clean_output_dir(db_root.into());
+
+	let genesis = genesis_block(&keychain);
+	let chain = Arc::new(init_chain(db_root, genesis));
+	let verifier_cache = Arc::new(RwLock::new(LruVerifierCache::new()));
+	let adapter = Arc::new(EvictionAdapter::default());
+
+	let mut pool = TransactionPool::new(
+		PoolConfig {
+			accept_fee_base: 0,
+			max_pool_size: 3,
+			..PoolConfig::default()
+		},
+		Arc::new(ChainAdapter {
+			chain: chain.clone(),
+		}),
+		verifier_cache,
+		adapter.clone(),
+	);
+
+	add_some_blocks(&chain, 3, &keychain);
+
+	let header_1 = chain.get_header_by_height(1).unwrap();
+	let initial_tx =
+		test_transaction_spending_coinbase(&keychain, &header_1, vec![100, 200, 300, 400]);
+	add_block(&chain, &[initial_tx], &keychain);
+
+	let header = chain.head_header().unwrap();
+
+	// All the txs have the same weight, the fee rate follows the fee.
+	let tx_a = test_transaction(&keychain, vec![100], vec![90]);
+	let tx_b = test_transaction(&keychain, vec![200], vec![192]);
+	// Spends tx_b, the lowest fee rate.
+	let tx_c = test_transaction(&keychain, vec![192], vec![187]);
+	for tx in &[&tx_a, &tx_b, &tx_c] {
+		pool.add_to_pool(test_source(), (*tx).clone(), false, &header)
+			.unwrap();
+	}
+	assert_eq!(pool.total_size(), 3);
+
+	// The pool is full, a tx paying less than any evictable tx is rejected.
+	let tx = test_transaction(&keychain, vec![300], vec![299]);
+	assert_eq!(
+		pool.add_to_pool(test_source(), tx, false, &header),
+		Err(PoolError::OverCapacity)
+	);
+	assert_eq!(pool.total_size(), 3);
+	assert!(adapter.evicted().is_empty());
+	assert_eq!(adapter.rejected.read().len(), 1);
+	assert_eq!(
+		adapter.rejected.read()[0].1,
+		PoolError::OverCapacity.to_string()
+	);
+
+	// Duplicates are not reported as rejected.
+	assert_eq!(
+		pool.add_to_pool(test_source(), tx_a.clone(), false, &header),
+		Err(PoolError::DuplicateTx)
+	);
+	assert_eq!(adapter.rejected.read().len(), 1);
+
+	// Evicts tx_c, the lowest fee rate.
+	let tx_d = test_transaction(&keychain, vec![300], vec![270]);
+	pool.add_to_pool(test_source(), tx_d.clone(), false, &header)
+		.unwrap();
+	assert_eq!(pool.total_size(), 3);
+	assert_eq!(adapter.evicted(), kernels(&[tx_c.clone()]));
+
+	// Nothing depends on tx_b anymore, so it is evicted next.
+	let tx_e = test_transaction(&keychain, vec![400], vec![380]);
+	pool.add_to_pool(test_source(), tx_e.clone(), false, &header)
+		.unwrap();
+	assert_eq!(adapter.evicted(), kernels(&[tx_c.clone(), tx_b.clone()]));
+
+	// tx_a has the lowest fee rate, but it is the parent of the new tx.
+	let tx = test_transaction(&keychain, vec![90], vec![79]);
+	assert_eq!(
+		pool.add_to_pool(test_source(), tx, false, &header),
+		Err(PoolError::OverCapacity)
+	);
+
+	// Pays more than tx_e, the lowest fee rate tx that can be evicted.
+	let tx_f = test_transaction(&keychain, vec![90], vec![65]);
+	pool.add_to_pool(test_source(), tx_f.clone(), false, &header)
+		.unwrap();
+	assert_eq!(adapter.evicted(), kernels(&[tx_c, tx_b, tx_e]));
+	assert_eq!(
+		kernels(&pool.txpool.all_transactions()),
+		kernels(&[tx_a.clone(), tx_d.clone(), tx_f.clone()])
+	);
+
+	// The fee index follows the evictions, the highest fee rate first.
+	let rates: Vec<_> = pool.txpool.fee_rates().iter().map(|(r, _)| *r).collect();
+	assert_eq!(
+		rates,
+		vec![
+			tx_d.fee_to_weight(),
+			tx_f.fee_to_weight(),
+			tx_a.fee_to_weight()
+		]
+	);
+
+	// The pool is still valid, all the remaining txs make it into a block.
+	// The highest fee rate first, tx_f goes after its parent.
+	let txs = pool.prepare_mineable_transactions().unwrap();
+	assert_eq!(kernels(&txs), kernels(&[tx_d, tx_a, tx_f]));
+
+	// Cleanup db directory
+	clean_output_dir(db_root.into());
+}
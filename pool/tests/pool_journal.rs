@@ -0,0 +1,107 @@
+// Copyright 2021 The MWC Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Test coverage for the txpool journal.
+
+pub mod common;
+use self::core::core::verifier_cache::LruVerifierCache;
+use self::core::global;
+use self::keychain::{ExtKeychain, Keychain};
+use self::pool::PoolJournal;
+use self::util::RwLock;
+use crate::common::*;
+use grin_core as core;
+use grin_keychain as keychain;
+use grin_pool as pool;
+use grin_util as util;
+use std::fs;
+use std::io::Write;
+use std::sync::Arc;
+
+#[test]
+fn test_pool_journal() {
+	util::init_test_logger();
+	global::set_local_chain_type(global::ChainTypes::AutomatedTesting);
+
+	let keychain: ExtKeychain = Keychain::from_random_seed(false).unwrap();
+
+	let db_root = "target/.pool_journal";
+	clean_output_dir(db_root.into());
+
+	let genesis = genesis_block(&keychain);
+	let chain = Arc::new(init_chain(db_root, genesis));
+	let verifier_cache = Arc::new(RwLock::new(LruVerifierCache::new()));
+	let journal_path = format!("{}/pool_journal.json", db_root);
+
+	add_some_blocks(&chain, 3, &keychain);
+
+	let header_1 = chain.get_header_by_height(1).unwrap();
+	let initial_tx = test_transaction_spending_coinbase(&keychain, &header_1, vec![100, 200, 300]);
+	add_block(&chain, &[initial_tx], &keychain);
+
+	let tx_1 = test_transaction(&keychain, vec![100], vec![90]);
+	let tx_2 = test_transaction(&keychain, vec![200], vec![190]);
+	let tx_3 = test_transaction(&keychain, vec![300], vec![290]);
+	{
+		let mut pool = init_transaction_pool(
+			Arc::new(ChainAdapter {
+				chain: chain.clone(),
+			}),
+			verifier_cache.clone(),
+		);
+		let header = chain.head_header().unwrap();
+		let (journal, entries) = PoolJournal::open(&journal_path).unwrap();
+		assert!(entries.is_empty());
+		assert_eq!(pool.load_journal(journal, entries, &header), Ok(0));
+
+		for tx in &[&tx_1, &tx_2, &tx_3] {
+			pool.add_to_pool(test_source(), (*tx).clone(), false, &header)
+				.unwrap();
+		}
+		// Stem txs are private, they are not journaled.
+		let tx = test_transaction(&keychain, vec![90], vec![80]);
+		pool.add_to_pool(test_source(), tx, true, &header).unwrap();
+		assert_eq!(pool.journal.as_ref().unwrap().len(), 3);
+	}
+
+	// Node was restarted after tx_1 was mined, crashing in the middle of a journal write.
+	add_block(&chain, &[tx_1.clone()], &keychain);
+	{
+		let mut file = fs::OpenOptions::new()
+			.append(true)
+			.open(&journal_path)
+			.unwrap();
+		write!(file, "{{\"src\":\"Broad").unwrap();
+	}
+
+	let mut pool = init_transaction_pool(
+		Arc::new(ChainAdapter {
+			chain: chain.clone(),
+		}),
+		verifier_cache,
+	);
+	let header = chain.head_header().unwrap();
+	let (journal, entries) = PoolJournal::open(&journal_path).unwrap();
+	assert_eq!(entries.len(), 3);
+	assert_eq!(pool.load_journal(journal, entries, &header), Ok(2));
+	assert_eq!(pool.txpool.all_transactions(), vec![tx_2, tx_3]);
+
+	// Journal is rewritten with the restored txs only.
+	assert_eq!(pool.journal.as_ref().unwrap().len(), 2);
+	let (_, entries) = PoolJournal::open(&journal_path).unwrap();
+	assert_eq!(entries.len(), 2);
+
+	// Cleanup db directory
+	clean_output_dir(db_root.into());
+}
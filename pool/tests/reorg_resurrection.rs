@@ -0,0 +1,110 @@
+// Copyright 2021 The MWC Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+pub mod common;
+
+use self::core::core::hash::Hashed;
+use self::core::core::verifier_cache::LruVerifierCache;
+use self::core::global;
+use self::keychain::{ExtKeychain, Keychain};
+use self::pool::ReorgTxStats;
+use self::util::RwLock;
+use crate::common::ChainAdapter;
+use crate::common::*;
+use grin_core as core;
+use grin_keychain as keychain;
+use grin_pool as pool;
+use grin_util as util;
+use std::sync::Arc;
+
+#[test]
+fn test_transaction_pool_reorg_resurrection() {
+	util::init_test_logger();
+	global::set_local_chain_type(global::ChainTypes::AutomatedTesting);
+	let keychain: ExtKeychain = Keychain::from_random_seed(false).unwrap();
+
+	let db_root = "target/.reorg_resurrection";
+	clean_output_dir(db_root.into());
+
+	let genesis = genesis_block(&keychain);
+	let chain = Arc::new(init_chain(db_root, genesis));
+	let verifier_cache = Arc::new(RwLock::new(LruVerifierCache::new()));
+
+	let mut pool = init_transaction_pool(
+		Arc::new(ChainAdapter {
+			chain: chain.clone(),
+		}),
+		verifier_cache,
+	);
+
+	add_some_blocks(&chain, 3, &keychain);
+
+	let header_1 = chain.get_header_by_height(1).unwrap();
+	let initial_tx = test_transaction_spending_coinbase(&keychain, &header_1, vec![10, 20, 30, 40]);
+	add_block(&chain, &[initial_tx], &keychain);
+
+	let fork_point = chain.head_header().unwrap();
+
+	// tx_1 goes through the pool, tx_2 and tx_3 we never seen before they are mined.
+	let tx_1 = test_transaction(&keychain, vec![10], vec![8]);
+	let tx_2 = test_transaction(&keychain, vec![20], vec![18]);
+	let tx_3 = test_transaction(&keychain, vec![30], vec![28]);
+	pool.add_to_pool(test_source(), tx_1.clone(), false, &fork_point)
+		.unwrap();
+	assert_eq!(pool.total_size(), 1);
+
+	let block = {
+		add_block(
+			&chain,
+			&[tx_1.clone(), tx_2.clone(), tx_3.clone()],
+			&keychain,
+		);
+		chain.get_block(&chain.head().unwrap().hash()).unwrap()
+	};
+	pool.reconcile_block(&block).unwrap();
+	assert_eq!(pool.total_size(), 0);
+
+	// The other fork is longer and confirms tx_1 again
+	let fork_1 = add_block_with_prev(&chain, &fork_point, &[], &keychain);
+	let fork_2 = add_block_with_prev(&chain, &fork_1, &[tx_1.clone()], &keychain);
+	assert_eq!(chain.head().unwrap().hash(), fork_2.hash());
+
+	let stats = pool
+		.resurrect_reorg_txs(&fork_2, &block.hash(), &fork_point.hash())
+		.unwrap();
+
+	// tx_1 from the reorg cache is confirmed, tx_2 and tx_3 are resurrected as the rest of the block.
+	assert_eq!(
+		stats,
+		ReorgTxStats {
+			resurrected: 2,
+			failed: 0,
+		}
+	);
+	assert_eq!(pool.total_size(), 1);
+	let mut kernels = vec![tx_2.kernels()[0], tx_3.kernels()[0]];
+	kernels.sort_unstable();
+	assert_eq!(pool.txpool.entries()[0].tx.kernels(), &kernels[..]);
+
+	// Txs that are already in the pool are not resurrected twice.
+	let stats = pool
+		.resurrect_reorg_txs(&fork_2, &block.hash(), &fork_point.hash())
+		.unwrap();
+	assert_eq!(stats, ReorgTxStats::default());
+	pool.reconcile_reorg_cache(&fork_2).unwrap();
+	assert_eq!(pool.total_size(), 1);
+
+	// Cleanup db directory
+	clean_output_dir(db_root.into());
+}
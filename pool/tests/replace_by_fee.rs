@@ -0,0 +1,111 @@
+// Copyright 2021 The MWC Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Test coverage for the replace-by-fee of the txpool transactions.
+
+pub mod common;
+use self::core::core::verifier_cache::LruVerifierCache;
+use self::core::global;
+use self::keychain::{ExtKeychain, Keychain};
+use self::pool::{PoolConfig, PoolError};
+use self::util::RwLock;
+use crate::common::*;
+use grin_core as core;
+use grin_keychain as keychain;
+use grin_pool as pool;
+use grin_util as util;
+use std::sync::Arc;
+
+#[test]
+fn test_replace_by_fee() {
+	util::init_test_logger();
+	global::set_local_chain_type(global::ChainTypes::AutomatedTesting);
+
+	let keychain: ExtKeychain = Keychain::from_random_seed(false).unwrap();
+
+	let db_root = "target/.replace_by_fee";
+	clean_output_dir(db_root.into());
+
+	let genesis = genesis_block(&keychain);
+	let chain = Arc::new(init_chain(db_root, genesis));
+	let verifier_cache = Arc::new(RwLock::new(LruVerifierCache::new()));
+
+	let mut pool = init_transaction_pool(
+		Arc::new(ChainAdapter {
+			chain: chain.clone(),
+		}),
+		verifier_cache,
+	);
+	// Replace-by-fee is opt-in.
+	assert!(!PoolConfig::default().replace_by_fee);
+	pool.config.replace_by_fee = true;
+
+	add_some_blocks(&chain, 3, &keychain);
+
+	let header_1 = chain.get_header_by_height(1).unwrap();
+	let initial_tx = test_transaction_spending_coinbase(&keychain, &header_1, vec![100, 200, 300]);
+	add_block(&chain, &[initial_tx], &keychain);
+
+	let header = chain.head_header().unwrap();
+
+	// Low fee tx and a tx spending its output.
+	let tx_1 = test_transaction(&keychain, vec![100], vec![99]);
+	let tx_2 = test_transaction(&keychain, vec![99], vec![97]);
+	pool.add_to_pool(test_source(), tx_1.clone(), false, &header)
+		.unwrap();
+	pool.add_to_pool(test_source(), tx_2.clone(), false, &header)
+		.unwrap();
+	let tx_3 = test_transaction(&keychain, vec![200], vec![190]);
+	pool.add_to_pool(test_source(), tx_3.clone(), false, &header)
+		.unwrap();
+	assert_eq!(pool.total_size(), 3);
+
+	// Higher fee rate, but less than the total fee of tx_1 and tx_2 it replaces.
+	let tx = test_transaction(&keychain, vec![100], vec![98]);
+	assert_eq!(
+		pool.add_to_pool(test_source(), tx, false, &header),
+		Err(PoolError::LowFeeReplacement(3))
+	);
+	assert_eq!(pool.total_size(), 3);
+
+	// Pays enough, tx_1 and its dependent tx_2 are evicted.
+	// The replacement is fluffed even if it is sent as a stem tx.
+	let tx_4 = test_transaction(&keychain, vec![100], vec![95]);
+	pool.add_to_pool(test_source(), tx_4.clone(), true, &header)
+		.unwrap();
+	assert_eq!(pool.stempool.size(), 0);
+	assert_eq!(pool.txpool.all_transactions(), vec![tx_3.clone(), tx_4]);
+
+	// Fee rate increase of tx_3 is below the premium.
+	let tx = test_transaction(&keychain, vec![200], vec![189]);
+	match pool.add_to_pool(test_source(), tx, false, &header) {
+		Err(PoolError::LowFeeReplacement(_)) => {}
+		res => panic!("unexpected result {:?}", res),
+	}
+
+	// No replacements at all when disabled.
+	pool.config.replace_by_fee = false;
+	let tx = test_transaction(&keychain, vec![200], vec![150]);
+	assert!(pool
+		.add_to_pool(test_source(), tx.clone(), false, &header)
+		.is_err());
+	pool.config.replace_by_fee = true;
+	pool.add_to_pool(test_source(), tx.clone(), false, &header)
+		.unwrap();
+	assert_eq!(pool.total_size(), 2);
+	assert!(!pool.txpool.all_transactions().contains(&tx_3));
+
+	// Cleanup db directory
+	clean_output_dir(db_root.into());
+}
@@ -171,6 +171,7 @@ fn test_the_transaction_pool() {
 		assert_eq!(pool.total_size(), 4);
 		assert_eq!(pool.txpool.size(), 4);
 		assert_eq!(pool.stempool.size(), 1);
+		assert!(pool.stempool.entry_tx_at(&tx).is_some());
 
 		// Duplicate stem tx so fluff, adding it to txpool and removing it from stempool.
 		pool.add_to_pool(test_source(), tx.clone(), true, &header)
@@ -178,6 +179,7 @@ fn test_the_transaction_pool() {
 		assert_eq!(pool.total_size(), 5);
 		assert_eq!(pool.txpool.size(), 5);
 		assert!(pool.stempool.is_empty());
+		assert!(pool.stempool.entry_tx_at(&tx).is_none());
 	}
 
 	// Now check we can correctly deaggregate a multi-kernel tx based on current
@@ -198,7 +200,7 @@ fn test_the_transaction_pool() {
 		pool.add_to_pool(test_source(), agg_tx, false, &header)
 			.unwrap();
 		assert_eq!(pool.total_size(), 6);
-		let entry = pool.txpool.entries.last().unwrap();
+		let entry = pool.txpool.entries().last().unwrap();
 		assert_eq!(entry.tx.kernels().len(), 1);
 		assert_eq!(entry.src, TxSource::Deaggregate);
 	}
@@ -0,0 +1,123 @@
+// Copyright 2021 The MWC Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Test coverage for the transaction policy plugins.
+
+pub mod common;
+use self::core::core::verifier_cache::LruVerifierCache;
+use self::core::global;
+use self::keychain::{ExtKeychain, Keychain};
+use self::pool::{
+	check_tx_policies, PoolError, TxPolicy, TxPolicyDecision, TxPolicySummary, TxSource,
+};
+use self::util::RwLock;
+use crate::common::*;
+use grin_core as core;
+use grin_keychain as keychain;
+use grin_pool as pool;
+use grin_util as util;
+use std::sync::Arc;
+
+// Rejects the txs spending more than `max_inputs` outputs, requires a fee floor for the others.
+struct TestPolicy {
+	max_inputs: usize,
+	fee_floor: u64,
+	seen: RwLock<Vec<TxPolicySummary>>,
+}
+
+impl TxPolicy for TestPolicy {
+	fn check(&self, summary: &TxPolicySummary) -> TxPolicyDecision {
+		self.seen.write().push(summary.clone());
+		if summary.inputs.len() > self.max_inputs {
+			TxPolicyDecision::Reject("too many inputs".to_string())
+		} else if summary.fee < self.fee_floor {
+			TxPolicyDecision::FeeFloor(self.fee_floor)
+		} else {
+			TxPolicyDecision::Accept
+		}
+	}
+}
+
+#[test]
+fn test_tx_policy() {
+	util::init_test_logger();
+	global::set_local_chain_type(global::ChainTypes::AutomatedTesting);
+
+	let keychain: ExtKeychain = Keychain::from_random_seed(false).unwrap();
+
+	let db_root = "target/.tx_policy";
+	clean_output_dir(db_root.into());
+
+	let genesis = genesis_block(&keychain);
+	let chain = Arc::new(init_chain(db_root, genesis));
+	let verifier_cache = Arc::new(RwLock::new(LruVerifierCache::new()));
+
+	let mut pool = init_transaction_pool(
+		Arc::new(ChainAdapter {
+			chain: chain.clone(),
+		}),
+		verifier_cache,
+	);
+	let policy = Arc::new(TestPolicy {
+		max_inputs: 1,
+		fee_floor: 5,
+		seen: RwLock::new(vec![]),
+	});
+	pool.add_policy(policy.clone());
+
+	add_some_blocks(&chain, 3, &keychain);
+
+	let header_1 = chain.get_header_by_height(1).unwrap();
+	let initial_tx = test_transaction_spending_coinbase(&keychain, &header_1, vec![100, 200, 300]);
+	add_block(&chain, &[initial_tx], &keychain);
+
+	let header = chain.head_header().unwrap();
+
+	// Spends two outputs, rejected.
+	let tx = test_transaction(&keychain, vec![100, 200], vec![290]);
+	assert_eq!(
+		check_tx_policies(&pool.policies, &tx, test_source(), false),
+		Err(PoolError::PolicyRejected("too many inputs".to_string()))
+	);
+
+	// Fee of 1 is below the floor.
+	let tx = test_transaction(&keychain, vec![100], vec![99]);
+	assert_eq!(
+		check_tx_policies(&pool.policies, &tx, test_source(), false),
+		Err(PoolError::LowFeeTransaction(5))
+	);
+
+	// The txs that were in the pool before are not checked again.
+	check_tx_policies(&pool.policies, &tx, TxSource::Journal, false).unwrap();
+	check_tx_policies(&pool.policies, &tx, TxSource::Reorg, false).unwrap();
+
+	// Fee of 10 is accepted.
+	let tx = test_transaction(&keychain, vec![300], vec![290]);
+	check_tx_policies(&pool.policies, &tx, test_source(), true).unwrap();
+	pool.add_to_pool(test_source(), tx.clone(), true, &header)
+		.unwrap();
+	assert_eq!(pool.stempool.size(), 1);
+
+	let seen = policy.seen.read();
+	assert_eq!(seen.len(), 3);
+	let last = seen.last().unwrap();
+	assert!(last.stem);
+	assert_eq!(last.fee, 10);
+	assert_eq!(last.inputs.len(), 1);
+	assert_eq!(last.outputs.len(), 1);
+	assert_eq!(last.kernels.len(), 1);
+
+	// Cleanup db directory
+	clean_output_dir(db_root.into());
+}
@@ -17,4 +17,5 @@
 pub mod adapters;
 pub mod hooks;
 pub mod stats;
+pub mod tx_policy;
 pub mod types;
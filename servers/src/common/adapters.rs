@@ -22,24 +22,33 @@ use std::sync::{Arc, Weak};
 use std::thread;
 use std::time::Instant;
 
+use crate::chain::txhashset::Desegmenter;
+use crate::chain::types::OutputRoots;
 use crate::chain::{
 	self, BlockStatus, ChainAdapter, Options, SyncState, SyncStatus, TxHashsetDownloadStats,
 };
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::sync::Mutex;
 
-use crate::common::hooks::{ChainEvents, NetEvents};
-use crate::common::types::{ChainValidationMode, DandelionEpoch, ServerConfig};
+use crate::common::hooks::{ChainEvents, NetEvents, PoolEvents};
+use crate::common::types::{ChainValidationMode, DandelionEpoch, HeaderSyncPipeline, ServerConfig};
 use crate::core::core::hash::{Hash, Hashed};
+use crate::core::core::id::ShortIdentifiable;
+use crate::core::core::pmmr::{Segment, SegmentIdentifier};
 use crate::core::core::transaction::Transaction;
 use crate::core::core::verifier_cache::VerifierCache;
-use crate::core::core::{BlockHeader, BlockSums, CompactBlock, Inputs, OutputIdentifier};
+use crate::core::core::{
+	BlockHeader, BlockSums, CompactBlock, Inputs, OutputIdentifier, ShortId, TxKernel,
+};
 use crate::core::pow::Difficulty;
 use crate::core::ser::ProtocolVersion;
 use crate::core::{core, global};
 use crate::p2p;
-use crate::p2p::types::PeerInfo;
+use crate::p2p::libp2p_connection;
+use crate::p2p::types::{PeerAddr, PeerInfo};
+use crate::p2p::{seen_cache, SeenNetwork, SeenObject};
 use crate::pool::{self, BlockChain, PoolAdapter};
+use crate::util::secp::pedersen::RangeProof;
 use crate::util::OneTime;
 use chrono::prelude::*;
 use chrono::Duration;
@@ -47,6 +56,57 @@ use rand::prelude::*;
 use std::sync::atomic::AtomicI64;
 use std::sync::atomic::Ordering;
 
+/// Txs we first saw less than this many seconds ago are likely still propagating,
+/// the compact blocks we mine carry them in full.
+const PREFILL_TX_AGE_SECS: i64 = 30;
+
+/// How long we wait for the missing txs of a compact block before forgetting it.
+const PENDING_COMPACT_BLOCK_SECS: u64 = 30;
+
+// Transaction of the `push_transaction` json-rpc call with its fluff flag, params are
+// positional or named
+fn pushed_transaction(request: &serde_json::Value) -> Option<(Transaction, bool)> {
+	if request.get("method").and_then(|m| m.as_str()) != Some("push_transaction") {
+		return None;
+	}
+	let params = request.get("params")?;
+	let (tx, fluff) = match params {
+		serde_json::Value::Array(p) => (p.get(0)?, p.get(1)),
+		p => (p.get("tx")?, p.get("fluff")),
+	};
+	let tx = serde_json::from_value::<Transaction>(tx.clone()).ok()?;
+	let fluff = fluff.and_then(|f| f.as_bool()).unwrap_or(false);
+	Some((tx, fluff))
+}
+
+/// Serve the foreign json-rpc request that came over libp2p. The transactions pushed this way
+/// share the seen cache with the p2p network, so the same tx is not validated twice.
+pub fn serve_gossip_rpc_request<F>(request: serde_json::Value, serve: F) -> serde_json::Value
+where
+	F: FnOnce(serde_json::Value) -> serde_json::Value,
+{
+	let now = Utc::now().timestamp_millis();
+	let pushed = pushed_transaction(&request).map(|(tx, fluff)| (tx.hash(), fluff));
+	if let Some((tx_hash, _)) = pushed {
+		if seen_cache().is_seen(&tx_hash, SeenObject::Transaction, SeenNetwork::Gossip, now) {
+			debug!("libp2p push_transaction, {} is already seen", tx_hash);
+			let id = request
+				.get("id")
+				.cloned()
+				.unwrap_or(serde_json::Value::Null);
+			return serde_json::json!({"jsonrpc": "2.0", "id": id, "result": {"Ok": null}});
+		}
+	}
+	let response = serve(request);
+	// Stem txs are expected to come again, fluffed
+	if let Some((tx_hash, true)) = pushed {
+		if response.get("result").and_then(|r| r.get("Ok")).is_some() {
+			seen_cache().add(tx_hash, SeenNetwork::Gossip, now);
+		}
+	}
+	response
+}
+
 // NetToChainAdapter need a memory cache to prevent data overloading for network core nodes (non leaf nodes)
 // This cache will drop sequense of the events during the second
 struct EventCache {
@@ -111,6 +171,13 @@ where
 	header_cache: Arc<Mutex<HashMap<u64, core::BlockHeader>>>,
 	tip_processed: Arc<Mutex<u64>>,
 	reset_tip: Arc<Mutex<u64>>,
+	header_pipeline: Arc<HeaderSyncPipeline>,
+	dandelion_epoch: Arc<RwLock<DandelionEpoch>>,
+
+	// compact blocks waiting for their missing txs, with the txs we already have
+	// and the peer we asked for the rest
+	pending_compact_blocks:
+		Mutex<HashMap<Hash, (core::CompactBlock, Vec<Transaction>, PeerAddr, Instant)>>,
 }
 
 impl<B, P, V> p2p::ChainAdapter for NetToChainAdapter<B, P, V>
@@ -153,6 +220,7 @@ where
 		&self,
 		tx: core::Transaction,
 		stem: bool,
+		peer_info: &PeerInfo,
 	) -> Result<bool, chain::Error> {
 		if self.sync_state.is_syncing() {
 			return Ok(true);
@@ -168,6 +236,12 @@ where
 		} else {
 			debug!("transaction_received, cache for {} OK", tx_hash);
 		}
+		// Already accepted from the p2p or the gossip network
+		let now = Utc::now().timestamp_millis();
+		if seen_cache().is_seen(&tx_hash, SeenObject::Transaction, SeenNetwork::P2p, now) {
+			debug!("transaction_received, {} is already seen", tx_hash);
+			return Ok(true);
+		}
 
 		let source = pool::TxSource::Broadcast;
 
@@ -177,10 +251,28 @@ where
 			hook.on_transaction_received(&tx);
 		}
 
-		let mut tx_pool = self.tx_pool.write();
-		match tx_pool.add_to_pool(source, tx, stem, &header) {
+		// The stem relay is selected by the peer the tx came from.
+		let kernel_hash = tx.kernels().first().map(|k| k.hash());
+		if let (true, Some(kernel_hash)) = (stem, kernel_hash) {
+			self.dandelion_epoch
+				.write()
+				.set_stem_source(kernel_hash, peer_info.addr.clone());
+		}
+
+		// The policy plugins are consulted before the pool lock is taken, they can be slow
+		let policies = self.tx_pool.read().policies.clone();
+		let res = pool::check_tx_policies(&policies, &tx, source, stem)
+			.and_then(|_| self.tx_pool.write().add_to_pool(source, tx, stem, &header));
+		if let (true, Some(kernel_hash)) = (stem, kernel_hash) {
+			self.dandelion_epoch.write().take_stem_source(&kernel_hash);
+		}
+		match res {
 			Ok(_) => {
 				self.processed_transactions.contains(&tx_hash, true);
+				// Stem txs are expected to come again, fluffed
+				if !stem {
+					seen_cache().add(tx_hash, SeenNetwork::P2p, now);
+				}
 				Ok(true)
 			}
 			Err(e) => {
@@ -203,6 +295,15 @@ where
 		} else {
 			debug!("block_received, cache for {} OK", b_hash);
 		}
+		if seen_cache().is_seen(
+			&b_hash,
+			SeenObject::Block,
+			SeenNetwork::P2p,
+			Utc::now().timestamp_millis(),
+		) {
+			debug!("block_received, {} is already seen", b_hash);
+			return Ok(true);
+		}
 
 		if self.chain().block_exists(b.hash())? {
 			return Ok(true);
@@ -226,11 +327,20 @@ where
 		cb: core::CompactBlock,
 		peer_info: &PeerInfo,
 	) -> Result<bool, chain::Error> {
+		let bhash = cb.hash();
+		if seen_cache().is_seen(
+			&bhash,
+			SeenObject::Block,
+			SeenNetwork::P2p,
+			Utc::now().timestamp_millis(),
+		) {
+			debug!("compact_block_received, {} is already seen", bhash);
+			return Ok(true);
+		}
 		// No need to process this compact block if we have previously accepted the _full block_.
-		if self.chain().block_exists(cb.hash())? {
+		if self.chain().block_exists(bhash)? {
 			return Ok(true);
 		}
-		let bhash = cb.hash();
 		debug!(
 			"Received compact_block {} at {} from {} [out/kern/kern_ids: {}/{}/{}] going to process.",
 			bhash,
@@ -242,7 +352,7 @@ where
 		);
 
 		let cb_hash = cb.hash();
-		if cb.kern_ids().is_empty() {
+		if cb.kern_ids().is_empty() && cb.prefilled().is_empty() {
 			// push the freshly hydrated block through the chain pipeline
 			match core::Block::hydrate_from(cb, &[]) {
 				Ok(block) => {
@@ -274,66 +384,105 @@ where
 				return Ok(!e.is_bad_data());
 			}
 
-			let (txs, missing_short_ids) = {
+			let (mut txs, missing_short_ids) = {
 				self.tx_pool
 					.read()
 					.retrieve_transactions(cb.hash(), cb.nonce, cb.kern_ids())
 			};
 
 			debug!(
-				"compact_block_received: txs from tx pool - {}, (unknown kern_ids: {})",
+				"compact_block_received: txs from tx pool - {}, prefilled - {}, (unknown kern_ids: {})",
 				txs.len(),
+				cb.prefilled().len(),
 				missing_short_ids.len(),
 			);
+			txs.extend_from_slice(cb.prefilled());
 
-			// If we have missing kernels then we know we cannot hydrate this compact block.
+			// If we have missing kernels then we know we cannot hydrate this compact block yet.
+			// Peers supporting it are asked for the missing txs only, the others for the full block.
 			if !missing_short_ids.is_empty() {
-				self.request_block(&cb.header, peer_info, chain::Options::NONE);
+				if peer_info.version.value() >= 4 {
+					self.request_block_transactions(cb, txs, missing_short_ids, peer_info);
+				} else {
+					self.request_block(&cb.header, peer_info, chain::Options::NONE);
+				}
 				return Ok(true);
 			}
 
-			let block = match core::Block::hydrate_from(cb.clone(), &txs) {
-				Ok(block) => {
-					if !self.sync_state.is_syncing() {
-						for hook in &self.hooks {
-							hook.on_block_received(&block, &peer_info.addr);
-						}
-					}
-					block
-				}
-				Err(e) => {
-					debug!("Invalid hydrated block {}: {:?}", cb.hash(), e);
-					return Ok(false);
-				}
-			};
+			self.hydrate_compact_block(cb, &txs, peer_info)
+		}
+	}
 
-			if let Ok(prev) = self.chain().get_previous_header(&cb.header) {
-				if block
-					.validate(&prev.total_kernel_offset, self.verifier_cache.clone())
-					.is_ok()
-				{
-					debug!(
-						"successfully hydrated block: {} at {} ({})",
-						block.header.hash(),
-						block.header.height,
-						block.inputs().version_str(),
-					);
-					self.process_block(block, peer_info, chain::Options::NONE)
-				} else if self.sync_state.status() == SyncStatus::NoSync {
-					debug!("adapter: block invalid after hydration, requesting full block");
-					self.request_block(&cb.header, peer_info, chain::Options::NONE);
-					Ok(true)
-				} else {
-					debug!("block invalid after hydration, ignoring it, cause still syncing");
-					Ok(true)
-				}
-			} else {
-				debug!("failed to retrieve previous block header (still syncing?)");
+	fn block_transactions_received(
+		&self,
+		block_hash: Hash,
+		txs: Vec<Transaction>,
+		peer_info: &PeerInfo,
+	) -> Result<bool, chain::Error> {
+		// Only the peer we asked can answer, anybody else could feed us junk txs
+		// and have us drop the pending compact block.
+		let pending = {
+			let mut pending = self.pending_compact_blocks.lock().unwrap();
+			match pending.get(&block_hash) {
+				Some((_, _, addr, _)) if *addr == peer_info.addr => pending.remove(&block_hash),
+				_ => None,
+			}
+		};
+		let (cb, mut known_txs) = match pending {
+			Some((cb, known_txs, _, _)) => (cb, known_txs),
+			None => {
+				debug!(
+					"block_transactions_received: no pending compact block {} from {}",
+					block_hash, peer_info.addr
+				);
+				return Ok(true);
+			}
+		};
+		if self.chain().block_exists(block_hash)? {
+			return Ok(true);
+		}
+		known_txs.extend(txs);
+
+		// The peer might not have all the txs anymore, get the full block then.
+		let short_ids: HashSet<_> = known_txs
+			.iter()
+			.flat_map(|tx| tx.kernels().iter())
+			.map(|k| k.short_id(&block_hash, cb.nonce))
+			.collect();
+		if !cb.kern_ids().iter().all(|id| short_ids.contains(id)) {
+			debug!(
+				"block_transactions_received: still missing txs of {}, requesting full block",
+				block_hash
+			);
+			self.request_block(&cb.header, peer_info, chain::Options::NONE);
+			return Ok(true);
+		}
+
+		let header = cb.header.clone();
+		match self.hydrate_compact_block(cb, &known_txs, peer_info)? {
+			true => Ok(true),
+			false => {
+				debug!(
+					"block_transactions_received: failed to hydrate {}, requesting full block",
+					block_hash
+				);
+				self.request_block(&header, peer_info, chain::Options::NONE);
 				Ok(true)
 			}
 		}
 	}
 
+	fn get_block_transactions(
+		&self,
+		block_hash: Hash,
+		nonce: u64,
+		kern_ids: &[ShortId],
+	) -> Vec<Transaction> {
+		self.tx_pool
+			.read()
+			.retrieve_block_transactions(block_hash, nonce, kern_ids)
+	}
+
 	fn header_received(
 		&self,
 		bh: core::BlockHeader,
@@ -438,6 +587,13 @@ where
 			tip_processed,
 		);
 
+		if !self.header_pipeline.headers_received(bhs) {
+			debug!(
+				"headers from {} don't stitch with the parallel header sync",
+				peer_info.addr
+			);
+		}
+
 		if bhs[0].height > tip_processed + 1 {
 			// we can't process this yet.
 			// try to process anything in the cache that we can
@@ -513,6 +669,7 @@ where
 						);
 						hashmap.clear();
 						*(self.reset_tip.lock().unwrap()) = first_height - 1;
+						self.header_pipeline.mark_inconsistent();
 						break;
 					}
 				}
@@ -606,12 +763,20 @@ where
 	/// the required indexes for a consumer to rewind to a consistent state
 	/// at the provided block hash.
 	fn txhashset_read(&self, h: Hash) -> Option<p2p::TxHashSetRead> {
-		match self.chain().txhashset_read(h.clone()) {
-			Ok((out_index, kernel_index, read)) => Some(p2p::TxHashSetRead {
-				output_index: out_index,
-				kernel_index: kernel_index,
-				reader: read,
-			}),
+		let res =
+			self.chain()
+				.txhashset_read(h.clone())
+				.and_then(|(out_index, kernel_index, read)| {
+					let archive_hash = self.chain().txhashset_zip_hash(&h, &read)?;
+					Ok(p2p::TxHashSetRead {
+						output_index: out_index,
+						kernel_index: kernel_index,
+						reader: read,
+						archive_hash,
+					})
+				});
+		match res {
+			Ok(read) => Some(read),
 			Err(e) => {
 				warn!("Couldn't produce txhashset data for block {}: {:?}", h, e);
 				None
@@ -694,6 +859,101 @@ where
 		}
 	}
 
+	fn get_output_segment(
+		&self,
+		hash: Hash,
+		id: SegmentIdentifier,
+	) -> Result<(Segment<OutputIdentifier>, Vec<u64>, Vec<Hash>, OutputRoots), chain::Error> {
+		self.chain().get_output_segment(&hash, id)
+	}
+
+	fn get_rangeproof_segment(
+		&self,
+		hash: Hash,
+		id: SegmentIdentifier,
+	) -> Result<Segment<RangeProof>, chain::Error> {
+		self.chain().get_rangeproof_segment(&hash, id)
+	}
+
+	fn get_kernel_segment(
+		&self,
+		hash: Hash,
+		id: SegmentIdentifier,
+	) -> Result<Segment<TxKernel>, chain::Error> {
+		self.chain().get_kernel_segment(&hash, id)
+	}
+
+	fn receive_output_segment(
+		&self,
+		block_hash: Hash,
+		segment: Segment<OutputIdentifier>,
+		spent: Vec<u64>,
+		bitmap_proof: Vec<Hash>,
+		output_roots: OutputRoots,
+		peer_info: &PeerInfo,
+	) -> Result<bool, chain::Error> {
+		self.segment_received(block_hash, peer_info, |desegmenter| {
+			desegmenter.add_output_segment(segment, &spent, bitmap_proof, output_roots)
+		})
+	}
+
+	fn receive_rangeproof_segment(
+		&self,
+		block_hash: Hash,
+		segment: Segment<RangeProof>,
+		peer_info: &PeerInfo,
+	) -> Result<bool, chain::Error> {
+		self.segment_received(block_hash, peer_info, |desegmenter| {
+			desegmenter.add_rangeproof_segment(segment)
+		})
+	}
+
+	fn receive_kernel_segment(
+		&self,
+		block_hash: Hash,
+		segment: Segment<TxKernel>,
+		peer_info: &PeerInfo,
+	) -> Result<bool, chain::Error> {
+		self.segment_received(block_hash, peer_info, |desegmenter| {
+			desegmenter.add_kernel_segment(segment)
+		})
+	}
+
+	fn get_historical_output(&self, pos: u64) -> Option<p2p::msg::HistoricalOutput> {
+		let chain = self.chain();
+		if !chain.archive_mode() {
+			return None;
+		}
+		match chain.get_historical_output(pos) {
+			Ok((output, spent, height)) => Some(p2p::msg::HistoricalOutput {
+				pos,
+				height,
+				spent,
+				output,
+			}),
+			Err(e) => {
+				debug!("get_historical_output: pos {}, {}", pos, e);
+				None
+			}
+		}
+	}
+
+	fn historical_output_received(
+		&self,
+		output: p2p::msg::HistoricalOutput,
+		peer_info: &PeerInfo,
+	) -> Result<bool, chain::Error> {
+		debug!(
+			"Received historical output {:?} at pos {} (height {}, spent {}) from {}",
+			output.output.commitment(),
+			output.pos,
+			output.height,
+			output.spent,
+			peer_info.addr
+		);
+		Ok(true)
+	}
+
 	fn get_tmp_dir(&self) -> PathBuf {
 		self.chain().get_tmp_dir()
 	}
@@ -709,6 +969,50 @@ where
 	P: PoolAdapter,
 	V: VerifierCache + 'static,
 {
+	// Hand a received txhashset segment over to the desegmenter. Segments we
+	// are not (or no longer) downloading are ignored. An invalid segment is
+	// reported as bad data, any other failure restarts the state sync.
+	fn segment_received<F>(
+		&self,
+		block_hash: Hash,
+		peer_info: &PeerInfo,
+		add_segment: F,
+	) -> Result<bool, chain::Error>
+	where
+		F: FnOnce(&mut Desegmenter) -> Result<(), chain::Error>,
+	{
+		let chain = self.chain();
+		let desegmenter = chain.desegmenter();
+		let mut desegmenter = desegmenter.write();
+		let desegmenter = match desegmenter.as_mut() {
+			Some(desegmenter) if desegmenter.header().hash() == block_hash => desegmenter,
+			_ => {
+				debug!(
+					"Received a txhashset segment for {} from {}, not downloading it",
+					block_hash, peer_info.addr
+				);
+				return Ok(true);
+			}
+		};
+		match add_segment(desegmenter) {
+			Ok(_) => Ok(true),
+			Err(e) => match e.kind() {
+				chain::ErrorKind::Segment(_) => {
+					warn!(
+						"Received an invalid txhashset segment from {}, {}",
+						peer_info.addr, e
+					);
+					Ok(false)
+				}
+				_ => {
+					error!("Failed to apply txhashset segment, {}", e);
+					self.sync_state.set_sync_error(e);
+					Ok(true)
+				}
+			},
+		}
+	}
+
 	/// Construct a new NetToChainAdapter instance
 	pub fn new(
 		sync_state: Arc<SyncState>,
@@ -717,6 +1021,8 @@ where
 		verifier_cache: Arc<RwLock<V>>,
 		config: ServerConfig,
 		hooks: Vec<Box<dyn NetEvents + Send + Sync>>,
+		header_pipeline: Arc<HeaderSyncPipeline>,
+		dandelion_epoch: Arc<RwLock<DandelionEpoch>>,
 	) -> Self {
 		NetToChainAdapter {
 			sync_state,
@@ -732,6 +1038,9 @@ where
 			header_cache: Arc::new(Mutex::new(HashMap::new())),
 			tip_processed: Arc::new(Mutex::new(0)),
 			reset_tip: Arc::new(Mutex::new(0)),
+			header_pipeline,
+			dandelion_epoch,
+			pending_compact_blocks: Mutex::new(HashMap::new()),
 		}
 	}
 
@@ -797,6 +1106,7 @@ where
 
 		match self.chain().process_block(b, opts) {
 			Ok(_) => {
+				seen_cache().add(bhash, SeenNetwork::P2p, Utc::now().timestamp_millis());
 				self.validate_chain(bhash);
 				self.check_compact();
 				Ok(true)
@@ -866,6 +1176,11 @@ where
 			return;
 		}
 
+		// Background compactor takes care of it
+		if self.config.chain_config.background_compaction {
+			return;
+		}
+
 		// Roll the dice to trigger compaction at 1/COMPACTION_CHECK chance per block,
 		// uses a different thread to avoid blocking the caller thread (likely a peer)
 		let mut rng = thread_rng();
@@ -889,6 +1204,87 @@ where
 	// it into a full block then fallback to requesting the full block
 	// from the same peer that gave us the compact block
 	// consider additional peers for redundancy?
+	// Hydrates a compact block with the provided txs and processes the resulting block.
+	fn hydrate_compact_block(
+		&self,
+		cb: core::CompactBlock,
+		txs: &[Transaction],
+		peer_info: &PeerInfo,
+	) -> Result<bool, chain::Error> {
+		let block = match core::Block::hydrate_from(cb.clone(), txs) {
+			Ok(block) => {
+				if !self.sync_state.is_syncing() {
+					for hook in &self.hooks {
+						hook.on_block_received(&block, &peer_info.addr);
+					}
+				}
+				block
+			}
+			Err(e) => {
+				debug!("Invalid hydrated block {}: {:?}", cb.hash(), e);
+				return Ok(false);
+			}
+		};
+
+		if let Ok(prev) = self.chain().get_previous_header(&cb.header) {
+			if block
+				.validate(&prev.total_kernel_offset, self.verifier_cache.clone())
+				.is_ok()
+			{
+				debug!(
+					"successfully hydrated block: {} at {} ({})",
+					block.header.hash(),
+					block.header.height,
+					block.inputs().version_str(),
+				);
+				self.process_block(block, peer_info, chain::Options::NONE)
+			} else if self.sync_state.status() == SyncStatus::NoSync {
+				debug!("adapter: block invalid after hydration, requesting full block");
+				self.request_block(&cb.header, peer_info, chain::Options::NONE);
+				Ok(true)
+			} else {
+				debug!("block invalid after hydration, ignoring it, cause still syncing");
+				Ok(true)
+			}
+		} else {
+			debug!("failed to retrieve previous block header (still syncing?)");
+			Ok(true)
+		}
+	}
+
+	// Keeps the compact block aside and asks the peer for the txs we are missing.
+	fn request_block_transactions(
+		&self,
+		cb: core::CompactBlock,
+		txs: Vec<Transaction>,
+		missing_short_ids: Vec<ShortId>,
+		peer_info: &PeerInfo,
+	) {
+		let block_hash = cb.hash();
+		let nonce = cb.nonce;
+		{
+			let mut pending = self.pending_compact_blocks.lock().unwrap();
+			pending.retain(|_, (_, _, _, at)| at.elapsed().as_secs() < PENDING_COMPACT_BLOCK_SECS);
+			pending.insert(
+				block_hash,
+				(cb, txs, peer_info.addr.clone(), Instant::now()),
+			);
+		}
+		match self.peers().get_connected_peer(peer_info.addr.clone()) {
+			None => debug!(
+				"request_block_transactions: can't send request to peer {:?}, not connected",
+				peer_info.addr
+			),
+			Some(peer) => {
+				if let Err(e) =
+					peer.send_block_transactions_request(block_hash, nonce, missing_short_ids)
+				{
+					error!("request_block_transactions: failed: {:?}", e)
+				}
+			}
+		}
+	}
+
 	fn request_block(&self, bh: &BlockHeader, peer_info: &PeerInfo, opts: Options) {
 		self.send_block_request_to_peer(bh.hash(), peer_info, |peer, h| {
 			peer.send_block_request(h, opts)
@@ -969,15 +1365,15 @@ where
 	fn block_accepted(&self, b: &core::Block, status: BlockStatus, opts: Options) {
 		// not broadcasting blocks received through sync
 		if !opts.contains(chain::Options::SYNC) {
-			for hook in &self.hooks {
-				hook.on_block_accepted(b, status);
-			}
 			// If we mined the block then we want to broadcast the compact block.
 			// If we received the block from another node then broadcast "header first"
 			// to minimize network traffic.
 			if opts.contains(Options::MINE) {
-				// propagate compact block out if we mined the block
-				let cb: CompactBlock = b.clone().into();
+				// propagate compact block out if we mined the block,
+				// the txs that are likely still propagating are sent in full
+				let since = Utc::now() - Duration::seconds(PREFILL_TX_AGE_SECS);
+				let prefilled = self.tx_pool.read().recent_transactions(since);
+				let cb = CompactBlock::with_prefilled(b.clone(), prefilled);
 				self.peers().broadcast_compact_block(&cb);
 			} else {
 				// "header first" propagation if we are not the originator of this block
@@ -999,8 +1395,32 @@ where
 			tx_pool.truncate_reorg_cache(cutoff);
 		}
 
-		if status.is_reorg() {
-			let _ = self.tx_pool.write().reconcile_reorg_cache(&b.header);
+		// New tip moves the horizon of the integrity kernels for the libp2p messages
+		if status.is_next() || status.is_reorg() {
+			libp2p_connection::notify_chain_tip(b.header.height);
+		}
+
+		// On reorg the txs from the disconnected blocks are going back to the pool,
+		// the counts are reported to the hooks together with the reorg event.
+		let mut reorg_txs = None;
+		if let BlockStatus::Reorg {
+			prev_head,
+			fork_point,
+			..
+		} = status
+		{
+			let mut tx_pool = self.tx_pool.write();
+			match tx_pool.resurrect_reorg_txs(&b.header, &prev_head.hash(), &fork_point.hash()) {
+				Ok(stats) => reorg_txs = Some(stats),
+				Err(e) => warn!("Unable to resurrect txs after reorg, {}", e),
+			}
+			let _ = tx_pool.reconcile_reorg_cache(&b.header);
+		}
+
+		if !opts.contains(chain::Options::SYNC) {
+			for hook in &self.hooks {
+				hook.on_block_accepted(b, status, reorg_txs.as_ref());
+			}
 		}
 	}
 }
@@ -1037,11 +1457,29 @@ where
 	}
 }
 
+/// Reorg listener for the libp2p node, integrity kernels it has cached
+/// might be reverted by the reorg.
+pub struct Libp2pReorgListener;
+
+impl chain::ReorgListener for Libp2pReorgListener {
+	fn on_reorg(&self, event: &chain::ReorgEvent) {
+		debug!(
+			"Chain reorg from {} to {}, {} blocks disconnected",
+			event.old_tip.last_block_h,
+			event.new_tip.last_block_h,
+			event.disconnected.len()
+		);
+		libp2p_connection::notify_chain_reorg();
+	}
+}
+
 /// Adapter between the transaction pool and the network, to relay
 /// transactions that have been accepted.
 pub struct PoolToNetAdapter {
 	peers: OneTime<Weak<p2p::Peers>>,
 	dandelion_epoch: Arc<RwLock<DandelionEpoch>>,
+	tx_receipts: bool,
+	hooks: Vec<Box<dyn PoolEvents + Send + Sync>>,
 }
 
 /// Adapter between the Dandelion monitor and the current Dandelion "epoch".
@@ -1073,6 +1511,16 @@ impl DandelionAdapter for PoolToNetAdapter {
 impl pool::PoolAdapter for PoolToNetAdapter {
 	fn tx_accepted(&self, entry: &pool::PoolEntry) {
 		self.peers().broadcast_transaction(&entry.tx);
+
+		// Note, stem transactions are never reported, receipt would break the dandelion privacy
+		if self.tx_receipts {
+			let kernels: Vec<_> = entry.tx.kernels().iter().map(|k| k.excess()).collect();
+			libp2p_connection::publish_tx_receipt(&kernels);
+		}
+
+		for hook in &self.hooks {
+			hook.on_tx_accepted(entry);
+		}
 	}
 
 	fn stem_tx_accepted(&self, entry: &pool::PoolEntry) -> Result<(), pool::PoolError> {
@@ -1085,10 +1533,18 @@ impl pool::PoolAdapter for PoolToNetAdapter {
 		// If "fluff" epoch then nothing to do right now (fluff via Dandelion monitor).
 		// If node is configured to always stem our (pushed via api) txs then do so.
 		if epoch.is_stem() || (entry.src.is_pushed() && epoch.always_stem_our_txs()) {
-			if let Some(peer) = epoch.relay_peer(&self.peers()) {
+			let source = entry
+				.tx
+				.kernels()
+				.first()
+				.and_then(|k| epoch.take_stem_source(&k.hash()));
+			if let Some(peer) = epoch.relay_peer(&self.peers(), source.as_ref()) {
 				match peer.send_stem_transaction(&entry.tx) {
 					Ok(_) => {
 						info!("Stemming this epoch, relaying to next peer.");
+						for hook in &self.hooks {
+							hook.on_stem_tx_broadcast(entry);
+						}
 						Ok(())
 					}
 					Err(e) => {
@@ -1105,14 +1561,34 @@ impl pool::PoolAdapter for PoolToNetAdapter {
 			Ok(())
 		}
 	}
+
+	fn tx_evicted(&self, entry: &pool::PoolEntry) {
+		for hook in &self.hooks {
+			hook.on_tx_evicted(entry);
+		}
+	}
+
+	fn tx_rejected(&self, tx: &Transaction, src: &pool::TxSource, err: &pool::PoolError) {
+		for hook in &self.hooks {
+			hook.on_tx_rejected(tx, src, err);
+		}
+	}
 }
 
 impl PoolToNetAdapter {
 	/// Create a new pool to net adapter
-	pub fn new(config: pool::DandelionConfig) -> PoolToNetAdapter {
+	/// tx_receipts - publish receipts for accepted transactions to libp2p network
+	/// hooks - the pool event hooks
+	pub fn new(
+		config: pool::DandelionConfig,
+		tx_receipts: bool,
+		hooks: Vec<Box<dyn PoolEvents + Send + Sync>>,
+	) -> PoolToNetAdapter {
 		PoolToNetAdapter {
 			peers: OneTime::new(),
 			dandelion_epoch: Arc::new(RwLock::new(DandelionEpoch::new(config))),
+			tx_receipts,
+			hooks,
 		}
 	}
 
@@ -1121,6 +1597,11 @@ impl PoolToNetAdapter {
 		self.peers.init(Arc::downgrade(&peers));
 	}
 
+	/// Current Dandelion epoch, shared with the net adapter receiving the stem txs
+	pub fn dandelion_epoch(&self) -> Arc<RwLock<DandelionEpoch>> {
+		self.dandelion_epoch.clone()
+	}
+
 	fn peers(&self) -> Arc<p2p::Peers> {
 		self.peers
 			.borrow()
@@ -1171,6 +1652,12 @@ impl pool::BlockChain for PoolToChainAdapter {
 			.map_err(|e| pool::PoolError::Other(format!("failed to get block_header, {}", e)))
 	}
 
+	fn get_block(&self, hash: &Hash) -> Result<core::Block, pool::PoolError> {
+		self.chain()
+			.get_block(hash)
+			.map_err(|e| pool::PoolError::Other(format!("failed to get block, {}", e)))
+	}
+
 	fn get_block_sums(&self, hash: &Hash) -> Result<BlockSums, pool::PoolError> {
 		self.chain()
 			.get_block_sums(hash)
@@ -1183,6 +1670,15 @@ impl pool::BlockChain for PoolToChainAdapter {
 			.map_err(|e| pool::PoolError::Other(format!("failed to validate tx, {}", e)))
 	}
 
+	fn validate_nrd_kernels(&self, kernels: &[TxKernel]) -> Result<(), pool::PoolError> {
+		self.chain()
+			.validate_nrd_kernels(kernels)
+			.map_err(|e| match e.kind() {
+				chain::ErrorKind::NRDRelativeHeight => pool::PoolError::NRDKernelRelativeHeight,
+				_ => pool::PoolError::Other(format!("failed to validate kernels, {}", e)),
+			})
+	}
+
 	fn validate_inputs(&self, inputs: &Inputs) -> Result<Vec<OutputIdentifier>, pool::PoolError> {
 		self.chain()
 			.validate_inputs(inputs)
@@ -1207,6 +1703,15 @@ impl pool::BlockChain for PoolToChainAdapter {
 			.replay_attack_check(tx)
 			.map_err(|_| pool::PoolError::DuplicateKernelOrDuplicateSpent)
 	}
+
+	fn record_tx_first_seen(&self, tx: &Transaction, at: DateTime<Utc>) -> DateTime<Utc> {
+		self.chain()
+			.record_tx_first_seen(tx, at)
+			.unwrap_or_else(|e| {
+				warn!("failed to record tx {} first seen time, {}", tx.hash(), e);
+				at
+			})
+	}
 }
 
 #[cfg(test)]
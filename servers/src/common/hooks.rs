@@ -19,13 +19,17 @@ extern crate hyper;
 extern crate hyper_rustls;
 extern crate tokio;
 
+use crate::api::ws;
 use crate::chain::BlockStatus;
 use crate::common::types::{ServerConfig, WebHooksConfig};
 use crate::core::core;
-use crate::core::core::hash::Hashed;
+use crate::core::core::hash::{Hash, Hashed};
+use crate::p2p::libp2p_connection::Libp2pObserver;
 use crate::p2p::types::PeerAddr;
+use crate::p2p::PeerId;
+use crate::pool::{PoolEntry, PoolError, ReorgTxStats, TxSource};
 use futures::TryFutureExt;
-use grin_util::ToHex;
+use grin_util::{Mutex, ToHex};
 use hyper::client::HttpConnector;
 use hyper::header::HeaderValue;
 use hyper::Client;
@@ -33,28 +37,79 @@ use hyper::{Body, Method, Request};
 use hyper_rustls::HttpsConnector;
 use serde::Serialize;
 use serde_json::{json, to_string};
-use std::time::Duration;
-use tokio::runtime::{Builder, Runtime};
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::runtime::Handle;
+use tokio::sync::Semaphore;
 
 /// Returns the list of event hooks that will be initialized for network events
-pub fn init_net_hooks(config: &ServerConfig) -> Vec<Box<dyn NetEvents + Send + Sync>> {
+pub fn init_net_hooks(
+	config: &ServerConfig,
+	runtime: &Handle,
+) -> Vec<Box<dyn NetEvents + Send + Sync>> {
 	let mut list: Vec<Box<dyn NetEvents + Send + Sync>> = Vec::new();
 	list.push(Box::new(EventLogger));
 	if config.webhook_config.block_received_url.is_some()
 		|| config.webhook_config.tx_received_url.is_some()
 		|| config.webhook_config.header_received_url.is_some()
 	{
-		list.push(Box::new(WebHook::from_config(&config.webhook_config)));
+		list.push(Box::new(WebHook::from_config(
+			&config.webhook_config,
+			runtime.clone(),
+		)));
 	}
 	list
 }
 
 /// Returns the list of event hooks that will be initialized for chain events
-pub fn init_chain_hooks(config: &ServerConfig) -> Vec<Box<dyn ChainEvents + Send + Sync>> {
+pub fn init_chain_hooks(
+	config: &ServerConfig,
+	runtime: &Handle,
+) -> Vec<Box<dyn ChainEvents + Send + Sync>> {
 	let mut list: Vec<Box<dyn ChainEvents + Send + Sync>> = Vec::new();
 	list.push(Box::new(EventLogger));
+	list.push(Box::new(WsEventPublisher));
 	if config.webhook_config.block_accepted_url.is_some() {
-		list.push(Box::new(WebHook::from_config(&config.webhook_config)));
+		list.push(Box::new(WebHook::from_config(
+			&config.webhook_config,
+			runtime.clone(),
+		)));
+	}
+	list
+}
+
+/// Returns the list of event hooks that will be initialized for transaction pool events
+pub fn init_pool_hooks(
+	config: &ServerConfig,
+	runtime: &Handle,
+) -> Vec<Box<dyn PoolEvents + Send + Sync>> {
+	let mut list: Vec<Box<dyn PoolEvents + Send + Sync>> = Vec::new();
+	list.push(Box::new(EventLogger));
+	list.push(Box::new(WsEventPublisher));
+	if config.webhook_config.tx_pool_accepted_url.is_some()
+		|| config.webhook_config.tx_pool_rejected_url.is_some()
+		|| config.webhook_config.tx_stem_broadcast_url.is_some()
+		|| config.webhook_config.tx_evicted_url.is_some()
+	{
+		list.push(Box::new(WebHook::from_config(
+			&config.webhook_config,
+			runtime.clone(),
+		)));
+	}
+	list
+}
+
+/// Returns the list of event hooks that will be initialized for libp2p node events
+pub fn init_libp2p_hooks(config: &ServerConfig, runtime: &Handle) -> Vec<Arc<dyn Libp2pObserver>> {
+	let mut list: Vec<Arc<dyn Libp2pObserver>> = Vec::new();
+	list.push(Arc::new(EventLogger));
+	if config.webhook_config.libp2p_peer_event_url.is_some() {
+		list.push(Arc::new(WebHook::from_config(
+			&config.webhook_config,
+			runtime.clone(),
+		)));
 	}
 	list
 }
@@ -75,8 +130,31 @@ pub trait NetEvents {
 #[allow(unused_variables)]
 /// Trait to be implemented by Chain Event Hooks
 pub trait ChainEvents {
-	/// Triggers when a new block is accepted by the chain (might be a Reorg or a Fork)
-	fn on_block_accepted(&self, block: &core::Block, status: BlockStatus) {}
+	/// Triggers when a new block is accepted by the chain (might be a Reorg or a Fork).
+	/// For Reorg the counts of txs from the disconnected blocks processed by the pool are provided.
+	fn on_block_accepted(
+		&self,
+		block: &core::Block,
+		status: BlockStatus,
+		reorg_txs: Option<&ReorgTxStats>,
+	) {
+	}
+}
+
+#[allow(unused_variables)]
+/// Trait to be implemented by Transaction Pool Event Hooks
+pub trait PoolEvents {
+	/// Triggers when a transaction is accepted to the txpool
+	fn on_tx_accepted(&self, entry: &PoolEntry) {}
+
+	/// Triggers when a transaction is rejected by the pool
+	fn on_tx_rejected(&self, tx: &core::Transaction, src: &TxSource, err: &PoolError) {}
+
+	/// Triggers when a stem transaction is relayed to the next Dandelion peer
+	fn on_stem_tx_broadcast(&self, entry: &PoolEntry) {}
+
+	/// Triggers when a transaction is evicted from the txpool by a better paying one
+	fn on_tx_evicted(&self, entry: &PoolEntry) {}
 }
 
 /// Basic Logger
@@ -116,15 +194,21 @@ impl NetEvents for EventLogger {
 }
 
 impl ChainEvents for EventLogger {
-	fn on_block_accepted(&self, block: &core::Block, status: BlockStatus) {
+	fn on_block_accepted(
+		&self,
+		block: &core::Block,
+		status: BlockStatus,
+		reorg_txs: Option<&ReorgTxStats>,
+	) {
 		match status {
 			BlockStatus::Reorg {
 				prev,
 				prev_head,
 				fork_point,
 			} => {
+				let reorg_txs = reorg_txs.cloned().unwrap_or_default();
 				warn!(
-					"block_accepted (REORG!): {} at {}, (prev: {} at {}, prev_head: {} at {}, fork_point: {} at {}, depth: {})",
+					"block_accepted (REORG!): {} at {}, (prev: {} at {}, prev_head: {} at {}, fork_point: {} at {}, depth: {}, resurrected txs: {}, failed txs: {})",
 					block.hash(),
 					block.header.height,
 					prev.hash(),
@@ -134,6 +218,8 @@ impl ChainEvents for EventLogger {
 					fork_point.hash(),
 					fork_point.height,
 					prev_head.height.saturating_sub(fork_point.height),
+					reorg_txs.resurrected,
+					reorg_txs.failed,
 				);
 			}
 			BlockStatus::Fork {
@@ -167,6 +253,105 @@ impl ChainEvents for EventLogger {
 	}
 }
 
+impl PoolEvents for EventLogger {
+	fn on_tx_rejected(&self, tx: &core::Transaction, src: &TxSource, err: &PoolError) {
+		debug!(
+			"Tx {} from {:?} rejected by the pool, {}",
+			tx.hash(),
+			src,
+			err
+		);
+	}
+
+	fn on_tx_evicted(&self, entry: &PoolEntry) {
+		info!(
+			"Tx {} evicted from the txpool, fee_to_weight {}",
+			entry.tx.hash(),
+			entry.tx.fee_to_weight()
+		);
+	}
+}
+
+impl Libp2pObserver for EventLogger {
+	fn peer_connected(&self, peer: &PeerId) {
+		debug!("libp2p peer {} is connected", peer);
+	}
+
+	fn peer_disconnected(&self, peer: &PeerId) {
+		debug!("libp2p peer {} is disconnected", peer);
+	}
+
+	fn message_rejected(&self, peer: &PeerId, topic: &str) {
+		info!(
+			"libp2p message from {} at topic {} is rejected",
+			peer, topic
+		);
+	}
+
+	fn dial_failed(&self, address: &str) {
+		debug!("Unable to dial libp2p peer {}", address);
+	}
+}
+
+/// Publisher of the events for the WebSocket clients of the api
+struct WsEventPublisher;
+
+impl ChainEvents for WsEventPublisher {
+	fn on_block_accepted(
+		&self,
+		block: &core::Block,
+		status: BlockStatus,
+		reorg_txs: Option<&ReorgTxStats>,
+	) {
+		if !ws::has_ws_clients() {
+			return;
+		}
+		let status_str = match status {
+			BlockStatus::Reorg { .. } => "reorg",
+			BlockStatus::Fork { .. } => "fork",
+			BlockStatus::Next { .. } => "head",
+		};
+		ws::publish_ws_event(
+			ws::WS_TOPIC_BLOCK_ACCEPTED,
+			json!({
+				"hash": block.header.hash().to_hex(),
+				"height": block.header.height,
+				"prev_hash": block.header.prev_hash.to_hex(),
+				"status": status_str,
+			}),
+		);
+		if let BlockStatus::Reorg {
+			fork_point,
+			prev_head,
+			..
+		} = status
+		{
+			ws::publish_ws_event(
+				ws::WS_TOPIC_REORG,
+				json!({
+					"hash": block.header.hash().to_hex(),
+					"height": block.header.height,
+					"prev_head": { "hash": prev_head.hash().to_hex(), "height": prev_head.height },
+					"fork_point": { "hash": fork_point.hash().to_hex(), "height": fork_point.height },
+					"depth": prev_head.height.saturating_sub(fork_point.height),
+					"txs": reorg_txs.cloned().unwrap_or_default(),
+				}),
+			);
+		}
+	}
+}
+
+impl PoolEvents for WsEventPublisher {
+	fn on_tx_accepted(&self, entry: &PoolEntry) {
+		if !ws::has_ws_clients() {
+			return;
+		}
+		let mut payload = pool_tx_payload(&entry.tx);
+		payload["source"] = json!(entry.src);
+		ws::publish_ws_event(ws::WS_TOPIC_TX_POOL_ACCEPTED, payload);
+	}
+}
+
 fn parse_url(value: &Option<String>) -> Option<hyper::Uri> {
 	match value {
 		Some(url) => {
@@ -187,6 +372,50 @@ fn parse_url(value: &Option<String>) -> Option<hyper::Uri> {
 	}
 }
 
+/// Pool event POST for the same tx is sent once per this period
+const POOL_WEBHOOK_DEBOUNCE: Duration = Duration::from_secs(60);
+/// Max number of the pool event POSTs per second, the rest are dropped
+const POOL_WEBHOOK_MAX_RATE: u32 = 20;
+
+/// Debounce of the pool event POSTs. Peers can send any number of invalid txs, every one of
+/// them is a rejected tx event.
+struct PoolEventsDebounce {
+	/// Last POST time of the event for the tx
+	sent: HashMap<(&'static str, Hash), Instant>,
+	/// Start of the current second and the number of POSTs in it
+	window: (Instant, u32),
+}
+
+impl PoolEventsDebounce {
+	fn new(now: Instant) -> PoolEventsDebounce {
+		PoolEventsDebounce {
+			sent: HashMap::new(),
+			window: (now, 0),
+		}
+	}
+
+	/// Check if the event POST for the tx can be sent now, the POST is counted if it can
+	fn allow(&mut self, event: &'static str, tx_hash: Hash, now: Instant) -> bool {
+		if now.duration_since(self.window.0) >= Duration::from_secs(1) {
+			self.window = (now, 0);
+			self.sent
+				.retain(|_, t| now.duration_since(*t) < POOL_WEBHOOK_DEBOUNCE);
+		}
+		let key = (event, tx_hash);
+		if let Some(t) = self.sent.get(&key) {
+			if now.duration_since(*t) < POOL_WEBHOOK_DEBOUNCE {
+				return false;
+			}
+		}
+		if self.window.1 >= POOL_WEBHOOK_MAX_RATE {
+			return false;
+		}
+		self.window.1 += 1;
+		self.sent.insert(key, now);
+		true
+	}
+}
+
 /// A struct that holds the hyper/tokio runtime.
 struct WebHook {
 	/// url to POST transaction data when a new transaction arrives from a peer
@@ -197,10 +426,24 @@ struct WebHook {
 	block_received_url: Option<hyper::Uri>,
 	/// url to POST block data when a new block is accepted by our node (might be a reorg or a fork)
 	block_accepted_url: Option<hyper::Uri>,
+	/// url to POST transaction data when a transaction is accepted to the txpool
+	tx_pool_accepted_url: Option<hyper::Uri>,
+	/// url to POST transaction data and the reason when a transaction is rejected by the pool
+	tx_pool_rejected_url: Option<hyper::Uri>,
+	/// url to POST transaction data when a stem transaction is relayed to the next peer
+	tx_stem_broadcast_url: Option<hyper::Uri>,
+	/// url to POST transaction data when a transaction is evicted from the txpool
+	tx_evicted_url: Option<hyper::Uri>,
+	/// url to POST libp2p peer events: connected, disconnected, rejected message, failed dial
+	libp2p_peer_event_url: Option<hyper::Uri>,
 	/// The hyper client to be used for all requests
 	client: Client<HttpsConnector<HttpConnector>>,
-	/// The tokio event loop
-	runtime: Runtime,
+	/// The node tokio runtime, the requests are spawned there
+	runtime: Handle,
+	/// Limit of the concurrent requests
+	requests_limit: Arc<Semaphore>,
+	/// Debounce of the pool event POSTs
+	pool_debounce: Arc<Mutex<PoolEventsDebounce>>,
 }
 
 impl WebHook {
@@ -210,13 +453,19 @@ impl WebHook {
 		header_received_url: Option<hyper::Uri>,
 		block_received_url: Option<hyper::Uri>,
 		block_accepted_url: Option<hyper::Uri>,
+		tx_pool_accepted_url: Option<hyper::Uri>,
+		tx_pool_rejected_url: Option<hyper::Uri>,
+		tx_stem_broadcast_url: Option<hyper::Uri>,
+		tx_evicted_url: Option<hyper::Uri>,
+		libp2p_peer_event_url: Option<hyper::Uri>,
 		nthreads: u16,
 		timeout: u16,
+		runtime: Handle,
 	) -> WebHook {
 		let keep_alive = Duration::from_secs(timeout as u64);
 
 		info!(
-			"Spawning {} threads for webhooks (timeout set to {} secs)",
+			"Webhooks run up to {} requests at once (timeout set to {} secs)",
 			nthreads, timeout
 		);
 
@@ -230,43 +479,99 @@ impl WebHook {
 			block_received_url,
 			header_received_url,
 			block_accepted_url,
+			tx_pool_accepted_url,
+			tx_pool_rejected_url,
+			tx_stem_broadcast_url,
+			tx_evicted_url,
+			libp2p_peer_event_url,
 			client,
-			runtime: Builder::new()
-				.threaded_scheduler()
-				.enable_all()
-				.core_threads(nthreads as usize)
-				.build()
-				.unwrap(),
+			runtime,
+			requests_limit: Arc::new(Semaphore::new(std::cmp::max(nthreads as usize, 1))),
+			pool_debounce: Arc::new(Mutex::new(PoolEventsDebounce::new(Instant::now()))),
 		}
 	}
 
 	/// Instantiates a Webhook struct from a configuration file
-	fn from_config(config: &WebHooksConfig) -> WebHook {
+	fn from_config(config: &WebHooksConfig, runtime: Handle) -> WebHook {
 		WebHook::new(
 			parse_url(&config.tx_received_url),
 			parse_url(&config.header_received_url),
 			parse_url(&config.block_received_url),
 			parse_url(&config.block_accepted_url),
+			parse_url(&config.tx_pool_accepted_url),
+			parse_url(&config.tx_pool_rejected_url),
+			parse_url(&config.tx_stem_broadcast_url),
+			parse_url(&config.tx_evicted_url),
+			parse_url(&config.libp2p_peer_event_url),
 			config.nthreads,
 			config.timeout,
+			runtime,
 		)
 	}
 
 	fn post(&self, url: hyper::Uri, data: String) {
-		let mut req = Request::new(Body::from(data));
-		*req.method_mut() = Method::POST;
-		*req.uri_mut() = url.clone();
-		req.headers_mut().insert(
-			hyper::header::CONTENT_TYPE,
-			HeaderValue::from_static("application/json"),
-		);
+		let future = post_request(&self.client, url, data);
+		let requests_limit = self.requests_limit.clone();
 
-		let future = self.client.request(req).map_err(move |e| {
-			warn!("Error sending POST request to {}, error: {}", url, e);
+		self.runtime.spawn(async move {
+			let _permit = requests_limit.acquire().await;
+			let _ = future.await;
 		});
+	}
+	/// POST the libp2p event. Observer is called from the swarm loop, the request is spawned
+	/// on the runtime.
+	fn post_libp2p_event(&self, payload: serde_json::Value) {
+		if let Some(url) = &self.libp2p_peer_event_url {
+			match to_string(&payload) {
+				Ok(data) => self.post(url.clone(), data),
+				Err(_) => error!("Failed to serialize libp2p event {}", payload),
+			}
+		}
+	}
+
+	/// POST the pool event for the tx. The pool lock is held by the caller, so the payload is
+	/// built by the runtime. Repeated events for the same tx and the events over the rate
+	/// limit are dropped.
+	fn post_pool_event(
+		&self,
+		event: &'static str,
+		uri: &Option<hyper::Uri>,
+		tx: &core::Transaction,
+		src: TxSource,
+		reason: Option<String>,
+	) {
+		let url = match uri {
+			Some(url) => url.clone(),
+			None => return,
+		};
+		let tx = tx.clone();
+		let client = self.client.clone();
+		let requests_limit = self.requests_limit.clone();
+		let debounce = self.pool_debounce.clone();
 
-		self.runtime.spawn(future);
+		self.runtime.spawn(async move {
+			let tx_hash = tx.hash();
+			if !debounce.lock().allow(event, tx_hash, Instant::now()) {
+				debug!("Webhook {} for tx {} is debounced", event, tx_hash);
+				return;
+			}
+			let mut payload = pool_tx_payload(&tx);
+			payload["source"] = json!(src);
+			if let Some(reason) = reason {
+				payload["reason"] = json!(reason);
+			}
+			let data = match to_string(&payload) {
+				Ok(data) => data,
+				Err(_) => {
+					error!("Failed to serialize transaction {}", tx_hash);
+					return;
+				}
+			};
+			let _permit = requests_limit.acquire().await;
+			let _ = post_request(&client, url, data).await;
+		});
 	}
+
 	fn make_request<T: Serialize>(&self, payload: &T, uri: &Option<hyper::Uri>) -> bool {
 		if let Some(url) = uri {
 			let payload = match to_string(payload) {
@@ -282,14 +587,19 @@ impl WebHook {
 }
 
 impl ChainEvents for WebHook {
-	fn on_block_accepted(&self, block: &core::Block, status: BlockStatus) {
+	fn on_block_accepted(
+		&self,
+		block: &core::Block,
+		status: BlockStatus,
+		reorg_txs: Option<&ReorgTxStats>,
+	) {
 		let status_str = match status {
 			BlockStatus::Reorg { .. } => "reorg",
 			BlockStatus::Fork { .. } => "fork",
 			BlockStatus::Next { .. } => "head",
 		};
 
-		// Add additional `depth` field to the JSON in case of reorg
+		// Add additional `depth` and `txs` fields to the JSON in case of reorg
 		let payload = if let BlockStatus::Reorg {
 			fork_point,
 			prev_head,
@@ -301,7 +611,8 @@ impl ChainEvents for WebHook {
 				"hash": block.header.hash().to_hex(),
 				"status": status_str,
 				"data": block,
-				"depth": depth
+				"depth": depth,
+				"txs": reorg_txs.cloned().unwrap_or_default()
 			})
 		} else {
 			json!({
@@ -365,3 +676,132 @@ impl NetEvents for WebHook {
 		}
 	}
 }
+
+/// POST the json data to the url, the errors are logged
+fn post_request(
+	client: &Client<HttpsConnector<HttpConnector>>,
+	url: hyper::Uri,
+	data: String,
+) -> impl Future<Output = Result<hyper::Response<Body>, ()>> {
+	let mut req = Request::new(Body::from(data));
+	*req.method_mut() = Method::POST;
+	*req.uri_mut() = url.clone();
+	req.headers_mut().insert(
+		hyper::header::CONTENT_TYPE,
+		HeaderValue::from_static("application/json"),
+	);
+
+	client.request(req).map_err(move |e| {
+		warn!("Error sending POST request to {}, error: {}", url, e);
+	})
+}
+
+/// Transaction payload of the pool events, the kernel excesses identify the tx
+fn pool_tx_payload(tx: &core::Transaction) -> serde_json::Value {
+	let kernels: Vec<String> = tx.kernels().iter().map(|k| k.excess().to_hex()).collect();
+	json!({
+		"hash": tx.hash().to_hex(),
+		"kernels": kernels,
+		"fee": tx.fee(),
+		"fee_to_weight": tx.fee_to_weight(),
+		"data": tx
+	})
+}
+
+impl PoolEvents for WebHook {
+	fn on_tx_accepted(&self, entry: &PoolEntry) {
+		self.post_pool_event(
+			"tx_pool_accepted",
+			&self.tx_pool_accepted_url,
+			&entry.tx,
+			entry.src,
+			None,
+		);
+	}
+
+	fn on_tx_rejected(&self, tx: &core::Transaction, src: &TxSource, err: &PoolError) {
+		self.post_pool_event(
+			"tx_pool_rejected",
+			&self.tx_pool_rejected_url,
+			tx,
+			*src,
+			Some(err.to_string()),
+		);
+	}
+
+	fn on_stem_tx_broadcast(&self, entry: &PoolEntry) {
+		self.post_pool_event(
+			"tx_stem_broadcast",
+			&self.tx_stem_broadcast_url,
+			&entry.tx,
+			entry.src,
+			None,
+		);
+	}
+
+	fn on_tx_evicted(&self, entry: &PoolEntry) {
+		self.post_pool_event(
+			"tx_evicted",
+			&self.tx_evicted_url,
+			&entry.tx,
+			entry.src,
+			None,
+		);
+	}
+}
+
+impl Libp2pObserver for WebHook {
+	fn peer_connected(&self, peer: &PeerId) {
+		self.post_libp2p_event(json!({"event": "peer_connected", "peer": peer.to_string()}));
+	}
+
+	fn peer_disconnected(&self, peer: &PeerId) {
+		self.post_libp2p_event(json!({"event": "peer_disconnected", "peer": peer.to_string()}));
+	}
+
+	fn message_rejected(&self, peer: &PeerId, topic: &str) {
+		self.post_libp2p_event(json!({
+			"event": "message_rejected",
+			"peer": peer.to_string(),
+			"topic": topic,
+		}));
+	}
+
+	fn dial_failed(&self, address: &str) {
+		self.post_libp2p_event(json!({"event": "dial_failed", "address": address}));
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+
+	#[test]
+	fn test_pool_events_debounce() {
+		let start = Instant::now();
+		let mut debounce = PoolEventsDebounce::new(start);
+		let tx_hash = Hash::from_vec(&[1]);
+
+		// Same event for the same tx is sent once per debounce period
+		assert!(debounce.allow("tx_pool_rejected", tx_hash, start));
+		assert!(!debounce.allow("tx_pool_rejected", tx_hash, start));
+		assert!(debounce.allow("tx_evicted", tx_hash, start));
+		let later = start + POOL_WEBHOOK_DEBOUNCE;
+		assert!(debounce.allow("tx_pool_rejected", tx_hash, later));
+
+		// Events over the rate are dropped until the next second
+		let mut debounce = PoolEventsDebounce::new(start);
+		for i in 0..POOL_WEBHOOK_MAX_RATE {
+			assert!(debounce.allow("tx_pool_rejected", Hash::from_vec(&[i as u8]), start));
+		}
+		let tx_hash = Hash::from_vec(&[0xff]);
+		assert!(!debounce.allow("tx_pool_rejected", tx_hash, start));
+		let next = start + Duration::from_secs(1);
+		assert!(debounce.allow("tx_pool_rejected", tx_hash, next));
+
+		// Expired events are cleaned up
+		let expired = next + POOL_WEBHOOK_DEBOUNCE;
+		assert!(debounce.allow("tx_pool_rejected", tx_hash, expired));
+		assert_eq!(debounce.sent.len(), 1);
+	}
+}
@@ -26,8 +26,11 @@ use crate::core::ser::ProtocolVersion;
 
 use chrono::prelude::*;
 
-use crate::chain::SyncStatus;
+use crate::chain::{BlockTimingStats, OrphanStats, SyncStatus};
 use crate::p2p;
+use crate::p2p::libp2p_connection::Libp2pStats;
+use crate::p2p::{SeenCacheStats, TxRelayStats};
+use crate::pool::{DandelionStats, StemPoolStatus};
 use grin_core::pow::Difficulty;
 
 /// Server state info collection struct, to be passed around into internals
@@ -55,6 +58,10 @@ pub struct ServerStats {
 	pub chain_stats: ChainStats,
 	/// Header head (may differ from chain head)
 	pub header_stats: ChainStats,
+	/// Orphan blocks pool
+	pub orphan_stats: OrphanStats,
+	/// Per-stage timings of the block acceptance pipeline
+	pub block_timing_stats: BlockTimingStats,
 	/// Whether we're currently syncing
 	pub sync_status: SyncStatus,
 	/// Handle to current stratum server stats
@@ -67,6 +74,10 @@ pub struct ServerStats {
 	pub tx_stats: Option<TxStats>,
 	/// Disk usage in GB
 	pub disk_usage_gb: String,
+	/// libp2p node statistics, none if libp2p node is not running
+	pub libp2p_stats: Option<Libp2pStats>,
+	/// Recently seen blocks and txs, with the duplicates from the p2p and gossip networks
+	pub seen_cache_stats: SeenCacheStats,
 }
 
 /// Chain Statistics
@@ -92,6 +103,10 @@ pub struct TxStats {
 	pub stem_pool_size: usize,
 	/// Number of transaction kernels in the stem pool
 	pub stem_pool_kernels: usize,
+	/// Stem pool entries with their embargo timers
+	pub stem_pool: StemPoolStatus,
+	/// Dandelion routing statistics
+	pub dandelion_stats: DandelionStats,
 }
 /// Struct to return relevant information about stratum workers
 #[derive(Clone, Serialize, Debug)]
@@ -192,6 +207,8 @@ pub struct PeerStats {
 	pub sent_bytes_per_sec: u64,
 	/// Number of bytes we've received from the peer.
 	pub received_bytes_per_sec: u64,
+	/// Counters of the tx messages the peer relayed to us
+	pub tx_relay: TxRelayStats,
 }
 
 impl PartialEq for PeerStats {
@@ -291,6 +308,7 @@ impl PeerStats {
 			last_seen: peer.info.last_seen(),
 			sent_bytes_per_sec: peer.last_min_sent_bytes().unwrap_or(0) / 60,
 			received_bytes_per_sec: peer.last_min_received_bytes().unwrap_or(0) / 60,
+			tx_relay: peer.tx_relay_stats(),
 		}
 	}
 }
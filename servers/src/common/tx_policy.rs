@@ -0,0 +1,82 @@
+// Copyright 2021 The MWC Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Transaction policy plugin running as a local service. The pool posts the
+//! transaction summary to it and expects a `TxPolicyDecision` json back.
+
+use crate::api;
+use crate::pool::{PoolConfig, TxPolicy, TxPolicyDecision, TxPolicySummary};
+use std::sync::mpsc;
+use std::thread;
+use std::time::Duration;
+
+/// Returns the transaction policy plugin configured for the pool, if any
+pub fn init_tx_policy(config: &PoolConfig) -> Option<HttpTxPolicy> {
+	config
+		.tx_policy_url
+		.as_ref()
+		.map(|url| HttpTxPolicy::new(url, Duration::from_millis(config.tx_policy_timeout_ms)))
+}
+
+/// Transaction policy consulted over HTTP. The transaction is rejected if
+/// the plugin doesn't answer in time, so a failing plugin can't be bypassed.
+pub struct HttpTxPolicy {
+	url: String,
+	timeout: Duration,
+}
+
+impl HttpTxPolicy {
+	pub fn new(url: &str, timeout: Duration) -> HttpTxPolicy {
+		HttpTxPolicy {
+			url: url.to_string(),
+			timeout,
+		}
+	}
+
+	/// Url of the plugin
+	pub fn url(&self) -> &str {
+		&self.url
+	}
+}
+
+impl TxPolicy for HttpTxPolicy {
+	fn check(&self, summary: &TxPolicySummary) -> TxPolicyDecision {
+		// The pool can be called from within the api runtime, the blocking client
+		// needs a thread of its own.
+		let (tx, rx) = mpsc::channel();
+		let url = self.url.clone();
+		let summary = summary.clone();
+		let res = thread::Builder::new()
+			.name("tx_policy".to_string())
+			.spawn(move || {
+				let _ = tx.send(api::client::post::<_, TxPolicyDecision>(
+					&url, None, &summary,
+				));
+			});
+		if let Err(e) = res {
+			return TxPolicyDecision::Reject(format!("tx policy thread failed, {}", e));
+		}
+		match rx.recv_timeout(self.timeout) {
+			Ok(Ok(decision)) => decision,
+			Ok(Err(e)) => {
+				warn!("tx policy call to {} failed, {}", self.url, e);
+				TxPolicyDecision::Reject(format!("tx policy unavailable, {}", e))
+			}
+			Err(_) => {
+				warn!("tx policy call to {} timed out", self.url);
+				TxPolicyDecision::Reject("tx policy timed out".to_string())
+			}
+		}
+	}
+}
@@ -17,19 +17,22 @@ use std::convert::From;
 use std::sync::Arc;
 
 use chrono::prelude::Utc;
-use rand::prelude::*;
 
 use crate::api;
 use crate::chain;
-use crate::core::global::ChainTypes;
+use crate::core::core::hash::Hashed;
+use crate::core::global::{ChainTypes, ConsensusOverrides};
 use crate::core::{core, libtx, pow};
+use crate::grin::sandbox;
 use crate::keychain;
 use crate::p2p;
 use crate::pool;
+use crate::pool::dandelion::{DandelionSecret, DANDELION_RELAY_COUNT};
 use crate::pool::types::DandelionConfig;
 use crate::store;
+use crate::util::Mutex;
 use failure::Fail;
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 
 /// Error type wrapping underlying module errors.
 #[derive(Debug, Fail)]
@@ -73,6 +76,12 @@ pub enum Error {
 	/// Configuration error
 	#[fail(display = "Configuration error, {}", _0)]
 	Configuration(String),
+	/// Data dir is used by another live node
+	#[fail(display = "Data dir in use, {}", _0)]
+	DataDirInUse(String),
+	/// Pre-flight check of the host failed
+	#[fail(display = "Pre-flight checks failed:\n{}", _0)]
+	Preflight(String),
 	/// General error
 	#[fail(display = "General error, {}", _0)]
 	General(String),
@@ -182,6 +191,15 @@ pub struct ServerConfig {
 	/// Directory under which the rocksdb stores will be created
 	pub db_root: String,
 
+	/// Key file to encrypt the chain and peer databases and the PMMR files at rest.
+	/// Generated if missing. The databases are not encrypted if not set.
+	pub db_encryption_key_path: Option<String>,
+
+	/// OS keystore entry holding the encryption key, instead of the key file.
+	/// Generated if missing.
+	#[serde(default)]
+	pub db_encryption_keystore_entry: Option<String>,
+
 	/// Network address for the Rest API HTTP server.
 	pub api_http_addr: String,
 
@@ -191,10 +209,46 @@ pub struct ServerConfig {
 	/// Location of secret for basic auth on v2 Foreign API server.
 	pub foreign_api_secret_path: Option<String>,
 
+	/// Append only audit log of the owner API calls, disabled if not set.
+	#[serde(default)]
+	pub api_audit_log_path: Option<String>,
+
+	/// Max API requests per minute from a single IP, the owner API is not limited.
+	/// No limit if not set.
+	#[serde(default)]
+	pub api_rate_limit_rpm: Option<u32>,
+	/// Max heavyweight API requests (txhashset and output queries) per minute from a single IP.
+	/// No limit if not set.
+	#[serde(default)]
+	pub api_heavy_rate_limit_rpm: Option<u32>,
+
+	/// The node is not ready (/v2/health/ready) if it is more than that number of blocks behind
+	/// the peers. Default: 5
+	#[serde(default)]
+	pub health_max_blocks_behind: Option<u64>,
+	/// The node is not ready (/v2/health/ready) with less connected peers. Default: 1
+	#[serde(default)]
+	pub health_min_peers: Option<u32>,
+
+	/// Listen address of the gRPC foreign API, disabled if not set. Requires the node built
+	/// with the grpc feature.
+	#[serde(default)]
+	pub grpc_api_addr: Option<String>,
+
 	/// TLS certificate file
 	pub tls_certificate_file: Option<String>,
 	/// TLS certificate private key file
 	pub tls_certificate_key: Option<String>,
+	/// CA bundle (PEM) of the TLS client certificates, client certificates are not requested if not set
+	#[serde(default)]
+	pub tls_client_ca_file: Option<String>,
+	/// Reject the API connections without a TLS client certificate
+	#[serde(default)]
+	pub tls_client_cert_required: bool,
+	/// SHA256 fingerprints (hex) of the TLS client certificates with the full owner API permission.
+	/// Other client certificates are read-only.
+	#[serde(default)]
+	pub tls_client_owner_certs: Vec<String>,
 
 	/// Setup the server for tests, testnet or mainnet
 	#[serde(default)]
@@ -204,9 +258,18 @@ pub struct ServerConfig {
 	#[serde(default)]
 	pub chain_validation_mode: ChainValidationMode,
 
-	/// Whether this node is a full archival node or a fast-sync, pruned node
+	/// Whether this node is a full archival node or a fast-sync, pruned node.
+	/// Archive nodes sync full blocks from genesis, never compact the
+	/// txhashset and serve historical blocks and outputs to their peers.
+	/// (Default: false)
 	pub archive_mode: Option<bool>,
 
+	/// Low memory mode for the devices with about 1GB of RAM (Raspberry Pi and such).
+	/// The txhashset is validated in small windows, the LMDB map grows in smaller
+	/// steps and the verifier and header caches are disabled.
+	/// (Default: false)
+	pub low_memory_mode: Option<bool>,
+
 	/// Whether to skip the sync timeout on startup
 	/// (To assist testing on solo chains)
 	pub skip_sync_wait: Option<bool>,
@@ -223,6 +286,17 @@ pub struct ServerConfig {
 	/// Set to 0 for now
 	pub header_cache_size: Option<u64>,
 
+	/// Number of peers to download the headers from in parallel during header sync.
+	/// 1 means single peer sync. Parallel sync requires the header cache.
+	/// (Default: 1)
+	pub header_sync_parallel_peers: Option<u32>,
+
+	/// Download the txhashset as segments from all the peers supporting it
+	/// instead of a single zip archive from one peer. Falls back to the zip
+	/// archive when no connected peer serves segments.
+	/// (Default: false)
+	pub pibd_sync: Option<bool>,
+
 	/// Invalid Block hash list
 	/// (Default: none)
 	pub invalid_block_hashes: Option<Vec<String>>,
@@ -248,6 +322,43 @@ pub struct ServerConfig {
 	/// libp2p connection port (will be activated with Tor)
 	pub libp2p_port: Option<u16>,
 
+	/// Serve selected foreign API methods over libp2p request-response protocol.
+	/// Default value: enabled
+	pub libp2p_node_api: Option<bool>,
+
+	/// Publish tx receipts (kernel excesses of accepted transactions) to the libp2p network
+	/// and relay receipts from other nodes. Default value: disabled
+	pub libp2p_tx_receipts: Option<bool>,
+
+	/// Store and relay the wallet mailbox messages, the wallets fetch their messages with
+	/// the foreign API. Default value: enabled
+	pub libp2p_mailbox: Option<bool>,
+
+	/// TCP port for the clearnet libp2p transport. Clearnet transport is disabled if not set.
+	pub libp2p_clearnet_port: Option<u16>,
+
+	/// Public clearnet multiaddress of this node, announced to the libp2p peers
+	pub libp2p_clearnet_address: Option<String>,
+
+	/// Clearnet libp2p seeds, <onion_address>@<multiaddress>
+	pub libp2p_clearnet_seeds: Option<Vec<String>>,
+
+	/// TTL of the libp2p message dedup cache, seconds. Duplicated payloads are dropped during that time.
+	pub libp2p_message_dedup_ttl: Option<i64>,
+
+	/// Size of the gossip message trace (ring buffer). Tracing is disabled if not set or 0.
+	pub libp2p_gossip_trace_size: Option<usize>,
+
+	/// Bandwidth limit for forwarding of the libp2p integrity messages, bytes per second.
+	/// Higher fee messages are forwarded first. No limit if not set or 0.
+	pub libp2p_forward_bandwidth: Option<u64>,
+
+	/// Gossip topics that the node refuses to relay. Can be updated at runtime with the owner API.
+	pub libp2p_relay_deny_topics: Option<Vec<String>>,
+
+	/// Gossip message ID patterns (`*` is a wildcard) that the node refuses to relay.
+	pub libp2p_relay_deny_message_ids: Option<Vec<String>>,
+
 	/// Configuration for the peer-to-peer server
 	pub p2p_config: p2p::P2PConfig,
 
@@ -259,6 +370,18 @@ pub struct ServerConfig {
 	#[serde(default)]
 	pub dandelion_config: pool::DandelionConfig,
 
+	/// Chain configuration (background compaction)
+	#[serde(default)]
+	pub chain_config: chain::ChainConfig,
+
+	/// Consensus parameters of the CustomTesting chain, ignored by the other chain types
+	#[serde(default)]
+	pub consensus_overrides: Option<ConsensusOverrides>,
+
+	/// Trusted txhashset snapshot to start from instead of downloading the txhashset
+	#[serde(default)]
+	pub utxo_snapshot: Option<UtxoSnapshotConfig>,
+
 	/// Configuration for the mining daemon
 	#[serde(default)]
 	pub stratum_mining_config: Option<StratumServerConfig>,
@@ -267,6 +390,10 @@ pub struct ServerConfig {
 	#[serde(default)]
 	pub webhook_config: WebHooksConfig,
 
+	/// Seccomp and landlock hardening of the node process on Linux, disabled if not set
+	#[serde(default)]
+	pub sandbox: Option<SandboxConfig>,
+
 	/// Tor Configuration
 	#[serde(default)]
 	pub tor_config: TorConfig,
@@ -276,20 +403,37 @@ impl Default for ServerConfig {
 	fn default() -> ServerConfig {
 		ServerConfig {
 			db_root: "mwc_chain".to_string(),
+			db_encryption_key_path: None,
+			db_encryption_keystore_entry: None,
 			api_http_addr: "127.0.0.1:3413".to_string(),
 			api_secret_path: Some(".api_secret".to_string()),
 			foreign_api_secret_path: Some(".foreign_api_secret".to_string()),
+			api_audit_log_path: None,
+			api_rate_limit_rpm: None,
+			api_heavy_rate_limit_rpm: None,
+			health_max_blocks_behind: None,
+			health_min_peers: None,
+			grpc_api_addr: None,
 			tls_certificate_file: None,
 			tls_certificate_key: None,
+			tls_client_ca_file: None,
+			tls_client_cert_required: false,
+			tls_client_owner_certs: vec![],
 			p2p_config: p2p::P2PConfig::default(),
 			dandelion_config: pool::DandelionConfig::default(),
+			chain_config: chain::ChainConfig::default(),
+			consensus_overrides: None,
+			utxo_snapshot: None,
 			stratum_mining_config: Some(StratumServerConfig::default()),
 			chain_type: ChainTypes::default(),
 			archive_mode: Some(false),
+			low_memory_mode: Some(false),
 			chain_validation_mode: ChainValidationMode::default(),
 			pool_config: pool::PoolConfig::default(),
 			skip_sync_wait: Some(false),
 			header_cache_size: Some(0),
+			header_sync_parallel_peers: Some(1),
+			pibd_sync: Some(false),
 			invalid_block_hashes: Some(vec![]),
 			duration_sync_short: Some(30),
 			duration_sync_long: Some(50),
@@ -299,7 +443,19 @@ impl Default for ServerConfig {
 			libp2p_enabled: Some(true),
 			libp2p_port: Some(3417),
 			libp2p_topics: None,
+			libp2p_node_api: Some(true),
+			libp2p_tx_receipts: Some(false),
+			libp2p_mailbox: Some(true),
+			libp2p_clearnet_port: None,
+			libp2p_clearnet_address: None,
+			libp2p_clearnet_seeds: None,
+			libp2p_message_dedup_ttl: None,
+			libp2p_gossip_trace_size: None,
+			libp2p_forward_bandwidth: None,
+			libp2p_relay_deny_topics: None,
+			libp2p_relay_deny_message_ids: None,
 			webhook_config: WebHooksConfig::default(),
+			sandbox: None,
 			tor_config: TorConfig::default(),
 		}
 	}
@@ -322,6 +478,17 @@ pub struct StratumServerConfig {
 	/// Minimum difficulty for worker shares
 	pub minimum_share_difficulty: u64,
 
+	/// Rebuild the job before `attempt_time_per_block` expires once the fees of the
+	/// transactions added to the pool since the current job reach this amount.
+	/// Units: nanocoins, 0 disables the fee driven refresh
+	#[serde(default)]
+	pub job_refresh_fee_threshold: u64,
+
+	/// Send the rebuilt jobs at the same height as the changes of the previous job to
+	/// the miners that support it, and don't resend the job if its transactions didn't change.
+	#[serde(default)]
+	pub incremental_jobs: bool,
+
 	/// Base address to the HTTP wallet receiver
 	pub wallet_listener_url: String,
 
@@ -397,6 +564,8 @@ impl Default for StratumServerConfig {
 			burn_reward: false,
 			attempt_time_per_block: 15,
 			minimum_share_difficulty: 1,
+			job_refresh_fee_threshold: 0,
+			incremental_jobs: false,
 			enable_stratum_server: Some(false),
 			stratum_server_addr: Some("127.0.0.1:3416".to_string()),
 			ip_tracking: StratumServerConfig::default_ip_tracking(),
@@ -412,6 +581,45 @@ impl Default for StratumServerConfig {
 	}
 }
 
+/// Trusted txhashset snapshot (zip archive) the node can start from. The node
+/// follows the chain from the snapshot right away and validates its history in
+/// background.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct UtxoSnapshotConfig {
+	/// Path of the txhashset zip archive
+	pub path: String,
+	/// Hash of the block the snapshot is taken at, hex
+	pub block_hash: String,
+	/// Blake2b hash of the zip archive, hex
+	pub archive_hash: String,
+	/// Publisher signature of the hash of (block_hash, archive_hash), compact hex.
+	/// The publisher key must be trusted by the release or pinned by the operator,
+	/// see `utxo_snapshot::PINNED_KEYS_FILE`.
+	pub signature: String,
+}
+
+/// Hardening of the node process on Linux. The filesystem access is restricted
+/// with landlock, the syscalls not needed by a running node (exec, ptrace, mounts,
+/// kernel modules...) are denied with seccomp.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct SandboxConfig {
+	/// Paths the node can read, in addition to the writable ones
+	#[serde(default = "sandbox::default_read_paths")]
+	pub read_paths: Vec<String>,
+	/// Paths the node can write, in addition to its home dir (parent of the db root)
+	#[serde(default)]
+	pub write_paths: Vec<String>,
+}
+
+impl Default for SandboxConfig {
+	fn default() -> SandboxConfig {
+		SandboxConfig {
+			read_paths: sandbox::default_read_paths(),
+			write_paths: vec![],
+		}
+	}
+}
+
 /// Web hooks configuration
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub struct WebHooksConfig {
@@ -423,7 +631,17 @@ pub struct WebHooksConfig {
 	pub block_received_url: Option<String>,
 	/// url to POST block data when a new block is accepted by our node (might be a reorg or a fork)
 	pub block_accepted_url: Option<String>,
-	/// number of worker threads in the tokio runtime
+	/// url to POST transaction data when a transaction is accepted to the txpool
+	pub tx_pool_accepted_url: Option<String>,
+	/// url to POST transaction data and the reason when a transaction is rejected by the pool
+	pub tx_pool_rejected_url: Option<String>,
+	/// url to POST transaction data when a stem transaction is relayed to the next peer
+	pub tx_stem_broadcast_url: Option<String>,
+	/// url to POST transaction data when a transaction is evicted from the txpool
+	pub tx_evicted_url: Option<String>,
+	/// url to POST libp2p peer events: connected, disconnected, rejected message, failed dial
+	pub libp2p_peer_event_url: Option<String>,
+	/// number of the webhook requests running at once, the requests run on the node runtime
 	#[serde(default = "default_nthreads")]
 	pub nthreads: u16,
 	/// timeout in seconds for the http request
@@ -446,6 +664,11 @@ impl Default for WebHooksConfig {
 			header_received_url: None,
 			block_received_url: None,
 			block_accepted_url: None,
+			tx_pool_accepted_url: None,
+			tx_pool_rejected_url: None,
+			tx_stem_broadcast_url: None,
+			tx_evicted_url: None,
+			libp2p_peer_event_url: None,
 			nthreads: default_nthreads(),
 			timeout: default_timeout(),
 		}
@@ -453,16 +676,22 @@ impl Default for WebHooksConfig {
 }
 
 /// A node is either "stem" of "fluff" for the duration of a single epoch.
-/// A node also maintains an outbound relay peer for the epoch.
+/// Following Dandelion++ a node maintains two outbound relay peers for the epoch.
+/// The stem txs of an inbound peer always go to the same relay during the epoch,
+/// our own txs go to the first one.
 #[derive(Debug)]
 pub struct DandelionEpoch {
 	config: DandelionConfig,
 	// When did this epoch start?
 	start_time: Option<i64>,
+	// Secret of this epoch, the stem/fluff decision and the relay mapping are derived from it.
+	secret: DandelionSecret,
 	// Are we in "stem" mode or "fluff" mode for this epoch?
 	is_stem: bool,
-	// Our current Dandelion relay peer (effective for this epoch).
-	relay_peer: Option<Arc<p2p::Peer>>,
+	// Our current Dandelion relay peers (effective for this epoch), by relay slot.
+	relay_peers: Vec<Arc<p2p::Peer>>,
+	// Peers that sent the stem txs being added to the stempool, by kernel hash.
+	stem_sources: HashMap<core::hash::Hash, p2p::PeerAddr>,
 }
 
 impl DandelionEpoch {
@@ -471,8 +700,10 @@ impl DandelionEpoch {
 		DandelionEpoch {
 			config,
 			start_time: None,
+			secret: DandelionSecret::random(),
 			is_stem: true,
-			relay_peer: None,
+			relay_peers: vec![],
+			stem_sources: HashMap::new(),
 		}
 	}
 
@@ -489,21 +720,29 @@ impl DandelionEpoch {
 	}
 
 	/// Transition to next Dandelion epoch.
-	/// Select stem/fluff based on configured stem_probability.
-	/// Choose a new outbound stem relay peer.
+	/// Draw a new epoch secret, select stem/fluff based on configured stem_probability.
+	/// Choose new outbound stem relay peers.
 	pub fn next_epoch(&mut self, peers: &Arc<p2p::Peers>) {
 		self.start_time = Some(Utc::now().timestamp());
-		self.relay_peer = peers.outgoing_connected_peers().first().cloned();
+		self.secret = DandelionSecret::random();
 
 		// If stem_probability == 90 then we stem 90% of the time.
-		let mut rng = rand::thread_rng();
 		let stem_probability = self.config.stem_probability;
-		self.is_stem = rng.gen_range(0, 100) < stem_probability;
+		self.is_stem = self.secret.is_stem(stem_probability);
+
+		let outgoing = Self::relay_candidates(peers);
+		self.relay_peers = self
+			.secret
+			.select(outgoing.len(), DANDELION_RELAY_COUNT)
+			.into_iter()
+			.map(|i| outgoing[i].clone())
+			.collect();
 
-		let addr = self.relay_peer.clone().map(|p| p.info.addr.clone());
 		info!(
-			"DandelionEpoch: next_epoch: is_stem: {} ({}%), relay: {:?}",
-			self.is_stem, stem_probability, addr
+			"DandelionEpoch: next_epoch: is_stem: {} ({}%), relays: {:?}",
+			self.is_stem,
+			stem_probability,
+			self.relay_addrs()
 		);
 	}
 
@@ -517,30 +756,189 @@ impl DandelionEpoch {
 		self.config.always_stem_our_txs
 	}
 
-	/// What is our current relay peer?
-	/// If it is not connected then choose a new one.
-	pub fn relay_peer(&mut self, peers: &Arc<p2p::Peers>) -> Option<Arc<p2p::Peer>> {
-		let mut update_relay = false;
-		if let Some(peer) = &self.relay_peer {
-			if !peer.is_connected() {
-				info!(
-					"DandelionEpoch: relay_peer: {:?} not connected, choosing a new one.",
-					peer.info.addr
-				);
-				update_relay = true;
+	/// Record the peer a stem tx was received from, until the tx is relayed.
+	pub fn set_stem_source(&mut self, kernel_hash: core::hash::Hash, addr: p2p::PeerAddr) {
+		self.stem_sources.insert(kernel_hash, addr);
+	}
+
+	/// Forget the peer a stem tx was received from.
+	pub fn take_stem_source(&mut self, kernel_hash: &core::hash::Hash) -> Option<p2p::PeerAddr> {
+		self.stem_sources.remove(kernel_hash)
+	}
+
+	/// What is our current relay peer for the stem txs received from the source peer
+	/// (None for our own txs)?
+	/// The relays that are not connected any more are replaced.
+	pub fn relay_peer(
+		&mut self,
+		peers: &Arc<p2p::Peers>,
+		source: Option<&p2p::PeerAddr>,
+	) -> Option<Arc<p2p::Peer>> {
+		let connected = self.relay_peers.iter().filter(|p| p.is_connected()).count();
+		if connected < self.relay_peers.len() || connected < DANDELION_RELAY_COUNT {
+			self.replace_relay_peers(peers);
+		}
+
+		let source = source.map(|addr| addr.to_string());
+		let slot = self.secret.relay_slot(
+			source.as_ref().map(|s| s.as_bytes()),
+			self.relay_peers.len(),
+		);
+		self.relay_peers.get(slot).cloned()
+	}
+
+	// Replace the disconnected relays, keeping the slots of the connected ones so the
+	// inbound peers mapped to them keep their relay.
+	fn replace_relay_peers(&mut self, peers: &Arc<p2p::Peers>) {
+		let outgoing: Vec<_> = Self::relay_candidates(peers)
+			.into_iter()
+			.filter(|p| !self.relay_peers.iter().any(|r| r.info.addr == p.info.addr))
+			.collect();
+		let mut candidates = self
+			.secret
+			.select(outgoing.len(), outgoing.len())
+			.into_iter()
+			.map(|i| outgoing[i].clone());
+
+		for relay in self.relay_peers.iter_mut() {
+			if !relay.is_connected() {
+				if let Some(peer) = candidates.next() {
+					info!(
+						"DandelionEpoch: relay_peer: {:?} not connected, replaced by {:?}",
+						relay.info.addr, peer.info.addr
+					);
+					*relay = peer;
+				}
 			}
-		} else {
-			update_relay = true;
 		}
+		self.relay_peers.retain(|p| p.is_connected());
+		while self.relay_peers.len() < DANDELION_RELAY_COUNT {
+			match candidates.next() {
+				Some(peer) => self.relay_peers.push(peer),
+				None => break,
+			}
+		}
+		info!(
+			"DandelionEpoch: relay_peer: relays: {:?}",
+			self.relay_addrs()
+		);
+	}
+
+	// Outbound peers the peer groups allow to relay the txs to.
+	fn relay_candidates(peers: &Arc<p2p::Peers>) -> Vec<Arc<p2p::Peer>> {
+		peers
+			.outgoing_connected_peers()
+			.into_iter()
+			.filter(|p| peers.relays_to(p, true))
+			.collect()
+	}
+
+	fn relay_addrs(&self) -> Vec<p2p::PeerAddr> {
+		self.relay_peers
+			.iter()
+			.map(|p| p.info.addr.clone())
+			.collect()
+	}
+}
+
+#[derive(Debug, Default)]
+struct HeaderSyncPipelineState {
+	// Highest received header (height, hash), might be not validated by the chain yet
+	frontier: Option<(u64, core::hash::Hash)>,
+	// Received headers don't stitch with the frontier
+	inconsistent: bool,
+}
+
+/// State of the parallel header sync that is shared between the net adapter and
+/// the header sync. Next header range is requested after the highest received header,
+/// so the ranges that are requested from different peers don't overlap and download
+/// goes in parallel with the headers validation.
+#[derive(Debug, Default)]
+pub struct HeaderSyncPipeline {
+	state: Mutex<HeaderSyncPipelineState>,
+}
+
+impl HeaderSyncPipeline {
+	/// Create an empty pipeline
+	pub fn new() -> HeaderSyncPipeline {
+		HeaderSyncPipeline::default()
+	}
+
+	/// Restart the pipeline from the header, inconsistency flag is cleared
+	pub fn reset(&self, height: u64, hash: core::hash::Hash) {
+		let mut state = self.state.lock();
+		state.frontier = Some((height, hash));
+		state.inconsistent = false;
+	}
+
+	/// Highest received header, height and hash
+	pub fn frontier(&self) -> Option<(u64, core::hash::Hash)> {
+		self.state.lock().frontier
+	}
+
+	/// True if received headers didn't stitch, parallel sync must not be used
+	pub fn is_inconsistent(&self) -> bool {
+		self.state.lock().inconsistent
+	}
+
+	/// Mark the pipeline as inconsistent, for example when the chain found differing headers
+	pub fn mark_inconsistent(&self) {
+		let mut state = self.state.lock();
+		if !state.inconsistent {
+			warn!("header sync: inconsistent headers, falling back to single peer header sync");
+			state.inconsistent = true;
+		}
+	}
+
+	/// Register received headers. Headers that overlap or continue the frontier must
+	/// stitch with it, otherwise the pipeline is marked as inconsistent and false is returned.
+	pub fn headers_received(&self, bhs: &[core::BlockHeader]) -> bool {
+		let (first, last) = match (bhs.first(), bhs.last()) {
+			(Some(first), Some(last)) => (first, last),
+			_ => return !self.is_inconsistent(),
+		};
+		// Headers are not validated yet, a batch that is not contiguous is rejected
+		// without touching the pipeline, the chain rejects it as well.
+		if bhs.windows(2).any(|w| w[1].height != w[0].height + 1) {
+			return false;
+		}
+
+		let (height, hash) = {
+			let state = self.state.lock();
+			if state.inconsistent {
+				return false;
+			}
+			match state.frontier {
+				Some(frontier) => frontier,
+				None => return true,
+			}
+		};
 
-		if update_relay {
-			self.relay_peer = peers.outgoing_connected_peers().first().cloned();
-			info!(
-				"DandelionEpoch: relay_peer: new peer chosen: {:?}",
-				self.relay_peer.clone().map(|p| p.info.addr.clone())
-			);
+		if first.height > height + 1 {
+			// Not connected to the frontier, response for some other request
+			return true;
 		}
 
-		self.relay_peer.clone()
+		let stitched = if first.height == height + 1 {
+			first.prev_hash == hash
+		} else if last.height >= height {
+			match bhs.get((height - first.height) as usize) {
+				Some(header) => header.height == height && header.hash() == hash,
+				None => return false,
+			}
+		} else {
+			// Below the frontier, nothing to check
+			true
+		};
+
+		if !stitched {
+			self.mark_inconsistent();
+			return false;
+		}
+
+		if last.height > height {
+			self.state.lock().frontier = Some((last.height, last.hash()));
+		}
+		true
 	}
 }
@@ -14,7 +14,12 @@
 
 //! Grin P2P / API server
 
+pub mod compactor;
 pub mod dandelion_monitor;
+pub mod heartbeat;
+pub mod preflight;
+pub mod sandbox;
 pub mod seed;
 pub mod server;
 pub mod sync;
+pub mod utxo_snapshot;
@@ -0,0 +1,78 @@
+// Copyright 2021 The MWC Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::chain::{self, ChainConfig, CompactionState, SyncState};
+use crate::util::StopState;
+
+/// A process compacting the chain in background. At startup and then every compaction
+/// interval the compaction runs in chunks of limited duration, pausing for the same time
+/// after every chunk, so the block processing is never stalled for long.
+/// Compaction is not started while we are syncing.
+pub fn start_compactor(
+	chain_config: ChainConfig,
+	chain: Arc<chain::Chain>,
+	sync_state: Arc<SyncState>,
+	stop_state: Arc<StopState>,
+) -> std::io::Result<thread::JoinHandle<()>> {
+	debug!("Started chain compactor.");
+
+	thread::Builder::new()
+		.name("compactor".to_string())
+		.spawn(move || {
+			let run_interval = Duration::from_secs(chain_config.compaction_interval_secs);
+			let max_chunk = Duration::from_millis(chain_config.compaction_max_chunk_ms);
+			let mut last_run = Instant::now();
+			// The first run is at startup, it is skipped if the chain was compacted recently
+			let mut compaction: Option<CompactionState> = Some(CompactionState::default());
+			loop {
+				// Halt compactor if we have been notified that we are stopping.
+				if stop_state.is_stopped() {
+					break;
+				}
+
+				if sync_state.is_syncing() {
+					thread::sleep(Duration::from_secs(1));
+					continue;
+				}
+
+				match compaction.as_mut() {
+					None => {
+						if last_run.elapsed() >= run_interval {
+							compaction = Some(CompactionState::default());
+						} else {
+							thread::sleep(Duration::from_secs(1));
+						}
+					}
+					Some(state) => match chain.compact_chunk(state, max_chunk) {
+						Ok(true) => {
+							debug!("compactor: chain compaction is done");
+							compaction = None;
+							last_run = Instant::now();
+						}
+						// Let the block processing go on before the next chunk.
+						Ok(false) => thread::sleep(max_chunk),
+						Err(e) => {
+							error!("compactor: could not compact chain: {:?}", e);
+							compaction = None;
+							last_run = Instant::now();
+						}
+					},
+				}
+			}
+		})
+}
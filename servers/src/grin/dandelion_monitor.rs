@@ -13,7 +13,6 @@
 // limitations under the License.
 
 use chrono::prelude::Utc;
-use rand::{thread_rng, Rng};
 use std::sync::Arc;
 use std::thread;
 use std::time::{Duration, Instant};
@@ -33,7 +32,8 @@ use crate::{ServerTxPool, ServerVerifierCache};
 /// each transaction sent in stem phase. This function will monitor the
 /// stempool and test if the timer is expired for each transaction. In that case
 /// the transaction will be sent in fluff phase (to multiple peers) instead of
-/// sending only to the peer relay.
+/// sending only to the peer relay. Each transaction gets its own random embargo
+/// timer.
 pub fn monitor_transactions(
 	dandelion_config: DandelionConfig,
 	tx_pool: ServerTxPool,
@@ -70,7 +70,7 @@ pub fn monitor_transactions(
 					}
 
 					// Now find all expired entries based on embargo timer.
-					let _ = process_expired_entries(&dandelion_config, &tx_pool).map_err(|e| {
+					let _ = process_expired_entries(&tx_pool).map_err(|e| {
 						error!("dand_mon: Problem processing expired entries. {}", e);
 					});
 
@@ -97,7 +97,7 @@ where
 	V: VerifierCache,
 {
 	let cutoff = Utc::now().timestamp() - cutoff_secs as i64;
-	pool.entries
+	pool.entries()
 		.iter()
 		.filter(|x| x.tx_at.timestamp() < cutoff)
 		.cloned()
@@ -113,7 +113,7 @@ fn process_fluff_phase(
 	// Take a write lock on the txpool for the duration of this processing.
 	let mut tx_pool = tx_pool.write();
 
-	let all_entries = tx_pool.stempool.entries.clone();
+	let all_entries = tx_pool.stempool.entries().to_vec();
 	if all_entries.is_empty() {
 		return Ok(());
 	}
@@ -156,15 +156,18 @@ fn process_fluff_phase(
 	Ok(())
 }
 
-fn process_expired_entries(
-	dandelion_config: &DandelionConfig,
-	tx_pool: &ServerTxPool,
-) -> Result<(), PoolError> {
+fn process_expired_entries(tx_pool: &ServerTxPool) -> Result<(), PoolError> {
 	// Take a write lock on the txpool for the duration of this processing.
 	let mut tx_pool = tx_pool.write();
 
-	let embargo_secs = dandelion_config.embargo_secs + thread_rng().gen_range(0, 31);
-	let expired_entries = select_txs_cutoff(&tx_pool.stempool, embargo_secs);
+	let now = Utc::now().timestamp();
+	let expired_entries: Vec<_> = tx_pool
+		.stempool
+		.entries()
+		.iter()
+		.filter(|x| tx_pool.embargo_expiry(x) < now)
+		.cloned()
+		.collect();
 
 	if expired_entries.is_empty() {
 		return Ok(());
@@ -0,0 +1,259 @@
+// Copyright 2021 The MWC Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Heartbeat of the node using a data dir. The lock file only stops a second node
+//! on the same host, file locks are not reliable on the network filesystems and
+//! shared container volumes. A live node keeps its heartbeat file fresh, so
+//! another node can detect it and refuse to open the same LMDB.
+
+use crate::common::types::Error;
+use crate::util::StopState;
+use chrono::Utc;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Heartbeat file name, in the db root
+pub const HEARTBEAT_FILE: &str = "mwc.heartbeat";
+
+/// How often the heartbeat is updated
+const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Heartbeat older than this (in seconds) is left by a dead node
+const HEARTBEAT_EXPIRATION: i64 = 30;
+
+/// Content of the heartbeat file.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Heartbeat {
+	/// Process id of the node
+	pub pid: u32,
+	/// Random id of the node run, pids are not unique across the hosts
+	pub instance: u64,
+	/// Host name of the node, the pid can be checked on the same host only
+	#[serde(default)]
+	pub hostname: String,
+	/// Last update, unix timestamp
+	pub updated: i64,
+}
+
+fn heartbeat_path(db_root: &str) -> PathBuf {
+	Path::new(db_root).join(HEARTBEAT_FILE)
+}
+
+/// Read the heartbeat left in the data dir, if any.
+pub fn read_heartbeat(db_root: &str) -> Option<Heartbeat> {
+	let data = fs::read_to_string(heartbeat_path(db_root)).ok()?;
+	serde_json::from_str(&data).ok()
+}
+
+/// Name of this host, empty if it can't be read.
+#[cfg(unix)]
+pub fn hostname() -> String {
+	let mut buf = [0u8; 256];
+	if unsafe { libc::gethostname(buf.as_mut_ptr() as *mut libc::c_char, buf.len()) } != 0 {
+		return String::new();
+	}
+	let len = buf.iter().position(|b| *b == 0).unwrap_or(buf.len());
+	String::from_utf8_lossy(&buf[..len]).to_string()
+}
+
+/// Name of this host, empty if it can't be read.
+#[cfg(not(unix))]
+pub fn hostname() -> String {
+	std::env::var("COMPUTERNAME").unwrap_or_default()
+}
+
+/// Check if the process with this pid is running on this host.
+#[cfg(unix)]
+fn is_process_alive(pid: u32) -> bool {
+	// Zero and negative pids are the process groups
+	if pid == 0 || pid > i32::MAX as u32 {
+		return false;
+	}
+	if unsafe { libc::kill(pid as libc::pid_t, 0) } == 0 {
+		return true;
+	}
+	// The process exists, but belongs to another user
+	io::Error::last_os_error().raw_os_error() == Some(libc::EPERM)
+}
+
+/// Check if the process with this pid is running on this host. Unknown, so it is
+/// considered alive and the heartbeat decides.
+#[cfg(not(unix))]
+fn is_process_alive(_pid: u32) -> bool {
+	true
+}
+
+fn write_heartbeat(db_root: &str, heartbeat: &Heartbeat) -> io::Result<()> {
+	let path = heartbeat_path(db_root);
+	let tmp_path = path.with_extension("tmp");
+	let data =
+		serde_json::to_string(heartbeat).map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
+	fs::write(&tmp_path, data)?;
+	fs::rename(&tmp_path, &path)
+}
+
+/// Check no other live node is using the data dir. With `force_takeover` a fresh
+/// heartbeat is watched for a while, the data dir is taken over only if it isn't
+/// updated any more, i.e. the node that left it is dead.
+pub fn check_data_dir(db_root: &str, force_takeover: bool) -> Result<(), Error> {
+	let heartbeat = match read_heartbeat(db_root) {
+		Some(heartbeat) => heartbeat,
+		None => return Ok(()),
+	};
+	let age = Utc::now().timestamp().saturating_sub(heartbeat.updated);
+	if age > HEARTBEAT_EXPIRATION {
+		return Ok(());
+	}
+	// On the same host the heartbeat of a crashed node is recognized by its pid. Our own
+	// pid is left by the previous run, in the containers the node often gets the same pid.
+	if !heartbeat.hostname.is_empty() && heartbeat.hostname == hostname() {
+		if heartbeat.pid == std::process::id() || !is_process_alive(heartbeat.pid) {
+			info!(
+				"Data dir {} has a fresh heartbeat of pid {} that is not running any more",
+				db_root, heartbeat.pid
+			);
+			return Ok(());
+		}
+	}
+
+	let in_use = Error::DataDirInUse(format!(
+		"{} is used by a running node (pid {} at {}, last heartbeat {}s ago). \
+		 Stop that node, or start with --force-takeover if you are sure it is dead.",
+		db_root, heartbeat.pid, heartbeat.hostname, age
+	));
+	if !force_takeover {
+		return Err(in_use);
+	}
+
+	warn!(
+		"Data dir {} has a fresh heartbeat of pid {}, checking that the node is dead",
+		db_root, heartbeat.pid
+	);
+	thread::sleep(HEARTBEAT_INTERVAL * 2 + Duration::from_secs(1));
+	if read_heartbeat(db_root).as_ref() != Some(&heartbeat) {
+		return Err(in_use);
+	}
+	warn!(
+		"Taking over data dir {} from pid {}",
+		db_root, heartbeat.pid
+	);
+	Ok(())
+}
+
+/// Write the heartbeat of this node and keep updating it until the node is stopped.
+/// The heartbeat file is removed on stop.
+pub fn start_heartbeat(
+	db_root: String,
+	stop_state: Arc<StopState>,
+) -> io::Result<thread::JoinHandle<()>> {
+	let mut heartbeat = Heartbeat {
+		pid: std::process::id(),
+		instance: rand::random(),
+		hostname: hostname(),
+		updated: Utc::now().timestamp(),
+	};
+	write_heartbeat(&db_root, &heartbeat)?;
+
+	thread::Builder::new()
+		.name("heartbeat".to_string())
+		.spawn(move || {
+			let mut last_update = Instant::now();
+			while !stop_state.is_stopped() {
+				thread::sleep(Duration::from_secs(1));
+				if last_update.elapsed() < HEARTBEAT_INTERVAL {
+					continue;
+				}
+				heartbeat.updated = Utc::now().timestamp();
+				if let Err(e) = write_heartbeat(&db_root, &heartbeat) {
+					error!("heartbeat: failed to update {}, {}", HEARTBEAT_FILE, e);
+				}
+				last_update = Instant::now();
+			}
+			let _ = fs::remove_file(heartbeat_path(&db_root));
+		})
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_check_data_dir() {
+		let db_root = "target/test_output/heartbeat";
+		let _ = fs::remove_dir_all(db_root);
+		fs::create_dir_all(db_root).unwrap();
+
+		// No heartbeat, the dir is free.
+		assert!(check_data_dir(db_root, false).is_ok());
+
+		// Fresh heartbeat of another node.
+		let mut heartbeat = Heartbeat {
+			pid: 1,
+			instance: 2,
+			hostname: "another_host".to_string(),
+			updated: Utc::now().timestamp(),
+		};
+		write_heartbeat(db_root, &heartbeat).unwrap();
+		assert_eq!(read_heartbeat(db_root), Some(heartbeat.clone()));
+		match check_data_dir(db_root, false) {
+			Err(Error::DataDirInUse(_)) => {}
+			res => panic!("unexpected {:?}", res),
+		}
+
+		// Expired heartbeat, the node is dead.
+		heartbeat.updated -= HEARTBEAT_EXPIRATION + 1;
+		write_heartbeat(db_root, &heartbeat).unwrap();
+		assert!(check_data_dir(db_root, false).is_ok());
+
+		// Timestamp far in the past doesn't overflow
+		heartbeat.updated = i64::MIN;
+		write_heartbeat(db_root, &heartbeat).unwrap();
+		assert!(check_data_dir(db_root, false).is_ok());
+
+		// Fresh heartbeat on this host, the pid decides
+		#[cfg(unix)]
+		{
+			heartbeat.hostname = hostname();
+			heartbeat.updated = Utc::now().timestamp();
+			// Left by the previous run of this process
+			heartbeat.pid = std::process::id();
+			write_heartbeat(db_root, &heartbeat).unwrap();
+			assert!(check_data_dir(db_root, false).is_ok());
+			// Running node
+			heartbeat.pid = std::os::unix::process::parent_id();
+			write_heartbeat(db_root, &heartbeat).unwrap();
+			assert!(check_data_dir(db_root, false).is_err());
+			// Crashed node
+			let mut child = std::process::Command::new("true").spawn().unwrap();
+			heartbeat.pid = child.id();
+			child.wait().unwrap();
+			write_heartbeat(db_root, &heartbeat).unwrap();
+			assert!(check_data_dir(db_root, false).is_ok());
+		}
+
+		// Our own heartbeat is removed on stop.
+		let stop_state = Arc::new(StopState::new());
+		let handle = start_heartbeat(db_root.to_string(), stop_state.clone()).unwrap();
+		assert_eq!(read_heartbeat(db_root).unwrap().pid, std::process::id());
+		stop_state.stop();
+		handle.join().unwrap();
+		assert!(read_heartbeat(db_root).is_none());
+
+		let _ = fs::remove_dir_all(db_root);
+	}
+}
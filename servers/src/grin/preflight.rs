@@ -0,0 +1,183 @@
+// Copyright 2021 The MWC Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Pre-flight checks of the host, run before the server is started. A node with
+//! a wrong clock, a full disk or too few file descriptors starts fine and then
+//! misbehaves (rejected blocks, corrupted db, dropped peers), so it is better to
+//! refuse to start with an error explaining what to fix.
+
+use crate::common::types::{Error, ServerConfig};
+use crate::core::global;
+use chrono::{TimeZone, Utc};
+use std::fs;
+use std::path::Path;
+
+/// Allowed skew of the clock before the build time, seconds
+const CLOCK_BUILD_TIME_SLACK: i64 = 24 * 3600;
+
+/// Minimum free disk space in the data dir for the production chains, bytes
+const MIN_FREE_DISK_SPACE: u64 = 2 * 1024 * 1024 * 1024;
+
+/// Minimum free disk space in the data dir for the test chains, bytes
+const MIN_FREE_DISK_SPACE_TESTING: u64 = 100 * 1024 * 1024;
+
+/// File descriptors used besides the peer connections: LMDB, PMMR files,
+/// API and stratum connections, logs.
+const RESERVED_FILE_DESCRIPTORS: u64 = 256;
+
+const WRITE_TEST_FILE: &str = "mwc.preflight";
+
+/// Run all the pre-flight checks. `build_time` is the unix timestamp the binary was
+/// built at, if known. All the failed checks are reported together.
+pub fn run_preflight_checks(config: &ServerConfig, build_time: Option<i64>) -> Result<(), Error> {
+	let min_free_space = if global::is_production_mode() {
+		MIN_FREE_DISK_SPACE
+	} else {
+		MIN_FREE_DISK_SPACE_TESTING
+	};
+	let p2p = &config.p2p_config;
+	let required_fds = p2p.peer_max_inbound_count() as u64
+		+ p2p.peer_max_outbound_count() as u64
+		+ RESERVED_FILE_DESCRIPTORS;
+
+	let failures: Vec<String> = vec![
+		build_time.map_or(Ok(()), |t| check_clock(Utc::now().timestamp(), t)),
+		check_data_dir_writable(&config.db_root),
+		check_free_disk_space(&config.db_root, min_free_space),
+		check_file_descriptors(required_fds),
+	]
+	.into_iter()
+	.filter_map(|res| res.err())
+	.collect();
+
+	if failures.is_empty() {
+		return Ok(());
+	}
+	Err(Error::Preflight(format!(
+		"{}\nFix the issues above, or start with --skip-preflight to ignore them.",
+		failures.join("\n")
+	)))
+}
+
+/// The clock must not be behind the build time of the binary.
+pub fn check_clock(now: i64, build_time: i64) -> Result<(), String> {
+	if now + CLOCK_BUILD_TIME_SLACK < build_time {
+		return Err(format!(
+			"System clock ({}) is behind the build time of this binary ({}). \
+			 Set the correct time, preferably with NTP.",
+			Utc.timestamp(now, 0).to_rfc2822(),
+			Utc.timestamp(build_time, 0).to_rfc2822()
+		));
+	}
+	Ok(())
+}
+
+/// The data dir must be writable, it is created if missing.
+pub fn check_data_dir_writable(db_root: &str) -> Result<(), String> {
+	let path = Path::new(db_root);
+	let test_file = path.join(WRITE_TEST_FILE);
+	fs::create_dir_all(path)
+		.and_then(|_| fs::write(&test_file, b"mwc"))
+		.and_then(|_| fs::remove_file(&test_file))
+		.map_err(|e| {
+			format!(
+				"Data dir {} is not writable, {}. Check the permissions of the dir \
+				 or set another db_root in the config.",
+				db_root, e
+			)
+		})
+}
+
+/// The disk of the data dir must have at least `min_free` bytes available.
+pub fn check_free_disk_space(db_root: &str, min_free: u64) -> Result<(), String> {
+	// The data dir may not exist yet, check the nearest existing parent
+	let path = Path::new(db_root)
+		.ancestors()
+		.find(|p| p.exists())
+		.unwrap_or_else(|| Path::new("."));
+	let available = fs2::available_space(path).map_err(|e| {
+		format!(
+			"Unable to read the free disk space of {}, {}",
+			path.display(),
+			e
+		)
+	})?;
+	if available < min_free {
+		return Err(format!(
+			"Only {} MB of disk space is available for the data dir {}, at least {} MB \
+			 is required. Free some space or set db_root to a larger disk.",
+			available / 1024 / 1024,
+			db_root,
+			min_free / 1024 / 1024
+		));
+	}
+	Ok(())
+}
+
+/// The open files limit must be enough for the configured peers.
+#[cfg(unix)]
+pub fn check_file_descriptors(required: u64) -> Result<(), String> {
+	let mut limit = libc::rlimit {
+		rlim_cur: 0,
+		rlim_max: 0,
+	};
+	if unsafe { libc::getrlimit(libc::RLIMIT_NOFILE, &mut limit) } != 0 {
+		return Err(format!(
+			"Unable to read the open files limit, {}",
+			std::io::Error::last_os_error()
+		));
+	}
+	if limit.rlim_cur != libc::RLIM_INFINITY && (limit.rlim_cur as u64) < required {
+		return Err(format!(
+			"Open files limit is {}, at least {} is required for the configured peers. \
+			 Raise it with 'ulimit -n {}' (or LimitNOFILE for systemd), or lower \
+			 peer_max_inbound_count and peer_max_outbound_count in the config.",
+			limit.rlim_cur, required, required
+		));
+	}
+	Ok(())
+}
+
+/// The open files limit must be enough for the configured peers.
+#[cfg(not(unix))]
+pub fn check_file_descriptors(_required: u64) -> Result<(), String> {
+	Ok(())
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+
+	#[test]
+	fn test_preflight_checks() {
+		let db_root = "target/test_output/preflight";
+		let _ = fs::remove_dir_all(db_root);
+
+		let build_time = 1_600_000_000;
+		assert!(check_clock(build_time, build_time).is_ok());
+		assert!(check_clock(build_time - 3600, build_time).is_ok());
+		assert!(check_clock(build_time - 2 * CLOCK_BUILD_TIME_SLACK, build_time).is_err());
+
+		assert!(check_data_dir_writable(db_root).is_ok());
+		assert!(Path::new(db_root).exists());
+		assert!(!Path::new(db_root).join(WRITE_TEST_FILE).exists());
+
+		assert!(check_free_disk_space(db_root, 0).is_ok());
+		assert!(check_free_disk_space(db_root, u64::MAX).is_err());
+
+		assert!(check_file_descriptors(1).is_ok());
+
+		let _ = fs::remove_dir_all(db_root);
+	}
+}
@@ -0,0 +1,310 @@
+// Copyright 2021 The MWC Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Optional hardening of the node process on Linux, for the operators running
+//! public-facing nodes.
+//!
+//! * Landlock restricts the filesystem access to the configured paths. A landlock
+//!   domain covers the thread that applies it and the threads (and processes) it
+//!   starts afterwards, so it is applied before the server starts its threads.
+//!   The threads started before the server (the logger and, in the TUI mode, the
+//!   UI) are not covered and keep the unrestricted filesystem access.
+//! * Seccomp denies the syscalls a node never needs once initialized: exec (the
+//!   tor process is started by then), ptrace, mounts, kernel modules, bpf etc.
+//!   The filter is synchronized to all the threads of the process.
+
+use crate::common::types::{Error, SandboxConfig};
+use std::path::Path;
+
+/// Paths the node needs to read on a typical Linux host.
+pub fn default_read_paths() -> Vec<String> {
+	[
+		"/etc", "/usr", "/lib", "/lib64", "/bin", "/proc", "/sys", "/dev",
+	]
+	.iter()
+	.map(|p| p.to_string())
+	.collect()
+}
+
+/// Restrict the filesystem access of the process to the configured paths. The node
+/// home dir (parent of the db root) is always writable.
+pub fn apply_landlock(config: &SandboxConfig, db_root: &str) -> Result<(), Error> {
+	let home = Path::new(db_root)
+		.parent()
+		.filter(|p| !p.as_os_str().is_empty())
+		.unwrap_or_else(|| Path::new(db_root));
+	let mut write_paths = vec![home.to_string_lossy().into_owned()];
+	write_paths.extend(config.write_paths.iter().cloned());
+	imp::apply_landlock(&config.read_paths, &write_paths)?;
+	info!(
+		"Sandbox: filesystem restricted, read {:?}, write {:?}",
+		config.read_paths, write_paths
+	);
+	Ok(())
+}
+
+/// Deny the syscalls the node doesn't need after the initialization, in all the threads.
+pub fn apply_seccomp() -> Result<(), Error> {
+	imp::apply_seccomp()?;
+	info!("Sandbox: seccomp filter applied");
+	Ok(())
+}
+
+#[cfg(all(
+	target_os = "linux",
+	any(target_arch = "x86_64", target_arch = "aarch64")
+))]
+mod imp {
+	use crate::common::types::Error;
+	use std::ffi::CString;
+	use std::io;
+	use std::mem::size_of;
+
+	const SYS_LANDLOCK_CREATE_RULESET: libc::c_long = 444;
+	const SYS_LANDLOCK_ADD_RULE: libc::c_long = 445;
+	const SYS_LANDLOCK_RESTRICT_SELF: libc::c_long = 446;
+	const LANDLOCK_CREATE_RULESET_VERSION: u32 = 1;
+	const LANDLOCK_RULE_PATH_BENEATH: u32 = 1;
+
+	// Filesystem access rights of the landlock ABI v1
+	const ACCESS_FS_EXECUTE: u64 = 1 << 0;
+	const ACCESS_FS_READ_FILE: u64 = 1 << 2;
+	const ACCESS_FS_READ_DIR: u64 = 1 << 3;
+	const ACCESS_FS_ALL: u64 = (1 << 13) - 1;
+	const ACCESS_FS_READ: u64 = ACCESS_FS_EXECUTE | ACCESS_FS_READ_FILE | ACCESS_FS_READ_DIR;
+
+	#[repr(C)]
+	struct LandlockRulesetAttr {
+		handled_access_fs: u64,
+	}
+
+	#[repr(C, packed)]
+	struct LandlockPathBeneathAttr {
+		allowed_access: u64,
+		parent_fd: i32,
+	}
+
+	const SECCOMP_SET_MODE_FILTER: libc::c_ulong = 1;
+	const SECCOMP_FILTER_FLAG_TSYNC: libc::c_ulong = 1;
+	const SECCOMP_RET_ALLOW: u32 = 0x7fff_0000;
+	const SECCOMP_RET_ERRNO: u32 = 0x0005_0000;
+	const SECCOMP_RET_KILL_PROCESS: u32 = 0x8000_0000;
+	const BPF_LD_W_ABS: u16 = 0x20;
+	const BPF_JEQ_K: u16 = 0x15;
+	const BPF_JGE_K: u16 = 0x35;
+	const BPF_RET_K: u16 = 0x06;
+	// Offsets in struct seccomp_data
+	const SECCOMP_DATA_NR: u32 = 0;
+	const SECCOMP_DATA_ARCH: u32 = 4;
+	// x32 syscalls come with the x86_64 arch and this bit set in the syscall number
+	const X32_SYSCALL_BIT: u32 = 0x4000_0000;
+
+	#[cfg(target_arch = "x86_64")]
+	const AUDIT_ARCH: u32 = 0xc000_003e;
+	#[cfg(target_arch = "aarch64")]
+	const AUDIT_ARCH: u32 = 0xc000_00b7;
+
+	/// Syscalls a running node never needs.
+	const DENIED_SYSCALLS: &[libc::c_long] = &[
+		libc::SYS_execve,
+		libc::SYS_execveat,
+		libc::SYS_ptrace,
+		libc::SYS_process_vm_readv,
+		libc::SYS_process_vm_writev,
+		libc::SYS_mount,
+		libc::SYS_umount2,
+		libc::SYS_pivot_root,
+		libc::SYS_chroot,
+		libc::SYS_setns,
+		libc::SYS_unshare,
+		libc::SYS_kexec_load,
+		libc::SYS_kexec_file_load,
+		libc::SYS_init_module,
+		libc::SYS_finit_module,
+		libc::SYS_delete_module,
+		libc::SYS_bpf,
+		libc::SYS_perf_event_open,
+		libc::SYS_userfaultfd,
+		libc::SYS_keyctl,
+		libc::SYS_add_key,
+		libc::SYS_request_key,
+		libc::SYS_reboot,
+		libc::SYS_swapon,
+		libc::SYS_swapoff,
+		libc::SYS_acct,
+		libc::SYS_personality,
+	];
+
+	fn os_error(what: &str) -> Error {
+		Error::General(format!("{}, {}", what, io::Error::last_os_error()))
+	}
+
+	fn no_new_privs() -> Result<(), Error> {
+		if unsafe { libc::prctl(libc::PR_SET_NO_NEW_PRIVS, 1, 0, 0, 0) } != 0 {
+			return Err(os_error("Unable to set no_new_privs"));
+		}
+		Ok(())
+	}
+
+	fn add_path_rule(ruleset_fd: i32, path: &str, access: u64) -> Result<(), Error> {
+		let c_path = CString::new(path)
+			.map_err(|_| Error::Configuration(format!("Invalid sandbox path {}", path)))?;
+		let fd = unsafe { libc::open(c_path.as_ptr(), libc::O_PATH | libc::O_CLOEXEC) };
+		if fd < 0 {
+			// Missing system dirs (e.g. /lib64) are fine
+			warn!(
+				"Sandbox: skipping path {}, {}",
+				path,
+				io::Error::last_os_error()
+			);
+			return Ok(());
+		}
+		let attr = LandlockPathBeneathAttr {
+			allowed_access: access,
+			parent_fd: fd,
+		};
+		let res = unsafe {
+			libc::syscall(
+				SYS_LANDLOCK_ADD_RULE,
+				ruleset_fd,
+				LANDLOCK_RULE_PATH_BENEATH,
+				&attr as *const LandlockPathBeneathAttr,
+				0,
+			)
+		};
+		let err = os_error(&format!("Unable to add the landlock rule for {}", path));
+		unsafe { libc::close(fd) };
+		if res != 0 {
+			return Err(err);
+		}
+		Ok(())
+	}
+
+	pub fn apply_landlock(read_paths: &[String], write_paths: &[String]) -> Result<(), Error> {
+		let abi = unsafe {
+			libc::syscall(
+				SYS_LANDLOCK_CREATE_RULESET,
+				std::ptr::null::<LandlockRulesetAttr>(),
+				0,
+				LANDLOCK_CREATE_RULESET_VERSION,
+			)
+		};
+		if abi < 1 {
+			return Err(os_error(
+				"Landlock is not supported by the kernel (Linux 5.13+ with landlock enabled)",
+			));
+		}
+
+		let attr = LandlockRulesetAttr {
+			handled_access_fs: ACCESS_FS_ALL,
+		};
+		let ruleset_fd = unsafe {
+			libc::syscall(
+				SYS_LANDLOCK_CREATE_RULESET,
+				&attr as *const LandlockRulesetAttr,
+				size_of::<LandlockRulesetAttr>(),
+				0,
+			)
+		} as i32;
+		if ruleset_fd < 0 {
+			return Err(os_error("Unable to create the landlock ruleset"));
+		}
+
+		let res = read_paths
+			.iter()
+			.map(|p| (p, ACCESS_FS_READ))
+			.chain(write_paths.iter().map(|p| (p, ACCESS_FS_ALL)))
+			.try_for_each(|(path, access)| add_path_rule(ruleset_fd, path, access))
+			.and_then(|_| no_new_privs())
+			.and_then(|_| {
+				if unsafe { libc::syscall(SYS_LANDLOCK_RESTRICT_SELF, ruleset_fd, 0) } != 0 {
+					return Err(os_error("Unable to apply the landlock ruleset"));
+				}
+				Ok(())
+			});
+		unsafe { libc::close(ruleset_fd) };
+		res
+	}
+
+	fn stmt(code: u16, k: u32) -> libc::sock_filter {
+		libc::sock_filter {
+			code,
+			jt: 0,
+			jf: 0,
+			k,
+		}
+	}
+
+	fn jump(code: u16, k: u32, jt: u8, jf: u8) -> libc::sock_filter {
+		libc::sock_filter { code, jt, jf, k }
+	}
+
+	pub fn apply_seccomp() -> Result<(), Error> {
+		let mut filter = vec![
+			// Kill the process on the syscalls of a foreign arch (32 bit ABIs) and on the
+			// x32 syscalls, their numbers don't match the deny list.
+			stmt(BPF_LD_W_ABS, SECCOMP_DATA_ARCH),
+			jump(BPF_JEQ_K, AUDIT_ARCH, 1, 0),
+			stmt(BPF_RET_K, SECCOMP_RET_KILL_PROCESS),
+			stmt(BPF_LD_W_ABS, SECCOMP_DATA_NR),
+			jump(BPF_JGE_K, X32_SYSCALL_BIT, 0, 1),
+			stmt(BPF_RET_K, SECCOMP_RET_KILL_PROCESS),
+		];
+		for nr in DENIED_SYSCALLS {
+			filter.push(jump(BPF_JEQ_K, *nr as u32, 0, 1));
+			filter.push(stmt(BPF_RET_K, SECCOMP_RET_ERRNO | libc::EPERM as u32));
+		}
+		filter.push(stmt(BPF_RET_K, SECCOMP_RET_ALLOW));
+
+		let prog = libc::sock_fprog {
+			len: filter.len() as u16,
+			filter: filter.as_mut_ptr(),
+		};
+		no_new_privs()?;
+		let res = unsafe {
+			libc::syscall(
+				libc::SYS_seccomp,
+				SECCOMP_SET_MODE_FILTER,
+				SECCOMP_FILTER_FLAG_TSYNC,
+				&prog as *const libc::sock_fprog,
+			)
+		};
+		if res != 0 {
+			return Err(os_error("Unable to apply the seccomp filter"));
+		}
+		Ok(())
+	}
+}
+
+#[cfg(not(all(
+	target_os = "linux",
+	any(target_arch = "x86_64", target_arch = "aarch64")
+)))]
+mod imp {
+	use crate::common::types::Error;
+
+	fn unsupported() -> Result<(), Error> {
+		Err(Error::Configuration(
+			"Sandbox is supported on Linux x86_64 and aarch64 only".to_owned(),
+		))
+	}
+
+	pub fn apply_landlock(_read_paths: &[String], _write_paths: &[String]) -> Result<(), Error> {
+		unsupported()
+	}
+
+	pub fn apply_seccomp() -> Result<(), Error> {
+		unsupported()
+	}
+}
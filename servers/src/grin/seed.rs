@@ -26,6 +26,7 @@ use std::collections::HashMap;
 use std::net::ToSocketAddrs;
 use std::sync::{mpsc, Arc};
 use std::{cmp, str, thread, time};
+use sysinfo::{ProcessorExt, System, SystemExt};
 
 use crate::core::global;
 use crate::core::global::{FLOONET_DNS_SEEDS, MAINNET_DNS_SEEDS};
@@ -39,6 +40,7 @@ pub fn connect_and_monitor(
 	p2p_server: Arc<p2p::Server>,
 	capabilities: p2p::Capabilities,
 	seed_list: Box<dyn Fn() -> Vec<PeerAddr> + Send>,
+	validation_backlog: Box<dyn Fn() -> usize + Send>,
 	preferred_peers: &[PeerAddr],
 	stop_state: Arc<StopState>,
 	header_cache_size: u64,
@@ -50,6 +52,12 @@ pub fn connect_and_monitor(
 		.spawn(move || {
 			let peers = p2p_server.peers.clone();
 			let mut connect_all = false;
+			// CPU load is measured only for the dynamic peer targets
+			let mut system = if peers.peer_targets().is_enabled() {
+				Some(System::new())
+			} else {
+				None
+			};
 
 			// open a channel with a listener that connects every peer address sent below
 			// max peer count
@@ -102,9 +110,9 @@ pub fn connect_and_monitor(
 					connect_all = true;
 				}
 
-				// Check for and remove expired peers from the storage
+				// Check for and remove expired peers from the storage, keep it under its max size
 				if peer_count > 0 && Utc::now() - prev_expire_check > Duration::hours(1) {
-					peers.remove_expired();
+					peers.prune_store();
 
 					prev_expire_check = Utc::now();
 				}
@@ -138,6 +146,10 @@ pub fn connect_and_monitor(
 						continue;
 					}
 
+					if let Some(system) = system.as_mut() {
+						update_peer_targets(&peers, system, validation_backlog());
+					}
+
 					// monitor additional peers if we need to add more
 					monitor_peers(
 						peers.clone(),
@@ -169,6 +181,19 @@ pub fn connect_and_monitor(
 		})
 }
 
+// Adjust the peer connection targets to the CPU load and the validation backlog,
+// the bandwidth is measured by the peers.
+fn update_peer_targets(peers: &p2p::Peers, system: &mut System, validation_backlog: usize) {
+	system.refresh_system();
+	// sysinfo reports the usage as a fraction, the first processor is the global one
+	let cpu_load = system
+		.get_processor_list()
+		.first()
+		.map_or(0.0, |p| p.get_cpu_usage());
+	let cpu_load = (cpu_load * 100.0).round().max(0.0).min(100.0) as u8;
+	peers.update_peer_targets(cpu_load, validation_backlog);
+}
+
 fn monitor_peers(
 	peers: Arc<p2p::Peers>,
 	config: p2p::P2PConfig,
@@ -219,11 +244,16 @@ fn monitor_peers(
 
 	// maintenance step first, clean up p2p server peers
 	peers.clean_peers(
-		config.peer_max_inbound_count() as usize,
-		config.peer_max_outbound_count() as usize,
+		peers.max_inbound_count() as usize,
+		peers.max_outbound_count() as usize,
 		preferred_peers,
 	);
 
+	// Keep the peer groups at their min connections.
+	for addr in peers.group_connect_candidates() {
+		tx.send(addr).unwrap();
+	}
+
 	if peers.enough_outbound_peers() {
 		return;
 	}
@@ -343,10 +373,17 @@ fn listen_for_addrs(
 		}
 	}
 
-	// If we have a healthy number of outbound peers then we are done here.
+	// If we have a healthy number of outbound peers then we only connect the peers
+	// the peer groups are missing.
 	if peers.enough_outbound_peers() {
-		return;
+		let candidates = peers.group_connect_candidates();
+		addrs.retain(|addr| candidates.contains(addr));
+		if addrs.is_empty() {
+			return;
+		}
 	}
+	// Don't connect the peers of the groups with all their connections taken.
+	addrs.retain(|addr| !peers.is_group_full(addr));
 	// Note: We drained the rx queue earlier to keep it under control.
 	// Even if there are many addresses to try we will only try a bounded number of them for safety.
 	let connect_min_interval = 30;
@@ -39,28 +39,32 @@ use grin_util::{to_hex, OnionV3Address};
 use walkdir::WalkDir;
 
 use crate::api;
-use crate::api::TLSConfig;
+use crate::api::rate_limit::{self, RateLimitConfig};
+use crate::api::{normalize_fingerprint, ClientPermission, HealthConfig, TLSClientAuth, TLSConfig};
 use crate::chain::{self, SyncState, SyncStatus};
 use crate::common::adapters::{
-	ChainToPoolAndNetAdapter, NetToChainAdapter, PoolToChainAdapter, PoolToNetAdapter,
+	serve_gossip_rpc_request, ChainToPoolAndNetAdapter, Libp2pReorgListener, NetToChainAdapter,
+	PoolToChainAdapter, PoolToNetAdapter,
 };
-use crate::common::hooks::{init_chain_hooks, init_net_hooks};
+use crate::common::hooks::{init_chain_hooks, init_libp2p_hooks, init_net_hooks, init_pool_hooks};
 use crate::common::stats::{
 	ChainStats, DiffBlock, DiffStats, PeerStats, ServerStateInfo, ServerStats, TxStats,
 };
+use crate::common::tx_policy::init_tx_policy;
 
-use crate::common::types::{Error, ServerConfig, StratumServerConfig};
+use crate::common::types::{Error, HeaderSyncPipeline, ServerConfig, StratumServerConfig};
 use crate::core::core::hash::Hashed;
 use crate::core::core::verifier_cache::LruVerifierCache;
 use crate::core::ser::ProtocolVersion;
 use crate::core::stratum::connections;
 use crate::core::{consensus, genesis, global, pow};
-use crate::grin::{dandelion_monitor, seed, sync};
+use crate::grin::{compactor, dandelion_monitor, heartbeat, sandbox, seed, sync, utxo_snapshot};
 use crate::mining::stratumserver;
 use crate::mining::test_miner::Miner;
 use crate::p2p;
 use crate::p2p::types::PeerAddr;
 use crate::pool;
+use crate::store::encryption::EncryptionKey;
 use crate::tor::process as tor_process;
 use crate::util::file::get_first_line;
 use crate::util::{RwLock, StopState};
@@ -70,12 +74,12 @@ use std::collections::HashSet;
 use std::sync::atomic::Ordering;
 
 use crate::p2p::libp2p_connection;
-use chrono::Utc;
+use crate::p2p::libp2p_mailbox;
+use crate::p2p::libp2p_node_api;
 use grin_core::core::TxKernel;
 use grin_util::from_hex;
 use grin_util::secp::constants::SECRET_KEY_SIZE;
 use grin_util::secp::pedersen::Commitment;
-use std::collections::HashMap;
 
 /// Arcified  thread-safe TransactionPool with type parameters used by server components
 pub type ServerTxPool =
@@ -83,6 +87,11 @@ pub type ServerTxPool =
 /// Arcified thread-safe LruVerifierCache
 pub type ServerVerifierCache = Arc<RwLock<LruVerifierCache>>;
 
+/// Time to wait for the libp2p node task to stop
+const LIBP2P_STOP_TIMEOUT: Duration = Duration::from_secs(10);
+/// Time to wait for the node runtime tasks to stop
+const RUNTIME_STOP_TIMEOUT: Duration = Duration::from_secs(10);
+
 /// Grin server holding internal structures.
 pub struct Server {
 	/// server config
@@ -104,9 +113,14 @@ pub struct Server {
 	pub stop_state: Arc<StopState>,
 	/// Maintain a lock_file so we do not run multiple Grin nodes from same dir.
 	lock_file: Arc<File>,
+	heartbeat_thread: JoinHandle<()>,
 	connect_thread: Option<JoinHandle<()>>,
+	/// Tokio runtime of the node, the APIs, the webhooks and the libp2p node run there
+	runtime: tokio::runtime::Runtime,
+	libp2p_task: Option<tokio::task::JoinHandle<()>>,
 	sync_thread: JoinHandle<()>,
 	dandelion_thread: JoinHandle<()>,
+	compactor_thread: Option<JoinHandle<()>>,
 }
 
 impl Server {
@@ -118,6 +132,7 @@ impl Server {
 		logs_rx: Option<mpsc::Receiver<LogEntry>>,
 		mut info_callback: F,
 		allow_to_stop: bool,
+		force_takeover: bool,
 	) -> Result<(), Error>
 	where
 		F: FnMut(Server, Option<mpsc::Receiver<LogEntry>>),
@@ -147,7 +162,12 @@ impl Server {
 			shares_weight,
 			connection_pace_ms,
 		));
-		let serv = Server::new(config, allow_to_stop, stratum_ip_pool.clone())?;
+		let serv = Server::new(
+			config,
+			allow_to_stop,
+			force_takeover,
+			stratum_ip_pool.clone(),
+		)?;
 
 		if let Some(c) = mining_config {
 			let enable_stratum_server = c.enable_stratum_server;
@@ -177,7 +197,9 @@ impl Server {
 	// Exclusive (advisory) lock_file to ensure we do not run multiple
 	// instance of grin server from the same dir.
 	// This uses fs2 and should be safe cross-platform unless somebody abuses the file itself.
-	fn one_grin_at_a_time(config: &ServerConfig) -> Result<Arc<File>, Error> {
+	// The lock doesn't work across the hosts sharing the dir, so the heartbeat
+	// of the node using the dir is checked as well.
+	fn one_grin_at_a_time(config: &ServerConfig, force_takeover: bool) -> Result<Arc<File>, Error> {
 		let path = Path::new(&config.db_root);
 		fs::create_dir_all(&path)?;
 		let path = path.join("mwc.lock");
@@ -196,6 +218,11 @@ impl Server {
 			.expect("Could not write to stderr");
 			e
 		})?;
+		heartbeat::check_data_dir(&config.db_root, force_takeover).map_err(|e| {
+			let mut stderr = std::io::stderr();
+			writeln!(&mut stderr, "{}", e).expect("Could not write to stderr");
+			e
+		})?;
 		Ok(Arc::new(lock_file))
 	}
 
@@ -206,14 +233,26 @@ impl Server {
 	pub fn new(
 		config: ServerConfig,
 		allow_to_stop: bool,
+		force_takeover: bool,
 		stratum_ip_pool: Arc<connections::StratumIpPool>,
 	) -> Result<Server, Error> {
-		let header_cache_size = config.header_cache_size.unwrap_or(25_000);
+		// The low memory mode runs without the in-memory caches
+		let low_memory_mode = global::is_low_memory_mode();
+		let header_cache_size = match low_memory_mode {
+			true => 0,
+			false => config.header_cache_size.unwrap_or(25_000),
+		};
+		// Parallel header sync requires the header cache
+		let header_sync_parallel_peers = match low_memory_mode {
+			true => 1,
+			false => config.header_sync_parallel_peers.unwrap_or(1) as usize,
+		};
+		let pibd_sync = config.pibd_sync.unwrap_or(false);
 		//let duration_sync_long = config.duration_sync_long.unwrap_or(150);
 		//let duration_sync_short = config.duration_sync_short.unwrap_or(100);
 
 		// Obtain our lock_file or fail immediately with an error.
-		let lock_file = Server::one_grin_at_a_time(&config).map_err(|e| {
+		let lock_file = Server::one_grin_at_a_time(&config, force_takeover).map_err(|e| {
 			error!(
 				"Unable to lock db. Likely your DB path is wrong. Error: {}",
 				e
@@ -221,6 +260,14 @@ impl Server {
 			e
 		})?;
 
+		// One tokio runtime for the whole node. Its tasks must not block, the blocking
+		// chain and pool calls go to its blocking pool.
+		let runtime = tokio::runtime::Builder::new()
+			.threaded_scheduler()
+			.thread_name("node_runtime")
+			.enable_all()
+			.build()?;
+
 		// Defaults to None (optional) in config file.
 		// This translates to false here.
 		let archive_mode = match config.archive_mode {
@@ -228,62 +275,105 @@ impl Server {
 			Some(b) => b,
 		};
 
+		// Landlock covers only the threads started after it is applied, the logger
+		// and the UI threads are already running and stay unrestricted.
+		if let Some(ref sandbox_config) = config.sandbox {
+			sandbox::apply_landlock(sandbox_config, &config.db_root)?;
+		}
+
 		let stop_state = Arc::new(StopState::new());
+		let heartbeat_thread =
+			heartbeat::start_heartbeat(config.db_root.clone(), stop_state.clone())?;
 
 		// Shared cache for verification results.
 		// We cache rangeproof verification and kernel signature verification.
-		let verifier_cache = Arc::new(RwLock::new(LruVerifierCache::new()));
+		let verifier_cache = Arc::new(RwLock::new(match low_memory_mode {
+			true => LruVerifierCache::with_capacity(0),
+			false => LruVerifierCache::new(),
+		}));
 
 		let pool_adapter = Arc::new(PoolToChainAdapter::new());
-		let pool_net_adapter = Arc::new(PoolToNetAdapter::new(config.dandelion_config.clone()));
-		let tx_pool = Arc::new(RwLock::new(pool::TransactionPool::new(
+		let pool_net_adapter = Arc::new(PoolToNetAdapter::new(
+			config.dandelion_config.clone(),
+			config.libp2p_tx_receipts.unwrap_or(false),
+			init_pool_hooks(&config, runtime.handle()),
+		));
+		let mut tx_pool = pool::TransactionPool::new(
 			config.pool_config.clone(),
 			pool_adapter.clone(),
 			verifier_cache.clone(),
 			pool_net_adapter.clone(),
-		)));
+		);
+		tx_pool.set_dandelion_config(config.dandelion_config.clone());
+		if let Some(policy) = init_tx_policy(&config.pool_config) {
+			info!("Transaction policy plugin at {}", policy.url());
+			tx_pool.add_policy(Arc::new(policy));
+		}
+		let tx_pool = Arc::new(RwLock::new(tx_pool));
 
 		let sync_state = Arc::new(SyncState::new());
 
 		let chain_adapter = Arc::new(ChainToPoolAndNetAdapter::new(
 			tx_pool.clone(),
-			init_chain_hooks(&config),
+			init_chain_hooks(&config, runtime.handle()),
 		));
 
 		let genesis = match config.chain_type {
 			global::ChainTypes::AutomatedTesting => pow::mine_genesis_block().unwrap(),
 			global::ChainTypes::UserTesting => pow::mine_genesis_block().unwrap(),
+			global::ChainTypes::CustomTesting => pow::mine_genesis_block().unwrap(),
 			global::ChainTypes::Floonet => genesis::genesis_floo(),
 			global::ChainTypes::Mainnet => genesis::genesis_main(),
 		};
 
 		info!("Starting server, genesis block: {}", genesis.hash());
 
-		let shared_chain = Arc::new(chain::Chain::init(
+		// The chain and peer stores are encrypted with the configured key.
+		let encryption_key = Server::load_encryption_key(&config)?;
+
+		let shared_chain = Arc::new(chain::Chain::init_with_key(
 			config.db_root.clone(),
 			chain_adapter.clone(),
 			genesis.clone(),
 			pow::verify_size,
 			verifier_cache.clone(),
 			archive_mode,
+			encryption_key.clone(),
 		)?);
 
 		pool_adapter.set_chain(shared_chain.clone());
+		shared_chain
+			.set_orphans_max_size_bytes(config.chain_config.orphans_max_size_bytes as usize);
+		shared_chain.set_rejected_blocks_forensics(
+			config.chain_config.dump_rejected_blocks,
+			config.chain_config.max_rejected_blocks,
+		);
+		shared_chain.register_reorg_listener(Arc::new(Libp2pReorgListener));
 
+		let header_pipeline = Arc::new(HeaderSyncPipeline::new());
 		let net_adapter = Arc::new(NetToChainAdapter::new(
 			sync_state.clone(),
 			shared_chain.clone(),
 			tx_pool.clone(),
 			verifier_cache.clone(),
 			config.clone(),
-			init_net_hooks(&config),
+			init_net_hooks(&config, runtime.handle()),
+			header_pipeline.clone(),
+			pool_net_adapter.dandelion_epoch(),
 		));
 
 		// we always support tor, so don't rely on config. This fixes
 		// the problem of old config files
 		// only for capabilities params, doesn't mean
 		// tor _MUST_ be on.
-		let capab = config.p2p_config.capabilities | p2p::Capabilities::TOR_ADDRESS;
+		let mut capab = config.p2p_config.capabilities
+			| p2p::Capabilities::TOR_ADDRESS
+			| p2p::Capabilities::PIBD_HIST
+			| p2p::Capabilities::TXHASHSET_RANGE;
+		// archive nodes advertise the full history they can serve
+		if archive_mode {
+			capab |= p2p::Capabilities::ARCHIVE_HIST;
+		}
 
 		api::reset_server_onion_address();
 
@@ -389,18 +479,61 @@ impl Server {
 		);
 
 		// Initialize libp2p server
-		if config.libp2p_enabled.unwrap_or(true) && onion_address.is_some() && tor_secret.is_some()
-		{
-			let onion_address = onion_address.clone().unwrap();
-			let tor_secret = tor_secret.unwrap();
-			let tor_secret = from_hex(&tor_secret).map_err(|e| {
-				Error::General(format!("Unable to parse secret hex {}, {}", tor_secret, e))
-			})?;
+		let mut libp2p_task = None;
+		let libp2p_tor = onion_address.is_some() && tor_secret.is_some();
+		let libp2p_clearnet_port = config.libp2p_clearnet_port;
+		if config.libp2p_enabled.unwrap_or(true) && (libp2p_tor || libp2p_clearnet_port.is_some()) {
+			let tor_secret = match tor_secret {
+				Some(tor_secret) if libp2p_tor => from_hex(&tor_secret).map_err(|e| {
+					Error::General(format!("Unable to parse secret hex {}, {}", tor_secret, e))
+				})?,
+				// Without Tor we still need the identity for libp2p node
+				_ => Server::init_libp2p_identity(&config.db_root)?.0.to_vec(),
+			};
 
 			let libp2p_port = config.libp2p_port;
-			let tor_socks_port = config.tor_config.socks_port;
+			let tor_socks_port = if libp2p_tor {
+				Some(config.tor_config.socks_port)
+			} else {
+				None
+			};
 			let fee_base = config.pool_config.accept_fee_base;
-			api::set_server_onion_address(&onion_address);
+			if libp2p_tor {
+				api::set_server_onion_address(&onion_address.clone().unwrap());
+			}
+
+			let libp2p_clearnet_address = config.libp2p_clearnet_address.clone();
+			let mut clearnet_seeds = vec![];
+			for seed in config.libp2p_clearnet_seeds.clone().unwrap_or(vec![]) {
+				let parts: Vec<&str> = seed.splitn(2, '@').collect();
+				if parts.len() == 2 {
+					clearnet_seeds.push((
+						parts[0].trim_end_matches(".onion").to_string(),
+						parts[1].to_string(),
+					));
+				} else {
+					warn!("Invalid libp2p clearnet seed {}, skipping", seed);
+				}
+			}
+			libp2p_connection::set_clearnet_seed_list(&clearnet_seeds);
+			for observer in init_libp2p_hooks(&config, runtime.handle()) {
+				libp2p_connection::register_observer(observer);
+			}
+			libp2p_connection::set_libp2p_config(&config.p2p_config.libp2p);
+			libp2p_connection::set_message_dedup_ttl(
+				config
+					.libp2p_message_dedup_ttl
+					.unwrap_or(libp2p_connection::MESSAGE_DEDUP_TTL_DEFAULT),
+			);
+			libp2p_connection::set_gossip_trace_size(config.libp2p_gossip_trace_size.unwrap_or(0));
+			libp2p_connection::set_forward_bandwidth(config.libp2p_forward_bandwidth.unwrap_or(0));
+			libp2p_connection::set_relay_deny_list(libp2p_connection::RelayDenyList {
+				topics: config.libp2p_relay_deny_topics.clone().unwrap_or(vec![]),
+				message_id_patterns: config
+					.libp2p_relay_deny_message_ids
+					.clone()
+					.unwrap_or(vec![]),
+			});
 
 			let clone_shared_chain = shared_chain.clone();
 			let libp2p_topics = config
@@ -408,94 +541,103 @@ impl Server {
 				.clone()
 				.unwrap_or(vec!["SwapMarketplace".to_string()]);
 
-			thread::Builder::new()
-				.name("libp2p_node".to_string())
-				.spawn(move || {
-					let requested_kernel_cache: RwLock<HashMap<Commitment, (TxKernel, u64)>> =
-						RwLock::new(HashMap::new());
-					let last_time_cache_cleanup: RwLock<i64> = RwLock::new(0);
-
-					let output_validation_fn =
-						move |excess: &Commitment| -> Result<Option<TxKernel>, grin_p2p::Error> {
-							// Tip is needed in order to request from last 24 hours (1440 blocks)
-							let tip_height = clone_shared_chain.head()?.height;
-
-							let cur_time = Utc::now().timestamp();
-							// let's clean cache every 10 minutes. Removing all expired items
-							{
-								let mut last_time_cache_cleanup = last_time_cache_cleanup.write();
-								if cur_time - 600 > *last_time_cache_cleanup {
-									let min_height = tip_height
-										- libp2p_connection::INTEGRITY_FEE_VALID_BLOCKS
-										- libp2p_connection::INTEGRITY_FEE_VALID_BLOCKS / 12;
-									requested_kernel_cache
-										.write()
-										.retain(|_k, v| v.1 > min_height);
-									*last_time_cache_cleanup = cur_time;
-								}
-							}
-
-							// Checking if we hit the cache
-							if let Some(tx) = requested_kernel_cache.read().get(excess) {
-								return Ok(Some(tx.clone().0));
-							}
-
-							// !!! Note, get_kernel_height does iteration through the MMR. That will work until we
-							// Ban nodes that sent us incorrect excess. For now it should work fine. Normally
-							// peers reusing the integrity kernels so cache hit should happen most of the time.
-							match clone_shared_chain.get_kernel_height(
-								excess,
-								Some(tip_height - libp2p_connection::INTEGRITY_FEE_VALID_BLOCKS),
-								None,
-							)? {
-								Some((tx_kernel, height, _)) => {
-									requested_kernel_cache
-										.write()
-										.insert(excess.clone(), (tx_kernel.clone(), height));
-									Ok(Some(tx_kernel))
-								}
-								None => Ok(None),
-							}
-						};
-
-					let mut secret: [u8; SECRET_KEY_SIZE] = [0; SECRET_KEY_SIZE];
-					secret.copy_from_slice(&tor_secret);
-
-					let validation_fn = Arc::new(output_validation_fn);
+			let libp2p_stop_state = stop_state.clone();
+			let tx_receipts = config.libp2p_tx_receipts.unwrap_or(false);
+			let receipts_pool = tx_pool.clone();
+			let receipts_chain = shared_chain.clone();
+			let mailbox = config.libp2p_mailbox.unwrap_or(true);
+
+			// Found kernels are cached by libp2p node, cache is cleared on reorg
+			let output_validation_fn =
+				move |excess: &Commitment| -> Result<Option<TxKernel>, grin_p2p::Error> {
+					// Tip is needed in order to request from last 24 hours (1440 blocks)
+					let tip_height = clone_shared_chain.head()?.height;
+
+					// Kernel excess index lookup, only kernels from the last 24 hours are valid.
+					let min_height =
+						tip_height.saturating_sub(libp2p_connection::INTEGRITY_FEE_VALID_BLOCKS);
+					Ok(clone_shared_chain
+						.get_kernel_by_excess(excess)?
+						.filter(|(_, height, _)| *height >= min_height)
+						.map(|(tx_kernel, _height, _)| tx_kernel))
+				};
 
-					let libp2p_stopper = Arc::new(std::sync::Mutex::new(1));
+			let mut secret: [u8; SECRET_KEY_SIZE] = [0; SECRET_KEY_SIZE];
+			secret.copy_from_slice(&tor_secret);
 
-					loop {
-						for t in &libp2p_topics {
-							libp2p_connection::add_topic(t, 1);
-						}
+			let validation_fn = Arc::new(output_validation_fn);
+			libp2p_connection::notify_chain_tip(shared_chain.head()?.height);
 
-						let libp2p_node_runner = libp2p_connection::run_libp2p_node(
-							tor_socks_port,
-							&secret,
-							libp2p_port.unwrap_or(3417),
-							fee_base,
-							validation_fn.clone(),
-							libp2p_stopper.clone(), // passing new obj, because we never will stop the libp2p process
+			// libp2p node runs as a task on the node runtime, the swarm and its
+			// transports share the same reactor
+			let task = runtime.spawn(async move {
+				loop {
+					for t in &libp2p_topics {
+						libp2p_connection::add_config_topic(t, 1);
+					}
+					if tx_receipts {
+						// Node only relays the receipts of the transactions it knows about. The
+						// handler runs on the blocking pool, the lookups don't stall the swarm loop.
+						let pool = receipts_pool.clone();
+						let chain = receipts_chain.clone();
+						libp2p_connection::add_topic_to_libp2p(
+							libp2p_connection::TX_RECEIPT_TOPIC,
+							libp2p_connection::gossip_handler(
+								move |_sender, _topic, data, _fee| {
+									let (kernels, _timestamp) =
+										libp2p_connection::parse_tx_receipt(&data)
+											.ok_or(grin_p2p::Error::BadMessage)?;
+									let not_in_pool: Vec<_> = {
+										let pool = pool.read();
+										kernels
+											.iter()
+											.filter(|excess| !pool.txpool.contains_kernel(excess))
+											.collect()
+									};
+									for excess in not_in_pool {
+										if chain.get_kernel_by_excess(excess)?.is_none() {
+											return Err(grin_p2p::Error::Libp2pError(format!(
+												"Receipt kernel {} is not found at the pool or the chain",
+												to_hex(&excess.0)
+											)));
+										}
+									}
+									Ok(())
+								},
+							),
+							libp2p_connection::HandlerErrorPolicy::Log,
 						);
+					}
+					if mailbox {
+						libp2p_mailbox::start_mailbox();
+					}
 
-						info!("Starting gossipsub libp2p server");
-						let mut rt = tokio::runtime::Runtime::new().unwrap();
-
-						match rt.block_on(libp2p_node_runner) {
-							Ok(_) => info!("libp2p node is exited"),
-							Err(e) => error!("Unable to start libp2p node, {}", e),
-						}
-						// Swarm is not valid any more, let's update our global instance.
-						libp2p_connection::reset_libp2p_swarm();
-
-						if *libp2p_stopper.lock().unwrap() == 0 {
-							// Should never happen for the node
-							debug_assert!(false);
-							break;
-						}
+					let libp2p_node_runner = libp2p_connection::run_libp2p_node(
+						tor_socks_port,
+						&secret,
+						libp2p_port.unwrap_or(3417),
+						libp2p_clearnet_port,
+						libp2p_clearnet_address.clone(),
+						fee_base,
+						validation_fn.clone(),
+						libp2p_stop_state.clone(),
+					);
+
+					info!("Starting gossipsub libp2p server");
+					match libp2p_node_runner.await {
+						Ok(_) => info!("libp2p node is exited"),
+						Err(e) => error!("Unable to start libp2p node, {}", e),
 					}
-				})?;
+					// Swarm is not valid any more, let's update our global instance.
+					libp2p_connection::reset_libp2p_swarm();
+
+					if libp2p_stop_state.is_stopped() {
+						info!("libp2p node is stopped");
+						break;
+					}
+				}
+			});
+			libp2p_task = Some(task);
 		}
 
 		let p2p_server = Arc::new(p2p::Server::new(
@@ -507,12 +649,31 @@ impl Server {
 			stop_state.clone(),
 			socks_port,
 			onion_address,
+			encryption_key,
 		)?);
 
 		// Initialize various adapters with our dynamic set of connected peers.
 		chain_adapter.init(p2p_server.peers.clone());
 		pool_net_adapter.init(p2p_server.peers.clone());
 		net_adapter.init(p2p_server.peers.clone());
+		// libp2p node shares the ban list with the classic p2p layer
+		libp2p_connection::set_ban_list(p2p_server.peers.clone());
+		// Dynamically subscribed topics are stored with the peers
+		libp2p_connection::set_topic_store(p2p_server.peers.clone());
+
+		// Transactions of the txpool from before the restart, the pool adapter must be
+		// initialized already, it is notified about the restored transactions
+		if config.pool_config.journal {
+			let path = Path::new(&config.db_root).join("pool_journal.json");
+			let (journal, entries) = pool::PoolJournal::open(&path)?;
+			let journaled = entries.len();
+			let header = shared_chain.head_header()?;
+			let restored = tx_pool.write().load_journal(journal, entries, &header)?;
+			info!(
+				"Restored {} of {} journaled pool transactions",
+				restored, journaled
+			);
+		}
 
 		let mut connect_thread = None;
 
@@ -539,10 +700,17 @@ impl Server {
 				None => vec![],
 			};
 
+			// Blocks waiting for validation, a backlog means the node can't keep up with its peers
+			let validation_backlog = {
+				let chain = shared_chain.clone();
+				Box::new(move || chain.orphans_len())
+			};
+
 			connect_thread = Some(seed::connect_and_monitor(
 				p2p_server.clone(),
 				config.p2p_config.capabilities,
 				seeder,
+				validation_backlog,
 				&preferred_peers,
 				stop_state.clone(),
 				header_cache_size,
@@ -559,6 +727,11 @@ impl Server {
 			p2p_server.peers.clone(),
 			shared_chain.clone(),
 			stop_state.clone(),
+			header_pipeline,
+			header_sync_parallel_peers,
+			header_cache_size,
+			pibd_sync,
+			config.utxo_snapshot.clone(),
 		)?;
 
 		let p2p_inner = p2p_server.clone();
@@ -571,8 +744,11 @@ impl Server {
 			})?;
 
 		info!("Starting rest apis at: {}", &config.api_http_addr);
-		let api_secret = get_first_line(config.api_secret_path.clone());
-		let foreign_api_secret = get_first_line(config.foreign_api_secret_path.clone());
+		// Secrets are passed with their files, so they can be rotated through the owner API
+		let api_secret =
+			get_first_line(config.api_secret_path.clone()).zip(config.api_secret_path.clone());
+		let foreign_api_secret = get_first_line(config.foreign_api_secret_path.clone())
+			.zip(config.foreign_api_secret_path.clone());
 		let tls_conf = match config.tls_certificate_file.clone() {
 			None => None,
 			Some(file) => {
@@ -583,10 +759,44 @@ impl Server {
 						return Err(Error::ArgumentError(msg));
 					}
 				};
-				Some(TLSConfig::new(file, key))
+				let tls_conf = TLSConfig::new(file, key);
+				match config.tls_client_ca_file.clone() {
+					Some(ca_bundle) => Some(
+						tls_conf.with_client_auth(TLSClientAuth {
+							ca_bundle,
+							required: config.tls_client_cert_required,
+							permissions: config
+								.tls_client_owner_certs
+								.iter()
+								.map(|fingerprint| {
+									(normalize_fingerprint(fingerprint), ClientPermission::Owner)
+								})
+								.collect(),
+						}),
+					),
+					None => Some(tls_conf),
+				}
 			}
 		};
 
+		let api_rate_limit =
+			if config.api_rate_limit_rpm.is_some() || config.api_heavy_rate_limit_rpm.is_some() {
+				Some(RateLimitConfig {
+					requests_per_minute: config.api_rate_limit_rpm.unwrap_or(0),
+					heavy_requests_per_minute: config.api_heavy_rate_limit_rpm.unwrap_or(0),
+				})
+			} else {
+				None
+			};
+
+		let health_defaults = HealthConfig::default();
+		let health_config = HealthConfig {
+			max_blocks_behind: config
+				.health_max_blocks_behind
+				.unwrap_or(health_defaults.max_blocks_behind),
+			min_peers: config.health_min_peers.unwrap_or(health_defaults.min_peers),
+		};
+
 		// TODO fix API shutdown and join this thread
 		api::node_apis(
 			&config.api_http_addr,
@@ -596,11 +806,49 @@ impl Server {
 			sync_state.clone(),
 			api_secret,
 			foreign_api_secret,
-			tls_conf,
+			tls_conf.clone(),
 			allow_to_stop,
 			stratum_ip_pool,
+			config.api_audit_log_path.clone(),
+			api_rate_limit,
+			health_config,
+			runtime.handle().clone(),
 		)?;
 
+		// Serving selected foreign API methods for the wallets that are connected over libp2p
+		if config.libp2p_enabled.unwrap_or(true) && config.libp2p_node_api.unwrap_or(true) {
+			let foreign_api = api::Foreign::new(
+				Arc::downgrade(&p2p_server.peers),
+				Arc::downgrade(&shared_chain),
+				Arc::downgrade(&tx_pool),
+				Arc::downgrade(&sync_state),
+			);
+			libp2p_node_api::set_node_api_handler(Arc::new(move |peer, request| {
+				if let Some(limiter) = rate_limit::get_api_rate_limiter() {
+					let heavy = rate_limit::heavy_foreign_calls(&request);
+					if let Err(limited) = limiter.check_peer(&peer.to_string(), heavy) {
+						debug!("libp2p peer {} is rate limited, {:?}", peer, limited);
+						return rate_limit::rate_limited_rpc_response(&request, limited);
+					}
+				}
+				serve_gossip_rpc_request(request, |request| {
+					api::foreign_rpc_request(&foreign_api, request)
+				})
+			}));
+		}
+
+		if let Some(grpc_api_addr) = &config.grpc_api_addr {
+			Server::start_grpc_api(
+				grpc_api_addr,
+				shared_chain.clone(),
+				tx_pool.clone(),
+				p2p_server.peers.clone(),
+				sync_state.clone(),
+				tls_conf,
+				runtime.handle().clone(),
+			)?;
+		}
+
 		info!("Starting dandelion monitor: {}", &config.api_http_addr);
 		let dandelion_thread = dandelion_monitor::monitor_transactions(
 			config.dandelion_config.clone(),
@@ -610,6 +858,34 @@ impl Server {
 			stop_state.clone(),
 		)?;
 
+		let compactor_thread = if config.chain_config.background_compaction {
+			info!("Starting chain compactor");
+			Some(compactor::start_compactor(
+				config.chain_config.clone(),
+				shared_chain.clone(),
+				sync_state.clone(),
+				stop_state.clone(),
+			)?)
+		} else {
+			None
+		};
+
+		// The validation of a snapshot history can take hours and resumes from its
+		// checkpoint on restart, so we don't wait for it on stop.
+		if config.utxo_snapshot.is_some() || shared_chain.assumed_utxo()?.is_some() {
+			info!("Starting utxo snapshot validator");
+			let _ = utxo_snapshot::start_validator(
+				shared_chain.clone(),
+				sync_state.clone(),
+				stop_state.clone(),
+			)?;
+		}
+
+		// Tor is running by now, the node doesn't exec anything any more.
+		if config.sandbox.is_some() {
+			sandbox::apply_seccomp()?;
+		}
+
 		warn!("MWC server started.");
 		Ok(Server {
 			config,
@@ -623,9 +899,13 @@ impl Server {
 			},
 			stop_state,
 			lock_file,
+			heartbeat_thread,
 			connect_thread,
+			runtime,
+			libp2p_task,
 			sync_thread,
 			dandelion_thread,
+			compactor_thread,
 		})
 	}
 
@@ -654,16 +934,7 @@ impl Server {
 		socks_port: u16,
 	) -> Result<(tor_process::TorProcess, String, SecretKey), Error> {
 		let mut process = tor_process::TorProcess::new();
-		let tor_dir = if tor_base.is_some() {
-			format!("{}/tor/listener", tor_base.unwrap())
-		} else {
-			format!("{}/tor/listener", "~/.mwc/main")
-		};
-
-		let home_dir = dirs::home_dir()
-			.map(|p| p.to_str().unwrap().to_string())
-			.unwrap_or("~".to_string());
-		let tor_dir = tor_dir.replace("~", &home_dir);
+		let tor_dir = Server::tor_listener_dir(tor_base);
 
 		// remove all other onion addresses that were previously used.
 
@@ -767,6 +1038,130 @@ impl Server {
 		}
 	}
 
+	fn tor_listener_dir(tor_base: Option<&str>) -> String {
+		let tor_dir = if tor_base.is_some() {
+			format!("{}/tor/listener", tor_base.unwrap())
+		} else {
+			format!("{}/tor/listener", "~/.mwc/main")
+		};
+
+		let home_dir = dirs::home_dir()
+			.map(|p| p.to_str().unwrap().to_string())
+			.unwrap_or("~".to_string());
+		tor_dir.replace("~", &home_dir)
+	}
+
+	/// Start the gRPC foreign API at the listen address
+	#[cfg(feature = "grpc")]
+	fn start_grpc_api(
+		addr: &str,
+		chain: Arc<chain::Chain>,
+		tx_pool: ServerTxPool,
+		peers: Arc<p2p::Peers>,
+		sync_state: Arc<SyncState>,
+		tls_conf: Option<TLSConfig>,
+		runtime: tokio::runtime::Handle,
+	) -> Result<(), Error> {
+		let addr = addr
+			.parse()
+			.map_err(|e| Error::Configuration(format!("Invalid grpc_api_addr {}, {}", addr, e)))?;
+		api::grpc::start_grpc_server(addr, chain, tx_pool, peers, sync_state, tls_conf, runtime)?;
+		Ok(())
+	}
+
+	#[cfg(not(feature = "grpc"))]
+	fn start_grpc_api(
+		_addr: &str,
+		_chain: Arc<chain::Chain>,
+		_tx_pool: ServerTxPool,
+		_peers: Arc<p2p::Peers>,
+		_sync_state: Arc<SyncState>,
+		_tls_conf: Option<TLSConfig>,
+		_runtime: tokio::runtime::Handle,
+	) -> Result<(), Error> {
+		warn!("grpc_api_addr is set, but the node is built without the grpc feature");
+		Ok(())
+	}
+
+	/// Rebuild the header MMR from the headers stored in the db. Node must be stopped.
+	/// Returns the height of the header head.
+	pub fn rebuild_header_mmr(config: &ServerConfig) -> Result<u64, Error> {
+		// Fails if the node is running
+		let _lock_file = Server::one_grin_at_a_time(config, false)?;
+		let encryption_key = Server::load_encryption_key(config)?;
+		Ok(chain::Chain::rebuild_header_mmr(
+			&config.db_root,
+			encryption_key,
+		)?)
+	}
+
+	/// Key of the chain and peer stores, None if they are not encrypted.
+	fn load_encryption_key(config: &ServerConfig) -> Result<Option<Arc<EncryptionKey>>, Error> {
+		let key = match (
+			&config.db_encryption_key_path,
+			&config.db_encryption_keystore_entry,
+		) {
+			(Some(_), Some(_)) => {
+				return Err(Error::Configuration(
+					"Set either db_encryption_key_path or db_encryption_keystore_entry".to_owned(),
+				))
+			}
+			(Some(path), None) => Some(EncryptionKey::load_or_create(path)?),
+			(None, Some(entry)) => Some(EncryptionKey::load_or_create_in_keystore(entry)?),
+			(None, None) => None,
+		};
+		Ok(key.map(Arc::new))
+	}
+
+	/// Remove the onion and libp2p identity keys of the node, new keys are generated
+	/// on the next start. Node must be stopped. Returns the removed directories.
+	pub fn regenerate_identity(config: &ServerConfig) -> Result<Vec<String>, Error> {
+		// Fails if the node is running
+		let _lock_file = Server::one_grin_at_a_time(config, false)?;
+
+		let identity_dirs = vec![
+			format!(
+				"{}{}onion_service_addresses",
+				Server::tor_listener_dir(Some(&config.db_root)),
+				MAIN_SEPARATOR
+			),
+			format!("{}{}libp2p", config.db_root, MAIN_SEPARATOR),
+		];
+
+		let mut removed = vec![];
+		for dir in identity_dirs {
+			if Path::new(&dir).exists() {
+				fs::remove_dir_all(&dir).map_err(|e| {
+					Error::General(format!("Unable to remove identity at {}, {}", dir, e))
+				})?;
+				warn!("Node identity at {} is removed", dir);
+				removed.push(dir);
+			}
+		}
+		Ok(removed)
+	}
+
+	/// libp2p identity key for the nodes that are running without Tor.
+	/// The key is generated once and stored at <db_root>/libp2p
+	fn init_libp2p_identity(db_root: &str) -> Result<SecretKey, Error> {
+		let identity_dir = format!("{}{}libp2p", db_root, MAIN_SEPARATOR);
+		if let Ok(secret) = tor_config::read_sec_key_file(&identity_dir) {
+			return Ok(secret);
+		}
+
+		fs::create_dir_all(&identity_dir)?;
+		let secret = secp::key::SecretKey::new(&mut rand::thread_rng());
+		let key_file_path = format!(
+			"{}{}{}",
+			identity_dir,
+			MAIN_SEPARATOR,
+			tor_config::SEC_KEY_FILE_COPY
+		);
+		let mut file = File::create(&key_file_path)?;
+		file.write_all(&secret.0)?;
+		Ok(secret)
+	}
+
 	/// Asks the server to connect to a peer at the provided network address.
 	pub fn connect_peer(&self, addr: PeerAddr, header_cache_size: u64) -> Result<(), Error> {
 		self.p2p.connect(addr, header_cache_size)?;
@@ -834,6 +1229,8 @@ impl Server {
 			stratum_server_addr: None,
 			wallet_listener_url: config_wallet_url,
 			minimum_share_difficulty: 1,
+			job_refresh_fee_threshold: 0,
+			incremental_jobs: false,
 			ip_tracking: false,
 			workers_connection_limit: 30000,
 			ban_action_limit: 5,
@@ -915,12 +1312,13 @@ impl Server {
 
 			let block_time_sum = diff_entries.iter().fold(0, |sum, t| sum + t.duration);
 			let block_diff_sum = diff_entries.iter().fold(0, |sum, d| sum + d.difficulty);
+			let window_size = global::difficulty_adjust_window();
 			DiffStats {
 				height: height as u64,
 				last_blocks: diff_entries,
-				average_block_time: block_time_sum / (consensus::DIFFICULTY_ADJUST_WINDOW - 1),
-				average_difficulty: block_diff_sum / (consensus::DIFFICULTY_ADJUST_WINDOW - 1),
-				window_size: consensus::DIFFICULTY_ADJUST_WINDOW,
+				average_block_time: block_time_sum / (window_size - 1),
+				average_difficulty: block_diff_sum / (window_size - 1),
+				window_size,
 			}
 		};
 
@@ -941,6 +1339,8 @@ impl Server {
 			tx_pool_kernels: pool.txpool.kernel_count(),
 			stem_pool_size: pool.stempool.size(),
 			stem_pool_kernels: pool.stempool.kernel_count(),
+			stem_pool: pool.stempool_status(),
+			dandelion_stats: pool.dandelion_stats.clone(),
 		});
 
 		let head = self.chain.head_header()?;
@@ -975,20 +1375,29 @@ impl Server {
 			peer_count: self.peer_count(),
 			chain_stats: head_stats,
 			header_stats: header_stats,
+			orphan_stats: self.chain.orphans_stats(),
+			block_timing_stats: self.chain.block_timing_stats(),
 			sync_status: self.sync_state.status(),
 			disk_usage_gb: disk_usage_gb,
 			stratum_stats: self.state_info.stratum_stats.clone(),
 			peer_stats: peer_stats,
 			diff_stats: diff_stats,
 			tx_stats: tx_stats,
+			libp2p_stats: if libp2p_connection::get_libp2p_running() {
+				Some(libp2p_connection::get_libp2p_stats())
+			} else {
+				None
+			},
+			seen_cache_stats: p2p::seen_cache().stats(),
 		})
 	}
 
 	/// Stop the server.
-	pub fn stop(self) {
+	pub fn stop(mut self) {
 		{
 			self.sync_state.update(SyncStatus::Shutdown);
 			self.stop_state.stop();
+			libp2p_node_api::reset_node_api_handler();
 
 			if let Some(connect_thread) = self.connect_thread {
 				match connect_thread.join() {
@@ -1008,10 +1417,35 @@ impl Server {
 				Err(e) => error!("failed to join to dandelion_monitor thread: {:?}", e),
 				Ok(_) => info!("dandelion_monitor thread stopped"),
 			}
+
+			if let Some(compactor_thread) = self.compactor_thread {
+				match compactor_thread.join() {
+					Err(e) => error!("failed to join to compactor thread: {:?}", e),
+					Ok(_) => info!("compactor thread stopped"),
+				}
+			}
+
+			if let Some(task) = self.libp2p_task.take() {
+				// The swarm polling task checks the stop state every second
+				match self
+					.runtime
+					.block_on(tokio::time::timeout(LIBP2P_STOP_TIMEOUT, task))
+				{
+					Ok(Ok(_)) => info!("libp2p_node task stopped"),
+					Ok(Err(e)) => error!("libp2p_node task failed, {}", e),
+					Err(_) => warn!("libp2p_node task is not stopped in time, dropping it"),
+				}
+			}
 		}
 		// this call is blocking and makes sure all peers stop, however
 		// we can't be sure that we stopped a listener blocked on accept, so we don't join the p2p thread
 		self.p2p.stop();
+		match self.heartbeat_thread.join() {
+			Err(e) => error!("failed to join to heartbeat thread: {:?}", e),
+			Ok(_) => info!("heartbeat thread stopped"),
+		}
+		// The API servers and the webhook requests are dropped with the runtime
+		self.runtime.shutdown_timeout(RUNTIME_STOP_TIMEOUT);
 		let _ = self.lock_file.unlock();
 		warn!("Shutdown complete");
 	}
@@ -19,6 +19,7 @@ use std::sync::Arc;
 
 use crate::chain::{self, SyncState, SyncStatus};
 use crate::core::core::hash::Hash;
+use crate::core::global;
 use crate::p2p;
 
 pub struct BodySync {
@@ -89,7 +90,28 @@ impl BodySync {
 
 		hashes.reverse();
 
-		let peers = self.peers.more_work_peers()?;
+		let mut peers = self.peers.more_work_peers()?;
+
+		// Archive nodes need the blocks beyond the horizon, only the peers
+		// advertising the full history are guaranteed to have them.
+		if self.chain.archive_mode() {
+			let head = self.chain.head()?;
+			let header_head = self.chain.header_head()?;
+			if head.height + (global::cut_through_horizon() as u64) < header_head.height {
+				let archive_peers: Vec<_> = peers
+					.iter()
+					.filter(|p| {
+						p.info
+							.capabilities
+							.contains(p2p::Capabilities::ARCHIVE_HIST)
+					})
+					.cloned()
+					.collect();
+				if !archive_peers.is_empty() {
+					peers = archive_peers;
+				}
+			}
+		}
 
 		// if we have 5 peers to sync from then ask for 50 blocks total (peer_count *
 		// 10) max will be 80 if all 8 peers are advertising more work
@@ -17,9 +17,13 @@ use chrono::Duration;
 use std::sync::Arc;
 
 use crate::chain::{self, SyncState, SyncStatus};
-use crate::common::types::Error;
+use crate::common::types::{Error, HeaderSyncPipeline};
 use crate::core::core::hash::{Hash, Hashed};
-use crate::p2p::{self, types::ReasonForBan, Peer};
+use crate::p2p::{self, types::ReasonForBan, Peer, PeerAddr};
+
+/// Seconds to wait for the response to the parallel header request
+/// before the range is requested from another peer.
+const PIPELINE_REQUEST_TIMEOUT: i64 = 10;
 
 pub struct HeaderSync {
 	sync_state: Arc<SyncState>,
@@ -28,6 +32,14 @@ pub struct HeaderSync {
 	prev_header_sync: (DateTime<Utc>, u64, u64),
 	syncing_peer: Option<Arc<Peer>>,
 	stalling_ts: Option<DateTime<Utc>>,
+	// Parallel header sync
+	pipeline: Arc<HeaderSyncPipeline>,
+	parallel_peers: usize,
+	header_cache_size: u64,
+	// Frontier height of the last parallel request and its time
+	pipeline_request: Option<(u64, DateTime<Utc>)>,
+	// Peers that got the latest parallel requests
+	pipeline_peers: Vec<PeerAddr>,
 }
 
 impl HeaderSync {
@@ -35,7 +47,15 @@ impl HeaderSync {
 		sync_state: Arc<SyncState>,
 		peers: Arc<p2p::Peers>,
 		chain: Arc<chain::Chain>,
+		pipeline: Arc<HeaderSyncPipeline>,
+		parallel_peers: usize,
+		header_cache_size: u64,
 	) -> HeaderSync {
+		if parallel_peers > 1 && header_cache_size == 0 {
+			warn!(
+				"sync: parallel header sync requires header cache, using single peer header sync"
+			);
+		}
 		HeaderSync {
 			sync_state,
 			peers,
@@ -43,6 +63,11 @@ impl HeaderSync {
 			prev_header_sync: (Utc::now(), 0, 0),
 			syncing_peer: None,
 			stalling_ts: None,
+			pipeline,
+			parallel_peers,
+			header_cache_size,
+			pipeline_request: None,
+			pipeline_peers: vec![],
 		}
 	}
 
@@ -51,6 +76,12 @@ impl HeaderSync {
 		header_head: &chain::Tip,
 		highest_height: u64,
 	) -> Result<bool, chain::Error> {
+		if let SyncStatus::HeaderSync { .. } = self.sync_state.status() {
+			if self.pipeline_active() {
+				self.request_next_range(header_head, highest_height);
+			}
+		}
+
 		if !self.header_sync_due(header_head) {
 			return Ok(false);
 		}
@@ -78,6 +109,9 @@ impl HeaderSync {
 				// our last known "good" header_head.
 				//
 				self.chain.rebuild_sync_mmr(&header_head)?;
+				self.pipeline.reset(header_head.height, header_head.hash());
+				self.pipeline_request = None;
+				self.pipeline_peers.clear();
 				true
 			}
 			_ => false,
@@ -89,7 +123,11 @@ impl HeaderSync {
 				highest_height: highest_height,
 			});
 
-			self.syncing_peer = self.header_sync();
+			// Parallel requests are driven by the received headers, single peer request
+			// is needed to start the pipeline or if it is stalled.
+			if !self.pipeline_active() || self.pipeline_stalled() {
+				self.syncing_peer = self.header_sync();
+			}
 			return Ok(true);
 		}
 		Ok(false)
@@ -195,6 +233,76 @@ impl HeaderSync {
 		return None;
 	}
 
+	fn pipeline_active(&self) -> bool {
+		self.parallel_peers > 1 && self.header_cache_size > 0 && !self.pipeline.is_inconsistent()
+	}
+
+	fn pipeline_stalled(&self) -> bool {
+		match self.pipeline_request {
+			Some((_, ts)) => Utc::now() > ts + Duration::seconds(PIPELINE_REQUEST_TIMEOUT),
+			None => true,
+		}
+	}
+
+	/// Request the headers after the highest received header from the next peer,
+	/// so the download of the next range goes while the previous one is validated.
+	fn request_next_range(&mut self, header_head: &chain::Tip, highest_height: u64) {
+		let (height, hash) = match self.pipeline.frontier() {
+			Some(frontier) => frontier,
+			None => return,
+		};
+		if height >= highest_height {
+			return;
+		}
+		if let Some((requested_height, _)) = self.pipeline_request {
+			// still waiting for the previous range
+			if height <= requested_height && !self.pipeline_stalled() {
+				return;
+			}
+		}
+		// Not validated headers are waiting in the header cache, don't run ahead of it
+		if height.saturating_sub(header_head.height) + p2p::MAX_BLOCK_HEADERS as u64
+			>= self.header_cache_size
+		{
+			return;
+		}
+
+		let peer = match self.next_pipeline_peer() {
+			Some(peer) => peer,
+			None => return,
+		};
+
+		// Regular locator is needed in case if the peer doesn't know the frontier header
+		let mut locator = vec![hash];
+		if let Ok(sync_locator) = self.get_locator() {
+			locator.extend(sync_locator);
+		}
+		locator.truncate(p2p::MAX_LOCATORS as usize);
+
+		debug!(
+			"sync: parallel header request: asking {} for headers after {} at {}",
+			peer.info.addr, hash, height,
+		);
+		if peer.send_header_request(locator).is_ok() {
+			self.pipeline_request = Some((height, Utc::now()));
+			self.pipeline_peers.push(peer.info.addr.clone());
+			if self.pipeline_peers.len() >= self.parallel_peers {
+				self.pipeline_peers.remove(0);
+			}
+			self.syncing_peer = Some(peer);
+		}
+	}
+
+	/// Most work peer that didn't get the latest parallel requests
+	fn next_pipeline_peer(&self) -> Option<Arc<Peer>> {
+		let peers = self.peers.most_work_peers();
+		peers
+			.iter()
+			.find(|p| !self.pipeline_peers.contains(&p.info.addr))
+			.or_else(|| peers.first())
+			.cloned()
+	}
+
 	/// We build a locator based on sync_head.
 	/// Even if sync_head is significantly out of date we will "reset" it once we
 	/// start getting headers back from a peer.
@@ -225,6 +333,56 @@ fn get_locator_heights(height: u64) -> Vec<u64> {
 #[cfg(test)]
 mod test {
 	use super::*;
+	use crate::core::core::BlockHeader;
+
+	fn build_headers(prev: &BlockHeader, count: u64) -> Vec<BlockHeader> {
+		let mut headers: Vec<BlockHeader> = vec![];
+		for _ in 0..count {
+			let prev = headers.last().unwrap_or(prev);
+			headers.push(BlockHeader {
+				height: prev.height + 1,
+				prev_hash: prev.hash(),
+				..BlockHeader::default()
+			});
+		}
+		headers
+	}
+
+	#[test]
+	fn test_header_sync_pipeline() {
+		let genesis = BlockHeader::default();
+		let pipeline = HeaderSyncPipeline::new();
+		pipeline.reset(genesis.height, genesis.hash());
+
+		let headers = build_headers(&genesis, 10);
+		assert!(pipeline.headers_received(&headers[..5]));
+		assert_eq!(pipeline.frontier(), Some((5, headers[4].hash())));
+		// overlapping range stitches by the frontier header
+		assert!(pipeline.headers_received(&headers[3..]));
+		assert_eq!(pipeline.frontier(), Some((10, headers[9].hash())));
+		// ranges below the frontier and not connected ranges are ignored
+		assert!(pipeline.headers_received(&headers[..3]));
+		assert!(pipeline.headers_received(&build_headers(&headers[9], 5)[1..]));
+		assert_eq!(pipeline.frontier(), Some((10, headers[9].hash())));
+		// not contiguous batch is rejected, the pipeline stays consistent
+		let mut gapped = headers[8..].to_vec();
+		gapped.extend(build_headers(&headers[9], 3)[1..].to_vec());
+		assert!(!pipeline.headers_received(&gapped));
+		assert!(!pipeline.is_inconsistent());
+		assert_eq!(pipeline.frontier(), Some((10, headers[9].hash())));
+
+		// fork doesn't stitch, pipeline falls back
+		let mut fork_base = headers[8].clone();
+		fork_base.timestamp = fork_base.timestamp + Duration::seconds(1);
+		assert!(!pipeline.headers_received(&build_headers(&fork_base, 3)));
+		assert!(pipeline.is_inconsistent());
+		assert!(!pipeline.headers_received(&build_headers(&headers[9], 3)));
+
+		pipeline.reset(headers[9].height, headers[9].hash());
+		assert!(!pipeline.is_inconsistent());
+		assert!(pipeline.headers_received(&build_headers(&headers[9], 3)));
+		assert_eq!(pipeline.frontier().map(|f| f.0), Some(13));
+	}
 
 	#[test]
 	fn test_get_locator_heights() {
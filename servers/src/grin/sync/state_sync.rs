@@ -14,26 +14,44 @@
 
 use chrono::prelude::{DateTime, Utc};
 use chrono::Duration;
+use std::collections::HashMap;
 use std::sync::Arc;
 
-use crate::chain::{self, SyncState, SyncStatus};
+use crate::chain::txhashset::SegmentType;
+use crate::chain::{self, SyncState, SyncStatus, TxHashsetDownloadStats};
+use crate::common::types::UtxoSnapshotConfig;
 use crate::core::core::hash::Hashed;
+use crate::core::core::BlockHeader;
 use crate::core::global;
-use crate::p2p::{self, Peer};
+use crate::grin::utxo_snapshot;
+use crate::p2p::{self, Capabilities, Peer};
+
+/// Max number of segment requests in flight during a segmented txhashset download.
+const PIBD_MAX_REQUESTS: usize = 24;
+
+/// A segment request not answered within this time is sent again, to another peer.
+const PIBD_REQUEST_TIMEOUT_SECS: i64 = 60;
 
 /// Fast sync has 3 "states":
 /// * syncing headers
 /// * once all headers are sync'd, requesting the txhashset state
 /// * once we have the state, get blocks after that
 ///
-/// The StateSync struct implements and monitors the middle step.
+/// The StateSync struct implements and monitors the middle step. The state
+/// is either downloaded as a single zip archive from one peer, or as segments
+/// from all the peers serving them (PIBD) when enabled. A configured trusted
+/// snapshot is tried first, once.
 pub struct StateSync {
 	sync_state: Arc<SyncState>,
 	peers: Arc<p2p::Peers>,
 	chain: Arc<chain::Chain>,
+	pibd_sync: bool,
+	utxo_snapshot: Option<UtxoSnapshotConfig>,
 
 	prev_state_sync: Option<DateTime<Utc>>,
 	state_sync_peer: Option<Arc<Peer>>,
+	pibd_requests: HashMap<(SegmentType, u64), DateTime<Utc>>,
+	pibd_next_peer: usize,
 
 	last_logged_time: i64,
 	last_download_size: u64,
@@ -44,13 +62,19 @@ impl StateSync {
 		sync_state: Arc<SyncState>,
 		peers: Arc<p2p::Peers>,
 		chain: Arc<chain::Chain>,
+		pibd_sync: bool,
+		utxo_snapshot: Option<UtxoSnapshotConfig>,
 	) -> StateSync {
 		StateSync {
 			sync_state,
 			peers,
 			chain,
+			pibd_sync,
+			utxo_snapshot,
 			prev_state_sync: None,
 			state_sync_peer: None,
+			pibd_requests: HashMap::new(),
+			pibd_next_peer: 0,
 			last_logged_time: 0,
 			last_download_size: 0,
 		}
@@ -127,14 +151,22 @@ impl StateSync {
 			}
 
 			if go {
-				self.state_sync_peer = None;
-				match self.request_state(&header_head) {
-					Ok(peer) => {
-						self.state_sync_peer = Some(peer);
+				if let Some(snapshot) = self.utxo_snapshot.take() {
+					if self.load_utxo_snapshot(&snapshot) {
+						return true;
 					}
-					Err(e) => self
-						.sync_state
-						.set_sync_error(chain::ErrorKind::SyncError(format!("{:?}", e)).into()),
+				}
+
+				self.state_sync_peer = None;
+				let res = if self.pibd_sync && !self.pibd_peers(header_head).is_empty() {
+					self.start_pibd(header_head)
+				} else {
+					self.request_state(&header_head)
+						.map(|peer| self.state_sync_peer = Some(peer))
+				};
+				if let Err(e) = res {
+					self.sync_state
+						.set_sync_error(chain::ErrorKind::SyncError(format!("{:?}", e)).into());
 				}
 
 				// to avoid the confusing log,
@@ -154,42 +186,77 @@ impl StateSync {
 					.update(SyncStatus::TxHashsetDownload(Default::default()));
 			}
 		}
+
+		if self.chain.desegmenter().read().is_some() {
+			self.continue_pibd(header_head);
+		}
 		true
 	}
 
-	fn request_state(&self, header_head: &chain::Tip) -> Result<Arc<Peer>, p2p::Error> {
+	// Start from the trusted snapshot instead of downloading the txhashset. On failure
+	// we fall back to the download.
+	fn load_utxo_snapshot(&self, snapshot: &UtxoSnapshotConfig) -> bool {
+		info!("state_sync: loading the utxo snapshot {}", snapshot.path);
+		// The tmp dir is in the node home dir, next to the chain data
+		let tmp_dir = self.chain.get_tmp_dir();
+		let home_dir = tmp_dir.parent().unwrap_or(&tmp_dir);
+		let res = utxo_snapshot::open_snapshot(snapshot, home_dir).and_then(|(hash, file)| {
+			self.chain
+				.load_utxo_snapshot(hash, file, self.sync_state.as_ref())
+				.map_err(|e| e.into())
+		});
+		match res {
+			Ok(()) => true,
+			Err(e) => {
+				self.chain.clean_txhashset_sandbox();
+				error!(
+					"state_sync: failed to load the utxo snapshot {}, {}",
+					snapshot.path, e
+				);
+				false
+			}
+		}
+	}
+
+	// Header of the txhashset archive the peers serve, at state_sync_threshold.
+	fn txhashset_head(&self, header_head: &chain::Tip) -> Result<BlockHeader, p2p::Error> {
 		let threshold = global::state_sync_threshold() as u64;
 		let archive_interval = global::txhashset_archive_interval();
 		let mut txhashset_height = header_head.height.saturating_sub(threshold);
 		txhashset_height = txhashset_height.saturating_sub(txhashset_height % archive_interval);
 
-		if let Some(peer) = self.peers.most_work_peer() {
-			// ask for txhashset at state_sync_threshold
-			let mut txhashset_head = self
+		let mut txhashset_head = self
+			.chain
+			.get_block_header(&header_head.prev_block_h)
+			.map_err(|e| {
+				let err_msg = format!(
+					"chain error during getting a block header {}, {}",
+					header_head.prev_block_h, e
+				);
+				error!("{}", err_msg);
+				p2p::Error::Internal(err_msg)
+			})?;
+		while txhashset_head.height > txhashset_height {
+			txhashset_head = self
 				.chain
-				.get_block_header(&header_head.prev_block_h)
+				.get_previous_header(&txhashset_head)
 				.map_err(|e| {
 					let err_msg = format!(
-						"chain error during getting a block header {}, {}",
-						header_head.prev_block_h, e
+						"chain error during getting a previous block header {}, {}",
+						txhashset_head.hash(),
+						e
 					);
 					error!("{}", err_msg);
 					p2p::Error::Internal(err_msg)
 				})?;
-			while txhashset_head.height > txhashset_height {
-				txhashset_head = self
-					.chain
-					.get_previous_header(&txhashset_head)
-					.map_err(|e| {
-						let err_msg = format!(
-							"chain error during getting a previous block header {}, {}",
-							txhashset_head.hash(),
-							e
-						);
-						error!("{}", err_msg);
-						p2p::Error::Internal(err_msg)
-					})?;
-			}
+		}
+		Ok(txhashset_head)
+	}
+
+	fn request_state(&self, header_head: &chain::Tip) -> Result<Arc<Peer>, p2p::Error> {
+		if let Some(peer) = self.peers.most_work_peer() {
+			// ask for txhashset at state_sync_threshold
+			let txhashset_head = self.txhashset_head(header_head)?;
 			let bhash = txhashset_head.hash();
 			debug!(
 				"state_sync: before txhashset request, header head: {} / {}, txhashset_head: {} / {}",
@@ -246,7 +313,113 @@ impl StateSync {
 		}
 	}
 
+	// Connected peers serving the txhashset segments, at the same height as us.
+	fn pibd_peers(&self, header_head: &chain::Tip) -> Vec<Arc<Peer>> {
+		self.peers
+			.connected_peers()
+			.into_iter()
+			.filter(|p| {
+				p.info.capabilities.contains(Capabilities::PIBD_HIST)
+					&& p.info.total_difficulty() >= header_head.total_difficulty
+			})
+			.collect()
+	}
+
+	fn start_pibd(&mut self, header_head: &chain::Tip) -> Result<(), p2p::Error> {
+		let txhashset_head = self.txhashset_head(header_head)?;
+		info!(
+			"state_sync: downloading txhashset segments at {} {}",
+			txhashset_head.height,
+			txhashset_head.hash()
+		);
+		self.pibd_requests.clear();
+		self.chain
+			.init_desegmenter(&txhashset_head)
+			.map_err(|e| p2p::Error::Internal(format!("unable to start txhashset download, {}", e)))
+	}
+
+	// Request the missing segments from the peers serving them, resending the
+	// ones not answered in time. Once all the segments are received, the
+	// txhashset is validated and installed.
+	fn continue_pibd(&mut self, header_head: &chain::Tip) {
+		let desegmenter = self.chain.desegmenter();
+		let (complete, (downloaded_size, total_size), block_hash, missing) = {
+			let desegmenter = desegmenter.read();
+			let desegmenter = match desegmenter.as_ref() {
+				Some(desegmenter) => desegmenter,
+				None => return,
+			};
+			(
+				desegmenter.is_complete(),
+				desegmenter.progress(),
+				desegmenter.header().hash(),
+				desegmenter.missing_segments(PIBD_MAX_REQUESTS),
+			)
+		};
+
+		if complete {
+			info!("state_sync: all txhashset segments received, validating");
+			self.pibd_requests.clear();
+			if let Err(e) = self
+				.chain
+				.txhashset_segments_write(self.sync_state.as_ref())
+			{
+				error!("state_sync: failed to write txhashset segments, {}", e);
+				self.chain.clean_txhashset_sandbox();
+				self.sync_state.set_sync_error(e);
+			}
+			return;
+		}
+
+		if let SyncStatus::TxHashsetDownload(prev) = self.sync_state.status() {
+			if downloaded_size != prev.downloaded_size {
+				self.sync_state
+					.update_txhashset_download(TxHashsetDownloadStats {
+						start_time: prev.start_time,
+						prev_update_time: prev.update_time,
+						update_time: Utc::now(),
+						prev_downloaded_size: prev.downloaded_size,
+						downloaded_size,
+						total_size,
+					});
+			}
+		}
+
+		let now = Utc::now();
+		let timeout = Duration::seconds(PIBD_REQUEST_TIMEOUT_SECS);
+		self.pibd_requests
+			.retain(|_, requested| now - *requested < timeout);
+
+		let peers = self.pibd_peers(header_head);
+		if peers.is_empty() {
+			return;
+		}
+		for (segment_type, id) in missing {
+			if self.pibd_requests.contains_key(&(segment_type, id.idx)) {
+				continue;
+			}
+			let peer = &peers[self.pibd_next_peer % peers.len()];
+			self.pibd_next_peer = self.pibd_next_peer.wrapping_add(1);
+			let res = match segment_type {
+				SegmentType::Output => peer.send_output_segment_request(block_hash, id),
+				SegmentType::RangeProof => peer.send_rangeproof_segment_request(block_hash, id),
+				SegmentType::Kernel => peer.send_kernel_segment_request(block_hash, id),
+			};
+			match res {
+				Ok(_) => {
+					self.pibd_requests.insert((segment_type, id.idx), now);
+				}
+				Err(e) => debug!(
+					"state_sync: failed to request {:?} segment {} from {:?}, {:?}",
+					segment_type, id.idx, peer.info.addr, e
+				),
+			}
+		}
+	}
+
 	fn state_sync_reset(&mut self) {
+		self.chain.reset_desegmenter();
+		self.pibd_requests.clear();
 		self.prev_state_sync = None;
 		self.state_sync_peer = None;
 		self.last_logged_time = 0;
@@ -17,6 +17,7 @@ use std::thread;
 use std::time;
 
 use crate::chain::{self, SyncState, SyncStatus};
+use crate::common::types::{HeaderSyncPipeline, UtxoSnapshotConfig};
 use crate::core::global;
 use crate::core::pow::Difficulty;
 use crate::grin::sync::body_sync::BodySync;
@@ -30,11 +31,26 @@ pub fn run_sync(
 	peers: Arc<p2p::Peers>,
 	chain: Arc<chain::Chain>,
 	stop_state: Arc<StopState>,
+	header_pipeline: Arc<HeaderSyncPipeline>,
+	header_sync_parallel_peers: usize,
+	header_cache_size: u64,
+	pibd_sync: bool,
+	utxo_snapshot: Option<UtxoSnapshotConfig>,
 ) -> std::io::Result<std::thread::JoinHandle<()>> {
 	thread::Builder::new()
 		.name("sync".to_string())
 		.spawn(move || {
-			let runner = SyncRunner::new(sync_state, peers, chain, stop_state);
+			let runner = SyncRunner::new(
+				sync_state,
+				peers,
+				chain,
+				stop_state,
+				header_pipeline,
+				header_sync_parallel_peers,
+				header_cache_size,
+				pibd_sync,
+				utxo_snapshot,
+			);
 			runner.sync_loop();
 		})
 }
@@ -44,6 +60,11 @@ pub struct SyncRunner {
 	peers: Arc<p2p::Peers>,
 	chain: Arc<chain::Chain>,
 	stop_state: Arc<StopState>,
+	header_pipeline: Arc<HeaderSyncPipeline>,
+	header_sync_parallel_peers: usize,
+	header_cache_size: u64,
+	pibd_sync: bool,
+	utxo_snapshot: Option<UtxoSnapshotConfig>,
 }
 
 impl SyncRunner {
@@ -52,12 +73,22 @@ impl SyncRunner {
 		peers: Arc<p2p::Peers>,
 		chain: Arc<chain::Chain>,
 		stop_state: Arc<StopState>,
+		header_pipeline: Arc<HeaderSyncPipeline>,
+		header_sync_parallel_peers: usize,
+		header_cache_size: u64,
+		pibd_sync: bool,
+		utxo_snapshot: Option<UtxoSnapshotConfig>,
 	) -> SyncRunner {
 		SyncRunner {
 			sync_state,
 			peers,
 			chain,
 			stop_state,
+			header_pipeline,
+			header_sync_parallel_peers,
+			header_cache_size,
+			pibd_sync,
+			utxo_snapshot,
 		}
 	}
 
@@ -125,6 +156,9 @@ impl SyncRunner {
 			self.sync_state.clone(),
 			self.peers.clone(),
 			self.chain.clone(),
+			self.header_pipeline.clone(),
+			self.header_sync_parallel_peers,
+			self.header_cache_size,
 		);
 		let mut body_sync = BodySync::new(
 			self.sync_state.clone(),
@@ -135,6 +169,8 @@ impl SyncRunner {
 			self.sync_state.clone(),
 			self.peers.clone(),
 			self.chain.clone(),
+			self.pibd_sync,
+			self.utxo_snapshot.clone(),
 		);
 
 		// Highest height seen on the network, generally useful for a fast test on
@@ -149,6 +185,9 @@ impl SyncRunner {
 
 			thread::sleep(time::Duration::from_millis(10));
 
+			// ends the expired tip freeze even if no new blocks are coming
+			self.chain.check_tip_freeze();
+
 			let currently_syncing = self.sync_state.is_syncing();
 
 			// check whether syncing is generally needed, when we compare our state with others
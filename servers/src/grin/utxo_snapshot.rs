@@ -0,0 +1,204 @@
+// Copyright 2021 The MWC Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Starting from a trusted txhashset snapshot (assumeutxo) and validating its
+//! history in background.
+
+use std::fs::{self, File};
+use std::io::{Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+use crate::chain::{self, SyncState};
+use crate::common::types::{Error, UtxoSnapshotConfig};
+use crate::core::core::hash::{Hash, HashWriter, Hashed};
+use crate::core::global::{self, ChainTypes};
+use crate::core::libtx::aggsig;
+use crate::core::ser::Writer;
+use crate::util::secp::key::PublicKey;
+use crate::util::secp::{Message, Signature};
+use crate::util::{self, StopState};
+
+/// Keys of the snapshot publishers trusted by the release, hex. None for now, the
+/// operator pins the publisher keys.
+const MAINNET_PUBLISHER_KEYS: &[&str] = &[];
+/// Keys of the floonet snapshot publishers trusted by the release, hex.
+const FLOONET_PUBLISHER_KEYS: &[&str] = &[];
+
+/// File with the snapshot publisher keys pinned by the operator, one hex key per line.
+/// It lives in the node home dir, apart from the snapshot config, so whoever hands out
+/// a snapshot can't vouch for it with a key of their own.
+pub const PINNED_KEYS_FILE: &str = "utxo_snapshot_trusted_keys";
+
+/// Path of the file with the pinned snapshot publisher keys in the node home dir.
+pub fn pinned_keys_path(home_dir: &Path) -> PathBuf {
+	home_dir.join(PINNED_KEYS_FILE)
+}
+
+// Keys of the trusted snapshot publishers: the ones of the release and the ones
+// pinned by the operator.
+fn trusted_publisher_keys(home_dir: &Path) -> Result<Vec<String>, Error> {
+	let release_keys = match global::get_chain_type() {
+		ChainTypes::Mainnet => MAINNET_PUBLISHER_KEYS,
+		ChainTypes::Floonet => FLOONET_PUBLISHER_KEYS,
+		_ => &[],
+	};
+	let mut keys: Vec<String> = release_keys.iter().map(|k| k.to_string()).collect();
+	let path = pinned_keys_path(home_dir);
+	if path.exists() {
+		let pinned = fs::read_to_string(&path)?;
+		keys.extend(
+			pinned
+				.lines()
+				.map(|l| l.trim())
+				.filter(|l| !l.is_empty() && !l.starts_with('#'))
+				.map(|l| l.to_string()),
+		);
+	}
+	Ok(keys)
+}
+
+/// Open the snapshot archive, checking it against its hash and the hash against the
+/// signature of one of the trusted publishers. Returns the hash of the block the
+/// snapshot is taken at.
+pub fn open_snapshot(config: &UtxoSnapshotConfig, home_dir: &Path) -> Result<(Hash, File), Error> {
+	let block_hash = Hash::from_hex(&config.block_hash)
+		.map_err(|e| Error::Configuration(format!("invalid snapshot block hash, {}", e)))?;
+	let archive_hash = Hash::from_hex(&config.archive_hash)
+		.map_err(|e| Error::Configuration(format!("invalid snapshot archive hash, {}", e)))?;
+
+	let trusted_keys = trusted_publisher_keys(home_dir)?;
+	if trusted_keys.is_empty() {
+		return Err(Error::Configuration(format!(
+			"no trusted snapshot publisher, pin the publisher key in {:?}",
+			pinned_keys_path(home_dir)
+		)));
+	}
+
+	{
+		let secp = util::static_secp_instance();
+		let secp = secp.lock();
+		let signature = util::from_hex(&config.signature)
+			.ok()
+			.and_then(|bytes| Signature::from_compact(&secp, &bytes).ok())
+			.ok_or_else(|| Error::Configuration("invalid snapshot signature".to_owned()))?;
+		let msg = Message::from_slice((block_hash, archive_hash).hash().as_bytes())
+			.map_err(|e| Error::General(format!("unable to build the snapshot message, {}", e)))?;
+		let mut signed = false;
+		for key in &trusted_keys {
+			let public_key = util::from_hex(key)
+				.ok()
+				.and_then(|bytes| PublicKey::from_slice(&secp, &bytes).ok())
+				.ok_or_else(|| {
+					Error::Configuration(format!("invalid trusted snapshot publisher key {}", key))
+				})?;
+			if aggsig::verify_single(
+				&secp,
+				&signature,
+				&msg,
+				None,
+				&public_key,
+				Some(&public_key),
+				false,
+			) {
+				signed = true;
+				break;
+			}
+		}
+		if !signed {
+			return Err(Error::Configuration(
+				"snapshot is not signed by a trusted publisher".to_owned(),
+			));
+		}
+	}
+
+	let mut file = File::open(&config.path)?;
+	let mut hasher = HashWriter::default();
+	let mut buf = vec![0u8; 1024 * 1024];
+	loop {
+		let n = file.read(&mut buf)?;
+		if n == 0 {
+			break;
+		}
+		hasher
+			.write_fixed_bytes(&buf[..n])
+			.map_err(|e| Error::General(format!("unable to hash the snapshot, {}", e)))?;
+	}
+	if hasher.into_hash() != archive_hash {
+		return Err(Error::Configuration(format!(
+			"snapshot {} doesn't match its archive hash",
+			config.path
+		)));
+	}
+	file.seek(SeekFrom::Start(0))?;
+
+	Ok((block_hash, file))
+}
+
+/// A process validating in background the history of the trusted txhashset snapshot
+/// the chain started from. The snapshot is loaded by the state sync, so we wait for
+/// the sync to be done. The validation resumes from its checkpoint after a restart.
+/// If the history is invalid the node is stopped, it must not keep following the
+/// chain from that state. The snapshot stays recorded, so the node stops again on
+/// restart until the chain data is removed.
+pub fn start_validator(
+	chain: Arc<chain::Chain>,
+	sync_state: Arc<SyncState>,
+	stop_state: Arc<StopState>,
+) -> std::io::Result<thread::JoinHandle<()>> {
+	debug!("Started utxo snapshot validator.");
+
+	thread::Builder::new()
+		.name("utxo_snapshot_validator".to_string())
+		.spawn(move || loop {
+			// Halt validator if we have been notified that we are stopping.
+			if stop_state.is_stopped() {
+				break;
+			}
+
+			// Let the state sync install the snapshot first.
+			if sync_state.is_syncing() {
+				thread::sleep(Duration::from_secs(1));
+				continue;
+			}
+
+			match chain.assumed_utxo() {
+				Ok(Some(assumed)) => {
+					info!(
+						"utxo_snapshot: validating the history of the snapshot at {} {}",
+						assumed.height, assumed.hash
+					);
+					match chain.validate_assumed_utxo(&chain::types::NoStatus) {
+						Ok(()) => info!("utxo_snapshot: the chain is fully validated"),
+						Err(e) => {
+							error!(
+								"utxo_snapshot: history of the snapshot at {} {} is invalid, {}. \
+								 Stopping the node, remove the chain data and sync again.",
+								assumed.height, assumed.hash, e
+							);
+							stop_state.stop();
+						}
+					}
+					break;
+				}
+				Ok(None) => break,
+				Err(e) => {
+					error!("utxo_snapshot: unable to read the snapshot state, {}", e);
+					break;
+				}
+			}
+		})
+}
@@ -44,6 +44,7 @@ mod mining;
 mod tor;
 
 pub use crate::common::stats::{DiffBlock, PeerStats, ServerStats, StratumStats, WorkerStats};
-pub use crate::common::types::{ServerConfig, StratumServerConfig};
+pub use crate::common::types::{SandboxConfig, ServerConfig, StratumServerConfig};
 pub use crate::core::global::{FLOONET_DNS_SEEDS, MAINNET_DNS_SEEDS};
+pub use crate::grin::preflight::run_preflight_checks;
 pub use crate::grin::server::{Server, ServerTxPool, ServerVerifierCache};
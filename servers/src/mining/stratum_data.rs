@@ -37,6 +37,8 @@ pub struct Worker {
 	pub create_time: i64,
 	pub agent: String,
 	pub login: Option<String>,
+	/// Miner gets the job updates instead of the full jobs
+	pub incremental_jobs: bool,
 	pub authenticated: bool,
 	tx: Arc<Tx>, // private, please use send_to method
 	kill_switch: Arc<RwLock<Option<oneshot::Sender<()>>>>,
@@ -51,6 +53,7 @@ impl Worker {
 			create_time: Utc::now().timestamp_millis(),
 			agent: String::from(""),
 			login: None,
+			incremental_jobs: false,
 			authenticated: false,
 			tx: Arc::new(tx),
 			kill_switch: Arc::new(RwLock::new(Some(kill_switch))),
@@ -63,6 +66,7 @@ impl Worker {
 		// updating only 'data' releated data
 		self.agent = worker.agent.clone();
 		self.login = worker.login.clone();
+		self.incremental_jobs = worker.incremental_jobs;
 		self.authenticated = worker.authenticated;
 	}
 
@@ -190,7 +194,13 @@ impl WorkersList {
 		self.update_stats(worker_id, |ws| ws.is_connected = false);
 	}
 
-	pub fn login(&self, worker_id: &usize, login: String, agent: String) -> bool {
+	pub fn login(
+		&self,
+		worker_id: &usize,
+		login: String,
+		agent: String,
+		incremental_jobs: bool,
+	) -> bool {
 		if let Some(mut worker) = self.get_worker(worker_id) {
 			worker.login = Some(login);
 
@@ -198,6 +208,7 @@ impl WorkersList {
 			// Here you can add you code and work with worker as long as you need. Here nothing is blocked
 
 			worker.agent = agent;
+			worker.incremental_jobs = incremental_jobs;
 			worker.authenticated = true;
 
 			// Apply what you changed to the workrer
@@ -238,6 +249,18 @@ impl WorkersList {
 		}
 	}
 
+	/// Send the job to all the workers, the workers that support the job updates get
+	/// the update if there is one.
+	pub fn broadcast_job(&self, job: String, update: Option<String>) {
+		for worker in self.workers_map.get_workers_list() {
+			let msg = match &update {
+				Some(update) if worker.incremental_jobs => update.clone(),
+				_ => job.clone(),
+			};
+			self.send_to(&worker.id, msg);
+		}
+	}
+
 	pub fn count(&self) -> usize {
 		self.workers_map.size()
 	}
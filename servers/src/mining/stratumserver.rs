@@ -26,6 +26,8 @@ use chrono::prelude::Utc;
 use serde;
 use serde_json;
 use serde_json::Value;
+use std::collections::HashSet;
+use std::io::Cursor;
 use std::net::{IpAddr, Ipv4Addr, SocketAddr};
 use std::sync::atomic::{AtomicI32, Ordering};
 use std::sync::Arc;
@@ -37,7 +39,8 @@ use crate::chain::{self, SyncState};
 use crate::common::stats::StratumStats;
 use crate::common::types::StratumServerConfig;
 use crate::core::core::hash::Hashed;
-use crate::core::core::Block;
+use crate::core::core::{Block, BlockHeader};
+use crate::core::ser::{ProtocolVersion, Readable, Reader, Writeable, Writer};
 use crate::core::stratum::connections;
 use crate::core::{pow, ser};
 use crate::keychain;
@@ -47,6 +50,14 @@ use crate::util::ToHex;
 use crate::{ServerTxPool, ServerVerifierCache};
 use std::cmp::min;
 
+/// How often the pool fees are checked against `job_refresh_fee_threshold`, ms
+const JOB_REFRESH_FEE_CHECK_MS: i64 = 1000;
+
+/// Maximum number of shares in a single `submitbatch` request
+const MAX_SHARES_PER_BATCH: usize = 1000;
+/// Maximum length of the packed shares hex string, a binary share is under 512 bytes
+const MAX_PACKED_SHARES_LEN: usize = MAX_SHARES_PER_BATCH * 512 * 2;
+
 // ----------------------------------------
 // http://www.jsonrpc.org/specification
 // RPC Methods
@@ -149,9 +160,12 @@ struct LoginParams {
 	login: String,
 	pass: String,
 	agent: String,
+	/// Miner applies the job updates, it gets the diff instead of the full job
+	#[serde(default)]
+	incremental_jobs: bool,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
 struct SubmitParams {
 	height: u64,
 	job_id: u64,
@@ -160,12 +174,200 @@ struct SubmitParams {
 	pow: Vec<u64>,
 }
 
+/// Binary share: height, job_id and nonce as u64, followed by the proof
+/// as it is serialized in the block header (edge_bits and bit packed nonces).
+impl Readable for SubmitParams {
+	fn read<R: Reader>(reader: &mut R) -> Result<SubmitParams, ser::Error> {
+		let height = reader.read_u64()?;
+		let job_id = reader.read_u64()?;
+		let nonce = reader.read_u64()?;
+		let proof = pow::Proof::read(reader)?;
+		Ok(SubmitParams {
+			height,
+			job_id,
+			nonce,
+			edge_bits: proof.edge_bits as u32,
+			pow: proof.nonces,
+		})
+	}
+}
+
+impl Writeable for SubmitParams {
+	fn write<W: Writer>(&self, writer: &mut W) -> Result<(), ser::Error> {
+		writer.write_u64(self.height)?;
+		writer.write_u64(self.job_id)?;
+		writer.write_u64(self.nonce)?;
+		pow::Proof {
+			edge_bits: self.edge_bits as u8,
+			nonces: self.pow.clone(),
+		}
+		.write(writer)
+	}
+}
+
+/// Batch of shares. Shares can be listed in the `submit` format or packed
+/// as hex encoded concatenation of binary shares, or both.
+#[derive(Serialize, Deserialize, Debug)]
+struct SubmitBatchParams {
+	#[serde(default, deserialize_with = "deserialize_shares")]
+	shares: Vec<SubmitParams>,
+	#[serde(default)]
+	packed: Option<String>,
+}
+
+/// Deserialize the listed shares, the batch is rejected as soon as it is over the limit
+fn deserialize_shares<'de, D>(deserializer: D) -> Result<Vec<SubmitParams>, D::Error>
+where
+	D: serde::Deserializer<'de>,
+{
+	struct SharesVisitor;
+
+	impl<'de> serde::de::Visitor<'de> for SharesVisitor {
+		type Value = Vec<SubmitParams>;
+
+		fn expecting(&self, formatter: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+			write!(formatter, "at most {} shares", MAX_SHARES_PER_BATCH)
+		}
+
+		fn visit_seq<A>(self, mut seq: A) -> Result<Self::Value, A::Error>
+		where
+			A: serde::de::SeqAccess<'de>,
+		{
+			let mut shares = vec![];
+			while let Some(share) = seq.next_element()? {
+				if shares.len() >= MAX_SHARES_PER_BATCH {
+					return Err(serde::de::Error::invalid_length(shares.len() + 1, &self));
+				}
+				shares.push(share);
+			}
+			Ok(shares)
+		}
+	}
+
+	deserializer.deserialize_seq(SharesVisitor)
+}
+
 #[derive(Serialize, Deserialize, Debug)]
 pub struct JobTemplate {
 	height: u64,
 	job_id: u64,
 	difficulty: u64,
+	/// Empty for the job update, the miner patches the pre_pow of the previous job
+	#[serde(default, skip_serializing_if = "String::is_empty")]
 	pre_pow: String,
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	update: Option<JobUpdate>,
+}
+
+/// Changes of a job compared to the previous job at the same height, sent instead of
+/// the full job to the miners that logged in with `incremental_jobs` when it is enabled.
+/// The coinbase is not included. The shares for the previous jobs at the height are
+/// still accepted. A miner that doesn't have the previous job requests the full one
+/// with `getjobtemplate`.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct JobUpdate {
+	prev_job_id: u64,
+	/// Changed bytes of the previous job pre_pow, (offset, hex bytes)
+	pre_pow_patch: Vec<(usize, String)>,
+	/// Excess of the added kernels, hex
+	added_kernels: Vec<String>,
+	/// Commitments of the added outputs, hex
+	added_outputs: Vec<String>,
+	/// Number of the kernels not in the job any more
+	removed_kernels: usize,
+}
+
+impl JobUpdate {
+	/// Changes of the transactions from prev to block, None if the transactions are
+	/// the same.
+	fn new(prev: &Block, block: &Block, prev_job_id: u64) -> Option<JobUpdate> {
+		let kernels = |b: &Block| -> HashSet<String> {
+			b.kernels()
+				.iter()
+				.filter(|k| !k.is_coinbase())
+				.map(|k| k.excess().to_hex())
+				.collect()
+		};
+		let prev_kernels = kernels(prev);
+		let new_kernels = kernels(block);
+		if prev_kernels == new_kernels {
+			return None;
+		}
+		let prev_outputs: HashSet<String> = prev
+			.outputs()
+			.iter()
+			.map(|o| o.commitment().to_hex())
+			.collect();
+		Some(JobUpdate {
+			prev_job_id,
+			pre_pow_patch: pre_pow_patch(
+				&header_pre_pow(&prev.header),
+				&header_pre_pow(&block.header),
+			),
+			added_kernels: block
+				.kernels()
+				.iter()
+				.filter(|k| !k.is_coinbase())
+				.map(|k| k.excess().to_hex())
+				.filter(|k| !prev_kernels.contains(k))
+				.collect(),
+			added_outputs: block
+				.outputs()
+				.iter()
+				.filter(|o| !o.is_coinbase())
+				.map(|o| o.commitment().to_hex())
+				.filter(|c| !prev_outputs.contains(c))
+				.collect(),
+			removed_kernels: prev_kernels.difference(&new_kernels).count(),
+		})
+	}
+}
+
+/// Job notification for the stratum clients
+fn job_request(job_template: &JobTemplate) -> String {
+	let job_template_json = serde_json::to_string(job_template).unwrap_or("{}".to_string());
+	// Issue #1159 - use a serde_json Value type to avoid extra quoting
+	let job_template_value: Value = serde_json::from_str(&job_template_json).unwrap_or(Value::Null);
+	let job_request = RpcRequest {
+		id: JsonId::StrId(String::from("Stratum")),
+		jsonrpc: String::from("2.0"),
+		method: String::from("job"),
+		params: Some(job_template_value),
+	};
+	serde_json::to_string(&job_request).unwrap_or("{}".to_string())
+}
+
+/// Block header serialized up to the nonce, the part of the header the miners hash
+fn header_pre_pow(bh: &BlockHeader) -> Vec<u8> {
+	let mut header_buf = vec![];
+	{
+		let mut writer = ser::BinWriter::default(&mut header_buf);
+		bh.write_pre_pow(&mut writer).unwrap();
+		bh.pow.write_pre_pow(&mut writer).unwrap();
+	}
+	header_buf
+}
+
+/// Changed byte ranges of the pre_pow, (offset, hex bytes). The whole pre_pow is
+/// replaced if its size changed.
+fn pre_pow_patch(prev: &[u8], pre_pow: &[u8]) -> Vec<(usize, String)> {
+	if prev.len() != pre_pow.len() {
+		return vec![(0, util::to_hex(pre_pow))];
+	}
+	let mut patch = vec![];
+	let mut i = 0;
+	while i < pre_pow.len() {
+		if prev[i] == pre_pow[i] {
+			i += 1;
+			continue;
+		}
+		let start = i;
+		while i < pre_pow.len() && prev[i] != pre_pow[i] {
+			i += 1;
+		}
+		patch.push((start, util::to_hex(&pre_pow[start..i])));
+	}
+	patch
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -187,6 +389,8 @@ struct State {
 	current_key_id: Option<keychain::Identifier>,
 	current_difficulty: u64,
 	minimum_share_difficulty: u64,
+	// changes of the last block version compared to the previous one
+	current_update: Option<JobUpdate>,
 }
 
 impl State {
@@ -197,6 +401,7 @@ impl State {
 			current_key_id: None,
 			current_difficulty: <u64>::max_value(),
 			minimum_share_difficulty: minimum_share_difficulty,
+			current_update: None,
 		}
 	}
 }
@@ -248,25 +453,19 @@ impl Handler {
 					Err(e)
 				}
 			},
-			"submit" => {
-				let res = self.handle_submit(request.params, worker_id);
-				// this key_id has been used now, reset
-				let res = match res {
-					Ok(ok) => {
-						self.ip_pool.report_ok_shares(ip);
-						Ok(ok)
-					}
-					Err(rpc_err) => {
-						if rpc_err.code != RpcError::too_late().code {
-							self.ip_pool.report_fail_noise(ip);
-						};
-						Err(rpc_err)
-					}
-				};
-				if let Ok((_, true)) = res {
-					self.current_state.write().current_key_id = None;
+			"submit" => match parse_params(request.params) {
+				Ok(params) => self.submit_share(params, worker_id, ip),
+				Err(e) => {
+					self.ip_pool.report_fail_noise(ip);
+					Err(e)
+				}
+			},
+			"submitbatch" => {
+				let res = self.handle_submit_batch(request.params, worker_id, ip);
+				if res.is_err() {
+					self.ip_pool.report_fail_noise(ip);
 				}
-				res.map(|(v, _)| v)
+				res
 			}
 			"keepalive" => self.handle_keepalive(),
 			"getjobtemplate" => {
@@ -307,7 +506,12 @@ impl Handler {
 	fn handle_login(&self, params: Option<Value>, worker_id: &usize) -> Result<Value, RpcError> {
 		// Note !!!! self.workers.login HAS to be there.
 		let params: LoginParams = parse_params(params)?;
-		if !self.workers.login(worker_id, params.login, params.agent) {
+		if !self.workers.login(
+			worker_id,
+			params.login,
+			params.agent,
+			params.incremental_jobs,
+		) {
 			return Ok("false".into()); // you migth change that response, Possible solution Error 'Unauthorized worker'
 		}
 		return Ok("ok".into());
@@ -367,21 +571,30 @@ impl Handler {
 		};
 
 		// Serialize the block header into pre and post nonce strings
-		let mut header_buf = vec![];
-		{
-			let mut writer = ser::BinWriter::default(&mut header_buf);
-			bh.write_pre_pow(&mut writer).unwrap();
-			bh.pow.write_pre_pow(&mut writer).unwrap();
-		}
-		let pre_pow = util::to_hex(&header_buf);
+		let pre_pow = util::to_hex(&header_pre_pow(&bh));
 		let job_template = JobTemplate {
 			height: bh.height,
 			job_id: job_id as u64,
 			difficulty,
 			pre_pow,
+			update: None,
 		};
 		return job_template;
 	}
+
+	// Build the update of the previous job for the miners that support it, None if the
+	// current job is not an update
+	fn build_job_update(&self) -> Option<JobTemplate> {
+		let state = self.current_state.read();
+		let update = state.current_update.clone()?;
+		Some(JobTemplate {
+			height: state.current_block_versions.last().unwrap().header.height,
+			job_id: state.current_block_versions.len() as u64 - 1,
+			difficulty: state.minimum_share_difficulty,
+			pre_pow: String::new(),
+			update: Some(update),
+		})
+	}
 	// Handle SUBMIT message
 	// params contains a solved block header
 	// We accept and log valid shares of all difficulty above configured minimum
@@ -389,12 +602,9 @@ impl Handler {
 	// network
 	fn handle_submit(
 		&self,
-		params: Option<Value>,
+		params: SubmitParams,
 		worker_id: usize,
 	) -> Result<(Value, bool), RpcError> {
-		// Validate parameters
-		let params: SubmitParams = parse_params(params)?;
-
 		let (b, header_height, minimum_share_difficulty, current_difficulty) = {
 			let state = self.current_state.read();
 
@@ -549,26 +759,75 @@ impl Handler {
 		));
 	} // handle submit a solution
 
+	// Submit a share and account the result for the worker IP
+	fn submit_share(
+		&self,
+		params: SubmitParams,
+		worker_id: usize,
+		ip: &String,
+	) -> Result<Value, RpcError> {
+		let res = match self.handle_submit(params, worker_id) {
+			Ok(ok) => {
+				self.ip_pool.report_ok_shares(ip);
+				Ok(ok)
+			}
+			Err(rpc_err) => {
+				if rpc_err.code != RpcError::too_late().code {
+					self.ip_pool.report_fail_noise(ip);
+				};
+				Err(rpc_err)
+			}
+		};
+		// this key_id has been used now, reset
+		if let Ok((_, true)) = res {
+			self.current_state.write().current_key_id = None;
+		}
+		res.map(|(v, _)| v)
+	}
+
+	// Handle SUBMITBATCH message. Shares are processed in order, the response
+	// has a result or an error for every share.
+	fn handle_submit_batch(
+		&self,
+		params: Option<Value>,
+		worker_id: usize,
+		ip: &String,
+	) -> Result<Value, RpcError> {
+		let params: SubmitBatchParams = parse_params(params)?;
+		let mut shares = params.shares;
+		if let Some(packed) = params.packed {
+			shares.extend(read_packed_shares(&packed)?);
+		}
+		if shares.is_empty() || shares.len() > MAX_SHARES_PER_BATCH {
+			return Err(RpcError::invalid_request());
+		}
+
+		let results = shares
+			.into_iter()
+			.map(|share| match self.submit_share(share, worker_id, ip) {
+				Ok(result) => serde_json::json!({ "result": result }),
+				Err(e) => serde_json::json!({ "error": Value::from(e) }),
+			})
+			.collect();
+		Ok(Value::Array(results))
+	}
+
 	fn broadcast_job(&self) {
 		debug!("broadcast job");
 		// Package new block into RpcRequest
 		let job_template = self.build_block_template();
-		let job_template_json = serde_json::to_string(&job_template).unwrap_or("{}".to_string());
-		// Issue #1159 - use a serde_json Value type to avoid extra quoting
-		let job_template_value: Value =
-			serde_json::from_str(&job_template_json).unwrap_or(Value::Null);
-		let job_request = RpcRequest {
-			id: JsonId::StrId(String::from("Stratum")),
-			jsonrpc: String::from("2.0"),
-			method: String::from("job"),
-			params: Some(job_template_value),
-		};
-		let job_request_json = serde_json::to_string(&job_request).unwrap_or("{}".to_string());
 		debug!(
 			"(Server ID: {}) sending block {} with id {} to stratum clients",
 			self.id, job_template.height, job_template.job_id,
 		);
-		self.workers.broadcast(job_request_json);
+		let job_request_json = job_request(&job_template);
+		// The miners that support the updates get only the changes
+		match self.build_job_update() {
+			Some(job_update) => self
+				.workers
+				.broadcast_job(job_request_json, Some(job_request(&job_update))),
+			None => self.workers.broadcast(job_request_json),
+		}
 	}
 
 	pub fn run(
@@ -591,20 +850,42 @@ impl Handler {
 		let mut next_worker_checking = Utc::now().timestamp_millis() + worker_checking_period;
 		let mut next_ip_pool_checking =
 			Utc::now().timestamp_millis() + self.config.ip_pool_ban_history_s * 1000 / 10;
+		// pool fees at the time the current job was built
+		let mut job_pool_fees: u64 = 0;
+		let mut next_fee_checking: i64 = 0;
 
 		loop {
 			// get the latest chain state
 			head = self.chain.head().unwrap();
 			let latest_hash = head.last_block_h;
 
+			// Check if enough fees were added to the pool since the current job was built
+			let mut fees_refresh = false;
+			if config.job_refresh_fee_threshold > 0 && current_hash == latest_hash {
+				let cur_time = Utc::now().timestamp_millis();
+				if cur_time >= next_fee_checking {
+					next_fee_checking = cur_time + JOB_REFRESH_FEE_CHECK_MS;
+					let new_fees = tx_pool
+						.read()
+						.txpool
+						.total_fees()
+						.saturating_sub(job_pool_fees);
+					if new_fees >= config.job_refresh_fee_threshold {
+						debug!("{} of new fees in the pool, refreshing the job", new_fees);
+						fees_refresh = true;
+					}
+				}
+			}
+
 			// Build a new block if:
 			//    There is a new block on the chain
 			// or We are rebuilding the current one to include new transactions
+			// or Enough new fees are waiting in the pool
 			// and there is at least one worker connected
-			if (current_hash != latest_hash || Utc::now().timestamp() >= deadline)
+			if (current_hash != latest_hash || Utc::now().timestamp() >= deadline || fees_refresh)
 				&& self.workers.count() > 0
 			{
-				{
+				let send_job = {
 					debug!("resend updated block");
 					let wallet_listener_url = if !config.burn_reward {
 						Some(config.wallet_listener_url.clone())
@@ -614,6 +895,7 @@ impl Handler {
 					// If this is a new block, clear the current_block version history
 					let clear_blocks = current_hash != latest_hash;
 
+					job_pool_fees = tx_pool.read().txpool.total_fees();
 					// Build the new block (version)
 					let (new_block, block_fees) = mine_block::get_block(
 						&self.chain,
@@ -623,38 +905,62 @@ impl Handler {
 						wallet_listener_url,
 					);
 
-					{
-						let mut state = self.current_state.write();
+					// With the incremental jobs, a rebuild at the same height is only sent
+					// if the transactions changed, along with the changes
+					let update = if config.incremental_jobs && !clear_blocks {
+						let state = self.current_state.read();
+						JobUpdate::new(
+							state.current_block_versions.last().unwrap(),
+							&new_block,
+							state.current_block_versions.len() as u64 - 1,
+						)
+					} else {
+						None
+					};
 
-						state.current_difficulty =
-							(new_block.header.total_difficulty() - head.total_difficulty).to_num();
+					// set a new deadline for rebuilding with fresh transactions
+					deadline = Utc::now().timestamp() + config.attempt_time_per_block as i64;
 
-						state.current_key_id = block_fees.key_id();
+					if config.incremental_jobs && !clear_blocks && update.is_none() {
+						debug!("no new transactions, keeping the current job");
+						false
+					} else {
+						{
+							let mut state = self.current_state.write();
 
-						current_hash = latest_hash;
-						// set the minimum acceptable share difficulty for this block
-						state.minimum_share_difficulty =
-							cmp::min(config.minimum_share_difficulty, state.current_difficulty);
-					}
+							state.current_difficulty = (new_block.header.total_difficulty()
+								- head.total_difficulty)
+								.to_num();
 
-					// set a new deadline for rebuilding with fresh transactions
-					deadline = Utc::now().timestamp() + config.attempt_time_per_block as i64;
+							state.current_key_id = block_fees.key_id();
+
+							current_hash = latest_hash;
+							// set the minimum acceptable share difficulty for this block
+							state.minimum_share_difficulty =
+								cmp::min(config.minimum_share_difficulty, state.current_difficulty);
+						}
 
-					self.workers.update_block_height(new_block.header.height);
-					self.workers
-						.update_network_difficulty(self.current_state.read().current_difficulty);
+						self.workers.update_block_height(new_block.header.height);
+						self.workers.update_network_difficulty(
+							self.current_state.read().current_difficulty,
+						);
 
-					{
-						let mut state = self.current_state.write();
+						{
+							let mut state = self.current_state.write();
 
-						if clear_blocks {
-							state.current_block_versions.clear();
+							if clear_blocks {
+								state.current_block_versions.clear();
+							}
+							state.current_block_versions.push(new_block);
+							state.current_update = update;
 						}
-						state.current_block_versions.push(new_block);
+						true
 					}
-					// Send this job to all connected workers
+				};
+				// Send this job to all connected workers
+				if send_job {
+					self.broadcast_job();
 				}
-				self.broadcast_job();
 			}
 
 			// Check workers login statuses and do IP pool maintaince
@@ -980,9 +1286,100 @@ where
 		.ok_or_else(RpcError::invalid_request)
 }
 
+/// Decode the hex encoded concatenation of binary shares
+fn read_packed_shares(packed: &str) -> Result<Vec<SubmitParams>, RpcError> {
+	if packed.len() > MAX_PACKED_SHARES_LEN {
+		return Err(RpcError::invalid_request());
+	}
+	let bytes = util::from_hex(packed).map_err(|_| RpcError::invalid_request())?;
+	let mut cursor = Cursor::new(&bytes);
+	let mut shares = vec![];
+	while (cursor.position() as usize) < bytes.len() {
+		if shares.len() >= MAX_SHARES_PER_BATCH {
+			return Err(RpcError::invalid_request());
+		}
+		let share = ser::deserialize(&mut cursor, ProtocolVersion::local())
+			.map_err(|_| RpcError::invalid_request())?;
+		shares.push(share);
+	}
+	Ok(shares)
+}
+
 #[cfg(test)]
 mod tests {
 	use super::*;
+	use crate::core::global;
+
+	#[test]
+	fn test_read_packed_shares() {
+		global::set_local_chain_type(global::ChainTypes::AutomatedTesting);
+		let shares = vec![
+			SubmitParams {
+				height: 10,
+				job_id: 0,
+				nonce: 123456,
+				edge_bits: 29,
+				pow: vec![1, 2, 3, 4, 5, 6, 7, (1 << 29) - 1],
+			},
+			SubmitParams {
+				height: 10,
+				job_id: 1,
+				nonce: u64::max_value(),
+				edge_bits: 31,
+				pow: vec![8, 16, 32, 64, 128, 256, 512, (1 << 31) - 1],
+			},
+		];
+		let mut bytes = vec![];
+		for share in &shares {
+			bytes.extend(ser::ser_vec(share, ProtocolVersion::local()).unwrap());
+		}
+		// 3 x u64, edge_bits and 8 x 29 bits of the nonces
+		assert_eq!(
+			ser::ser_vec(&shares[0], ProtocolVersion::local())
+				.unwrap()
+				.len(),
+			54
+		);
+
+		let packed = bytes.to_hex();
+		assert_eq!(read_packed_shares(&packed).unwrap(), shares);
+
+		// batch in both formats
+		let json = format!(
+			r#"{{"shares":[{{"height":10,"job_id":2,"nonce":1,"edge_bits":29,"pow":[1,2,3,4,5,6,7,8]}}],"packed":"{}"}}"#,
+			packed
+		);
+		let params: SubmitBatchParams = serde_json::from_str(&json).unwrap();
+		assert_eq!(params.shares.len(), 1);
+		assert_eq!(
+			read_packed_shares(&params.packed.unwrap()).unwrap().len(),
+			2
+		);
+
+		// truncated share
+		assert!(read_packed_shares(&packed[..packed.len() - 2]).is_err());
+		assert!(read_packed_shares("zz").is_err());
+
+		// batches over the limit are rejected
+		let share = r#"{"height":10,"job_id":2,"nonce":1,"edge_bits":29,"pow":[1,2,3,4,5,6,7,8]}"#;
+		let json = format!(
+			r#"{{"shares":[{}]}}"#,
+			vec![share; MAX_SHARES_PER_BATCH + 1].join(",")
+		);
+		assert!(serde_json::from_str::<SubmitBatchParams>(&json).is_err());
+		let json = format!(
+			r#"{{"shares":[{}]}}"#,
+			vec![share; MAX_SHARES_PER_BATCH].join(",")
+		);
+		assert_eq!(
+			serde_json::from_str::<SubmitBatchParams>(&json)
+				.unwrap()
+				.shares
+				.len(),
+			MAX_SHARES_PER_BATCH
+		);
+		assert!(read_packed_shares(&"00".repeat(MAX_PACKED_SHARES_LEN / 2 + 1)).is_err());
+	}
 
 	/// Tests deserializing an `RpcRequest` given a String as the id.
 	#[test]
@@ -1123,4 +1520,73 @@ mod tests {
 
 		assert_eq!(expected_deserialized, actual_deserialized);
 	}
+
+	#[test]
+	fn test_job_update() {
+		use crate::core::core::{
+			KernelFeatures, Output, OutputFeatures, TransactionBody, TxKernel,
+		};
+		use crate::util::secp::pedersen::{Commitment, RangeProof};
+
+		let kernel = |features, n: u8| {
+			let mut k = TxKernel::with_features(features);
+			k.excess = Commitment::from_vec(vec![n; 33]);
+			k
+		};
+		let output = |features, n: u8| {
+			Output::new(
+				features,
+				Commitment::from_vec(vec![n; 33]),
+				RangeProof::zero(),
+			)
+		};
+		let block = |body: TransactionBody| Block {
+			header: Default::default(),
+			body,
+		};
+
+		let prev = block(
+			TransactionBody::empty()
+				.with_kernel(kernel(KernelFeatures::Coinbase, 1))
+				.with_kernel(kernel(KernelFeatures::Plain { fee: 1 }, 2))
+				.with_output(output(OutputFeatures::Coinbase, 1))
+				.with_output(output(OutputFeatures::Plain, 2)),
+		);
+		// Only the coinbase changed
+		let same = block(
+			TransactionBody::empty()
+				.with_kernel(kernel(KernelFeatures::Coinbase, 3))
+				.with_kernel(kernel(KernelFeatures::Plain { fee: 1 }, 2))
+				.with_output(output(OutputFeatures::Coinbase, 3))
+				.with_output(output(OutputFeatures::Plain, 2)),
+		);
+		assert_eq!(JobUpdate::new(&prev, &same, 0), None);
+
+		let next = block(
+			TransactionBody::empty()
+				.with_kernel(kernel(KernelFeatures::Coinbase, 3))
+				.with_kernel(kernel(KernelFeatures::Plain { fee: 2 }, 4))
+				.with_output(output(OutputFeatures::Coinbase, 3))
+				.with_output(output(OutputFeatures::Plain, 4)),
+		);
+		let update = JobUpdate::new(&prev, &next, 3).unwrap();
+		assert_eq!(update.prev_job_id, 3);
+		assert_eq!(update.added_kernels, vec![vec![4u8; 33].to_hex()]);
+		assert_eq!(update.added_outputs, vec![vec![4u8; 33].to_hex()]);
+		assert_eq!(update.removed_kernels, 1);
+		// Same header
+		assert!(update.pre_pow_patch.is_empty());
+	}
+
+	#[test]
+	fn test_pre_pow_patch() {
+		let prev = vec![1u8, 2, 3, 4, 5, 6];
+		assert!(pre_pow_patch(&prev, &prev).is_empty());
+		assert_eq!(
+			pre_pow_patch(&prev, &[1, 9, 9, 4, 5, 7]),
+			vec![(1, "0909".to_string()), (5, "07".to_string())]
+		);
+		// Size changed, the whole pre_pow is sent
+		assert_eq!(pre_pow_patch(&prev, &[1, 2]), vec![(0, "0102".to_string())]);
+	}
 }
@@ -23,6 +23,7 @@ use crate::p2p;
 use crate::servers::ServerConfig;
 use crate::util::file::get_first_line;
 use failure::Fail;
+use serde_json::{json, Value};
 use term;
 
 pub fn client_command(client_args: &ArgMatches<'_>, global_config: GlobalConfig) -> i32 {
@@ -55,6 +56,27 @@ pub fn client_command(client_args: &ArgMatches<'_>, global_config: GlobalConfig)
 				panic!("Invalid peer address format");
 			}
 		}
+		("rotate_api_secret", Some(args)) => {
+			let grace_period = parse_grace_period(args);
+			rotate_secret(
+				&server_config,
+				"rotate_api_secret",
+				grace_period,
+				api_secret,
+			);
+		}
+		("rotate_foreign_api_secret", Some(args)) => {
+			let grace_period = parse_grace_period(args);
+			rotate_secret(
+				&server_config,
+				"rotate_foreign_api_secret",
+				grace_period,
+				api_secret,
+			);
+		}
+		("rebuild_kernel_index", Some(_)) => {
+			rebuild_kernel_index(&server_config, api_secret);
+		}
 		_ => panic!("Unknown client command, use 'mwc help client' for details"),
 	}
 	0
@@ -155,6 +177,79 @@ pub fn list_connected_peers(config: &ServerConfig, api_secret: Option<String>) {
 	e.reset().unwrap();
 }
 
+fn parse_grace_period(args: &ArgMatches<'_>) -> Option<u64> {
+	args.value_of("grace_period").map(|v| {
+		v.parse::<u64>()
+			.unwrap_or_else(|_| panic!("Invalid grace period {}", v))
+	})
+}
+
+/// Rotate the API secret through the owner API. New secret is written into the secret file by the node.
+pub fn rotate_secret(
+	config: &ServerConfig,
+	method: &str,
+	grace_period: Option<u64>,
+	api_secret: Option<String>,
+) {
+	let mut e = term::stdout().unwrap();
+	let url = format!("http://{}/v2/owner", config.api_http_addr);
+	let request = json!({
+		"jsonrpc": "2.0",
+		"method": method,
+		"id": 1,
+		"params": [grace_period]
+	});
+	let res = api::client::post::<_, Value>(url.as_str(), api_secret, &request)
+		.map_err(|e| Error::API(url, e));
+
+	match res {
+		Ok(response) => match response.get("result").and_then(|r| r.get("Ok")) {
+			Some(rotation) => {
+				writeln!(
+					e,
+					"New secret is written into {}",
+					rotation["secret_path"].as_str().unwrap_or("")
+				)
+				.unwrap();
+				writeln!(
+					e,
+					"Previous secret is accepted until {}",
+					rotation["previous_secret_valid_until"]
+						.as_str()
+						.unwrap_or("")
+				)
+				.unwrap();
+			}
+			None => writeln!(e, "Failed to rotate the secret, {}", response).unwrap(),
+		},
+		Err(err) => writeln!(e, "Failed to rotate the secret, {}", err).unwrap(),
+	};
+	e.reset().unwrap();
+}
+
+/// Rebuild the kernel excess index through the owner API.
+pub fn rebuild_kernel_index(config: &ServerConfig, api_secret: Option<String>) {
+	let mut e = term::stdout().unwrap();
+	let url = format!("http://{}/v2/owner", config.api_http_addr);
+	let request = json!({
+		"jsonrpc": "2.0",
+		"method": "rebuild_kernel_index",
+		"id": 1,
+		"params": []
+	});
+	let res = api::client::post::<_, Value>(url.as_str(), api_secret, &request)
+		.map_err(|e| Error::API(url, e));
+
+	match res {
+		Ok(response) => match response.get("result").and_then(|r| r.get("Ok")) {
+			Some(_) => writeln!(e, "Kernel index is rebuilt").unwrap(),
+			None => writeln!(e, "Failed to rebuild the kernel index, {}", response).unwrap(),
+		},
+		Err(err) => writeln!(e, "Failed to rebuild the kernel index, {}", err).unwrap(),
+	};
+	e.reset().unwrap();
+}
+
 fn get_status_from_node(
 	config: &ServerConfig,
 	api_secret: Option<String>,
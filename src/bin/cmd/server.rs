@@ -17,9 +17,11 @@ use std::process::exit;
 use std::thread;
 use std::time::Duration;
 
+use chrono::DateTime;
 use clap::ArgMatches;
 use ctrlc;
 
+use crate::built_info;
 use crate::config::GlobalConfig;
 use crate::core::global;
 use crate::p2p::Seeding;
@@ -35,8 +37,9 @@ pub fn start_server(
 	config: servers::ServerConfig,
 	logs_rx: Option<mpsc::Receiver<LogEntry>>,
 	allow_to_stop: bool,
+	force_takeover: bool,
 ) {
-	start_server_tui(config, logs_rx, allow_to_stop);
+	start_server_tui(config, logs_rx, allow_to_stop, force_takeover);
 	// Just kill process for now, otherwise the process
 	// hangs around until sigint because the API server
 	// currently has no shutdown facility
@@ -47,6 +50,7 @@ fn start_server_tui(
 	config: servers::ServerConfig,
 	logs_rx: Option<mpsc::Receiver<LogEntry>>,
 	allow_to_stop: bool,
+	force_takeover: bool,
 ) {
 	// Run the UI controller.. here for now for simplicity to access
 	// everything it might need
@@ -62,6 +66,7 @@ fn start_server_tui(
 				controller.run(serv);
 			},
 			allow_to_stop,
+			force_takeover,
 		)
 		.map_err(|e| error!("Unable to start MWC in UI mode, {}", e))
 		.expect("Unable to start MWC in UI mode");
@@ -82,6 +87,7 @@ fn start_server_tui(
 				serv.stop();
 			},
 			allow_to_stop,
+			force_takeover,
 		)
 		.map_err(|e| error!("Unable to start MWC w/o UI mode, {}", e))
 		.expect("Unable to start MWC w/o UI mode");
@@ -100,6 +106,8 @@ pub fn server_command(
 	// just get defaults from the global config
 	let mut server_config = global_config.members.as_ref().unwrap().server.clone();
 	let mut allow_to_stop = false;
+	let mut force_takeover = false;
+	let mut skip_preflight = false;
 
 	if let Some(a) = server_args {
 		if let Some(port) = a.value_of("port") {
@@ -129,6 +137,8 @@ pub fn server_command(
 		}
 
 		allow_to_stop = a.is_present("allow_to_stop");
+		force_takeover = a.is_present("force_takeover");
+		skip_preflight = a.is_present("skip_preflight");
 	}
 
 	if allow_to_stop {
@@ -138,7 +148,19 @@ pub fn server_command(
 	if let Some(a) = server_args {
 		match a.subcommand() {
 			("run", _) => {
-				start_server(server_config, logs_rx, allow_to_stop);
+				if !preflight(&server_config, skip_preflight) {
+					return 1;
+				}
+				start_server(server_config, logs_rx, allow_to_stop, force_takeover);
+			}
+			("rebuild_header_mmr", _) => {
+				match servers::Server::rebuild_header_mmr(&server_config) {
+					Ok(height) => println!("Header MMR is rebuilt up to height {}", height),
+					Err(e) => {
+						println!("Unable to rebuild the header MMR, {}", e);
+						return 1;
+					}
+				}
 			}
 			("", _) => {
 				println!("Subcommand required, use 'mwc help server' for details");
@@ -152,7 +174,29 @@ pub fn server_command(
 			}
 		}
 	} else {
-		start_server(server_config, logs_rx, allow_to_stop);
+		if !preflight(&server_config, skip_preflight) {
+			return 1;
+		}
+		start_server(server_config, logs_rx, allow_to_stop, force_takeover);
 	}
 	0
 }
+
+/// Run the pre-flight checks of the host, false if the server must not be started.
+fn preflight(config: &servers::ServerConfig, skip_preflight: bool) -> bool {
+	let build_time = DateTime::parse_from_rfc2822(built_info::BUILT_TIME_UTC)
+		.map(|t| t.timestamp())
+		.ok();
+	match servers::run_preflight_checks(config, build_time) {
+		Ok(_) => true,
+		Err(e) if skip_preflight => {
+			warn!("Ignoring the failed pre-flight checks, {}", e);
+			true
+		}
+		Err(e) => {
+			error!("{}", e);
+			eprintln!("{}", e);
+			false
+		}
+	}
+}
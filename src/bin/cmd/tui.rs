@@ -0,0 +1,44 @@
+// Copyright 2021 The MWC Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+/// Remote TUI command processing
+use clap::ArgMatches;
+
+use crate::config::GlobalConfig;
+use crate::tui::remote::RemoteNode;
+use crate::tui::ui;
+use crate::util::file::get_first_line;
+use grin_util::logger::LogEntry;
+use std::sync::mpsc;
+
+/// Run the TUI for the remote node. Node is monitored through the owner API.
+pub fn tui_command(
+	tui_args: &ArgMatches<'_>,
+	global_config: GlobalConfig,
+	logs_rx: Option<mpsc::Receiver<LogEntry>>,
+) -> i32 {
+	let server_config = global_config.members.unwrap().server;
+	let node_url = tui_args.value_of("remote").unwrap();
+	let api_secret_path = match tui_args.value_of("api_secret_path") {
+		Some(path) => Some(path.to_string()),
+		None => server_config.api_secret_path.clone(),
+	};
+	let api_secret = get_first_line(api_secret_path);
+
+	let mut controller = ui::Controller::new(logs_rx.unwrap()).unwrap_or_else(|e| {
+		panic!("Error loading UI controller: {}", e);
+	});
+	controller.run_remote(RemoteNode::new(node_url, api_secret));
+	0
+}
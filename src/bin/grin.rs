@@ -21,7 +21,8 @@ extern crate clap;
 extern crate log;
 use crate::config::config::SERVER_CONFIG_FILE_NAME;
 use crate::core::global;
-use crate::util::init_logger;
+use crate::util::{init_logger, ToHex};
+use blake2_rfc::blake2b::blake2b;
 use clap::App;
 use grin_api as api;
 use grin_chain as chain;
@@ -39,6 +40,7 @@ pub mod tui;
 // include build information
 pub mod built_info {
 	include!(concat!(env!("OUT_DIR"), "/built.rs"));
+	include!(concat!(env!("OUT_DIR"), "/provenance.rs"));
 }
 
 pub fn info_strings() -> (String, String) {
@@ -58,6 +60,35 @@ pub fn info_strings() -> (String, String) {
 	)
 }
 
+/// Provenance of this binary. The reproducibility hash covers everything that determines
+/// the binary except the build environment paths, which the reproducible build script fixes.
+pub fn build_info() -> api::BuildInfo {
+	let inputs = format!(
+		"{}\n{:?}\n{:?}\n{}\n{}\n{}\n{}\n{:?}\n{}",
+		built_info::PKG_VERSION,
+		built_info::GIT_COMMIT,
+		built_info::GIT_DIRTY,
+		built_info::RUSTC_VERSION,
+		built_info::TARGET,
+		built_info::PROFILE,
+		built_info::FEATURES_STR,
+		built_info::SOURCE_DATE_EPOCH,
+		built_info::DEPENDENCIES_STR,
+	);
+	api::BuildInfo {
+		version: built_info::PKG_VERSION.to_string(),
+		git_commit: built_info::GIT_COMMIT.map(|c| c.to_string()),
+		git_dirty: built_info::GIT_DIRTY,
+		rustc_version: built_info::RUSTC_VERSION.to_string(),
+		target: built_info::TARGET.to_string(),
+		profile: built_info::PROFILE.to_string(),
+		features: built_info::FEATURES_STR.to_string(),
+		build_time: built_info::BUILT_TIME_UTC.to_string(),
+		reproducible: built_info::SOURCE_DATE_EPOCH.is_some(),
+		reproducibility_hash: blake2b(32, &[], inputs.as_bytes()).as_bytes().to_hex(),
+	}
+}
+
 fn log_build_info() {
 	let (basic_info, detailed_info) = info_strings();
 	info!("{}", basic_info);
@@ -66,6 +97,7 @@ fn log_build_info() {
 
 fn log_feature_flags() {
 	info!("Feature: NRD kernel enabled: {}", global::is_nrd_enabled());
+	info!("Feature: low memory mode: {}", global::is_low_memory_mode());
 }
 
 fn main() {
@@ -78,12 +110,27 @@ fn real_main() -> i32 {
 	let args = App::from_yaml(yml)
 		.version(built_info::PKG_VERSION)
 		.get_matches();
+
+	if args.is_present("build_info") {
+		match serde_json::to_string_pretty(&build_info()) {
+			Ok(json) => {
+				println!("{}", json);
+				return 0;
+			}
+			Err(e) => {
+				eprintln!("Unable to print the build info, {}", e);
+				return 1;
+			}
+		}
+	}
 	let node_config;
 
 	let chain_type = if args.is_present("floonet") {
 		global::ChainTypes::Floonet
 	} else if args.is_present("usernet") {
 		global::ChainTypes::UserTesting
+	} else if args.is_present("customnet") {
+		global::ChainTypes::CustomTesting
 	} else {
 		global::ChainTypes::Mainnet
 	};
@@ -126,6 +173,10 @@ fn real_main() -> i32 {
 	let mut config = node_config.clone().unwrap();
 	let mut logging_config = config.members.as_mut().unwrap().logging.clone().unwrap();
 	logging_config.tui_running = config.members.as_mut().unwrap().server.run_tui;
+	if let ("tui", Some(_)) = args.subcommand() {
+		// Remote TUI shows the logs in the logs view
+		logging_config.tui_running = Some(true);
+	}
 
 	let (logs_tx, logs_rx) = if logging_config.tui_running.unwrap() {
 		let (logs_tx, logs_rx) = mpsc::sync_channel::<LogEntry>(200);
@@ -145,11 +196,21 @@ fn real_main() -> i32 {
 	};
 
 	log_build_info();
+	api::set_build_info(build_info());
 
 	// Initialize our global chain_type and feature flags (NRD kernel support currently).
 	// These are read via global and not read from config beyond this point.
-	global::init_global_chain_type(config.members.unwrap().server.chain_type);
+	let server_config = config.members.unwrap().server;
+	global::init_global_chain_type(server_config.chain_type);
 	info!("Chain: {:?}", global::get_chain_type());
+	if global::get_chain_type() == global::ChainTypes::CustomTesting {
+		let overrides = server_config.consensus_overrides.unwrap_or_default();
+		if let Err(e) = overrides.validate() {
+			panic!("Invalid consensus overrides, {}", e);
+		}
+		info!("Consensus overrides: {:?}", overrides);
+		global::init_global_consensus_overrides(overrides);
+	}
 	match global::get_chain_type() {
 		global::ChainTypes::Mainnet => {
 			// Set various mainnet specific feature flags.
@@ -160,6 +221,7 @@ fn real_main() -> i32 {
 			global::init_global_nrd_enabled(true);
 		}
 	}
+	global::init_global_low_memory_mode(server_config.low_memory_mode.unwrap_or(false));
 	log_feature_flags();
 
 	// Execute subcommand
@@ -172,6 +234,9 @@ fn real_main() -> i32 {
 		// client commands and options
 		("client", Some(client_args)) => cmd::client_command(client_args, node_config.unwrap()),
 
+		// TUI for the remote node
+		("tui", Some(tui_args)) => cmd::tui_command(tui_args, node_config.unwrap(), logs_rx),
+
 		// clean command
 		("clean", _) => {
 			let db_root_path = node_config.unwrap().members.unwrap().server.db_root;
@@ -182,6 +247,27 @@ fn real_main() -> i32 {
 			}
 		}
 
+		// identity regeneration, node must be stopped
+		("regenerate_identity", _) => {
+			let server_config = node_config.unwrap().members.unwrap().server;
+			match servers::Server::regenerate_identity(&server_config) {
+				Ok(removed) => {
+					if removed.is_empty() {
+						println!(
+							"Node doesn't have identity yet, it will be generated on the start"
+						);
+					} else {
+						println!("Node identity is removed, new identity will be generated on the next start");
+					}
+					0
+				}
+				Err(e) => {
+					println!("Unable to regenerate the node identity, {}", e);
+					1
+				}
+			}
+		}
+
 		// If nothing is specified, try to just use the config file instead
 		// this could possibly become the way to configure most things
 		// with most command line options being phased out
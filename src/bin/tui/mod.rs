@@ -21,6 +21,7 @@ mod logs;
 mod menu;
 mod mining;
 mod peers;
+pub mod remote;
 mod status;
 pub mod table;
 mod types;
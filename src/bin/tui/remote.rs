@@ -0,0 +1,171 @@
+// Copyright 2021 The MWC Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Remote mode of the TUI. Stats are requested from the owner API of the node
+//! instead of the in-process server, so headless nodes can be monitored from
+//! another host. Mining and tx pool stats are not available through the owner API.
+
+use crate::api;
+use crate::chain::{BlockTimingStats, OrphanStats, SyncStatus, TxHashsetDownloadStats};
+use crate::core::core::hash::{Hash, ZERO_HASH};
+use crate::core::pow::Difficulty;
+use crate::servers::common::stats::{ChainStats, DiffStats};
+use crate::servers::{PeerStats, ServerStats, StratumStats};
+use chrono::prelude::Utc;
+use grin_p2p::types::{Direction, PeerInfoDisplayLegacy};
+use grin_p2p::{SeenCacheStats, TxRelayStats};
+use serde::de::DeserializeOwned;
+use serde_json::{json, Value};
+use std::sync::Arc;
+
+/// Owner API client of the remote node
+pub struct RemoteNode {
+	/// Owner API v2 url
+	url: String,
+	/// Owner API secret
+	api_secret: Option<String>,
+}
+
+impl RemoteNode {
+	/// Create client for the node API, for example http://127.0.0.1:3413
+	pub fn new(node_url: &str, api_secret: Option<String>) -> RemoteNode {
+		RemoteNode {
+			url: format!("{}/v2/owner", node_url.trim_end_matches('/')),
+			api_secret,
+		}
+	}
+
+	/// Owner API url
+	pub fn url(&self) -> &str {
+		&self.url
+	}
+
+	fn call<T: DeserializeOwned>(&self, method: &str) -> Result<T, String> {
+		let request = json!({
+			"jsonrpc": "2.0",
+			"method": method,
+			"id": 1,
+			"params": []
+		});
+		let response: Value = api::client::post(&self.url, self.api_secret.clone(), &request)
+			.map_err(|e| format!("Unable to call {} at {}, {}", method, self.url, e))?;
+		let result = response
+			.get("result")
+			.and_then(|r| r.get("Ok"))
+			.ok_or_else(|| format!("Call {} is failed, {}", method, response))?;
+		serde_json::from_value(result.clone())
+			.map_err(|e| format!("Unable to parse {} response, {}", method, e))
+	}
+
+	/// Build the server stats from the node status and connected peers
+	pub fn get_server_stats(&self) -> Result<ServerStats, String> {
+		let status: api::Status = self.call("get_status")?;
+		let peers: Vec<PeerInfoDisplayLegacy> = self.call("get_connected_peers")?;
+
+		let sync_status = sync_status_from_api(&status.sync_status, status.sync_info.as_ref());
+		// Owner API doesn't report the block time, the time of the update is used instead
+		let chain_stats = ChainStats {
+			height: status.tip.height,
+			last_block_h: Hash::from_hex(&status.tip.last_block_pushed).unwrap_or(ZERO_HASH),
+			total_difficulty: Difficulty::from_num(status.tip.total_difficulty),
+			latest_timestamp: Utc::now(),
+		};
+		let mut header_stats = chain_stats.clone();
+		if let SyncStatus::HeaderSync { current_height, .. } = sync_status {
+			header_stats.height = current_height;
+		}
+
+		Ok(ServerStats {
+			peer_count: status.connections,
+			chain_stats,
+			header_stats,
+			orphan_stats: OrphanStats::default(),
+			block_timing_stats: BlockTimingStats::default(),
+			sync_status,
+			stratum_stats: Arc::new(StratumStats::default()),
+			peer_stats: peers.iter().map(peer_stats_from_api).collect(),
+			diff_stats: DiffStats {
+				height: status.tip.height,
+				last_blocks: vec![],
+				average_block_time: 0,
+				average_difficulty: 0,
+				window_size: 0,
+			},
+			tx_stats: None,
+			disk_usage_gb: "n/a".to_string(),
+			libp2p_stats: status.libp2p,
+			seen_cache_stats: SeenCacheStats::default(),
+		})
+	}
+}
+
+fn sync_info_u64(sync_info: Option<&Value>, name: &str) -> u64 {
+	sync_info
+		.and_then(|i| i.get(name))
+		.and_then(|v| v.as_u64())
+		.unwrap_or(0)
+}
+
+/// Reverse of the sync status conversion that the status API does
+fn sync_status_from_api(sync_status: &str, sync_info: Option<&Value>) -> SyncStatus {
+	match sync_status {
+		"no_sync" => SyncStatus::NoSync,
+		"awaiting_peers" => SyncStatus::AwaitingPeers(false),
+		"header_sync" => SyncStatus::HeaderSync {
+			current_height: sync_info_u64(sync_info, "current_height"),
+			highest_height: sync_info_u64(sync_info, "highest_height"),
+		},
+		"txhashset_download" => SyncStatus::TxHashsetDownload(TxHashsetDownloadStats {
+			downloaded_size: sync_info_u64(sync_info, "downloaded_size"),
+			total_size: sync_info_u64(sync_info, "total_size"),
+			..TxHashsetDownloadStats::default()
+		}),
+		"txhashset_rangeproofs_validation" => SyncStatus::TxHashsetRangeProofsValidation {
+			rproofs: sync_info_u64(sync_info, "rproofs"),
+			rproofs_total: sync_info_u64(sync_info, "rproofs_total"),
+		},
+		"txhashset_kernels_validation" => SyncStatus::TxHashsetKernelsValidation {
+			kernels: sync_info_u64(sync_info, "kernels"),
+			kernels_total: sync_info_u64(sync_info, "kernels_total"),
+		},
+		"body_sync" => SyncStatus::BodySync {
+			current_height: sync_info_u64(sync_info, "current_height"),
+			highest_height: sync_info_u64(sync_info, "highest_height"),
+		},
+		"shutdown" => SyncStatus::Shutdown,
+		_ => SyncStatus::TxHashsetSetup,
+	}
+}
+
+fn peer_stats_from_api(peer: &PeerInfoDisplayLegacy) -> PeerStats {
+	let direction = match peer.direction {
+		Direction::Inbound => "Inbound",
+		Direction::Outbound => "Outbound",
+		Direction::InboundTor => "Inbound  (TOR)",
+		Direction::OutboundTor => "Outbound (TOR)",
+	};
+	PeerStats {
+		state: "Connected".to_string(),
+		addr: peer.addr.clone(),
+		version: peer.version,
+		user_agent: peer.user_agent.clone(),
+		total_difficulty: peer.total_difficulty.to_num(),
+		height: peer.height,
+		direction: direction.to_string(),
+		last_seen: Utc::now(),
+		sent_bytes_per_sec: 0,
+		received_bytes_per_sec: 0,
+		tx_relay: TxRelayStats::default(),
+	}
+}
@@ -142,6 +142,21 @@ impl TUIStatusView {
 						.child(TextView::new("Disk Usage (GB):              "))
 						.child(TextView::new("0").with_name("disk_usage")),
 				)
+				.child(
+					LinearLayout::new(Orientation::Horizontal)
+						.child(TextView::new("Libp2p Connections:           "))
+						.child(TextView::new("Not running").with_name("libp2p_connections")),
+				)
+				.child(
+					LinearLayout::new(Orientation::Horizontal)
+						.child(TextView::new("Libp2p Messages:              "))
+						.child(TextView::new("  ").with_name("libp2p_messages")),
+				)
+				.child(
+					LinearLayout::new(Orientation::Horizontal)
+						.child(TextView::new("Duplicate Blocks/Txs:         "))
+						.child(TextView::new("  ").with_name("seen_duplicates")),
+				)
 				.child(
 					LinearLayout::new(Orientation::Horizontal).child(TextView::new(
 						"--------------------------------------------------------",
@@ -213,6 +228,16 @@ impl TUIStatusView {
 						.child(TextView::new("0").with_name("stem_pool_kernels"))
 						.child(TextView::new(")")),
 				)
+				.child(
+					LinearLayout::new(Orientation::Horizontal)
+						.child(TextView::new("Stem Pool Embargo:            "))
+						.child(TextView::new("  ").with_name("stem_pool_embargo")),
+				)
+				.child(
+					LinearLayout::new(Orientation::Horizontal)
+						.child(TextView::new("Dandelion Stem Paths:         "))
+						.child(TextView::new("  ").with_name("dandelion_stem_paths")),
+				)
 				.child(
 					LinearLayout::new(Orientation::Horizontal).child(TextView::new(
 						"--------------------------------------------------------",
@@ -248,6 +273,42 @@ impl TUIStatusListener for TUIStatusView {
 		c.call_on_name("disk_usage", |t: &mut TextView| {
 			t.set_content(stats.disk_usage_gb.clone());
 		});
+		c.call_on_name("seen_duplicates", |t: &mut TextView| {
+			t.set_content(format!(
+				"{}/{} (cross network {})",
+				stats.seen_cache_stats.block_duplicates,
+				stats.seen_cache_stats.tx_duplicates,
+				stats.seen_cache_stats.cross_network_duplicates
+			));
+		});
+		match &stats.libp2p_stats {
+			Some(libp2p_stats) => {
+				c.call_on_name("libp2p_connections", |t: &mut TextView| {
+					t.set_content(format!(
+						"{} (dialing {})",
+						libp2p_stats.connections, libp2p_stats.dials_in_flight
+					));
+				});
+				c.call_on_name("libp2p_messages", |t: &mut TextView| {
+					t.set_content(format!(
+						"received {}, accepted {}, rejected {}, in/out {}/{} bytes",
+						libp2p_stats.messages_received,
+						libp2p_stats.messages_accepted,
+						libp2p_stats.messages_rejected,
+						libp2p_stats.bytes_in,
+						libp2p_stats.bytes_out
+					));
+				});
+			}
+			None => {
+				c.call_on_name("libp2p_connections", |t: &mut TextView| {
+					t.set_content("Not running");
+				});
+				c.call_on_name("libp2p_messages", |t: &mut TextView| {
+					t.set_content("  ");
+				});
+			}
+		}
 		c.call_on_name("tip_hash", |t: &mut TextView| {
 			t.set_content(stats.chain_stats.last_block_h.to_string() + "...");
 		});
@@ -285,6 +346,28 @@ impl TUIStatusListener for TUIStatusView {
 			c.call_on_name("stem_pool_kernels", |t: &mut TextView| {
 				t.set_content(tx_stats.stem_pool_kernels.to_string());
 			});
+			c.call_on_name("stem_pool_embargo", |t: &mut TextView| {
+				let stem_pool = &tx_stats.stem_pool;
+				t.set_content(match stem_pool.next_embargo_expiry {
+					Some(expiry) => format!(
+						"weight {}, next expiry in {}s",
+						stem_pool.total_weight,
+						(expiry - Utc::now()).num_seconds().max(0)
+					),
+					None => "-".to_string(),
+				});
+			});
+			c.call_on_name("dandelion_stem_paths", |t: &mut TextView| {
+				let d = &tx_stats.dandelion_stats;
+				t.set_content(format!(
+					"{} relayed, {} completed (avg {}s, max {}s), {} embargo expired",
+					d.stem_relayed,
+					d.stem_completed,
+					d.stem_secs_avg(),
+					d.stem_secs_max,
+					d.embargo_expired
+				));
+			});
 		}
 	}
 }
@@ -999,6 +999,7 @@ mod test {
 	use crate::tui::table::TableView;
 	use chrono::Utc;
 	use grin_core::ser::ProtocolVersion;
+	use grin_p2p::TxRelayStats;
 	use grin_servers::PeerStats;
 	use std::cmp::Ordering;
 
@@ -1060,6 +1061,7 @@ mod test {
 				last_seen: Utc::now(),
 				sent_bytes_per_sec: 0,
 				received_bytes_per_sec: 0,
+				tx_relay: TxRelayStats::default(),
 			}
 		}
 	}
@@ -31,13 +31,15 @@ use cursive::views::{
 };
 use cursive::Cursive;
 use cursive::CursiveExt;
-use std::sync::mpsc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Arc};
 use std::{thread, time};
 
 use super::constants::MAIN_MENU;
 use crate::built_info;
 use crate::servers::Server;
 use crate::tui::constants::{ROOT_STACK, VIEW_BASIC_STATUS, VIEW_MINING, VIEW_PEER_SYNC};
+use crate::tui::remote::RemoteNode;
 use crate::tui::types::{TUIStatusListener, UIMessage};
 use crate::tui::{logs, menu, mining, peers, status, version};
 use grin_core::global;
@@ -221,4 +223,47 @@ impl Controller {
 		}
 		server.stop();
 	}
+
+	/// Run the controller as a client of the remote node. Node is not stopped on exit.
+	/// The owner API calls block, so the stats are requested by a background thread.
+	pub fn run_remote(&mut self, node: RemoteNode) {
+		warn!("Monitoring remote node {}", node.url());
+		let stop = Arc::new(AtomicBool::new(false));
+		let stats_stop = stop.clone();
+		let ui_tx = self.ui.ui_tx.clone();
+		let stats_thread = thread::Builder::new()
+			.name("tui_remote_stats".to_string())
+			.spawn(move || {
+				let stat_update_interval = time::Duration::from_secs(1);
+				while !stats_stop.load(Ordering::Relaxed) {
+					match node.get_server_stats() {
+						Ok(stats) => {
+							if ui_tx.send(UIMessage::UpdateStatus(stats)).is_err() {
+								break;
+							}
+						}
+						Err(e) => warn!("Unable to get the remote node status, {}", e),
+					}
+					thread::sleep(stat_update_interval);
+				}
+			});
+		if let Err(e) = &stats_thread {
+			error!("Unable to start the remote node stats thread, {}", e);
+		}
+
+		let delay = time::Duration::from_millis(50);
+		while self.ui.step() {
+			if let Some(message) = self.rx.try_iter().next() {
+				match message {
+					ControllerMessage::Shutdown => {
+						self.ui.stop();
+						break;
+					}
+				}
+			}
+			thread::sleep(delay);
+		}
+		// The thread might wait for the node response, it is not joined
+		stop.store(true, Ordering::Relaxed);
+	}
 }
@@ -16,7 +16,9 @@
 
 use built;
 
+use chrono::{TimeZone, Utc};
 use std::env;
+use std::fs;
 use std::path::{Path, PathBuf};
 use std::process::Command;
 
@@ -49,4 +51,77 @@ fn main() {
 		Path::new(env!("CARGO_MANIFEST_DIR")),
 		Path::new(&out_dir_path),
 	);
+
+	// Reproducible builds set SOURCE_DATE_EPOCH (the commit time), so the binary
+	// doesn't depend on the time it was built at.
+	let source_date_epoch = env::var("SOURCE_DATE_EPOCH")
+		.ok()
+		.and_then(|v| v.parse::<i64>().ok());
+	if let Some(epoch) = source_date_epoch {
+		if let Ok(built_file) = fs::read_to_string(&out_dir_path) {
+			let built_file: Vec<String> = built_file
+				.lines()
+				.map(|line| {
+					if line.starts_with("pub const BUILT_TIME_UTC") {
+						format!(
+							"pub const BUILT_TIME_UTC: &str = \"{}\";",
+							Utc.timestamp(epoch, 0).to_rfc2822()
+						)
+					} else {
+						line.to_string()
+					}
+				})
+				.collect();
+			let _ = fs::write(&out_dir_path, built_file.join("\n"));
+		}
+	}
+
+	// Provenance of the build, reported by 'mwc --build-info' and the owner API
+	let git_commit = git_output(&["rev-parse", "HEAD"]);
+	let git_dirty = git_output(&["status", "--porcelain", "--untracked-files=no"])
+		.map(|status| !status.is_empty());
+	let provenance = format!(
+		"/// Git commit the binary was built from\n\
+		 pub const GIT_COMMIT: Option<&str> = {:?};\n\
+		 /// Whether the sources had uncommitted changes\n\
+		 pub const GIT_DIRTY: Option<bool> = {:?};\n\
+		 /// SOURCE_DATE_EPOCH of a reproducible build\n\
+		 pub const SOURCE_DATE_EPOCH: Option<i64> = {:?};\n",
+		git_commit, git_dirty, source_date_epoch
+	);
+	let provenance_path = format!("{}{}", env::var("OUT_DIR").unwrap(), "/provenance.rs");
+	fs::write(&provenance_path, provenance).expect("failed to write provenance.rs");
+
+	// The embedded commit must follow checkouts and new commits. Listing the paths replaces
+	// the cargo default (any package file), so the sources and the lock file are listed too.
+	if let Some(git_dir) = git_output(&["rev-parse", "--git-dir"]) {
+		let git_dir = Path::new(env!("CARGO_MANIFEST_DIR")).join(git_dir);
+		println!("cargo:rerun-if-changed={}", git_dir.join("HEAD").display());
+		println!("cargo:rerun-if-changed={}", git_dir.join("index").display());
+		println!(
+			"cargo:rerun-if-changed={}",
+			git_dir.join("packed-refs").display()
+		);
+		if let Some(head_ref) = git_output(&["symbolic-ref", "-q", "HEAD"]) {
+			println!(
+				"cargo:rerun-if-changed={}",
+				git_dir.join(head_ref).display()
+			);
+		}
+	}
+	println!("cargo:rerun-if-changed=src");
+	println!("cargo:rerun-if-changed=Cargo.toml");
+	println!("cargo:rerun-if-changed=Cargo.lock");
+	println!("cargo:rerun-if-env-changed=SOURCE_DATE_EPOCH");
+}
+
+fn git_output(args: &[&str]) -> Option<String> {
+	Command::new("git")
+		.args(args)
+		.current_dir(env!("CARGO_MANIFEST_DIR"))
+		.output()
+		.ok()
+		.filter(|output| output.status.success())
+		.and_then(|output| String::from_utf8(output.stdout).ok())
+		.map(|output| output.trim().to_string())
 }
@@ -0,0 +1,447 @@
+// Copyright 2021 The MWC Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Encryption at rest of the LMDB stores and of the PMMR files. The key is loaded from
+//! a key file or from an OS keystore entry.
+//! LMDB values are sealed with XChaCha20-Poly1305. Db keys are mostly public chain data
+//! (hashes, commitments, heights) and are kept as they are, so the prefix iteration keeps
+//! working. Stores with sensitive keys (the peer addresses) mask them with
+//! `Store::to_masked_key`.
+//! The PMMR hash and data files are read at random positions, so they are XORed with a
+//! XChaCha20 keystream at the file offset instead (see `FileCipher`). The leaf, prune and
+//! size files only hold positions and sizes and are not encrypted.
+
+use std::fs::{self, OpenOptions};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::path::Path;
+
+use chacha20::stream_cipher::{NewStreamCipher, SyncStreamCipher, SyncStreamCipherSeek};
+use chacha20::XChaCha20;
+use chacha20poly1305::aead::generic_array::GenericArray;
+use chacha20poly1305::aead::{Aead, NewAead};
+use chacha20poly1305::XChaCha20Poly1305;
+use rand::{thread_rng, Rng};
+
+use crate::core::core::hash::{Hash, HashWriter};
+use crate::core::ser::Writer;
+use crate::lmdb::Error;
+use crate::util;
+
+/// Size of the encryption key, bytes
+pub const KEY_SIZE: usize = 32;
+/// Size of the nonce prepended to every encrypted value, bytes
+const NONCE_SIZE: usize = 24;
+/// Service of the keys stored in the OS keystore
+const KEYSTORE_SERVICE: &str = "mwc-node";
+
+/// Key to encrypt the values and to mask the keys of the stores.
+pub struct EncryptionKey {
+	cipher: XChaCha20Poly1305,
+	mask_secret: Hash,
+	file_key: Hash,
+}
+
+impl EncryptionKey {
+	/// Build the key from its raw bytes.
+	pub fn from_bytes(key: &[u8]) -> Result<EncryptionKey, Error> {
+		if key.len() != KEY_SIZE {
+			return Err(Error::OtherErr(format!(
+				"encryption key must be {} bytes, got {}",
+				KEY_SIZE,
+				key.len()
+			)));
+		}
+		// Keys are masked and PMMR files are encrypted with secrets derived from the key,
+		// not with the key itself.
+		Ok(EncryptionKey {
+			cipher: XChaCha20Poly1305::new(GenericArray::from_slice(key)),
+			mask_secret: derive_secret(b"mwc_store_key_mask", key)?,
+			file_key: derive_secret(b"mwc_store_file_key", key)?,
+		})
+	}
+
+	/// Load the key from a key file holding the hex encoded key.
+	pub fn from_file<P: AsRef<Path>>(path: P) -> Result<EncryptionKey, Error> {
+		let path = path.as_ref();
+		let hex = fs::read_to_string(path).map_err(|e| {
+			Error::FileErr(format!("Unable to read key file {}, {}", path.display(), e))
+		})?;
+		let key = util::from_hex(hex.trim())
+			.map_err(|e| Error::OtherErr(format!("Invalid key file {}, {}", path.display(), e)))?;
+		EncryptionKey::from_bytes(&key)
+	}
+
+	/// Load the key from the key file, generating a new random key file if there is none.
+	pub fn load_or_create<P: AsRef<Path>>(path: P) -> Result<EncryptionKey, Error> {
+		let path = path.as_ref();
+		if !path.exists() {
+			let mut key = [0u8; KEY_SIZE];
+			thread_rng().fill(&mut key);
+			let mut options = OpenOptions::new();
+			options.write(true).create_new(true);
+			#[cfg(unix)]
+			{
+				use std::os::unix::fs::OpenOptionsExt;
+				options.mode(0o600);
+			}
+			options
+				.open(path)
+				.and_then(|mut file| file.write_all(util::to_hex(&key).as_bytes()))
+				.map_err(|e| {
+					Error::FileErr(format!(
+						"Unable to create key file {}, {}",
+						path.display(),
+						e
+					))
+				})?;
+			info!("Generated the db encryption key file {}", path.display());
+		}
+		EncryptionKey::from_file(path)
+	}
+
+	/// Load the key from the OS keystore entry `name` (the macOS keychain, or the Secret
+	/// Service through `secret-tool` on Linux), storing a new random key there if there is none.
+	pub fn load_or_create_in_keystore(name: &str) -> Result<EncryptionKey, Error> {
+		let hex = match keystore::read(name)? {
+			Some(hex) => hex,
+			None => {
+				let mut key = [0u8; KEY_SIZE];
+				thread_rng().fill(&mut key);
+				let hex = util::to_hex(&key);
+				keystore::write(name, &hex)?;
+				info!(
+					"Generated the db encryption key in the OS keystore entry {}",
+					name
+				);
+				hex
+			}
+		};
+		let key = util::from_hex(hex.trim()).map_err(|e| {
+			Error::OtherErr(format!(
+				"Invalid key in the OS keystore entry {}, {}",
+				name, e
+			))
+		})?;
+		EncryptionKey::from_bytes(&key)
+	}
+
+	/// Encrypt a value, the random nonce is prepended to the ciphertext.
+	pub fn encrypt(&self, value: &[u8]) -> Result<Vec<u8>, Error> {
+		let mut nonce = [0u8; NONCE_SIZE];
+		thread_rng().fill(&mut nonce);
+		let ciphertext = self
+			.cipher
+			.encrypt(GenericArray::from_slice(&nonce), value)
+			.map_err(|_| Error::OtherErr("Unable to encrypt the db value".to_owned()))?;
+		let mut res = Vec::with_capacity(NONCE_SIZE + ciphertext.len());
+		res.extend_from_slice(&nonce);
+		res.extend_from_slice(&ciphertext);
+		Ok(res)
+	}
+
+	/// Decrypt a value produced by `encrypt`.
+	pub fn decrypt(&self, value: &[u8]) -> Result<Vec<u8>, Error> {
+		if value.len() < NONCE_SIZE {
+			return Err(Error::SerErr("Encrypted db value is too short".to_owned()));
+		}
+		let (nonce, ciphertext) = value.split_at(NONCE_SIZE);
+		self.cipher
+			.decrypt(GenericArray::from_slice(nonce), ciphertext)
+			.map_err(|_| {
+				Error::SerErr("Unable to decrypt the db value, wrong encryption key?".to_owned())
+			})
+	}
+
+	/// Mask a db key identifier. The same identifier always gives the same masked one,
+	/// so the values can still be looked up, but the identifier can't be recovered.
+	pub fn mask(&self, k: &[u8]) -> Result<Vec<u8>, Error> {
+		let mut hasher = HashWriter::default();
+		hasher
+			.write_fixed_bytes(&self.mask_secret)
+			.and_then(|_| hasher.write_fixed_bytes(k))
+			.map_err(|e| Error::SerErr(format!("{}", e)))?;
+		Ok(hasher.into_hash().to_vec())
+	}
+
+	/// Keystream cipher of the PMMR file at `path`. The keystream depends on the file name
+	/// and on its parent dir (`output/pmmr_data.bin`), so the file can be moved along with
+	/// its dir, as the txhashset sandbox is.
+	pub fn file_cipher(&self, path: &Path) -> Result<FileCipher, Error> {
+		let dir = path
+			.parent()
+			.and_then(|p| p.file_name())
+			.unwrap_or_default();
+		let name = path.file_name().unwrap_or_default();
+		let id = format!("{}/{}", dir.to_string_lossy(), name.to_string_lossy());
+		let nonce = derive_secret(b"mwc_store_file_nonce", id.as_bytes())?;
+		let mut res = FileCipher {
+			key: [0u8; KEY_SIZE],
+			nonce: [0u8; NONCE_SIZE],
+		};
+		res.key.copy_from_slice(self.file_key.as_bytes());
+		res.nonce.copy_from_slice(&nonce.as_bytes()[..NONCE_SIZE]);
+		Ok(res)
+	}
+}
+
+fn derive_secret(label: &[u8], data: &[u8]) -> Result<Hash, Error> {
+	let mut hasher = HashWriter::default();
+	hasher
+		.write_fixed_bytes(label)
+		.and_then(|_| hasher.write_fixed_bytes(data))
+		.map_err(|e| Error::SerErr(format!("{}", e)))?;
+	Ok(hasher.into_hash())
+}
+
+/// Keystream cipher of a PMMR file. The bytes at a file offset are XORed with the
+/// XChaCha20 keystream at the same offset, so the elements keep their offsets and can be
+/// read at random positions. The same operation encrypts and decrypts.
+/// Rewound and rewritten bytes reuse the keystream, which only exposes the difference of
+/// two pieces of public chain data.
+#[derive(Clone)]
+pub struct FileCipher {
+	key: [u8; KEY_SIZE],
+	nonce: [u8; NONCE_SIZE],
+}
+
+impl FileCipher {
+	/// Apply the keystream to `data`, found at `offset` in the file.
+	pub fn apply(&self, offset: u64, data: &mut [u8]) {
+		let mut cipher = XChaCha20::new(
+			GenericArray::from_slice(&self.key),
+			GenericArray::from_slice(&self.nonce),
+		);
+		cipher.seek(offset);
+		cipher.apply_keystream(data);
+	}
+
+	/// Apply the keystream to the whole file in place.
+	pub fn apply_to_file(&self, path: &Path) -> io::Result<()> {
+		let mut file = OpenOptions::new().read(true).write(true).open(path)?;
+		let mut buf = vec![0u8; 1024 * 1024];
+		let mut offset = 0;
+		loop {
+			file.seek(SeekFrom::Start(offset))?;
+			let n = file.read(&mut buf)?;
+			if n == 0 {
+				break;
+			}
+			self.apply(offset, &mut buf[..n]);
+			file.seek(SeekFrom::Start(offset))?;
+			file.write_all(&buf[..n])?;
+			offset += n as u64;
+		}
+		file.sync_all()
+	}
+}
+
+/// Reader of a PMMR file from its start, decrypting it if there is a cipher.
+pub struct CipherReader<R> {
+	inner: R,
+	cipher: Option<FileCipher>,
+	offset: u64,
+}
+
+impl<R: Read> CipherReader<R> {
+	/// Read the file from `inner`, positioned at the start of the file.
+	pub fn new(inner: R, cipher: Option<FileCipher>) -> CipherReader<R> {
+		CipherReader {
+			inner,
+			cipher,
+			offset: 0,
+		}
+	}
+}
+
+impl<R: Read> Read for CipherReader<R> {
+	fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+		let n = self.inner.read(buf)?;
+		if let Some(ref cipher) = self.cipher {
+			cipher.apply(self.offset, &mut buf[..n]);
+		}
+		self.offset += n as u64;
+		Ok(n)
+	}
+}
+
+/// Writer of a PMMR file from its start, encrypting it if there is a cipher.
+pub struct CipherWriter<W> {
+	inner: W,
+	cipher: Option<FileCipher>,
+	offset: u64,
+}
+
+impl<W: Write> CipherWriter<W> {
+	/// Write the file to `inner`, positioned at the start of the file.
+	pub fn new(inner: W, cipher: Option<FileCipher>) -> CipherWriter<W> {
+		CipherWriter {
+			inner,
+			cipher,
+			offset: 0,
+		}
+	}
+}
+
+impl<W: Write> Write for CipherWriter<W> {
+	fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+		match self.cipher {
+			Some(ref cipher) => {
+				let mut data = buf.to_vec();
+				cipher.apply(self.offset, &mut data);
+				self.inner.write_all(&data)?;
+			}
+			None => self.inner.write_all(buf)?,
+		}
+		self.offset += buf.len() as u64;
+		Ok(buf.len())
+	}
+
+	fn flush(&mut self) -> io::Result<()> {
+		self.inner.flush()
+	}
+}
+
+#[cfg(target_os = "macos")]
+mod keystore {
+	use super::KEYSTORE_SERVICE;
+	use crate::lmdb::Error;
+	use std::process::Command;
+
+	// Exit code of `security` when the keychain item doesn't exist
+	const ITEM_NOT_FOUND: i32 = 44;
+
+	pub fn read(name: &str) -> Result<Option<String>, Error> {
+		let output = Command::new("security")
+			.args(&[
+				"find-generic-password",
+				"-s",
+				KEYSTORE_SERVICE,
+				"-a",
+				name,
+				"-w",
+			])
+			.output()
+			.map_err(|e| Error::OtherErr(format!("Unable to run security, {}", e)))?;
+		match output.status.code() {
+			Some(0) => Ok(Some(
+				String::from_utf8_lossy(&output.stdout).trim().to_owned(),
+			)),
+			Some(ITEM_NOT_FOUND) => Ok(None),
+			_ => Err(Error::OtherErr(format!(
+				"Unable to read the keychain item {}, {}",
+				name,
+				String::from_utf8_lossy(&output.stderr).trim()
+			))),
+		}
+	}
+
+	pub fn write(name: &str, hex: &str) -> Result<(), Error> {
+		let output = Command::new("security")
+			.args(&[
+				"add-generic-password",
+				"-s",
+				KEYSTORE_SERVICE,
+				"-a",
+				name,
+				"-w",
+				hex,
+			])
+			.output()
+			.map_err(|e| Error::OtherErr(format!("Unable to run security, {}", e)))?;
+		if output.status.success() {
+			Ok(())
+		} else {
+			Err(Error::OtherErr(format!(
+				"Unable to store the keychain item {}, {}",
+				name,
+				String::from_utf8_lossy(&output.stderr).trim()
+			)))
+		}
+	}
+}
+
+#[cfg(all(unix, not(target_os = "macos")))]
+mod keystore {
+	use super::KEYSTORE_SERVICE;
+	use crate::lmdb::Error;
+	use std::io::Write;
+	use std::process::{Command, Stdio};
+
+	pub fn read(name: &str) -> Result<Option<String>, Error> {
+		let output = Command::new("secret-tool")
+			.args(&["lookup", "service", KEYSTORE_SERVICE, "account", name])
+			.output()
+			.map_err(|e| Error::OtherErr(format!("Unable to run secret-tool, {}", e)))?;
+		if output.status.success() {
+			Ok(Some(
+				String::from_utf8_lossy(&output.stdout).trim().to_owned(),
+			))
+		} else if output.stdout.is_empty() && output.stderr.is_empty() {
+			// secret-tool fails silently when there is no such secret
+			Ok(None)
+		} else {
+			Err(Error::OtherErr(format!(
+				"Unable to read the keystore secret {}, {}",
+				name,
+				String::from_utf8_lossy(&output.stderr).trim()
+			)))
+		}
+	}
+
+	pub fn write(name: &str, hex: &str) -> Result<(), Error> {
+		let err = |e: std::io::Error| Error::OtherErr(format!("Unable to run secret-tool, {}", e));
+		// The secret is passed on stdin, not on the command line.
+		let mut child = Command::new("secret-tool")
+			.args(&[
+				"store",
+				"--label=MWC node db encryption key",
+				"service",
+				KEYSTORE_SERVICE,
+				"account",
+				name,
+			])
+			.stdin(Stdio::piped())
+			.stderr(Stdio::piped())
+			.spawn()
+			.map_err(err)?;
+		if let Some(mut stdin) = child.stdin.take() {
+			stdin.write_all(hex.as_bytes()).map_err(err)?;
+		}
+		let output = child.wait_with_output().map_err(err)?;
+		if output.status.success() {
+			Ok(())
+		} else {
+			Err(Error::OtherErr(format!(
+				"Unable to store the keystore secret {}, {}",
+				name,
+				String::from_utf8_lossy(&output.stderr).trim()
+			)))
+		}
+	}
+}
+
+#[cfg(not(unix))]
+mod keystore {
+	use crate::lmdb::Error;
+
+	pub fn read(_name: &str) -> Result<Option<String>, Error> {
+		Err(Error::OtherErr(
+			"The OS keystore is not supported on this platform, use a key file".to_owned(),
+		))
+	}
+
+	pub fn write(name: &str, _hex: &str) -> Result<(), Error> {
+		read(name).map(|_| ())
+	}
+}
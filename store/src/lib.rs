@@ -31,6 +31,7 @@ extern crate grin_util as util;
 
 //use grin_core as core;
 
+pub mod encryption;
 pub mod leaf_set;
 pub mod lmdb;
 pub mod pmmr;
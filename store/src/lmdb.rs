@@ -14,6 +14,7 @@
 
 //! Storage of core types using LMDB.
 
+use std::borrow::Cow;
 use std::fs;
 use std::marker;
 use std::sync::Arc;
@@ -24,12 +25,15 @@ use lmdb_zero::LmdbResultExt;
 
 use crate::core::global;
 use crate::core::ser::{self, ProtocolVersion};
+use crate::encryption::EncryptionKey;
 use crate::util::RwLock;
 
 /// number of bytes to grow the database by when needed
 pub const ALLOC_CHUNK_SIZE_DEFAULT: usize = 134_217_728; //128 MB
 /// And for test mode, to avoid too much disk allocation on windows
 pub const ALLOC_CHUNK_SIZE_DEFAULT_TEST: usize = 1_048_576; //1 MB
+/// And for the low memory mode, to keep the mapped size close to the data on small devices
+pub const ALLOC_CHUNK_SIZE_LOW_MEMORY: usize = 16_777_216; //16 MB
 const RESIZE_PERCENT: f32 = 0.5;
 /// Want to ensure that each resize gives us at least this %
 /// of total space free
@@ -75,6 +79,21 @@ where
 
 const DEFAULT_DB_VERSION: ProtocolVersion = ProtocolVersion(3);
 
+/// Key of the marker telling whether the store is encrypted, sorts after all the prefixes.
+const ENCRYPTION_MARKER_KEY: &[u8] = b"\xffencrypted";
+const ENCRYPTION_MARKER_VALUE: &[u8] = b"mwc";
+
+/// Number of bytes to grow the database by, for the current chain type and memory mode
+fn alloc_chunk_size() -> usize {
+	if !global::is_production_mode() {
+		ALLOC_CHUNK_SIZE_DEFAULT_TEST
+	} else if global::is_low_memory_mode() {
+		ALLOC_CHUNK_SIZE_LOW_MEMORY
+	} else {
+		ALLOC_CHUNK_SIZE_DEFAULT
+	}
+}
+
 /// LMDB-backed store facilitating data access and serialization. All writes
 /// are done through a Batch abstraction providing atomicity.
 pub struct Store {
@@ -83,6 +102,7 @@ pub struct Store {
 	name: String,
 	version: ProtocolVersion,
 	alloc_chunk_size: usize,
+	encryption: Option<Arc<EncryptionKey>>,
 }
 
 impl Store {
@@ -95,6 +115,18 @@ impl Store {
 		env_name: Option<&str>,
 		db_name: Option<&str>,
 		max_readers: Option<u32>,
+	) -> Result<Store, Error> {
+		Store::new_with_key(root_path, env_name, db_name, max_readers, None)
+	}
+
+	/// Create a new LMDB env like `new`, with its values encrypted with the provided key.
+	/// No key opens the store unencrypted.
+	pub fn new_with_key(
+		root_path: &str,
+		env_name: Option<&str>,
+		db_name: Option<&str>,
+		max_readers: Option<u32>,
+		encryption: Option<Arc<EncryptionKey>>,
 	) -> Result<Store, Error> {
 		let name = match env_name {
 			Some(n) => n.to_owned(),
@@ -119,10 +151,7 @@ impl Store {
 			env_builder.set_maxreaders(max_readers)?;
 		}
 
-		let alloc_chunk_size = match global::is_production_mode() {
-			true => ALLOC_CHUNK_SIZE_DEFAULT,
-			false => ALLOC_CHUNK_SIZE_DEFAULT_TEST,
-		};
+		let alloc_chunk_size = alloc_chunk_size();
 
 		let env = unsafe { env_builder.open(&full_path, lmdb::open::NOTLS, 0o600)? };
 
@@ -133,6 +162,7 @@ impl Store {
 			name: db_name,
 			version: DEFAULT_DB_VERSION,
 			alloc_chunk_size,
+			encryption,
 		};
 
 		{
@@ -143,22 +173,91 @@ impl Store {
 				&lmdb::DatabaseOptions::new(lmdb::db::CREATE),
 			)?));
 		}
+		res.check_encryption()?;
 		Ok(res)
 	}
 
+	// A store is either fully encrypted or not at all. The marker written at its creation
+	// tells which one, so a missing or wrong key is reported when the store is opened.
+	fn check_encryption(&self) -> Result<(), Error> {
+		let (marker, empty) = {
+			let lock = self.db.read();
+			let db = lock
+				.as_ref()
+				.ok_or_else(|| Error::NotFoundErr("chain db is None".to_string()))?;
+			let txn = lmdb::ReadTransaction::new(self.env.clone())?;
+			let access = txn.access();
+			let marker: Option<&[u8]> = access.get(db, ENCRYPTION_MARKER_KEY).to_opt()?;
+			(marker.map(|m| m.to_vec()), txn.db_stat(db)?.entries == 0)
+		};
+		match (&self.encryption, marker) {
+			(Some(key), Some(marker)) => key
+				.decrypt(&marker)
+				.map(|_| ())
+				.map_err(|_| Error::OtherErr(format!("Wrong encryption key for db {}", self.name))),
+			(Some(_), None) if empty => {
+				let batch = self.batch()?;
+				batch.put(ENCRYPTION_MARKER_KEY, ENCRYPTION_MARKER_VALUE)?;
+				batch.commit()
+			}
+			(Some(_), None) => Err(Error::OtherErr(format!(
+				"Db {} is not encrypted, it has to be removed and synced again to be encrypted",
+				self.name
+			))),
+			(None, Some(_)) => Err(Error::OtherErr(format!(
+				"Db {} is encrypted, its encryption key is required",
+				self.name
+			))),
+			(None, None) => Ok(()),
+		}
+	}
+
+	/// Whether the values of the store are encrypted.
+	pub fn is_encrypted(&self) -> bool {
+		self.encryption.is_some()
+	}
+
+	/// Key the store is encrypted with, also used for the files kept along with it.
+	pub fn encryption_key(&self) -> Option<Arc<EncryptionKey>> {
+		self.encryption.clone()
+	}
+
+	/// Build a db key from a prefix and an identifier that shouldn't be readable on disk
+	/// (like a peer address). The identifier is masked if the store is encrypted.
+	pub fn to_masked_key<K: AsRef<[u8]>>(&self, prefix: u8, k: K) -> Result<Vec<u8>, Error> {
+		match self.encryption {
+			Some(ref key) => Ok(crate::to_key(prefix, key.mask(k.as_ref())?)),
+			None => Ok(crate::to_key(prefix, k)),
+		}
+	}
+
+	// Encrypt a value to write it to the db, if the store is encrypted.
+	fn seal<'b>(&self, value: &'b [u8]) -> Result<Cow<'b, [u8]>, Error> {
+		match self.encryption {
+			Some(ref key) => key.encrypt(value).map(Cow::Owned),
+			None => Ok(Cow::Borrowed(value)),
+		}
+	}
+
+	// Decrypt a value read from the db, if the store is encrypted.
+	fn unseal<'b>(&self, value: &'b [u8]) -> Result<Cow<'b, [u8]>, Error> {
+		match self.encryption {
+			Some(ref key) => key.decrypt(value).map(Cow::Owned),
+			None => Ok(Cow::Borrowed(value)),
+		}
+	}
+
 	/// Construct a new store using a specific protocol version.
 	/// Permits access to the db with legacy protocol versions for db migrations.
 	pub fn with_version(&self, version: ProtocolVersion) -> Store {
-		let alloc_chunk_size = match global::is_production_mode() {
-			true => ALLOC_CHUNK_SIZE_DEFAULT,
-			false => ALLOC_CHUNK_SIZE_DEFAULT_TEST,
-		};
+		let alloc_chunk_size = alloc_chunk_size();
 		Store {
 			env: self.env.clone(),
 			db: self.db.clone(),
 			name: self.name.clone(),
 			version,
 			alloc_chunk_size,
+			encryption: self.encryption.clone(),
 		}
 	}
 
@@ -256,8 +355,11 @@ impl Store {
 			.ok_or_else(|| Error::NotFoundErr("chain db is None".to_string()))?;
 		let txn = lmdb::ReadTransaction::new(self.env.clone())?;
 		let access = txn.access();
-		let res = access.get(db, key);
-		res.map(f).to_opt().map_err(From::from)
+		let res: Option<&[u8]> = access.get(db, key).to_opt()?;
+		match res {
+			Some(res) => Ok(Some(f(&self.unseal(res)?))),
+			None => Ok(None),
+		}
 	}
 
 	/// Gets a `Readable` value from the db, provided its key. Encapsulates
@@ -280,10 +382,13 @@ impl Store {
 	) -> Result<Option<T>, Error> {
 		let res: lmdb::error::Result<&[u8]> = access.get(&db, key);
 		match res.to_opt() {
-			Ok(Some(mut res)) => match ser::deserialize(&mut res, self.protocol_version()) {
-				Ok(res) => Ok(Some(res)),
-				Err(e) => Err(Error::SerErr(format!("{}", e))),
-			},
+			Ok(Some(res)) => {
+				let res = self.unseal(res)?;
+				match ser::deserialize(&mut &res[..], self.protocol_version()) {
+					Ok(res) => Ok(Some(res)),
+					Err(e) => Err(Error::SerErr(format!("{}", e))),
+				}
+			}
 			Ok(None) => Ok(None),
 			Err(e) => Err(From::from(e)),
 		}
@@ -304,6 +409,16 @@ impl Store {
 	/// Produces an iterator of (key, value) pairs, where values are `Readable` types
 	/// moving forward from the provided key.
 	pub fn iter<T: ser::Readable>(&self, from: &[u8]) -> Result<SerIterator<T>, Error> {
+		self.iter_from(from, from)
+	}
+
+	/// Produces an iterator of (key, value) pairs with the keys matching the prefix,
+	/// where values are `Readable` types, moving forward from the provided key.
+	pub fn iter_from<T: ser::Readable>(
+		&self,
+		prefix: &[u8],
+		from: &[u8],
+	) -> Result<SerIterator<T>, Error> {
 		let db = self.db.read();
 		let cloned_db = db.as_ref();
 		let cloned_db = if cloned_db.is_some() {
@@ -317,8 +432,10 @@ impl Store {
 			tx,
 			cursor,
 			seek: false,
-			prefix: from.to_vec(),
+			prefix: prefix.to_vec(),
+			start: from.to_vec(),
 			version: self.protocol_version(),
+			encryption: self.encryption.clone(),
 			_marker: marker::PhantomData,
 		})
 	}
@@ -347,9 +464,10 @@ impl<'a> Batch<'a> {
 		let db = lock
 			.as_ref()
 			.ok_or_else(|| Error::NotFoundErr("chain db is None".to_string()))?;
+		let value = self.store.seal(value)?;
 		self.tx
 			.access()
-			.put(db, key, value, lmdb::put::Flags::empty())?;
+			.put(db, key, &value[..], lmdb::put::Flags::empty())?;
 		Ok(())
 	}
 
@@ -398,6 +516,15 @@ impl<'a> Batch<'a> {
 		self.store.iter(from)
 	}
 
+	/// Iterator over the keys matching the prefix, starting from the provided key.
+	pub fn iter_from<T: ser::Readable>(
+		&self,
+		prefix: &[u8],
+		from: &[u8],
+	) -> Result<SerIterator<T>, Error> {
+		self.store.iter_from(prefix, from)
+	}
+
 	/// Gets a `Readable` value from the db, provided its key, taking the
 	/// content of the current batch into account.
 	pub fn get_ser<T: ser::Readable>(&self, key: &[u8]) -> Result<Option<T>, Error> {
@@ -438,7 +565,8 @@ impl<'a> Batch<'a> {
 }
 
 /// An iterator that produces Readable instances back. Wraps the lower level
-/// DBIterator and deserializes the returned values.
+/// DBIterator and deserializes the returned values. A value that can't be
+/// decrypted is returned as an error.
 pub struct SerIterator<T>
 where
 	T: ser::Readable,
@@ -447,7 +575,9 @@ where
 	cursor: Arc<lmdb::Cursor<'static, 'static>>,
 	seek: bool,
 	prefix: Vec<u8>,
+	start: Vec<u8>,
 	version: ProtocolVersion,
+	encryption: Option<Arc<EncryptionKey>>,
 	_marker: marker::PhantomData<T>,
 }
 
@@ -455,9 +585,9 @@ impl<T> Iterator for SerIterator<T>
 where
 	T: ser::Readable,
 {
-	type Item = (Vec<u8>, T);
+	type Item = Result<(Vec<u8>, T), Error>;
 
-	fn next(&mut self) -> Option<(Vec<u8>, T)> {
+	fn next(&mut self) -> Option<Self::Item> {
 		let access = self.tx.access();
 		let kv = if self.seek {
 			Arc::get_mut(&mut self.cursor).unwrap().next(&access)
@@ -465,7 +595,7 @@ where
 			self.seek = true;
 			Arc::get_mut(&mut self.cursor)
 				.unwrap()
-				.seek_range_k(&access, &self.prefix[..])
+				.seek_range_k(&access, &self.start[..])
 		};
 		match kv {
 			Ok((k, v)) => self.deser_if_prefix_match(k, v),
@@ -478,11 +608,22 @@ impl<T> SerIterator<T>
 where
 	T: ser::Readable,
 {
-	fn deser_if_prefix_match(&self, key: &[u8], value: &[u8]) -> Option<(Vec<u8>, T)> {
+	fn deser_if_prefix_match(
+		&self,
+		key: &[u8],
+		value: &[u8],
+	) -> Option<Result<(Vec<u8>, T), Error>> {
 		let plen = self.prefix.len();
 		if plen == 0 || (key.len() >= plen && key[0..plen] == self.prefix[..]) {
+			let value = match self.encryption {
+				Some(ref k) => match k.decrypt(value) {
+					Ok(value) => Cow::Owned(value),
+					Err(e) => return Some(Err(e)),
+				},
+				None => Cow::Borrowed(value),
+			};
 			if let Ok(value) = ser::deserialize(&mut &value[..], self.version) {
-				Some((key.to_vec(), value))
+				Some(Ok((key.to_vec(), value)))
 			} else {
 				None
 			}
@@ -20,6 +20,7 @@ use crate::core::core::hash::{Hash, Hashed};
 use crate::core::core::pmmr::{self, family, Backend};
 use crate::core::core::BlockHeader;
 use crate::core::ser::{PMMRable, ProtocolVersion};
+use crate::encryption::EncryptionKey;
 use crate::leaf_set::LeafSet;
 use crate::prune_list::PruneList;
 use crate::types::{AppendOnlyFile, DataFile, SizeEntry, SizeInfo};
@@ -42,6 +43,9 @@ pub const PMMR_FILES: [&str; 4] = [
 	PMMR_PRUN_FILE,
 ];
 
+/// The PMMR files encrypted at rest when the backend has an encryption key
+pub const PMMR_ENCRYPTED_FILES: [&str; 2] = [PMMR_HASH_FILE, PMMR_DATA_FILE];
+
 /// PMMR persistent backend implementation. Relies on multiple facilities to
 /// handle writing, reading and pruning.
 ///
@@ -88,6 +92,22 @@ impl<T: PMMRable> Backend<T> for PMMRBackend<T> {
 		Ok(())
 	}
 
+	fn append_pruned_subtree(&mut self, hash: Hash, pos: u64) -> Result<(), String> {
+		assert!(self.prunable, "Pruned subtree on non-prunable MMR");
+		self.hash_file
+			.append(&hash)
+			.map_err(|e| format!("Failed to append hash to file. {}", e))?;
+		self.prune_list.append(pos);
+		Ok(())
+	}
+
+	fn append_hash(&mut self, hash: Hash) -> Result<(), String> {
+		self.hash_file
+			.append(&hash)
+			.map_err(|e| format!("Failed to append hash to file. {}", e))?;
+		Ok(())
+	}
+
 	fn get_from_file(&self, position: u64) -> Option<Hash> {
 		if self.is_compacted(position) {
 			return None;
@@ -235,6 +255,18 @@ impl<T: PMMRable> PMMRBackend<T> {
 		prunable: bool,
 		version: ProtocolVersion,
 		header: Option<&BlockHeader>,
+	) -> io::Result<PMMRBackend<T>> {
+		PMMRBackend::new_with_key(data_dir, prunable, version, header, None)
+	}
+
+	/// Instantiates a new PMMR backend like `new`, with its hash and data files encrypted
+	/// with the provided key.
+	pub fn new_with_key<P: AsRef<Path>>(
+		data_dir: P,
+		prunable: bool,
+		version: ProtocolVersion,
+		header: Option<&BlockHeader>,
+		encryption: Option<&EncryptionKey>,
 	) -> io::Result<PMMRBackend<T>> {
 		let data_dir = data_dir.as_ref();
 
@@ -247,14 +279,25 @@ impl<T: PMMRable> PMMRBackend<T> {
 				data_dir.join(PMMR_SIZE_FILE),
 				SizeInfo::FixedSize(SizeEntry::LEN as u16),
 				version,
+				None,
 			)?))
 		};
 
 		// Hash file is always "fixed size" and we use 32 bytes per hash.
 		let hash_size_info = SizeInfo::FixedSize(Hash::LEN.try_into().unwrap());
 
-		let hash_file = DataFile::open(&data_dir.join(PMMR_HASH_FILE), hash_size_info, version)?;
-		let data_file = DataFile::open(&data_dir.join(PMMR_DATA_FILE), size_info, version)?;
+		let hash_file = DataFile::open(
+			&data_dir.join(PMMR_HASH_FILE),
+			hash_size_info,
+			version,
+			encryption,
+		)?;
+		let data_file = DataFile::open(
+			&data_dir.join(PMMR_DATA_FILE),
+			size_info,
+			version,
+			encryption,
+		)?;
 
 		let leaf_set_path = data_dir.join(PMMR_LEAF_FILE);
 
@@ -327,6 +370,12 @@ impl<T: PMMRable> PMMRBackend<T> {
 			})
 	}
 
+	/// Saves the prune_list to disk. Compaction takes care of this, only needed
+	/// after pruned subtrees got appended while rebuilding the MMR from segments.
+	pub fn sync_prune_list(&mut self) -> io::Result<()> {
+		self.prune_list.flush()
+	}
+
 	// Sync the leaf_set if this is a prunable backend.
 	fn sync_leaf_set(&mut self) -> io::Result<()> {
 		if !self.prunable {
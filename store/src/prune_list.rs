@@ -26,7 +26,7 @@ use std::path::{Path, PathBuf};
 
 use croaring::Bitmap;
 
-use crate::core::core::pmmr::{bintree_postorder_height, family, path};
+use crate::core::core::pmmr::{bintree_leftmost, bintree_postorder_height, family, path};
 use crate::{read_bitmap, save_via_temp_file};
 
 /// Maintains a list of previously pruned nodes in PMMR, compacting the list as
@@ -248,6 +248,31 @@ impl PruneList {
 		}
 	}
 
+	/// Append the root of a pruned subtree beyond the current last entry,
+	/// keeping the shift caches up to date (no full rebuild required).
+	/// Used when rebuilding a pruned MMR from segments. Unlike add() this
+	/// does not merge with a pruned sibling, the caller appends maximal
+	/// subtrees only.
+	pub fn append(&mut self, pos: u64) {
+		assert!(pos > 0, "prune list 1-indexed, 0 not valid pos");
+		assert!(
+			pos > self.bitmap.maximum().unwrap_or(0) as u64,
+			"prune list append must be beyond the last entry"
+		);
+
+		let prev_shift = self.get_total_shift();
+		let prev_leaf_shift = self.get_total_leaf_shift();
+		let height = bintree_postorder_height(pos);
+		let leaf_shift = if height == 0 { 0 } else { 1 << height };
+
+		self.bitmap.add(pos as u32);
+		self.shift_cache.push(prev_shift + 2 * ((1 << height) - 1));
+		self.leaf_shift_cache.push(prev_leaf_shift + leaf_shift);
+		for x in bintree_leftmost(pos)..=pos {
+			self.pruned_cache.add(x as u32);
+		}
+	}
+
 	/// Number of entries in the prune_list.
 	pub fn len(&self) -> u64 {
 		self.bitmap.cardinality()
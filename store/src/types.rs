@@ -18,6 +18,8 @@ use tempfile::tempfile;
 use crate::core::ser::{
 	self, BinWriter, ProtocolVersion, Readable, Reader, StreamingReader, Writeable, Writer,
 };
+use crate::encryption::{CipherReader, CipherWriter, EncryptionKey, FileCipher};
+use std::borrow::Cow;
 use std::fmt::Debug;
 use std::fs::{self, File, OpenOptions};
 use std::io::{self, BufReader, BufWriter, Seek, SeekFrom, Write};
@@ -78,16 +80,18 @@ where
 	T: Readable + Writeable + Debug,
 {
 	/// Open (or create) a file at the provided path on disk.
+	/// The file is encrypted with the provided key, if any.
 	pub fn open<P>(
 		path: P,
 		size_info: SizeInfo,
 		version: ProtocolVersion,
+		encryption: Option<&EncryptionKey>,
 	) -> io::Result<DataFile<T>>
 	where
 		P: AsRef<Path> + Debug,
 	{
 		Ok(DataFile {
-			file: AppendOnlyFile::open(path, size_info, version)?,
+			file: AppendOnlyFile::open(path, size_info, version, encryption)?,
 		})
 	}
 
@@ -161,12 +165,16 @@ where
 /// Despite being append-only, the file can still be pruned and truncated. The
 /// former simply happens by rewriting it, ignoring some of the data. The
 /// latter by truncating the underlying file and re-creating the mmap.
+///
+/// With a cipher the file is encrypted on disk, the buffer and the elements read
+/// are plain.
 pub struct AppendOnlyFile<T> {
 	path: PathBuf,
 	file: Option<File>,
 	size_info: SizeInfo,
 	version: ProtocolVersion,
 	mmap: Option<memmap::Mmap>,
+	cipher: Option<FileCipher>,
 
 	// Buffer of unsync'd bytes. These bytes will be appended to the file when flushed.
 	buffer: Vec<u8>,
@@ -191,20 +199,30 @@ where
 	T: Debug + Readable + Writeable,
 {
 	/// Open a file (existing or not) as append-only, backed by a mmap.
+	/// The file is encrypted with the provided key, if any.
 	pub fn open<P>(
 		path: P,
 		size_info: SizeInfo,
 		version: ProtocolVersion,
+		encryption: Option<&EncryptionKey>,
 	) -> io::Result<AppendOnlyFile<T>>
 	where
 		P: AsRef<Path> + Debug,
 	{
+		let cipher = match encryption {
+			Some(key) => Some(
+				key.file_cipher(path.as_ref())
+					.map_err(|e| io::Error::new(io::ErrorKind::Other, e.to_string()))?,
+			),
+			None => None,
+		};
 		let mut aof = AppendOnlyFile {
 			file: None,
 			path: path.as_ref().to_path_buf(),
 			size_info,
 			version,
 			mmap: None,
+			cipher,
 			buffer: vec![],
 			buffer_start_pos: 0,
 			buffer_start_pos_bak: 0,
@@ -371,7 +389,16 @@ where
 			self.buffer_start_pos_bak = 0;
 		}
 
-		self.file.as_mut().unwrap().write_all(&self.buffer[..])?;
+		match self.cipher {
+			Some(ref cipher) => {
+				// The buffer is appended at the end of the file.
+				let offset = self.file.as_ref().unwrap().metadata()?.len();
+				let mut bytes = self.buffer.clone();
+				cipher.apply(offset, &mut bytes);
+				self.file.as_mut().unwrap().write_all(&bytes)?;
+			}
+			None => self.file.as_mut().unwrap().write_all(&self.buffer[..])?,
+		}
 		self.file.as_mut().unwrap().sync_all()?;
 
 		self.buffer.clear();
@@ -406,17 +433,25 @@ where
 	/// Read the bytes representing the element at the given position (0-indexed).
 	/// Uses the offset cache to determine the offset to read from and the size
 	/// in bytes to actually read.
-	/// Leverages the memory map.
-	pub fn read(&self, pos: u64) -> io::Result<&[u8]> {
+	/// Leverages the memory map, the bytes read from it are decrypted if the file is encrypted.
+	pub fn read(&self, pos: u64) -> io::Result<Cow<'_, [u8]>> {
 		if pos >= self.size_unsync_in_elmts()? {
-			return Ok(<&[u8]>::default());
+			return Ok(Cow::Borrowed(<&[u8]>::default()));
 		}
 		let (offset, length) = self.offset_and_size(pos)?;
 		let res = if pos < self.buffer_start_pos {
-			self.read_from_mmap(offset, length)
+			let bytes = self.read_from_mmap(offset, length);
+			match self.cipher {
+				Some(ref cipher) => {
+					let mut bytes = bytes.to_vec();
+					cipher.apply(offset, &mut bytes);
+					Cow::Owned(bytes)
+				}
+				None => Cow::Borrowed(bytes),
+			}
 		} else {
 			let (buffer_offset, _) = self.offset_and_size(self.buffer_start_pos)?;
-			self.read_from_buffer(offset.saturating_sub(buffer_offset), length)
+			Cow::Borrowed(self.read_from_buffer(offset.saturating_sub(buffer_offset), length))
 		};
 		Ok(res)
 	}
@@ -458,11 +493,14 @@ where
 		}
 	}
 
-	/// Create a new tempfile containing the contents of this append only file.
+	/// Create a new tempfile containing the (decrypted) contents of this append only file.
 	/// This allows callers to see a consistent view of the data without
 	/// locking the append only file.
 	pub fn as_temp_file(&self) -> io::Result<File> {
-		let mut reader = BufReader::new(File::open(&self.path)?);
+		let mut reader = BufReader::new(CipherReader::new(
+			File::open(&self.path)?,
+			self.cipher.clone(),
+		));
 		let mut writer = BufWriter::new(tempfile()?);
 		io::copy(&mut reader, &mut writer)?;
 
@@ -481,11 +519,13 @@ where
 
 		// Scope the reader and writer to within the block so we can safely replace files later on.
 		{
-			let reader = File::open(&self.path)?;
+			// The tmp file replaces our file, it is encrypted with the same keystream.
+			let reader = CipherReader::new(File::open(&self.path)?, self.cipher.clone());
 			let mut buf_reader = BufReader::new(reader);
 			let mut streaming_reader = StreamingReader::new(&mut buf_reader, self.version);
 
-			let mut buf_writer = BufWriter::new(File::create(&tmp_path)?);
+			let writer = CipherWriter::new(File::create(&tmp_path)?, self.cipher.clone());
+			let mut buf_writer = BufWriter::new(writer);
 			let mut bin_writer = BinWriter::new(&mut buf_writer, self.version);
 
 			let mut current_pos = 0;
@@ -529,11 +569,12 @@ where
 
 			// Scope the reader and writer to within the block so we can safely replace files later on.
 			{
-				let reader = File::open(&self.path)?;
+				let reader = CipherReader::new(File::open(&self.path)?, self.cipher.clone());
 				let mut buf_reader = BufReader::new(reader);
 				let mut streaming_reader = StreamingReader::new(&mut buf_reader, self.version);
 
-				let mut buf_writer = BufWriter::new(File::create(&tmp_path)?);
+				let writer = CipherWriter::new(File::create(&tmp_path)?, size_file.cipher.clone());
+				let mut buf_writer = BufWriter::new(writer);
 				let mut bin_writer = BinWriter::new(&mut buf_writer, self.version);
 
 				let mut current_offset = 0;
@@ -0,0 +1,84 @@
+// Copyright 2021 The MWC Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use grin_core as core;
+use grin_store as store;
+use grin_util as util;
+
+use crate::core::global;
+use crate::store::encryption::EncryptionKey;
+use std::fs;
+use std::sync::Arc;
+
+fn clean_output_dir(test_dir: &str) {
+	let _ = fs::remove_dir_all(test_dir);
+}
+
+fn key(seed: u8) -> Option<Arc<EncryptionKey>> {
+	Some(Arc::new(EncryptionKey::from_bytes(&[seed; 32]).unwrap()))
+}
+
+#[test]
+fn lmdb_encryption() -> Result<(), store::Error> {
+	let test_dir = "test_output/lmdb_encryption";
+	global::set_local_chain_type(global::ChainTypes::Mainnet);
+	util::init_test_logger();
+	clean_output_dir(test_dir);
+
+	let peer_key = store::to_key(b'P', "10.0.0.1");
+	{
+		let store = store::Store::new_with_key(test_dir, Some("encrypted"), None, None, key(1))?;
+		assert!(store.is_encrypted());
+		let masked_key = store.to_masked_key(b'P', "10.0.0.1")?;
+		assert_ne!(masked_key, peer_key);
+		assert_eq!(masked_key, store.to_masked_key(b'P', "10.0.0.1")?);
+
+		let batch = store.batch()?;
+		batch.put_ser(&masked_key, &42u64)?;
+		assert_eq!(batch.get_ser::<u64>(&masked_key)?, Some(42));
+		batch.commit()?;
+
+		assert_eq!(store.get_ser::<u64>(&masked_key)?, Some(42));
+		assert_eq!(store.get_with(&masked_key, |v| v.len())?, Some(8));
+		let values: Vec<u64> = store
+			.iter::<u64>(&store::to_key(b'P', ""))?
+			.map(|res| res.map(|(_, v)| v))
+			.collect::<Result<_, _>>()?;
+		assert_eq!(values, vec![42]);
+	}
+
+	// The encrypted store can't be opened without its key.
+	assert!(store::Store::new(test_dir, Some("encrypted"), None, None).is_err());
+	assert!(store::Store::new_with_key(test_dir, Some("encrypted"), None, None, key(2)).is_err());
+
+	// Nor can an unencrypted store be opened with a key.
+	{
+		let store = store::Store::new(test_dir, Some("plain"), None, None)?;
+		assert!(!store.is_encrypted());
+		assert_eq!(store.to_masked_key(b'P', "10.0.0.1")?, peer_key);
+		let batch = store.batch()?;
+		batch.put_ser(&peer_key, &42u64)?;
+		batch.commit()?;
+	}
+	assert!(store::Store::new_with_key(test_dir, Some("plain"), None, None, key(1)).is_err());
+
+	{
+		let store = store::Store::new_with_key(test_dir, Some("encrypted"), None, None, key(1))?;
+		let masked_key = store.to_masked_key(b'P', "10.0.0.1")?;
+		assert_eq!(store.get_ser::<u64>(&masked_key)?, Some(42));
+	}
+
+	clean_output_dir(test_dir);
+	Ok(())
+}
@@ -22,10 +22,11 @@ use chrono::prelude::Utc;
 use croaring::Bitmap;
 
 use crate::core::core::hash::DefaultHashable;
-use crate::core::core::pmmr::{Backend, PMMR};
+use crate::core::core::pmmr::{Backend, Segment, SegmentEntry, SegmentIdentifier, PMMR};
 use crate::core::ser::{
 	Error, PMMRIndexHashable, PMMRable, ProtocolVersion, Readable, Reader, Writeable, Writer,
 };
+use crate::store::encryption::EncryptionKey;
 
 #[test]
 fn pmmr_leaf_idx_iter() {
@@ -266,6 +267,61 @@ fn pmmr_prune_compact() {
 	teardown(data_dir);
 }
 
+#[test]
+fn pmmr_encryption() {
+	let (data_dir, elems) = setup("encryption");
+	let key = EncryptionKey::from_bytes(&[1; 32]).unwrap();
+	let open = |key: Option<&EncryptionKey>| {
+		store::pmmr::PMMRBackend::<TestElem>::new_with_key(
+			data_dir.to_string(),
+			true,
+			ProtocolVersion(1),
+			None,
+			key,
+		)
+		.unwrap()
+	};
+
+	let mmr_size;
+	let root;
+	{
+		let mut backend = open(Some(&key));
+		mmr_size = load(0, &elems[..], &mut backend);
+		backend.sync().unwrap();
+		root = {
+			let pmmr: PMMR<'_, TestElem, _> = PMMR::at(&mut backend, mmr_size);
+			pmmr.root().unwrap()
+		};
+
+		// pruned and compacted files are rewritten encrypted
+		{
+			let mut pmmr: PMMR<'_, TestElem, _> = PMMR::at(&mut backend, mmr_size);
+			pmmr.prune(1).unwrap();
+			pmmr.prune(4).unwrap();
+			pmmr.prune(5).unwrap();
+		}
+		backend.sync().unwrap();
+		backend.check_compact(2, &Bitmap::create()).unwrap();
+	}
+
+	// the files are only readable with the key
+	{
+		let mut backend = open(Some(&key));
+		let pmmr: PMMR<'_, TestElem, _> = PMMR::at(&mut backend, mmr_size);
+		assert_eq!(root, pmmr.root().unwrap());
+		assert_eq!(pmmr.get_data(2).unwrap(), TestElem(2));
+		assert_eq!(pmmr.get_data(11).unwrap(), TestElem(7));
+	}
+	{
+		let mut backend = open(None);
+		let pmmr: PMMR<'_, TestElem, _> = PMMR::at(&mut backend, mmr_size);
+		assert_ne!(root, pmmr.root().unwrap());
+		assert_ne!(pmmr.get_data(2), Some(TestElem(2)));
+	}
+
+	teardown(data_dir);
+}
+
 #[test]
 fn pmmr_reload() {
 	let (data_dir, elems) = setup("reload");
@@ -901,6 +957,83 @@ fn cleanup_rewind_files_test() {
 ///
 /// in the current working directory that are all 100 seconds old (modified and accessed time)
 ///
+#[test]
+fn pmmr_rebuild_from_segments() {
+	let (data_dir, elems) = setup("rebuild_from_segments");
+	let rebuilt_dir = format!("{}/rebuilt", data_dir);
+	fs::create_dir_all(&rebuilt_dir).unwrap();
+	{
+		let mut backend =
+			store::pmmr::PMMRBackend::new(data_dir.to_string(), true, ProtocolVersion(1), None)
+				.unwrap();
+		let mmr_size = load(0, &elems[..], &mut backend);
+		backend.sync().unwrap();
+
+		// spend the first 8 leaves (subtree at pos 15) and pos 16 (sibling 17 unspent)
+		{
+			let mut pmmr: PMMR<'_, TestElem, _> = PMMR::at(&mut backend, mmr_size);
+			for pos in &[1, 2, 4, 5, 8, 9, 11, 12, 16] {
+				pmmr.prune(*pos).unwrap();
+			}
+		}
+		backend.sync().unwrap();
+		backend.check_compact(20, &Bitmap::create()).unwrap();
+
+		let root = {
+			let pmmr: PMMR<'_, TestElem, _> = PMMR::at(&mut backend, mmr_size);
+			pmmr.root().unwrap()
+		};
+
+		let mut rebuilt_backend =
+			store::pmmr::PMMRBackend::new(rebuilt_dir.clone(), true, ProtocolVersion(1), None)
+				.unwrap();
+		{
+			let pmmr: PMMR<'_, TestElem, _> = PMMR::at(&mut backend, mmr_size);
+			let mut rebuilt: PMMR<'_, TestElem, _> = PMMR::new(&mut rebuilt_backend);
+			let height = 2;
+			for idx in 0..SegmentIdentifier::count_for_mmr_size(height, mmr_size) {
+				let id = SegmentIdentifier { height, idx };
+				let segment = Segment::from_pmmr(id, &pmmr.readonly_pmmr()).unwrap();
+				segment.validate(mmr_size, root).unwrap();
+				for (pos, entry) in segment.entries() {
+					match entry {
+						SegmentEntry::Hash(hash) => {
+							// segments below the same pruned subtree share its root
+							if pos > rebuilt.unpruned_size() {
+								rebuilt.push_pruned_subtree(*hash, pos).unwrap();
+							}
+						}
+						SegmentEntry::Leaf(elem) => {
+							assert_eq!(rebuilt.push(elem).unwrap(), pos);
+							if pmmr.get_hash(pos).is_none() {
+								rebuilt.prune(pos).unwrap();
+							}
+						}
+					}
+				}
+			}
+			assert_eq!(rebuilt.unpruned_size(), mmr_size);
+			assert_eq!(rebuilt.root().unwrap(), root);
+			assert_eq!(rebuilt.n_unpruned_leaves(), pmmr.n_unpruned_leaves());
+		}
+		rebuilt_backend.sync().unwrap();
+		rebuilt_backend.sync_prune_list().unwrap();
+	}
+
+	// reopen and check the prune list got persisted
+	{
+		let mut backend =
+			store::pmmr::PMMRBackend::new(rebuilt_dir, true, ProtocolVersion(1), None).unwrap();
+		let mmr_size = backend.unpruned_size();
+		let pmmr: PMMR<'_, TestElem, _> = PMMR::at(&mut backend, mmr_size);
+		assert_eq!(pmmr.get_hash(16), None);
+		assert_eq!(pmmr.get_data(17), Some(elems[9]));
+		pmmr.validate().unwrap();
+	}
+
+	teardown(data_dir);
+}
+
 fn create_numbered_files(
 	data_dir: &str,
 	num_files: u32,
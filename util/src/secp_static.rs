@@ -34,6 +34,21 @@ pub fn static_secp_instance() -> Arc<Mutex<secp::Secp256k1>> {
 	SECP256K1.clone()
 }
 
+thread_local! {
+	/// Secp instance of the current thread, used for the parallel verification
+	static THREAD_SECP: secp::Secp256k1 = secp::Secp256k1::with_caps(secp::ContextFlag::Commit);
+}
+
+/// Call the closure with the secp instance that belongs to the current thread. Unlike the static
+/// instance it is not shared, so verification can run on many threads in parallel.
+/// Intended for verification only, the instance is not randomized.
+pub fn with_thread_secp<F, T>(f: F) -> T
+where
+	F: FnOnce(&secp::Secp256k1) -> T,
+{
+	THREAD_SECP.with(|secp| f(secp))
+}
+
 /// Convenient way to generate a commitment to zero.
 pub fn commit_to_zero_value() -> secp::pedersen::Commitment {
 	secp::pedersen::Commitment::from_vec(vec![0])